@@ -7,8 +7,6 @@ use abci::{
 use chain_abci::app::check_validators;
 use chain_abci::app::*;
 use chain_abci::enclave_bridge::mock::MockClient;
-use chain_core::common::MerkleTree;
-use chain_core::compute_app_hash;
 use chain_core::init::coin::Coin;
 use chain_core::init::config::InitConfig;
 use chain_core::init::config::NetworkParameters;
@@ -174,14 +172,12 @@ fuzz_target!(|data: &[u8]| {
                                 if r.is_err() {
                                     defaultinit
                                 } else {
-                                    let tx_tree = MerkleTree::empty();
                                     let mut storage = Storage::new_db(create_db());
                                     let new_account_root = storage.put_stakings(0, &state.accounts);
 
-                                    let genesis_app_hash = compute_app_hash(
-                                        &tx_tree,
+                                    let genesis_app_hash = compute_genesis_app_hash(
                                         &new_account_root,
-                                        &state.rewards_pool,
+                                        &state,
                                         &network_params,
                                     );
                                     if req.chain_id.len() > 3 {
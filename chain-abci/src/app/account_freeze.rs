@@ -0,0 +1,72 @@
+use crate::tx_error::AccountFreezeError;
+use chain_core::state::governance::{AccountFreezeState, AccountFreezeTx};
+
+/// Checks the decision against the current account freeze state
+/// (the votes are checked by the staking table)
+pub fn check_account_freeze(
+    tx: &AccountFreezeTx,
+    state: &AccountFreezeState,
+) -> Result<(), AccountFreezeError> {
+    if !state.enabled {
+        return Err(AccountFreezeError::Disabled);
+    }
+    if tx.freeze_version != state.version {
+        return Err(AccountFreezeError::VersionMismatch);
+    }
+    if tx.frozen == state.is_frozen(&tx.address) {
+        return Err(AccountFreezeError::NoChange);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chain_core::init::address::RedeemAddress;
+    use chain_core::state::account::{StakedStateAddress, StakedStateOpAttributes};
+
+    fn freeze_tx(freeze_version: u64, frozen: bool) -> AccountFreezeTx {
+        AccountFreezeTx::new(
+            freeze_version,
+            StakedStateAddress::BasicRedeem(RedeemAddress::from([1u8; 20])),
+            frozen,
+            [0u8; 32],
+            StakedStateOpAttributes::new(0),
+        )
+    }
+
+    #[test]
+    fn check_account_freeze_validation() {
+        let mut state = AccountFreezeState::default();
+        assert!(matches!(
+            check_account_freeze(&freeze_tx(0, true), &state),
+            Err(AccountFreezeError::Disabled)
+        ));
+
+        state.enabled = true;
+        assert!(matches!(
+            check_account_freeze(&freeze_tx(1, true), &state),
+            Err(AccountFreezeError::VersionMismatch)
+        ));
+        assert!(matches!(
+            check_account_freeze(&freeze_tx(0, false), &state),
+            Err(AccountFreezeError::NoChange)
+        ));
+        check_account_freeze(&freeze_tx(0, true), &state).unwrap();
+
+        let tx = freeze_tx(0, true);
+        state.apply(&tx);
+        assert!(state.is_frozen(&tx.address));
+        assert_eq!(1, state.version);
+        // the votes of an applied decision can't be replayed
+        assert!(matches!(
+            check_account_freeze(&tx, &state),
+            Err(AccountFreezeError::VersionMismatch)
+        ));
+        check_account_freeze(&freeze_tx(1, false), &state).unwrap();
+
+        // no account is frozen while the capability is disabled
+        state.enabled = false;
+        assert!(!state.is_frozen(&tx.address));
+    }
+}
@@ -4,34 +4,48 @@ use std::mem;
 
 use abci::*;
 use log::{info, warn};
-use parity_scale_codec::{Decode, Encode};
+use parity_scale_codec::{Decode, Encode, Input, Output};
 use protobuf::Message;
 use serde::{Deserialize, Serialize};
 
+use super::backup::BackupScheduler;
+use super::block_stats::BlockStats;
+use super::check_tx_cache::CheckTxCache;
+use super::health::NodeStatusHandle;
+use super::mempool_policy::MempoolPolicy;
+use super::metrics::AppMetrics;
+use super::params_update::PendingParamsUpdate;
+use super::pruning::PruningMode;
+use super::rejected_txs::RejectedTxLog;
+use super::state_sync::StateSync;
+use super::storage_metrics::configure_storage_metrics;
+use super::upgrade::ScheduledUpgrade;
+use super::watch_list::AddressWatchList;
 use crate::enclave_bridge::EnclaveProxy;
 use crate::staking::StakingTable;
-use chain_core::common::MerkleTree;
 use chain_core::common::Timespec;
 use chain_core::common::{H256, HASH_SIZE_256};
-use chain_core::compute_app_hash;
 use chain_core::init::address::RedeemAddress;
 use chain_core::init::coin::Coin;
-use chain_core::init::config::InitConfig;
 use chain_core::init::config::NetworkParameters;
+use chain_core::init::config::{GenesisState, InitConfig};
+use chain_core::init::export::ExportedState;
 use chain_core::state::account::StakedStateDestination;
 use chain_core::state::account::{CouncilNodeMeta, StakedStateAddress};
+use chain_core::state::governance::AccountFreezeState;
 use chain_core::state::tendermint::{BlockHeight, TendermintVotePower};
-use chain_core::state::{ChainState, RewardsPoolState};
+use chain_core::state::{validator_set_hash, ChainState, RewardsPoolState};
 use chain_core::tx::TxAux;
 use chain_core::ChainInfo;
 use chain_storage::buffer::{
-    flush_storage, GetStaking, KVBuffer, StakingBuffer, StoreKV, StoreStaking,
+    flush_storage, GetStaking, KVBuffer, MemStore, StakingBuffer, StoreKV, StoreStaking,
 };
 use chain_storage::jellyfish::{compute_staking_root, sum_staking_coins, StakingGetter, Version};
+use chain_storage::utxo_mmr::get_utxo_root;
 use chain_storage::{Storage, StoredChainState};
 
 /// ABCI app state snapshot
-#[derive(Serialize, Deserialize, Clone, Encode, Decode)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct ChainNodeState {
     /// last processed block height, set in end block
     pub last_block_height: BlockHeight,
@@ -55,11 +69,116 @@ pub struct ChainNodeState {
     /// Record the biggest enclave ISVSVN (Security Version Number of the Enclave) we've seen in
     /// keypackage so far
     pub enclave_isv_svn: u16,
+    /// Network parameters update scheduled by council node votes
+    pub pending_params_update: Option<PendingParamsUpdate>,
+    /// Upgrade scheduled by a network parameters update, with the readiness signals
+    pub scheduled_upgrade: Option<ScheduledUpgrade>,
+    /// Staking accounts frozen by council decisions
+    pub account_freeze: AccountFreezeState,
 
     /// The parts of states which involved in computing app_hash
     pub top_level: ChainState,
 }
 
+/// Marks the versioned layout of the stored state: the layout of the 0.5 release starts
+/// with the last block height, which is never `u64::MAX`
+const STATE_LAYOUT_MARKER: u64 = u64::MAX;
+/// Version of the stored state layout (after the marker):
+/// 2 added the account freeze state (empty when decoding the layout 1)
+const STATE_LAYOUT_VERSION: u8 = 2;
+
+impl Encode for ChainNodeState {
+    fn encode_to<W: Output>(&self, dest: &mut W) {
+        STATE_LAYOUT_MARKER.encode_to(dest);
+        STATE_LAYOUT_VERSION.encode_to(dest);
+        self.last_block_height.encode_to(dest);
+        self.last_apphash.encode_to(dest);
+        self.block_time.encode_to(dest);
+        self.block_height.encode_to(dest);
+        self.staking_table.encode_to(dest);
+        self.genesis_time.encode_to(dest);
+        self.max_evidence_age.encode_to(dest);
+        self.staking_version.encode_to(dest);
+        self.utxo_coins.encode_to(dest);
+        self.enclave_isv_svn.encode_to(dest);
+        self.pending_params_update.encode_to(dest);
+        self.scheduled_upgrade.encode_to(dest);
+        self.account_freeze.encode_to(dest);
+        self.top_level.encode_to(dest);
+    }
+}
+
+impl Decode for ChainNodeState {
+    fn decode<I: Input>(input: &mut I) -> Result<Self, parity_scale_codec::Error> {
+        let first = u64::decode(input)?;
+        if first != STATE_LAYOUT_MARKER {
+            return decode_legacy_state(BlockHeight::new(first), input);
+        }
+        let layout_version = u8::decode(input)?;
+        if layout_version == 0 || layout_version > STATE_LAYOUT_VERSION {
+            return Err("Unknown layout of the chain node state".into());
+        }
+        Ok(ChainNodeState {
+            last_block_height: BlockHeight::decode(input)?,
+            last_apphash: H256::decode(input)?,
+            block_time: Timespec::decode(input)?,
+            block_height: BlockHeight::decode(input)?,
+            staking_table: StakingTable::decode(input)?,
+            genesis_time: Timespec::decode(input)?,
+            max_evidence_age: Timespec::decode(input)?,
+            staking_version: Version::decode(input)?,
+            utxo_coins: Coin::decode(input)?,
+            enclave_isv_svn: u16::decode(input)?,
+            pending_params_update: Option::<PendingParamsUpdate>::decode(input)?,
+            scheduled_upgrade: Option::<ScheduledUpgrade>::decode(input)?,
+            account_freeze: if layout_version >= 2 {
+                AccountFreezeState::decode(input)?
+            } else {
+                AccountFreezeState::default()
+            },
+            top_level: ChainState::decode(input)?,
+        })
+    }
+}
+
+/// Migrates the state stored by the 0.5 release (the fields added since are empty)
+fn decode_legacy_state<I: Input>(
+    last_block_height: BlockHeight,
+    input: &mut I,
+) -> Result<ChainNodeState, parity_scale_codec::Error> {
+    let last_apphash = H256::decode(input)?;
+    let block_time = Timespec::decode(input)?;
+    let block_height = BlockHeight::decode(input)?;
+    let staking_table = StakingTable::decode(input)?;
+    let genesis_time = Timespec::decode(input)?;
+    let max_evidence_age = Timespec::decode(input)?;
+    let staking_version = Version::decode(input)?;
+    let utxo_coins = Coin::decode(input)?;
+    let enclave_isv_svn = u16::decode(input)?;
+    let top_level = ChainState::genesis(
+        H256::decode(input)?,
+        RewardsPoolState::decode(input)?,
+        NetworkParameters::decode(input)?,
+        validator_set_hash(staking_table.get_chosen_validators()),
+    );
+    Ok(ChainNodeState {
+        last_block_height,
+        last_apphash,
+        block_time,
+        block_height,
+        staking_table,
+        genesis_time,
+        max_evidence_age,
+        staking_version,
+        utxo_coins,
+        enclave_isv_svn,
+        pending_params_update: None,
+        scheduled_upgrade: None,
+        account_freeze: AccountFreezeState::default(),
+        top_level,
+    })
+}
+
 impl StoredChainState for ChainNodeState {
     fn get_encoded(&self) -> Vec<u8> {
         self.encode()
@@ -79,9 +198,8 @@ impl StoredChainState for ChainNodeState {
 }
 
 impl ChainNodeState {
-    #[allow(clippy::too_many_arguments)]
+    /// creates the state at genesis, the app hash is computed from the genesis chain state
     pub fn genesis(
-        genesis_apphash: H256,
         genesis_time: Timespec,
         max_evidence_age: Timespec,
         account_root: H256,
@@ -90,9 +208,15 @@ impl ChainNodeState {
         staking_table: StakingTable,
         enclave_isv_svn: u16,
     ) -> Self {
+        let top_level = ChainState::genesis(
+            account_root,
+            rewards_pool,
+            network_params,
+            validator_set_hash(staking_table.get_chosen_validators()),
+        );
         ChainNodeState {
             last_block_height: BlockHeight::genesis(),
-            last_apphash: genesis_apphash,
+            last_apphash: top_level.compute_app_hash(vec![]),
             block_time: genesis_time,
             block_height: BlockHeight::genesis(),
             staking_table,
@@ -101,11 +225,10 @@ impl ChainNodeState {
             staking_version: 0,
             utxo_coins: Coin::zero(),
             enclave_isv_svn,
-            top_level: ChainState {
-                account_root,
-                rewards_pool,
-                network_params,
-            },
+            pending_params_update: None,
+            scheduled_upgrade: None,
+            account_freeze: AccountFreezeState::default(),
+            top_level,
         }
     }
 
@@ -151,6 +274,28 @@ pub struct ChainNodeApp<T: EnclaveProxy> {
     pub kv_buffer: KVBuffer,
     /// mempool buffer of key-value storage
     pub mempool_kv_buffer: KVBuffer,
+    /// cached CheckTx verdicts reused when Tendermint re-checks the mempool
+    pub check_tx_cache: CheckTxCache,
+    /// node-local CheckTx policy (minimal fee multiplier and per-address rate limits)
+    pub mempool_policy: MempoolPolicy,
+    /// statistics and captured payloads of rejected transactions
+    pub rejected_txs: RejectedTxLog,
+    /// production statistics of the current block (stored on commit)
+    pub block_stats: BlockStats,
+    /// alerts on operator-configured staking addresses
+    pub watch_list: AddressWatchList,
+    /// automatic storage backups (if configured)
+    pub backup: Option<BackupScheduler>,
+    /// state sync over the storage backups (if configured)
+    pub state_sync: Option<StateSync>,
+    /// pruning of the stored bodies of old transactions
+    pub pruning: PruningMode,
+    /// status of the node reported by the health endpoint
+    pub node_status: NodeStatusHandle,
+    /// Prometheus metrics (shared with the enclave bridge and the metrics endpoint)
+    pub metrics: AppMetrics,
+    /// consensus parameters changed in the current block (returned in EndBlock)
+    pub consensus_params_update: Option<ConsensusParams>,
 }
 
 pub fn get_validator_key(node: &CouncilNodeMeta) -> PubKey {
@@ -161,7 +306,7 @@ pub fn get_validator_key(node: &CouncilNodeMeta) -> PubKey {
     pk
 }
 
-fn check_and_store_consensus_params(
+pub(super) fn check_and_store_consensus_params(
     init_consensus_params: Option<&ConsensusParams>,
     _validators: &[(StakedStateAddress, CouncilNodeMeta)],
     _network_params: &NetworkParameters,
@@ -190,7 +335,7 @@ fn check_and_store_consensus_params(
 /// checks InitChain's req.validators is consistent with InitChain's app_state's council nodes
 pub fn check_validators(
     nodes: &[(StakedStateAddress, CouncilNodeMeta)],
-    mut req_validators: Vec<ValidatorUpdate>,
+    req_validators: Vec<ValidatorUpdate>,
     distribution: &BTreeMap<RedeemAddress, (StakedStateDestination, Coin)>,
 ) -> Result<(), ()> {
     let mut validators = Vec::with_capacity(nodes.len());
@@ -203,6 +348,18 @@ pub fn check_validators(
         validators.push(validator);
     }
 
+    if validators_match(validators, req_validators) {
+        Ok(())
+    } else {
+        Err(())
+    }
+}
+
+/// compares the validator updates regardless of their order
+pub(super) fn validators_match(
+    mut validators: Vec<ValidatorUpdate>,
+    mut req_validators: Vec<ValidatorUpdate>,
+) -> bool {
     let fn_sort_key = |a: &ValidatorUpdate| {
         a.pub_key
             .as_ref()
@@ -210,12 +367,7 @@ pub fn check_validators(
     };
     validators.sort_by_key(fn_sort_key);
     req_validators.sort_by_key(fn_sort_key);
-
-    if validators == req_validators {
-        Ok(())
-    } else {
-        Err(())
-    }
+    validators == req_validators
 }
 
 fn get_voting_power(
@@ -232,14 +384,46 @@ pub fn init_app_hash(conf: &InitConfig, genesis_time: Timespec) -> H256 {
         .validate_config_get_genesis(genesis_time)
         .expect("distribution validation error");
 
-    compute_app_hash(
-        &MerkleTree::empty(),
+    compute_genesis_app_hash(
         &compute_staking_root(&state.accounts),
-        &state.rewards_pool,
+        &state,
         &NetworkParameters::Genesis(conf.network_params.clone()),
     )
 }
 
+/// Computes the app hash of the validated genesis state with the given root of its staked states:
+/// the validator set is chosen from the genesis council nodes
+pub fn compute_genesis_app_hash(
+    account_root: &H256,
+    state: &GenesisState,
+    network_params: &NetworkParameters,
+) -> H256 {
+    let heap = MemStore(
+        state
+            .accounts
+            .iter()
+            .map(|staking| (staking.address, staking.clone()))
+            .collect(),
+    );
+    let staking_table = StakingTable::from_genesis(
+        &heap,
+        network_params.get_required_council_node_stake(),
+        network_params.get_max_validators(),
+        &state
+            .validators
+            .iter()
+            .map(|(addr, _)| *addr)
+            .collect::<Vec<_>>(),
+    );
+    let top_level = ChainState::genesis(
+        *account_root,
+        state.rewards_pool.clone(),
+        network_params.clone(),
+        validator_set_hash(staking_table.get_chosen_validators()),
+    );
+    top_level.compute_app_hash(vec![])
+}
+
 impl<T: EnclaveProxy + 'static> ChainNodeApp<T> {
     fn restore_from_storage(
         tx_validator: T,
@@ -269,6 +453,9 @@ impl<T: EnclaveProxy + 'static> ChainNodeApp<T> {
         let chain_hex_id = hex::decode(&chain_id[chain_id.len() - 2..])
             .expect("failed to decode two last hex digits in chain ID")[0];
 
+        configure_storage_metrics(&storage);
+        let node_status = NodeStatusHandle::default();
+        node_status.on_commit(&last_app_state, tx_query_address.as_ref());
         ChainNodeApp {
             storage,
             delivered_txs: Vec::new(),
@@ -285,6 +472,17 @@ impl<T: EnclaveProxy + 'static> ChainNodeApp<T> {
             mempool_staking_buffer: HashMap::new(),
             kv_buffer: HashMap::new(),
             mempool_kv_buffer: HashMap::new(),
+            check_tx_cache: CheckTxCache::default(),
+            mempool_policy: MempoolPolicy::from_env(),
+            rejected_txs: RejectedTxLog::from_env(),
+            block_stats: BlockStats::default(),
+            watch_list: AddressWatchList::from_env(),
+            backup: None,
+            state_sync: None,
+            pruning: PruningMode::default(),
+            node_status,
+            metrics: AppMetrics::default(),
+            consensus_params_update: None,
         }
     }
 
@@ -352,6 +550,10 @@ impl<T: EnclaveProxy + 'static> ChainNodeApp<T> {
                     .network_params
                     .get_required_council_node_stake(),
             );
+            // APP_VERSION 1 app state doesn't store these commitments (not in its app hash)
+            last_state.top_level.utxo_commitment = get_utxo_root(&storage);
+            last_state.top_level.validator_set_hash =
+                validator_set_hash(last_state.staking_table.get_chosen_validators());
             ChainNodeApp::restore_from_storage(
                 tx_validator,
                 last_state,
@@ -374,6 +576,7 @@ impl<T: EnclaveProxy + 'static> ChainNodeApp<T> {
                 }
             }
             storage.write_genesis_chain_id(&genesis_app_hash, chain_id);
+            configure_storage_metrics(&storage);
             ChainNodeApp {
                 storage,
                 delivered_txs: Vec::new(),
@@ -390,11 +593,22 @@ impl<T: EnclaveProxy + 'static> ChainNodeApp<T> {
                 mempool_staking_buffer: HashMap::new(),
                 kv_buffer: HashMap::new(),
                 mempool_kv_buffer: HashMap::new(),
+                check_tx_cache: CheckTxCache::default(),
+                mempool_policy: MempoolPolicy::from_env(),
+                rejected_txs: RejectedTxLog::from_env(),
+                block_stats: BlockStats::default(),
+                watch_list: AddressWatchList::from_env(),
+                backup: None,
+                state_sync: None,
+                pruning: PruningMode::default(),
+                node_status: NodeStatusHandle::default(),
+                metrics: AppMetrics::default(),
+                consensus_params_update: None,
             }
         }
     }
 
-    /// Handles InitChain requests:
+    /// Handles InitChain requests (app_state is the initial configuration or an exported state):
     /// should validate initial genesis distribution, initialize everything in the key-value DB and check it matches the expected values
     /// provided as arguments.
     pub fn init_chain_handler(&mut self, req: &RequestInitChain) -> ResponseInitChain {
@@ -410,7 +624,7 @@ impl<T: EnclaveProxy + 'static> ChainNodeApp<T> {
                 })
             })
             .expect("No valid max_evidence_age");
-        let conf: InitConfig =
+        let app_state: serde_json::Value =
             serde_json::from_slice(&req.app_state_bytes).expect("failed to parse initial config");
 
         let genesis_time = req
@@ -420,10 +634,6 @@ impl<T: EnclaveProxy + 'static> ChainNodeApp<T> {
             .get_seconds()
             .try_into()
             .expect("invalid genesis time");
-        let state = conf
-            .validate_config_get_genesis(genesis_time)
-            .expect("distribution validation error");
-
         let stored_chain_id = self.storage.get_stored_chain_id();
         if stored_chain_id != req.chain_id.as_bytes() {
             panic!(
@@ -432,15 +642,20 @@ impl<T: EnclaveProxy + 'static> ChainNodeApp<T> {
                 req.chain_id
             );
         }
+        if app_state.get("exported_height").is_some() {
+            let exported: ExportedState =
+                serde_json::from_value(app_state).expect("failed to parse exported state");
+            return self.init_chain_from_export(req, exported, genesis_time, max_evidence_age);
+        }
+        let conf: InitConfig =
+            serde_json::from_value(app_state).expect("failed to parse initial config");
+        let state = conf
+            .validate_config_get_genesis(genesis_time)
+            .expect("distribution validation error");
 
         let network_params = NetworkParameters::Genesis(conf.network_params);
         let new_account_root = self.storage.put_stakings(0, &state.accounts);
-        let genesis_app_hash = compute_app_hash(
-            &MerkleTree::empty(),
-            &new_account_root,
-            &state.rewards_pool,
-            &network_params,
-        );
+        let genesis_app_hash = compute_genesis_app_hash(&new_account_root, &state, &network_params);
 
         if self.genesis_app_hash != genesis_app_hash {
             panic!("initchain resulting genesis app hash: {} does not match the expected genesis app hash: {}", hex::encode(genesis_app_hash), hex::encode(self.genesis_app_hash));
@@ -473,7 +688,6 @@ impl<T: EnclaveProxy + 'static> ChainNodeApp<T> {
         );
 
         let genesis_state = ChainNodeState::genesis(
-            genesis_app_hash,
             genesis_time,
             max_evidence_age,
             new_account_root,
@@ -482,6 +696,7 @@ impl<T: EnclaveProxy + 'static> ChainNodeApp<T> {
             staking_table,
             state.isv_svn,
         );
+        debug_assert_eq!(genesis_app_hash, genesis_state.last_apphash);
         chain_storage::store_genesis_state(
             &mut kv_store!(self),
             &genesis_state,
@@ -570,3 +785,77 @@ impl<T: EnclaveProxy + 'static> ChainNodeApp<T> {
         total1
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_legacy_state_migration() {
+        // golden encoding of the schema registry sample
+        let network_params = NetworkParameters::decode(
+            &mut hex::decode("004c04000000000000e20400000000000050c30000000000001027000000000000640032006400000000000000c8000000000000002c0100000000000060ea0000000000008051010000000000c201000000000000e803000000000000b4410f00000000003200")
+                .unwrap()
+                .as_slice(),
+        )
+        .unwrap();
+        let rewards_pool = RewardsPoolState::new(1_000, 7);
+        let staking_version: Version = 5;
+
+        // the state stored by the 0.5 release
+        let mut legacy = BlockHeight::new(10).encode();
+        legacy.extend([1u8; 32].encode());
+        legacy.extend(1_100u64.encode());
+        legacy.extend(BlockHeight::new(11).encode());
+        legacy.extend(StakingTable::default().encode());
+        legacy.extend(1_000u64.encode());
+        legacy.extend(3_600u64.encode());
+        legacy.extend(staking_version.encode());
+        legacy.extend(Coin::new(500).unwrap().encode());
+        legacy.extend(2u16.encode());
+        legacy.extend([2u8; 32].encode());
+        legacy.extend(rewards_pool.encode());
+        legacy.extend(network_params.encode());
+
+        let state = ChainNodeState::decode(&mut legacy.as_slice()).unwrap();
+        assert_eq!(BlockHeight::new(10), state.last_block_height);
+        assert_eq!([1u8; 32], state.last_apphash);
+        assert_eq!(1_100, state.block_time);
+        assert_eq!(BlockHeight::new(11), state.block_height);
+        assert_eq!(1_000, state.genesis_time);
+        assert_eq!(3_600, state.max_evidence_age);
+        assert_eq!(staking_version, state.staking_version);
+        assert_eq!(Coin::new(500).unwrap(), state.utxo_coins);
+        assert_eq!(2, state.enclave_isv_svn);
+        assert!(state.pending_params_update.is_none());
+        assert!(state.scheduled_upgrade.is_none());
+        assert_eq!(AccountFreezeState::default(), state.account_freeze);
+        assert_eq!([2u8; 32], state.top_level.account_root);
+        assert_eq!(rewards_pool, state.top_level.rewards_pool);
+        assert_eq!(network_params, state.top_level.network_params);
+
+        // stored again with the versioned layout
+        let encoded = state.encode();
+        assert_ne!(legacy, encoded);
+        let decoded = ChainNodeState::decode(&mut encoded.as_slice()).unwrap();
+        assert_eq!(state.last_block_height, decoded.last_block_height);
+        assert_eq!(state.staking_version, decoded.staking_version);
+        assert_eq!(state.top_level.account_root, decoded.top_level.account_root);
+        assert_eq!(state.top_level.rewards_pool, decoded.top_level.rewards_pool);
+        assert_eq!(encoded, decoded.encode());
+
+        // the layout 1 (before the account freeze state, which is stored before the top level)
+        let top_level_start = encoded.len() - state.top_level.encode().len();
+        let freeze_len = state.account_freeze.encode().len();
+        let mut layout1 = encoded[..top_level_start - freeze_len].to_vec();
+        layout1.extend(&encoded[top_level_start..]);
+        layout1[8] = 1;
+        let decoded = ChainNodeState::decode(&mut layout1.as_slice()).unwrap();
+        assert_eq!(AccountFreezeState::default(), decoded.account_freeze);
+        assert_eq!(encoded, decoded.encode());
+
+        let mut unknown = encoded;
+        unknown[8] = STATE_LAYOUT_VERSION + 1;
+        assert!(ChainNodeState::decode(&mut unknown.as_slice()).is_err());
+    }
+}
@@ -0,0 +1,135 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+
+use serde::{Deserialize, Serialize};
+
+use chain_core::common::H256;
+use chain_core::state::tendermint::BlockHeight;
+use chain_storage::backup::{open_columns, write_snapshot};
+use chain_storage::Storage;
+
+pub(crate) const BACKUP_DIR_PREFIX: &str = "backup-";
+
+/// Automatic storage backups (the `backup` section of the configuration file)
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct BackupConfig {
+    /// target directory (backups are disabled if not set)
+    pub directory: Option<String>,
+    /// a backup is made every `interval` blocks
+    pub interval: u64,
+    /// number of the most recent backups kept (0 = keep all)
+    pub retain: usize,
+}
+
+/// Exports a storage snapshot (see `chain_storage::backup`) every N blocks at Commit
+/// into `<directory>/backup-<height>` and removes the old ones.
+/// The snapshot is written on a background thread; block processing is only paused
+/// until the column iterators are opened.
+pub struct BackupScheduler {
+    directory: PathBuf,
+    interval: u64,
+    retain: usize,
+    /// a backup is being written
+    in_progress: Arc<AtomicBool>,
+}
+
+impl BackupScheduler {
+    /// None if backups are not configured
+    pub fn from_config(config: &BackupConfig) -> Option<Self> {
+        match &config.directory {
+            Some(directory) if config.interval > 0 => Some(BackupScheduler {
+                directory: PathBuf::from(directory),
+                interval: config.interval,
+                retain: config.retain,
+                in_progress: Arc::new(AtomicBool::new(false)),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Starts a backup if the committed height is at the interval and the previous backup
+    /// is finished (failures are only logged)
+    pub fn on_commit(&self, storage: &Storage, height: BlockHeight, app_hash: H256) {
+        if height.value() % self.interval != 0 {
+            return;
+        }
+        if self.in_progress.swap(true, Ordering::SeqCst) {
+            log::warn!(
+                "storage backup at height {} skipped: the previous backup is not finished",
+                height
+            );
+            return;
+        }
+        let db = storage.temp_hack_for_tdbe();
+        let directory = self.directory.clone();
+        let dir = directory.join(format!("{}{:020}", BACKUP_DIR_PREFIX, height.value()));
+        let retain = self.retain;
+        let in_progress = self.in_progress.clone();
+        let (opened_sender, opened_receiver) = mpsc::channel();
+        let spawned = thread::Builder::new()
+            .name("storage-backup".to_owned())
+            .spawn(move || {
+                let columns = open_columns(&*db);
+                let _ = opened_sender.send(());
+                if let Err(e) = fs::create_dir_all(&directory) {
+                    log::error!("failed to create backup directory: {}", e);
+                } else {
+                    match write_snapshot(columns, &dir, height, app_hash) {
+                        Ok(_) => {
+                            log::info!("storage backup at height {}: {}", height, dir.display());
+                            if let Err(e) = prune(&directory, retain) {
+                                log::warn!("failed to remove old backups: {}", e);
+                            }
+                        }
+                        Err(e) => log::error!("storage backup at height {} failed: {}", height, e),
+                    }
+                }
+                in_progress.store(false, Ordering::SeqCst);
+            });
+        match spawned {
+            // the next block must not be written before the snapshot view is taken
+            Ok(_) => {
+                let _ = opened_receiver.recv();
+            }
+            Err(e) => {
+                log::error!("failed to start storage backup thread: {}", e);
+                self.in_progress.store(false, Ordering::SeqCst);
+            }
+        }
+    }
+}
+
+/// Removes the oldest backups, keeping the `retain` most recent ones (0 = keep all)
+fn prune(directory: &Path, retain: usize) -> std::io::Result<()> {
+    if retain == 0 {
+        return Ok(());
+    }
+    let backups = list_backups(directory)?;
+    let remove = backups.len().saturating_sub(retain);
+    for path in backups.into_iter().take(remove) {
+        fs::remove_dir_all(&path)?;
+    }
+    Ok(())
+}
+
+/// Complete backups in the directory (ordered by height)
+pub(crate) fn list_backups(directory: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut backups = Vec::new();
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+        let is_backup = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .map(|name| name.starts_with(BACKUP_DIR_PREFIX) && !name.ends_with(".tmp"))
+            .unwrap_or(false);
+        if is_backup && path.is_dir() {
+            backups.push(path);
+        }
+    }
+    // zero-padded heights, so the lexicographic order is the height order
+    backups.sort();
+    Ok(backups)
+}
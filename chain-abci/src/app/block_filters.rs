@@ -0,0 +1,66 @@
+use chain_core::state::tendermint::BlockHeight;
+use chain_storage::buffer::GetKV;
+
+/// Maximum number of blocks served by one "block-filters" query
+pub const MAX_BLOCK_FILTERS_RANGE: u64 = 1_000;
+
+/// Filters of a block: the view key bloom filter produced by the enclave in EndBlock
+/// (None if the block had no transactions with view keys) and the compact filter
+/// of its public transaction data
+pub type BlockFilters = (BlockHeight, Option<Vec<u8>>, Vec<u8>);
+
+/// Collects the filters of the blocks in the (inclusive) height range, so that wallets can
+/// only download the blocks which may be relevant to them.
+/// The blocks without stored filters (e.g. committed before they were stored) are omitted.
+pub fn collect_block_filters(
+    db: &impl GetKV,
+    from_height: BlockHeight,
+    to_height: BlockHeight,
+) -> Result<Vec<BlockFilters>, &'static str> {
+    if from_height > to_height {
+        return Err("invalid height range");
+    }
+    if to_height.value() - from_height.value() >= MAX_BLOCK_FILTERS_RANGE {
+        return Err("height range too large");
+    }
+    Ok((from_height.value()..=to_height.value())
+        .map(BlockHeight::new)
+        .filter_map(|height| {
+            chain_storage::get_compact_filter(db, height).map(|compact_filter| {
+                (
+                    height,
+                    chain_storage::get_block_filter(db, height),
+                    compact_filter,
+                )
+            })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chain_storage::buffer::MemStore;
+
+    #[test]
+    fn check_collect_block_filters() {
+        let mut db = MemStore::new();
+        for height in 1u64..=3 {
+            chain_storage::store_compact_filter(&mut db, height.into(), &[height as u8; 12]);
+        }
+        chain_storage::store_block_filter(&mut db, 2.into(), &[0xff; 256]);
+
+        let filters = collect_block_filters(&db, 2.into(), 10.into()).unwrap();
+        assert_eq!(
+            vec![
+                (BlockHeight::new(2), Some(vec![0xff; 256]), vec![2; 12]),
+                (BlockHeight::new(3), None, vec![3; 12]),
+            ],
+            filters
+        );
+        assert!(collect_block_filters(&db, 3.into(), 2.into()).is_err());
+        assert!(
+            collect_block_filters(&db, 1.into(), (MAX_BLOCK_FILTERS_RANGE + 1).into()).is_err()
+        );
+    }
+}
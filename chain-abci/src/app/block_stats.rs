@@ -0,0 +1,186 @@
+use std::collections::BTreeMap;
+
+use parity_scale_codec::{Decode, Encode};
+use serde::Serialize;
+
+use chain_core::init::coin::Coin;
+use chain_core::state::account::StakedStateAddress;
+use chain_core::state::tendermint::{BlockHeight, TendermintValidatorAddress};
+use chain_storage::buffer::GetKV;
+
+use crate::staking::RewardsDistribution;
+
+/// Maximum number of blocks aggregated by one "block-stats" query
+pub const MAX_BLOCK_STATS_RANGE: u64 = 100_000;
+
+/// Production statistics of a block (stored per height, not part of the app hash);
+/// aggregated per validator by the "block-stats" ABCI query
+#[derive(Debug, Clone, Default, PartialEq, Encode, Decode)]
+pub struct BlockStats {
+    /// validator address of the block proposer
+    pub proposer: Option<TendermintValidatorAddress>,
+    /// staking address of the block proposer (if it was a known validator at the beginning of the block)
+    pub proposer_staking_address: Option<StakedStateAddress>,
+    /// number of valid transactions in the block
+    pub transactions: u32,
+    /// fees of the valid transactions in the block (they go to the rewards pool)
+    pub fees: Coin,
+    /// rewards distributed at the beginning of the block (if it was the end of a reward period)
+    pub rewards: RewardsDistribution,
+}
+
+impl BlockStats {
+    /// Statistics of a new block proposed by the given validator
+    pub fn new(
+        proposer: Option<TendermintValidatorAddress>,
+        proposer_staking_address: Option<StakedStateAddress>,
+    ) -> Self {
+        BlockStats {
+            proposer,
+            proposer_staking_address,
+            ..Default::default()
+        }
+    }
+
+    /// Records a valid transaction of the block
+    pub fn add_transaction(&mut self, fee: Coin) {
+        self.transactions += 1;
+        self.fees = (self.fees + fee).expect("block fees greater than max coin?");
+    }
+}
+
+/// Aggregated statistics of one validator over a height range
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ValidatorBlockStats {
+    /// number of blocks proposed
+    pub blocks_proposed: u64,
+    /// number of valid transactions in the proposed blocks
+    pub transactions: u64,
+    /// fees of the valid transactions in the proposed blocks
+    pub fees: Coin,
+    /// distributed rewards
+    pub rewards: Coin,
+}
+
+/// Response of the "block-stats" query: per-validator aggregates over a height range
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BlockStatsSummary {
+    pub from_height: BlockHeight,
+    pub to_height: BlockHeight,
+    /// number of blocks with stored statistics in the range
+    pub blocks: u64,
+    /// fees of blocks whose proposer was not a known validator
+    pub unattributed_fees: Coin,
+    /// aggregates by the staking address
+    pub validators: BTreeMap<String, ValidatorBlockStats>,
+}
+
+impl BlockStatsSummary {
+    fn add_block(&mut self, stats: BlockStats) {
+        self.blocks += 1;
+        match stats.proposer_staking_address {
+            Some(address) => {
+                let entry = self.validators.entry(address.to_string()).or_default();
+                entry.blocks_proposed += 1;
+                entry.transactions += u64::from(stats.transactions);
+                entry.fees = (entry.fees + stats.fees).expect("fees greater than max coin?");
+            }
+            None => {
+                self.unattributed_fees =
+                    (self.unattributed_fees + stats.fees).expect("fees greater than max coin?");
+            }
+        }
+        for (address, amount) in stats.rewards.iter() {
+            let entry = self.validators.entry(address.to_string()).or_default();
+            entry.rewards = (entry.rewards + *amount).expect("rewards greater than max coin?");
+        }
+    }
+}
+
+/// Aggregates the stored block statistics in the (inclusive) height range
+pub fn aggregate_block_stats(
+    db: &impl GetKV,
+    from_height: BlockHeight,
+    to_height: BlockHeight,
+) -> Result<BlockStatsSummary, &'static str> {
+    if from_height > to_height {
+        return Err("invalid height range");
+    }
+    if to_height.value() - from_height.value() >= MAX_BLOCK_STATS_RANGE {
+        return Err("height range too large");
+    }
+    let mut summary = BlockStatsSummary {
+        from_height,
+        to_height,
+        blocks: 0,
+        unattributed_fees: Coin::zero(),
+        validators: BTreeMap::new(),
+    };
+    for height in from_height.value()..=to_height.value() {
+        if let Some(raw) = chain_storage::get_block_stats(db, BlockHeight::new(height)) {
+            let stats =
+                BlockStats::decode(&mut raw.as_slice()).map_err(|_| "block stats decode failed")?;
+            summary.add_block(stats);
+        }
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chain_core::init::address::RedeemAddress;
+    use chain_storage::buffer::MemStore;
+
+    #[test]
+    fn check_block_stats_aggregation() {
+        let validator = StakedStateAddress::BasicRedeem(RedeemAddress::from([1; 20]));
+        let other = StakedStateAddress::BasicRedeem(RedeemAddress::from([2; 20]));
+        let mut db = MemStore::new();
+
+        let mut block1 = BlockStats::new(None, Some(validator));
+        block1.add_transaction(Coin::new(10).unwrap());
+        block1.add_transaction(Coin::new(5).unwrap());
+        let mut block2 = BlockStats::new(None, None);
+        block2.add_transaction(Coin::new(7).unwrap());
+        let mut block3 = BlockStats::new(None, Some(validator));
+        block3.rewards = vec![
+            (validator, Coin::new(100).unwrap()),
+            (other, Coin::new(50).unwrap()),
+        ];
+        for (height, stats) in [block1, block2, block3].iter().enumerate() {
+            chain_storage::store_block_stats(
+                &mut db,
+                BlockHeight::new(height as u64 + 1),
+                &stats.encode(),
+            );
+        }
+
+        let summary = aggregate_block_stats(&db, 1.into(), 10.into()).unwrap();
+        assert_eq!(3, summary.blocks);
+        assert_eq!(Coin::new(7).unwrap(), summary.unattributed_fees);
+        assert_eq!(
+            ValidatorBlockStats {
+                blocks_proposed: 2,
+                transactions: 2,
+                fees: Coin::new(15).unwrap(),
+                rewards: Coin::new(100).unwrap(),
+            },
+            summary.validators[&validator.to_string()]
+        );
+        assert_eq!(
+            ValidatorBlockStats {
+                rewards: Coin::new(50).unwrap(),
+                ..Default::default()
+            },
+            summary.validators[&other.to_string()]
+        );
+
+        let summary = aggregate_block_stats(&db, 2.into(), 2.into()).unwrap();
+        assert_eq!(1, summary.blocks);
+        assert!(summary.validators.is_empty());
+
+        assert!(aggregate_block_stats(&db, 3.into(), 2.into()).is_err());
+        assert!(aggregate_block_stats(&db, 1.into(), (MAX_BLOCK_STATS_RANGE + 1).into()).is_err());
+    }
+}
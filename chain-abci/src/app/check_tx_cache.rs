@@ -0,0 +1,280 @@
+use std::cell::Cell;
+use std::collections::HashMap;
+
+use crate::storage::TxEnclaveAction;
+use chain_core::common::H256;
+use chain_core::state::tendermint::BlockHeight;
+use chain_core::tx::data::input::TxoPointer;
+use chain_core::tx::fee::Fee;
+use chain_storage::buffer::{GetKV, GetStaking};
+use chain_tx_validation::verify_unjailed;
+
+/// Default number of cached verdicts
+pub const DEFAULT_CHECK_TX_CACHE_CAPACITY: usize = 10_000;
+/// Default number of blocks a cached verdict is kept for
+pub const DEFAULT_CHECK_TX_CACHE_MAX_AGE: u64 = 20;
+
+/// Key of a cached verdict: blake3 hash of the raw transaction (as in CheckTx).
+/// The transaction ID of an enclave transaction is claimed by the submitter outside of the
+/// sealed payload, so it doesn't identify the validated transaction.
+pub type CacheKey = H256;
+
+/// The key of the verdict of the raw transaction
+pub fn cache_key(raw_tx: &[u8]) -> CacheKey {
+    blake3::hash(raw_tx).into()
+}
+
+struct CachedVerdict {
+    action: TxEnclaveAction,
+    /// the minimal fee the transaction was validated against
+    min_fee: Fee,
+    /// block height at which the verdict was produced
+    height: BlockHeight,
+}
+
+/// Short-lived cache of successful CheckTx verdicts of enclave transactions,
+/// so that Tendermint's mempool re-check after each block doesn't redo
+/// the full signature and enclave validation of unchanged transactions.
+///
+/// Only transfer and deposit verdicts are cached: their validity only depends on
+/// the inputs being unspent (and the target staked state not being jailed),
+/// which is re-checked against the mempool state on every hit.
+/// Withdraw verdicts depend on the full staked state, so they are always revalidated.
+pub struct CheckTxCache {
+    entries: HashMap<CacheKey, CachedVerdict>,
+    capacity: usize,
+    max_age: u64,
+    /// number of lookups which returned a still valid verdict
+    hits: Cell<u64>,
+    /// number of lookups without a (valid) verdict
+    misses: Cell<u64>,
+}
+
+impl Default for CheckTxCache {
+    fn default() -> Self {
+        CheckTxCache::new(
+            DEFAULT_CHECK_TX_CACHE_CAPACITY,
+            DEFAULT_CHECK_TX_CACHE_MAX_AGE,
+        )
+    }
+}
+
+impl CheckTxCache {
+    /// capacity: maximal number of cached verdicts (0 disables the cache)
+    /// max_age: number of blocks a verdict is kept for
+    pub fn new(capacity: usize, max_age: u64) -> Self {
+        CheckTxCache {
+            entries: HashMap::new(),
+            capacity,
+            max_age,
+            hits: Cell::new(0),
+            misses: Cell::new(0),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Number of lookups with and without a valid cached verdict
+    pub fn stats(&self) -> (u64, u64) {
+        (self.hits.get(), self.misses.get())
+    }
+
+    /// Returns the cached action if the verdict is still valid against the mempool state
+    pub fn get(
+        &self,
+        key: &CacheKey,
+        min_fee: Fee,
+        trie: &impl GetStaking,
+        kvdb: &impl GetKV,
+    ) -> Option<TxEnclaveAction> {
+        let action = self.lookup(key, min_fee, trie, kvdb);
+        if action.is_some() {
+            self.hits.set(self.hits.get() + 1);
+        } else {
+            self.misses.set(self.misses.get() + 1);
+        }
+        action
+    }
+
+    fn lookup(
+        &self,
+        key: &CacheKey,
+        min_fee: Fee,
+        trie: &impl GetStaking,
+        kvdb: &impl GetKV,
+    ) -> Option<TxEnclaveAction> {
+        let verdict = self.entries.get(key)?;
+        if verdict.min_fee != min_fee {
+            return None;
+        }
+        let still_valid = match &verdict.action {
+            TxEnclaveAction::Transfer { spend_utxo, .. } => all_unspent(kvdb, spend_utxo),
+            TxEnclaveAction::Deposit {
+                spend_utxo,
+                deposit: (address, _),
+                ..
+            } => {
+                all_unspent(kvdb, spend_utxo)
+                    && trie
+                        .get(address)
+                        .map(|account| verify_unjailed(&account).is_ok())
+                        .unwrap_or(true)
+            }
+            TxEnclaveAction::Withdraw { .. } => false,
+        };
+        if still_valid {
+            Some(verdict.action.clone())
+        } else {
+            None
+        }
+    }
+
+    /// Caches a successful verdict produced at the given block height
+    pub fn insert(
+        &mut self,
+        key: CacheKey,
+        action: &TxEnclaveAction,
+        min_fee: Fee,
+        height: BlockHeight,
+    ) {
+        if let TxEnclaveAction::Withdraw { .. } = action {
+            return;
+        }
+        if self.entries.len() >= self.capacity && !self.entries.contains_key(&key) {
+            return;
+        }
+        self.entries.insert(
+            key,
+            CachedVerdict {
+                action: action.clone(),
+                min_fee,
+                height,
+            },
+        );
+    }
+
+    /// Drops the verdicts of transactions which spend any of the consumed inputs
+    pub fn invalidate_spent(&mut self, consumed: &[TxoPointer]) {
+        if consumed.is_empty() {
+            return;
+        }
+        self.entries.retain(|_, verdict| match &verdict.action {
+            TxEnclaveAction::Transfer { spend_utxo, .. }
+            | TxEnclaveAction::Deposit { spend_utxo, .. } => {
+                !spend_utxo.iter().any(|input| consumed.contains(input))
+            }
+            TxEnclaveAction::Withdraw { .. } => false,
+        });
+    }
+
+    /// Drops the verdicts older than `max_age` blocks
+    pub fn prune(&mut self, current_height: BlockHeight) {
+        let max_age = self.max_age;
+        self.entries
+            .retain(|_, verdict| verdict.height.saturating_add(max_age) >= current_height);
+    }
+}
+
+fn all_unspent(kvdb: &impl GetKV, inputs: &[TxoPointer]) -> bool {
+    chain_storage::lookup_inputs(kvdb, inputs)
+        .into_iter()
+        .all(|spent| spent == Some(false))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chain_core::init::address::RedeemAddress;
+    use chain_core::init::coin::Coin;
+    use chain_core::state::account::{
+        DepositBondTx, StakedState, StakedStateAddress, StakedStateOpAttributes,
+    };
+    use chain_core::tx::{TxAux, TxEnclaveAux, TxObfuscated};
+    use chain_storage::buffer::MemStore;
+    use parity_scale_codec::Encode;
+
+    fn deposit(inputs: Vec<TxoPointer>) -> TxEnclaveAction {
+        TxEnclaveAction::Deposit {
+            fee: Fee::new(Coin::zero()),
+            spend_utxo: inputs,
+            deposit: (
+                StakedStateAddress::BasicRedeem(RedeemAddress::default()),
+                Coin::one(),
+            ),
+        }
+    }
+
+    #[test]
+    fn check_invalidate_spent_and_prune() {
+        let mut cache = CheckTxCache::new(10, 2);
+        let input_a = TxoPointer::new([0; 32], 0);
+        let input_b = TxoPointer::new([1; 32], 0);
+        let fee = Fee::new(Coin::zero());
+        cache.insert([2; 32], &deposit(vec![input_a.clone()]), fee, 1.into());
+        cache.insert([3; 32], &deposit(vec![input_b]), fee, 2.into());
+        assert_eq!(2, cache.len());
+
+        cache.invalidate_spent(&[input_a]);
+        assert_eq!(1, cache.len());
+
+        cache.prune(4.into());
+        assert_eq!(1, cache.len());
+        cache.prune(5.into());
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn check_same_txid_with_other_payload_misses() {
+        let tx = |txpayload: Vec<u8>| {
+            TxAux::EnclaveTx(TxEnclaveAux::DepositStakeTx {
+                tx: DepositBondTx::new(
+                    vec![],
+                    StakedStateAddress::BasicRedeem(RedeemAddress::default()),
+                    StakedStateOpAttributes::new(0),
+                ),
+                payload: TxObfuscated {
+                    txid: [5; 32],
+                    key_from: BlockHeight::genesis(),
+                    init_vector: [0; 12],
+                    txpayload,
+                },
+            })
+        };
+        let validated = tx(vec![1; 100]);
+        let other = tx(vec![2; 100]);
+        assert_eq!(validated.tx_id(), other.tx_id());
+
+        let mut cache = CheckTxCache::new(10, 2);
+        let fee = Fee::new(Coin::zero());
+        let trie: MemStore<StakedStateAddress, StakedState> = MemStore::new();
+        let kvdb: MemStore<(u32, Vec<u8>), Vec<u8>> = MemStore::new();
+        cache.insert(
+            cache_key(&validated.encode()),
+            &deposit(vec![]),
+            fee,
+            1.into(),
+        );
+        assert!(cache
+            .get(&cache_key(&other.encode()), fee, &trie, &kvdb)
+            .is_none());
+        assert!(cache
+            .get(&cache_key(&validated.encode()), fee, &trie, &kvdb)
+            .is_some());
+        assert_eq!((1, 1), cache.stats());
+    }
+
+    #[test]
+    fn check_capacity() {
+        let mut cache = CheckTxCache::new(1, 2);
+        let fee = Fee::new(Coin::zero());
+        cache.insert([2; 32], &deposit(vec![]), fee, 1.into());
+        cache.insert([3; 32], &deposit(vec![]), fee, 1.into());
+        assert_eq!(1, cache.len());
+    }
+}
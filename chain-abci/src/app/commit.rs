@@ -1,16 +1,23 @@
 use std::mem;
 
+use super::pruning::prune_on_commit;
+use super::storage_metrics::log_slow_storage_ops;
 use super::ChainNodeApp;
 use crate::enclave_bridge::EnclaveProxy;
 use abci::*;
 use chain_core::common::MerkleTree;
-use chain_core::compute_app_hash;
+use chain_core::state::account::{DataAnchorRecord, DataAnchorTx, StakedStateAddress};
+use chain_core::state::tendermint::BlockHeight;
+use chain_core::state::{validator_set_hash, UtxoChange};
 use chain_core::tx::data::input::{TxoPointer, TxoSize};
 use chain_core::tx::data::TxId;
 use chain_core::tx::{TxAux, TxEnclaveAux, TxPublicAux};
+use chain_core::{compute_app_hash, UTXO_SET_COMMITTED};
 use chain_storage::buffer::{flush_storage, StoreKV};
 use chain_storage::jellyfish::flush_stakings;
-use parity_scale_codec::Encode;
+use chain_storage::utxo_mmr::apply_utxo_changes;
+use chain_tx_filter::CompactFilterBuilder;
+use parity_scale_codec::{Decode, Encode};
 
 /// Given a db and a DB transaction, it will go through TX inputs and mark them as spent
 /// in the TX_META storage and it will create a new entry for TX in TX_META with all outputs marked as unspent.
@@ -24,7 +31,22 @@ fn update_utxos_commit(
     chain_storage::create_utxo(db, no_of_outputs, &txid);
 }
 
-fn process_txs(delivered_txs: &[TxAux], db: &mut impl StoreKV) {
+/// Appends the anchoring to the index of the commitment's anchorings
+fn index_data_anchor(db: &mut impl StoreKV, height: BlockHeight, tx: &DataAnchorTx) {
+    let mut anchors = chain_storage::get_data_anchors(&*db, &tx.commitment)
+        .map(|value| {
+            Vec::<DataAnchorRecord>::decode(&mut value.as_slice())
+                .expect("data anchor storage corrupted")
+        })
+        .unwrap_or_default();
+    anchors.push(DataAnchorRecord {
+        height,
+        tx: tx.clone(),
+    });
+    chain_storage::store_data_anchors(db, &tx.commitment, &anchors.encode());
+}
+
+fn process_txs(delivered_txs: &[TxAux], height: BlockHeight, db: &mut impl StoreKV) {
     for txaux in delivered_txs.iter() {
         let txid: TxId = txaux.tx_id();
         match &txaux {
@@ -70,10 +92,96 @@ fn process_txs(delivered_txs: &[TxAux], db: &mut impl StoreKV) {
                 // staked state updated in deliver_tx
                 // validator state updated in end_block
             }
+            TxAux::PublicTx(TxPublicAux::NetworkParamsUpdateTx(tx, votes)) => {
+                chain_storage::store_tx_body(db, &txid, &tx.encode());
+                chain_storage::store_tx_witness(db, &txid, &votes.encode());
+                // the update is scheduled in deliver_tx and applied in begin_block
+            }
+            TxAux::PublicTx(TxPublicAux::UpgradeSignalTx(tx, witness)) => {
+                chain_storage::store_tx_body(db, &txid, &tx.encode());
+                chain_storage::store_tx_witness(db, &txid, &witness.encode());
+                // the signal is recorded in deliver_tx
+            }
+            TxAux::PublicTx(TxPublicAux::DataAnchorTx(tx, witness)) => {
+                chain_storage::store_tx_body(db, &txid, &tx.encode());
+                chain_storage::store_tx_witness(db, &txid, &witness.encode());
+                // the fee is paid in deliver_tx
+                index_data_anchor(db, height, tx);
+            }
+            TxAux::PublicTx(TxPublicAux::AccountFreezeTx(tx, votes)) => {
+                chain_storage::store_tx_body(db, &txid, &tx.encode());
+                chain_storage::store_tx_witness(db, &txid, &votes.encode());
+                // the decision is applied in deliver_tx
+            }
         }
     }
 }
 
+/// Returns the transaction outputs spent by the delivered transactions
+fn consumed_inputs(delivered_txs: &[TxAux]) -> Vec<TxoPointer> {
+    delivered_txs
+        .iter()
+        .flat_map(|txaux| match txaux {
+            TxAux::EnclaveTx(TxEnclaveAux::TransferTx { inputs, .. }) => inputs.clone(),
+            TxAux::EnclaveTx(TxEnclaveAux::DepositStakeTx { tx, .. }) => tx.inputs.clone(),
+            _ => vec![],
+        })
+        .collect()
+}
+
+/// Returns the changes of the UTXO set by the delivered transactions (in the block order)
+fn utxo_changes(delivered_txs: &[TxAux], txids: &[TxId]) -> Vec<UtxoChange> {
+    delivered_txs
+        .iter()
+        .zip(txids.iter())
+        .flat_map(|(txaux, txid)| {
+            let (inputs, no_of_outputs) = match txaux {
+                TxAux::EnclaveTx(TxEnclaveAux::TransferTx {
+                    inputs,
+                    no_of_outputs,
+                    ..
+                }) => (inputs.clone(), Some(*no_of_outputs)),
+                TxAux::EnclaveTx(TxEnclaveAux::DepositStakeTx { tx, .. }) => {
+                    (tx.inputs.clone(), None)
+                }
+                TxAux::EnclaveTx(TxEnclaveAux::WithdrawUnbondedStakeTx {
+                    no_of_outputs, ..
+                }) => (vec![], Some(*no_of_outputs)),
+                _ => (vec![], None),
+            };
+            let created = no_of_outputs.map(|no_of_outputs| UtxoChange::Created {
+                txid: *txid,
+                no_of_outputs,
+            });
+            inputs
+                .into_iter()
+                .map(UtxoChange::Spent)
+                .chain(created)
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Builds the compact block filter over the public data of the delivered transactions
+/// and the staked states updated in this block
+fn compact_filter<'a>(
+    delivered_txs: &[TxAux],
+    txids: &[TxId],
+    updated_stakings: impl Iterator<Item = &'a StakedStateAddress>,
+) -> CompactFilterBuilder {
+    let mut builder = CompactFilterBuilder::default();
+    for input in consumed_inputs(delivered_txs).iter() {
+        builder.add_input(input);
+    }
+    for address in updated_stakings {
+        builder.add_staking_address(address);
+    }
+    for txid in txids.iter() {
+        builder.add_txid(txid);
+    }
+    builder
+}
+
 impl<T: EnclaveProxy + 'static> ChainNodeApp<T> {
     /// Commits delivered TX: flushes updates to the underlying storage
     pub fn commit_handler(&mut self, _req: &RequestCommit) -> ResponseCommit {
@@ -86,16 +194,31 @@ impl<T: EnclaveProxy + 'static> ChainNodeApp<T> {
             .iter()
             .map(chain_core::tx::TxAux::tx_id)
             .collect();
+        let filter = compact_filter(&self.delivered_txs, &ids, self.staking_buffer.keys())
+            .build(new_state.last_block_height);
+        if UTXO_SET_COMMITTED {
+            let changes = utxo_changes(&self.delivered_txs, &ids);
+            if !changes.is_empty() {
+                top_level.utxo_commitment = apply_utxo_changes(&mut kv_store!(self), &changes);
+            }
+        }
         let tree = MerkleTree::new(ids);
 
         if !self.delivered_txs.is_empty() {
-            process_txs(&self.delivered_txs, &mut kv_store!(self));
+            process_txs(
+                &self.delivered_txs,
+                new_state.last_block_height,
+                &mut kv_store!(self),
+            );
         }
         if self.rewards_pool_updated {
             top_level.rewards_pool.last_block_height = new_state.last_block_height;
             self.rewards_pool_updated = false;
         }
 
+        self.watch_list
+            .on_commit(new_state.last_block_height, &self.staking_buffer);
+
         // flush staking storage
         if !self.staking_buffer.is_empty() {
             new_state.staking_version = new_state
@@ -110,15 +233,35 @@ impl<T: EnclaveProxy + 'static> ChainNodeApp<T> {
             .expect("merkle trie io error");
         }
 
-        let app_hash = compute_app_hash(
-            &tree,
-            &top_level.account_root,
-            &top_level.rewards_pool,
-            &top_level.network_params,
-        );
+        top_level.validator_set_hash =
+            validator_set_hash(new_state.staking_table.get_chosen_validators());
+        let app_hash = compute_app_hash(&tree, top_level);
         new_state.last_apphash = app_hash;
 
         chain_storage::store_txs_merkle_tree(&mut kv_store!(self), &app_hash, &tree.encode());
+        chain_storage::store_compact_filter(
+            &mut kv_store!(self),
+            new_state.last_block_height,
+            &filter.to_bytes(),
+        );
+        chain_storage::store_block_stats(
+            &mut kv_store!(self),
+            new_state.last_block_height,
+            &mem::take(&mut self.block_stats).encode(),
+        );
+        let pruned = prune_on_commit(
+            self.pruning,
+            &mut kv_store!(self),
+            new_state.last_block_height,
+            &self.delivered_txs,
+        );
+        if pruned > 0 {
+            log::debug!(
+                "pruned {} transaction bodies at height {}",
+                pruned,
+                new_state.last_block_height
+            );
+        }
         chain_storage::store_chain_state(
             &mut kv_store!(self),
             &*new_state,
@@ -127,15 +270,29 @@ impl<T: EnclaveProxy + 'static> ChainNodeApp<T> {
         );
 
         // flush key-value storage
+        let write_batch_size = self.kv_buffer.len();
         flush_storage(&mut self.storage, mem::take(&mut self.kv_buffer))
             .expect("kv storage io error");
+        if let Some(backup) = &self.backup {
+            backup.on_commit(&self.storage, new_state.last_block_height, app_hash);
+        }
+        log_slow_storage_ops(&self.storage, new_state.last_block_height);
+        self.node_status
+            .on_commit(new_state, self.tx_query_address.as_ref());
 
         resp.data = new_state.last_apphash.to_vec();
 
+        self.check_tx_cache
+            .invalidate_spent(&consumed_inputs(&self.delivered_txs));
+        self.check_tx_cache.prune(new_state.last_block_height);
+        self.rejected_txs.new_block();
+        self.mempool_policy.new_block();
+
         self.mempool_state = Some(new_state.clone());
         self.delivered_txs.clear();
         self.mempool_kv_buffer.clear();
         self.mempool_staking_buffer.clear();
+        self.metrics.commit(write_batch_size);
         resp
     }
 }
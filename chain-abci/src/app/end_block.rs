@@ -20,6 +20,12 @@ impl<T: EnclaveProxy + 'static> ChainNodeApp<T> {
                     let filter = BlockFilter::from(&*raw_filter);
 
                     let (key, value) = filter.get_tendermint_kv();
+                    // also served by the "block-filters" ABCI query
+                    chain_storage::store_block_filter(
+                        &mut kv_store!(self),
+                        req.height.try_into().unwrap(),
+                        &value,
+                    );
                     let mut kvpair = KVPair::new();
                     kvpair.key = key;
                     kvpair.value = value;
@@ -56,6 +62,9 @@ impl<T: EnclaveProxy + 'static> ChainNodeApp<T> {
                 })
                 .collect(),
         );
+        if let Some(params) = self.consensus_params_update.take() {
+            resp.set_consensus_param_updates(params);
+        }
         state.last_block_height = req.height.try_into().unwrap();
         resp
     }
@@ -0,0 +1,282 @@
+//! Typed query service (`chain.abci.query.v1.Query` defined in `proto/query.proto`).
+//!
+//! The service is served alongside the raw key/value queries: the ABCI query path is the method
+//! path (e.g. `/chain.abci.query.v1.Query/StakedState`), the query data is the protobuf-encoded
+//! request and the response value is the protobuf-encoded response message.
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+
+use abci::ResponseQuery;
+use parity_scale_codec::{Decode, Encode};
+use protobuf::wire_format::WireType;
+use protobuf::{CodedInputStream, CodedOutputStream, ProtobufResult};
+
+use super::ChainNodeApp;
+use crate::enclave_bridge::EnclaveProxy;
+use chain_core::common::H256;
+use chain_core::state::account::StakedStateAddress;
+use chain_core::state::tendermint::{BlockHeight, TendermintValidatorPubKey};
+use chain_core::state::{ChainState, RewardsPoolState};
+use chain_core::tx::data::input::TxoPointer;
+use chain_storage::jellyfish::{get_with_proof, Version};
+use chain_storage::lookup_input;
+
+/// Path prefix of the typed query service methods
+pub const QUERY_SERVICE_PATH: &str = "/chain.abci.query.v1.Query/";
+
+const CODE_NOT_FOUND: u32 = 1;
+const CODE_INVALID_REQUEST: u32 = 4;
+
+/// `UtxoStatusResponse.Status` values
+const UTXO_UNKNOWN: i32 = 0;
+const UTXO_UNSPENT: i32 = 1;
+const UTXO_SPENT: i32 = 2;
+
+/// response code and log of a failed query
+type QueryError = (u32, String);
+
+fn invalid_request(log: &str) -> QueryError {
+    (CODE_INVALID_REQUEST, log.to_owned())
+}
+
+fn not_found(log: &str) -> QueryError {
+    (CODE_NOT_FOUND, log.to_owned())
+}
+
+/// Fields of a request message by the field number
+/// (requests only have scalar and bytes fields)
+#[derive(Debug, Default)]
+struct RequestFields {
+    varints: BTreeMap<u32, u64>,
+    bytes: BTreeMap<u32, Vec<u8>>,
+}
+
+impl RequestFields {
+    fn decode(data: &[u8]) -> ProtobufResult<Self> {
+        let mut fields = RequestFields::default();
+        let mut is = CodedInputStream::from_bytes(data);
+        while !is.eof()? {
+            let (field, wire_type) = is.read_tag_unpack()?;
+            match wire_type {
+                WireType::WireTypeVarint => {
+                    fields.varints.insert(field, is.read_raw_varint64()?);
+                }
+                WireType::WireTypeLengthDelimited => {
+                    fields.bytes.insert(field, is.read_bytes()?);
+                }
+                _ => is.skip_field(wire_type)?,
+            }
+        }
+        Ok(fields)
+    }
+
+    fn varint(&self, field: u32) -> u64 {
+        self.varints.get(&field).copied().unwrap_or_default()
+    }
+
+    fn bytes(&self, field: u32) -> &[u8] {
+        self.bytes
+            .get(&field)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    fn address(&self, field: u32) -> Result<StakedStateAddress, QueryError> {
+        StakedStateAddress::try_from(self.bytes(field))
+            .map_err(|_| invalid_request("invalid staking address"))
+    }
+}
+
+fn encode_message(write: impl FnOnce(&mut CodedOutputStream<'_>) -> ProtobufResult<()>) -> Vec<u8> {
+    let mut buf = Vec::new();
+    {
+        let mut os = CodedOutputStream::vec(&mut buf);
+        write(&mut os)
+            .and_then(|_| os.flush())
+            .expect("protobuf encoding into a vector");
+    }
+    buf
+}
+
+impl<T: EnclaveProxy + 'static> ChainNodeApp<T> {
+    /// Responds to the typed query service requests (the method is the path without `QUERY_SERVICE_PATH`)
+    pub(super) fn query_service_handler(
+        &self,
+        method: &str,
+        data: &[u8],
+        resp: &mut ResponseQuery,
+    ) {
+        let result = RequestFields::decode(data)
+            .map_err(|_| invalid_request("invalid request message"))
+            .and_then(|request| match method {
+                "StakedState" => self.query_staked_state(&request),
+                "UtxoStatus" => self.query_utxo_status(&request),
+                "CouncilNodes" => self.query_council_nodes(),
+                "RewardsPool" => self.query_rewards_pool(&request),
+                "AccountProof" => self.query_account_proof(&request),
+                _ => Err(invalid_request("unknown query service method")),
+            });
+        match result {
+            Ok(value) => {
+                resp.value = value;
+            }
+            Err((code, log)) => {
+                resp.code = code;
+                resp.log += &log;
+            }
+        }
+    }
+
+    /// the requested height (0 = the last committed block)
+    fn query_height(&self, height: u64) -> Result<BlockHeight, QueryError> {
+        let last_height = self
+            .last_state
+            .as_ref()
+            .ok_or_else(|| not_found("node not correctly restored / initialized"))?
+            .last_block_height;
+        match height {
+            0 => Ok(last_height),
+            height if height > last_height.value() => Err(not_found("block not yet committed")),
+            height => Ok(BlockHeight::new(height)),
+        }
+    }
+
+    fn staking_version_at(&self, height: BlockHeight) -> Result<Version, QueryError> {
+        match &self.last_state {
+            Some(state) if state.last_block_height == height => Ok(state.staking_version),
+            _ => self
+                .storage
+                .get_historical_staking_version(height)
+                .ok_or_else(|| not_found("staking version not found")),
+        }
+    }
+
+    fn query_staked_state(&self, request: &RequestFields) -> Result<Vec<u8>, QueryError> {
+        let address = request.address(1)?;
+        let version = self.staking_version_at(self.query_height(request.varint(2))?)?;
+        let (staking, _proof) = get_with_proof(&self.storage, version, &address);
+        Ok(encode_message(|os| {
+            os.write_bool(1, staking.is_some())?;
+            os.write_string(2, &address.to_string())?;
+            if let Some(staking) = staking {
+                os.write_uint64(3, staking.nonce)?;
+                os.write_uint64(4, staking.bonded.into())?;
+                os.write_uint64(5, staking.unbonded.into())?;
+                os.write_uint64(6, staking.unbonded_from)?;
+                os.write_bool(7, staking.node_meta.is_some())?;
+                os.write_bool(8, staking.is_jailed())?;
+                os.write_bytes(9, &staking.encode())?;
+            }
+            Ok(())
+        }))
+    }
+
+    fn query_utxo_status(&self, request: &RequestFields) -> Result<Vec<u8>, QueryError> {
+        let txid = request.bytes(1);
+        if txid.len() != 32 {
+            return Err(invalid_request("invalid txid length"));
+        }
+        let mut id = H256::default();
+        id.copy_from_slice(txid);
+        let index = request.varint(2) as usize;
+        let status = match lookup_input(&self.storage, &TxoPointer::new(id, index)) {
+            Some(true) => UTXO_SPENT,
+            Some(false) => UTXO_UNSPENT,
+            None => UTXO_UNKNOWN,
+        };
+        Ok(encode_message(|os| os.write_enum(1, status)))
+    }
+
+    fn query_council_nodes(&self) -> Result<Vec<u8>, QueryError> {
+        let council_nodes = self
+            .last_state
+            .as_ref()
+            .ok_or_else(|| not_found("node not correctly restored / initialized"))?
+            .staking_table
+            .list_council_nodes(&self.staking_getter_committed());
+        Ok(encode_message(|os| {
+            for node in council_nodes.iter() {
+                let TendermintValidatorPubKey::Ed25519(consensus_pubkey) = &node.tendermint_pubkey;
+                let encoded = encode_message(|os| {
+                    os.write_string(1, &node.name)?;
+                    os.write_string(2, &node.staking_address.to_string())?;
+                    os.write_uint64(3, node.voting_power.into())?;
+                    if let Some(security_contact) = &node.security_contact {
+                        os.write_string(4, security_contact)?;
+                    }
+                    os.write_bytes(5, consensus_pubkey)
+                });
+                os.write_bytes(1, &encoded)?;
+            }
+            Ok(())
+        }))
+    }
+
+    fn query_rewards_pool(&self, request: &RequestFields) -> Result<Vec<u8>, QueryError> {
+        let height = self.query_height(request.varint(1))?;
+        let rewards_pool: RewardsPoolState = match &self.last_state {
+            Some(state) if state.last_block_height == height => {
+                state.top_level.rewards_pool.clone()
+            }
+            _ => {
+                let value = self.storage.get_historical_state(height).ok_or_else(|| {
+                    not_found(
+                        "state not found (state history is only stored with tx query address set)",
+                    )
+                })?;
+                ChainState::decode(&mut value.as_slice())
+                    .map_err(|_| not_found("state decode failed"))?
+                    .rewards_pool
+            }
+        };
+        Ok(encode_message(|os| {
+            os.write_uint64(1, height.value())?;
+            os.write_uint64(2, rewards_pool.period_bonus.into())?;
+            os.write_uint64(3, rewards_pool.last_block_height.value())?;
+            os.write_uint64(4, rewards_pool.last_distribution_time)?;
+            os.write_uint64(5, rewards_pool.minted.into())?;
+            os.write_uint64(6, rewards_pool.tau)
+        }))
+    }
+
+    fn query_account_proof(&self, request: &RequestFields) -> Result<Vec<u8>, QueryError> {
+        let address = request.address(1)?;
+        let height = self.query_height(request.varint(2))?;
+        let version = self.staking_version_at(height)?;
+        let app_hash = self
+            .storage
+            .get_historical_app_hash(height)
+            .ok_or_else(|| not_found("app hash not found"))?;
+        let (staking, proof) = get_with_proof(&self.storage, version, &address);
+        Ok(encode_message(|os| {
+            os.write_uint64(1, height.value())?;
+            os.write_bytes(2, &app_hash)?;
+            if let Some(staking) = staking {
+                os.write_bytes(3, &staking.encode())?;
+            }
+            os.write_bytes(4, &proof.encode())
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_request_fields_decoding() {
+        let data = encode_message(|os| {
+            os.write_bytes(1, &[0x11; 20])?;
+            os.write_string(3, "ignored")?;
+            os.write_fixed32(4, 7)?;
+            os.write_uint64(2, 10)
+        });
+        let request = RequestFields::decode(&data).unwrap();
+        assert_eq!(10, request.varint(2));
+        assert_eq!(0, request.varint(5));
+        assert!(request.address(1).is_ok());
+        assert_eq!(CODE_INVALID_REQUEST, request.address(3).unwrap_err().0);
+        assert!(request.bytes(6).is_empty());
+        assert!(RequestFields::decode(&[0x0a, 0x05, 0x01]).is_err());
+    }
+}
@@ -0,0 +1,425 @@
+//! Node status and health endpoint for monitoring (the `health` section of the configuration).
+//!
+//! A plain HTTP server (on its own thread, serving one request at a time) with:
+//! - `GET /status`: the status of the node as JSON (last committed block, app hash,
+//!   account trie root, enclave, storage size per column and sync lag versus Tendermint)
+//! - `GET /health`: the same JSON with `200 OK` if the node is healthy
+//!   or `503 Service Unavailable` (with the problems) otherwise
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, RwLock};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use kvdb::KeyValueDB;
+use serde::{Deserialize, Serialize};
+
+use super::ChainNodeState;
+use chain_storage::tuning::COLUMN_NAMES;
+
+/// Timeout of the Tendermint RPC requests and of the reads/writes of the health connections
+const IO_TIMEOUT: Duration = Duration::from_secs(5);
+/// Maximum size of a request (only the request line is used)
+const MAX_REQUEST_SIZE: usize = 8 * 1024;
+
+/// The `health` section of the configuration file
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct HealthConfig {
+    /// listen address of the endpoint, e.g. "127.0.0.1:26660" (disabled if not set)
+    pub listen_address: Option<String>,
+    /// address of the Tendermint RPC (for the sync lag)
+    pub tendermint_rpc: String,
+    /// maximum number of blocks the application can be behind Tendermint while healthy
+    pub max_sync_lag: u64,
+    /// the storage sizes (computed by iterating over all the stored values)
+    /// are refreshed at most every `storage_stats_interval` seconds
+    pub storage_stats_interval: u64,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            listen_address: None,
+            tendermint_rpc: "127.0.0.1:26657".to_owned(),
+            max_sync_lag: 5,
+            storage_stats_interval: 300,
+        }
+    }
+}
+
+/// Last committed block
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct CommittedStatus {
+    pub height: u64,
+    pub app_hash: String,
+    pub account_root: String,
+    pub block_time: u64,
+}
+
+/// Transaction enclaves used by the node
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct EnclaveStatus {
+    /// "sgx" or "mock" (development builds)
+    pub mode: &'static str,
+    /// the biggest enclave security version number seen in the key packages
+    pub isv_svn: u16,
+    /// address of the tx query enclave supplied to clients (if any)
+    pub tx_query_address: Option<String>,
+}
+
+#[derive(Debug, Default)]
+struct NodeStatus {
+    committed: Option<CommittedStatus>,
+    enclave: Option<EnclaveStatus>,
+}
+
+/// Status of the node shared by the application (updated at every commit)
+/// and the health endpoint
+#[derive(Debug, Default, Clone)]
+pub struct NodeStatusHandle(Arc<RwLock<NodeStatus>>);
+
+impl NodeStatusHandle {
+    /// Records the committed state
+    pub fn on_commit(&self, state: &ChainNodeState, tx_query_address: Option<&String>) {
+        let mode = if cfg!(any(
+            feature = "mock-enclave",
+            not(feature = "edp"),
+            not(target_os = "linux")
+        )) {
+            "mock"
+        } else {
+            "sgx"
+        };
+        let mut status = self.0.write().expect("node status lock");
+        status.committed = Some(CommittedStatus {
+            height: state.last_block_height.value(),
+            app_hash: hex::encode(state.last_apphash),
+            account_root: hex::encode(state.top_level.account_root),
+            block_time: state.block_time,
+        });
+        status.enclave = Some(EnclaveStatus {
+            mode,
+            isv_svn: state.enclave_isv_svn,
+            tx_query_address: tx_query_address.cloned(),
+        });
+    }
+
+    fn snapshot(&self) -> (Option<CommittedStatus>, Option<EnclaveStatus>) {
+        let status = self.0.read().expect("node status lock");
+        (status.committed.clone(), status.enclave.clone())
+    }
+}
+
+/// Number of values and their total size (keys included) in a storage column
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ColumnSize {
+    pub column: &'static str,
+    pub values: u64,
+    pub bytes: u64,
+}
+
+/// Latest block of Tendermint and the number of blocks the application is behind
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct TendermintSyncStatus {
+    pub latest_height: u64,
+    pub catching_up: bool,
+    pub sync_lag: u64,
+}
+
+#[derive(Serialize, Debug)]
+struct HealthReport {
+    healthy: bool,
+    problems: Vec<String>,
+    committed: Option<CommittedStatus>,
+    enclave: Option<EnclaveStatus>,
+    tendermint: Option<TendermintSyncStatus>,
+    storage: Vec<ColumnSize>,
+}
+
+/// Parses the latest block height and the `catching_up` flag of a Tendermint `/status` response
+fn parse_tendermint_status(body: &str) -> Result<(u64, bool), String> {
+    let value: serde_json::Value = serde_json::from_str(body).map_err(|e| e.to_string())?;
+    let sync_info = &value["result"]["sync_info"];
+    let latest_height = sync_info["latest_block_height"]
+        .as_str()
+        .and_then(|height| height.parse().ok())
+        .ok_or_else(|| "no latest block height in Tendermint status".to_owned())?;
+    let catching_up = sync_info["catching_up"].as_bool().unwrap_or(false);
+    Ok((latest_height, catching_up))
+}
+
+fn query_tendermint_status(address: &str) -> Result<(u64, bool), String> {
+    let socket_address = address
+        .to_socket_addrs()
+        .map_err(|e| e.to_string())?
+        .next()
+        .ok_or_else(|| format!("invalid Tendermint RPC address: {}", address))?;
+    let mut stream =
+        TcpStream::connect_timeout(&socket_address, IO_TIMEOUT).map_err(|e| e.to_string())?;
+    stream
+        .set_read_timeout(Some(IO_TIMEOUT))
+        .map_err(|e| e.to_string())?;
+    write!(
+        stream,
+        "GET /status HTTP/1.0\r\nHost: {}\r\nAccept: application/json\r\n\r\n",
+        address
+    )
+    .map_err(|e| e.to_string())?;
+    let mut response = String::new();
+    stream
+        .read_to_string(&mut response)
+        .map_err(|e| e.to_string())?;
+    let body = response
+        .splitn(2, "\r\n\r\n")
+        .nth(1)
+        .ok_or_else(|| "invalid Tendermint RPC response".to_owned())?;
+    parse_tendermint_status(body)
+}
+
+/// Sync status versus Tendermint and the problems making the node unhealthy
+fn check_health(
+    committed: Option<&CommittedStatus>,
+    tendermint: Result<(u64, bool), String>,
+    max_sync_lag: u64,
+) -> (Option<TendermintSyncStatus>, Vec<String>) {
+    let mut problems = Vec::new();
+    let committed_height = match committed {
+        Some(committed) => committed.height,
+        None => {
+            problems.push("no block committed yet".to_owned());
+            0
+        }
+    };
+    let tendermint = match tendermint {
+        Ok((latest_height, catching_up)) => {
+            let sync_lag = latest_height.saturating_sub(committed_height);
+            if catching_up {
+                problems.push("Tendermint is catching up".to_owned());
+            }
+            if sync_lag > max_sync_lag {
+                problems.push(format!(
+                    "application is {} blocks behind Tendermint (maximum {})",
+                    sync_lag, max_sync_lag
+                ));
+            }
+            Some(TendermintSyncStatus {
+                latest_height,
+                catching_up,
+                sync_lag,
+            })
+        }
+        Err(e) => {
+            problems.push(format!("Tendermint RPC unavailable: {}", e));
+            None
+        }
+    };
+    (tendermint, problems)
+}
+
+fn column_sizes(db: &dyn KeyValueDB) -> Vec<ColumnSize> {
+    COLUMN_NAMES
+        .iter()
+        .enumerate()
+        .map(|(col, column)| {
+            let (values, bytes) = db.iter(col as u32).fold((0, 0), |(values, bytes), (k, v)| {
+                (values + 1, bytes + (k.len() + v.len()) as u64)
+            });
+            ColumnSize {
+                column: *column,
+                values,
+                bytes,
+            }
+        })
+        .collect()
+}
+
+/// The health endpoint (if configured)
+pub struct HealthServer {
+    listen_address: String,
+    tendermint_rpc: String,
+    max_sync_lag: u64,
+    storage_stats_interval: Duration,
+    status: NodeStatusHandle,
+    db: Arc<dyn KeyValueDB>,
+    storage_sizes: Option<(Instant, Vec<ColumnSize>)>,
+}
+
+impl HealthServer {
+    /// None if the endpoint is not configured
+    pub fn from_config(
+        config: &HealthConfig,
+        status: NodeStatusHandle,
+        db: Arc<dyn KeyValueDB>,
+    ) -> Option<Self> {
+        config
+            .listen_address
+            .as_ref()
+            .map(|listen_address| HealthServer {
+                listen_address: listen_address.clone(),
+                tendermint_rpc: config.tendermint_rpc.clone(),
+                max_sync_lag: config.max_sync_lag,
+                storage_stats_interval: Duration::from_secs(config.storage_stats_interval),
+                status,
+                db,
+                storage_sizes: None,
+            })
+    }
+
+    /// Binds the listen address and serves the requests on a new thread
+    pub fn spawn(mut self) -> io::Result<JoinHandle<()>> {
+        let listen_address = self.listen_address.clone();
+        spawn_http_server("health", &listen_address, move |path| match path {
+            "/status" => HttpResponse::json("200 OK", self.report_json().1),
+            "/health" => match self.report_json() {
+                (true, body) => HttpResponse::json("200 OK", body),
+                (false, body) => HttpResponse::json("503 Service Unavailable", body),
+            },
+            _ => HttpResponse::json("404 Not Found", "{}".to_owned()),
+        })
+    }
+
+    fn report_json(&mut self) -> (bool, String) {
+        let (committed, enclave) = self.status.snapshot();
+        let (tendermint, problems) = check_health(
+            committed.as_ref(),
+            query_tendermint_status(&self.tendermint_rpc),
+            self.max_sync_lag,
+        );
+        let report = HealthReport {
+            healthy: problems.is_empty(),
+            problems,
+            committed,
+            enclave,
+            tendermint,
+            storage: self.storage_sizes(),
+        };
+        let body = serde_json::to_string(&report).expect("serialize health report");
+        (report.healthy, body)
+    }
+
+    fn storage_sizes(&mut self) -> Vec<ColumnSize> {
+        match &self.storage_sizes {
+            Some((computed_at, sizes)) if computed_at.elapsed() < self.storage_stats_interval => {
+                sizes.clone()
+            }
+            _ => {
+                let sizes = column_sizes(&*self.db);
+                self.storage_sizes = Some((Instant::now(), sizes.clone()));
+                sizes
+            }
+        }
+    }
+}
+
+/// Response of the monitoring endpoints
+pub(super) struct HttpResponse {
+    pub status: &'static str,
+    pub content_type: &'static str,
+    pub body: String,
+}
+
+impl HttpResponse {
+    pub fn json(status: &'static str, body: String) -> Self {
+        HttpResponse {
+            status,
+            content_type: "application/json",
+            body,
+        }
+    }
+}
+
+/// Binds the listen address and serves the `GET` requests of the monitoring endpoints
+/// (`handler` maps the request path to the response) one at a time on a new thread
+pub(super) fn spawn_http_server<F>(
+    name: &str,
+    listen_address: &str,
+    mut handler: F,
+) -> io::Result<JoinHandle<()>>
+where
+    F: FnMut(&str) -> HttpResponse + Send + 'static,
+{
+    let listener = TcpListener::bind(listen_address)?;
+    log::info!("{} endpoint listening on {}", name, listen_address);
+    let endpoint = name.to_owned();
+    thread::Builder::new().name(name.to_owned()).spawn(move || {
+        for stream in listener.incoming() {
+            let result = stream.and_then(|stream| handle_http(stream, &mut handler));
+            if let Err(e) = result {
+                log::warn!("{} endpoint connection failed: {}", endpoint, e);
+            }
+        }
+    })
+}
+
+fn handle_http<F>(mut stream: TcpStream, handler: &mut F) -> io::Result<()>
+where
+    F: FnMut(&str) -> HttpResponse,
+{
+    stream.set_read_timeout(Some(IO_TIMEOUT))?;
+    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+    let mut request = Vec::new();
+    let mut buffer = [0u8; 1024];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n")
+        && request.len() < MAX_REQUEST_SIZE
+    {
+        let read = stream.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        request.extend_from_slice(&buffer[..read]);
+    }
+    let request = String::from_utf8_lossy(&request);
+    let mut request_line = request.lines().next().unwrap_or("").split_whitespace();
+    let response = match (request_line.next(), request_line.next()) {
+        (Some("GET"), Some(path)) => handler(path),
+        _ => HttpResponse::json("405 Method Not Allowed", "{}".to_owned()),
+    };
+    write!(
+        stream,
+        "HTTP/1.0 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n\r\n{}",
+        response.status,
+        response.content_type,
+        response.body.len(),
+        response.body
+    )?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_health_report() {
+        let body = r#"{"jsonrpc":"2.0","id":-1,"result":{"sync_info":{
+            "latest_block_hash":"AB","latest_block_height":"120","catching_up":false}}}"#;
+        assert_eq!(Ok((120, false)), parse_tendermint_status(body));
+        assert!(parse_tendermint_status(r#"{"result":{}}"#).is_err());
+
+        let committed = CommittedStatus {
+            height: 118,
+            app_hash: "00".to_owned(),
+            account_root: "00".to_owned(),
+            block_time: 0,
+        };
+        let (tendermint, problems) = check_health(Some(&committed), Ok((120, false)), 5);
+        assert!(problems.is_empty());
+        assert_eq!(2, tendermint.unwrap().sync_lag);
+
+        let (_, problems) = check_health(Some(&committed), Ok((130, true)), 5);
+        assert_eq!(2, problems.len());
+        let (tendermint, problems) = check_health(None, Err("refused".to_owned()), 5);
+        assert!(tendermint.is_none());
+        assert_eq!(2, problems.len());
+
+        let db = kvdb_memorydb::create(COLUMN_NAMES.len() as u32);
+        let mut tx = db.transaction();
+        tx.put(1, b"key", b"value");
+        db.write(tx).unwrap();
+        let sizes = column_sizes(&db);
+        assert_eq!(COLUMN_NAMES.len(), sizes.len());
+        assert_eq!((1, 8), (sizes[1].values, sizes[1].bytes));
+        assert_eq!(0, sizes[0].values);
+    }
+}
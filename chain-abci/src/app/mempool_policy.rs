@@ -0,0 +1,283 @@
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+
+use serde::Serialize;
+
+use crate::storage::TxEnclaveAction;
+use crate::tx_error::MempoolPolicyError;
+use chain_core::init::coin::Coin;
+use chain_core::state::account::StakedStateAddress;
+use chain_core::tx::fee::Fee;
+use chain_core::tx::TxPublicAux;
+
+/// Minimal fee of the enclave transactions accepted in the mempool, in percent of the consensus
+/// minimal fee (e.g. 150 requires 1.5 times the consensus minimal fee)
+pub const MEMPOOL_MIN_FEE_MULTIPLIER_ENV: &str = "CRYPTO_CHAIN_MEMPOOL_MIN_FEE_MULTIPLIER";
+/// Maximal number of transactions per staking address accepted in the mempool between
+/// two blocks (0 = unlimited)
+pub const MEMPOOL_ADDRESS_RATE_LIMIT_ENV: &str = "CRYPTO_CHAIN_MEMPOOL_ADDRESS_RATE_LIMIT";
+
+/// the consensus minimal fee
+const DEFAULT_MIN_FEE_MULTIPLIER: u64 = 100;
+/// Maximal number of zero-fee upgrade signals per staking address between two blocks
+/// (whatever the configured rate limit)
+const UPGRADE_SIGNAL_RATE_LIMIT: u64 = 1;
+
+/// Node-local policy of the CheckTx connection, protecting the mempool of the node from spam.
+/// It's not part of the consensus: DeliverTx only applies the consensus rules, so the
+/// transactions rejected by the policy of this node are still valid in the blocks.
+///
+/// * the minimal fee multiplier only applies to the enclave transactions (the fee of the
+///   public transactions is the consensus minimal fee)
+/// * the rate limit counts the transactions of the staking addresses (deposit, withdraw and
+///   public transactions) accepted since the last block, including the re-checked ones
+/// * the zero-fee `UpgradeSignalTx` is always limited to one per council node between two blocks
+///   (`NetworkParamsUpdateTx` and `AccountFreezeTx` don't need a limit: the votes are bounded
+///   by the council size and a decision is only valid for the current version of its state)
+///
+/// The configuration and the rejection counters are available via the "mempool-policy"
+/// ABCI query path.
+#[derive(Debug, Serialize)]
+pub struct MempoolPolicy {
+    min_fee_multiplier: u64,
+    address_rate_limit: u64,
+    /// number of rejected transactions per error variant
+    rejected: BTreeMap<String, u64>,
+    /// number of accepted transactions per staking address since the last block
+    #[serde(skip)]
+    accepted: HashMap<StakedStateAddress, u64>,
+}
+
+impl Default for MempoolPolicy {
+    fn default() -> Self {
+        MempoolPolicy::new(DEFAULT_MIN_FEE_MULTIPLIER, 0)
+    }
+}
+
+impl MempoolPolicy {
+    /// min_fee_multiplier: percent of the consensus minimal fee (at least 100)
+    /// address_rate_limit: transactions per staking address between two blocks (0 = unlimited)
+    pub fn new(min_fee_multiplier: u64, address_rate_limit: u64) -> Self {
+        MempoolPolicy {
+            min_fee_multiplier: min_fee_multiplier.max(DEFAULT_MIN_FEE_MULTIPLIER),
+            address_rate_limit,
+            rejected: BTreeMap::new(),
+            accepted: HashMap::new(),
+        }
+    }
+
+    /// Reads the configuration from `CRYPTO_CHAIN_MEMPOOL_*` environment variables
+    pub fn from_env() -> Self {
+        let min_fee_multiplier = env::var(MEMPOOL_MIN_FEE_MULTIPLIER_ENV)
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_MIN_FEE_MULTIPLIER);
+        let address_rate_limit = env::var(MEMPOOL_ADDRESS_RATE_LIMIT_ENV)
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0);
+        if min_fee_multiplier > DEFAULT_MIN_FEE_MULTIPLIER || address_rate_limit > 0 {
+            log::info!(
+                "local mempool policy: minimal fee {}%, {} transactions per address",
+                min_fee_multiplier,
+                address_rate_limit
+            );
+        }
+        MempoolPolicy::new(min_fee_multiplier, address_rate_limit)
+    }
+
+    /// Local minimal fee for the given consensus minimal fee
+    pub fn min_fee(&self, consensus_min_fee: Fee) -> Coin {
+        let min_fee = u128::from(u64::from(consensus_min_fee.to_coin()))
+            * u128::from(self.min_fee_multiplier)
+            / 100;
+        Coin::new(min_fee.min(u128::from(u64::from(Coin::max()))) as u64)
+            .expect("minimal fee is capped by the maximal coin")
+    }
+
+    /// Checks the verified enclave transaction (before it's applied to the mempool state)
+    pub fn check_enclave_tx(
+        &self,
+        action: &TxEnclaveAction,
+        consensus_min_fee: Fee,
+    ) -> Result<(), MempoolPolicyError> {
+        let minimal = self.min_fee(consensus_min_fee);
+        let paid = action.fee().to_coin();
+        if paid < minimal {
+            return Err(MempoolPolicyError::InsufficientFee { paid, minimal });
+        }
+        match action.staking_address() {
+            Some(address) => self.check_address(&address),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks the public transaction (before it's processed)
+    pub fn check_public_tx(&self, tx: &TxPublicAux) -> Result<(), MempoolPolicyError> {
+        match tx {
+            TxPublicAux::UpgradeSignalTx(tx, _) => {
+                self.check_address_limit(&tx.address, UPGRADE_SIGNAL_RATE_LIMIT)
+            }
+            _ => match public_tx_address(tx) {
+                Some(address) => self.check_address(&address),
+                None => Ok(()),
+            },
+        }
+    }
+
+    fn check_address(&self, address: &StakedStateAddress) -> Result<(), MempoolPolicyError> {
+        if self.address_rate_limit > 0 {
+            self.check_address_limit(address, self.address_rate_limit)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn check_address_limit(
+        &self,
+        address: &StakedStateAddress,
+        limit: u64,
+    ) -> Result<(), MempoolPolicyError> {
+        let accepted = self.accepted.get(address).copied().unwrap_or(0);
+        if accepted >= limit {
+            return Err(MempoolPolicyError::AddressRateLimited(*address));
+        }
+        Ok(())
+    }
+
+    /// Counts the transaction accepted in the mempool
+    pub fn record_accepted(&mut self, address: Option<StakedStateAddress>) {
+        if let Some(address) = address {
+            *self.accepted.entry(address).or_insert(0) += 1;
+        }
+    }
+
+    /// Counts the transaction rejected by the policy
+    pub fn record_rejected(&mut self, error: &MempoolPolicyError) {
+        let kind = match error {
+            MempoolPolicyError::InsufficientFee { .. } => "InsufficientFee",
+            MempoolPolicyError::AddressRateLimited(_) => "AddressRateLimited",
+        };
+        *self.rejected.entry(kind.to_owned()).or_insert(0) += 1;
+    }
+
+    /// Number of transactions rejected by the policy
+    pub fn rejected_count(&self) -> u64 {
+        self.rejected.values().sum()
+    }
+
+    /// Resets the rate limit counters
+    pub fn new_block(&mut self) {
+        self.accepted.clear();
+    }
+
+    /// JSON dump of the configuration and the rejection counters
+    pub fn dump(&self) -> String {
+        serde_json::to_string(self).expect("serialize mempool policy")
+    }
+}
+
+/// Staking address of the public transaction (if it's signed by a single one)
+fn public_tx_address(tx: &TxPublicAux) -> Option<StakedStateAddress> {
+    match tx {
+        TxPublicAux::UnbondStakeTx(tx, _) => Some(tx.from_staked_account),
+        TxPublicAux::UnjailTx(tx, _) => Some(tx.address),
+        TxPublicAux::NodeJoinTx(tx, _) => Some(tx.address),
+        TxPublicAux::UpgradeSignalTx(tx, _) => Some(tx.address),
+        TxPublicAux::DataAnchorTx(tx, _) => Some(tx.from_staked_account),
+        TxPublicAux::NetworkParamsUpdateTx(..) | TxPublicAux::AccountFreezeTx(..) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chain_core::init::address::RedeemAddress;
+    use chain_core::state::account::{StakedStateOpAttributes, StakedStateOpWitness};
+    use chain_core::state::governance::UpgradeSignalTx;
+    use secp256k1::{key::SecretKey, Message};
+
+    fn address(id: u8) -> StakedStateAddress {
+        StakedStateAddress::BasicRedeem(RedeemAddress::from([id; 20]))
+    }
+
+    fn deposit(fee: u64, id: u8) -> TxEnclaveAction {
+        TxEnclaveAction::Deposit {
+            fee: Fee::new(Coin::new(fee).unwrap()),
+            spend_utxo: vec![],
+            deposit: (address(id), Coin::unit()),
+        }
+    }
+
+    #[test]
+    fn check_min_fee_multiplier() {
+        let consensus_min_fee = Fee::new(Coin::new(100).unwrap());
+        let policy = MempoolPolicy::new(150, 0);
+        assert_eq!(Coin::new(150).unwrap(), policy.min_fee(consensus_min_fee));
+        assert!(matches!(
+            policy.check_enclave_tx(&deposit(149, 1), consensus_min_fee),
+            Err(MempoolPolicyError::InsufficientFee { .. })
+        ));
+        assert!(policy
+            .check_enclave_tx(&deposit(150, 1), consensus_min_fee)
+            .is_ok());
+
+        // the local minimal fee can't be below the consensus one
+        let policy = MempoolPolicy::new(50, 0);
+        assert_eq!(Coin::new(100).unwrap(), policy.min_fee(consensus_min_fee));
+    }
+
+    #[test]
+    fn check_address_rate_limit() {
+        let consensus_min_fee = Fee::new(Coin::zero());
+        let mut policy = MempoolPolicy::new(100, 2);
+        for _ in 0..2 {
+            assert!(policy
+                .check_enclave_tx(&deposit(0, 1), consensus_min_fee)
+                .is_ok());
+            policy.record_accepted(Some(address(1)));
+        }
+        let error = policy
+            .check_enclave_tx(&deposit(0, 1), consensus_min_fee)
+            .unwrap_err();
+        assert!(matches!(error, MempoolPolicyError::AddressRateLimited(_)));
+        policy.record_rejected(&error);
+        assert_eq!(1, policy.rejected_count());
+        // other addresses aren't limited
+        assert!(policy
+            .check_enclave_tx(&deposit(0, 2), consensus_min_fee)
+            .is_ok());
+
+        policy.new_block();
+        assert!(policy
+            .check_enclave_tx(&deposit(0, 1), consensus_min_fee)
+            .is_ok());
+        assert!(policy.dump().contains("AddressRateLimited"));
+    }
+
+    #[test]
+    fn check_upgrade_signal_rate_limit() {
+        let secret_key = SecretKey::from_slice(&[0xcc; 32]).unwrap();
+        let message = Message::from_slice(&[0x11; 32]).unwrap();
+        let witness =
+            StakedStateOpWitness::new(secp256k1::SECP256K1.sign_recoverable(&message, &secret_key));
+        let signal = |id| {
+            TxPublicAux::UpgradeSignalTx(
+                UpgradeSignalTx::new(0, address(id), 2, StakedStateOpAttributes::new(0)),
+                witness.clone(),
+            )
+        };
+        // limited even if the configured rate limit is off
+        let mut policy = MempoolPolicy::new(100, 0);
+        assert!(policy.check_public_tx(&signal(1)).is_ok());
+        policy.record_accepted(Some(address(1)));
+        assert!(matches!(
+            policy.check_public_tx(&signal(1)),
+            Err(MempoolPolicyError::AddressRateLimited(_))
+        ));
+        assert!(policy.check_public_tx(&signal(2)).is_ok());
+
+        policy.new_block();
+        assert!(policy.check_public_tx(&signal(1)).is_ok());
+    }
+}
@@ -0,0 +1,315 @@
+//! Prometheus metrics of the ABCI application (the `metrics` section of the configuration),
+//! served in the text exposition format on `GET /metrics`:
+//! - `chain_abci_txs_total{connection,kind}`: accepted transactions per transaction kind
+//! - `chain_abci_tx_failures_total{connection,error}`: rejected transactions per error variant
+//! - `chain_abci_block_processing_seconds`: time from BeginBlock to the end of Commit
+//! - `chain_abci_enclave_request_seconds`: latency of the enclave requests
+//! - `chain_abci_db_write_batch_size`: number of key-value writes flushed at commit
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use super::health::{spawn_http_server, HttpResponse};
+use super::rejected_txs::TxOrigin;
+use super::tx_event::TxAttributes;
+use crate::enclave_bridge::EnclaveProxy;
+use crate::tx_error::TxError;
+use chain_core::tx::TxAux;
+use enclave_protocol::{IntraEnclaveRequest, IntraEnclaveResponse};
+
+/// Upper bounds (in seconds) of the block processing time buckets
+const BLOCK_SECONDS_BUCKETS: [f64; 9] = [0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+/// Upper bounds (in seconds) of the enclave request latency buckets
+const ENCLAVE_SECONDS_BUCKETS: [f64; 8] = [0.0005, 0.001, 0.0025, 0.005, 0.01, 0.05, 0.1, 0.5];
+/// Upper bounds (number of writes) of the write batch size buckets
+const BATCH_SIZE_BUCKETS: [f64; 8] = [10.0, 50.0, 100.0, 500.0, 1e3, 5e3, 1e4, 5e4];
+
+/// The `metrics` section of the configuration file
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct MetricsConfig {
+    /// listen address of the Prometheus endpoint, e.g. "127.0.0.1:26661" (disabled if not set)
+    pub listen_address: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct Histogram {
+    bounds: &'static [f64],
+    /// non-cumulative counts per bucket (`bounds` + the overflow bucket)
+    buckets: Vec<u64>,
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [f64]) -> Self {
+        Histogram {
+            bounds,
+            buckets: vec![0; bounds.len() + 1],
+            count: 0,
+            sum: 0.0,
+        }
+    }
+
+    fn observe(&mut self, value: f64) {
+        let bucket = self
+            .bounds
+            .iter()
+            .position(|bound| value <= *bound)
+            .unwrap_or_else(|| self.bounds.len());
+        self.buckets[bucket] += 1;
+        self.count += 1;
+        self.sum += value;
+    }
+
+    fn render(&self, out: &mut String, name: &str, help: &str) {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} histogram", name);
+        let mut cumulative = 0;
+        for (i, count) in self.buckets.iter().enumerate() {
+            cumulative += count;
+            let le = self
+                .bounds
+                .get(i)
+                .map(|bound| bound.to_string())
+                .unwrap_or_else(|| "+Inf".to_owned());
+            let _ = writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, le, cumulative);
+        }
+        let _ = writeln!(out, "{}_sum {}", name, self.sum);
+        let _ = writeln!(out, "{}_count {}", name, self.count);
+    }
+}
+
+#[derive(Debug)]
+struct Metrics {
+    /// (connection, transaction kind) -> accepted transactions
+    txs: BTreeMap<(&'static str, String), u64>,
+    /// (connection, error variant) -> rejected transactions
+    tx_failures: BTreeMap<(&'static str, String), u64>,
+    block_seconds: Histogram,
+    enclave_seconds: Histogram,
+    write_batch_size: Histogram,
+    /// start of the block being processed
+    block_started: Option<Instant>,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Metrics {
+            txs: BTreeMap::new(),
+            tx_failures: BTreeMap::new(),
+            block_seconds: Histogram::new(&BLOCK_SECONDS_BUCKETS),
+            enclave_seconds: Histogram::new(&ENCLAVE_SECONDS_BUCKETS),
+            write_batch_size: Histogram::new(&BATCH_SIZE_BUCKETS),
+            block_started: None,
+        }
+    }
+}
+
+fn connection_label(origin: TxOrigin) -> &'static str {
+    match origin {
+        TxOrigin::CheckTx => "check_tx",
+        TxOrigin::DeliverTx => "deliver_tx",
+    }
+}
+
+fn render_counter(
+    out: &mut String,
+    name: &str,
+    help: &str,
+    label: &str,
+    counters: &BTreeMap<(&'static str, String), u64>,
+) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} counter", name);
+    for ((connection, value), count) in counters.iter() {
+        let _ = writeln!(
+            out,
+            "{}{{connection=\"{}\",{}=\"{}\"}} {}",
+            name, connection, label, value, count
+        );
+    }
+}
+
+/// Metrics shared by the application, the enclave bridge and the metrics endpoint
+#[derive(Debug, Default, Clone)]
+pub struct AppMetrics(Arc<Mutex<Metrics>>);
+
+impl AppMetrics {
+    fn lock(&self) -> std::sync::MutexGuard<'_, Metrics> {
+        self.0.lock().expect("metrics lock")
+    }
+
+    /// Records an accepted transaction
+    pub fn record_tx(&self, origin: TxOrigin, txaux: &TxAux) {
+        let kind = TxAttributes::from(txaux).tx_type.to_string();
+        *self
+            .lock()
+            .txs
+            .entry((connection_label(origin), kind))
+            .or_default() += 1;
+    }
+
+    /// Records a rejected transaction
+    pub fn record_tx_failure(&self, origin: TxOrigin, error: &TxError) {
+        *self
+            .lock()
+            .tx_failures
+            .entry((connection_label(origin), error.kind()))
+            .or_default() += 1;
+    }
+
+    /// Starts measuring the processing time of a block (at BeginBlock)
+    pub fn begin_block(&self) {
+        self.lock().block_started = Some(Instant::now());
+    }
+
+    /// Records the number of flushed writes and the processing time of the block (at Commit)
+    pub fn commit(&self, write_batch_size: usize) {
+        let mut metrics = self.lock();
+        metrics.write_batch_size.observe(write_batch_size as f64);
+        if let Some(started) = metrics.block_started.take() {
+            metrics
+                .block_seconds
+                .observe(started.elapsed().as_secs_f64());
+        }
+    }
+
+    fn record_enclave_request(&self, elapsed: Duration) {
+        self.lock().enclave_seconds.observe(elapsed.as_secs_f64());
+    }
+
+    /// The metrics in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let metrics = self.lock();
+        let mut out = String::new();
+        render_counter(
+            &mut out,
+            "chain_abci_txs_total",
+            "Accepted transactions by kind.",
+            "kind",
+            &metrics.txs,
+        );
+        render_counter(
+            &mut out,
+            "chain_abci_tx_failures_total",
+            "Rejected transactions by error variant.",
+            "error",
+            &metrics.tx_failures,
+        );
+        metrics.block_seconds.render(
+            &mut out,
+            "chain_abci_block_processing_seconds",
+            "Time from BeginBlock to the end of Commit.",
+        );
+        metrics.enclave_seconds.render(
+            &mut out,
+            "chain_abci_enclave_request_seconds",
+            "Latency of the enclave requests.",
+        );
+        metrics.write_batch_size.render(
+            &mut out,
+            "chain_abci_db_write_batch_size",
+            "Number of key-value writes flushed at commit.",
+        );
+        out
+    }
+
+    /// Serves the metrics on `GET /metrics` (if configured)
+    pub fn spawn_server(&self, config: &MetricsConfig) -> Option<io::Result<JoinHandle<()>>> {
+        let metrics = self.clone();
+        config.listen_address.as_ref().map(|listen_address| {
+            spawn_http_server("metrics", listen_address, move |path| match path {
+                "/metrics" => HttpResponse {
+                    status: "200 OK",
+                    content_type: "text/plain; version=0.0.4",
+                    body: metrics.render(),
+                },
+                _ => HttpResponse::json("404 Not Found", "{}".to_owned()),
+            })
+        })
+    }
+}
+
+/// Wraps an enclave bridge to measure the latency of its requests
+pub struct MeteredEnclave<T: EnclaveProxy> {
+    inner: T,
+    metrics: AppMetrics,
+}
+
+impl<T: EnclaveProxy> MeteredEnclave<T> {
+    pub fn new(inner: T, metrics: AppMetrics) -> Self {
+        MeteredEnclave { inner, metrics }
+    }
+}
+
+impl<T: EnclaveProxy> EnclaveProxy for MeteredEnclave<T> {
+    fn check_chain(&mut self, network_id: u8) -> Result<(), ()> {
+        self.inner.check_chain(network_id)
+    }
+
+    fn process_request(&mut self, request: IntraEnclaveRequest) -> IntraEnclaveResponse {
+        let started = Instant::now();
+        let response = self.inner.process_request(request);
+        self.metrics.record_enclave_request(started.elapsed());
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use secp256k1::recovery::{RecoverableSignature, RecoveryId};
+
+    use crate::tx_error::PublicTxError;
+    use chain_core::init::address::RedeemAddress;
+    use chain_core::state::account::{
+        StakedStateAddress, StakedStateOpAttributes, StakedStateOpWitness, UnjailTx,
+    };
+    use chain_core::tx::TxPublicAux;
+
+    #[test]
+    fn check_render_metrics() {
+        let metrics = AppMetrics::default();
+        let tx = UnjailTx::new(
+            0,
+            StakedStateAddress::BasicRedeem(RedeemAddress::default()),
+            StakedStateOpAttributes::new(0),
+        );
+        let witness = StakedStateOpWitness::BasicRedeem(
+            RecoverableSignature::from_compact(&[0x01; 64], RecoveryId::from_i32(1).unwrap())
+                .unwrap(),
+        );
+        let txaux = TxAux::PublicTx(TxPublicAux::UnjailTx(tx, witness));
+        metrics.record_tx(TxOrigin::CheckTx, &txaux);
+        metrics.record_tx(TxOrigin::CheckTx, &txaux);
+        metrics.record_tx_failure(
+            TxOrigin::DeliverTx,
+            &TxError::Public(PublicTxError::IncorrectNonce),
+        );
+        metrics.begin_block();
+        metrics.commit(120);
+        metrics.commit(3);
+
+        let rendered = metrics.render();
+        assert!(
+            rendered.contains("chain_abci_txs_total{connection=\"check_tx\",kind=\"unjail\"} 2\n")
+        );
+        assert!(rendered.contains(concat!(
+            "chain_abci_tx_failures_total",
+            "{connection=\"deliver_tx\",error=\"Public::IncorrectNonce\"} 1\n"
+        )));
+        assert!(rendered.contains("chain_abci_block_processing_seconds_count 1\n"));
+        assert!(rendered.contains("chain_abci_db_write_batch_size_bucket{le=\"10\"} 1\n"));
+        assert!(rendered.contains("chain_abci_db_write_batch_size_bucket{le=\"500\"} 2\n"));
+        assert!(rendered.contains("chain_abci_db_write_batch_size_bucket{le=\"+Inf\"} 2\n"));
+        assert!(rendered.contains("chain_abci_db_write_batch_size_sum 123\n"));
+        assert!(rendered.contains("chain_abci_enclave_request_seconds_count 0\n"));
+    }
+}
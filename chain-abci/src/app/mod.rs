@@ -1,30 +1,81 @@
 #[macro_use]
 mod macros;
 
+mod account_freeze;
 mod app_init;
+mod backup;
+mod block_filters;
+mod block_stats;
+mod check_tx_cache;
 mod commit;
 mod end_block;
+mod grpc_query;
+mod health;
+mod mempool_policy;
+mod metrics;
+mod params_update;
+mod priority;
+mod pruning;
 mod query;
+mod rejected_txs;
 mod rewards;
+mod simulate_tx;
 mod staking_event;
+mod state_export;
+mod state_sync;
+mod storage_encryption;
+mod storage_metrics;
+mod storage_tuning;
+mod tx_event;
+mod upgrade;
 pub mod validate_tx;
+mod watch_list;
 
 use abci::Pair as KVPair;
 use abci::*;
-use log::info;
+use log::{info, warn};
 use std::convert::{TryFrom, TryInto};
 use std::env;
 
+pub use self::account_freeze::check_account_freeze;
 #[cfg(fuzzing)]
 pub use self::app_init::check_validators;
 pub use self::app_init::{
-    get_validator_key, init_app_hash, BufferType, ChainNodeApp, ChainNodeState,
+    compute_genesis_app_hash, get_validator_key, init_app_hash, BufferType, ChainNodeApp,
+    ChainNodeState,
 };
+pub use self::backup::{BackupConfig, BackupScheduler};
+pub use self::block_filters::{BlockFilters, MAX_BLOCK_FILTERS_RANGE};
+pub use self::block_stats::{
+    BlockStats, BlockStatsSummary, ValidatorBlockStats, MAX_BLOCK_STATS_RANGE,
+};
+pub use self::check_tx_cache::CheckTxCache;
+pub use self::grpc_query::QUERY_SERVICE_PATH;
+pub use self::health::{HealthConfig, HealthServer, NodeStatusHandle};
+pub use self::mempool_policy::MempoolPolicy;
+pub use self::metrics::{AppMetrics, MeteredEnclave, MetricsConfig};
+pub use self::params_update::{check_params_update, evidence_params_update, PendingParamsUpdate};
+pub use self::priority::{TxPriority, FEE_EXEMPT_PRIORITY};
+pub use self::pruning::{PruningMode, AGGRESSIVE_PRUNING_KEEP_BLOCKS, DEFAULT_PRUNING_KEEP_BLOCKS};
+pub use self::rejected_txs::{RejectedTxLog, TxOrigin};
+pub use self::state_export::{
+    export_state, exported_genesis_app_hash, exported_genesis_validators, store_exported_utxos,
+};
+pub use self::state_sync::{ApplyChunkResult, OfferSnapshotResult, SnapshotInfo, StateSync};
+pub use self::storage_encryption::StorageEncryptionConfig;
+pub use self::storage_metrics::SLOW_STORAGE_OP_ENV;
+pub use self::storage_tuning::StorageTuningConfig;
+pub use self::upgrade::{
+    check_tx_activation, check_upgrade_signal, is_tx_aux_version_active_at, ScheduledUpgrade,
+    UpgradeReadiness,
+};
+pub use self::watch_list::{AddressWatchList, WatchAlert};
 use crate::app::staking_event::StakingEvent;
+use crate::app::tx_event::generate_tx_events;
 use crate::app::validate_tx::ResponseWithCodeAndLog;
 use crate::enclave_bridge::EnclaveProxy;
 use crate::staking::RewardsDistribution;
-use crate::storage::{TxAction, TxEnclaveAction, TxPublicAction};
+use crate::tx_error::TxError;
 use chain_core::common::{TendermintEventKey, TendermintEventType, Timespec};
 use chain_core::init::coin::Coin;
 use chain_core::init::config::NetworkParameters;
@@ -95,13 +146,26 @@ impl<T: EnclaveProxy + 'static> abci::Application for ChainNodeApp<T> {
         info!("received checktx request");
         let mut resp = ResponseCheckTx::new();
         match self.process_tx(req, BufferType::Mempool) {
-            Ok(_) => {
+            Ok((txaux, tx_action)) => {
                 resp.set_code(0);
+                self.metrics.record_tx(TxOrigin::CheckTx, &txaux);
+                resp.events
+                    .push(TxPriority::new(req.tx.len(), &tx_action).to_event());
+                self.mempool_policy
+                    .record_accepted(tx_action.staking_address());
+            }
+            Err(TxError::MempoolPolicy(error)) => {
+                // not a consensus rule: a different code
+                resp.set_code(2);
+                resp.add_log(&format!("rejected by the local mempool policy: {}", error));
+                self.mempool_policy.record_rejected(&error);
+                self.metrics
+                    .record_tx_failure(TxOrigin::CheckTx, &TxError::MempoolPolicy(error));
             }
             Err(msg) => {
                 resp.set_code(1);
                 resp.add_log(&msg.to_string());
-                log::warn!("check tx failed: {}", msg);
+                self.record_rejected_tx(TxOrigin::CheckTx, &req.tx, &msg);
             }
         }
         resp
@@ -129,6 +193,7 @@ impl<T: EnclaveProxy + 'static> abci::Application for ChainNodeApp<T> {
             .expect("No block header in begin block request from tendermint");
         let block_height = abci_block_height(header.height).expect("invalid block height");
         let block_time = abci_timespec(&header.time).expect("invalid block time");
+        self.metrics.begin_block();
 
         let voters = if let Some(last_commit_info) = req.last_commit_info.as_ref() {
             // ignore the invalid items (logged)
@@ -153,6 +218,45 @@ impl<T: EnclaveProxy + 'static> abci::Application for ChainNodeApp<T> {
             .expect("executing begin block, but no app state stored (i.e. no initchain or recovery was executed)");
         last_state.block_time = block_time;
         last_state.block_height = block_height;
+        if let Some(change) = last_state.apply_due_params_update() {
+            info!(
+                "network parameters updated at height {}: {:?}",
+                block_height, change
+            );
+            if let Some(unbonding_period) = change.unbonding_period {
+                self.consensus_params_update = Some(evidence_params_update(
+                    self.storage.get_consensus_params().as_deref(),
+                    unbonding_period,
+                ));
+            }
+        }
+        if let Some(upgrade) = last_state.scheduled_upgrade.as_mut() {
+            if upgrade.checkpoint_due(block_height) {
+                let council_nodes = last_state
+                    .staking_table
+                    .list_council_nodes(&staking_getter!(self, last_state.staking_version));
+                if let Some(upgrade_height) = upgrade.check_readiness(&council_nodes) {
+                    warn!(
+                        "council nodes not ready for app version {}, upgrade delayed to height {}",
+                        upgrade.plan.app_version, upgrade_height
+                    );
+                }
+            }
+            if upgrade.plan.height == block_height {
+                info!(
+                    "reached the upgrade height of app version {}",
+                    upgrade.plan.app_version
+                );
+            }
+        }
+
+        let proposer =
+            TendermintValidatorAddress::try_from(header.proposer_address.as_slice()).ok();
+        let proposer_staking_address = proposer
+            .as_ref()
+            .and_then(|address| last_state.staking_table.lookup_address(address))
+            .copied();
+        self.block_stats = BlockStats::new(proposer, proposer_staking_address);
 
         // ignore the invalid items (logged)
         let evidences = req
@@ -237,6 +341,7 @@ impl<T: EnclaveProxy + 'static> abci::Application for ChainNodeApp<T> {
         }
 
         if let Some((distributed, minted)) = self.rewards_try_distribute() {
+            self.block_stats.rewards = distributed.clone();
             let events = generate_reward_events(distributed, minted);
             for event in events.iter() {
                 response.events.push(event.to_owned());
@@ -258,12 +363,14 @@ impl<T: EnclaveProxy + 'static> abci::Application for ChainNodeApp<T> {
                 let tx_events = generate_tx_events(&txaux, tx_action);
 
                 resp.set_code(0);
+                self.metrics.record_tx(TxOrigin::DeliverTx, &txaux);
 
                 for event in tx_events.iter() {
                     resp.events.push(event.to_owned());
                 }
 
                 self.delivered_txs.push(txaux);
+                self.block_stats.add_transaction(fee_amount);
 
                 if fee_amount > Coin::zero() {
                     let rewards_pool =
@@ -273,10 +380,15 @@ impl<T: EnclaveProxy + 'static> abci::Application for ChainNodeApp<T> {
                     self.rewards_pool_updated = true;
                 }
             }
+            Err(TxError::Enclave(chain_tx_validation::Error::IoError)) => {
+                // rejecting the transaction could diverge from the other nodes,
+                // so the node halts (and the block is replayed after a restart)
+                panic!("enclave unavailable while delivering a transaction");
+            }
             Err(msg) => {
                 resp.set_code(1);
                 resp.add_log(&msg.to_string());
-                log::error!("deliver tx failed: {}", msg);
+                self.record_rejected_tx(TxOrigin::DeliverTx, &req.tx, &msg);
             }
         }
         resp
@@ -301,6 +413,20 @@ impl<T: EnclaveProxy + 'static> abci::Application for ChainNodeApp<T> {
     }
 }
 
+impl<T: EnclaveProxy + 'static> ChainNodeApp<T> {
+    fn record_rejected_tx(&mut self, origin: TxOrigin, payload: &[u8], error: &TxError) {
+        self.metrics.record_tx_failure(origin, error);
+        let txid = TxAux::decode(&mut &payload[..])
+            .ok()
+            .map(|txaux| txaux.tx_id());
+        let height = self
+            .last_state
+            .as_ref()
+            .map_or(BlockHeight::genesis(), |state| state.last_block_height);
+        self.rejected_txs.record(origin, txid, error, height);
+    }
+}
+
 fn iter_votes(last_commit_info: &LastCommitInfo) -> impl Iterator<Item = &VoteInfo> {
     last_commit_info.votes.iter()
 }
@@ -362,63 +488,6 @@ fn generate_reward_events(distribution: RewardsDistribution, minted: Coin) -> Ve
     events
 }
 
-fn generate_tx_events(txaux: &TxAux, tx_action: TxAction) -> Vec<abci::Event> {
-    let mut events = Vec::new();
-
-    let mut valid_txs_event = Event::new();
-    valid_txs_event.field_type = TendermintEventType::ValidTransactions.to_string();
-
-    let mut fee_kvpair = KVPair::new();
-    let fee = tx_action.fee();
-    fee_kvpair.key = TendermintEventKey::Fee.into();
-    fee_kvpair.value = Vec::from(format!("{}", fee.to_coin()));
-    valid_txs_event.attributes.push(fee_kvpair);
-
-    let mut txid_kvpair = KVPair::new();
-    txid_kvpair.key = TendermintEventKey::TxId.into();
-    txid_kvpair.value = Vec::from(hex::encode(txaux.tx_id()).as_bytes());
-    valid_txs_event.attributes.push(txid_kvpair);
-
-    events.push(valid_txs_event);
-
-    let maybe_tx_staking_event = generate_tx_staking_change_event(tx_action);
-    if let Some(tx_staking_event) = maybe_tx_staking_event {
-        events.push(tx_staking_event);
-    }
-
-    events
-}
-
-fn generate_tx_staking_change_event(tx_action: TxAction) -> Option<abci::Event> {
-    match tx_action {
-        TxAction::Enclave(tx_enclave_action) => match tx_enclave_action {
-            TxEnclaveAction::Transfer { .. } => None,
-            TxEnclaveAction::Deposit { deposit, .. } => {
-                Some(StakingEvent::Deposit(&deposit.0, deposit.1).into())
-            }
-            TxEnclaveAction::Withdraw { withdraw, .. } => {
-                Some(StakingEvent::Withdraw(&withdraw.0, withdraw.1).into())
-            }
-        },
-        TxAction::Public(tx_public_action) => match tx_public_action {
-            TxPublicAction::Unbond {
-                unbond,
-                unbonded_from,
-                fee,
-                ..
-            } => Some(StakingEvent::Unbond(&unbond.0, unbond.1, unbonded_from, fee).into()),
-            TxPublicAction::NodeJoin {
-                address,
-                council_node,
-                ..
-            } => Some(StakingEvent::NodeJoin(&address, council_node).into()),
-            TxPublicAction::Unjail(staking_address) => {
-                Some(StakingEvent::Unjail(&staking_address).into())
-            }
-        },
-    }
-}
-
 pub fn sanity_check_enabled() -> bool {
     env::var("CRYPTO_CHAIN_ENABLE_SANITY_CHECKS") == Ok("1".to_owned())
 }
@@ -0,0 +1,313 @@
+use abci::{ConsensusParams, EvidenceParams};
+use parity_scale_codec::{Decode, Encode};
+use protobuf::well_known_types::Duration;
+use protobuf::Message;
+use serde::{Deserialize, Serialize};
+
+use super::upgrade::ScheduledUpgrade;
+use super::ChainNodeState;
+use crate::tx_error::NetworkParamsUpdateError;
+use chain_core::common::Timespec;
+use chain_core::init::coin::Coin;
+use chain_core::init::params::NetworkParameters;
+use chain_core::state::governance::{NetworkParamsChange, NetworkParamsUpdateTx};
+use chain_core::state::tendermint::BlockHeight;
+use chain_core::APP_VERSION;
+
+/// Tendermint's default max age of the evidence in blocks
+const DEFAULT_EVIDENCE_MAX_AGE_NUM_BLOCKS: i64 = 100_000;
+
+/// Network parameters update scheduled by a valid `NetworkParamsUpdateTx`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Encode, Decode)]
+pub struct PendingParamsUpdate {
+    /// the change takes effect at the beginning of this block
+    pub effective_height: BlockHeight,
+    pub change: NetworkParamsChange,
+}
+
+/// Checks the proposal against the current parameters (the votes are checked by the staking table)
+pub fn check_params_update(
+    tx: &NetworkParamsUpdateTx,
+    params: &NetworkParameters,
+    pending_update: Option<&PendingParamsUpdate>,
+    block_height: BlockHeight,
+) -> Result<PendingParamsUpdate, NetworkParamsUpdateError> {
+    if pending_update.is_some() {
+        return Err(NetworkParamsUpdateError::UpdatePending);
+    }
+    if tx.params_version != params.version() {
+        return Err(NetworkParamsUpdateError::VersionMismatch);
+    }
+    if tx.effective_height <= block_height {
+        return Err(NetworkParamsUpdateError::InvalidEffectiveHeight);
+    }
+    let change = &tx.change;
+    if change.is_empty() {
+        return Err(NetworkParamsUpdateError::InvalidChange(
+            "no parameter is changed",
+        ));
+    }
+    if change.unbonding_period == Some(0) {
+        return Err(NetworkParamsUpdateError::InvalidChange(
+            "unbonding period can't be zero",
+        ));
+    }
+    if change.required_council_node_stake == Some(Coin::zero()) {
+        return Err(NetworkParamsUpdateError::InvalidChange(
+            "required council node stake can't be zero",
+        ));
+    }
+    if let Some(upgrade) = &change.upgrade {
+        if upgrade.app_version <= APP_VERSION {
+            return Err(NetworkParamsUpdateError::InvalidChange(
+                "upgrade app version should be greater than the current one",
+            ));
+        }
+        if upgrade.height <= tx.effective_height {
+            return Err(NetworkParamsUpdateError::InvalidChange(
+                "upgrade height should be after the effective height",
+            ));
+        }
+        if let Some(checkpoint) = &upgrade.checkpoint {
+            if checkpoint.height < tx.effective_height || checkpoint.height >= upgrade.height {
+                return Err(NetworkParamsUpdateError::InvalidChange(
+                    "upgrade checkpoint should be between the effective and the upgrade heights",
+                ));
+            }
+            if checkpoint.min_readiness_percent == 0 || checkpoint.min_readiness_percent > 100 {
+                return Err(NetworkParamsUpdateError::InvalidChange(
+                    "minimal upgrade readiness should be between 1 and 100 percent",
+                ));
+            }
+            if checkpoint.delay == 0 {
+                return Err(NetworkParamsUpdateError::InvalidChange(
+                    "upgrade delay can't be zero",
+                ));
+            }
+        }
+    }
+    Ok(PendingParamsUpdate {
+        effective_height: tx.effective_height,
+        change: change.clone(),
+    })
+}
+
+/// Tendermint's evidence parameters for the new unbonding period (returned in EndBlock
+/// of the block the change takes effect at); Tendermint replaces both max ages,
+/// so the one in blocks is kept from the consensus parameters stored at InitChain
+pub fn evidence_params_update(
+    stored_consensus_params: Option<&[u8]>,
+    unbonding_period: Timespec,
+) -> ConsensusParams {
+    let mut stored = ConsensusParams::new();
+    if let Some(bytes) = stored_consensus_params {
+        if let Err(e) = stored.merge_from_bytes(bytes) {
+            log::warn!("invalid stored consensus params: {}", e);
+        }
+    }
+    let mut evidence = stored.take_evidence();
+    if evidence.max_age_num_blocks <= 0 {
+        evidence.max_age_num_blocks = DEFAULT_EVIDENCE_MAX_AGE_NUM_BLOCKS;
+    }
+    let mut max_age = Duration::new();
+    max_age.seconds = unbonding_period as i64;
+    evidence.set_max_age_duration(max_age);
+    let mut update = ConsensusParams::new();
+    update.set_evidence(evidence);
+    update
+}
+
+impl ChainNodeState {
+    /// Applies the pending network parameters update if it takes effect at the current block
+    /// (the unbonding period is the max evidence age, also updated in Tendermint's
+    /// evidence parameters, see `evidence_params_update`;
+    /// a new upgrade plan replaces the scheduled upgrade and its signals;
+    /// the frozen accounts are kept while the account freeze is disabled)
+    pub fn apply_due_params_update(&mut self) -> Option<NetworkParamsChange> {
+        match &self.pending_params_update {
+            Some(update) if update.effective_height <= self.block_height => {}
+            _ => return None,
+        }
+        let change = self.pending_params_update.take()?.change;
+        self.top_level.network_params = self.top_level.network_params.apply_change(&change);
+        if let Some(stake) = change.required_council_node_stake {
+            self.staking_table.minimal_required_staking = stake;
+        }
+        if let Some(unbonding_period) = change.unbonding_period {
+            self.max_evidence_age = unbonding_period;
+        }
+        if let Some(plan) = &change.upgrade {
+            self.scheduled_upgrade = Some(ScheduledUpgrade::new(plan.clone()));
+        }
+        if let Some(enabled) = change.account_freeze {
+            self.account_freeze.enabled = enabled;
+        }
+        Some(change)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chain_core::init::params::{
+        InitNetworkParameters, JailingParameters, RewardsParameters, SlashRatio, SlashingParameters,
+    };
+    use chain_core::state::account::StakedStateOpAttributes;
+    use chain_core::state::governance::{UpgradeCheckpoint, UpgradePlan};
+    use chain_core::tx::fee::{LinearFee, Milli};
+    use std::str::FromStr;
+
+    fn genesis_params() -> NetworkParameters {
+        NetworkParameters::Genesis(InitNetworkParameters {
+            initial_fee_policy: LinearFee::new(Milli::new(1, 1), Milli::new(1, 1)),
+            required_council_node_stake: Coin::unit(),
+            required_community_node_stake: Coin::unit(),
+            jailing_config: JailingParameters {
+                block_signing_window: 100,
+                missed_block_threshold: 50,
+            },
+            slashing_config: SlashingParameters {
+                liveness_slash_percent: SlashRatio::from_str("0.1").unwrap(),
+                byzantine_slash_percent: SlashRatio::from_str("0.2").unwrap(),
+                invalid_commit_slash_percent: SlashRatio::from_str("0.2").unwrap(),
+            },
+            rewards_config: RewardsParameters {
+                monetary_expansion_cap: Coin::zero(),
+                reward_period_seconds: 24 * 60 * 60,
+                monetary_expansion_r0: Milli::new(0, 500),
+                monetary_expansion_tau: 166_666_600,
+                monetary_expansion_decay: 999_860,
+            },
+            max_validators: 2,
+        })
+    }
+
+    fn update_tx(params_version: u64, change: NetworkParamsChange) -> NetworkParamsUpdateTx {
+        NetworkParamsUpdateTx::new(
+            params_version,
+            10.into(),
+            change,
+            StakedStateOpAttributes::new(0),
+        )
+    }
+
+    #[test]
+    fn check_params_update_validation() {
+        let params = genesis_params();
+        let change = NetworkParamsChange {
+            fee_policy: Some(LinearFee::new(Milli::new(2, 0), Milli::new(1, 0))),
+            ..Default::default()
+        };
+        let update =
+            check_params_update(&update_tx(0, change.clone()), &params, None, 5.into()).unwrap();
+        assert_eq!(BlockHeight::new(10), update.effective_height);
+
+        assert!(matches!(
+            check_params_update(
+                &update_tx(0, change.clone()),
+                &params,
+                Some(&update),
+                5.into()
+            ),
+            Err(NetworkParamsUpdateError::UpdatePending)
+        ));
+        assert!(matches!(
+            check_params_update(&update_tx(1, change.clone()), &params, None, 5.into()),
+            Err(NetworkParamsUpdateError::VersionMismatch)
+        ));
+        assert!(matches!(
+            check_params_update(&update_tx(0, change), &params, None, 10.into()),
+            Err(NetworkParamsUpdateError::InvalidEffectiveHeight)
+        ));
+        assert!(matches!(
+            check_params_update(
+                &update_tx(0, NetworkParamsChange::default()),
+                &params,
+                None,
+                5.into()
+            ),
+            Err(NetworkParamsUpdateError::InvalidChange(_))
+        ));
+        let zero_period = NetworkParamsChange {
+            unbonding_period: Some(0),
+            ..Default::default()
+        };
+        assert!(matches!(
+            check_params_update(&update_tx(0, zero_period), &params, None, 5.into()),
+            Err(NetworkParamsUpdateError::InvalidChange(_))
+        ));
+    }
+
+    #[test]
+    fn check_upgrade_plan_validation() {
+        let params = genesis_params();
+        let upgrade_change =
+            |app_version, height: u64, checkpoint_height: u64| NetworkParamsChange {
+                upgrade: Some(UpgradePlan {
+                    app_version,
+                    height: height.into(),
+                    checkpoint: Some(UpgradeCheckpoint {
+                        height: checkpoint_height.into(),
+                        min_readiness_percent: 67,
+                        delay: 100,
+                    }),
+                }),
+                ..Default::default()
+            };
+        let check = |change| check_params_update(&update_tx(0, change), &params, None, 5.into());
+
+        assert!(check(upgrade_change(APP_VERSION + 1, 20, 15)).is_ok());
+        assert!(check(upgrade_change(APP_VERSION, 20, 15)).is_err());
+        // effective at height 10
+        assert!(check(upgrade_change(APP_VERSION + 1, 10, 10)).is_err());
+        assert!(check(upgrade_change(APP_VERSION + 1, 20, 9)).is_err());
+        assert!(check(upgrade_change(APP_VERSION + 1, 20, 20)).is_err());
+    }
+
+    #[test]
+    fn check_params_change_application() {
+        let params = genesis_params();
+        let fee_policy = LinearFee::new(Milli::new(2, 0), Milli::new(1, 0));
+        let stake = Coin::new(10_000).unwrap();
+        let updated = params.apply_change(&NetworkParamsChange {
+            fee_policy: Some(fee_policy),
+            unbonding_period: Some(100),
+            required_council_node_stake: Some(stake),
+            upgrade: None,
+            account_freeze: None,
+        });
+        assert_eq!(1, updated.version());
+        assert_eq!(fee_policy, updated.current().initial_fee_policy);
+        assert_eq!(stake, updated.get_required_council_node_stake());
+        assert_eq!(
+            params.current().rewards_config,
+            updated.current().rewards_config
+        );
+        assert_ne!(params.hash(), updated.hash());
+        assert_eq!(2, updated.apply_change(&Default::default()).version());
+    }
+
+    #[test]
+    fn check_evidence_params_update() {
+        let mut evidence = EvidenceParams::new();
+        evidence.max_age_num_blocks = 50;
+        let mut max_age = Duration::new();
+        max_age.seconds = 10;
+        evidence.set_max_age_duration(max_age);
+        let mut stored = ConsensusParams::new();
+        stored.set_evidence(evidence);
+        let stored = stored.write_to_bytes().unwrap();
+
+        let update = evidence_params_update(Some(&stored), 100);
+        assert_eq!(50, update.get_evidence().max_age_num_blocks);
+        assert_eq!(100, update.get_evidence().get_max_age_duration().seconds);
+        assert!(!update.has_block());
+
+        let update = evidence_params_update(None, 100);
+        assert_eq!(
+            DEFAULT_EVIDENCE_MAX_AGE_NUM_BLOCKS,
+            update.get_evidence().max_age_num_blocks
+        );
+        assert_eq!(100, update.get_evidence().get_max_age_duration().seconds);
+    }
+}
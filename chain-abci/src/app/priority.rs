@@ -0,0 +1,104 @@
+use abci::Event;
+use abci::Pair as KVPair;
+
+use crate::storage::{TxAction, TxPublicAction};
+use chain_core::common::{TendermintEventKey, TendermintEventType};
+
+/// Priority of the fee-exempt validator operations (node join / unjail / network parameters update
+/// / upgrade signal):
+/// they restore the validator set, and can't be repeated once processed
+pub const FEE_EXEMPT_PRIORITY: u64 = u64::MAX;
+
+/// Mempool priority of a valid transaction, computed from its `verify()` result
+/// and returned in the `check_tx` response events, so that the mempool ordering
+/// can follow the paid fee rather than the arrival order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TxPriority {
+    /// priority score (higher first)
+    pub priority: u64,
+    /// paid fee (in base units) per kilobyte of the serialized transaction
+    pub fee_density: u64,
+}
+
+impl TxPriority {
+    /// `tx_len` is the length of the serialized `TxAux`
+    pub fn new(tx_len: usize, action: &TxAction) -> Self {
+        let fee = u128::from(u64::from(action.fee().to_coin()));
+        let fee_density = (fee * 1000 / (tx_len.max(1) as u128)).min(u64::MAX.into()) as u64;
+        let priority = match action {
+            TxAction::Public(TxPublicAction::NodeJoin { .. })
+            | TxAction::Public(TxPublicAction::Unjail(_))
+            | TxAction::Public(TxPublicAction::NetworkParamsUpdate(_))
+            | TxAction::Public(TxPublicAction::UpgradeSignal(_))
+            | TxAction::Public(TxPublicAction::AccountFreeze(_)) => FEE_EXEMPT_PRIORITY,
+            _ => fee_density,
+        };
+        TxPriority {
+            priority,
+            fee_density,
+        }
+    }
+
+    /// "mempool_priority" event for the `check_tx` response
+    pub fn to_event(self) -> Event {
+        let mut event = Event::new();
+        event.field_type = TendermintEventType::MempoolPriority.to_string();
+
+        let mut priority = KVPair::new();
+        priority.key = TendermintEventKey::Priority.into();
+        priority.value = self.priority.to_string().into_bytes();
+        event.attributes.push(priority);
+
+        let mut fee_density = KVPair::new();
+        fee_density.key = TendermintEventKey::FeeDensity.into();
+        fee_density.value = self.fee_density.to_string().into_bytes();
+        event.attributes.push(fee_density);
+
+        event
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::TxEnclaveAction;
+    use chain_core::init::coin::Coin;
+    use chain_core::state::account::StakedStateAddress;
+    use chain_core::tx::fee::Fee;
+
+    fn deposit(fee: u64) -> TxAction {
+        TxAction::Enclave(TxEnclaveAction::Deposit {
+            fee: Fee::new(Coin::new(fee).unwrap()),
+            spend_utxo: vec![],
+            deposit: (
+                StakedStateAddress::BasicRedeem([0u8; 20].into()),
+                Coin::one(),
+            ),
+        })
+    }
+
+    #[test]
+    fn check_priority_follows_fee_density() {
+        let cheap = TxPriority::new(500, &deposit(100));
+        assert_eq!(200, cheap.fee_density);
+        assert_eq!(200, cheap.priority);
+        let expensive = TxPriority::new(250, &deposit(100));
+        assert!(expensive > cheap);
+        let max = TxPriority::new(1, &deposit(Coin::max().into()));
+        assert_eq!(u64::MAX, max.fee_density);
+
+        let unjail = TxPriority::new(
+            100,
+            &TxAction::Public(TxPublicAction::Unjail(StakedStateAddress::BasicRedeem(
+                [0u8; 20].into(),
+            ))),
+        );
+        assert_eq!(0, unjail.fee_density);
+        assert_eq!(FEE_EXEMPT_PRIORITY, unjail.priority);
+
+        let event = cheap.to_event();
+        assert_eq!("mempool_priority", event.field_type);
+        assert_eq!(TendermintEventKey::Priority, event.attributes[0].key);
+        assert_eq!(b"200".to_vec(), event.attributes[0].value);
+    }
+}
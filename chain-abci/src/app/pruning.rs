@@ -0,0 +1,184 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use chain_core::state::tendermint::BlockHeight;
+use chain_core::tx::data::TxId;
+use chain_core::tx::{TxAux, TxEnclaveAux, TxPublicAux};
+use chain_storage::buffer::StoreKV;
+
+/// Number of the most recent blocks whose transaction bodies are kept in the default pruning mode
+pub const DEFAULT_PRUNING_KEEP_BLOCKS: u64 = 100_000;
+/// Number of the most recent blocks whose transaction bodies are kept in the aggressive pruning mode
+pub const AGGRESSIVE_PRUNING_KEEP_BLOCKS: u64 = 1_000;
+/// Maximum number of queued heights pruned at one commit
+/// (spreads the work when the pruning is enabled again or the retention is lowered)
+const MAX_PRUNED_HEIGHTS_PER_COMMIT: u64 = 1_000;
+
+/// Pruning of the stored bodies and witnesses of old transactions (the `pruning` setting
+/// of the configuration file or the `--pruning` flag).
+/// Only the transactions without outputs have their bodies stored in plain
+/// (deposits, unbonds, unjails, node joins, network parameters updates and data anchors),
+/// so they're pruned once they're older than the retention; the sealed transaction payloads
+/// (needed by tx-query), UTXO metadata, data anchor index, merkle trees and app hashes
+/// are always kept, so inclusion proofs can still be made. Only the transactions committed
+/// while the pruning is enabled are pruned.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PruningMode {
+    /// keep everything
+    Archive,
+    /// keep the last `DEFAULT_PRUNING_KEEP_BLOCKS` blocks
+    Default,
+    /// keep the last `AGGRESSIVE_PRUNING_KEEP_BLOCKS` blocks
+    Aggressive,
+}
+
+impl Default for PruningMode {
+    fn default() -> Self {
+        PruningMode::Archive
+    }
+}
+
+impl PruningMode {
+    /// Number of the most recent blocks whose transaction bodies are kept (None = all)
+    pub fn keep_blocks(self) -> Option<u64> {
+        match self {
+            PruningMode::Archive => None,
+            PruningMode::Default => Some(DEFAULT_PRUNING_KEEP_BLOCKS),
+            PruningMode::Aggressive => Some(AGGRESSIVE_PRUNING_KEEP_BLOCKS),
+        }
+    }
+}
+
+impl fmt::Display for PruningMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PruningMode::Archive => write!(f, "archive"),
+            PruningMode::Default => write!(f, "default"),
+            PruningMode::Aggressive => write!(f, "aggressive"),
+        }
+    }
+}
+
+impl FromStr for PruningMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "archive" => Ok(PruningMode::Archive),
+            "default" => Ok(PruningMode::Default),
+            "aggressive" => Ok(PruningMode::Aggressive),
+            _ => Err(format!(
+                "invalid pruning mode: {} (expected archive, default or aggressive)",
+                s
+            )),
+        }
+    }
+}
+
+/// Delivered transactions whose stored body and witness can be pruned (the ones without outputs;
+/// the witnesses of withdrawals are kept, as their outputs may be unspent)
+fn prunable_txs(delivered_txs: &[TxAux]) -> Vec<TxId> {
+    delivered_txs
+        .iter()
+        .filter(|txaux| match txaux {
+            TxAux::EnclaveTx(TxEnclaveAux::DepositStakeTx { .. }) => true,
+            TxAux::PublicTx(TxPublicAux::UnbondStakeTx(..))
+            | TxAux::PublicTx(TxPublicAux::UnjailTx(..))
+            | TxAux::PublicTx(TxPublicAux::NodeJoinTx(..))
+            | TxAux::PublicTx(TxPublicAux::NetworkParamsUpdateTx(..))
+            | TxAux::PublicTx(TxPublicAux::UpgradeSignalTx(..))
+            | TxAux::PublicTx(TxPublicAux::DataAnchorTx(..))
+            | TxAux::PublicTx(TxPublicAux::AccountFreezeTx(..)) => true,
+            _ => false,
+        })
+        .map(TxAux::tx_id)
+        .collect()
+}
+
+/// Queues the prunable transactions of the committed block and prunes the ones
+/// older than the retention, returns the number of pruned transactions
+pub(crate) fn prune_on_commit(
+    mode: PruningMode,
+    db: &mut impl StoreKV,
+    height: BlockHeight,
+    delivered_txs: &[TxAux],
+) -> usize {
+    let keep_blocks = match mode.keep_blocks() {
+        Some(keep_blocks) => keep_blocks,
+        None => return 0,
+    };
+    chain_storage::enqueue_prunable_txs(db, height, &prunable_txs(delivered_txs));
+    if height.value() <= keep_blocks {
+        return 0;
+    }
+    chain_storage::prune_txs(
+        db,
+        BlockHeight::new(height.value() - keep_blocks),
+        MAX_PRUNED_HEIGHTS_PER_COMMIT,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chain_storage::buffer::MemStore;
+    use chain_storage::LookupItem;
+
+    fn store_tx(db: &mut impl StoreKV, height: u64, txid: TxId) {
+        chain_storage::store_tx_body(db, &txid, b"body");
+        chain_storage::store_tx_witness(db, &txid, b"witness");
+        chain_storage::enqueue_prunable_txs(db, BlockHeight::new(height), &[txid]);
+    }
+
+    #[test]
+    fn check_pruning_queue() {
+        let mut db = MemStore::new();
+        assert_eq!(0, chain_storage::prune_txs(&mut db, 10.into(), 100));
+
+        store_tx(&mut db, 5, [5u8; 32]);
+        store_tx(&mut db, 5, [6u8; 32]);
+        store_tx(&mut db, 7, [7u8; 32]);
+        store_tx(&mut db, 9, [9u8; 32]);
+        // a transaction with outputs isn't queued
+        chain_storage::store_tx_witness(&mut db, &[1u8; 32], b"witness");
+
+        assert_eq!(0, chain_storage::prune_txs(&mut db, 4.into(), 100));
+        assert_eq!(2, chain_storage::prune_txs(&mut db, 6.into(), 100));
+        assert!(chain_storage::lookup_item(&db, LookupItem::TxBody, &[5u8; 32]).is_none());
+        assert!(chain_storage::lookup_item(&db, LookupItem::TxWitness, &[6u8; 32]).is_none());
+        assert!(chain_storage::lookup_item(&db, LookupItem::TxBody, &[7u8; 32]).is_some());
+
+        // at most one queued height
+        assert_eq!(1, chain_storage::prune_txs(&mut db, 20.into(), 1));
+        assert!(chain_storage::lookup_item(&db, LookupItem::TxWitness, &[7u8; 32]).is_none());
+        assert_eq!(0, chain_storage::prune_txs(&mut db, 20.into(), 1));
+        assert_eq!(1, chain_storage::prune_txs(&mut db, 20.into(), 100));
+        assert!(chain_storage::lookup_item(&db, LookupItem::TxBody, &[9u8; 32]).is_none());
+        assert!(chain_storage::lookup_item(&db, LookupItem::TxWitness, &[1u8; 32]).is_some());
+    }
+
+    #[test]
+    fn check_archive_mode_keeps_everything() {
+        let mut db = MemStore::new();
+        store_tx(&mut db, 1, [1u8; 32]);
+        assert_eq!(
+            0,
+            prune_on_commit(PruningMode::Archive, &mut db, 1_000_000.into(), &[])
+        );
+        assert!(chain_storage::lookup_item(&db, LookupItem::TxBody, &[1u8; 32]).is_some());
+        assert_eq!(
+            1,
+            prune_on_commit(
+                PruningMode::Aggressive,
+                &mut db,
+                (AGGRESSIVE_PRUNING_KEEP_BLOCKS + 1).into(),
+                &[]
+            )
+        );
+        assert_eq!(Ok(PruningMode::Default), "default".parse());
+        assert!("full".parse::<PruningMode>().is_err());
+    }
+}
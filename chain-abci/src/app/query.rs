@@ -1,14 +1,22 @@
 use std::convert::{TryFrom, TryInto};
 
+use super::block_filters::collect_block_filters;
+use super::block_stats::aggregate_block_stats;
+use super::grpc_query::QUERY_SERVICE_PATH;
+use super::storage_metrics::dump_storage_metrics;
 use super::ChainNodeApp;
 use crate::enclave_bridge::EnclaveProxy;
 use abci::*;
 use chain_core::common::{MerkleTree, Proof as MerkleProof, H256, HASH_SIZE_256};
-use chain_core::state::account::StakedStateAddress;
+use chain_core::state::account::{DataAnchorProof, DataAnchorRecord, StakedStateAddress};
 use chain_core::state::tendermint::BlockHeight;
 use chain_core::state::ChainState;
+use chain_core::tx::data::input::TxoPointer;
 use chain_core::tx::data::TXID_HASH_ID;
+use chain_core::tx::TransactionId;
+use chain_core::{AppHashParts, UTXO_SET_COMMITTED};
 use chain_storage::jellyfish::get_with_proof;
+use chain_storage::utxo_mmr::get_utxo_proof;
 use chain_storage::LookupItem;
 use parity_scale_codec::{Decode, Encode};
 
@@ -71,9 +79,86 @@ impl<T: EnclaveProxy + 'static> ChainNodeApp<T> {
         None
     }
 
+    /// Chain state at the end of the block (the last one if the height is 0), with the parts
+    /// of the app hash of the block (the proof of each of the committed state components)
+    fn committed_state(
+        &self,
+        req_height: BlockHeight,
+    ) -> Result<(BlockHeight, ChainState, AppHashParts), &'static str> {
+        let last_state = self
+            .last_state
+            .as_ref()
+            .ok_or("node not correctly restored / initialized")?;
+        let height = if req_height == BlockHeight::genesis() {
+            last_state.last_block_height
+        } else {
+            req_height
+        };
+        let state = if height == last_state.last_block_height {
+            last_state.top_level.clone()
+        } else if height > last_state.last_block_height {
+            return Err("block not yet committed");
+        } else {
+            let value = self.storage.get_historical_state(height).ok_or(
+                "state not found (state history is only stored with tx query address set)",
+            )?;
+            ChainState::decode(&mut value.as_slice()).map_err(|_| "state decode failed")?
+        };
+        let app_hash = self
+            .storage
+            .get_historical_app_hash(height)
+            .ok_or("app hash not found")?;
+        // genesis has no transactions (the tree is not stored)
+        let valid_tx_root = match self.storage.lookup_item(LookupItem::TxsMerkle, &app_hash) {
+            Some(data) => MerkleTree::<H256>::decode(&mut data.as_slice())
+                .map_err(|_| "merkle tree decode failed")?
+                .root_hash(),
+            None if height == BlockHeight::genesis() => MerkleTree::<H256>::empty().root_hash(),
+            None => return Err("merkle tree not found"),
+        };
+        let parts = state.app_hash_parts(valid_tx_root);
+        debug_assert!(parts.verify(&app_hash));
+        Ok((height, state, parts))
+    }
+
+    /// Anchorings of the commitment with their inclusion proofs (empty if it's not anchored)
+    fn data_anchor_proofs(&self, commitment: &H256) -> Result<Vec<DataAnchorProof>, &'static str> {
+        let records = match self.storage.get_data_anchors(commitment) {
+            Some(value) => Vec::<DataAnchorRecord>::decode(&mut value.as_slice())
+                .map_err(|_| "data anchors decode failed")?,
+            None => return Ok(vec![]),
+        };
+        records
+            .into_iter()
+            .map(|record| {
+                let (_, _, app_hash_parts) = self.committed_state(record.height)?;
+                let app_hash = self
+                    .storage
+                    .get_historical_app_hash(record.height)
+                    .ok_or("app hash not found")?;
+                let tree = self
+                    .storage
+                    .lookup_item(LookupItem::TxsMerkle, &app_hash)
+                    .ok_or("merkle tree not found")
+                    .and_then(|data| {
+                        MerkleTree::<H256>::decode(&mut data.as_slice())
+                            .map_err(|_| "merkle tree decode failed")
+                    })?;
+                let tx_proof = tree
+                    .generate_proof(record.tx.id())
+                    .ok_or("anchoring transaction not in the block")?;
+                Ok(DataAnchorProof {
+                    record,
+                    tx_proof,
+                    app_hash_parts,
+                })
+            })
+            .collect()
+    }
+
     /// Responds to query requests -- note that path is hex-encoded in the original request on the client side
     /// e.g. "store" == 0x73746f7265.
-    pub fn query_handler(&self, _req: &RequestQuery) -> ResponseQuery {
+    pub fn query_handler(&mut self, _req: &RequestQuery) -> ResponseQuery {
         let mut resp = ResponseQuery::new();
 
         // "When Tendermint connects to a peer, it sends two queries to the ABCI application using the following paths, with no additional data:
@@ -85,6 +170,11 @@ impl<T: EnclaveProxy + 'static> ChainNodeApp<T> {
             return resp;
         }
 
+        if let Some(method) = _req.path.strip_prefix(QUERY_SERVICE_PATH) {
+            self.query_service_handler(method, &_req.data, &mut resp);
+            return resp;
+        }
+
         match _req.path.as_ref() {
             "txquery" => match &self.tx_query_address {
                 Some(addr) => {
@@ -195,8 +285,13 @@ impl<T: EnclaveProxy + 'static> ChainNodeApp<T> {
                 }
             }
             "staking" => {
-                let mversion = if let Ok(height) = _req.height.try_into() {
-                    self.storage.get_historical_staking_version(height)
+                // Negative height default to 0 (the last block, as for the proof of the root)
+                let req_height = _req
+                    .height
+                    .try_into()
+                    .unwrap_or_else(|_| BlockHeight::genesis());
+                let mversion = if req_height != BlockHeight::genesis() {
+                    self.storage.get_historical_staking_version(req_height)
                 } else {
                     self.last_state.as_ref().map(|state| state.staking_version)
                 };
@@ -205,20 +300,85 @@ impl<T: EnclaveProxy + 'static> ChainNodeApp<T> {
                     let (mstaking, proof) = get_with_proof(&self.storage, version, &address);
                     resp.value = mstaking.encode();
                     if _req.prove {
+                        let mut ops = vec![ProofOp {
+                            field_type: "staking".to_owned(),
+                            key: address.encode(),
+                            data: proof.encode(),
+                            ..Default::default()
+                        }];
+                        // the staking proof is checked against the account trie root,
+                        // which is proven against the app hash
+                        match self.committed_state(req_height) {
+                            Ok((height, _, parts)) => {
+                                resp.height = height.value() as i64;
+                                ops.push(ProofOp {
+                                    field_type: "account-state".to_owned(),
+                                    key: height.encode(),
+                                    data: parts.encode(),
+                                    ..Default::default()
+                                });
+                            }
+                            Err(log) => {
+                                resp.log += "account state proof not available: ";
+                                resp.log += log;
+                            }
+                        }
+                        resp.set_proof(Proof {
+                            ops: ops.into(),
+                            ..Default::default()
+                        });
+                    }
+                } else {
+                    resp.log += "account lookup failed (either invalid address or node not correctly restored / initialized)";
+                    resp.code = 3;
+                }
+            }
+            "utxo" => match TxoPointer::decode(&mut _req.data.as_slice()) {
+                Ok(_) if !UTXO_SET_COMMITTED => {
+                    resp.log +=
+                        "utxo proof not available: the utxo set is not committed in the app hash";
+                    resp.code = 1;
+                }
+                // the merkle mountain range is only stored for the last committed block
+                Ok(txo) => match self.committed_state(BlockHeight::genesis()) {
+                    Ok((height, _, parts)) => {
+                        let proof = get_utxo_proof(&self.storage, &txo);
+                        resp.height = height.value() as i64;
+                        resp.value = proof.encode();
                         resp.set_proof(Proof {
                             ops: vec![ProofOp {
-                                field_type: "staking".to_owned(),
-                                key: address.encode(),
-                                data: proof.encode(),
+                                field_type: "utxo-state".to_owned(),
+                                key: height.encode(),
+                                data: parts.encode(),
                                 ..Default::default()
                             }]
                             .into(),
                             ..Default::default()
                         });
                     }
-                } else {
-                    resp.log += "account lookup failed (either invalid address or node not correctly restored / initialized)";
-                    resp.code = 3;
+                    Err(log) => {
+                        resp.log += "utxo proof not available: ";
+                        resp.log += log;
+                        resp.code = 1;
+                    }
+                },
+                Err(_) => {
+                    resp.log += "invalid transaction output pointer";
+                    resp.code = 4;
+                }
+            },
+            "data-anchor" => {
+                if let Some(commitment) = get_key(&mut resp, &_req.data) {
+                    match self.data_anchor_proofs(&commitment) {
+                        Ok(proofs) => {
+                            resp.value = proofs.encode();
+                        }
+                        Err(log) => {
+                            resp.log += "data anchor proof not available: ";
+                            resp.log += log;
+                            resp.code = 1;
+                        }
+                    }
                 }
             }
             "state" => {
@@ -245,6 +405,35 @@ impl<T: EnclaveProxy + 'static> ChainNodeApp<T> {
                     }
                 }
             }
+            "network-params" => {
+                // Negative height default to 0
+                let req_height = _req
+                    .height
+                    .try_into()
+                    .unwrap_or_else(|_| BlockHeight::genesis());
+                match self.committed_state(req_height) {
+                    Ok((height, state, proof)) => {
+                        resp.value = state.network_params.encode();
+                        resp.height = height.value() as i64;
+                        if _req.prove {
+                            resp.set_proof(Proof {
+                                ops: vec![ProofOp {
+                                    field_type: "network-params".to_owned(),
+                                    key: height.encode(),
+                                    data: proof.encode(),
+                                    ..Default::default()
+                                }]
+                                .into(),
+                                ..Default::default()
+                            });
+                        }
+                    }
+                    Err(log) => {
+                        resp.log += log;
+                        resp.code = 2;
+                    }
+                }
+            }
             "council-nodes" => {
                 let council_nodes = &self
                     .last_state
@@ -257,6 +446,130 @@ impl<T: EnclaveProxy + 'static> ChainNodeApp<T> {
                     .expect("Unable to serialize validator metadata into json")
                     .into_bytes();
             }
+            "upgrade-readiness" => {
+                let state = self
+                    .last_state
+                    .as_ref()
+                    .expect("Missing last_state: init chain was not called");
+                match &state.scheduled_upgrade {
+                    Some(upgrade) => {
+                        let council_nodes = state
+                            .staking_table
+                            .list_council_nodes(&self.staking_getter_committed());
+                        resp.value = serde_json::to_vec(&upgrade.readiness(&council_nodes))
+                            .expect("serialize upgrade readiness");
+                    }
+                    None => {
+                        resp.code = 1;
+                        resp.log += "no scheduled upgrade";
+                    }
+                }
+            }
+            "account-freeze" => {
+                resp.value = self
+                    .last_state
+                    .as_ref()
+                    .expect("Missing last_state: init chain was not called")
+                    .account_freeze
+                    .encode();
+            }
+            "compact-filter" => {
+                let height = _req
+                    .height
+                    .try_into()
+                    .unwrap_or_else(|_| BlockHeight::genesis());
+                match self.storage.get_compact_filter(height) {
+                    Some(filter) => {
+                        resp.value = filter;
+                    }
+                    None => {
+                        resp.log += "compact filter not found";
+                        resp.code = 1;
+                    }
+                }
+            }
+            "block-stats" => {
+                // data: SCALE-encoded (from height, to height), inclusive
+                let result = <(BlockHeight, BlockHeight)>::decode(&mut _req.data.as_slice())
+                    .map_err(|_| "invalid height range")
+                    .and_then(|(from_height, to_height)| {
+                        aggregate_block_stats(&self.storage, from_height, to_height)
+                    });
+                match result {
+                    Ok(summary) => {
+                        resp.value = serde_json::to_vec(&summary).expect("serialize block stats");
+                    }
+                    Err(log) => {
+                        resp.log += log;
+                        resp.code = 4;
+                    }
+                }
+            }
+            "block-filters" => {
+                // data: SCALE-encoded (from height, to height), inclusive
+                // value: SCALE-encoded `Vec<BlockFilters>`
+                let result = <(BlockHeight, BlockHeight)>::decode(&mut _req.data.as_slice())
+                    .map_err(|_| "invalid height range")
+                    .and_then(|(from_height, to_height)| {
+                        collect_block_filters(&self.storage, from_height, to_height)
+                    });
+                match result {
+                    Ok(filters) => {
+                        resp.value = filters.encode();
+                    }
+                    Err(log) => {
+                        resp.log += log;
+                        resp.code = 4;
+                    }
+                }
+            }
+            "rejected-txs" => {
+                if self.rejected_txs.query_enabled() {
+                    resp.value = self.rejected_txs.dump().into_bytes();
+                } else {
+                    resp.code = 1;
+                    resp.log += "rejected-txs query not enabled on this node";
+                }
+            }
+            "mempool-policy" => {
+                resp.value = self.mempool_policy.dump().into_bytes();
+            }
+            "watch-list" => {
+                resp.value = self.watch_list.dump().into_bytes();
+            }
+            "storage-metrics" => {
+                resp.value = dump_storage_metrics(&self.storage, &self.check_tx_cache).into_bytes();
+            }
+            "snapshots" => match &self.state_sync {
+                Some(state_sync) => {
+                    resp.value = serde_json::to_vec(&state_sync.list_snapshots())
+                        .expect("serialize snapshots");
+                }
+                None => {
+                    resp.code = 1;
+                    resp.log += "state sync not enabled";
+                }
+            },
+            "snapshot-chunk" => {
+                // data: SCALE-encoded (height, format, chunk index)
+                let chunk = <(u64, u32, u32)>::decode(&mut _req.data.as_slice())
+                    .ok()
+                    .and_then(|(height, format, index)| {
+                        self.state_sync.as_ref()?.load_chunk(height, format, index)
+                    });
+                match chunk {
+                    Some(chunk) => {
+                        resp.value = chunk;
+                    }
+                    None => {
+                        resp.code = 1;
+                        resp.log += "snapshot chunk not found";
+                    }
+                }
+            }
+            "simulate_tx" => {
+                self.simulate_tx_query(&_req.data, &mut resp);
+            }
             "sealed" => {
                 self.lookup(
                     &mut resp,
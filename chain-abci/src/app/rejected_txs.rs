@@ -0,0 +1,217 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::env;
+
+use serde::Serialize;
+
+use crate::tx_error::TxError;
+use chain_core::state::tendermint::BlockHeight;
+use chain_core::tx::data::TxId;
+
+/// Maximum number of rejected transactions logged at warn level per block
+/// (the rest are only counted and logged at debug level)
+pub const REJECTED_TX_LOG_LIMIT_ENV: &str = "CRYPTO_CHAIN_REJECTED_TX_LOG_LIMIT";
+/// Number of rejected transactions (ids and errors) kept in the ring buffer (0 = capture disabled)
+pub const REJECTED_TX_CAPTURE_ENV: &str = "CRYPTO_CHAIN_REJECTED_TX_CAPTURE";
+/// Node-local admin switch of the "rejected-txs" ABCI query path ("1" or "true" enables it);
+/// it's disabled by default, as the ABCI queries are public via the Tendermint RPC
+pub const REJECTED_TX_QUERY_ENV: &str = "CRYPTO_CHAIN_REJECTED_TX_QUERY";
+
+const DEFAULT_LOG_LIMIT: u64 = 20;
+
+/// Which ABCI connection the rejected transaction came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum TxOrigin {
+    /// CheckTx (mempool connection)
+    CheckTx,
+    /// DeliverTx (consensus connection)
+    DeliverTx,
+}
+
+/// Captured rejected transaction
+#[derive(Debug, Clone, Serialize)]
+pub struct RejectedTx {
+    /// transaction id (if the payload could be decoded)
+    pub txid: Option<String>,
+    pub origin: TxOrigin,
+    /// label of the error variant
+    pub kind: String,
+    pub reason: String,
+    /// the height of the last committed block when the transaction was rejected
+    pub height: BlockHeight,
+}
+
+/// Statistics and (optional) capture of rejected transactions (not their payloads),
+/// used for debugging spam waves and integration errors.
+/// The dump is available via the "rejected-txs" ABCI query path, if it's enabled
+/// by the node operator.
+#[derive(Debug, Serialize)]
+pub struct RejectedTxLog {
+    /// number of rejected transactions per error variant
+    counters: BTreeMap<String, u64>,
+    /// most recently rejected transactions (if capture is enabled)
+    captured: VecDeque<RejectedTx>,
+    #[serde(skip)]
+    capture_capacity: usize,
+    #[serde(skip)]
+    log_limit: u64,
+    #[serde(skip)]
+    query_enabled: bool,
+    /// number of rejections logged at warn level in the current block
+    #[serde(skip)]
+    logged_in_block: u64,
+    /// number of rejections not logged at warn level (due to the rate limit) since startup
+    suppressed: u64,
+}
+
+impl Default for RejectedTxLog {
+    fn default() -> Self {
+        RejectedTxLog::new(DEFAULT_LOG_LIMIT, 0)
+    }
+}
+
+impl RejectedTxLog {
+    pub fn new(log_limit: u64, capture_capacity: usize) -> Self {
+        RejectedTxLog {
+            counters: BTreeMap::new(),
+            captured: VecDeque::with_capacity(capture_capacity),
+            capture_capacity,
+            log_limit,
+            query_enabled: false,
+            logged_in_block: 0,
+            suppressed: 0,
+        }
+    }
+
+    /// Reads the configuration from `CRYPTO_CHAIN_REJECTED_TX_*` environment variables
+    pub fn from_env() -> Self {
+        let log_limit = env::var(REJECTED_TX_LOG_LIMIT_ENV)
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_LOG_LIMIT);
+        let capture_capacity = env::var(REJECTED_TX_CAPTURE_ENV)
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0);
+        let mut log = RejectedTxLog::new(log_limit, capture_capacity);
+        log.query_enabled = env::var(REJECTED_TX_QUERY_ENV)
+            .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+            .unwrap_or(false);
+        log
+    }
+
+    /// Whether the dump is served on the "rejected-txs" ABCI query path
+    pub fn query_enabled(&self) -> bool {
+        self.query_enabled
+    }
+
+    /// Counts, logs (rate-limited) and possibly captures the rejected transaction
+    pub fn record(
+        &mut self,
+        origin: TxOrigin,
+        txid: Option<TxId>,
+        error: &TxError,
+        height: BlockHeight,
+    ) {
+        let kind = error.kind();
+        *self.counters.entry(kind.clone()).or_insert(0) += 1;
+        let txid = txid.map(hex::encode);
+        let txid_str = txid.as_deref().unwrap_or("-");
+
+        if self.logged_in_block < self.log_limit {
+            self.logged_in_block += 1;
+            log::warn!(
+                "rejected tx: origin={:?} txid={} kind={} reason={}",
+                origin,
+                txid_str,
+                kind,
+                error
+            );
+        } else {
+            self.suppressed += 1;
+            log::debug!(
+                "rejected tx: origin={:?} txid={} kind={} reason={}",
+                origin,
+                txid_str,
+                kind,
+                error
+            );
+        }
+
+        if self.capture_capacity > 0 {
+            if self.captured.len() >= self.capture_capacity {
+                self.captured.pop_front();
+            }
+            self.captured.push_back(RejectedTx {
+                txid,
+                origin,
+                kind,
+                reason: error.to_string(),
+                height,
+            });
+        }
+    }
+
+    /// Resets the per-block rate limit
+    pub fn new_block(&mut self) {
+        if self.logged_in_block >= self.log_limit && self.log_limit > 0 {
+            log::warn!(
+                "rejected tx log limit reached ({} per block), {} suppressed in total",
+                self.log_limit,
+                self.suppressed
+            );
+        }
+        self.logged_in_block = 0;
+    }
+
+    /// Number of rejected transactions of the given error variant
+    pub fn count(&self, kind: &str) -> u64 {
+        self.counters.get(kind).copied().unwrap_or(0)
+    }
+
+    /// JSON dump of the counters and captured transactions
+    pub fn dump(&self) -> String {
+        serde_json::to_string(self).expect("serialize rejected tx log")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_counting_and_capture() {
+        let mut log = RejectedTxLog::new(1, 2);
+        for i in 0..3u8 {
+            log.record(
+                TxOrigin::CheckTx,
+                Some([i; 32]),
+                &TxError::WIPMLSData,
+                BlockHeight::genesis(),
+            );
+        }
+        assert_eq!(3, log.count("WIPMLSData"));
+        assert_eq!(2, log.suppressed);
+        assert_eq!(2, log.captured.len());
+        assert_eq!(
+            Some(hex::encode([2u8; 32])),
+            log.captured.back().unwrap().txid
+        );
+        assert!(!log.query_enabled());
+
+        log.new_block();
+        assert_eq!(0, log.logged_in_block);
+        assert!(log.dump().contains("WIPMLSData"));
+    }
+
+    #[test]
+    fn check_capture_disabled() {
+        let mut log = RejectedTxLog::new(1, 0);
+        log.record(
+            TxOrigin::DeliverTx,
+            None,
+            &TxError::WIPMLSData,
+            BlockHeight::genesis(),
+        );
+        assert!(log.captured.is_empty());
+    }
+}
@@ -0,0 +1,141 @@
+use abci::ResponseQuery;
+use parity_scale_codec::Decode;
+use serde::Serialize;
+
+use super::tx_event::TxAttributes;
+use super::upgrade::check_tx_activation;
+use super::{ChainNodeApp, ChainNodeState};
+use crate::enclave_bridge::EnclaveProxy;
+use crate::storage::{check_unfrozen_withdraw, process_public_tx, verify_enclave_tx, TxAction};
+use crate::tx_error::TxError;
+use chain_core::init::coin::Coin;
+use chain_core::tx::TxAux;
+use chain_core::ChainInfo;
+use chain_storage::buffer::StakingBuffer;
+use chain_storage::jellyfish::{StakingBufferStore, StakingGetter};
+
+/// Outcome of a dry-run transaction (JSON value of the "simulate_tx" ABCI query)
+#[derive(Debug, Serialize)]
+#[serde(tag = "result", rename_all = "snake_case")]
+enum TxSimulation {
+    Accepted {
+        txid: String,
+        tx_type: String,
+        fee: Coin,
+    },
+    Rejected {
+        /// transaction id (if the payload could be decoded)
+        txid: Option<String>,
+        /// label of the error variant
+        error: String,
+        reason: String,
+    },
+}
+
+impl<T: EnclaveProxy + 'static> ChainNodeApp<T> {
+    /// The last committed state (independent of the block being processed and of the mempool)
+    fn committed_state(&self) -> Option<ChainNodeState> {
+        let raw = self.storage.get_last_app_state()?;
+        let mut state = ChainNodeState::decode(&mut raw.as_slice()).expect("decode app state");
+        state.staking_table.initialize(
+            &StakingGetter::new(&self.storage, state.staking_version),
+            state
+                .top_level
+                .network_params
+                .get_required_council_node_stake(),
+        );
+        Some(state)
+    }
+
+    /// Validates a transaction (the same payload as in CheckTx) against the committed state;
+    /// the staking changes are made in a temporary buffer and nothing is persisted
+    /// (the node-local mempool policy isn't applied)
+    fn simulate_tx(
+        &mut self,
+        mut state: ChainNodeState,
+        payload: &[u8],
+    ) -> Result<(TxAux, TxAction), TxError> {
+        let extra_info = ChainInfo {
+            min_fee_computed: state
+                .top_level
+                .network_params
+                .calculate_fee(payload.len())
+                .expect("invalid fee policy"),
+            chain_hex_id: self.chain_hex_id,
+            block_time: state.block_time,
+            block_height: state.block_height,
+            max_evidence_age: state.max_evidence_age,
+        };
+        let (version, txaux) = TxAux::decode_versioned(&mut &payload[..])?;
+        check_tx_activation(
+            version,
+            &txaux,
+            state.scheduled_upgrade.as_ref(),
+            extra_info.block_height,
+        )?;
+        let tx_action = match &txaux {
+            TxAux::MLSHandshake(_) => return Err(TxError::WIPMLSData),
+            TxAux::EnclaveTx(tx) => {
+                let action = verify_enclave_tx(
+                    &mut self.tx_validator,
+                    tx,
+                    &extra_info,
+                    &StakingGetter::new(&self.storage, state.staking_version),
+                    &self.storage,
+                )?;
+                check_unfrozen_withdraw(&action, &state.account_freeze)?;
+                TxAction::Enclave(action)
+            }
+            TxAux::PublicTx(tx) => {
+                let mut staking_buffer = StakingBuffer::new();
+                TxAction::Public(process_public_tx(
+                    &mut StakingBufferStore::new(
+                        StakingGetter::new(&self.storage, state.staking_version),
+                        &mut staking_buffer,
+                    ),
+                    &mut state.staking_table,
+                    state.enclave_isv_svn,
+                    &state.top_level.network_params,
+                    state.pending_params_update.as_ref(),
+                    state.scheduled_upgrade.as_ref(),
+                    &state.account_freeze,
+                    &extra_info,
+                    tx,
+                )?)
+            }
+        };
+        Ok((txaux, tx_action))
+    }
+
+    /// "simulate_tx" ABCI query -- data: the raw transaction (as in CheckTx),
+    /// value: JSON-encoded outcome (the computed fee or the rejection reason)
+    pub(super) fn simulate_tx_query(&mut self, payload: &[u8], resp: &mut ResponseQuery) {
+        let state = match self.committed_state() {
+            Some(state) => state,
+            None => {
+                resp.code = 1;
+                resp.log += "no committed state";
+                return;
+            }
+        };
+        let simulation = match self.simulate_tx(state, payload) {
+            Ok((txaux, tx_action)) => TxSimulation::Accepted {
+                txid: hex::encode(txaux.tx_id()),
+                tx_type: TxAttributes::from(&txaux).tx_type.to_string(),
+                fee: tx_action.fee().to_coin(),
+            },
+            Err(e) => {
+                resp.code = 1;
+                resp.log += &e.to_string();
+                TxSimulation::Rejected {
+                    txid: TxAux::decode(&mut &payload[..])
+                        .ok()
+                        .map(|txaux| hex::encode(txaux.tx_id())),
+                    error: e.kind(),
+                    reason: e.to_string(),
+                }
+            }
+        };
+        resp.value = serde_json::to_vec(&simulation).expect("serialize tx simulation");
+    }
+}
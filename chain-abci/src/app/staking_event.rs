@@ -18,6 +18,7 @@ pub(crate) enum StakingEvent<'a> {
     Jail(&'a StakedStateAddress, Timespec, PunishmentKind),
     Slash(&'a StakedStateAddress, Coin, Coin, PunishmentKind),
     Unjail(&'a StakedStateAddress),
+    Freeze(&'a StakedStateAddress, bool),
 }
 
 impl<'a> From<StakingEvent<'a>> for Event {
@@ -55,6 +56,9 @@ impl<'a> From<StakingEvent<'a>> for Event {
                 punishment_kind,
             ),
             StakingEvent::Unjail(staking_address) => builder.unjail(staking_address),
+            StakingEvent::Freeze(staking_address, frozen) => {
+                builder.freeze(staking_address, frozen)
+            }
         }
 
         builder.to_event()
@@ -188,6 +192,16 @@ impl StakingEventBuilder {
         self.attributes.push(StakingEventOpType::Unjail.into());
     }
 
+    fn freeze(&mut self, staking_address: &StakedStateAddress, frozen: bool) {
+        self.attributes
+            .push(staking_address_attribute(staking_address));
+        if frozen {
+            self.attributes.push(StakingEventOpType::Freeze.into());
+        } else {
+            self.attributes.push(StakingEventOpType::Unfreeze.into());
+        }
+    }
+
     fn to_event(&self) -> Event {
         let mut event = Event::new();
         event.field_type = TendermintEventType::StakingChange.to_string();
@@ -225,6 +239,8 @@ enum StakingEventOpType {
     Jail,
     Slash,
     Unjail,
+    Freeze,
+    Unfreeze,
 }
 
 impl fmt::Display for StakingEventOpType {
@@ -238,6 +254,8 @@ impl fmt::Display for StakingEventOpType {
             StakingEventOpType::Jail => write!(f, "jail"),
             StakingEventOpType::Slash => write!(f, "slash"),
             StakingEventOpType::Unjail => write!(f, "unjail"),
+            StakingEventOpType::Freeze => write!(f, "freeze"),
+            StakingEventOpType::Unfreeze => write!(f, "unfreeze"),
         }
     }
 }
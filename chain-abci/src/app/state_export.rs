@@ -0,0 +1,295 @@
+//! Export of the committed state into the genesis of an upgraded chain (see `ExportedState`),
+//! and the initialization of a chain from such a genesis.
+//!
+//! The sealed transaction payloads are not exported: the enclaves of the upgraded chain
+//! obtain them via the transaction data bootstrapping (TDBE) from the nodes of the exported chain.
+use std::collections::BTreeMap;
+use std::mem;
+
+use abci::{RequestInitChain, ResponseInitChain, ValidatorUpdate};
+use bit_vec::BitVec;
+use log::info;
+use parity_scale_codec::Decode;
+
+use super::app_init::{check_and_store_consensus_params, get_validator_key, validators_match};
+use super::{ChainNodeApp, ChainNodeState};
+use crate::enclave_bridge::EnclaveProxy;
+use crate::staking::StakingTable;
+use chain_core::common::{Timespec, H256};
+use chain_core::init::export::ExportedState;
+use chain_core::state::account::{CouncilNodeMeta, NodeState, StakedStateAddress};
+use chain_core::state::tendermint::{
+    BlockHeight, TendermintValidator, TendermintValidatorAddress, TendermintVotePower,
+};
+use chain_core::state::{UtxoChange, GENESIS_UTXO_COMMITMENT};
+use chain_core::tx::data::input::TxoPointer;
+use chain_core::UTXO_SET_COMMITTED;
+use chain_storage::buffer::{flush_storage, GetKV, MemStore, StoreKV};
+use chain_storage::jellyfish::{compute_staking_root, iter_stakings};
+use chain_storage::utxo_mmr::apply_utxo_changes;
+use chain_storage::{Storage, COL_TX_META};
+
+/// Exports the last committed state of the storage, which must be at the height
+pub fn export_state(storage: &Storage, height: BlockHeight) -> Result<ExportedState, String> {
+    let raw = storage
+        .get_last_app_state()
+        .ok_or_else(|| "no committed state in the storage".to_owned())?;
+    let state = ChainNodeState::decode(&mut raw.as_slice())
+        .map_err(|e| format!("failed to decode the app state: {}", e))?;
+    if state.last_block_height != height {
+        return Err(format!(
+            "only the last committed height ({}) can be exported",
+            state.last_block_height
+        ));
+    }
+    let exported_chain_id = String::from_utf8(storage.get_stored_chain_id())
+        .map_err(|_| "invalid stored chain id".to_owned())?;
+
+    let mut unspent_outputs = Vec::new();
+    for (txid, spent) in storage.iter_with_prefix(COL_TX_META, &[]) {
+        let mut id = [0u8; 32];
+        id.copy_from_slice(&txid);
+        unspent_outputs.extend(unspent_outputs_of(id, &BitVec::from_bytes(&spent)));
+    }
+
+    let mut rewards_pool = state.top_level.rewards_pool.clone();
+    rewards_pool.last_block_height = BlockHeight::genesis();
+    let exported = ExportedState {
+        exported_chain_id,
+        exported_height: height,
+        network_params: state.top_level.network_params.clone(),
+        accounts: iter_stakings(storage, state.staking_version).collect(),
+        rewards_pool,
+        unspent_outputs,
+        utxo_coins: state.utxo_coins,
+        enclave_isv_svn: state.enclave_isv_svn,
+    };
+    exported.validate().map_err(|e| e.to_string())?;
+    Ok(exported)
+}
+
+/// The unspent outputs of the transaction from its stored spent flags: the bit vector
+/// is padded to whole bytes, and as the number of outputs isn't stored, the padding
+/// is exported as unspent (the same padded flags are then stored by `store_exported_utxos`,
+/// and the enclaves reject the outputs that aren't in the sealed transaction)
+fn unspent_outputs_of(txid: [u8; 32], spent: &BitVec) -> Vec<TxoPointer> {
+    spent
+        .iter()
+        .enumerate()
+        .filter(|(_, is_spent)| !is_spent)
+        .map(|(index, _)| TxoPointer::new(txid, index))
+        .collect()
+}
+
+/// Stores the spent flags of the transaction outputs, and returns the utxo commitment
+/// (the mountain range is only maintained if the app hash commits to it)
+pub fn store_exported_utxos(db: &mut impl StoreKV, exported: &ExportedState) -> H256 {
+    let changes = exported.utxo_changes();
+    let mut spent = Vec::new();
+    for change in changes.iter() {
+        match change {
+            UtxoChange::Created {
+                txid,
+                no_of_outputs,
+            } => chain_storage::create_utxo(db, *no_of_outputs, txid),
+            UtxoChange::Spent(txo) => spent.push(txo.clone()),
+        }
+    }
+    chain_storage::spend_utxos(db, &spent);
+    if UTXO_SET_COMMITTED {
+        apply_utxo_changes(db, &changes)
+    } else {
+        GENESIS_UTXO_COMMITMENT
+    }
+}
+
+/// The council nodes of the exported staked states, and the validators chosen among them
+fn exported_validators(
+    exported: &ExportedState,
+    council_nodes: &[StakedStateAddress],
+) -> (Vec<(StakedStateAddress, CouncilNodeMeta)>, StakingTable) {
+    let heap = MemStore(
+        exported
+            .accounts
+            .iter()
+            .map(|staking| (staking.address, staking.clone()))
+            .collect(),
+    );
+    let staking_table = StakingTable::from_genesis(
+        &heap,
+        exported.network_params.get_required_council_node_stake(),
+        exported.network_params.get_max_validators(),
+        council_nodes,
+    );
+    let nodes = exported
+        .accounts
+        .iter()
+        .filter_map(|account| match &account.node_meta {
+            Some(NodeState::CouncilNode(validator)) => {
+                Some((account.address, validator.council_node.clone()))
+            }
+            _ => None,
+        })
+        .collect();
+    (nodes, staking_table)
+}
+
+/// The genesis validators of the upgraded chain
+pub fn exported_genesis_validators(
+    exported: &ExportedState,
+) -> Result<Vec<TendermintValidator>, String> {
+    let council_nodes = exported.validate().map_err(|e| e.to_string())?;
+    let (nodes, staking_table) = exported_validators(exported, &council_nodes);
+    Ok(
+        chosen_validators(&nodes, staking_table.get_chosen_validators())
+            .map(|(node, power)| TendermintValidator {
+                address: TendermintValidatorAddress::from(&node.consensus_pubkey),
+                name: node.node_info.name.clone(),
+                power,
+                pub_key: node.consensus_pubkey.clone(),
+            })
+            .collect(),
+    )
+}
+
+fn chosen_validators<'a>(
+    nodes: &'a [(StakedStateAddress, CouncilNodeMeta)],
+    chosen: &'a BTreeMap<StakedStateAddress, TendermintVotePower>,
+) -> impl Iterator<Item = (&'a CouncilNodeMeta, TendermintVotePower)> + 'a {
+    nodes
+        .iter()
+        .filter_map(move |(address, node)| chosen.get(address).map(|power| (node, *power)))
+}
+
+/// The genesis state of the upgraded chain (its staked states are expected to be stored
+/// at version 0 with the root `account_root`)
+fn exported_genesis_state(
+    exported: &ExportedState,
+    account_root: H256,
+    utxo_commitment: H256,
+    staking_table: StakingTable,
+    genesis_time: Timespec,
+    max_evidence_age: Timespec,
+) -> ChainNodeState {
+    let mut state = ChainNodeState::genesis(
+        genesis_time,
+        max_evidence_age,
+        account_root,
+        exported.rewards_pool.clone(),
+        exported.network_params.clone(),
+        staking_table,
+        exported.enclave_isv_svn,
+    );
+    state.utxo_coins = exported.utxo_coins;
+    state.top_level.utxo_commitment = utxo_commitment;
+    state.last_apphash = state.top_level.compute_app_hash(vec![]);
+    state
+}
+
+/// Computes the genesis app hash of the chain upgraded from the exported state
+/// (the genesis time is not included in it)
+pub fn exported_genesis_app_hash(exported: &ExportedState) -> Result<H256, String> {
+    let council_nodes = exported.validate().map_err(|e| e.to_string())?;
+    let (_, staking_table) = exported_validators(exported, &council_nodes);
+    let mut utxos: MemStore<(u32, Vec<u8>), Vec<u8>> = MemStore::new();
+    let utxo_commitment = store_exported_utxos(&mut utxos, exported);
+    let state = exported_genesis_state(
+        exported,
+        compute_staking_root(&exported.accounts),
+        utxo_commitment,
+        staking_table,
+        0,
+        0,
+    );
+    Ok(state.last_apphash)
+}
+
+impl<T: EnclaveProxy + 'static> ChainNodeApp<T> {
+    /// Handles InitChain requests with an exported state in app_state
+    pub fn init_chain_from_export(
+        &mut self,
+        req: &RequestInitChain,
+        exported: ExportedState,
+        genesis_time: Timespec,
+        max_evidence_age: Timespec,
+    ) -> ResponseInitChain {
+        let council_nodes = exported
+            .validate()
+            .expect("exported state validation error");
+        if exported.exported_chain_id == req.chain_id {
+            panic!(
+                "the upgraded chain needs a new chain id (exported: {})",
+                exported.exported_chain_id
+            );
+        }
+        info!(
+            "initializing from the exported state of {} at height {}",
+            exported.exported_chain_id, exported.exported_height
+        );
+
+        let (nodes, staking_table) = exported_validators(&exported, &council_nodes);
+        let account_root = self.storage.put_stakings(0, &exported.accounts);
+        let utxo_commitment = store_exported_utxos(&mut kv_store!(self), &exported);
+        let genesis_state = exported_genesis_state(
+            &exported,
+            account_root,
+            utxo_commitment,
+            staking_table,
+            genesis_time,
+            max_evidence_age,
+        );
+        if self.genesis_app_hash != genesis_state.last_apphash {
+            panic!("initchain resulting genesis app hash: {} does not match the expected genesis app hash: {}", hex::encode(genesis_state.last_apphash), hex::encode(self.genesis_app_hash));
+        }
+
+        let validators =
+            chosen_validators(&nodes, genesis_state.staking_table.get_chosen_validators())
+                .map(|(node, power)| {
+                    let mut validator = ValidatorUpdate::default();
+                    validator.set_power(power.into());
+                    validator.set_pub_key(get_validator_key(node));
+                    validator
+                })
+                .collect();
+        if !validators_match(validators, req.validators.clone().into_vec()) {
+            panic!(
+                "validators in genesis configuration are not consistent with the exported state"
+            );
+        }
+        check_and_store_consensus_params(
+            req.consensus_params.as_ref(),
+            &nodes,
+            &exported.network_params,
+            &mut self.storage,
+        );
+
+        chain_storage::store_genesis_state(
+            &mut kv_store!(self),
+            &genesis_state,
+            self.tx_query_address.is_some(),
+        );
+        flush_storage(&mut self.storage, mem::take(&mut self.kv_buffer)).expect("storage io error");
+
+        self.last_state = Some(genesis_state);
+        self.mempool_state = self.last_state.clone();
+        ResponseInitChain::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_unspent_outputs_of() {
+        let txid = [1u8; 32];
+        // padded to 8 bits
+        let mut spent = BitVec::from_elem(3, false);
+        spent.set(1, true);
+        let spent = BitVec::from_bytes(&spent.to_bytes());
+        let outputs = unspent_outputs_of(txid, &spent);
+        assert_eq!(7, outputs.len());
+        assert_eq!(TxoPointer::new(txid, 0), outputs[0]);
+        assert_eq!(TxoPointer::new(txid, 2), outputs[1]);
+    }
+}
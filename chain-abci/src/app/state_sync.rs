@@ -0,0 +1,342 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use kvdb::KeyValueDB;
+use serde::Serialize;
+
+use super::backup::list_backups;
+use chain_core::common::H256;
+use chain_storage::backup::{
+    SnapshotChunks, SnapshotManifest, SnapshotRestore, DEFAULT_CHUNK_SIZE, SNAPSHOT_FORMAT,
+};
+
+const RESTORE_DIR: &str = "restore";
+
+/// Snapshot offered to other nodes (fields as in Tendermint's state sync `Snapshot`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SnapshotInfo {
+    pub height: u64,
+    pub format: u32,
+    pub chunks: u32,
+    /// blake3 hash of the chunk hashes
+    #[serde(serialize_with = "serialize_hex")]
+    pub hash: H256,
+    /// concatenated blake3 hashes of the chunks
+    #[serde(serialize_with = "serialize_hex")]
+    pub metadata: Vec<u8>,
+}
+
+fn serialize_hex<S: serde::Serializer, T: AsRef<[u8]>>(
+    data: &T,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&hex::encode(data))
+}
+
+impl SnapshotInfo {
+    fn chunk_hashes(&self) -> Option<Vec<H256>> {
+        if self.metadata.len() != self.chunks as usize * 32 {
+            return None;
+        }
+        let hashes: Vec<H256> = self
+            .metadata
+            .chunks(32)
+            .map(|chunk| {
+                let mut hash = H256::default();
+                hash.copy_from_slice(chunk);
+                hash
+            })
+            .collect();
+        if snapshot_hash(&hashes) == self.hash {
+            Some(hashes)
+        } else {
+            None
+        }
+    }
+}
+
+fn snapshot_hash(chunk_hashes: &[H256]) -> H256 {
+    *blake3::hash(&chunk_hashes.concat()).as_bytes()
+}
+
+/// Response to a snapshot offer (as in Tendermint's `ResponseOfferSnapshot`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OfferSnapshotResult {
+    Accept,
+    Abort,
+    Reject,
+    RejectFormat,
+}
+
+/// Response to an applied chunk (as in Tendermint's `ResponseApplySnapshotChunk`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApplyChunkResult {
+    Accept,
+    Abort,
+    Retry,
+    RejectSnapshot,
+}
+
+struct PendingRestore {
+    snapshot: SnapshotInfo,
+    app_hash: H256,
+    restore: SnapshotRestore,
+}
+
+/// State sync over the storage backups (see `BackupScheduler`):
+/// the backups are served as chunked snapshots, and a fresh node can restore its storage
+/// from the chunks of an offered snapshot.
+///
+/// The handlers follow Tendermint's state sync ABCI methods
+/// (ListSnapshots / LoadSnapshotChunk / OfferSnapshot / ApplySnapshotChunk),
+/// which aren't available in the ABCI version currently used (Tendermint 0.33),
+/// so snapshots are served via the "snapshots" and "snapshot-chunk" query paths meanwhile,
+/// and restored from a copy of the backup directory by `restore_from`
+/// (the "restore-snapshot" command). After a completed restore, the node has to be restarted
+/// to load the restored state.
+pub struct StateSync {
+    directory: PathBuf,
+    chunk_size: u64,
+    /// snapshots by height (computing the chunk hashes reads the whole snapshot)
+    snapshots: RefCell<BTreeMap<u64, SnapshotInfo>>,
+    pending: Option<PendingRestore>,
+}
+
+impl StateSync {
+    /// `directory` is the backup directory
+    pub fn new(directory: &Path) -> Self {
+        StateSync::with_chunk_size(directory, DEFAULT_CHUNK_SIZE)
+    }
+
+    pub fn with_chunk_size(directory: &Path, chunk_size: u64) -> Self {
+        StateSync {
+            directory: directory.to_owned(),
+            chunk_size,
+            snapshots: RefCell::new(BTreeMap::new()),
+            pending: None,
+        }
+    }
+
+    fn open_chunks(&self, path: &Path) -> std::io::Result<SnapshotChunks> {
+        SnapshotChunks::open(path, self.chunk_size)
+    }
+
+    /// Snapshots of the available backups
+    pub fn list_snapshots(&self) -> Vec<SnapshotInfo> {
+        let backups = match list_backups(&self.directory) {
+            Ok(backups) => backups,
+            Err(e) => {
+                log::warn!("failed to list backups: {}", e);
+                return vec![];
+            }
+        };
+        let mut snapshots = BTreeMap::new();
+        for path in backups {
+            let info = self.open_chunks(&path).and_then(|chunks| {
+                let height = chunks.manifest().height.value();
+                if let Some(info) = self.snapshots.borrow().get(&height) {
+                    return Ok(info.clone());
+                }
+                let chunk_hashes = chunks.chunk_hashes()?;
+                Ok(SnapshotInfo {
+                    height,
+                    format: SNAPSHOT_FORMAT,
+                    chunks: chunks.count(),
+                    hash: snapshot_hash(&chunk_hashes),
+                    metadata: chunk_hashes.concat(),
+                })
+            });
+            match info {
+                Ok(info) => {
+                    snapshots.insert(info.height, info);
+                }
+                Err(e) => log::warn!("invalid backup {}: {}", path.display(), e),
+            }
+        }
+        let list = snapshots.values().cloned().collect();
+        self.snapshots.replace(snapshots);
+        list
+    }
+
+    /// Chunk of the snapshot at the given height (if available)
+    pub fn load_chunk(&self, height: u64, format: u32, index: u32) -> Option<Vec<u8>> {
+        if format != SNAPSHOT_FORMAT {
+            return None;
+        }
+        let path = list_backups(&self.directory)
+            .ok()?
+            .into_iter()
+            .find(|path| {
+                SnapshotManifest::read(path)
+                    .map(|manifest| manifest.height.value() == height)
+                    .unwrap_or(false)
+            })?;
+        self.open_chunks(&path).ok()?.chunk(index).ok()
+    }
+
+    /// Starts restoring the snapshot, which should result in the (trusted) app hash
+    pub fn offer_snapshot(
+        &mut self,
+        snapshot: &SnapshotInfo,
+        app_hash: H256,
+    ) -> OfferSnapshotResult {
+        if snapshot.format != SNAPSHOT_FORMAT {
+            return OfferSnapshotResult::RejectFormat;
+        }
+        let chunk_hashes = match snapshot.chunk_hashes() {
+            Some(chunk_hashes) => chunk_hashes,
+            None => return OfferSnapshotResult::Reject,
+        };
+        match SnapshotRestore::new(&self.directory.join(RESTORE_DIR), chunk_hashes) {
+            Ok(restore) => {
+                self.pending = Some(PendingRestore {
+                    snapshot: snapshot.clone(),
+                    app_hash,
+                    restore,
+                });
+                OfferSnapshotResult::Accept
+            }
+            Err(e) => {
+                log::error!("failed to start snapshot restore: {}", e);
+                OfferSnapshotResult::Abort
+            }
+        }
+    }
+
+    /// Applies the chunk of the offered snapshot; the storage is imported into `db`
+    /// once all chunks are applied
+    pub fn apply_chunk(
+        &mut self,
+        db: &dyn KeyValueDB,
+        index: u32,
+        chunk: &[u8],
+    ) -> ApplyChunkResult {
+        let pending = match self.pending.as_mut() {
+            Some(pending) => pending,
+            None => return ApplyChunkResult::Abort,
+        };
+        if let Err(e) = pending.restore.apply_chunk(index, chunk) {
+            log::warn!("invalid snapshot chunk {}: {}", index, e);
+            return ApplyChunkResult::Retry;
+        }
+        if !pending.restore.is_complete() {
+            return ApplyChunkResult::Accept;
+        }
+        let pending = self.pending.take().expect("pending restore");
+        match pending.restore.finish(db) {
+            Ok(manifest)
+                if manifest.app_hash == pending.app_hash
+                    && manifest.height.value() == pending.snapshot.height =>
+            {
+                log::info!("restored snapshot at height {}", manifest.height);
+                ApplyChunkResult::Accept
+            }
+            Ok(_) => {
+                log::error!("restored snapshot doesn't match the trusted app hash");
+                ApplyChunkResult::RejectSnapshot
+            }
+            Err(e) => {
+                log::error!("failed to import snapshot: {}", e);
+                ApplyChunkResult::RejectSnapshot
+            }
+        }
+    }
+
+    /// Restores the snapshot at the height from the source (e.g. the backups copied
+    /// from another node) into the empty `db` via `offer_snapshot` and `apply_chunk`
+    pub fn restore_from(
+        &mut self,
+        source: &StateSync,
+        height: u64,
+        app_hash: H256,
+        db: &dyn KeyValueDB,
+    ) -> Result<(), String> {
+        let snapshot = source
+            .list_snapshots()
+            .into_iter()
+            .find(|snapshot| snapshot.height == height)
+            .ok_or_else(|| format!("no snapshot at height {}", height))?;
+        match self.offer_snapshot(&snapshot, app_hash) {
+            OfferSnapshotResult::Accept => {}
+            result => return Err(format!("snapshot not accepted: {:?}", result)),
+        }
+        for index in 0..snapshot.chunks {
+            let chunk = source
+                .load_chunk(height, snapshot.format, index)
+                .ok_or_else(|| format!("snapshot chunk {} not found", index))?;
+            match self.apply_chunk(db, index, &chunk) {
+                ApplyChunkResult::Accept => {}
+                result => {
+                    self.pending = None;
+                    return Err(format!(
+                        "snapshot chunk {} not applied: {:?}",
+                        index, result
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chain_core::state::tendermint::BlockHeight;
+    use chain_storage::backup::export_snapshot;
+    use chain_storage::{COL_NODE_INFO, NUM_COLUMNS};
+
+    #[test]
+    fn check_state_sync_roundtrip() {
+        let directory =
+            std::env::temp_dir().join(format!("chain-abci-state-sync-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        let db = kvdb_memorydb::create(NUM_COLUMNS);
+        let mut tx = db.transaction();
+        tx.put(COL_NODE_INFO, b"key", b"value");
+        db.write(tx).unwrap();
+        export_snapshot(
+            &db,
+            &directory.join("backup-00000000000000000007"),
+            BlockHeight::new(7),
+            [7u8; 32],
+        )
+        .unwrap();
+
+        let mut state_sync = StateSync::with_chunk_size(&directory, 32);
+        let snapshots = state_sync.list_snapshots();
+        assert_eq!(1, snapshots.len());
+        let snapshot = snapshots[0].clone();
+        assert_eq!(7, snapshot.height);
+
+        let restored = kvdb_memorydb::create(NUM_COLUMNS);
+        assert_eq!(
+            OfferSnapshotResult::Accept,
+            state_sync.offer_snapshot(&snapshot, [7u8; 32])
+        );
+        assert_eq!(
+            ApplyChunkResult::Retry,
+            state_sync.apply_chunk(&restored, 0, b"invalid")
+        );
+        for index in 0..snapshot.chunks {
+            let chunk = state_sync.load_chunk(7, SNAPSHOT_FORMAT, index).unwrap();
+            assert_eq!(
+                ApplyChunkResult::Accept,
+                state_sync.apply_chunk(&restored, index, &chunk)
+            );
+        }
+        assert_eq!(
+            Some(b"value".to_vec()),
+            restored.get(COL_NODE_INFO, b"key").unwrap()
+        );
+
+        let mut tampered = snapshot;
+        tampered.hash = [0u8; 32];
+        assert_eq!(
+            OfferSnapshotResult::Reject,
+            state_sync.offer_snapshot(&tampered, [7u8; 32])
+        );
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
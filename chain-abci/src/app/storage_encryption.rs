@@ -0,0 +1,41 @@
+use std::io;
+use std::path::Path;
+use std::process::Command;
+
+use serde::{Deserialize, Serialize};
+
+use chain_storage::encryption::{KeyRing, StorageEncryption};
+
+/// Encryption at rest of the storage columns with sealed transaction payloads and transaction
+/// metadata (the `storage_encryption` section of the configuration file), see `chain_storage::encryption`
+///
+/// Backups contain the encrypted values, so the keys are needed to use them.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct StorageEncryptionConfig {
+    /// key file with one `<key id> <64 hex digits>` per line (the last key is used for writing)
+    pub key_file: Option<String>,
+    /// command printing the keys in the key file format to its standard output
+    /// (e.g. decrypting the key file with a KMS); used if `key_file` is not set
+    pub key_command: Option<String>,
+}
+
+impl StorageEncryptionConfig {
+    /// None if the encryption is not configured
+    pub fn load(&self) -> io::Result<Option<StorageEncryption>> {
+        let keys = match (&self.key_file, &self.key_command) {
+            (Some(key_file), _) => KeyRing::from_file(Path::new(key_file))?,
+            (None, Some(key_command)) => {
+                let output = Command::new("sh").arg("-c").arg(key_command).output()?;
+                if !output.status.success() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::Other,
+                        format!("storage encryption key command failed: {}", output.status),
+                    ));
+                }
+                KeyRing::parse(&String::from_utf8_lossy(&output.stdout))?
+            }
+            (None, None) => return Ok(None),
+        };
+        StorageEncryption::new(keys).map(Some)
+    }
+}
@@ -0,0 +1,101 @@
+use std::env;
+use std::time::Duration;
+
+use serde_json::json;
+
+use super::check_tx_cache::CheckTxCache;
+use chain_core::state::tendermint::BlockHeight;
+use chain_storage::metrics::{HistogramSnapshot, StorageOp, LATENCY_BUCKETS_MICROS};
+use chain_storage::Storage;
+
+/// Storage operations slower than this number of microseconds are logged at commit
+/// (slow operation tracing is disabled if not set)
+pub const SLOW_STORAGE_OP_ENV: &str = "CRYPTO_CHAIN_SLOW_STORAGE_OP_MICROS";
+
+/// Maximum number of slow operations logged individually per block
+const SLOW_OP_LOG_LIMIT: usize = 10;
+
+/// Enables the slow operation tracing if configured in `CRYPTO_CHAIN_SLOW_STORAGE_OP_MICROS`
+pub fn configure_storage_metrics(storage: &Storage) {
+    let threshold = env::var(SLOW_STORAGE_OP_ENV)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|micros| *micros > 0)
+        .map(Duration::from_micros);
+    if let Some(threshold) = threshold {
+        log::info!("tracing storage operations slower than {:?}", threshold);
+    }
+    storage.metrics().set_slow_threshold(threshold);
+}
+
+/// Logs the storage operations which exceeded the threshold since the previous commit
+pub fn log_slow_storage_ops(storage: &Storage, height: BlockHeight) {
+    let slow_ops = storage.metrics().take_slow_ops();
+    if slow_ops.is_empty() {
+        return;
+    }
+    let total_micros: u64 = slow_ops.iter().map(|op| op.micros).sum();
+    log::warn!(
+        "{} slow storage operations (total {} us) in block {}",
+        slow_ops.len(),
+        total_micros,
+        height
+    );
+    for slow_op in slow_ops.iter().take(SLOW_OP_LOG_LIMIT) {
+        match slow_op.op {
+            StorageOp::Get(column) => {
+                log::warn!(
+                    "slow storage read in column {}: {} us",
+                    column,
+                    slow_op.micros
+                )
+            }
+            StorageOp::Write => log::warn!("slow storage write: {} us", slow_op.micros),
+        }
+    }
+}
+
+fn histogram_json(histogram: &HistogramSnapshot) -> serde_json::Value {
+    let buckets: Vec<serde_json::Value> = histogram
+        .buckets
+        .iter()
+        .enumerate()
+        .map(|(i, count)| {
+            let le = LATENCY_BUCKETS_MICROS
+                .get(i)
+                .map(|bound| bound.to_string())
+                .unwrap_or_else(|| "+Inf".to_owned());
+            json!({ "le_micros": le, "count": count })
+        })
+        .collect();
+    json!({
+        "count": histogram.count,
+        "sum_micros": histogram.sum_micros,
+        "buckets": buckets,
+    })
+}
+
+/// JSON dump of the storage access metrics (the "storage-metrics" ABCI query path)
+pub fn dump_storage_metrics(storage: &Storage, check_tx_cache: &CheckTxCache) -> String {
+    let snapshot = storage.metrics().snapshot();
+    let reads: Vec<serde_json::Value> = snapshot
+        .reads
+        .iter()
+        .map(|column| {
+            json!({
+                "column": column.column,
+                "hits": column.hits,
+                "misses": column.misses,
+                "latency": histogram_json(&column.latency),
+            })
+        })
+        .collect();
+    let (cache_hits, cache_misses) = check_tx_cache.stats();
+    json!({
+        "reads": reads,
+        "writes": histogram_json(&snapshot.writes),
+        "slow_threshold_micros": snapshot.slow_threshold_micros,
+        "check_tx_cache": { "hits": cache_hits, "misses": cache_misses },
+    })
+    .to_string()
+}
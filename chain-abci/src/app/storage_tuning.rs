@@ -0,0 +1,78 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use chain_storage::tuning::{column_by_name, CompactionStyle, DbTuning};
+
+/// Tuning of the RocksDB database (the `storage_tuning` section of the configuration file),
+/// see `chain_storage::tuning`; the unset options keep their defaults
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct StorageTuningConfig {
+    /// `auto` (detected from the disk), `ssd` or `hdd`
+    pub compaction: Option<String>,
+    /// size of the data blocks (in KiB), overrides the one of the compaction style
+    pub block_size_kb: Option<usize>,
+    pub max_open_files: Option<i32>,
+    pub keep_log_file_num: Option<i32>,
+    #[serde(default)]
+    pub enable_statistics: bool,
+    /// memory budget (in MiB) of the columns without their own budget
+    pub column_memory_budget_mb: Option<usize>,
+    /// memory budgets (in MiB) by column name (e.g. `bodies` or `tx_meta`)
+    #[serde(default)]
+    pub column_memory_budgets_mb: BTreeMap<String, usize>,
+}
+
+impl StorageTuningConfig {
+    pub fn tuning(&self) -> Result<DbTuning, String> {
+        let mut tuning = DbTuning::default();
+        if let Some(compaction) = self.compaction.as_ref() {
+            tuning.compaction = compaction.parse::<CompactionStyle>()?;
+        }
+        tuning.block_size_kb = self.block_size_kb;
+        if let Some(max_open_files) = self.max_open_files {
+            tuning.max_open_files = max_open_files;
+        }
+        if let Some(keep_log_file_num) = self.keep_log_file_num {
+            tuning.keep_log_file_num = keep_log_file_num;
+        }
+        tuning.enable_statistics = self.enable_statistics;
+        if let Some(column_budget_mb) = self.column_memory_budget_mb {
+            tuning.column_budget_mb = column_budget_mb;
+        }
+        for (name, budget_mb) in self.column_memory_budgets_mb.iter() {
+            let col = column_by_name(name).ok_or_else(|| format!("unknown column: {}", name))?;
+            tuning.column_budgets_mb.insert(col, *budget_mb);
+        }
+        Ok(tuning)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chain_storage::{COL_BODIES, COL_EXTRA, COL_TX_META};
+
+    #[test]
+    fn check_storage_tuning_config() {
+        let config: StorageTuningConfig = serde_yaml::from_str(
+            "compaction: hdd\ncolumn_memory_budget_mb: 64\ncolumn_memory_budgets_mb:\n  bodies: 512\n",
+        )
+        .unwrap();
+        let tuning = config.tuning().unwrap();
+        assert_eq!(CompactionStyle::Hdd, tuning.compaction);
+        assert_eq!(512, tuning.column_budget_mb(COL_BODIES));
+        assert_eq!(
+            DbTuning::default().column_budget_mb(COL_TX_META),
+            tuning.column_budget_mb(COL_TX_META)
+        );
+        assert_eq!(64, tuning.column_budget_mb(COL_EXTRA));
+
+        let mut config = StorageTuningConfig::default();
+        assert_eq!(DbTuning::default(), config.tuning().unwrap());
+        config
+            .column_memory_budgets_mb
+            .insert("unknown".to_owned(), 1);
+        assert!(config.tuning().is_err());
+    }
+}
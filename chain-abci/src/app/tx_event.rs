@@ -0,0 +1,488 @@
+use std::fmt;
+
+use abci::Event;
+use abci::Pair as KVPair;
+
+use crate::app::staking_event::StakingEvent;
+use crate::storage::{TxAction, TxEnclaveAction, TxPublicAction};
+use chain_core::common::{TendermintEventKey, TendermintEventType};
+use chain_core::tx::{TxAux, TxEnclaveAux, TxPublicAux};
+
+/// Transaction type tagged in the "valid_txs" event
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TxType {
+    Transfer,
+    Deposit,
+    Withdraw,
+    Unbond,
+    Unjail,
+    NodeJoin,
+    NetworkParamsUpdate,
+    UpgradeSignal,
+    DataAnchor,
+    AccountFreeze,
+    MLSHandshake,
+}
+
+impl fmt::Display for TxType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TxType::Transfer => write!(f, "transfer"),
+            TxType::Deposit => write!(f, "deposit"),
+            TxType::Withdraw => write!(f, "withdraw"),
+            TxType::Unbond => write!(f, "unbond"),
+            TxType::Unjail => write!(f, "unjail"),
+            TxType::NodeJoin => write!(f, "nodejoin"),
+            TxType::NetworkParamsUpdate => write!(f, "params_update"),
+            TxType::UpgradeSignal => write!(f, "upgrade_signal"),
+            TxType::DataAnchor => write!(f, "data_anchor"),
+            TxType::AccountFreeze => write!(f, "account_freeze"),
+            TxType::MLSHandshake => write!(f, "mls_handshake"),
+        }
+    }
+}
+
+/// Public attributes of a transaction (the outputs and the view keys of the enclave
+/// transactions are only visible inside the enclave, apart from the number of outputs)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct TxAttributes {
+    pub tx_type: TxType,
+    /// number of the spent transaction outputs (transfer / deposit)
+    pub input_count: Option<usize>,
+    /// number of the created transaction outputs (transfer / withdraw)
+    pub output_count: Option<u16>,
+}
+
+impl From<&TxAux> for TxAttributes {
+    fn from(txaux: &TxAux) -> Self {
+        let (tx_type, input_count, output_count) = match txaux {
+            TxAux::EnclaveTx(TxEnclaveAux::TransferTx {
+                inputs,
+                no_of_outputs,
+                ..
+            }) => (TxType::Transfer, Some(inputs.len()), Some(*no_of_outputs)),
+            TxAux::EnclaveTx(TxEnclaveAux::DepositStakeTx { tx, .. }) => {
+                (TxType::Deposit, Some(tx.inputs.len()), None)
+            }
+            TxAux::EnclaveTx(TxEnclaveAux::WithdrawUnbondedStakeTx { no_of_outputs, .. }) => {
+                (TxType::Withdraw, None, Some(*no_of_outputs))
+            }
+            TxAux::PublicTx(TxPublicAux::UnbondStakeTx(..)) => (TxType::Unbond, None, None),
+            TxAux::PublicTx(TxPublicAux::UnjailTx(..)) => (TxType::Unjail, None, None),
+            TxAux::PublicTx(TxPublicAux::NodeJoinTx(..)) => (TxType::NodeJoin, None, None),
+            TxAux::PublicTx(TxPublicAux::NetworkParamsUpdateTx(..)) => {
+                (TxType::NetworkParamsUpdate, None, None)
+            }
+            TxAux::PublicTx(TxPublicAux::UpgradeSignalTx(..)) => {
+                (TxType::UpgradeSignal, None, None)
+            }
+            TxAux::PublicTx(TxPublicAux::DataAnchorTx(..)) => (TxType::DataAnchor, None, None),
+            TxAux::PublicTx(TxPublicAux::AccountFreezeTx(..)) => {
+                (TxType::AccountFreeze, None, None)
+            }
+            TxAux::MLSHandshake(_) => (TxType::MLSHandshake, None, None),
+        };
+        TxAttributes {
+            tx_type,
+            input_count,
+            output_count,
+        }
+    }
+}
+
+#[inline]
+fn kv_pair(key: TendermintEventKey, value: String) -> KVPair {
+    let mut kv_pair = KVPair::new();
+    kv_pair.key = key.into();
+    kv_pair.value = value.into_bytes();
+    kv_pair
+}
+
+/// Events of a delivered transaction: "valid_txs" (fee, transaction id, type, staking address,
+/// input and output counts), so that indexing services can subscribe to them, and
+/// "staking_change" for the transactions which change a staked state
+pub(crate) fn generate_tx_events(txaux: &TxAux, tx_action: TxAction) -> Vec<Event> {
+    let mut events = Vec::new();
+
+    let mut valid_txs_event = Event::new();
+    valid_txs_event.field_type = TendermintEventType::ValidTransactions.to_string();
+    let attributes = &mut valid_txs_event.attributes;
+    attributes.push(kv_pair(
+        TendermintEventKey::Fee,
+        format!("{}", tx_action.fee().to_coin()),
+    ));
+    attributes.push(kv_pair(
+        TendermintEventKey::TxId,
+        hex::encode(txaux.tx_id()),
+    ));
+
+    let tx_attributes = TxAttributes::from(txaux);
+    attributes.push(kv_pair(
+        TendermintEventKey::TxType,
+        tx_attributes.tx_type.to_string(),
+    ));
+    if let Some(staking_address) = tx_action.staking_address() {
+        attributes.push(kv_pair(
+            TendermintEventKey::StakingAddress,
+            staking_address.to_string(),
+        ));
+    }
+    if let Some(input_count) = tx_attributes.input_count {
+        attributes.push(kv_pair(
+            TendermintEventKey::InputCount,
+            input_count.to_string(),
+        ));
+    }
+    if let Some(output_count) = tx_attributes.output_count {
+        attributes.push(kv_pair(
+            TendermintEventKey::OutputCount,
+            output_count.to_string(),
+        ));
+    }
+    // indexed, so the anchoring transactions of a commitment can be searched for
+    if let TxAction::Public(TxPublicAction::DataAnchor { commitment, .. }) = &tx_action {
+        attributes.push(kv_pair(
+            TendermintEventKey::AnchorCommitment,
+            hex::encode(commitment),
+        ));
+    }
+    events.push(valid_txs_event);
+
+    let maybe_tx_staking_event = generate_tx_staking_change_event(tx_action);
+    if let Some(tx_staking_event) = maybe_tx_staking_event {
+        events.push(tx_staking_event);
+    }
+
+    events
+}
+
+fn generate_tx_staking_change_event(tx_action: TxAction) -> Option<Event> {
+    match tx_action {
+        TxAction::Enclave(tx_enclave_action) => match tx_enclave_action {
+            TxEnclaveAction::Transfer { .. } => None,
+            TxEnclaveAction::Deposit { deposit, .. } => {
+                Some(StakingEvent::Deposit(&deposit.0, deposit.1).into())
+            }
+            TxEnclaveAction::Withdraw { withdraw, .. } => {
+                Some(StakingEvent::Withdraw(&withdraw.0, withdraw.1).into())
+            }
+        },
+        TxAction::Public(tx_public_action) => match tx_public_action {
+            TxPublicAction::Unbond {
+                unbond,
+                unbonded_from,
+                fee,
+                ..
+            } => Some(StakingEvent::Unbond(&unbond.0, unbond.1, unbonded_from, fee).into()),
+            TxPublicAction::NodeJoin {
+                address,
+                council_node,
+                ..
+            } => Some(StakingEvent::NodeJoin(&address, council_node).into()),
+            TxPublicAction::Unjail(staking_address) => {
+                Some(StakingEvent::Unjail(&staking_address).into())
+            }
+            TxPublicAction::NetworkParamsUpdate(_) => None,
+            TxPublicAction::UpgradeSignal(_) => None,
+            // the paid fee is in the "valid_txs" event
+            TxPublicAction::DataAnchor { .. } => None,
+            TxPublicAction::AccountFreeze(tx) => {
+                Some(StakingEvent::Freeze(&tx.address, tx.frozen).into())
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::PendingParamsUpdate;
+    use chain_core::init::address::RedeemAddress;
+    use chain_core::init::coin::Coin;
+    use chain_core::state::account::{
+        ConfidentialInit, CouncilNodeMeta, DataAnchorTx, DepositBondTx, MLSInit, NodeMetadata,
+        StakedStateAddress, StakedStateOpAttributes, StakedStateOpWitness, UnbondTx,
+    };
+    use chain_core::state::governance::{
+        AccountFreezeTx, NetworkParamsChange, NetworkParamsUpdateTx, UpgradeSignalTx,
+    };
+    use chain_core::state::tendermint::{BlockHeight, TendermintValidatorPubKey};
+    use chain_core::state::validator::{NodeJoinRequestTx, UnjailTx};
+    use chain_core::tx::data::input::TxoPointer;
+    use chain_core::tx::fee::Fee;
+    use chain_core::tx::TxObfuscated;
+    use secp256k1::{key::SecretKey, Message};
+
+    fn address(id: u8) -> StakedStateAddress {
+        StakedStateAddress::BasicRedeem(RedeemAddress::from([id; 20]))
+    }
+
+    fn witness() -> StakedStateOpWitness {
+        let secret_key = SecretKey::from_slice(&[0xcc; 32]).unwrap();
+        let message = Message::from_slice(&[0x11; 32]).unwrap();
+        StakedStateOpWitness::new(secp256k1::SECP256K1.sign_recoverable(&message, &secret_key))
+    }
+
+    fn payload(txid: u8) -> TxObfuscated {
+        TxObfuscated {
+            txid: [txid; 32],
+            key_from: BlockHeight::genesis(),
+            init_vector: [0; 12],
+            txpayload: Vec::new(),
+        }
+    }
+
+    fn fee(amount: u64) -> Fee {
+        Fee::new(Coin::new(amount).unwrap())
+    }
+
+    /// Checks the "valid_txs" event (after the fee and the transaction id) and the number of events
+    fn check_tx_events(
+        txaux: TxAux,
+        tx_action: TxAction,
+        expected: Vec<(TendermintEventKey, String)>,
+        event_count: usize,
+    ) {
+        let mut expected_attributes = vec![
+            (
+                TendermintEventKey::Fee.to_string(),
+                tx_action.fee().to_coin().to_string(),
+            ),
+            (
+                TendermintEventKey::TxId.to_string(),
+                hex::encode(txaux.tx_id()),
+            ),
+        ];
+        expected_attributes.extend(
+            expected
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value)),
+        );
+
+        let events = generate_tx_events(&txaux, tx_action);
+        assert_eq!(event_count, events.len());
+        assert_eq!(
+            TendermintEventType::ValidTransactions.to_string(),
+            events[0].field_type
+        );
+        let attributes = events[0]
+            .attributes
+            .iter()
+            .map(|pair| {
+                (
+                    String::from_utf8(pair.key.clone()).unwrap(),
+                    String::from_utf8(pair.value.clone()).unwrap(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(expected_attributes, attributes);
+    }
+
+    #[test]
+    fn check_enclave_tx_events() {
+        let inputs = vec![TxoPointer::new([1; 32], 0), TxoPointer::new([1; 32], 1)];
+        check_tx_events(
+            TxAux::EnclaveTx(TxEnclaveAux::TransferTx {
+                inputs: inputs.clone(),
+                no_of_outputs: 3,
+                payload: payload(2),
+            }),
+            TxAction::Enclave(TxEnclaveAction::Transfer {
+                fee: fee(10),
+                spend_utxo: inputs.clone(),
+                create_utxo: 3,
+                sealed_log: Vec::new(),
+            }),
+            vec![
+                (TendermintEventKey::TxType, "transfer".to_owned()),
+                (TendermintEventKey::InputCount, "2".to_owned()),
+                (TendermintEventKey::OutputCount, "3".to_owned()),
+            ],
+            1,
+        );
+
+        check_tx_events(
+            TxAux::EnclaveTx(TxEnclaveAux::DepositStakeTx {
+                tx: DepositBondTx {
+                    inputs: inputs.clone(),
+                    to_staked_account: address(1),
+                    attributes: StakedStateOpAttributes::new(0),
+                },
+                payload: payload(3),
+            }),
+            TxAction::Enclave(TxEnclaveAction::Deposit {
+                fee: fee(10),
+                spend_utxo: inputs,
+                deposit: (address(1), Coin::unit()),
+            }),
+            vec![
+                (TendermintEventKey::TxType, "deposit".to_owned()),
+                (TendermintEventKey::StakingAddress, address(1).to_string()),
+                (TendermintEventKey::InputCount, "2".to_owned()),
+            ],
+            2,
+        );
+
+        check_tx_events(
+            TxAux::EnclaveTx(TxEnclaveAux::WithdrawUnbondedStakeTx {
+                no_of_outputs: 2,
+                witness: witness(),
+                payload: payload(4),
+            }),
+            TxAction::Enclave(TxEnclaveAction::Withdraw {
+                fee: fee(10),
+                withdraw: (address(1), Coin::unit()),
+                create_utxo: 2,
+                sealed_log: Vec::new(),
+            }),
+            vec![
+                (TendermintEventKey::TxType, "withdraw".to_owned()),
+                (TendermintEventKey::StakingAddress, address(1).to_string()),
+                (TendermintEventKey::OutputCount, "2".to_owned()),
+            ],
+            2,
+        );
+    }
+
+    #[test]
+    fn check_public_tx_events() {
+        let attributes = StakedStateOpAttributes::new(0);
+        check_tx_events(
+            TxAux::PublicTx(TxPublicAux::UnbondStakeTx(
+                UnbondTx::new(address(1), 0, Coin::unit(), attributes.clone()),
+                witness(),
+            )),
+            TxAction::Public(TxPublicAction::Unbond {
+                fee: fee(10),
+                unbond: (address(1), Coin::unit()),
+                unbonded_from: 100,
+            }),
+            vec![
+                (TendermintEventKey::TxType, "unbond".to_owned()),
+                (TendermintEventKey::StakingAddress, address(1).to_string()),
+            ],
+            2,
+        );
+
+        check_tx_events(
+            TxAux::PublicTx(TxPublicAux::UnjailTx(
+                UnjailTx {
+                    nonce: 0,
+                    address: address(1),
+                    attributes: attributes.clone(),
+                },
+                witness(),
+            )),
+            TxAction::Public(TxPublicAction::Unjail(address(1))),
+            vec![
+                (TendermintEventKey::TxType, "unjail".to_owned()),
+                (TendermintEventKey::StakingAddress, address(1).to_string()),
+            ],
+            2,
+        );
+
+        let council_node = CouncilNodeMeta::new_with_details(
+            String::from("Council Node"),
+            None,
+            TendermintValidatorPubKey::Ed25519([0u8; 32]),
+            ConfidentialInit {
+                init_payload: MLSInit::Genesis([0u8; 32].to_vec()),
+            },
+        );
+        check_tx_events(
+            TxAux::PublicTx(TxPublicAux::NodeJoinTx(
+                NodeJoinRequestTx {
+                    nonce: 0,
+                    address: address(1),
+                    attributes: attributes.clone(),
+                    node_meta: NodeMetadata::CouncilNode(council_node.clone()),
+                },
+                witness(),
+            )),
+            TxAction::Public(TxPublicAction::NodeJoin {
+                address: address(1),
+                council_node,
+                isv_svn: 0,
+            }),
+            vec![
+                (TendermintEventKey::TxType, "nodejoin".to_owned()),
+                (TendermintEventKey::StakingAddress, address(1).to_string()),
+            ],
+            2,
+        );
+
+        check_tx_events(
+            TxAux::PublicTx(TxPublicAux::NetworkParamsUpdateTx(
+                NetworkParamsUpdateTx {
+                    params_version: 0,
+                    effective_height: BlockHeight::new(10),
+                    change: NetworkParamsChange::default(),
+                    attributes: attributes.clone(),
+                },
+                vec![witness()],
+            )),
+            TxAction::Public(TxPublicAction::NetworkParamsUpdate(PendingParamsUpdate {
+                effective_height: BlockHeight::new(10),
+                change: NetworkParamsChange::default(),
+            })),
+            vec![(TendermintEventKey::TxType, "params_update".to_owned())],
+            1,
+        );
+
+        check_tx_events(
+            TxAux::PublicTx(TxPublicAux::UpgradeSignalTx(
+                UpgradeSignalTx::new(0, address(1), 2, attributes.clone()),
+                witness(),
+            )),
+            TxAction::Public(TxPublicAction::UpgradeSignal(address(1))),
+            vec![
+                (TendermintEventKey::TxType, "upgrade_signal".to_owned()),
+                (TendermintEventKey::StakingAddress, address(1).to_string()),
+            ],
+            1,
+        );
+
+        check_tx_events(
+            TxAux::PublicTx(TxPublicAux::DataAnchorTx(
+                DataAnchorTx {
+                    from_staked_account: address(1),
+                    nonce: 0,
+                    commitment: [5; 32],
+                    attributes: attributes.clone(),
+                },
+                witness(),
+            )),
+            TxAction::Public(TxPublicAction::DataAnchor {
+                fee: fee(10),
+                address: address(1),
+                commitment: [5; 32],
+            }),
+            vec![
+                (TendermintEventKey::TxType, "data_anchor".to_owned()),
+                (TendermintEventKey::StakingAddress, address(1).to_string()),
+                (TendermintEventKey::AnchorCommitment, hex::encode([5; 32])),
+            ],
+            1,
+        );
+
+        let freeze = AccountFreezeTx {
+            freeze_version: 0,
+            address: address(1),
+            frozen: true,
+            reason_hash: [6; 32],
+            attributes,
+        };
+        check_tx_events(
+            TxAux::PublicTx(TxPublicAux::AccountFreezeTx(
+                freeze.clone(),
+                vec![witness()],
+            )),
+            TxAction::Public(TxPublicAction::AccountFreeze(freeze)),
+            vec![
+                (TendermintEventKey::TxType, "account_freeze".to_owned()),
+                (TendermintEventKey::StakingAddress, address(1).to_string()),
+            ],
+            2,
+        );
+    }
+}
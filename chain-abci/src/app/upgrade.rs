@@ -0,0 +1,374 @@
+use std::collections::BTreeSet;
+
+use parity_scale_codec::{Decode, Encode};
+use serde::{Deserialize, Serialize};
+
+use super::ChainNodeState;
+use crate::staking::CouncilNodeMetadata;
+use crate::tx_error::{TxError, UpgradeSignalError};
+use chain_core::state::account::StakedStateAddress;
+use chain_core::state::governance::{UpgradePlan, UpgradeSignalTx};
+use chain_core::state::tendermint::BlockHeight;
+use chain_core::tx::version::{tx_activation, tx_aux_version_activation, TxAuxVersion};
+use chain_core::tx::TxAux;
+use chain_core::APP_VERSION;
+
+/// Upgrade scheduled by a network parameters update, with the council nodes which signalled
+/// their readiness
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Encode, Decode)]
+pub struct ScheduledUpgrade {
+    /// the current plan (the upgrade height is moved by the checkpoint delays)
+    pub plan: UpgradePlan,
+    /// council nodes which signalled their readiness
+    pub signals: BTreeSet<StakedStateAddress>,
+    /// number of times the upgrade was delayed
+    pub delays: u32,
+    /// set once the readiness at the checkpoint was sufficient
+    pub checkpoint_passed: bool,
+}
+
+/// Readiness of the active council nodes for the scheduled upgrade (`upgrade-readiness` query)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UpgradeReadiness {
+    pub plan: UpgradePlan,
+    pub delays: u32,
+    /// active council nodes which signalled their readiness
+    pub ready_nodes: Vec<StakedStateAddress>,
+    pub ready_voting_power: u64,
+    pub total_voting_power: u64,
+    /// percentage of the voting power of the active council nodes which is ready (rounded down)
+    pub readiness_percent: u8,
+}
+
+impl ScheduledUpgrade {
+    pub fn new(plan: UpgradePlan) -> Self {
+        ScheduledUpgrade {
+            plan,
+            signals: BTreeSet::new(),
+            delays: 0,
+            checkpoint_passed: false,
+        }
+    }
+
+    /// Tallies the signals of the active council nodes (the signals of the nodes which left
+    /// the council don't count)
+    pub fn readiness(&self, council_nodes: &[CouncilNodeMetadata]) -> UpgradeReadiness {
+        let mut ready_nodes = Vec::new();
+        let mut ready_voting_power = 0;
+        let mut total_voting_power = 0;
+        for node in council_nodes.iter() {
+            let voting_power = u64::from(node.voting_power);
+            total_voting_power += voting_power;
+            if self.signals.contains(&node.staking_address) {
+                ready_nodes.push(node.staking_address);
+                ready_voting_power += voting_power;
+            }
+        }
+        let readiness_percent = if total_voting_power == 0 {
+            0
+        } else {
+            (u128::from(ready_voting_power) * 100 / u128::from(total_voting_power)) as u8
+        };
+        UpgradeReadiness {
+            plan: self.plan.clone(),
+            delays: self.delays,
+            ready_nodes,
+            ready_voting_power,
+            total_voting_power,
+            readiness_percent,
+        }
+    }
+
+    /// Returns `true` if the readiness needs to be checked at this block
+    pub fn checkpoint_due(&self, block_height: BlockHeight) -> bool {
+        match &self.plan.checkpoint {
+            Some(checkpoint) => !self.checkpoint_passed && checkpoint.height <= block_height,
+            None => false,
+        }
+    }
+
+    /// Checks the readiness at the checkpoint: if it's below the minimum, the upgrade height
+    /// and the checkpoint are moved by the delay (so the readiness is checked again later),
+    /// returns the new upgrade height in that case
+    pub fn check_readiness(
+        &mut self,
+        council_nodes: &[CouncilNodeMetadata],
+    ) -> Option<BlockHeight> {
+        let readiness = self.readiness(council_nodes);
+        let checkpoint = self.plan.checkpoint.as_mut()?;
+        if u128::from(readiness.ready_voting_power) * 100
+            >= u128::from(readiness.total_voting_power)
+                * u128::from(checkpoint.min_readiness_percent)
+        {
+            self.checkpoint_passed = true;
+            return None;
+        }
+        checkpoint.height = checkpoint.height.saturating_add(checkpoint.delay);
+        self.plan.height = self.plan.height.saturating_add(checkpoint.delay);
+        self.delays += 1;
+        Some(self.plan.height)
+    }
+}
+
+/// Checks the signal against the scheduled upgrade (the signing council node is checked by the
+/// staking table)
+pub fn check_upgrade_signal(
+    tx: &UpgradeSignalTx,
+    scheduled_upgrade: Option<&ScheduledUpgrade>,
+    block_height: BlockHeight,
+) -> Result<(), UpgradeSignalError> {
+    let upgrade = scheduled_upgrade.ok_or(UpgradeSignalError::NoScheduledUpgrade)?;
+    if tx.app_version != upgrade.plan.app_version {
+        return Err(UpgradeSignalError::VersionMismatch);
+    }
+    if upgrade.plan.height <= block_height {
+        return Err(UpgradeSignalError::UpgradeHeightReached);
+    }
+    if upgrade.signals.contains(&tx.address) {
+        return Err(UpgradeSignalError::AlreadySignalled);
+    }
+    Ok(())
+}
+
+/// Checks if the rules activated in the app version are in effect at the block height:
+/// the rules activated by the scheduled upgrade are only in effect from the upgrade height
+pub fn is_activated_at(
+    activation: u64,
+    scheduled_upgrade: Option<&ScheduledUpgrade>,
+    block_height: BlockHeight,
+) -> bool {
+    if activation > APP_VERSION {
+        return false;
+    }
+    match scheduled_upgrade {
+        Some(upgrade) if activation >= upgrade.plan.app_version => {
+            upgrade.plan.height <= block_height
+        }
+        _ => true,
+    }
+}
+
+/// Checks if the transactions of the envelope version are valid at the block height:
+/// a version activated by the scheduled upgrade is only valid from the upgrade height
+pub fn is_tx_aux_version_active_at(
+    version: TxAuxVersion,
+    scheduled_upgrade: Option<&ScheduledUpgrade>,
+    block_height: BlockHeight,
+) -> bool {
+    tx_aux_version_activation(version).map_or(false, |activation| {
+        is_activated_at(activation, scheduled_upgrade, block_height)
+    })
+}
+
+/// Checks the envelope version, the transaction type and the staked state witnesses of the
+/// transaction are activated at the block height
+pub fn check_tx_activation(
+    version: TxAuxVersion,
+    txaux: &TxAux,
+    scheduled_upgrade: Option<&ScheduledUpgrade>,
+    block_height: BlockHeight,
+) -> Result<(), TxError> {
+    if !is_tx_aux_version_active_at(version, scheduled_upgrade, block_height) {
+        return Err(TxError::InactiveTxVersion(version));
+    }
+    if !is_activated_at(tx_activation(txaux), scheduled_upgrade, block_height) {
+        return Err(TxError::InactiveTxType);
+    }
+    Ok(())
+}
+
+impl ChainNodeState {
+    /// Records the readiness of the council node for the scheduled upgrade
+    pub fn record_upgrade_signal(&mut self, address: StakedStateAddress) {
+        if let Some(upgrade) = self.scheduled_upgrade.as_mut() {
+            upgrade.signals.insert(address);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chain_core::init::address::RedeemAddress;
+    use chain_core::init::coin::Coin;
+    use chain_core::state::account::StakedStateOpAttributes;
+    use chain_core::state::governance::UpgradeCheckpoint;
+    use chain_core::state::tendermint::TendermintValidatorPubKey;
+
+    fn address(id: u8) -> StakedStateAddress {
+        StakedStateAddress::BasicRedeem(RedeemAddress::from([id; 20]))
+    }
+
+    fn council_node(id: u8, bonded: u64) -> CouncilNodeMetadata {
+        CouncilNodeMetadata {
+            name: format!("node {}", id),
+            voting_power: Coin::new(bonded).unwrap().into(),
+            staking_address: address(id),
+            security_contact: None,
+            tendermint_pubkey: TendermintValidatorPubKey::Ed25519([id; 32]),
+        }
+    }
+
+    fn scheduled_upgrade() -> ScheduledUpgrade {
+        ScheduledUpgrade::new(UpgradePlan {
+            app_version: 10,
+            height: 100.into(),
+            checkpoint: Some(UpgradeCheckpoint {
+                height: 80.into(),
+                min_readiness_percent: 67,
+                delay: 50,
+            }),
+        })
+    }
+
+    #[test]
+    fn check_upgrade_signal_validation() {
+        let mut upgrade = scheduled_upgrade();
+        let signal = UpgradeSignalTx::new(0, address(1), 10, StakedStateOpAttributes::new(0));
+        assert!(matches!(
+            check_upgrade_signal(&signal, None, 5.into()),
+            Err(UpgradeSignalError::NoScheduledUpgrade)
+        ));
+        let other_version =
+            UpgradeSignalTx::new(0, address(1), 11, StakedStateOpAttributes::new(0));
+        assert!(matches!(
+            check_upgrade_signal(&other_version, Some(&upgrade), 5.into()),
+            Err(UpgradeSignalError::VersionMismatch)
+        ));
+        assert!(matches!(
+            check_upgrade_signal(&signal, Some(&upgrade), 100.into()),
+            Err(UpgradeSignalError::UpgradeHeightReached)
+        ));
+        assert!(check_upgrade_signal(&signal, Some(&upgrade), 5.into()).is_ok());
+        upgrade.signals.insert(address(1));
+        assert!(matches!(
+            check_upgrade_signal(&signal, Some(&upgrade), 5.into()),
+            Err(UpgradeSignalError::AlreadySignalled)
+        ));
+    }
+
+    #[test]
+    fn check_readiness_and_delay() {
+        let council_nodes = vec![
+            council_node(1, 50_000),
+            council_node(2, 30_000),
+            council_node(3, 20_000),
+        ];
+        let mut upgrade = scheduled_upgrade();
+        upgrade.signals.insert(address(1));
+        // signals of the nodes not in the council don't count
+        upgrade.signals.insert(address(4));
+
+        let readiness = upgrade.readiness(&council_nodes);
+        assert_eq!(vec![address(1)], readiness.ready_nodes);
+        assert_eq!(50, readiness.readiness_percent);
+
+        assert!(!upgrade.checkpoint_due(79.into()));
+        assert!(upgrade.checkpoint_due(80.into()));
+        assert_eq!(
+            Some(BlockHeight::new(150)),
+            upgrade.check_readiness(&council_nodes)
+        );
+        assert_eq!(1, upgrade.delays);
+        assert!(!upgrade.checkpoint_due(80.into()));
+        assert!(upgrade.checkpoint_due(130.into()));
+
+        upgrade.signals.insert(address(3));
+        assert_eq!(None, upgrade.check_readiness(&council_nodes));
+        assert_eq!(BlockHeight::new(150), upgrade.plan.height);
+        assert!(!upgrade.checkpoint_due(140.into()));
+    }
+
+    #[test]
+    fn check_tx_aux_version_activation() {
+        use chain_core::tx::version::{
+            is_tx_aux_version_active, TX_AUX_VERSION_1, TX_AUX_VERSION_UNVERSIONED,
+        };
+
+        let upgrade = ScheduledUpgrade::new(UpgradePlan {
+            app_version: tx_aux_version_activation(TX_AUX_VERSION_1).unwrap(),
+            height: 100.into(),
+            checkpoint: None,
+        });
+        // not supported by the 0.5-compatible app version
+        let supported = is_tx_aux_version_active(TX_AUX_VERSION_1, APP_VERSION);
+        assert_eq!(
+            supported,
+            is_tx_aux_version_active_at(TX_AUX_VERSION_1, None, 5.into())
+        );
+        // gated until the upgrade height
+        assert!(!is_tx_aux_version_active_at(
+            TX_AUX_VERSION_1,
+            Some(&upgrade),
+            99.into()
+        ));
+        assert_eq!(
+            supported,
+            is_tx_aux_version_active_at(TX_AUX_VERSION_1, Some(&upgrade), 100.into())
+        );
+        assert!(is_tx_aux_version_active_at(
+            TX_AUX_VERSION_UNVERSIONED,
+            Some(&upgrade),
+            99.into()
+        ));
+        assert!(!is_tx_aux_version_active_at(7, None, 5.into()));
+    }
+
+    #[test]
+    fn check_tx_type_activation() {
+        use chain_core::state::account::{StakedStateOpWitness, ThresholdSignature, UnbondTx};
+        use chain_core::tx::version::{TX_AUX_VERSION_UNVERSIONED, UPGRADE_APP_VERSION};
+        use chain_core::tx::TxPublicAux;
+        use secp256k1::recovery::{RecoverableSignature, RecoveryId};
+
+        let upgrade = ScheduledUpgrade::new(UpgradePlan {
+            app_version: UPGRADE_APP_VERSION,
+            height: 100.into(),
+            checkpoint: None,
+        });
+        let unbond = UnbondTx::new(
+            address(1),
+            0,
+            Coin::new(1_000).unwrap(),
+            StakedStateOpAttributes::new(0),
+        );
+        let basic_witness = StakedStateOpWitness::BasicRedeem(
+            RecoverableSignature::from_compact(&[0x01; 64], RecoveryId::from_i32(1).unwrap())
+                .unwrap(),
+        );
+        let threshold_witness = StakedStateOpWitness::ThresholdSig(ThresholdSignature {
+            threshold: 1,
+            signatures: vec![],
+        });
+        let check = |tx: &TxAux, upgrade: Option<&ScheduledUpgrade>, height: u64| {
+            check_tx_activation(TX_AUX_VERSION_UNVERSIONED, tx, upgrade, height.into())
+        };
+
+        let new_txs = vec![
+            TxAux::PublicTx(TxPublicAux::UpgradeSignalTx(
+                UpgradeSignalTx::new(0, address(1), 10, StakedStateOpAttributes::new(0)),
+                basic_witness.clone(),
+            )),
+            TxAux::PublicTx(TxPublicAux::UnbondStakeTx(
+                unbond.clone(),
+                threshold_witness,
+            )),
+        ];
+        // not valid in the 0.5-compatible app version
+        let supported = UPGRADE_APP_VERSION <= APP_VERSION;
+        for tx in new_txs.iter() {
+            assert_eq!(supported, check(tx, None, 5).is_ok());
+            // gated until the upgrade height
+            assert!(matches!(
+                check(tx, Some(&upgrade), 99),
+                Err(TxError::InactiveTxType)
+            ));
+            assert_eq!(supported, check(tx, Some(&upgrade), 100).is_ok());
+        }
+
+        // the transactions of the 0.5 release aren't gated
+        let tx = TxAux::PublicTx(TxPublicAux::UnbondStakeTx(unbond, basic_witness));
+        assert!(check(&tx, None, 5).is_ok());
+        assert!(check(&tx, Some(&upgrade), 99).is_ok());
+    }
+}
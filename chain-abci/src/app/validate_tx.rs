@@ -1,14 +1,16 @@
+use super::check_tx_cache::cache_key;
+use super::upgrade::check_tx_activation;
 use super::{BufferType, ChainNodeApp, ChainNodeState};
 use crate::enclave_bridge::EnclaveProxy;
 use crate::storage::{
-    process_public_tx, verify_enclave_tx, TxAction, TxEnclaveAction, TxPublicAction,
+    check_unfrozen_withdraw, process_public_tx, verify_enclave_tx, TxAction, TxEnclaveAction,
+    TxPublicAction,
 };
 use crate::tx_error::TxError;
 use abci::*;
 use chain_core::tx::data::TxId;
 use chain_core::tx::TxAux;
 use chain_storage::buffer::{StoreKV, StoreStaking};
-use parity_scale_codec::Decode;
 
 /// Wrapper to abstract over CheckTx and DeliverTx requests
 pub trait RequestWithTx {
@@ -71,18 +73,57 @@ impl<T: EnclaveProxy + 'static> ChainNodeApp<T> {
             BufferType::Consensus => self.last_state.as_mut().expect("expect last_state"),
             BufferType::Mempool => self.mempool_state.as_mut().expect("expect mempool_state"),
         };
-        let txaux = TxAux::decode(&mut req.tx())?;
+        let (version, txaux) = TxAux::decode_versioned(&mut req.tx())?;
+        check_tx_activation(
+            version,
+            &txaux,
+            state.scheduled_upgrade.as_ref(),
+            extra_info.block_height,
+        )?;
         let txid = txaux.tx_id();
         let tx_action = match &txaux {
             TxAux::MLSHandshake(_) => return Err(TxError::WIPMLSData),
             TxAux::EnclaveTx(tx) => {
-                let action = verify_enclave_tx(
-                    &mut self.tx_validator,
-                    &tx,
-                    &extra_info,
-                    &staking_getter!(self, state.staking_version, buffer_type),
-                    &kv_store!(self, buffer_type),
-                )?;
+                let verdict_key = match buffer_type {
+                    BufferType::Mempool => Some(cache_key(req.tx())),
+                    BufferType::Consensus => None,
+                };
+                let cached = match &verdict_key {
+                    Some(key) => self.check_tx_cache.get(
+                        key,
+                        extra_info.min_fee_computed,
+                        &staking_getter!(self, state.staking_version, buffer_type),
+                        &kv_store!(self, buffer_type),
+                    ),
+                    None => None,
+                };
+                let action = match cached {
+                    Some(action) => action,
+                    None => {
+                        let action = verify_enclave_tx(
+                            &mut self.tx_validator,
+                            &tx,
+                            &extra_info,
+                            &staking_getter!(self, state.staking_version, buffer_type),
+                            &kv_store!(self, buffer_type),
+                        )?;
+                        if let Some(key) = verdict_key {
+                            self.check_tx_cache.insert(
+                                key,
+                                &action,
+                                extra_info.min_fee_computed,
+                                state.block_height,
+                            );
+                        }
+                        action
+                    }
+                };
+                check_unfrozen_withdraw(&action, &state.account_freeze)?;
+                // the local policy is checked before the mempool state is changed
+                if let BufferType::Mempool = buffer_type {
+                    self.mempool_policy
+                        .check_enclave_tx(&action, extra_info.min_fee_computed)?;
+                }
                 // execute the action
                 execute_enclave_tx(
                     &mut staking_store!(self, state.staking_version, buffer_type),
@@ -118,10 +159,17 @@ impl<T: EnclaveProxy + 'static> ChainNodeApp<T> {
                 TxAction::Enclave(action)
             }
             TxAux::PublicTx(tx) => {
+                if let BufferType::Mempool = buffer_type {
+                    self.mempool_policy.check_public_tx(&tx)?;
+                }
                 let action = process_public_tx(
                     &mut staking_store!(self, state.staking_version, buffer_type),
                     &mut state.staking_table,
                     state.enclave_isv_svn,
+                    &state.top_level.network_params,
+                    state.pending_params_update.as_ref(),
+                    state.scheduled_upgrade.as_ref(),
+                    &state.account_freeze,
                     &extra_info,
                     &tx,
                 )?;
@@ -129,6 +177,15 @@ impl<T: EnclaveProxy + 'static> ChainNodeApp<T> {
                 if let TxPublicAction::NodeJoin { isv_svn, .. } = action {
                     state.enclave_isv_svn = isv_svn;
                 };
+                if let TxPublicAction::NetworkParamsUpdate(update) = &action {
+                    state.pending_params_update = Some(update.clone());
+                }
+                if let TxPublicAction::UpgradeSignal(address) = &action {
+                    state.record_upgrade_signal(*address);
+                }
+                if let TxPublicAction::AccountFreeze(tx) = &action {
+                    state.account_freeze.apply(tx);
+                }
 
                 TxAction::Public(action)
             }
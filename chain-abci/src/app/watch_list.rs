@@ -0,0 +1,189 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::env;
+use std::fs;
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use chain_core::init::coin::Coin;
+use chain_core::state::account::{Nonce, StakedStateAddress};
+use chain_core::state::tendermint::BlockHeight;
+use chain_core::tx::data::address::ExtendedAddr;
+use chain_storage::buffer::StakingBuffer;
+
+/// Path to the watch-list file (one staking address per line, `#` starts a comment)
+pub const WATCH_LIST_ENV: &str = "CRYPTO_CHAIN_WATCH_LIST";
+/// Number of alerts kept for the "watch-list" ABCI query path
+pub const WATCH_LIST_ALERTS_ENV: &str = "CRYPTO_CHAIN_WATCH_LIST_ALERTS";
+
+const DEFAULT_ALERT_CAPACITY: usize = 100;
+
+/// Watched address involved in a block
+#[derive(Debug, Clone, Serialize)]
+pub struct WatchAlert {
+    pub height: BlockHeight,
+    pub address: StakedStateAddress,
+    /// staked state after the block
+    pub nonce: Nonce,
+    pub bonded: Coin,
+    pub unbonded: Coin,
+    pub jailed: bool,
+}
+
+/// Operator-configured staking addresses (e.g. treasury accounts) which are alerted on
+/// (at warn level) whenever their staked state is changed by a block
+/// (transactions, rewards, slashing / jailing).
+/// Counters and recent alerts are available via the "watch-list" ABCI query path.
+///
+/// Transfer addresses can not be watched: they only appear in the obfuscated transaction payloads.
+#[derive(Debug, Serialize)]
+pub struct AddressWatchList {
+    /// number of blocks each watched address was involved in
+    hits: BTreeMap<StakedStateAddress, u64>,
+    /// most recent alerts
+    alerts: VecDeque<WatchAlert>,
+    #[serde(skip)]
+    alert_capacity: usize,
+}
+
+impl Default for AddressWatchList {
+    fn default() -> Self {
+        AddressWatchList::new(BTreeSet::new(), DEFAULT_ALERT_CAPACITY)
+    }
+}
+
+impl AddressWatchList {
+    pub fn new(addresses: BTreeSet<StakedStateAddress>, alert_capacity: usize) -> Self {
+        AddressWatchList {
+            hits: addresses.into_iter().map(|address| (address, 0)).collect(),
+            alerts: VecDeque::with_capacity(alert_capacity),
+            alert_capacity,
+        }
+    }
+
+    /// Reads the configuration from `CRYPTO_CHAIN_WATCH_LIST*` environment variables
+    /// (invalid configuration is fatal, so that operators don't rely on a silently empty list)
+    pub fn from_env() -> Self {
+        let alert_capacity = env::var(WATCH_LIST_ALERTS_ENV)
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_ALERT_CAPACITY);
+        let addresses = match env::var(WATCH_LIST_ENV) {
+            Ok(path) => {
+                let content = fs::read_to_string(&path)
+                    .unwrap_or_else(|e| panic!("failed to read watch-list {}: {}", path, e));
+                parse_watch_list(&content)
+                    .unwrap_or_else(|e| panic!("invalid watch-list {}: {}", path, e))
+            }
+            Err(_) => BTreeSet::new(),
+        };
+        if !addresses.is_empty() {
+            log::info!("watching {} staking addresses", addresses.len());
+        }
+        AddressWatchList::new(addresses, alert_capacity)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hits.is_empty()
+    }
+
+    /// Alerts on the watched addresses among the staked states updated in the block
+    pub fn on_commit(&mut self, height: BlockHeight, stakings: &StakingBuffer) {
+        if self.is_empty() {
+            return;
+        }
+        for (address, hits) in self.hits.iter_mut() {
+            let staking = match stakings.get(address) {
+                Some(staking) => staking,
+                None => continue,
+            };
+            *hits += 1;
+            let alert = WatchAlert {
+                height,
+                address: *address,
+                nonce: staking.nonce,
+                bonded: staking.bonded,
+                unbonded: staking.unbonded,
+                jailed: staking.is_jailed(),
+            };
+            log::warn!(
+                "watched address {} involved in block {}: nonce={} bonded={} unbonded={} jailed={}",
+                alert.address,
+                alert.height,
+                alert.nonce,
+                alert.bonded,
+                alert.unbonded,
+                alert.jailed
+            );
+            if self.alert_capacity > 0 {
+                if self.alerts.len() >= self.alert_capacity {
+                    self.alerts.pop_front();
+                }
+                self.alerts.push_back(alert);
+            }
+        }
+    }
+
+    /// Number of blocks the address was involved in (None if not watched)
+    pub fn hits(&self, address: &StakedStateAddress) -> Option<u64> {
+        self.hits.get(address).copied()
+    }
+
+    /// JSON dump of the counters and recent alerts
+    pub fn dump(&self) -> String {
+        serde_json::to_string(self).expect("serialize watch-list")
+    }
+}
+
+fn parse_watch_list(content: &str) -> Result<BTreeSet<StakedStateAddress>, String> {
+    let mut addresses = BTreeSet::new();
+    for line in content.lines() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        match StakedStateAddress::from_str(line) {
+            Ok(address) => {
+                addresses.insert(address);
+            }
+            Err(_) if ExtendedAddr::from_str(line).is_ok() => {
+                return Err(format!(
+                    "{} is a transfer address (only staking addresses can be watched)",
+                    line
+                ));
+            }
+            Err(e) => return Err(format!("{}: {}", line, e)),
+        }
+    }
+    Ok(addresses)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chain_core::state::account::StakedState;
+
+    #[test]
+    fn check_watch_list_alerts() {
+        let content = "# treasury\n0x0e7c045110b8dbf29765047380898919c5cb56f4\n\n";
+        let addresses = parse_watch_list(content).unwrap();
+        let address = *addresses.iter().next().unwrap();
+        let mut watch_list = AddressWatchList::new(addresses, 1);
+
+        let mut stakings = StakingBuffer::new();
+        watch_list.on_commit(BlockHeight::new(1), &stakings);
+        assert_eq!(Some(0), watch_list.hits(&address));
+
+        stakings.insert(address, StakedState::default(address));
+        watch_list.on_commit(BlockHeight::new(2), &stakings);
+        watch_list.on_commit(BlockHeight::new(3), &stakings);
+        assert_eq!(Some(2), watch_list.hits(&address));
+        assert_eq!(1, watch_list.alerts.len());
+        assert_eq!(BlockHeight::new(3), watch_list.alerts[0].height);
+        assert!(watch_list
+            .dump()
+            .contains("0x0e7c045110b8dbf29765047380898919c5cb56f4"));
+
+        assert!(parse_watch_list("not an address").is_err());
+    }
+}
@@ -138,8 +138,9 @@ impl EnclaveProxy for TxValidationApp {
                 }
             },
             Err(e) => {
-                log::error!("enclave response decode error {:?}", e);
-                Err(chain_tx_validation::Error::EnclaveRejected)
+                // not a rejection: the node can't tell whether the transaction is valid
+                log::error!("enclave response read error {:?}", e);
+                Err(chain_tx_validation::Error::IoError)
             }
         }
     }
@@ -1,17 +1,19 @@
 use crate::app::ChainNodeState;
 use crate::enclave_bridge::EnclaveProxy;
+use chain_core::common::MerkleTree;
 use chain_core::state::account::StakedState;
 use chain_core::state::account::StakedStateOpWitness;
+use chain_core::state::tendermint::BlockHeight;
 use chain_core::tx::data::TxId;
 use chain_storage::buffer::Get;
 use chain_storage::jellyfish::StakingGetter;
-use chain_storage::ReadOnlyStorage;
+use chain_storage::{LookupItem, ReadOnlyStorage};
 use chain_tx_validation::witness::verify_tx_recover_address;
 use chain_tx_validation::ChainInfo;
 use enclave_protocol::codec::StreamWrite;
 use enclave_protocol::IntraEnclaveRequest;
 use enclave_protocol::{
-    EnclaveRequest, EnclaveResponse, IntraEnclaveResponseOk, IntraEncryptRequest,
+    EnclaveRequest, EnclaveResponse, IntraEnclaveResponseOk, IntraEncryptRequest, SealedLog,
     ENCRYPTION_REQUEST_SIZE,
 };
 use parity_scale_codec::Decode;
@@ -48,15 +50,53 @@ impl<T: EnclaveProxy> TxValidationServer<T> {
     where
         I: IntoIterator<Item = TxId> + ExactSizeIterator,
     {
-        let mut result = Vec::with_capacity(inputs.len());
-        for input in inputs.into_iter() {
-            if let Some(txin) = self.storage.get_sealed_log(&input) {
-                result.push(txin);
-            } else {
-                return None;
+        let txids = inputs.into_iter().collect::<Vec<_>>();
+        chain_storage::get_sealed_logs(&self.storage, &txids)
+            .into_iter()
+            .collect()
+    }
+
+    /// sealed data of the transactions with outputs committed after `after_height`
+    /// (in at most `max_heights` blocks), and the last checked block height
+    fn lookup_committed_txs(
+        &self,
+        after_height: BlockHeight,
+        max_heights: u32,
+    ) -> (BlockHeight, Vec<(BlockHeight, TxId, SealedLog)>) {
+        let last_block_height = match self.storage.get_last_app_state() {
+            Some(state) => {
+                ChainNodeState::decode(&mut state.as_slice())
+                    .expect("deserialize app state")
+                    .last_block_height
+            }
+            None => return (after_height, vec![]),
+        };
+        let last_height = last_block_height
+            .value()
+            .min(after_height.value().saturating_add(max_heights.into()));
+
+        let mut txs = Vec::new();
+        for height in after_height.value().saturating_add(1)..=last_height {
+            let height = BlockHeight::new(height);
+            let tree = chain_storage::get_historical_app_hash(&self.storage, height)
+                .and_then(|app_hash| {
+                    chain_storage::lookup_item(&self.storage, LookupItem::TxsMerkle, &app_hash)
+                })
+                .map(|tree| {
+                    MerkleTree::<TxId>::decode(&mut tree.as_slice()).expect("deserialize txs tree")
+                });
+            if let Some(tree) = tree {
+                // only transfers and withdraws (transactions with outputs) are sealed
+                let txids = tree.values().into_iter().copied().collect::<Vec<_>>();
+                let sealed_logs = chain_storage::get_sealed_logs(&self.storage, &txids);
+                for (txid, sealed_log) in txids.into_iter().zip(sealed_logs) {
+                    if let Some(sealed_log) = sealed_log {
+                        txs.push((height, txid, sealed_log));
+                    }
+                }
             }
         }
-        Some(result)
+        (BlockHeight::new(last_height.max(after_height.value())), txs)
     }
 
     fn lookup_state(
@@ -100,8 +140,8 @@ impl<T: EnclaveProxy> TxValidationServer<T> {
                                         ),
                                         _ => None,
                                     };
-                                    // TODO: fee in enclave?
-
+                                    // the same fee as when the obfuscated tx is validated
+                                    // (its size is computed by `chain_core::tx::fee`)
                                     let min_fee = last_state
                                         .top_level
                                         .network_params
@@ -141,6 +181,14 @@ impl<T: EnclaveProxy> TxValidationServer<T> {
                         };
                         EnclaveResponse::EncryptTx(result)
                     }
+                    Ok(EnclaveRequest::GetCommittedSealedTxs {
+                        after_height,
+                        max_heights,
+                    }) => {
+                        let (last_height, txs) =
+                            self.lookup_committed_txs(after_height, max_heights);
+                        EnclaveResponse::GetCommittedSealedTxs { last_height, txs }
+                    }
                     Err(e) => {
                         log::error!("unknown request / failed to decode: {}", e);
                         EnclaveResponse::UnknownRequest
@@ -0,0 +1,35 @@
+//! Enclave bridge with injected faults (chaos testing, only with the `fault-injection` feature)
+use std::sync::Arc;
+
+use chain_storage::fault::FaultInjector;
+use enclave_protocol::{IntraEnclaveRequest, IntraEnclaveResponse};
+
+use super::EnclaveProxy;
+
+/// Wraps an enclave bridge: every call goes through the fault injector first
+/// (a transient error is returned as `Error::IoError`, like an unreachable enclave)
+pub struct FaultyEnclave<T: EnclaveProxy> {
+    inner: T,
+    faults: Arc<FaultInjector>,
+}
+
+impl<T: EnclaveProxy> FaultyEnclave<T> {
+    pub fn new(inner: T, faults: Arc<FaultInjector>) -> Self {
+        FaultyEnclave { inner, faults }
+    }
+}
+
+impl<T: EnclaveProxy> EnclaveProxy for FaultyEnclave<T> {
+    fn check_chain(&mut self, network_id: u8) -> Result<(), ()> {
+        self.faults.inject("enclave check chain").map_err(|_| ())?;
+        self.inner.check_chain(network_id)
+    }
+
+    fn process_request(&mut self, request: IntraEnclaveRequest) -> IntraEnclaveResponse {
+        if let Err(e) = self.faults.inject("enclave request") {
+            log::error!("enclave request failed: {}", e);
+            return Err(chain_tx_validation::Error::IoError);
+        }
+        self.inner.process_request(request)
+    }
+}
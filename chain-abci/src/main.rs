@@ -1,4 +1,9 @@
-use chain_abci::app::{sanity_check_enabled, ChainNodeApp};
+use chain_abci::app::{
+    export_state, exported_genesis_app_hash, exported_genesis_validators, sanity_check_enabled,
+    AppMetrics, BackupConfig, BackupScheduler, ChainNodeApp, HealthConfig, HealthServer,
+    MeteredEnclave, MetricsConfig, PruningMode, StateSync, StorageEncryptionConfig,
+    StorageTuningConfig,
+};
 #[cfg(all(not(feature = "mock-enclave"), feature = "edp", target_os = "linux"))]
 use chain_abci::enclave_bridge::edp::{
     launch_tx_validation, tdbe::TdbeApp, temp_start_up_ra_tx_query, TempTxQueryOptions,
@@ -7,7 +12,10 @@ use chain_abci::enclave_bridge::edp::{
 #[cfg(any(feature = "mock-enclave", not(target_os = "linux")))]
 use chain_abci::enclave_bridge::mock::MockClient;
 use chain_abci::enclave_bridge::{EnclaveProxy, TdbeConfig};
+use chain_core::init::export::ExportedState;
 use chain_core::init::network::{get_network, get_network_id, init_chain_id};
+use chain_storage::encryption::{migrate_columns, MigrationMode};
+use chain_storage::tuning::CompactionStyle;
 use chain_storage::ReadOnlyStorage;
 use chain_storage::{Storage, StorageConfig, StorageType};
 use kvdb::KeyValueDB;
@@ -38,6 +46,18 @@ pub struct Config {
     launch_ra_proxy: bool,
     remote_attestation: SpRaConfig,
     data_bootstrap: TdbeConfig,
+    #[serde(default)]
+    backup: BackupConfig,
+    #[serde(default)]
+    storage_encryption: StorageEncryptionConfig,
+    #[serde(default)]
+    pruning: PruningMode,
+    #[serde(default)]
+    storage_tuning: StorageTuningConfig,
+    #[serde(default)]
+    health: HealthConfig,
+    #[serde(default)]
+    metrics: MetricsConfig,
 }
 
 impl Default for Config {
@@ -68,6 +88,12 @@ impl Default for Config {
                 ias_report_path: "/attestation/v4/report".into(),
             },
             data_bootstrap: TdbeConfig::default(),
+            backup: BackupConfig::default(),
+            storage_encryption: StorageEncryptionConfig::default(),
+            pruning: PruningMode::default(),
+            storage_tuning: StorageTuningConfig::default(),
+            health: HealthConfig::default(),
+            metrics: MetricsConfig::default(),
         }
     }
 }
@@ -98,6 +124,15 @@ impl Config {
         if opt.tx_query.is_some() {
             self.tx_query = opt.tx_query.clone();
         }
+        if let Some(pruning) = opt.pruning {
+            self.pruning = pruning;
+        }
+        if let Some(compaction) = opt.db_compaction {
+            self.storage_tuning.compaction = Some(compaction.to_string());
+        }
+        if opt.db_memory_budget.is_some() {
+            self.storage_tuning.column_memory_budget_mb = opt.db_memory_budget;
+        }
     }
     pub fn is_valid(&self) -> bool {
         let mut valid = true;
@@ -141,6 +176,89 @@ pub enum AbciApp {
         )]
         data: String,
     },
+
+    /// Encrypts the storage columns with the active key of the configured storage encryption
+    /// keys (after enabling the encryption or adding a new key), or decrypts them
+    /// (before disabling the encryption); chain-abci must not be running
+    #[structopt(
+        name = "migrate-storage-encryption",
+        about = "Re-encrypt (or decrypt) the encrypted storage columns in the data directory"
+    )]
+    MigrateStorageEncryption {
+        #[structopt(
+            short = "d",
+            long = "data",
+            default_value = ".cro-storage/",
+            help = "Sets a data storage directory"
+        )]
+        data: String,
+        #[structopt(long = "decrypt", help = "Decrypt all values")]
+        decrypt: bool,
+    },
+
+    /// Exports the staked states, unspent outputs, rewards pool and council nodes
+    /// of the last committed block into a genesis of an upgraded chain;
+    /// chain-abci must not be running
+    #[structopt(
+        name = "export-state",
+        about = "Export the committed state into genesis.json of an upgraded chain"
+    )]
+    ExportState {
+        #[structopt(
+            short = "d",
+            long = "data",
+            default_value = ".cro-storage/",
+            help = "Sets a data storage directory"
+        )]
+        data: String,
+        #[structopt(
+            long = "height",
+            help = "Height of the exported state (must be the last committed height)"
+        )]
+        height: u64,
+        #[structopt(
+            long = "genesis",
+            help = "genesis.json of the exported chain (its consensus parameters and genesis time are kept)"
+        )]
+        genesis: PathBuf,
+        #[structopt(long = "chain_id", help = "Chain id of the upgraded chain")]
+        chain_id: String,
+        #[structopt(
+            short = "o",
+            long = "output",
+            help = "Output file (standard output if not set)"
+        )]
+        output: Option<PathBuf>,
+    },
+
+    /// Restores the (empty) storage from a snapshot of the backups of another node
+    /// (e.g. fetched via the "snapshots" and "snapshot-chunk" queries), which must result
+    /// in the trusted app hash; chain-abci must not be running
+    #[structopt(
+        name = "restore-snapshot",
+        about = "Restore the storage in the data directory from a storage snapshot"
+    )]
+    RestoreSnapshot {
+        #[structopt(
+            short = "d",
+            long = "data",
+            default_value = ".cro-storage/",
+            help = "Sets a data storage directory"
+        )]
+        data: String,
+        #[structopt(
+            long = "snapshots",
+            help = "Directory with the snapshots (backup-<height> directories)"
+        )]
+        snapshots: PathBuf,
+        #[structopt(long = "height", help = "Height of the restored snapshot")]
+        height: u64,
+        #[structopt(
+            long = "app_hash",
+            help = "Trusted app hash at the height (e.g. from the block header at height + 1)"
+        )]
+        app_hash: String,
+    },
 }
 
 #[derive(Debug, StructOpt)]
@@ -176,6 +294,23 @@ pub struct AbciOpt {
         help = "Optional transaction query support for clients (tx query enclave listening address, e.g. mydomain.com:4444)"
     )]
     tx_query: Option<String>,
+    #[structopt(
+        long = "pruning",
+        possible_values = &["archive", "default", "aggressive"],
+        help = "Pruning of the stored bodies of old transactions: archive (keep all), default (keep the last 100000 blocks) or aggressive (keep the last 1000 blocks)"
+    )]
+    pruning: Option<PruningMode>,
+    #[structopt(
+        long = "db-compaction",
+        possible_values = &["auto", "ssd", "hdd"],
+        help = "Compaction style of the database: auto (detected from the disk), ssd or hdd"
+    )]
+    db_compaction: Option<CompactionStyle>,
+    #[structopt(
+        long = "db-memory-budget",
+        help = "Memory budget (in MiB) of the database columns without their own budget in the configuration"
+    )]
+    db_memory_budget: Option<usize>,
 }
 
 /// edp
@@ -249,6 +384,17 @@ fn start_up_ra_tx_query<T: EnclaveProxy + 'static>(
     // nothing
 }
 
+fn load_config(data: &str) -> Config {
+    // use DATA_PATH/config.yaml as default
+    let mut config_file = PathBuf::from(data);
+    config_file.push("config.yaml");
+    if config_file.exists() {
+        Config::from_file(config_file.as_path())
+    } else {
+        Config::default()
+    }
+}
+
 fn main() {
     env_logger::init();
     let app_command = AbciApp::from_args();
@@ -275,14 +421,7 @@ fn main() {
         }
         AbciApp::Run { run_command } => {
             let opt = run_command;
-            // use DATA_PATH/config.yaml as default
-            let mut config_file = PathBuf::from(&opt.data);
-            config_file.push("config.yaml");
-            let mut config = if config_file.exists() {
-                Config::from_file(config_file.as_path())
-            } else {
-                Config::default()
-            };
+            let mut config = load_config(&opt.data);
             config.update(&opt);
             if !config.is_valid() {
                 return;
@@ -297,7 +436,34 @@ fn main() {
 
             let host = config.host.parse().expect("invalid host");
             let addr = SocketAddr::new(host, config.port);
-            let storage = Storage::new(&StorageConfig::new(&opt.data, StorageType::Node));
+            let tuning = match config.storage_tuning.tuning() {
+                Ok(tuning) => tuning,
+                Err(e) => {
+                    error!("invalid storage tuning: {}", e);
+                    return;
+                }
+            };
+            info!(
+                "storage compaction: {}, block cache: {} MiB",
+                tuning.compaction,
+                tuning.block_cache_mb()
+            );
+            let mut storage =
+                Storage::new(&StorageConfig::new(&opt.data, StorageType::Node).with_tuning(tuning));
+            match config.storage_encryption.load() {
+                Ok(Some(encryption)) => {
+                    info!(
+                        "storage encryption enabled (active key id: {:?})",
+                        encryption.keys().active_key_id()
+                    );
+                    storage = storage.with_encryption(encryption);
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    error!("failed to load storage encryption keys: {}", e);
+                    return;
+                }
+            }
 
             let tx_validator = get_enclave_proxy(&config, storage.temp_hack_for_tdbe());
             if sanity_check_enabled() {
@@ -310,17 +476,178 @@ fn main() {
                 storage.get_read_only(),
             );
             info!("starting up");
-            abci::run(
-                addr,
-                ChainNodeApp::new_with_storage(
-                    tx_validator,
-                    &config.genesis_app_hash,
-                    &config.chain_id,
-                    storage,
-                    config.tx_query,
-                    config.data_bootstrap.external_listen_address,
-                ),
+            let metrics = AppMetrics::default();
+            let mut app = ChainNodeApp::new_with_storage(
+                MeteredEnclave::new(tx_validator, metrics.clone()),
+                &config.genesis_app_hash,
+                &config.chain_id,
+                storage,
+                config.tx_query,
+                config.data_bootstrap.external_listen_address,
             );
+            app.backup = BackupScheduler::from_config(&config.backup);
+            app.pruning = config.pruning;
+            info!("pruning mode: {}", config.pruning);
+            app.state_sync = config
+                .backup
+                .directory
+                .as_ref()
+                .map(|directory| StateSync::new(Path::new(directory)));
+            let health = HealthServer::from_config(
+                &config.health,
+                app.node_status.clone(),
+                app.storage.temp_hack_for_tdbe(),
+            );
+            if let Some(Err(e)) = health.map(HealthServer::spawn) {
+                error!("failed to start the health endpoint: {}", e);
+            }
+            if let Some(Err(e)) = metrics.spawn_server(&config.metrics) {
+                error!("failed to start the metrics endpoint: {}", e);
+            }
+            app.metrics = metrics;
+            abci::run(addr, app);
+        }
+        AbciApp::MigrateStorageEncryption { data, decrypt } => {
+            let config = load_config(&data);
+            let encryption = match config.storage_encryption.load() {
+                Ok(Some(encryption)) => encryption,
+                Ok(None) => {
+                    error!("storage encryption keys are not configured");
+                    return;
+                }
+                Err(e) => {
+                    error!("failed to load storage encryption keys: {}", e);
+                    return;
+                }
+            };
+            let mode = if decrypt {
+                MigrationMode::Decrypt
+            } else {
+                MigrationMode::Encrypt
+            };
+            let tuning = match config.storage_tuning.tuning() {
+                Ok(tuning) => tuning,
+                Err(e) => {
+                    error!("invalid storage tuning: {}", e);
+                    return;
+                }
+            };
+            let storage =
+                Storage::new(&StorageConfig::new(&data, StorageType::Node).with_tuning(tuning));
+            match migrate_columns(&*storage.temp_hack_for_tdbe(), &encryption, mode) {
+                Ok(stats) => info!(
+                    "storage encryption migration finished: {} values rewritten, {} unchanged",
+                    stats.rewritten, stats.unchanged
+                ),
+                Err(e) => error!("storage encryption migration failed: {}", e),
+            }
+        }
+        AbciApp::ExportState {
+            data,
+            height,
+            genesis,
+            chain_id,
+            output,
+        } => {
+            let config = load_config(&data);
+            let tuning = match config.storage_tuning.tuning() {
+                Ok(tuning) => tuning,
+                Err(e) => {
+                    error!("invalid storage tuning: {}", e);
+                    return;
+                }
+            };
+            let mut storage =
+                Storage::new(&StorageConfig::new(&data, StorageType::Node).with_tuning(tuning));
+            match config.storage_encryption.load() {
+                Ok(Some(encryption)) => storage = storage.with_encryption(encryption),
+                Ok(None) => {}
+                Err(e) => {
+                    error!("failed to load storage encryption keys: {}", e);
+                    return;
+                }
+            }
+            match export_genesis(&storage, height, &genesis, &chain_id) {
+                Ok(exported) => match output {
+                    Some(output) => match write(&output, exported) {
+                        Ok(_) => info!("exported state written to {}", output.display()),
+                        Err(e) => error!("failed to write the exported state: {}", e),
+                    },
+                    None => println!("{}", exported),
+                },
+                Err(e) => error!("state export failed: {}", e),
+            }
+        }
+        AbciApp::RestoreSnapshot {
+            data,
+            snapshots,
+            height,
+            app_hash,
+        } => {
+            let mut trusted_app_hash = [0u8; 32];
+            match hex::decode(&app_hash) {
+                Ok(decoded) if decoded.len() == trusted_app_hash.len() => {
+                    trusted_app_hash.copy_from_slice(&decoded)
+                }
+                _ => {
+                    error!("invalid app hash: {}", app_hash);
+                    return;
+                }
+            }
+            let config = load_config(&data);
+            let tuning = match config.storage_tuning.tuning() {
+                Ok(tuning) => tuning,
+                Err(e) => {
+                    error!("invalid storage tuning: {}", e);
+                    return;
+                }
+            };
+            let storage =
+                Storage::new(&StorageConfig::new(&data, StorageType::Node).with_tuning(tuning));
+            if storage.get_last_app_state().is_some() {
+                error!("the storage is not empty");
+                return;
+            }
+            let mut state_sync = StateSync::new(Path::new(&data));
+            match state_sync.restore_from(
+                &StateSync::new(&snapshots),
+                height,
+                trusted_app_hash,
+                &*storage.temp_hack_for_tdbe(),
+            ) {
+                Ok(()) => info!("storage restored at height {}", height),
+                Err(e) => error!("snapshot restore failed: {}", e),
+            }
         }
     }
 }
+
+/// genesis.json of the upgraded chain: the one of the exported chain with the exported state
+fn export_genesis(
+    storage: &Storage,
+    height: u64,
+    genesis: &Path,
+    chain_id: &str,
+) -> Result<String, String> {
+    let mut genesis: serde_json::Value = serde_json::from_reader(BufReader::new(
+        File::open(genesis).map_err(|e| format!("failed to open genesis: {}", e))?,
+    ))
+    .map_err(|e| format!("failed to parse genesis: {}", e))?;
+    let exported = export_state(storage, height.into())?;
+    info!(
+        "exported {} staked states and {} unspent outputs",
+        exported.accounts.len(),
+        exported.unspent_outputs.len()
+    );
+    // the app hash is computed from the staked states as parsed at init chain
+    // (e.g. the recently used validator addresses are not serialized)
+    let app_state = serde_json::to_value(&exported).map_err(|e| e.to_string())?;
+    let exported: ExportedState =
+        serde_json::from_value(app_state.clone()).map_err(|e| e.to_string())?;
+    genesis["chain_id"] = chain_id.into();
+    genesis["app_hash"] = hex::encode_upper(exported_genesis_app_hash(&exported)?).into();
+    genesis["validators"] =
+        serde_json::to_value(exported_genesis_validators(&exported)?).map_err(|e| e.to_string())?;
+    genesis["app_state"] = app_state;
+    serde_json::to_string_pretty(&genesis).map_err(|e| e.to_string())
+}
@@ -1,11 +1,12 @@
 mod table;
 mod tx;
 
-pub use table::{RewardsDistribution, StakingTable};
+pub use table::{CouncilNodeMetadata, RewardsDistribution, StakingTable};
 
 #[cfg(test)]
 mod tests {
     use secp256k1::key::{PublicKey, SecretKey};
+    use secp256k1::Message;
     use std::str::FromStr;
 
     use chain_core::init::address::RedeemAddress;
@@ -13,7 +14,8 @@ mod tests {
     use chain_core::init::config::SlashRatio;
     use chain_core::init::params::NetworkParameters;
     use chain_core::state::account::{
-        NodeState, PunishmentKind, StakedState, StakedStateAddress, UnbondTx, UnjailTx, Validator,
+        NodeState, PunishmentKind, StakedState, StakedStateAddress, StakedStateOpWitness, UnbondTx,
+        UnjailTx, Validator,
     };
     use chain_core::state::tendermint::{BlockHeight, TendermintValidatorPubKey};
     use chain_core::state::validator::NodeJoinRequestTx;
@@ -28,7 +30,8 @@ mod tests {
     use crate::app::BeginBlockInfo;
     use crate::staking::table::{PunishmentOutcome, SlashedCoin};
     use crate::tx_error::{
-        DepositError, NodeJoinError, PublicTxError, UnbondError, UnjailError, WithdrawError,
+        CouncilVotesError, DepositError, NodeJoinError, PublicTxError, UnbondError,
+        UnjailError, WithdrawError,
     };
 
     macro_rules! matches {
@@ -839,4 +842,49 @@ mod tests {
         );
         assert!(staking.is_jailed());
     }
+
+    fn council_vote(seed: &[u8; 32], txid: &[u8; 32]) -> StakedStateOpWitness {
+        let secret_key = SecretKey::from_slice(seed).expect("32 bytes, within curve order");
+        let message = Message::from_slice(txid).expect("32 bytes");
+        StakedStateOpWitness::new(secp256k1::SECP256K1.sign_recoverable(&message, &secret_key))
+    }
+
+    #[test]
+    fn check_council_votes() {
+        let (table, store) = init_staking_table();
+        let txid = [0x11; 32];
+        let vote1 = council_vote(&[0xcc; 32], &txid);
+        let vote2 = council_vote(&[0xcd; 32], &txid);
+        let vote3 = council_vote(&[0xce; 32], &txid);
+
+        assert!(table
+            .check_council_votes(&store, &txid, &[vote2.clone(), vote3.clone()])
+            .is_ok());
+        assert!(matches!(
+            table.check_council_votes(&store, &txid, &[vote1.clone(), vote2.clone()]),
+            Err(PublicTxError::CouncilVotes(CouncilVotesError::InsufficientVotes))
+        ));
+        assert!(matches!(
+            table.check_council_votes(
+                &store,
+                &txid,
+                &[
+                    vote1.clone(),
+                    vote2.clone(),
+                    council_vote(&[0xcf; 32], &txid)
+                ]
+            ),
+            Err(PublicTxError::CouncilVotes(CouncilVotesError::NotCouncilNode))
+        ));
+        // the same vote repeated
+        assert!(matches!(
+            table.check_council_votes(&store, &txid, &[vote3.clone(), vote3.clone()]),
+            Err(PublicTxError::CouncilVotes(CouncilVotesError::DuplicateVote))
+        ));
+        // more votes than council nodes
+        assert!(matches!(
+            table.check_council_votes(&store, &txid, &[vote1, vote2, vote3.clone(), vote3]),
+            Err(PublicTxError::CouncilVotes(CouncilVotesError::TooManyVotes))
+        ));
+    }
 }
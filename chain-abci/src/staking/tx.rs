@@ -1,19 +1,26 @@
+use std::collections::BTreeSet;
+
 use log::warn;
 
 use chain_core::common::Timespec;
 use chain_core::init::coin::Coin;
 use chain_core::state::account::{
-    NodeMetadata, NodeState, StakedStateAddress, UnbondTx, UnjailTx, Validator,
+    DataAnchorTx, NodeMetadata, NodeState, StakedStateAddress, StakedStateOpWitness, UnbondTx,
+    UnjailTx, Validator,
 };
+use chain_core::state::governance::UpgradeSignalTx;
 use chain_core::state::tendermint::{BlockHeight, TendermintValidatorAddress};
 use chain_core::state::validator::NodeJoinRequestTx;
+use chain_core::tx::data::TxId;
 use chain_core::tx::fee::Fee;
-use chain_storage::buffer::StoreStaking;
+use chain_storage::buffer::{GetStaking, StoreStaking};
+use chain_tx_validation::witness::verify_tx_recover_address;
 use mls::{extras::check_nodejoin, DefaultCipherSuite};
 
 use super::table::{set_staking, StakingTable};
 use crate::tx_error::{
-    DepositError, NodeJoinError, PublicTxError, UnbondError, UnjailError, WithdrawError,
+    CouncilVotesError, DataAnchorError, DepositError, NodeJoinError, PublicTxError, UnbondError,
+    UnjailError, UpgradeSignalError, WithdrawError,
 };
 
 const MAX_USED_VALIDATOR_ADDR: usize = 10;
@@ -162,6 +169,72 @@ impl StakingTable {
         }
     }
 
+    /// Handle `UpgradeSignalTx` (the scheduled upgrade is checked by the caller)
+    pub fn upgrade_signal(
+        &mut self,
+        heap: &mut impl StoreStaking,
+        tx: &UpgradeSignalTx,
+    ) -> Result<(), PublicTxError> {
+        let mut staking = self.get_or_default(heap, &tx.address);
+        if tx.nonce != staking.nonce {
+            return Err(PublicTxError::IncorrectNonce);
+        }
+        match staking.node_meta.as_ref() {
+            Some(NodeState::CouncilNode(val)) if val.is_active() => {}
+            _ => return Err(UpgradeSignalError::NotCouncilNode.into()),
+        }
+        staking.inc_nonce();
+        set_staking(heap, staking, self.minimal_required_staking);
+        #[cfg(debug_assertions)]
+        self.check_invariants(heap);
+        Ok(())
+    }
+
+    /// Checks the council node votes of `NetworkParamsUpdateTx` and `AccountFreezeTx`
+    /// (the tally of the decision): the voters need to be distinct
+    /// active council nodes with more than 2/3 of the total voting power.
+    /// The number of votes and the repeated witnesses are checked before any signature is
+    /// verified, and the votes are verified one by one, so that an invalid vote is rejected
+    /// without verifying the following ones.
+    pub fn check_council_votes(
+        &self,
+        heap: &impl GetStaking,
+        txid: &TxId,
+        votes: &[StakedStateOpWitness],
+    ) -> Result<(), PublicTxError> {
+        let council_nodes = self.list_council_nodes(heap);
+        if votes.len() > council_nodes.len() {
+            return Err(CouncilVotesError::TooManyVotes.into());
+        }
+        for (i, vote) in votes.iter().enumerate() {
+            if votes[..i].contains(vote) {
+                return Err(CouncilVotesError::DuplicateVote.into());
+            }
+        }
+        let mut voted = BTreeSet::new();
+        let mut voted_power: u128 = 0;
+        for vote in votes.iter() {
+            let voter = verify_tx_recover_address(vote, txid)?;
+            let node = council_nodes
+                .iter()
+                .find(|node| node.staking_address == voter)
+                .ok_or(CouncilVotesError::NotCouncilNode)?;
+            if !voted.insert(voter) {
+                return Err(CouncilVotesError::DuplicateVote.into());
+            }
+            voted_power += u128::from(u64::from(node.voting_power));
+        }
+        let total_power = council_nodes
+            .iter()
+            .map(|node| u128::from(u64::from(node.voting_power)))
+            .sum::<u128>();
+        if voted_power * 3 > total_power * 2 {
+            Ok(())
+        } else {
+            Err(CouncilVotesError::InsufficientVotes.into())
+        }
+    }
+
     /// Handle deposit tx
     /// Enclave validation is done in enclave, only incomplete check here.
     pub fn deposit(
@@ -223,6 +296,31 @@ impl StakingTable {
         Ok(unbonded_from)
     }
 
+    /// Handle data anchor tx: the fee is paid from the bonded amount
+    pub fn data_anchor(
+        &mut self,
+        heap: &mut impl StoreStaking,
+        block_time: Timespec,
+        block_height: BlockHeight,
+        tx: &DataAnchorTx,
+        fee: Fee,
+    ) -> Result<(), PublicTxError> {
+        let mut staking = self.get_or_default(heap, &tx.from_staked_account);
+        if tx.nonce != staking.nonce {
+            return Err(PublicTxError::IncorrectNonce);
+        }
+        if staking.is_jailed() {
+            return Err(DataAnchorError::IsJailed.into());
+        }
+        self.sub_bonded(block_time, block_height, fee.to_coin(), &mut staking)
+            .map_err(DataAnchorError::CoinError)?;
+        staking.inc_nonce();
+        set_staking(heap, staking, self.minimal_required_staking);
+        #[cfg(debug_assertions)]
+        self.check_invariants(heap);
+        Ok(())
+    }
+
     /// Handle withdraw tx
     /// Enclave validation is done in enclave, only incomplete check here.
     pub fn withdraw(
@@ -1,17 +1,25 @@
+use crate::app::{
+    check_account_freeze, check_params_update, check_upgrade_signal, PendingParamsUpdate,
+    ScheduledUpgrade,
+};
 use crate::enclave_bridge::EnclaveProxy;
 use crate::staking::StakingTable;
-use crate::tx_error::PublicTxError;
-use chain_core::common::Timespec;
+use crate::tx_error::{PublicTxError, UnbondError};
+use chain_core::common::{Timespec, H256};
 use chain_core::init::coin::Coin;
+use chain_core::init::params::NetworkParameters;
 use chain_core::state::account::{
     CouncilNodeMeta, NodeMetadata, StakedStateAddress, StakedStateOpAttributes,
 };
+use chain_core::state::governance::{AccountFreezeState, AccountFreezeTx};
 use chain_core::tx::data::input::{TxoPointer, TxoSize};
+use chain_core::tx::data::TxId;
 use chain_core::tx::fee::Fee;
 use chain_core::tx::{TransactionId, TxEnclaveAux, TxObfuscated, TxPublicAux};
 use chain_storage::buffer::{GetKV, GetStaking, StoreStaking};
 use chain_tx_validation::{verify_unjailed, witness::verify_tx_recover_address, ChainInfo, Error};
 use enclave_protocol::{IntraEnclaveRequest, IntraEnclaveResponseOk, SealedLog};
+use std::collections::{BTreeMap, BTreeSet};
 
 pub enum TxAction {
     Enclave(TxEnclaveAction),
@@ -125,6 +133,14 @@ pub enum TxPublicAction {
         isv_svn: u16,
     },
     Unjail(StakedStateAddress),
+    NetworkParamsUpdate(PendingParamsUpdate),
+    UpgradeSignal(StakedStateAddress),
+    DataAnchor {
+        fee: Fee,
+        address: StakedStateAddress,
+        commitment: H256,
+    },
+    AccountFreeze(AccountFreezeTx),
 }
 
 impl TxPublicAction {
@@ -151,6 +167,10 @@ impl TxPublicAction {
             Self::Unbond { fee, .. } => *fee,
             Self::NodeJoin { .. } => Fee::new(Coin::zero()),
             Self::Unjail(_) => Fee::new(Coin::zero()),
+            Self::NetworkParamsUpdate(_) => Fee::new(Coin::zero()),
+            Self::UpgradeSignal(_) => Fee::new(Coin::zero()),
+            Self::DataAnchor { fee, .. } => *fee,
+            Self::AccountFreeze(_) => Fee::new(Coin::zero()),
         }
     }
 
@@ -159,6 +179,10 @@ impl TxPublicAction {
             Self::Unbond { unbond, .. } => Some(unbond.0),
             Self::NodeJoin { address, .. } => Some(*address),
             Self::Unjail(staking_address) => Some(*staking_address),
+            Self::NetworkParamsUpdate(_) => None,
+            Self::UpgradeSignal(staking_address) => Some(*staking_address),
+            Self::DataAnchor { address, .. } => Some(*address),
+            Self::AccountFreeze(tx) => Some(tx.address),
         }
     }
 }
@@ -171,19 +195,51 @@ fn check_spent_input_lookup(
     if inputs.is_empty() {
         return Err(Error::NoInputs);
     }
-    let mut result = Vec::with_capacity(inputs.len());
-    for txin in inputs.iter() {
-        let spent = chain_storage::lookup_input(kvdb, txin).ok_or(Error::InvalidInput)?;
-        if spent {
-            return Err(Error::InputSpent);
-        } else {
-            result.push(
-                chain_storage::get_sealed_log(kvdb, &txin.id)
-                    .expect("valid unspent tx output should be stored"),
-            );
+    // the spent status of all the inputs is read first, so that the first invalid or spent input
+    // (in the input order) is reported before any payload is read
+    for spent in chain_storage::lookup_inputs(kvdb, inputs) {
+        match spent {
+            None => return Err(Error::InvalidInput),
+            Some(true) => return Err(Error::InputSpent),
+            Some(false) => {}
         }
     }
-    Ok(result)
+    // the sealed payload of a transaction is read once, even if several of its outputs are spent
+    let txids = inputs
+        .iter()
+        .map(|txin| txin.id)
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect::<Vec<_>>();
+    let sealed_logs = txids
+        .iter()
+        .zip(chain_storage::get_sealed_logs(kvdb, &txids))
+        .map(|(txid, sealed_log)| {
+            (
+                *txid,
+                sealed_log.expect("valid unspent tx output should be stored"),
+            )
+        })
+        .collect::<BTreeMap<TxId, SealedLog>>();
+    Ok(inputs
+        .iter()
+        .map(|txin| sealed_logs[&txin.id].clone())
+        .collect())
+}
+
+/// Withdrawals out of a staked state frozen by a council decision are rejected
+/// (the enclave doesn't know the freeze decisions, so it's checked on its verified action)
+pub fn check_unfrozen_withdraw(
+    action: &TxEnclaveAction,
+    account_freeze: &AccountFreezeState,
+) -> Result<(), Error> {
+    match action {
+        TxEnclaveAction::Withdraw {
+            withdraw: (address, _),
+            ..
+        } if account_freeze.is_frozen(address) => Err(Error::AccountFrozen),
+        _ => Ok(()),
+    }
 }
 
 /// Checks TX against the current DB, passes to the enclave and returns an `Error` if something fails.
@@ -294,10 +350,15 @@ fn check_staking_attributes(
 
 /// Execute public transactions against uncommitted db.
 /// If OK, returns the paid fee + affected staking address
+#[allow(clippy::too_many_arguments)]
 pub fn process_public_tx(
     staking_store: &mut impl StoreStaking,
     staking_table: &mut StakingTable,
     enclave_isv_svn: u16,
+    network_params: &NetworkParameters,
+    pending_params_update: Option<&PendingParamsUpdate>,
+    scheduled_upgrade: Option<&ScheduledUpgrade>,
+    account_freeze: &AccountFreezeState,
     chain_info: &ChainInfo,
     txaux: &TxPublicAux,
 ) -> Result<TxPublicAction, PublicTxError> {
@@ -309,6 +370,9 @@ pub fn process_public_tx(
             if address != maintx.from_staked_account {
                 return Err(PublicTxError::StakingWitnessNotMatch);
             }
+            if account_freeze.is_frozen(&address) {
+                return Err(UnbondError::AccountFrozen.into());
+            }
             let unbonded_from = staking_table.unbond(
                 staking_store,
                 chain_info.get_unbonding_period(),
@@ -358,5 +422,49 @@ pub fn process_public_tx(
                 isv_svn,
             ))
         }
+        TxPublicAux::NetworkParamsUpdateTx(maintx, votes) => {
+            // the votes are verified after the cheap checks of the update
+            let update = check_params_update(
+                maintx,
+                network_params,
+                pending_params_update,
+                chain_info.block_height,
+            )?;
+            staking_table.check_council_votes(staking_store, &maintx.id(), votes)?;
+            Ok(TxPublicAction::NetworkParamsUpdate(update))
+        }
+        TxPublicAux::UpgradeSignalTx(maintx, witness) => {
+            let address = verify_tx_recover_address(&witness, &maintx.id())?;
+            if address != maintx.address {
+                return Err(PublicTxError::StakingWitnessNotMatch);
+            }
+            check_upgrade_signal(maintx, scheduled_upgrade, chain_info.block_height)?;
+            staking_table.upgrade_signal(staking_store, maintx)?;
+            Ok(TxPublicAction::UpgradeSignal(address))
+        }
+        TxPublicAux::DataAnchorTx(maintx, witness) => {
+            let address = verify_tx_recover_address(&witness, &maintx.id())?;
+            if address != maintx.from_staked_account {
+                return Err(PublicTxError::StakingWitnessNotMatch);
+            }
+            staking_table.data_anchor(
+                staking_store,
+                chain_info.block_time,
+                chain_info.block_height,
+                maintx,
+                chain_info.min_fee_computed,
+            )?;
+            Ok(TxPublicAction::DataAnchor {
+                fee: chain_info.min_fee_computed,
+                address,
+                commitment: maintx.commitment,
+            })
+        }
+        TxPublicAux::AccountFreezeTx(maintx, votes) => {
+            // the votes are verified after the cheap checks of the decision
+            check_account_freeze(maintx, account_freeze)?;
+            staking_table.check_council_votes(staking_store, &maintx.id(), votes)?;
+            Ok(TxPublicAction::AccountFreeze(maintx.clone()))
+        }
     }
 }
@@ -1,16 +1,48 @@
 use chain_core::init::coin::{Coin, CoinError};
+use chain_core::state::account::StakedStateAddress;
+use chain_core::tx::version::TxAuxVersion;
 use mls::extras::{self};
 
 #[derive(thiserror::Error, Debug)]
 pub enum TxError {
     #[error("deserialize TxAux failed: {0}")]
     DeserializeTx(#[from] parity_scale_codec::Error),
+    #[error("TxAux version {0} is not active at this block height")]
+    InactiveTxVersion(TxAuxVersion),
+    #[error("transaction type or staked state witness is not active at this block height")]
+    InactiveTxType,
     #[error("enclave tx validation failed: {0}")]
     Enclave(#[from] chain_tx_validation::Error),
     #[error("public tx process failed: {0}")]
     Public(#[from] PublicTxError),
     #[error("FIXME/WIP payload for MLS handshake (not yet supported)")]
     WIPMLSData,
+    #[error("rejected by the local mempool policy (not a consensus rule): {0}")]
+    MempoolPolicy(#[from] MempoolPolicyError),
+}
+
+impl TxError {
+    /// Stable label of the error variant (e.g. for metrics), without any payload
+    pub fn kind(&self) -> String {
+        match self {
+            TxError::DeserializeTx(_) => "DeserializeTx".to_owned(),
+            TxError::InactiveTxVersion(_) => "InactiveTxVersion".to_owned(),
+            TxError::InactiveTxType => "InactiveTxType".to_owned(),
+            TxError::Enclave(e) => format!("Enclave::{}", variant_name(e)),
+            TxError::Public(e) => format!("Public::{}", e.kind()),
+            TxError::WIPMLSData => "WIPMLSData".to_owned(),
+            TxError::MempoolPolicy(e) => format!("MempoolPolicy::{}", variant_name(e)),
+        }
+    }
+}
+
+/// Rejections of the node-local mempool policy (CheckTx only, never applied to DeliverTx)
+#[derive(thiserror::Error, Debug)]
+pub enum MempoolPolicyError {
+    #[error("paid fee {paid} is below the local minimal fee {minimal}")]
+    InsufficientFee { paid: Coin, minimal: Coin },
+    #[error("too many transactions of the staking address {0} since the last block")]
+    AddressRateLimited(StakedStateAddress),
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -31,6 +63,46 @@ pub enum PublicTxError {
     NodeJoin(#[from] NodeJoinError),
     #[error("unbond tx process failed: {0}")]
     Unbond(#[from] UnbondError),
+    #[error("network parameters update tx process failed: {0}")]
+    NetworkParamsUpdate(#[from] NetworkParamsUpdateError),
+    #[error("council votes rejected: {0}")]
+    CouncilVotes(#[from] CouncilVotesError),
+    #[error("account freeze tx process failed: {0}")]
+    AccountFreeze(#[from] AccountFreezeError),
+    #[error("upgrade signal tx process failed: {0}")]
+    UpgradeSignal(#[from] UpgradeSignalError),
+    #[error("data anchor tx process failed: {0}")]
+    DataAnchor(#[from] DataAnchorError),
+}
+
+impl PublicTxError {
+    /// Stable label of the error variant (e.g. for metrics), without any payload
+    pub fn kind(&self) -> String {
+        match self {
+            PublicTxError::StakingWitnessVerify(_) => "StakingWitnessVerify".to_owned(),
+            PublicTxError::Unjail(e) => format!("Unjail::{}", variant_name(e)),
+            PublicTxError::NodeJoin(e) => format!("NodeJoin::{}", variant_name(e)),
+            PublicTxError::Unbond(e) => format!("Unbond::{}", variant_name(e)),
+            PublicTxError::NetworkParamsUpdate(e) => {
+                format!("NetworkParamsUpdate::{}", variant_name(e))
+            }
+            PublicTxError::CouncilVotes(e) => format!("CouncilVotes::{}", variant_name(e)),
+            PublicTxError::AccountFreeze(e) => format!("AccountFreeze::{}", variant_name(e)),
+            PublicTxError::UpgradeSignal(e) => format!("UpgradeSignal::{}", variant_name(e)),
+            PublicTxError::DataAnchor(e) => format!("DataAnchor::{}", variant_name(e)),
+            e => variant_name(e),
+        }
+    }
+}
+
+/// name of the enum variant from its `Debug` representation
+fn variant_name(e: &impl std::fmt::Debug) -> String {
+    let debug = format!("{:?}", e);
+    debug
+        .split(|c: char| c == '(' || c == '{' || c == ' ')
+        .next()
+        .unwrap_or_default()
+        .to_owned()
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -81,6 +153,65 @@ pub enum UnbondError {
     IsJailed,
     #[error("the value of tx is zero")]
     ZeroValue,
+    #[error("the staking address is frozen by a council decision")]
+    AccountFrozen,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum NetworkParamsUpdateError {
+    #[error("another network parameters update is pending")]
+    UpdatePending,
+    #[error("network parameters version doesn't match")]
+    VersionMismatch,
+    #[error("the effective height is not after the current block")]
+    InvalidEffectiveHeight,
+    #[error("invalid network parameters change: {0}")]
+    InvalidChange(&'static str),
+}
+
+/// Rejections of the council node votes of `NetworkParamsUpdateTx` and `AccountFreezeTx`
+#[derive(thiserror::Error, Debug)]
+pub enum CouncilVotesError {
+    #[error("more votes than active council nodes")]
+    TooManyVotes,
+    #[error("the vote is not from an active council node")]
+    NotCouncilNode,
+    #[error("duplicate vote of a council node")]
+    DuplicateVote,
+    #[error("the votes don't have more than 2/3 of the voting power")]
+    InsufficientVotes,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum AccountFreezeError {
+    #[error("account freeze is not enabled in the network parameters")]
+    Disabled,
+    #[error("account freeze version doesn't match")]
+    VersionMismatch,
+    #[error("the account is already in the requested state")]
+    NoChange,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum UpgradeSignalError {
+    #[error("no upgrade is scheduled")]
+    NoScheduledUpgrade,
+    #[error("app version doesn't match the scheduled upgrade")]
+    VersionMismatch,
+    #[error("the upgrade height is reached")]
+    UpgradeHeightReached,
+    #[error("the council node already signalled its readiness")]
+    AlreadySignalled,
+    #[error("the signal is not from an active council node")]
+    NotCouncilNode,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum DataAnchorError {
+    #[error("coin error in data anchor tx: {0}")]
+    CoinError(#[from] CoinError),
+    #[error("the staking address is jailed")]
+    IsJailed,
 }
 
 #[derive(thiserror::Error, Debug)]
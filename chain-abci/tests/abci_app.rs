@@ -3,7 +3,9 @@ use bit_vec::BitVec;
 use chain_abci::app::*;
 use chain_abci::enclave_bridge::mock::MockClient;
 use chain_abci::staking::StakingTable;
-use chain_core::common::{MerkleTree, Proof, H256, HASH_SIZE_256};
+use chain_core::common::{
+    MerkleTree, Proof, TendermintEventKey, TendermintEventType, H256, HASH_SIZE_256,
+};
 use chain_core::compute_app_hash;
 use chain_core::init::address::RedeemAddress;
 use chain_core::init::coin::Coin;
@@ -37,11 +39,13 @@ use chain_core::tx::{
     witness::{TxInWitness, TxWitness},
     PlainTxAux, TransactionId, TxAux, TxEnclaveAux, TxPublicAux,
 };
-use chain_storage::buffer::Get;
+use chain_core::AppHashParts;
+use chain_storage::backup::export_snapshot;
+use chain_storage::buffer::{Get, MemStore};
 use chain_storage::jellyfish::SparseMerkleProof;
 use chain_storage::{
-    LookupItem, Storage, CHAIN_ID_KEY, COL_EXTRA, COL_NODE_INFO, GENESIS_APP_HASH_KEY,
-    LAST_STATE_KEY, NUM_COLUMNS,
+    LookupItem, Storage, CHAIN_ID_KEY, COL_EXTRA, COL_NODE_INFO, COL_UTXO_MMR,
+    GENESIS_APP_HASH_KEY, LAST_STATE_KEY, NUM_COLUMNS,
 };
 use chain_tx_filter::BlockFilter;
 use hex::decode;
@@ -223,11 +227,15 @@ fn get_dummy_app_state(app_hash: H256) -> ChainNodeState {
         staking_version: 0,
         utxo_coins: Coin::zero(),
         enclave_isv_svn: 0,
-        top_level: ChainState {
-            account_root: [0u8; 32],
-            rewards_pool: RewardsPoolState::new(0, params.get_rewards_monetary_expansion_tau()),
-            network_params: params,
-        },
+        pending_params_update: None,
+        scheduled_upgrade: None,
+        account_freeze: Default::default(),
+        top_level: ChainState::genesis(
+            [0u8; 32],
+            RewardsPoolState::new(0, params.get_rewards_monetary_expansion_tau()),
+            params,
+            [0u8; 32],
+        ),
     }
 }
 
@@ -266,7 +274,7 @@ fn init_chain_for(address: RedeemAddress) -> ChainNodeApp<MockClient> {
     .iter()
     .cloned()
     .collect();
-    let NetworkParameters::Genesis(params) = get_dummy_network_params();
+    let params = get_dummy_network_params().current().clone();
     let mut nodes = BTreeMap::new();
     let pub_key =
         TendermintValidatorPubKey::from_base64(b"MDAwMDAwMDAwMDAwMDAwMDAwMDAwMDAwMDAwMDAwMDA=")
@@ -295,13 +303,11 @@ fn init_chain_for(address: RedeemAddress) -> ChainNodeApp<MockClient> {
     };
     let result = c.validate_config_get_genesis(t.get_seconds().try_into().unwrap());
     if let Ok(genesis_state) = result {
-        let tx_tree = MerkleTree::empty();
         let mut storage = Storage::new_db(db.clone());
         let new_account_root = storage.put_stakings(0, &genesis_state.accounts);
-        let genesis_app_hash = compute_app_hash(
-            &tx_tree,
+        let genesis_app_hash = compute_genesis_app_hash(
             &new_account_root,
-            &genesis_state.rewards_pool,
+            &genesis_state,
             &get_dummy_network_params(),
         );
 
@@ -499,6 +505,43 @@ fn check_tx_should_accept_valid_tx() {
     creq.set_tx(txaux.encode());
     let cresp = app.check_tx(&creq);
     assert_eq!(0, cresp.code, "{}", cresp.log);
+    assert_eq!(1, cresp.events.len());
+    assert_eq!(
+        TendermintEventType::MempoolPriority.to_string(),
+        cresp.events[0].field_type
+    );
+    assert_eq!(
+        TendermintEventKey::Priority,
+        cresp.events[0].attributes[0].key
+    );
+}
+
+#[test]
+fn simulate_tx_query_should_not_persist_changes() {
+    let (mut app, txaux, _) = prepare_app_valid_tx();
+    let mut qreq = RequestQuery::new();
+    qreq.path = "simulate_tx".into();
+    qreq.data = txaux.encode();
+    for _ in 0..2 {
+        let qresp = app.query(&qreq);
+        assert_eq!(0, qresp.code, "{}", qresp.log);
+        let simulation: serde_json::Value = serde_json::from_slice(&qresp.value).unwrap();
+        assert_eq!("accepted", simulation["result"]);
+        assert_eq!("withdraw", simulation["tx_type"]);
+    }
+    assert!(app.mempool_staking_buffer.is_empty());
+    assert!(app.mempool_kv_buffer.is_empty());
+
+    qreq.data = vec![0xff; 3];
+    let qresp = app.query(&qreq);
+    assert_ne!(0, qresp.code);
+    let simulation: serde_json::Value = serde_json::from_slice(&qresp.value).unwrap();
+    assert_eq!("rejected", simulation["result"]);
+    assert_eq!("DeserializeTx", simulation["error"]);
+
+    let mut creq = RequestCheckTx::default();
+    creq.set_tx(txaux.encode());
+    assert_eq!(0, app.check_tx(&creq).code);
 }
 
 #[test]
@@ -625,7 +668,7 @@ fn deliver_tx_should_add_tx_events() {
     assert_eq!(2, cresp.events.len());
 
     let valid_tx_event = &cresp.events[0];
-    assert_eq!(2, valid_tx_event.attributes.len());
+    assert_eq!(5, valid_tx_event.attributes.len());
     // the unit test transaction just three outputs: 1 CRO + 1 carson / base unit + the rest
     assert_eq!(
         "0.00000347",
@@ -635,9 +678,21 @@ fn deliver_tx_should_add_tx_events() {
         &hex::encode(&tx.id()).as_bytes().to_vec(),
         &valid_tx_event.attributes[1].value
     );
+    assert_eq!(
+        "withdraw",
+        String::from_utf8(valid_tx_event.attributes[2].value.clone()).unwrap()
+    );
+    assert_eq!(
+        "0x89aef553a06ab0c3173e79de1ce241a9ed3b992c",
+        String::from_utf8(valid_tx_event.attributes[3].value.clone()).unwrap()
+    );
+    assert_eq!(
+        "3",
+        String::from_utf8(valid_tx_event.attributes[4].value.clone()).unwrap()
+    );
 
     let staking_event = &cresp.events[1];
-    assert_eq!(2, valid_tx_event.attributes.len());
+    assert_eq!(5, valid_tx_event.attributes.len());
     assert_eq!(
         "0x89aef553a06ab0c3173e79de1ce241a9ed3b992c",
         String::from_utf8(staking_event.attributes[0].value.clone()).unwrap()
@@ -835,13 +890,45 @@ fn staking_query_should_return_an_account() {
     let qresp = app.query(&qreq);
     let mstaking = <Option<StakedState>>::decode(&mut qresp.value.as_slice()).unwrap();
     let mut proof_bytes = qresp.proof.get_ref().ops[0].data.as_slice();
-    let _proof = SparseMerkleProof::decode(&mut proof_bytes).unwrap();
+    let proof = SparseMerkleProof::decode(&mut proof_bytes).unwrap();
+    let mut root_proof_bytes = qresp.proof.get_ref().ops[1].data.as_slice();
+    let root_proof = AppHashParts::decode(&mut root_proof_bytes).unwrap();
+    assert!(root_proof.verify(&app.genesis_app_hash));
+    assert!(proof
+        .verify(
+            root_proof.account_state_root,
+            &StakedStateAddress::from_str(addr).unwrap(),
+            mstaking.as_ref()
+        )
+        .is_ok());
     assert_eq!(
         mstaking.unwrap().address,
         StakedStateAddress::from_str(addr).unwrap()
     );
 }
 
+#[test]
+fn network_params_query_should_return_proven_params() {
+    let addr = "fe7c045110b8dbf29765047380898919c5cb56f9";
+    let mut app = init_chain_for(addr.parse().unwrap());
+    let mut qreq = RequestQuery::new();
+    qreq.path = "network-params".into();
+    qreq.prove = true;
+    let qresp = app.query(&qreq);
+    assert_eq!(0, qresp.code);
+    let params = NetworkParameters::decode(&mut qresp.value.as_slice()).unwrap();
+    assert_eq!(
+        params,
+        app.last_state.as_ref().unwrap().top_level.network_params
+    );
+    let mut proof_bytes = qresp.proof.get_ref().ops[0].data.as_slice();
+    let proof = AppHashParts::decode(&mut proof_bytes).unwrap();
+    assert!(proof.verify_network_params(&params, &app.genesis_app_hash));
+
+    qreq.height = 10;
+    assert_ne!(0, app.query(&qreq).code);
+}
+
 fn block_commit_with_check(app: &mut ChainNodeApp<MockClient>, tx: TxAux, block_height: i64) {
     let r = RequestInfo::default();
     let info_1 = app.info(&r);
@@ -1130,6 +1217,153 @@ fn all_valid_tx_types_should_commit() {
     }
 }
 
+#[test]
+fn spend_after_restoring_legacy_database_should_commit() {
+    // the app hash of the 0.5.0 release doesn't commit to the utxo set
+    // (its databases can't be used when it does)
+    if chain_core::UTXO_SET_COMMITTED {
+        return;
+    }
+    let secp = secp256k1::SECP256K1;
+    let secret_key = SecretKey::from_slice(&[0xcd; 32]).expect("32 bytes, within curve order");
+    let x_public_key = XOnlyPublicKey::from_secret_key(&secp, &secret_key);
+    let public_key = PublicKey::from_secret_key(&secp, &secret_key);
+    let addr = RedeemAddress::from(&public_key);
+    let mut app = init_chain_for(addr);
+
+    let merkle_tree = MerkleTree::new(vec![RawXOnlyPubkey::from(x_public_key.serialize())]);
+    let eaddr = ExtendedAddr::OrTree(merkle_tree.root_hash());
+    let tx0 = WithdrawUnbondedTx::new(
+        0,
+        vec![TxOut::new_with_timelock(
+            eaddr.clone(),
+            Coin::one(),
+            DEFAULT_GENESIS_TIME,
+        )],
+        TxAttributes::new_with_access(0, vec![TxAccessPolicy::new(public_key, TxAccess::AllData)]),
+    );
+    let txid = tx0.id();
+    let witness0 = StakedStateOpWitness::new(get_ecdsa_witness(&secp, &txid, &secret_key));
+    let withdrawtx = TxAux::EnclaveTx(TxEnclaveAux::WithdrawUnbondedStakeTx {
+        no_of_outputs: tx0.outputs.len() as TxoSize,
+        witness: witness0,
+        payload: encrypt(&PlainTxAux::WithdrawUnbondedStakeTx(tx0.clone()), tx0.id()),
+    });
+    block_commit_with_check(&mut app, withdrawtx, 1);
+
+    // a database of the 0.5.0 release has no mountain range of the outputs
+    let db = app.storage.temp_hack_for_tdbe();
+    let mut dbtx = db.transaction();
+    for (key, _) in db.iter(COL_UTXO_MMR) {
+        dbtx.delete(COL_UTXO_MMR, &key);
+    }
+    db.write(dbtx).unwrap();
+    let mut app = ChainNodeApp::new_with_storage(
+        get_enclave_bridge_mock(),
+        &hex::encode_upper(app.genesis_app_hash),
+        TEST_CHAIN_ID,
+        Storage::new_db(db),
+        None,
+        "".to_string(),
+    );
+
+    let mut tx1 = Tx::new();
+    tx1.add_input(TxoPointer::new(txid, 0));
+    tx1.add_output(TxOut::new(eaddr, Coin::from(99999700u32)));
+    let witness1 = vec![TxInWitness::TreeSig(
+        schnorr_sign(
+            &secp,
+            &Message::from_slice(&tx1.id()).unwrap(),
+            &secret_key,
+            &mut rand::thread_rng(),
+        ),
+        merkle_tree
+            .generate_proof(RawXOnlyPubkey::from(x_public_key.serialize()))
+            .unwrap(),
+    )]
+    .into();
+    let transfertx = TxAux::EnclaveTx(TxEnclaveAux::TransferTx {
+        inputs: tx1.inputs.clone(),
+        no_of_outputs: tx1.outputs.len() as TxoSize,
+        payload: encrypt(&PlainTxAux::TransferTx(tx1.clone(), witness1), tx1.id()),
+    });
+    block_commit_with_check(&mut app, transfertx, 2);
+    assert!(get_tx_meta(&txid, &app)[0]);
+    assert!(!get_tx_meta(&tx1.id(), &app).any());
+}
+
+#[test]
+fn export_should_include_outputs_created_before_utxo_commitment() {
+    let (mut app, withdrawtx, tx0) = prepare_app_valid_tx();
+    let txid = tx0.id();
+    block_commit_with_check(&mut app, withdrawtx, 1);
+
+    // the database was created before the mountain range of the outputs existed
+    let db = app.storage.temp_hack_for_tdbe();
+    let mut dbtx = db.transaction();
+    for (key, _) in db.iter(COL_UTXO_MMR) {
+        dbtx.delete(COL_UTXO_MMR, &key);
+    }
+    db.write(dbtx).unwrap();
+
+    let exported = export_state(&app.storage, BlockHeight::new(1)).expect("export state");
+    assert!(exported.unspent_outputs.contains(&TxoPointer::new(txid, 0)));
+    assert!(exported.unspent_outputs.contains(&TxoPointer::new(txid, 1)));
+    // the upgraded chain stores the same spent flags
+    let mut utxos: MemStore<(u32, Vec<u8>), Vec<u8>> = MemStore::new();
+    store_exported_utxos(&mut utxos, &exported);
+    assert_eq!(
+        app.storage.lookup_item(LookupItem::TxMetaSpent, &txid),
+        chain_storage::lookup_item(&utxos, LookupItem::TxMetaSpent, &txid)
+    );
+}
+
+#[test]
+fn restored_snapshot_should_have_same_app_hash() {
+    let (mut app, withdrawtx, _) = prepare_app_valid_tx();
+    block_commit_with_check(&mut app, withdrawtx, 1);
+    let state = app.last_state.clone().unwrap();
+
+    let directory = std::env::temp_dir().join(format!("chain-abci-restore-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&directory);
+    let backups = directory.join("backups");
+    std::fs::create_dir_all(&backups).unwrap();
+    export_snapshot(
+        &*app.storage.temp_hack_for_tdbe(),
+        &backups.join("backup-00000000000000000001"),
+        state.last_block_height,
+        state.last_apphash,
+    )
+    .unwrap();
+    let source = StateSync::new(&backups);
+
+    // not the trusted app hash
+    assert!(StateSync::new(&directory)
+        .restore_from(&source, 1, [0u8; 32], &*create_db())
+        .is_err());
+
+    let restored = create_db();
+    StateSync::new(&directory)
+        .restore_from(&source, 1, state.last_apphash, &*restored)
+        .expect("restore snapshot");
+    let mut restored_app = ChainNodeApp::new_with_storage(
+        get_enclave_bridge_mock(),
+        &hex::encode_upper(app.genesis_app_hash),
+        TEST_CHAIN_ID,
+        Storage::new_db(restored),
+        None,
+        "".to_string(),
+    );
+    let info = restored_app.info(&RequestInfo::default());
+    assert_eq!(1, info.last_block_height);
+    assert_eq!(state.last_apphash.to_vec(), info.last_block_app_hash);
+    assert_eq!(
+        state.last_apphash,
+        restored_app.last_state.unwrap().last_apphash
+    );
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
 #[test]
 fn query_should_return_proof_for_committed_tx() {
     let (env, storage) =
@@ -1181,13 +1415,7 @@ fn query_should_return_proof_for_committed_tx() {
     assert_eq!(merkle.root_hash(), transaction_root_hash);
     let last_state = app.last_state.clone().unwrap();
     assert_eq!(
-        compute_app_hash(
-            &merkle,
-            &last_state.top_level.account_root,
-            &last_state.top_level.rewards_pool,
-            &last_state.top_level.network_params
-        )
-        .to_vec(),
+        compute_app_hash(&merkle, &last_state.top_level).to_vec(),
         cresp.data
     );
     let mut qreq2 = RequestQuery::new();
@@ -0,0 +1,157 @@
+#![cfg(feature = "fault-injection")]
+//! Chaos tests: the node is run with faults injected at the storage writes and
+//! the enclave bridge calls, and restarted from its storage whenever it halts
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::Arc;
+use std::time::Duration;
+
+use abci::*;
+use chain_abci::app::ChainNodeApp;
+use chain_abci::enclave_bridge::fault::FaultyEnclave;
+use chain_abci::enclave_bridge::mock::MockClient;
+use chain_core::common::H256;
+use chain_core::init::coin::Coin;
+use chain_storage::fault::{FaultConfig, FaultInjector};
+use chain_storage::Storage;
+use kvdb::KeyValueDB;
+use parity_scale_codec::Encode;
+use test_common::chain_env::ChainEnv;
+
+type FaultyApp = ChainNodeApp<FaultyEnclave<MockClient>>;
+
+const BLOCKS: i64 = 5;
+
+/// Starts the node over the database (it recovers the last committed state, if any)
+fn start_node(
+    env: &ChainEnv,
+    db: &Arc<dyn KeyValueDB>,
+    storage_faults: &Arc<FaultInjector>,
+    enclave_faults: &Arc<FaultInjector>,
+) -> FaultyApp {
+    let mut app = ChainNodeApp::new_with_storage(
+        FaultyEnclave::new(MockClient::new(0), enclave_faults.clone()),
+        &hex::encode_upper(env.genesis_app_hash),
+        "test-00",
+        Storage::new_db(db.clone()).with_fault_injection(storage_faults.clone()),
+        None,
+        "".to_string(),
+    );
+    if app.last_state.is_none() {
+        app.init_chain(&env.req_init_chain());
+    }
+    app
+}
+
+fn last_committed(app: &FaultyApp) -> (i64, H256) {
+    let state = app.last_state.as_ref().expect("app state");
+    (state.last_block_height.value() as i64, state.last_apphash)
+}
+
+/// Block unbonding a small amount of the first account, returns the committed app hash
+fn run_block(env: &ChainEnv, app: &mut FaultyApp, height: i64) -> H256 {
+    app.begin_block(&env.req_begin_block(height, 0));
+    let tx = env.unbond_tx(Coin::unit(), height as u64 - 1, 0);
+    let rsp = app.deliver_tx(&RequestDeliverTx {
+        tx: tx.encode(),
+        ..Default::default()
+    });
+    assert_eq!(0, rsp.code, "{}", rsp.log);
+    app.end_block(&RequestEndBlock {
+        height,
+        ..Default::default()
+    });
+    let rsp = app.commit(&RequestCommit::new());
+    let mut app_hash = H256::default();
+    app_hash.copy_from_slice(&rsp.data);
+    app_hash
+}
+
+/// Runs the blocks, restarting the node whenever it halts (like tendermint would replay
+/// the uncommitted block after a restart), returns the app hashes and the number of restarts
+fn run_chain(storage_faults: FaultConfig, enclave_faults: FaultConfig) -> (Vec<H256>, usize) {
+    let (env, storage) = ChainEnv::new(Coin::max(), Coin::zero(), 1);
+    let db = storage.temp_hack_for_tdbe();
+    let storage_faults = Arc::new(FaultInjector::new(storage_faults));
+    let enclave_faults = Arc::new(FaultInjector::new(enclave_faults));
+
+    let mut app_hashes = Vec::new();
+    let mut restarts = 0;
+    let mut node = None;
+    while (app_hashes.len() as i64) < BLOCKS {
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            let app =
+                node.get_or_insert_with(|| start_node(&env, &db, &storage_faults, &enclave_faults));
+            let (last_height, last_app_hash) = last_committed(app);
+            // a crashed node resumes from its last committed block
+            assert_eq!(app_hashes.len() as i64, last_height);
+            if let Some(committed) = app_hashes.last() {
+                assert_eq!(*committed, last_app_hash);
+            }
+            run_block(&env, app, last_height + 1)
+        }));
+        match result {
+            Ok(app_hash) => app_hashes.push(app_hash),
+            Err(_) => {
+                node = None;
+                restarts += 1;
+                assert!(restarts < 100, "the node doesn't make progress");
+            }
+        }
+    }
+    (app_hashes, restarts)
+}
+
+#[test]
+fn crash_during_commit_should_recover_last_committed_state() {
+    let (expected, restarts) = run_chain(Default::default(), Default::default());
+    assert_eq!(0, restarts);
+
+    // the first storage writes are made by the init chain, the next ones by the commits
+    for crash_at in 0..=4 {
+        let (app_hashes, restarts) = run_chain(
+            FaultConfig {
+                crash_at: Some(crash_at),
+                ..Default::default()
+            },
+            Default::default(),
+        );
+        assert_eq!(1, restarts);
+        assert_eq!(expected, app_hashes);
+    }
+}
+
+#[test]
+fn enclave_failures_should_halt_the_node_safely() {
+    let (expected, _) = run_chain(Default::default(), Default::default());
+    // enclave calls: 0 = sanity check at start, 1 = end block of block 1, 2 = of block 2, ...
+    let (app_hashes, restarts) = run_chain(
+        Default::default(),
+        FaultConfig {
+            crash_at: Some(2),
+            ..Default::default()
+        },
+    );
+    assert_eq!(1, restarts);
+    assert_eq!(expected, app_hashes);
+}
+
+#[test]
+fn random_faults_should_keep_consensus_state_consistent() {
+    let (expected, _) = run_chain(Default::default(), Default::default());
+    for seed in 1..=5 {
+        let faults = FaultConfig {
+            seed,
+            max_latency: Duration::from_millis(2),
+            error_rate: 0.2,
+            crash_at: None,
+        };
+        let (app_hashes, _) = run_chain(
+            faults.clone(),
+            FaultConfig {
+                seed: seed + 100,
+                ..faults
+            },
+        );
+        assert_eq!(expected, app_hashes, "seed {}", seed);
+    }
+}
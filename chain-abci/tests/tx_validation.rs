@@ -12,6 +12,7 @@ use chain_abci::tx_error::{NodeJoinError, PublicTxError, TxError, UnbondError, U
 use chain_core::common::{MerkleTree, Timespec};
 use chain_core::init::address::RedeemAddress;
 use chain_core::init::coin::{Coin, CoinError};
+use chain_core::init::params::NetworkParameters;
 use chain_core::state::account::StakedState;
 use chain_core::state::account::StakedStateAddress;
 use chain_core::state::account::StakedStateOpAttributes;
@@ -19,6 +20,7 @@ use chain_core::state::account::{
     DepositBondTx, NodeMetadata, StakedStateOpWitness, UnbondTx, UnjailTx, Validator,
     WithdrawUnbondedTx,
 };
+use chain_core::state::governance::AccountFreezeState;
 use chain_core::state::tendermint::BlockHeight;
 use chain_core::state::tendermint::TendermintValidatorPubKey;
 use chain_core::state::validator::NodeJoinRequestTx;
@@ -53,7 +55,8 @@ use std::fmt::Debug;
 use std::mem;
 use std::sync::Arc;
 use test_common::chain_env::{
-    mock_confidential_init_node_join, mock_council_node_meta, DEFAULT_GENESIS_TIME,
+    get_init_network_params, mock_confidential_init_node_join, mock_council_node_meta,
+    DEFAULT_GENESIS_TIME,
 };
 
 fn verify_enclave_tx<T: EnclaveProxy>(
@@ -78,13 +81,42 @@ fn verify_public_tx(
     info: NodeInfoWrap,
     version: Version,
     storage: &Storage,
+) -> Result<(Fee, Option<StakedState>), TxError> {
+    verify_public_tx_with_freeze(
+        txaux,
+        extra_info,
+        info,
+        version,
+        storage,
+        &AccountFreezeState::default(),
+    )
+}
+
+fn verify_public_tx_with_freeze(
+    txaux: &TxPublicAux,
+    extra_info: &ChainInfo,
+    info: NodeInfoWrap,
+    version: Version,
+    storage: &Storage,
+    account_freeze: &AccountFreezeState,
 ) -> Result<(Fee, Option<StakedState>), TxError> {
     let mut tbl =
         StakingTable::from_genesis(&StakingGetter::new(storage, version), info.0, 50, &info.1);
     let mut buffer = HashMap::new();
 
     let mut store = StakingBufferStore::new(StakingGetter::new(storage, version), &mut buffer);
-    let tx_action = process_public_tx(&mut store, &mut tbl, 0, extra_info, txaux)?;
+    let params = NetworkParameters::Genesis(get_init_network_params(Coin::zero()));
+    let tx_action = process_public_tx(
+        &mut store,
+        &mut tbl,
+        0,
+        &params,
+        None,
+        None,
+        account_freeze,
+        extra_info,
+        txaux,
+    )?;
 
     let fee = tx_action.fee();
     let maddress = tx_action.staking_address();
@@ -364,6 +396,35 @@ fn test_account_unbond_verify_fail() {
     }
 }
 
+#[test]
+fn frozen_account_unbond_tx_should_fail() {
+    let (txaux, tx, _, storage) = prepare_app_valid_unbond_tx();
+    let extra_info = get_chain_info_pub(&txaux);
+    let mut account_freeze = AccountFreezeState::default();
+    account_freeze.frozen.insert(tx.from_staked_account);
+    // the frozen accounts are ignored while the capability is disabled
+    let result = verify_public_tx_with_freeze(
+        &txaux,
+        &extra_info,
+        NodeInfoWrap::default(),
+        0,
+        &storage,
+        &account_freeze,
+    );
+    assert!(result.is_ok());
+
+    account_freeze.enabled = true;
+    let result = verify_public_tx_with_freeze(
+        &txaux,
+        &extra_info,
+        NodeInfoWrap::default(),
+        0,
+        &storage,
+        &account_freeze,
+    );
+    expect_error_unbond(&result, UnbondError::AccountFrozen);
+}
+
 fn prepare_app_valid_withdraw_tx(
     unbonded_from: Timespec,
 ) -> (
@@ -1134,6 +1195,39 @@ fn test_transfer_verify_fail() {
         let result = verify_enclave_tx(&mut mock_bridge, &txaux, &extra_info, 0, &storage);
         assert!(result.is_err());
     }
+    // the inputs of all witnesses are checked before the signatures
+    {
+        let secp = secp256k1::SECP256K1;
+        let addr = get_address(&secp, &secret_key).0;
+        let unlocked_tx = get_old_tx(addr.clone(), false);
+        let locked_tx = get_old_tx(addr.clone(), true);
+        let mut tx = Tx::new();
+        tx.add_input(TxoPointer::new(unlocked_tx.id(), 0));
+        tx.add_input(TxoPointer::new(locked_tx.id(), 0));
+        tx.add_output(TxOut::new(addr, Coin::one()));
+        let other_key = SecretKey::from_slice(&[0x11; 32]).expect("32 bytes, within curve order");
+        let witness: TxWitness = vec![
+            get_tx_witness(
+                secp,
+                &tx.id(),
+                &other_key,
+                &get_address(&secp, &other_key).1,
+            ),
+            get_tx_witness(secp, &tx.id(), &secret_key, &merkle_tree),
+        ]
+        .into();
+        let input_txs = vec![
+            TxWithOutputs::Transfer(unlocked_tx),
+            TxWithOutputs::Transfer(locked_tx),
+        ];
+        let result = verify_transfer(&tx, &witness, &extra_info, input_txs.clone());
+        expect_error(&result, Error::OutputInTimelock);
+        // the invalid signature of the first input once the second one is unlocked
+        let mut extra_info = extra_info;
+        extra_info.block_time = DEFAULT_GENESIS_TIME + 20;
+        let result = verify_transfer(&tx, &witness, &extra_info, input_txs);
+        expect_error(&result, Error::EcdsaCrypto);
+    }
 }
 
 fn prepare_jailed_accounts() -> (
@@ -65,6 +65,18 @@ impl<T> Tree<T> {
         }
     }
 
+    /// Appends the values of the leaves of tree (from left to right) to `values`
+    fn collect_values<'a>(&'a self, values: &mut Vec<&'a T>) {
+        match self {
+            Tree::Empty => {}
+            Tree::Leaf { value, .. } => values.push(value),
+            Tree::Node { left, right, .. } => {
+                left.collect_values(values);
+                right.collect_values(values);
+            }
+        }
+    }
+
     /// Generates merkle path for given value. Returns `None` if given value is not present in tree.
     /// Uses depth first search (DFS) to find value in tree
     fn generate_path(&self, value: &T) -> Option<Path>
@@ -421,6 +433,13 @@ impl<T> MerkleTree<T> {
         0 == self.len
     }
 
+    /// Returns the values of the leaf nodes in merkle tree (in the order they were given)
+    pub fn values(&self) -> Vec<&T> {
+        let mut values = Vec::with_capacity(self.len());
+        self.tree.collect_values(&mut values);
+        values
+    }
+
     /// Generates inclusion proof for given value. Returns `None` if given value is not present in merkle tree
     pub fn generate_proof(&self, value: T) -> Option<Proof<T>>
     where
@@ -501,6 +520,15 @@ mod tests {
         assert_eq!(0, tree.height());
         assert_eq!(0, tree.len());
         assert_eq!(None, tree.generate_proof([0u8; 32]));
+        assert!(tree.values().is_empty());
+    }
+
+    #[test]
+    fn check_values() {
+        let values = vec!["one", "two", "three", "four", "five"];
+        let tree = MerkleTree::new(values.clone());
+
+        assert_eq!(values.iter().collect::<Vec<_>>(), tree.values());
     }
 
     #[test]
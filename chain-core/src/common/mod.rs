@@ -32,7 +32,7 @@ pub type H264 = [u8; HASH_SIZE_256 + 1];
 /// 64-byte for sigs etc.
 pub type H512 = [u8; HASH_SIZE_256 * 2];
 
-/// Types of tendermint events created during `deliver_tx` / `end_block`
+/// Types of tendermint events created during `check_tx` / `deliver_tx` / `end_block`
 #[derive(Debug, Clone, Copy)]
 pub enum TendermintEventType {
     /// if transaction is valid
@@ -43,6 +43,8 @@ pub enum TendermintEventType {
     StakingChange,
     /// when reward was distributed
     Reward,
+    /// mempool priority of a transaction (in `check_tx`)
+    MempoolPriority,
 }
 
 impl fmt::Display for TendermintEventType {
@@ -52,6 +54,7 @@ impl fmt::Display for TendermintEventType {
             TendermintEventType::BlockFilter => write!(f, "block_filter"),
             TendermintEventType::StakingChange => write!(f, "staking_change"),
             TendermintEventType::Reward => write!(f, "reward"),
+            TendermintEventType::MempoolPriority => write!(f, "mempool_priority"),
         }
     }
 }
@@ -77,6 +80,18 @@ pub enum TendermintEventKey {
     CoinMinted,
     /// when state was slashed
     Slash,
+    /// mempool priority score
+    Priority,
+    /// paid fee per kilobyte of the transaction
+    FeeDensity,
+    /// transaction type (in valid transactions)
+    TxType,
+    /// number of the spent transaction outputs
+    InputCount,
+    /// number of the created transaction outputs
+    OutputCount,
+    /// anchored commitment (in valid data anchoring transactions)
+    AnchorCommitment,
 }
 
 impl From<TendermintEventKey> for Vec<u8> {
@@ -109,6 +124,12 @@ impl fmt::Display for TendermintEventKey {
             TendermintEventKey::StakingOpReason => write!(f, "staking_opreason"),
             TendermintEventKey::CoinMinted => write!(f, "minted"),
             TendermintEventKey::Slash => write!(f, "slash"),
+            TendermintEventKey::Priority => write!(f, "priority"),
+            TendermintEventKey::FeeDensity => write!(f, "fee_density"),
+            TendermintEventKey::TxType => write!(f, "txtype"),
+            TendermintEventKey::InputCount => write!(f, "input_count"),
+            TendermintEventKey::OutputCount => write!(f, "output_count"),
+            TendermintEventKey::AnchorCommitment => write!(f, "anchor_commitment"),
         }
     }
 }
@@ -133,6 +154,12 @@ impl TendermintEventKey {
             TendermintEventKey::StakingOpReason => String::from("c3Rha2luZ19vcHJlYXNvbg=="),
             TendermintEventKey::CoinMinted => String::from("bWludGVk"),
             TendermintEventKey::Slash => String::from("c2xhc2g="),
+            TendermintEventKey::Priority => String::from("cHJpb3JpdHk="),
+            TendermintEventKey::FeeDensity => String::from("ZmVlX2RlbnNpdHk="),
+            TendermintEventKey::TxType => String::from("dHh0eXBl"),
+            TendermintEventKey::InputCount => String::from("aW5wdXRfY291bnQ="),
+            TendermintEventKey::OutputCount => String::from("b3V0cHV0X2NvdW50"),
+            TendermintEventKey::AnchorCommitment => String::from("YW5jaG9yX2NvbW1pdG1lbnQ="),
         }
     }
 }
@@ -93,6 +93,9 @@ pub enum ErrorAddress {
 
     /// CRO error
     InvalidCroAddress,
+
+    /// A mixed-case address with a wrong EIP-55 checksum
+    InvalidChecksum(String),
 }
 
 impl From<hex::FromHexError> for ErrorAddress {
@@ -119,6 +122,9 @@ impl fmt::Display for ErrorAddress {
             }
             ErrorAddress::EcdsaCrypto(ref err) => write!(f, "ECDSA crypto error: {}", err),
             ErrorAddress::InvalidCroAddress => write!(f, "Invalid CroAddress"),
+            ErrorAddress::InvalidChecksum(ref str) => {
+                write!(f, "Invalid EIP-55 address checksum: {}", str)
+            }
         }
     }
 }
@@ -193,6 +199,29 @@ impl RedeemAddress {
 
         Ok(RedeemAddress(to_arr(data)))
     }
+
+    /// EIP-55 mixed-case checksum encoding (the `Display` / serialization form stays lowercase,
+    /// which is the canonical one used in storage)
+    pub fn to_checksummed(&self) -> String {
+        format!("0x{}", checksum_encode(&hex::encode(self.0)))
+    }
+}
+
+/// Applies the EIP-55 checksum to the lowercase hex-encoded address (without `0x`)
+fn checksum_encode(lowercase_hex: &str) -> String {
+    let hash = keccak256(lowercase_hex.as_bytes());
+    lowercase_hex
+        .chars()
+        .enumerate()
+        .map(|(i, c)| {
+            let nibble = (hash[i / 2] >> (if i % 2 == 0 { 4 } else { 0 })) & 0x0f;
+            if nibble >= 8 {
+                c.to_ascii_uppercase()
+            } else {
+                c
+            }
+        })
+        .collect()
 }
 
 impl ops::Deref for RedeemAddress {
@@ -230,7 +259,14 @@ impl FromStr for RedeemAddress {
             s
         };
 
-        RedeemAddress::try_from(hex::decode(&value)?.as_slice())
+        let address = RedeemAddress::try_from(hex::decode(&value)?.as_slice())?;
+        // all-lowercase and all-uppercase addresses carry no checksum
+        let mixed_case = value.chars().any(|c| c.is_ascii_lowercase())
+            && value.chars().any(|c| c.is_ascii_uppercase());
+        if mixed_case && checksum_encode(&value.to_ascii_lowercase()) != value {
+            return Err(ErrorAddress::InvalidChecksum(s.to_string()));
+        }
+        Ok(address)
     }
 }
 
@@ -363,4 +399,30 @@ mod tests {
     fn should_catch_empty_address_string() {
         assert!("".parse::<RedeemAddress>().is_err());
     }
+
+    #[test]
+    fn should_parse_and_display_checksummed_address() {
+        for checksummed in &[
+            "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed",
+            "0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359",
+            "0xdbF03B407c01E7cD3CBea99509d93f8DDDC8C6FB",
+            "0xD1220A0cf47c7B9Be7A2E6BA89F429762e7b9aDb",
+        ] {
+            let addr = checksummed.parse::<RedeemAddress>().unwrap();
+            assert_eq!(&addr.to_checksummed(), checksummed);
+            assert_eq!(addr.to_string(), checksummed.to_lowercase());
+            assert_eq!(
+                addr,
+                checksummed.to_uppercase()[2..]
+                    .parse::<RedeemAddress>()
+                    .unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn should_catch_wrong_address_checksum() {
+        let result = "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAeD".parse::<RedeemAddress>();
+        assert!(matches!(result, Err(ErrorAddress::InvalidChecksum(_))));
+    }
 }
@@ -0,0 +1,198 @@
+use crate::init::coin::{sum_coins, Coin, CoinError};
+use crate::init::params::NetworkParameters;
+use crate::state::account::{NodeState, StakedState, StakedStateAddress};
+use crate::state::tendermint::BlockHeight;
+use crate::state::{RewardsPoolState, UtxoChange};
+use crate::tx::data::input::{TxoPointer, TxoSize};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// problems with an exported state
+#[derive(thiserror::Error, Debug)]
+pub enum ExportedStateError {
+    /// coin arithmetic problems
+    #[error("coin error: {0}")]
+    CoinError(#[from] CoinError),
+    /// the circulating coins don't match the initial distribution and the minted rewards
+    #[error("The circulating coins ({0}) do not match the initial distribution and the minted rewards ({1})")]
+    SupplyMismatch(Coin, Coin),
+    /// staked state included more than once
+    #[error("Duplicate staked state ({0})")]
+    DuplicateAccount(StakedStateAddress),
+    /// unspent output included more than once
+    #[error("Duplicate unspent output")]
+    DuplicateOutput,
+    /// at least one council node needs to be included
+    #[error("No council nodes in the exported state")]
+    NoValidators,
+}
+
+/// State of a chain after a committed block ("app_state" in genesis.json of the upgraded chain
+/// which starts from it, e.g. for a coordinated hard fork)
+///
+/// The export is deterministic (the staked states are ordered as in the merkle trie,
+/// the unspent outputs by their pointers), so the exports of all nodes can be compared.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct ExportedState {
+    /// chain ID of the exported chain
+    pub exported_chain_id: String,
+    /// height of the last block of the exported chain
+    pub exported_height: BlockHeight,
+    /// network parameters (including the applied updates)
+    pub network_params: NetworkParameters,
+    /// all staked states
+    pub accounts: Vec<StakedState>,
+    /// rewards pool
+    pub rewards_pool: RewardsPoolState,
+    /// the unspent transaction outputs (their amounts are only known to the enclaves)
+    pub unspent_outputs: Vec<TxoPointer>,
+    /// sum of the amounts of the unspent transaction outputs
+    pub utxo_coins: Coin,
+    /// the biggest enclave ISVSVN seen in the key packages
+    pub enclave_isv_svn: u16,
+}
+
+impl ExportedState {
+    /// checks if the exported state is valid:
+    /// - staked states and outputs are unique
+    /// - utxo_coins + staking + rewards pool = initial distribution + minted
+    /// - there is at least one council node
+    ///
+    /// if valid, it'll return the addresses of the council nodes
+    pub fn validate(&self) -> Result<Vec<StakedStateAddress>, ExportedStateError> {
+        let mut addresses = BTreeSet::new();
+        for account in self.accounts.iter() {
+            if !addresses.insert(account.address) {
+                return Err(ExportedStateError::DuplicateAccount(account.address));
+            }
+        }
+        let outputs = self.unspent_outputs.iter().collect::<BTreeSet<_>>();
+        if outputs.len() != self.unspent_outputs.len() {
+            return Err(ExportedStateError::DuplicateOutput);
+        }
+
+        let staking = sum_coins(
+            self.accounts
+                .iter()
+                .flat_map(|account| vec![account.bonded, account.unbonded].into_iter()),
+        )?;
+        let circulating = ((staking + self.utxo_coins)? + self.rewards_pool.period_bonus)?;
+        let init_dist = (Coin::max() - self.network_params.get_rewards_monetary_expansion_cap())?;
+        let expected = (init_dist + self.rewards_pool.minted)?;
+        if circulating != expected {
+            return Err(ExportedStateError::SupplyMismatch(circulating, expected));
+        }
+
+        let council_nodes = self
+            .accounts
+            .iter()
+            .filter(|account| match account.node_meta {
+                Some(NodeState::CouncilNode(_)) => true,
+                _ => false,
+            })
+            .map(|account| account.address)
+            .collect::<Vec<_>>();
+        if council_nodes.is_empty() {
+            return Err(ExportedStateError::NoValidators);
+        }
+        Ok(council_nodes)
+    }
+
+    /// The changes of the UTXO set re-creating the unspent outputs: the outputs of each
+    /// transaction up to its last unspent one are created, and the others among them are spent
+    pub fn utxo_changes(&self) -> Vec<UtxoChange> {
+        let mut txs: BTreeMap<_, BTreeSet<TxoSize>> = BTreeMap::new();
+        for txo in self.unspent_outputs.iter() {
+            txs.entry(txo.id).or_default().insert(txo.index);
+        }
+        let mut created = Vec::with_capacity(txs.len());
+        let mut spent = Vec::new();
+        for (txid, unspent) in txs.into_iter() {
+            let no_of_outputs = unspent.iter().max().map_or(0, |index| index + 1);
+            created.push(UtxoChange::Created {
+                txid,
+                no_of_outputs,
+            });
+            spent.extend(
+                (0..no_of_outputs)
+                    .filter(|index| !unspent.contains(index))
+                    .map(|index| UtxoChange::Spent(TxoPointer::new(txid, index as usize))),
+            );
+        }
+        created.extend(spent);
+        created
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::init::params::{
+        InitNetworkParameters, JailingParameters, RewardsParameters, SlashRatio, SlashingParameters,
+    };
+    use crate::tx::fee::{LinearFee, Milli};
+    use std::convert::TryFrom;
+
+    fn network_params() -> NetworkParameters {
+        let slash_ratio = |millis| SlashRatio::try_from(Milli::from_millis(millis)).unwrap();
+        NetworkParameters::Genesis(InitNetworkParameters {
+            initial_fee_policy: LinearFee::new(Milli::new(0, 0), Milli::new(0, 0)),
+            required_council_node_stake: Coin::new(50_000).unwrap(),
+            required_community_node_stake: Coin::new(10_000).unwrap(),
+            jailing_config: JailingParameters {
+                block_signing_window: 100,
+                missed_block_threshold: 50,
+            },
+            slashing_config: SlashingParameters {
+                liveness_slash_percent: slash_ratio(100),
+                byzantine_slash_percent: slash_ratio(200),
+                invalid_commit_slash_percent: slash_ratio(300),
+            },
+            rewards_config: RewardsParameters {
+                monetary_expansion_cap: Coin::new(60_000).unwrap(),
+                reward_period_seconds: 86400,
+                monetary_expansion_r0: Milli::from_millis(450),
+                monetary_expansion_tau: 1_000,
+                monetary_expansion_decay: 999_860,
+            },
+            max_validators: 50,
+        })
+    }
+
+    #[test]
+    fn check_exported_utxo_changes() {
+        let state = ExportedState {
+            exported_chain_id: "test-chain-y3m1e6-AB".to_owned(),
+            exported_height: BlockHeight::new(10),
+            network_params: network_params(),
+            accounts: vec![],
+            rewards_pool: RewardsPoolState::new(0, 0),
+            unspent_outputs: vec![
+                TxoPointer::new([1u8; 32], 2),
+                TxoPointer::new([1u8; 32], 0),
+                TxoPointer::new([0u8; 32], 0),
+            ],
+            utxo_coins: Coin::zero(),
+            enclave_isv_svn: 0,
+        };
+        assert_eq!(
+            vec![
+                UtxoChange::Created {
+                    txid: [0u8; 32],
+                    no_of_outputs: 1
+                },
+                UtxoChange::Created {
+                    txid: [1u8; 32],
+                    no_of_outputs: 3
+                },
+                UtxoChange::Spent(TxoPointer::new([1u8; 32], 1)),
+            ],
+            state.utxo_changes()
+        );
+        // the initial distribution is missing
+        assert!(matches!(
+            state.validate(),
+            Err(ExportedStateError::SupplyMismatch(_, _))
+        ));
+    }
+}
@@ -7,6 +7,8 @@ pub mod address;
 pub mod coin;
 /// Configuration in JSON passed to InitChain
 pub mod config;
+/// State exported for upgraded chains
+pub mod export;
 
 /// Network static configuration
 pub mod network;
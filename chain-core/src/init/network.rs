@@ -94,12 +94,15 @@ pub fn get_bip44_coin_type() -> u32 {
     get_bip44_coin_type_from_network(get_network())
 }
 
+/// BIP44 coin type registered for CRO (SLIP-0044)
+pub const CRO_BIP44_COIN_TYPE: u32 = 394;
+
 /// Returns bip44 cointype of the provided network
 /// 1       0x80000001             Testnet (all coins)
 /// 394     0x8000018a     CRO     Crypto.com Chain
 pub fn get_bip44_coin_type_from_network(network: Network) -> u32 {
     match network {
-        Network::Mainnet => 394,
+        Network::Mainnet => CRO_BIP44_COIN_TYPE,
         Network::Testnet => 1,
         Network::Devnet => 1,
     }
@@ -1,5 +1,6 @@
 use crate::common::H256;
 use crate::init::coin::{Coin, CoinError};
+use crate::state::governance::NetworkParamsChange;
 use crate::tx::fee::{Fee, FeeAlgorithm};
 use crate::tx::fee::{LinearFee, Milli, MilliError};
 use parity_scale_codec::{Decode, Encode};
@@ -33,11 +34,14 @@ pub struct InitNetworkParameters {
     pub max_validators: u16,
 }
 
-/// so far only one specified at genesis
+/// network parameters: specified at genesis and updated by council node votes
+/// (see `NetworkParamsUpdateTx`)
 #[derive(Debug, PartialEq, Eq, Clone, Encode, Decode, Serialize, Deserialize)]
 pub enum NetworkParameters {
     /// parameters specified at genesis time
     Genesis(InitNetworkParameters),
+    /// parameters after the given number of applied updates
+    Updated(u64, InitNetworkParameters),
 }
 
 /// TODO: extract these to a trait?
@@ -47,100 +51,99 @@ impl NetworkParameters {
         blake3::hash(&self.encode()).into()
     }
 
-    /// cap on validators in tendermint
-    pub fn get_max_validators(&self) -> usize {
+    /// the current parameter values
+    pub fn current(&self) -> &InitNetworkParameters {
         match self {
-            NetworkParameters::Genesis(params) => params.max_validators as usize,
+            NetworkParameters::Genesis(params) => params,
+            NetworkParameters::Updated(_, params) => params,
         }
     }
 
-    /// minimal stake required for node joining (to be a validator)
-    pub fn get_required_council_node_stake(&self) -> Coin {
+    /// number of the applied updates (0 = genesis parameters)
+    pub fn version(&self) -> u64 {
         match self {
-            NetworkParameters::Genesis(params) => params.required_council_node_stake,
+            NetworkParameters::Genesis(_) => 0,
+            NetworkParameters::Updated(version, _) => *version,
+        }
+    }
+
+    /// the parameters after applying the change (the parameters not part of `InitNetworkParameters`,
+    /// i.e. the unbonding period, need to be applied by the caller)
+    pub fn apply_change(&self, change: &NetworkParamsChange) -> NetworkParameters {
+        let mut params = self.current().clone();
+        if let Some(fee_policy) = change.fee_policy {
+            params.initial_fee_policy = fee_policy;
+        }
+        if let Some(stake) = change.required_council_node_stake {
+            params.required_council_node_stake = stake;
         }
+        NetworkParameters::Updated(self.version() + 1, params)
+    }
+
+    /// cap on validators in tendermint
+    pub fn get_max_validators(&self) -> usize {
+        self.current().max_validators as usize
+    }
+
+    /// minimal stake required for node joining (to be a validator)
+    pub fn get_required_council_node_stake(&self) -> Coin {
+        self.current().required_council_node_stake
     }
 
     /// infraction configuration for byzantine fault
     pub fn get_byzantine_slash_percent(&self) -> SlashRatio {
-        match self {
-            NetworkParameters::Genesis(params) => params.slashing_config.byzantine_slash_percent,
-        }
+        self.current().slashing_config.byzantine_slash_percent
     }
 
     /// infraction configuration for liveness fault
     pub fn get_liveness_slash_percent(&self) -> SlashRatio {
-        match self {
-            NetworkParameters::Genesis(params) => params.slashing_config.liveness_slash_percent,
-        }
+        self.current().slashing_config.liveness_slash_percent
     }
 
     /// infraction configuration for liveness fault
     pub fn get_missed_block_threshold(&self) -> u16 {
-        match self {
-            NetworkParameters::Genesis(params) => params.jailing_config.missed_block_threshold,
-        }
+        self.current().jailing_config.missed_block_threshold
     }
 
     /// infraction configuration for liveness fault
     pub fn get_block_signing_window(&self) -> u16 {
-        match self {
-            NetworkParameters::Genesis(params) => params.jailing_config.block_signing_window,
-        }
+        self.current().jailing_config.block_signing_window
     }
 
     /// The period of reward being distributed
     pub fn get_rewards_reward_period_seconds(&self) -> u64 {
-        match self {
-            NetworkParameters::Genesis(params) => params.rewards_config.reward_period_seconds,
-        }
+        self.current().rewards_config.reward_period_seconds
     }
 
     /// The upper bound for the reward rate per annum
     pub fn get_rewards_monetary_expansion_r0(&self) -> Milli {
-        match self {
-            NetworkParameters::Genesis(params) => params.rewards_config.monetary_expansion_r0,
-        }
+        self.current().rewards_config.monetary_expansion_r0
     }
 
     /// Initial value of tau in the reward function
     pub fn get_rewards_monetary_expansion_tau(&self) -> u64 {
-        match self {
-            NetworkParameters::Genesis(params) => params.rewards_config.monetary_expansion_tau,
-        }
+        self.current().rewards_config.monetary_expansion_tau
     }
 
     /// The decay rate of tau.
     pub fn get_rewards_monetary_expansion_decay(&self) -> u64 {
-        match self {
-            NetworkParameters::Genesis(params) => params.rewards_config.monetary_expansion_decay,
-        }
+        self.current().rewards_config.monetary_expansion_decay
     }
 
     /// The total amount of tokens reserved for validator's reward in the basic unit
     pub fn get_rewards_monetary_expansion_cap(&self) -> Coin {
-        match self {
-            NetworkParameters::Genesis(params) => params.rewards_config.monetary_expansion_cap,
-        }
+        self.current().rewards_config.monetary_expansion_cap
     }
 
     /// constant fee -- TODO: will it be necessary? (used in the tx-query fee?)
     pub fn get_min_const_fee(&self) -> Result<Fee, CoinError> {
-        match self {
-            NetworkParameters::Genesis(params) => {
-                let coin = Coin::new(params.initial_fee_policy.coefficient.to_integral())?;
-                Ok(Fee::new(coin))
-            }
-        }
+        let coin = Coin::new(self.current().initial_fee_policy.coefficient.to_integral())?;
+        Ok(Fee::new(coin))
     }
 
     /// calculates the fee based on the specified policy
     pub fn calculate_fee(&self, num_bytes: usize) -> Result<Fee, CoinError> {
-        match self {
-            NetworkParameters::Genesis(params) => {
-                params.initial_fee_policy.calculate_fee(num_bytes)
-            }
-        }
+        self.current().initial_fee_policy.calculate_fee(num_bytes)
     }
 }
 
@@ -7,6 +7,8 @@ pub mod common;
 pub mod init;
 /// mls wrapper types for TDBE-related logic
 pub mod mls;
+/// Registry of the consensus-encoded types with their golden encodings
+pub mod schema;
 /// Rewards pool and other stateful structures
 pub mod state;
 /// Transaction structure types and serialization/deserialization
@@ -19,7 +21,11 @@ use common::{MerkleTree, Timespec, H256};
 use init::params::NetworkParameters;
 use parity_scale_codec::{Decode, Encode};
 use state::tendermint::BlockHeight;
-use state::RewardsPoolState;
+#[cfg(feature = "new-txid")]
+use state::utxo::UtxoProof;
+use state::ChainState;
+#[cfg(feature = "new-txid")]
+use tx::data::input::TxoPointer;
 use tx::fee::Fee;
 
 /// The app version returned in Tendermint "Info" response,
@@ -36,26 +42,88 @@ pub const APP_VERSION: u64 = 1;
 /// version 2 -- 0.6.0 (not yet released --> transaction data bootstrapping, new TX types, genesis changes, TXID calculation change, app hash calculation change);
 pub const APP_VERSION: u64 = 2;
 
+/// true if the app hash commits to the UTXO set (APP_VERSION 2, see `AppHashParts`):
+/// only then the merkle mountain range of the outputs is maintained, as the databases
+/// of the 0.5.0 release don't have it for the outputs created before the upgrade
+pub const UTXO_SET_COMMITTED: bool = cfg!(feature = "new-txid");
+
 /// computes the "global" application hash (used by Tendermint to check consistency + block replaying)
-/// currently: app_hash = blake3(b"app_hash" || root of valid TX merkle tree
-/// || root of account/staked state trie || blake3(scale bytes(rewards pool state)) || blake3(scale bytes(network params)))
+/// from the root of valid TX merkle tree of the block and the chain state at the end of it
+/// (see `AppHashParts` for the layout)
 /// TODO: cache (as many parts remain static)
-pub fn compute_app_hash(
-    valid_tx_id_tree: &MerkleTree<H256>,
-    account_state_root: &H256,
-    reward_pool: &RewardsPoolState,
-    params: &NetworkParameters,
-) -> H256 {
-    let valid_tx_part = valid_tx_id_tree.root_hash();
-    let rewards_pool_part = reward_pool.hash();
-    let network_params_part = params.hash();
-    let mut hasher = blake3::Hasher::new();
-    hasher.update(b"app_hash");
-    hasher.update(&valid_tx_part);
-    hasher.update(&account_state_root[..]);
-    hasher.update(&rewards_pool_part);
-    hasher.update(&network_params_part);
-    hasher.finalize().into()
+pub fn compute_app_hash(valid_tx_id_tree: &MerkleTree<H256>, state: &ChainState) -> H256 {
+    state
+        .app_hash_parts(valid_tx_id_tree.root_hash())
+        .app_hash()
+}
+
+/// The parts the "global" application hash commits to, each of them 32 bytes:
+///
+/// | offset | part                                                                    |
+/// |--------|-------------------------------------------------------------------------|
+/// | 0      | root of valid TX merkle tree of the block                               |
+/// | 32     | root of account/staked state trie                                       |
+/// | 64     | blake3(scale bytes(rewards pool state))                                 |
+/// | 96     | blake3(scale bytes(network params))                                     |
+/// | 128    | root of merkle mountain range of the outputs (`state::utxo::UtxoMmr`)   |
+/// | 160    | hash of the chosen validator set (`state::validator_set_hash`)          |
+///
+/// app_hash = blake3(b"app_hash" || the parts in the above order), which is the same as
+/// blake3(b"app_hash" || scale bytes(parts)).
+/// The last two parts are only committed from APP_VERSION 2 ("new-txid"):
+/// APP_VERSION 1 (0.5.0 release) app hash only includes the first four parts.
+/// The parts are the proof of each of them being committed in a given app hash,
+/// e.g. the staked states are then proven against the account state root (sparse merkle proofs)
+/// and the unspent outputs against the UTXO set commitment (`state::utxo::UtxoProof`).
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+pub struct AppHashParts {
+    /// root of valid TX merkle tree of the block
+    pub valid_tx_root: H256,
+    /// root of account/staked state trie
+    pub account_state_root: H256,
+    /// hash of the rewards pool state
+    pub rewards_pool_hash: H256,
+    /// hash of the network parameters
+    pub network_params_hash: H256,
+    /// commitment to the UTXO set
+    pub utxo_commitment: H256,
+    /// hash of the chosen validator set
+    pub validator_set_hash: H256,
+}
+
+impl AppHashParts {
+    /// the app hash the parts are included in
+    pub fn app_hash(&self) -> H256 {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(b"app_hash");
+        hasher.update(&self.valid_tx_root);
+        hasher.update(&self.account_state_root);
+        hasher.update(&self.rewards_pool_hash);
+        hasher.update(&self.network_params_hash);
+        #[cfg(feature = "new-txid")]
+        {
+            hasher.update(&self.utxo_commitment);
+            hasher.update(&self.validator_set_hash);
+        }
+        hasher.finalize().into()
+    }
+
+    /// checks the parts are included in the app hash
+    pub fn verify(&self, app_hash: &H256) -> bool {
+        self.app_hash() == *app_hash
+    }
+
+    /// checks the network parameters are included in the app hash
+    pub fn verify_network_params(&self, params: &NetworkParameters, app_hash: &H256) -> bool {
+        self.network_params_hash == params.hash() && self.verify(app_hash)
+    }
+
+    /// checks the output is unspent in the UTXO set committed in the app hash
+    /// (APP_VERSION 1 app hash doesn't commit to the UTXO set)
+    #[cfg(feature = "new-txid")]
+    pub fn verify_unspent(&self, txo: &TxoPointer, proof: &UtxoProof, app_hash: &H256) -> bool {
+        proof.verify_unspent(txo, &self.utxo_commitment) && self.verify(app_hash)
+    }
 }
 
 /// External information needed for TX validation
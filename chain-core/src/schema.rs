@@ -0,0 +1,478 @@
+//! Registry of the consensus-encoded (SCALE) types with golden encodings of fixed sample values
+//!
+//! The encodings of the registered types are exported as a `TypeSchema` list
+//! (see `chain-core/tests/scale_schema.json` for the golden one). Any difference in an encoding
+//! changes the wire format or the stored / hashed data, so it needs to be accompanied by
+//! an explicit bump of the type's schema version (and the corresponding migration);
+//! `diff_schemas` reports the encoding changes between two exported schemas.
+//!
+//! NOTE: types embedding MLS key packages (node join / MLS handshake transactions and node states)
+//! are not registered, as their samples need attested key packages.
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::prelude::v1::{String, ToString, Vec};
+
+use parity_scale_codec::{Decode, Encode};
+use secp256k1::key::PublicKey;
+use secp256k1::recovery::{RecoverableSignature, RecoveryId};
+use secp256k1::schnorrsig::SchnorrSignature;
+use serde::{Deserialize, Serialize};
+
+use crate::common::{MerkleTree, Timespec};
+use crate::init::address::RedeemAddress;
+use crate::init::coin::Coin;
+use crate::init::params::{
+    InitNetworkParameters, JailingParameters, NetworkParameters, RewardsParameters, SlashRatio,
+    SlashingParameters,
+};
+use crate::state::account::{
+    DepositBondTx, PunishmentKind, SlashRecord, StakedState, StakedStateAddress,
+    StakedStateOpAttributes, StakedStateOpWitness, UnbondTx, UnjailTx, WithdrawUnbondedTx,
+};
+use crate::state::tendermint::BlockHeight;
+use crate::state::RewardsPoolState;
+use crate::tx::data::access::{TxAccess, TxAccessPolicy};
+use crate::tx::data::address::ExtendedAddr;
+use crate::tx::data::attribute::TxAttributes;
+use crate::tx::data::input::TxoPointer;
+use crate::tx::data::output::TxOut;
+use crate::tx::data::{Tx, TxId};
+use crate::tx::fee::{Fee, LinearFee, Milli};
+use crate::tx::witness::tree::RawXOnlyPubkey;
+use crate::tx::witness::{TxInWitness, TxWitness};
+use crate::tx::{PlainTxAux, TxAux, TxEnclaveAux, TxObfuscated, TxPublicAux, TxWithOutputs};
+
+/// A registered consensus-encoded type
+pub struct SchemaEntry {
+    /// type name
+    pub name: &'static str,
+    /// schema version of the type -- needs to be bumped whenever its encoding changes
+    pub version: u16,
+    /// SCALE encoding of the sample value
+    pub encode_sample: fn() -> Vec<u8>,
+    /// decodes the value from the bytes and encodes it again (None if decoding fails)
+    pub reencode: fn(&[u8]) -> Option<Vec<u8>>,
+}
+
+impl fmt::Debug for SchemaEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (version {})", self.name, self.version)
+    }
+}
+
+/// Exported schema of a registered type
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TypeSchema {
+    /// type name
+    pub name: String,
+    /// schema version of the type
+    pub version: u16,
+    /// hex-encoded SCALE encoding of the sample value
+    pub encoding: String,
+}
+
+/// A difference between two exported schemas
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SchemaChange {
+    /// type was registered
+    Added(String),
+    /// type was removed from the registry
+    Removed(String),
+    /// encoding of the sample value changed
+    EncodingChanged {
+        /// type name
+        name: String,
+        /// previous schema version
+        old_version: u16,
+        /// new schema version
+        new_version: u16,
+    },
+}
+
+impl SchemaChange {
+    /// false if the encoding changed without bumping the schema version
+    /// (i.e. most likely unintentionally)
+    pub fn is_versioned(&self) -> bool {
+        match self {
+            SchemaChange::EncodingChanged {
+                old_version,
+                new_version,
+                ..
+            } => new_version > old_version,
+            _ => true,
+        }
+    }
+}
+
+impl fmt::Display for SchemaChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchemaChange::Added(name) => write!(f, "{}: added", name),
+            SchemaChange::Removed(name) => write!(f, "{}: removed", name),
+            SchemaChange::EncodingChanged {
+                name,
+                old_version,
+                new_version,
+            } if self.is_versioned() => write!(
+                f,
+                "{}: encoding changed (version {} -> {}, needs migration)",
+                name, old_version, new_version
+            ),
+            SchemaChange::EncodingChanged {
+                name, new_version, ..
+            } => write!(
+                f,
+                "{}: encoding changed without a version bump (version {})",
+                name, new_version
+            ),
+        }
+    }
+}
+
+macro_rules! schema_entry {
+    ($ty:ty, $version:expr, $sample:expr) => {
+        SchemaEntry {
+            name: stringify!($ty),
+            version: $version,
+            encode_sample: || {
+                let sample: $ty = $sample;
+                sample.encode()
+            },
+            reencode: |bytes| {
+                <$ty>::decode(&mut &bytes[..])
+                    .ok()
+                    .map(|value| value.encode())
+            },
+        }
+    };
+}
+
+/// All registered types
+pub fn registry() -> Vec<SchemaEntry> {
+    vec![
+        schema_entry!(Coin, 0, sample_coin()),
+        schema_entry!(Fee, 0, Fee::new(Coin::new(10).unwrap())),
+        schema_entry!(BlockHeight, 0, BlockHeight::new(42)),
+        schema_entry!(TxoPointer, 0, sample_input()),
+        schema_entry!(ExtendedAddr, 0, sample_transfer_address()),
+        schema_entry!(TxAccessPolicy, 0, sample_access_policy()),
+        schema_entry!(TxAttributes, 0, sample_tx_attributes()),
+        schema_entry!(TxOut, 0, sample_output()),
+        schema_entry!(Tx, 0, sample_tx()),
+        schema_entry!(TxInWitness, 0, sample_input_witness()),
+        schema_entry!(TxWitness, 0, sample_witness()),
+        schema_entry!(TxObfuscated, 0, sample_obfuscated()),
+        schema_entry!(StakedStateAddress, 0, sample_staking_address()),
+        schema_entry!(StakedStateOpAttributes, 0, sample_op_attributes()),
+        schema_entry!(StakedStateOpWitness, 0, sample_op_witness()),
+        schema_entry!(
+            DepositBondTx,
+            0,
+            DepositBondTx::new(
+                vec![sample_input()],
+                sample_staking_address(),
+                sample_op_attributes()
+            )
+        ),
+        schema_entry!(UnbondTx, 0, sample_unbond_tx()),
+        schema_entry!(
+            WithdrawUnbondedTx,
+            0,
+            WithdrawUnbondedTx::new(5, vec![sample_output()], sample_tx_attributes())
+        ),
+        schema_entry!(
+            UnjailTx,
+            0,
+            UnjailTx::new(5, sample_staking_address(), sample_op_attributes())
+        ),
+        schema_entry!(
+            TxEnclaveAux,
+            0,
+            TxEnclaveAux::TransferTx {
+                inputs: vec![sample_input()],
+                no_of_outputs: 1,
+                payload: sample_obfuscated(),
+            }
+        ),
+        schema_entry!(TxPublicAux, 0, sample_public_aux()),
+        schema_entry!(TxAux, 0, TxAux::PublicTx(sample_public_aux())),
+        schema_entry!(TxWithOutputs, 0, TxWithOutputs::Transfer(sample_tx())),
+        schema_entry!(
+            PlainTxAux,
+            0,
+            PlainTxAux::TransferTx(sample_tx(), sample_witness())
+        ),
+        schema_entry!(SlashRecord, 0, sample_slash_record()),
+        schema_entry!(
+            StakedState,
+            0,
+            StakedState {
+                nonce: 5,
+                bonded: sample_coin(),
+                unbonded: Coin::new(2_000).unwrap(),
+                unbonded_from: SAMPLE_TIME,
+                address: sample_staking_address(),
+                node_meta: None,
+                last_slash: Some(sample_slash_record()),
+            }
+        ),
+        schema_entry!(
+            RewardsPoolState,
+            0,
+            RewardsPoolState {
+                period_bonus: sample_coin(),
+                last_block_height: BlockHeight::new(42),
+                last_distribution_time: SAMPLE_TIME,
+                minted: Coin::new(2_000).unwrap(),
+                tau: 7,
+            }
+        ),
+        schema_entry!(NetworkParameters, 0, sample_network_parameters()),
+    ]
+}
+
+/// Exports the schemas of the registered types
+pub fn current_schema() -> Vec<TypeSchema> {
+    registry()
+        .into_iter()
+        .map(|entry| TypeSchema {
+            name: entry.name.to_string(),
+            version: entry.version,
+            encoding: hex::encode((entry.encode_sample)()),
+        })
+        .collect()
+}
+
+/// Lists the differences between two exported schemas (ordered by the type name)
+pub fn diff_schemas(old: &[TypeSchema], new: &[TypeSchema]) -> Vec<SchemaChange> {
+    let old: BTreeMap<&str, &TypeSchema> = old.iter().map(|s| (s.name.as_str(), s)).collect();
+    let new: BTreeMap<&str, &TypeSchema> = new.iter().map(|s| (s.name.as_str(), s)).collect();
+
+    let mut changes = Vec::new();
+    for (name, old_schema) in old.iter() {
+        match new.get(name) {
+            None => changes.push(SchemaChange::Removed(name.to_string())),
+            Some(new_schema) if new_schema.encoding != old_schema.encoding => {
+                changes.push(SchemaChange::EncodingChanged {
+                    name: name.to_string(),
+                    old_version: old_schema.version,
+                    new_version: new_schema.version,
+                })
+            }
+            Some(_) => {}
+        }
+    }
+    for name in new.keys().filter(|name| !old.contains_key(*name)) {
+        changes.push(SchemaChange::Added(name.to_string()));
+    }
+    changes.sort_by(|a, b| change_name(a).cmp(change_name(b)));
+    changes
+}
+
+fn change_name(change: &SchemaChange) -> &str {
+    match change {
+        SchemaChange::Added(name) | SchemaChange::Removed(name) => name,
+        SchemaChange::EncodingChanged { name, .. } => name,
+    }
+}
+
+const SAMPLE_TIME: Timespec = 1_600_000_000;
+const SAMPLE_CHAIN_HEX_ID: u8 = 0xab;
+const SAMPLE_TXID: TxId = [0x22; 32];
+/// compressed secp256k1 generator point
+const SAMPLE_PUBKEY: [u8; 33] = [
+    0x02, 0x79, 0xbe, 0x66, 0x7e, 0xf9, 0xdc, 0xbb, 0xac, 0x55, 0xa0, 0x62, 0x95, 0xce, 0x87, 0x0b,
+    0x07, 0x02, 0x9b, 0xfc, 0xdb, 0x2d, 0xce, 0x28, 0xd9, 0x59, 0xf2, 0x81, 0x5b, 0x16, 0xf8, 0x17,
+    0x98,
+];
+
+fn sample_coin() -> Coin {
+    Coin::new(1_000).unwrap()
+}
+
+fn sample_input() -> TxoPointer {
+    TxoPointer::new(SAMPLE_TXID, 1)
+}
+
+fn sample_transfer_address() -> ExtendedAddr {
+    ExtendedAddr::OrTree([0x33; 32])
+}
+
+fn sample_staking_address() -> StakedStateAddress {
+    StakedStateAddress::BasicRedeem(RedeemAddress([0x11; 20]))
+}
+
+fn sample_access_policy() -> TxAccessPolicy {
+    TxAccessPolicy::new(
+        PublicKey::from_slice(&SAMPLE_PUBKEY).unwrap(),
+        TxAccess::AllData,
+    )
+}
+
+fn sample_tx_attributes() -> TxAttributes {
+    TxAttributes {
+        chain_hex_id: SAMPLE_CHAIN_HEX_ID,
+        allowed_view: vec![sample_access_policy()],
+        app_version: 1,
+    }
+}
+
+fn sample_output() -> TxOut {
+    TxOut::new_with_timelock(sample_transfer_address(), sample_coin(), SAMPLE_TIME)
+}
+
+fn sample_tx() -> Tx {
+    Tx::new_with(
+        vec![sample_input()],
+        vec![sample_output()],
+        sample_tx_attributes(),
+    )
+}
+
+fn sample_input_witness() -> TxInWitness {
+    let leaf = RawXOnlyPubkey::from([0x55; 32]);
+    let proof = MerkleTree::new(vec![leaf.clone()])
+        .generate_proof(leaf)
+        .unwrap();
+    TxInWitness::TreeSig(SchnorrSignature::from_default(&[0x44; 64]).unwrap(), proof)
+}
+
+fn sample_witness() -> TxWitness {
+    TxWitness::from(vec![sample_input_witness()])
+}
+
+fn sample_obfuscated() -> TxObfuscated {
+    TxObfuscated {
+        key_from: BlockHeight::new(42),
+        init_vector: [0x66; 12],
+        txpayload: vec![0x77; 4],
+        txid: SAMPLE_TXID,
+    }
+}
+
+fn sample_op_attributes() -> StakedStateOpAttributes {
+    StakedStateOpAttributes {
+        chain_hex_id: SAMPLE_CHAIN_HEX_ID,
+        app_version: 1,
+    }
+}
+
+fn sample_op_witness() -> StakedStateOpWitness {
+    StakedStateOpWitness::BasicRedeem(
+        RecoverableSignature::from_compact(&[0x01; 64], RecoveryId::from_i32(1).unwrap()).unwrap(),
+    )
+}
+
+fn sample_unbond_tx() -> UnbondTx {
+    UnbondTx::new(
+        sample_staking_address(),
+        5,
+        sample_coin(),
+        sample_op_attributes(),
+    )
+}
+
+fn sample_public_aux() -> TxPublicAux {
+    TxPublicAux::UnbondStakeTx(sample_unbond_tx(), sample_op_witness())
+}
+
+fn sample_slash_record() -> SlashRecord {
+    SlashRecord {
+        kind: PunishmentKind::NonLive,
+        time: SAMPLE_TIME,
+        amount: Coin::new(10).unwrap(),
+    }
+}
+
+fn sample_network_parameters() -> NetworkParameters {
+    let slash_ratio = |millis| SlashRatio::try_from(Milli::from_millis(millis)).unwrap();
+    NetworkParameters::Genesis(InitNetworkParameters {
+        initial_fee_policy: LinearFee::new(Milli::from_millis(1_100), Milli::from_millis(1_250)),
+        required_council_node_stake: Coin::new(50_000).unwrap(),
+        required_community_node_stake: Coin::new(10_000).unwrap(),
+        jailing_config: JailingParameters {
+            block_signing_window: 100,
+            missed_block_threshold: 50,
+        },
+        slashing_config: SlashingParameters {
+            liveness_slash_percent: slash_ratio(100),
+            byzantine_slash_percent: slash_ratio(200),
+            invalid_commit_slash_percent: slash_ratio(300),
+        },
+        rewards_config: RewardsParameters {
+            monetary_expansion_cap: Coin::new(60_000).unwrap(),
+            reward_period_seconds: 86400,
+            monetary_expansion_r0: Milli::from_millis(450),
+            monetary_expansion_tau: 1_000,
+            monetary_expansion_decay: 999_860,
+        },
+        max_validators: 50,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schema(name: &str, version: u16, encoding: &str) -> TypeSchema {
+        TypeSchema {
+            name: name.to_string(),
+            version,
+            encoding: encoding.to_string(),
+        }
+    }
+
+    #[test]
+    fn check_registry_samples_roundtrip() {
+        for entry in registry() {
+            let encoded = (entry.encode_sample)();
+            assert_eq!(
+                Some(encoded.clone()),
+                (entry.reencode)(&encoded),
+                "{:?}",
+                entry
+            );
+        }
+    }
+
+    #[test]
+    fn check_diff_schemas() {
+        let old = vec![
+            schema("A", 0, "00"),
+            schema("B", 0, "01"),
+            schema("C", 1, "02"),
+        ];
+        let new = vec![
+            schema("B", 0, "0101"),
+            schema("C", 2, "03"),
+            schema("D", 0, "04"),
+        ];
+        let changes = diff_schemas(&old, &new);
+        assert_eq!(
+            vec![
+                SchemaChange::Removed("A".to_string()),
+                SchemaChange::EncodingChanged {
+                    name: "B".to_string(),
+                    old_version: 0,
+                    new_version: 0,
+                },
+                SchemaChange::EncodingChanged {
+                    name: "C".to_string(),
+                    old_version: 1,
+                    new_version: 2,
+                },
+                SchemaChange::Added("D".to_string()),
+            ],
+            changes
+        );
+        assert_eq!(
+            vec![true, false, true, true],
+            changes
+                .iter()
+                .map(SchemaChange::is_versioned)
+                .collect::<Vec<_>>()
+        );
+        assert!(diff_schemas(&new, &new).is_empty());
+    }
+}
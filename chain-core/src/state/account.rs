@@ -7,11 +7,14 @@ use crate::state::tendermint::{
 };
 pub use crate::state::validator::UnjailTx;
 pub use address::StakedStateAddress;
+pub use op::data::anchor::{DataAnchorProof, DataAnchorRecord, DataAnchorTx};
 pub use op::data::attribute::StakedStateOpAttributes;
 pub use op::data::deposit::DepositBondTx;
 pub use op::data::unbond::UnbondTx;
 pub use op::data::withdraw::WithdrawUnbondedTx;
-pub use op::witness::StakedStateOpWitness;
+pub use op::witness::{
+    threshold_staking_address, StakedStateOpWitness, ThresholdSignature, MAX_THRESHOLD_SIGNATURES,
+};
 use parity_scale_codec::{Decode, Encode, Error, Input, Output};
 use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
 use std::convert::From;
@@ -0,0 +1,176 @@
+use crate::common::{Proof, H256};
+use crate::state::account::address::StakedStateAddress;
+use crate::state::account::op::data::attribute::StakedStateOpAttributes;
+use crate::state::account::Nonce;
+use crate::state::tendermint::BlockHeight;
+use crate::tx::data::TxId;
+#[cfg(feature = "new-txid")]
+use crate::tx::TaggedTransaction;
+use crate::tx::TransactionId;
+use crate::AppHashParts;
+use parity_scale_codec::{Decode, Encode, Error, Input, Output};
+
+use serde::{Deserialize, Serialize};
+
+use std::fmt;
+
+/// anchors a 32-byte commitment to external data (e.g. a document hash) in the chain;
+/// the fee (computed from the transaction size) is paid from the bonded amount of the staked state
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct DataAnchorTx {
+    /// which (staking) state pays the fee
+    pub from_staked_account: StakedStateAddress,
+    /// expected counter to check against
+    pub nonce: Nonce,
+    /// the anchored commitment
+    pub commitment: H256,
+    /// versioning info etc.
+    pub attributes: StakedStateOpAttributes,
+}
+
+impl Decode for DataAnchorTx {
+    fn decode<I: Input>(input: &mut I) -> Result<Self, Error> {
+        let from_staked_account = StakedStateAddress::decode(input)?;
+        let nonce = Nonce::decode(input)?;
+        let commitment = H256::decode(input)?;
+        let attributes = StakedStateOpAttributes::decode(input)?;
+
+        Ok(DataAnchorTx {
+            from_staked_account,
+            nonce,
+            commitment,
+            attributes,
+        })
+    }
+}
+
+impl Encode for DataAnchorTx {
+    fn encode_to<EncOut: Output>(&self, dest: &mut EncOut) {
+        dest.push(&self.from_staked_account);
+        dest.push(&self.nonce);
+        dest.push(&self.commitment);
+        dest.push(&self.attributes);
+    }
+
+    fn size_hint(&self) -> usize {
+        self.from_staked_account.size_hint()
+            + self.nonce.size_hint()
+            + self.commitment.size_hint()
+            + self.attributes.size_hint()
+    }
+}
+
+#[cfg(not(feature = "new-txid"))]
+impl TransactionId for DataAnchorTx {}
+
+#[cfg(feature = "new-txid")]
+impl From<DataAnchorTx> for TaggedTransaction {
+    fn from(tx: DataAnchorTx) -> TaggedTransaction {
+        TaggedTransaction::DataAnchorTx(tx)
+    }
+}
+
+impl DataAnchorTx {
+    /// creates a new tx to anchor the commitment
+    pub fn new(
+        from_staked_account: StakedStateAddress,
+        nonce: Nonce,
+        commitment: H256,
+        attributes: StakedStateOpAttributes,
+    ) -> Self {
+        DataAnchorTx {
+            from_staked_account,
+            nonce,
+            commitment,
+            attributes,
+        }
+    }
+}
+
+impl fmt::Display for DataAnchorTx {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} anchored: {} (nonce: {})",
+            self.from_staked_account,
+            hex::encode(&self.commitment),
+            self.nonce
+        )?;
+        write!(f, "")
+    }
+}
+
+/// an anchoring of a commitment, as indexed by chain-abci
+#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
+pub struct DataAnchorRecord {
+    /// the block the anchoring transaction was included in
+    pub height: BlockHeight,
+    /// the anchoring transaction
+    pub tx: DataAnchorTx,
+}
+
+/// proof of a commitment being anchored at the block height: the anchoring transaction
+/// is proven against the valid TX merkle tree root of the block, which is a part
+/// of the block's app hash (included in the header of the next block)
+#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
+pub struct DataAnchorProof {
+    /// the anchoring
+    pub record: DataAnchorRecord,
+    /// inclusion proof of the transaction ID in the valid TX merkle tree of the block
+    pub tx_proof: Proof<TxId>,
+    /// the parts of the block's app hash
+    pub app_hash_parts: AppHashParts,
+}
+
+impl DataAnchorProof {
+    /// checks the commitment was anchored in the block with the app hash
+    pub fn verify(&self, commitment: &H256, app_hash: &H256) -> bool {
+        self.record.tx.commitment == *commitment
+            && *self.tx_proof.value() == self.record.tx.id()
+            && self.tx_proof.verify(&self.app_hash_parts.valid_tx_root)
+            && self.app_hash_parts.verify(app_hash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::MerkleTree;
+    use crate::init::address::RedeemAddress;
+
+    #[test]
+    fn check_data_anchor_proof() {
+        let tx = DataAnchorTx::new(
+            StakedStateAddress::BasicRedeem(RedeemAddress([1u8; 20])),
+            0,
+            [2u8; 32],
+            StakedStateOpAttributes::new(0),
+        );
+        let tree = MerkleTree::new(vec![[3u8; 32], tx.id(), [4u8; 32]]);
+        let app_hash_parts = AppHashParts {
+            valid_tx_root: tree.root_hash(),
+            account_state_root: [5u8; 32],
+            rewards_pool_hash: [6u8; 32],
+            network_params_hash: [7u8; 32],
+            utxo_commitment: [8u8; 32],
+            validator_set_hash: [9u8; 32],
+        };
+        let app_hash = app_hash_parts.app_hash();
+        let proof = DataAnchorProof {
+            record: DataAnchorRecord {
+                height: BlockHeight::new(5),
+                tx: tx.clone(),
+            },
+            tx_proof: tree.generate_proof(tx.id()).unwrap(),
+            app_hash_parts,
+        };
+        assert!(proof.verify(&[2u8; 32], &app_hash));
+        assert!(!proof.verify(&[3u8; 32], &app_hash));
+        assert!(!proof.verify(&[2u8; 32], &[0u8; 32]));
+        let other = DataAnchorProof {
+            tx_proof: tree.generate_proof([3u8; 32]).unwrap(),
+            ..proof
+        };
+        assert!(!other.verify(&[2u8; 32], &app_hash));
+    }
+}
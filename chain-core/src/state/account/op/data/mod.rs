@@ -1,3 +1,5 @@
+/// data anchoring transaction
+pub mod anchor;
 /// versioning info etc.
 pub mod attribute;
 /// deposit transaction
@@ -1,13 +1,31 @@
-use crate::tx::witness::{tree::RawSignature, EcdsaSignature};
-use parity_scale_codec::{Decode, Encode, Error, Input, Output};
+use crate::common::{Proof, H256};
+use crate::init::address::{keccak256, RedeemAddress};
+use crate::state::account::StakedStateAddress;
+use crate::tx::witness::{
+    tree::{RawSignature, RawXOnlyPubkey},
+    EcdsaSignature,
+};
+use parity_scale_codec::{Compact, Decode, Encode, Error, Input, Output};
 use secp256k1::recovery::{RecoverableSignature, RecoveryId};
-use serde::{Deserialize, Serialize};
+use secp256k1::schnorrsig::SchnorrSignature;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::prelude::v1::Vec;
+
+/// Maximal number of signatures in a threshold witness
+pub const MAX_THRESHOLD_SIGNATURES: usize = 32;
+
+/// Domain separation of the threshold key set addresses
+/// (from the addresses of single keys, which hash 64-byte public keys)
+const THRESHOLD_ADDRESS_PREFIX: &[u8] = b"threshold";
 
 /// A witness for StakedState operations
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
 pub enum StakedStateOpWitness {
     /// Eth-style recoverable signature
     BasicRedeem(EcdsaSignature),
+    /// m-of-n BIP340 Schnorr signatures of a Merkle-committed key set
+    ThresholdSig(ThresholdSignature),
 }
 
 impl StakedStateOpWitness {
@@ -17,6 +35,110 @@ impl StakedStateOpWitness {
     }
 }
 
+/// Signatures of (at least) `threshold` distinct keys of a key set,
+/// each with the Merkle inclusion proof of its public key.
+///
+/// The staking address of the key set is derived from the threshold and the key set root
+/// (see `threshold_staking_address`), so the witness doesn't need the full key set.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ThresholdSignature {
+    /// number of required signatures
+    pub threshold: u16,
+    /// signatures with the inclusion proofs of the signing keys
+    pub signatures: Vec<(SchnorrSignature, Proof<RawXOnlyPubkey>)>,
+}
+
+impl ThresholdSignature {
+    /// Root of the key set (None if there are no signatures or their proofs disagree on the root)
+    pub fn key_set_root(&self) -> Option<H256> {
+        let (_, first) = self.signatures.first()?;
+        let root = first.root_hash();
+        if self
+            .signatures
+            .iter()
+            .all(|(_, proof)| proof.root_hash() == root)
+        {
+            Some(root)
+        } else {
+            None
+        }
+    }
+}
+
+/// Staking address of an m-of-n key set:
+/// the last 20 bytes of Keccak-256("threshold" | threshold (SCALE) | key set Merkle root)
+pub fn threshold_staking_address(threshold: u16, key_set_root: &H256) -> StakedStateAddress {
+    let mut preimage = THRESHOLD_ADDRESS_PREFIX.to_vec();
+    threshold.encode_to(&mut preimage);
+    preimage.extend_from_slice(key_set_root);
+    let hash = keccak256(&preimage);
+    let mut address = [0u8; 20];
+    address.copy_from_slice(&hash[12..]);
+    StakedStateAddress::BasicRedeem(RedeemAddress::from(address))
+}
+
+impl Encode for ThresholdSignature {
+    fn encode_to<W: Output>(&self, dest: &mut W) {
+        self.threshold.encode_to(dest);
+        Compact(self.signatures.len() as u32).encode_to(dest);
+        for (sig, proof) in self.signatures.iter() {
+            sig.serialize_default().encode_to(dest);
+            proof.encode_to(dest);
+        }
+    }
+
+    fn size_hint(&self) -> usize {
+        2 + 1
+            + self
+                .signatures
+                .iter()
+                .map(|(_, proof)| 64 + proof.size_hint())
+                .sum::<usize>()
+    }
+}
+
+impl Decode for ThresholdSignature {
+    fn decode<I: Input>(input: &mut I) -> Result<Self, Error> {
+        let threshold = u16::decode(input)?;
+        let len = <Compact<u32>>::decode(input)?.0 as usize;
+        if len > MAX_THRESHOLD_SIGNATURES {
+            return Err(Error::from("Too many threshold signatures"));
+        }
+        let mut signatures = Vec::with_capacity(len);
+        for _ in 0..len {
+            let raw_sig = RawSignature::decode(input)?;
+            let sig = SchnorrSignature::from_default(&raw_sig)
+                .map_err(|_| Error::from("Unable to parse schnorr signature"))?;
+            let proof = Proof::decode(input)?;
+            signatures.push((sig, proof));
+        }
+        Ok(ThresholdSignature {
+            threshold,
+            signatures,
+        })
+    }
+}
+
+/// serialized as the SCALE-encoded bytes
+impl Serialize for ThresholdSignature {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_bytes(&self.encode())
+    }
+}
+
+impl<'de> Deserialize<'de> for ThresholdSignature {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let bytes = Vec::<u8>::deserialize(deserializer)?;
+        ThresholdSignature::decode(&mut bytes.as_slice()).map_err(D::Error::custom)
+    }
+}
+
 impl Encode for StakedStateOpWitness {
     fn encode_to<W: Output>(&self, dest: &mut W) {
         match *self {
@@ -28,12 +150,17 @@ impl Encode for StakedStateOpWitness {
                 dest.push_byte(rid);
                 serialized_sig.encode_to(dest);
             }
+            StakedStateOpWitness::ThresholdSig(ref sig) => {
+                dest.push_byte(1);
+                sig.encode_to(dest);
+            }
         }
     }
 
     fn size_hint(&self) -> usize {
         match self {
             StakedStateOpWitness::BasicRedeem(_) => 66,
+            StakedStateOpWitness::ThresholdSig(sig) => 1 + sig.size_hint(),
         }
     }
 }
@@ -51,6 +178,9 @@ impl Decode for StakedStateOpWitness {
                     .map_err(|_| Error::from("Unable to create recoverable signature"))?;
                 Ok(StakedStateOpWitness::BasicRedeem(sig))
             }
+            1 => Ok(StakedStateOpWitness::ThresholdSig(
+                ThresholdSignature::decode(input)?,
+            )),
             _ => Err(Error::from("Invalid tag")),
         }
     }
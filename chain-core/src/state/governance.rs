@@ -0,0 +1,289 @@
+use crate::common::{Timespec, H256};
+use crate::init::coin::Coin;
+use crate::state::account::{Nonce, StakedStateAddress, StakedStateOpAttributes};
+use crate::state::tendermint::BlockHeight;
+use crate::tx::fee::LinearFee;
+#[cfg(feature = "new-txid")]
+use crate::tx::TaggedTransaction;
+#[cfg(not(feature = "new-txid"))]
+use crate::tx::TransactionId;
+use parity_scale_codec::{Decode, Encode};
+
+use serde::{Deserialize, Serialize};
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+/// Network parameters changed by a council node vote (`None` = unchanged)
+#[derive(Debug, Default, PartialEq, Eq, Clone, Encode, Decode, Serialize, Deserialize)]
+pub struct NetworkParamsChange {
+    /// new fee policy
+    pub fee_policy: Option<LinearFee>,
+    /// new unbonding period (in seconds)
+    pub unbonding_period: Option<Timespec>,
+    /// new minimal council node stake
+    pub required_council_node_stake: Option<Coin>,
+    /// new scheduled upgrade (replacing the current one, if any)
+    pub upgrade: Option<UpgradePlan>,
+    /// enables or disables the account freeze decisions (see `AccountFreezeTx`)
+    pub account_freeze: Option<bool>,
+}
+
+impl NetworkParamsChange {
+    /// true if no parameter is changed
+    pub fn is_empty(&self) -> bool {
+        self.fee_policy.is_none()
+            && self.unbonding_period.is_none()
+            && self.required_council_node_stake.is_none()
+            && self.upgrade.is_none()
+            && self.account_freeze.is_none()
+    }
+}
+
+/// Delay of a scheduled upgrade if not enough council nodes are ready at the checkpoint
+#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode, Serialize, Deserialize)]
+pub struct UpgradeCheckpoint {
+    /// the readiness is checked at the beginning of this block
+    pub height: BlockHeight,
+    /// minimal percentage of the voting power of the active council nodes which signalled
+    /// their readiness
+    pub min_readiness_percent: u8,
+    /// number of blocks the upgrade height (and the checkpoint) is moved by if the readiness
+    /// is below the minimum
+    pub delay: u64,
+}
+
+/// Upgrade to a new app version scheduled at a block height, which council nodes signal
+/// their readiness for (see `UpgradeSignalTx`)
+#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode, Serialize, Deserialize)]
+pub struct UpgradePlan {
+    /// the app version of the upgrade
+    pub app_version: u64,
+    /// the upgraded version runs from this block
+    pub height: BlockHeight,
+    /// optional automatic delay of the upgrade
+    pub checkpoint: Option<UpgradeCheckpoint>,
+}
+
+/// Proposal to change the network parameters from the given block height.
+/// Its witness is the list of council node signatures (votes) of the transaction ID;
+/// it's valid if the signing council nodes have more than 2/3 of the total voting power.
+#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode, Serialize, Deserialize)]
+pub struct NetworkParamsUpdateTx {
+    /// version of the network parameters to be changed (the number of already applied updates),
+    /// so that the votes can't be replayed
+    pub params_version: u64,
+    /// the change takes effect at the beginning of this block
+    pub effective_height: BlockHeight,
+    /// the changed parameters
+    pub change: NetworkParamsChange,
+    /// the versioning and network identifier
+    pub attributes: StakedStateOpAttributes,
+}
+
+#[cfg(not(feature = "new-txid"))]
+impl TransactionId for NetworkParamsUpdateTx {}
+
+#[cfg(feature = "new-txid")]
+impl From<NetworkParamsUpdateTx> for TaggedTransaction {
+    fn from(tx: NetworkParamsUpdateTx) -> TaggedTransaction {
+        TaggedTransaction::NetworkParamsUpdateTx(tx)
+    }
+}
+
+impl NetworkParamsUpdateTx {
+    /// constructs a new network parameters update transaction from the provided components
+    #[inline]
+    pub fn new(
+        params_version: u64,
+        effective_height: BlockHeight,
+        change: NetworkParamsChange,
+        attributes: StakedStateOpAttributes,
+    ) -> Self {
+        Self {
+            params_version,
+            effective_height,
+            change,
+            attributes,
+        }
+    }
+}
+
+impl fmt::Display for NetworkParamsUpdateTx {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "network parameters update (version: {}) at height {}",
+            self.params_version, self.effective_height
+        )?;
+        if let Some(fee_policy) = &self.change.fee_policy {
+            writeln!(
+                f,
+                "fee policy: constant {} coefficient {}",
+                fee_policy.constant, fee_policy.coefficient
+            )?;
+        }
+        if let Some(unbonding_period) = self.change.unbonding_period {
+            writeln!(f, "unbonding period: {}", unbonding_period)?;
+        }
+        if let Some(stake) = self.change.required_council_node_stake {
+            writeln!(f, "required council node stake: {}", stake)?;
+        }
+        if let Some(upgrade) = &self.change.upgrade {
+            writeln!(
+                f,
+                "upgrade to app version {} at height {}",
+                upgrade.app_version, upgrade.height
+            )?;
+        }
+        if let Some(account_freeze) = self.change.account_freeze {
+            writeln!(f, "account freeze enabled: {}", account_freeze)?;
+        }
+        write!(f, "")
+    }
+}
+
+/// Signals that a council node is ready for the scheduled upgrade
+/// (witness for the staked state of the council node)
+#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode, Serialize, Deserialize)]
+pub struct UpgradeSignalTx {
+    /// the expected nonce on the corresponding state
+    pub nonce: Nonce,
+    /// the staking address of the council node
+    pub address: StakedStateAddress,
+    /// the app version of the scheduled upgrade
+    pub app_version: u64,
+    /// the versioning and network identifier
+    pub attributes: StakedStateOpAttributes,
+}
+
+#[cfg(not(feature = "new-txid"))]
+impl TransactionId for UpgradeSignalTx {}
+
+#[cfg(feature = "new-txid")]
+impl From<UpgradeSignalTx> for TaggedTransaction {
+    fn from(tx: UpgradeSignalTx) -> TaggedTransaction {
+        TaggedTransaction::UpgradeSignalTx(tx)
+    }
+}
+
+impl UpgradeSignalTx {
+    /// constructs a new upgrade signal transaction from the provided components
+    #[inline]
+    pub fn new(
+        nonce: Nonce,
+        address: StakedStateAddress,
+        app_version: u64,
+        attributes: StakedStateOpAttributes,
+    ) -> Self {
+        Self {
+            nonce,
+            address,
+            app_version,
+            attributes,
+        }
+    }
+}
+
+impl fmt::Display for UpgradeSignalTx {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} ready for the upgrade to app version {} (nonce: {})",
+            self.address, self.app_version, self.nonce
+        )?;
+        write!(f, "")
+    }
+}
+
+/// Council decision to freeze (or unfreeze) a staking account: the unbond and withdraw
+/// transactions of a frozen account are rejected.
+/// Its witness is the list of council node signatures (votes) of the transaction ID,
+/// checked as the ones of `NetworkParamsUpdateTx`.
+#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode, Serialize, Deserialize)]
+pub struct AccountFreezeTx {
+    /// version of the account freeze state (the number of already applied decisions),
+    /// so that the votes can't be replayed
+    pub freeze_version: u64,
+    /// the staking address to freeze or unfreeze
+    pub address: StakedStateAddress,
+    /// true to freeze the account, false to unfreeze it
+    pub frozen: bool,
+    /// hash of the reason of the decision (e.g. of the court order), kept off-chain
+    pub reason_hash: H256,
+    /// the versioning and network identifier
+    pub attributes: StakedStateOpAttributes,
+}
+
+#[cfg(not(feature = "new-txid"))]
+impl TransactionId for AccountFreezeTx {}
+
+#[cfg(feature = "new-txid")]
+impl From<AccountFreezeTx> for TaggedTransaction {
+    fn from(tx: AccountFreezeTx) -> TaggedTransaction {
+        TaggedTransaction::AccountFreezeTx(tx)
+    }
+}
+
+impl AccountFreezeTx {
+    /// constructs a new account freeze transaction from the provided components
+    #[inline]
+    pub fn new(
+        freeze_version: u64,
+        address: StakedStateAddress,
+        frozen: bool,
+        reason_hash: H256,
+        attributes: StakedStateOpAttributes,
+    ) -> Self {
+        Self {
+            freeze_version,
+            address,
+            frozen,
+            reason_hash,
+            attributes,
+        }
+    }
+}
+
+impl fmt::Display for AccountFreezeTx {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} {} (version: {}, reason hash: {})",
+            if self.frozen { "freeze" } else { "unfreeze" },
+            self.address,
+            self.freeze_version,
+            hex::encode(&self.reason_hash)
+        )?;
+        write!(f, "")
+    }
+}
+
+/// Staking accounts frozen by the council decisions (see `AccountFreezeTx`)
+#[derive(Debug, Default, PartialEq, Eq, Clone, Encode, Decode, Serialize, Deserialize)]
+pub struct AccountFreezeState {
+    /// the decisions are only accepted (and the frozen accounts are only blocked) if enabled
+    /// by a network parameters update
+    pub enabled: bool,
+    /// number of the applied decisions
+    pub version: u64,
+    /// the frozen staking addresses
+    pub frozen: BTreeSet<StakedStateAddress>,
+}
+
+impl AccountFreezeState {
+    /// true if the unbond and withdraw transactions of the staking address are rejected
+    pub fn is_frozen(&self, address: &StakedStateAddress) -> bool {
+        self.enabled && self.frozen.contains(address)
+    }
+
+    /// Applies the (checked) decision
+    pub fn apply(&mut self, tx: &AccountFreezeTx) {
+        if tx.frozen {
+            self.frozen.insert(tx.address);
+        } else {
+            self.frozen.remove(&tx.address);
+        }
+        self.version += 1;
+    }
+}
@@ -1,23 +1,39 @@
 /// data types related to staked state operations
 pub mod account;
+/// data types related to network parameter updates voted by council nodes
+pub mod governance;
 /// data types related to working with Tendermint
 pub mod tendermint;
+/// Merkle mountain range commitment to the UTXO set (with proofs of unspent outputs)
+pub mod utxo;
 /// data types related to council node operations in staked state (nodejoin and unjail)
 pub mod validator;
 
 use parity_scale_codec::{Decode, Encode};
+#[cfg(not(feature = "new-txid"))]
+use parity_scale_codec::{Error, Input, Output};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::prelude::v1::Vec;
 
-use self::tendermint::BlockHeight;
+use self::account::StakedStateAddress;
+use self::tendermint::{BlockHeight, TendermintVotePower};
 use crate::common::{MerkleTree, Timespec, H256};
 use crate::compute_app_hash;
 use crate::init::coin::Coin;
 use crate::init::params::NetworkParameters;
+use crate::tx::data::input::{TxoPointer, TxoSize};
 use crate::tx::data::TxId;
+use crate::AppHashParts;
+
+/// UTXO set commitment before any transaction outputs were created
+pub const GENESIS_UTXO_COMMITMENT: H256 = utxo::EMPTY_UTXO_ROOT;
 
 /// ABCI chain state
-#[derive(PartialEq, Debug, Clone, Encode, Decode, Serialize, Deserialize)]
+/// (APP_VERSION 1 encoding is the one of the 0.5.0 release: the app hash commitments
+/// added in APP_VERSION 2 are not included, so they are derived from the storage on restore)
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "new-txid", derive(Encode, Decode))]
 pub struct ChainState {
     /// root hash of the sparse merkle patricia trie of staking account states
     pub account_root: H256,
@@ -25,21 +41,94 @@ pub struct ChainState {
     pub rewards_pool: RewardsPoolState,
     /// network parameters (fee policy, staking configuration etc.)
     pub network_params: NetworkParameters,
+    /// root of the merkle mountain range of the transaction outputs (see `utxo::UtxoMmr`)
+    pub utxo_commitment: H256,
+    /// hash of the validator set chosen by the staking logic (see `validator_set_hash`)
+    pub validator_set_hash: H256,
+}
+
+#[cfg(not(feature = "new-txid"))]
+impl Encode for ChainState {
+    fn encode_to<W: Output>(&self, dest: &mut W) {
+        self.account_root.encode_to(dest);
+        self.rewards_pool.encode_to(dest);
+        self.network_params.encode_to(dest);
+    }
+}
+
+#[cfg(not(feature = "new-txid"))]
+impl Decode for ChainState {
+    fn decode<I: Input>(input: &mut I) -> Result<Self, Error> {
+        let account_root = H256::decode(input)?;
+        let rewards_pool = RewardsPoolState::decode(input)?;
+        let network_params = NetworkParameters::decode(input)?;
+        Ok(ChainState::genesis(
+            account_root,
+            rewards_pool,
+            network_params,
+            H256::default(),
+        ))
+    }
 }
 
 impl ChainState {
+    /// creates the state at genesis (no transaction outputs created yet)
+    pub fn genesis(
+        account_root: H256,
+        rewards_pool: RewardsPoolState,
+        network_params: NetworkParameters,
+        validator_set_hash: H256,
+    ) -> Self {
+        ChainState {
+            account_root,
+            rewards_pool,
+            network_params,
+            utxo_commitment: GENESIS_UTXO_COMMITMENT,
+            validator_set_hash,
+        }
+    }
+
     /// computes the app hash based on the internal parameters
     /// identifiers of valid transactions in a given block
     pub fn compute_app_hash(&self, txids: Vec<TxId>) -> H256 {
-        compute_app_hash(
-            &MerkleTree::new(txids),
-            &self.account_root,
-            &self.rewards_pool,
-            &self.network_params,
-        )
+        compute_app_hash(&MerkleTree::new(txids), self)
+    }
+
+    /// the parts of the app hash, given the root of valid TX merkle tree of the block
+    pub fn app_hash_parts(&self, valid_tx_root: H256) -> AppHashParts {
+        AppHashParts {
+            valid_tx_root,
+            account_state_root: self.account_root,
+            rewards_pool_hash: self.rewards_pool.hash(),
+            network_params_hash: self.network_params.hash(),
+            utxo_commitment: self.utxo_commitment,
+            validator_set_hash: self.validator_set_hash,
+        }
     }
 }
 
+/// A change of the UTXO set made by a valid transaction
+/// (applied to the merkle mountain range of the outputs in the order of the valid transactions,
+/// spent inputs before created outputs)
+#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
+pub enum UtxoChange {
+    /// the outputs of the transaction were created (transfer and withdraw transactions)
+    Created {
+        /// the transaction identifier
+        txid: TxId,
+        /// the number of the transaction outputs
+        no_of_outputs: TxoSize,
+    },
+    /// the output was spent (transfer and deposit transactions)
+    Spent(TxoPointer),
+}
+
+/// computes the hash of the validator set chosen by the staking logic:
+/// blake3(scale bytes(vector of (staking address, voting power) sorted by the staking address))
+pub fn validator_set_hash(validators: &BTreeMap<StakedStateAddress, TendermintVotePower>) -> H256 {
+    blake3::hash(&validators.encode()).into()
+}
+
 /// State from which periodic rewards are distributed and calculated
 #[derive(Debug, PartialEq, Eq, Clone, Encode, Decode, Serialize, Deserialize)]
 pub struct RewardsPoolState {
@@ -73,3 +162,85 @@ impl RewardsPoolState {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "new-txid")]
+    fn check_app_hash_layout() {
+        let parts = AppHashParts {
+            valid_tx_root: [1u8; 32],
+            account_state_root: [2u8; 32],
+            rewards_pool_hash: [3u8; 32],
+            network_params_hash: [4u8; 32],
+            utxo_commitment: [5u8; 32],
+            validator_set_hash: validator_set_hash(&BTreeMap::new()),
+        };
+        let mut data = b"app_hash".to_vec();
+        data.extend(parts.encode());
+        assert_eq!(8 + 6 * 32, data.len());
+        let app_hash: H256 = blake3::hash(&data).into();
+        assert!(parts.verify(&app_hash));
+    }
+
+    /// chain state with the golden encodings of the rewards pool and network parameters
+    /// in the schema registry
+    #[cfg(not(feature = "new-txid"))]
+    fn sample_state() -> ChainState {
+        let rewards_pool = RewardsPoolState::decode(
+            &mut hex::decode(
+                "e8030000000000002a0000000000000000105e5f00000000d0070000000000000700000000000000",
+            )
+            .unwrap()
+            .as_slice(),
+        )
+        .unwrap();
+        let network_params = NetworkParameters::decode(
+            &mut hex::decode("004c04000000000000e20400000000000050c30000000000001027000000000000640032006400000000000000c8000000000000002c0100000000000060ea0000000000008051010000000000c201000000000000e803000000000000b4410f00000000003200")
+                .unwrap()
+                .as_slice(),
+        )
+        .unwrap();
+        ChainState::genesis([7u8; 32], rewards_pool, network_params, [9u8; 32])
+    }
+
+    #[test]
+    #[cfg(not(feature = "new-txid"))]
+    fn check_legacy_app_hash() {
+        let state = sample_state();
+        let parts = state.app_hash_parts([1u8; 32]);
+
+        // the 0.5.0 release app hash: only the first four parts are committed
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(b"app_hash");
+        hasher.update(&parts.valid_tx_root);
+        hasher.update(&state.account_root);
+        hasher.update(&state.rewards_pool.hash());
+        hasher.update(&state.network_params.hash());
+        let app_hash: H256 = hasher.finalize().into();
+        assert_eq!(app_hash, parts.app_hash());
+        assert_eq!(
+            "079cd61ff1b0fce097e674cbef0c98eaf0aef8d5b315b2b7fad52d0c98aaa298",
+            hex::encode(parts.app_hash())
+        );
+    }
+
+    #[test]
+    #[cfg(not(feature = "new-txid"))]
+    fn check_legacy_state_encoding() {
+        let state = sample_state();
+        let mut expected = state.account_root.encode();
+        expected.extend(state.rewards_pool.encode());
+        expected.extend(state.network_params.encode());
+        let encoded = state.encode();
+        assert_eq!(expected, encoded);
+
+        let decoded = ChainState::decode(&mut encoded.as_slice()).unwrap();
+        assert_eq!(state.account_root, decoded.account_root);
+        assert_eq!(state.rewards_pool, decoded.rewards_pool);
+        assert_eq!(state.network_params, decoded.network_params);
+        assert_eq!(GENESIS_UTXO_COMMITMENT, decoded.utxo_commitment);
+    }
+}
@@ -0,0 +1,295 @@
+use parity_scale_codec::{Decode, Encode};
+use std::prelude::v1::Vec;
+
+use crate::common::H256;
+use crate::tx::data::input::TxoPointer;
+
+/// root of the Merkle mountain range without any leaves
+pub const EMPTY_UTXO_ROOT: H256 = [0u8; 32];
+
+/// hash of the leaf of a transaction output:
+/// blake3(b"utxo_leaf" || scale bytes(TxoPointer) || 1 if spent, 0 otherwise)
+pub fn utxo_leaf_hash(txo: &TxoPointer, spent: bool) -> H256 {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(b"utxo_leaf");
+    hasher.update(&txo.encode());
+    hasher.update(&[spent as u8]);
+    hasher.finalize().into()
+}
+
+/// hash of an inner node: blake3(b"utxo_node" || left || right)
+fn node_hash(left: &H256, right: &H256) -> H256 {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(b"utxo_node");
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+/// root of the mountain range: blake3(b"utxo_root" || leaf count (u64 LE) || peaks)
+/// where the peaks are ordered from the highest one (the leftmost one)
+fn bag_peaks(leaf_count: u64, peaks: &[H256]) -> H256 {
+    if leaf_count == 0 {
+        return EMPTY_UTXO_ROOT;
+    }
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(b"utxo_root");
+    hasher.update(&leaf_count.to_le_bytes());
+    for peak in peaks.iter() {
+        hasher.update(peak);
+    }
+    hasher.finalize().into()
+}
+
+/// Read access to the nodes of the Merkle mountain range of the UTXO set
+///
+/// The node at (`height`, `index`) is the root of the perfect binary tree over the leaves
+/// `index * 2^height .. (index + 1) * 2^height` (the leaves being at height 0),
+/// so with `n` leaves, the nodes at `height` are the ones with `index < n >> height`.
+pub trait UtxoMmrGet {
+    /// returns the node hash (if it was stored)
+    fn get_node(&self, height: u8, index: u64) -> Option<H256>;
+}
+
+/// Write access to the nodes of the Merkle mountain range of the UTXO set
+pub trait UtxoMmrStore: UtxoMmrGet {
+    /// stores the node hash
+    fn set_node(&mut self, height: u8, index: u64, hash: H256);
+}
+
+/// Merkle mountain range over the transaction outputs (in the order of their creation),
+/// each leaf committing to the output and whether it was spent:
+/// outputs are appended when created and their leaves are updated when spent,
+/// so the cost of both is logarithmic in the number of outputs
+pub struct UtxoMmr<S> {
+    store: S,
+    leaf_count: u64,
+}
+
+impl<S: UtxoMmrGet> UtxoMmr<S> {
+    /// the mountain range with `leaf_count` leaves in the store
+    pub fn new(store: S, leaf_count: u64) -> Self {
+        UtxoMmr { store, leaf_count }
+    }
+
+    /// the number of leaves (i.e. the number of outputs ever created)
+    pub fn leaf_count(&self) -> u64 {
+        self.leaf_count
+    }
+
+    fn node(&self, height: u8, index: u64) -> H256 {
+        self.store
+            .get_node(height, index)
+            .expect("utxo merkle mountain range storage corrupted")
+    }
+
+    /// the peaks, from the highest one
+    fn peaks(&self) -> Vec<H256> {
+        (0..64u8)
+            .rev()
+            .filter(|height| self.leaf_count & (1 << height) != 0)
+            .map(|height| self.node(height, (self.leaf_count >> height) - 1))
+            .collect()
+    }
+
+    /// the commitment to the UTXO set
+    pub fn root(&self) -> H256 {
+        bag_peaks(self.leaf_count, &self.peaks())
+    }
+
+    /// the proof of the leaf at the index (if there is such leaf)
+    pub fn prove(&self, leaf_index: u64) -> Option<UtxoProof> {
+        if leaf_index >= self.leaf_count {
+            return None;
+        }
+        let mut path = Vec::new();
+        let mut height = 0u8;
+        let mut index = leaf_index;
+        // climb while the current node has a parent (i.e. it's not a peak)
+        while (index | 1) < self.leaf_count >> height {
+            path.push(self.node(height, index ^ 1));
+            height += 1;
+            index >>= 1;
+        }
+        Some(UtxoProof {
+            leaf_index,
+            leaf_count: self.leaf_count,
+            path,
+            peaks: self.peaks(),
+        })
+    }
+}
+
+impl<S: UtxoMmrStore> UtxoMmr<S> {
+    /// appends the leaf of a created output, returns its index
+    pub fn append(&mut self, leaf: H256) -> u64 {
+        let leaf_index = self.leaf_count;
+        self.leaf_count += 1;
+        self.store.set_node(0, leaf_index, leaf);
+        let mut hash = leaf;
+        let mut height = 0u8;
+        let mut index = leaf_index;
+        // each right child completes its parent
+        while index & 1 == 1 {
+            hash = node_hash(&self.node(height, index - 1), &hash);
+            height += 1;
+            index >>= 1;
+            self.store.set_node(height, index, hash);
+        }
+        leaf_index
+    }
+
+    /// replaces the leaf at the index (e.g. when the output is spent)
+    pub fn update(&mut self, leaf_index: u64, leaf: H256) {
+        assert!(
+            leaf_index < self.leaf_count,
+            "utxo merkle mountain range leaf index out of range"
+        );
+        self.store.set_node(0, leaf_index, leaf);
+        let mut hash = leaf;
+        let mut height = 0u8;
+        let mut index = leaf_index;
+        while (index | 1) < self.leaf_count >> height {
+            let sibling = self.node(height, index ^ 1);
+            hash = if index & 1 == 0 {
+                node_hash(&hash, &sibling)
+            } else {
+                node_hash(&sibling, &hash)
+            };
+            height += 1;
+            index >>= 1;
+            self.store.set_node(height, index, hash);
+        }
+    }
+}
+
+/// Proof of a leaf of the Merkle mountain range of the UTXO set:
+/// the path from the leaf to its peak and all the peaks of the range
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+pub struct UtxoProof {
+    /// index of the leaf (the output's position in the order of creation)
+    pub leaf_index: u64,
+    /// number of leaves of the range
+    pub leaf_count: u64,
+    /// hashes of the siblings from the leaf up to its peak
+    pub path: Vec<H256>,
+    /// peaks of the range, from the highest one
+    pub peaks: Vec<H256>,
+}
+
+impl UtxoProof {
+    /// the root the proof is against
+    pub fn root(&self) -> H256 {
+        bag_peaks(self.leaf_count, &self.peaks)
+    }
+
+    /// checks the output has the leaf (with the spent flag) in the range with the root
+    pub fn verify(&self, txo: &TxoPointer, spent: bool, root: &H256) -> bool {
+        let height = self.path.len();
+        if self.leaf_index >= self.leaf_count
+            || height >= 64
+            || self.leaf_count & (1 << height) == 0
+            // the leaf should be under the peak at the height
+            || self.leaf_index >> height != (self.leaf_count >> height) - 1
+            || self.peaks.len() != self.leaf_count.count_ones() as usize
+        {
+            return false;
+        }
+        let mut hash = utxo_leaf_hash(txo, spent);
+        for (level, sibling) in self.path.iter().enumerate() {
+            hash = if (self.leaf_index >> level) & 1 == 0 {
+                node_hash(&hash, sibling)
+            } else {
+                node_hash(sibling, &hash)
+            };
+        }
+        // peaks are ordered by height, so the leaf's peak follows the higher ones
+        let position = self
+            .leaf_count
+            .checked_shr(height as u32 + 1)
+            .unwrap_or(0)
+            .count_ones() as usize;
+        self.peaks[position] == hash && self.root() == *root
+    }
+
+    /// checks the output is unspent in the UTXO set with the commitment
+    pub fn verify_unspent(&self, txo: &TxoPointer, root: &H256) -> bool {
+        self.verify(txo, false, root)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[derive(Default)]
+    struct MemStore(BTreeMap<(u8, u64), H256>);
+
+    impl UtxoMmrGet for &mut MemStore {
+        fn get_node(&self, height: u8, index: u64) -> Option<H256> {
+            self.0.get(&(height, index)).copied()
+        }
+    }
+
+    impl UtxoMmrStore for &mut MemStore {
+        fn set_node(&mut self, height: u8, index: u64, hash: H256) {
+            self.0.insert((height, index), hash);
+        }
+    }
+
+    fn txo(index: u16) -> TxoPointer {
+        TxoPointer::new([1u8; 32], index)
+    }
+
+    #[test]
+    fn check_utxo_proofs() {
+        let mut store = MemStore::default();
+        let mut mmr = UtxoMmr::new(&mut store, 0);
+        assert_eq!(EMPTY_UTXO_ROOT, mmr.root());
+        assert!(mmr.prove(0).is_none());
+
+        for i in 0..11 {
+            assert_eq!(i as u64, mmr.append(utxo_leaf_hash(&txo(i), false)));
+        }
+        let root = mmr.root();
+        for i in 0..11 {
+            let proof = mmr.prove(i as u64).unwrap();
+            assert!(proof.verify_unspent(&txo(i), &root));
+            assert!(!proof.verify(&txo(i), true, &root));
+            assert!(!proof.verify_unspent(&txo(i + 1), &root));
+        }
+
+        mmr.update(4, utxo_leaf_hash(&txo(4), true));
+        let spent_root = mmr.root();
+        assert_ne!(root, spent_root);
+        let proof = mmr.prove(4).unwrap();
+        assert!(!proof.verify_unspent(&txo(4), &spent_root));
+        assert!(proof.verify(&txo(4), true, &spent_root));
+        // the other outputs are still proven unspent against the new root
+        assert!(mmr.prove(5).unwrap().verify_unspent(&txo(5), &spent_root));
+        assert!(mmr.prove(10).unwrap().verify_unspent(&txo(10), &spent_root));
+        assert!(!mmr.prove(10).unwrap().verify_unspent(&txo(10), &root));
+    }
+
+    #[test]
+    fn check_utxo_root_is_incremental() {
+        let mut store = MemStore::default();
+        let mut mmr = UtxoMmr::new(&mut store, 0);
+        for i in 0..5 {
+            mmr.append(utxo_leaf_hash(&txo(i), i % 2 == 0));
+        }
+        let root = mmr.root();
+
+        // the same range built with the leaves updated afterwards
+        let mut other_store = MemStore::default();
+        let mut other = UtxoMmr::new(&mut other_store, 0);
+        for i in 0..5 {
+            other.append(utxo_leaf_hash(&txo(i), false));
+        }
+        for i in (0..5).step_by(2) {
+            other.update(i as u64, utxo_leaf_hash(&txo(i), true));
+        }
+        assert_eq!(root, other.root());
+    }
+}
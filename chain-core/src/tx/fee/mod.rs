@@ -4,7 +4,12 @@
 //! Modifications Copyright (c) 2018 - 2020, Foris Limited (licensed under the Apache License, Version 2.0)
 
 use crate::init::coin::{Coin, CoinError};
-use crate::tx::TxAux;
+use crate::state::account::{DepositBondTx, StakedStateOpWitness, WithdrawUnbondedTx};
+use crate::state::tendermint::BlockHeight;
+use crate::tx::data::input::TxoSize;
+use crate::tx::data::{Tx, TxId};
+use crate::tx::witness::TxWitness;
+use crate::tx::{PlainTxAux, TransactionId, TxAux, TxEnclaveAux, TxObfuscated};
 use parity_scale_codec::{Decode, Encode};
 
 use serde::{Deserialize, Serialize};
@@ -252,6 +257,58 @@ impl FeeAlgorithm for LinearFee {
     }
 }
 
+/// Length of the authentication tag appended to the obfuscated payloads
+/// by the encryption (AES-GCM-SIV: https://tools.ietf.org/html/rfc8452)
+pub const OBFUSCATION_TAG_LENGTH: usize = 16;
+
+/// Obfuscated payload of the same length as the one encrypted by the enclave
+/// (zeroed ciphertext + authentication tag)
+fn mock_payload(plain: &PlainTxAux, txid: TxId) -> TxObfuscated {
+    TxObfuscated {
+        txid,
+        key_from: BlockHeight::genesis(),
+        init_vector: [0; 12],
+        txpayload: vec![0; plain.encode().len() + OBFUSCATION_TAG_LENGTH],
+    }
+}
+
+/// The obfuscated transfer transaction with a mock payload: its encoded size (which the fee
+/// is computed for) is the same as of the transaction encrypted by the enclave.
+///
+/// The minimal fee of an enclave transaction is computed from the encoded `TxAux`
+/// when it's validated, but from the plain transaction when it's encrypted (and by the clients),
+/// so both must use these functions for the sizes to agree.
+pub fn mock_obfuscated_transfer(tx: &Tx, witness: &TxWitness) -> TxAux {
+    let payload = mock_payload(
+        &PlainTxAux::TransferTx(tx.clone(), witness.clone()),
+        tx.id(),
+    );
+    TxAux::EnclaveTx(TxEnclaveAux::TransferTx {
+        inputs: tx.inputs.clone(),
+        no_of_outputs: tx.outputs.len() as TxoSize,
+        payload,
+    })
+}
+
+/// The obfuscated deposit transaction with a mock payload (see `mock_obfuscated_transfer`)
+pub fn mock_obfuscated_deposit(tx: &DepositBondTx, witness: &TxWitness) -> TxAux {
+    let payload = mock_payload(&PlainTxAux::DepositStakeTx(witness.clone()), tx.id());
+    TxAux::EnclaveTx(TxEnclaveAux::DepositStakeTx {
+        tx: tx.clone(),
+        payload,
+    })
+}
+
+/// The obfuscated withdraw transaction with a mock payload (see `mock_obfuscated_transfer`)
+pub fn mock_obfuscated_withdraw(tx: &WithdrawUnbondedTx, witness: &StakedStateOpWitness) -> TxAux {
+    let payload = mock_payload(&PlainTxAux::WithdrawUnbondedStakeTx(tx.clone()), tx.id());
+    TxAux::EnclaveTx(TxEnclaveAux::WithdrawUnbondedStakeTx {
+        no_of_outputs: tx.outputs.len() as TxoSize,
+        witness: witness.clone(),
+        payload,
+    })
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -4,6 +4,8 @@ use std::prelude::v1::Vec;
 pub mod data;
 /// Transaction fee calculation
 pub mod fee;
+/// Versions of the encoded transaction envelope
+pub mod version;
 /// Witness structures (e.g. signatures) for transactions
 pub mod witness;
 
@@ -12,12 +14,17 @@ use std::fmt;
 use parity_scale_codec::{Decode, Encode, Error, Input, Output};
 
 use self::data::Tx;
+use self::version::{
+    TxAuxVersion, CURRENT_TX_AUX_VERSION, TX_AUX_VERSIONED_TAG, TX_AUX_VERSION_1,
+    TX_AUX_VERSION_UNVERSIONED,
+};
 use self::witness::TxWitness;
 use crate::mls::MLSHandshakeAux;
 use crate::state::account::{
-    DepositBondTx, StakedStateOpAttributes, StakedStateOpWitness, UnbondTx, UnjailTx,
+    DataAnchorTx, DepositBondTx, StakedStateOpAttributes, StakedStateOpWitness, UnbondTx, UnjailTx,
     WithdrawUnbondedTx,
 };
+use crate::state::governance::{AccountFreezeTx, NetworkParamsUpdateTx, UpgradeSignalTx};
 use crate::state::tendermint::BlockHeight;
 use crate::state::validator::NodeJoinRequestTx;
 use crate::tx::data::TxId;
@@ -389,6 +396,14 @@ pub enum TxPublicAux {
     UnjailTx(UnjailTx, StakedStateOpWitness),
     /// Tx that updates a staked state with node (community or council node) details
     NodeJoinTx(NodeJoinRequestTx, StakedStateOpWitness),
+    /// Tx that schedules a network parameters update (witnessed by the votes of council nodes)
+    NetworkParamsUpdateTx(NetworkParamsUpdateTx, Vec<StakedStateOpWitness>),
+    /// Tx that signals a council node is ready for the scheduled upgrade
+    UpgradeSignalTx(UpgradeSignalTx, StakedStateOpWitness),
+    /// Tx that anchors a commitment to external data (witness for the staked state paying the fee)
+    DataAnchorTx(DataAnchorTx, StakedStateOpWitness),
+    /// Tx that freezes or unfreezes a staked state (witnessed by the votes of council nodes)
+    AccountFreezeTx(AccountFreezeTx, Vec<StakedStateOpWitness>),
 }
 
 impl Encode for TxPublicAux {
@@ -409,6 +424,26 @@ impl Encode for TxPublicAux {
                 dest.push(tx);
                 dest.push(witness);
             }
+            TxPublicAux::NetworkParamsUpdateTx(ref tx, ref votes) => {
+                dest.push_byte(3);
+                dest.push(tx);
+                dest.push(votes);
+            }
+            TxPublicAux::UpgradeSignalTx(ref tx, ref witness) => {
+                dest.push_byte(4);
+                dest.push(tx);
+                dest.push(witness);
+            }
+            TxPublicAux::DataAnchorTx(ref tx, ref witness) => {
+                dest.push_byte(5);
+                dest.push(tx);
+                dest.push(witness);
+            }
+            TxPublicAux::AccountFreezeTx(ref tx, ref votes) => {
+                dest.push_byte(6);
+                dest.push(tx);
+                dest.push(votes);
+            }
         }
     }
 
@@ -417,6 +452,10 @@ impl Encode for TxPublicAux {
             TxPublicAux::UnbondStakeTx(tx, witness) => tx.size_hint() + witness.size_hint(),
             TxPublicAux::UnjailTx(tx, witness) => tx.size_hint() + witness.size_hint(),
             TxPublicAux::NodeJoinTx(tx, witness) => tx.size_hint() + witness.size_hint(),
+            TxPublicAux::NetworkParamsUpdateTx(tx, votes) => tx.size_hint() + votes.size_hint(),
+            TxPublicAux::UpgradeSignalTx(tx, witness) => tx.size_hint() + witness.size_hint(),
+            TxPublicAux::DataAnchorTx(tx, witness) => tx.size_hint() + witness.size_hint(),
+            TxPublicAux::AccountFreezeTx(tx, votes) => tx.size_hint() + votes.size_hint(),
         }
     }
 }
@@ -424,7 +463,7 @@ impl Encode for TxPublicAux {
 impl Decode for TxPublicAux {
     fn decode<DecIn: Input>(input: &mut DecIn) -> Result<Self, Error> {
         let tag = input.read_byte()?;
-        // note: 3.. tags reserved for other tx types (node metadata update etc.)
+        // note: 7.. tags reserved for other tx types (node metadata update etc.)
         match tag {
             0 => {
                 let tx = UnbondTx::decode(input)?;
@@ -441,6 +480,26 @@ impl Decode for TxPublicAux {
                 let witness = StakedStateOpWitness::decode(input)?;
                 Ok(TxPublicAux::NodeJoinTx(tx, witness))
             }
+            3 => {
+                let tx = NetworkParamsUpdateTx::decode(input)?;
+                let votes = Vec::<StakedStateOpWitness>::decode(input)?;
+                Ok(TxPublicAux::NetworkParamsUpdateTx(tx, votes))
+            }
+            4 => {
+                let tx = UpgradeSignalTx::decode(input)?;
+                let witness = StakedStateOpWitness::decode(input)?;
+                Ok(TxPublicAux::UpgradeSignalTx(tx, witness))
+            }
+            5 => {
+                let tx = DataAnchorTx::decode(input)?;
+                let witness = StakedStateOpWitness::decode(input)?;
+                Ok(TxPublicAux::DataAnchorTx(tx, witness))
+            }
+            6 => {
+                let tx = AccountFreezeTx::decode(input)?;
+                let votes = Vec::<StakedStateOpWitness>::decode(input)?;
+                Ok(TxPublicAux::AccountFreezeTx(tx, votes))
+            }
             _ => Err("No such variant in enum TxPublicAux".into()),
         }
     }
@@ -453,6 +512,10 @@ impl TxPublicAux {
             TxPublicAux::UnbondStakeTx(tx, _) => tx.id(),
             TxPublicAux::UnjailTx(tx, _) => tx.id(),
             TxPublicAux::NodeJoinTx(tx, _) => tx.id(),
+            TxPublicAux::NetworkParamsUpdateTx(tx, _) => tx.id(),
+            TxPublicAux::UpgradeSignalTx(tx, _) => tx.id(),
+            TxPublicAux::DataAnchorTx(tx, _) => tx.id(),
+            TxPublicAux::AccountFreezeTx(tx, _) => tx.id(),
         }
     }
 
@@ -462,6 +525,10 @@ impl TxPublicAux {
             TxPublicAux::UnbondStakeTx(tx, _) => &tx.attributes,
             TxPublicAux::UnjailTx(tx, _) => &tx.attributes,
             TxPublicAux::NodeJoinTx(tx, _) => &tx.attributes,
+            TxPublicAux::NetworkParamsUpdateTx(tx, _) => &tx.attributes,
+            TxPublicAux::UpgradeSignalTx(tx, _) => &tx.attributes,
+            TxPublicAux::DataAnchorTx(tx, _) => &tx.attributes,
+            TxPublicAux::AccountFreezeTx(tx, _) => &tx.attributes,
         }
     }
 
@@ -480,6 +547,7 @@ impl TxPublicAux {
 /// it can be a variant in ExtendedAddr + a corresponding witness type.
 /// (could be even to e.g. support a different signature scheme)
 /// - If the extension is a different behaviour, it'll be a new transaction type (possibly under enclave or public auxiliary type).
+/// - If the envelope encoding changes, it'll be a new version activated by a network upgrade (see `version`).
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum TxAux {
     /// transactions that need to be processed inside TEE (or need TEE in their finalization)
@@ -490,8 +558,9 @@ pub enum TxAux {
     MLSHandshake(MLSHandshakeAux),
 }
 
-impl Encode for TxAux {
-    fn encode_to<EncOut: Output>(&self, dest: &mut EncOut) {
+impl TxAux {
+    /// encodes the variant index and the payload (without the envelope)
+    fn encode_variant_to<EncOut: Output>(&self, dest: &mut EncOut) {
         match *self {
             TxAux::EnclaveTx(ref tx) => {
                 dest.push_byte(0);
@@ -508,17 +577,32 @@ impl Encode for TxAux {
         }
     }
 
-    fn size_hint(&self) -> usize {
-        1 + match self {
-            TxAux::EnclaveTx(tx) => tx.size_hint(),
-            TxAux::PublicTx(tx) => tx.size_hint(),
-            TxAux::MLSHandshake(tx) => tx.size_hint(),
+    fn decode_variant<I: Input>(variant: u8, input: &mut I) -> Result<Self, Error> {
+        match variant {
+            0 => Ok(TxAux::EnclaveTx(TxEnclaveAux::decode(input)?)),
+            1 => Ok(TxAux::PublicTx(TxPublicAux::decode(input)?)),
+            2 => Ok(TxAux::MLSHandshake(MLSHandshakeAux::decode(input)?)),
+            _ => Err("No such variant in enum TxAux".into()),
         }
     }
-}
 
-impl Decode for TxAux {
-    fn decode<I: Input>(input: &mut I) -> Result<Self, Error> {
+    /// encodes the transaction in the version (None if the version is unknown)
+    pub fn encode_version(&self, version: TxAuxVersion) -> Option<Vec<u8>> {
+        let mut dest = Vec::with_capacity(self.size_hint());
+        match version {
+            TX_AUX_VERSION_UNVERSIONED => {}
+            TX_AUX_VERSION_1 => {
+                dest.push_byte(TX_AUX_VERSIONED_TAG);
+                dest.push_byte(version);
+            }
+            _ => return None,
+        }
+        self.encode_variant_to(&mut dest);
+        Some(dest)
+    }
+
+    /// decodes the transaction in any of the known versions, and returns its version
+    pub fn decode_versioned<I: Input>(input: &mut I) -> Result<(TxAuxVersion, Self), Error> {
         let size = input
             .remaining_len()?
             .ok_or("Unable to calculate size of input")?;
@@ -528,14 +612,53 @@ impl Decode for TxAux {
         }
 
         match input.read_byte()? {
-            0 => Ok(TxAux::EnclaveTx(TxEnclaveAux::decode(input)?)),
-            1 => Ok(TxAux::PublicTx(TxPublicAux::decode(input)?)),
-            2 => Ok(TxAux::MLSHandshake(MLSHandshakeAux::decode(input)?)),
-            _ => Err("No such variant in enum TxAux".into()),
+            TX_AUX_VERSIONED_TAG => match input.read_byte()? {
+                TX_AUX_VERSION_1 => {
+                    let variant = input.read_byte()?;
+                    Ok((TX_AUX_VERSION_1, Self::decode_variant(variant, input)?))
+                }
+                _ => Err("Unknown version of TxAux".into()),
+            },
+            variant => Ok((
+                TX_AUX_VERSION_UNVERSIONED,
+                Self::decode_variant(variant, input)?,
+            )),
         }
     }
 }
 
+/// Encodes in `CURRENT_TX_AUX_VERSION`
+impl Encode for TxAux {
+    fn encode_to<EncOut: Output>(&self, dest: &mut EncOut) {
+        if CURRENT_TX_AUX_VERSION != TX_AUX_VERSION_UNVERSIONED {
+            dest.push_byte(TX_AUX_VERSIONED_TAG);
+            dest.push_byte(CURRENT_TX_AUX_VERSION);
+        }
+        self.encode_variant_to(dest);
+    }
+
+    fn size_hint(&self) -> usize {
+        let envelope = if CURRENT_TX_AUX_VERSION != TX_AUX_VERSION_UNVERSIONED {
+            3
+        } else {
+            1
+        };
+        envelope
+            + match self {
+                TxAux::EnclaveTx(tx) => tx.size_hint(),
+                TxAux::PublicTx(tx) => tx.size_hint(),
+                TxAux::MLSHandshake(tx) => tx.size_hint(),
+            }
+    }
+}
+
+/// Accepts all the known versions (see `TxAux::decode_versioned`)
+impl Decode for TxAux {
+    fn decode<I: Input>(input: &mut I) -> Result<Self, Error> {
+        Self::decode_versioned(input).map(|(_, tx)| tx)
+    }
+}
+
 /// This trait is for transaction data type to define the "txid"
 /// that's used as a message digest in signing.
 /// *IMPORTANT*: only auto-implement TransactionId for data types
@@ -590,6 +713,14 @@ pub enum TaggedTransaction {
     MLSSelfUpdateProposal(crate::mls::SelfUpdateProposalTx),
     /// NACK
     MLSMsgNack(crate::mls::NackMsgTx),
+    /// network parameters update
+    NetworkParamsUpdateTx(NetworkParamsUpdateTx),
+    /// upgrade readiness signal
+    UpgradeSignalTx(UpgradeSignalTx),
+    /// data anchoring
+    DataAnchorTx(DataAnchorTx),
+    /// account freeze decision
+    AccountFreezeTx(AccountFreezeTx),
 }
 
 #[cfg(feature = "new-txid")]
@@ -652,6 +783,18 @@ impl fmt::Display for TxAux {
             TxAux::PublicTx(TxPublicAux::NodeJoinTx(tx, witness)) => {
                 display_tx_witness(f, tx, witness)
             }
+            TxAux::PublicTx(TxPublicAux::NetworkParamsUpdateTx(tx, votes)) => {
+                display_tx_witness(f, tx, votes)
+            }
+            TxAux::PublicTx(TxPublicAux::UpgradeSignalTx(tx, witness)) => {
+                display_tx_witness(f, tx, witness)
+            }
+            TxAux::PublicTx(TxPublicAux::DataAnchorTx(tx, witness)) => {
+                display_tx_witness(f, tx, witness)
+            }
+            TxAux::PublicTx(TxPublicAux::AccountFreezeTx(tx, votes)) => {
+                display_tx_witness(f, tx, votes)
+            }
             TxAux::MLSHandshake(_) => {
                 // FIXME
                 writeln!(f, "mls handshake")
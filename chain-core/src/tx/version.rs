@@ -0,0 +1,75 @@
+//! Versions of the encoded transaction envelope (`TxAux`)
+//!
+//! The versioned envelope is `TX_AUX_VERSIONED_TAG || version || variant index || payload`.
+//! The unversioned encoding of the 0.5 format (`variant index || payload`) is decoded
+//! as `TX_AUX_VERSION_UNVERSIONED`.
+//!
+//! A new encoding gets a new version, which is activated by a network upgrade: the decoding
+//! accepts all the known versions, but the transactions of a version are only valid
+//! from the app version in `tx_aux_version_activation` (i.e. from the upgrade height).
+
+use crate::state::account::StakedStateOpWitness;
+use crate::tx::{TxAux, TxEnclaveAux, TxPublicAux};
+
+/// Version of the encoded transaction envelope
+pub type TxAuxVersion = u8;
+
+/// The app version of the network upgrade which activates the versioned envelope,
+/// and the transaction types and staked state witness types added since the 0.5 release
+pub const UPGRADE_APP_VERSION: u64 = 2;
+
+/// The first byte of the versioned envelope (not used as a `TxAux` variant index)
+pub const TX_AUX_VERSIONED_TAG: u8 = 0xff;
+
+/// The encoding without the version byte (the first byte is the `TxAux` variant index)
+pub const TX_AUX_VERSION_UNVERSIONED: TxAuxVersion = 0;
+/// The versioned envelope around the payloads of the unversioned encoding
+pub const TX_AUX_VERSION_1: TxAuxVersion = 1;
+
+/// The version of the encoded transactions (0.5-compatible)
+#[cfg(not(feature = "new-txid"))]
+pub const CURRENT_TX_AUX_VERSION: TxAuxVersion = TX_AUX_VERSION_UNVERSIONED;
+/// The version of the encoded transactions
+#[cfg(feature = "new-txid")]
+pub const CURRENT_TX_AUX_VERSION: TxAuxVersion = TX_AUX_VERSION_1;
+
+/// All the known versions (in the order of their activation)
+pub const TX_AUX_VERSIONS: [TxAuxVersion; 2] = [TX_AUX_VERSION_UNVERSIONED, TX_AUX_VERSION_1];
+
+/// The app version from which the transactions of the version are valid
+/// (None if the version is unknown)
+pub fn tx_aux_version_activation(version: TxAuxVersion) -> Option<u64> {
+    match version {
+        TX_AUX_VERSION_UNVERSIONED => Some(0),
+        TX_AUX_VERSION_1 => Some(UPGRADE_APP_VERSION),
+        _ => None,
+    }
+}
+
+/// Checks if the transactions of the version are valid in the app version
+pub fn is_tx_aux_version_active(version: TxAuxVersion, app_version: u64) -> bool {
+    tx_aux_version_activation(version).map_or(false, |activation| activation <= app_version)
+}
+
+/// The app version from which the transaction is valid (regardless of its envelope version):
+/// the public transaction types and staked state witness types added since the 0.5 release
+/// are only valid from `UPGRADE_APP_VERSION`
+pub fn tx_activation(tx: &TxAux) -> u64 {
+    let witness_activation = |witness: &StakedStateOpWitness| match witness {
+        StakedStateOpWitness::BasicRedeem(_) => 0,
+        StakedStateOpWitness::ThresholdSig(_) => UPGRADE_APP_VERSION,
+    };
+    match tx {
+        TxAux::EnclaveTx(TxEnclaveAux::WithdrawUnbondedStakeTx { witness, .. }) => {
+            witness_activation(witness)
+        }
+        TxAux::EnclaveTx(_) | TxAux::MLSHandshake(_) => 0,
+        TxAux::PublicTx(TxPublicAux::UnbondStakeTx(_, witness))
+        | TxAux::PublicTx(TxPublicAux::UnjailTx(_, witness))
+        | TxAux::PublicTx(TxPublicAux::NodeJoinTx(_, witness)) => witness_activation(witness),
+        TxAux::PublicTx(TxPublicAux::NetworkParamsUpdateTx(..))
+        | TxAux::PublicTx(TxPublicAux::UpgradeSignalTx(..))
+        | TxAux::PublicTx(TxPublicAux::DataAnchorTx(..))
+        | TxAux::PublicTx(TxPublicAux::AccountFreezeTx(..)) => UPGRADE_APP_VERSION,
+    }
+}
@@ -7,7 +7,7 @@ use std::prelude::v1::Vec;
 // TODO: switch to normal signatures + explicit public key
 use secp256k1::{self, recovery::RecoverableSignature, schnorrsig::SchnorrSignature};
 
-use crate::common::Proof;
+use crate::common::{Proof, Timespec};
 use crate::tx::witness::tree::{RawSignature, RawXOnlyPubkey};
 
 /// ETH-style recoverable ECDSA
@@ -101,6 +101,24 @@ pub enum TxInWitness {
     /// BIP340-compatible Schnorr signature
     /// + Merkle proof from the pubkey leaf to the address root
     TreeSig(SchnorrSignature, Proof<RawXOnlyPubkey>),
+    /// BIP340-compatible Schnorr signature of the key, which can only sign from the given time
+    /// + Merkle proof from the timelocked leaf (`tree::timelocked_leaf(key, time)`) to the address root
+    TimelockedTreeSig(
+        SchnorrSignature,
+        RawXOnlyPubkey,
+        Timespec,
+        Proof<RawXOnlyPubkey>,
+    ),
+}
+
+impl TxInWitness {
+    /// Returns the time from which the witness is valid (if it's timelocked)
+    pub fn valid_from(&self) -> Option<Timespec> {
+        match self {
+            TxInWitness::TreeSig(..) => None,
+            TxInWitness::TimelockedTreeSig(_, _, valid_from, _) => Some(*valid_from),
+        }
+    }
 }
 
 impl fmt::Display for TxInWitness {
@@ -117,12 +135,20 @@ impl Encode for TxInWitness {
                 schnorrsig.serialize_default().encode_to(dest);
                 proof.encode_to(dest);
             }
+            TxInWitness::TimelockedTreeSig(ref schnorrsig, ref key, valid_from, ref proof) => {
+                dest.push_byte(1);
+                schnorrsig.serialize_default().encode_to(dest);
+                key.encode_to(dest);
+                valid_from.encode_to(dest);
+                proof.encode_to(dest);
+            }
         }
     }
 
     fn size_hint(&self) -> usize {
         match self {
             TxInWitness::TreeSig(_, ref proof) => 65 + proof.size_hint(),
+            TxInWitness::TimelockedTreeSig(_, _, _, ref proof) => 65 + 32 + 8 + proof.size_hint(),
         }
     }
 }
@@ -138,6 +164,17 @@ impl Decode for TxInWitness {
                 let proof = Proof::decode(input)?;
                 Ok(TxInWitness::TreeSig(schnorrsig, proof))
             }
+            1 => {
+                let raw_sig = RawSignature::decode(input)?;
+                let schnorrsig = SchnorrSignature::from_default(&raw_sig)
+                    .map_err(|_| Error::from("Unable to parse schnorr signature"))?;
+                let key = RawXOnlyPubkey::decode(input)?;
+                let valid_from = Timespec::decode(input)?;
+                let proof = Proof::decode(input)?;
+                Ok(TxInWitness::TimelockedTreeSig(
+                    schnorrsig, key, valid_from, proof,
+                ))
+            }
             _ => Err(Error::from("Invalid tag")),
         }
     }
@@ -1,4 +1,4 @@
-use crate::common::{H256, H512};
+use crate::common::{Timespec, H256, H512};
 use parity_scale_codec::{Decode, Encode, EncodeLike, Error, Input, Output};
 
 use serde::{Deserialize, Serialize};
@@ -55,6 +55,20 @@ impl From<H256> for RawXOnlyPubkey {
     }
 }
 
+/// Domain separation of the timelocked leaves (from the merkle tree nodes and plain keys)
+const TIMELOCKED_LEAF_PREFIX: &[u8] = b"timelocked-leaf";
+
+/// Leaf of a key which can only sign for the address from the given time:
+/// the hash of the key and the time, so it can't be used as a (plain) key itself
+pub fn timelocked_leaf(key: &RawXOnlyPubkey, valid_from: Timespec) -> RawXOnlyPubkey {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(TIMELOCKED_LEAF_PREFIX);
+    hasher.update(key.as_bytes());
+    hasher.update(&valid_from.to_le_bytes());
+    let hash: H256 = hasher.finalize().into();
+    RawXOnlyPubkey(hash)
+}
+
 /// BIP340 signature
 /// ("raw" as in unparsed, so potentially invalid)
 pub type RawSignature = H512;
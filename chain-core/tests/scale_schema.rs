@@ -0,0 +1,39 @@
+use chain_core::schema::{current_schema, diff_schemas, registry, TypeSchema};
+
+fn golden_schema() -> Vec<TypeSchema> {
+    serde_json::from_str(include_str!("scale_schema.json")).unwrap()
+}
+
+/// Fails when a consensus encoding changes; if the change is intended, bump the type's schema
+/// version in `chain_core::schema::registry` and regenerate `scale_schema.json`
+/// (`dev-utils scale-schema export -o chain-core/tests/scale_schema.json`)
+#[test]
+fn test_scale_schema_compatibility() {
+    let golden = golden_schema();
+    let changes = diff_schemas(&golden, &current_schema());
+    let report = changes
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("\n");
+    assert!(changes.is_empty(), "SCALE schema changed:\n{}", report);
+    assert_eq!(golden, current_schema());
+}
+
+#[test]
+fn test_golden_encodings_decode() {
+    let golden = golden_schema();
+    for entry in registry() {
+        let schema = golden
+            .iter()
+            .find(|schema| schema.name == entry.name)
+            .unwrap();
+        let encoding = hex::decode(&schema.encoding).unwrap();
+        assert_eq!(
+            Some(encoding.clone()),
+            (entry.reencode)(&encoding),
+            "{:?}",
+            entry
+        );
+    }
+}
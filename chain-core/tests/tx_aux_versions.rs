@@ -0,0 +1,106 @@
+use chain_core::init::address::RedeemAddress;
+use chain_core::init::coin::Coin;
+use chain_core::state::account::{
+    StakedStateAddress, StakedStateOpAttributes, StakedStateOpWitness, UnbondTx,
+};
+use chain_core::state::tendermint::BlockHeight;
+use chain_core::tx::data::input::TxoPointer;
+use chain_core::tx::version::{
+    is_tx_aux_version_active, tx_aux_version_activation, TxAuxVersion, CURRENT_TX_AUX_VERSION,
+    TX_AUX_VERSIONED_TAG, TX_AUX_VERSIONS, TX_AUX_VERSION_1, TX_AUX_VERSION_UNVERSIONED,
+};
+use chain_core::tx::{TxAux, TxEnclaveAux, TxObfuscated, TxPublicAux};
+use chain_core::APP_VERSION;
+use parity_scale_codec::{Decode, Encode};
+use secp256k1::recovery::{RecoverableSignature, RecoveryId};
+
+fn sample_txs() -> Vec<TxAux> {
+    let witness = StakedStateOpWitness::BasicRedeem(
+        RecoverableSignature::from_compact(&[0x01; 64], RecoveryId::from_i32(1).unwrap()).unwrap(),
+    );
+    let unbond = UnbondTx::new(
+        StakedStateAddress::BasicRedeem(RedeemAddress([0x11; 20])),
+        5,
+        Coin::new(1_000).unwrap(),
+        StakedStateOpAttributes::new(0xab),
+    );
+    let transfer = TxEnclaveAux::TransferTx {
+        inputs: vec![TxoPointer::new([0x22; 32], 1)],
+        no_of_outputs: 2,
+        payload: TxObfuscated {
+            key_from: BlockHeight::new(42),
+            init_vector: [0x66; 12],
+            txpayload: vec![0x77; 4],
+            txid: [0x22; 32],
+        },
+    };
+    vec![
+        TxAux::PublicTx(TxPublicAux::UnbondStakeTx(unbond, witness)),
+        TxAux::EnclaveTx(transfer),
+    ]
+}
+
+fn decode_versioned(encoded: &[u8]) -> Result<(TxAuxVersion, TxAux), parity_scale_codec::Error> {
+    TxAux::decode_versioned(&mut &encoded[..])
+}
+
+#[test]
+fn test_all_versions_roundtrip() {
+    for tx in sample_txs() {
+        for version in TX_AUX_VERSIONS.iter() {
+            let encoded = tx.encode_version(*version).unwrap();
+            assert_eq!((*version, tx.clone()), decode_versioned(&encoded).unwrap());
+            assert_eq!(tx, TxAux::decode(&mut encoded.as_slice()).unwrap());
+        }
+        assert_eq!(tx.encode_version(CURRENT_TX_AUX_VERSION), Some(tx.encode()));
+    }
+}
+
+#[test]
+fn test_versioned_envelope_layout() {
+    for tx in sample_txs() {
+        let unversioned = tx.encode_version(TX_AUX_VERSION_UNVERSIONED).unwrap();
+        let mut versioned = vec![TX_AUX_VERSIONED_TAG, TX_AUX_VERSION_1];
+        versioned.extend_from_slice(&unversioned);
+        assert_eq!(Some(versioned), tx.encode_version(TX_AUX_VERSION_1));
+        // the envelope tag is not a variant index of the unversioned encoding
+        assert_ne!(TX_AUX_VERSIONED_TAG, unversioned[0]);
+    }
+}
+
+#[test]
+fn test_unknown_versions_rejected() {
+    let tx = sample_txs().remove(0);
+    let unknown = TX_AUX_VERSIONS.iter().max().unwrap() + 1;
+    assert_eq!(None, tx.encode_version(unknown));
+    assert_eq!(None, tx_aux_version_activation(unknown));
+
+    let mut encoded = tx.encode_version(TX_AUX_VERSION_1).unwrap();
+    encoded[1] = unknown;
+    assert!(decode_versioned(&encoded).is_err());
+    // unknown variant index in the envelope
+    let mut encoded = tx.encode_version(TX_AUX_VERSION_1).unwrap();
+    encoded[2] = 0xfe;
+    assert!(decode_versioned(&encoded).is_err());
+}
+
+#[test]
+fn test_version_activations() {
+    let activations = TX_AUX_VERSIONS
+        .iter()
+        .map(|version| tx_aux_version_activation(*version).unwrap())
+        .collect::<Vec<_>>();
+    let mut sorted = activations.clone();
+    sorted.sort();
+    assert_eq!(sorted, activations);
+
+    assert!(is_tx_aux_version_active(
+        CURRENT_TX_AUX_VERSION,
+        APP_VERSION
+    ));
+    assert!(is_tx_aux_version_active(
+        TX_AUX_VERSION_UNVERSIONED,
+        APP_VERSION
+    ));
+    assert!(!is_tx_aux_version_active(TX_AUX_VERSION_1, 1));
+}
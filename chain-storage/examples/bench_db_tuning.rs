@@ -0,0 +1,80 @@
+use std::path::{Path, PathBuf};
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use kvdb::KeyValueDB;
+
+use chain_storage::tuning::DbTuning;
+use chain_storage::{COL_BODIES, COL_TX_META};
+
+/// Number of the transactions stored before the benchmarks
+const NUM_TXS: u64 = 100_000;
+/// Number of the transactions per write batch (about a block)
+const BATCH_SIZE: u64 = 500;
+
+fn txid(i: u64) -> [u8; 32] {
+    *blake3::hash(&i.to_le_bytes()).as_bytes()
+}
+
+/// 1-4 KiB transaction body
+fn tx_body(i: u64) -> Vec<u8> {
+    let len = 1024 + (i % 4) as usize * 1024;
+    txid(i).iter().cycle().take(len).copied().collect()
+}
+
+fn write_txs(db: &kvdb_rocksdb::Database, from: u64, count: u64) {
+    let mut tx = db.transaction();
+    for i in from..from + count {
+        let txid = txid(i);
+        tx.put(COL_BODIES, &txid, &tx_body(i));
+        tx.put(COL_TX_META, &txid, &[0u8; 2]);
+    }
+    db.write(tx).expect("write transactions");
+}
+
+fn open(base: &Path, name: &str, tuning: &DbTuning) -> kvdb_rocksdb::Database {
+    let db_path: PathBuf = base.join(name);
+    let _ = std::fs::remove_dir_all(&db_path);
+    let db = kvdb_rocksdb::Database::open(
+        &tuning.database_config(&db_path),
+        db_path.to_str().expect("invalid db path"),
+    )
+    .expect("open db");
+    for from in (0..NUM_TXS).step_by(BATCH_SIZE as usize) {
+        write_txs(&db, from, BATCH_SIZE);
+    }
+    db
+}
+
+/// Compares the uniform column options of kvdb-rocksdb with the default tuning
+/// (`DBPATH` is the directory of the benchmark databases, `/tmp/db-tuning` by default)
+fn criterion_benchmark(c: &mut Criterion) {
+    let base = PathBuf::from(std::env::var("DBPATH").unwrap_or("/tmp/db-tuning".to_owned()));
+    for (name, tuning) in [
+        ("uniform", DbTuning::uniform()),
+        ("tuned", DbTuning::default()),
+    ]
+    .iter()
+    {
+        let db = open(&base, name, tuning);
+
+        let mut i = 0;
+        c.bench_function(&format!("db_tuning {}, tx_meta get", name), |b| {
+            b.iter(|| {
+                // scattered reads, as in the input lookups of the transactions
+                i = (i + 7919) % NUM_TXS;
+                db.get(COL_TX_META, &txid(i)).expect("read tx meta")
+            })
+        });
+
+        let mut next = NUM_TXS;
+        c.bench_function(&format!("db_tuning {}, block write", name), |b| {
+            b.iter(|| {
+                write_txs(&db, next, BATCH_SIZE);
+                next += BATCH_SIZE;
+            })
+        });
+    }
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
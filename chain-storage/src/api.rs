@@ -1,4 +1,4 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
 use bit_vec::BitVec;
 use parity_scale_codec::{Decode, Encode};
@@ -13,9 +13,10 @@ use chain_core::tx::data::{
 
 use super::buffer::{GetKV, StoreKV};
 use super::{
-    LookupItem, StoredChainState, CHAIN_ID_KEY, COL_APP_HASHS, COL_APP_STATES, COL_EXTRA,
-    COL_NODE_INFO, COL_STAKING_VERSIONS, GENESIS_APP_HASH_KEY, LAST_FETCHED_BLOCK_KEY,
-    LAST_STATE_KEY,
+    LookupItem, StoredChainState, CHAIN_ID_KEY, COL_APP_HASHS, COL_APP_STATES, COL_BLOCK_FILTERS,
+    COL_BLOCK_STATS, COL_COMPACT_FILTERS, COL_DATA_ANCHORS, COL_EXTRA, COL_NODE_INFO,
+    COL_PRUNE_QUEUE, COL_STAKING_VERSIONS, GENESIS_APP_HASH_KEY, LAST_FETCHED_BLOCK_KEY,
+    LAST_STATE_KEY, PRUNED_HEIGHT_KEY,
 };
 
 pub fn get_last_app_state(db: &impl GetKV) -> Option<Vec<u8>> {
@@ -26,6 +27,11 @@ pub fn get_sealed_log(db: &impl GetKV, txid: &TxId) -> Option<Vec<u8>> {
     lookup_item(db, LookupItem::TxSealed, txid)
 }
 
+/// batched `get_sealed_log`, the sealed logs are in the order of the transaction IDs
+pub fn get_sealed_logs(db: &impl GetKV, txids: &[TxId]) -> Vec<Option<Vec<u8>>> {
+    lookup_items(db, LookupItem::TxSealed, txids)
+}
+
 pub fn lookup_item(
     db: &impl GetKV,
     item_type: LookupItem,
@@ -35,6 +41,20 @@ pub fn lookup_item(
     db.get(&(col, txid_or_app_hash.to_vec()))
 }
 
+/// batched `lookup_item`, the items are in the order of the transaction IDs / app hashes
+pub fn lookup_items(
+    db: &impl GetKV,
+    item_type: LookupItem,
+    txids_or_app_hashes: &[H256],
+) -> Vec<Option<Vec<u8>>> {
+    let col = item_type as u32;
+    let keys = txids_or_app_hashes
+        .iter()
+        .map(|id| (col, id.to_vec()))
+        .collect::<Vec<_>>();
+    db.get_many(&keys)
+}
+
 pub fn set_last_fetched_block(db: &mut impl StoreKV, last_fetched_block: u32) {
     db.set(
         (COL_EXTRA, LAST_FETCHED_BLOCK_KEY.to_vec()),
@@ -79,6 +99,38 @@ pub fn get_historical_staking_version(db: &impl GetKV, height: BlockHeight) -> O
     Version::decode(&mut sah.as_slice()).ok()
 }
 
+pub fn get_compact_filter(db: &impl GetKV, height: BlockHeight) -> Option<Vec<u8>> {
+    db.get(&(COL_COMPACT_FILTERS, height.encode()))
+}
+
+pub fn store_compact_filter(db: &mut impl StoreKV, height: BlockHeight, filter: &[u8]) {
+    db.set((COL_COMPACT_FILTERS, height.encode()), filter.to_vec());
+}
+
+pub fn get_block_filter(db: &impl GetKV, height: BlockHeight) -> Option<Vec<u8>> {
+    db.get(&(COL_BLOCK_FILTERS, height.encode()))
+}
+
+pub fn store_block_filter(db: &mut impl StoreKV, height: BlockHeight, filter: &[u8]) {
+    db.set((COL_BLOCK_FILTERS, height.encode()), filter.to_vec());
+}
+
+pub fn get_block_stats(db: &impl GetKV, height: BlockHeight) -> Option<Vec<u8>> {
+    db.get(&(COL_BLOCK_STATS, height.encode()))
+}
+
+pub fn store_block_stats(db: &mut impl StoreKV, height: BlockHeight, stats: &[u8]) {
+    db.set((COL_BLOCK_STATS, height.encode()), stats.to_vec());
+}
+
+pub fn get_data_anchors(db: &impl GetKV, commitment: &H256) -> Option<Vec<u8>> {
+    db.get(&(COL_DATA_ANCHORS, commitment.to_vec()))
+}
+
+pub fn store_data_anchors(db: &mut impl StoreKV, commitment: &H256, anchors: &[u8]) {
+    db.set((COL_DATA_ANCHORS, commitment.to_vec()), anchors.to_vec());
+}
+
 pub fn store_chain_state<T: StoredChainState>(
     db: &mut impl StoreKV,
     genesis_state: &T,
@@ -177,3 +229,127 @@ pub fn lookup_input(db: &impl GetKV, txin: &TxoPointer) -> Option<bool> {
     lookup_item(db, LookupItem::TxMetaSpent, &txin.id)
         .and_then(|v| BitVec::from_bytes(&v).get(txin.index as usize))
 }
+
+/// Same as `lookup_input` for each of the inputs (in the same order), but the spent status
+/// of the outputs of a transaction is read only once (in one batch for all the transactions)
+pub fn lookup_inputs(db: &impl GetKV, inputs: &[TxoPointer]) -> Vec<Option<bool>> {
+    let txids = inputs
+        .iter()
+        .map(|txin| txin.id)
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect::<Vec<_>>();
+    let spent_bits = txids
+        .iter()
+        .zip(lookup_items(db, LookupItem::TxMetaSpent, &txids))
+        .map(|(txid, v)| (*txid, v.map(|v| BitVec::from_bytes(&v))))
+        .collect::<BTreeMap<_, _>>();
+    inputs
+        .iter()
+        .map(|txin| {
+            spent_bits[&txin.id]
+                .as_ref()
+                .and_then(|bits| bits.get(txin.index as usize))
+        })
+        .collect()
+}
+
+fn get_pruned_height(db: &impl GetKV) -> Option<BlockHeight> {
+    let value = db.get(&(COL_EXTRA, PRUNED_HEIGHT_KEY.to_vec()))?;
+    BlockHeight::decode(&mut value.as_slice()).ok()
+}
+
+/// Schedules the bodies and witnesses of the transactions committed at the height for pruning
+pub fn enqueue_prunable_txs(db: &mut impl StoreKV, height: BlockHeight, txids: &[TxId]) {
+    if txids.is_empty() {
+        return;
+    }
+    if get_pruned_height(db).is_none() {
+        // nothing was queued before this height
+        let pruned = BlockHeight::new(height.value().saturating_sub(1));
+        db.set((COL_EXTRA, PRUNED_HEIGHT_KEY.to_vec()), pruned.encode());
+    }
+    let key = (COL_PRUNE_QUEUE, height.encode());
+    let mut queued = db
+        .get(&key)
+        .and_then(|v| Vec::<TxId>::decode(&mut v.as_slice()).ok())
+        .unwrap_or_default();
+    queued.extend_from_slice(txids);
+    db.set(key, queued.encode());
+}
+
+/// Deletes the bodies and witnesses of the transactions queued up to the height
+/// (going through at most `max_heights` queued heights), and returns their number.
+/// UTXO metadata, sealed payloads, merkle trees and app hashes are retained.
+pub fn prune_txs(db: &mut impl StoreKV, until: BlockHeight, max_heights: u64) -> usize {
+    let from = match get_pruned_height(db) {
+        Some(pruned) => pruned.value() + 1,
+        None => return 0,
+    };
+    let to = until
+        .value()
+        .min(from.saturating_add(max_heights).saturating_sub(1));
+    if from > to {
+        return 0;
+    }
+    let mut pruned = 0;
+    for height in from..=to {
+        let key = (COL_PRUNE_QUEUE, BlockHeight::new(height).encode());
+        if let Some(value) = db.get(&key) {
+            let txids = Vec::<TxId>::decode(&mut value.as_slice()).unwrap_or_default();
+            for txid in txids.iter() {
+                db.delete((LookupItem::TxBody as u32, txid.to_vec()));
+                db.delete((LookupItem::TxWitness as u32, txid.to_vec()));
+            }
+            db.delete(key);
+            pruned += txids.len();
+        }
+    }
+    db.set(
+        (COL_EXTRA, PRUNED_HEIGHT_KEY.to_vec()),
+        BlockHeight::new(to).encode(),
+    );
+    pruned
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer::MemStore;
+
+    #[test]
+    fn check_lookup_inputs() {
+        let mut db: MemStore<(u32, Vec<u8>), Vec<u8>> = MemStore::new();
+        create_utxo(&mut db, 2, &[1u8; 32]);
+        create_utxo(&mut db, 1, &[2u8; 32]);
+        spend_utxos(&mut db, &[TxoPointer::new([1u8; 32], 1)]);
+        let inputs = vec![
+            TxoPointer::new([1u8; 32], 1),
+            TxoPointer::new([2u8; 32], 0),
+            // repeated transaction
+            TxoPointer::new([1u8; 32], 0),
+            // missing transaction
+            TxoPointer::new([3u8; 32], 0),
+            // the padding of the spent flags
+            TxoPointer::new([2u8; 32], 7),
+            // out of the padded spent flags
+            TxoPointer::new([2u8; 32], 8),
+        ];
+        assert_eq!(
+            vec![
+                Some(true),
+                Some(false),
+                Some(false),
+                None,
+                Some(false),
+                None
+            ],
+            lookup_inputs(&db, &inputs)
+        );
+        let single = inputs
+            .iter()
+            .map(|txin| lookup_input(&db, txin))
+            .collect::<Vec<_>>();
+        assert_eq!(single, lookup_inputs(&db, &inputs));
+    }
+}
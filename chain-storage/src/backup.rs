@@ -0,0 +1,499 @@
+//! Export / import of consistent snapshots of the node storage (used for backups).
+//!
+//! A snapshot is a directory with one file per DB column
+//! (a sequence of `u32 LE key length | key | u32 LE value length | value` entries)
+//! and a `MANIFEST` file with the block height, app hash and blake3 digests of the column files.
+//!
+//! For the transfer to other nodes, a snapshot directory is served as a sequence of fixed-size
+//! chunks (see `SnapshotChunks`) and reassembled on the receiving side (see `SnapshotRestore`).
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use kvdb::KeyValueDB;
+
+use crate::NUM_COLUMNS;
+use chain_core::common::H256;
+use chain_core::state::tendermint::BlockHeight;
+
+/// name of the manifest file in the snapshot directory
+pub const MANIFEST_FILE: &str = "MANIFEST";
+
+/// Content of one column file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnDigest {
+    pub column: u32,
+    pub entries: u64,
+    /// hex-encoded blake3 digest of the column file
+    pub digest: String,
+}
+
+/// Snapshot integrity manifest
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotManifest {
+    pub height: BlockHeight,
+    pub app_hash: H256,
+    pub columns: Vec<ColumnDigest>,
+}
+
+fn column_file(column: u32) -> String {
+    format!("col-{:02}", column)
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+impl SnapshotManifest {
+    fn write(&self, path: &Path) -> io::Result<()> {
+        let mut file = File::create(path)?;
+        writeln!(file, "height {}", self.height)?;
+        writeln!(file, "app_hash {}", hex::encode(&self.app_hash))?;
+        for column in self.columns.iter() {
+            writeln!(
+                file,
+                "column {} {} {}",
+                column.column, column.entries, column.digest
+            )?;
+        }
+        file.sync_all()
+    }
+
+    /// Reads the manifest of the snapshot directory
+    pub fn read(dir: &Path) -> io::Result<Self> {
+        let file = File::open(dir.join(MANIFEST_FILE))?;
+        let mut height = None;
+        let mut app_hash = None;
+        let mut columns = Vec::new();
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            match parts.as_slice() {
+                ["height", value] => {
+                    height = value.parse::<u64>().ok().map(BlockHeight::new);
+                }
+                ["app_hash", value] => {
+                    app_hash = decode_hash(value);
+                }
+                ["column", column, entries, digest] => {
+                    columns.push(ColumnDigest {
+                        column: column
+                            .parse()
+                            .map_err(|_| invalid_data(format!("invalid column: {}", line)))?,
+                        entries: entries
+                            .parse()
+                            .map_err(|_| invalid_data(format!("invalid column: {}", line)))?,
+                        digest: (*digest).to_owned(),
+                    });
+                }
+                _ => return Err(invalid_data(format!("invalid manifest line: {}", line))),
+            }
+        }
+        match (height, app_hash) {
+            (Some(height), Some(app_hash)) => Ok(SnapshotManifest {
+                height,
+                app_hash,
+                columns,
+            }),
+            _ => Err(invalid_data("incomplete manifest".to_owned())),
+        }
+    }
+}
+
+/// Iterators over the key-values of all DB columns (in the column order)
+pub type ColumnIterators<'a> = Vec<Box<dyn Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'a>>;
+
+/// Opens iterators over all columns of the DB. A RocksDB (or in-memory DB) iterator reads the DB
+/// as it was when the iterator was created, so the snapshot can be written (see `write_snapshot`)
+/// while new blocks are committed, as long as the DB is not written to until this returns.
+pub fn open_columns(db: &dyn KeyValueDB) -> ColumnIterators<'_> {
+    (0..NUM_COLUMNS).map(|column| db.iter(column)).collect()
+}
+
+/// Exports all columns of the DB into the (not yet existing) directory.
+/// The DB must not be written to during the export (see `open_columns` and `write_snapshot`
+/// for exporting on another thread).
+pub fn export_snapshot(
+    db: &dyn KeyValueDB,
+    dir: &Path,
+    height: BlockHeight,
+    app_hash: H256,
+) -> io::Result<SnapshotManifest> {
+    write_snapshot(open_columns(db), dir, height, app_hash)
+}
+
+/// Writes the columns (see `open_columns`) into the (not yet existing) directory.
+/// The snapshot is first written to a temporary directory, so `dir` only appears when complete.
+pub fn write_snapshot(
+    column_iters: ColumnIterators<'_>,
+    dir: &Path,
+    height: BlockHeight,
+    app_hash: H256,
+) -> io::Result<SnapshotManifest> {
+    let tmp_dir = dir.with_extension("tmp");
+    if tmp_dir.exists() {
+        fs::remove_dir_all(&tmp_dir)?;
+    }
+    fs::create_dir_all(&tmp_dir)?;
+
+    let mut columns = Vec::with_capacity(NUM_COLUMNS as usize);
+    for (column, iter) in (0..NUM_COLUMNS).zip(column_iters) {
+        let path = tmp_dir.join(column_file(column));
+        let mut writer = BufWriter::new(File::create(&path)?);
+        let mut entries = 0;
+        for (key, value) in iter {
+            writer.write_all(&(key.len() as u32).to_le_bytes())?;
+            writer.write_all(&key)?;
+            writer.write_all(&(value.len() as u32).to_le_bytes())?;
+            writer.write_all(&value)?;
+            entries += 1;
+        }
+        writer.flush()?;
+        writer.get_ref().sync_all()?;
+        columns.push(ColumnDigest {
+            column,
+            entries,
+            digest: file_digest(&path)?,
+        });
+    }
+
+    let manifest = SnapshotManifest {
+        height,
+        app_hash,
+        columns,
+    };
+    manifest.write(&tmp_dir.join(MANIFEST_FILE))?;
+    fs::rename(&tmp_dir, dir)?;
+    Ok(manifest)
+}
+
+/// Verifies the integrity of the snapshot directory against its manifest
+pub fn verify_snapshot(dir: &Path) -> io::Result<SnapshotManifest> {
+    let manifest = SnapshotManifest::read(dir)?;
+    if manifest.columns.len() != NUM_COLUMNS as usize {
+        return Err(invalid_data(format!(
+            "expected {} columns, found {}",
+            NUM_COLUMNS,
+            manifest.columns.len()
+        )));
+    }
+    for column in manifest.columns.iter() {
+        let digest = file_digest(&dir.join(column_file(column.column)))?;
+        if digest != column.digest {
+            return Err(invalid_data(format!(
+                "digest mismatch of column {}",
+                column.column
+            )));
+        }
+    }
+    Ok(manifest)
+}
+
+/// Verifies the snapshot and imports it into the (empty) DB
+pub fn import_snapshot(db: &dyn KeyValueDB, dir: &Path) -> io::Result<SnapshotManifest> {
+    let manifest = verify_snapshot(dir)?;
+    for column in manifest.columns.iter() {
+        let mut reader = BufReader::new(File::open(dir.join(column_file(column.column)))?);
+        let mut tx = db.transaction();
+        for _ in 0..column.entries {
+            let key = read_chunk(&mut reader)?;
+            let value = read_chunk(&mut reader)?;
+            tx.put_vec(column.column, &key, value);
+        }
+        db.write(tx)?;
+    }
+    Ok(manifest)
+}
+
+/// Version of the chunked snapshot stream format
+pub const SNAPSHOT_FORMAT: u32 = 1;
+/// Default size of snapshot chunks (Tendermint limits chunks to 16 MB)
+pub const DEFAULT_CHUNK_SIZE: u64 = 10 * 1024 * 1024;
+/// name of the reassembled stream in the restore directory
+const STREAM_FILE: &str = "STREAM";
+
+enum StreamPart {
+    Header(Vec<u8>),
+    File(PathBuf, u64),
+}
+
+impl StreamPart {
+    fn len(&self) -> u64 {
+        match self {
+            StreamPart::Header(header) => header.len() as u64,
+            StreamPart::File(_, len) => *len,
+        }
+    }
+}
+
+fn stream_header(name: &str, len: u64) -> Vec<u8> {
+    let mut header = Vec::with_capacity(12 + name.len());
+    header.extend_from_slice(&(name.len() as u32).to_le_bytes());
+    header.extend_from_slice(name.as_bytes());
+    header.extend_from_slice(&len.to_le_bytes());
+    header
+}
+
+/// Snapshot directory served as a stream of
+/// `u32 LE name length | name | u64 LE file length | file content` entries
+/// (the manifest followed by the column files) split into fixed-size chunks
+pub struct SnapshotChunks {
+    manifest: SnapshotManifest,
+    parts: Vec<StreamPart>,
+    total_len: u64,
+    chunk_size: u64,
+}
+
+impl SnapshotChunks {
+    pub fn open(dir: &Path, chunk_size: u64) -> io::Result<Self> {
+        assert!(chunk_size > 0, "chunk size should be positive");
+        let manifest = SnapshotManifest::read(dir)?;
+        let names = std::iter::once(MANIFEST_FILE.to_owned())
+            .chain(manifest.columns.iter().map(|c| column_file(c.column)));
+        let mut parts = Vec::new();
+        for name in names {
+            let path = dir.join(&name);
+            let len = fs::metadata(&path)?.len();
+            parts.push(StreamPart::Header(stream_header(&name, len)));
+            parts.push(StreamPart::File(path, len));
+        }
+        let total_len = parts.iter().map(StreamPart::len).sum();
+        Ok(SnapshotChunks {
+            manifest,
+            parts,
+            total_len,
+            chunk_size,
+        })
+    }
+
+    pub fn manifest(&self) -> &SnapshotManifest {
+        &self.manifest
+    }
+
+    /// Number of chunks
+    pub fn count(&self) -> u32 {
+        ((self.total_len + self.chunk_size - 1) / self.chunk_size) as u32
+    }
+
+    /// Reads the chunk at the index
+    pub fn chunk(&self, index: u32) -> io::Result<Vec<u8>> {
+        if index >= self.count() {
+            return Err(invalid_data(format!("chunk {} out of range", index)));
+        }
+        let start = u64::from(index) * self.chunk_size;
+        let end = (start + self.chunk_size).min(self.total_len);
+        let mut chunk = Vec::with_capacity((end - start) as usize);
+        let mut offset = 0;
+        for part in self.parts.iter() {
+            let (part_start, part_end) = (offset, offset + part.len());
+            offset = part_end;
+            if part_end <= start || part_start >= end {
+                continue;
+            }
+            let from = start.max(part_start) - part_start;
+            let to = end.min(part_end) - part_start;
+            match part {
+                StreamPart::Header(header) => {
+                    chunk.extend_from_slice(&header[from as usize..to as usize])
+                }
+                StreamPart::File(path, _) => {
+                    let mut file = File::open(path)?;
+                    file.seek(SeekFrom::Start(from))?;
+                    file.take(to - from).read_to_end(&mut chunk)?;
+                }
+            }
+        }
+        if chunk.len() as u64 != end - start {
+            return Err(invalid_data("snapshot files changed".to_owned()));
+        }
+        Ok(chunk)
+    }
+
+    /// blake3 hashes of all chunks
+    pub fn chunk_hashes(&self) -> io::Result<Vec<H256>> {
+        (0..self.count())
+            .map(|index| self.chunk(index).map(|chunk| chunk_hash(&chunk)))
+            .collect()
+    }
+}
+
+/// blake3 hash of a snapshot chunk
+pub fn chunk_hash(chunk: &[u8]) -> H256 {
+    *blake3::hash(chunk).as_bytes()
+}
+
+/// Reassembles a snapshot from its chunks (verified against the expected chunk hashes)
+/// and imports it into an empty DB
+pub struct SnapshotRestore {
+    dir: PathBuf,
+    chunk_hashes: Vec<H256>,
+    applied: u32,
+    stream: File,
+}
+
+impl SnapshotRestore {
+    /// `dir` is the (temporary) directory the snapshot is reassembled in
+    pub fn new(dir: &Path, chunk_hashes: Vec<H256>) -> io::Result<Self> {
+        if dir.exists() {
+            fs::remove_dir_all(dir)?;
+        }
+        fs::create_dir_all(dir)?;
+        let stream = File::create(dir.join(STREAM_FILE))?;
+        Ok(SnapshotRestore {
+            dir: dir.to_owned(),
+            chunk_hashes,
+            applied: 0,
+            stream,
+        })
+    }
+
+    /// Index of the next expected chunk
+    pub fn next_chunk(&self) -> u32 {
+        self.applied
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.applied as usize == self.chunk_hashes.len()
+    }
+
+    /// Appends the chunk (chunks must be applied in order)
+    pub fn apply_chunk(&mut self, index: u32, chunk: &[u8]) -> io::Result<()> {
+        if index != self.applied {
+            return Err(invalid_data(format!(
+                "expected chunk {}, got {}",
+                self.applied, index
+            )));
+        }
+        if self.chunk_hashes.get(index as usize) != Some(&chunk_hash(chunk)) {
+            return Err(invalid_data(format!("hash mismatch of chunk {}", index)));
+        }
+        self.stream.write_all(chunk)?;
+        self.applied += 1;
+        Ok(())
+    }
+
+    /// Unpacks the reassembled stream, verifies the snapshot and imports it into the (empty) DB
+    pub fn finish(mut self, db: &dyn KeyValueDB) -> io::Result<SnapshotManifest> {
+        if !self.is_complete() {
+            return Err(invalid_data("missing snapshot chunks".to_owned()));
+        }
+        self.stream.flush()?;
+        let mut reader = BufReader::new(File::open(self.dir.join(STREAM_FILE))?);
+        loop {
+            let mut len = [0u8; 4];
+            match reader.read_exact(&mut len) {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e),
+            }
+            let mut name = vec![0u8; u32::from_le_bytes(len) as usize];
+            reader.read_exact(&mut name)?;
+            let name = String::from_utf8(name)
+                .map_err(|_| invalid_data("invalid file name in snapshot".to_owned()))?;
+            // only the known file names, so that the stream can't write outside of the directory
+            let known = name == MANIFEST_FILE || (0..NUM_COLUMNS).any(|c| column_file(c) == name);
+            if !known {
+                return Err(invalid_data(format!(
+                    "unexpected file in snapshot: {}",
+                    name
+                )));
+            }
+            let mut file_len = [0u8; 8];
+            reader.read_exact(&mut file_len)?;
+            let file_len = u64::from_le_bytes(file_len);
+            let mut file = File::create(self.dir.join(&name))?;
+            let copied = io::copy(&mut (&mut reader).take(file_len), &mut file)?;
+            if copied != file_len {
+                return Err(invalid_data(format!(
+                    "truncated file in snapshot: {}",
+                    name
+                )));
+            }
+        }
+        fs::remove_file(self.dir.join(STREAM_FILE))?;
+        let manifest = import_snapshot(db, &self.dir)?;
+        fs::remove_dir_all(&self.dir)?;
+        Ok(manifest)
+    }
+}
+
+fn read_chunk(reader: &mut impl Read) -> io::Result<Vec<u8>> {
+    let mut len = [0u8; 4];
+    reader.read_exact(&mut len)?;
+    let mut chunk = vec![0u8; u32::from_le_bytes(len) as usize];
+    reader.read_exact(&mut chunk)?;
+    Ok(chunk)
+}
+
+fn file_digest(path: &Path) -> io::Result<String> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut hasher = blake3::Hasher::new();
+    let mut buf = [0u8; 64 * 1024];
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+fn decode_hash(value: &str) -> Option<H256> {
+    let mut result = H256::default();
+    hex::decode_to_slice(value, &mut result).ok()?;
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{COL_EXTRA, COL_NODE_INFO};
+
+    #[test]
+    fn check_snapshot_roundtrip() {
+        let db = kvdb_memorydb::create(NUM_COLUMNS);
+        let mut tx = db.transaction();
+        tx.put(COL_NODE_INFO, b"key", b"value");
+        tx.put(COL_EXTRA, b"other key", b"other value");
+        db.write(tx).unwrap();
+
+        let dir =
+            std::env::temp_dir().join(format!("chain-storage-snapshot-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let manifest = export_snapshot(&db, &dir, BlockHeight::new(5), [1u8; 32]).unwrap();
+        assert_eq!(manifest, verify_snapshot(&dir).unwrap());
+
+        let restored = kvdb_memorydb::create(NUM_COLUMNS);
+        import_snapshot(&restored, &dir).unwrap();
+        assert_eq!(
+            Some(b"other value".to_vec()),
+            restored.get(COL_EXTRA, b"other key").unwrap()
+        );
+
+        // transfer in chunks
+        let chunks = SnapshotChunks::open(&dir, 16).unwrap();
+        assert!(chunks.count() > 1);
+        let restore_dir = dir.with_extension("restore");
+        let mut restore =
+            SnapshotRestore::new(&restore_dir, chunks.chunk_hashes().unwrap()).unwrap();
+        assert!(restore.apply_chunk(1, &chunks.chunk(1).unwrap()).is_err());
+        assert!(restore.apply_chunk(0, b"invalid").is_err());
+        for index in 0..chunks.count() {
+            restore
+                .apply_chunk(index, &chunks.chunk(index).unwrap())
+                .unwrap();
+        }
+        let restored = kvdb_memorydb::create(NUM_COLUMNS);
+        assert_eq!(manifest, restore.finish(&restored).unwrap());
+        assert_eq!(
+            Some(b"value".to_vec()),
+            restored.get(COL_NODE_INFO, b"key").unwrap()
+        );
+        assert!(!restore_dir.exists());
+
+        fs::write(dir.join(column_file(COL_EXTRA)), b"corrupted").unwrap();
+        assert!(verify_snapshot(&dir).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -40,12 +40,17 @@ use kvdb::KeyValueDB;
 
 use chain_core::state::account::{StakedState, StakedStateAddress};
 
+use crate::encryption::encrypt_value;
 use crate::Storage;
 
 pub trait Get {
     type Key;
     type Value;
     fn get(&self, key: &Self::Key) -> Option<Self::Value>;
+    /// batched `get`, the values are in the order of the keys
+    fn get_many(&self, keys: &[Self::Key]) -> Vec<Option<Self::Value>> {
+        keys.iter().map(|key| self.get(key)).collect()
+    }
 }
 pub trait SimpleStore: Get {
     fn set(&mut self, key: Self::Key, value: Self::Value);
@@ -69,6 +74,36 @@ impl<S: KeyValueDB> Get for S {
     }
 }
 
+/// `get_many` of the buffered storages: the keys not found in the buffer are read
+/// from the underlying storage in one batch
+fn get_many_buffered<S, F>(storage: &S, keys: &[S::Key], buffered: F) -> Vec<Option<S::Value>>
+where
+    S: Get,
+    S::Key: Clone,
+    F: Fn(&S::Key) -> Option<Option<S::Value>>,
+{
+    let mut values = Vec::with_capacity(keys.len());
+    let mut missed_indices = vec![];
+    let mut missed_keys = vec![];
+    for (i, key) in keys.iter().enumerate() {
+        match buffered(key) {
+            Some(value) => values.push(value),
+            None => {
+                values.push(None);
+                missed_indices.push(i);
+                missed_keys.push(key.clone());
+            }
+        }
+    }
+    for (i, value) in missed_indices
+        .into_iter()
+        .zip(storage.get_many(&missed_keys))
+    {
+        values[i] = value;
+    }
+    values
+}
+
 /// Specialized for staking
 pub trait GetStaking: Get<Key = StakedStateAddress, Value = StakedState> {
     fn get_or_default(&self, addr: &Self::Key) -> Self::Value {
@@ -102,7 +137,7 @@ impl<'a, S, H> Get for BufferGetter<'a, S, H>
 where
     S: Get,
     H: BuildHasher,
-    S::Key: Hash + Eq,
+    S::Key: Hash + Eq + Clone,
     S::Value: Clone,
 {
     type Key = S::Key;
@@ -113,6 +148,11 @@ where
             .cloned()
             .or_else(|| self.storage.get(key))
     }
+    fn get_many(&self, keys: &[Self::Key]) -> Vec<Option<Self::Value>> {
+        get_many_buffered(&self.storage, keys, |key| {
+            self.buffer.get(key).cloned().map(Some)
+        })
+    }
 }
 
 /// Generic buffered simple storage implementation
@@ -131,7 +171,7 @@ impl<'a, S, H> Get for BufferSimpleStore<'a, S, H>
 where
     S: Get,
     H: BuildHasher,
-    S::Key: Hash + Eq,
+    S::Key: Hash + Eq + Clone,
     S::Value: Clone,
 {
     type Key = S::Key;
@@ -142,13 +182,18 @@ where
             .cloned()
             .or_else(|| self.storage.get(key))
     }
+    fn get_many(&self, keys: &[Self::Key]) -> Vec<Option<Self::Value>> {
+        get_many_buffered(&self.storage, keys, |key| {
+            self.buffer.get(key).cloned().map(Some)
+        })
+    }
 }
 
 impl<'a, S, H> SimpleStore for BufferSimpleStore<'a, S, H>
 where
     S: Get,
     H: BuildHasher,
-    S::Key: Hash + Eq,
+    S::Key: Hash + Eq + Clone,
     S::Value: Clone,
 {
     fn set(&mut self, key: Self::Key, value: Self::Value) {
@@ -173,7 +218,7 @@ impl<'a, S, H> Get for BufferStore<'a, S, H>
 where
     S: Get,
     H: BuildHasher,
-    S::Key: Hash + Eq,
+    S::Key: Hash + Eq + Clone,
     S::Value: Clone,
 {
     type Key = S::Key;
@@ -184,13 +229,16 @@ where
             .cloned()
             .unwrap_or_else(|| self.storage.get(key))
     }
+    fn get_many(&self, keys: &[Self::Key]) -> Vec<Option<Self::Value>> {
+        get_many_buffered(self.storage, keys, |key| self.buffer.get(key).cloned())
+    }
 }
 
 impl<'a, S, H> SimpleStore for BufferStore<'a, S, H>
 where
     S: Get,
     H: BuildHasher,
-    S::Key: Hash + Eq,
+    S::Key: Hash + Eq + Clone,
     S::Value: Clone,
 {
     fn set(&mut self, key: Self::Key, value: Self::Value) {
@@ -202,7 +250,7 @@ impl<'a, S, H> Store for BufferStore<'a, S, H>
 where
     S: Get,
     H: BuildHasher,
-    S::Key: Hash + Eq,
+    S::Key: Hash + Eq + Clone,
     S::Value: Clone,
 {
     fn delete(&mut self, key: Self::Key) {
@@ -227,7 +275,7 @@ impl<'a, S, H> Get for BufferStoreGetter<'a, S, H>
 where
     S: Get,
     H: BuildHasher,
-    S::Key: Hash + Eq,
+    S::Key: Hash + Eq + Clone,
     S::Value: Clone,
 {
     type Key = S::Key;
@@ -238,6 +286,9 @@ where
             .cloned()
             .unwrap_or_else(|| self.storage.get(key))
     }
+    fn get_many(&self, keys: &[Self::Key]) -> Vec<Option<Self::Value>> {
+        get_many_buffered(self.storage, keys, |key| self.buffer.get(key).cloned())
+    }
 }
 
 /// Dummy storage implemented with a HashMap in memory.
@@ -308,10 +359,15 @@ pub fn flush_kvdb<S: KeyValueDB + ?Sized>(storage: &S, buffer: KVBuffer) -> std:
 
 /// Flush buffer to storage
 pub fn flush_storage(storage: &mut Storage, buffer: KVBuffer) -> std::io::Result<()> {
+    let encryption = storage.encryption.clone();
     let tx = storage.get_or_create_tx();
     for ((col, key), value) in buffer.into_iter() {
         if let Some(val) = &value {
-            tx.put(col, &key, val);
+            tx.put(
+                col,
+                &key,
+                &encrypt_value(encryption.as_deref(), col, &key, val),
+            );
         } else {
             tx.delete(col, &key);
         }
@@ -439,4 +495,40 @@ mod tests {
         // deletion also happens in buffer.
         assert!(buffer.get(&key1).unwrap().is_none());
     }
+
+    #[test]
+    fn check_get_many() {
+        let mut app = App::new_memory();
+        let key = |name: &str| (0, name.as_bytes().to_owned());
+        let value = |name: &str| name.as_bytes().to_owned();
+
+        app.kv_store().set(key("key1"), value("value1"));
+        app.kv_store().set(key("key2"), value("value2"));
+        app.commit();
+        app.kv_store().delete(key("key1"));
+        app.kv_store().set(key("key3"), value("value3"));
+
+        // the buffered writes take precedence, the values are in the order of the keys
+        let keys = vec![
+            key("key3"),
+            key("key1"),
+            key("key2"),
+            key("key4"),
+            key("key2"),
+        ];
+        assert_eq!(
+            app.kv_store().get_many(&keys),
+            vec![
+                Some(value("value3")),
+                None,
+                Some(value("value2")),
+                None,
+                Some(value("value2"))
+            ]
+        );
+        assert_eq!(
+            app.tmp_kv_store().get_many(&keys[..2]),
+            vec![None, Some(value("value1"))]
+        );
+    }
 }
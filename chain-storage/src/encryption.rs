@@ -0,0 +1,416 @@
+//! Optional encryption at rest of the columns with sealed transaction payloads and
+//! wallet-relevant transaction metadata (on top of the SGX sealing of the payloads),
+//! for operators with disk-compliance requirements.
+//!
+//! Values are encrypted with a deterministic authenticated scheme built from blake3
+//! (synthetic IV: the tag is a keyed hash of the column, the DB key and the plaintext,
+//! and the keystream is the keyed blake3 XOF of the tag), so no random source is needed.
+//! The column and the DB key are bound to the value, so encrypted values can not be moved
+//! to other keys.
+//!
+//! An encrypted value is stored as `MAGIC | u32 LE key id | 32-byte tag | ciphertext`.
+//! Values without the magic prefix are read as plaintext (written before the encryption was
+//! enabled), so existing databases can be migrated in place (see `migrate_columns`).
+//!
+//! Key rotation: append the new key to the key file (the last key is used for writing,
+//! all the keys are used for reading), run the migration and then remove the old key.
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use kvdb::KeyValueDB;
+
+use crate::{COL_ENCLAVE_TX, COL_TX_META};
+
+/// Columns which are encrypted when the encryption is enabled
+pub const ENCRYPTED_COLUMNS: [u32; 2] = [COL_ENCLAVE_TX, COL_TX_META];
+
+const MAGIC: &[u8; 8] = b"CROENC\x00\x01";
+const TAG_LEN: usize = 32;
+const HEADER_LEN: usize = MAGIC.len() + 4 + TAG_LEN;
+const KEYSTREAM_KEY_CONTEXT: &str = "chain-storage 2021-03 encryption at rest keystream";
+const TAG_KEY_CONTEXT: &str = "chain-storage 2021-03 encryption at rest tag";
+/// number of values rewritten in one DB transaction during the migration
+const MIGRATION_BATCH_SIZE: usize = 1000;
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn derive_key(context: &str, key: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = blake3::Hasher::new_derive_key(context);
+    hasher.update(key);
+    *hasher.finalize().as_bytes()
+}
+
+/// Sub-keys derived from one encryption key
+#[derive(Clone)]
+struct ColumnKey {
+    keystream_key: [u8; 32],
+    tag_key: [u8; 32],
+}
+
+impl ColumnKey {
+    fn new(key: &[u8; 32]) -> Self {
+        ColumnKey {
+            keystream_key: derive_key(KEYSTREAM_KEY_CONTEXT, key),
+            tag_key: derive_key(TAG_KEY_CONTEXT, key),
+        }
+    }
+
+    fn tag(&self, col: u32, key: &[u8], plaintext: &[u8]) -> [u8; TAG_LEN] {
+        let mut hasher = blake3::Hasher::new_keyed(&self.tag_key);
+        hasher.update(&col.to_le_bytes());
+        hasher.update(&(key.len() as u64).to_le_bytes());
+        hasher.update(key);
+        hasher.update(plaintext);
+        *hasher.finalize().as_bytes()
+    }
+
+    fn apply_keystream(&self, tag: &[u8], data: &mut [u8]) {
+        let mut hasher = blake3::Hasher::new_keyed(&self.keystream_key);
+        hasher.update(tag);
+        let mut keystream = vec![0u8; data.len()];
+        hasher.finalize_xof().fill(&mut keystream);
+        for (byte, k) in data.iter_mut().zip(keystream) {
+            *byte ^= k;
+        }
+    }
+}
+
+/// Encryption keys by their IDs; the last added key is the active one (used for writing)
+#[derive(Clone, Default)]
+pub struct KeyRing {
+    keys: BTreeMap<u32, ColumnKey>,
+    active: Option<u32>,
+}
+
+impl fmt::Debug for KeyRing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyRing")
+            .field("key_ids", &self.keys.keys().collect::<Vec<_>>())
+            .field("active", &self.active)
+            .finish()
+    }
+}
+
+impl KeyRing {
+    /// Adds the key and makes it the active one
+    pub fn add_key(&mut self, id: u32, key: [u8; 32]) -> io::Result<()> {
+        if self.keys.contains_key(&id) {
+            return Err(invalid_data(format!("duplicate encryption key id {}", id)));
+        }
+        self.keys.insert(id, ColumnKey::new(&key));
+        self.active = Some(id);
+        Ok(())
+    }
+
+    /// Parses the keys: one `<key id> <64 hex digits>` per line
+    /// (empty lines and lines starting with `#` are ignored)
+    pub fn parse(content: &str) -> io::Result<Self> {
+        let mut keys = KeyRing::default();
+        for line in content.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            match parts.as_slice() {
+                [id, key] => {
+                    let id = id
+                        .parse::<u32>()
+                        .map_err(|_| invalid_data(format!("invalid encryption key id: {}", id)))?;
+                    let key = decode_key(key)
+                        .ok_or_else(|| invalid_data(format!("invalid encryption key {}", id)))?;
+                    keys.add_key(id, key)?;
+                }
+                _ => return Err(invalid_data("invalid encryption key line".to_owned())),
+            }
+        }
+        Ok(keys)
+    }
+
+    /// Reads the keys from the key file (see `parse`)
+    pub fn from_file(path: &Path) -> io::Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    /// ID of the key used for writing
+    pub fn active_key_id(&self) -> Option<u32> {
+        self.active
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+}
+
+fn decode_key(value: &str) -> Option<[u8; 32]> {
+    if value.len() != 64 {
+        return None;
+    }
+    let mut result = [0u8; 32];
+    for (i, byte) in result.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(value.get(2 * i..2 * i + 2)?, 16).ok()?;
+    }
+    Some(result)
+}
+
+/// ID of the key the value is encrypted with (None for plaintext values)
+pub fn encryption_key_id(value: &[u8]) -> Option<u32> {
+    if value.len() < HEADER_LEN || !value.starts_with(MAGIC) {
+        return None;
+    }
+    let mut id = [0u8; 4];
+    id.copy_from_slice(&value[MAGIC.len()..MAGIC.len() + 4]);
+    Some(u32::from_le_bytes(id))
+}
+
+/// Transparent encryption of the values in `ENCRYPTED_COLUMNS`
+#[derive(Debug, Clone)]
+pub struct StorageEncryption {
+    keys: KeyRing,
+}
+
+impl StorageEncryption {
+    /// the key ring needs at least one key
+    pub fn new(keys: KeyRing) -> io::Result<Self> {
+        if keys.is_empty() {
+            return Err(invalid_data("no storage encryption key".to_owned()));
+        }
+        Ok(StorageEncryption { keys })
+    }
+
+    pub fn keys(&self) -> &KeyRing {
+        &self.keys
+    }
+
+    pub fn is_encrypted_column(col: u32) -> bool {
+        ENCRYPTED_COLUMNS.contains(&col)
+    }
+
+    /// Encrypts the value with the active key (values of other columns are returned as they are)
+    pub fn encrypt(&self, col: u32, key: &[u8], value: &[u8]) -> Vec<u8> {
+        if !Self::is_encrypted_column(col) {
+            return value.to_vec();
+        }
+        let id = self.keys.active.expect("key ring is not empty");
+        let column_key = &self.keys.keys[&id];
+        let tag = column_key.tag(col, key, value);
+        let mut result = Vec::with_capacity(HEADER_LEN + value.len());
+        result.extend_from_slice(MAGIC);
+        result.extend_from_slice(&id.to_le_bytes());
+        result.extend_from_slice(&tag);
+        result.extend_from_slice(value);
+        column_key.apply_keystream(&tag, &mut result[HEADER_LEN..]);
+        result
+    }
+
+    /// Decrypts the value (plaintext values are returned as they are)
+    pub fn decrypt(&self, col: u32, key: &[u8], value: Vec<u8>) -> io::Result<Vec<u8>> {
+        decrypt_value(Some(self), col, key, value)
+    }
+}
+
+/// Encrypts the value if it's stored in an encrypted column and the encryption is enabled
+pub fn encrypt_value<'a>(
+    encryption: Option<&StorageEncryption>,
+    col: u32,
+    key: &[u8],
+    value: &'a [u8],
+) -> Cow<'a, [u8]> {
+    match encryption {
+        Some(encryption) if StorageEncryption::is_encrypted_column(col) => {
+            Cow::Owned(encryption.encrypt(col, key, value))
+        }
+        _ => Cow::Borrowed(value),
+    }
+}
+
+/// Decrypts the value read from the DB; fails if the value is encrypted and the key is not known
+/// (or the encryption is not configured)
+pub fn decrypt_value(
+    encryption: Option<&StorageEncryption>,
+    col: u32,
+    key: &[u8],
+    value: Vec<u8>,
+) -> io::Result<Vec<u8>> {
+    if !StorageEncryption::is_encrypted_column(col) {
+        return Ok(value);
+    }
+    let id = match encryption_key_id(&value) {
+        Some(id) => id,
+        None => return Ok(value),
+    };
+    let column_key = encryption
+        .ok_or_else(|| invalid_data("storage is encrypted, but no key is configured".to_owned()))?
+        .keys
+        .keys
+        .get(&id)
+        .ok_or_else(|| invalid_data(format!("unknown storage encryption key id {}", id)))?;
+    let tag = &value[MAGIC.len() + 4..HEADER_LEN];
+    let mut plaintext = value[HEADER_LEN..].to_vec();
+    column_key.apply_keystream(tag, &mut plaintext);
+    let expected = column_key.tag(col, key, &plaintext);
+    // constant-time comparison
+    let diff = expected
+        .iter()
+        .zip(tag)
+        .fold(0u8, |acc, (a, b)| acc | (a ^ b));
+    if diff != 0 {
+        return Err(invalid_data(format!(
+            "storage value authentication failed (column {})",
+            col
+        )));
+    }
+    Ok(plaintext)
+}
+
+/// What the migration does with the values of the encrypted columns
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MigrationMode {
+    /// (re-)encrypt plaintext values and values encrypted with other than the active key
+    Encrypt,
+    /// decrypt all the values (before disabling the encryption)
+    Decrypt,
+}
+
+/// Number of rewritten values
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MigrationStats {
+    pub rewritten: u64,
+    pub unchanged: u64,
+}
+
+/// Rewrites the values of the encrypted columns in place.
+/// The key ring has to contain all the keys the values are currently encrypted with.
+/// The migration can be interrupted and restarted (it skips values which are already migrated).
+pub fn migrate_columns(
+    db: &dyn KeyValueDB,
+    encryption: &StorageEncryption,
+    mode: MigrationMode,
+) -> io::Result<MigrationStats> {
+    let mut stats = MigrationStats::default();
+    for col in ENCRYPTED_COLUMNS.iter().copied() {
+        let mut tx = db.transaction();
+        let mut batch = 0;
+        for (key, value) in db.iter(col) {
+            let current_id = encryption_key_id(&value);
+            let up_to_date = match mode {
+                MigrationMode::Encrypt => current_id == encryption.keys.active,
+                MigrationMode::Decrypt => current_id.is_none(),
+            };
+            if up_to_date {
+                stats.unchanged += 1;
+                continue;
+            }
+            let plaintext = encryption.decrypt(col, &key, value.to_vec())?;
+            match mode {
+                MigrationMode::Encrypt => {
+                    tx.put(col, &key, &encryption.encrypt(col, &key, &plaintext))
+                }
+                MigrationMode::Decrypt => tx.put(col, &key, &plaintext),
+            }
+            stats.rewritten += 1;
+            batch += 1;
+            if batch == MIGRATION_BATCH_SIZE {
+                db.write(std::mem::replace(&mut tx, db.transaction()))?;
+                batch = 0;
+            }
+        }
+        db.write(tx)?;
+    }
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{COL_EXTRA, NUM_COLUMNS};
+
+    const OLD_KEY: &str = "1 0101010101010101010101010101010101010101010101010101010101010101";
+    const NEW_KEY: &str = "2 0202020202020202020202020202020202020202020202020202020202020202";
+
+    fn key_file() -> String {
+        format!("# old key\n{}\n\n{}\n", OLD_KEY, NEW_KEY)
+    }
+
+    #[test]
+    fn check_encryption_roundtrip() {
+        let encryption = StorageEncryption::new(KeyRing::parse(&key_file()).unwrap()).unwrap();
+        assert_eq!(Some(2), encryption.keys().active_key_id());
+        assert!(StorageEncryption::new(KeyRing::parse("# no keys").unwrap()).is_err());
+        assert!(KeyRing::parse("1 0101").is_err());
+
+        let value = b"sealed payload".to_vec();
+        assert_eq!(value, encryption.encrypt(COL_EXTRA, b"key", &value));
+        let encrypted = encryption.encrypt(COL_ENCLAVE_TX, b"key", &value);
+        assert_eq!(Some(2), encryption_key_id(&encrypted));
+        assert!(!encrypted.ends_with(&value));
+        assert_eq!(
+            value,
+            encryption
+                .decrypt(COL_ENCLAVE_TX, b"key", encrypted.clone())
+                .unwrap()
+        );
+        // plaintext values are read as they are
+        assert_eq!(
+            value,
+            encryption
+                .decrypt(COL_ENCLAVE_TX, b"key", value.clone())
+                .unwrap()
+        );
+        // bound to the column and the key
+        assert!(encryption
+            .decrypt(COL_TX_META, b"key", encrypted.clone())
+            .is_err());
+        assert!(encryption
+            .decrypt(COL_ENCLAVE_TX, b"other key", encrypted.clone())
+            .is_err());
+        let mut tampered = encrypted.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(encryption
+            .decrypt(COL_ENCLAVE_TX, b"key", tampered)
+            .is_err());
+        assert!(decrypt_value(None, COL_ENCLAVE_TX, b"key", encrypted).is_err());
+    }
+
+    #[test]
+    fn check_key_rotation_migration() {
+        let db = kvdb_memorydb::create(NUM_COLUMNS);
+        let old = StorageEncryption::new(KeyRing::parse(OLD_KEY).unwrap()).unwrap();
+        assert_eq!(Some(1), old.keys().active_key_id());
+        let mut tx = db.transaction();
+        tx.put(COL_ENCLAVE_TX, b"plain", b"plaintext value");
+        tx.put(
+            COL_ENCLAVE_TX,
+            b"old",
+            &old.encrypt(COL_ENCLAVE_TX, b"old", b"old value"),
+        );
+        tx.put(COL_EXTRA, b"other", b"not encrypted");
+        db.write(tx).unwrap();
+
+        let new = StorageEncryption::new(KeyRing::parse(&key_file()).unwrap()).unwrap();
+        let stats = migrate_columns(&db, &new, MigrationMode::Encrypt).unwrap();
+        assert_eq!(2, stats.rewritten);
+        let stats = migrate_columns(&db, &new, MigrationMode::Encrypt).unwrap();
+        assert_eq!(0, stats.rewritten);
+        for (key, value) in db.iter(COL_ENCLAVE_TX) {
+            assert_eq!(Some(2), encryption_key_id(&value));
+            assert!(new.decrypt(COL_ENCLAVE_TX, &key, value.to_vec()).is_ok());
+        }
+        assert_eq!(
+            Some(b"not encrypted".to_vec()),
+            db.get(COL_EXTRA, b"other").unwrap()
+        );
+
+        let stats = migrate_columns(&db, &new, MigrationMode::Decrypt).unwrap();
+        assert_eq!(2, stats.rewritten);
+        assert_eq!(
+            Some(b"old value".to_vec()),
+            db.get(COL_ENCLAVE_TX, b"old").unwrap()
+        );
+    }
+}
@@ -0,0 +1,120 @@
+//! Fault injection for chaos testing (only with the `fault-injection` feature):
+//! a `FaultInjector` is consulted at injection points (e.g. the storage writes of `Storage`
+//! or the enclave bridge calls of chain-abci) and may add a random latency, return
+//! a transient I/O error or crash (panic) at a given point.
+//! The faults are pseudo-random, but reproducible from the configured seed.
+use std::io;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+/// What faults are injected
+#[derive(Debug, Clone, Default)]
+pub struct FaultConfig {
+    /// seed of the pseudo-random fault generator
+    pub seed: u64,
+    /// maximum latency added at each injection point (none if zero)
+    pub max_latency: Duration,
+    /// probability (0.0 - 1.0) of a transient error at each injection point
+    pub error_rate: f64,
+    /// the injection point (counted from 0) where the process crashes
+    pub crash_at: Option<u64>,
+}
+
+/// Fault generator shared by the injection points
+#[derive(Debug)]
+pub struct FaultInjector {
+    config: FaultConfig,
+    /// (generator state, number of reached injection points)
+    state: Mutex<(u64, u64)>,
+}
+
+impl FaultInjector {
+    pub fn new(config: FaultConfig) -> Self {
+        // xorshift can't start from zero
+        let seed = config.seed.max(1);
+        FaultInjector {
+            config,
+            state: Mutex::new((seed, 0)),
+        }
+    }
+
+    /// Number of the injection points reached so far
+    pub fn points(&self) -> u64 {
+        self.state.lock().expect("fault injector lock").1
+    }
+
+    /// Called at an injection point: sleeps for a random latency, then crashes if it's
+    /// the configured crash point or returns a transient error with the configured probability
+    pub fn inject(&self, point: &str) -> io::Result<()> {
+        let (index, latency, fails) = {
+            let mut state = self.state.lock().expect("fault injector lock");
+            let index = state.1;
+            state.1 += 1;
+            let latency =
+                next_random(&mut state.0) % (self.config.max_latency.as_micros() as u64 + 1);
+            let fails =
+                (next_random(&mut state.0) as f64 / u64::MAX as f64) < self.config.error_rate;
+            (index, latency, fails)
+        };
+        if latency > 0 {
+            thread::sleep(Duration::from_micros(latency));
+        }
+        if self.config.crash_at == Some(index) {
+            panic!("injected crash at {} (injection point {})", point, index);
+        }
+        if fails {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("injected error at {} (injection point {})", point, index),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// xorshift64* step
+fn next_random(state: &mut u64) -> u64 {
+    *state ^= *state >> 12;
+    *state ^= *state << 25;
+    *state ^= *state >> 27;
+    state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outcomes(config: FaultConfig, points: usize) -> Vec<bool> {
+        let faults = FaultInjector::new(config);
+        (0..points).map(|_| faults.inject("test").is_ok()).collect()
+    }
+
+    #[test]
+    fn check_faults_are_reproducible() {
+        let config = FaultConfig {
+            seed: 42,
+            error_rate: 0.5,
+            ..Default::default()
+        };
+        let first = outcomes(config.clone(), 100);
+        assert_eq!(first, outcomes(config, 100));
+        let failures = first.iter().filter(|ok| !**ok).count();
+        assert!(failures > 20 && failures < 80);
+
+        assert!(outcomes(Default::default(), 100).iter().all(|ok| *ok));
+    }
+
+    #[test]
+    fn check_crash_point() {
+        let faults = FaultInjector::new(FaultConfig {
+            crash_at: Some(2),
+            ..Default::default()
+        });
+        assert!(faults.inject("test").is_ok());
+        assert!(faults.inject("test").is_ok());
+        let crash = std::panic::catch_unwind(|| faults.inject("test"));
+        assert!(crash.is_err());
+        assert_eq!(3, faults.points());
+    }
+}
@@ -1,16 +1,28 @@
 mod api;
+pub mod backup;
 pub mod buffer;
+pub mod encryption;
+#[cfg(feature = "fault-injection")]
+pub mod fault;
 pub mod jellyfish;
+pub mod metrics;
+pub mod tuning;
+pub mod utxo_mmr;
 
 use crate::buffer::{flush_storage, BufferStore, Get, KVBuffer};
+use crate::encryption::{decrypt_value, StorageEncryption};
 use crate::jellyfish::{put_stakings, Version};
+use crate::metrics::StorageMetrics;
+use crate::tuning::DbTuning;
 use chain_core::common::H256;
 use chain_core::state::account::StakedState;
 use chain_core::state::tendermint::BlockHeight;
 use chain_core::tx::data::TxId;
 use kvdb::{DBTransaction, KeyValueDB};
+use std::collections::{BTreeMap, BTreeSet};
 use std::path::Path;
 use std::sync::Arc;
+use std::time::Instant;
 
 pub use api::*;
 
@@ -39,24 +51,39 @@ pub const COL_TRIE_NODE: u32 = 9;
 pub const COL_TRIE_STALED: u32 = 10;
 /// Column to store block height -> staking version
 pub const COL_STAKING_VERSIONS: u32 = 11;
+/// Column to store block height -> compact block filter
+pub const COL_COMPACT_FILTERS: u32 = 12;
+/// Column to store block height -> block production statistics (proposer, fees, rewards)
+pub const COL_BLOCK_STATS: u32 = 13;
+/// Column to store block height -> IDs of the transactions committed at that height whose bodies and witnesses will be pruned
+pub const COL_PRUNE_QUEUE: u32 = 14;
+/// Column to store the merkle mountain range of the transaction outputs (nodes, leaf indices of the outputs and the leaf count)
+pub const COL_UTXO_MMR: u32 = 15;
+/// Column to store data anchor commitment -> anchorings of the commitment
+/// (block heights and anchoring transactions)
+pub const COL_DATA_ANCHORS: u32 = 16;
+/// Column to store block height -> view key bloom filter of the block (produced by the enclave in EndBlock)
+pub const COL_BLOCK_FILTERS: u32 = 17;
 /// Number of columns in DB
-pub const NUM_COLUMNS: u32 = 12;
+pub const NUM_COLUMNS: u32 = 18;
 
 pub const CHAIN_ID_KEY: &[u8] = b"chain_id";
 pub const GENESIS_APP_HASH_KEY: &[u8] = b"genesis_app_hash";
 pub const LAST_STATE_KEY: &[u8] = b"last_state";
 pub const LAST_FETCHED_BLOCK_KEY: &[u8] = b"last_fetched_block";
+pub const PRUNED_HEIGHT_KEY: &[u8] = b"pruned_height";
+pub const CONSENSUS_PARAMS_KEY: &[u8] = b"init_chain_consensus_params";
 
 pub enum StorageType {
     Node,
     AccountTrie,
 }
 
-/// Storage configuration -- currently only the path to RocksDB directory
-/// TODO: other options? e.g. HDD vs SDD?
+/// Storage configuration -- the path to RocksDB directory and the database tuning
 pub struct StorageConfig<'a> {
     base_dbs_path: &'a str,
     purpose: StorageType,
+    tuning: DbTuning,
 }
 
 impl<'a> StorageConfig<'a> {
@@ -64,9 +91,15 @@ impl<'a> StorageConfig<'a> {
         StorageConfig {
             base_dbs_path,
             purpose,
+            tuning: DbTuning::default(),
         }
     }
 
+    pub fn with_tuning(mut self, tuning: DbTuning) -> Self {
+        self.tuning = tuning;
+        self
+    }
+
     pub fn db_path(&self) -> String {
         match self.purpose {
             StorageType::Node => Path::new(self.base_dbs_path)
@@ -89,6 +122,61 @@ pub struct Storage {
     db: Arc<dyn KeyValueDB>,
     /// tx to be committed
     current_tx: Option<DBTransaction>,
+    /// access metrics (shared with the read-only handles)
+    metrics: Arc<StorageMetrics>,
+    /// optional encryption at rest of some columns (shared with the read-only handles)
+    encryption: Option<Arc<StorageEncryption>>,
+    /// optional faults injected at the writes (chaos testing)
+    #[cfg(feature = "fault-injection")]
+    faults: Option<Arc<fault::FaultInjector>>,
+}
+
+fn get_decrypted(
+    db: &dyn KeyValueDB,
+    encryption: Option<&StorageEncryption>,
+    col: u32,
+    key: &[u8],
+) -> Option<Vec<u8>> {
+    db.get(col, key).expect("kv storage io error").map(|value| {
+        decrypt_value(encryption, col, key, value).expect("kv storage decryption error")
+    })
+}
+
+/// batched reads: `kvdb` has no multi-get, so the distinct keys are read in the key order
+/// (which is the order of RocksDB's sorted storage) and each key is read only once
+fn get_many_decrypted(
+    db: &dyn KeyValueDB,
+    encryption: Option<&StorageEncryption>,
+    metrics: &StorageMetrics,
+    keys: &[(u32, Vec<u8>)],
+) -> Vec<Option<Vec<u8>>> {
+    let distinct_keys = keys.iter().collect::<BTreeSet<_>>();
+    let values = distinct_keys
+        .into_iter()
+        .map(|(col, key)| {
+            let start = Instant::now();
+            let value = get_decrypted(db, encryption, *col, key);
+            metrics.record_get(*col, value.is_some(), start.elapsed());
+            ((*col, key.as_slice()), value)
+        })
+        .collect::<BTreeMap<_, _>>();
+    keys.iter()
+        .map(|(col, key)| values[&(*col, key.as_slice())].clone())
+        .collect()
+}
+
+/// prefix scan of the column (the values are decrypted)
+fn iter_decrypted_with_prefix<'a>(
+    db: &'a dyn KeyValueDB,
+    encryption: Option<&'a StorageEncryption>,
+    col: u32,
+    prefix: &'a [u8],
+) -> impl Iterator<Item = (Box<[u8]>, Vec<u8>)> + 'a {
+    db.iter_with_prefix(col, prefix).map(move |(key, value)| {
+        let value = decrypt_value(encryption, col, &key, value.into_vec())
+            .expect("kv storage decryption error");
+        (key, value)
+    })
 }
 
 impl Get for Storage {
@@ -96,13 +184,22 @@ impl Get for Storage {
     type Value = Vec<u8>;
     fn get(&self, key: &Self::Key) -> Option<Self::Value> {
         let (col, key) = key;
-        self.db.get(*col, &key).expect("kv storage io error")
+        let start = Instant::now();
+        let value = get_decrypted(&*self.db, self.encryption.as_deref(), *col, key);
+        self.metrics
+            .record_get(*col, value.is_some(), start.elapsed());
+        value
+    }
+    fn get_many(&self, keys: &[Self::Key]) -> Vec<Option<Self::Value>> {
+        get_many_decrypted(&*self.db, self.encryption.as_deref(), &self.metrics, keys)
     }
 }
 
 /// committed storage only
 pub struct ReadOnlyStorage {
     db: Arc<dyn KeyValueDB>,
+    metrics: Arc<StorageMetrics>,
+    encryption: Option<Arc<StorageEncryption>>,
 }
 
 impl Get for ReadOnlyStorage {
@@ -110,13 +207,24 @@ impl Get for ReadOnlyStorage {
     type Value = Vec<u8>;
     fn get(&self, key: &Self::Key) -> Option<Self::Value> {
         let (col, key) = key;
-        self.db.get(*col, &key).expect("kv storage io error")
+        let start = Instant::now();
+        let value = get_decrypted(&*self.db, self.encryption.as_deref(), *col, key);
+        self.metrics
+            .record_get(*col, value.is_some(), start.elapsed());
+        value
+    }
+    fn get_many(&self, keys: &[Self::Key]) -> Vec<Option<Self::Value>> {
+        get_many_decrypted(&*self.db, self.encryption.as_deref(), &self.metrics, keys)
     }
 }
 
 impl ReadOnlyStorage {
     pub fn new_db(db: Arc<dyn KeyValueDB>) -> Self {
-        Self { db }
+        Self {
+            db,
+            metrics: Default::default(),
+            encryption: None,
+        }
     }
 
     pub fn get_last_app_state(&self) -> Option<Vec<u8>> {
@@ -127,10 +235,16 @@ impl ReadOnlyStorage {
     }
 
     pub fn get_sealed_log(&self, txid: &TxId) -> Option<Vec<u8>> {
-        self.db
-            .get(COL_ENCLAVE_TX, txid)
-            .expect("IO fail")
-            .map(|x| x.to_vec())
+        get_decrypted(&*self.db, self.encryption.as_deref(), COL_ENCLAVE_TX, txid)
+    }
+
+    /// the committed key-values of the column with the key prefix (in the key order)
+    pub fn iter_with_prefix<'a>(
+        &'a self,
+        col: u32,
+        prefix: &'a [u8],
+    ) -> impl Iterator<Item = (Box<[u8]>, Vec<u8>)> + 'a {
+        iter_decrypted_with_prefix(&*self.db, self.encryption.as_deref(), col, prefix)
     }
 }
 
@@ -174,35 +288,75 @@ impl Storage {
     pub fn get_read_only(&self) -> ReadOnlyStorage {
         ReadOnlyStorage {
             db: self.db.clone(),
+            metrics: self.metrics.clone(),
+            encryption: self.encryption.clone(),
         }
     }
 
+    /// enables the encryption at rest of `encryption::ENCRYPTED_COLUMNS`
+    /// (values written before are still readable, see `encryption::migrate_columns`)
+    pub fn with_encryption(mut self, encryption: StorageEncryption) -> Self {
+        self.encryption = Some(Arc::new(encryption));
+        self
+    }
+
+    /// injects the faults at every write of the buffered transaction (a failed write is lost)
+    #[cfg(feature = "fault-injection")]
+    pub fn with_fault_injection(mut self, faults: Arc<fault::FaultInjector>) -> Self {
+        self.faults = Some(faults);
+        self
+    }
+
+    /// storage access metrics
+    pub fn metrics(&self) -> &StorageMetrics {
+        &self.metrics
+    }
+
     pub fn lookup_item(&self, item_type: LookupItem, txid_or_app_hash: &H256) -> Option<Vec<u8>> {
         lookup_item(self, item_type, txid_or_app_hash)
     }
 
+    /// the committed key-values of the column with the key prefix (in the key order);
+    /// the writes of the current (not yet persisted) transaction are not included
+    pub fn iter_with_prefix<'a>(
+        &'a self,
+        col: u32,
+        prefix: &'a [u8],
+    ) -> impl Iterator<Item = (Box<[u8]>, Vec<u8>)> + 'a {
+        iter_decrypted_with_prefix(&*self.db, self.encryption.as_deref(), col, prefix)
+    }
+
     /// initializes Storage with a provided reference to KV DB (used in testing / benches -- in-mem KVDB)
     #[allow(dead_code)]
     pub fn new_db(db: Arc<dyn KeyValueDB>) -> Self {
         Storage {
             db,
             current_tx: None,
+            metrics: Default::default(),
+            encryption: None,
+            #[cfg(feature = "fault-injection")]
+            faults: None,
         }
     }
 
     /// inititalizes Storage based on the provided config
     #[cfg(feature = "kvdb-rocksdb")]
     pub fn new(config: &StorageConfig<'_>) -> Self {
+        let db_path = config.db_path();
         let db = Arc::new(
             kvdb_rocksdb::Database::open(
-                &kvdb_rocksdb::DatabaseConfig::with_columns(NUM_COLUMNS),
-                &config.db_path(),
+                &config.tuning.database_config(Path::new(&db_path)),
+                &db_path,
             )
             .expect("failed to open db"),
         );
         Storage {
             db,
             current_tx: None,
+            metrics: Default::default(),
+            encryption: None,
+            #[cfg(feature = "fault-injection")]
+            faults: None,
         }
     }
 
@@ -221,11 +375,16 @@ impl Storage {
         self.lookup_item(LookupItem::TxSealed, txid)
     }
 
-    /// currently for potential debugging / diagnostics
+    /// for diagnostics and the evidence parameters updates
     /// parameters are protobuf-serialized (what was passed in initchain)
     pub fn store_consensus_params(&mut self, cp: &[u8]) {
         let inittx = self.get_or_create_tx();
-        inittx.put(COL_EXTRA, b"init_chain_consensus_params", cp);
+        inittx.put(COL_EXTRA, CONSENSUS_PARAMS_KEY, cp);
+    }
+
+    /// the consensus parameters passed in initchain (protobuf-serialized, if any)
+    pub fn get_consensus_params(&self) -> Option<Vec<u8>> {
+        self.get(&(COL_EXTRA, CONSENSUS_PARAMS_KEY.to_vec()))
     }
 
     pub fn get_genesis_app_hash(&self) -> H256 {
@@ -248,6 +407,22 @@ impl Storage {
         get_historical_staking_version(self, height)
     }
 
+    pub fn get_compact_filter(&self, height: BlockHeight) -> Option<Vec<u8>> {
+        get_compact_filter(self, height)
+    }
+
+    pub fn get_block_filter(&self, height: BlockHeight) -> Option<Vec<u8>> {
+        get_block_filter(self, height)
+    }
+
+    pub fn get_block_stats(&self, height: BlockHeight) -> Option<Vec<u8>> {
+        get_block_stats(self, height)
+    }
+
+    pub fn get_data_anchors(&self, commitment: &H256) -> Option<Vec<u8>> {
+        get_data_anchors(self, commitment)
+    }
+
     pub fn get_historical_app_hash(&self, height: BlockHeight) -> Option<H256> {
         get_historical_app_hash(self, height)
     }
@@ -260,14 +435,25 @@ impl Storage {
             .current_tx
             .take()
             .expect("there should be a tx after `get_or_create_tx`");
+        let start = Instant::now();
         self.db
             .write(tx)
             .expect("genesis app hash should be stored");
+        self.metrics.record_write(start.elapsed());
     }
 
     pub fn persist_write(&mut self) -> std::io::Result<()> {
         if let Some(dbtx) = self.current_tx.take() {
-            self.db.write(dbtx)
+            #[cfg(feature = "fault-injection")]
+            {
+                if let Some(faults) = &self.faults {
+                    faults.inject("storage write")?;
+                }
+            }
+            let start = Instant::now();
+            let result = self.db.write(dbtx);
+            self.metrics.record_write(start.elapsed());
+            result
         } else {
             Ok(())
         }
@@ -0,0 +1,224 @@
+//! Storage access metrics: per-column read counters (found / missing key)
+//! and latency histograms of reads and batch writes.
+//! Operations slower than the configured threshold are additionally recorded
+//! (and drained once per block by the caller, e.g. to be logged at commit).
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::NUM_COLUMNS;
+
+/// Upper bounds (in microseconds) of the latency histogram buckets
+/// (the last, implicit bucket counts the slower operations)
+pub const LATENCY_BUCKETS_MICROS: [u64; 8] = [10, 50, 100, 500, 1_000, 5_000, 10_000, 50_000];
+
+/// Maximum number of slow operations kept between two drains
+const MAX_SLOW_OPS: usize = 1_000;
+
+/// Kind of the storage operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageOp {
+    /// single key read (in a column)
+    Get(u32),
+    /// batch write of a transaction
+    Write,
+}
+
+/// Storage operation which exceeded the slow operation threshold
+#[derive(Debug, Clone, Copy)]
+pub struct SlowStorageOp {
+    pub op: StorageOp,
+    pub micros: u64,
+}
+
+/// Point-in-time copy of a latency histogram
+#[derive(Debug, Clone, Default)]
+pub struct HistogramSnapshot {
+    /// counts per bucket (`LATENCY_BUCKETS_MICROS` + the overflow bucket)
+    pub buckets: Vec<u64>,
+    pub count: u64,
+    pub sum_micros: u64,
+}
+
+/// Point-in-time copy of the read metrics of a column
+#[derive(Debug, Clone, Default)]
+pub struct ColumnSnapshot {
+    pub column: u32,
+    /// reads of existing keys
+    pub hits: u64,
+    /// reads of missing keys
+    pub misses: u64,
+    pub latency: HistogramSnapshot,
+}
+
+/// Point-in-time copy of all storage metrics
+#[derive(Debug, Clone, Default)]
+pub struct MetricsSnapshot {
+    /// columns which were read at least once
+    pub reads: Vec<ColumnSnapshot>,
+    pub writes: HistogramSnapshot,
+    /// slow operation threshold (in microseconds, if enabled)
+    pub slow_threshold_micros: Option<u64>,
+}
+
+#[derive(Default)]
+struct Histogram {
+    buckets: [AtomicU64; LATENCY_BUCKETS_MICROS.len() + 1],
+    count: AtomicU64,
+    sum_micros: AtomicU64,
+}
+
+impl Histogram {
+    fn observe(&self, micros: u64) {
+        let bucket = LATENCY_BUCKETS_MICROS
+            .iter()
+            .position(|bound| micros <= *bound)
+            .unwrap_or(LATENCY_BUCKETS_MICROS.len());
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_micros.fetch_add(micros, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> HistogramSnapshot {
+        HistogramSnapshot {
+            buckets: self
+                .buckets
+                .iter()
+                .map(|bucket| bucket.load(Ordering::Relaxed))
+                .collect(),
+            count: self.count.load(Ordering::Relaxed),
+            sum_micros: self.sum_micros.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[derive(Default)]
+struct ColumnReads {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    latency: Histogram,
+}
+
+/// Metrics shared by the storage handles of the same database
+pub struct StorageMetrics {
+    reads: Vec<ColumnReads>,
+    writes: Histogram,
+    /// 0 = slow operations aren't recorded
+    slow_threshold_micros: AtomicU64,
+    slow_ops: Mutex<Vec<SlowStorageOp>>,
+}
+
+impl Default for StorageMetrics {
+    fn default() -> Self {
+        StorageMetrics {
+            reads: (0..NUM_COLUMNS).map(|_| ColumnReads::default()).collect(),
+            writes: Histogram::default(),
+            slow_threshold_micros: AtomicU64::new(0),
+            slow_ops: Mutex::new(Vec::new()),
+        }
+    }
+}
+
+impl StorageMetrics {
+    /// Operations taking longer than `threshold` are recorded (None disables it)
+    pub fn set_slow_threshold(&self, threshold: Option<Duration>) {
+        let micros = threshold
+            .map(|t| (t.as_micros() as u64).max(1))
+            .unwrap_or(0);
+        self.slow_threshold_micros.store(micros, Ordering::Relaxed);
+    }
+
+    pub fn record_get(&self, column: u32, found: bool, elapsed: Duration) {
+        let micros = elapsed.as_micros() as u64;
+        if let Some(reads) = self.reads.get(column as usize) {
+            if found {
+                reads.hits.fetch_add(1, Ordering::Relaxed);
+            } else {
+                reads.misses.fetch_add(1, Ordering::Relaxed);
+            }
+            reads.latency.observe(micros);
+        }
+        self.check_slow(StorageOp::Get(column), micros);
+    }
+
+    pub fn record_write(&self, elapsed: Duration) {
+        let micros = elapsed.as_micros() as u64;
+        self.writes.observe(micros);
+        self.check_slow(StorageOp::Write, micros);
+    }
+
+    fn check_slow(&self, op: StorageOp, micros: u64) {
+        let threshold = self.slow_threshold_micros.load(Ordering::Relaxed);
+        if threshold == 0 || micros < threshold {
+            return;
+        }
+        let mut slow_ops = self.slow_ops.lock().expect("slow storage ops lock");
+        if slow_ops.len() < MAX_SLOW_OPS {
+            slow_ops.push(SlowStorageOp { op, micros });
+        }
+    }
+
+    /// Returns (and clears) the slow operations recorded since the last call
+    pub fn take_slow_ops(&self) -> Vec<SlowStorageOp> {
+        std::mem::take(&mut *self.slow_ops.lock().expect("slow storage ops lock"))
+    }
+
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        let reads = self
+            .reads
+            .iter()
+            .enumerate()
+            .map(|(column, reads)| ColumnSnapshot {
+                column: column as u32,
+                hits: reads.hits.load(Ordering::Relaxed),
+                misses: reads.misses.load(Ordering::Relaxed),
+                latency: reads.latency.snapshot(),
+            })
+            .filter(|column| column.latency.count > 0)
+            .collect();
+        let threshold = self.slow_threshold_micros.load(Ordering::Relaxed);
+        MetricsSnapshot {
+            reads,
+            writes: self.writes.snapshot(),
+            slow_threshold_micros: if threshold == 0 {
+                None
+            } else {
+                Some(threshold)
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_metrics_recording() {
+        let metrics = StorageMetrics::default();
+        metrics.record_get(1, true, Duration::from_micros(5));
+        metrics.record_get(1, false, Duration::from_micros(20_000));
+        metrics.record_write(Duration::from_millis(100));
+        assert!(metrics.take_slow_ops().is_empty());
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(1, snapshot.reads.len());
+        let column = &snapshot.reads[0];
+        assert_eq!((1, 1, 1), (column.column, column.hits, column.misses));
+        assert_eq!(2, column.latency.count);
+        assert_eq!(1, column.latency.buckets[0]);
+        assert_eq!(1, column.latency.buckets[7]);
+        assert_eq!(1, snapshot.writes.buckets[8]);
+
+        metrics.set_slow_threshold(Some(Duration::from_millis(10)));
+        metrics.record_get(2, true, Duration::from_micros(5));
+        metrics.record_get(2, true, Duration::from_millis(15));
+        metrics.record_write(Duration::from_millis(100));
+        let slow_ops = metrics.take_slow_ops();
+        assert_eq!(2, slow_ops.len());
+        assert_eq!(StorageOp::Get(2), slow_ops[0].op);
+        assert_eq!(StorageOp::Write, slow_ops[1].op);
+        assert!(metrics.take_slow_ops().is_empty());
+        assert_eq!(Some(10_000), metrics.snapshot().slow_threshold_micros);
+    }
+}
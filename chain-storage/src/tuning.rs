@@ -0,0 +1,207 @@
+//! Tuning of the RocksDB database of the node
+//!
+//! The memory budget of a column is mostly used by its memtables, and a third of the total
+//! budget of all the columns is the block cache shared by the columns. The bloom filters
+//! (10 bits per key) and the write-ahead log settings are fixed by kvdb-rocksdb.
+use std::collections::BTreeMap;
+use std::fmt;
+#[cfg(feature = "kvdb-rocksdb")]
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::{COL_BODIES, COL_TX_META, NUM_COLUMNS};
+
+/// Names of the columns (by their index), as used in the configuration
+pub const COLUMN_NAMES: [&str; NUM_COLUMNS as usize] = [
+    "tx_meta",
+    "witness",
+    "bodies",
+    "extra",
+    "node_info",
+    "merkle_proofs",
+    "app_hashs",
+    "app_states",
+    "enclave_tx",
+    "trie_node",
+    "trie_staled",
+    "staking_versions",
+    "compact_filters",
+    "block_stats",
+    "prune_queue",
+    "utxo_mmr",
+    "data_anchors",
+    "block_filters",
+];
+
+/// Default memory budget (in MiB) of a column (as in kvdb-rocksdb)
+pub const DEFAULT_COLUMN_BUDGET_MB: usize = 128;
+/// Default memory budget (in MiB) of `COL_BODIES`: the large transaction bodies are mostly
+/// written (and pruned), so larger memtables mean fewer flushes and compactions
+pub const DEFAULT_BODIES_BUDGET_MB: usize = 256;
+/// Default memory budget (in MiB) of `COL_TX_META`: the small UTXO metadata is read
+/// by every transaction, so more of it stays in the memtables and the block cache
+pub const DEFAULT_TX_META_BUDGET_MB: usize = 192;
+
+/// Index of the column with the name
+pub fn column_by_name(name: &str) -> Option<u32> {
+    COLUMN_NAMES
+        .iter()
+        .position(|column| *column == name)
+        .map(|col| col as u32)
+}
+
+/// Size of the data blocks and of the files of the first level
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompactionStyle {
+    /// detected from the disk of the database (SSD if unknown)
+    Auto,
+    /// small blocks and files for fast random reads
+    Ssd,
+    /// large blocks and files for fewer seeks
+    Hdd,
+}
+
+impl Default for CompactionStyle {
+    fn default() -> Self {
+        CompactionStyle::Auto
+    }
+}
+
+impl fmt::Display for CompactionStyle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompactionStyle::Auto => write!(f, "auto"),
+            CompactionStyle::Ssd => write!(f, "ssd"),
+            CompactionStyle::Hdd => write!(f, "hdd"),
+        }
+    }
+}
+
+impl FromStr for CompactionStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(CompactionStyle::Auto),
+            "ssd" => Ok(CompactionStyle::Ssd),
+            "hdd" => Ok(CompactionStyle::Hdd),
+            _ => Err(format!("invalid compaction style: {}", s)),
+        }
+    }
+}
+
+/// Options of the RocksDB database
+#[derive(Debug, Clone, PartialEq)]
+pub struct DbTuning {
+    pub compaction: CompactionStyle,
+    /// overrides the block size of the compaction style (in KiB)
+    pub block_size_kb: Option<usize>,
+    /// maximum number of open files (-1 = unlimited)
+    pub max_open_files: i32,
+    /// number of the kept info log files of RocksDB
+    pub keep_log_file_num: i32,
+    /// collects RocksDB statistics (small performance cost)
+    pub enable_statistics: bool,
+    /// memory budget (in MiB) of the columns without their own budget
+    pub column_budget_mb: usize,
+    /// memory budgets (in MiB) of the columns
+    pub column_budgets_mb: BTreeMap<u32, usize>,
+}
+
+impl Default for DbTuning {
+    fn default() -> Self {
+        let mut column_budgets_mb = BTreeMap::new();
+        column_budgets_mb.insert(COL_BODIES, DEFAULT_BODIES_BUDGET_MB);
+        column_budgets_mb.insert(COL_TX_META, DEFAULT_TX_META_BUDGET_MB);
+        DbTuning {
+            compaction: CompactionStyle::default(),
+            block_size_kb: None,
+            max_open_files: 512,
+            keep_log_file_num: 1,
+            enable_statistics: false,
+            column_budget_mb: DEFAULT_COLUMN_BUDGET_MB,
+            column_budgets_mb,
+        }
+    }
+}
+
+impl DbTuning {
+    /// The options of kvdb-rocksdb (the same memory budget for all the columns)
+    pub fn uniform() -> Self {
+        DbTuning {
+            column_budgets_mb: BTreeMap::new(),
+            ..Default::default()
+        }
+    }
+
+    pub fn column_budget_mb(&self, col: u32) -> usize {
+        self.column_budgets_mb
+            .get(&col)
+            .copied()
+            .unwrap_or(self.column_budget_mb)
+    }
+
+    /// Size (in MiB) of the block cache shared by the columns
+    pub fn block_cache_mb(&self) -> usize {
+        (0..NUM_COLUMNS)
+            .map(|col| self.column_budget_mb(col))
+            .sum::<usize>()
+            / 3
+    }
+
+    /// Configuration of the database in the directory
+    #[cfg(feature = "kvdb-rocksdb")]
+    pub fn database_config(&self, db_path: &Path) -> kvdb_rocksdb::DatabaseConfig {
+        use kvdb_rocksdb::CompactionProfile;
+
+        let mut config = kvdb_rocksdb::DatabaseConfig::with_columns(NUM_COLUMNS);
+        config.compaction = match self.compaction {
+            CompactionStyle::Auto => CompactionProfile::auto(db_path),
+            CompactionStyle::Ssd => CompactionProfile::ssd(),
+            CompactionStyle::Hdd => CompactionProfile::hdd(),
+        };
+        if let Some(block_size_kb) = self.block_size_kb {
+            config.compaction.block_size = block_size_kb * 1024;
+        }
+        config.max_open_files = self.max_open_files;
+        config.keep_log_file_num = self.keep_log_file_num;
+        config.enable_statistics = self.enable_statistics;
+        config.memory_budget = (0..NUM_COLUMNS)
+            .map(|col| (col, self.column_budget_mb(col)))
+            .collect();
+        config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_column_budgets() {
+        for (col, name) in COLUMN_NAMES.iter().enumerate() {
+            assert_eq!(Some(col as u32), column_by_name(name));
+        }
+        assert_eq!(None, column_by_name("unknown"));
+        assert_eq!(Ok(CompactionStyle::Hdd), "hdd".parse());
+
+        let tuning = DbTuning::default();
+        assert_eq!(
+            DEFAULT_BODIES_BUDGET_MB,
+            tuning.column_budget_mb(COL_BODIES)
+        );
+        assert_eq!(
+            DEFAULT_TX_META_BUDGET_MB,
+            tuning.column_budget_mb(COL_TX_META)
+        );
+        assert_eq!(
+            DEFAULT_COLUMN_BUDGET_MB,
+            tuning.column_budget_mb(crate::COL_EXTRA)
+        );
+        assert_eq!(
+            DEFAULT_COLUMN_BUDGET_MB * NUM_COLUMNS as usize / 3,
+            DbTuning::uniform().block_cache_mb()
+        );
+        assert!(tuning.block_cache_mb() > DbTuning::uniform().block_cache_mb());
+    }
+}
@@ -0,0 +1,188 @@
+//! Storage of the merkle mountain range of the transaction outputs (the UTXO set commitment)
+use parity_scale_codec::{Decode, Encode};
+
+use chain_core::common::H256;
+use chain_core::state::utxo::{utxo_leaf_hash, UtxoMmr, UtxoMmrGet, UtxoMmrStore, UtxoProof};
+use chain_core::state::UtxoChange;
+use chain_core::tx::data::input::TxoPointer;
+
+use super::COL_UTXO_MMR;
+use crate::buffer::{GetKV, StoreKV};
+
+/// key of the number of leaves (u64)
+const LEAF_COUNT_KEY: &[u8] = b"leaf_count";
+/// prefix of the node keys (followed by the height and the big endian index)
+const NODE_PREFIX: u8 = b'n';
+/// prefix of the output keys (followed by scale bytes of the `TxoPointer`)
+const LEAF_INDEX_PREFIX: u8 = b'l';
+
+fn node_key(height: u8, index: u64) -> Vec<u8> {
+    let mut key = vec![NODE_PREFIX, height];
+    key.extend_from_slice(&index.to_be_bytes());
+    key
+}
+
+fn leaf_index_key(txo: &TxoPointer) -> Vec<u8> {
+    let mut key = vec![LEAF_INDEX_PREFIX];
+    key.extend(txo.encode());
+    key
+}
+
+/// Nodes of the mountain range in the kv storage (read only)
+pub struct UtxoMmrGetter<'a, S: GetKV>(&'a S);
+
+impl<'a, S: GetKV> UtxoMmrGet for UtxoMmrGetter<'a, S> {
+    fn get_node(&self, height: u8, index: u64) -> Option<H256> {
+        get_node(self.0, height, index)
+    }
+}
+
+/// Nodes of the mountain range in the kv storage
+pub struct UtxoMmrKVStore<'a, S: StoreKV>(&'a mut S);
+
+impl<'a, S: StoreKV> UtxoMmrGet for UtxoMmrKVStore<'a, S> {
+    fn get_node(&self, height: u8, index: u64) -> Option<H256> {
+        get_node(&*self.0, height, index)
+    }
+}
+
+impl<'a, S: StoreKV> UtxoMmrStore for UtxoMmrKVStore<'a, S> {
+    fn set_node(&mut self, height: u8, index: u64, hash: H256) {
+        self.0
+            .set((COL_UTXO_MMR, node_key(height, index)), hash.to_vec());
+    }
+}
+
+fn get_node(db: &impl GetKV, height: u8, index: u64) -> Option<H256> {
+    db.get(&(COL_UTXO_MMR, node_key(height, index)))
+        .map(|value| H256::decode(&mut value.as_slice()).expect("utxo mmr storage corrupted"))
+}
+
+/// Returns the number of leaves of the mountain range (i.e. of outputs ever created)
+pub fn get_leaf_count(db: &impl GetKV) -> u64 {
+    db.get(&(COL_UTXO_MMR, LEAF_COUNT_KEY.to_vec()))
+        .map(|value| u64::decode(&mut value.as_slice()).expect("utxo mmr storage corrupted"))
+        .unwrap_or_default()
+}
+
+/// Returns the index of the leaf of the output (if it was created)
+pub fn get_leaf_index(db: &impl GetKV, txo: &TxoPointer) -> Option<u64> {
+    db.get(&(COL_UTXO_MMR, leaf_index_key(txo)))
+        .map(|value| u64::decode(&mut value.as_slice()).expect("utxo mmr storage corrupted"))
+}
+
+/// Applies the UTXO changes of a block (spent outputs are marked, created ones appended)
+/// and returns the new root of the mountain range
+pub fn apply_utxo_changes(db: &mut impl StoreKV, changes: &[UtxoChange]) -> H256 {
+    // leaves of the outputs created in the previous blocks
+    let stored_indices = changes
+        .iter()
+        .map(|change| match change {
+            UtxoChange::Spent(txo) => get_leaf_index(&*db, txo),
+            UtxoChange::Created { .. } => None,
+        })
+        .collect::<Vec<_>>();
+    let leaf_count = get_leaf_count(&*db);
+    let mut leaf_indices = vec![];
+    let mut mmr = UtxoMmr::new(UtxoMmrKVStore(db), leaf_count);
+    for (change, stored_index) in changes.iter().zip(stored_indices.into_iter()) {
+        match change {
+            UtxoChange::Spent(txo) => {
+                let leaf_index = stored_index
+                    .or_else(|| {
+                        leaf_indices
+                            .iter()
+                            .find(|(created, _)| created == txo)
+                            .map(|(_, leaf_index)| *leaf_index)
+                    })
+                    .expect("spent output is not in the utxo mmr storage");
+                mmr.update(leaf_index, utxo_leaf_hash(txo, true));
+            }
+            UtxoChange::Created {
+                txid,
+                no_of_outputs,
+            } => {
+                for index in 0..*no_of_outputs {
+                    let txo = TxoPointer::new(*txid, index as usize);
+                    let leaf_index = mmr.append(utxo_leaf_hash(&txo, false));
+                    leaf_indices.push((txo, leaf_index));
+                }
+            }
+        }
+    }
+    let (root, leaf_count) = (mmr.root(), mmr.leaf_count());
+    for (txo, leaf_index) in leaf_indices.into_iter() {
+        db.set((COL_UTXO_MMR, leaf_index_key(&txo)), leaf_index.encode());
+    }
+    db.set((COL_UTXO_MMR, LEAF_COUNT_KEY.to_vec()), leaf_count.encode());
+    root
+}
+
+/// Returns the proof of the output's leaf against the current root (if the output was created),
+/// the leaf of a spent output is only proven with the spent flag
+pub fn get_utxo_proof(db: &impl GetKV, txo: &TxoPointer) -> Option<UtxoProof> {
+    let leaf_index = get_leaf_index(db, txo)?;
+    UtxoMmr::new(UtxoMmrGetter(db), get_leaf_count(db)).prove(leaf_index)
+}
+
+/// Returns the current root of the mountain range
+pub fn get_utxo_root(db: &impl GetKV) -> H256 {
+    UtxoMmr::new(UtxoMmrGetter(db), get_leaf_count(db)).root()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::buffer::{BufferStore, KVBuffer};
+    use crate::Storage;
+    use chain_core::state::GENESIS_UTXO_COMMITMENT;
+    use kvdb_memorydb::create as create_memorydb;
+    use std::sync::Arc;
+
+    #[test]
+    fn check_apply_utxo_changes() {
+        let storage = Storage::new_db(Arc::new(create_memorydb(crate::NUM_COLUMNS)));
+        let mut buffer = KVBuffer::new();
+        let mut store = BufferStore::new(&storage, &mut buffer);
+        assert_eq!(GENESIS_UTXO_COMMITMENT, get_utxo_root(&store));
+
+        let first = TxoPointer::new([1u8; 32], 0);
+        let second = TxoPointer::new([1u8; 32], 1);
+        let third = TxoPointer::new([2u8; 32], 0);
+        let root = apply_utxo_changes(
+            &mut store,
+            &[
+                UtxoChange::Created {
+                    txid: [1u8; 32],
+                    no_of_outputs: 2,
+                },
+                UtxoChange::Spent(first.clone()),
+            ],
+        );
+        assert_eq!(root, get_utxo_root(&store));
+        assert!(!get_utxo_proof(&store, &first)
+            .unwrap()
+            .verify_unspent(&first, &root));
+        assert!(get_utxo_proof(&store, &second)
+            .unwrap()
+            .verify_unspent(&second, &root));
+        assert!(get_utxo_proof(&store, &third).is_none());
+
+        let root = apply_utxo_changes(
+            &mut store,
+            &[
+                UtxoChange::Spent(second.clone()),
+                UtxoChange::Created {
+                    txid: [2u8; 32],
+                    no_of_outputs: 1,
+                },
+            ],
+        );
+        assert_eq!(3, get_leaf_count(&store));
+        let proof = get_utxo_proof(&store, &second).unwrap();
+        assert!(proof.verify(&second, true, &root));
+        assert!(get_utxo_proof(&store, &third)
+            .unwrap()
+            .verify_unspent(&third, &root));
+    }
+}
@@ -5,7 +5,10 @@ pub use rs_libc::alloc::*;
 use std::{
     io::{Read, Write},
     net::{TcpListener, TcpStream},
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
 };
 
 use parity_scale_codec::{Decode, Encode};
@@ -13,14 +16,16 @@ use rustls::{NoClientAuth, ServerConfig, ServerSession, StreamOwned};
 use thread_pool::ThreadPool;
 
 use enclave_protocol::{
-    DecryptionRequest, TxQueryInitRequest, TxQueryInitResponse, ENCRYPTION_REQUEST_SIZE,
+    DecryptionRequest, DiscoveryRequest, SubscriptionRequest, TxQueryInitRequest,
+    TxQueryInitResponse, ENCRYPTION_REQUEST_SIZE,
 };
 use ra_enclave::DEFAULT_EXPIRATION_SECS;
 use ra_enclave::{EnclaveRaConfig, EnclaveRaContext};
 
 use self::handler::{
-    get_random_challenge, handle_decryption_request, handle_encryption_request,
-    verify_decryption_request,
+    get_random_challenge, handle_decryption_request, handle_discovery_request,
+    handle_encryption_request, handle_subscription_request, verify_decryption_request,
+    verify_discovery_request, verify_subscription_request,
 };
 use chrono::Duration;
 
@@ -32,6 +37,9 @@ pub fn entry(cert_expiration: Option<Duration>) -> std::io::Result<()> {
     let chain_data_stream = Arc::new(Mutex::new(TcpStream::connect("chain-abci-data")?));
     // FIXME: connect to tx-validation (mutually attested TLS)
     let num_threads = 4;
+    // subscriptions (long-polls) hold a thread, so one is always left for the other requests
+    let max_active_subscriptions = num_threads - 1;
+    let active_subscriptions = Arc::new(AtomicUsize::new(0));
 
     // use the smaller Duration as certificate validity, so that we can check the certification is expired or not correctly
     let default_expiration_time = Duration::seconds(DEFAULT_EXPIRATION_SECS);
@@ -59,6 +67,7 @@ pub fn entry(cert_expiration: Option<Duration>) -> std::io::Result<()> {
     for stream in listener.incoming() {
         let context = context.clone();
         let chain_data_stream = chain_data_stream.clone();
+        let active_subscriptions = active_subscriptions.clone();
 
         thread_pool_sender
             .send(move || {
@@ -76,7 +85,12 @@ pub fn entry(cert_expiration: Option<Duration>) -> std::io::Result<()> {
                 let tls_session = ServerSession::new(&tls_server_config);
                 let stream = StreamOwned::new(tls_session, stream.unwrap());
 
-                handle_connection(stream, chain_data_stream);
+                handle_connection(
+                    stream,
+                    chain_data_stream,
+                    active_subscriptions,
+                    max_active_subscriptions,
+                );
             })
             .expect("Unable to send tasks to thread pool");
     }
@@ -85,14 +99,19 @@ pub fn entry(cert_expiration: Option<Duration>) -> std::io::Result<()> {
     Ok(())
 }
 
-fn handle_connection<T: Read + Write>(mut stream: T, chain_data_stream: Arc<Mutex<TcpStream>>) {
+fn handle_connection<T: Read + Write>(
+    mut stream: T,
+    chain_data_stream: Arc<Mutex<TcpStream>>,
+    active_subscriptions: Arc<AtomicUsize>,
+    max_active_subscriptions: usize,
+) {
     let mut bytes = vec![0u8; ENCRYPTION_REQUEST_SIZE];
 
     match stream.read(&mut bytes) {
         Ok(len) => {
             match TxQueryInitRequest::decode(&mut &bytes.as_slice()[0..len]) {
                 Ok(TxQueryInitRequest::Encrypt(request)) => {
-                    let response = handle_encryption_request(request, len, chain_data_stream);
+                    let response = handle_encryption_request(request, chain_data_stream);
 
                     let response = match response {
                         Ok(response) => response,
@@ -158,6 +177,20 @@ fn handle_connection<T: Read + Write>(mut stream: T, chain_data_stream: Arc<Mute
                         }
                     }
                 }
+                Ok(TxQueryInitRequest::SubscribeChallenge) => {
+                    if active_subscriptions.fetch_add(1, Ordering::SeqCst)
+                        >= max_active_subscriptions
+                    {
+                        active_subscriptions.fetch_sub(1, Ordering::SeqCst);
+                        log::error!("Too many active subscriptions");
+                        return;
+                    }
+                    handle_subscription(&mut stream, chain_data_stream, &mut bytes);
+                    active_subscriptions.fetch_sub(1, Ordering::SeqCst);
+                }
+                Ok(TxQueryInitRequest::DiscoverChallenge) => {
+                    handle_discovery(&mut stream, chain_data_stream, &mut bytes);
+                }
                 Err(err) => {
                     log::error!("Error while decoding tx-query init request: {}", err);
                 }
@@ -166,3 +199,93 @@ fn handle_connection<T: Read + Write>(mut stream: T, chain_data_stream: Arc<Mute
         Err(err) => log::error!("Error while reading bytes from TLS stream: {}", err),
     }
 }
+
+fn handle_subscription<T: Read + Write>(
+    stream: &mut T,
+    chain_data_stream: Arc<Mutex<TcpStream>>,
+    bytes: &mut [u8],
+) {
+    let challenge = get_random_challenge();
+
+    if let Err(err) = stream.write_all(&TxQueryInitResponse::SubscribeChallenge(challenge).encode())
+    {
+        log::error!("Unable to write random challenge to TLS stream: {}", err);
+        return;
+    }
+
+    let len = match stream.read(bytes) {
+        Ok(len) => len,
+        Err(err) => {
+            log::error!("Unable to read challenge response from TLS stream: {}", err);
+            return;
+        }
+    };
+    let subscription_request = match SubscriptionRequest::decode(&mut &bytes[0..len]) {
+        Ok(subscription_request) => subscription_request,
+        Err(err) => {
+            log::error!("Unable to decode subscription request: {}", err);
+            return;
+        }
+    };
+    if !verify_subscription_request(&subscription_request, challenge) {
+        log::error!("Subscription request is invalid");
+        return;
+    }
+
+    match handle_subscription_request(&subscription_request, chain_data_stream) {
+        Ok(notification) => {
+            if let Err(err) = stream.write_all(&notification.encode()) {
+                log::error!(
+                    "Error while writing subscription notification back to TLS stream: {}",
+                    err
+                );
+            }
+        }
+        Err(err) => log::error!("Error while handling subscription request: {}", err),
+    }
+}
+
+fn handle_discovery<T: Read + Write>(
+    stream: &mut T,
+    chain_data_stream: Arc<Mutex<TcpStream>>,
+    bytes: &mut [u8],
+) {
+    let challenge = get_random_challenge();
+
+    if let Err(err) = stream.write_all(&TxQueryInitResponse::DiscoverChallenge(challenge).encode())
+    {
+        log::error!("Unable to write random challenge to TLS stream: {}", err);
+        return;
+    }
+
+    let len = match stream.read(bytes) {
+        Ok(len) => len,
+        Err(err) => {
+            log::error!("Unable to read challenge response from TLS stream: {}", err);
+            return;
+        }
+    };
+    let discovery_request = match DiscoveryRequest::decode(&mut &bytes[0..len]) {
+        Ok(discovery_request) => discovery_request,
+        Err(err) => {
+            log::error!("Unable to decode discovery request: {}", err);
+            return;
+        }
+    };
+    if !verify_discovery_request(&discovery_request, challenge) {
+        log::error!("Discovery request is invalid");
+        return;
+    }
+
+    match handle_discovery_request(&discovery_request, chain_data_stream) {
+        Ok(response) => {
+            if let Err(err) = stream.write_all(&response.encode()) {
+                log::error!(
+                    "Error while writing discovery response back to TLS stream: {}",
+                    err
+                );
+            }
+        }
+        Err(err) => log::error!("Error while handling discovery request: {}", err),
+    }
+}
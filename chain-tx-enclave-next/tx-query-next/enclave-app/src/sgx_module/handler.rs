@@ -1,9 +1,13 @@
 mod decryption_request;
+mod discovery_request;
 mod encryption_request;
+mod subscription_request;
 
 pub use self::{
     decryption_request::{
         get_random_challenge, handle_decryption_request, verify_decryption_request,
     },
+    discovery_request::{handle_discovery_request, verify_discovery_request},
     encryption_request::handle_encryption_request,
+    subscription_request::{handle_subscription_request, verify_subscription_request},
 };
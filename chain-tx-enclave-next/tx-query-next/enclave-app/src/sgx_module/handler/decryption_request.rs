@@ -11,9 +11,8 @@ use zeroize::Zeroize;
 
 use chain_core::{
     common::H256,
-    state::account::WithdrawUnbondedTx,
     tx::{
-        data::{access::TxAccessPolicy, attribute::TxAttributes, Tx},
+        data::{access::TxAccessPolicy, TxId},
         TxWithOutputs,
     },
 };
@@ -74,48 +73,9 @@ pub fn handle_decryption_request(
             let mut return_result = Vec::with_capacity(sealed_logs.len());
 
             for (txid, sealed_log) in txids.into_iter().zip(sealed_logs.into_iter()) {
-                let sealed_data = match SealedData::try_copy_from(&sealed_log) {
-                    Some(sealed_data) => sealed_data,
-                    None => {
-                        return Err(
-                            "Unable to parse sealed data returned from chain-abci".to_owned()
-                        )
-                    }
-                };
-
-                if sealed_data.aes_data.additional_txt != txid {
-                    return Err("Transaction ID does not match in sealed data".to_owned());
+                if let Some(otx) = unseal_readable_tx(&txid, &sealed_log, &view_key)? {
+                    return_result.push(otx);
                 }
-
-                let mut unsealed_data = sealed_data
-                    .unseal()
-                    .map_err(|e| format!("Error while unsealing sealed data: {:?}", e))?;
-                let otx = TxWithOutputs::decode(&mut unsealed_data.as_slice());
-                let push: bool;
-
-                match &otx {
-                    Ok(TxWithOutputs::Transfer(Tx {
-                        attributes: TxAttributes { allowed_view, .. },
-                        ..
-                    })) => {
-                        push = is_allowed_view(&allowed_view, &view_key);
-                    }
-                    Ok(TxWithOutputs::StakeWithdraw(WithdrawUnbondedTx {
-                        attributes: TxAttributes { allowed_view, .. },
-                        ..
-                    })) => {
-                        push = is_allowed_view(&allowed_view, &view_key);
-                    }
-                    _ => {
-                        return Err("Invalid transaction type".to_owned());
-                    }
-                }
-
-                if push {
-                    return_result.push(otx.unwrap());
-                }
-
-                unsealed_data.zeroize();
             }
 
             let decryption_response = DecryptionResponse { txs: return_result };
@@ -129,6 +89,46 @@ pub fn handle_decryption_request(
     }
 }
 
+/// Unseals the sealed data of the transaction and returns it if it's readable by the view key
+pub fn unseal_readable_tx(
+    txid: &TxId,
+    sealed_log: &[u8],
+    view_key: &PublicKey,
+) -> Result<Option<TxWithOutputs>, String> {
+    let sealed_data = match SealedData::try_copy_from(sealed_log) {
+        Some(sealed_data) => sealed_data,
+        None => return Err("Unable to parse sealed data returned from chain-abci".to_owned()),
+    };
+
+    if sealed_data.aes_data.additional_txt != *txid {
+        return Err("Transaction ID does not match in sealed data".to_owned());
+    }
+
+    let mut unsealed_data = sealed_data
+        .unseal()
+        .map_err(|e| format!("Error while unsealing sealed data: {:?}", e))?;
+    let otx = TxWithOutputs::decode(&mut unsealed_data.as_slice());
+    unsealed_data.zeroize();
+
+    match otx {
+        Ok(TxWithOutputs::Transfer(tx)) => {
+            if is_allowed_view(&tx.attributes.allowed_view, view_key) {
+                Ok(Some(TxWithOutputs::Transfer(tx)))
+            } else {
+                Ok(None)
+            }
+        }
+        Ok(TxWithOutputs::StakeWithdraw(tx)) => {
+            if is_allowed_view(&tx.attributes.allowed_view, view_key) {
+                Ok(Some(TxWithOutputs::StakeWithdraw(tx)))
+            } else {
+                Ok(None)
+            }
+        }
+        _ => Err("Invalid transaction type".to_owned()),
+    }
+}
+
 #[inline]
 fn is_allowed_view(allowed_views: &[TxAccessPolicy], view_key: &PublicKey) -> bool {
     // TODO: policy != alldata + const eq?
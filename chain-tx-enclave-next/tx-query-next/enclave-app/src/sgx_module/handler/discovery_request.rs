@@ -0,0 +1,54 @@
+use std::{
+    net::TcpStream,
+    sync::{Arc, Mutex},
+};
+
+use chain_core::{common::H256, state::tendermint::BlockHeight};
+use enclave_protocol::{DiscoveryRequest, DiscoveryResponse, MAX_DISCOVERY_HEIGHTS};
+
+use super::decryption_request::unseal_readable_tx;
+use super::subscription_request::{get_committed_sealed_txs, MAX_HEIGHTS_PER_REQUEST};
+
+pub fn verify_discovery_request(discovery_request: &DiscoveryRequest, challenge: H256) -> bool {
+    let secp = secp256k1::SECP256K1;
+    discovery_request.verify(&secp, challenge).is_ok()
+}
+
+/// Scans the committed blocks in the requested range (at most `MAX_DISCOVERY_HEIGHTS` of them)
+/// for transactions readable by the view key
+pub fn handle_discovery_request(
+    discovery_request: &DiscoveryRequest,
+    chain_data_stream: Arc<Mutex<TcpStream>>,
+) -> Result<DiscoveryResponse, String> {
+    let view_key = discovery_request.body.view_key;
+    let from_height = discovery_request.body.from_height.value();
+    if from_height == 0 || discovery_request.body.to_height.value() < from_height {
+        return Err("Invalid block range in discovery request".to_owned());
+    }
+    let after_height = from_height - 1;
+    let to_height = discovery_request
+        .body
+        .to_height
+        .value()
+        .min(after_height.saturating_add(MAX_DISCOVERY_HEIGHTS.into()));
+
+    let mut last_height = BlockHeight::new(after_height);
+    let mut txs = Vec::new();
+    while last_height.value() < to_height {
+        let max_heights = (to_height - last_height.value()).min(MAX_HEIGHTS_PER_REQUEST.into());
+        let (checked_height, sealed_txs) =
+            get_committed_sealed_txs(&chain_data_stream, last_height, max_heights as u32)?;
+
+        for (height, txid, sealed_log) in sealed_txs {
+            if let Some(otx) = unseal_readable_tx(&txid, &sealed_log, &view_key)? {
+                txs.push((height, otx));
+            }
+        }
+        if checked_height == last_height {
+            // the range isn't committed yet
+            break;
+        }
+        last_height = checked_height;
+    }
+    Ok(DiscoveryResponse { last_height, txs })
+}
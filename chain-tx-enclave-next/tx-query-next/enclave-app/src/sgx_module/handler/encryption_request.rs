@@ -7,6 +7,9 @@ use std::{
 
 use parity_scale_codec::{Decode, Encode};
 
+use chain_core::tx::fee::{
+    mock_obfuscated_deposit, mock_obfuscated_transfer, mock_obfuscated_withdraw,
+};
 use chain_core::tx::{data::input::TxoSize, TransactionId, TxEnclaveAux};
 use enclave_protocol::{
     EnclaveRequest, EnclaveResponse, EncryptionRequest, EncryptionResponse, QueryEncryptRequest,
@@ -16,10 +19,9 @@ use enclave_utils::SealedData;
 #[allow(clippy::boxed_local)]
 pub fn handle_encryption_request(
     encryption_request: Box<EncryptionRequest>,
-    request_len: usize,
     chain_data_stream: Arc<Mutex<TcpStream>>,
 ) -> Result<EncryptionResponse, String> {
-    let request = construct_request(&*encryption_request, request_len);
+    let request = construct_request(&*encryption_request);
 
     match request {
         None => Err("Failed to seal request data".to_owned()),
@@ -99,10 +101,10 @@ pub fn handle_encryption_request(
     }
 }
 
-fn construct_request(req: &EncryptionRequest, req_len: usize) -> Option<QueryEncryptRequest> {
+fn construct_request(req: &EncryptionRequest) -> Option<QueryEncryptRequest> {
+    // the fee is computed for the size of the obfuscated transaction (as when it's validated)
     let (txid, sealed, tx_inputs, tx_size, op_sig) = match req {
-        // TODO: are the size estimates ok?
-        EncryptionRequest::TransferTx(tx, _) => {
+        EncryptionRequest::TransferTx(tx, witness) => {
             let txid = tx.id();
             let sealed = SealedData::seal(&req.encode(), txid).ok();
             let tx_inputs = Some(tx.inputs.clone());
@@ -110,11 +112,11 @@ fn construct_request(req: &EncryptionRequest, req_len: usize) -> Option<QueryEnc
                 txid,
                 sealed,
                 tx_inputs,
-                req_len + 34 * tx.inputs.len() + 74,
+                mock_obfuscated_transfer(tx, witness).encode().len(),
                 None,
             )
         }
-        EncryptionRequest::DepositStake(tx, _) => {
+        EncryptionRequest::DepositStake(tx, witness) => {
             let txid = tx.id();
             let sealed = SealedData::seal(&req.encode(), txid).ok();
             let tx_inputs = Some(tx.inputs.clone());
@@ -122,14 +124,20 @@ fn construct_request(req: &EncryptionRequest, req_len: usize) -> Option<QueryEnc
                 txid,
                 sealed,
                 tx_inputs,
-                req_len + 34 * tx.inputs.len() + 74,
+                mock_obfuscated_deposit(tx, witness).encode().len(),
                 None,
             )
         }
         EncryptionRequest::WithdrawStake(tx, witness) => {
             let txid = tx.id();
             let sealed = SealedData::seal(&req.encode(), txid).ok();
-            (txid, sealed, None, req_len + 73, Some(witness.clone()))
+            (
+                txid,
+                sealed,
+                None,
+                mock_obfuscated_withdraw(tx, witness).encode().len(),
+                Some(witness.clone()),
+            )
         }
     };
     sealed.map(|sealed_enc_request| QueryEncryptRequest {
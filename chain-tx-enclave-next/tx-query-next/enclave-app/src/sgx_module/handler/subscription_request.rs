@@ -0,0 +1,123 @@
+use std::{
+    convert::TryInto,
+    io::{Read, Write},
+    net::TcpStream,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use parity_scale_codec::{Decode, Encode};
+
+use chain_core::{common::H256, state::tendermint::BlockHeight, tx::data::TxId};
+use enclave_protocol::{
+    EnclaveRequest, EnclaveResponse, SealedLog, SubscriptionNotification, SubscriptionRequest,
+    MAX_SUBSCRIPTION_TIMEOUT_SECS,
+};
+
+use super::decryption_request::unseal_readable_tx;
+
+/// Maximum number of blocks requested from chain-abci at once
+pub(super) const MAX_HEIGHTS_PER_REQUEST: u32 = 100;
+/// Time between the checks for newly committed blocks
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+pub fn verify_subscription_request(
+    subscription_request: &SubscriptionRequest,
+    challenge: H256,
+) -> bool {
+    let secp = secp256k1::SECP256K1;
+    subscription_request.verify(&secp, challenge).is_ok()
+}
+
+/// Waits (at most the requested timeout) for transactions readable by the view key
+/// to be committed after the requested height
+pub fn handle_subscription_request(
+    subscription_request: &SubscriptionRequest,
+    chain_data_stream: Arc<Mutex<TcpStream>>,
+) -> Result<SubscriptionNotification, String> {
+    let view_key = subscription_request.body.view_key;
+    let timeout_secs = subscription_request
+        .body
+        .timeout_secs
+        .min(MAX_SUBSCRIPTION_TIMEOUT_SECS);
+    let deadline = Instant::now() + Duration::from_secs(timeout_secs.into());
+    let mut last_height = subscription_request.body.after_height;
+
+    loop {
+        let (checked_height, sealed_txs) =
+            get_committed_sealed_txs(&chain_data_stream, last_height, MAX_HEIGHTS_PER_REQUEST)?;
+
+        let mut txs = Vec::new();
+        for (height, txid, sealed_log) in sealed_txs {
+            if let Some(otx) = unseal_readable_tx(&txid, &sealed_log, &view_key)? {
+                txs.push((height, otx));
+            }
+        }
+        let caught_up = checked_height.value()
+            < last_height
+                .value()
+                .saturating_add(MAX_HEIGHTS_PER_REQUEST.into());
+        last_height = checked_height;
+
+        if !txs.is_empty() || Instant::now() >= deadline {
+            return Ok(SubscriptionNotification { last_height, txs });
+        }
+        if caught_up {
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+}
+
+/// Requests the sealed transactions committed after the height from chain-abci
+/// (the stream is only locked during the request)
+pub(super) fn get_committed_sealed_txs(
+    chain_data_stream: &Mutex<TcpStream>,
+    after_height: BlockHeight,
+    max_heights: u32,
+) -> Result<(BlockHeight, Vec<(BlockHeight, TxId, SealedLog)>), String> {
+    let enclave_request = EnclaveRequest::GetCommittedSealedTxs {
+        after_height,
+        max_heights,
+    }
+    .encode();
+
+    let mut chain_data_stream = chain_data_stream.lock().unwrap();
+
+    // Send request to chain-abci
+    chain_data_stream
+        .write_all(&enclave_request)
+        .map_err(|err| format!("Error while writing request to chain-abci: {}", err))?;
+
+    // Read reponse length from chain-abci (little endian u32 bytes)
+    let mut response_len = [0u8; 4];
+    chain_data_stream
+        .read_exact(&mut response_len)
+        .map_err(|err| {
+            format!(
+                "Error while reading reponse length from chain-abci: {}",
+                err
+            )
+        })?;
+
+    let response_len: usize = u32::from_le_bytes(response_len)
+        .try_into()
+        .map_err(|_| "Response length exceeds `usize` bounds".to_owned())?;
+    if response_len == 0 {
+        return Err("Unexpected response from chain-abci".to_owned());
+    }
+    // Read result from chain-abci
+    let mut result_buf = vec![0u8; response_len];
+    chain_data_stream
+        .read_exact(&mut result_buf)
+        .map_err(|err| format!("Error while reading response from chain-abci: {}", err))?;
+
+    match EnclaveResponse::decode(&mut result_buf.as_ref()) {
+        Ok(EnclaveResponse::GetCommittedSealedTxs { last_height, txs }) => Ok((last_height, txs)),
+        Ok(_) => Err("Unexpected response from chain-abci".to_owned()),
+        Err(err) => Err(format!(
+            "Error while decoding response from chain-abci: {}",
+            err
+        )),
+    }
+}
@@ -1,6 +1,7 @@
 use parity_scale_codec::{Decode, Encode};
 
 use chain_core::state::tendermint::BlockHeight;
+use chain_core::tx::fee::OBFUSCATION_TAG_LENGTH;
 use chain_core::tx::{data::TxId, PlainTxAux, TxObfuscated, TxWithOutputs};
 use chain_tx_validation::Error;
 
@@ -13,7 +14,7 @@ pub fn decrypt(payload: &TxObfuscated) -> Result<PlainTxAux, Error> {
 }
 
 fn unpad_payload(payload: &[u8]) -> Result<&[u8], Error> {
-    if let Some(n) = payload.len().checked_sub(16) {
+    if let Some(n) = payload.len().checked_sub(OBFUSCATION_TAG_LENGTH) {
         Ok(&payload[0..n])
     } else {
         Err(Error::EnclaveRejected)
@@ -31,9 +32,9 @@ pub fn unseal(payload: &[u8]) -> Result<TxWithOutputs, Error> {
     TxWithOutputs::decode(&mut bytes.as_slice()).map_err(|_| Error::EnclaveRejected)
 }
 fn pad_payload(payload: &[u8]) -> Vec<u8> {
-    let mut result = Vec::with_capacity(payload.len() + 16);
+    let mut result = Vec::with_capacity(payload.len() + OBFUSCATION_TAG_LENGTH);
     result.extend_from_slice(payload);
-    result.extend_from_slice(&[0; 16]);
+    result.extend_from_slice(&[0; OBFUSCATION_TAG_LENGTH]);
     result
 }
 pub fn encrypt_payload(plain: &PlainTxAux) -> Vec<u8> {
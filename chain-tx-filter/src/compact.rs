@@ -0,0 +1,313 @@
+//! # Compact block filter
+//! BIP158-style Golomb-coded set over the public parts of a block's transactions:
+//! spent transaction outputs, staked state addresses whose state changed and transaction ids.
+//! Unlike the view key-based `BlockFilter` (which is produced inside the enclave),
+//! it can be computed from the public data alone, so light wallets can test
+//! whether a block is relevant to the outputs / staking addresses they already know about.
+use chain_core::init::address::keccak256;
+use chain_core::state::account::StakedStateAddress;
+use chain_core::state::tendermint::BlockHeight;
+use chain_core::tx::data::input::TxoPointer;
+use chain_core::tx::data::TxId;
+use parity_scale_codec::Encode;
+use std::convert::TryFrom;
+use std::prelude::v1::Vec;
+
+/// Golomb-Rice coding parameter (as in BIP158)
+const P: u8 = 19;
+/// Inverse false positive rate (as in BIP158)
+const M: u64 = 784_931;
+
+/// domain separation of the item kinds
+const INPUT_TAG: u8 = 0;
+const STAKING_ADDRESS_TAG: u8 = 1;
+const TXID_TAG: u8 = 2;
+
+/// Items of a compact block filter
+#[derive(Default, Debug)]
+pub struct CompactFilterBuilder {
+    items: Vec<Vec<u8>>,
+}
+
+impl CompactFilterBuilder {
+    /// adds a spent transaction output
+    pub fn add_input(&mut self, input: &TxoPointer) {
+        self.items.push(input_item(input));
+    }
+
+    /// adds a staked state address whose state changed
+    pub fn add_staking_address(&mut self, address: &StakedStateAddress) {
+        self.items.push(staking_address_item(address));
+    }
+
+    /// adds a transaction id
+    pub fn add_txid(&mut self, txid: &TxId) {
+        self.items.push(txid_item(txid));
+    }
+
+    /// builds the filter for the block at the given height
+    pub fn build(self, height: BlockHeight) -> CompactFilter {
+        let key = filter_key(height);
+        let n = self.items.len() as u64;
+        let mut values: Vec<u64> = self
+            .items
+            .iter()
+            .map(|item| hash_to_range(&key, item, n * M))
+            .collect();
+        values.sort_unstable();
+        values.dedup();
+
+        let mut writer = BitWriter::default();
+        let mut last = 0;
+        for value in values.iter() {
+            let delta = value - last;
+            last = *value;
+            for _ in 0..(delta >> P) {
+                writer.write_bit(true);
+            }
+            writer.write_bit(false);
+            writer.write_bits(delta, P);
+        }
+        CompactFilter {
+            n: values.len() as u32,
+            modulus: n * M,
+            data: writer.bytes,
+        }
+    }
+}
+
+/// Golomb-coded set of a block's public transaction data
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompactFilter {
+    /// number of (distinct) elements in the set
+    n: u32,
+    /// range the items are hashed into
+    modulus: u64,
+    /// Golomb-Rice coded deltas
+    data: Vec<u8>,
+}
+
+impl CompactFilter {
+    /// number of elements in the filter
+    pub fn len(&self) -> usize {
+        self.n as usize
+    }
+
+    /// true if the block had no public transaction data
+    pub fn is_empty(&self) -> bool {
+        self.n == 0
+    }
+
+    /// serializes the filter (stored and served via the "compact-filter" ABCI query)
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(12 + self.data.len());
+        bytes.extend_from_slice(&self.n.to_le_bytes());
+        bytes.extend_from_slice(&self.modulus.to_le_bytes());
+        bytes.extend_from_slice(&self.data);
+        bytes
+    }
+
+    /// tests if any of the spent outputs may be in the filter
+    pub fn match_any_input(&self, height: BlockHeight, inputs: &[TxoPointer]) -> bool {
+        self.match_any(height, inputs.iter().map(input_item))
+    }
+
+    /// tests if any of the staked state addresses may be in the filter
+    pub fn match_any_staking_address(
+        &self,
+        height: BlockHeight,
+        addresses: &[StakedStateAddress],
+    ) -> bool {
+        self.match_any(height, addresses.iter().map(staking_address_item))
+    }
+
+    /// tests if the transaction id may be in the filter
+    pub fn match_txid(&self, height: BlockHeight, txid: &TxId) -> bool {
+        self.match_any(height, std::iter::once(txid_item(txid)))
+    }
+
+    /// true = maybe present
+    /// false = not present
+    fn match_any(&self, height: BlockHeight, items: impl Iterator<Item = Vec<u8>>) -> bool {
+        if self.n == 0 {
+            return false;
+        }
+        let key = filter_key(height);
+        let mut queries: Vec<u64> = items
+            .map(|item| hash_to_range(&key, &item, self.modulus))
+            .collect();
+        queries.sort_unstable();
+
+        let mut reader = BitReader::new(&self.data);
+        let mut value = 0u64;
+        let mut queries = queries.into_iter().peekable();
+        for _ in 0..self.n {
+            let mut quotient = 0u64;
+            loop {
+                match reader.read_bit() {
+                    Some(true) => quotient += 1,
+                    Some(false) => break,
+                    None => return false,
+                }
+            }
+            let remainder = match reader.read_bits(P) {
+                Some(remainder) => remainder,
+                None => return false,
+            };
+            value += (quotient << P) + remainder;
+            while let Some(query) = queries.peek() {
+                if *query < value {
+                    queries.next();
+                } else {
+                    break;
+                }
+            }
+            match queries.peek() {
+                Some(query) if *query == value => return true,
+                Some(_) => {}
+                None => return false,
+            }
+        }
+        false
+    }
+}
+
+impl TryFrom<&[u8]> for CompactFilter {
+    type Error = &'static str;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        if value.len() < 12 {
+            return Err("Invalid length, compact filter header is expected to be 12-bytes");
+        }
+        let mut n = [0u8; 4];
+        n.copy_from_slice(&value[0..4]);
+        let mut modulus = [0u8; 8];
+        modulus.copy_from_slice(&value[4..12]);
+        Ok(CompactFilter {
+            n: u32::from_le_bytes(n),
+            modulus: u64::from_le_bytes(modulus),
+            data: value[12..].to_vec(),
+        })
+    }
+}
+
+fn input_item(input: &TxoPointer) -> Vec<u8> {
+    let mut item = vec![INPUT_TAG];
+    item.extend(input.encode());
+    item
+}
+
+fn staking_address_item(address: &StakedStateAddress) -> Vec<u8> {
+    let mut item = vec![STAKING_ADDRESS_TAG];
+    item.extend(address.encode());
+    item
+}
+
+fn txid_item(txid: &TxId) -> Vec<u8> {
+    let mut item = vec![TXID_TAG];
+    item.extend_from_slice(txid);
+    item
+}
+
+/// per-block hashing key (so that false positives differ between blocks)
+fn filter_key(height: BlockHeight) -> [u8; 32] {
+    let mut data = b"compact-filter".to_vec();
+    data.extend(height.encode());
+    keccak256(&data)
+}
+
+/// hashes the item uniformly into [0, modulus)
+fn hash_to_range(key: &[u8; 32], item: &[u8], modulus: u64) -> u64 {
+    let mut data = key.to_vec();
+    data.extend_from_slice(item);
+    let hash = keccak256(&data);
+    let mut value = [0u8; 8];
+    value.copy_from_slice(&hash[0..8]);
+    ((u128::from(u64::from_le_bytes(value)) * u128::from(modulus)) >> 64) as u64
+}
+
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    used: u8,
+}
+
+impl BitWriter {
+    fn write_bit(&mut self, bit: bool) {
+        if self.used == 0 {
+            self.bytes.push(0);
+        }
+        if bit {
+            let last = self.bytes.len() - 1;
+            self.bytes[last] |= 0x80 >> self.used;
+        }
+        self.used = (self.used + 1) % 8;
+    }
+
+    fn write_bits(&mut self, value: u64, count: u8) {
+        for i in (0..count).rev() {
+            self.write_bit((value >> i) & 1 == 1);
+        }
+    }
+}
+
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        BitReader { bytes, position: 0 }
+    }
+
+    fn read_bit(&mut self) -> Option<bool> {
+        let byte = self.bytes.get(self.position / 8)?;
+        let bit = byte & (0x80 >> (self.position % 8)) != 0;
+        self.position += 1;
+        Some(bit)
+    }
+
+    fn read_bits(&mut self, count: u8) -> Option<u64> {
+        let mut value = 0;
+        for _ in 0..count {
+            value = (value << 1) | u64::from(self.read_bit()?);
+        }
+        Some(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chain_core::init::address::RedeemAddress;
+
+    #[test]
+    fn check_filter_matching() {
+        let height = BlockHeight::new(10);
+        let spent = TxoPointer::new([1; 32], 0);
+        let address = StakedStateAddress::BasicRedeem(RedeemAddress::from([2u8; 20]));
+        let mut builder = CompactFilterBuilder::default();
+        builder.add_input(&spent);
+        builder.add_staking_address(&address);
+        for i in 0..50u8 {
+            builder.add_txid(&[i; 32]);
+        }
+        let filter = builder.build(height);
+        let filter = CompactFilter::try_from(filter.to_bytes().as_slice()).unwrap();
+        assert_eq!(52, filter.len());
+
+        assert!(filter.match_any_input(height, &[TxoPointer::new([3; 32], 1), spent]));
+        assert!(filter.match_any_staking_address(height, &[address]));
+        assert!(filter.match_txid(height, &[49; 32]));
+        assert!(!filter.match_any_input(height, &[TxoPointer::new([3; 32], 1)]));
+        assert!(!filter.match_txid(height, &[100; 32]));
+    }
+
+    #[test]
+    fn check_empty_filter() {
+        let filter = CompactFilterBuilder::default().build(BlockHeight::genesis());
+        assert!(filter.is_empty());
+        assert!(!filter.match_txid(BlockHeight::genesis(), &[0; 32]));
+    }
+}
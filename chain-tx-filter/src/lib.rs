@@ -1,5 +1,7 @@
+pub mod compact;
 mod filter;
 use chain_core::common::TendermintEventKey;
+pub use compact::{CompactFilter, CompactFilterBuilder};
 use filter::Bloom;
 use filter::H2048;
 use secp256k1::key::PublicKey;
@@ -12,7 +12,7 @@ use chain_core::tx::data::output::TxOut;
 use chain_core::tx::data::Tx;
 use chain_core::tx::data::TxId;
 use chain_core::tx::fee::Fee;
-use chain_core::tx::witness::TxWitness;
+use chain_core::tx::witness::{TxInWitness, TxWitness};
 use chain_core::tx::TransactionId;
 pub use chain_core::tx::TxWithOutputs;
 pub use chain_core::ChainInfo;
@@ -73,6 +73,10 @@ pub enum Error {
     AccountIncorrectNonce,
     /// Account is jailed
     AccountJailed,
+    /// witness is valid for a different input's address (witnesses must follow the order of inputs)
+    MisplacedWitness,
+    /// staked state is frozen by a council decision
+    AccountFrozen,
 }
 
 impl fmt::Display for Error {
@@ -122,6 +126,11 @@ impl fmt::Display for Error {
             AccountIncorrectNonce => write!(f, "incorrect transaction count for account operation"),
             MismatchAccountAddress => write!(f, "mismatch account address"),
             AccountJailed => write!(f, "account is jailed"),
+            MisplacedWitness => write!(
+                f,
+                "witness belongs to a different input (witnesses must follow the order of inputs)"
+            ),
+            AccountFrozen => write!(f, "account is frozen by a council decision"),
         }
     }
 }
@@ -173,6 +182,61 @@ pub fn check_inputs_basic(inputs: &[TxoPointer], witness: &TxWitness) -> Result<
     Ok(())
 }
 
+/// Canonical correspondence of inputs and witnesses: the i-th witness signs for the address
+/// of the i-th input. Every input needs its own witness, even if several inputs are spent from
+/// the same address (the witnesses of such inputs may then be identical).
+/// Only called on the error path, in order to report signatures that are valid,
+/// but in the wrong slot.
+fn is_misplaced_witness(
+    main_txid: &TxId,
+    inputs: &[TxoPointer],
+    transaction_inputs: &[TxWithOutputs],
+    witness: &TxInWitness,
+) -> bool {
+    inputs
+        .iter()
+        .zip(transaction_inputs.iter())
+        .filter_map(|(txin, tx)| tx.outputs().get(txin.index as usize))
+        .any(|txout| verify_tx_address(witness, main_txid, &txout.address).is_ok())
+}
+
+/// Verifies the witness of each input against the address of the spent output
+/// (`None` if the input doesn't point to an existing output); only called after
+/// the cheap checks of all inputs passed (see `check_inputs`).
+/// With the `parallel` feature, the signatures are verified on the rayon thread pool;
+/// the results are in the input order either way, so the reported error stays deterministic.
+fn verify_input_witnesses(
+    main_txid: &TxId,
+    inputs: &[TxoPointer],
+    transaction_inputs: &[TxWithOutputs],
+    witness: &TxWitness,
+) -> Vec<Option<bool>> {
+    let verify = |((txin, tx), in_witness): ((&TxoPointer, &TxWithOutputs), &TxInWitness)| {
+        tx.outputs()
+            .get(txin.index as usize)
+            .map(|txout| verify_tx_address(in_witness, main_txid, &txout.address).is_ok())
+    };
+    #[cfg(feature = "parallel")]
+    {
+        use rayon::prelude::*;
+        inputs
+            .par_iter()
+            .zip(transaction_inputs.par_iter())
+            .zip(witness.par_iter())
+            .map(verify)
+            .collect()
+    }
+    #[cfg(not(feature = "parallel"))]
+    {
+        inputs
+            .iter()
+            .zip(transaction_inputs.iter())
+            .zip(witness.iter())
+            .map(verify)
+            .collect()
+    }
+}
+
 fn check_inputs(
     main_txid: &TxId,
     inputs: &[TxoPointer],
@@ -181,9 +245,8 @@ fn check_inputs(
     transaction_inputs: Vec<TxWithOutputs>,
 ) -> Result<Coin, Error> {
     let mut incoins = Coin::zero();
-    // verify that txids of inputs correspond to the owner/signer
-    // and it'd check they are not spent
-    // TODO: zip3 / itertools?
+    // verify that txids of inputs correspond to the spent transactions
+    // and that the outputs are not timelocked (before verifying any signature)
     for (txin, (tx, in_witness)) in inputs
         .iter()
         .zip(transaction_inputs.iter().zip(witness.iter()))
@@ -191,20 +254,19 @@ fn check_inputs(
         if txin.id != tx.id() {
             return Err(Error::InvalidInput);
         }
-        let input_index = txin.index as usize;
-        let outputs = tx.outputs();
-        if input_index >= outputs.len() {
-            return Err(Error::InvalidInput);
-        }
-        let txout = &outputs[input_index];
+        let txout = match tx.outputs().get(txin.index as usize) {
+            Some(txout) => txout,
+            None => return Err(Error::InvalidInput),
+        };
         if let Some(valid_from) = &txout.valid_from {
             if *valid_from > extra_info.block_time {
                 return Err(Error::OutputInTimelock);
             }
         }
-        let wv = verify_tx_address(&in_witness, main_txid, &txout.address);
-        if let Err(_e) = wv {
-            return Err(Error::EcdsaCrypto); // FIXME: Err(Error::EcdsaCrypto(e));
+        if let Some(valid_from) = in_witness.valid_from() {
+            if valid_from > extra_info.block_time {
+                return Err(Error::OutputInTimelock);
+            }
         }
         let sum = incoins + txout.value;
         if let Err(_e) = sum {
@@ -213,6 +275,16 @@ fn check_inputs(
             incoins = sum.unwrap();
         }
     }
+    // verify that the witnesses correspond to the owners of the spent outputs
+    let witnesses_valid = verify_input_witnesses(main_txid, inputs, &transaction_inputs, witness);
+    for (in_witness, witness_valid) in witness.iter().zip(witnesses_valid) {
+        if witness_valid != Some(true) {
+            if is_misplaced_witness(main_txid, inputs, &transaction_inputs, &in_witness) {
+                return Err(Error::MisplacedWitness);
+            }
+            return Err(Error::EcdsaCrypto); // FIXME: Err(Error::EcdsaCrypto(e));
+        }
+    }
     Ok(incoins)
 }
 
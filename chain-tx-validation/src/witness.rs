@@ -1,12 +1,18 @@
+use std::collections::BTreeSet;
+
 use chain_core::init::address::RedeemAddress;
-use chain_core::state::account::{StakedStateAddress, StakedStateOpWitness};
+use chain_core::state::account::{
+    threshold_staking_address, StakedStateAddress, StakedStateOpWitness, ThresholdSignature,
+};
 use chain_core::tx::data::address::ExtendedAddr;
 use chain_core::tx::data::TxId;
+use chain_core::tx::witness::tree::timelocked_leaf;
 use chain_core::tx::witness::TxInWitness;
 use secp256k1::{key::XOnlyPublicKey, schnorrsig::schnorr_verify, Message};
 
 /// verify a given extended address is associated to the witness
 /// and the signature against the given transaction `Tx`
+/// (the time of timelocked witnesses is checked by the caller)
 /// TODO: capture possible errors in enum?
 ///
 pub fn verify_tx_address(
@@ -30,6 +36,21 @@ pub fn verify_tx_address(
                 )
             }
         }
+        (
+            TxInWitness::TimelockedTreeSig(sig, key, valid_from, proof),
+            ExtendedAddr::OrTree(root_hash),
+        ) => {
+            if *proof.value() != timelocked_leaf(key, *valid_from) || !proof.verify(root_hash) {
+                Err(secp256k1::Error::InvalidPublicKey)
+            } else {
+                schnorr_verify(
+                    &secp,
+                    &message,
+                    &sig,
+                    &XOnlyPublicKey::from_slice(key.as_bytes())?,
+                )
+            }
+        }
     }
 }
 
@@ -48,9 +69,46 @@ pub fn verify_tx_recover_address(
             secp.verify(&message, &sig.to_standard(), &pk)?;
             Ok(StakedStateAddress::BasicRedeem(RedeemAddress::from(&pk)))
         }
+        StakedStateOpWitness::ThresholdSig(threshold_sig) => {
+            verify_threshold_signature(threshold_sig, txid)
+        }
     }
 }
 
+/// verify that at least `threshold` distinct keys of the key set signed the transaction
+/// and returns the address of the key set
+fn verify_threshold_signature(
+    threshold_sig: &ThresholdSignature,
+    txid: &TxId,
+) -> Result<StakedStateAddress, secp256k1::Error> {
+    let threshold = usize::from(threshold_sig.threshold);
+    if threshold == 0 || threshold_sig.signatures.len() < threshold {
+        return Err(secp256k1::Error::InvalidSignature);
+    }
+    let root = threshold_sig
+        .key_set_root()
+        .ok_or(secp256k1::Error::InvalidPublicKey)?;
+    let secp = secp256k1::SECP256K1;
+    let message = Message::from_slice(txid)?;
+    let mut signers = BTreeSet::new();
+    for (sig, proof) in threshold_sig.signatures.iter() {
+        if !proof.verify(&root) {
+            return Err(secp256k1::Error::InvalidPublicKey);
+        }
+        // the same key can't be counted twice towards the threshold
+        if !signers.insert(proof.value().clone()) {
+            return Err(secp256k1::Error::InvalidSignature);
+        }
+        schnorr_verify(
+            &secp,
+            &message,
+            &sig,
+            &XOnlyPublicKey::from_slice(proof.value().as_bytes())?,
+        )?;
+    }
+    Ok(threshold_staking_address(threshold_sig.threshold, &root))
+}
+
 #[cfg(test)]
 pub mod tests {
     use super::*;
@@ -59,6 +117,7 @@ pub mod tests {
     use secp256k1::{PublicKey, SecretKey};
 
     use chain_core::common::MerkleTree;
+    use chain_core::tx::data::attribute::TxAttributes;
     use chain_core::tx::data::Tx;
     use chain_core::tx::witness::tree::RawXOnlyPubkey;
     use chain_core::tx::TransactionId;
@@ -162,6 +221,58 @@ pub mod tests {
         assert!(verify_tx_address(&witness, &transation.id(), &address).is_err())
     }
 
+    #[test]
+    fn check_timelocked_leaf_verify() {
+        let transation = Tx::new();
+        let secp = secp256k1::SECP256K1;
+        let message = Message::from_slice(&transation.id()).unwrap();
+
+        let secret_keys = [
+            SecretKey::from_slice(&[0xcd; 32]).expect("Unable to create secret key"),
+            SecretKey::from_slice(&[0xde; 32]).expect("Unable to create secret key"),
+        ];
+        let public_keys = [
+            RawXOnlyPubkey::from(
+                XOnlyPublicKey::from_secret_key(&secp, &secret_keys[0]).serialize(),
+            ),
+            RawXOnlyPubkey::from(
+                XOnlyPublicKey::from_secret_key(&secp, &secret_keys[1]).serialize(),
+            ),
+        ];
+        let leaf = timelocked_leaf(&public_keys[0], 100);
+        let merkle_tree = MerkleTree::new(vec![leaf.clone(), public_keys[1].clone()]);
+        let address = ExtendedAddr::OrTree(merkle_tree.root_hash());
+        let proof = merkle_tree.generate_proof(leaf).unwrap();
+        let signature = schnorr_sign(&secp, &message, &secret_keys[0], &mut rand::thread_rng());
+
+        let witness = TxInWitness::TimelockedTreeSig(
+            signature.clone(),
+            public_keys[0].clone(),
+            100,
+            proof.clone(),
+        );
+        assert_eq!(Some(100), witness.valid_from());
+        assert!(verify_tx_address(&witness, &transation.id(), &address).is_ok());
+
+        // the time is part of the leaf
+        let witness = TxInWitness::TimelockedTreeSig(
+            signature.clone(),
+            public_keys[0].clone(),
+            99,
+            proof.clone(),
+        );
+        assert!(verify_tx_address(&witness, &transation.id(), &address).is_err());
+        // the key can't sign with the timelocked leaf as a plain key
+        let witness = TxInWitness::TreeSig(signature, proof);
+        assert!(verify_tx_address(&witness, &transation.id(), &address).is_err());
+        // other leaves are plain keys
+        let witness = TxInWitness::TreeSig(
+            schnorr_sign(&secp, &message, &secret_keys[1], &mut rand::thread_rng()),
+            merkle_tree.generate_proof(public_keys[1].clone()).unwrap(),
+        );
+        assert!(verify_tx_address(&witness, &transation.id(), &address).is_ok());
+    }
+
     #[test]
     fn check_staked_verify() {
         let transation = Tx::new();
@@ -183,4 +294,53 @@ pub mod tests {
 
         assert_eq!(address, recovered_address);
     }
+
+    #[test]
+    fn check_2_of_3_threshold_verify() {
+        let transation = Tx::new();
+        let secp = secp256k1::SECP256K1;
+        let message = Message::from_slice(&transation.id()).unwrap();
+
+        let secret_keys: Vec<SecretKey> = [0xcd, 0xde, 0xef]
+            .iter()
+            .map(|b| SecretKey::from_slice(&[*b; 32]).expect("Unable to create secret key"))
+            .collect();
+        let public_keys: Vec<RawXOnlyPubkey> = secret_keys
+            .iter()
+            .map(|sk| RawXOnlyPubkey::from(XOnlyPublicKey::from_secret_key(&secp, sk).serialize()))
+            .collect();
+        let merkle_tree = MerkleTree::new(public_keys.clone());
+        let address = threshold_staking_address(2, &merkle_tree.root_hash());
+
+        let sign = |i: usize| {
+            (
+                schnorr_sign(&secp, &message, &secret_keys[i], &mut rand::thread_rng()),
+                merkle_tree.generate_proof(public_keys[i].clone()).unwrap(),
+            )
+        };
+        let witness = |signatures| {
+            StakedStateOpWitness::ThresholdSig(ThresholdSignature {
+                threshold: 2,
+                signatures,
+            })
+        };
+
+        let recovered_address =
+            verify_tx_recover_address(&witness(vec![sign(0), sign(2)]), &transation.id())
+                .expect("Unable to verify threshold signature");
+        assert_eq!(address, recovered_address);
+
+        // not enough signatures
+        assert!(verify_tx_recover_address(&witness(vec![sign(1)]), &transation.id()).is_err());
+        // the same key twice
+        assert!(
+            verify_tx_recover_address(&witness(vec![sign(1), sign(1)]), &transation.id()).is_err()
+        );
+        // signature of another transaction
+        let other = Tx {
+            attributes: TxAttributes::new(1),
+            ..Default::default()
+        };
+        assert!(verify_tx_recover_address(&witness(vec![sign(0), sign(1)]), &other.id()).is_err());
+    }
 }
@@ -1,4 +1,5 @@
 mod address_command;
+mod daemon_command;
 mod multisig_command;
 mod transaction_command;
 mod wallet_command;
@@ -19,23 +20,22 @@ use structopt::StructOpt;
 use chain_core::init::coin::Coin;
 use chain_core::state::account::{NodeState, StakedStateAddress};
 use client_common::storage::SledStorage;
-#[cfg(not(feature = "mock-enclave"))]
-use client_common::tendermint::types::AbciQueryExt;
 use client_common::tendermint::types::GenesisExt;
 use client_common::tendermint::{Client, WebsocketRpcClient};
 use client_common::TransactionObfuscation;
 use client_common::{ErrorKind, Result, ResultExt, SecKey, Storage};
 use client_core::signer::WalletSignerManager;
 use client_core::transaction_builder::DefaultWalletTransactionBuilder;
-use client_core::types::BalanceChange;
+use client_core::types::{BalanceChange, WalletBalance};
 use client_core::wallet::syncer::{
     spawn_light_client_supervisor, Handle, ObfuscationSyncerConfig, ProgressReport, SyncerOptions,
     WalletSyncer,
 };
-use client_core::wallet::{DefaultWalletClient, WalletClient};
+use client_core::wallet::{DefaultWalletClient, WalletClient, WalletRequest};
 use client_network::network_ops::{DefaultNetworkOpsClient, NetworkOpsClient};
 
 use self::address_command::AddressCommand;
+use self::daemon_command::{is_daemon_running, query_daemon, DaemonCommand, DaemonRequest};
 use self::multisig_command::MultiSigCommand;
 use self::transaction_command::TransactionCommand;
 use self::wallet_command::WalletCommand;
@@ -45,7 +45,9 @@ use chain_core::tx::fee::LinearFee;
 use client_core::hd_wallet::HardwareKind;
 #[cfg(feature = "mock-hardware-wallet")]
 use client_core::service::LedgerServiceZemu;
-use client_core::service::{HwKeyService, LedgerServiceHID, WalletService};
+use client_core::service::{
+    HwKeyService, LedgerServiceHID, LegacyRecord, StorageMigrationService, WalletService,
+};
 use once_cell::sync::Lazy;
 use std::env;
 
@@ -90,6 +92,14 @@ ENVIRONMENT VARIABLES:
     CRYPTO_CLIENT_STORAGE           Storage directory (Default: `.storage`)
     CRYPTO_CLIENT_TENDERMINT        Websocket endpoint for tendermint (Default: `ws://localhost:26657/websocket`)
     CRYPTO_GENESIS_FINGERPRINT             Set the genesis fingerprint(Optional)
+    CRYPTO_CLIENT_TX_QUERY          Comma-separated tx-query endpoints, tried in order (Default: address advertised by the node)
+    CRYPTO_CLIENT_TX_QUERY_MRENCLAVE           Hex of the accepted tx-query MRENCLAVE (Optional)
+    CRYPTO_CLIENT_TX_QUERY_PREVIOUS_MRENCLAVE  Hex of the accepted previous tx-query MRENCLAVE (Optional)
+    CRYPTO_CLIENT_TX_QUERY_MIN_TCB  Minimum TCB level: up-to-date|sw-hardening-needed|configuration-needed|out-of-date (Default: `sw-hardening-needed`)
+    CRYPTO_CLIENT_PASSPHRASE_MIN_LENGTH   Minimum number of characters of wallet passphrases (Default: `0`)
+    CRYPTO_CLIENT_PASSPHRASE_MIN_SCORE    Minimum passphrase strength score 0-4 (Default: `3` in release builds)
+    CRYPTO_CLIENT_PASSPHRASE_BREACH_LIST  Path to a list of breached passphrases, one per line (Optional)
+    CRYPTO_CLIENT_DAEMON_SOCKET     Socket of the sync daemon (Default: `daemon.sock` in the storage directory, `127.0.0.1:26660` on Windows)
 "#
 )]
 pub enum Command {
@@ -246,6 +256,12 @@ pub enum Command {
             help = "Disable address recovery, which is not necessary, if addresses already exist"
         )]
         disable_address_recovery: bool,
+        #[structopt(
+            name = "enable-block-filters",
+            long,
+            help = "Only download the blocks whose filters may match the wallet (less bandwidth)"
+        )]
+        enable_block_filters: bool,
         #[structopt(
             name = "block-height-ensure",
             long,
@@ -259,31 +275,38 @@ pub enum Command {
         #[structopt(subcommand)]
         multisig_command: MultiSigCommand,
     },
+    #[structopt(
+        name = "migrate",
+        about = "Migrates wallets written by older client versions to the current storage layout"
+    )]
+    Migrate {
+        #[structopt(
+            name = "dry-run",
+            long,
+            help = "Only reports the legacy records without converting them"
+        )]
+        dry_run: bool,
+        #[structopt(
+            name = "rollback",
+            long,
+            help = "Restores the records modified by the last migration from its backup"
+        )]
+        rollback: bool,
+    },
+    #[structopt(
+        name = "daemon",
+        about = "Keeps wallets synchronized in the background for the other commands"
+    )]
+    Daemon {
+        #[structopt(subcommand)]
+        daemon_command: DaemonCommand,
+    },
 }
 
 /// normal
 #[cfg(not(feature = "mock-enclave"))]
 fn get_tx_query(tendermint_client: WebsocketRpcClient) -> Result<DefaultTransactionObfuscation> {
-    let result = tendermint_client
-        .query("txquery", &[], None, false)?
-        .bytes();
-    let address = std::str::from_utf8(&result).chain(|| {
-        (
-            ErrorKind::ConnectionError,
-            "Unable to decode txquery address",
-        )
-    })?;
-    if let Some(hostname) = address.split(':').next() {
-        Ok(DefaultTransactionObfuscation::new(
-            address.to_string(),
-            hostname.to_string(),
-        ))
-    } else {
-        Err(client_common::Error::new(
-            ErrorKind::ConnectionError,
-            "Unable to decode txquery address",
-        ))
-    }
+    DefaultTransactionObfuscation::from_tx_query(&tendermint_client)
 }
 
 /// mock
@@ -304,6 +327,10 @@ impl Command {
                 wallet_command.execute(wallet_client)
             }
             Command::Address { address_command } => {
+                // deriving addresses from an extended public key doesn't need the wallet storage
+                if let AddressCommand::Derive { .. } = address_command {
+                    return address_command.derive();
+                }
                 let storage = SledStorage::new(storage_path())?;
                 let wallet_client = DefaultWalletClient::new_read_only(storage);
                 address_command.execute(wallet_client)
@@ -315,6 +342,17 @@ impl Command {
                 Self::get_view_key(wallet_client, name, *private)
             }
             Command::Balance { name } => {
+                // the storage is locked by the running daemon, which has the latest balance
+                if is_daemon_running() {
+                    let enckey = ask_seckey(None)?;
+                    let balance = query_daemon(&DaemonRequest::Balance {
+                        request: WalletRequest {
+                            name: name.clone(),
+                            enckey,
+                        },
+                    })?;
+                    return Self::print_balance(&balance);
+                }
                 let storage = SledStorage::new(storage_path())?;
                 let wallet_client = DefaultWalletClient::new_read_only(storage);
                 Self::get_balance(wallet_client, name)
@@ -350,7 +388,7 @@ impl Command {
                 };
                 let signer_manager =
                     WalletSignerManager::new(storage.clone(), hw_key_service.clone());
-                let fee_algorithm = tendermint_client.genesis()?.fee_policy();
+                let fee_algorithm = tendermint_client.get_fee_policy()?;
                 let transaction_obfuscation = get_tx_query(tendermint_client.clone())?;
                 let transaction_builder = DefaultWalletTransactionBuilder::new(
                     signer_manager.clone(),
@@ -398,7 +436,7 @@ impl Command {
                 let tendermint_client = WebsocketRpcClient::new(&tendermint_url())?;
                 let signer_manager =
                     WalletSignerManager::new(storage.clone(), hw_key_service.clone());
-                let fee_algorithm = tendermint_client.genesis()?.fee_policy();
+                let fee_algorithm = tendermint_client.get_fee_policy()?;
                 let transaction_obfuscation = get_tx_query(tendermint_client.clone())?;
                 let transaction_builder = DefaultWalletTransactionBuilder::new(
                     signer_manager.clone(),
@@ -429,6 +467,7 @@ impl Command {
                 enable_fast_forward,
                 disable_light_client,
                 disable_address_recovery,
+                enable_block_filters,
                 block_height_ensure,
                 light_client_peers,
                 light_client_trusting_period_seconds,
@@ -572,6 +611,7 @@ impl Command {
                         enable_fast_forward: *enable_fast_forward,
                         disable_light_client: *disable_light_client,
                         enable_address_recovery: !*disable_address_recovery,
+                        enable_block_filters: *enable_block_filters,
                         batch_size: *batch_size,
                         block_height_ensure: *block_height_ensure,
                         light_client_peers: light_client_peers_user,
@@ -595,7 +635,74 @@ impl Command {
                 let wallet_client = DefaultWalletClient::new_read_only(storage);
                 multisig_command.execute(wallet_client)
             }
+            Command::Migrate { dry_run, rollback } => {
+                let storage = SledStorage::new(storage_path())?;
+                Self::migrate(StorageMigrationService::new(storage), *dry_run, *rollback)
+            }
+            Command::Daemon { daemon_command } => daemon_command.execute(),
+        }
+    }
+
+    fn migrate<S: Storage>(
+        service: StorageMigrationService<S>,
+        dry_run: bool,
+        rollback: bool,
+    ) -> Result<()> {
+        if rollback {
+            if service.rollback()? {
+                success(&format!(
+                    "Last migration rolled back (storage version {})",
+                    service.version()?
+                ));
+            } else {
+                ask("No migration backup found");
+                println!();
+            }
+            return Ok(());
+        }
+
+        let report = if dry_run {
+            service.detect()?
+        } else {
+            service.migrate()?
+        };
+        for record in report.legacy_records.iter() {
+            match record {
+                LegacyRecord::UnindexedWallet { name } => {
+                    ask(&format!(
+                        "Wallet {} is missing from the list of wallets",
+                        name
+                    ));
+                }
+                LegacyRecord::LegacySyncState { name } => {
+                    ask(&format!(
+                        "Sync state of wallet {} is in an older encoding (to be synchronized again)",
+                        name
+                    ));
+                }
+            }
+            println!();
+        }
+        if report.applied {
+            success(&format!(
+                "Storage migrated from version {} to {} ({} legacy records)",
+                report.from_version,
+                report.to_version,
+                report.legacy_records.len()
+            ));
+        } else if report.from_version == report.to_version {
+            success(&format!(
+                "Storage is up to date (version {})",
+                report.to_version
+            ));
+        } else {
+            success(&format!(
+                "Dry run: storage version {} has {} legacy records",
+                report.from_version,
+                report.legacy_records.len()
+            ));
         }
+        Ok(())
     }
 
     fn get_staked_stake<N: NetworkOpsClient>(
@@ -720,7 +827,10 @@ impl Command {
         print_sync_warning();
 
         let balance = wallet_client.balance(name, &enckey)?;
+        Self::print_balance(&balance)
+    }
 
+    fn print_balance(balance: &WalletBalance) -> Result<()> {
         let rows = vec![
             Row::new(vec![
                 Cell::new("Total", Default::default()),
@@ -886,7 +996,7 @@ fn get_wallet_client(storage: SledStorage) -> Result<AppWalletClient> {
     let hw_key_service = HwKeyService::default();
 
     let signer_manager = WalletSignerManager::new(storage.clone(), hw_key_service.clone());
-    let fee_algorithm = tendermint_client.genesis()?.fee_policy();
+    let fee_algorithm = tendermint_client.get_fee_policy()?;
     let transaction_obfuscation = get_tx_query(tendermint_client.clone())?;
     let transaction_builder = DefaultWalletTransactionBuilder::new(
         signer_manager,
@@ -4,10 +4,16 @@ use quest::{ask, error, success, text};
 use structopt::StructOpt;
 use unicase::eq_ascii;
 
-use client_common::{error::ResultExt, Error, ErrorKind, PublicKey, Result};
-use client_core::WalletClient;
+use client_common::{error::ResultExt, Error, ErrorKind, MultiSigAddress, PublicKey, Result};
+use client_core::hd_wallet::traits::Deserialize;
+use client_core::hd_wallet::ExtendedPubKey;
+use client_core::service::HDAccountType;
+use client_core::{HDSeed, WalletClient};
 
 use crate::ask_seckey;
+use chain_core::init::address::RedeemAddress;
+use chain_core::init::network::{get_bip44_coin_type_from_network, get_network};
+use chain_core::state::account::StakedStateAddress;
 use chain_core::tx::data::address::ExtendedAddr;
 
 const ADDRESS_TYPE_VARIANTS: [&str; 3] = ["transfer", "transfer-watch", "staking"];
@@ -38,6 +44,40 @@ impl FromStr for AddressType {
     }
 }
 
+const DERIVE_ADDRESS_TYPE_VARIANTS: [&str; 2] = ["transfer", "staking"];
+const DERIVE_FORMAT_VARIANTS: [&str; 2] = ["csv", "json"];
+
+#[derive(Debug)]
+pub enum DeriveFormat {
+    Csv,
+    Json,
+}
+
+impl FromStr for DeriveFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if eq_ascii(s, "csv") {
+            Ok(DeriveFormat::Csv)
+        } else if eq_ascii(s, "json") {
+            Ok(DeriveFormat::Json)
+        } else {
+            Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Output format can either be `csv` or `json`",
+            ))
+        }
+    }
+}
+
+/// Derived address with its path and public key
+#[derive(Debug)]
+pub struct DerivedAddress {
+    pub path: String,
+    pub public_key: PublicKey,
+    pub address: String,
+}
+
 #[derive(Debug, StructOpt)]
 pub enum AddressCommand {
     #[structopt(name = "new", about = "Creates a new address")]
@@ -89,6 +129,41 @@ pub enum AddressCommand {
         )]
         reversed: bool,
     },
+    #[structopt(
+        name = "derive",
+        about = "Derives addresses from an extended public key (offline, without a wallet)"
+    )]
+    Derive {
+        #[structopt(
+            name = "extended public key",
+            short = "k",
+            long = "xpub",
+            help = "Hex encoded extended public key (public key and chain code), optionally prefixed with its path, e.g. [m/44'/394'/0'/0]<hex>; the default path is the one of the address type's account"
+        )]
+        xpub: String,
+        #[structopt(
+            name = "address type",
+            short = "t",
+            long = "type",
+            help = "Type of addresses to derive",
+            possible_values = &DERIVE_ADDRESS_TYPE_VARIANTS,
+            case_insensitive = true
+        )]
+        address_type: AddressType,
+        #[structopt(name = "from", short, long, help = "First index", default_value = "0")]
+        from: u32,
+        #[structopt(name = "count", short, long, help = "Number of addresses")]
+        count: u32,
+        #[structopt(
+            name = "format",
+            long,
+            help = "Output format",
+            possible_values = &DERIVE_FORMAT_VARIANTS,
+            case_insensitive = true,
+            default_value = "csv"
+        )]
+        format: DeriveFormat,
+    },
     #[structopt(name = "list-pub-key", about = "Shows the public keys of a wallet")]
     ListPubKey {
         #[structopt(
@@ -133,7 +208,53 @@ impl AddressCommand {
             AddressCommand::ListPubKey { name, address_type } => {
                 Self::list_pubkeys(wallet_client, name, address_type)
             }
+            AddressCommand::Derive { .. } => self.derive(),
+        }
+    }
+
+    /// Derives addresses in bulk, only with the extended public key (no wallet storage needed)
+    pub fn derive(&self) -> Result<()> {
+        if let AddressCommand::Derive {
+            xpub,
+            address_type,
+            from,
+            count,
+            format,
+        } = self
+        {
+            let addresses = derive_addresses(xpub, address_type, *from, *count)?;
+            match format {
+                DeriveFormat::Csv => {
+                    println!("path,public_key,address");
+                    for derived in addresses {
+                        println!(
+                            "{},{},{}",
+                            derived.path, derived.public_key, derived.address
+                        );
+                    }
+                }
+                DeriveFormat::Json => {
+                    let json = addresses
+                        .into_iter()
+                        .map(|derived| {
+                            serde_json::json!({
+                                "path": derived.path,
+                                "public_key": derived.public_key.to_string(),
+                                "address": derived.address,
+                            })
+                        })
+                        .collect::<Vec<_>>();
+                    let json = serde_json::to_string_pretty(&json).chain(|| {
+                        (
+                            ErrorKind::SerializationError,
+                            "Unable to serialize derived addresses",
+                        )
+                    })?;
+                    println!("{}", json);
+                }
+            }
         }
+        Ok(())
     }
 
     fn new_address<T: WalletClient>(
@@ -252,6 +373,73 @@ impl AddressCommand {
     }
 }
 
+/// Parses `[<path>]<hex>` (or only `<hex>`, with the path of the account of the address type)
+fn parse_xpub(xpub: &str, address_type: &AddressType) -> Result<(String, ExtendedPubKey)> {
+    let xpub = xpub.trim();
+    let (path, key) = if xpub.starts_with('[') {
+        let end = xpub
+            .find(']')
+            .chain(|| (ErrorKind::InvalidInput, "Missing `]` after the key path"))?;
+        (xpub[1..end].to_owned(), &xpub[end + 1..])
+    } else {
+        let account = match address_type {
+            AddressType::Staking => HDAccountType::Staking,
+            _ => HDAccountType::Transfer,
+        };
+        let coin_type = get_bip44_coin_type_from_network(get_network());
+        (format!("m/44'/{}'/{}'/0", coin_type, account.index()), xpub)
+    };
+    let bytes = hex::decode(key).chain(|| {
+        (
+            ErrorKind::InvalidInput,
+            "Unable to decode hex of extended public key",
+        )
+    })?;
+    // 33 bytes of compressed public key and 32 bytes of chain code
+    if bytes.len() != 65 {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "Extended public key should be 65 bytes (public key and chain code)",
+        ));
+    }
+    let xpub = ExtendedPubKey::deserialize(&bytes)
+        .chain(|| (ErrorKind::InvalidInput, "Invalid extended public key"))?;
+    Ok((path.trim_end_matches('/').to_owned(), xpub))
+}
+
+/// Derives `count` addresses from index `from` (non-hardened child keys of the extended public key)
+pub fn derive_addresses(
+    xpub: &str,
+    address_type: &AddressType,
+    from: u32,
+    count: u32,
+) -> Result<Vec<DerivedAddress>> {
+    let (path, xpub) = parse_xpub(xpub, address_type)?;
+    let to = from
+        .checked_add(count)
+        .chain(|| (ErrorKind::InvalidInput, "Index out of range"))?;
+    (from..to)
+        .map(|index| {
+            let public_key = HDSeed::get_pubkey_from_parent_pubkey(&xpub, index)?;
+            let address = match address_type {
+                AddressType::Staking => {
+                    StakedStateAddress::BasicRedeem(RedeemAddress::from(&public_key)).to_string()
+                }
+                _ => {
+                    let multi_sig_address =
+                        MultiSigAddress::new(vec![public_key.clone()], public_key.clone(), 1)?;
+                    ExtendedAddr::from(multi_sig_address).to_string()
+                }
+            };
+            Ok(DerivedAddress {
+                path: format!("{}/{}", path, index),
+                public_key,
+                address,
+            })
+        })
+        .collect()
+}
+
 pub fn ask_public_key(message: Option<&str>) -> Result<PublicKey> {
     ask(message.unwrap_or("Enter public key: "));
     let pubkey_str = text().chain(|| (ErrorKind::InvalidInput, "Invalid input"))?;
@@ -260,3 +448,36 @@ pub fn ask_public_key(message: Option<&str>) -> Result<PublicKey> {
         .chain(|| (ErrorKind::InvalidInput, "Invalid public key"))?;
     Ok(pubkey)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chain_core::init::network::Network;
+    use client_core::hd_wallet::traits::Serialize;
+    use client_core::Mnemonic;
+
+    #[test]
+    fn check_derive_addresses_from_xpub() {
+        let mnemonic = Mnemonic::new(24).unwrap();
+        let hd_seed = HDSeed::from(&mnemonic);
+        let xpub = hd_seed
+            .get_parent_pubkey(Network::Devnet, HDAccountType::Transfer.index())
+            .unwrap();
+        let xpub = format!("[m/44'/1'/0'/0]{}", hex::encode(xpub.serialize()));
+
+        let derived = derive_addresses(&xpub, &AddressType::Transfer, 5, 3).unwrap();
+        assert_eq!(3, derived.len());
+        for (derived, index) in derived.iter().zip(5..) {
+            assert_eq!(format!("m/44'/1'/0'/0/{}", index), derived.path);
+            assert_eq!(
+                hd_seed
+                    .get_pubkey(Network::Devnet, HDAccountType::Transfer.index(), index)
+                    .unwrap(),
+                derived.public_key
+            );
+        }
+
+        assert!(derive_addresses("[m/44'/1'/0'/0]00", &AddressType::Transfer, 0, 1).is_err());
+        assert!(derive_addresses("[m/44'", &AddressType::Staking, 0, 1).is_err());
+    }
+}
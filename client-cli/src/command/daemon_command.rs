@@ -0,0 +1,953 @@
+use std::env;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+#[cfg(not(unix))]
+use std::net::{TcpListener as Listener, TcpStream as Stream};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener as Listener, UnixStream as Stream};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use cli_table::format::{CellFormat, Justify};
+use cli_table::{Cell, Row, Table};
+use quest::success;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use structopt::StructOpt;
+use unicase::eq_ascii;
+
+use client_common::storage::SledStorage;
+use client_common::tendermint::types::GenesisExt;
+use client_common::tendermint::{
+    CachedClient, Client, WebsocketRpcClient, DEFAULT_BLOCK_CACHE_CAPACITY,
+};
+use client_common::{Error, ErrorKind, Result, ResultExt};
+use client_core::wallet::sync_manager::{SyncCallback, SyncManager, SyncProgress, SyncStatus};
+use client_core::wallet::syncer::{
+    spawn_light_client_supervisor, LightClientHandle, ObfuscationSyncerConfig, SyncerOptions,
+};
+use client_core::wallet::{WalletClient, WalletRequest};
+
+use super::{get_tx_query, get_wallet_client, AppTransactionCipher, AppWalletClient};
+use crate::{ask_seckey, storage_path, tendermint_url};
+
+const SERVICE_KIND_VARIANTS: [&str; 2] = ["systemd", "launchd"];
+
+#[cfg(target_os = "macos")]
+const DEFAULT_SERVICE_KIND: &str = "launchd";
+#[cfg(not(target_os = "macos"))]
+const DEFAULT_SERVICE_KIND: &str = "systemd";
+
+/// Loopback address of the daemon on the platforms without Unix domain sockets
+#[cfg(not(unix))]
+const DEFAULT_DAEMON_ADDRESS: &str = "127.0.0.1:26660";
+
+/// Label of the launchd agent (and name of the systemd unit)
+const SERVICE_LABEL: &str = "com.crypto.client-cli.daemon";
+const SYSTEMD_UNIT_NAME: &str = "client-cli-daemon.service";
+
+/// Environment variables passed on to the daemon run by the service manager
+const SERVICE_ENV_VARS: [&str; 9] = [
+    "CRYPTO_CHAIN_ID",
+    "CRYPTO_GENESIS_FINGERPRINT",
+    "CRYPTO_CLIENT_TX_QUERY",
+    "CRYPTO_CLIENT_TX_QUERY_MRENCLAVE",
+    "CRYPTO_CLIENT_TX_QUERY_PREVIOUS_MRENCLAVE",
+    "CRYPTO_CLIENT_TX_QUERY_MIN_TCB",
+    "CRYPTO_CLIENT_DAEMON_SOCKET",
+    "CRYPTO_CLIENT_DEBUG",
+    "RUST_LOG",
+];
+
+type AppSyncManager<L> = SyncManager<
+    SledStorage,
+    CachedClient<WebsocketRpcClient>,
+    AppTransactionCipher,
+    AppWalletClient,
+    L,
+>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ServiceKind {
+    Systemd,
+    Launchd,
+}
+
+impl FromStr for ServiceKind {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if eq_ascii(s, "systemd") {
+            Ok(ServiceKind::Systemd)
+        } else if eq_ascii(s, "launchd") {
+            Ok(ServiceKind::Launchd)
+        } else {
+            Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Unsupported service manager: {}", s),
+            ))
+        }
+    }
+}
+
+#[derive(Debug, StructOpt)]
+pub struct DaemonOptions {
+    #[structopt(
+        name = "wallet name",
+        short = "n",
+        long = "name",
+        help = "Names of the wallets to synchronize (Default: all the wallets of the enckey file)"
+    )]
+    names: Vec<String>,
+    #[structopt(
+        name = "enckey-file",
+        long,
+        parse(from_os_str),
+        help = "JSON file with the wallets and their authentication tokens: [{\"name\": .., \"enckey\": ..}]"
+    )]
+    enckey_file: Option<PathBuf>,
+    #[structopt(
+        name = "interval",
+        short,
+        long,
+        default_value = "10",
+        help = "Number of seconds between the synchronizations of the wallets"
+    )]
+    interval: u64,
+    #[structopt(
+        name = "batch-size",
+        short,
+        long,
+        default_value = "20",
+        help = "Number of requests per batch in RPC calls to tendermint"
+    )]
+    batch_size: usize,
+    #[structopt(
+        name = "enable-fast-forward",
+        long,
+        help = "Enable fast forward, which is not secure when connecting to outside nodes"
+    )]
+    enable_fast_forward: bool,
+    #[structopt(
+        name = "disable-light-client",
+        long,
+        help = "Disable light client, which is not secure when connecting to outside nodes"
+    )]
+    disable_light_client: bool,
+    #[structopt(
+        name = "light client peer",
+        short = "l",
+        long = "light-client-peers",
+        help = "Light client peers"
+    )]
+    light_client_peers: Option<String>,
+    #[structopt(
+        name = "light client trusting period in seconds",
+        long = "light-client-trusting-period",
+        help = "light client trusting period in seconds"
+    )]
+    light_client_trusting_period_seconds: Option<u64>,
+    #[structopt(
+        name = "light client trusting height",
+        long = "light-client-trusting-height",
+        help = "light client trusting height"
+    )]
+    light_client_trusting_height: Option<u64>,
+    #[structopt(
+        name = "light client trusting blockhash",
+        long = "light-client-trusting-blockhash",
+        help = "light client trusting blockhash (Default: the block of the trusting height)"
+    )]
+    light_client_trusting_blockhash: Option<String>,
+    #[structopt(
+        name = "disable-address-recovery",
+        long,
+        help = "Disable address recovery, which is not necessary, if addresses already exist"
+    )]
+    disable_address_recovery: bool,
+    #[structopt(
+        name = "enable-block-filters",
+        long,
+        help = "Only download the blocks whose filters may match the wallet (less bandwidth)"
+    )]
+    enable_block_filters: bool,
+    #[structopt(
+        name = "block-height-ensure",
+        long,
+        default_value = "50",
+        help = "Number of block height to rollback the utxos in pending transactions"
+    )]
+    block_height_ensure: u64,
+}
+
+#[derive(Debug, StructOpt)]
+pub enum DaemonCommand {
+    #[structopt(
+        name = "run",
+        about = "Keeps the wallets synchronized in the background and serves their status and balances"
+    )]
+    Run {
+        #[structopt(flatten)]
+        options: DaemonOptions,
+    },
+    #[structopt(
+        name = "status",
+        about = "Synchronization status of the wallets of the daemon"
+    )]
+    Status,
+    #[structopt(
+        name = "sync",
+        about = "Synchronizes a wallet of the daemon right away"
+    )]
+    Sync {
+        #[structopt(
+            name = "wallet name",
+            short = "n",
+            long = "name",
+            help = "Name of wallet"
+        )]
+        name: String,
+    },
+    #[structopt(
+        name = "unit",
+        about = "Generates the service definition to run the daemon with systemd or launchd"
+    )]
+    Unit {
+        #[structopt(
+            name = "kind",
+            short,
+            long,
+            default_value = DEFAULT_SERVICE_KIND,
+            possible_values = &SERVICE_KIND_VARIANTS,
+            case_insensitive = true,
+            help = "Service manager"
+        )]
+        kind: ServiceKind,
+        #[structopt(
+            name = "output",
+            short,
+            long,
+            parse(from_os_str),
+            help = "Path of the generated file (Default: standard output)"
+        )]
+        output: Option<PathBuf>,
+        #[structopt(flatten)]
+        options: DaemonOptions,
+    },
+}
+
+impl DaemonCommand {
+    pub fn execute(&self) -> Result<()> {
+        match self {
+            DaemonCommand::Run { options } => run(options),
+            DaemonCommand::Status => {
+                let progresses: Vec<SyncProgress> = query_daemon(&DaemonRequest::Status)?;
+                print_progresses(&progresses)
+            }
+            DaemonCommand::Sync { name } => {
+                let progress: SyncProgress =
+                    query_daemon(&DaemonRequest::Sync { name: name.clone() })?;
+                print_progresses(&[progress])
+            }
+            DaemonCommand::Unit {
+                kind,
+                output,
+                options,
+            } => generate_unit(*kind, output.as_deref(), options),
+        }
+    }
+}
+
+/// Request to the daemon (one JSON object per line)
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum DaemonRequest {
+    /// Progress of the synchronizations of all the wallets
+    Status,
+    /// Balance of a wallet as of its latest synchronized block
+    Balance { request: WalletRequest },
+    /// Synchronizes a wallet without waiting for the next interval
+    Sync { name: String },
+}
+
+/// Response of the daemon (one JSON object per line)
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum DaemonResponse {
+    Ok(Value),
+    Error(String),
+}
+
+/// Path of the Unix domain socket of the daemon (or its loopback TCP address on the other
+/// platforms)
+fn daemon_address() -> String {
+    env::var("CRYPTO_CLIENT_DAEMON_SOCKET").unwrap_or_else(|_| default_daemon_address())
+}
+
+#[cfg(unix)]
+fn default_daemon_address() -> String {
+    Path::new(&storage_path())
+        .join("daemon.sock")
+        .to_string_lossy()
+        .into_owned()
+}
+
+#[cfg(not(unix))]
+fn default_daemon_address() -> String {
+    DEFAULT_DAEMON_ADDRESS.to_owned()
+}
+
+#[cfg(unix)]
+fn bind(address: &str) -> Result<Listener> {
+    use std::os::unix::fs::PermissionsExt;
+
+    if Path::new(address).exists() {
+        if Stream::connect(address).is_ok() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Daemon is already running: {}", address),
+            ));
+        }
+        // left by a daemon which was not stopped gracefully
+        fs::remove_file(address).chain(|| {
+            (
+                ErrorKind::IoError,
+                format!("Unable to remove stale daemon socket: {}", address),
+            )
+        })?;
+    }
+    let listener = Listener::bind(address).chain(|| {
+        (
+            ErrorKind::IoError,
+            format!("Unable to bind daemon socket: {}", address),
+        )
+    })?;
+    // only the owner of the wallets may query them
+    fs::set_permissions(address, fs::Permissions::from_mode(0o600)).chain(|| {
+        (
+            ErrorKind::IoError,
+            format!("Unable to set permissions of daemon socket: {}", address),
+        )
+    })?;
+    Ok(listener)
+}
+
+#[cfg(not(unix))]
+fn bind(address: &str) -> Result<Listener> {
+    Listener::bind(address).chain(|| {
+        (
+            ErrorKind::IoError,
+            format!("Unable to bind daemon address: {}", address),
+        )
+    })
+}
+
+/// Returns `true` if a daemon is listening (the storage is then locked by the daemon)
+pub fn is_daemon_running() -> bool {
+    Stream::connect(daemon_address()).is_ok()
+}
+
+/// Sends the request to the running daemon and waits for its response
+pub fn query_daemon<T: DeserializeOwned>(request: &DaemonRequest) -> Result<T> {
+    let address = daemon_address();
+    let mut stream = Stream::connect(&address).chain(|| {
+        (
+            ErrorKind::ConnectionError,
+            format!("Daemon is not running: {}", address),
+        )
+    })?;
+
+    let mut line = serde_json::to_string(request).chain(|| {
+        (
+            ErrorKind::SerializationError,
+            "Unable to serialize daemon request",
+        )
+    })?;
+    line.push('\n');
+    stream
+        .write_all(line.as_bytes())
+        .chain(|| (ErrorKind::IoError, "Unable to send request to daemon"))?;
+
+    let mut response = String::new();
+    BufReader::new(stream)
+        .read_line(&mut response)
+        .chain(|| (ErrorKind::IoError, "Unable to read response of daemon"))?;
+    let response = serde_json::from_str(&response).chain(|| {
+        (
+            ErrorKind::DeserializationError,
+            "Unable to deserialize daemon response",
+        )
+    })?;
+    match response {
+        DaemonResponse::Ok(value) => serde_json::from_value(value).chain(|| {
+            (
+                ErrorKind::DeserializationError,
+                "Unable to deserialize daemon response",
+            )
+        }),
+        DaemonResponse::Error(error) => Err(Error::new(ErrorKind::InvalidInput, error)),
+    }
+}
+
+/// Synchronizes the wallets in the background and answers the requests of the interactive CLI
+struct Daemon<L: LightClientHandle + 'static> {
+    manager: AppSyncManager<L>,
+    wallet_client: AppWalletClient,
+    requests: Vec<WalletRequest>,
+}
+
+impl<L: LightClientHandle + 'static> Daemon<L> {
+    /// Starts synchronizing the wallet (if it is not synchronizing already)
+    fn sync(&self, request: &WalletRequest) -> Result<()> {
+        match self.manager.progress(&request.name)? {
+            Some(ref progress) if progress.status == SyncStatus::Running => Ok(()),
+            _ => self.manager.start(request.clone(), false),
+        }
+    }
+
+    fn sync_all(&self) {
+        for request in self.requests.iter() {
+            if let Err(err) = self.sync(request) {
+                log::warn!("Unable to synchronize wallet {}: {}", request.name, err);
+            }
+        }
+    }
+
+    fn handle(&self, request: DaemonRequest) -> Result<Value> {
+        let result = match request {
+            DaemonRequest::Status => {
+                let progresses = self
+                    .requests
+                    .iter()
+                    .map(|request| self.manager.progress(&request.name))
+                    .collect::<Result<Vec<_>>>()?;
+                serde_json::to_value(progresses.into_iter().flatten().collect::<Vec<_>>())
+            }
+            DaemonRequest::Balance { request } => {
+                serde_json::to_value(self.wallet_client.balance(&request.name, &request.enckey)?)
+            }
+            DaemonRequest::Sync { name } => {
+                let request = self
+                    .requests
+                    .iter()
+                    .find(|request| request.name == name)
+                    .ok_or_else(|| {
+                        Error::new(
+                            ErrorKind::InvalidInput,
+                            format!("Wallet is not synchronized by the daemon: {}", name),
+                        )
+                    })?;
+                self.sync(request)?;
+                serde_json::to_value(self.manager.progress(&name)?)
+            }
+        };
+        result.chain(|| {
+            (
+                ErrorKind::SerializationError,
+                "Unable to serialize daemon response",
+            )
+        })
+    }
+
+    /// Answers the requests of a connection, one per line
+    fn serve(&self, stream: Stream) -> Result<()> {
+        let mut writer = stream
+            .try_clone()
+            .chain(|| (ErrorKind::IoError, "Unable to clone daemon connection"))?;
+        for line in BufReader::new(stream).lines() {
+            let line = line.chain(|| (ErrorKind::IoError, "Unable to read daemon request"))?;
+            let response = serde_json::from_str(&line)
+                .chain(|| {
+                    (
+                        ErrorKind::DeserializationError,
+                        "Unable to deserialize daemon request",
+                    )
+                })
+                .and_then(|request| self.handle(request));
+            let response = match response {
+                Ok(value) => DaemonResponse::Ok(value),
+                Err(err) => DaemonResponse::Error(err.to_string()),
+            };
+            let mut line = serde_json::to_string(&response).chain(|| {
+                (
+                    ErrorKind::SerializationError,
+                    "Unable to serialize daemon response",
+                )
+            })?;
+            line.push('\n');
+            writer
+                .write_all(line.as_bytes())
+                .chain(|| (ErrorKind::IoError, "Unable to send daemon response"))?;
+        }
+        Ok(())
+    }
+}
+
+fn run(options: &DaemonOptions) -> Result<()> {
+    let tendermint_client = WebsocketRpcClient::new(&tendermint_url())?;
+    let tx_obfuscation = get_tx_query(tendermint_client.clone())?;
+    let db_path = storage_path();
+    let storage = SledStorage::new(&db_path)?;
+    let wallet_client = get_wallet_client(storage.clone())?;
+
+    let requests = options.wallet_requests()?;
+    // fails early on a wrong authentication token instead of in the background
+    for request in requests.iter() {
+        wallet_client.view_key(&request.name, &request.enckey)?;
+    }
+
+    let light_client_peers = options.light_client_peers.clone().unwrap_or_default();
+    let light_client_trusting_period_seconds = options
+        .light_client_trusting_period_seconds
+        .unwrap_or_default();
+    let light_client_trusting_height = options.light_client_trusting_height.unwrap_or_default();
+    // the daemon is not interactive, so the block of the trusting height is trusted
+    let light_client_trusting_blockhash = options
+        .light_client_trusting_blockhash
+        .clone()
+        .unwrap_or_default();
+    let handle = if options.disable_light_client {
+        None
+    } else {
+        if light_client_peers.is_empty() || 0 == light_client_trusting_height {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Light client peers and trusting height are required (unless the light client is disabled)",
+            ));
+        }
+        Some(spawn_light_client_supervisor(
+            db_path.as_ref(),
+            tendermint_client.genesis()?.trusting_period() / 2,
+            light_client_peers.clone(),
+            light_client_trusting_period_seconds,
+            light_client_trusting_height,
+            light_client_trusting_blockhash.clone(),
+            None,
+        )?)
+    };
+
+    // the wallets synchronized concurrently fetch each block only once
+    let config = ObfuscationSyncerConfig::new(
+        storage,
+        CachedClient::new(tendermint_client, DEFAULT_BLOCK_CACHE_CAPACITY),
+        tx_obfuscation,
+        SyncerOptions {
+            enable_fast_forward: options.enable_fast_forward,
+            disable_light_client: options.disable_light_client,
+            enable_address_recovery: !options.disable_address_recovery,
+            enable_block_filters: options.enable_block_filters,
+            batch_size: options.batch_size,
+            block_height_ensure: options.block_height_ensure,
+            light_client_peers,
+            light_client_trusting_period_seconds,
+            light_client_trusting_height,
+            light_client_trusting_blockhash,
+        },
+        handle,
+    );
+    let callback: SyncCallback = Arc::new(|progress: &SyncProgress| {
+        if let SyncStatus::Failed { error } = &progress.status {
+            log::warn!(
+                "Synchronization of wallet {} failed: {}",
+                progress.name,
+                error
+            );
+        }
+    });
+    let daemon = Arc::new(Daemon {
+        manager: SyncManager::new(config, wallet_client.clone()).with_callback(callback),
+        wallet_client,
+        requests,
+    });
+
+    let address = daemon_address();
+    let listener = bind(&address)?;
+
+    let interval = Duration::from_secs(options.interval);
+    {
+        let daemon = daemon.clone();
+        thread::spawn(move || loop {
+            daemon.sync_all();
+            thread::sleep(interval);
+        });
+    }
+
+    success(&format!("Daemon is listening on {}", address));
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let daemon = daemon.clone();
+                thread::spawn(move || {
+                    if let Err(err) = daemon.serve(stream) {
+                        log::warn!("Unable to serve daemon connection: {}", err);
+                    }
+                });
+            }
+            Err(err) => log::warn!("Unable to accept daemon connection: {}", err),
+        }
+    }
+    Ok(())
+}
+
+impl DaemonOptions {
+    /// Reads the wallets from the enckey file, or asks for the authentication tokens of the
+    /// named wallets
+    fn wallet_requests(&self) -> Result<Vec<WalletRequest>> {
+        let path = match &self.enckey_file {
+            Some(path) => path,
+            None if self.names.is_empty() => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "Either wallet names or an enckey file is required",
+                ))
+            }
+            None => {
+                return self
+                    .names
+                    .iter()
+                    .map(|name| {
+                        let message = format!("Enter authentication token of wallet {}: ", name);
+                        Ok(WalletRequest {
+                            name: name.clone(),
+                            enckey: ask_seckey(Some(message.as_str()))?,
+                        })
+                    })
+                    .collect();
+            }
+        };
+
+        let file = fs::read_to_string(path).chain(|| {
+            (
+                ErrorKind::IoError,
+                format!("Unable to read enckey file: {}", path.display()),
+            )
+        })?;
+        let requests: Vec<WalletRequest> = serde_json::from_str(&file).chain(|| {
+            (
+                ErrorKind::DeserializationError,
+                format!("Unable to deserialize enckey file: {}", path.display()),
+            )
+        })?;
+        if self.names.is_empty() {
+            return Ok(requests);
+        }
+        self.names
+            .iter()
+            .map(|name| {
+                requests
+                    .iter()
+                    .find(|request| &request.name == name)
+                    .cloned()
+                    .ok_or_else(|| {
+                        Error::new(
+                            ErrorKind::InvalidInput,
+                            format!("Wallet is missing from the enckey file: {}", name),
+                        )
+                    })
+            })
+            .collect()
+    }
+
+    /// Arguments of `client-cli` to run the daemon with these options
+    fn to_args(&self) -> Result<Vec<String>> {
+        let enckey_file = self.enckey_file.as_ref().ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                "An enckey file is required to run the daemon as a service",
+            )
+        })?;
+
+        let mut args = vec!["daemon".to_owned(), "run".to_owned()];
+        for name in self.names.iter() {
+            args.push("--name".to_owned());
+            args.push(name.clone());
+        }
+        args.push("--enckey-file".to_owned());
+        args.push(absolute_path(enckey_file)?);
+        args.push("--interval".to_owned());
+        args.push(self.interval.to_string());
+        args.push("--batch-size".to_owned());
+        args.push(self.batch_size.to_string());
+        args.push("--block-height-ensure".to_owned());
+        args.push(self.block_height_ensure.to_string());
+        if self.enable_fast_forward {
+            args.push("--enable-fast-forward".to_owned());
+        }
+        if self.disable_address_recovery {
+            args.push("--disable-address-recovery".to_owned());
+        }
+        if self.enable_block_filters {
+            args.push("--enable-block-filters".to_owned());
+        }
+        if self.disable_light_client {
+            args.push("--disable-light-client".to_owned());
+        }
+        if let Some(peers) = &self.light_client_peers {
+            args.push("--light-client-peers".to_owned());
+            args.push(peers.clone());
+        }
+        if let Some(period) = self.light_client_trusting_period_seconds {
+            args.push("--light-client-trusting-period".to_owned());
+            args.push(period.to_string());
+        }
+        if let Some(height) = self.light_client_trusting_height {
+            args.push("--light-client-trusting-height".to_owned());
+            args.push(height.to_string());
+        }
+        if let Some(blockhash) = &self.light_client_trusting_blockhash {
+            args.push("--light-client-trusting-blockhash".to_owned());
+            args.push(blockhash.clone());
+        }
+        Ok(args)
+    }
+}
+
+fn absolute_path<P: AsRef<Path>>(path: P) -> Result<String> {
+    let path = path.as_ref();
+    let path = if path.is_absolute() {
+        path.to_owned()
+    } else {
+        env::current_dir()
+            .chain(|| (ErrorKind::IoError, "Unable to get current directory"))?
+            .join(path)
+    };
+    Ok(path.to_string_lossy().into_owned())
+}
+
+fn generate_unit(kind: ServiceKind, output: Option<&Path>, options: &DaemonOptions) -> Result<()> {
+    let program = env::current_exe()
+        .chain(|| (ErrorKind::IoError, "Unable to get path of client-cli"))?
+        .to_string_lossy()
+        .into_owned();
+    let args = options.to_args()?;
+
+    // the service manager doesn't run the daemon in the current directory or environment
+    let storage = absolute_path(storage_path())?;
+    let mut envs = vec![
+        ("CRYPTO_CLIENT_STORAGE".to_owned(), storage.clone()),
+        ("CRYPTO_CLIENT_TENDERMINT".to_owned(), tendermint_url()),
+    ];
+    for name in SERVICE_ENV_VARS.iter() {
+        if let Ok(value) = env::var(name) {
+            envs.push(((*name).to_owned(), value));
+        }
+    }
+    let log_path = Path::new(&storage)
+        .join("daemon.log")
+        .to_string_lossy()
+        .into_owned();
+
+    let unit = match kind {
+        ServiceKind::Systemd => systemd_unit(&program, &args, &envs),
+        ServiceKind::Launchd => launchd_plist(&program, &args, &envs, &log_path),
+    };
+
+    match output {
+        None => print!("{}", unit),
+        Some(path) => {
+            fs::write(path, unit).chain(|| {
+                (
+                    ErrorKind::IoError,
+                    format!("Unable to write service file: {}", path.display()),
+                )
+            })?;
+            let hint = match kind {
+                ServiceKind::Systemd => format!(
+                    "Copy it to ~/.config/systemd/user/{} and run `systemctl --user enable --now {}`",
+                    SYSTEMD_UNIT_NAME, SYSTEMD_UNIT_NAME
+                ),
+                ServiceKind::Launchd => format!(
+                    "Copy it to ~/Library/LaunchAgents/{}.plist and run `launchctl load -w ~/Library/LaunchAgents/{}.plist`",
+                    SERVICE_LABEL, SERVICE_LABEL
+                ),
+            };
+            success(&format!(
+                "Service file written to {}. {}",
+                path.display(),
+                hint
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Quotes the argument of `ExecStart` or the assignment of `Environment` (if necessary)
+fn systemd_quote(value: &str) -> String {
+    if value.is_empty()
+        || value
+            .chars()
+            .any(|c| c.is_whitespace() || c == '"' || c == '\\' || c == '%' || c == '$')
+    {
+        let escaped = value
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('%', "%%")
+            .replace('$', "$$");
+        format!("\"{}\"", escaped)
+    } else {
+        value.to_owned()
+    }
+}
+
+fn systemd_unit(program: &str, args: &[String], envs: &[(String, String)]) -> String {
+    let exec_start = std::iter::once(program)
+        .chain(args.iter().map(String::as_str))
+        .map(systemd_quote)
+        .collect::<Vec<_>>()
+        .join(" ");
+    let environment = envs
+        .iter()
+        .map(|(name, value)| {
+            format!(
+                "Environment={}\n",
+                systemd_quote(&format!("{}={}", name, value))
+            )
+        })
+        .collect::<String>();
+
+    format!(
+        "[Unit]\n\
+         Description=client-cli wallet synchronization daemon\n\
+         Wants=network-online.target\n\
+         After=network-online.target\n\
+         \n\
+         [Service]\n\
+         ExecStart={}\n\
+         {}\
+         Restart=on-failure\n\
+         RestartSec=10\n\
+         \n\
+         [Install]\n\
+         WantedBy=default.target\n",
+        exec_start, environment
+    )
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn launchd_plist(
+    program: &str,
+    args: &[String],
+    envs: &[(String, String)],
+    log_path: &str,
+) -> String {
+    let arguments = std::iter::once(program)
+        .chain(args.iter().map(String::as_str))
+        .map(|arg| format!("        <string>{}</string>\n", xml_escape(arg)))
+        .collect::<String>();
+    let environment = envs
+        .iter()
+        .map(|(name, value)| {
+            format!(
+                "        <key>{}</key>\n        <string>{}</string>\n",
+                xml_escape(name),
+                xml_escape(value)
+            )
+        })
+        .collect::<String>();
+    let log_path = xml_escape(log_path);
+
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n\
+         <plist version=\"1.0\">\n\
+         <dict>\n    \
+             <key>Label</key>\n    \
+             <string>{}</string>\n    \
+             <key>ProgramArguments</key>\n    \
+             <array>\n{}    </array>\n    \
+             <key>EnvironmentVariables</key>\n    \
+             <dict>\n{}    </dict>\n    \
+             <key>RunAtLoad</key>\n    \
+             <true/>\n    \
+             <key>KeepAlive</key>\n    \
+             <true/>\n    \
+             <key>StandardOutPath</key>\n    \
+             <string>{}</string>\n    \
+             <key>StandardErrorPath</key>\n    \
+             <string>{}</string>\n\
+         </dict>\n\
+         </plist>\n",
+        SERVICE_LABEL, arguments, environment, log_path, log_path
+    )
+}
+
+fn print_progresses(progresses: &[SyncProgress]) -> Result<()> {
+    if progresses.is_empty() {
+        success("No wallet is synchronizing yet");
+        return Ok(());
+    }
+
+    let bold = CellFormat::builder().bold(true).build();
+    let justify_right = CellFormat::builder().justify(Justify::Right).build();
+
+    let mut rows = vec![Row::new(vec![
+        Cell::new("Wallet", bold),
+        Cell::new("Status", bold),
+        Cell::new("Block Height", bold),
+        Cell::new("Target Height", bold),
+        Cell::new("Progress", bold),
+    ])];
+    for progress in progresses {
+        let status = match &progress.status {
+            SyncStatus::Running => "Running".to_owned(),
+            SyncStatus::Completed => "Completed".to_owned(),
+            SyncStatus::Cancelled => "Cancelled".to_owned(),
+            SyncStatus::Failed { error } => format!("Failed: {}", error),
+        };
+        rows.push(Row::new(vec![
+            Cell::new(&progress.name, Default::default()),
+            Cell::new(&status, Default::default()),
+            Cell::new(&progress.current_block_height, justify_right),
+            Cell::new(&progress.finish_block_height, justify_right),
+            Cell::new(&format!("{:.1}%", progress.percent()), justify_right),
+        ]));
+    }
+
+    let table = Table::new(rows, Default::default())
+        .chain(|| (ErrorKind::InternalError, "Unable to create new table"))?;
+    table
+        .print_stdout()
+        .chain(|| (ErrorKind::IoError, "Unable to print table"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_service_units() {
+        let args = vec![
+            "daemon".to_owned(),
+            "run".to_owned(),
+            "--enckey-file".to_owned(),
+            "/home/user/wallet keys.json".to_owned(),
+        ];
+        let envs = vec![(
+            "CRYPTO_CHAIN_ID".to_owned(),
+            "test-chain-y3m1e6-AB".to_owned(),
+        )];
+
+        let unit = systemd_unit("/usr/bin/client-cli", &args, &envs);
+        assert!(unit.contains(
+            "ExecStart=/usr/bin/client-cli daemon run --enckey-file \"/home/user/wallet keys.json\"\n"
+        ));
+        assert!(unit.contains("Environment=CRYPTO_CHAIN_ID=test-chain-y3m1e6-AB\n"));
+        assert_eq!("\"100%% $$HOME\"", systemd_quote("100% $HOME"));
+
+        let plist = launchd_plist("/usr/bin/client-cli", &args, &envs, "/tmp/a&b.log");
+        assert!(plist.contains("<string>/home/user/wallet keys.json</string>"));
+        assert!(plist.contains("<key>CRYPTO_CHAIN_ID</key>"));
+        assert!(plist.contains("<string>/tmp/a&amp;b.log</string>"));
+    }
+}
@@ -21,7 +21,7 @@ use chain_core::tx::data::output::TxOut;
 use chain_core::tx::TxAux;
 use client_common::{Error, ErrorKind, PublicKey, Result, ResultExt, SecKey, Transaction};
 use client_core::transaction_builder::SignedTransferTransaction;
-use client_core::types::{BalanceChange, TransactionPending};
+use client_core::types::{parse_staking_address, BalanceChange, TransactionPending};
 use client_core::WalletClient;
 use client_network::NetworkOpsClient;
 use mls::{Codec, DefaultCipherSuite, KeyPackage};
@@ -1012,17 +1012,8 @@ fn ask_inputs() -> Result<Vec<TxoPointer>> {
 
 fn ask_staking_address() -> Result<StakedStateAddress> {
     ask("Enter staking address: ");
-    let address = text()
-        .chain(|| (ErrorKind::IoError, "Unable to read staking address"))?
-        .parse::<StakedStateAddress>()
-        .chain(|| {
-            (
-                ErrorKind::DeserializationError,
-                "Unable to deserialize staking address",
-            )
-        })?;
-
-    Ok(address)
+    let address = text().chain(|| (ErrorKind::IoError, "Unable to read staking address"))?;
+    parse_staking_address(&address)
 }
 
 fn ask_transfer_address() -> Result<ExtendedAddr> {
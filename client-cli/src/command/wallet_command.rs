@@ -1,3 +1,4 @@
+use pbr::ProgressBar;
 use quest::{ask, error, success, text};
 use secstr::SecUtf8;
 use structopt::StructOpt;
@@ -8,13 +9,13 @@ use client_core::{Mnemonic, WalletClient};
 
 use crate::{ask_hardware_kind, ask_passphrase, ask_seckey};
 use client_core::hd_wallet::HardwareKind;
-use client_core::service::WalletInfo;
+use client_core::service::{ReindexProgress, WalletInfo};
 use client_core::wallet::WalletRequest;
 use std::fs::File;
 use std::io::Write;
 use std::path::PathBuf;
 
-const WALLET_KIND_VARIANTS: [&str; 3] = ["basic", "hd", "hw"];
+const WALLET_KIND_VARIANTS: [&str; 4] = ["basic", "hd", "hw", "watch"];
 
 #[derive(Debug, StructOpt)]
 pub enum WalletCommand {
@@ -124,6 +125,27 @@ pub enum WalletCommand {
         )]
         name: String,
     },
+    #[structopt(
+        name = "reindex",
+        about = "Rebuild unspent transactions and history index of wallet from locally synced data"
+    )]
+    Reindex {
+        #[structopt(
+            name = "wallet name",
+            short = "n",
+            long = "name",
+            help = "Name of wallet"
+        )]
+        name: String,
+        #[structopt(
+            name = "threads",
+            short = "t",
+            long = "threads",
+            default_value = "4",
+            help = "Number of worker threads"
+        )]
+        threads: usize,
+    },
 }
 
 impl WalletCommand {
@@ -145,6 +167,9 @@ impl WalletCommand {
                 to_file,
             } => Self::export(wallet_client, name, from_file, to_file),
             WalletCommand::Import { file } => Self::import(wallet_client, file),
+            WalletCommand::Reindex { name, threads } => {
+                Self::reindex(wallet_client, name, *threads)
+            }
         }
     }
 
@@ -386,6 +411,49 @@ impl WalletCommand {
         wallet_client.delete_wallet(name, &passphrase)?;
         Ok(())
     }
+
+    fn reindex<T: WalletClient>(wallet_client: T, name: &str, threads: usize) -> Result<()> {
+        let enckey = ask_seckey(None)?;
+
+        let mut progress_bar = None;
+        let report =
+            wallet_client.reindex_wallet(
+                name,
+                &enckey,
+                threads,
+                &mut |progress| match progress {
+                    ReindexProgress::Init { transactions, .. } => {
+                        let mut pb = ProgressBar::new(transactions);
+                        pb.message("Reindexing: ");
+                        progress_bar = Some(pb);
+                    }
+                    ReindexProgress::Update {
+                        indexed_transactions,
+                        ..
+                    } => {
+                        if let Some(ref mut pb) = progress_bar {
+                            pb.set(indexed_transactions);
+                        }
+                    }
+                },
+            )?;
+        if let Some(ref mut pb) = progress_bar {
+            pb.finish_print("Reindexing complete!");
+        }
+
+        success(&format!(
+            "Reindexed {} transactions of wallet {}",
+            report.transactions, report.wallet_name
+        ));
+        success(&format!(
+            "Unspent transactions: {} ({} added, {} removed)",
+            report.unspent_transactions,
+            report.added_unspent_transactions,
+            report.removed_unspent_transactions
+        ));
+        success(&format!("Total balance: {}", report.balance.total));
+        Ok(())
+    }
 }
 
 fn ask_mnemonic(message: Option<&str>) -> Result<Mnemonic> {
@@ -2,14 +2,26 @@
 mod default;
 
 pub mod mock;
+pub mod policy;
 
 pub use default::DefaultTransactionObfuscation;
 pub use mock::MockAbciTransactionObfuscation;
+pub use policy::{AttestationPolicy, EndpointStats, TcbLevel, TxQueryConfig, TxQueryEndpoint};
 
 use crate::{PrivateKey, Result, SignedTransaction, Transaction};
+use chain_core::state::tendermint::BlockHeight;
 use chain_core::tx::data::TxId;
 use chain_core::tx::TxAux;
 
+/// Transactions readable by a view key, notified (or discovered) by the transaction query enclave
+#[derive(Debug)]
+pub struct TransactionNotification {
+    /// last block height checked by the enclave
+    pub last_height: BlockHeight,
+    /// notified transactions with the height of their block
+    pub transactions: Vec<(BlockHeight, Transaction)>,
+}
+
 /// Interface for encryption and decryption of transactions
 pub trait TransactionObfuscation: Send + Sync + Clone {
     /// Retrieves decrypted transactions with given ids. Only transactions of type `Transfer` and `Withdraw` need to be
@@ -22,4 +34,10 @@ pub trait TransactionObfuscation: Send + Sync + Clone {
 
     /// Encrypts a signed transaction
     fn encrypt(&self, transaction: SignedTransaction) -> Result<TxAux>;
+
+    /// Returns the telemetry of the tx-query endpoints (empty if the transactions are not
+    /// obfuscated by tx-query enclaves), fails if the endpoints are not known
+    fn tx_query_stats(&self) -> Result<Vec<EndpointStats>> {
+        Ok(vec![])
+    }
 }
@@ -2,30 +2,37 @@ use std::{
     io::{Read, Write},
     net::{Shutdown, TcpStream},
     sync::Arc,
+    time::Duration,
 };
 
 use parity_scale_codec::{Decode, Encode};
 
+use super::policy::{
+    AttestationPolicy, EndpointStats, TxQueryConfig, TxQueryEndpoint, TxQueryNodes,
+};
+use super::TransactionNotification;
 use crate::TransactionObfuscation;
 use crate::{
     tendermint::{types::AbciQueryExt, Client},
     Error, ErrorKind, PrivateKey, Result, ResultExt, SignedTransaction, Transaction,
 };
+use chain_core::state::tendermint::BlockHeight;
 use chain_core::tx::{data::TxId, TxAux, TxWithOutputs};
 use enclave_macro::{get_mrsigner, get_network_id, get_tqe_mrenclave};
 use enclave_protocol::{
-    DecryptionRequest, DecryptionResponse, EncryptionRequest, EncryptionResponse,
-    TxQueryInitRequest, TxQueryInitResponse,
+    DecryptionRequest, DecryptionResponse, DiscoveryRequest, DiscoveryResponse, EncryptionRequest,
+    EncryptionResponse, SubscriptionNotification, SubscriptionRequest, TxQueryInitRequest,
+    TxQueryInitResponse, MAX_SUBSCRIPTION_TIMEOUT_SECS,
 };
 use ra_client::{EnclaveCertVerifier, EnclaveCertVerifierConfig, EnclaveInfo};
 
-fn get_tls_config() -> Arc<rustls::ClientConfig> {
+fn get_tls_config(policy: &AttestationPolicy) -> Result<Arc<rustls::ClientConfig>> {
     let mr_signer: [u8; 32] = get_mrsigner!();
-    let mr_enclave: Option<[u8; 32]> = Some(get_tqe_mrenclave!());
+    let mr_enclave: [u8; 32] = policy.mr_enclave.unwrap_or(get_tqe_mrenclave!());
     let tqe_info = EnclaveInfo {
-        mr_enclave,
+        mr_enclave: Some(mr_enclave),
         mr_signer,
-        previous_mr_enclave: None,
+        previous_mr_enclave: policy.previous_mr_enclave,
         isv_prod_id: get_network_id!(),
         // TODO: it seems there's no global CPU SVN across all CPU models,
         // so one can't really fix it to one at compile-time?
@@ -36,97 +43,155 @@ fn get_tls_config() -> Arc<rustls::ClientConfig> {
         // e.g. which flag is the debug mode launch
         attributes: [0; 16],
     };
-    let config = EnclaveCertVerifierConfig::new_with_enclave_info(tqe_info);
-    let verifier = EnclaveCertVerifier::new(config).expect("verifier config");
-    Arc::new(
-        verifier
-            .into_client_config()
-            .expect("Error while creating TLS client configuration"),
-    )
+    let mut config = EnclaveCertVerifierConfig::new_with_enclave_info(tqe_info);
+    config.valid_enclave_quote_statuses = policy
+        .min_tcb_level
+        .accepted_quote_statuses()
+        .into_iter()
+        .map(Into::into)
+        .collect::<Vec<_>>()
+        .into();
+    let verifier = EnclaveCertVerifier::new(config).chain(|| {
+        (
+            ErrorKind::InitializationError,
+            "Invalid tx-query attestation policy",
+        )
+    })?;
+    let client_config = verifier.into_client_config().chain(|| {
+        (
+            ErrorKind::InitializationError,
+            "Error while creating TLS client configuration",
+        )
+    })?;
+    Ok(Arc::new(client_config))
 }
 
 /// Implementation of transaction obfuscation which directly talks to transaction decryption query and encryption enclaves
-/// TODO: querying from multiple nodes / addresses
 #[derive(Debug, Clone)]
 pub struct DefaultTransactionObfuscation {
-    tqe_address: String,
-    tqe_hostname: webpki::DNSName,
+    nodes: TxQueryNodes,
+    policy: AttestationPolicy,
 }
 
 impl DefaultTransactionObfuscation {
     /// tqe_address: connection string <HOST/IP:PORT>
     /// tqe_hostname: expected hostname (e.g. localhost in testing)
     pub fn new(tqe_address: String, tqe_hostname: String) -> Self {
-        // one may just write an ip address instead of a domain name, which isn't a valid DNS name
-        // so there's a default case
-        // TODO: should TQE enforce valid domain names,
-        // as some of the infra may not be assigned a domain name
-        // and the TLS checking is augmented with attestation anyway?
-        let dns_name = webpki::DNSNameRef::try_from_ascii_str(&tqe_hostname)
-            .unwrap_or_else(|_| webpki::DNSNameRef::try_from_ascii_str("localhost").unwrap())
-            .to_owned();
+        let endpoint = TxQueryEndpoint::new(tqe_address, &tqe_hostname);
         DefaultTransactionObfuscation {
-            tqe_address,
-            tqe_hostname: dns_name,
+            nodes: TxQueryNodes::new(vec![endpoint]).expect("one endpoint"),
+            policy: AttestationPolicy::default(),
         }
     }
 
+    /// Creates obfuscation which fails over between the configured endpoints
+    /// and pins the enclaves to the given attestation policy
+    pub fn new_with_config(config: TxQueryConfig) -> Result<Self> {
+        Ok(DefaultTransactionObfuscation {
+            nodes: TxQueryNodes::new(config.endpoints)?,
+            policy: config.attestation,
+        })
+    }
+
     /// Get DefaultTransactionObfuscation from txquery call to Tendermint client
+    /// (unless tx-query endpoints are configured via environment variables)
     pub fn from_tx_query<C>(tendermint_client: &C) -> Result<DefaultTransactionObfuscation>
     where
         C: Client,
     {
-        let result = tendermint_client
-            .query("txquery", &[], None, false)?
-            .bytes();
-        let address = std::str::from_utf8(&result).chain(|| {
-            (
-                ErrorKind::ConnectionError,
-                "Unable to decode txquery address",
-            )
-        })?;
-        DefaultTransactionObfuscation::from_tx_query_address(&address)
+        let mut config = TxQueryConfig::from_env()?;
+        if config.endpoints.is_empty() {
+            let result = tendermint_client
+                .query("txquery", &[], None, false)?
+                .bytes();
+            let address = std::str::from_utf8(&result).chain(|| {
+                (
+                    ErrorKind::ConnectionError,
+                    "Unable to decode txquery address",
+                )
+            })?;
+            config
+                .endpoints
+                .push(TxQueryEndpoint::from_address(address)?);
+        }
+        DefaultTransactionObfuscation::new_with_config(config)
     }
 
     /// Get DefaultTransactionObfuscation from tx query address
     pub fn from_tx_query_address(address: &str) -> Result<DefaultTransactionObfuscation> {
-        if let Some(hostname) = address.split(':').next() {
-            Ok(DefaultTransactionObfuscation::new(
-                address.to_string(),
-                hostname.to_string(),
-            ))
-        } else {
-            Err(Error::new(
-                ErrorKind::ConnectionError,
-                "Unable to decode txquery address",
-            ))
-        }
+        let config = TxQueryConfig {
+            endpoints: vec![TxQueryEndpoint::from_address(address)?],
+            attestation: AttestationPolicy::default(),
+        };
+        DefaultTransactionObfuscation::new_with_config(config)
     }
-}
 
-impl TransactionObfuscation for DefaultTransactionObfuscation {
-    fn decrypt(
+    /// Returns per-endpoint telemetry
+    pub fn endpoint_stats(&self) -> Vec<EndpointStats> {
+        self.nodes.stats()
+    }
+
+    /// Waits (at most `timeout_secs`, capped by the enclave) for transactions readable by
+    /// the view key to be committed after `after_height` (instead of polling and filtering
+    /// new blocks). The next subscription should start after the returned `last_height`.
+    pub fn subscribe(
+        &self,
+        after_height: BlockHeight,
+        timeout_secs: u32,
+        private_key: &PrivateKey,
+    ) -> Result<TransactionNotification> {
+        let client_config = get_tls_config(&self.policy)?;
+        self.nodes.with_failover(|endpoint| {
+            self.subscribe_to(
+                endpoint,
+                &client_config,
+                after_height,
+                timeout_secs,
+                private_key,
+            )
+        })
+    }
+
+    /// Discovers the transactions readable by the view key committed in the block range
+    /// (without downloading and decrypting all of them). The enclave scans at most
+    /// `MAX_DISCOVERY_HEIGHTS` blocks, so the discovery should continue after the returned
+    /// `last_height` if it's below `to_height`.
+    pub fn discover(
         &self,
+        from_height: BlockHeight,
+        to_height: BlockHeight,
+        private_key: &PrivateKey,
+    ) -> Result<TransactionNotification> {
+        let client_config = get_tls_config(&self.policy)?;
+        self.nodes.with_failover(|endpoint| {
+            self.discover_from(
+                endpoint,
+                &client_config,
+                from_height,
+                to_height,
+                private_key,
+            )
+        })
+    }
+
+    fn decrypt_from(
+        &self,
+        endpoint: &TxQueryEndpoint,
+        client_config: &Arc<rustls::ClientConfig>,
         transaction_ids: &[TxId],
         private_key: &PrivateKey,
     ) -> Result<Vec<Transaction>> {
-        if transaction_ids.is_empty() {
-            return Ok(vec![]);
-        }
-
-        let client_config = get_tls_config();
-        let dns_name = self.tqe_hostname.as_ref();
+        let dns_name = endpoint.hostname.as_ref();
         // FIXME: better response from enclave and retry mechanism
         for attempt in 0..3 {
-            let mut sess = rustls::ClientSession::new(&client_config, dns_name);
+            let mut sess = rustls::ClientSession::new(client_config, dns_name);
 
-            let mut conn = TcpStream::connect(&self.tqe_address).chain(|| {
+            let mut conn = TcpStream::connect(&endpoint.address).chain(|| {
                 (
                     ErrorKind::ConnectionError,
-                    format!("Unable to connect to TQE address: {}", self.tqe_address),
+                    format!("Unable to connect to TQE address: {}", endpoint.address),
                 )
             })?;
-
             let mut tls = rustls::Stream::new(&mut sess, &mut conn);
             tls.write_all(&TxQueryInitRequest::DecryptChallenge.encode())
                 .chain(|| {
@@ -186,12 +251,7 @@ impl TransactionObfuscation for DefaultTransactionObfuscation {
 
                         let transactions = txs
                             .into_iter()
-                            .map(|tx| match tx {
-                                TxWithOutputs::Transfer(t) => Transaction::TransferTransaction(t),
-                                TxWithOutputs::StakeWithdraw(t) => {
-                                    Transaction::WithdrawUnbondedStakeTransaction(t)
-                                }
-                            })
+                            .map(into_transaction)
                             .collect::<Vec<Transaction>>();
 
                         Ok(transactions)
@@ -218,15 +278,201 @@ impl TransactionObfuscation for DefaultTransactionObfuscation {
         unreachable!()
     }
 
-    fn encrypt(&self, transaction: SignedTransaction) -> Result<TxAux> {
-        let client_config = get_tls_config();
-        let dns_name = self.tqe_hostname.as_ref();
-        let mut sess = rustls::ClientSession::new(&client_config, dns_name);
+    fn subscribe_to(
+        &self,
+        endpoint: &TxQueryEndpoint,
+        client_config: &Arc<rustls::ClientConfig>,
+        after_height: BlockHeight,
+        timeout_secs: u32,
+        private_key: &PrivateKey,
+    ) -> Result<TransactionNotification> {
+        let mut sess = rustls::ClientSession::new(client_config, endpoint.hostname.as_ref());
 
-        let mut conn = TcpStream::connect(&self.tqe_address).chain(|| {
+        let mut conn = TcpStream::connect(&endpoint.address).chain(|| {
             (
                 ErrorKind::ConnectionError,
-                format!("Unable to connect to TQE address: {}", self.tqe_address),
+                format!("Unable to connect to TQE address: {}", endpoint.address),
+            )
+        })?;
+        // the enclave holds the request at most `MAX_SUBSCRIPTION_TIMEOUT_SECS`
+        let read_timeout = u64::from(timeout_secs.min(MAX_SUBSCRIPTION_TIMEOUT_SECS)) + 30;
+        conn.set_read_timeout(Some(Duration::from_secs(read_timeout)))
+            .chain(|| (ErrorKind::IoError, "Unable to set TQE connection timeout"))?;
+        let mut tls = rustls::Stream::new(&mut sess, &mut conn);
+        tls.write_all(&TxQueryInitRequest::SubscribeChallenge.encode())
+            .chain(|| {
+                (
+                    ErrorKind::IoError,
+                    "Unable to write to TQE connection stream (init subscribe)",
+                )
+            })?;
+        tls.flush().chain(|| {
+            (
+                ErrorKind::IoError,
+                "Unable to write to TQE connection stream (init subscribe flush)",
+            )
+        })?;
+        let mut challenge = [0u8; 33];
+        tls.read_exact(&mut challenge).chain(|| {
+            (
+                ErrorKind::ConnectionError,
+                "Unable to read from TQE connection stream (too many active subscriptions?)",
+            )
+        })?;
+        let ch = match TxQueryInitResponse::decode(&mut challenge.as_ref()) {
+            Ok(TxQueryInitResponse::SubscribeChallenge(challenge)) => challenge,
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::IoError,
+                    "unexpected response from TQE connection stream",
+                ))
+            }
+        };
+
+        let request = SubscriptionRequest::create(
+            secp256k1::SECP256K1,
+            after_height,
+            timeout_secs,
+            ch,
+            &private_key.into(),
+        );
+        tls.write_all(&request.encode()).chain(|| {
+            (
+                ErrorKind::IoError,
+                "Unable to write to TQE connection stream (subscription request)",
+            )
+        })?;
+        tls.flush().chain(|| {
+            (
+                ErrorKind::IoError,
+                "Unable to write to TQE connection stream (subscription request flush)",
+            )
+        })?;
+        let mut plaintext = Vec::new();
+        tls.read_to_end(&mut plaintext).chain(|| {
+            (
+                ErrorKind::IoError,
+                "Unable to read from TQE connection stream",
+            )
+        })?;
+        let notification =
+            SubscriptionNotification::decode(&mut plaintext.as_slice()).chain(|| {
+                (
+                    ErrorKind::DeserializationError,
+                    "Unable to deserialize subscription notification from enclave",
+                )
+            })?;
+        Ok(TransactionNotification {
+            last_height: notification.last_height,
+            transactions: notification
+                .txs
+                .into_iter()
+                .map(|(height, tx)| (height, into_transaction(tx)))
+                .collect(),
+        })
+    }
+
+    fn discover_from(
+        &self,
+        endpoint: &TxQueryEndpoint,
+        client_config: &Arc<rustls::ClientConfig>,
+        from_height: BlockHeight,
+        to_height: BlockHeight,
+        private_key: &PrivateKey,
+    ) -> Result<TransactionNotification> {
+        let mut sess = rustls::ClientSession::new(client_config, endpoint.hostname.as_ref());
+
+        let mut conn = TcpStream::connect(&endpoint.address).chain(|| {
+            (
+                ErrorKind::ConnectionError,
+                format!("Unable to connect to TQE address: {}", endpoint.address),
+            )
+        })?;
+        let mut tls = rustls::Stream::new(&mut sess, &mut conn);
+        tls.write_all(&TxQueryInitRequest::DiscoverChallenge.encode())
+            .chain(|| {
+                (
+                    ErrorKind::IoError,
+                    "Unable to write to TQE connection stream (init discover)",
+                )
+            })?;
+        tls.flush().chain(|| {
+            (
+                ErrorKind::IoError,
+                "Unable to write to TQE connection stream (init discover flush)",
+            )
+        })?;
+        let mut challenge = [0u8; 33];
+        tls.read_exact(&mut challenge).chain(|| {
+            (
+                ErrorKind::IoError,
+                "Unable to read from TQE connection stream",
+            )
+        })?;
+        let ch = match TxQueryInitResponse::decode(&mut challenge.as_ref()) {
+            Ok(TxQueryInitResponse::DiscoverChallenge(challenge)) => challenge,
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::IoError,
+                    "unexpected response from TQE connection stream",
+                ))
+            }
+        };
+
+        let request = DiscoveryRequest::create(
+            secp256k1::SECP256K1,
+            from_height,
+            to_height,
+            ch,
+            &private_key.into(),
+        );
+        tls.write_all(&request.encode()).chain(|| {
+            (
+                ErrorKind::IoError,
+                "Unable to write to TQE connection stream (discovery request)",
+            )
+        })?;
+        tls.flush().chain(|| {
+            (
+                ErrorKind::IoError,
+                "Unable to write to TQE connection stream (discovery request flush)",
+            )
+        })?;
+        let mut plaintext = Vec::new();
+        tls.read_to_end(&mut plaintext).chain(|| {
+            (
+                ErrorKind::IoError,
+                "Unable to read from TQE connection stream",
+            )
+        })?;
+        let response = DiscoveryResponse::decode(&mut plaintext.as_slice()).chain(|| {
+            (
+                ErrorKind::DeserializationError,
+                "Unable to deserialize discovery response from enclave",
+            )
+        })?;
+        Ok(TransactionNotification {
+            last_height: response.last_height,
+            transactions: response
+                .txs
+                .into_iter()
+                .map(|(height, tx)| (height, into_transaction(tx)))
+                .collect(),
+        })
+    }
+
+    fn encrypt_to(
+        &self,
+        endpoint: &TxQueryEndpoint,
+        client_config: &Arc<rustls::ClientConfig>,
+        transaction: SignedTransaction,
+    ) -> Result<TxAux> {
+        let mut sess = rustls::ClientSession::new(client_config, endpoint.hostname.as_ref());
+
+        let mut conn = TcpStream::connect(&endpoint.address).chain(|| {
+            (
+                ErrorKind::ConnectionError,
+                format!("Unable to connect to TQE address: {}", endpoint.address),
             )
         })?;
         let mut tls = rustls::Stream::new(&mut sess, &mut conn);
@@ -280,3 +526,38 @@ impl TransactionObfuscation for DefaultTransactionObfuscation {
         }
     }
 }
+
+fn into_transaction(tx: TxWithOutputs) -> Transaction {
+    match tx {
+        TxWithOutputs::Transfer(t) => Transaction::TransferTransaction(t),
+        TxWithOutputs::StakeWithdraw(t) => Transaction::WithdrawUnbondedStakeTransaction(t),
+    }
+}
+
+impl TransactionObfuscation for DefaultTransactionObfuscation {
+    fn decrypt(
+        &self,
+        transaction_ids: &[TxId],
+        private_key: &PrivateKey,
+    ) -> Result<Vec<Transaction>> {
+        if transaction_ids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let client_config = get_tls_config(&self.policy)?;
+        self.nodes.with_failover(|endpoint| {
+            self.decrypt_from(endpoint, &client_config, transaction_ids, private_key)
+        })
+    }
+
+    fn encrypt(&self, transaction: SignedTransaction) -> Result<TxAux> {
+        let client_config = get_tls_config(&self.policy)?;
+        self.nodes.with_failover(|endpoint| {
+            self.encrypt_to(endpoint, &client_config, transaction.clone())
+        })
+    }
+
+    fn tx_query_stats(&self) -> Result<Vec<EndpointStats>> {
+        Ok(self.endpoint_stats())
+    }
+}
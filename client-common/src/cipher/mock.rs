@@ -100,6 +100,31 @@ mod tests {
     use chain_core::tx::{TxEnclaveAux, TxWithOutputs};
     use mock_utils::seal;
 
+    use aes_gcm_siv::aead::generic_array::GenericArray;
+    use aes_gcm_siv::aead::{Aead, NewAead};
+    use aes_gcm_siv::Aes128GcmSiv;
+    use chain_core::common::MerkleTree;
+    use chain_core::init::coin::Coin;
+    use chain_core::state::account::{Nonce, StakedStateOpWitness, WithdrawUnbondedTx};
+    use chain_core::state::tendermint::BlockHeight;
+    use chain_core::tx::data::access::{TxAccess, TxAccessPolicy};
+    use chain_core::tx::data::address::ExtendedAddr;
+    use chain_core::tx::data::attribute::TxAttributes;
+    use chain_core::tx::data::input::TxoPointer;
+    use chain_core::tx::data::output::TxOut;
+    use chain_core::tx::fee::{
+        mock_obfuscated_transfer, mock_obfuscated_withdraw, FeeAlgorithm, LinearFee, Milli,
+    };
+    use chain_core::tx::witness::tree::RawXOnlyPubkey;
+    use chain_core::tx::witness::TxInWitness;
+    use chain_core::tx::{PlainTxAux, TransactionId, TxObfuscated, TxToObfuscate};
+    use quickcheck::quickcheck;
+    use secp256k1::recovery::{RecoverableSignature, RecoveryId};
+    use secp256k1::schnorrsig::SchnorrSignature;
+
+    use crate::PublicKey;
+    use parity_scale_codec::Encode;
+
     #[derive(Clone)]
     struct MockClient;
 
@@ -148,6 +173,10 @@ mod tests {
             })
         }
 
+        fn unconfirmed_txs(&self, _limit: u64) -> Result<Vec<Vec<u8>>> {
+            unreachable!()
+        }
+
         fn query_state_batch<T: Iterator<Item = u64>>(
             &self,
             _heights: T,
@@ -187,4 +216,130 @@ mod tests {
             _ => unreachable!(),
         }
     }
+
+    /// Encrypts the plain transaction as the enclave does (`tx-validation-next`)
+    fn enclave_encrypt(plain: PlainTxAux, txid: TxId) -> TxObfuscated {
+        let aead = Aes128GcmSiv::new(GenericArray::from_slice(&[7; 16]));
+        let init_vector = [3; 12];
+        let tx = TxToObfuscate::from(plain, txid).unwrap();
+        TxObfuscated {
+            key_from: BlockHeight::genesis(),
+            init_vector,
+            txpayload: aead
+                .encrypt(GenericArray::from_slice(&init_vector), &tx)
+                .unwrap(),
+            txid,
+        }
+    }
+
+    fn outputs(no_of_outputs: u8, timelocked: bool) -> Vec<TxOut> {
+        (0..no_of_outputs % 8)
+            .map(|i| {
+                let address = ExtendedAddr::OrTree([i; 32]);
+                let value = Coin::new(u64::from(i) * 1_000_000).unwrap();
+                if timelocked {
+                    TxOut::new_with_timelock(address, value, u64::from(i))
+                } else {
+                    TxOut::new(address, value)
+                }
+            })
+            .collect()
+    }
+
+    fn attributes(no_of_view_keys: u8) -> TxAttributes {
+        let policies = (0..no_of_view_keys % 4)
+            .map(|_| {
+                let public_key = PublicKey::from(&PrivateKey::new().unwrap());
+                TxAccessPolicy::new((&public_key).into(), TxAccess::AllData)
+            })
+            .collect();
+        TxAttributes::new_with_access(0xab, policies)
+    }
+
+    fn fee_and_size(txaux: &TxAux) -> (u64, u64) {
+        let fee_algorithm = LinearFee::new(
+            Milli::try_new(1, 250).unwrap(),
+            Milli::try_new(0, 1).unwrap(),
+        );
+        let fee = fee_algorithm.calculate_for_txaux(txaux).unwrap();
+        (fee.to_coin().into(), txaux.encode().len() as u64)
+    }
+
+    quickcheck! {
+        // the fee computed before the encryption (by the enclave and the clients) is the same
+        // as the one computed for the encrypted transaction (when it's validated)
+        fn check_transfer_fee_before_and_after_encryption(
+            no_of_inputs: u8,
+            no_of_outputs: u8,
+            no_of_view_keys: u8,
+            tree_sizes: Vec<u8>,
+            timelocked: bool
+        ) -> bool {
+            let tx = Tx::new_with(
+                (0..no_of_inputs % 8 + 1)
+                    .map(|i| TxoPointer::new([i; 32], i as usize))
+                    .collect(),
+                outputs(no_of_outputs, timelocked),
+                attributes(no_of_view_keys),
+            );
+            let witness: TxWitness = tx
+                .inputs
+                .iter()
+                .enumerate()
+                .map(|(i, _)| {
+                    let size = tree_sizes.get(i).map(|size| size % 16 + 1).unwrap_or(1);
+                    let leaves = (0..size)
+                        .map(|leaf| RawXOnlyPubkey::from([leaf; 32]))
+                        .collect::<Vec<_>>();
+                    let proof = MerkleTree::new(leaves)
+                        .generate_proof(RawXOnlyPubkey::from([0; 32]))
+                        .unwrap();
+                    let signature = SchnorrSignature::from_default(&[i as u8; 64]).unwrap();
+                    TxInWitness::TreeSig(signature, proof)
+                })
+                .collect::<Vec<_>>()
+                .into();
+
+            let encrypted = TxAux::EnclaveTx(TxEnclaveAux::TransferTx {
+                inputs: tx.inputs.clone(),
+                no_of_outputs: tx.outputs.len() as u16,
+                payload: enclave_encrypt(
+                    PlainTxAux::TransferTx(tx.clone(), witness.clone()),
+                    tx.id(),
+                ),
+            });
+            let mock_encrypted = MockAbciTransactionObfuscation::new(MockClient)
+                .encrypt(SignedTransaction::TransferTransaction(tx.clone(), witness.clone()))
+                .unwrap();
+
+            let expected = fee_and_size(&encrypted);
+            expected == fee_and_size(&mock_obfuscated_transfer(&tx, &witness))
+                && expected == fee_and_size(&mock_encrypted)
+        }
+
+        fn check_withdraw_fee_before_and_after_encryption(
+            nonce: Nonce,
+            no_of_outputs: u8,
+            no_of_view_keys: u8,
+            timelocked: bool
+        ) -> bool {
+            let tx = WithdrawUnbondedTx::new(
+                nonce,
+                outputs(no_of_outputs, timelocked),
+                attributes(no_of_view_keys),
+            );
+            let witness = StakedStateOpWitness::new(
+                RecoverableSignature::from_compact(&[1; 64], RecoveryId::from_i32(1).unwrap())
+                    .unwrap(),
+            );
+
+            let encrypted = TxAux::EnclaveTx(TxEnclaveAux::WithdrawUnbondedStakeTx {
+                no_of_outputs: tx.outputs.len() as u16,
+                witness: witness.clone(),
+                payload: enclave_encrypt(PlainTxAux::WithdrawUnbondedStakeTx(tx.clone()), tx.id()),
+            });
+
+            fee_and_size(&encrypted) == fee_and_size(&mock_obfuscated_withdraw(&tx, &witness))
+        }
+    }
 }
@@ -0,0 +1,368 @@
+//! Tx-query node selection and attestation pinning policy
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+
+use crate::{Error, ErrorKind, Result, ResultExt};
+
+/// Environment variable with comma-separated list of tx-query endpoints (`<HOST/IP:PORT>`)
+pub const TX_QUERY_ENDPOINTS_ENV: &str = "CRYPTO_CLIENT_TX_QUERY";
+/// Environment variable with hex-encoded accepted MRENCLAVE (current enclave version)
+pub const TX_QUERY_MRENCLAVE_ENV: &str = "CRYPTO_CLIENT_TX_QUERY_MRENCLAVE";
+/// Environment variable with hex-encoded accepted MRENCLAVE of the previous enclave version
+pub const TX_QUERY_PREVIOUS_MRENCLAVE_ENV: &str = "CRYPTO_CLIENT_TX_QUERY_PREVIOUS_MRENCLAVE";
+/// Environment variable with the minimum accepted TCB level
+pub const TX_QUERY_MIN_TCB_ENV: &str = "CRYPTO_CLIENT_TX_QUERY_MIN_TCB";
+
+/// Minimum platform TCB level an attested tx-query enclave has to report
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TcbLevel {
+    /// Only fully up-to-date platforms (`OK` quote status)
+    UpToDate,
+    /// Platforms which need software hardening (e.g. LVI mitigations) are accepted as well
+    SwHardeningNeeded,
+    /// Platforms which need configuration changes are accepted as well
+    ConfigurationNeeded,
+    /// Platforms with out-of-date TCB are accepted as well (testing only)
+    OutOfDate,
+}
+
+impl TcbLevel {
+    /// Returns all the enclave quote statuses which satisfy this TCB level
+    pub fn accepted_quote_statuses(self) -> Vec<&'static str> {
+        let mut statuses = vec!["OK"];
+        if self >= TcbLevel::SwHardeningNeeded {
+            statuses.push("SW_HARDENING_NEEDED");
+        }
+        if self >= TcbLevel::ConfigurationNeeded {
+            statuses.push("CONFIGURATION_NEEDED");
+            statuses.push("CONFIGURATION_AND_SW_HARDENING_NEEDED");
+        }
+        if self >= TcbLevel::OutOfDate {
+            statuses.push("GROUP_OUT_OF_DATE");
+        }
+        statuses
+    }
+}
+
+impl Default for TcbLevel {
+    #[inline]
+    fn default() -> Self {
+        // same as the default of `EnclaveCertVerifierConfig`
+        TcbLevel::SwHardeningNeeded
+    }
+}
+
+impl FromStr for TcbLevel {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "up-to-date" | "ok" => Ok(TcbLevel::UpToDate),
+            "sw-hardening-needed" => Ok(TcbLevel::SwHardeningNeeded),
+            "configuration-needed" => Ok(TcbLevel::ConfigurationNeeded),
+            "out-of-date" => Ok(TcbLevel::OutOfDate),
+            _ => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Unknown TCB level: {}", s),
+            )),
+        }
+    }
+}
+
+/// Enclave measurements and platform requirements a tx-query enclave is pinned to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttestationPolicy {
+    /// accepted MRENCLAVE of the current enclave version (`None` = the compile-time value)
+    pub mr_enclave: Option<[u8; 32]>,
+    /// accepted MRENCLAVE of the previous enclave version (to support enclave upgrades)
+    pub previous_mr_enclave: Option<[u8; 32]>,
+    /// minimum accepted TCB level
+    pub min_tcb_level: TcbLevel,
+}
+
+impl Default for AttestationPolicy {
+    fn default() -> Self {
+        AttestationPolicy {
+            mr_enclave: None,
+            previous_mr_enclave: None,
+            min_tcb_level: TcbLevel::default(),
+        }
+    }
+}
+
+/// A single tx-query endpoint
+#[derive(Debug, Clone)]
+pub struct TxQueryEndpoint {
+    /// connection string <HOST/IP:PORT>
+    pub address: String,
+    /// expected hostname (e.g. localhost in testing)
+    pub hostname: webpki::DNSName,
+}
+
+impl TxQueryEndpoint {
+    /// tqe_address: connection string <HOST/IP:PORT>
+    /// tqe_hostname: expected hostname (e.g. localhost in testing)
+    pub fn new(tqe_address: String, tqe_hostname: &str) -> Self {
+        // one may just write an ip address instead of a domain name, which isn't a valid DNS name
+        // so there's a default case
+        let hostname = webpki::DNSNameRef::try_from_ascii_str(tqe_hostname)
+            .unwrap_or_else(|_| webpki::DNSNameRef::try_from_ascii_str("localhost").unwrap())
+            .to_owned();
+        TxQueryEndpoint {
+            address: tqe_address,
+            hostname,
+        }
+    }
+
+    /// Parses endpoint from connection string <HOST/IP:PORT>
+    pub fn from_address(address: &str) -> Result<Self> {
+        let address = address.trim();
+        match address.split(':').next() {
+            Some(hostname) if !hostname.is_empty() => {
+                Ok(TxQueryEndpoint::new(address.to_owned(), hostname))
+            }
+            _ => Err(Error::new(
+                ErrorKind::ConnectionError,
+                "Unable to decode txquery address",
+            )),
+        }
+    }
+}
+
+/// Tx-query client configuration
+#[derive(Debug, Clone, Default)]
+pub struct TxQueryConfig {
+    /// endpoints in order of preference; if empty, the address advertised by the node is used
+    pub endpoints: Vec<TxQueryEndpoint>,
+    /// attestation pinning policy
+    pub attestation: AttestationPolicy,
+}
+
+impl TxQueryConfig {
+    /// Reads the configuration from `CRYPTO_CLIENT_TX_QUERY*` environment variables
+    pub fn from_env() -> Result<Self> {
+        let endpoints = match std::env::var(TX_QUERY_ENDPOINTS_ENV) {
+            Ok(value) => value
+                .split(',')
+                .filter(|address| !address.trim().is_empty())
+                .map(TxQueryEndpoint::from_address)
+                .collect::<Result<Vec<_>>>()?,
+            Err(_) => vec![],
+        };
+        let mr_enclave = read_measurement(TX_QUERY_MRENCLAVE_ENV)?;
+        let previous_mr_enclave = read_measurement(TX_QUERY_PREVIOUS_MRENCLAVE_ENV)?;
+        let min_tcb_level = match std::env::var(TX_QUERY_MIN_TCB_ENV) {
+            Ok(value) => value.parse()?,
+            Err(_) => TcbLevel::default(),
+        };
+        Ok(TxQueryConfig {
+            endpoints,
+            attestation: AttestationPolicy {
+                mr_enclave,
+                previous_mr_enclave,
+                min_tcb_level,
+            },
+        })
+    }
+}
+
+fn read_measurement(name: &str) -> Result<Option<[u8; 32]>> {
+    match std::env::var(name) {
+        Ok(value) => {
+            let bytes = hex::decode(value.trim()).chain(|| {
+                (
+                    ErrorKind::InvalidInput,
+                    format!("Unable to decode hex of {}", name),
+                )
+            })?;
+            if bytes.len() != 32 {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("{} should be 32 bytes", name),
+                ));
+            }
+            let mut measurement = [0u8; 32];
+            measurement.copy_from_slice(&bytes);
+            Ok(Some(measurement))
+        }
+        Err(_) => Ok(None),
+    }
+}
+
+/// Telemetry of a single tx-query endpoint
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct EndpointStats {
+    /// endpoint connection string
+    pub address: String,
+    /// number of successful requests
+    pub successes: u64,
+    /// number of failed requests
+    pub failures: u64,
+    /// duration of the last successful request in milliseconds
+    pub last_latency_ms: Option<u64>,
+    /// time of the last successful request (seconds since the unix epoch); every request is made
+    /// over a newly attested connection, so it's also the time of the last valid attestation
+    pub last_success_at: Option<u64>,
+    /// last error message
+    pub last_error: Option<String>,
+}
+
+/// Tx-query endpoints with automatic failover
+#[derive(Debug, Clone)]
+pub struct TxQueryNodes {
+    endpoints: Vec<TxQueryEndpoint>,
+    /// index of the endpoint which served the last successful request
+    preferred: Arc<AtomicUsize>,
+    stats: Arc<Mutex<Vec<EndpointStats>>>,
+}
+
+impl TxQueryNodes {
+    /// Creates a new set of endpoints (at least one is required)
+    pub fn new(endpoints: Vec<TxQueryEndpoint>) -> Result<Self> {
+        if endpoints.is_empty() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "At least one tx-query endpoint is required",
+            ));
+        }
+        let stats = endpoints
+            .iter()
+            .map(|endpoint| EndpointStats {
+                address: endpoint.address.clone(),
+                ..Default::default()
+            })
+            .collect();
+        Ok(TxQueryNodes {
+            endpoints,
+            preferred: Arc::new(AtomicUsize::new(0)),
+            stats: Arc::new(Mutex::new(stats)),
+        })
+    }
+
+    /// Returns telemetry of all endpoints
+    pub fn stats(&self) -> Vec<EndpointStats> {
+        self.stats.lock().expect("tx-query stats lock").clone()
+    }
+
+    /// Runs the request against the endpoints, starting from the last healthy one.
+    /// Only connection and I/O errors cause failover to the next endpoint.
+    pub fn with_failover<T, F>(&self, mut request: F) -> Result<T>
+    where
+        F: FnMut(&TxQueryEndpoint) -> Result<T>,
+    {
+        let start = self.preferred.load(Ordering::Relaxed);
+        let count = self.endpoints.len();
+        let mut last_error = None;
+        for offset in 0..count {
+            let index = (start + offset) % count;
+            let endpoint = &self.endpoints[index];
+            let begin = Instant::now();
+            match request(endpoint) {
+                Ok(value) => {
+                    self.record(index, Ok(begin.elapsed()));
+                    self.preferred.store(index, Ordering::Relaxed);
+                    return Ok(value);
+                }
+                Err(e) => {
+                    self.record(index, Err(&e));
+                    match e.kind() {
+                        ErrorKind::ConnectionError | ErrorKind::IoError => {
+                            log::warn!("tx-query endpoint {} failed: {}", endpoint.address, e);
+                            last_error = Some(e);
+                        }
+                        _ => return Err(e),
+                    }
+                }
+            }
+        }
+        Err(last_error.expect("at least one endpoint"))
+    }
+
+    fn record(&self, index: usize, result: std::result::Result<Duration, &Error>) {
+        let mut stats = self.stats.lock().expect("tx-query stats lock");
+        let entry = &mut stats[index];
+        match result {
+            Ok(latency) => {
+                entry.successes += 1;
+                entry.last_latency_ms = Some(latency.as_millis() as u64);
+                entry.last_success_at = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|duration| duration.as_secs())
+                    .ok();
+            }
+            Err(e) => {
+                entry.failures += 1;
+                entry.last_error = Some(e.to_string());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nodes() -> TxQueryNodes {
+        TxQueryNodes::new(vec![
+            TxQueryEndpoint::from_address("10.0.0.1:3443").unwrap(),
+            TxQueryEndpoint::from_address("10.0.0.2:3443").unwrap(),
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn check_failover_on_connection_error() {
+        let nodes = nodes();
+        let served_by = nodes
+            .with_failover(|endpoint| {
+                if endpoint.address.starts_with("10.0.0.1") {
+                    Err(Error::new(ErrorKind::ConnectionError, "refused"))
+                } else {
+                    Ok(endpoint.address.clone())
+                }
+            })
+            .unwrap();
+        assert_eq!("10.0.0.2:3443", served_by);
+
+        // the healthy endpoint is tried first next time
+        let first_tried = nodes
+            .with_failover(|endpoint| Ok(endpoint.address.clone()))
+            .unwrap();
+        assert_eq!("10.0.0.2:3443", first_tried);
+
+        let stats = nodes.stats();
+        assert_eq!(1, stats[0].failures);
+        assert_eq!(2, stats[1].successes);
+    }
+
+    #[test]
+    fn check_no_failover_on_invalid_input() {
+        let nodes = nodes();
+        let mut attempts = 0;
+        let result: Result<()> = nodes.with_failover(|_| {
+            attempts += 1;
+            Err(Error::new(ErrorKind::InvalidInput, "invalid tx"))
+        });
+        assert_eq!(ErrorKind::InvalidInput, result.unwrap_err().kind());
+        assert_eq!(1, attempts);
+    }
+
+    #[test]
+    fn check_tcb_level_statuses() {
+        assert_eq!(vec!["OK"], TcbLevel::UpToDate.accepted_quote_statuses());
+        assert!(TcbLevel::default()
+            .accepted_quote_statuses()
+            .contains(&"SW_HARDENING_NEEDED"));
+        assert!(!TcbLevel::default()
+            .accepted_quote_statuses()
+            .contains(&"GROUP_OUT_OF_DATE"));
+        assert_eq!(
+            TcbLevel::ConfigurationNeeded,
+            "configuration-needed".parse().unwrap()
+        );
+    }
+}
@@ -0,0 +1,106 @@
+//! Correlation IDs tracing a user operation across the wallet stack:
+//!
+//! the RPC server enters the ID of each request (from the `X-Correlation-Id` header, or a generated
+//! one) on the thread handling it, the log lines of the operation are prefixed with it and it's
+//! embedded in the JSON-RPC request IDs sent to the node, so the node logs of the queries
+//! and broadcasts made on behalf of the operation can be matched as well.
+use std::cell::RefCell;
+
+/// HTTP header carrying the correlation ID of a request
+pub const CORRELATION_ID_HEADER: &str = "x-correlation-id";
+/// Maximum length of a correlation ID provided by the caller
+const MAX_CORRELATION_ID_LEN: usize = 64;
+
+thread_local! {
+    static CURRENT: RefCell<Option<String>> = RefCell::new(None);
+}
+
+/// Generates a new (random) correlation ID
+pub fn new_correlation_id() -> String {
+    uuid::Uuid::new_v4().to_simple().to_string()
+}
+
+/// Validates a correlation ID provided by the caller (it ends up in log lines and node requests,
+/// so only short alphanumeric IDs with `-`, `_`, `.` or `:` are accepted)
+pub fn parse_correlation_id(id: &str) -> Option<String> {
+    let id = id.trim();
+    let valid = !id.is_empty()
+        && id.len() <= MAX_CORRELATION_ID_LEN
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_.:".contains(c));
+    if valid {
+        Some(id.to_owned())
+    } else {
+        None
+    }
+}
+
+/// Returns the correlation ID of the operation handled by the current thread
+pub fn current_correlation_id() -> Option<String> {
+    CURRENT.with(|current| current.borrow().clone())
+}
+
+/// Sets the correlation ID of the current thread until the guard is dropped
+/// (the previous ID is restored then)
+#[must_use]
+pub fn enter_correlation_id(id: String) -> CorrelationGuard {
+    let previous = CURRENT.with(|current| current.replace(Some(id)));
+    CorrelationGuard { previous }
+}
+
+/// Restores the previous correlation ID of the thread when dropped
+#[derive(Debug)]
+pub struct CorrelationGuard {
+    previous: Option<String>,
+}
+
+impl Drop for CorrelationGuard {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        CURRENT.with(|current| *current.borrow_mut() = previous);
+    }
+}
+
+/// Prefix of the log lines of the current operation (empty without a correlation ID)
+pub fn log_prefix() -> String {
+    match current_correlation_id() {
+        Some(id) => format!("[{}] ", id),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_correlation_scope() {
+        assert_eq!(None, current_correlation_id());
+        {
+            let _outer = enter_correlation_id("outer".to_owned());
+            {
+                let _inner = enter_correlation_id("inner".to_owned());
+                assert_eq!(Some("inner".to_owned()), current_correlation_id());
+                assert_eq!("[inner] ", log_prefix());
+            }
+            assert_eq!(Some("outer".to_owned()), current_correlation_id());
+        }
+        assert_eq!(None, current_correlation_id());
+        assert_eq!("", log_prefix());
+    }
+
+    #[test]
+    fn check_correlation_id_validation() {
+        assert_eq!(
+            Some("req-1:a_b.c".to_owned()),
+            parse_correlation_id(" req-1:a_b.c ")
+        );
+        assert_eq!(None, parse_correlation_id(""));
+        assert_eq!(None, parse_correlation_id("with space"));
+        assert_eq!(None, parse_correlation_id("line\nbreak"));
+        assert_eq!(None, parse_correlation_id(&"a".repeat(65)));
+        assert_eq!(32, new_correlation_id().len());
+        assert!(parse_correlation_id(&new_correlation_id()).is_some());
+    }
+}
@@ -125,6 +125,10 @@ pub enum ErrorKind {
     RunEnclaveError,
     /// Ledger error
     LedgerError,
+    /// Passphrase doesn't satisfy the passphrase policy
+    WeakPassphrase,
+    /// Address with a wrong checksum (e.g. a mistyped EIP-55 staking address)
+    InvalidChecksum,
 }
 
 impl fmt::Display for ErrorKind {
@@ -149,6 +153,8 @@ impl fmt::Display for ErrorKind {
             ErrorKind::VerifyError => write!(f, "Verify error"),
             ErrorKind::RunEnclaveError => write!(f, "Run enclave error"),
             ErrorKind::LedgerError => write!(f, "ledger error"),
+            ErrorKind::WeakPassphrase => write!(f, "Weak passphrase"),
+            ErrorKind::InvalidChecksum => write!(f, "Invalid address checksum"),
         }
     }
 }
@@ -8,6 +8,7 @@
 mod transaction;
 
 pub mod cipher;
+pub mod correlation;
 pub mod error;
 pub mod key;
 pub mod multi_sig_address;
@@ -16,7 +17,7 @@ pub mod storage;
 pub mod tendermint;
 
 #[doc(inline)]
-pub use crate::cipher::TransactionObfuscation;
+pub use crate::cipher::{TransactionNotification, TransactionObfuscation};
 #[doc(inline)]
 pub use error::{Error, ErrorKind, Result, ResultExt};
 #[doc(inline)]
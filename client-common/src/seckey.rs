@@ -3,6 +3,8 @@ use std::str::FromStr;
 
 use aes::{Aes256, NewBlockCipher};
 use aes_gcm_siv::aead::generic_array::GenericArray;
+use rand::rngs::OsRng;
+use rand::Rng;
 use secstr::{SecBox, SecUtf8};
 use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
 use zeroize::Zeroize;
@@ -35,15 +37,28 @@ impl FromStr for SecKey {
 /// Parse encryption key from hex string
 pub fn parse_hex_enckey(s: &str) -> Result<SecKey> {
     if let Ok(mut bytes) = hex::decode(s) {
-        let arr = GenericArray::from_exact_iter(bytes.iter().copied())
-            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "invalid seckey length"))?;
+        let key = enckey_from_bytes(&bytes);
         bytes.zeroize();
-        Ok(SecKey(SecBox::new(Box::new(arr))))
+        key
     } else {
         Err(Error::new(ErrorKind::InvalidInput, "invalid hex seckey"))
     }
 }
 
+/// Encryption key from raw bytes
+pub fn enckey_from_bytes(bytes: &[u8]) -> Result<SecKey> {
+    let arr = GenericArray::from_exact_iter(bytes.iter().copied())
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "invalid seckey length"))?;
+    Ok(SecKey(SecBox::new(Box::new(arr))))
+}
+
+/// Random encryption key
+pub fn generate_enckey() -> SecKey {
+    let mut arr = GenericArray::clone_from_slice(&[0; 32]);
+    OsRng.fill(&mut arr[..]);
+    SecKey(SecBox::new(Box::new(arr)))
+}
+
 const GLOBAL_DATA_CONTEXT: &str =
     "Thaler Experimental Network Wallet 2020-10-19 16:59:10 global wallet data encryption";
 const SALT_CONTEXT: &str =
@@ -2,12 +2,14 @@
 mod memory_storage;
 #[cfg(feature = "sled")]
 mod sled_storage;
+mod tenant_storage;
 mod unauthorized_storage;
 use parity_scale_codec::{Decode, Encode};
 
 pub use memory_storage::MemoryStorage;
 #[cfg(feature = "sled")]
 pub use sled_storage::SledStorage;
+pub use tenant_storage::{validate_tenant_id, TenantStorage};
 pub use unauthorized_storage::UnauthorizedStorage;
 
 use crate::SecKey;
@@ -237,6 +239,12 @@ pub fn encrypt_bytes<K: AsRef<[u8]>>(key: K, enckey: &SecKey, bytes: &[u8]) -> R
 
 /// Decrypts bytes with given enckey
 pub fn decrypt_bytes<K: AsRef<[u8]>>(key: K, enckey: &SecKey, bytes: &[u8]) -> Result<Vec<u8>> {
+    if bytes.len() < NONCE_SIZE {
+        return Err(Error::new(
+            ErrorKind::DecryptionError,
+            "Encrypted bytes are too short",
+        ));
+    }
     let algo = get_algo(enckey);
 
     let payload = Payload {
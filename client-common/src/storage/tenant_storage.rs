@@ -0,0 +1,221 @@
+use std::sync::Arc;
+
+use crate::seckey::{enckey_from_bytes, generate_enckey};
+use crate::storage::{decrypt_bytes, encrypt_bytes, NONCE_SIZE};
+use crate::{Error, ErrorKind, Result, ResultExt, SecKey, Storage};
+
+/// Keyspace of the tenant storage keys (wrapped by the tenant master keys)
+const TENANT_KEYS_KEYSPACE: &str = "core_tenant_keys";
+
+/// `Storage` of a single tenant on a shared storage: the keyspaces are namespaced
+/// with the tenant identifier, and all values are encrypted with the tenant storage key.
+///
+/// The storage key is random per tenant, and is stored wrapped (encrypted) by the tenant
+/// master key, so the data of a tenant can't be read without its master key, and a tenant
+/// can't access (or even list) the keyspaces of the other tenants.
+#[derive(Debug, Clone)]
+pub struct TenantStorage<S: Storage> {
+    inner: S,
+    prefix: Vec<u8>,
+    enckey: Arc<SecKey>,
+}
+
+/// Checks that the tenant identifier is non-empty and only contains `[A-Za-z0-9_-]`
+pub fn validate_tenant_id(tenant: &str) -> Result<()> {
+    if !tenant.is_empty()
+        && tenant
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        Ok(())
+    } else {
+        Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("Invalid tenant identifier: {}", tenant),
+        ))
+    }
+}
+
+impl<S: Storage> TenantStorage<S> {
+    /// Opens the storage of the tenant: its storage key is unwrapped with the master key
+    /// (or generated and stored wrapped, for a new tenant)
+    pub fn open(inner: S, tenant: &str, master_key: &SecKey) -> Result<Self> {
+        validate_tenant_id(tenant)?;
+        let enckey = match inner.get(TENANT_KEYS_KEYSPACE, tenant)? {
+            Some(wrapped) => {
+                if wrapped.len() <= NONCE_SIZE {
+                    return Err(Error::new(
+                        ErrorKind::DeserializationError,
+                        format!("Invalid wrapped storage key of tenant: {}", tenant),
+                    ));
+                }
+                let raw = decrypt_bytes(tenant, master_key, &wrapped).chain(|| {
+                    (
+                        ErrorKind::DecryptionError,
+                        format!("Unable to unwrap storage key of tenant: {}", tenant),
+                    )
+                })?;
+                enckey_from_bytes(&raw)?
+            }
+            None => {
+                let enckey = generate_enckey();
+                let wrapped = encrypt_bytes(tenant, master_key, enckey.unsecure())?;
+                inner.set(TENANT_KEYS_KEYSPACE, tenant, wrapped)?;
+                enckey
+            }
+        };
+        Ok(TenantStorage {
+            inner,
+            prefix: format!("tenant_{}/", tenant).into_bytes(),
+            enckey: Arc::new(enckey),
+        })
+    }
+
+    fn keyspace<K: AsRef<[u8]>>(&self, keyspace: K) -> Vec<u8> {
+        let mut namespaced = self.prefix.clone();
+        namespaced.extend_from_slice(keyspace.as_ref());
+        namespaced
+    }
+
+    /// values are bound to their (namespaced) keyspace and key
+    fn aad(keyspace: &[u8], key: &[u8]) -> Vec<u8> {
+        let mut aad = keyspace.to_vec();
+        aad.push(0);
+        aad.extend_from_slice(key);
+        aad
+    }
+
+    fn decrypt(&self, aad: &[u8], value: Option<Vec<u8>>) -> Result<Option<Vec<u8>>> {
+        value
+            .map(|value| decrypt_bytes(aad, &self.enckey, &value))
+            .transpose()
+    }
+}
+
+impl<S: Storage> Storage for TenantStorage<S> {
+    fn flush(&self) -> Result<()> {
+        self.inner.flush()
+    }
+
+    fn clear<K: AsRef<[u8]>>(&self, keyspace: K) -> Result<()> {
+        self.inner.clear(self.keyspace(keyspace))
+    }
+
+    fn get<K: AsRef<[u8]>, V: AsRef<[u8]>>(&self, keyspace: K, key: V) -> Result<Option<Vec<u8>>> {
+        let keyspace = self.keyspace(keyspace);
+        let value = self.inner.get(&keyspace, &key)?;
+        self.decrypt(&Self::aad(&keyspace, key.as_ref()), value)
+    }
+
+    fn set<K: AsRef<[u8]>, V: AsRef<[u8]>>(
+        &self,
+        keyspace: K,
+        key: V,
+        value: Vec<u8>,
+    ) -> Result<Option<Vec<u8>>> {
+        let keyspace = self.keyspace(keyspace);
+        let aad = Self::aad(&keyspace, key.as_ref());
+        let encrypted = encrypt_bytes(&aad, &self.enckey, &value)?;
+        let previous = self.inner.set(&keyspace, &key, encrypted)?;
+        self.decrypt(&aad, previous)
+    }
+
+    fn delete<K: AsRef<[u8]>, V: AsRef<[u8]>>(
+        &self,
+        keyspace: K,
+        key: V,
+    ) -> Result<Option<Vec<u8>>> {
+        let keyspace = self.keyspace(keyspace);
+        let previous = self.inner.delete(&keyspace, &key)?;
+        self.decrypt(&Self::aad(&keyspace, key.as_ref()), previous)
+    }
+
+    fn fetch_and_update<K, V, F>(&self, keyspace: K, key: V, f: F) -> Result<Option<Vec<u8>>>
+    where
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+        F: Fn(Option<&[u8]>) -> Result<Option<Vec<u8>>>,
+    {
+        let keyspace = self.keyspace(keyspace);
+        let aad = Self::aad(&keyspace, key.as_ref());
+        let previous = self.inner.fetch_and_update(&keyspace, &key, |current| {
+            let opened = current
+                .map(|current| decrypt_bytes(&aad, &self.enckey, current))
+                .transpose()?;
+            f(opened.as_ref().map(AsRef::as_ref))?
+                .map(|next| encrypt_bytes(&aad, &self.enckey, &next))
+                .transpose()
+        })?;
+        self.decrypt(&aad, previous)
+    }
+
+    fn keys<K: AsRef<[u8]>>(&self, keyspace: K) -> Result<Vec<Vec<u8>>> {
+        self.inner.keys(self.keyspace(keyspace))
+    }
+
+    fn contains_key<K: AsRef<[u8]>, V: AsRef<[u8]>>(&self, keyspace: K, key: V) -> Result<bool> {
+        self.inner.contains_key(self.keyspace(keyspace), key)
+    }
+
+    fn keyspaces(&self) -> Result<Vec<Vec<u8>>> {
+        Ok(self
+            .inner
+            .keyspaces()?
+            .into_iter()
+            .filter_map(|keyspace| {
+                keyspace
+                    .strip_prefix(self.prefix.as_slice())
+                    .map(<[u8]>::to_vec)
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+
+    #[test]
+    fn check_tenant_isolation() {
+        let storage = MemoryStorage::default();
+        let master_a = generate_enckey();
+        let master_b = generate_enckey();
+        let tenant_a = TenantStorage::open(storage.clone(), "a", &master_a).unwrap();
+        let tenant_b = TenantStorage::open(storage.clone(), "b", &master_b).unwrap();
+
+        tenant_a.set("wallet", "name", b"secret".to_vec()).unwrap();
+        assert_eq!(
+            Some(b"secret".to_vec()),
+            tenant_a.get("wallet", "name").unwrap()
+        );
+        assert_eq!(None, tenant_b.get("wallet", "name").unwrap());
+        assert_eq!(vec![b"wallet".to_vec()], tenant_a.keyspaces().unwrap());
+        assert!(tenant_b.keyspaces().unwrap().is_empty());
+
+        // stored encrypted under the namespaced keyspace
+        let raw = storage.get("tenant_a/wallet", "name").unwrap().unwrap();
+        assert_ne!(b"secret".to_vec(), raw);
+
+        // reopening requires the tenant's master key
+        let reopened = TenantStorage::open(storage.clone(), "a", &master_a).unwrap();
+        assert_eq!(
+            Some(b"secret".to_vec()),
+            reopened.get("wallet", "name").unwrap()
+        );
+        assert!(TenantStorage::open(storage.clone(), "a", &master_b).is_err());
+        assert!(TenantStorage::open(storage, "../a", &master_a).is_err());
+
+        let previous = tenant_a
+            .fetch_and_update("wallet", "name", |current| {
+                assert_eq!(Some(&b"secret"[..]), current);
+                Ok(Some(b"updated".to_vec()))
+            })
+            .unwrap();
+        assert_eq!(Some(b"secret".to_vec()), previous);
+        assert_eq!(
+            Some(b"updated".to_vec()),
+            tenant_a.delete("wallet", "name").unwrap()
+        );
+    }
+}
@@ -1,14 +1,20 @@
 //! Tendermint client operations
+mod cached_client;
 mod client;
 #[cfg(feature = "websocket-rpc")]
 mod rpc_client;
 mod unauthorized_client;
 
+pub mod fee_policy;
 pub mod lite;
 pub mod mock;
 pub mod types;
 
+pub use cached_client::{CachedClient, DEFAULT_BLOCK_CACHE_CAPACITY};
 pub use client::Client;
 #[cfg(feature = "websocket-rpc")]
-pub use rpc_client::WebsocketRpcClient;
+pub use rpc_client::{
+    Subscription, SubscriptionEvent, WebsocketRpcClient, NEW_BLOCK_QUERY, SUBSCRIPTION_BUFFER_SIZE,
+    TX_QUERY, VALIDATOR_SET_UPDATES_QUERY,
+};
 pub use unauthorized_client::UnauthorizedClient;
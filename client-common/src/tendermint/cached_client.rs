@@ -0,0 +1,340 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+
+use chain_core::state::ChainState;
+
+use crate::tendermint::{types::*, Client};
+use crate::{Error, ErrorKind, Result};
+
+/// Default number of blocks kept in the cache
+pub const DEFAULT_BLOCK_CACHE_CAPACITY: usize = 1000;
+
+/// `Client` sharing the fetched blocks, block results and chain states among its clones
+///
+/// Wallets synchronized concurrently with clones of this client request the same heights from
+/// the inner client only once: heights being fetched by one wallet are waited for by the others,
+/// and the fetched values stay in a bounded cache (the oldest entries are evicted first).
+#[derive(Clone)]
+pub struct CachedClient<C: Client> {
+    client: C,
+    blocks: Arc<HeightCache<Block>>,
+    block_results: Arc<HeightCache<BlockResultsResponse>>,
+    states: Arc<HeightCache<ChainState>>,
+}
+
+impl<C: Client> CachedClient<C> {
+    /// Creates a new instance of `CachedClient` keeping at most `capacity` entries per cache
+    pub fn new(client: C, capacity: usize) -> Self {
+        CachedClient {
+            client,
+            blocks: Arc::new(HeightCache::new(capacity)),
+            block_results: Arc::new(HeightCache::new(capacity)),
+            states: Arc::new(HeightCache::new(capacity)),
+        }
+    }
+
+    /// Returns the inner client
+    pub fn inner(&self) -> &C {
+        &self.client
+    }
+}
+
+impl<C: Client> Client for CachedClient<C> {
+    fn genesis(&self) -> Result<Genesis> {
+        self.client.genesis()
+    }
+
+    fn status(&self) -> Result<StatusResponse> {
+        self.client.status()
+    }
+
+    fn block(&self, height: u64) -> Result<Block> {
+        self.block_batch([height].iter())
+            .map(|mut blocks| blocks.remove(0))
+    }
+
+    fn block_batch<'a, T: Iterator<Item = &'a u64>>(&self, heights: T) -> Result<Vec<Block>> {
+        let heights = heights.copied().collect::<Vec<_>>();
+        self.blocks
+            .get_batch(&heights, |missing| self.client.block_batch(missing.iter()))
+    }
+
+    fn block_results(&self, height: u64) -> Result<BlockResultsResponse> {
+        self.block_results_batch([height].iter())
+            .map(|mut results| results.remove(0))
+    }
+
+    fn block_results_batch<'a, T: Iterator<Item = &'a u64>>(
+        &self,
+        heights: T,
+    ) -> Result<Vec<BlockResultsResponse>> {
+        let heights = heights.copied().collect::<Vec<_>>();
+        self.block_results.get_batch(&heights, |missing| {
+            self.client.block_results_batch(missing.iter())
+        })
+    }
+
+    fn broadcast_transaction(&self, transaction: &[u8]) -> Result<BroadcastTxResponse> {
+        self.client.broadcast_transaction(transaction)
+    }
+
+    fn query(
+        &self,
+        path: &str,
+        data: &[u8],
+        height: Option<Height>,
+        prove: bool,
+    ) -> Result<AbciQuery> {
+        self.client.query(path, data, height, prove)
+    }
+
+    fn unconfirmed_txs(&self, limit: u64) -> Result<Vec<Vec<u8>>> {
+        self.client.unconfirmed_txs(limit)
+    }
+
+    fn query_state_batch<T: Iterator<Item = u64>>(&self, heights: T) -> Result<Vec<ChainState>> {
+        let heights = heights.collect::<Vec<_>>();
+        self.states.get_batch(&heights, |missing| {
+            self.client.query_state_batch(missing.iter().copied())
+        })
+    }
+}
+
+struct CacheState<T> {
+    values: BTreeMap<u64, T>,
+    /// Insertion order of the cached heights (for eviction)
+    order: VecDeque<u64>,
+    /// Heights currently being fetched from the inner client
+    in_flight: BTreeSet<u64>,
+}
+
+/// Values indexed by block height, fetched at most once at a time
+struct HeightCache<T> {
+    state: Mutex<CacheState<T>>,
+    fetched: Condvar,
+    capacity: usize,
+}
+
+impl<T: Clone> HeightCache<T> {
+    fn new(capacity: usize) -> Self {
+        HeightCache {
+            state: Mutex::new(CacheState {
+                values: BTreeMap::new(),
+                order: VecDeque::new(),
+                in_flight: BTreeSet::new(),
+            }),
+            fetched: Condvar::new(),
+            capacity,
+        }
+    }
+
+    fn lock(&self) -> Result<MutexGuard<CacheState<T>>> {
+        self.state
+            .lock()
+            .map_err(|_| Error::new(ErrorKind::InternalError, "Unable to acquire lock on cache"))
+    }
+
+    /// Returns the values at given heights (in the same order), fetching the ones which are
+    /// neither cached nor being fetched by another thread with `fetch`
+    fn get_batch<F>(&self, heights: &[u64], fetch: F) -> Result<Vec<T>>
+    where
+        F: Fn(&[u64]) -> Result<Vec<T>>,
+    {
+        let mut found = BTreeMap::new();
+
+        loop {
+            let mut state = self.lock()?;
+            let mut missing = Vec::new();
+            let mut waiting = false;
+
+            for height in heights {
+                if found.contains_key(height) {
+                    continue;
+                }
+
+                if let Some(value) = state.values.get(height) {
+                    found.insert(*height, value.clone());
+                } else if state.in_flight.contains(height) {
+                    waiting = true;
+                } else if !missing.contains(height) {
+                    missing.push(*height);
+                }
+            }
+
+            if missing.is_empty() {
+                if !waiting {
+                    break;
+                }
+
+                // heights fetched by other threads are inserted (or released on failure) before
+                // notification
+                drop(self.fetched.wait(state).map_err(|_| {
+                    Error::new(ErrorKind::InternalError, "Unable to acquire lock on cache")
+                })?);
+                continue;
+            }
+
+            state.in_flight.extend(missing.iter().copied());
+            drop(state);
+
+            let result = fetch(&missing).and_then(|values| {
+                if values.len() == missing.len() {
+                    Ok(values)
+                } else {
+                    Err(Error::new(
+                        ErrorKind::InvalidInput,
+                        "Number of values returned by tendermint does not match requested heights",
+                    ))
+                }
+            });
+
+            let mut state = self.lock()?;
+            for height in missing.iter() {
+                state.in_flight.remove(height);
+            }
+
+            match result {
+                Ok(values) => {
+                    for (height, value) in missing.into_iter().zip(values) {
+                        self.insert(&mut state, height, value.clone());
+                        found.insert(height, value);
+                    }
+                    self.fetched.notify_all();
+                }
+                Err(err) => {
+                    self.fetched.notify_all();
+                    return Err(err);
+                }
+            }
+        }
+
+        Ok(heights.iter().map(|height| found[height].clone()).collect())
+    }
+
+    fn insert(&self, state: &mut CacheState<T>, height: u64, value: T) {
+        if state.values.insert(height, value).is_none() {
+            state.order.push_back(height);
+        }
+
+        while state.order.len() > self.capacity {
+            if let Some(oldest) = state.order.pop_front() {
+                state.values.remove(&oldest);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+
+    use crate::tendermint::mock;
+
+    #[derive(Clone, Default)]
+    struct CountingClient {
+        fetched: Arc<AtomicUsize>,
+    }
+
+    impl Client for CountingClient {
+        fn genesis(&self) -> Result<Genesis> {
+            unreachable!()
+        }
+
+        fn status(&self) -> Result<StatusResponse> {
+            unreachable!()
+        }
+
+        fn block(&self, _height: u64) -> Result<Block> {
+            unreachable!()
+        }
+
+        fn block_batch<'a, T: Iterator<Item = &'a u64>>(&self, heights: T) -> Result<Vec<Block>> {
+            let blocks = heights.map(|_| mock::block()).collect::<Vec<_>>();
+            self.fetched.fetch_add(blocks.len(), Ordering::SeqCst);
+            thread::sleep(std::time::Duration::from_millis(10));
+            Ok(blocks)
+        }
+
+        fn block_results(&self, _height: u64) -> Result<BlockResultsResponse> {
+            unreachable!()
+        }
+
+        fn block_results_batch<'a, T: Iterator<Item = &'a u64>>(
+            &self,
+            _heights: T,
+        ) -> Result<Vec<BlockResultsResponse>> {
+            unreachable!()
+        }
+
+        fn broadcast_transaction(&self, _transaction: &[u8]) -> Result<BroadcastTxResponse> {
+            unreachable!()
+        }
+
+        fn query(
+            &self,
+            _path: &str,
+            _data: &[u8],
+            _height: Option<Height>,
+            _prove: bool,
+        ) -> Result<AbciQuery> {
+            unreachable!()
+        }
+
+        fn unconfirmed_txs(&self, _limit: u64) -> Result<Vec<Vec<u8>>> {
+            unreachable!()
+        }
+
+        fn query_state_batch<T: Iterator<Item = u64>>(
+            &self,
+            _heights: T,
+        ) -> Result<Vec<ChainState>> {
+            unreachable!()
+        }
+    }
+
+    #[test]
+    fn check_concurrent_block_fetching() {
+        let inner = CountingClient::default();
+        let client = CachedClient::new(inner.clone(), DEFAULT_BLOCK_CACHE_CAPACITY);
+
+        let handles = (0..4)
+            .map(|_| {
+                let client = client.clone();
+                thread::spawn(move || {
+                    for start in (1..=100).step_by(10) {
+                        let heights = (start..start + 10).collect::<Vec<u64>>();
+                        assert_eq!(10, client.block_batch(heights.iter()).unwrap().len());
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(100, inner.fetched.load(Ordering::SeqCst));
+
+        client.block(50).unwrap();
+        assert_eq!(100, inner.fetched.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn check_cache_eviction() {
+        let inner = CountingClient::default();
+        let client = CachedClient::new(inner.clone(), 5);
+
+        let heights = (1..=10).collect::<Vec<u64>>();
+        assert_eq!(10, client.block_batch(heights.iter()).unwrap().len());
+        assert_eq!(10, inner.fetched.load(Ordering::SeqCst));
+
+        client.block(10).unwrap();
+        assert_eq!(10, inner.fetched.load(Ordering::SeqCst));
+
+        client.block(1).unwrap();
+        assert_eq!(11, inner.fetched.load(Ordering::SeqCst));
+    }
+}
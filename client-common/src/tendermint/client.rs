@@ -40,6 +40,9 @@ pub trait Client: Send + Sync + Clone {
         prove: bool,
     ) -> Result<AbciQuery>;
 
+    /// Makes `unconfirmed_txs` call to tendermint (returns at most `limit` raw transactions from the mempool)
+    fn unconfirmed_txs(&self, limit: u64) -> Result<Vec<Vec<u8>>>;
+
     /// Match batch state `abci_query` call to tendermint
     fn query_state_batch<T: Iterator<Item = u64>>(&self, heights: T) -> Result<Vec<ChainState>>;
 }
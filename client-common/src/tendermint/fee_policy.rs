@@ -0,0 +1,183 @@
+//! Fee policy in force on the node, cached with a staleness guard
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use parity_scale_codec::Decode;
+
+use chain_core::init::params::NetworkParameters;
+use chain_core::tx::fee::LinearFee;
+
+use crate::tendermint::types::AbciQueryExt;
+use crate::tendermint::Client;
+use crate::{ErrorKind, Result, ResultExt};
+
+/// Environment variable with the number of seconds a fetched fee policy is considered fresh
+pub const FEE_POLICY_TTL_ENV: &str = "CRYPTO_CLIENT_FEE_POLICY_TTL";
+/// Environment variable allowing to build transactions with a stale fee policy (`true`/`false`)
+/// when it can't be refreshed
+pub const ALLOW_STALE_FEE_POLICY_ENV: &str = "CRYPTO_CLIENT_ALLOW_STALE_FEE_POLICY";
+/// Default time a fetched fee policy is considered fresh
+pub const DEFAULT_FEE_POLICY_TTL: Duration = Duration::from_secs(600);
+
+/// Fee policy cache configuration
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeePolicyConfig {
+    /// time a fetched fee policy is considered fresh
+    pub ttl: Duration,
+    /// use the stale fee policy if it can't be refreshed (instead of failing)
+    pub allow_stale: bool,
+}
+
+impl Default for FeePolicyConfig {
+    fn default() -> Self {
+        FeePolicyConfig {
+            ttl: DEFAULT_FEE_POLICY_TTL,
+            allow_stale: false,
+        }
+    }
+}
+
+impl FeePolicyConfig {
+    /// Reads the configuration from `CRYPTO_CLIENT_FEE_POLICY_TTL` and
+    /// `CRYPTO_CLIENT_ALLOW_STALE_FEE_POLICY` environment variables
+    pub fn from_env() -> Result<Self> {
+        let ttl = match std::env::var(FEE_POLICY_TTL_ENV) {
+            Ok(value) => Duration::from_secs(value.trim().parse().chain(|| {
+                (
+                    ErrorKind::InvalidInput,
+                    format!("Invalid number of seconds in {}", FEE_POLICY_TTL_ENV),
+                )
+            })?),
+            Err(_) => DEFAULT_FEE_POLICY_TTL,
+        };
+        let allow_stale = match std::env::var(ALLOW_STALE_FEE_POLICY_ENV) {
+            Ok(value) => value.trim().parse().chain(|| {
+                (
+                    ErrorKind::InvalidInput,
+                    format!("{} should be true or false", ALLOW_STALE_FEE_POLICY_ENV),
+                )
+            })?,
+            Err(_) => false,
+        };
+        Ok(FeePolicyConfig { ttl, allow_stale })
+    }
+}
+
+/// Fetches the fee policy in force at the latest committed block (`network-params` query)
+pub fn query_fee_policy<C: Client>(client: &C) -> Result<LinearFee> {
+    let rsp = client.query("network-params", &[], None, false)?;
+    let params = NetworkParameters::decode(&mut rsp.bytes().as_slice())
+        .err_kind(ErrorKind::DeserializationError, || {
+            "Cannot deserialize network parameters"
+        })?;
+    Ok(params.current().initial_fee_policy)
+}
+
+/// Last fetched fee policy (shared by the clones)
+#[derive(Debug, Clone, Default)]
+pub struct FeePolicyCache {
+    config: FeePolicyConfig,
+    cached: Arc<Mutex<Option<(LinearFee, Instant)>>>,
+}
+
+impl FeePolicyCache {
+    /// Creates an empty cache
+    pub fn new(config: FeePolicyConfig) -> Self {
+        FeePolicyCache {
+            config,
+            cached: Default::default(),
+        }
+    }
+
+    /// Returns the cached fee policy if it's fresh, otherwise fetches it again; if it can't be
+    /// fetched, the stale fee policy is only returned if the configuration allows it
+    pub fn get<F>(&self, fetch: F) -> Result<LinearFee>
+    where
+        F: FnOnce() -> Result<LinearFee>,
+    {
+        let mut cached = self.cached.lock().expect("fee policy cache lock");
+        if let Some((policy, fetched_at)) = *cached {
+            if fetched_at.elapsed() < self.config.ttl {
+                return Ok(policy);
+            }
+        }
+        match fetch() {
+            Ok(policy) => {
+                *cached = Some((policy, Instant::now()));
+                Ok(policy)
+            }
+            Err(e) => match *cached {
+                Some((policy, fetched_at)) if self.config.allow_stale => {
+                    log::warn!(
+                        "Using fee policy fetched {}s ago, refresh failed: {}",
+                        fetched_at.elapsed().as_secs(),
+                        e
+                    );
+                    Ok(policy)
+                }
+                Some((_, fetched_at)) => Err(e).chain(|| {
+                    (
+                        ErrorKind::ValidationError,
+                        format!(
+                            "Fee policy fetched {}s ago is stale and can't be refreshed \
+                             (set {}=true to use it anyway)",
+                            fetched_at.elapsed().as_secs(),
+                            ALLOW_STALE_FEE_POLICY_ENV
+                        ),
+                    )
+                }),
+                None => Err(e),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chain_core::tx::fee::Milli;
+
+    use crate::Error;
+
+    fn fee_policy(constant: u64) -> LinearFee {
+        LinearFee::new(
+            Milli::try_new(constant, 0).unwrap(),
+            Milli::try_new(1, 0).unwrap(),
+        )
+    }
+
+    fn unreachable_node() -> Result<LinearFee> {
+        Err(Error::new(
+            ErrorKind::TendermintRpcError,
+            "node unreachable",
+        ))
+    }
+
+    #[test]
+    fn check_fee_policy_cache() {
+        let cache = FeePolicyCache::new(FeePolicyConfig::default());
+        assert!(cache.get(unreachable_node).is_err());
+        assert_eq!(fee_policy(1), cache.get(|| Ok(fee_policy(1))).unwrap());
+        // fresh: not fetched again
+        assert_eq!(fee_policy(1), cache.get(unreachable_node).unwrap());
+
+        let stale = FeePolicyCache::new(FeePolicyConfig {
+            ttl: Duration::from_secs(0),
+            allow_stale: false,
+        });
+        stale.get(|| Ok(fee_policy(1))).unwrap();
+        assert_eq!(fee_policy(2), stale.get(|| Ok(fee_policy(2))).unwrap());
+        assert_eq!(
+            ErrorKind::ValidationError,
+            stale.get(unreachable_node).unwrap_err().kind()
+        );
+
+        let overridden = FeePolicyCache::new(FeePolicyConfig {
+            ttl: Duration::from_secs(0),
+            allow_stale: true,
+        });
+        overridden.get(|| Ok(fee_policy(3))).unwrap();
+        assert_eq!(fee_policy(3), overridden.get(unreachable_node).unwrap());
+    }
+}
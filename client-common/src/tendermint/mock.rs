@@ -288,3 +288,15 @@ pub fn status_response() -> StatusResponse {
         validator_info: validator_info(),
     }
 }
+
+pub fn broadcast_tx_response() -> BroadcastTxResponse {
+    serde_json::from_str(
+        r#"{
+    "code": 0,
+    "data": "",
+    "log": "",
+    "hash": "E245B6E4B3FC65FF3A97EE7B6FC6135FDC004E9AACE54741B5E12C7FE10AAEC2"
+}"#,
+    )
+    .unwrap()
+}
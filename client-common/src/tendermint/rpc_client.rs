@@ -1,7 +1,12 @@
 mod async_rpc_client;
+mod subscription;
 mod sync_rpc_client;
 mod types;
 mod websocket_rpc_loop;
 
 pub use async_rpc_client::AsyncRpcClient;
+pub use subscription::{
+    Subscription, SubscriptionEvent, NEW_BLOCK_QUERY, SUBSCRIPTION_BUFFER_SIZE, TX_QUERY,
+    VALIDATOR_SET_UPDATES_QUERY,
+};
 pub use sync_rpc_client::SyncRpcClient as WebsocketRpcClient;
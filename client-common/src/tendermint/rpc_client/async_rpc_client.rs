@@ -12,10 +12,11 @@ use futures_util::{
     stream::{SplitSink, SplitStream},
 };
 use serde::Deserialize;
-use serde_json::Value;
+use serde_json::{json, Value};
 use tokio::{
     net::TcpStream,
     sync::{
+        mpsc::unbounded_channel,
         oneshot::{channel, Receiver, Sender},
         Mutex,
     },
@@ -28,6 +29,10 @@ pub type WebSocketWriter = SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>,
 /// websocket reader
 pub type WebSocketReader = SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>;
 use super::{
+    subscription::{
+        Subscription, SubscriptionEvent, SubscriptionRegistry, NEW_BLOCK_QUERY,
+        SUBSCRIPTION_BUFFER_SIZE, TX_QUERY,
+    },
     types::{ConnectionState, JsonRpcRequest, JsonRpcResponse},
     websocket_rpc_loop,
 };
@@ -37,6 +42,9 @@ const WAIT_FOR_CONNECTION_COUNT: usize = 50;
 
 const RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
 
+/// Number of transactions queried per page when backfilling missed transaction events
+const BACKFILL_TX_PAGE_SIZE: usize = 100;
+
 /// Tendermint RPC Client (uses websocket in transport layer)
 #[derive(Clone)]
 pub struct AsyncRpcClient {
@@ -44,7 +52,9 @@ pub struct AsyncRpcClient {
     /// websocket
     pub websocket_writer: Arc<Mutex<WebSocketWriter>>,
     channel_map: Arc<Mutex<HashMap<String, Sender<JsonRpcResponse>>>>,
+    subscriptions: Arc<SubscriptionRegistry>,
     unique_id: Arc<AtomicUsize>,
+    correlation_id: Option<String>,
 }
 
 impl AsyncRpcClient {
@@ -54,14 +64,18 @@ impl AsyncRpcClient {
     //
     // - Spawns `websocket_rpc_loop`.
     // - Spawns `websocket_rpc_loop` monitor.
+    // - Spawns a task renewing the subscriptions after reconnection.
     pub async fn new(url: &str) -> Result<Self> {
         let channel_map: Arc<Mutex<HashMap<String, Sender<JsonRpcResponse>>>> = Default::default();
+        let subscriptions: Arc<SubscriptionRegistry> = Default::default();
+        let (reconnected_sender, mut reconnected_receiver) = unbounded_channel();
 
         let (websocket_writer, websocket_reader) = websocket_rpc_loop::new_connection(url).await?;
         let websocket_writer = Arc::new(Mutex::new(websocket_writer));
 
         let loop_handle = websocket_rpc_loop::spawn(
             channel_map.clone(),
+            subscriptions.clone(),
             websocket_reader,
             websocket_writer.clone(),
         );
@@ -69,16 +83,43 @@ impl AsyncRpcClient {
         let connection_state = websocket_rpc_loop::monitor(
             url.to_owned(),
             channel_map.clone(),
+            subscriptions.clone(),
             loop_handle,
             websocket_writer.clone(),
+            reconnected_sender,
         );
 
-        Ok(Self {
+        let client = Self {
             connection_state,
             websocket_writer,
             channel_map,
+            subscriptions,
             unique_id: Arc::new(AtomicUsize::new(0)),
-        })
+            correlation_id: None,
+        };
+
+        let resubscribing_client = client.clone();
+        tokio::spawn(async move {
+            while reconnected_receiver.recv().await.is_some() {
+                if let Err(err) = resubscribing_client.resubscribe().await {
+                    log::warn!(
+                        "Unable to renew subscriptions after reconnection: {:?}",
+                        err
+                    );
+                }
+            }
+        });
+
+        Ok(client)
+    }
+
+    /// Returns a client (sharing the connection) whose JSON-RPC request IDs are prefixed
+    /// with the correlation ID, so the requests can be found in the node logs
+    pub fn with_correlation_id(&self, correlation_id: Option<String>) -> Self {
+        Self {
+            correlation_id,
+            ..self.clone()
+        }
     }
 
     /// Sends a RPC request
@@ -172,13 +213,134 @@ impl AsyncRpcClient {
         Ok(responses)
     }
 
+    /// Subscribes to the events matching a tendermint query (e.g. `NEW_BLOCK_QUERY`)
+    //
+    // # How it works
+    //
+    // - Unsubscribes the queries whose subscriptions were all dropped.
+    // - Adds a subscriber to the registry (events are dispatched to it by `websocket_rpc_loop`).
+    // - Subscribes to the query on the connection if it isn't subscribed yet.
+    pub async fn subscribe(&self, query: &str) -> Result<Subscription> {
+        for unused in self.subscriptions.take_unused() {
+            if let Err(err) = self.request("unsubscribe", &[json!(unused)]).await {
+                log::warn!("Unable to unsubscribe from {}: {:?}", unused, err);
+            }
+        }
+
+        let (subscription, needs_subscribe) =
+            self.subscriptions.add(query, SUBSCRIPTION_BUFFER_SIZE);
+        if needs_subscribe {
+            self.request("subscribe", &[json!(query)]).await?;
+        }
+
+        Ok(subscription)
+    }
+
+    /// Renews the subscriptions on a new connection and backfills the events missed while
+    /// disconnected
+    async fn resubscribe(&self) -> Result<()> {
+        // subscriptions of the previous connection are gone
+        self.subscriptions.take_unused();
+
+        for (query, last_position) in self.subscriptions.queries() {
+            self.request("subscribe", &[json!(query)])
+                .await
+                .with_context(|| format!("Unable to subscribe to {}", query))?;
+
+            if let Some((height, _)) = last_position {
+                for event in self.missed_events(&query, height).await? {
+                    self.subscriptions.dispatch(event);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Queries the events of `query` from block `height` (events which were already received are
+    /// skipped by the registry)
+    async fn missed_events(&self, query: &str, height: u64) -> Result<Vec<SubscriptionEvent>> {
+        let mut events = Vec::new();
+
+        if query == NEW_BLOCK_QUERY {
+            let status = self.request("status", &[]).await?;
+            let latest_height = status["sync_info"]["latest_block_height"]
+                .as_str()
+                .and_then(|height| height.parse::<u64>().ok())
+                .context("Unable to parse latest block height from status")?;
+
+            for height in height + 1..=latest_height {
+                let response = self.request("block", &[json!(height.to_string())]).await?;
+                events.push(backfilled_event(
+                    query,
+                    json!({
+                        "type": "tendermint/event/NewBlock",
+                        "value": { "block": response["block"] },
+                    }),
+                ));
+            }
+        } else if query.starts_with(TX_QUERY) {
+            let search_query = format!("{} AND tx.height >= {}", query, height);
+            let mut page = 1;
+
+            loop {
+                let response = self
+                    .request(
+                        "tx_search",
+                        &[
+                            json!(search_query),
+                            json!(false),
+                            json!(page.to_string()),
+                            json!(BACKFILL_TX_PAGE_SIZE.to_string()),
+                            json!("asc"),
+                        ],
+                    )
+                    .await?;
+                let txs = response["txs"].as_array().cloned().unwrap_or_default();
+
+                for tx in txs.iter() {
+                    events.push(backfilled_event(
+                        query,
+                        json!({
+                            "type": "tendermint/event/Tx",
+                            "value": {
+                                "TxResult": {
+                                    "height": tx["height"],
+                                    "index": tx["index"],
+                                    "tx": tx["tx"],
+                                    "result": tx["tx_result"],
+                                }
+                            },
+                        }),
+                    ));
+                }
+
+                if txs.len() < BACKFILL_TX_PAGE_SIZE {
+                    break;
+                }
+                page += 1;
+            }
+        } else {
+            log::warn!(
+                "Unable to query the events missed while disconnected for {}",
+                query
+            );
+        }
+
+        Ok(events)
+    }
+
     /// Sends a JSON-RPC request and returns `request_id` and `response_channel`
     async fn send_request(
         &self,
         method: &str,
         params: &[Value],
     ) -> Result<(String, Receiver<JsonRpcResponse>)> {
-        let id = self.unique_id.fetch_add(1, Ordering::Relaxed).to_string();
+        let unique_id = self.unique_id.fetch_add(1, Ordering::Relaxed);
+        let id = match &self.correlation_id {
+            Some(correlation_id) => format!("{}:{}", correlation_id, unique_id),
+            None => unique_id.to_string(),
+        };
         let message = prepare_message(&id, method, params)?;
         let (channel_sender, channel_receiver) = channel::<JsonRpcResponse>();
 
@@ -226,7 +388,8 @@ impl AsyncRpcClient {
 
         match response.error {
             Some(err) => bail!(
-                "Error response from tendermint RPC for request method ({}) and params ({:?}): {}",
+                "Error response from tendermint RPC for request {} method ({}) and params ({:?}): {}",
+                id,
                 method,
                 params,
                 err
@@ -249,6 +412,15 @@ impl AsyncRpcClient {
     }
 }
 
+fn backfilled_event(query: &str, data: Value) -> SubscriptionEvent {
+    SubscriptionEvent {
+        query: query.to_owned(),
+        data,
+        events: Default::default(),
+        backfilled: true,
+    }
+}
+
 fn prepare_message(id: &str, method: &str, params: &[Value]) -> Result<Message> {
     let request = JsonRpcRequest {
         id,
@@ -0,0 +1,312 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{Error, ErrorKind, Result};
+
+/// Query of the events of new blocks
+pub const NEW_BLOCK_QUERY: &str = "tm.event='NewBlock'";
+/// Query of the events of transactions (conditions can be appended, e.g. `AND tx.height > 5`)
+pub const TX_QUERY: &str = "tm.event='Tx'";
+/// Query of the events of validator set updates
+pub const VALIDATOR_SET_UPDATES_QUERY: &str = "tm.event='ValidatorSetUpdates'";
+
+/// Number of events buffered for a subscriber (newer events are dropped when it's full)
+pub const SUBSCRIPTION_BUFFER_SIZE: usize = 1000;
+
+/// Event received on a subscription
+#[derive(Debug, Clone, Deserialize)]
+pub struct SubscriptionEvent {
+    /// query of the subscription
+    pub query: String,
+    /// event data (`type` and `value`)
+    pub data: Value,
+    /// event attributes (e.g. `tx.height`)
+    #[serde(default)]
+    pub events: BTreeMap<String, Vec<String>>,
+    /// `true` if the event was missed while disconnected and queried after reconnection
+    #[serde(default)]
+    pub backfilled: bool,
+}
+
+impl SubscriptionEvent {
+    /// Returns the position (block height, transaction index) of new block and transaction
+    /// events, used to skip the events which were already received
+    pub fn position(&self) -> Option<(u64, u64)> {
+        let value = &self.data["value"];
+        if let Some(height) = value["block"]["header"]["height"].as_str() {
+            return height.parse().ok().map(|height| (height, 0));
+        }
+
+        let tx_result = &value["TxResult"];
+        let height = tx_result["height"].as_str()?.parse().ok()?;
+        let index = tx_result["index"].as_u64().unwrap_or_default();
+        Some((height, index))
+    }
+}
+
+/// Subscription to the events matching a query
+///
+/// Subscriptions to the same query share one subscription on the websocket connection, which is
+/// renewed after reconnection. The subscription is removed when it's dropped.
+pub struct Subscription {
+    id: u64,
+    query: String,
+    receiver: Receiver<SubscriptionEvent>,
+    dropped: Arc<AtomicU64>,
+    registry: Arc<SubscriptionRegistry>,
+}
+
+impl Subscription {
+    /// Returns the query of the subscription
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    /// Waits for the next event
+    pub fn recv(&self) -> Result<SubscriptionEvent> {
+        self.receiver.recv().map_err(|_| {
+            Error::new(
+                ErrorKind::TendermintRpcError,
+                format!("Subscription to {} is closed", self.query),
+            )
+        })
+    }
+
+    /// Waits for the next event at most for `timeout` (`None` if no event was received)
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<Option<SubscriptionEvent>> {
+        match self.receiver.recv_timeout(timeout) {
+            Ok(event) => Ok(Some(event)),
+            Err(RecvTimeoutError::Timeout) => Ok(None),
+            Err(RecvTimeoutError::Disconnected) => Err(Error::new(
+                ErrorKind::TendermintRpcError,
+                format!("Subscription to {} is closed", self.query),
+            )),
+        }
+    }
+
+    /// Returns the number of events dropped because the buffer of the subscription was full
+    pub fn dropped_events(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        self.registry.remove(self.id);
+    }
+}
+
+struct Subscriber {
+    id: u64,
+    sender: SyncSender<SubscriptionEvent>,
+    dropped: Arc<AtomicU64>,
+}
+
+#[derive(Default)]
+struct QuerySubscribers {
+    subscribers: Vec<Subscriber>,
+    /// position of the last received event
+    last_position: Option<(u64, u64)>,
+}
+
+#[derive(Default)]
+struct RegistryState {
+    next_id: u64,
+    queries: HashMap<String, QuerySubscribers>,
+    /// queries without subscribers which are still subscribed on the connection
+    unused: Vec<String>,
+}
+
+/// Subscribers of the queries subscribed on the websocket connection
+#[derive(Default)]
+pub struct SubscriptionRegistry {
+    state: Mutex<RegistryState>,
+}
+
+impl SubscriptionRegistry {
+    /// Adds a subscriber of the query, returns `true` if the query needs to be subscribed on
+    /// the connection
+    pub fn add(self: &Arc<Self>, query: &str, buffer_size: usize) -> (Subscription, bool) {
+        let (sender, receiver) = sync_channel(buffer_size);
+        let dropped = Arc::new(AtomicU64::new(0));
+
+        let mut state = self.state.lock().expect("subscription registry lock");
+        let id = state.next_id;
+        state.next_id += 1;
+
+        let was_unused = state.unused.iter().any(|unused| unused == query);
+        state.unused.retain(|unused| unused != query);
+        let is_new = !state.queries.contains_key(query);
+
+        state
+            .queries
+            .entry(query.to_owned())
+            .or_default()
+            .subscribers
+            .push(Subscriber {
+                id,
+                sender,
+                dropped: dropped.clone(),
+            });
+
+        let subscription = Subscription {
+            id,
+            query: query.to_owned(),
+            receiver,
+            dropped,
+            registry: self.clone(),
+        };
+        (subscription, is_new && !was_unused)
+    }
+
+    fn remove(&self, id: u64) {
+        let mut state = self.state.lock().expect("subscription registry lock");
+        let query = state.queries.iter_mut().find_map(|(query, subscribers)| {
+            let len = subscribers.subscribers.len();
+            subscribers
+                .subscribers
+                .retain(|subscriber| subscriber.id != id);
+            if subscribers.subscribers.len() != len {
+                Some(query.clone())
+            } else {
+                None
+            }
+        });
+
+        if let Some(query) = query {
+            if state.queries[&query].subscribers.is_empty() {
+                state.queries.remove(&query);
+                state.unused.push(query);
+            }
+        }
+    }
+
+    /// Sends the event to the subscribers of its query (events at or before the position of
+    /// the last received event are skipped)
+    pub fn dispatch(&self, event: SubscriptionEvent) {
+        let mut state = self.state.lock().expect("subscription registry lock");
+        let subscribers = match state.queries.get_mut(&event.query) {
+            Some(subscribers) => subscribers,
+            None => {
+                log::trace!(
+                    "Received an event of an unused subscription: {}",
+                    event.query
+                );
+                return;
+            }
+        };
+
+        if let Some(position) = event.position() {
+            if subscribers
+                .last_position
+                .map_or(false, |last_position| position <= last_position)
+            {
+                log::debug!("Skipping an already received event of {}", event.query);
+                return;
+            }
+            subscribers.last_position = Some(position);
+        }
+
+        for subscriber in subscribers.subscribers.iter() {
+            if let Err(TrySendError::Full(_)) = subscriber.sender.try_send(event.clone()) {
+                subscriber.dropped.fetch_add(1, Ordering::Relaxed);
+                log::warn!(
+                    "Subscription buffer of {} is full, event dropped",
+                    event.query
+                );
+            }
+        }
+    }
+
+    /// Returns the subscribed queries and the positions of their last received events
+    pub fn queries(&self) -> Vec<(String, Option<(u64, u64)>)> {
+        let state = self.state.lock().expect("subscription registry lock");
+        state
+            .queries
+            .iter()
+            .map(|(query, subscribers)| (query.clone(), subscribers.last_position))
+            .collect()
+    }
+
+    /// Takes the queries without subscribers (to be unsubscribed on the connection)
+    pub fn take_unused(&self) -> Vec<String> {
+        let mut state = self.state.lock().expect("subscription registry lock");
+        std::mem::take(&mut state.unused)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    fn block_event(height: u64) -> SubscriptionEvent {
+        SubscriptionEvent {
+            query: NEW_BLOCK_QUERY.to_owned(),
+            data: json!({
+                "type": "tendermint/event/NewBlock",
+                "value": { "block": { "header": { "height": height.to_string() } } }
+            }),
+            events: Default::default(),
+            backfilled: false,
+        }
+    }
+
+    #[test]
+    fn check_dispatch() {
+        let registry = Arc::new(SubscriptionRegistry::default());
+
+        let (first, is_new) = registry.add(NEW_BLOCK_QUERY, 2);
+        assert!(is_new);
+        let (second, is_new) = registry.add(NEW_BLOCK_QUERY, 1);
+        assert!(!is_new);
+
+        registry.dispatch(block_event(1));
+        registry.dispatch(block_event(1));
+        registry.dispatch(block_event(2));
+
+        assert_eq!(Some((1, 0)), first.recv().unwrap().position());
+        assert_eq!(Some((2, 0)), first.recv().unwrap().position());
+        assert_eq!(
+            None,
+            first
+                .recv_timeout(Duration::from_millis(10))
+                .unwrap()
+                .map(|event| event.position())
+        );
+        assert_eq!(0, first.dropped_events());
+
+        assert_eq!(Some((1, 0)), second.recv().unwrap().position());
+        assert_eq!(1, second.dropped_events());
+
+        assert_eq!(
+            vec![(NEW_BLOCK_QUERY.to_owned(), Some((2, 0)))],
+            registry.queries()
+        );
+    }
+
+    #[test]
+    fn check_unused_queries() {
+        let registry = Arc::new(SubscriptionRegistry::default());
+
+        let (subscription, _) = registry.add(TX_QUERY, SUBSCRIPTION_BUFFER_SIZE);
+        drop(subscription);
+        assert!(registry.queries().is_empty());
+
+        // still subscribed on the connection
+        let (subscription, is_new) = registry.add(TX_QUERY, SUBSCRIPTION_BUFFER_SIZE);
+        assert!(!is_new);
+        assert!(registry.take_unused().is_empty());
+
+        drop(subscription);
+        assert_eq!(vec![TX_QUERY.to_owned()], registry.take_unused());
+        assert!(registry.take_unused().is_empty());
+    }
+}
@@ -12,9 +12,15 @@ use tokio::runtime::Runtime;
 use chain_core::state::ChainState;
 use std::sync::Mutex;
 
-use super::async_rpc_client::AsyncRpcClient;
+use super::{async_rpc_client::AsyncRpcClient, subscription::Subscription};
 use crate::{
-    tendermint::{types::*, Client},
+    cipher::EndpointStats,
+    correlation::{current_correlation_id, log_prefix},
+    tendermint::{
+        fee_policy::{query_fee_policy, FeePolicyCache, FeePolicyConfig},
+        types::*,
+        Client,
+    },
     Error, ErrorKind, PrivateKey, Result, ResultExt, SignedTransaction, Transaction,
     TransactionObfuscation,
 };
@@ -47,15 +53,16 @@ pub struct SyncRpcClient {
     /// ASYNC RPC CLIENT
     pub async_rpc_client: Arc<Mutex<Option<AsyncRpcClient>>>,
     url: String,
+    fee_policy: FeePolicyCache,
 }
 
 impl FeeAlgorithm for SyncRpcClient {
     fn calculate_fee(&self, num_bytes: usize) -> std::result::Result<Fee, CoinError> {
-        self.get_fee_policy().calculate_fee(num_bytes)
+        self.fee_algorithm()?.calculate_fee(num_bytes)
     }
 
     fn calculate_for_txaux(&self, txaux: &TxAux) -> std::result::Result<Fee, CoinError> {
-        self.get_fee_policy().calculate_for_txaux(txaux)
+        self.fee_algorithm()?.calculate_for_txaux(txaux)
     }
 }
 
@@ -73,6 +80,11 @@ impl TransactionObfuscation for SyncRpcClient {
         let obfuscator = self.get_tx_query().map_err(|e| Error::new(e.0, e.1))?;
         obfuscator.encrypt(transaction)
     }
+
+    fn tx_query_stats(&self) -> Result<Vec<EndpointStats>> {
+        let obfuscator = self.get_tx_query().map_err(|e| Error::new(e.0, e.1))?;
+        obfuscator.tx_query_stats()
+    }
 }
 
 impl SyncRpcClient {
@@ -89,19 +101,22 @@ impl SyncRpcClient {
             runtime: Arc::new(Mutex::new(runtime)),
             async_rpc_client: Arc::new(Mutex::new(None)),
             url: url.to_string(),
+            fee_policy: FeePolicyCache::new(FeePolicyConfig::from_env()?),
         })
     }
 
-    /// get the fee policy
-    pub fn get_fee_policy(&self) -> LinearFee {
-        static POLICY: OnceCell<LinearFee> = OnceCell::new();
-        let policy = POLICY.get_or_init(|| {
-            self.genesis()
-                .map_err(|e| log::error!("get genesis failed: {:?}", e))
-                .expect("get tendermint genesis")
-                .fee_policy()
-        });
-        *policy
+    /// get the fee policy in force on the node (refreshed when the cached one is stale,
+    /// see `FeePolicyConfig`)
+    pub fn get_fee_policy(&self) -> Result<LinearFee> {
+        self.fee_policy.get(|| query_fee_policy(self))
+    }
+
+    /// the fee policy for `FeeAlgorithm` (whose error type can't carry the reason)
+    fn fee_algorithm(&self) -> std::result::Result<LinearFee, CoinError> {
+        self.get_fee_policy().map_err(|e| {
+            log::error!("Unable to get the fee policy: {}", e);
+            CoinError::Overflow
+        })
     }
 
     /// get the obfuscation from tx query
@@ -144,7 +159,9 @@ impl SyncRpcClient {
         for<'de> T: Deserialize<'de>,
     {
         let (sender, receiver) = sync_channel(1);
-        let async_rpc_client = self.get_async_client()?;
+        let async_rpc_client = self
+            .get_async_client()?
+            .with_correlation_id(current_correlation_id());
 
         self.runtime.lock().unwrap().spawn(async move {
             let response = async_rpc_client.call(method, &params).await;
@@ -167,6 +184,33 @@ impl SyncRpcClient {
             })
     }
 
+    /// Subscribes to the events matching a tendermint query (e.g. `NEW_BLOCK_QUERY`)
+    pub fn subscribe(&self, query: &str) -> Result<Subscription> {
+        let (sender, receiver) = sync_channel(1);
+        let async_rpc_client = self.get_async_client()?;
+        let query = query.to_owned();
+
+        self.runtime.lock().unwrap().spawn(async move {
+            let response = async_rpc_client.subscribe(&query).await;
+            if let Err(e) = sender.send(response) {
+                log::error!(
+                    "Unable to send tendermint subscription back to response channel: {}",
+                    e
+                );
+            }
+        });
+
+        receiver
+            .recv_timeout(RESPONSE_TIMEOUT)
+            .chain(|| (ErrorKind::TendermintRpcError, "Request timed out"))?
+            .chain(|| {
+                (
+                    ErrorKind::TendermintRpcError,
+                    "Error while subscribing to tendermint events",
+                )
+            })
+    }
+
     /// Makes RPC call in batch and deserializes responses
     pub fn call_batch<T>(&self, params: Vec<(&'static str, Vec<Value>)>) -> Result<Vec<T>>
     where
@@ -174,7 +218,9 @@ impl SyncRpcClient {
         for<'de> T: Deserialize<'de>,
     {
         let (sender, receiver) = sync_channel(1);
-        let async_rpc_client = self.get_async_client()?;
+        let async_rpc_client = self
+            .get_async_client()?
+            .with_correlation_id(current_correlation_id());
 
         self.runtime.lock().unwrap().spawn(async move {
             let response = async_rpc_client.call_batch(&params).await;
@@ -247,6 +293,12 @@ impl Client for SyncRpcClient {
     fn broadcast_transaction(&self, transaction: &[u8]) -> Result<BroadcastTxResponse> {
         let params = vec![json!(transaction)];
         let rsp = self.call::<BroadcastTxResponse>("broadcast_tx_sync", params)?;
+        log::info!(
+            "{}broadcast transaction {}: code {:?}",
+            log_prefix(),
+            rsp.hash,
+            rsp.code
+        );
 
         if rsp.code.is_err() {
             Err(Error::new(ErrorKind::TendermintRpcError, rsp.log.as_ref()))
@@ -288,6 +340,24 @@ impl Client for SyncRpcClient {
         Ok(result)
     }
 
+    /// Makes `unconfirmed_txs` call to tendermint
+    fn unconfirmed_txs(&self, limit: u64) -> Result<Vec<Vec<u8>>> {
+        let params = vec![json!(limit.to_string())];
+        self.call::<UnconfirmedTxsResponse>("unconfirmed_txs", params)?
+            .txs
+            .unwrap_or_default()
+            .iter()
+            .map(|tx| {
+                base64::decode(tx).chain(|| {
+                    (
+                        ErrorKind::DeserializationError,
+                        "Unable to decode unconfirmed transaction",
+                    )
+                })
+            })
+            .collect()
+    }
+
     /// Match batch state `abci_query` call to tendermint
     fn query_state_batch<T: Iterator<Item = u64>>(&self, heights: T) -> Result<Vec<ChainState>> {
         let params: Vec<(&str, Vec<Value>)> = heights
@@ -4,7 +4,7 @@ use std::sync::Arc;
 use anyhow::{Context, Result};
 use futures_util::{sink::SinkExt, stream::StreamExt};
 use tokio::{
-    sync::{oneshot::Sender, Mutex},
+    sync::{mpsc::UnboundedSender, oneshot::Sender, Mutex},
     task::JoinHandle,
     time::{delay_for, Duration},
 };
@@ -12,6 +12,7 @@ use tokio_tungstenite::{connect_async, tungstenite::Message};
 
 use super::{
     async_rpc_client::{WebSocketReader, WebSocketWriter},
+    subscription::{SubscriptionEvent, SubscriptionRegistry},
     types::{ConnectionState, JsonRpcResponse},
 };
 
@@ -37,10 +38,13 @@ pub async fn new_connection(url: &str) -> Result<(WebSocketWriter, WebSocketRead
 /// - Spawns a thread and runs `websocket_rpc_loop` in the thread which continues until the thread panics.
 /// - For each websocket message received:
 ///   - Parse the message into JSON-RPC response.
-///   - Pop the response channel from `channel_map` corresponding to response's `request_id`.
-///   - Send the response to the channel.
+///   - Events of subscriptions (`request_id` suffixed with `#event`) are sent to the subscribers
+///     in `subscriptions`.
+///   - Otherwise, pop the response channel from `channel_map` corresponding to response's
+///     `request_id` and send the response to the channel.
 pub fn spawn(
     channel_map: Arc<Mutex<HashMap<String, Sender<JsonRpcResponse>>>>,
+    subscriptions: Arc<SubscriptionRegistry>,
     mut websocket_reader: WebSocketReader,
     websocket_writer: Arc<Mutex<WebSocketWriter>>,
 ) -> JoinHandle<()> {
@@ -48,9 +52,11 @@ pub fn spawn(
         while let Some(message) = websocket_reader.next().await {
             match message {
                 Ok(message) => match message {
-                    Message::Text(ref message) => handle_text(message, channel_map.clone()).await,
+                    Message::Text(ref message) => {
+                        handle_text(message, channel_map.clone(), &subscriptions).await
+                    }
                     Message::Binary(ref message) => {
-                        handle_slice(message, channel_map.clone()).await
+                        handle_slice(message, channel_map.clone(), &subscriptions).await
                     }
                     Message::Ping(data) => send_pong(websocket_writer.clone(), data).await,
                     _ => {
@@ -74,13 +80,16 @@ pub fn spawn(
 ///   - `Connected`: `websocket_rpc_loop` is connected to websocket server
 ///   - `Disconnected`: `websocket_rpc_loop` is disconnected from websocket server. Connection should be retried.
 /// - This function spawns a thread and runs connection state machine in a loop.
-///   - If current state is `Disconnected`: Spawns `websocket_rpc_loop` and sets state to `Connected`.
+///   - If current state is `Disconnected`: Spawns `websocket_rpc_loop`, sets state to `Connected`
+///     and notifies `reconnected` (to renew the subscriptions).
 ///   - If current state is `Connected`: Waits for `websocket_rpc_loop` thread to end and sets state to `Disconnected`.
 pub fn monitor(
     url: String,
     channel_map: Arc<Mutex<HashMap<String, Sender<JsonRpcResponse>>>>,
+    subscriptions: Arc<SubscriptionRegistry>,
     loop_handle: JoinHandle<()>,
     websocket_writer: Arc<Mutex<WebSocketWriter>>,
+    reconnected: UnboundedSender<()>,
 ) -> Arc<Mutex<ConnectionState>> {
     let connection_state = Arc::new(Mutex::new(ConnectionState::Connected));
     let connection_state_clone = connection_state.clone();
@@ -107,9 +116,13 @@ pub fn monitor(
 
                             let new_handle = spawn(
                                 channel_map.clone(),
+                                subscriptions.clone(),
                                 new_websocket_reader,
                                 websocket_writer.clone(),
                             );
+                            if reconnected.send(()).is_err() {
+                                log::warn!("Unable to renew subscriptions after reconnection");
+                            }
 
                             (ConnectionState::Connected, Some(new_handle))
                         }
@@ -149,11 +162,12 @@ fn parse_slice(message: &[u8]) -> Result<JsonRpcResponse> {
 async fn handle_text(
     message: &str,
     channel_map: Arc<Mutex<HashMap<String, Sender<JsonRpcResponse>>>>,
+    subscriptions: &SubscriptionRegistry,
 ) {
     log::trace!("Received text websocket message: {}", message);
 
     match parse_text(message) {
-        Ok(text) => send_response(text, channel_map).await,
+        Ok(text) => send_response(text, channel_map, subscriptions).await,
         Err(err) => log::error!("{:?}", err),
     }
 }
@@ -162,19 +176,36 @@ async fn handle_text(
 async fn handle_slice(
     message: &[u8],
     channel_map: Arc<Mutex<HashMap<String, Sender<JsonRpcResponse>>>>,
+    subscriptions: &SubscriptionRegistry,
 ) {
     log::trace!("Received binary websocket message: {:?}", message);
     match parse_slice(message) {
-        Ok(slice) => send_response(slice, channel_map).await,
+        Ok(slice) => send_response(slice, channel_map, subscriptions).await,
         Err(err) => log::error!("{:?}", err),
     }
 }
 
-/// Sends json response to appropriate channel
+/// Sends json response to appropriate channel (or subscription event to the subscribers)
 async fn send_response(
     response: JsonRpcResponse,
     channel_map: Arc<Mutex<HashMap<String, Sender<JsonRpcResponse>>>>,
+    subscriptions: &SubscriptionRegistry,
 ) {
+    if response.id.ends_with("#event") {
+        match response
+            .result
+            .map(serde_json::from_value::<SubscriptionEvent>)
+        {
+            Some(Ok(event)) => subscriptions.dispatch(event),
+            Some(Err(err)) => log::error!("Unable to deserialize subscription event: {}", err),
+            None => log::warn!(
+                "Received a subscription event without result: {:?}",
+                response.error
+            ),
+        }
+        return;
+    }
+
     let sender = channel_map.lock().await.remove(&response.id);
 
     if let Some(sender) = sender {
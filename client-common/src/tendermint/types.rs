@@ -5,11 +5,13 @@ use parity_scale_codec::Decode;
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
-use crate::{ErrorKind, Result, ResultExt, Transaction};
+use crate::{Error, ErrorKind, Result, ResultExt, Transaction};
+use chain_core::common::H256;
 use chain_core::init::config::InitConfig;
 use chain_core::tx::data::TxId;
 use chain_core::tx::fee::LinearFee;
 use chain_core::tx::{TxAux, TxEnclaveAux, TxPublicAux};
+use chain_core::AppHashParts;
 
 pub use self::block_results::BlockResults;
 pub use tendermint::{
@@ -33,28 +35,68 @@ pub struct GenesisResponse {
     pub genesis: Genesis,
 }
 
+/// Response of `unconfirmed_txs` call
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct UnconfirmedTxsResponse {
+    /// total number of transactions in the mempool
+    pub total: String,
+    /// base64 encoded transactions (`null` if the mempool is empty)
+    pub txs: Option<Vec<String>>,
+}
+
 /// crypto-chain specific methods.
 pub trait BlockExt {
     /// Returns un-encrypted staking(deposit/unbound) transactions in a block
     /// (this may also contain invalid transactions)
-    fn staking_transactions(&self) -> Result<Vec<Transaction>>;
+    fn staking_transactions(&self) -> Result<Vec<Transaction>> {
+        self.staking_transactions_except(&|_| false)
+    }
 
     /// Returns ids of transactions whose main content is only available in enclaves (Transfer, Withdraw)
-    fn enclave_transaction_ids(&self) -> Result<Vec<TxId>>;
+    fn enclave_transaction_ids(&self) -> Result<Vec<TxId>> {
+        self.enclave_transaction_ids_except(&|_| false)
+    }
+
+    /// Same as `staking_transactions`, skipping the raw transactions matched by `is_custom`
+    /// (transaction types added by forks, which aren't `TxAux`)
+    fn staking_transactions_except(
+        &self,
+        is_custom: &dyn Fn(&[u8]) -> bool,
+    ) -> Result<Vec<Transaction>>;
+
+    /// Same as `enclave_transaction_ids`, skipping the raw transactions matched by `is_custom`
+    fn enclave_transaction_ids_except(
+        &self,
+        is_custom: &dyn Fn(&[u8]) -> bool,
+    ) -> Result<Vec<TxId>>;
 }
 
-impl BlockExt for Block {
-    fn staking_transactions(&self) -> Result<Vec<Transaction>> {
-        self.data
-            .iter()
-            .map(|raw| -> Result<TxAux> {
-                TxAux::decode(&mut raw.clone().into_vec().as_slice()).chain(|| {
-                    (
-                        ErrorKind::DeserializationError,
-                        "Unable to decode transactions from bytes in a block",
-                    )
-                })
+/// Decodes the transactions of the block which aren't matched by `is_custom`
+fn decode_transactions<'a>(
+    block: &'a Block,
+    is_custom: &'a dyn Fn(&[u8]) -> bool,
+) -> impl Iterator<Item = Result<TxAux>> + 'a {
+    block
+        .data
+        .iter()
+        .map(|raw| raw.clone().into_vec())
+        .filter(move |raw| !is_custom(raw))
+        .map(|raw| -> Result<TxAux> {
+            TxAux::decode(&mut raw.as_slice()).chain(|| {
+                (
+                    ErrorKind::DeserializationError,
+                    "Unable to decode transactions from bytes in a block",
+                )
             })
+        })
+}
+
+impl BlockExt for Block {
+    fn staking_transactions_except(
+        &self,
+        is_custom: &dyn Fn(&[u8]) -> bool,
+    ) -> Result<Vec<Transaction>> {
+        decode_transactions(self, is_custom)
             .filter_map(|tx_aux_result| match tx_aux_result {
                 Err(e) => Some(Err(e)),
                 Ok(tx_aux) => match tx_aux {
@@ -69,17 +111,12 @@ impl BlockExt for Block {
             })
             .collect::<Result<Vec<Transaction>>>()
     }
-    fn enclave_transaction_ids(&self) -> Result<Vec<TxId>> {
-        self.data
-            .iter()
-            .map(|raw| -> Result<TxAux> {
-                TxAux::decode(&mut raw.clone().into_vec().as_slice()).chain(|| {
-                    (
-                        ErrorKind::DeserializationError,
-                        "Unable to decode transactions from bytes in a block",
-                    )
-                })
-            })
+
+    fn enclave_transaction_ids_except(
+        &self,
+        is_custom: &dyn Fn(&[u8]) -> bool,
+    ) -> Result<Vec<TxId>> {
+        decode_transactions(self, is_custom)
             .filter_map(|tx_aux_result| match tx_aux_result {
                 Err(e) => Some(Err(e)),
                 Ok(tx_aux) => match tx_aux {
@@ -120,10 +157,108 @@ impl GenesisExt for Genesis {
 pub trait AbciQueryExt {
     /// get query result
     fn bytes(&self) -> Vec<u8>;
+
+    /// get the data of the proof operation with given type (if the query was proven)
+    fn proof_data(&self, field_type: &str) -> Option<&[u8]>;
+
+    /// get the root of the account trie the staked state of a `staking` query is proven
+    /// against, after checking it's included in given app hash (of the queried block);
+    /// `None` if the node didn't prove it (only the nodes with tx query keep
+    /// the app hash parts of the historical states)
+    fn verified_account_state_root(&self, app_hash: &H256) -> Result<Option<H256>>;
 }
 
 impl AbciQueryExt for AbciQuery {
     fn bytes(&self) -> Vec<u8> {
         self.value.clone()
     }
+
+    fn proof_data(&self, field_type: &str) -> Option<&[u8]> {
+        self.proof
+            .as_ref()?
+            .ops
+            .iter()
+            .find(|op| op.field_type == field_type)
+            .map(|op| op.data.as_slice())
+    }
+
+    fn verified_account_state_root(&self, app_hash: &H256) -> Result<Option<H256>> {
+        let mut proof_bytes = match self.proof_data("account-state") {
+            Some(proof_bytes) => proof_bytes,
+            None => return Ok(None),
+        };
+        let proof = AppHashParts::decode(&mut proof_bytes)
+            .err_kind(ErrorKind::DeserializationError, || {
+                "Cannot deserialize account state proof"
+            })?;
+        if !proof.verify(app_hash) {
+            return Err(Error::new(
+                ErrorKind::VerifyError,
+                "Verify account state root against the app hash failed",
+            ));
+        }
+        Ok(Some(proof.account_state_root))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parity_scale_codec::Encode;
+    use tendermint::merkle::proof::{Proof, ProofOp};
+
+    fn account_state_query(data: Vec<u8>) -> AbciQuery {
+        AbciQuery {
+            proof: Some(Proof {
+                ops: vec![ProofOp {
+                    field_type: "account-state".to_owned(),
+                    key: vec![],
+                    data,
+                }],
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn check_verified_account_state_root() {
+        let parts = AppHashParts {
+            valid_tx_root: [0u8; 32],
+            account_state_root: [1u8; 32],
+            rewards_pool_hash: [2u8; 32],
+            network_params_hash: [3u8; 32],
+            utxo_commitment: [4u8; 32],
+            validator_set_hash: [5u8; 32],
+        };
+        let app_hash = parts.app_hash();
+
+        let query = account_state_query(parts.encode());
+        assert_eq!(
+            Some([1u8; 32]),
+            query.verified_account_state_root(&app_hash).unwrap()
+        );
+        // not included in the app hash
+        assert_eq!(
+            ErrorKind::VerifyError,
+            query
+                .verified_account_state_root(&[0u8; 32])
+                .unwrap_err()
+                .kind()
+        );
+        // invalid proof data
+        assert_eq!(
+            ErrorKind::DeserializationError,
+            account_state_query(vec![1, 2, 3])
+                .verified_account_state_root(&app_hash)
+                .unwrap_err()
+                .kind()
+        );
+        // not proven by the node (no historical state)
+        assert_eq!(
+            None,
+            AbciQuery::default()
+                .verified_account_state_root(&app_hash)
+                .unwrap()
+        );
+    }
 }
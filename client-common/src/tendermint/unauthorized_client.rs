@@ -50,6 +50,10 @@ impl Client for UnauthorizedClient {
         Err(ErrorKind::PermissionDenied.into())
     }
 
+    fn unconfirmed_txs(&self, _limit: u64) -> Result<Vec<Vec<u8>>> {
+        Err(ErrorKind::PermissionDenied.into())
+    }
+
     fn query_state_batch<T: Iterator<Item = u64>>(&self, _heights: T) -> Result<Vec<ChainState>> {
         Err(ErrorKind::PermissionDenied.into())
     }
@@ -3,7 +3,7 @@ use serde::{Deserialize, Serialize};
 
 use super::{ErrorKind, Result, ResultExt};
 use chain_core::state::account::{
-    DepositBondTx, StakedStateOpWitness, UnbondTx, UnjailTx, WithdrawUnbondedTx,
+    DataAnchorTx, DepositBondTx, StakedStateOpWitness, UnbondTx, UnjailTx, WithdrawUnbondedTx,
 };
 use chain_core::state::validator::NodeJoinRequestTx;
 use chain_core::tx::data::input::TxoPointer;
@@ -75,6 +75,8 @@ pub enum Transaction {
     UnjailTransaction(UnjailTx),
     /// Node join transaction
     NodejoinTransaction(NodeJoinRequestTx),
+    /// Data anchor transaction
+    DataAnchorTransaction(DataAnchorTx),
 }
 
 impl Transaction {
@@ -86,7 +88,8 @@ impl Transaction {
             Transaction::UnbondStakeTransaction(_)
             | Transaction::WithdrawUnbondedStakeTransaction(_)
             | Transaction::UnjailTransaction(_)
-            | Transaction::NodejoinTransaction(_) => &[],
+            | Transaction::NodejoinTransaction(_)
+            | Transaction::DataAnchorTransaction(_) => &[],
         }
     }
 
@@ -98,7 +101,8 @@ impl Transaction {
             Transaction::UnbondStakeTransaction(_)
             | Transaction::DepositStakeTransaction(_)
             | Transaction::UnjailTransaction(_)
-            | Transaction::NodejoinTransaction(_) => &[],
+            | Transaction::NodejoinTransaction(_)
+            | Transaction::DataAnchorTransaction(_) => &[],
         }
     }
 }
@@ -113,6 +117,7 @@ impl Transaction {
             Transaction::WithdrawUnbondedStakeTransaction(ref transaction) => transaction.id(),
             Transaction::UnjailTransaction(ref transaction) => transaction.id(),
             Transaction::NodejoinTransaction(ref transaction) => transaction.id(),
+            Transaction::DataAnchorTransaction(ref transaction) => transaction.id(),
         }
     }
 }
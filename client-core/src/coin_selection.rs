@@ -0,0 +1,510 @@
+//! Coin selection strategies of the transaction builder
+//!
+//! A strategy selects the unspent transactions to spend for a given amount. Selection is
+//! fee-aware: the fee of the transaction depends on the number of inputs and on whether a change
+//! output is needed, so strategies ask a `FeeEstimator` for it. As transfer validation requires
+//! the inputs to exactly match the outputs plus the fee, a selection either matches the amount
+//! plus fee exactly (no change output), or leaves a non-zero change amount after paying the fee
+//! of the transaction *with* the change output. Inputs keep being added until one of these holds.
+use std::fmt;
+
+use rand::seq::SliceRandom;
+
+use chain_core::init::coin::Coin;
+use chain_core::tx::data::input::TxoPointer;
+use chain_core::tx::data::output::TxOut;
+use client_common::{Error, ErrorKind, Result, ResultExt};
+
+/// Estimates the fee of the transfer transaction being built
+pub trait FeeEstimator {
+    /// Returns the fee of the transaction with the given number of inputs, with or without a
+    /// change output
+    fn estimate_fee(&self, inputs: usize, change: bool) -> Result<Coin>;
+}
+
+/// Unspent transactions selected for a transfer transaction
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoinSelection {
+    /// Selected unspent transactions
+    pub inputs: Vec<(TxoPointer, TxOut)>,
+    /// Fee of the transaction
+    pub fee: Coin,
+    /// Change amount (zero if there's no change output)
+    pub change: Coin,
+}
+
+/// Strategy for selecting the unspent transactions spent by a transfer transaction
+pub trait CoinSelectionStrategy: fmt::Debug + Send + Sync {
+    /// Selects unspent transactions for `amount` (sum of the outputs) plus fee
+    fn select(
+        &self,
+        unspent_transactions: &[(TxoPointer, TxOut)],
+        amount: Coin,
+        fee_estimator: &dyn FeeEstimator,
+    ) -> Result<CoinSelection>;
+}
+
+/// Selects unspent transactions in the given order (e.g. after sorting them with
+/// `unspent_transactions::Operation`)
+#[derive(Debug, Default, Clone, Copy)]
+pub struct InputOrder;
+
+impl CoinSelectionStrategy for InputOrder {
+    fn select(
+        &self,
+        unspent_transactions: &[(TxoPointer, TxOut)],
+        amount: Coin,
+        fee_estimator: &dyn FeeEstimator,
+    ) -> Result<CoinSelection> {
+        select_in_order(unspent_transactions.iter(), amount, fee_estimator)
+    }
+}
+
+/// Selects unspent transactions with highest value first (fewest inputs)
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LargestFirst;
+
+impl CoinSelectionStrategy for LargestFirst {
+    fn select(
+        &self,
+        unspent_transactions: &[(TxoPointer, TxOut)],
+        amount: Coin,
+        fee_estimator: &dyn FeeEstimator,
+    ) -> Result<CoinSelection> {
+        let mut sorted = unspent_transactions.iter().collect::<Vec<_>>();
+        sorted.sort_by(|(_, a), (_, b)| a.value.cmp(&b.value).reverse());
+        select_in_order(sorted.into_iter(), amount, fee_estimator)
+    }
+}
+
+/// Selects unspent transactions with lowest value first (consolidates small outputs)
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SmallestFirst;
+
+impl CoinSelectionStrategy for SmallestFirst {
+    fn select(
+        &self,
+        unspent_transactions: &[(TxoPointer, TxOut)],
+        amount: Coin,
+        fee_estimator: &dyn FeeEstimator,
+    ) -> Result<CoinSelection> {
+        let mut sorted = unspent_transactions.iter().collect::<Vec<_>>();
+        sorted.sort_by(|(_, a), (_, b)| a.value.cmp(&b.value));
+        select_in_order(sorted.into_iter(), amount, fee_estimator)
+    }
+}
+
+/// Selects unspent transactions in random order, so that the selected inputs do not reveal
+/// the order (or value) of the unspent transactions of the wallet
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RandomSelection;
+
+impl CoinSelectionStrategy for RandomSelection {
+    fn select(
+        &self,
+        unspent_transactions: &[(TxoPointer, TxOut)],
+        amount: Coin,
+        fee_estimator: &dyn FeeEstimator,
+    ) -> Result<CoinSelection> {
+        let mut shuffled = unspent_transactions.iter().collect::<Vec<_>>();
+        shuffled.shuffle(&mut rand::thread_rng());
+        select_in_order(shuffled.into_iter(), amount, fee_estimator)
+    }
+}
+
+/// Selects all the unspent transactions (e.g. to sweep the wallet: without outputs, everything
+/// but the fee goes to the change output)
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SelectAll;
+
+impl CoinSelectionStrategy for SelectAll {
+    fn select(
+        &self,
+        unspent_transactions: &[(TxoPointer, TxOut)],
+        amount: Coin,
+        fee_estimator: &dyn FeeEstimator,
+    ) -> Result<CoinSelection> {
+        let inputs = unspent_transactions.to_vec();
+        let total = sum_values(inputs.iter())?;
+
+        let fee = fee_estimator.estimate_fee(inputs.len(), false)?;
+        if (amount + fee).ok() == Some(total) {
+            return Ok(CoinSelection {
+                inputs,
+                fee,
+                change: Coin::zero(),
+            });
+        }
+
+        let fee = fee_estimator.estimate_fee(inputs.len(), true)?;
+        match (amount + fee).ok() {
+            Some(required) if total > required => {
+                let change = (total - required).chain(|| {
+                    (
+                        ErrorKind::IllegalInput,
+                        "Amount of selected UTXOs is negative",
+                    )
+                })?;
+                Ok(CoinSelection {
+                    inputs,
+                    fee,
+                    change,
+                })
+            }
+            _ => Err(Error::new(ErrorKind::InvalidInput, "Insufficient balance")),
+        }
+    }
+}
+
+/// Searches (depth-first, highest values first) for unspent transactions which exactly match
+/// the amount plus fee, so that no change output is needed. Falls back to `LargestFirst` if
+/// no exact match is found within `max_tries` steps.
+#[derive(Debug, Clone, Copy)]
+pub struct BranchAndBound {
+    /// Maximum number of search steps
+    pub max_tries: usize,
+}
+
+impl Default for BranchAndBound {
+    fn default() -> Self {
+        BranchAndBound { max_tries: 100_000 }
+    }
+}
+
+impl CoinSelectionStrategy for BranchAndBound {
+    fn select(
+        &self,
+        unspent_transactions: &[(TxoPointer, TxOut)],
+        amount: Coin,
+        fee_estimator: &dyn FeeEstimator,
+    ) -> Result<CoinSelection> {
+        if !unspent_transactions.is_empty() {
+            if let Some(selection) = self.search(unspent_transactions, amount, fee_estimator)? {
+                return Ok(selection);
+            }
+        }
+        LargestFirst.select(unspent_transactions, amount, fee_estimator)
+    }
+}
+
+impl BranchAndBound {
+    fn search(
+        &self,
+        unspent_transactions: &[(TxoPointer, TxOut)],
+        amount: Coin,
+        fee_estimator: &dyn FeeEstimator,
+    ) -> Result<Option<CoinSelection>> {
+        // the search works on effective values (value minus the fee of spending it), the exact
+        // fee of a candidate selection is checked when it is found
+        let fee_one = u64::from(fee_estimator.estimate_fee(1, false)?);
+        let fee_two = u64::from(fee_estimator.estimate_fee(2, false)?);
+        let input_fee = fee_two.saturating_sub(fee_one);
+        let target = u64::from(amount).saturating_add(fee_one.saturating_sub(input_fee));
+
+        let mut candidates = unspent_transactions
+            .iter()
+            .filter(|(_, output)| u64::from(output.value) > input_fee)
+            .map(|utxo| (u64::from(utxo.1.value) - input_fee, utxo))
+            .collect::<Vec<_>>();
+        candidates.sort_by(|(a, _), (b, _)| a.cmp(b).reverse());
+        let values = candidates
+            .iter()
+            .map(|(value, _)| *value)
+            .collect::<Vec<_>>();
+
+        let mut search = Search {
+            values: &values,
+            target,
+            tries: self.max_tries,
+            selected: Vec::new(),
+        };
+        let remaining = values
+            .iter()
+            .fold(0u64, |sum, value| sum.saturating_add(*value));
+        if !search.run(0, 0, remaining) {
+            return Ok(None);
+        }
+
+        let inputs = search
+            .selected
+            .iter()
+            .map(|index| candidates[*index].1.clone())
+            .collect::<Vec<_>>();
+        let total = sum_values(inputs.iter())?;
+        let fee = fee_estimator.estimate_fee(inputs.len(), false)?;
+        if (amount + fee).ok() == Some(total) {
+            Ok(Some(CoinSelection {
+                inputs,
+                fee,
+                change: Coin::zero(),
+            }))
+        } else {
+            // fee is not exactly linear in the number of inputs
+            Ok(None)
+        }
+    }
+}
+
+struct Search<'a> {
+    values: &'a [u64],
+    target: u64,
+    tries: usize,
+    selected: Vec<usize>,
+}
+
+impl<'a> Search<'a> {
+    fn run(&mut self, position: usize, sum: u64, remaining: u64) -> bool {
+        if sum == self.target && !self.selected.is_empty() {
+            return true;
+        }
+        if self.tries == 0
+            || position == self.values.len()
+            || sum > self.target
+            || sum.saturating_add(remaining) < self.target
+        {
+            return false;
+        }
+        self.tries -= 1;
+
+        let value = self.values[position];
+        let remaining = remaining - value;
+
+        self.selected.push(position);
+        if self.run(position + 1, sum.saturating_add(value), remaining) {
+            return true;
+        }
+        self.selected.pop();
+
+        self.run(position + 1, sum, remaining)
+    }
+}
+
+fn sum_values<'a, I>(unspent_transactions: I) -> Result<Coin>
+where
+    I: Iterator<Item = &'a (TxoPointer, TxOut)>,
+{
+    let mut total = Coin::zero();
+    for (_, output) in unspent_transactions {
+        total = (total + output.value).chain(|| {
+            (
+                ErrorKind::IllegalInput,
+                "Total amount of selected UTXOs exceeds maximum allowed value",
+            )
+        })?;
+    }
+    Ok(total)
+}
+
+/// Adds unspent transactions in the given order until they pay for `amount` and fee
+fn select_in_order<'a, I>(
+    unspent_transactions: I,
+    amount: Coin,
+    fee_estimator: &dyn FeeEstimator,
+) -> Result<CoinSelection>
+where
+    I: Iterator<Item = &'a (TxoPointer, TxOut)>,
+{
+    let mut inputs = Vec::new();
+    let mut total = Coin::zero();
+
+    for unspent_transaction in unspent_transactions {
+        total = (total + unspent_transaction.1.value).chain(|| {
+            (
+                ErrorKind::IllegalInput,
+                "Total amount of selected UTXOs exceeds maximum allowed value",
+            )
+        })?;
+        inputs.push(unspent_transaction.clone());
+
+        let fee = fee_estimator.estimate_fee(inputs.len(), false)?;
+        if (amount + fee).ok() == Some(total) {
+            return Ok(CoinSelection {
+                inputs,
+                fee,
+                change: Coin::zero(),
+            });
+        }
+
+        let fee = fee_estimator.estimate_fee(inputs.len(), true)?;
+        if let Ok(required) = amount + fee {
+            if total > required {
+                let change = (total - required).chain(|| {
+                    (
+                        ErrorKind::IllegalInput,
+                        "Amount of selected UTXOs is negative",
+                    )
+                })?;
+                return Ok(CoinSelection {
+                    inputs,
+                    fee,
+                    change,
+                });
+            }
+        }
+    }
+
+    Err(Error::new(ErrorKind::InvalidInput, "Insufficient balance"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chain_core::tx::data::address::ExtendedAddr;
+
+    /// 10 + 3 per input + 2 for the change output
+    struct LinearEstimator;
+
+    impl FeeEstimator for LinearEstimator {
+        fn estimate_fee(&self, inputs: usize, change: bool) -> Result<Coin> {
+            Ok(Coin::new(10 + 3 * inputs as u64 + if change { 2 } else { 0 }).unwrap())
+        }
+    }
+
+    fn unspent_transactions(values: &[u64]) -> Vec<(TxoPointer, TxOut)> {
+        values
+            .iter()
+            .enumerate()
+            .map(|(i, value)| {
+                (
+                    TxoPointer::new([i as u8; 32], 0),
+                    TxOut::new(ExtendedAddr::OrTree([0; 32]), Coin::new(*value).unwrap()),
+                )
+            })
+            .collect()
+    }
+
+    fn values(selection: &CoinSelection) -> Vec<u64> {
+        selection
+            .inputs
+            .iter()
+            .map(|(_, output)| u64::from(output.value))
+            .collect()
+    }
+
+    #[test]
+    fn check_largest_and_smallest_first() {
+        let unspent_transactions = unspent_transactions(&[50, 200, 100]);
+        let amount = Coin::new(120).unwrap();
+
+        let selection = LargestFirst
+            .select(&unspent_transactions, amount, &LinearEstimator)
+            .unwrap();
+        assert_eq!(vec![200], values(&selection));
+        assert_eq!(Coin::new(15).unwrap(), selection.fee);
+        assert_eq!(Coin::new(65).unwrap(), selection.change);
+
+        let selection = SmallestFirst
+            .select(&unspent_transactions, amount, &LinearEstimator)
+            .unwrap();
+        assert_eq!(vec![50, 100], values(&selection));
+        assert_eq!(Coin::new(12).unwrap(), selection.change);
+    }
+
+    #[test]
+    fn check_fee_aware_change() {
+        // 100 covers 85 + 13 (fee without change), but not 85 + 15 (fee with change) plus a
+        // non-zero change, so another input is added
+        let unspent_transactions = unspent_transactions(&[100, 50]);
+        let selection = InputOrder
+            .select(
+                &unspent_transactions,
+                Coin::new(85).unwrap(),
+                &LinearEstimator,
+            )
+            .unwrap();
+        assert_eq!(vec![100, 50], values(&selection));
+        assert_eq!(Coin::new(18).unwrap(), selection.fee);
+        assert_eq!(Coin::new(47).unwrap(), selection.change);
+
+        // exact match without change output
+        let selection = InputOrder
+            .select(
+                &unspent_transactions,
+                Coin::new(87).unwrap(),
+                &LinearEstimator,
+            )
+            .unwrap();
+        assert_eq!(vec![100], values(&selection));
+        assert_eq!(Coin::zero(), selection.change);
+
+        assert_eq!(
+            ErrorKind::InvalidInput,
+            InputOrder
+                .select(
+                    &unspent_transactions,
+                    Coin::new(200).unwrap(),
+                    &LinearEstimator
+                )
+                .unwrap_err()
+                .kind()
+        );
+    }
+
+    #[test]
+    fn check_branch_and_bound() {
+        let unspent_transactions = unspent_transactions(&[400, 70, 300, 45, 90]);
+
+        // 300 + 90 + 45 = 435 = 416 + 10 + 3 * 3 (fee)
+        let selection = BranchAndBound::default()
+            .select(
+                &unspent_transactions,
+                Coin::new(416).unwrap(),
+                &LinearEstimator,
+            )
+            .unwrap();
+        let mut selected = values(&selection);
+        selected.sort();
+        assert_eq!(vec![45, 90, 300], selected);
+        assert_eq!(Coin::zero(), selection.change);
+
+        // no exact match: falls back to largest first
+        let selection = BranchAndBound::default()
+            .select(
+                &unspent_transactions,
+                Coin::new(100).unwrap(),
+                &LinearEstimator,
+            )
+            .unwrap();
+        assert_eq!(vec![400], values(&selection));
+        assert_eq!(Coin::new(285).unwrap(), selection.change);
+    }
+
+    #[test]
+    fn check_random_selection() {
+        let unspent_transactions = unspent_transactions(&[10, 20, 30, 40, 50]);
+        let selection = RandomSelection
+            .select(
+                &unspent_transactions,
+                Coin::new(100).unwrap(),
+                &LinearEstimator,
+            )
+            .unwrap();
+        let total = sum_values(selection.inputs.iter()).unwrap();
+        assert_eq!(
+            total,
+            ((Coin::new(100).unwrap() + selection.fee).unwrap() + selection.change).unwrap()
+        );
+    }
+
+    #[test]
+    fn check_select_all() {
+        let unspent_transactions = unspent_transactions(&[50, 200, 100]);
+
+        // sweep: the change output gets everything but the fee of 3 inputs and the change
+        let selection = SelectAll
+            .select(&unspent_transactions, Coin::zero(), &LinearEstimator)
+            .unwrap();
+        assert_eq!(vec![50, 200, 100], values(&selection));
+        assert_eq!(Coin::new(21).unwrap(), selection.fee);
+        assert_eq!(Coin::new(329).unwrap(), selection.change);
+
+        assert_eq!(
+            ErrorKind::InvalidInput,
+            SelectAll
+                .select(&unspent_transactions(&[15]), Coin::zero(), &LinearEstimator)
+                .unwrap_err()
+                .kind()
+        );
+    }
+}
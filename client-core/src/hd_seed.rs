@@ -5,7 +5,8 @@ use chain_core::init::network::{get_bip44_coin_type_from_network, Network};
 use client_common::{ErrorKind, PrivateKey, PublicKey, Result, ResultExt};
 
 use crate::hd_wallet::{
-    ChainPath, DefaultKeyChain, ExtendedPrivKey, ExtendedPubKey, KeyChain, KeyIndex,
+    Bip44Path, ChainPath, DefaultKeyChain, ExtendedPrivKey, ExtendedPubKey, KeyChain, KeyIndex,
+    Xpub, BIP44_EXTERNAL_CHANGE,
 };
 use crate::Mnemonic;
 
@@ -44,13 +45,22 @@ impl HDSeed {
         account_index: u32,
         index: u32,
     ) -> Result<(PublicKey, PrivateKey)> {
-        let chain_path = ChainPath::create_bip44(network, account_index, index);
+        self.derive_bip44_key_pair(Bip44Path::new(
+            network,
+            account_index,
+            BIP44_EXTERNAL_CHANGE,
+            index,
+        ))
+    }
+
+    /// Derive HD wallet at the full bip44 path (any account and change), and returns the key pair
+    pub fn derive_bip44_key_pair(&self, path: Bip44Path) -> Result<(PublicKey, PrivateKey)> {
         let key_chain = DefaultKeyChain::new(
             ExtendedPrivKey::with_seed(&self.bytes)
                 .chain(|| (ErrorKind::InternalError, "Invalid seed bytes"))?,
         );
 
-        let (extended_private_key, _) = key_chain.derive_private_key(chain_path).chain(|| {
+        let (extended_private_key, _) = key_chain.derive_private_key(path.into()).chain(|| {
             (
                 ErrorKind::InternalError,
                 "Failed to derive HD wallet private key",
@@ -63,6 +73,25 @@ impl HDSeed {
         Ok((public_key, private_key))
     }
 
+    /// Returns the account-level extended public key (`m / 44' / coin_type' / account'`), which
+    /// derives the public keys of the account without the seed (e.g. in watch-only wallets)
+    pub fn get_account_xpub(&self, network: Network, account_index: u32) -> Result<Xpub> {
+        let chain_path =
+            Bip44Path::new(network, account_index, BIP44_EXTERNAL_CHANGE, 0).account_path();
+        let key_chain = DefaultKeyChain::new(
+            ExtendedPrivKey::with_seed(&self.bytes)
+                .chain(|| (ErrorKind::InternalError, "Invalid seed bytes"))?,
+        );
+
+        let (account_key, derivation) = key_chain.derive_private_key(chain_path).chain(|| {
+            (
+                ErrorKind::InternalError,
+                "Failed to derive HD wallet private key",
+            )
+        })?;
+        Ok(Xpub::from_derivation(network, &account_key, &derivation))
+    }
+
     /// get publickey on specific index    
     pub fn get_pubkey(
         &self,
@@ -217,4 +246,28 @@ mod hd_seed_tests {
             );
         }
     }
+
+    #[test]
+    fn should_account_xpub_derive_the_account_keys() {
+        let mnemonic_words = SecUtf8::from("point shiver hurt flight fun online hub antenna engine pave chef fantasy front interest poem accident catch load frequent praise elite pet remove used");
+        let mnemonic = Mnemonic::from_secstr(&mnemonic_words)
+            .expect("should create mnemonic from mnemonic words");
+        let hd_seed = HDSeed::from(&mnemonic);
+
+        let xpub = hd_seed
+            .get_account_xpub(Network::Mainnet, 4)
+            .expect("account xpub");
+        assert_eq!(3, xpub.depth);
+        assert_eq!(xpub, xpub.to_string().parse().unwrap());
+
+        for change in 0..2 {
+            let (public_key, _) = hd_seed
+                .derive_bip44_key_pair(Bip44Path::new(Network::Mainnet, 4, change, 9))
+                .unwrap();
+            assert_eq!(
+                public_key,
+                PublicKey::from(xpub.derive_public_key(change, 9).unwrap())
+            );
+        }
+    }
 }
@@ -16,6 +16,8 @@ pub enum Error {
     ChainPath(ChainPathError),
     /// secp256k1 errors
     Secp(secp256k1::Error),
+    /// Invalid serialized extended public key
+    InvalidXpub,
 }
 
 impl std::error::Error for Error {}
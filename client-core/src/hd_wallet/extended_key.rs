@@ -6,6 +6,8 @@
 
 /// Key-index for hdwallet
 pub mod key_index;
+/// Serialized extended public key for hdwallet
+pub mod xpub;
 
 use crate::hd_wallet::{
     error::Error,
@@ -0,0 +1,174 @@
+//! # Serialized extended public key (BIP32 `xpub` format)
+use std::convert::TryInto;
+use std::fmt;
+use std::str::FromStr;
+
+use base58::{FromBase58, ToBase58};
+use chain_core::init::network::Network;
+use ring::digest;
+use ripemd160::{Digest, Ripemd160};
+use secp256k1::PublicKey;
+
+use crate::hd_wallet::{error::Error, Derivation, ExtendedPrivKey, ExtendedPubKey, KeyIndex};
+
+/// version bytes of extended public keys on mainnet (`xpub`)
+const MAINNET_VERSION: [u8; 4] = [0x04, 0x88, 0xb2, 0x1e];
+/// version bytes of extended public keys on the other networks (`tpub`)
+const TESTNET_VERSION: [u8; 4] = [0x04, 0x35, 0x87, 0xcf];
+/// length of the serialized key (without checksum)
+const SERIALIZED_LENGTH: usize = 78;
+
+/// Extended public key with its position in the key tree, as exported for watch-only wallets
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Xpub {
+    /// network of the key (`Testnet` for all the `tpub` keys)
+    pub network: Network,
+    /// depth of the key, 0 if it is master key
+    pub depth: u8,
+    /// fingerprint of the parent key
+    pub parent_fingerprint: [u8; 4],
+    /// raw index of the key in its parent
+    pub child_number: u32,
+    /// extended public key
+    pub key: ExtendedPubKey,
+}
+
+impl Xpub {
+    /// Creates the xpub of an extended private key derived by a key chain
+    pub fn from_derivation(
+        network: Network,
+        key: &ExtendedPrivKey,
+        derivation: &Derivation,
+    ) -> Self {
+        Xpub {
+            network,
+            depth: derivation.depth,
+            parent_fingerprint: derivation
+                .parent_key
+                .as_ref()
+                .map(|parent_key| fingerprint(&ExtendedPubKey::from_private_key(parent_key)))
+                .unwrap_or_default(),
+            child_number: derivation
+                .key_index
+                .map(KeyIndex::raw_index)
+                .unwrap_or_default(),
+            key: ExtendedPubKey::from_private_key(key),
+        }
+    }
+
+    /// Derives the public key at `change / index` under this key
+    pub fn derive_public_key(&self, change: u32, index: u32) -> Result<PublicKey, Error> {
+        Ok(self
+            .key
+            .derive_public_key(KeyIndex::Normal(change))?
+            .derive_public_key(KeyIndex::Normal(index))?
+            .public_key)
+    }
+}
+
+/// Returns the fingerprint (first 4 bytes of HASH160) of the public key
+fn fingerprint(key: &ExtendedPubKey) -> [u8; 4] {
+    let sha = digest::digest(&digest::SHA256, &key.public_key.serialize());
+    let hash = Ripemd160::digest(sha.as_ref());
+    hash[..4].try_into().expect("4 bytes fingerprint")
+}
+
+fn checksum(payload: &[u8]) -> [u8; 4] {
+    let hash = digest::digest(
+        &digest::SHA256,
+        digest::digest(&digest::SHA256, payload).as_ref(),
+    );
+    hash.as_ref()[..4].try_into().expect("4 bytes checksum")
+}
+
+impl fmt::Display for Xpub {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut buf = Vec::with_capacity(SERIALIZED_LENGTH + 4);
+        match self.network {
+            Network::Mainnet => buf.extend_from_slice(&MAINNET_VERSION),
+            Network::Testnet | Network::Devnet => buf.extend_from_slice(&TESTNET_VERSION),
+        }
+        buf.push(self.depth);
+        buf.extend_from_slice(&self.parent_fingerprint);
+        buf.extend_from_slice(&self.child_number.to_be_bytes());
+        buf.extend_from_slice(&self.key.chain_code);
+        buf.extend_from_slice(&self.key.public_key.serialize());
+        let checksum = checksum(&buf);
+        buf.extend_from_slice(&checksum);
+        write!(f, "{}", buf.to_base58())
+    }
+}
+
+impl FromStr for Xpub {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let data = s.trim().from_base58().map_err(|_| Error::InvalidXpub)?;
+        if data.len() != SERIALIZED_LENGTH + 4 {
+            return Err(Error::InvalidXpub);
+        }
+        let (payload, expected_checksum) = data.split_at(SERIALIZED_LENGTH);
+        if checksum(payload)[..] != *expected_checksum {
+            return Err(Error::InvalidXpub);
+        }
+
+        let network = if payload[..4] == MAINNET_VERSION {
+            Network::Mainnet
+        } else if payload[..4] == TESTNET_VERSION {
+            Network::Testnet
+        } else {
+            return Err(Error::InvalidXpub);
+        };
+        Ok(Xpub {
+            network,
+            depth: payload[4],
+            parent_fingerprint: payload[5..9].try_into().expect("4 bytes fingerprint"),
+            child_number: u32::from_be_bytes(payload[9..13].try_into().expect("4 bytes index")),
+            key: ExtendedPubKey {
+                chain_code: payload[13..45].to_vec(),
+                public_key: PublicKey::from_slice(&payload[45..])?,
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hd_wallet::{ChainPath, DefaultKeyChain, KeyChain};
+
+    #[test]
+    fn check_xpub_serialization() {
+        // BIP32 test vector 1
+        let seed = hex::decode("000102030405060708090a0b0c0d0e0f").unwrap();
+        let key_chain = DefaultKeyChain::new(ExtendedPrivKey::with_seed(&seed).unwrap());
+        for (path, expected) in &[
+            ("m", "xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8"),
+            ("m/0H", "xpub68Gmy5EdvgibQVfPdqkBBCHxA5htiqg55crXYuXoQRKfDBFA1WEjWgP6LHhwBZeNK1VTsfTFUHCdrfp1bgwQ9xv5ski8PX9rL2dZXvgGDnw"),
+            ("m/0H/1/2H", "xpub6D4BDPcP2GT577Vvch3R8wDkScZWzQzMMUm3PWbmWvVJrZwQY4VUNgqFJPMM3No2dFDFGTsxxpG5uJh7n7epu4trkrX7x7DogT5Uv6fcLW5"),
+        ] {
+            let (key, derivation) = key_chain.derive_private_key(ChainPath::from(*path)).unwrap();
+            let xpub = Xpub::from_derivation(Network::Mainnet, &key, &derivation);
+            assert_eq!(*expected, xpub.to_string());
+            assert_eq!(xpub, expected.parse().unwrap());
+        }
+
+        let (key, _) = key_chain
+            .derive_private_key(ChainPath::from("m/0H/1/2H/2/3"))
+            .unwrap();
+        let (parent_key, parent_derivation) = key_chain
+            .derive_private_key(ChainPath::from("m/0H/1/2H"))
+            .unwrap();
+        let xpub = Xpub::from_derivation(Network::Testnet, &parent_key, &parent_derivation);
+        assert_eq!(
+            ExtendedPubKey::from_private_key(&key).public_key,
+            xpub.derive_public_key(2, 3).unwrap()
+        );
+        assert!(xpub.to_string().starts_with("tpub"));
+
+        assert_eq!(
+            Err(Error::InvalidXpub),
+            "xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet9".parse::<Xpub>()
+        );
+    }
+}
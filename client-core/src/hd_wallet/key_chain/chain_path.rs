@@ -12,6 +12,13 @@ const MASTER_SYMBOL: &str = "m";
 const HARDENED_SYMBOLS: [&str; 2] = ["H", "'"];
 const SEPARATOR: char = '/';
 
+/// Purpose of BIP44 paths
+pub const BIP44_PURPOSE: u32 = 44;
+/// `change` of the external (receiving) addresses
+pub const BIP44_EXTERNAL_CHANGE: u32 = 0;
+/// `change` of the internal (change) addresses
+pub const BIP44_INTERNAL_CHANGE: u32 = 1;
+
 #[derive(Clone, Debug, Copy, PartialEq, Eq)]
 /// Error category
 pub enum Error {
@@ -58,10 +65,86 @@ impl ChainPath {
 
     /// Returns the bip44 hd path
     pub fn create_bip44(network: Network, account_index: u32, index: u32) -> Self {
-        let coin_type = get_bip44_coin_type_from_network(network);
+        Bip44Path::new(network, account_index, BIP44_EXTERNAL_CHANGE, index).into()
+    }
+
+    /// Parses the chain path as a full bip44 path
+    pub fn bip44(&self) -> Result<Bip44Path, Error> {
+        let sub_paths = self.iter().collect::<Result<Vec<_>, _>>()?;
+        let key_indexes = sub_paths
+            .iter()
+            .skip(1)
+            .map(|sub_path| match sub_path {
+                SubPath::Child(key_index) => Ok(*key_index),
+                SubPath::Root => Err(Error::Invalid),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if sub_paths.first() != Some(&SubPath::Root) || key_indexes.len() != 5 {
+            return Err(Error::Invalid);
+        }
+
+        let hardened = |position: usize| match key_indexes[position] {
+            key_index @ KeyIndex::Hardened(_) => Ok(key_index.normalize_index()),
+            KeyIndex::Normal(_) => Err(Error::Invalid),
+        };
+        let normal = |position: usize| match key_indexes[position] {
+            KeyIndex::Normal(index) => Ok(index),
+            KeyIndex::Hardened(_) => Err(Error::Invalid),
+        };
+        if hardened(0)? != BIP44_PURPOSE {
+            return Err(Error::Invalid);
+        }
+        Ok(Bip44Path {
+            coin_type: hardened(1)?,
+            account: hardened(2)?,
+            change: normal(3)?,
+            index: normal(4)?,
+        })
+    }
+}
+
+/// BIP44 path: `m / purpose' / coin_type' / account' / change / address_index`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bip44Path {
+    /// coin type (`394` for CRO on mainnet, `1` on the other networks)
+    pub coin_type: u32,
+    /// account (hardened)
+    pub account: u32,
+    /// `0` for external addresses, `1` for internal (change) addresses
+    pub change: u32,
+    /// address index
+    pub index: u32,
+}
 
-        let chain_path_string = format!("m/44'/{}'/{}'/0/{}", coin_type, account_index, index);
-        Self::from(chain_path_string)
+impl Bip44Path {
+    /// Creates the bip44 path with the coin type of the network
+    pub fn new(network: Network, account: u32, change: u32, index: u32) -> Self {
+        Bip44Path {
+            coin_type: get_bip44_coin_type_from_network(network),
+            account,
+            change,
+            index,
+        }
+    }
+
+    /// Returns the account-level path (`m / purpose' / coin_type' / account'`), whose extended
+    /// public key derives all the addresses of the account
+    pub fn account_path(&self) -> ChainPath {
+        ChainPath::from(format!(
+            "m/{}'/{}'/{}'",
+            BIP44_PURPOSE, self.coin_type, self.account
+        ))
+    }
+}
+
+impl From<Bip44Path> for ChainPath {
+    fn from(path: Bip44Path) -> Self {
+        ChainPath::from(format!(
+            "{}/{}/{}",
+            path.account_path(),
+            path.change,
+            path.index
+        ))
     }
 }
 
@@ -189,4 +272,30 @@ mod tests {
             .collect::<Result<Vec<_>, _>>()
             .is_err());
     }
+
+    #[test]
+    fn test_bip44_path() {
+        let path = Bip44Path::new(Network::Mainnet, 3, BIP44_INTERNAL_CHANGE, 7);
+        let chain_path = ChainPath::from(path);
+        assert_eq!("m/44'/394'/3'/1/7", chain_path.to_string());
+        assert_eq!("m/44'/394'/3'", path.account_path().to_string());
+        assert_eq!(Ok(path), chain_path.bip44());
+
+        assert_eq!(
+            ChainPath::from("m/44'/1'/0'/0/2"),
+            ChainPath::create_bip44(Network::Devnet, 0, 2)
+        );
+        assert_eq!(
+            Err(Error::Invalid),
+            ChainPath::from("m/44'/394'/0'").bip44()
+        );
+        assert_eq!(
+            Err(Error::Invalid),
+            ChainPath::from("m/49'/394'/0'/0/1").bip44()
+        );
+        assert_eq!(
+            Err(Error::Invalid),
+            ChainPath::from("m/44'/394'/0/0/1").bip44()
+        );
+    }
 }
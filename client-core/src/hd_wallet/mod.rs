@@ -17,9 +17,12 @@ mod wallet_kind;
 pub use wallet_kind::HardwareKind;
 
 pub use crate::hd_wallet::extended_key::{
-    key_index::KeyIndex, ExtendedPrivKey, ExtendedPubKey, KeySeed,
+    key_index::KeyIndex, xpub::Xpub, ExtendedPrivKey, ExtendedPubKey, KeySeed,
 };
 pub use crate::hd_wallet::key_chain::{
-    chain_path::{ChainPath, Error as ChainPathError, SubPath},
+    chain_path::{
+        Bip44Path, ChainPath, Error as ChainPathError, SubPath, BIP44_EXTERNAL_CHANGE,
+        BIP44_INTERNAL_CHANGE, BIP44_PURPOSE,
+    },
     DefaultKeyChain, Derivation, KeyChain,
 };
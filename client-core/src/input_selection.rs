@@ -1,8 +1,13 @@
 //! Input selection operations
+use std::sync::Arc;
+
+use crate::coin_selection::{
+    BranchAndBound, CoinSelectionStrategy, LargestFirst, RandomSelection, SmallestFirst,
+};
 use crate::unspent_transactions::{Operation, Sorter};
 
 /// Different strategies for input selection
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum InputSelectionStrategy {
     /// Selects unspent transactions with highest value first
     HighestValueFirst,
@@ -10,6 +15,9 @@ pub enum InputSelectionStrategy {
     LowestValueFirst,
     /// Selects unspent transactions randomly
     Random,
+    /// Selects unspent transactions exactly matching the amount and fee if possible (no change
+    /// output), otherwise with highest value first
+    BranchAndBound,
 }
 
 impl Default for InputSelectionStrategy {
@@ -19,10 +27,22 @@ impl Default for InputSelectionStrategy {
     }
 }
 
+impl InputSelectionStrategy {
+    /// Returns the coin selection strategy used by the transaction builder
+    pub fn coin_selection(self) -> Arc<dyn CoinSelectionStrategy> {
+        match self {
+            InputSelectionStrategy::HighestValueFirst => Arc::new(LargestFirst),
+            InputSelectionStrategy::LowestValueFirst => Arc::new(SmallestFirst),
+            InputSelectionStrategy::Random => Arc::new(RandomSelection),
+            InputSelectionStrategy::BranchAndBound => Arc::new(BranchAndBound::default()),
+        }
+    }
+}
+
 impl AsRef<[Operation]> for InputSelectionStrategy {
     fn as_ref(&self) -> &[Operation] {
         match self {
-            InputSelectionStrategy::HighestValueFirst => {
+            InputSelectionStrategy::HighestValueFirst | InputSelectionStrategy::BranchAndBound => {
                 &[Operation::Sort(Sorter::HighestValueFirst)]
             }
             InputSelectionStrategy::LowestValueFirst => {
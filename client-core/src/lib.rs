@@ -10,12 +10,14 @@
 //! - Transaction history
 //! - Transaction creation and signing (with automatic unspent transaction selection)
 
+pub mod coin_selection;
 pub mod hd_seed;
 pub mod hd_wallet;
 pub mod input_selection;
 pub mod mnemonic;
 #[cfg(feature = "experimental")]
 pub mod multi_sig;
+pub mod plugin;
 pub mod service;
 pub mod signer;
 
@@ -12,7 +12,10 @@ use client_common::{ErrorKind, Result, ResultExt};
 const MNEMONIC_LANGUAGE: Language = Language::English;
 
 /// Mnemonic wrapped in secures string
-pub struct Mnemonic(bip39::Mnemonic);
+///
+/// The optional BIP39 passphrase (the "25th word") is part of the seed, so the same mnemonic
+/// with different passphrases gives independent wallets. It's never serialized.
+pub struct Mnemonic(bip39::Mnemonic, Option<SecUtf8>);
 
 impl Serialize for Mnemonic {
     fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
@@ -53,7 +56,7 @@ impl Mnemonic {
         };
         let mnemonic = bip39::Mnemonic::new(flag, MNEMONIC_LANGUAGE);
 
-        Ok(Mnemonic(mnemonic))
+        Ok(Mnemonic(mnemonic, None))
     }
 
     /// Create Mnemonic from words in secure string
@@ -68,7 +71,7 @@ impl Mnemonic {
         let mnemonic = bip39::Mnemonic::from_phrase(words, MNEMONIC_LANGUAGE)
             .chain(|| (ErrorKind::DeserializationError, "Invalid mnemonic phrase"))?;
 
-        Ok(Mnemonic(mnemonic))
+        Ok(Mnemonic(mnemonic, None))
     }
 
     /// Returns mnemonic phrase as secure string
@@ -83,17 +86,22 @@ impl Mnemonic {
         self.0.phrase()
     }
 
-    /// Returns the seed from the mnemonic words as byte slice
+    /// Sets the BIP39 passphrase used (with the mnemonic words) to derive the seed
+    #[inline]
+    pub fn with_passphrase(self, passphrase: SecUtf8) -> Self {
+        Mnemonic(self.0, Some(passphrase))
+    }
+
+    /// Returns the seed from the mnemonic words (and the BIP39 passphrase) as byte slice
     #[inline]
     pub fn seed(&self) -> Vec<u8> {
-        // TODO: advanced/optional recovery" seeding option
-        // give salt as another argument, make default as ""
-        Seed::new(&self.0, "").as_bytes().to_vec()
+        let passphrase = self.1.as_ref().map(SecUtf8::unsecure).unwrap_or_default();
+        Seed::new(&self.0, passphrase).as_bytes().to_vec()
     }
 
     // TODO: Implement zeroize for bip39::Mnemonic phrase and entropy
     // Right now only the phrase can be zero out
-    /// Take ownership and zeroize
+    /// Take ownership and zeroize (the passphrase is zeroized when dropped)
     #[inline]
     pub fn zeroize(self) {
         self.0.into_phrase().zeroize()
@@ -177,6 +185,23 @@ mod mnemonic_tests {
         }
     }
 
+    #[test]
+    fn should_passphrase_change_the_seed() {
+        let words = SecUtf8::from("point shiver hurt flight fun online hub antenna engine pave chef fantasy front interest poem accident catch load frequent praise elite pet remove used");
+        let seed = |passphrase: Option<&str>| {
+            let mnemonic = Mnemonic::from_secstr(&words).expect("mnemonic");
+            match passphrase {
+                Some(passphrase) => mnemonic.with_passphrase(SecUtf8::from(passphrase)).seed(),
+                None => mnemonic.seed(),
+            }
+        };
+
+        assert_eq!(seed(None), seed(Some("")));
+        assert_ne!(seed(None), seed(Some("passphrase")));
+        assert_ne!(seed(Some("passphrase")), seed(Some("other passphrase")));
+        assert_eq!(seed(Some("passphrase")), seed(Some("passphrase")));
+    }
+
     #[test]
     fn test_deserialize_error() {
         let invalid_mnemonic_json = "\"hello from rust\"";
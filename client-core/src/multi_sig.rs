@@ -1,8 +1,10 @@
 //! MultiSig operations support
 mod builder;
+mod message;
 mod session;
 mod signer;
 
 pub use builder::MultiSigBuilder;
+pub use message::{MultiSigMessage, MultiSigMessageContent};
 pub use session::MultiSigSession;
 use signer::Signer;
@@ -2,9 +2,9 @@ use parity_scale_codec::{Decode, Encode};
 use secp256k1::schnorrsig::SchnorrSignature;
 
 use chain_core::common::H256;
-use client_common::{ErrorKind, PrivateKey, PublicKey, Result, ResultExt};
+use client_common::{Error, ErrorKind, PrivateKey, PublicKey, Result, ResultExt};
 
-use super::MultiSigSession;
+use super::{MultiSigMessage, MultiSigMessageContent, MultiSigSession};
 
 /// MultiSig session builder tailored for Crypto.com chain flow
 ///
@@ -155,6 +155,58 @@ impl MultiSigBuilder {
         self.session.has_partial_signature(public_key)
     }
 
+    /// Returns the message of current signer to send to all co-signers: the nonce commitment,
+    /// then the nonce once the nonce commitments of all signers are added, and the partial
+    /// signature once the nonces of all signers are added.
+    pub fn export_message(&mut self) -> Result<MultiSigMessage> {
+        let mut has_all_nonce_commitments = true;
+        let mut has_all_nonces = true;
+        for public_key in self.public_keys().iter() {
+            has_all_nonce_commitments &= self.has_nonce_commitment(public_key)?;
+            has_all_nonces &= self.has_nonce(public_key)?;
+        }
+
+        let content = if has_all_nonces {
+            MultiSigMessageContent::PartialSignature(self.partial_signature()?)
+        } else if has_all_nonce_commitments {
+            MultiSigMessageContent::Nonce(self.nonce()?)
+        } else {
+            MultiSigMessageContent::NonceCommitment(self.nonce_commitment()?)
+        };
+
+        Ok(MultiSigMessage {
+            message: self.session.message,
+            sender: self.session.public_key.clone(),
+            content,
+        })
+    }
+
+    /// Adds the nonce commitment, nonce or partial signature in a message of a co-signer
+    pub fn import_message(&mut self, message: &MultiSigMessage) -> Result<()> {
+        if message.message != self.session.message {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Message does not belong to this MultiSig session",
+            ));
+        }
+        if message.sender == self.session.public_key {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Cannot import a message of current signer",
+            ));
+        }
+
+        match message.content {
+            MultiSigMessageContent::NonceCommitment(nonce_commitment) => {
+                self.add_nonce_commitment(&message.sender, nonce_commitment)
+            }
+            MultiSigMessageContent::Nonce(nonce) => self.add_nonce(&message.sender, &nonce),
+            MultiSigMessageContent::PartialSignature(partial_signature) => {
+                self.add_partial_signature(&message.sender, partial_signature)
+            }
+        }
+    }
+
     /// Returns incompleted MultiSig session in bytes
     pub fn to_incomplete(&self) -> Vec<u8> {
         self.session.encode()
@@ -0,0 +1,55 @@
+use std::str::FromStr;
+use std::string::ToString;
+
+use parity_scale_codec::{Decode, Encode};
+
+use chain_core::common::H256;
+use client_common::{Error, ErrorKind, PublicKey, Result, ResultExt};
+
+/// Round of a MultiSig session a message belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+pub enum MultiSigMessageContent {
+    /// Nonce commitment of the sender
+    NonceCommitment(H256),
+    /// Nonce of the sender (once it received the nonce commitments of all co-signers)
+    Nonce(H256),
+    /// Partial signature of the sender (once it received the nonces of all co-signers)
+    PartialSignature(H256),
+}
+
+/// Message exchanged between the co-signers of a MultiSig session (each co-signer has its
+/// own session id, so the message is bound to the signed message instead)
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+pub struct MultiSigMessage {
+    /// The message signed in the session
+    pub message: H256,
+    /// Public key of the co-signer who sent the message
+    pub sender: PublicKey,
+    /// Content of the message
+    pub content: MultiSigMessageContent,
+}
+
+impl ToString for MultiSigMessage {
+    fn to_string(&self) -> String {
+        base64::encode(&self.encode())
+    }
+}
+
+impl FromStr for MultiSigMessage {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let raw_data = base64::decode(s).chain(|| {
+            (
+                ErrorKind::DeserializationError,
+                "Unable to decode MultiSig session message",
+            )
+        })?;
+        Self::decode(&mut raw_data.as_slice()).chain(|| {
+            (
+                ErrorKind::DeserializationError,
+                "Unable to deserialize MultiSig session message",
+            )
+        })
+    }
+}
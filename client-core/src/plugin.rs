@@ -0,0 +1,235 @@
+//! Plugins of the custom transaction types added by forks of the chain
+//!
+//! Forks adding transaction variants register a [`TransactionPlugin`] at startup (with
+//! [`register_plugin`]) instead of patching the wallet stack:
+//!
+//! - raw transactions decoded by a plugin are skipped when decoding blocks and the mempool,
+//! - they are added to the wallet history with the balance change rendered by the plugin,
+//! - they can be built and previewed (before broadcasting) through this module.
+use std::sync::{Arc, RwLock};
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use chain_core::tx::data::TxId;
+use client_common::{Error, ErrorKind, Result};
+
+use crate::service::Wallet;
+use crate::types::transaction_change::{
+    deserialize_transaction_id, serialize_transaction_id, BalanceChange,
+};
+
+static PLUGINS: Lazy<RwLock<Vec<Arc<dyn TransactionPlugin>>>> = Lazy::new(Default::default);
+
+/// Transaction of a custom type, decoded by a plugin
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CustomTransaction {
+    /// Name of the plugin which decoded the transaction
+    pub plugin: String,
+    /// Custom type of the transaction (defined by the plugin)
+    pub kind: String,
+    /// Transaction ID
+    #[serde(serialize_with = "serialize_transaction_id")]
+    #[serde(deserialize_with = "deserialize_transaction_id")]
+    pub transaction_id: TxId,
+    /// Raw transaction (as broadcasted)
+    #[serde(with = "hex_bytes")]
+    pub raw: Vec<u8>,
+}
+
+/// Integration of the custom transaction types of a fork in the wallet stack
+pub trait TransactionPlugin: Send + Sync {
+    /// Unique name of the plugin
+    fn name(&self) -> &str;
+
+    /// Codec: decodes a raw transaction of one of the custom types of the plugin (`None` if it
+    /// isn't one)
+    fn decode(&self, raw: &[u8]) -> Option<CustomTransaction>;
+
+    /// Validation preview: returns the reasons the transaction would be rejected by the chain
+    /// (empty if it should be accepted)
+    fn preview(&self, _transaction: &CustomTransaction) -> Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+
+    /// Builder: builds a raw transaction of the custom type `kind` from JSON parameters
+    fn build(&self, kind: &str, _params: &Value) -> Result<Vec<u8>> {
+        Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "Plugin {} can't build transactions of type {}",
+                self.name(),
+                kind
+            ),
+        ))
+    }
+
+    /// History rendering: balance change the transaction caused to the wallet (`None` if it
+    /// isn't related to the wallet, so it's not added to the history)
+    fn render(&self, _transaction: &CustomTransaction, _wallet: &Wallet) -> Option<BalanceChange> {
+        None
+    }
+}
+
+/// Registers the plugin (names have to be unique)
+pub fn register_plugin(plugin: Arc<dyn TransactionPlugin>) -> Result<()> {
+    let mut plugins = PLUGINS.write().expect("transaction plugins lock");
+    if plugins
+        .iter()
+        .any(|existing| existing.name() == plugin.name())
+    {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("Transaction plugin {} is already registered", plugin.name()),
+        ));
+    }
+    plugins.push(plugin);
+    Ok(())
+}
+
+/// Whether any plugin is registered
+pub fn has_plugins() -> bool {
+    !PLUGINS.read().expect("transaction plugins lock").is_empty()
+}
+
+/// Decodes a raw transaction with the registered plugins (`None` if it isn't of a custom type)
+pub fn decode_custom_transaction(raw: &[u8]) -> Option<CustomTransaction> {
+    let plugins = PLUGINS.read().expect("transaction plugins lock");
+    plugins.iter().find_map(|plugin| plugin.decode(raw))
+}
+
+/// Whether the raw transaction is of a custom type of a registered plugin
+pub fn is_custom_transaction(raw: &[u8]) -> bool {
+    decode_custom_transaction(raw).is_some()
+}
+
+/// Builds a transaction of the custom type `kind` with the plugin
+pub fn build_custom_transaction(
+    plugin: &str,
+    kind: &str,
+    params: &Value,
+) -> Result<CustomTransaction> {
+    let raw = find_plugin(plugin)?.build(kind, params)?;
+    decode_custom_transaction(&raw).ok_or_else(|| {
+        Error::new(
+            ErrorKind::InternalError,
+            format!("Plugin {} built a transaction it can't decode", plugin),
+        )
+    })
+}
+
+/// Returns the reasons the custom transaction would be rejected by the chain
+pub fn preview_custom_transaction(transaction: &CustomTransaction) -> Result<Vec<String>> {
+    find_plugin(&transaction.plugin)?.preview(transaction)
+}
+
+/// Returns the balance change the custom transaction caused to the wallet
+pub(crate) fn render_custom_transaction(
+    transaction: &CustomTransaction,
+    wallet: &Wallet,
+) -> Option<BalanceChange> {
+    find_plugin(&transaction.plugin)
+        .ok()
+        .and_then(|plugin| plugin.render(transaction, wallet))
+}
+
+fn find_plugin(name: &str) -> Result<Arc<dyn TransactionPlugin>> {
+    let plugins = PLUGINS.read().expect("transaction plugins lock");
+    plugins
+        .iter()
+        .find(|plugin| plugin.name() == name)
+        .cloned()
+        .ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("Transaction plugin {} is not registered", name),
+            )
+        })
+}
+
+mod hex_bytes {
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let raw: &str = Deserialize::deserialize(deserializer)?;
+        hex::decode(raw).map_err(|e| de::Error::custom(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chain_core::init::coin::Coin;
+
+    /// Raw transactions prefixed with `0xff` (not a `TxAux` variant)
+    struct MemoPlugin;
+
+    impl TransactionPlugin for MemoPlugin {
+        fn name(&self) -> &str {
+            "memo"
+        }
+
+        fn decode(&self, raw: &[u8]) -> Option<CustomTransaction> {
+            if raw.first() != Some(&0xff) {
+                return None;
+            }
+            Some(CustomTransaction {
+                plugin: self.name().to_owned(),
+                kind: "memo".to_owned(),
+                transaction_id: [raw.len() as u8; 32],
+                raw: raw.to_vec(),
+            })
+        }
+
+        fn preview(&self, transaction: &CustomTransaction) -> Result<Vec<String>> {
+            if transaction.raw.len() > 3 {
+                Ok(vec!["memo too long".to_owned()])
+            } else {
+                Ok(Vec::new())
+            }
+        }
+
+        fn build(&self, _kind: &str, params: &Value) -> Result<Vec<u8>> {
+            let mut raw = vec![0xff];
+            raw.extend(params.as_str().unwrap_or_default().as_bytes());
+            Ok(raw)
+        }
+
+        fn render(
+            &self,
+            _transaction: &CustomTransaction,
+            _wallet: &Wallet,
+        ) -> Option<BalanceChange> {
+            Some(BalanceChange::Incoming { value: Coin::one() })
+        }
+    }
+
+    #[test]
+    fn check_plugin_registration() {
+        register_plugin(Arc::new(MemoPlugin)).unwrap();
+        assert!(register_plugin(Arc::new(MemoPlugin)).is_err());
+
+        assert!(!is_custom_transaction(&[0, 1, 2]));
+        let transaction = build_custom_transaction("memo", "memo", &Value::from("ab")).unwrap();
+        assert_eq!(vec![0xff, b'a', b'b'], transaction.raw);
+        assert_eq!(
+            Some(transaction.clone()),
+            decode_custom_transaction(&transaction.raw)
+        );
+        assert!(preview_custom_transaction(&transaction).unwrap().is_empty());
+
+        let transaction = build_custom_transaction("memo", "memo", &Value::from("abc")).unwrap();
+        assert_eq!(
+            vec!["memo too long".to_owned()],
+            preview_custom_transaction(&transaction).unwrap()
+        );
+
+        assert!(build_custom_transaction("unknown", "memo", &Value::Null).is_err());
+    }
+}
@@ -1,32 +1,60 @@
 //! Management services
+mod address_book_service;
+mod annotation_service;
+mod audit_service;
+mod event_log;
 mod hd_key_service;
 mod hw_key_service;
+mod inheritance_service;
+mod invoice_service;
+mod job_service;
 mod key_service;
 mod ledger_service;
 #[cfg(feature = "experimental")]
 mod multi_sig_session_service;
+mod recurring_payment_service;
 mod root_hash_service;
+mod spending_policy_service;
+mod storage_migration_service;
 mod sync_state_service;
+mod threshold_witness_service;
+mod vault_service;
 mod wallet_service;
 mod wallet_state_service;
 
 #[doc(hidden)]
 pub use self::wallet_state_service::WalletStateMemento;
 
-pub use self::hd_key_service::{HDAccountType, HdKey, HdKeyService};
+pub use self::address_book_service::AddressBookService;
+pub use self::annotation_service::AnnotationService;
+pub use self::audit_service::{derive_receive_address, AuditAccount, AuditAddress, AuditService};
+pub use self::hd_key_service::{HDAccountType, HdKey, HdKeyService, GAP_LIMIT, HD_ACCOUNT_TYPES};
 pub use self::hw_key_service::{HwKeyService, UnauthorizedHwKeyService};
+pub use self::inheritance_service::{update_inheritance_plans, InheritanceService};
+pub use self::invoice_service::{update_invoices, InvoiceService};
+pub use self::job_service::{JobService, MAX_FINISHED_JOBS};
 pub use self::key_service::KeyService;
 pub use self::ledger_service::{
     LedgerServiceHID, LedgerServiceZemu, LedgerSignKeyHID, LedgerSignKeyZemu,
 };
 #[cfg(feature = "experimental")]
 pub use self::multi_sig_session_service::MultiSigSessionService;
+pub use self::recurring_payment_service::RecurringPaymentService;
 pub use self::root_hash_service::RootHashService;
+pub use self::spending_policy_service::{SpendingPolicyService, OVERRIDE_VALIDITY_SECS};
+pub use self::storage_migration_service::{
+    LegacyRecord, MigrationReport, StorageMigrationService, STORAGE_VERSION,
+};
 pub use self::sync_state_service::{
     delete_sync_state, load_sync_state, save_sync_state, SyncState, SyncStateService,
 };
-pub use self::wallet_service::{load_wallet, Wallet, WalletInfo, WalletService, WalletStorageImpl};
+pub use self::threshold_witness_service::{PartialStakingSignature, ThresholdWitnessService};
+pub use self::vault_service::{update_vaults, VaultService};
+pub use self::wallet_service::{
+    load_wallet, SyncCheckpoint, Wallet, WalletBackup, WalletInfo, WalletService,
+    WalletStorageImpl, WALLET_BACKUP_VERSION,
+};
 pub use self::wallet_state_service::{
-    delete_wallet_state, load_wallet_state, modify_wallet_state, save_wallet_state, WalletState,
-    WalletStateService,
+    delete_wallet_state, load_wallet_state, modify_wallet_state, save_wallet_state,
+    ReindexProgress, ReindexReport, WalletState, WalletStateService,
 };
@@ -0,0 +1,181 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+use parity_scale_codec::{Decode, Encode};
+
+use client_common::{Error, ErrorKind, Result, ResultExt, SecKey, Storage};
+
+use crate::types::Contact;
+
+/// key space of wallet address books
+const KEYSPACE: &str = "core_address_book";
+
+/// Named recipients of a wallet
+#[derive(Debug, Default, Encode, Decode)]
+struct AddressBook {
+    /// Contact name -> contact
+    contacts: BTreeMap<String, Contact>,
+}
+
+fn parse_address_book<T: AsRef<[u8]>>(
+    name: &str,
+    bytes_optional: Option<T>,
+) -> Result<AddressBook> {
+    bytes_optional
+        .map(|bytes| {
+            AddressBook::decode(&mut bytes.as_ref()).chain(|| {
+                (
+                    ErrorKind::DeserializationError,
+                    format!(
+                        "Unable to deserialize address book for wallet with name {}",
+                        name
+                    ),
+                )
+            })
+        })
+        .transpose()
+        .map(|book_optional| book_optional.unwrap_or_default())
+}
+
+/// Maintains mapping `wallet-name -> address-book`
+#[derive(Debug, Default, Clone)]
+pub struct AddressBookService<S>
+where
+    S: Storage,
+{
+    storage: S,
+}
+
+impl<S> AddressBookService<S>
+where
+    S: Storage,
+{
+    /// Creates new instance of address book service
+    #[inline]
+    pub fn new(storage: S) -> Self {
+        Self { storage }
+    }
+
+    /// Adds a contact to the address book (fails if a contact with the same name exists)
+    pub fn add_contact(&self, name: &str, enckey: &SecKey, contact: Contact) -> Result<()> {
+        contact.validate()?;
+        self.modify_book(name, enckey, |book| {
+            if book.contacts.contains_key(&contact.name) {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Contact with name {} already exists", contact.name),
+                ));
+            }
+            book.contacts.insert(contact.name.clone(), contact.clone());
+            Ok(())
+        })
+    }
+
+    /// Removes a contact from the address book, returns `false` if there wasn't any
+    pub fn remove_contact(&self, name: &str, enckey: &SecKey, contact_name: &str) -> Result<bool> {
+        self.modify_book(name, enckey, |book| {
+            Ok(book.contacts.remove(contact_name).is_some())
+        })
+    }
+
+    /// Returns the contact with given name
+    pub fn get_contact(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        contact_name: &str,
+    ) -> Result<Option<Contact>> {
+        let mut book = self.get_book(name, enckey)?;
+        Ok(book.contacts.remove(contact_name))
+    }
+
+    /// Returns all the contacts (sorted by name)
+    pub fn get_contacts(&self, name: &str, enckey: &SecKey) -> Result<Vec<Contact>> {
+        let book = self.get_book(name, enckey)?;
+        Ok(book
+            .contacts
+            .into_iter()
+            .map(|(_, contact)| contact)
+            .collect())
+    }
+
+    /// Deletes the address book of the wallet
+    #[inline]
+    pub fn delete_address_book(&self, name: &str) -> Result<()> {
+        self.storage.delete(KEYSPACE, name).map(|_| ())
+    }
+
+    /// Clears all storage
+    #[inline]
+    pub fn clear(&self) -> Result<()> {
+        self.storage.clear(KEYSPACE)
+    }
+
+    fn get_book(&self, name: &str, enckey: &SecKey) -> Result<AddressBook> {
+        Ok(self
+            .storage
+            .load_secure(KEYSPACE, name, enckey)?
+            .unwrap_or_default())
+    }
+
+    fn modify_book<F, R>(&self, name: &str, enckey: &SecKey, f: F) -> Result<R>
+    where
+        F: Fn(&mut AddressBook) -> Result<R>,
+    {
+        let result = RefCell::new(None);
+        self.storage
+            .fetch_and_update_secure(KEYSPACE, name, enckey, |bytes_optional| {
+                let mut book = parse_address_book(name, bytes_optional)?;
+                *result.borrow_mut() = Some(f(&mut book)?);
+                Ok(Some(book.encode()))
+            })?;
+        Ok(result.into_inner().expect("address book is updated"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use secstr::SecUtf8;
+
+    use chain_core::tx::data::address::ExtendedAddr;
+    use client_common::{seckey::derive_enckey, storage::MemoryStorage};
+
+    use crate::types::ContactAddress;
+
+    #[test]
+    fn check_address_book_flow() {
+        let service = AddressBookService::new(MemoryStorage::default());
+
+        let name = "name";
+        let enckey = &derive_enckey(&SecUtf8::from("passphrase"), name).unwrap();
+        let contact = Contact {
+            name: "alice".to_owned(),
+            address: ContactAddress::Transfer(ExtendedAddr::OrTree([1; 32])),
+            memo: Some("landlord".to_owned()),
+        };
+
+        service.add_contact(name, enckey, contact.clone()).unwrap();
+        assert_eq!(
+            ErrorKind::InvalidInput,
+            service
+                .add_contact(name, enckey, contact.clone())
+                .unwrap_err()
+                .kind()
+        );
+        let mut invalid = contact.clone();
+        invalid.name = " bob".to_owned();
+        assert!(service.add_contact(name, enckey, invalid).is_err());
+
+        assert_eq!(
+            Some(contact.clone()),
+            service.get_contact(name, enckey, "alice").unwrap()
+        );
+        assert_eq!(vec![contact], service.get_contacts(name, enckey).unwrap());
+
+        assert!(service.remove_contact(name, enckey, "alice").unwrap());
+        assert!(!service.remove_contact(name, enckey, "alice").unwrap());
+        assert!(service.get_contacts(name, enckey).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,329 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+use parity_scale_codec::{Decode, Encode};
+
+use chain_core::tx::data::address::ExtendedAddr;
+use chain_core::tx::data::input::str2txid;
+use chain_core::tx::data::TxId;
+use client_common::{ErrorKind, Result, ResultExt, SecKey, Storage};
+
+use crate::types::{Annotation, TransactionChange, WalletAnnotations};
+
+/// key space of wallet annotations
+const KEYSPACE: &str = "core_annotation";
+
+/// Local annotations of the transactions and addresses of a wallet
+#[derive(Debug, Default, Encode, Decode)]
+struct AnnotationBook {
+    /// Transaction ID -> annotation
+    transactions: BTreeMap<TxId, Annotation>,
+    /// Address -> annotation
+    addresses: BTreeMap<ExtendedAddr, Annotation>,
+}
+
+fn parse_annotation_book<T: AsRef<[u8]>>(
+    name: &str,
+    bytes_optional: Option<T>,
+) -> Result<AnnotationBook> {
+    bytes_optional
+        .map(|bytes| {
+            AnnotationBook::decode(&mut bytes.as_ref()).chain(|| {
+                (
+                    ErrorKind::DeserializationError,
+                    format!(
+                        "Unable to deserialize annotations for wallet with name {}",
+                        name
+                    ),
+                )
+            })
+        })
+        .transpose()
+        .map(|book_optional| book_optional.unwrap_or_default())
+}
+
+/// Inserts the annotation (or removes the existing one if the annotation is empty)
+fn set_annotation<K: Ord>(
+    annotations: &mut BTreeMap<K, Annotation>,
+    key: K,
+    annotation: Annotation,
+) -> Result<()> {
+    if annotation.is_empty() {
+        annotations.remove(&key);
+    } else {
+        annotation.validate()?;
+        annotations.insert(key, annotation);
+    }
+    Ok(())
+}
+
+/// Maintains mapping `wallet-name -> annotation-book`
+#[derive(Debug, Default, Clone)]
+pub struct AnnotationService<S>
+where
+    S: Storage,
+{
+    storage: S,
+}
+
+impl<S> AnnotationService<S>
+where
+    S: Storage,
+{
+    /// Creates new instance of annotation service
+    #[inline]
+    pub fn new(storage: S) -> Self {
+        Self { storage }
+    }
+
+    /// Sets the annotation of the transaction (an empty annotation removes it)
+    pub fn set_transaction_annotation(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        transaction_id: TxId,
+        annotation: Annotation,
+    ) -> Result<()> {
+        self.modify_book(name, enckey, |book| {
+            set_annotation(&mut book.transactions, transaction_id, annotation.clone())
+        })
+    }
+
+    /// Returns the annotation of the transaction
+    pub fn get_transaction_annotation(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        transaction_id: &TxId,
+    ) -> Result<Option<Annotation>> {
+        let mut book = self.get_book(name, enckey)?;
+        Ok(book.transactions.remove(transaction_id))
+    }
+
+    /// Removes the annotation of the transaction, returns `false` if there wasn't any
+    pub fn delete_transaction_annotation(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        transaction_id: &TxId,
+    ) -> Result<bool> {
+        self.modify_book(name, enckey, |book| {
+            Ok(book.transactions.remove(transaction_id).is_some())
+        })
+    }
+
+    /// Sets the annotation of the address (an empty annotation removes it)
+    pub fn set_address_annotation(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        address: &ExtendedAddr,
+        annotation: Annotation,
+    ) -> Result<()> {
+        self.modify_book(name, enckey, |book| {
+            set_annotation(&mut book.addresses, address.clone(), annotation.clone())
+        })
+    }
+
+    /// Returns the annotation of the address
+    pub fn get_address_annotation(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        address: &ExtendedAddr,
+    ) -> Result<Option<Annotation>> {
+        let mut book = self.get_book(name, enckey)?;
+        Ok(book.addresses.remove(address))
+    }
+
+    /// Removes the annotation of the address, returns `false` if there wasn't any
+    pub fn delete_address_annotation(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        address: &ExtendedAddr,
+    ) -> Result<bool> {
+        self.modify_book(name, enckey, |book| {
+            Ok(book.addresses.remove(address).is_some())
+        })
+    }
+
+    /// Returns all the annotations of the wallet
+    pub fn get_annotations(&self, name: &str, enckey: &SecKey) -> Result<WalletAnnotations> {
+        let book = self.get_book(name, enckey)?;
+        Ok(WalletAnnotations {
+            transactions: book
+                .transactions
+                .into_iter()
+                .map(|(transaction_id, annotation)| (hex::encode(transaction_id), annotation))
+                .collect(),
+            addresses: book
+                .addresses
+                .into_iter()
+                .map(|(address, annotation)| (address.to_string(), annotation))
+                .collect(),
+        })
+    }
+
+    /// Adds the annotations (e.g. of an imported wallet), replacing the existing ones of the
+    /// same transactions and addresses
+    pub fn import_annotations(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        annotations: &WalletAnnotations,
+    ) -> Result<()> {
+        let mut transactions = Vec::with_capacity(annotations.transactions.len());
+        for (transaction_id, annotation) in annotations.transactions.iter() {
+            let transaction_id = str2txid(transaction_id).chain(|| {
+                (
+                    ErrorKind::InvalidInput,
+                    format!("Invalid transaction ID of annotation: {}", transaction_id),
+                )
+            })?;
+            transactions.push((transaction_id, annotation.clone()));
+        }
+        let mut addresses = Vec::with_capacity(annotations.addresses.len());
+        for (address, annotation) in annotations.addresses.iter() {
+            let address = address.parse::<ExtendedAddr>().chain(|| {
+                (
+                    ErrorKind::InvalidInput,
+                    format!("Invalid address of annotation: {}", address),
+                )
+            })?;
+            addresses.push((address, annotation.clone()));
+        }
+
+        self.modify_book(name, enckey, |book| {
+            for (transaction_id, annotation) in transactions.iter() {
+                set_annotation(&mut book.transactions, *transaction_id, annotation.clone())?;
+            }
+            for (address, annotation) in addresses.iter() {
+                set_annotation(&mut book.addresses, address.clone(), annotation.clone())?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Attaches the annotations to the transactions (e.g. of a history query)
+    pub fn annotate(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        changes: &mut [TransactionChange],
+    ) -> Result<()> {
+        let book = self.get_book(name, enckey)?;
+        for change in changes.iter_mut() {
+            change.annotation = book.transactions.get(&change.transaction_id).cloned();
+        }
+        Ok(())
+    }
+
+    /// Deletes all the annotations of the wallet
+    #[inline]
+    pub fn delete_annotations(&self, name: &str) -> Result<()> {
+        self.storage.delete(KEYSPACE, name).map(|_| ())
+    }
+
+    /// Clears all storage
+    #[inline]
+    pub fn clear(&self) -> Result<()> {
+        self.storage.clear(KEYSPACE)
+    }
+
+    fn get_book(&self, name: &str, enckey: &SecKey) -> Result<AnnotationBook> {
+        Ok(self
+            .storage
+            .load_secure(KEYSPACE, name, enckey)?
+            .unwrap_or_default())
+    }
+
+    fn modify_book<F, R>(&self, name: &str, enckey: &SecKey, f: F) -> Result<R>
+    where
+        F: Fn(&mut AnnotationBook) -> Result<R>,
+    {
+        let result = RefCell::new(None);
+        self.storage
+            .fetch_and_update_secure(KEYSPACE, name, enckey, |bytes_optional| {
+                let mut book = parse_annotation_book(name, bytes_optional)?;
+                *result.borrow_mut() = Some(f(&mut book)?);
+                Ok(Some(book.encode()))
+            })?;
+        Ok(result.into_inner().expect("annotation book is updated"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use secstr::SecUtf8;
+
+    use client_common::{seckey::derive_enckey, storage::MemoryStorage};
+
+    #[test]
+    fn check_annotation_flow() {
+        let service = AnnotationService::new(MemoryStorage::default());
+
+        let name = "name";
+        let enckey = &derive_enckey(&SecUtf8::from("passphrase"), name).unwrap();
+        let address = ExtendedAddr::OrTree([1; 32]);
+        let annotation = Annotation {
+            memo: Some("rent".to_owned()),
+            label: None,
+            category: Some("housing".to_owned()),
+        };
+
+        service
+            .set_transaction_annotation(name, enckey, [1; 32], annotation.clone())
+            .unwrap();
+        service
+            .set_address_annotation(name, enckey, &address, annotation.clone())
+            .unwrap();
+        assert_eq!(
+            Some(annotation.clone()),
+            service
+                .get_transaction_annotation(name, enckey, &[1; 32])
+                .unwrap()
+        );
+
+        let annotations = service.get_annotations(name, enckey).unwrap();
+        assert_eq!(1, annotations.transactions.len());
+        assert_eq!(
+            Some(&annotation),
+            annotations.addresses.get(&address.to_string())
+        );
+
+        // an empty annotation removes the existing one
+        service
+            .set_address_annotation(name, enckey, &address, Annotation::default())
+            .unwrap();
+        assert!(service
+            .get_address_annotation(name, enckey, &address)
+            .unwrap()
+            .is_none());
+        assert!(service
+            .delete_transaction_annotation(name, enckey, &[1; 32])
+            .unwrap());
+        assert!(!service
+            .delete_transaction_annotation(name, enckey, &[1; 32])
+            .unwrap());
+
+        // annotations of an exported wallet
+        let other_name = "other";
+        let other_enckey = &derive_enckey(&SecUtf8::from("passphrase"), other_name).unwrap();
+        service
+            .import_annotations(other_name, other_enckey, &annotations)
+            .unwrap();
+        assert_eq!(
+            annotations,
+            service.get_annotations(other_name, other_enckey).unwrap()
+        );
+
+        service.delete_annotations(other_name).unwrap();
+        assert_eq!(
+            WalletAnnotations::default(),
+            service.get_annotations(other_name, other_enckey).unwrap()
+        );
+    }
+}
@@ -0,0 +1,304 @@
+use parity_scale_codec::{Decode, Encode};
+use serde::{Deserialize, Serialize};
+
+use chain_core::init::network::{get_network, Network};
+use chain_core::tx::data::address::ExtendedAddr;
+use client_common::{Error, ErrorKind, MultiSigAddress, PublicKey, Result, ResultExt, Storage};
+
+use super::GAP_LIMIT;
+use crate::hd_wallet::{Xpub, BIP44_EXTERNAL_CHANGE};
+
+/// key space of the accounts watched by audit servers
+const KEYSPACE: &str = "core_audit_account";
+
+/// Depth of the account-level keys (`m / 44' / coin_type' / account'`)
+const ACCOUNT_DEPTH: u8 = 3;
+
+/// Account of an HD wallet watched by an audit server, which derives fresh receive addresses
+/// from the account-level extended public key (without any private key or seed)
+///
+/// The addresses are the ones of the wallet (`m / 44' / coin_type' / account' / 0 / index`), so
+/// the wallet recovers the ones handed out by the server when syncing. The server never hands
+/// out more than `GAP_LIMIT` consecutive unused addresses, so the payments stay within the
+/// address discovery window of the wallet.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Encode, Decode)]
+pub struct AuditAccount {
+    /// Account-level extended public key (BIP32 serialized)
+    pub xpub: String,
+    /// Index of the first address handed out by the server
+    pub start_index: u32,
+    /// Index of the next address to hand out
+    pub next_index: u32,
+    /// Index of the latest address marked as used
+    pub last_used_index: Option<u32>,
+}
+
+impl AuditAccount {
+    /// Number of addresses handed out after the latest used one
+    #[inline]
+    pub fn unused_addresses(&self) -> u32 {
+        let first_unused = self
+            .last_used_index
+            .map(|index| index + 1)
+            .unwrap_or(self.start_index)
+            .max(self.start_index);
+        self.next_index.saturating_sub(first_unused)
+    }
+}
+
+/// Receive address derived by an audit server
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditAddress {
+    /// Index of the address in the external chain of the account
+    pub index: u32,
+    /// Public key of the address
+    pub public_key: PublicKey,
+    /// Transfer address (1-of-1 multi-sig address of the public key, as in the wallet)
+    pub address: ExtendedAddr,
+}
+
+/// Derives the receive (external chain) transfer address at `index` from an account-level
+/// extended public key
+pub fn derive_receive_address(xpub: &Xpub, index: u32) -> Result<AuditAddress> {
+    let key = xpub
+        .derive_public_key(BIP44_EXTERNAL_CHANGE, index)
+        .chain(|| {
+            (
+                ErrorKind::InvalidInput,
+                format!("Unable to derive public key at index {}", index),
+            )
+        })?;
+    let public_key = PublicKey::from(key);
+    let multi_sig_address = MultiSigAddress::new(vec![public_key.clone()], public_key.clone(), 1)?;
+    Ok(AuditAddress {
+        index,
+        public_key,
+        address: ExtendedAddr::from(multi_sig_address),
+    })
+}
+
+/// Parses an account-level extended public key of the current network
+fn parse_account_xpub(xpub: &str) -> Result<Xpub> {
+    let xpub = xpub
+        .parse::<Xpub>()
+        .chain(|| (ErrorKind::InvalidInput, "Invalid extended public key"))?;
+    if xpub.depth != ACCOUNT_DEPTH {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "Extended public key should be an account-level key (depth {}), found depth {}",
+                ACCOUNT_DEPTH, xpub.depth
+            ),
+        ));
+    }
+    if (xpub.network == Network::Mainnet) != (get_network() == Network::Mainnet) {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "Extended public key is not a key of the current network",
+        ));
+    }
+    Ok(xpub)
+}
+
+/// Maintains mapping `account-name -> audit-account` (only public keys are stored, so the
+/// accounts are not encrypted)
+#[derive(Debug, Default, Clone)]
+pub struct AuditService<S>
+where
+    S: Storage,
+{
+    storage: S,
+}
+
+impl<S> AuditService<S>
+where
+    S: Storage,
+{
+    /// Creates new instance of audit service
+    #[inline]
+    pub fn new(storage: S) -> Self {
+        Self { storage }
+    }
+
+    /// Watches the account of the exported extended public key, the addresses are handed out
+    /// from `start_index` (e.g. the number of addresses already generated by the wallet)
+    pub fn add_account(&self, name: &str, xpub: &str, start_index: u32) -> Result<AuditAccount> {
+        let xpub = parse_account_xpub(xpub)?;
+        if self.storage.contains_key(KEYSPACE, name)? {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Audit account with name ({}) already exists", name),
+            ));
+        }
+        let account = AuditAccount {
+            xpub: xpub.to_string(),
+            start_index,
+            next_index: start_index,
+            last_used_index: None,
+        };
+        self.storage.save(KEYSPACE, name, &account)?;
+        Ok(account)
+    }
+
+    /// Returns the audit account with the given name
+    pub fn get_account(&self, name: &str) -> Result<AuditAccount> {
+        self.storage
+            .load(KEYSPACE, name)?
+            .err_kind(ErrorKind::InvalidInput, || {
+                format!("Audit account with name ({}) not found", name)
+            })
+    }
+
+    /// Hands out the next receive address of the account (fails if there are already
+    /// `GAP_LIMIT` unused addresses)
+    pub fn next_receive_address(&self, name: &str) -> Result<AuditAddress> {
+        let mut account = self.get_account(name)?;
+        if account.unused_addresses() >= GAP_LIMIT {
+            return Err(Error::new(
+                ErrorKind::IllegalInput,
+                format!(
+                    "Gap limit reached: {} addresses of audit account ({}) are unused",
+                    GAP_LIMIT, name
+                ),
+            ));
+        }
+        let address =
+            derive_receive_address(&parse_account_xpub(&account.xpub)?, account.next_index)?;
+        account.next_index = account.next_index.checked_add(1).chain(|| {
+            (
+                ErrorKind::IllegalInput,
+                "Address index of audit account overflows",
+            )
+        })?;
+        self.storage.save(KEYSPACE, name, &account)?;
+        Ok(address)
+    }
+
+    /// Marks the handed out address as used (e.g. when a payment to it is observed), which
+    /// allows handing out more addresses
+    pub fn mark_used(&self, name: &str, address: &ExtendedAddr) -> Result<AuditAccount> {
+        let mut account = self.get_account(name)?;
+        let index = self
+            .get_addresses(name)?
+            .into_iter()
+            .find(|derived| derived.address == *address)
+            .map(|derived| derived.index)
+            .err_kind(ErrorKind::InvalidInput, || {
+                format!(
+                    "Address {} is not handed out by audit account ({})",
+                    address, name
+                )
+            })?;
+        account.last_used_index = account.last_used_index.max(Some(index));
+        self.storage.save(KEYSPACE, name, &account)?;
+        Ok(account)
+    }
+
+    /// Returns the addresses handed out by the server (ordered by index)
+    pub fn get_addresses(&self, name: &str) -> Result<Vec<AuditAddress>> {
+        let account = self.get_account(name)?;
+        let xpub = parse_account_xpub(&account.xpub)?;
+        (account.start_index..account.next_index)
+            .map(|index| derive_receive_address(&xpub, index))
+            .collect()
+    }
+
+    /// Stops watching the audit account
+    #[inline]
+    pub fn delete_account(&self, name: &str) -> Result<()> {
+        self.storage.delete(KEYSPACE, name).map(|_| ())
+    }
+
+    /// Clears all storage
+    #[inline]
+    pub fn clear(&self) -> Result<()> {
+        self.storage.clear(KEYSPACE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use secstr::SecUtf8;
+
+    use client_common::storage::MemoryStorage;
+
+    use crate::types::AddressType;
+    use crate::wallet::{DefaultWalletClient, WalletClient};
+    use crate::Mnemonic;
+
+    #[test]
+    fn check_audit_addresses_match_wallet_addresses() {
+        let storage = MemoryStorage::default();
+        let name = "testhdwallet";
+        let passphrase = SecUtf8::from("passphrase");
+        let mnemonic =
+            Mnemonic::from_secstr(&SecUtf8::from("speed tortoise kiwi forward extend baby acoustic foil coach castle ship purchase unlock base hip erode tag keen present vibrant oyster cotton write fetch")).unwrap();
+
+        let wallet = DefaultWalletClient::new_read_only(storage.clone());
+        let enckey = wallet
+            .restore_wallet(&name, &passphrase, &mnemonic)
+            .expect("restore wallet");
+        let xpub = wallet
+            .export_account_xpub(name, &enckey, 0, AddressType::Transfer)
+            .unwrap();
+        assert_ne!(
+            xpub,
+            wallet
+                .export_account_xpub(name, &enckey, 0, AddressType::Staking)
+                .unwrap()
+        );
+
+        // the audit server only knows the extended public key
+        let audit_service = AuditService::new(MemoryStorage::default());
+        audit_service.add_account("audit", &xpub, 0).unwrap();
+        assert!(audit_service.add_account("audit", &xpub, 0).is_err());
+        for index in 0..5 {
+            let derived = audit_service.next_receive_address("audit").unwrap();
+            assert_eq!(index, derived.index);
+            assert_eq!(
+                wallet.new_transfer_address(name, &enckey).unwrap(),
+                derived.address
+            );
+        }
+    }
+
+    #[test]
+    fn check_audit_gap_limit() {
+        let mnemonic = Mnemonic::new(24).unwrap();
+        let xpub = crate::HDSeed::from(&mnemonic)
+            .get_account_xpub(get_network(), 0)
+            .unwrap()
+            .to_string();
+        let audit_service = AuditService::new(MemoryStorage::default());
+        assert!(audit_service.add_account("audit", "xpub", 0).is_err());
+        audit_service.add_account("audit", &xpub, 10).unwrap();
+
+        let addresses = (0..GAP_LIMIT)
+            .map(|_| audit_service.next_receive_address("audit").unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(10, addresses[0].index);
+        assert!(audit_service.next_receive_address("audit").is_err());
+
+        // each used address allows handing out the addresses up to the gap limit after it
+        let account = audit_service
+            .mark_used("audit", &addresses[2].address)
+            .unwrap();
+        assert_eq!(Some(12), account.last_used_index);
+        for _ in 0..3 {
+            audit_service.next_receive_address("audit").unwrap();
+        }
+        assert!(audit_service.next_receive_address("audit").is_err());
+        assert!(audit_service
+            .mark_used("audit", &ExtendedAddr::OrTree([0; 32]))
+            .is_err());
+        assert_eq!(
+            GAP_LIMIT as usize + 3,
+            audit_service.get_addresses("audit").unwrap().len()
+        );
+
+        audit_service.delete_account("audit").unwrap();
+        assert!(audit_service.get_account("audit").is_err());
+    }
+}
@@ -0,0 +1,109 @@
+use parity_scale_codec::{Decode, Encode};
+
+/// Maximum number of events kept per wallet (the oldest ones are dropped)
+pub const MAX_EVENTS: usize = 1000;
+
+/// Lifecycle event of a wallet item (invoice, inheritance plan, recurring payment, ...)
+/// with a sequence number (per wallet) for polling
+pub trait SequencedEvent: Clone {
+    /// Kind of the event
+    type Kind;
+
+    /// Creates the event of the item with the given sequence number
+    fn new(sequence: u64, item_id: u64, kind: Self::Kind) -> Self;
+
+    /// Sequence number of the event
+    fn sequence(&self) -> u64;
+}
+
+/// Latest lifecycle events of the items of a wallet, ordered by sequence number
+#[derive(Debug, Encode, Decode)]
+pub struct EventLog<E> {
+    /// Sequence number of the next event
+    next_sequence: u64,
+    events: Vec<E>,
+}
+
+impl<E> Default for EventLog<E> {
+    fn default() -> Self {
+        EventLog {
+            next_sequence: 0,
+            events: Vec::new(),
+        }
+    }
+}
+
+impl<E: SequencedEvent> EventLog<E> {
+    /// Appends an event of the item
+    pub fn push(&mut self, item_id: u64, kind: E::Kind) {
+        self.events.push(E::new(self.next_sequence, item_id, kind));
+        self.next_sequence += 1;
+        if self.events.len() > MAX_EVENTS {
+            let excess = self.events.len() - MAX_EVENTS;
+            self.events.drain(..excess);
+        }
+    }
+
+    /// Appends the events of the item and returns them with their sequence numbers
+    pub fn push_all(&mut self, item_id: u64, kinds: Vec<E::Kind>) -> Vec<E> {
+        let first_sequence = self.next_sequence;
+        for kind in kinds {
+            self.push(item_id, kind);
+        }
+        self.events_from(first_sequence)
+    }
+
+    /// Returns the kept events with a sequence number greater than or equal to `from_sequence`
+    pub fn events_from(&self, from_sequence: u64) -> Vec<E> {
+        self.events
+            .iter()
+            .filter(|event| event.sequence() >= from_sequence)
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct TestEvent(u64, u64, &'static str);
+
+    impl SequencedEvent for TestEvent {
+        type Kind = &'static str;
+
+        fn new(sequence: u64, item_id: u64, kind: Self::Kind) -> Self {
+            TestEvent(sequence, item_id, kind)
+        }
+
+        fn sequence(&self) -> u64 {
+            self.0
+        }
+    }
+
+    #[test]
+    fn check_event_log() {
+        let mut log = EventLog::<TestEvent>::default();
+        log.push(1, "created");
+        assert_eq!(
+            vec![TestEvent(1, 2, "created"), TestEvent(2, 2, "paid")],
+            log.push_all(2, vec!["created", "paid"])
+        );
+        assert_eq!(3, log.events_from(0).len());
+        assert_eq!(vec![TestEvent(2, 2, "paid")], log.events_from(2));
+        assert!(log.events_from(3).is_empty());
+
+        // the oldest events are dropped
+        for _ in 0..MAX_EVENTS {
+            log.push(3, "reminder");
+        }
+        let events = log.events_from(0);
+        assert_eq!(MAX_EVENTS, events.len());
+        assert_eq!(3, events[0].sequence());
+        assert_eq!(
+            vec![TestEvent(MAX_EVENTS as u64 + 2, 3, "reminder")],
+            log.events_from(MAX_EVENTS as u64 + 2)
+        );
+    }
+}
@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+
 use parity_scale_codec::{Decode, Encode};
 
 use chain_core::init::network::get_network;
@@ -9,10 +11,17 @@ use client_common::{
 use crate::types::AddressType;
 use crate::{HDSeed, Mnemonic};
 
-use crate::hd_wallet::ChainPath;
+use crate::hd_wallet::{Bip44Path, ChainPath, BIP44_EXTERNAL_CHANGE};
 use std::convert::From;
 
 const KEYSPACE: &str = "core_hd_key";
+/// key space of the address indexes of the accounts other than the default one
+const ACCOUNT_KEYSPACE: &str = "core_hd_key_account";
+
+/// Number of consecutive unused addresses after which the address discovery stops (BIP44)
+pub const GAP_LIMIT: u32 = 20;
+/// Number of account types, each BIP44 account of a wallet uses one hardened account per type
+pub const HD_ACCOUNT_TYPES: u32 = 3;
 
 /// HD key
 #[derive(Debug, Clone, PartialEq, Default, Encode, Decode)]
@@ -28,6 +37,10 @@ pub struct HdKey {
     pub seed: HDSeed,
 }
 
+/// Next address indexes of the accounts other than the default one, by BIP44 account and change
+#[derive(Debug, Clone, PartialEq, Default, Encode, Decode)]
+struct AccountIndexes(BTreeMap<(u32, u32), u32>);
+
 /// Enum for specifying different types of accounts
 #[derive(Debug, Clone, Copy)]
 pub enum HDAccountType {
@@ -45,6 +58,15 @@ impl HDAccountType {
     pub fn index(self) -> u32 {
         self as u32
     }
+
+    /// get the hardened BIP44 account of this type in the wallet account `account`
+    ///
+    /// The account `0` is the default account of the wallet, whose BIP44 accounts are the indexes
+    /// of the types (so the keys of single-account wallets are unchanged).
+    #[inline]
+    pub fn bip44_account(self, account: u32) -> u32 {
+        account * HD_ACCOUNT_TYPES + self.index()
+    }
 }
 
 // AddressType is subset of HDAccountType
@@ -107,6 +129,7 @@ where
                 format!("Wallet with name {} not found in hd key service", name)
             })?;
         self.storage.delete(KEYSPACE, name)?;
+        self.storage.delete(ACCOUNT_KEYSPACE, name)?;
         Ok(())
     }
 
@@ -276,16 +299,143 @@ where
         Ok(chain_path)
     }
 
+    /// Generates the key pair of the next address of the given type, account and change
+    ///
+    /// The external addresses of the default account (`0`) share their indexes with
+    /// `generate_keypair`.
+    pub fn generate_account_keypair(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        account: u32,
+        account_type: HDAccountType,
+        change: u32,
+    ) -> Result<(Bip44Path, PublicKey, PrivateKey)> {
+        let bip44_account = account_type.bip44_account(account);
+        let index = if account == 0 && change == BIP44_EXTERNAL_CHANGE {
+            let hd_key = self.update_hd_key(name, enckey, account_type)?;
+            match account_type {
+                HDAccountType::Transfer => hd_key.transfer_index,
+                HDAccountType::Staking => hd_key.staking_index,
+                HDAccountType::Viewkey => hd_key.viewkey_index,
+            }
+        } else {
+            let previous =
+                self.storage
+                    .fetch_and_update_secure(ACCOUNT_KEYSPACE, name, enckey, |bytes| {
+                        let mut indexes = match bytes {
+                            Some(mut bytes) => AccountIndexes::decode(&mut bytes).chain(|| {
+                                (
+                                    ErrorKind::DeserializationError,
+                                    "Unable to deserialize account indexes from bytes",
+                                )
+                            })?,
+                            None => AccountIndexes::default(),
+                        };
+                        *indexes.0.entry((bip44_account, change)).or_default() += 1;
+                        Ok(Some(indexes.encode()))
+                    })?;
+            match previous {
+                Some(bytes) => {
+                    let bytes = decrypt_bytes(name, enckey, &bytes)?;
+                    let indexes = AccountIndexes::decode(&mut bytes.as_slice()).chain(|| {
+                        (
+                            ErrorKind::DeserializationError,
+                            "Unable to decode account indexes bytes",
+                        )
+                    })?;
+                    indexes
+                        .0
+                        .get(&(bip44_account, change))
+                        .copied()
+                        .unwrap_or_default()
+                }
+                None => 0,
+            }
+        };
+
+        let path = Bip44Path::new(get_network(), bip44_account, change, index);
+        let (public_key, private_key) = self.get_seed(name, enckey)?.derive_bip44_key_pair(path)?;
+        Ok((path, public_key, private_key))
+    }
+
+    /// Exports the account-level extended public key (BIP32 serialized) of the given type and
+    /// account, for watch-only use
+    pub fn export_account_xpub(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        account: u32,
+        account_type: HDAccountType,
+    ) -> Result<String> {
+        let xpub = self
+            .get_seed(name, enckey)?
+            .get_account_xpub(get_network(), account_type.bip44_account(account))?;
+        Ok(xpub.to_string())
+    }
+
+    /// Gap-limit address discovery: checks the external addresses of the default account after the
+    /// latest generated one until `GAP_LIMIT` consecutive addresses are unused, returns the number
+    /// of addresses to generate to reach the last used one
+    pub fn discover_addresses<F>(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        account_type: HDAccountType,
+        mut is_used: F,
+    ) -> Result<u32>
+    where
+        F: FnMut(&PublicKey) -> Result<bool>,
+    {
+        let hd_key = self.get_hdkey(name, enckey)?.chain(|| {
+            (
+                ErrorKind::InvalidInput,
+                format!("HD Key with name ({}) not found", name),
+            )
+        })?;
+        let next_index = match account_type {
+            HDAccountType::Transfer => hd_key.transfer_index,
+            HDAccountType::Staking => hd_key.staking_index,
+            HDAccountType::Viewkey => hd_key.viewkey_index,
+        };
+        let parent_pubkey = hd_key
+            .seed
+            .get_parent_pubkey(get_network(), account_type.index())?;
+
+        let mut discovered = 0;
+        let mut index = next_index;
+        while index < next_index + discovered + GAP_LIMIT {
+            let public_key = HDSeed::get_pubkey_from_parent_pubkey(&parent_pubkey, index)?;
+            if is_used(&public_key)? {
+                discovered = index - next_index + 1;
+            }
+            index += 1;
+        }
+        Ok(discovered)
+    }
+
+    fn get_seed(&self, name: &str, enckey: &SecKey) -> Result<HDSeed> {
+        let hd_key = self.get_hdkey(name, enckey)?.chain(|| {
+            (
+                ErrorKind::InvalidInput,
+                format!("HD Key with name ({}) not found", name),
+            )
+        })?;
+        Ok(hd_key.seed)
+    }
+
     /// Clears all storage
     #[inline]
     pub fn clear(&self) -> Result<()> {
-        self.storage.clear(KEYSPACE)
+        self.storage.clear(KEYSPACE)?;
+        self.storage.clear(ACCOUNT_KEYSPACE)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::hd_wallet::Xpub;
     use crate::wallet::{DefaultWalletClient, WalletClient};
     use client_common::storage::MemoryStorage;
     use secstr::SecUtf8;
@@ -398,6 +548,84 @@ mod tests {
         }
     }
 
+    #[test]
+    fn check_account_keys() {
+        let storage = MemoryStorage::default();
+        let name = "testhdwallet";
+        let passphrase = SecUtf8::from("passphrase");
+        let service = HdKeyService::new(storage.clone());
+        let mnemonic =
+            Mnemonic::from_secstr(&SecUtf8::from("speed tortoise kiwi forward extend baby acoustic foil coach castle ship purchase unlock base hip erode tag keen present vibrant oyster cotton write fetch")).unwrap();
+
+        let wallet = DefaultWalletClient::new_read_only(storage.clone());
+        let enckey = wallet
+            .restore_wallet(&name, &passphrase, &mnemonic)
+            .expect("restore wallet");
+
+        // external addresses of the default account are the ones of `generate_keypair`
+        let (path, public_key, _) = service
+            .generate_account_keypair(name, &enckey, 0, HDAccountType::Transfer, 0)
+            .unwrap();
+        assert_eq!(0, path.index);
+        assert_eq!(public_key, service.peek_pubkey(name, &enckey, 0).unwrap());
+        let (public_key, _) = service
+            .generate_keypair(name, &enckey, HDAccountType::Transfer)
+            .unwrap();
+        assert_eq!(public_key, service.peek_pubkey(name, &enckey, 1).unwrap());
+
+        // other accounts and change addresses have their own indexes
+        for expected_index in 0..2 {
+            let (path, public_key, _) = service
+                .generate_account_keypair(name, &enckey, 2, HDAccountType::Staking, 1)
+                .unwrap();
+            assert_eq!(7, path.account);
+            assert_eq!(1, path.change);
+            assert_eq!(expected_index, path.index);
+
+            let xpub: Xpub = service
+                .export_account_xpub(name, &enckey, 2, HDAccountType::Staking)
+                .unwrap()
+                .parse()
+                .unwrap();
+            assert_eq!(
+                public_key,
+                PublicKey::from(xpub.derive_public_key(1, expected_index).unwrap())
+            );
+        }
+    }
+
+    #[test]
+    fn check_discover_addresses() {
+        let storage = MemoryStorage::default();
+        let name = "testhdwallet";
+        let passphrase = SecUtf8::from("passphrase");
+        let service = HdKeyService::new(storage.clone());
+        let mnemonic =
+            Mnemonic::from_secstr(&SecUtf8::from("speed tortoise kiwi forward extend baby acoustic foil coach castle ship purchase unlock base hip erode tag keen present vibrant oyster cotton write fetch")).unwrap();
+
+        let wallet = DefaultWalletClient::new_read_only(storage.clone());
+        let enckey = wallet
+            .restore_wallet(&name, &passphrase, &mnemonic)
+            .expect("restore wallet");
+
+        // addresses 3 and 3 + GAP_LIMIT are used, the one after the gap isn't discovered
+        let used = [3, 3 + GAP_LIMIT, 4 + 2 * GAP_LIMIT]
+            .iter()
+            .map(|index| service.peek_pubkey(name, &enckey, *index).unwrap())
+            .collect::<Vec<_>>();
+        let discovered = service
+            .discover_addresses(name, &enckey, HDAccountType::Transfer, |public_key| {
+                Ok(used.contains(public_key))
+            })
+            .unwrap();
+        assert_eq!(4 + GAP_LIMIT, discovered);
+
+        let discovered = service
+            .discover_addresses(name, &enckey, HDAccountType::Transfer, |_| Ok(false))
+            .unwrap();
+        assert_eq!(0, discovered);
+    }
+
     #[test]
     fn check_peek_pubkey() {
         let storage = MemoryStorage::default();
@@ -0,0 +1,494 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+
+use parity_scale_codec::{Decode, Encode};
+
+use chain_core::tx::data::address::ExtendedAddr;
+use client_common::tendermint::types::Time;
+use client_common::{Error, ErrorKind, Result, ResultExt, SecKey, SecureStorage, Storage};
+
+use super::event_log::{EventLog, SequencedEvent};
+use super::WalletStateMemento;
+use crate::types::{
+    InheritanceEvent, InheritanceEventKind, InheritancePackage, InheritancePlan, InheritanceStatus,
+    TransactionChange,
+};
+
+/// key space of wallet inheritance plans
+const KEYSPACE: &str = "core_inheritance";
+
+impl SequencedEvent for InheritanceEvent {
+    type Kind = InheritanceEventKind;
+
+    fn new(sequence: u64, plan_id: u64, kind: InheritanceEventKind) -> Self {
+        InheritanceEvent {
+            sequence,
+            plan_id,
+            kind,
+        }
+    }
+
+    fn sequence(&self) -> u64 {
+        self.sequence
+    }
+}
+
+/// Inheritance plans of a wallet with their lifecycle events
+#[derive(Debug, Default, Encode, Decode)]
+struct InheritanceBook {
+    /// ID of the next plan
+    next_id: u64,
+    /// Plans indexed by id
+    plans: BTreeMap<u64, InheritancePlan>,
+    /// Latest events
+    events: EventLog<InheritanceEvent>,
+}
+
+impl InheritanceBook {
+    fn get_plan_mut(&mut self, id: u64) -> Result<&mut InheritancePlan> {
+        self.plans
+            .get_mut(&id)
+            .err_kind(ErrorKind::InvalidInput, || {
+                format!("Inheritance plan not found: {}", id)
+            })
+    }
+
+    /// Updates the plans with a synced transaction
+    fn apply_transaction_change(&mut self, change: &TransactionChange) {
+        let spent_addresses = change
+            .inputs
+            .iter()
+            .filter_map(|input| input.output.as_ref())
+            .map(|output| &output.address)
+            .collect::<BTreeSet<_>>();
+
+        let mut changed = vec![];
+        for plan in self.plans.values_mut() {
+            if !plan.status.is_active() {
+                continue;
+            }
+            let transaction_id = change.transaction_id;
+            let status = if spent_addresses.contains(&plan.package.address) {
+                if transaction_id == plan.package.transaction_id {
+                    InheritanceStatus::Claimed { transaction_id }
+                } else {
+                    InheritanceStatus::Cancelled { transaction_id }
+                }
+            } else if transaction_id == plan.package.funding_transaction_id
+                && plan.status == InheritanceStatus::Unconfirmed
+            {
+                InheritanceStatus::Active
+            } else {
+                continue;
+            };
+            plan.status = status.clone();
+            changed.push((plan.id, status));
+        }
+        for (id, status) in changed {
+            self.events
+                .push(id, InheritanceEventKind::StatusChanged(status));
+        }
+    }
+
+    /// Applies the synced transactions, then updates the plans at the time of the last synced
+    /// block (reminders and unlocking)
+    fn apply_memento(&mut self, memento: &WalletStateMemento, block_time: u64) {
+        for change in memento.transaction_changes() {
+            self.apply_transaction_change(change);
+        }
+        let mut changed = vec![];
+        for plan in self.plans.values_mut() {
+            if let Some(status) = plan.update_status(block_time) {
+                match status {
+                    InheritanceStatus::RefreshDue => log::warn!(
+                        "Inheritance plan {} should be refreshed before {}",
+                        plan.id,
+                        plan.package.unlock_time
+                    ),
+                    InheritanceStatus::Unlocked => log::warn!(
+                        "Inheritance plan {} is unlocked, its package can be broadcast",
+                        plan.id
+                    ),
+                    _ => {}
+                }
+                changed.push((plan.id, status));
+            }
+        }
+        for (id, status) in changed {
+            self.events
+                .push(id, InheritanceEventKind::StatusChanged(status));
+        }
+    }
+}
+
+fn parse_inheritance_book<T: AsRef<[u8]>>(
+    name: &str,
+    bytes_optional: Option<T>,
+) -> Result<InheritanceBook> {
+    bytes_optional
+        .map(|bytes| {
+            InheritanceBook::decode(&mut bytes.as_ref()).chain(|| {
+                (
+                    ErrorKind::DeserializationError,
+                    format!(
+                        "Unable to deserialize inheritance plans for wallet with name {}",
+                        name
+                    ),
+                )
+            })
+        })
+        .transpose()
+        .map(|book_optional| book_optional.unwrap_or_default())
+}
+
+/// Updates the inheritance plans of the wallet with the synced transactions and the time
+/// of the last synced block (reminders and unlocking of the packages)
+pub fn update_inheritance_plans<S: SecureStorage>(
+    storage: &S,
+    name: &str,
+    enckey: &SecKey,
+    memento: &WalletStateMemento,
+    block_time: &Time,
+) -> Result<()> {
+    let block_time = block_time
+        .duration_since(Time::unix_epoch())
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+    storage
+        .fetch_and_update_secure(KEYSPACE, name, enckey, |bytes_optional| {
+            // nothing to update for wallets without inheritance plans
+            if bytes_optional.is_none() {
+                return Ok(None);
+            }
+            let mut book = parse_inheritance_book(name, bytes_optional)?;
+            book.apply_memento(memento, block_time);
+            Ok(Some(book.encode()))
+        })
+        .map(|_| ())
+}
+
+/// Maintains mapping `wallet-name -> inheritance-book`
+#[derive(Debug, Default, Clone)]
+pub struct InheritanceService<S>
+where
+    S: Storage,
+{
+    storage: S,
+}
+
+impl<S> InheritanceService<S>
+where
+    S: Storage,
+{
+    /// Creates new instance of inheritance service
+    #[inline]
+    pub fn new(storage: S) -> Self {
+        Self { storage }
+    }
+
+    /// Adds a new plan (its ID is assigned by the service)
+    pub fn add_plan(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        plan: InheritancePlan,
+    ) -> Result<InheritancePlan> {
+        self.modify_book(name, enckey, |book| {
+            let mut plan = plan.clone();
+            plan.id = book.next_id;
+            book.next_id += 1;
+            book.plans.insert(plan.id, plan.clone());
+            book.events.push(plan.id, InheritanceEventKind::Prepared);
+            Ok(plan)
+        })
+    }
+
+    /// Replaces the package of an active plan with a refreshed one
+    pub fn refresh_plan(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        id: u64,
+        package: InheritancePackage,
+    ) -> Result<InheritancePlan> {
+        self.modify_book(name, enckey, |book| {
+            let plan = book.get_plan_mut(id)?;
+            if !plan.status.is_active() {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "Inheritance plan is already claimed or cancelled",
+                ));
+            }
+            let unlock_time = package.unlock_time;
+            plan.package = package.clone();
+            plan.status = InheritanceStatus::Unconfirmed;
+            let plan = plan.clone();
+            book.events
+                .push(id, InheritanceEventKind::Refreshed { unlock_time });
+            Ok(plan)
+        })
+    }
+
+    /// Returns the plan with given ID
+    pub fn get_plan(&self, name: &str, enckey: &SecKey, id: u64) -> Result<InheritancePlan> {
+        let mut book = self.get_inheritance_book(name, enckey)?;
+        book.get_plan_mut(id).map(|plan| plan.clone())
+    }
+
+    /// Returns all the plans of the wallet
+    pub fn get_plans(&self, name: &str, enckey: &SecKey) -> Result<Vec<InheritancePlan>> {
+        let book = self.get_inheritance_book(name, enckey)?;
+        Ok(book.plans.into_iter().map(|(_, plan)| plan).collect())
+    }
+
+    /// Returns the events with a sequence number greater than or equal to `from_sequence`
+    pub fn get_events(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        from_sequence: u64,
+    ) -> Result<Vec<InheritanceEvent>> {
+        let book = self.get_inheritance_book(name, enckey)?;
+        Ok(book.events.events_from(from_sequence))
+    }
+
+    /// Returns the inheritance addresses of the active plans (their funds are only spent by
+    /// the inheritance workflow)
+    pub fn get_inheritance_addresses(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+    ) -> Result<BTreeSet<ExtendedAddr>> {
+        let book = self.get_inheritance_book(name, enckey)?;
+        Ok(book
+            .plans
+            .values()
+            .filter(|plan| plan.status.is_active())
+            .map(|plan| plan.package.address.clone())
+            .collect())
+    }
+
+    /// Deletes all the inheritance plans of the wallet
+    #[inline]
+    pub fn delete_plans(&self, name: &str) -> Result<()> {
+        self.storage.delete(KEYSPACE, name).map(|_| ())
+    }
+
+    /// Clears all storage
+    #[inline]
+    pub fn clear(&self) -> Result<()> {
+        self.storage.clear(KEYSPACE)
+    }
+
+    fn get_inheritance_book(&self, name: &str, enckey: &SecKey) -> Result<InheritanceBook> {
+        Ok(self
+            .storage
+            .load_secure(KEYSPACE, name, enckey)?
+            .unwrap_or_default())
+    }
+
+    fn modify_book<F, R>(&self, name: &str, enckey: &SecKey, f: F) -> Result<R>
+    where
+        F: Fn(&mut InheritanceBook) -> Result<R>,
+    {
+        let result = RefCell::new(None);
+        self.storage
+            .fetch_and_update_secure(KEYSPACE, name, enckey, |bytes_optional| {
+                let mut book = parse_inheritance_book(name, bytes_optional)?;
+                *result.borrow_mut() = Some(f(&mut book)?);
+                Ok(Some(book.encode()))
+            })?;
+        Ok(result.into_inner().expect("inheritance book is updated"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    use secstr::SecUtf8;
+
+    use chain_core::init::coin::Coin;
+    use chain_core::tx::data::input::TxoPointer;
+    use chain_core::tx::data::output::TxOut;
+    use chain_core::tx::data::TxId;
+    use chain_core::tx::fee::Fee;
+    use client_common::{seckey::derive_enckey, storage::MemoryStorage, PrivateKey, PublicKey};
+
+    use crate::types::{BalanceChange, Heir, TransactionInput, TransactionType};
+
+    fn transaction_change(id: u8, from: &ExtendedAddr, to: &ExtendedAddr) -> TransactionChange {
+        TransactionChange {
+            transaction_id: [id; 32],
+            inputs: vec![TransactionInput {
+                pointer: TxoPointer::new([0; 32], 0),
+                output: Some(TxOut::new(from.clone(), Coin::unit())),
+            }],
+            outputs: vec![TxOut::new(to.clone(), Coin::unit())],
+            fee_paid: Fee::new(Coin::zero()),
+            balance_change: BalanceChange::NoChange,
+            transaction_type: TransactionType::Transfer,
+            block_height: id.into(),
+            block_time: Time::from_str("2020-01-01T00:00:00Z").unwrap(),
+            annotation: None,
+        }
+    }
+
+    fn package(id: u8, unlock_time: u64) -> InheritancePackage {
+        InheritancePackage {
+            address: ExtendedAddr::OrTree([id; 32]),
+            funding_transaction_id: [id; 32],
+            amount: Coin::unit(),
+            unlock_time,
+            transaction_id: [id + 1; 32],
+            heir_amounts: vec![Coin::unit()],
+            transaction: vec![id],
+        }
+    }
+
+    fn statuses(
+        service: &InheritanceService<MemoryStorage>,
+        enckey: &SecKey,
+    ) -> Vec<InheritanceStatus> {
+        service
+            .get_plans("name", enckey)
+            .unwrap()
+            .into_iter()
+            .map(|plan| plan.status)
+            .collect()
+    }
+
+    #[test]
+    fn check_inheritance_plan_monitoring() {
+        let storage = MemoryStorage::default();
+        let inheritance_service = InheritanceService::new(storage.clone());
+
+        let name = "name";
+        let enckey = &derive_enckey(&SecUtf8::from("passphrase"), name).unwrap();
+        // 2020-01-01T00:01:40Z
+        let unlock_time = 1_577_836_900;
+        let time = |time: &str| Time::from_str(&format!("2020-01-01T{}Z", time)).unwrap();
+
+        // wallets without plans are not updated
+        let memento = WalletStateMemento::default();
+        update_inheritance_plans(&storage, name, enckey, &memento, &time("00:00:00")).unwrap();
+        assert!(inheritance_service
+            .get_plans(name, enckey)
+            .unwrap()
+            .is_empty());
+
+        let heir = ExtendedAddr::OrTree([100; 32]);
+        let owner = ExtendedAddr::OrTree([200; 32]);
+        for package in vec![package(10, unlock_time), package(20, unlock_time)] {
+            inheritance_service
+                .add_plan(
+                    name,
+                    enckey,
+                    InheritancePlan {
+                        id: 0,
+                        owner_public_key: PublicKey::from(&PrivateKey::new().unwrap()),
+                        heirs: vec![Heir {
+                            address: heir.clone(),
+                            share: 1,
+                        }],
+                        refresh_period: 100,
+                        reminder_period: 10,
+                        view_keys: vec![],
+                        package,
+                        status: InheritanceStatus::Unconfirmed,
+                    },
+                )
+                .unwrap();
+        }
+
+        // the packages are funded
+        let mut memento = WalletStateMemento::default();
+        memento.add_transaction_change(transaction_change(10, &owner, &package(10, 0).address));
+        memento.add_transaction_change(transaction_change(20, &owner, &package(20, 0).address));
+        update_inheritance_plans(&storage, name, enckey, &memento, &time("00:00:00")).unwrap();
+        assert_eq!(
+            vec![InheritanceStatus::Active, InheritanceStatus::Active],
+            statuses(&inheritance_service, enckey)
+        );
+
+        // reminder, then the first plan is refreshed
+        let memento = WalletStateMemento::default();
+        update_inheritance_plans(&storage, name, enckey, &memento, &time("00:01:30")).unwrap();
+        assert_eq!(
+            vec![InheritanceStatus::RefreshDue, InheritanceStatus::RefreshDue],
+            statuses(&inheritance_service, enckey)
+        );
+        inheritance_service
+            .refresh_plan(name, enckey, 0, package(30, unlock_time + 100))
+            .unwrap();
+        let mut memento = WalletStateMemento::default();
+        memento.add_transaction_change(transaction_change(
+            30,
+            &package(10, 0).address,
+            &package(30, 0).address,
+        ));
+        update_inheritance_plans(&storage, name, enckey, &memento, &time("00:01:40")).unwrap();
+        assert_eq!(
+            vec![InheritanceStatus::Active, InheritanceStatus::Unlocked],
+            statuses(&inheritance_service, enckey)
+        );
+        assert_eq!(
+            vec![package(30, 0).address],
+            inheritance_service
+                .get_inheritance_addresses(name, enckey)
+                .unwrap()
+                .into_iter()
+                .filter(|address| *address != package(20, 0).address)
+                .collect::<Vec<_>>()
+        );
+
+        // the second package is claimed by the heirs, the first plan is cancelled by the owner
+        let mut memento = WalletStateMemento::default();
+        memento.add_transaction_change(transaction_change(21, &package(20, 0).address, &heir));
+        memento.add_transaction_change(transaction_change(40, &package(30, 0).address, &owner));
+        update_inheritance_plans(&storage, name, enckey, &memento, &time("00:01:50")).unwrap();
+        let claimed: TxId = [21; 32];
+        let cancelled: TxId = [40; 32];
+        assert_eq!(
+            vec![
+                InheritanceStatus::Cancelled {
+                    transaction_id: cancelled
+                },
+                InheritanceStatus::Claimed {
+                    transaction_id: claimed
+                }
+            ],
+            statuses(&inheritance_service, enckey)
+        );
+        assert!(inheritance_service
+            .get_inheritance_addresses(name, enckey)
+            .unwrap()
+            .is_empty());
+        assert!(inheritance_service
+            .refresh_plan(name, enckey, 1, package(50, unlock_time + 200))
+            .is_err());
+
+        let events = inheritance_service.get_events(name, enckey, 0).unwrap();
+        let kinds = events
+            .iter()
+            .filter(|event| event.plan_id == 0)
+            .map(|event| event.kind.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                InheritanceEventKind::Prepared,
+                InheritanceEventKind::StatusChanged(InheritanceStatus::Active),
+                InheritanceEventKind::StatusChanged(InheritanceStatus::RefreshDue),
+                InheritanceEventKind::Refreshed {
+                    unlock_time: unlock_time + 100
+                },
+                InheritanceEventKind::StatusChanged(InheritanceStatus::Active),
+                InheritanceEventKind::StatusChanged(InheritanceStatus::Cancelled {
+                    transaction_id: cancelled
+                }),
+            ],
+            kinds
+        );
+    }
+}
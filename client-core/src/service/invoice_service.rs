@@ -0,0 +1,468 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+use parity_scale_codec::{Decode, Encode};
+
+use chain_core::init::coin::Coin;
+use chain_core::tx::data::address::ExtendedAddr;
+use chain_core::tx::data::input::TxoSize;
+use client_common::tendermint::types::Time;
+use client_common::{Error, ErrorKind, Result, ResultExt, SecKey, SecureStorage, Storage};
+
+use super::event_log::{EventLog, SequencedEvent};
+use super::WalletStateMemento;
+use crate::types::{Invoice, InvoiceEvent, InvoiceEventKind, InvoicePayment, InvoiceStatus};
+
+/// key space of wallet invoices
+const KEYSPACE: &str = "core_invoice";
+
+impl SequencedEvent for InvoiceEvent {
+    type Kind = InvoiceEventKind;
+
+    fn new(sequence: u64, invoice_id: u64, kind: InvoiceEventKind) -> Self {
+        InvoiceEvent {
+            sequence,
+            invoice_id,
+            kind,
+        }
+    }
+
+    fn sequence(&self) -> u64 {
+        self.sequence
+    }
+}
+
+/// Invoices of a wallet with their lifecycle events
+#[derive(Debug, Default, Encode, Decode)]
+struct InvoiceBook {
+    /// ID of the next invoice
+    next_id: u64,
+    /// Invoices indexed by id
+    invoices: BTreeMap<u64, Invoice>,
+    /// Latest events
+    events: EventLog<InvoiceEvent>,
+}
+
+impl InvoiceBook {
+    fn get_invoice_mut(&mut self, id: u64) -> Result<&mut Invoice> {
+        self.invoices
+            .get_mut(&id)
+            .err_kind(ErrorKind::InvalidInput, || {
+                format!("Invoice not found: {}", id)
+            })
+    }
+
+    fn expire_invoices(&mut self, time: u64) {
+        let mut expired = vec![];
+        for invoice in self.invoices.values_mut() {
+            if let Some(status) = invoice.expire(time) {
+                expired.push((invoice.id, status));
+            }
+        }
+        for (id, status) in expired {
+            self.events
+                .push(id, InvoiceEventKind::StatusChanged(status));
+        }
+    }
+
+    /// Records the outputs paid to the invoice addresses (an output is only recorded once,
+    /// so transactions can be synced again)
+    fn add_payment(&mut self, id: u64, payment: InvoicePayment) -> Result<()> {
+        let invoice = self.get_invoice_mut(id)?;
+        if invoice.payments.iter().any(|recorded| {
+            recorded.transaction_id == payment.transaction_id
+                && recorded.output_index == payment.output_index
+        }) {
+            return Ok(());
+        }
+        let status = invoice.add_payment(payment.clone())?;
+        self.events
+            .push(id, InvoiceEventKind::PaymentReceived(payment));
+        if let Some(status) = status {
+            self.events
+                .push(id, InvoiceEventKind::StatusChanged(status));
+        }
+        Ok(())
+    }
+
+    /// Applies the payments of the synced transactions, then expires the invoices at the time
+    /// of the last synced block
+    fn apply_memento(&mut self, memento: &WalletStateMemento, block_time: u64) -> Result<()> {
+        let addresses = self
+            .invoices
+            .values()
+            .map(|invoice| (invoice.address.clone(), invoice.id))
+            .collect::<BTreeMap<ExtendedAddr, u64>>();
+
+        for change in memento.transaction_changes() {
+            let change_time = unix_seconds(&change.block_time);
+            for (index, output) in change.outputs.iter().enumerate() {
+                if let Some(id) = addresses.get(&output.address) {
+                    self.expire_invoices(change_time);
+                    let payment = InvoicePayment {
+                        transaction_id: change.transaction_id,
+                        output_index: index as TxoSize,
+                        amount: output.value,
+                        block_height: change.block_height,
+                        block_time: change_time,
+                    };
+                    self.add_payment(*id, payment)?;
+                }
+            }
+        }
+        self.expire_invoices(block_time);
+        Ok(())
+    }
+}
+
+/// Seconds since the unix epoch
+fn unix_seconds(time: &Time) -> u64 {
+    time.duration_since(Time::unix_epoch())
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+fn parse_invoice_book<T: AsRef<[u8]>>(
+    name: &str,
+    bytes_optional: Option<T>,
+) -> Result<InvoiceBook> {
+    bytes_optional
+        .map(|bytes| {
+            InvoiceBook::decode(&mut bytes.as_ref()).chain(|| {
+                (
+                    ErrorKind::DeserializationError,
+                    format!(
+                        "Unable to deserialize invoices for wallet with name {}",
+                        name
+                    ),
+                )
+            })
+        })
+        .transpose()
+        .map(|book_optional| book_optional.unwrap_or_default())
+}
+
+/// Updates the invoices of the wallet with the synced transactions (payment detection)
+/// and the time of the last synced block (expiry)
+pub fn update_invoices<S: SecureStorage>(
+    storage: &S,
+    name: &str,
+    enckey: &SecKey,
+    memento: &WalletStateMemento,
+    block_time: &Time,
+) -> Result<()> {
+    let block_time = unix_seconds(block_time);
+    storage
+        .fetch_and_update_secure(KEYSPACE, name, enckey, |bytes_optional| {
+            // nothing to update for wallets without invoices
+            if bytes_optional.is_none() {
+                return Ok(None);
+            }
+            let mut book = parse_invoice_book(name, bytes_optional)?;
+            book.apply_memento(memento, block_time)?;
+            Ok(Some(book.encode()))
+        })
+        .map(|_| ())
+}
+
+/// Maintains mapping `wallet-name -> invoice-book`
+#[derive(Debug, Default, Clone)]
+pub struct InvoiceService<S>
+where
+    S: Storage,
+{
+    storage: S,
+}
+
+impl<S> InvoiceService<S>
+where
+    S: Storage,
+{
+    /// Creates new instance of invoice service
+    #[inline]
+    pub fn new(storage: S) -> Self {
+        Self { storage }
+    }
+
+    /// Creates a new invoice paid to the given (newly derived) address
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_invoice(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        amount: Coin,
+        address: ExtendedAddr,
+        memo: String,
+        created_at: u64,
+        expires_at: u64,
+    ) -> Result<Invoice> {
+        if amount == Coin::zero() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Invoice amount should be greater than zero",
+            ));
+        }
+        self.modify_book(name, enckey, |book| {
+            if book
+                .invoices
+                .values()
+                .any(|invoice| invoice.address == address)
+            {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Address is already used by another invoice: {}", address),
+                ));
+            }
+            let invoice = Invoice {
+                id: book.next_id,
+                amount,
+                address: address.clone(),
+                memo: memo.clone(),
+                created_at,
+                expires_at,
+                received: Coin::zero(),
+                payments: vec![],
+                status: InvoiceStatus::Open,
+            };
+            book.next_id += 1;
+            book.invoices.insert(invoice.id, invoice.clone());
+            book.events.push(invoice.id, InvoiceEventKind::Created);
+            Ok(invoice)
+        })
+    }
+
+    /// Returns the invoice with the given id
+    pub fn get_invoice(&self, name: &str, enckey: &SecKey, id: u64) -> Result<Invoice> {
+        let mut book = self.get_invoice_book(name, enckey)?;
+        book.invoices
+            .remove(&id)
+            .err_kind(ErrorKind::InvalidInput, || {
+                format!("Invoice not found: {}", id)
+            })
+    }
+
+    /// Returns all the invoices of the wallet (ordered by id)
+    pub fn get_invoices(&self, name: &str, enckey: &SecKey) -> Result<Vec<Invoice>> {
+        let book = self.get_invoice_book(name, enckey)?;
+        Ok(book
+            .invoices
+            .into_iter()
+            .map(|(_, invoice)| invoice)
+            .collect())
+    }
+
+    /// Cancels the invoice (if it is still open or partially paid)
+    pub fn cancel_invoice(&self, name: &str, enckey: &SecKey, id: u64) -> Result<Invoice> {
+        self.modify_book(name, enckey, |book| {
+            let invoice = book.get_invoice_mut(id)?;
+            let status = invoice.cancel()?;
+            let invoice = invoice.clone();
+            book.events
+                .push(id, InvoiceEventKind::StatusChanged(status));
+            Ok(invoice)
+        })
+    }
+
+    /// Returns the events with a sequence number greater than or equal to `from_sequence`
+    pub fn get_events(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        from_sequence: u64,
+    ) -> Result<Vec<InvoiceEvent>> {
+        let book = self.get_invoice_book(name, enckey)?;
+        Ok(book.events.events_from(from_sequence))
+    }
+
+    /// Deletes all the invoices of the wallet
+    #[inline]
+    pub fn delete_invoices(&self, name: &str) -> Result<()> {
+        self.storage.delete(KEYSPACE, name).map(|_| ())
+    }
+
+    /// Clears all storage
+    #[inline]
+    pub fn clear(&self) -> Result<()> {
+        self.storage.clear(KEYSPACE)
+    }
+
+    fn get_invoice_book(&self, name: &str, enckey: &SecKey) -> Result<InvoiceBook> {
+        Ok(self
+            .storage
+            .load_secure(KEYSPACE, name, enckey)?
+            .unwrap_or_default())
+    }
+
+    fn modify_book<F, R>(&self, name: &str, enckey: &SecKey, f: F) -> Result<R>
+    where
+        F: Fn(&mut InvoiceBook) -> Result<R>,
+    {
+        let result = RefCell::new(None);
+        self.storage
+            .fetch_and_update_secure(KEYSPACE, name, enckey, |bytes_optional| {
+                let mut book = parse_invoice_book(name, bytes_optional)?;
+                *result.borrow_mut() = Some(f(&mut book)?);
+                Ok(Some(book.encode()))
+            })?;
+        Ok(result.into_inner().expect("invoice book is updated"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    use secstr::SecUtf8;
+
+    use chain_core::tx::data::output::TxOut;
+    use chain_core::tx::fee::Fee;
+    use client_common::{seckey::derive_enckey, storage::MemoryStorage};
+
+    use crate::types::{BalanceChange, TransactionChange, TransactionType};
+
+    fn transaction_change(address: &ExtendedAddr, amount: u64, time: &str) -> TransactionChange {
+        TransactionChange {
+            transaction_id: [amount as u8; 32],
+            inputs: vec![],
+            outputs: vec![TxOut::new(address.clone(), Coin::new(amount).unwrap())],
+            fee_paid: Fee::new(Coin::zero()),
+            balance_change: BalanceChange::Incoming {
+                value: Coin::new(amount).unwrap(),
+            },
+            transaction_type: TransactionType::Transfer,
+            block_height: 1,
+            block_time: Time::from_str(time).unwrap(),
+            annotation: None,
+        }
+    }
+
+    #[test]
+    fn check_invoice_lifecycle() {
+        let storage = MemoryStorage::default();
+        let invoice_service = InvoiceService::new(storage.clone());
+
+        let name = "name";
+        let enckey = &derive_enckey(&SecUtf8::from("passphrase"), name).unwrap();
+        let block_time = Time::from_str("2020-01-01T00:00:00Z").unwrap();
+        let now = unix_seconds(&block_time);
+
+        // wallets without invoices are not updated
+        let memento = WalletStateMemento::default();
+        update_invoices(&storage, name, enckey, &memento, &block_time).unwrap();
+        assert!(invoice_service
+            .get_invoices(name, enckey)
+            .unwrap()
+            .is_empty());
+
+        let paid_address = ExtendedAddr::OrTree([1; 32]);
+        let expired_address = ExtendedAddr::OrTree([2; 32]);
+        let paid = invoice_service
+            .create_invoice(
+                name,
+                enckey,
+                Coin::new(100).unwrap(),
+                paid_address.clone(),
+                "paid".to_owned(),
+                now,
+                now + 60,
+            )
+            .unwrap();
+        let expired = invoice_service
+            .create_invoice(
+                name,
+                enckey,
+                Coin::new(100).unwrap(),
+                expired_address,
+                "expired".to_owned(),
+                now,
+                now + 10,
+            )
+            .unwrap();
+        assert!(invoice_service
+            .create_invoice(
+                name,
+                enckey,
+                Coin::new(100).unwrap(),
+                paid_address.clone(),
+                "reused".to_owned(),
+                now,
+                now + 10,
+            )
+            .is_err());
+
+        let mut memento = WalletStateMemento::default();
+        memento.add_transaction_change(transaction_change(
+            &paid_address,
+            40,
+            "2020-01-01T00:00:05Z",
+        ));
+        memento.add_transaction_change(transaction_change(
+            &paid_address,
+            70,
+            "2020-01-01T00:00:30Z",
+        ));
+        let last_block_time = Time::from_str("2020-01-01T00:00:30Z").unwrap();
+        update_invoices(&storage, name, enckey, &memento, &last_block_time).unwrap();
+        // syncing the same transactions again doesn't record the payments twice
+        update_invoices(&storage, name, enckey, &memento, &last_block_time).unwrap();
+
+        let paid = invoice_service.get_invoice(name, enckey, paid.id).unwrap();
+        assert_eq!(InvoiceStatus::Overpaid, paid.status);
+        assert_eq!(Coin::new(110).unwrap(), paid.received);
+        assert_eq!(2, paid.payments.len());
+        let expired = invoice_service
+            .get_invoice(name, enckey, expired.id)
+            .unwrap();
+        assert_eq!(InvoiceStatus::Expired, expired.status);
+        assert!(invoice_service
+            .cancel_invoice(name, enckey, expired.id)
+            .is_err());
+
+        let events = invoice_service.get_events(name, enckey, 0).unwrap();
+        let kinds = events
+            .iter()
+            .map(|event| (event.invoice_id, event.kind.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                (paid.id, InvoiceEventKind::Created),
+                (expired.id, InvoiceEventKind::Created),
+                (
+                    paid.id,
+                    InvoiceEventKind::PaymentReceived(paid.payments[0].clone())
+                ),
+                (
+                    paid.id,
+                    InvoiceEventKind::StatusChanged(InvoiceStatus::PartiallyPaid)
+                ),
+                (
+                    expired.id,
+                    InvoiceEventKind::StatusChanged(InvoiceStatus::Expired)
+                ),
+                (
+                    paid.id,
+                    InvoiceEventKind::PaymentReceived(paid.payments[1].clone())
+                ),
+                (
+                    paid.id,
+                    InvoiceEventKind::StatusChanged(InvoiceStatus::Overpaid)
+                ),
+            ],
+            kinds
+        );
+        assert_eq!(
+            2,
+            invoice_service
+                .get_events(name, enckey, events[5].sequence)
+                .unwrap()
+                .len()
+        );
+
+        invoice_service.delete_invoices(name).unwrap();
+        assert!(invoice_service
+            .get_invoices(name, enckey)
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,272 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+use parity_scale_codec::{Decode, Encode};
+
+use client_common::{Error, ErrorKind, Result, ResultExt, Storage};
+
+use crate::types::{Job, JobKind, JobProgress, JobStatus};
+
+/// key space of jobs
+const KEYSPACE: &str = "core_job";
+/// key of the job book (jobs of all the wallets, they don't contain any secret)
+const BOOK_KEY: &str = "jobs";
+
+/// Maximum number of finished jobs kept in the storage (the oldest ones are removed first)
+pub const MAX_FINISHED_JOBS: usize = 100;
+
+/// Jobs of all the wallets
+#[derive(Debug, Default, Encode, Decode)]
+struct JobBook {
+    /// ID of the next added job
+    next_id: u64,
+    /// Job ID -> job
+    jobs: BTreeMap<u64, Job>,
+}
+
+impl JobBook {
+    fn get_mut(&mut self, id: u64) -> Result<&mut Job> {
+        self.jobs.get_mut(&id).ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("Job with id {} not found", id),
+            )
+        })
+    }
+
+    /// Removes the oldest finished jobs above the limit
+    fn prune(&mut self) {
+        let finished = self
+            .jobs
+            .values()
+            .filter(|job| job.status.is_finished())
+            .map(|job| job.id)
+            .collect::<Vec<_>>();
+        if finished.len() > MAX_FINISHED_JOBS {
+            for id in finished[..finished.len() - MAX_FINISHED_JOBS].iter() {
+                self.jobs.remove(id);
+            }
+        }
+    }
+}
+
+fn parse_job_book<T: AsRef<[u8]>>(bytes_optional: Option<T>) -> Result<JobBook> {
+    bytes_optional
+        .map(|bytes| {
+            JobBook::decode(&mut bytes.as_ref()).chain(|| {
+                (
+                    ErrorKind::DeserializationError,
+                    "Unable to deserialize job book",
+                )
+            })
+        })
+        .transpose()
+        .map(|book_optional| book_optional.unwrap_or_default())
+}
+
+/// Maintains the persisted jobs (long-running wallet operations) with their status and progress
+#[derive(Debug, Default, Clone)]
+pub struct JobService<S>
+where
+    S: Storage,
+{
+    storage: S,
+}
+
+impl<S> JobService<S>
+where
+    S: Storage,
+{
+    /// Creates new instance of job service
+    #[inline]
+    pub fn new(storage: S) -> Self {
+        Self { storage }
+    }
+
+    /// Adds a queued job, fails if the same operation on the wallet is already queued or running
+    pub fn add_job(&self, name: &str, kind: JobKind, now: u64) -> Result<Job> {
+        self.modify_book(|book| {
+            if let Some(job) = book.jobs.values().find(|job| {
+                job.wallet_name == name && job.kind == kind && !job.status.is_finished()
+            }) {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!(
+                        "Job {} of the same operation on wallet {} is not finished",
+                        job.id, name
+                    ),
+                ));
+            }
+            let job = Job {
+                id: book.next_id,
+                wallet_name: name.to_owned(),
+                kind: kind.clone(),
+                status: JobStatus::Queued,
+                progress: JobProgress::default(),
+                error: None,
+                created_at: now,
+                updated_at: now,
+            };
+            book.next_id += 1;
+            book.jobs.insert(job.id, job.clone());
+            Ok(job)
+        })
+    }
+
+    /// Returns the job with given id
+    pub fn get_job(&self, id: u64) -> Result<Option<Job>> {
+        let mut book = self.get_book()?;
+        Ok(book.jobs.remove(&id))
+    }
+
+    /// Returns the jobs (of the wallet if the name is given) in the order they were added
+    pub fn get_jobs(&self, name: Option<&str>) -> Result<Vec<Job>> {
+        let book = self.get_book()?;
+        Ok(book
+            .jobs
+            .into_iter()
+            .map(|(_, job)| job)
+            .filter(|job| name.map_or(true, |name| job.wallet_name == name))
+            .collect())
+    }
+
+    /// Changes the status of the job (with the error of a failed job)
+    pub fn set_status(
+        &self,
+        id: u64,
+        status: JobStatus,
+        error: Option<String>,
+        now: u64,
+    ) -> Result<Job> {
+        self.modify_book(|book| {
+            let job = book.get_mut(id)?;
+            job.transition(status)?;
+            job.error = error.clone();
+            job.updated_at = now;
+            let job = job.clone();
+            book.prune();
+            Ok(job)
+        })
+    }
+
+    /// Records the progress of the running job
+    pub fn update_progress(&self, id: u64, progress: JobProgress, now: u64) -> Result<()> {
+        self.modify_book(|book| {
+            let job = book.get_mut(id)?;
+            if job.status == JobStatus::Running {
+                job.progress = progress;
+                job.updated_at = now;
+            }
+            Ok(())
+        })
+    }
+
+    /// Queues the failed, cancelled or interrupted job again
+    #[inline]
+    pub fn requeue(&self, id: u64, now: u64) -> Result<Job> {
+        self.set_status(id, JobStatus::Queued, None, now)
+    }
+
+    /// Marks the queued and running jobs as interrupted (on startup, the jobs of the previous run
+    /// were stopped with the process), returns the interrupted jobs
+    pub fn interrupt_unfinished(&self, now: u64) -> Result<Vec<Job>> {
+        self.modify_book(|book| {
+            let mut interrupted = vec![];
+            for job in book.jobs.values_mut() {
+                if !job.status.is_finished() {
+                    job.status = JobStatus::Interrupted;
+                    job.updated_at = now;
+                    interrupted.push(job.clone());
+                }
+            }
+            Ok(interrupted)
+        })
+    }
+
+    /// Deletes all the jobs of the wallet
+    pub fn delete_jobs(&self, name: &str) -> Result<()> {
+        self.modify_book(|book| {
+            book.jobs.retain(|_, job| job.wallet_name != name);
+            Ok(())
+        })
+    }
+
+    /// Clears all storage
+    #[inline]
+    pub fn clear(&self) -> Result<()> {
+        self.storage.clear(KEYSPACE)
+    }
+
+    fn get_book(&self) -> Result<JobBook> {
+        parse_job_book(self.storage.get(KEYSPACE, BOOK_KEY)?)
+    }
+
+    fn modify_book<F, R>(&self, f: F) -> Result<R>
+    where
+        F: Fn(&mut JobBook) -> Result<R>,
+    {
+        let result = RefCell::new(None);
+        self.storage
+            .fetch_and_update(KEYSPACE, BOOK_KEY, |bytes_optional| {
+                let mut book = parse_job_book(bytes_optional)?;
+                *result.borrow_mut() = Some(f(&mut book)?);
+                Ok(Some(book.encode()))
+            })?;
+        Ok(result.into_inner().expect("job book is updated"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use client_common::storage::MemoryStorage;
+
+    #[test]
+    fn check_job_flow() {
+        let service = JobService::new(MemoryStorage::default());
+
+        let sync = JobKind::Sync { reset: false };
+        let job = service.add_job("name", sync.clone(), 1).unwrap();
+        assert_eq!(JobStatus::Queued, job.status);
+        assert!(service.add_job("name", sync.clone(), 1).is_err());
+        let other = service.add_job("other", sync.clone(), 1).unwrap();
+        assert_ne!(job.id, other.id);
+
+        service
+            .set_status(job.id, JobStatus::Running, None, 2)
+            .unwrap();
+        let progress = JobProgress {
+            start: 0,
+            current: 5,
+            end: 10,
+        };
+        service.update_progress(job.id, progress, 3).unwrap();
+        assert_eq!(progress, service.get_job(job.id).unwrap().unwrap().progress);
+
+        // restart of the client
+        let interrupted = service.interrupt_unfinished(4).unwrap();
+        assert_eq!(2, interrupted.len());
+        assert_eq!(
+            JobStatus::Interrupted,
+            service.get_job(other.id).unwrap().unwrap().status
+        );
+        let job = service.requeue(job.id, 5).unwrap();
+        assert_eq!(JobStatus::Queued, job.status);
+        assert_eq!(progress, job.progress);
+
+        service
+            .set_status(job.id, JobStatus::Running, None, 6)
+            .unwrap();
+        let job = service
+            .set_status(job.id, JobStatus::Failed, Some("error".to_owned()), 7)
+            .unwrap();
+        assert_eq!(Some("error".to_owned()), job.error);
+        assert_eq!(1, service.get_jobs(Some("name")).unwrap().len());
+        assert_eq!(2, service.get_jobs(None).unwrap().len());
+
+        service.delete_jobs("name").unwrap();
+        assert!(service.get_job(job.id).unwrap().is_none());
+        assert_eq!(1, service.get_jobs(None).unwrap().len());
+    }
+}
@@ -97,7 +97,10 @@ impl PrivateKeyAction for LedgerSignKeyHID {
     }
 
     fn schnorr_sign(&self, _tx: &Transaction) -> Result<SchnorrSignature> {
-        unimplemented!("the crypto app doesn't support now, comming soon..")
+        Err(Error::new(
+            ErrorKind::LedgerError,
+            "schnorr signatures are not supported by the crypto app yet",
+        ))
     }
 
     fn schnorr_sign_unsafe(
@@ -2,7 +2,9 @@ use crate::hd_wallet::ChainPath;
 use crate::service::hw_key_service::HardwareWalletAction;
 use crate::service::ledger_service::get_blob;
 use crate::sync;
-use client_common::{ErrorKind, PrivateKeyAction, PublicKey, Result, ResultExt, Transaction};
+use client_common::{
+    Error, ErrorKind, PrivateKeyAction, PublicKey, Result, ResultExt, Transaction,
+};
 use ledger_crypto::{APDUTransport, Address, CryptoApp};
 use secp256k1::recovery::{RecoverableSignature, RecoveryId};
 use secp256k1::schnorrsig::SchnorrSignature;
@@ -94,7 +96,10 @@ impl PrivateKeyAction for LedgerSignKeyZemu {
     }
 
     fn schnorr_sign(&self, _tx: &Transaction) -> Result<SchnorrSignature> {
-        unimplemented!("the crypto app doesn't support now, comming soon..")
+        Err(Error::new(
+            ErrorKind::LedgerError,
+            "schnorr signatures are not supported by the crypto app yet",
+        ))
     }
 
     fn schnorr_sign_unsafe(
@@ -5,7 +5,7 @@ use client_common::{
     ErrorKind, PrivateKey, PublicKey, Result, ResultExt, SecKey, SecureStorage, Storage,
 };
 
-use crate::multi_sig::MultiSigBuilder;
+use crate::multi_sig::{MultiSigBuilder, MultiSigMessage};
 
 const KEYSPACE: &str = "core_multi_sig_address";
 
@@ -157,6 +157,40 @@ where
             .map(|_| ())
     }
 
+    /// Returns the next message of self for the co-signers (nonce commitment, nonce or partial
+    /// signature, depending on the messages already added to the session)
+    pub fn export_message(&self, session_id: &H256, enckey: &SecKey) -> Result<MultiSigMessage> {
+        let mut session = self.get_session(session_id, enckey)?;
+        let message = session.export_message()?;
+
+        self.set_session(session_id, session, enckey)?;
+        Ok(message)
+    }
+
+    /// Adds a message of a co-signer to session with given id
+    pub fn import_message(
+        &self,
+        session_id: &H256,
+        message: &MultiSigMessage,
+        enckey: &SecKey,
+    ) -> Result<()> {
+        self.storage
+            .fetch_and_update_secure(KEYSPACE, session_id, enckey, |value| {
+                let session_bytes = value.chain(|| {
+                    (
+                        ErrorKind::InvalidInput,
+                        format!("Session with ID ({}) not found", hex::encode(session_id)),
+                    )
+                })?;
+                let mut session =
+                    MultiSigBuilder::from_incomplete_insecure(session_bytes.to_vec())?;
+                session.import_message(message)?;
+
+                Ok(Some(session.to_incomplete()))
+            })
+            .map(|_| ())
+    }
+
     /// Returns final signature. This function will fail if partial signatures from all co-signers are not received.
     pub fn signature(&self, session_id: &H256, enckey: &SecKey) -> Result<SchnorrSignature> {
         let session = self.get_session(session_id, enckey)?;
@@ -317,4 +351,101 @@ mod multi_sig_session_service_tests {
         )
         .expect("Invalid signature");
     }
+
+    #[test]
+    fn check_multi_sig_message_flow() {
+        let multi_sig_service = MultiSigSessionService::new(MemoryStorage::default());
+        let enckey = derive_enckey(&SecUtf8::from("passphrase"), "").unwrap();
+
+        let message = [2u8; 32];
+        let private_keys = vec![
+            PrivateKey::new().unwrap(),
+            PrivateKey::new().unwrap(),
+            PrivateKey::new().unwrap(),
+        ];
+        let public_keys = private_keys
+            .iter()
+            .map(PublicKey::from)
+            .collect::<Vec<PublicKey>>();
+        let session_ids = private_keys
+            .iter()
+            .zip(public_keys.iter())
+            .map(|(private_key, public_key)| {
+                multi_sig_service
+                    .new_session(
+                        message,
+                        public_keys.clone(),
+                        public_key.clone(),
+                        private_key.clone(),
+                        &enckey,
+                    )
+                    .unwrap()
+            })
+            .collect::<Vec<H256>>();
+
+        // nonce commitments, nonces and partial signatures rounds
+        for _ in 0..3 {
+            let messages = session_ids
+                .iter()
+                .map(|session_id| {
+                    // exported messages are transported as strings
+                    multi_sig_service
+                        .export_message(session_id, &enckey)
+                        .unwrap()
+                        .to_string()
+                })
+                .collect::<Vec<String>>();
+            for (index, session_id) in session_ids.iter().enumerate() {
+                for (sender, message) in messages.iter().enumerate() {
+                    let message = message.parse::<MultiSigMessage>().unwrap();
+                    if sender == index {
+                        assert!(multi_sig_service
+                            .import_message(session_id, &message, &enckey)
+                            .is_err());
+                    } else {
+                        multi_sig_service
+                            .import_message(session_id, &message, &enckey)
+                            .expect("Unable to import message of co-signer");
+                    }
+                }
+            }
+        }
+
+        let signature = multi_sig_service
+            .signature(&session_ids[0], &enckey)
+            .unwrap();
+        for session_id in session_ids.iter().skip(1) {
+            assert_eq!(
+                signature,
+                multi_sig_service.signature(session_id, &enckey).unwrap()
+            );
+        }
+
+        let mut public_keys = public_keys;
+        public_keys.sort();
+        let combined_public_key = PublicKey::combine(&public_keys).unwrap().0;
+        schnorr_verify(
+            secp256k1::SECP256K1,
+            &Message::from_slice(&message).unwrap(),
+            &signature,
+            &combined_public_key.into(),
+        )
+        .expect("Invalid signature");
+
+        let other_session_id = multi_sig_service
+            .new_session(
+                [3u8; 32],
+                public_keys.clone(),
+                PublicKey::from(&private_keys[0]),
+                private_keys[0].clone(),
+                &enckey,
+            )
+            .unwrap();
+        let message = multi_sig_service
+            .export_message(&session_ids[1], &enckey)
+            .unwrap();
+        assert!(multi_sig_service
+            .import_message(&other_session_id, &message, &enckey)
+            .is_err());
+    }
 }
@@ -0,0 +1,398 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+use parity_scale_codec::{Decode, Encode};
+
+use chain_core::init::coin::Coin;
+use chain_core::tx::data::address::ExtendedAddr;
+use chain_core::tx::data::TxId;
+use client_common::{Error, ErrorKind, PublicKey, Result, ResultExt, SecKey, Storage};
+
+use super::event_log::{EventLog, SequencedEvent};
+use crate::types::{
+    RecurringPayment, RecurringPaymentEvent, RecurringPaymentEventKind, RecurringPaymentStatus,
+    RetryPolicy,
+};
+
+/// key space of wallet recurring payments
+const KEYSPACE: &str = "core_recurring_payment";
+
+impl SequencedEvent for RecurringPaymentEvent {
+    type Kind = RecurringPaymentEventKind;
+
+    fn new(sequence: u64, payment_id: u64, kind: RecurringPaymentEventKind) -> Self {
+        RecurringPaymentEvent {
+            sequence,
+            payment_id,
+            kind,
+        }
+    }
+
+    fn sequence(&self) -> u64 {
+        self.sequence
+    }
+}
+
+/// Recurring payments of a wallet with their lifecycle events
+#[derive(Debug, Default, Encode, Decode)]
+struct RecurringPaymentBook {
+    /// ID of the next recurring payment
+    next_id: u64,
+    /// Recurring payments indexed by id
+    payments: BTreeMap<u64, RecurringPayment>,
+    /// Latest events
+    events: EventLog<RecurringPaymentEvent>,
+}
+
+impl RecurringPaymentBook {
+    fn get_payment_mut(&mut self, id: u64) -> Result<&mut RecurringPayment> {
+        self.payments
+            .get_mut(&id)
+            .err_kind(ErrorKind::InvalidInput, || {
+                format!("Recurring payment not found: {}", id)
+            })
+    }
+}
+
+fn parse_recurring_payment_book<T: AsRef<[u8]>>(
+    name: &str,
+    bytes_optional: Option<T>,
+) -> Result<RecurringPaymentBook> {
+    bytes_optional
+        .map(|bytes| {
+            RecurringPaymentBook::decode(&mut bytes.as_ref()).chain(|| {
+                (
+                    ErrorKind::DeserializationError,
+                    format!(
+                        "Unable to deserialize recurring payments for wallet with name {}",
+                        name
+                    ),
+                )
+            })
+        })
+        .transpose()
+        .map(|book_optional| book_optional.unwrap_or_default())
+}
+
+/// Maintains mapping `wallet-name -> recurring-payment-book`
+#[derive(Debug, Default, Clone)]
+pub struct RecurringPaymentService<S>
+where
+    S: Storage,
+{
+    storage: S,
+}
+
+impl<S> RecurringPaymentService<S>
+where
+    S: Storage,
+{
+    /// Creates new instance of recurring payment service
+    #[inline]
+    pub fn new(storage: S) -> Self {
+        Self { storage }
+    }
+
+    /// Schedules a new recurring payment, the first payment is due at `start_at`
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_payment(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        to_address: ExtendedAddr,
+        amount: Coin,
+        interval: u64,
+        start_at: u64,
+        end_at: Option<u64>,
+        view_keys: Vec<PublicKey>,
+        retry_policy: RetryPolicy,
+        created_at: u64,
+    ) -> Result<RecurringPayment> {
+        if amount == Coin::zero() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Recurring payment amount should be greater than zero",
+            ));
+        }
+        if interval == 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Recurring payment interval should be greater than zero",
+            ));
+        }
+        if retry_policy.max_attempts == 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Recurring payment should be attempted at least once",
+            ));
+        }
+        if end_at.map(|end_at| end_at < start_at).unwrap_or(false) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "End time of recurring payment should not be before its start time",
+            ));
+        }
+        self.modify_book(name, enckey, |book| {
+            let payment = RecurringPayment {
+                id: book.next_id,
+                to_address: to_address.clone(),
+                amount,
+                interval,
+                end_at,
+                view_keys: view_keys.clone(),
+                retry_policy,
+                next_due_at: start_at,
+                failed_attempts: 0,
+                next_attempt_at: start_at,
+                created_at,
+                status: RecurringPaymentStatus::Active,
+            };
+            book.next_id += 1;
+            book.payments.insert(payment.id, payment.clone());
+            book.events
+                .push(payment.id, RecurringPaymentEventKind::Created);
+            Ok(payment)
+        })
+    }
+
+    /// Returns the recurring payment with the given id
+    pub fn get_payment(&self, name: &str, enckey: &SecKey, id: u64) -> Result<RecurringPayment> {
+        let mut book = self.get_book(name, enckey)?;
+        book.payments
+            .remove(&id)
+            .err_kind(ErrorKind::InvalidInput, || {
+                format!("Recurring payment not found: {}", id)
+            })
+    }
+
+    /// Returns all the recurring payments of the wallet (ordered by id)
+    pub fn get_payments(&self, name: &str, enckey: &SecKey) -> Result<Vec<RecurringPayment>> {
+        let book = self.get_book(name, enckey)?;
+        Ok(book
+            .payments
+            .into_iter()
+            .map(|(_, payment)| payment)
+            .collect())
+    }
+
+    /// Returns the recurring payments to be attempted at the given time
+    pub fn get_due_payments(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        time: u64,
+    ) -> Result<Vec<RecurringPayment>> {
+        Ok(self
+            .get_payments(name, enckey)?
+            .into_iter()
+            .filter(|payment| payment.is_due(time))
+            .collect())
+    }
+
+    /// Cancels the recurring payment (if it is still active)
+    pub fn cancel_payment(&self, name: &str, enckey: &SecKey, id: u64) -> Result<RecurringPayment> {
+        self.modify_book(name, enckey, |book| {
+            let payment = book.get_payment_mut(id)?;
+            let status = payment.cancel()?;
+            let payment = payment.clone();
+            book.events
+                .push(id, RecurringPaymentEventKind::StatusChanged(status));
+            Ok(payment)
+        })
+    }
+
+    /// Records the broadcast transaction of the due payment, returns the new events
+    pub fn record_paid(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        id: u64,
+        transaction_id: TxId,
+    ) -> Result<Vec<RecurringPaymentEvent>> {
+        self.modify_book(name, enckey, |book| {
+            let kinds = book.get_payment_mut(id)?.paid(transaction_id);
+            Ok(book.events.push_all(id, kinds))
+        })
+    }
+
+    /// Records the failed attempt of the due payment at the given time, returns the new events
+    pub fn record_failure(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        id: u64,
+        time: u64,
+        error: &str,
+    ) -> Result<Vec<RecurringPaymentEvent>> {
+        self.modify_book(name, enckey, |book| {
+            let kinds = book.get_payment_mut(id)?.failed(time, error.to_owned());
+            Ok(book.events.push_all(id, kinds))
+        })
+    }
+
+    /// Returns the events with a sequence number greater than or equal to `from_sequence`
+    pub fn get_events(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        from_sequence: u64,
+    ) -> Result<Vec<RecurringPaymentEvent>> {
+        let book = self.get_book(name, enckey)?;
+        Ok(book.events.events_from(from_sequence))
+    }
+
+    /// Deletes all the recurring payments of the wallet
+    #[inline]
+    pub fn delete_payments(&self, name: &str) -> Result<()> {
+        self.storage.delete(KEYSPACE, name).map(|_| ())
+    }
+
+    /// Clears all storage
+    #[inline]
+    pub fn clear(&self) -> Result<()> {
+        self.storage.clear(KEYSPACE)
+    }
+
+    fn get_book(&self, name: &str, enckey: &SecKey) -> Result<RecurringPaymentBook> {
+        Ok(self
+            .storage
+            .load_secure(KEYSPACE, name, enckey)?
+            .unwrap_or_default())
+    }
+
+    fn modify_book<F, R>(&self, name: &str, enckey: &SecKey, f: F) -> Result<R>
+    where
+        F: Fn(&mut RecurringPaymentBook) -> Result<R>,
+    {
+        let result = RefCell::new(None);
+        self.storage
+            .fetch_and_update_secure(KEYSPACE, name, enckey, |bytes_optional| {
+                let mut book = parse_recurring_payment_book(name, bytes_optional)?;
+                *result.borrow_mut() = Some(f(&mut book)?);
+                Ok(Some(book.encode()))
+            })?;
+        Ok(result
+            .into_inner()
+            .expect("recurring payment book is updated"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use secstr::SecUtf8;
+
+    use client_common::{seckey::derive_enckey, storage::MemoryStorage};
+
+    #[test]
+    fn check_recurring_payment_lifecycle() {
+        let service = RecurringPaymentService::new(MemoryStorage::default());
+
+        let name = "name";
+        let enckey = &derive_enckey(&SecUtf8::from("passphrase"), name).unwrap();
+        let address = ExtendedAddr::OrTree([1; 32]);
+        let amount = Coin::new(100).unwrap();
+        let retry_policy = RetryPolicy {
+            max_attempts: 1,
+            retry_delay: 0,
+        };
+
+        assert!(service
+            .add_payment(
+                name,
+                enckey,
+                address.clone(),
+                amount,
+                0,
+                100,
+                None,
+                vec![],
+                retry_policy,
+                0
+            )
+            .is_err());
+        let payment = service
+            .add_payment(
+                name,
+                enckey,
+                address.clone(),
+                amount,
+                100,
+                100,
+                Some(200),
+                vec![],
+                retry_policy,
+                0,
+            )
+            .unwrap();
+        let cancelled = service
+            .add_payment(
+                name,
+                enckey,
+                address,
+                amount,
+                100,
+                1000,
+                None,
+                vec![],
+                retry_policy,
+                0,
+            )
+            .unwrap();
+
+        assert!(service
+            .get_due_payments(name, enckey, 99)
+            .unwrap()
+            .is_empty());
+        let due = service.get_due_payments(name, enckey, 100).unwrap();
+        assert_eq!(vec![payment.clone()], due);
+
+        let events = service
+            .record_paid(name, enckey, payment.id, [1; 32])
+            .unwrap();
+        assert_eq!(1, events.len());
+        let events = service
+            .record_failure(name, enckey, payment.id, 200, "insufficient balance")
+            .unwrap();
+        assert_eq!(
+            vec![
+                RecurringPaymentEventKind::AttemptFailed {
+                    due_at: 200,
+                    attempt: 1,
+                    error: "insufficient balance".to_owned(),
+                },
+                RecurringPaymentEventKind::Skipped { due_at: 200 },
+                RecurringPaymentEventKind::StatusChanged(RecurringPaymentStatus::Completed),
+            ],
+            events
+                .into_iter()
+                .map(|event| event.kind)
+                .collect::<Vec<_>>()
+        );
+        assert!(service
+            .get_due_payments(name, enckey, 300)
+            .unwrap()
+            .is_empty());
+
+        service.cancel_payment(name, enckey, cancelled.id).unwrap();
+        assert!(service.cancel_payment(name, enckey, cancelled.id).is_err());
+        assert!(service
+            .get_due_payments(name, enckey, 1000)
+            .unwrap()
+            .is_empty());
+
+        let events = service.get_events(name, enckey, 0).unwrap();
+        assert_eq!(7, events.len());
+        assert_eq!(
+            3,
+            service
+                .get_events(name, enckey, events[4].sequence)
+                .unwrap()
+                .len()
+        );
+
+        service.delete_payments(name).unwrap();
+        assert!(service.get_payments(name, enckey).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,311 @@
+use std::cell::RefCell;
+
+use parity_scale_codec::{Decode, Encode};
+use secstr::SecUtf8;
+
+use chain_core::common::{Timespec, H256};
+use chain_core::init::coin::{sum_coins, Coin};
+use chain_core::tx::data::output::TxOut;
+use client_common::seckey::derive_enckey;
+use client_common::{Error, ErrorKind, Result, ResultExt, SecKey, Storage};
+
+use crate::types::SpendingPolicy;
+
+/// key space of wallet spending policies
+const KEYSPACE: &str = "core_spending_policy";
+/// Length of the window of the daily limit (in seconds)
+const DAY_SECS: Timespec = 86_400;
+/// Validity of an approved override (in seconds)
+pub const OVERRIDE_VALIDITY_SECS: Timespec = 300;
+
+/// Spending policy of a wallet and the transfers it has authorized
+#[derive(Debug, Default, Encode, Decode)]
+struct PolicyState {
+    policy: Option<SpendingPolicy>,
+    /// hash of the key derived from the override passphrase
+    override_hash: Option<H256>,
+    /// (time, amount) of the transfers authorized in the last 24 hours
+    spent: Vec<(Timespec, Coin)>,
+    /// the next violating transaction is allowed until this time
+    override_until: Option<Timespec>,
+}
+
+fn parse_policy_state<T: AsRef<[u8]>>(
+    name: &str,
+    bytes_optional: Option<T>,
+) -> Result<PolicyState> {
+    bytes_optional
+        .map(|bytes| {
+            PolicyState::decode(&mut bytes.as_ref()).chain(|| {
+                (
+                    ErrorKind::DeserializationError,
+                    format!(
+                        "Unable to deserialize spending policy for wallet with name {}",
+                        name
+                    ),
+                )
+            })
+        })
+        .transpose()
+        .map(|state_optional| state_optional.unwrap_or_default())
+}
+
+fn override_hash(name: &str, override_passphrase: &SecUtf8) -> Result<H256> {
+    let key =
+        derive_enckey(override_passphrase, &format!("{}/spending-policy", name)).chain(|| {
+            (
+                ErrorKind::InternalError,
+                "Unable to derive key from override passphrase",
+            )
+        })?;
+    Ok(blake3::hash(key.unsecure()).into())
+}
+
+impl PolicyState {
+    /// Checks the override passphrase (any passphrase is accepted if none is set yet)
+    fn check_override(&self, override_hash: &H256) -> Result<()> {
+        match &self.override_hash {
+            Some(hash) if hash != override_hash => Err(Error::new(
+                ErrorKind::PermissionDenied,
+                "Incorrect override passphrase of spending policy",
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn spent_since(&mut self, since: Timespec) -> Result<Coin> {
+        self.spent.retain(|(time, _)| *time > since);
+        sum_coins(self.spent.iter().map(|(_, amount)| *amount))
+            .chain(|| (ErrorKind::InternalError, "Invalid amount spent today"))
+    }
+}
+
+/// Maintains mapping `wallet-name -> spending-policy`
+#[derive(Debug, Default, Clone)]
+pub struct SpendingPolicyService<S>
+where
+    S: Storage,
+{
+    storage: S,
+}
+
+impl<S> SpendingPolicyService<S>
+where
+    S: Storage,
+{
+    /// Creates new instance of spending policy service
+    #[inline]
+    pub fn new(storage: S) -> Self {
+        Self { storage }
+    }
+
+    /// Sets the spending policy of the wallet; the override passphrase is set with the first
+    /// policy and must be provided to change the policy afterwards
+    pub fn set_policy(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        policy: SpendingPolicy,
+        override_passphrase: &SecUtf8,
+    ) -> Result<()> {
+        let hash = override_hash(name, override_passphrase)?;
+        self.modify_state(name, enckey, |state| {
+            state.check_override(&hash)?;
+            state.policy = Some(policy.clone());
+            state.override_hash = Some(hash);
+            Ok(())
+        })
+    }
+
+    /// Removes the spending policy of the wallet (requires the override passphrase)
+    pub fn remove_policy(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        override_passphrase: &SecUtf8,
+    ) -> Result<()> {
+        let hash = override_hash(name, override_passphrase)?;
+        self.modify_state(name, enckey, |state| {
+            state.check_override(&hash)?;
+            *state = PolicyState::default();
+            Ok(())
+        })
+    }
+
+    /// Returns the spending policy of the wallet
+    pub fn get_policy(&self, name: &str, enckey: &SecKey) -> Result<Option<SpendingPolicy>> {
+        Ok(self.get_state(name, enckey)?.policy)
+    }
+
+    /// Allows the next transaction violating the policy (within `OVERRIDE_VALIDITY_SECS`),
+    /// returns the time until which the override is valid
+    pub fn approve_override(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        override_passphrase: &SecUtf8,
+        now: Timespec,
+    ) -> Result<Timespec> {
+        let hash = override_hash(name, override_passphrase)?;
+        self.modify_state(name, enckey, |state| {
+            if state.policy.is_none() {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "Wallet has no spending policy",
+                ));
+            }
+            state.check_override(&hash)?;
+            let until = now.saturating_add(OVERRIDE_VALIDITY_SECS);
+            state.override_until = Some(until);
+            Ok(until)
+        })
+    }
+
+    /// Checks the outputs sent to other wallets against the policy (consuming an approved
+    /// override if they violate it) and records them in the daily limit
+    pub fn authorize(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        outputs: &[TxOut],
+        now: Timespec,
+    ) -> Result<()> {
+        if outputs.is_empty() || self.get_policy(name, enckey)?.is_none() {
+            return Ok(());
+        }
+        let amount = sum_coins(outputs.iter().map(|output| output.value))
+            .chain(|| (ErrorKind::IllegalInput, "Invalid amount of the outputs"))?;
+        self.modify_state(name, enckey, |state| {
+            let policy = match &state.policy {
+                Some(policy) => policy.clone(),
+                None => return Ok(()),
+            };
+            let spent = state.spent_since(now.saturating_sub(DAY_SECS))?;
+            let violations = policy.violations(outputs, spent, now)?;
+            if !violations.is_empty() {
+                match state.override_until.take() {
+                    Some(until) if until >= now => {}
+                    _ => {
+                        return Err(Error::new(
+                            ErrorKind::PermissionDenied,
+                            format!(
+                                "Transaction violates spending policy: {}",
+                                violations.join("; ")
+                            ),
+                        ))
+                    }
+                }
+            }
+            state.spent.push((now, amount));
+            Ok(())
+        })
+    }
+
+    /// Deletes the spending policy of the wallet
+    #[inline]
+    pub fn delete_policy(&self, name: &str) -> Result<()> {
+        self.storage.delete(KEYSPACE, name).map(|_| ())
+    }
+
+    /// Clears all storage
+    #[inline]
+    pub fn clear(&self) -> Result<()> {
+        self.storage.clear(KEYSPACE)
+    }
+
+    fn get_state(&self, name: &str, enckey: &SecKey) -> Result<PolicyState> {
+        Ok(self
+            .storage
+            .load_secure(KEYSPACE, name, enckey)?
+            .unwrap_or_default())
+    }
+
+    fn modify_state<F, R>(&self, name: &str, enckey: &SecKey, f: F) -> Result<R>
+    where
+        F: Fn(&mut PolicyState) -> Result<R>,
+    {
+        let result = RefCell::new(None);
+        self.storage
+            .fetch_and_update_secure(KEYSPACE, name, enckey, |bytes_optional| {
+                let mut state = parse_policy_state(name, bytes_optional)?;
+                *result.borrow_mut() = Some(f(&mut state)?);
+                Ok(Some(state.encode()))
+            })?;
+        Ok(result.into_inner().expect("spending policy is updated"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chain_core::tx::data::address::ExtendedAddr;
+    use client_common::storage::MemoryStorage;
+
+    #[test]
+    fn check_spending_policy_flow() {
+        let service = SpendingPolicyService::new(MemoryStorage::default());
+
+        let name = "name";
+        let enckey = &derive_enckey(&SecUtf8::from("passphrase"), name).unwrap();
+        let override_passphrase = SecUtf8::from("override");
+        let policy = SpendingPolicy {
+            max_per_day: Some(Coin::new(1000).unwrap()),
+            ..Default::default()
+        };
+        let output = TxOut::new(ExtendedAddr::OrTree([1; 32]), Coin::new(600).unwrap());
+        let now = 1_600_000_000;
+
+        // no policy
+        service
+            .authorize(name, enckey, &[output.clone()], now)
+            .unwrap();
+        service
+            .set_policy(name, enckey, policy.clone(), &override_passphrase)
+            .unwrap();
+        assert_eq!(
+            Some(policy.clone()),
+            service.get_policy(name, enckey).unwrap()
+        );
+        assert_eq!(
+            ErrorKind::PermissionDenied,
+            service
+                .set_policy(name, enckey, policy, &SecUtf8::from("wrong"))
+                .unwrap_err()
+                .kind()
+        );
+
+        service
+            .authorize(name, enckey, &[output.clone()], now)
+            .unwrap();
+        assert_eq!(
+            ErrorKind::PermissionDenied,
+            service
+                .authorize(name, enckey, &[output.clone()], now + 1)
+                .unwrap_err()
+                .kind()
+        );
+        // the override is valid for one transaction
+        service
+            .approve_override(name, enckey, &override_passphrase, now + 1)
+            .unwrap();
+        service
+            .authorize(name, enckey, &[output.clone()], now + 2)
+            .unwrap();
+        assert!(service
+            .authorize(name, enckey, &[output.clone()], now + 3)
+            .is_err());
+        // the daily limit is a rolling window
+        service
+            .authorize(name, enckey, &[output.clone()], now + DAY_SECS + 3)
+            .unwrap();
+
+        assert!(service
+            .remove_policy(name, enckey, &SecUtf8::from("wrong"))
+            .is_err());
+        service
+            .remove_policy(name, enckey, &override_passphrase)
+            .unwrap();
+        assert_eq!(None, service.get_policy(name, enckey).unwrap());
+    }
+}
@@ -0,0 +1,319 @@
+use std::collections::BTreeSet;
+
+use parity_scale_codec::{Decode, Encode};
+use serde::Serialize;
+
+use client_common::{Error, ErrorKind, Result, ResultExt, Storage};
+
+use super::sync_state_service::{SyncState, KEYSPACE as SYNC_STATE_KEYSPACE};
+use super::wallet_service::{get_wallet_keyspace, KEYSPACE as WALLET_KEYSPACE};
+use super::wallet_state_service::{
+    INDEX_KEYSPACE as WALLET_HISTORY_INDEX_KEYSPACE, KEYSPACE as WALLET_STATE_KEYSPACE,
+};
+
+/// Keyspace of the storage layout version and of the migration backup
+const KEYSPACE: &str = "core_storage_migration";
+const VERSION_KEY: &str = "version";
+const BACKUP_KEY: &str = "backup";
+
+/// Version of the storage layout written by this client
+///
+/// Storages without a version were written by older clients (and may contain legacy records).
+pub const STORAGE_VERSION: u32 = 1;
+
+/// Record of a storage written by an older client
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type")]
+pub enum LegacyRecord {
+    /// Wallet missing from the index of wallet names (wallets imported by older clients), so it
+    /// isn't listed; it's added to the index
+    UnindexedWallet {
+        /// name of the wallet
+        name: String,
+    },
+    /// Sync state in an older encoding; it's removed together with the wallet state, so the
+    /// wallet is synchronized again from the genesis (instead of being re-created)
+    LegacySyncState {
+        /// name of the wallet
+        name: String,
+    },
+}
+
+/// Report of a storage migration
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MigrationReport {
+    /// storage layout version before the migration
+    pub from_version: u32,
+    /// storage layout version after the migration
+    pub to_version: u32,
+    /// legacy records found in the storage
+    pub legacy_records: Vec<LegacyRecord>,
+    /// `true` if the legacy records were converted (`false` for a dry run)
+    pub applied: bool,
+}
+
+/// Value of a key before the migration (`None` if the key wasn't set)
+#[derive(Debug, Encode, Decode)]
+struct BackupEntry {
+    keyspace: String,
+    key: String,
+    value: Option<Vec<u8>>,
+}
+
+/// Backup of the values modified by the last migration
+#[derive(Debug, Encode, Decode)]
+struct MigrationBackup {
+    from_version: u32,
+    entries: Vec<BackupEntry>,
+}
+
+/// Migrates storages written by older clients to the current layout
+///
+/// The values modified by the migration are backed up in the storage first, so the last
+/// migration can be rolled back (e.g. to downgrade the client).
+#[derive(Debug, Default, Clone)]
+pub struct StorageMigrationService<S: Storage> {
+    storage: S,
+}
+
+impl<S> StorageMigrationService<S>
+where
+    S: Storage,
+{
+    /// Creates a new instance of storage migration service
+    pub fn new(storage: S) -> Self {
+        StorageMigrationService { storage }
+    }
+
+    /// Returns the layout version of the storage (`0` if it was written before the versioning)
+    pub fn version(&self) -> Result<u32> {
+        Ok(self.storage.load(KEYSPACE, VERSION_KEY)?.unwrap_or(0))
+    }
+
+    /// Detects the legacy records without converting them (dry run)
+    pub fn detect(&self) -> Result<MigrationReport> {
+        let from_version = self.version()?;
+        if from_version > STORAGE_VERSION {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Storage layout version {} is newer than the supported version {}",
+                    from_version, STORAGE_VERSION
+                ),
+            ));
+        }
+
+        let legacy_records = if from_version < STORAGE_VERSION {
+            self.legacy_records()?
+        } else {
+            Vec::new()
+        };
+
+        Ok(MigrationReport {
+            from_version,
+            to_version: STORAGE_VERSION,
+            legacy_records,
+            applied: false,
+        })
+    }
+
+    /// Converts the legacy records to the current layout (after backing up the modified values)
+    pub fn migrate(&self) -> Result<MigrationReport> {
+        let mut report = self.detect()?;
+        if report.from_version == STORAGE_VERSION {
+            return Ok(report);
+        }
+
+        let mut entries = Vec::new();
+        for record in report.legacy_records.iter() {
+            match record {
+                LegacyRecord::UnindexedWallet { name } => {
+                    entries.push(self.backup_entry(&get_wallet_keyspace(), name)?);
+                }
+                LegacyRecord::LegacySyncState { name } => {
+                    entries.push(self.backup_entry(SYNC_STATE_KEYSPACE, name)?);
+                    entries.push(self.backup_entry(WALLET_STATE_KEYSPACE, name)?);
+                    entries.push(self.backup_entry(WALLET_HISTORY_INDEX_KEYSPACE, name)?);
+                }
+            }
+        }
+        self.storage.save(
+            KEYSPACE,
+            BACKUP_KEY,
+            &MigrationBackup {
+                from_version: report.from_version,
+                entries,
+            },
+        )?;
+
+        for record in report.legacy_records.iter() {
+            match record {
+                LegacyRecord::UnindexedWallet { name } => {
+                    self.storage
+                        .set(get_wallet_keyspace(), name, name.as_bytes().to_vec())?;
+                }
+                LegacyRecord::LegacySyncState { name } => {
+                    self.storage.delete(SYNC_STATE_KEYSPACE, name)?;
+                    self.storage.delete(WALLET_STATE_KEYSPACE, name)?;
+                    self.storage.delete(WALLET_HISTORY_INDEX_KEYSPACE, name)?;
+                }
+            }
+        }
+
+        self.storage.save(KEYSPACE, VERSION_KEY, &STORAGE_VERSION)?;
+        self.storage.flush()?;
+
+        report.applied = true;
+        Ok(report)
+    }
+
+    /// Restores the values modified by the last migration, returns `false` if there is no backup
+    pub fn rollback(&self) -> Result<bool> {
+        let backup: MigrationBackup = match self.storage.load(KEYSPACE, BACKUP_KEY)? {
+            Some(backup) => backup,
+            None => return Ok(false),
+        };
+
+        for entry in backup.entries {
+            match entry.value {
+                Some(value) => self.storage.set(entry.keyspace, entry.key, value)?,
+                None => self.storage.delete(entry.keyspace, entry.key)?,
+            };
+        }
+
+        if backup.from_version == 0 {
+            self.storage.delete(KEYSPACE, VERSION_KEY)?;
+        } else {
+            self.storage
+                .save(KEYSPACE, VERSION_KEY, &backup.from_version)?;
+        }
+        self.storage.delete(KEYSPACE, BACKUP_KEY)?;
+        self.storage.flush()?;
+
+        Ok(true)
+    }
+
+    fn legacy_records(&self) -> Result<Vec<LegacyRecord>> {
+        let mut records = Vec::new();
+
+        let indexed_names = self
+            .storage
+            .keys(get_wallet_keyspace())?
+            .into_iter()
+            .collect::<BTreeSet<_>>();
+        for key in self.storage.keys(WALLET_KEYSPACE)? {
+            if !indexed_names.contains(&key) {
+                records.push(LegacyRecord::UnindexedWallet {
+                    name: key_to_name(key)?,
+                });
+            }
+        }
+
+        for key in self.storage.keys(SYNC_STATE_KEYSPACE)? {
+            let name = key_to_name(key)?;
+            if let Some(value) = self.storage.get(SYNC_STATE_KEYSPACE, &name)? {
+                if SyncState::decode(&mut value.as_slice()).is_err() {
+                    records.push(LegacyRecord::LegacySyncState { name });
+                }
+            }
+        }
+
+        Ok(records)
+    }
+
+    fn backup_entry(&self, keyspace: &str, key: &str) -> Result<BackupEntry> {
+        Ok(BackupEntry {
+            keyspace: keyspace.to_owned(),
+            key: key.to_owned(),
+            value: self.storage.get(keyspace, key)?,
+        })
+    }
+}
+
+fn key_to_name(key: Vec<u8>) -> Result<String> {
+    String::from_utf8(key).chain(|| {
+        (
+            ErrorKind::DeserializationError,
+            "Unable to deserialize wallet names in storage",
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use client_common::storage::MemoryStorage;
+
+    use crate::service::WalletService;
+
+    #[test]
+    fn check_migration_and_rollback() {
+        let storage = MemoryStorage::default();
+        let service = StorageMigrationService::new(storage.clone());
+
+        // wallet record without index entry and sync state in an older encoding
+        storage.set(WALLET_KEYSPACE, "legacy", vec![0; 62]).unwrap();
+        storage
+            .set(SYNC_STATE_KEYSPACE, "legacy", vec![1, 2, 3])
+            .unwrap();
+        storage
+            .set(WALLET_STATE_KEYSPACE, "legacy", vec![4, 5, 6])
+            .unwrap();
+
+        let report = service.detect().unwrap();
+        assert_eq!(0, report.from_version);
+        assert!(!report.applied);
+        assert_eq!(
+            vec![
+                LegacyRecord::UnindexedWallet {
+                    name: "legacy".to_owned()
+                },
+                LegacyRecord::LegacySyncState {
+                    name: "legacy".to_owned()
+                },
+            ],
+            report.legacy_records
+        );
+        assert!(WalletService::new(storage.clone())
+            .names()
+            .unwrap()
+            .is_empty());
+
+        let report = service.migrate().unwrap();
+        assert!(report.applied);
+        assert_eq!(STORAGE_VERSION, service.version().unwrap());
+        assert_eq!(
+            vec!["legacy".to_owned()],
+            WalletService::new(storage.clone()).names().unwrap()
+        );
+        assert!(storage
+            .get(SYNC_STATE_KEYSPACE, "legacy")
+            .unwrap()
+            .is_none());
+        assert!(storage
+            .get(WALLET_STATE_KEYSPACE, "legacy")
+            .unwrap()
+            .is_none());
+
+        let report = service.migrate().unwrap();
+        assert!(!report.applied);
+        assert!(report.legacy_records.is_empty());
+
+        assert!(service.rollback().unwrap());
+        assert_eq!(0, service.version().unwrap());
+        assert!(WalletService::new(storage.clone())
+            .names()
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            Some(vec![1, 2, 3]),
+            storage.get(SYNC_STATE_KEYSPACE, "legacy").unwrap()
+        );
+        assert_eq!(
+            Some(vec![4, 5, 6]),
+            storage.get(WALLET_STATE_KEYSPACE, "legacy").unwrap()
+        );
+        assert!(!service.rollback().unwrap());
+    }
+}
@@ -2,7 +2,7 @@ use chain_core::common::H256;
 use client_common::{ErrorKind, Result, ResultExt, Storage};
 use parity_scale_codec::{Decode, Encode};
 /// key space of wallet sync state
-const KEYSPACE: &str = "core_wallet_sync";
+pub(super) const KEYSPACE: &str = "core_wallet_sync";
 
 /// Sync state for wallet
 #[derive(Debug, Encode, Decode)]
@@ -0,0 +1,391 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use parity_scale_codec::{Decode, Encode};
+use secp256k1::key::XOnlyPublicKey;
+use secp256k1::schnorrsig::{schnorr_verify, SchnorrSignature};
+use secp256k1::Message;
+
+use chain_core::common::MerkleTree;
+use chain_core::state::account::{
+    threshold_staking_address, StakedStateAddress, StakedStateOpWitness, ThresholdSignature,
+    MAX_THRESHOLD_SIGNATURES,
+};
+use chain_core::tx::data::TxId;
+use chain_core::tx::witness::tree::{RawSignature, RawXOnlyPubkey};
+use chain_core::tx::{TxAux, TxPublicAux};
+use chain_tx_validation::witness::verify_tx_recover_address;
+use client_common::tendermint::types::BroadcastTxResponse;
+use client_common::tendermint::Client;
+use client_common::{
+    Error, ErrorKind, PrivateKeyAction, PublicKey, Result, ResultExt, SecKey, SecureStorage,
+    Storage, Transaction,
+};
+
+/// key space of the threshold witness sessions
+const KEYSPACE: &str = "core_threshold_witness";
+
+/// Signature of one co-signer of an m-of-n staking address, exchanged between the co-signers
+/// (e.g. in a file, see `to_base64`)
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+pub struct PartialStakingSignature {
+    /// the signed staking operation
+    pub txid: TxId,
+    /// key of the co-signer (a member of the key set)
+    pub public_key: RawXOnlyPubkey,
+    /// BIP340 Schnorr signature of the transaction ID
+    pub signature: RawSignature,
+}
+
+impl PartialStakingSignature {
+    /// Signs the staking operation with the key of a co-signer
+    pub fn sign(transaction: &Transaction, sign_key: &dyn PrivateKeyAction) -> Result<Self> {
+        let signature = sign_key.schnorr_sign(transaction)?;
+        Ok(PartialStakingSignature {
+            txid: transaction.id(),
+            public_key: RawXOnlyPubkey::from(&sign_key.public_key()?),
+            signature: signature.serialize_default(),
+        })
+    }
+
+    /// base64 of the SCALE encoding
+    pub fn to_base64(&self) -> String {
+        base64::encode(&self.encode())
+    }
+
+    /// decodes the output of `to_base64`
+    pub fn from_base64(encoded: &str) -> Result<Self> {
+        let bytes = base64::decode(encoded.trim()).chain(|| {
+            (
+                ErrorKind::DeserializationError,
+                "Unable to decode base64 of partial staking signature",
+            )
+        })?;
+        PartialStakingSignature::decode(&mut bytes.as_slice()).chain(|| {
+            (
+                ErrorKind::DeserializationError,
+                "Unable to deserialize partial staking signature",
+            )
+        })
+    }
+}
+
+/// Staking operation of an m-of-n staking address waiting for the signatures of its co-signers
+#[derive(Debug, Clone, Encode, Decode)]
+struct ThresholdWitnessSession {
+    transaction: Transaction,
+    threshold: u16,
+    key_set: Vec<RawXOnlyPubkey>,
+    /// key set index -> signature
+    signatures: BTreeMap<u16, RawSignature>,
+}
+
+impl ThresholdWitnessSession {
+    fn key_set_tree(&self) -> MerkleTree<RawXOnlyPubkey> {
+        MerkleTree::new(self.key_set.clone())
+    }
+
+    fn witness(&self) -> Result<StakedStateOpWitness> {
+        if self.signatures.len() < usize::from(self.threshold) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Not enough signatures: {} of {}",
+                    self.signatures.len(),
+                    self.threshold
+                ),
+            ));
+        }
+        let tree = self.key_set_tree();
+        let signatures = self
+            .signatures
+            .iter()
+            .take(usize::from(self.threshold))
+            .map(|(index, signature)| {
+                let key = self.key_set[usize::from(*index)].clone();
+                let proof = tree
+                    .generate_proof(key)
+                    .expect("signing key is in the key set");
+                let signature = SchnorrSignature::from_default(signature)
+                    .expect("signature was parsed when added");
+                (signature, proof)
+            })
+            .collect();
+        let witness = StakedStateOpWitness::ThresholdSig(ThresholdSignature {
+            threshold: self.threshold,
+            signatures,
+        });
+        // the same checks as the chain
+        verify_tx_recover_address(&witness, &self.transaction.id()).chain(|| {
+            (
+                ErrorKind::VerifyError,
+                "Unable to verify the aggregated threshold witness",
+            )
+        })?;
+        Ok(witness)
+    }
+}
+
+fn staking_address(transaction: &Transaction) -> Result<StakedStateAddress> {
+    match transaction {
+        Transaction::UnbondStakeTransaction(tx) => Ok(tx.from_staked_account),
+        Transaction::UnjailTransaction(tx) => Ok(tx.address),
+        Transaction::NodejoinTransaction(tx) => Ok(tx.address),
+        Transaction::DataAnchorTransaction(tx) => Ok(tx.from_staked_account),
+        _ => Err(Error::new(
+            ErrorKind::InvalidInput,
+            "Only public staking operations can be signed by a threshold witness",
+        )),
+    }
+}
+
+fn tx_aux(transaction: Transaction, witness: StakedStateOpWitness) -> TxAux {
+    let tx = match transaction {
+        Transaction::UnbondStakeTransaction(tx) => TxPublicAux::UnbondStakeTx(tx, witness),
+        Transaction::UnjailTransaction(tx) => TxPublicAux::UnjailTx(tx, witness),
+        Transaction::NodejoinTransaction(tx) => TxPublicAux::NodeJoinTx(tx, witness),
+        Transaction::DataAnchorTransaction(tx) => TxPublicAux::DataAnchorTx(tx, witness),
+        _ => unreachable!("checked when the session is created"),
+    };
+    TxAux::PublicTx(tx)
+}
+
+/// Collects the signatures of the co-signers of m-of-n staking addresses
+/// (`StakedStateOpWitness::ThresholdSig`), aggregates them and broadcasts the staking operations.
+///
+/// Maintains mapping `transaction id -> session`
+#[derive(Debug, Default, Clone)]
+pub struct ThresholdWitnessService<S>
+where
+    S: Storage,
+{
+    storage: S,
+}
+
+impl<S> ThresholdWitnessService<S>
+where
+    S: Storage,
+{
+    /// Creates new instance of threshold witness service
+    #[inline]
+    pub fn new(storage: S) -> Self {
+        Self { storage }
+    }
+
+    /// Creates a new session for a public staking operation, returns its transaction id
+    ///
+    /// # Arguments
+    ///
+    /// - `transaction`: unbond, unjail, node join or data anchor transaction
+    /// - `threshold`: number of required signatures
+    /// - `key_set`: keys of all the co-signers, in the order of the key set of the staking
+    ///   address (their Merkle root and the threshold need to match the staking address
+    ///   of the transaction)
+    /// - `enckey`: Passphrase for encryption
+    pub fn new_session(
+        &self,
+        transaction: Transaction,
+        threshold: u16,
+        key_set: Vec<PublicKey>,
+        enckey: &SecKey,
+    ) -> Result<TxId> {
+        if threshold == 0
+            || usize::from(threshold) > key_set.len()
+            || usize::from(threshold) > MAX_THRESHOLD_SIGNATURES
+            || key_set.len() > usize::from(u16::max_value())
+        {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Invalid threshold {} of {} keys", threshold, key_set.len()),
+            ));
+        }
+        let key_set: Vec<RawXOnlyPubkey> = key_set.iter().map(RawXOnlyPubkey::from).collect();
+        // the chain doesn't count the same key twice
+        if key_set.iter().collect::<BTreeSet<_>>().len() != key_set.len() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Duplicate keys in the key set",
+            ));
+        }
+        let root = MerkleTree::new(key_set.clone()).root_hash();
+        if threshold_staking_address(threshold, &root) != staking_address(&transaction)? {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "The key set doesn't match the staking address of the transaction",
+            ));
+        }
+
+        let txid = transaction.id();
+        let session = ThresholdWitnessSession {
+            transaction,
+            threshold,
+            key_set,
+            signatures: BTreeMap::new(),
+        };
+        self.storage
+            .save_secure(KEYSPACE, &hex::encode(&txid), enckey, &session)?;
+        Ok(txid)
+    }
+
+    /// Adds the signature of a co-signer, returns the number of collected signatures
+    pub fn add_partial_signature(
+        &self,
+        partial_signature: &PartialStakingSignature,
+        enckey: &SecKey,
+    ) -> Result<usize> {
+        let mut session = self.get_session(&partial_signature.txid, enckey)?;
+        let index = session
+            .key_set
+            .iter()
+            .position(|key| *key == partial_signature.public_key)
+            .chain(|| {
+                (
+                    ErrorKind::InvalidInput,
+                    "Signing key is not a member of the key set",
+                )
+            })?;
+        let signature = SchnorrSignature::from_default(&partial_signature.signature)
+            .chain(|| (ErrorKind::InvalidInput, "Unable to parse schnorr signature"))?;
+        let public_key = XOnlyPublicKey::from_slice(partial_signature.public_key.as_bytes())
+            .chain(|| (ErrorKind::InvalidInput, "Unable to parse signing key"))?;
+        let message = Message::from_slice(&partial_signature.txid)
+            .chain(|| (ErrorKind::InvalidInput, "Unable to parse transaction id"))?;
+        schnorr_verify(secp256k1::SECP256K1, &message, &signature, &public_key).chain(|| {
+            (
+                ErrorKind::InvalidInput,
+                "Invalid signature of the co-signer",
+            )
+        })?;
+
+        session
+            .signatures
+            .insert(index as u16, partial_signature.signature);
+        let collected = session.signatures.len();
+        self.set_session(&partial_signature.txid, &session, enckey)?;
+        Ok(collected)
+    }
+
+    /// Returns the threshold witness (fails if not enough signatures are collected)
+    pub fn witness(&self, txid: &TxId, enckey: &SecKey) -> Result<StakedStateOpWitness> {
+        self.get_session(txid, enckey)?.witness()
+    }
+
+    /// Broadcasts the staking operation with the threshold witness and removes the session
+    pub fn broadcast<C: Client>(
+        &self,
+        client: &C,
+        txid: &TxId,
+        enckey: &SecKey,
+    ) -> Result<BroadcastTxResponse> {
+        let session = self.get_session(txid, enckey)?;
+        let witness = session.witness()?;
+        let response =
+            client.broadcast_transaction(&tx_aux(session.transaction, witness).encode())?;
+        self.storage.delete(KEYSPACE, hex::encode(txid))?;
+        Ok(response)
+    }
+
+    /// Clears all storage
+    #[inline]
+    pub fn clear(&self) -> Result<()> {
+        self.storage.clear(KEYSPACE)
+    }
+
+    fn get_session(&self, txid: &TxId, enckey: &SecKey) -> Result<ThresholdWitnessSession> {
+        self.storage
+            .load_secure(KEYSPACE, &hex::encode(txid), enckey)?
+            .chain(|| {
+                (
+                    ErrorKind::InvalidInput,
+                    format!("Session with ID ({}) not found", hex::encode(txid)),
+                )
+            })
+    }
+
+    fn set_session(
+        &self,
+        txid: &TxId,
+        session: &ThresholdWitnessSession,
+        enckey: &SecKey,
+    ) -> Result<()> {
+        self.storage
+            .save_secure(KEYSPACE, &hex::encode(txid), enckey, session)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use secp256k1::key::SecretKey;
+    use secstr::SecUtf8;
+
+    use chain_core::init::coin::Coin;
+    use chain_core::state::account::{StakedStateOpAttributes, UnbondTx};
+    use client_common::{seckey::derive_enckey, storage::MemoryStorage, PrivateKey};
+
+    fn unbond(threshold: u16, key_set: &[PublicKey]) -> Transaction {
+        let root = MerkleTree::new(key_set.iter().map(RawXOnlyPubkey::from).collect()).root_hash();
+        Transaction::UnbondStakeTransaction(UnbondTx::new(
+            threshold_staking_address(threshold, &root),
+            0,
+            Coin::unit(),
+            StakedStateOpAttributes::new(0),
+        ))
+    }
+
+    #[test]
+    fn check_threshold_witness_flow() {
+        let service = ThresholdWitnessService::new(MemoryStorage::default());
+        let enckey = &derive_enckey(&SecUtf8::from("passphrase"), "name").unwrap();
+
+        let sign_keys: Vec<PrivateKey> = [0xcd, 0xde, 0xef]
+            .iter()
+            .map(|b| PrivateKey::from(SecretKey::from_slice(&[*b; 32]).unwrap()))
+            .collect();
+        let key_set: Vec<PublicKey> = sign_keys
+            .iter()
+            .map(|key| key.public_key().unwrap())
+            .collect();
+        let transaction = unbond(2, &key_set);
+
+        // the key set of another staking address
+        assert_eq!(
+            ErrorKind::InvalidInput,
+            service
+                .new_session(transaction.clone(), 2, key_set[..2].to_vec(), enckey)
+                .unwrap_err()
+                .kind()
+        );
+        let txid = service
+            .new_session(transaction.clone(), 2, key_set.clone(), enckey)
+            .unwrap();
+
+        let partial0 = PartialStakingSignature::sign(&transaction, &sign_keys[0]).unwrap();
+        let transported = PartialStakingSignature::from_base64(&partial0.to_base64()).unwrap();
+        assert_eq!(partial0, transported);
+        assert_eq!(
+            1,
+            service.add_partial_signature(&transported, enckey).unwrap()
+        );
+        // the same co-signer again
+        assert_eq!(1, service.add_partial_signature(&partial0, enckey).unwrap());
+        assert!(service.witness(&txid, enckey).is_err());
+
+        // not a co-signer
+        let other = PrivateKey::from(SecretKey::from_slice(&[0xaa; 32]).unwrap());
+        let partial = PartialStakingSignature::sign(&transaction, &other).unwrap();
+        assert!(service.add_partial_signature(&partial, enckey).is_err());
+        // signature of another co-signer
+        let mut forged = PartialStakingSignature::sign(&transaction, &sign_keys[2]).unwrap();
+        forged.signature = partial0.signature;
+        assert!(service.add_partial_signature(&forged, enckey).is_err());
+
+        let partial2 = PartialStakingSignature::sign(&transaction, &sign_keys[2]).unwrap();
+        assert_eq!(2, service.add_partial_signature(&partial2, enckey).unwrap());
+        let witness = service.witness(&txid, enckey).unwrap();
+        assert_eq!(
+            staking_address(&transaction).unwrap(),
+            verify_tx_recover_address(&witness, &txid).unwrap()
+        );
+    }
+}
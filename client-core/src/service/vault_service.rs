@@ -0,0 +1,451 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+
+use parity_scale_codec::{Decode, Encode};
+
+use chain_core::tx::data::address::ExtendedAddr;
+use chain_core::tx::data::TxId;
+use client_common::tendermint::types::Time;
+use client_common::{Error, ErrorKind, Result, ResultExt, SecKey, SecureStorage, Storage};
+
+use super::WalletStateMemento;
+use crate::types::{TransactionChange, Vault, VaultAlert, VaultSpend, VaultSpendStatus};
+
+/// key space of wallet vaults
+const KEYSPACE: &str = "core_vault";
+
+/// Vaults of a wallet with their spends
+#[derive(Debug, Default, Encode, Decode)]
+struct VaultBook {
+    /// Vaults indexed by address
+    vaults: BTreeMap<ExtendedAddr, Vault>,
+    /// Spends from the vaults (in the order they were initiated)
+    spends: Vec<VaultSpend>,
+}
+
+impl VaultBook {
+    fn get_vault(&self, address: &ExtendedAddr) -> Result<&Vault> {
+        self.vaults
+            .get(address)
+            .err_kind(ErrorKind::InvalidInput, || {
+                format!("Vault not found: {}", address)
+            })
+    }
+
+    fn get_spend_mut(&mut self, transaction_id: &TxId) -> Result<&mut VaultSpend> {
+        self.spends
+            .iter_mut()
+            .find(|spend| spend.transaction_id == *transaction_id)
+            .err_kind(ErrorKind::InvalidInput, || {
+                format!("Vault spend not found: {}", hex::encode(transaction_id))
+            })
+    }
+
+    /// Updates the spends (and alerts) with a synced transaction
+    fn apply_transaction_change(&mut self, change: &TransactionChange) {
+        let spent_addresses = change
+            .inputs
+            .iter()
+            .filter_map(|input| input.output.as_ref())
+            .map(|output| &output.address)
+            .collect::<BTreeSet<_>>();
+
+        for spend in self.spends.iter_mut() {
+            if spend.transaction_id == change.transaction_id
+                && spend.status == VaultSpendStatus::Unconfirmed
+            {
+                spend.status = VaultSpendStatus::Pending;
+            }
+            if spend.status.is_active() && spent_addresses.contains(&spend.pending_address) {
+                let transaction_id = change.transaction_id;
+                spend.status = if change
+                    .outputs
+                    .iter()
+                    .any(|output| output.address == spend.vault_address)
+                {
+                    VaultSpendStatus::Cancelled { transaction_id }
+                } else {
+                    VaultSpendStatus::Completed { transaction_id }
+                };
+            }
+        }
+
+        let initiated = self
+            .spends
+            .iter()
+            .any(|spend| spend.transaction_id == change.transaction_id);
+        if initiated {
+            return;
+        }
+        for vault in self.vaults.values_mut() {
+            if spent_addresses.contains(&vault.address)
+                && !vault
+                    .alerts
+                    .iter()
+                    .any(|alert| alert.transaction_id == change.transaction_id)
+            {
+                log::warn!(
+                    "Vault {} was spent by a transaction not initiated by the wallet: {}",
+                    vault.address,
+                    hex::encode(&change.transaction_id)
+                );
+                vault.alerts.push(VaultAlert {
+                    transaction_id: change.transaction_id,
+                    block_height: change.block_height,
+                });
+            }
+        }
+    }
+
+    /// Applies the synced transactions, then unlocks the pending spends at the time of the last
+    /// synced block
+    fn apply_memento(&mut self, memento: &WalletStateMemento, block_time: u64) {
+        for change in memento.transaction_changes() {
+            self.apply_transaction_change(change);
+        }
+        for spend in self.spends.iter_mut() {
+            if spend.status == VaultSpendStatus::Pending && spend.unlock_time <= block_time {
+                spend.status = VaultSpendStatus::Ready;
+            }
+        }
+    }
+}
+
+fn parse_vault_book<T: AsRef<[u8]>>(name: &str, bytes_optional: Option<T>) -> Result<VaultBook> {
+    bytes_optional
+        .map(|bytes| {
+            VaultBook::decode(&mut bytes.as_ref()).chain(|| {
+                (
+                    ErrorKind::DeserializationError,
+                    format!("Unable to deserialize vaults for wallet with name {}", name),
+                )
+            })
+        })
+        .transpose()
+        .map(|book_optional| book_optional.unwrap_or_default())
+}
+
+/// Updates the vault spends of the wallet with the synced transactions and the time
+/// of the last synced block (monitoring of the pending spends)
+pub fn update_vaults<S: SecureStorage>(
+    storage: &S,
+    name: &str,
+    enckey: &SecKey,
+    memento: &WalletStateMemento,
+    block_time: &Time,
+) -> Result<()> {
+    let block_time = block_time
+        .duration_since(Time::unix_epoch())
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+    storage
+        .fetch_and_update_secure(KEYSPACE, name, enckey, |bytes_optional| {
+            // nothing to update for wallets without vaults
+            if bytes_optional.is_none() {
+                return Ok(None);
+            }
+            let mut book = parse_vault_book(name, bytes_optional)?;
+            book.apply_memento(memento, block_time);
+            Ok(Some(book.encode()))
+        })
+        .map(|_| ())
+}
+
+/// Maintains mapping `wallet-name -> vault-book`
+#[derive(Debug, Default, Clone)]
+pub struct VaultService<S>
+where
+    S: Storage,
+{
+    storage: S,
+}
+
+impl<S> VaultService<S>
+where
+    S: Storage,
+{
+    /// Creates new instance of vault service
+    #[inline]
+    pub fn new(storage: S) -> Self {
+        Self { storage }
+    }
+
+    /// Adds a new vault
+    pub fn add_vault(&self, name: &str, enckey: &SecKey, vault: Vault) -> Result<()> {
+        self.modify_book(name, enckey, |book| {
+            if book.vaults.contains_key(&vault.address) {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Vault already exists: {}", vault.address),
+                ));
+            }
+            book.vaults.insert(vault.address.clone(), vault.clone());
+            Ok(())
+        })
+    }
+
+    /// Returns the vault with given address
+    pub fn get_vault(&self, name: &str, enckey: &SecKey, address: &ExtendedAddr) -> Result<Vault> {
+        let book = self.get_vault_book(name, enckey)?;
+        book.get_vault(address).map(Clone::clone)
+    }
+
+    /// Returns all the vaults of the wallet
+    pub fn get_vaults(&self, name: &str, enckey: &SecKey) -> Result<Vec<Vault>> {
+        let book = self.get_vault_book(name, enckey)?;
+        Ok(book.vaults.into_iter().map(|(_, vault)| vault).collect())
+    }
+
+    /// Records a spend from a vault (before broadcasting its transaction)
+    pub fn add_spend(&self, name: &str, enckey: &SecKey, spend: VaultSpend) -> Result<()> {
+        self.modify_book(name, enckey, |book| {
+            book.get_vault(&spend.vault_address)?;
+            book.spends.push(spend.clone());
+            Ok(())
+        })
+    }
+
+    /// Returns the spend initiated by the transaction with given ID
+    pub fn get_spend(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        transaction_id: &TxId,
+    ) -> Result<VaultSpend> {
+        let mut book = self.get_vault_book(name, enckey)?;
+        book.get_spend_mut(transaction_id)
+            .map(|spend| spend.clone())
+    }
+
+    /// Returns all the spends from the vaults of the wallet
+    pub fn get_spends(&self, name: &str, enckey: &SecKey) -> Result<Vec<VaultSpend>> {
+        let book = self.get_vault_book(name, enckey)?;
+        Ok(book.spends)
+    }
+
+    /// Returns the vault and pending addresses (their funds are only spent by the vault workflow)
+    pub fn get_vault_addresses(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+    ) -> Result<BTreeSet<ExtendedAddr>> {
+        let book = self.get_vault_book(name, enckey)?;
+        Ok(book
+            .vaults
+            .keys()
+            .cloned()
+            .chain(
+                book.spends
+                    .iter()
+                    .map(|spend| spend.pending_address.clone()),
+            )
+            .collect())
+    }
+
+    /// Deletes all the vaults of the wallet
+    #[inline]
+    pub fn delete_vaults(&self, name: &str) -> Result<()> {
+        self.storage.delete(KEYSPACE, name).map(|_| ())
+    }
+
+    /// Clears all storage
+    #[inline]
+    pub fn clear(&self) -> Result<()> {
+        self.storage.clear(KEYSPACE)
+    }
+
+    fn get_vault_book(&self, name: &str, enckey: &SecKey) -> Result<VaultBook> {
+        Ok(self
+            .storage
+            .load_secure(KEYSPACE, name, enckey)?
+            .unwrap_or_default())
+    }
+
+    fn modify_book<F, R>(&self, name: &str, enckey: &SecKey, f: F) -> Result<R>
+    where
+        F: Fn(&mut VaultBook) -> Result<R>,
+    {
+        let result = RefCell::new(None);
+        self.storage
+            .fetch_and_update_secure(KEYSPACE, name, enckey, |bytes_optional| {
+                let mut book = parse_vault_book(name, bytes_optional)?;
+                *result.borrow_mut() = Some(f(&mut book)?);
+                Ok(Some(book.encode()))
+            })?;
+        Ok(result.into_inner().expect("vault book is updated"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    use secstr::SecUtf8;
+
+    use chain_core::init::coin::Coin;
+    use chain_core::tx::data::input::TxoPointer;
+    use chain_core::tx::data::output::TxOut;
+    use chain_core::tx::fee::Fee;
+    use client_common::{seckey::derive_enckey, storage::MemoryStorage, PrivateKey, PublicKey};
+
+    use crate::types::{BalanceChange, TransactionInput, TransactionType};
+
+    fn transaction_change(id: u8, from: &ExtendedAddr, to: &ExtendedAddr) -> TransactionChange {
+        TransactionChange {
+            transaction_id: [id; 32],
+            inputs: vec![TransactionInput {
+                pointer: TxoPointer::new([0; 32], 0),
+                output: Some(TxOut::new(from.clone(), Coin::unit())),
+            }],
+            outputs: vec![TxOut::new(to.clone(), Coin::unit())],
+            fee_paid: Fee::new(Coin::zero()),
+            balance_change: BalanceChange::NoChange,
+            transaction_type: TransactionType::Transfer,
+            block_height: id.into(),
+            block_time: Time::from_str("2020-01-01T00:00:00Z").unwrap(),
+            annotation: None,
+        }
+    }
+
+    fn spend(
+        id: u8,
+        vault: &Vault,
+        pending_address: &ExtendedAddr,
+        unlock_time: u64,
+    ) -> VaultSpend {
+        VaultSpend {
+            transaction_id: [id; 32],
+            vault_address: vault.address.clone(),
+            pending_address: pending_address.clone(),
+            unlock_time,
+            to_address: ExtendedAddr::OrTree([9; 32]),
+            amount: Coin::unit(),
+            view_keys: vec![],
+            status: VaultSpendStatus::Unconfirmed,
+        }
+    }
+
+    #[test]
+    fn check_vault_spend_monitoring() {
+        let storage = MemoryStorage::default();
+        let vault_service = VaultService::new(storage.clone());
+
+        let name = "name";
+        let enckey = &derive_enckey(&SecUtf8::from("passphrase"), name).unwrap();
+        let start = Time::from_str("2020-01-01T00:00:00Z").unwrap();
+
+        // wallets without vaults are not updated
+        let memento = WalletStateMemento::default();
+        update_vaults(&storage, name, enckey, &memento, &start).unwrap();
+        assert!(vault_service.get_vaults(name, enckey).unwrap().is_empty());
+
+        let vault = Vault {
+            address: ExtendedAddr::OrTree([1; 32]),
+            spend_public_key: PublicKey::from(&PrivateKey::new().unwrap()),
+            cancel_public_key: PublicKey::from(&PrivateKey::new().unwrap()),
+            delay: 60,
+            alerts: vec![],
+        };
+        vault_service
+            .add_vault(name, enckey, vault.clone())
+            .unwrap();
+        assert!(vault_service
+            .add_vault(name, enckey, vault.clone())
+            .is_err());
+
+        let completed_address = ExtendedAddr::OrTree([2; 32]);
+        let cancelled_address = ExtendedAddr::OrTree([3; 32]);
+        let unlock_time = 1_577_836_860; // 2020-01-01T00:01:00Z
+        vault_service
+            .add_spend(
+                name,
+                enckey,
+                spend(10, &vault, &completed_address, unlock_time),
+            )
+            .unwrap();
+        vault_service
+            .add_spend(
+                name,
+                enckey,
+                spend(11, &vault, &cancelled_address, unlock_time),
+            )
+            .unwrap();
+        assert_eq!(
+            vec![
+                vault.address.clone(),
+                completed_address.clone(),
+                cancelled_address.clone()
+            ],
+            vault_service
+                .get_vault_addresses(name, enckey)
+                .unwrap()
+                .into_iter()
+                .collect::<Vec<_>>()
+        );
+
+        // the spends are synced, the vault is also spent by an unknown transaction
+        let mut memento = WalletStateMemento::default();
+        memento.add_transaction_change(transaction_change(10, &vault.address, &completed_address));
+        memento.add_transaction_change(transaction_change(11, &vault.address, &cancelled_address));
+        memento.add_transaction_change(transaction_change(20, &vault.address, &completed_address));
+        let block_time = Time::from_str("2020-01-01T00:00:30Z").unwrap();
+        update_vaults(&storage, name, enckey, &memento, &block_time).unwrap();
+        // syncing the same transactions again doesn't report the alert twice
+        update_vaults(&storage, name, enckey, &memento, &block_time).unwrap();
+
+        let spends = vault_service.get_spends(name, enckey).unwrap();
+        assert_eq!(VaultSpendStatus::Pending, spends[0].status);
+        assert_eq!(VaultSpendStatus::Pending, spends[1].status);
+        let alerts = vault_service
+            .get_vault(name, enckey, &vault.address)
+            .unwrap()
+            .alerts;
+        assert_eq!(
+            vec![VaultAlert {
+                transaction_id: [20; 32],
+                block_height: 20,
+            }],
+            alerts
+        );
+
+        // the delay passed
+        let block_time = Time::from_str("2020-01-01T00:01:00Z").unwrap();
+        update_vaults(
+            &storage,
+            name,
+            enckey,
+            &WalletStateMemento::default(),
+            &block_time,
+        )
+        .unwrap();
+        let spends = vault_service.get_spends(name, enckey).unwrap();
+        assert_eq!(VaultSpendStatus::Ready, spends[0].status);
+        assert_eq!(VaultSpendStatus::Ready, spends[1].status);
+
+        // one spend is completed, the other one is swept back to the vault
+        let mut memento = WalletStateMemento::default();
+        memento.add_transaction_change(transaction_change(
+            30,
+            &completed_address,
+            &spends[0].to_address,
+        ));
+        memento.add_transaction_change(transaction_change(31, &cancelled_address, &vault.address));
+        update_vaults(&storage, name, enckey, &memento, &block_time).unwrap();
+
+        let spends = vault_service.get_spends(name, enckey).unwrap();
+        assert_eq!(
+            VaultSpendStatus::Completed {
+                transaction_id: [30; 32]
+            },
+            spends[0].status
+        );
+        assert_eq!(
+            VaultSpendStatus::Cancelled {
+                transaction_id: [31; 32]
+            },
+            spends[1].status
+        );
+        assert!(!spends[1].status.is_active());
+    }
+}
@@ -2,16 +2,21 @@ use indexmap::IndexSet;
 use parity_scale_codec::{Decode, Encode, Input, Output};
 
 use crate::hd_wallet::{ChainPath, HardwareKind};
-use crate::service::{load_wallet_state, HdKey, WalletState};
-use crate::types::WalletKind;
+use crate::service::{load_wallet_state, HdKey, SyncState, WalletState};
+use crate::types::{wallet_id, WalletAnnotations, WalletEntry, WalletKind, WalletMetadata};
 use chain_core::common::H256;
 use chain_core::init::address::RedeemAddress;
+use chain_core::init::network::get_network_id;
 use chain_core::state::account::StakedStateAddress;
 use chain_core::tx::data::address::ExtendedAddr;
+use client_common::seckey::derive_enckey;
+use client_common::storage::{decrypt_bytes, encrypt_bytes};
 use client_common::{
     Error, ErrorKind, MultiSigAddress, PrivateKey, PublicKey, Result, ResultExt, SecKey,
     SecureStorage, Storage,
 };
+use rand::rngs::OsRng;
+use rand::Rng;
 use secstr::SecUtf8;
 use serde::de::{self, Visitor};
 use serde::export::PhantomData;
@@ -19,9 +24,20 @@ use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::collections::BTreeMap;
 use std::fmt;
 use std::str;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 /// Key space of wallet
-const KEYSPACE: &str = "core_wallet";
+pub(super) const KEYSPACE: &str = "core_wallet";
+
+/// Magic bytes at the beginning of encrypted wallet backups
+const BACKUP_MAGIC: &[u8; 8] = b"CROWLTBK";
+/// Size of the random salt of the backup passphrase
+const BACKUP_SALT_SIZE: usize = 32;
+/// Current version of the encrypted wallet backup format
+///
+/// Newer versions may only add (optional) fields to the backup, so backups of newer versions
+/// are decoded as well (ignoring the unknown fields).
+pub const WALLET_BACKUP_VERSION: u16 = 1;
 
 fn get_public_keyspace(name: &str) -> String {
     format!("{}_{}_publickey", KEYSPACE, name)
@@ -55,14 +71,28 @@ pub fn get_multisig_keyspace(name: &str) -> String {
     format!("{}_{}_multisigaddress", KEYSPACE, name)
 }
 
+fn get_importedkey_keyspace(name: &str) -> String {
+    format!("{}_{}_importedkey", KEYSPACE, name)
+}
+
 fn get_info_keyspace(name: &str) -> String {
     format!("{}_{}_info", KEYSPACE, name)
 }
 
-fn get_wallet_keyspace() -> String {
+pub(super) fn get_wallet_keyspace() -> String {
     format!("{}_walletname", KEYSPACE)
 }
 
+/// wallet name -> encoded wallet metadata
+fn get_walletmetadata_keyspace() -> String {
+    format!("{}_walletmetadata", KEYSPACE)
+}
+
+/// wallet id -> encoded names of the wallets with this id
+fn get_walletid_keyspace() -> String {
+    format!("{}_walletid", KEYSPACE)
+}
+
 fn serde_to_str<T, S>(value: &T, serializer: S) -> std::result::Result<S::Ok, S::Error>
 where
     T: Encode,
@@ -101,6 +131,12 @@ where
     deserializer.deserialize_str(Helper(PhantomData))
 }
 
+fn backup_enckey(passphrase: &SecUtf8, salt: &[u8]) -> Result<SecKey> {
+    derive_enckey(passphrase, &hex::encode(salt)).err_kind(ErrorKind::InvalidInput, || {
+        "unable to derive encryption key from passphrase"
+    })
+}
+
 /// Wallet information to export and import
 #[derive(Deserialize, Serialize)]
 pub struct WalletInfo {
@@ -130,6 +166,42 @@ pub struct WalletInfo {
     /// staking keys
     #[serde(deserialize_with = "deserde_from_str", serialize_with = "serde_to_str")]
     pub staking_keys: Vec<PublicKey>,
+
+    /// public keys of externally generated (non-HD) private keys imported into the wallet
+    #[serde(
+        default,
+        deserialize_with = "deserde_from_str",
+        serialize_with = "serde_to_str"
+    )]
+    pub imported_keys: Vec<PublicKey>,
+
+    /// local annotations of the transactions and addresses
+    #[serde(default)]
+    pub annotations: WalletAnnotations,
+}
+
+/// Sync checkpoint of a wallet: the synced state (so the imported wallet does not need to be
+/// synced from genesis)
+#[derive(Debug, Encode, Decode)]
+pub struct SyncCheckpoint {
+    /// global sync state
+    pub sync_state: SyncState,
+    /// wallet state at the sync state
+    pub wallet_state: WalletState,
+}
+
+/// Content of an encrypted wallet backup
+#[derive(Deserialize, Serialize)]
+pub struct WalletBackup {
+    /// key material (HD seed or keys) and address metadata
+    pub wallet_info: WalletInfo,
+    /// sync checkpoint (if the wallet was synced)
+    #[serde(
+        default,
+        deserialize_with = "deserde_from_str",
+        serialize_with = "serde_to_str"
+    )]
+    pub sync_checkpoint: Option<SyncCheckpoint>,
 }
 
 use std::sync::{Arc, Mutex};
@@ -576,6 +648,60 @@ where
         WalletService { storage }
     }
 
+    /// Encrypts the wallet backup with the given passphrase
+    ///
+    /// Format: `BACKUP_MAGIC | version (u16, little endian) | salt | encrypted JSON backup`,
+    /// where the encryption key is derived from the passphrase and the salt, and the header
+    /// is authenticated as additional data.
+    pub fn export(&self, backup: &WalletBackup, passphrase: &SecUtf8) -> Result<Vec<u8>> {
+        let mut salt = [0u8; BACKUP_SALT_SIZE];
+        OsRng.fill(&mut salt);
+        let enckey = backup_enckey(passphrase, &salt)?;
+
+        let mut blob = BACKUP_MAGIC.to_vec();
+        blob.extend_from_slice(&WALLET_BACKUP_VERSION.to_le_bytes());
+        blob.extend_from_slice(&salt);
+
+        let payload = serde_json::to_vec(backup).chain(|| {
+            (
+                ErrorKind::SerializationError,
+                "Unable to serialize wallet backup",
+            )
+        })?;
+        let encrypted = encrypt_bytes(&blob, &enckey, &payload)?;
+        blob.extend_from_slice(&encrypted);
+        Ok(blob)
+    }
+
+    /// Decrypts the wallet backup exported by `export` with the given passphrase
+    pub fn import(&self, blob: &[u8], passphrase: &SecUtf8) -> Result<WalletBackup> {
+        let header_len = BACKUP_MAGIC.len() + 2 + BACKUP_SALT_SIZE;
+        if blob.len() < header_len || !blob.starts_with(BACKUP_MAGIC) {
+            return Err(Error::new(
+                ErrorKind::DeserializationError,
+                "Not an encrypted wallet backup",
+            ));
+        }
+        let (header, encrypted) = blob.split_at(header_len);
+        let mut version = [0u8; 2];
+        version.copy_from_slice(&header[BACKUP_MAGIC.len()..BACKUP_MAGIC.len() + 2]);
+        if u16::from_le_bytes(version) == 0 {
+            return Err(Error::new(
+                ErrorKind::DeserializationError,
+                "Invalid wallet backup version",
+            ));
+        }
+        let enckey = backup_enckey(passphrase, &header[BACKUP_MAGIC.len() + 2..])?;
+
+        let payload = decrypt_bytes(header, &enckey, encrypted)?;
+        serde_json::from_slice(&payload).chain(|| {
+            (
+                ErrorKind::DeserializationError,
+                "Unable to deserialize wallet backup",
+            )
+        })
+    }
+
     /// Get the wallet info from storage
     pub fn get_wallet_info(&self, name: &str, enckey: &SecKey) -> Result<Wallet> {
         load_wallet_info(&self.storage, name, enckey)?.err_kind(ErrorKind::InvalidInput, || {
@@ -636,7 +762,115 @@ where
     /// Store the wallet to storage
     // wallet -> storage
     pub fn set_wallet(&self, name: &str, enckey: &SecKey, wallet: Wallet) -> Result<()> {
-        self.save_wallet(name, enckey, &wallet)
+        self.save_wallet(name, enckey, &wallet)?;
+        self.register_wallet_metadata(name, &wallet)
+    }
+
+    /// Records the metadata of a new wallet and adds it to the names of its wallet id
+    /// (wallets restored from the same seed share the id, they are reported as duplicates)
+    fn register_wallet_metadata(&self, name: &str, wallet: &Wallet) -> Result<()> {
+        let metadata_keyspace = get_walletmetadata_keyspace();
+        if self.storage.contains_key(&metadata_keyspace, name)? {
+            return Ok(());
+        }
+        let created_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .chain(|| (ErrorKind::InternalError, "System time is before unix epoch"))?
+            .as_secs();
+        let metadata = WalletMetadata {
+            id: wallet_id(&wallet.view_key),
+            created_at,
+            wallet_kind: wallet.wallet_kind,
+            hardware_kind: wallet.hardware_kind,
+            network_id: get_network_id(),
+        };
+
+        let mut names = self.wallet_names_by_id(&metadata.id)?;
+        if !names.is_empty() {
+            log::warn!(
+                "wallet {} has the same id as the wallets: {}",
+                name,
+                names.join(", ")
+            );
+        }
+        names.push(name.to_owned());
+        self.storage
+            .set(get_walletid_keyspace(), &metadata.id, names.encode())?;
+        self.storage
+            .set(metadata_keyspace, name, metadata.encode())?;
+        Ok(())
+    }
+
+    /// Returns the metadata of the wallet (`None` if it was created before the metadata
+    /// was recorded)
+    pub fn wallet_metadata(&self, name: &str) -> Result<Option<WalletMetadata>> {
+        self.storage
+            .get(get_walletmetadata_keyspace(), name)?
+            .map(|raw_value| {
+                WalletMetadata::decode(&mut raw_value.as_slice()).chain(|| {
+                    (
+                        ErrorKind::DeserializationError,
+                        format!("Unable to deserialize metadata of wallet {}", name),
+                    )
+                })
+            })
+            .transpose()
+    }
+
+    /// Returns the names of the wallets with given id
+    pub fn wallet_names_by_id(&self, id: &str) -> Result<Vec<String>> {
+        self.storage
+            .get(get_walletid_keyspace(), id)?
+            .map(|raw_value| {
+                Vec::<String>::decode(&mut raw_value.as_slice()).chain(|| {
+                    (
+                        ErrorKind::DeserializationError,
+                        format!("Unable to deserialize names of wallet id {}", id),
+                    )
+                })
+            })
+            .transpose()
+            .map(Option::unwrap_or_default)
+    }
+
+    /// Retrieves all the stored wallets with their metadata
+    pub fn entries(&self) -> Result<Vec<WalletEntry>> {
+        self.names()?
+            .into_iter()
+            .map(|name| {
+                let metadata = self.wallet_metadata(&name)?;
+                let duplicates = match &metadata {
+                    Some(metadata) => self
+                        .wallet_names_by_id(&metadata.id)?
+                        .into_iter()
+                        .filter(|other| *other != name)
+                        .collect(),
+                    None => vec![],
+                };
+                Ok(WalletEntry {
+                    name,
+                    metadata,
+                    duplicates,
+                })
+            })
+            .collect()
+    }
+
+    fn unregister_wallet_metadata(&self, name: &str) -> Result<()> {
+        let metadata = match self.wallet_metadata(name)? {
+            Some(metadata) => metadata,
+            None => return Ok(()),
+        };
+        let mut names = self.wallet_names_by_id(&metadata.id)?;
+        names.retain(|other| other != name);
+        if names.is_empty() {
+            self.storage.delete(get_walletid_keyspace(), &metadata.id)?;
+        } else {
+            self.storage
+                .set(get_walletid_keyspace(), &metadata.id, names.encode())?;
+        }
+        self.storage.delete(get_walletmetadata_keyspace(), name)?;
+        Ok(())
     }
 
     /// Finds staking key corresponding to given redeem address
@@ -916,6 +1150,31 @@ where
         Ok(())
     }
 
+    /// Marks the public key as belonging to an imported (externally generated) private key
+    // TODO: change api not to use _enckey
+    pub fn add_imported_key(
+        &self,
+        name: &str,
+        _enckey: &SecKey,
+        public_key: &PublicKey,
+    ) -> Result<()> {
+        let importedkey_keyspace = get_importedkey_keyspace(name);
+        self.storage
+            .set(importedkey_keyspace, public_key.serialize(), vec![])?;
+        Ok(())
+    }
+
+    /// Returns public keys of the imported private keys of given wallet
+    // TODO: change api not to use _enckey
+    pub fn imported_keys(&self, name: &str, _enckey: &SecKey) -> Result<Vec<PublicKey>> {
+        let importedkey_keyspace = get_importedkey_keyspace(name);
+        self.storage
+            .keys(importedkey_keyspace)?
+            .into_iter()
+            .map(|key| PublicKey::deserialize_from(&key))
+            .collect()
+    }
+
     /// Adds a (public_key, hd_path) pair to given wallet
     pub fn add_key_path(
         &self,
@@ -1075,6 +1334,8 @@ where
             self.delete_wallet_keyspace(&name_found)?;
         }
         self.storage.clear(wallet_keyspace)?;
+        self.storage.clear(get_walletmetadata_keyspace())?;
+        self.storage.clear(get_walletid_keyspace())?;
         self.storage.clear(KEYSPACE)?;
 
         Ok(())
@@ -1092,8 +1353,10 @@ where
         let roothash_keyspace = get_roothash_keyspace(name);
         let roothashset_keyspace = get_roothashset_keyspace(name);
         let multisigaddress_keyspace = get_multisig_keyspace(name);
+        let importedkey_keyspace = get_importedkey_keyspace(name);
         let wallet_keyspace = get_wallet_keyspace();
         self.storage.delete(wallet_keyspace, name)?;
+        self.unregister_wallet_metadata(name)?;
         self.storage.clear(info_keyspace)?;
         self.storage.clear(roothash_keyspace)?;
         self.storage.clear(roothashset_keyspace)?;
@@ -1102,6 +1365,7 @@ where
         self.storage.clear(public_keyspace)?;
         self.storage.clear(private_keyspace)?;
         self.storage.clear(multisigaddress_keyspace)?;
+        self.storage.clear(importedkey_keyspace)?;
         Ok(())
     }
     /// Delete the key
@@ -1243,8 +1507,73 @@ mod test {
             hdkey: Some(HdKey::default()),
             multisig_address_pair,
             staking_keys: vec![],
+            imported_keys: vec![],
+            annotations: Default::default(),
         };
         let s = serde_json::to_string(&info);
         assert!(s.is_ok());
     }
+
+    #[test]
+    fn check_wallet_metadata() {
+        let wallet_service = WalletService::new(MemoryStorage::default());
+        let enckey = derive_enckey(&SecUtf8::from("passphrase"), "name").unwrap();
+        let view_key = PublicKey::from(&PrivateKey::new().unwrap());
+        let id = wallet_id(&view_key);
+
+        for name in ["first", "second"].iter() {
+            wallet_service
+                .create(
+                    name,
+                    &enckey,
+                    view_key.clone(),
+                    WalletKind::HD,
+                    HardwareKind::LocalOnly,
+                )
+                .unwrap();
+        }
+        let other_view_key = PublicKey::from(&PrivateKey::new().unwrap());
+        wallet_service
+            .create(
+                "other",
+                &enckey,
+                other_view_key.clone(),
+                WalletKind::Basic,
+                HardwareKind::LocalOnly,
+            )
+            .unwrap();
+
+        let metadata = wallet_service.wallet_metadata("first").unwrap().unwrap();
+        assert_eq!(id, metadata.id);
+        assert_eq!(WalletKind::HD, metadata.wallet_kind);
+        assert_eq!(
+            vec!["first".to_owned(), "second".to_owned()],
+            wallet_service.wallet_names_by_id(&id).unwrap()
+        );
+
+        let entries = wallet_service.entries().unwrap();
+        assert_eq!(3, entries.len());
+        for entry in entries.iter() {
+            let expected_duplicates: &[&str] = match entry.name.as_str() {
+                "first" => &["second"],
+                "second" => &["first"],
+                _ => &[],
+            };
+            assert_eq!(expected_duplicates, entry.duplicates.as_slice());
+        }
+
+        wallet_service.delete("first", &enckey).unwrap();
+        assert!(wallet_service.wallet_metadata("first").unwrap().is_none());
+        assert_eq!(
+            vec!["second".to_owned()],
+            wallet_service.wallet_names_by_id(&id).unwrap()
+        );
+
+        wallet_service.clear().unwrap();
+        assert!(wallet_service.wallet_names_by_id(&id).unwrap().is_empty());
+        assert!(wallet_service
+            .wallet_names_by_id(&wallet_id(&other_view_key))
+            .unwrap()
+            .is_empty());
+    }
 }
@@ -1,16 +1,68 @@
+use indexmap::IndexSet;
 use parity_scale_codec::{Decode, Encode};
-use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
 
 use chain_core::{
-    init::coin::{sum_coins, CoinError},
-    tx::data::{input::TxoPointer, output::TxOut, TxId},
+    common::Timespec,
+    init::coin::{sum_coins, Coin, CoinError},
+    tx::data::{address::ExtendedAddr, input::TxoPointer, output::TxOut, TxId},
 };
 use client_common::{Error, ErrorKind, Result, ResultExt, SecKey, SecureStorage, Storage};
 
-use crate::types::{TransactionChange, TransactionPending, WalletBalance};
+use crate::types::{
+    Spendability, SpendabilityReport, TransactionChange, TransactionFilter, TransactionHistoryPage,
+    TransactionIndexEntry, TransactionPending, UnconfirmedAmount, UtxoSpendability, WalletBalance,
+    WalletEvent,
+};
 
 /// key space of wallet state
-const KEYSPACE: &str = "core_wallet_state";
+pub(super) const KEYSPACE: &str = "core_wallet_state";
+/// key space of transaction history index
+pub(super) const INDEX_KEYSPACE: &str = "core_wallet_history_index";
+/// key space of the outputs frozen by the user
+const FROZEN_KEYSPACE: &str = "core_wallet_frozen_outputs";
+/// Number of transactions indexed by a reindexing worker at once
+const REINDEX_BATCH_SIZE: usize = 500;
+
+/// Progress of wallet reindexing
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReindexProgress {
+    /// Reindexing started
+    Init {
+        /// Name of wallet
+        wallet_name: String,
+        /// Number of transactions in the local transaction history
+        transactions: u64,
+    },
+    /// Transactions indexed so far
+    Update {
+        /// Name of wallet
+        wallet_name: String,
+        /// Number of indexed transactions
+        indexed_transactions: u64,
+    },
+}
+
+/// Result of wallet reindexing
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReindexReport {
+    /// Name of wallet
+    pub wallet_name: String,
+    /// Number of reindexed transactions
+    pub transactions: u64,
+    /// Number of unspent transactions after reindexing
+    pub unspent_transactions: u64,
+    /// Number of unspent transactions which were missing before reindexing
+    pub added_unspent_transactions: u64,
+    /// Number of stale unspent transactions which were removed
+    pub removed_unspent_transactions: u64,
+    /// Balance after reindexing
+    pub balance: WalletBalance,
+}
 
 /// Maintains mapping `wallet-name -> wallet-state`
 #[derive(Debug, Default, Clone)]
@@ -34,7 +86,9 @@ where
     /// Clears all storage
     #[inline]
     pub fn clear(&self) -> Result<()> {
-        self.storage.clear(KEYSPACE)
+        self.storage.clear(KEYSPACE)?;
+        self.storage.clear(INDEX_KEYSPACE)?;
+        self.storage.clear(FROZEN_KEYSPACE)
     }
 
     /// Returns `true` if given transaction inputs are present in the list of unspent transactions, `false` otherwise
@@ -68,6 +122,65 @@ where
         }
     }
 
+    /// Freezes unspent outputs of the wallet, so they're not selected as inputs of the
+    /// transactions built by the wallet
+    pub fn freeze_outputs(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        outputs: &[TxoPointer],
+    ) -> Result<()> {
+        let unspent_transactions = self.get_unspent_transactions(name, enckey, true)?;
+        if let Some(output) = outputs
+            .iter()
+            .find(|output| !unspent_transactions.contains_key(output))
+        {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Output {}:{} is not an unspent output of the wallet",
+                    hex::encode(&output.id),
+                    output.index
+                ),
+            ));
+        }
+        let mut frozen = self.get_frozen_outputs(name, enckey)?;
+        frozen.extend(outputs.iter().cloned());
+        self.storage
+            .save_secure(FROZEN_KEYSPACE, name, enckey, &frozen)
+    }
+
+    /// Unfreezes outputs of the wallet
+    pub fn unfreeze_outputs(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        outputs: &[TxoPointer],
+    ) -> Result<()> {
+        let mut frozen = self.get_frozen_outputs(name, enckey)?;
+        for output in outputs {
+            frozen.remove(output);
+        }
+        self.storage
+            .save_secure(FROZEN_KEYSPACE, name, enckey, &frozen)
+    }
+
+    /// Returns the frozen outputs of the wallet which are still unspent
+    pub fn get_frozen_outputs(&self, name: &str, enckey: &SecKey) -> Result<BTreeSet<TxoPointer>> {
+        let frozen: BTreeSet<TxoPointer> = self
+            .storage
+            .load_secure(FROZEN_KEYSPACE, name, enckey)?
+            .unwrap_or_default();
+        if frozen.is_empty() {
+            return Ok(frozen);
+        }
+        let unspent_transactions = self.get_unspent_transactions(name, enckey, true)?;
+        Ok(frozen
+            .into_iter()
+            .filter(|output| unspent_transactions.contains_key(output))
+            .collect())
+    }
+
     /// Returns `true` or `false` depending if input is unspent or not. `true` if the input is unspent, `false`
     /// otherwise
     pub fn are_inputs_unspent(
@@ -106,6 +219,79 @@ where
         })
     }
 
+    /// Returns a page of the transactions matching the filter, using the transaction history
+    /// index of the wallet
+    ///
+    /// The page starts after the transaction at `cursor` (or at the first/last transaction if it
+    /// is `None`) and contains at most `limit` transactions.
+    pub fn query_transaction_history(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        filter: &TransactionFilter,
+        cursor: Option<u64>,
+        limit: usize,
+        reversed: bool,
+    ) -> Result<TransactionHistoryPage> {
+        let mut state = self.get_wallet_state(name, enckey)?;
+        let index = update_history_index(&self.storage, name, enckey, &state)?;
+
+        let len = index.entries.len();
+        let positions: Box<dyn Iterator<Item = usize>> = match (cursor, reversed) {
+            (None, false) => Box::new(0..len),
+            (Some(cursor), false) => Box::new((cursor as usize).saturating_add(1)..len),
+            (None, true) => Box::new((0..len).rev()),
+            (Some(cursor), true) => Box::new((0..len.min(cursor as usize)).rev()),
+        };
+        let mut matched = positions.filter(|position| filter.matches(&index.entries[*position]));
+
+        let mut transactions = Vec::new();
+        let mut next_cursor = cursor;
+        for position in matched.by_ref().take(limit) {
+            let transaction_id = &index.entries[position].transaction_id;
+            if let Some(change) = state.transaction_history.remove(transaction_id) {
+                transactions.push(change);
+            }
+            next_cursor = Some(position as u64);
+        }
+        if matched.next().is_none() {
+            next_cursor = None;
+        }
+
+        Ok(TransactionHistoryPage {
+            transactions,
+            next_cursor,
+        })
+    }
+
+    /// Returns at most `limit` events of the wallet event stream, starting at the event with
+    /// sequence number `from_sequence` (events of transactions dropped from the history are
+    /// skipped, their sequence numbers are not reused)
+    pub fn replay_events(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        from_sequence: u64,
+        limit: usize,
+    ) -> Result<Vec<WalletEvent>> {
+        let state = self.get_wallet_state(name, enckey)?;
+        let mut history = state.transaction_history;
+        let start = (from_sequence as usize).min(state.transaction_log.len());
+        Ok(state.transaction_log[start..]
+            .iter()
+            .zip(start as u64..)
+            .filter_map(|(transaction_id, sequence)| {
+                history
+                    .remove(transaction_id)
+                    .map(|transaction| WalletEvent {
+                        sequence,
+                        transaction,
+                    })
+            })
+            .take(limit)
+            .collect())
+    }
+
     /// Returns currently stored transaction change for given wallet and transaction id
     #[inline]
     pub fn get_transaction_change(
@@ -138,6 +324,23 @@ where
         Ok(balance)
     }
 
+    /// Returns the spendability of the unspent transactions of given wallet (see
+    /// `WalletState::get_spendability_report`)
+    pub fn get_spendability_report<F>(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        block_time: Timespec,
+        dust_threshold: Coin,
+        required_signers: F,
+    ) -> Result<SpendabilityReport>
+    where
+        F: Fn(&ExtendedAddr) -> Result<usize>,
+    {
+        self.get_wallet_state(name, enckey)?
+            .get_spendability_report(block_time, dust_threshold, required_signers)
+    }
+
     fn modify_state<F>(&self, name: &str, enckey: &SecKey, f: F) -> Result<()>
     where
         F: Fn(&mut WalletState) -> Result<()>,
@@ -166,15 +369,186 @@ where
     pub fn delete_wallet_state(&self, name: &str, enckey: &SecKey) -> Result<()> {
         // Check if the enckey is correct
         let _ = self.get_wallet_state(name, enckey)?;
+        self.storage.delete(INDEX_KEYSPACE, name)?;
+        self.storage.delete(FROZEN_KEYSPACE, name)?;
         self.storage.delete(KEYSPACE, name).map(|_| ())
     }
 
+    /// Rebuilds the unspent transactions and the transaction history index of the wallet from
+    /// the locally stored transaction history (without syncing again)
+    ///
+    /// Transactions are indexed in batches by `threads` worker threads; `transfer_addresses` are
+    /// the addresses whose outputs belong to the wallet. Pending transactions are kept as they are.
+    pub fn reindex(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        transfer_addresses: &IndexSet<ExtendedAddr>,
+        threads: usize,
+        progress: &mut dyn FnMut(ReindexProgress),
+    ) -> Result<ReindexReport> {
+        let state = self.get_wallet_state(name, enckey)?;
+        let transactions = state.transaction_log.len() as u64;
+        progress(ReindexProgress::Init {
+            wallet_name: name.to_owned(),
+            transactions,
+        });
+
+        let batches = state
+            .transaction_log
+            .chunks(REINDEX_BATCH_SIZE)
+            .map(|chunk| {
+                chunk
+                    .iter()
+                    .filter_map(|transaction_id| state.transaction_history.get(transaction_id))
+                    .cloned()
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let indexed = index_batches(batches, transfer_addresses.clone(), threads, &mut |count| {
+            progress(ReindexProgress::Update {
+                wallet_name: name.to_owned(),
+                indexed_transactions: count,
+            })
+        })?;
+
+        let mut unspent_transactions = BTreeMap::new();
+        let mut entries = Vec::with_capacity(state.transaction_log.len());
+        let mut spent = Vec::new();
+        for batch in indexed {
+            unspent_transactions.extend(batch.created);
+            spent.extend(batch.spent);
+            entries.extend(batch.entries);
+        }
+        for input in spent.iter() {
+            unspent_transactions.remove(input);
+        }
+
+        let added_unspent_transactions = unspent_transactions
+            .keys()
+            .filter(|input| !state.unspent_transactions.contains_key(input))
+            .count() as u64;
+        let removed_unspent_transactions = state
+            .unspent_transactions
+            .keys()
+            .filter(|input| !unspent_transactions.contains_key(input))
+            .count() as u64;
+
+        self.storage.delete(INDEX_KEYSPACE, name)?;
+        self.modify_state(name, enckey, |current| {
+            if current.transaction_log != state.transaction_log {
+                return Err(Error::new(
+                    ErrorKind::IllegalInput,
+                    "Wallet state was modified while reindexing, please try again",
+                ));
+            }
+            current.unspent_transactions = unspent_transactions.clone();
+            Ok(())
+        })?;
+        let index = HistoryIndex {
+            log_len: transactions,
+            last_transaction_id: state.transaction_log.last().copied(),
+            entries,
+        };
+        self.storage
+            .save_secure(INDEX_KEYSPACE, name, enckey, &index)?;
+
+        Ok(ReindexReport {
+            wallet_name: name.to_owned(),
+            transactions,
+            unspent_transactions: unspent_transactions.len() as u64,
+            added_unspent_transactions,
+            removed_unspent_transactions,
+            balance: self.get_balance(name, enckey)?,
+        })
+    }
+
     #[inline]
     fn get_wallet_state(&self, name: &str, enckey: &SecKey) -> Result<WalletState> {
         Ok(load_wallet_state(&self.storage, name, enckey)?.unwrap_or_default())
     }
 }
 
+/// Derived data of a batch of transactions
+#[derive(Debug, Default)]
+struct BatchIndex {
+    /// Outputs of the transactions which belong to the wallet
+    created: Vec<(TxoPointer, TxOut)>,
+    /// Inputs spent by the transactions
+    spent: Vec<TxoPointer>,
+    /// History index entries of the transactions
+    entries: Vec<TransactionIndexEntry>,
+}
+
+fn index_batch(changes: &[TransactionChange], addresses: &IndexSet<ExtendedAddr>) -> BatchIndex {
+    let mut batch = BatchIndex::default();
+    for change in changes {
+        batch
+            .spent
+            .extend(change.inputs.iter().map(|input| input.pointer.clone()));
+        for (i, output) in change.outputs.iter().enumerate() {
+            if addresses.contains(&output.address) {
+                batch
+                    .created
+                    .push((TxoPointer::new(change.transaction_id, i), output.clone()));
+            }
+        }
+        batch.entries.push(TransactionIndexEntry::from(change));
+    }
+    batch
+}
+
+/// Indexes the batches of transactions in worker threads, returns the results in the order of
+/// the batches; `progress` is called with the number of indexed transactions
+fn index_batches(
+    batches: Vec<Vec<TransactionChange>>,
+    addresses: IndexSet<ExtendedAddr>,
+    threads: usize,
+    progress: &mut dyn FnMut(u64),
+) -> Result<Vec<BatchIndex>> {
+    let batch_count = batches.len();
+    let batches = Arc::new(batches);
+    let addresses = Arc::new(addresses);
+    let next_batch = Arc::new(AtomicUsize::new(0));
+    let (sender, receiver) = mpsc::channel();
+
+    let workers = (0..threads.max(1).min(batch_count))
+        .map(|_| {
+            let batches = batches.clone();
+            let addresses = addresses.clone();
+            let next_batch = next_batch.clone();
+            let sender = sender.clone();
+            thread::spawn(move || loop {
+                let position = next_batch.fetch_add(1, Ordering::SeqCst);
+                if position >= batches.len() {
+                    break;
+                }
+                let batch = index_batch(&batches[position], &addresses);
+                if sender.send((position, batch)).is_err() {
+                    break;
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    drop(sender);
+
+    let mut results = (0..batch_count).map(|_| None).collect::<Vec<_>>();
+    let mut indexed = 0;
+    for (position, batch) in receiver {
+        indexed += batch.entries.len() as u64;
+        results[position] = Some(batch);
+        progress(indexed);
+    }
+    for worker in workers {
+        let _ = worker.join();
+    }
+
+    results
+        .into_iter()
+        .map(|batch| batch.chain(|| (ErrorKind::InternalError, "Reindexing worker thread failed")))
+        .collect()
+}
+
 fn parse_wallet_state<T: AsRef<[u8]>>(
     name: &str,
     bytes_optional: Option<T>,
@@ -211,7 +585,8 @@ pub fn save_wallet_state<S: SecureStorage>(
     enckey: &SecKey,
     state: &WalletState,
 ) -> Result<()> {
-    storage.save_secure(KEYSPACE, name, enckey, state)
+    storage.save_secure(KEYSPACE, name, enckey, state)?;
+    update_history_index(storage, name, enckey, state).map(|_| ())
 }
 
 /// Modify wallet state atomically, and returns the new one.
@@ -231,15 +606,68 @@ where
         Ok(Some(wallet_state.encode()))
     })?;
     // FIXME need to modify the storage trait to save this extra loading.
-    Ok(load_wallet_state(storage, name, enckey)?.unwrap())
+    let state = load_wallet_state(storage, name, enckey)?.unwrap();
+    update_history_index(storage, name, enckey, &state)?;
+    Ok(state)
 }
 
 /// Delete wallet state from storage
 pub fn delete_wallet_state<S: Storage>(storage: &S, name: &str) -> Result<()> {
     storage.delete(KEYSPACE, name)?;
+    storage.delete(INDEX_KEYSPACE, name)?;
+    storage.delete(FROZEN_KEYSPACE, name)?;
     Ok(())
 }
 
+/// Transaction history index of a wallet: the balance changes of the transactions in the order
+/// of the transaction log, so that history queries can be filtered without going through the
+/// full transaction details
+#[derive(Debug, Default, Encode, Decode)]
+struct HistoryIndex {
+    /// Number of transaction log entries which are indexed
+    log_len: u64,
+    /// Last indexed transaction log entry (to detect a replaced wallet state)
+    last_transaction_id: Option<TxId>,
+    /// Index entries
+    entries: Vec<TransactionIndexEntry>,
+}
+
+/// Brings the transaction history index of the wallet up to date with the transaction log of
+/// the wallet state (only the new transactions are indexed, unless the wallet state was
+/// replaced, e.g. when syncing again from genesis)
+fn update_history_index<S: SecureStorage>(
+    storage: &S,
+    name: &str,
+    enckey: &SecKey,
+    state: &WalletState,
+) -> Result<HistoryIndex> {
+    let mut index: HistoryIndex = storage
+        .load_secure(INDEX_KEYSPACE, name, enckey)?
+        .unwrap_or_default();
+
+    let log = &state.transaction_log;
+    let indexed = index.log_len as usize;
+    let up_to_date = indexed <= log.len()
+        && index.last_transaction_id == indexed.checked_sub(1).map(|last| log[last]);
+    if !up_to_date {
+        index = HistoryIndex::default();
+    }
+    if index.log_len as usize == log.len() {
+        return Ok(index);
+    }
+
+    for transaction_id in &log[index.log_len as usize..] {
+        if let Some(change) = state.transaction_history.get(transaction_id) {
+            index.entries.push(TransactionIndexEntry::from(change));
+        }
+    }
+    index.log_len = log.len() as u64;
+    index.last_transaction_id = log.last().copied();
+
+    storage.save_secure(INDEX_KEYSPACE, name, enckey, &index)?;
+    Ok(index)
+}
+
 /// Wallet state
 #[derive(Debug, Encode, Decode)]
 pub struct WalletState {
@@ -332,6 +760,71 @@ impl WalletState {
         };
         Ok(wallet_balances)
     }
+    /// Returns the spendability of every unspent transaction and the amounts of the pending
+    /// transactions, where
+    /// - `block_time` is the time of the latest block (timelocks are checked against it)
+    /// - outputs with a value below `dust_threshold` are reported as dust
+    /// - `required_signers` returns the number of signers required to spend from an address
+    pub fn get_spendability_report<F>(
+        &self,
+        block_time: Timespec,
+        dust_threshold: Coin,
+        required_signers: F,
+    ) -> Result<SpendabilityReport>
+    where
+        F: Fn(&ExtendedAddr) -> Result<usize>,
+    {
+        let reserved: BTreeMap<&TxoPointer, &TxId> = self
+            .pending_transactions
+            .iter()
+            .flat_map(|(transaction_id, pending)| {
+                pending
+                    .used_inputs
+                    .iter()
+                    .map(move |input| (input, transaction_id))
+            })
+            .collect();
+
+        let mut outputs = Vec::with_capacity(self.unspent_transactions.len());
+        for (pointer, output) in self.unspent_transactions.iter() {
+            let spendability = match (reserved.get(pointer), output.valid_from) {
+                (Some(transaction_id), _) => Spendability::Reserved {
+                    transaction_id: **transaction_id,
+                },
+                (None, Some(until)) if until > block_time => Spendability::Timelocked { until },
+                _ => match required_signers(&output.address)? {
+                    required_signers if required_signers > 1 => {
+                        Spendability::NeedsCoSigners { required_signers }
+                    }
+                    _ if output.value < dust_threshold => Spendability::BelowDust {
+                        threshold: dust_threshold,
+                    },
+                    _ => Spendability::Spendable,
+                },
+            };
+            outputs.push(UtxoSpendability {
+                pointer: pointer.clone(),
+                output: output.clone(),
+                spendability,
+            });
+        }
+
+        let unconfirmed = self
+            .pending_transactions
+            .iter()
+            .filter(|(_, pending)| pending.return_amount != Coin::zero())
+            .map(|(transaction_id, pending)| UnconfirmedAmount {
+                transaction_id: *transaction_id,
+                amount: pending.return_amount,
+            })
+            .collect();
+
+        Ok(SpendabilityReport {
+            outputs,
+            unconfirmed,
+        })
+    }
+
     /// Applies memento to wallet state
     pub fn apply_memento(&mut self, memento: &WalletStateMemento) -> Result<()> {
         for operation in memento.0.iter() {
@@ -432,6 +925,16 @@ impl WalletStateMemento {
         ))
     }
 
+    /// Returns the transaction changes added to memento
+    pub fn transaction_changes(&self) -> impl Iterator<Item = &TransactionChange> {
+        self.0.iter().filter_map(|operation| match operation {
+            MementoOperation::AddTransactionChange(_, transaction_change) => {
+                Some(transaction_change)
+            }
+            _ => None,
+        })
+    }
+
     /// Adds transaction pending info to memento
     #[inline]
     pub fn add_pending_transaction(&mut self, tx_id: TxId, tx_pending: TransactionPending) {
@@ -472,9 +975,201 @@ mod tests {
     use client_common::tendermint::types::Time;
     use client_common::{seckey::derive_enckey, storage::MemoryStorage};
 
-    use crate::types::{BalanceChange, TransactionType};
+    use crate::types::{BalanceChange, TransactionInput, TransactionType};
     use chain_core::init::coin::Coin;
 
+    #[test]
+    fn check_query_transaction_history() {
+        let storage = MemoryStorage::default();
+        let wallet_state_service = WalletStateService::new(storage);
+
+        let name = "name";
+        let enckey = &derive_enckey(&SecUtf8::from("passphrase"), name).unwrap();
+
+        let mut memento = WalletStateMemento::default();
+        for i in 0..10u8 {
+            let balance_change = if i % 2 == 0 {
+                BalanceChange::Incoming {
+                    value: Coin::new(u64::from(i) * 10).unwrap(),
+                }
+            } else {
+                BalanceChange::Outgoing {
+                    value: Coin::new(u64::from(i) * 10).unwrap(),
+                }
+            };
+            memento.add_transaction_change(TransactionChange {
+                transaction_id: [i; 32],
+                inputs: Vec::new(),
+                outputs: Vec::new(),
+                fee_paid: Fee::new(Coin::zero()),
+                balance_change,
+                transaction_type: if i < 5 {
+                    TransactionType::Transfer
+                } else {
+                    TransactionType::Deposit
+                },
+                block_height: u64::from(i),
+                block_time: Time::from_str("2019-04-09T09:38:41.735577Z").unwrap(),
+                annotation: None,
+            });
+        }
+        wallet_state_service
+            .apply_memento(name, enckey, &memento)
+            .unwrap();
+
+        let filter = TransactionFilter {
+            direction: Some(crate::types::TransactionDirection::Incoming),
+            ..Default::default()
+        };
+        let page = wallet_state_service
+            .query_transaction_history(name, enckey, &filter, None, 3, false)
+            .unwrap();
+        let ids = |page: &TransactionHistoryPage| {
+            page.transactions
+                .iter()
+                .map(|change| change.transaction_id[0])
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(vec![0, 2, 4], ids(&page));
+        assert_eq!(Some(4), page.next_cursor);
+
+        let page = wallet_state_service
+            .query_transaction_history(name, enckey, &filter, page.next_cursor, 3, false)
+            .unwrap();
+        assert_eq!(vec![6, 8], ids(&page));
+        assert_eq!(None, page.next_cursor);
+
+        let filter = TransactionFilter {
+            kind: Some(crate::types::TransactionKind::Staking),
+            min_block_height: Some(6),
+            max_amount: Some(Coin::new(80).unwrap()),
+            ..Default::default()
+        };
+        let page = wallet_state_service
+            .query_transaction_history(name, enckey, &filter, None, 10, true)
+            .unwrap();
+        assert_eq!(vec![8, 7, 6], ids(&page));
+        assert_eq!(None, page.next_cursor);
+
+        // new transactions are appended to the index
+        let mut memento = WalletStateMemento::default();
+        let mut change = page.transactions[0].clone();
+        change.transaction_id = [10; 32];
+        memento.add_transaction_change(change);
+        wallet_state_service
+            .apply_memento(name, enckey, &memento)
+            .unwrap();
+        let page = wallet_state_service
+            .query_transaction_history(name, enckey, &filter, None, 1, true)
+            .unwrap();
+        assert_eq!(vec![10], ids(&page));
+        assert_eq!(Some(10), page.next_cursor);
+    }
+
+    #[test]
+    fn check_reindex() {
+        let storage = MemoryStorage::default();
+        let wallet_state_service = WalletStateService::new(storage);
+
+        let name = "name";
+        let enckey = &derive_enckey(&SecUtf8::from("passphrase"), name).unwrap();
+
+        let own = ExtendedAddr::OrTree([1; 32]);
+        let foreign = ExtendedAddr::OrTree([2; 32]);
+        let change = |id: u8, inputs: Vec<TxoPointer>, outputs: Vec<TxOut>| TransactionChange {
+            transaction_id: [id; 32],
+            inputs: inputs
+                .into_iter()
+                .map(|pointer| TransactionInput {
+                    pointer,
+                    output: None,
+                })
+                .collect(),
+            outputs,
+            fee_paid: Fee::new(Coin::zero()),
+            balance_change: BalanceChange::NoChange,
+            transaction_type: TransactionType::Transfer,
+            block_height: u64::from(id),
+            block_time: Time::from_str("2019-04-09T09:38:41.735577Z").unwrap(),
+            annotation: None,
+        };
+
+        let mut memento = WalletStateMemento::default();
+        memento.add_transaction_change(change(
+            1,
+            vec![],
+            vec![
+                TxOut::new(own.clone(), Coin::new(100).unwrap()),
+                TxOut::new(foreign.clone(), Coin::new(50).unwrap()),
+            ],
+        ));
+        memento.add_transaction_change(change(
+            2,
+            vec![TxoPointer::new([1; 32], 0)],
+            vec![TxOut::new(own.clone(), Coin::new(70).unwrap())],
+        ));
+        // stale unspent output (already spent by the second transaction)
+        memento.add_unspent_transaction(
+            TxoPointer::new([1; 32], 0),
+            TxOut::new(own.clone(), Coin::new(100).unwrap()),
+        );
+        wallet_state_service
+            .apply_memento(name, enckey, &memento)
+            .unwrap();
+
+        let addresses = vec![own.clone()].into_iter().collect();
+        let mut updates = Vec::new();
+        let report = wallet_state_service
+            .reindex(name, enckey, &addresses, 4, &mut |progress| {
+                updates.push(progress)
+            })
+            .unwrap();
+
+        assert_eq!(2, report.transactions);
+        assert_eq!(1, report.unspent_transactions);
+        assert_eq!(1, report.added_unspent_transactions);
+        assert_eq!(1, report.removed_unspent_transactions);
+        assert_eq!(Coin::new(70).unwrap(), report.balance.total);
+        assert_eq!(
+            Some(&ReindexProgress::Update {
+                wallet_name: name.to_owned(),
+                indexed_transactions: 2,
+            }),
+            updates.last()
+        );
+
+        let unspent = wallet_state_service
+            .get_unspent_transactions(name, enckey, false)
+            .unwrap();
+        assert_eq!(
+            vec![TxoPointer::new([2; 32], 0)],
+            unspent.keys().cloned().collect::<Vec<_>>()
+        );
+        let page = wallet_state_service
+            .query_transaction_history(name, enckey, &TransactionFilter::default(), None, 10, false)
+            .unwrap();
+        assert_eq!(2, page.transactions.len());
+
+        // event sequence numbers are kept by the reindex
+        let events = wallet_state_service
+            .replay_events(name, enckey, 1, 10)
+            .unwrap();
+        assert_eq!(1, events.len());
+        assert_eq!(1, events[0].sequence);
+        assert_eq!([2; 32], events[0].transaction.transaction_id);
+        assert_eq!(
+            1,
+            wallet_state_service
+                .replay_events(name, enckey, 0, 1)
+                .unwrap()
+                .len()
+        );
+        assert!(wallet_state_service
+            .replay_events(name, enckey, 5, 10)
+            .unwrap()
+            .is_empty());
+    }
+
     #[test]
     fn check_wallet_state_service_flow() {
         let storage = MemoryStorage::default();
@@ -584,6 +1279,7 @@ mod tests {
             block_height: 0,
             fee_paid: Fee::new(Coin::new(10).unwrap()),
             block_time: Time::from_str("2019-04-09T09:38:41.735577Z").unwrap(),
+            annotation: None,
         });
 
         assert!(wallet_state_service
@@ -623,6 +1319,7 @@ mod tests {
             block_height: 0,
             fee_paid: Fee::new(Coin::new(10).unwrap()),
             block_time: Time::from_str("2019-04-09T09:38:41.735577Z").unwrap(),
+            annotation: None,
         });
 
         assert!(wallet_state_service
@@ -761,4 +1458,205 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn check_spendability_report() {
+        let address = |byte| ExtendedAddr::OrTree([byte; 32]);
+        let multi_sig_address = address(2);
+        let mut wallet_state = WalletState::default();
+        let outputs = vec![
+            TxOut::new(address(1), Coin::new(100).unwrap()),
+            TxOut::new(address(1), Coin::new(50).unwrap()),
+            TxOut::new_with_timelock(address(1), Coin::new(30).unwrap(), 2000),
+            TxOut::new(multi_sig_address.clone(), Coin::new(20).unwrap()),
+            TxOut::new(address(1), Coin::new(1).unwrap()),
+        ];
+        for (i, output) in outputs.into_iter().enumerate() {
+            wallet_state
+                .unspent_transactions
+                .insert(TxoPointer::new([0; 32], i), output);
+        }
+        wallet_state.pending_transactions.insert(
+            [1; 32],
+            TransactionPending {
+                used_inputs: vec![TxoPointer::new([0; 32], 1)],
+                block_height: 1,
+                return_amount: Coin::new(40).unwrap(),
+            },
+        );
+
+        let report = wallet_state
+            .get_spendability_report(1000, Coin::new(10).unwrap(), |address| {
+                Ok(if *address == multi_sig_address { 2 } else { 1 })
+            })
+            .unwrap();
+        let spendability = report
+            .outputs
+            .iter()
+            .map(|utxo| utxo.spendability.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                Spendability::Spendable,
+                Spendability::Reserved {
+                    transaction_id: [1; 32]
+                },
+                Spendability::Timelocked { until: 2000 },
+                Spendability::NeedsCoSigners {
+                    required_signers: 2
+                },
+                Spendability::BelowDust {
+                    threshold: Coin::new(10).unwrap()
+                },
+            ],
+            spendability
+        );
+        assert_eq!(
+            vec![(
+                TxoPointer::new([0; 32], 0),
+                TxOut::new(address(1), Coin::new(100).unwrap())
+            )],
+            report.spendable_outputs()
+        );
+        assert_eq!(Coin::new(40).unwrap(), report.unconfirmed_amount().unwrap());
+        assert_eq!(
+            "spendable: 0.00000100, reserved by pending transactions: 0.00000050, \
+             timelocked: 0.00000030, needing co-signers: 0.00000020, \
+             below dust threshold: 0.00000001, unconfirmed: 0.00000040",
+            report.to_string()
+        );
+
+        // the timelock is over
+        let report = wallet_state
+            .get_spendability_report(2000, Coin::zero(), |_| Ok(1))
+            .unwrap();
+        assert_eq!(
+            Coin::new(151).unwrap(),
+            report.amount(Spendability::is_spendable).unwrap()
+        );
+    }
+
+    #[test]
+    fn check_frozen_outputs() {
+        let storage = MemoryStorage::default();
+        let wallet_state_service = WalletStateService::new(storage);
+
+        let name = "name";
+        let enckey = &derive_enckey(&SecUtf8::from("passphrase"), name).unwrap();
+
+        let mut memento = WalletStateMemento::default();
+        for i in 0..3 {
+            memento.add_unspent_transaction(
+                TxoPointer::new([0; 32], i),
+                TxOut::new(ExtendedAddr::OrTree([0; 32]), Coin::unit()),
+            );
+        }
+        wallet_state_service
+            .apply_memento(name, enckey, &memento)
+            .unwrap();
+
+        // only unspent outputs of the wallet can be frozen
+        assert!(wallet_state_service
+            .freeze_outputs(name, enckey, &[TxoPointer::new([1; 32], 0)])
+            .is_err());
+        wallet_state_service
+            .freeze_outputs(
+                name,
+                enckey,
+                &[TxoPointer::new([0; 32], 0), TxoPointer::new([0; 32], 1)],
+            )
+            .unwrap();
+        wallet_state_service
+            .unfreeze_outputs(name, enckey, &[TxoPointer::new([0; 32], 0)])
+            .unwrap();
+        assert_eq!(
+            vec![TxoPointer::new([0; 32], 1)],
+            wallet_state_service
+                .get_frozen_outputs(name, enckey)
+                .unwrap()
+                .into_iter()
+                .collect::<Vec<_>>()
+        );
+
+        // spent outputs are not reported as frozen anymore
+        let mut memento = WalletStateMemento::default();
+        memento.remove_unspent_transaction(TxoPointer::new([0; 32], 1));
+        wallet_state_service
+            .apply_memento(name, enckey, &memento)
+            .unwrap();
+        assert!(wallet_state_service
+            .get_frozen_outputs(name, enckey)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn check_replay_events() {
+        let storage = MemoryStorage::default();
+        let wallet_state_service = WalletStateService::new(storage);
+
+        let name = "name";
+        let enckey = &derive_enckey(&SecUtf8::from("passphrase"), name).unwrap();
+
+        let mut memento = WalletStateMemento::default();
+        for i in 0..5u8 {
+            memento.add_transaction_change(TransactionChange {
+                transaction_id: [i; 32],
+                inputs: Vec::new(),
+                outputs: Vec::new(),
+                fee_paid: Fee::new(Coin::zero()),
+                balance_change: BalanceChange::NoChange,
+                transaction_type: TransactionType::Transfer,
+                block_height: u64::from(i),
+                block_time: Time::from_str("2019-04-09T09:38:41.735577Z").unwrap(),
+                annotation: None,
+            });
+        }
+        wallet_state_service
+            .apply_memento(name, enckey, &memento)
+            .unwrap();
+
+        let replay = |from_sequence, limit| {
+            wallet_state_service
+                .replay_events(name, enckey, from_sequence, limit)
+                .unwrap()
+                .iter()
+                .map(|event| {
+                    assert_eq!(
+                        event.sequence,
+                        u64::from(event.transaction.transaction_id[0])
+                    );
+                    event.sequence
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(vec![2, 3], replay(2, 2));
+        assert_eq!(vec![4], replay(4, 10));
+        assert!(replay(5, 10).is_empty());
+
+        // the oldest events are dropped, the sequence numbers of the others are kept
+        wallet_state_service
+            .modify_state(name, enckey, |state| {
+                state.transaction_history.remove(&[0; 32]);
+                state.transaction_history.remove(&[1; 32]);
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(vec![2, 3], replay(0, 2));
+        assert_eq!(vec![3, 4], replay(3, 10));
+
+        // new events continue the sequence
+        let mut memento = WalletStateMemento::default();
+        memento.add_transaction_change(TransactionChange {
+            transaction_id: [5; 32],
+            ..wallet_state_service
+                .get_transaction_change(name, enckey, &[4; 32])
+                .unwrap()
+                .unwrap()
+        });
+        wallet_state_service
+            .apply_memento(name, enckey, &memento)
+            .unwrap();
+        assert_eq!(vec![4, 5], replay(4, 10));
+    }
 }
@@ -1,11 +1,13 @@
 //! Transaction signing
 mod dummy_signer;
 mod key_pair_signer;
+mod ledger_signer;
 mod unauthorized_signer;
 mod wallet_signer;
 
 pub use dummy_signer::DummySigner;
 pub use key_pair_signer::KeyPairSigner;
+pub use ledger_signer::LedgerSigner;
 pub use unauthorized_signer::UnauthorizedSigner;
 pub use wallet_signer::{WalletSigner, WalletSignerManager};
 
@@ -3,17 +3,18 @@ use chain_core::common::MerkleTree;
 use chain_core::common::H256;
 use chain_core::init::address::RedeemAddress;
 use chain_core::state::account::{
-    DepositBondTx, StakedStateAddress, StakedStateOpAttributes, StakedStateOpWitness,
+    DepositBondTx, StakedStateAddress, StakedStateOpAttributes, StakedStateOpWitness, UnbondTx,
     WithdrawUnbondedTx,
 };
-use chain_core::state::tendermint::BlockHeight;
 use chain_core::tx::data::input::{TxoPointer, TxoSize};
 use chain_core::tx::data::{Tx, TxId};
+use chain_core::tx::fee::{
+    mock_obfuscated_deposit, mock_obfuscated_transfer, mock_obfuscated_withdraw,
+};
 use chain_core::tx::witness::tree::RawXOnlyPubkey;
 use chain_core::tx::witness::{TxInWitness, TxWitness};
-use chain_core::tx::{PlainTxAux, TransactionId, TxAux, TxEnclaveAux, TxObfuscated};
+use chain_core::tx::{TxAux, TxPublicAux};
 use client_common::Result;
-use parity_scale_codec::Encode;
 use secp256k1::recovery::{RecoverableSignature, RecoveryId};
 use secp256k1::schnorrsig::SchnorrSignature;
 
@@ -22,12 +23,6 @@ use secp256k1::schnorrsig::SchnorrSignature;
 pub struct DummySigner();
 
 impl DummySigner {
-    /// pad payload to the multiples of 128bits
-    fn pad_payload(&self, plain_txaux: PlainTxAux) -> Vec<u8> {
-        // https://tools.ietf.org/html/rfc8452
-        vec![0; plain_txaux.encode().len() + 16]
-    }
-
     /// Creates a mock merkletree
     fn mock_merkletree(
         &self,
@@ -64,27 +59,12 @@ impl DummySigner {
 
     /// Mock the txaux for transactions
     pub fn mock_txaux_for_tx(&self, tx: Tx, witness: TxWitness) -> TxAux {
-        let plain_payload = PlainTxAux::TransferTx(tx.clone(), witness);
-        let padded_payload = self.pad_payload(plain_payload);
-        // mock the enclave encrypted result
-        let tx_enclave_aux = TxEnclaveAux::TransferTx {
-            inputs: tx.inputs.clone(),
-            no_of_outputs: tx.outputs.len() as TxoSize,
-            payload: TxObfuscated {
-                txid: [0; 32],
-                key_from: BlockHeight::genesis(),
-                init_vector: [0u8; 12],
-                txpayload: padded_payload,
-            },
-        };
-        TxAux::EnclaveTx(tx_enclave_aux)
+        mock_obfuscated_transfer(&tx, &witness)
     }
 
     /// Mock the txaux for deposit transactions
     pub fn mock_txaux_for_deposit(&self, inputs: &[WitnessedUTxO]) -> Result<TxAux> {
         let witness = self.schnorr_sign_inputs_len(inputs)?;
-        let plain_payload = PlainTxAux::DepositStakeTx(witness);
-        let padded_payload = self.pad_payload(plain_payload);
         let deposit_bond_tx = DepositBondTx {
             inputs: vec![TxoPointer {
                 id: TxId::default(),
@@ -93,37 +73,25 @@ impl DummySigner {
             to_staked_account: StakedStateAddress::BasicRedeem(RedeemAddress::default()),
             attributes: StakedStateOpAttributes::default(),
         };
-        let payload = TxObfuscated {
-            txid: TxId::default(),
-            key_from: BlockHeight::genesis(),
-            init_vector: [0u8; 12],
-            txpayload: padded_payload,
-        };
-        let tx_deposit_aux = TxEnclaveAux::DepositStakeTx {
-            tx: deposit_bond_tx,
-            payload,
-        };
-        Ok(TxAux::EnclaveTx(tx_deposit_aux))
+        Ok(mock_obfuscated_deposit(&deposit_bond_tx, &witness))
     }
 
-    /// Mock the txaux for withdraw transactions
-    pub fn mock_txaux_for_withdraw(&self, tx: WithdrawUnbondedTx) -> TxAux {
+    /// Mock the witness of staked state operations
+    fn mock_staked_state_witness(&self) -> StakedStateOpWitness {
         let ecdsa_signature =
             RecoverableSignature::from_compact(&[0; 64], RecoveryId::from_i32(1).unwrap()).unwrap();
-        let witness = StakedStateOpWitness::new(ecdsa_signature);
-        let no_of_outputs = tx.outputs.len() as TxoSize;
-        let txid = tx.id();
-        let plain = PlainTxAux::WithdrawUnbondedStakeTx(tx);
-        let padded_plain = self.pad_payload(plain);
-        TxAux::EnclaveTx(TxEnclaveAux::WithdrawUnbondedStakeTx {
-            no_of_outputs,
-            witness,
-            payload: TxObfuscated {
-                txid,
-                key_from: BlockHeight::genesis(),
-                init_vector: [0u8; 12],
-                txpayload: padded_plain,
-            },
-        })
+        StakedStateOpWitness::new(ecdsa_signature)
+    }
+
+    /// Mock the txaux for unbond transactions
+    pub fn mock_txaux_for_unbond(&self, tx: UnbondTx) -> TxAux {
+        let witness = self.mock_staked_state_witness();
+        TxAux::PublicTx(TxPublicAux::UnbondStakeTx(tx, witness))
+    }
+
+    /// Mock the txaux for withdraw transactions
+    pub fn mock_txaux_for_withdraw(&self, tx: WithdrawUnbondedTx) -> TxAux {
+        let witness = self.mock_staked_state_witness();
+        mock_obfuscated_withdraw(&tx, &witness)
     }
 }
@@ -0,0 +1,144 @@
+//! A signer that signs with the keys of a hardware wallet (e.g. Ledger)
+use std::collections::BTreeMap;
+
+use chain_core::common::Proof;
+use chain_core::init::address::RedeemAddress;
+use chain_core::state::account::{StakedStateAddress, StakedStateOpWitness};
+use chain_core::tx::data::address::ExtendedAddr;
+use chain_core::tx::witness::tree::RawXOnlyPubkey;
+use chain_core::tx::witness::{TxInWitness, TxWitness};
+use client_common::{ErrorKind, MultiSigAddress, PublicKey, Result, ResultExt, Transaction};
+
+use crate::hd_wallet::ChainPath;
+use crate::service::HwKeyService;
+use crate::{SelectedUnspentTransactions, SignCondition, Signer};
+
+/// Signer using the keys of a hardware wallet: only the public keys and the paths of the keys
+/// are known to the signer, the transactions are signed on the device (so private keys
+/// never enter client-core storage)
+///
+/// Transfer addresses are signed with schnorr tree signatures,
+/// staking addresses with recoverable ECDSA signatures.
+#[derive(Debug, Clone)]
+pub struct LedgerSigner {
+    hw_key_service: HwKeyService,
+    transfer_keys: BTreeMap<ExtendedAddr, (ChainPath, Proof<RawXOnlyPubkey>)>,
+    staking_keys: BTreeMap<StakedStateAddress, ChainPath>,
+}
+
+impl LedgerSigner {
+    /// Create a new signer (without keys) using the hardware key service
+    #[inline]
+    pub fn new(hw_key_service: HwKeyService) -> Self {
+        LedgerSigner {
+            hw_key_service,
+            transfer_keys: BTreeMap::new(),
+            staking_keys: BTreeMap::new(),
+        }
+    }
+
+    /// Adds the (1-of-1) transfer address of the device key at the given path
+    pub fn add_transfer_key(&mut self, chain_path: ChainPath) -> Result<ExtendedAddr> {
+        let public_key = self.hw_key_service.get_public_key(chain_path.clone())?;
+        let multi_sig_address =
+            MultiSigAddress::new(vec![public_key.clone()], public_key.clone(), 1)?;
+        let proof = multi_sig_address
+            .generate_proof(vec![public_key])?
+            .chain(|| (ErrorKind::InvalidInput, "Unable to generate merkle proof"))?;
+        let extended_addr = ExtendedAddr::from(multi_sig_address);
+        self.transfer_keys
+            .insert(extended_addr.clone(), (chain_path, proof));
+        Ok(extended_addr)
+    }
+
+    /// Adds the staking address of the device key at the given path
+    pub fn add_staking_key(&mut self, chain_path: ChainPath) -> Result<StakedStateAddress> {
+        let public_key: PublicKey = self.hw_key_service.get_public_key(chain_path.clone())?;
+        let address = StakedStateAddress::BasicRedeem(RedeemAddress::from(&public_key));
+        self.staking_keys.insert(address, chain_path);
+        Ok(address)
+    }
+
+    /// Signs the staking operation (unbond, unjail, node join) of the staking address
+    pub fn sign_staking_operation(
+        &self,
+        tx: &Transaction,
+        staking_address: &StakedStateAddress,
+    ) -> Result<StakedStateOpWitness> {
+        let chain_path = self.staking_keys.get(staking_address).chain(|| {
+            (
+                ErrorKind::InvalidInput,
+                format!(
+                    "Staking address does not belong to the hardware wallet: {}",
+                    staking_address
+                ),
+            )
+        })?;
+        let sign_key = self.hw_key_service.get_sign_key(chain_path)?;
+        sign_key.sign(tx).map(StakedStateOpWitness::new)
+    }
+}
+
+impl Signer for LedgerSigner {
+    fn schnorr_sign_transaction(
+        &self,
+        tx: &Transaction,
+        selected_unspent_transactions: &SelectedUnspentTransactions<'_>,
+    ) -> Result<TxWitness> {
+        selected_unspent_transactions
+            .iter()
+            .map(|(_, output)| self.schnorr_sign(tx, &output.address))
+            .collect::<Result<Vec<TxInWitness>>>()
+            .map(Into::into)
+    }
+
+    fn schnorr_sign_condition(&self, signing_addr: &ExtendedAddr) -> Result<SignCondition> {
+        if self.transfer_keys.contains_key(signing_addr) {
+            Ok(SignCondition::SingleSignUnlock)
+        } else {
+            Ok(SignCondition::Impossible)
+        }
+    }
+
+    fn schnorr_sign(&self, tx: &Transaction, signing_addr: &ExtendedAddr) -> Result<TxInWitness> {
+        let (chain_path, proof) = self.transfer_keys.get(signing_addr).chain(|| {
+            (
+                ErrorKind::InvalidInput,
+                "Signing address does not belong to the hardware wallet",
+            )
+        })?;
+        let sign_key = self.hw_key_service.get_sign_key(chain_path)?;
+        let signature = sign_key.schnorr_sign(tx).chain(|| {
+            (
+                ErrorKind::LedgerError,
+                "Unable to sign transfer on the hardware wallet",
+            )
+        })?;
+        Ok(TxInWitness::TreeSig(signature, proof.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_unauthorized_ledger_signer() {
+        let mut signer = LedgerSigner::new(HwKeyService::default());
+        let chain_path = ChainPath::from("m/44'/394'/0'/0/0");
+        assert_eq!(
+            ErrorKind::PermissionDenied,
+            signer
+                .add_transfer_key(chain_path.clone())
+                .unwrap_err()
+                .kind()
+        );
+        assert_eq!(
+            ErrorKind::PermissionDenied,
+            signer.add_staking_key(chain_path).unwrap_err().kind()
+        );
+
+        let address = ExtendedAddr::OrTree([0; 32]);
+        assert!(signer.schnorr_sign_condition(&address).unwrap() == SignCondition::Impossible);
+    }
+}
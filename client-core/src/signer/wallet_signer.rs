@@ -6,6 +6,7 @@ use client_common::Transaction;
 use client_common::{Error, ErrorKind, Result, ResultExt, SecKey, Storage};
 
 use crate::service::{HwKeyService, KeyService, RootHashService, WalletService};
+use crate::transaction_builder::PayloadSigningKey;
 use crate::types::WalletKind;
 use crate::{SelectedUnspentTransactions, SignCondition, Signer};
 
@@ -136,12 +137,27 @@ impl<'a, S> WalletSigner<'a, S>
 where
     S: Storage + 'static,
 {
-    /// Schnorr signs message with private key corresponding to `self_public_key` in given 1-of-n root hash
-    fn schnorr_sign_with_root_hash(
-        &self,
-        tx: &Transaction,
-        root_hash: &H256,
-    ) -> Result<TxInWitness> {
+    /// Returns the public key and its proof for signing outputs of the address
+    /// (e.g. by an offline signer of a `watch-only` wallet)
+    pub fn signing_key(&self, signing_addr: &ExtendedAddr) -> Result<PayloadSigningKey> {
+        let root_hash = self
+            .wallet_service
+            .find_root_hash(self.name, self.enckey, signing_addr)?
+            .chain(|| {
+                (
+                    ErrorKind::InvalidInput,
+                    format!(
+                        "Output's address ({}) does not belong to wallet with name: {}",
+                        signing_addr, self.name
+                    ),
+                )
+            })?;
+
+        self.signing_key_with_root_hash(&root_hash)
+    }
+
+    /// Public key of the wallet in given 1-of-n root hash, with its merkle proof
+    fn signing_key_with_root_hash(&self, root_hash: &H256) -> Result<PayloadSigningKey> {
         if self
             .root_hash_service
             .required_signers(self.name, &root_hash, self.enckey)?
@@ -156,6 +172,23 @@ where
         let public_key = self
             .root_hash_service
             .public_key(self.name, &root_hash, self.enckey)?;
+        let proof = self.root_hash_service.generate_proof(
+            self.name,
+            &root_hash,
+            vec![public_key.clone()],
+            self.enckey,
+        )?;
+
+        Ok(PayloadSigningKey { public_key, proof })
+    }
+
+    /// Schnorr signs message with private key corresponding to `self_public_key` in given 1-of-n root hash
+    fn schnorr_sign_with_root_hash(
+        &self,
+        tx: &Transaction,
+        root_hash: &H256,
+    ) -> Result<TxInWitness> {
+        let PayloadSigningKey { public_key, proof } = self.signing_key_with_root_hash(root_hash)?;
         let wallet = self
             .wallet_service
             .get_wallet_info(self.name, self.enckey)?;
@@ -182,15 +215,14 @@ where
                     })?;
                 Box::new(private_key)
             }
+            WalletKind::WatchOnly => {
+                return Err(Error::new(
+                    ErrorKind::PermissionDenied,
+                    "Watch-only wallet can not sign transactions, the signing payload has to be signed offline",
+                ))
+            }
         };
 
-        let proof = self.root_hash_service.generate_proof(
-            self.name,
-            &root_hash,
-            vec![public_key],
-            self.enckey,
-        )?;
-
         Ok(TxInWitness::TreeSig(sign_key.schnorr_sign(tx)?, proof))
     }
 }
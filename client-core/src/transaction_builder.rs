@@ -1,12 +1,17 @@
 //! Transaction builder
 mod default_wallet_transaction_builder;
+mod offline_signing;
 mod raw_transfer_transaction_builder;
 mod unauthorized_wallet_transaction_builder;
 
-pub use default_wallet_transaction_builder::DefaultWalletTransactionBuilder;
+pub use default_wallet_transaction_builder::{
+    ChangeOutcome, ChangePolicy, DefaultWalletTransactionBuilder, SubThresholdChange,
+    TransferDryRun,
+};
+pub use offline_signing::{sign_offline, OfflineSignature, QrEncoding, DEFAULT_QR_PART_LEN};
 pub use raw_transfer_transaction_builder::{
-    RawTransferTransaction, RawTransferTransactionBuilder, SignedTransferTransaction,
-    UnsignedTransferTransaction, WitnessedUTxO,
+    PayloadSigningKey, RawTransferTransaction, RawTransferTransactionBuilder,
+    SignedTransferTransaction, SigningPayload, UnsignedTransferTransaction, WitnessedUTxO,
 };
 pub use unauthorized_wallet_transaction_builder::UnauthorizedWalletTransactionBuilder;
 
@@ -15,15 +20,22 @@ use chain_core::tx::data::address::ExtendedAddr;
 use chain_core::tx::data::attribute::TxAttributes;
 use chain_core::tx::data::input::TxoPointer;
 use chain_core::tx::data::output::TxOut;
+use chain_core::tx::witness::TxWitness;
 use chain_core::tx::TxAux;
 use client_common::{PrivateKey, Result, SecKey, SignedTransaction, Transaction};
 
+use crate::coin_selection::CoinSelectionStrategy;
 use crate::UnspentTransactions;
-use chain_core::tx::data::TxId;
+use chain_core::tx::data::{Tx, TxId};
+use std::sync::Arc;
 
 /// Interface for wallet transaction building from output addresses and amount.
 /// This trait is also responsible for UTXO selection.
 pub trait WalletTransactionBuilder: Send + Sync + Clone {
+    /// Returns a copy of the builder which selects the spent unspent transactions with the
+    /// given strategy
+    fn with_coin_selection(&self, coin_selection: Arc<dyn CoinSelectionStrategy>) -> Self;
+
     /// Builds a transfer transaction
     ///
     /// # Attributes
@@ -49,6 +61,49 @@ pub trait WalletTransactionBuilder: Send + Sync + Clone {
         attributes: TxAttributes,
     ) -> Result<(TxAux, Vec<TxoPointer>, Coin)>;
 
+    /// Builds a transfer transaction to be signed by an offline signer (for `watch-only` wallets)
+    ///
+    /// # Attributes
+    ///
+    /// Same as `build_transfer_tx`
+    ///
+    /// # return
+    /// - `SigningPayload`: unsigned transaction with the signing keys of the selected inputs
+    fn build_signing_payload(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        unspent_transactions: UnspentTransactions,
+        outputs: Vec<TxOut>,
+        return_address: ExtendedAddr,
+        attributes: TxAttributes,
+    ) -> Result<SigningPayload>;
+
+    /// Completes the transaction of the signing payload with the witness of the offline signer,
+    /// and obfuscates it
+    fn finalize_signing_payload(
+        &self,
+        payload: SigningPayload,
+        witness: TxWitness,
+    ) -> Result<TxAux>;
+
+    /// Selects inputs and estimates the fee and size of a transfer transaction without signing it
+    ///
+    /// # Attributes
+    ///
+    /// Same as `build_transfer_tx` (without the wallet name and encryption key)
+    fn dry_run_transfer_tx(
+        &self,
+        unspent_transactions: &UnspentTransactions,
+        outputs: Vec<TxOut>,
+        return_address: ExtendedAddr,
+        attributes: TxAttributes,
+    ) -> Result<TransferDryRun>;
+
+    /// Returns the fee of the transfer transaction signed with given witness (for transactions
+    /// which aren't built by the builder, e.g. the ones spending with timelocked witnesses)
+    fn transfer_fee(&self, tx: &Tx, witness: &TxWitness) -> Result<Coin>;
+
     /// Obfuscates given signed transaction
     fn obfuscate(&self, signed_transaction: SignedTransaction) -> Result<TxAux>;
 
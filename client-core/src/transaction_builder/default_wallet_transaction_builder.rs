@@ -1,33 +1,40 @@
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
 use chain_core::init::coin::{sum_coins, Coin};
 use chain_core::tx::data::address::ExtendedAddr;
 use chain_core::tx::data::attribute::TxAttributes;
 use chain_core::tx::data::input::TxoPointer;
 use chain_core::tx::data::output::TxOut;
 use chain_core::tx::fee::FeeAlgorithm;
+use chain_core::tx::witness::TxWitness;
 use chain_core::tx::TxAux;
 use client_common::{
     ErrorKind, PrivateKey, Result, ResultExt, SecKey, SignedTransaction, Storage, Transaction,
     TransactionObfuscation,
 };
 
-use crate::signer::WalletSignerManager;
-use crate::transaction_builder::RawTransferTransactionBuilder;
-use crate::{SelectedUnspentTransactions, UnspentTransactions, WalletTransactionBuilder};
-use chain_core::tx::data::TxId;
+use crate::coin_selection::{CoinSelectionStrategy, FeeEstimator, InputOrder};
+use crate::signer::{DummySigner, WalletSignerManager};
+use crate::transaction_builder::{
+    PayloadSigningKey, RawTransferTransactionBuilder, SigningPayload,
+};
+use crate::{UnspentTransactions, WalletTransactionBuilder};
+use chain_core::tx::data::{Tx, TxId};
 
 /// Default implementation of `TransactionBuilder`
 ///
 /// # Algorithm
 ///
 /// 1. Calculate `output_value`: Sum of all the output values.
-/// 2. Initialize `fees = 0`.
-/// 3. Select unspent transactions with `fees + output_value`.
-/// 4. Build transaction with selected unspent transactions (also add an extra output for change amount).
-/// 5. Sign transaction with dummy signer.
-/// 6. Wrap up transaction.
-/// 7. Calculate `new_fees`.
-/// 8. If `new_fees > fees`, then change `fees = new_fees` and goto step 3, otherwise return signed transaction.
+/// 2. Select unspent transactions for `output_value` with the `CoinSelectionStrategy` (by default
+///    in the given order), which estimates the fee of the transaction with the selected number
+///    of inputs (signed with dummy signer), with or without a change output.
+/// 3. Build transaction with selected unspent transactions (also add an extra output for change amount).
+/// 4. Sign and wrap up transaction.
 ///
+/// Where the change amount goes in step 3 is controlled by `ChangePolicy`.
 #[derive(Debug, Clone)]
 pub struct DefaultWalletTransactionBuilder<S, F, O>
 where
@@ -38,6 +45,69 @@ where
     signer_manager: WalletSignerManager<S>,
     fee_algorithm: F,
     transaction_obfuscation: O,
+    change_policy: ChangePolicy,
+    coin_selection: Arc<dyn CoinSelectionStrategy>,
+}
+
+/// What to do with change amounts below `ChangePolicy::min_change`
+///
+/// NOTE: folding the change into the fee is not an option, as transfer validation
+/// requires the inputs to exactly match the outputs plus the minimal fee.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SubThresholdChange {
+    /// return it to the return address anyway
+    Return,
+    /// redirect it to the given (e.g. cold storage) consolidation address
+    Consolidate(ExtendedAddr),
+}
+
+/// Policy for the change output of built transfer transactions
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChangePolicy {
+    /// change amounts below this value are handled according to `sub_threshold`
+    pub min_change: Coin,
+    /// what to do with change amounts below `min_change`
+    pub sub_threshold: SubThresholdChange,
+}
+
+impl Default for ChangePolicy {
+    fn default() -> Self {
+        ChangePolicy {
+            min_change: Coin::zero(),
+            sub_threshold: SubThresholdChange::Return,
+        }
+    }
+}
+
+/// Where the change amount of a built transfer transaction went
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ChangeOutcome {
+    /// selected inputs matched outputs and fee exactly
+    NoChange,
+    /// change output to the return address
+    Returned(Coin),
+    /// sub-threshold change output to the consolidation address
+    Consolidated {
+        /// consolidation address
+        address: ExtendedAddr,
+        /// change amount
+        amount: Coin,
+    },
+}
+
+/// Result of a transfer transaction dry-run (nothing is signed or broadcast)
+#[derive(Debug, Clone)]
+pub struct TransferDryRun {
+    /// selected inputs
+    pub inputs: Vec<TxoPointer>,
+    /// outputs (including the change output, if any)
+    pub outputs: Vec<TxOut>,
+    /// estimated fee
+    pub fee: Coin,
+    /// estimated serialized size of the signed transaction (in bytes)
+    pub size: usize,
+    /// where the change amount went
+    pub change: ChangeOutcome,
 }
 
 impl<F, S, O> DefaultWalletTransactionBuilder<S, F, O>
@@ -59,7 +129,7 @@ where
         // FIXME: this should be per unspent_transactions
         threshold: u16,
     ) -> Result<(TxAux, Vec<TxoPointer>, Coin)> {
-        let mut raw_builder = self.select_and_build(
+        let (mut raw_builder, _) = self.select_and_build_ex(
             &unspent_transactions,
             outputs,
             return_address.clone(),
@@ -71,11 +141,7 @@ where
             .iter_inputs()
             .map(|witness_utxo| witness_utxo.prev_txo_pointer.clone())
             .collect();
-        let return_amount = raw_builder
-            .iter_outputs()
-            .find(|&m| m.address == return_address)
-            .map(|output| output.value)
-            .unwrap_or_default();
+        let return_amount = return_amount(&raw_builder, &return_address);
 
         let signer =
             self.signer_manager
@@ -95,6 +161,12 @@ where
     F: FeeAlgorithm + Clone,
     O: TransactionObfuscation,
 {
+    fn with_coin_selection(&self, coin_selection: Arc<dyn CoinSelectionStrategy>) -> Self {
+        let mut builder = self.clone();
+        builder.coin_selection = coin_selection;
+        builder
+    }
+
     fn build_transfer_tx(
         &self,
         name: &str,
@@ -115,6 +187,74 @@ where
         )
     }
 
+    fn build_signing_payload(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        unspent_transactions: UnspentTransactions,
+        outputs: Vec<TxOut>,
+        return_address: ExtendedAddr,
+        attributes: TxAttributes,
+    ) -> Result<SigningPayload> {
+        let (raw_builder, _) = self.select_and_build_ex(
+            &unspent_transactions,
+            outputs,
+            return_address.clone(),
+            attributes,
+            1,
+        )?;
+        let return_amount = return_amount(&raw_builder, &return_address);
+
+        let signer =
+            self.signer_manager
+                .create_signer(name, enckey, &self.signer_manager.hw_key_service);
+        let signing_keys = raw_builder
+            .iter_inputs()
+            .map(|input| signer.signing_key(&input.prev_tx_out.address))
+            .collect::<Result<Vec<PayloadSigningKey>>>()?;
+
+        Ok(raw_builder.to_signing_payload(signing_keys, return_amount))
+    }
+
+    fn finalize_signing_payload(
+        &self,
+        payload: SigningPayload,
+        witness: TxWitness,
+    ) -> Result<TxAux> {
+        let raw_builder = RawTransferTransactionBuilder::from_signing_payload(
+            payload,
+            witness,
+            self.fee_algorithm.clone(),
+        )?;
+        raw_builder.to_tx_aux(self.transaction_obfuscation.clone())
+    }
+
+    #[inline]
+    fn dry_run_transfer_tx(
+        &self,
+        unspent_transactions: &UnspentTransactions,
+        outputs: Vec<TxOut>,
+        return_address: ExtendedAddr,
+        attributes: TxAttributes,
+    ) -> Result<TransferDryRun> {
+        self.dry_run(unspent_transactions, outputs, return_address, attributes)
+    }
+
+    fn transfer_fee(&self, tx: &Tx, witness: &TxWitness) -> Result<Coin> {
+        // the obfuscated transaction has the same size as the mock one
+        let tx_aux = DummySigner().mock_txaux_for_tx(tx.clone(), witness.clone());
+        Ok(self
+            .fee_algorithm
+            .calculate_for_txaux(&tx_aux)
+            .chain(|| {
+                (
+                    ErrorKind::IllegalInput,
+                    "Fee exceeds maximum allowed amount",
+                )
+            })?
+            .to_coin())
+    }
+
     #[inline]
     fn obfuscate(&self, signed_transaction: SignedTransaction) -> Result<TxAux> {
         self.transaction_obfuscation.encrypt(signed_transaction)
@@ -149,9 +289,41 @@ where
             signer_manager,
             fee_algorithm,
             transaction_obfuscation,
+            change_policy: ChangePolicy::default(),
+            coin_selection: Arc::new(InputOrder),
         }
     }
 
+    /// Sets the policy for change outputs
+    #[inline]
+    pub fn with_change_policy(mut self, change_policy: ChangePolicy) -> Self {
+        self.change_policy = change_policy;
+        self
+    }
+
+    /// Selects inputs and estimates the fee of a transfer transaction without signing it,
+    /// reporting explicitly where the change amount goes
+    pub fn dry_run(
+        &self,
+        unspent_transactions: &UnspentTransactions,
+        outputs: Vec<TxOut>,
+        return_address: ExtendedAddr,
+        attributes: TxAttributes,
+    ) -> Result<TransferDryRun> {
+        let (raw_builder, change) =
+            self.select_and_build_ex(unspent_transactions, outputs, return_address, attributes, 1)?;
+        Ok(TransferDryRun {
+            inputs: raw_builder
+                .iter_inputs()
+                .map(|witness_utxo| witness_utxo.prev_txo_pointer.clone())
+                .collect(),
+            outputs: raw_builder.iter_outputs().cloned().collect(),
+            fee: raw_builder.estimate_fee()?,
+            size: raw_builder.estimate_size()?,
+            change,
+        })
+    }
+
     /// Create a `DummySigner` which signs a transaction with dummy values for fees calculation.
     /// Returns a result of unsigned raw transfer transaction builder
     pub fn select_and_build<'a>(
@@ -163,51 +335,65 @@ where
         // FIXME: this should be per UnspentTransactions
         threshold: u16,
     ) -> Result<RawTransferTransactionBuilder<F>> {
+        self.select_and_build_ex(
+            unspent_transactions,
+            outputs,
+            return_address,
+            attributes,
+            threshold,
+        )
+        .map(|(raw_tx_builder, _)| raw_tx_builder)
+    }
+
+    fn select_and_build_ex(
+        &self,
+        unspent_transactions: &UnspentTransactions,
+        outputs: Vec<TxOut>,
+        return_address: ExtendedAddr,
+        attributes: TxAttributes,
+        threshold: u16,
+    ) -> Result<(RawTransferTransactionBuilder<F>, ChangeOutcome)> {
         let output_value = sum_coins(outputs.iter().map(|output| output.value)).chain(|| {
             (
                 ErrorKind::IllegalInput,
                 "Sum of output values exceeds maximum allowed amount",
             )
         })?;
-        let mut fees = Coin::zero();
-        let raw_tx_builder = loop {
-            let (selected_unspent_txs, change_amount) =
-                unspent_transactions.select((output_value + fees).chain(|| {
-                    (
-                        ErrorKind::IllegalInput,
-                        "Sum of output values and fee exceeds maximum allowed amount",
-                    )
-                })?)?;
-            let raw_tx_builder = self.build_raw_transaction(
-                &selected_unspent_txs,
-                &outputs,
-                return_address.clone(),
-                change_amount,
-                attributes.clone(),
-                threshold,
-            );
-
-            let new_fees = raw_tx_builder.estimate_fee()?;
-            if new_fees > fees {
-                fees = new_fees;
-            } else {
-                break raw_tx_builder;
-            }
+        let sample_input = unspent_transactions
+            .first()
+            .chain(|| (ErrorKind::InvalidInput, "Insufficient balance"))?;
+        let fee_estimator = TransferFeeEstimator {
+            builder: self,
+            sample_input,
+            outputs: &outputs,
+            return_address: &return_address,
+            attributes: &attributes,
+            threshold,
         };
+        let selection =
+            self.coin_selection
+                .select(unspent_transactions, output_value, &fee_estimator)?;
 
-        Ok(raw_tx_builder)
+        Ok(self.build_raw_transaction(
+            &selection.inputs,
+            &outputs,
+            return_address,
+            selection.change,
+            attributes,
+            threshold,
+        ))
     }
 
     fn build_raw_transaction(
         &self,
-        selected_unspent_transactions: &SelectedUnspentTransactions<'_>,
+        selected_unspent_transactions: &[(TxoPointer, TxOut)],
         outputs: &[TxOut],
         return_address: ExtendedAddr,
         change_amount: Coin,
         attributes: TxAttributes,
         // FIXME: this should be per SelectedUnspentTransactions
         threshold: u16,
-    ) -> RawTransferTransactionBuilder<F> {
+    ) -> (RawTransferTransactionBuilder<F>, ChangeOutcome) {
         let mut raw_tx_builder =
             RawTransferTransactionBuilder::new(attributes, self.fee_algorithm.clone());
         for input in selected_unspent_transactions.iter() {
@@ -216,14 +402,82 @@ where
         for output in outputs.iter() {
             raw_tx_builder.add_output(output.clone());
         }
-        if change_amount != Coin::zero() {
-            raw_tx_builder.add_output(TxOut::new(return_address, change_amount));
-        }
+        let change = if change_amount == Coin::zero() {
+            ChangeOutcome::NoChange
+        } else {
+            match &self.change_policy.sub_threshold {
+                SubThresholdChange::Consolidate(address)
+                    if change_amount < self.change_policy.min_change =>
+                {
+                    raw_tx_builder.add_output(TxOut::new(address.clone(), change_amount));
+                    ChangeOutcome::Consolidated {
+                        address: address.clone(),
+                        amount: change_amount,
+                    }
+                }
+                _ => {
+                    raw_tx_builder.add_output(TxOut::new(return_address, change_amount));
+                    ChangeOutcome::Returned(change_amount)
+                }
+            }
+        };
+
+        (raw_tx_builder, change)
+    }
+}
+
+/// Estimates the fee of a transfer transaction with the given outputs, as if all the inputs
+/// were like `sample_input` (so the fee only depends on the number of inputs)
+struct TransferFeeEstimator<'a, S, F, O>
+where
+    S: Storage,
+    F: FeeAlgorithm + Clone,
+    O: TransactionObfuscation,
+{
+    builder: &'a DefaultWalletTransactionBuilder<S, F, O>,
+    sample_input: &'a (TxoPointer, TxOut),
+    outputs: &'a [TxOut],
+    return_address: &'a ExtendedAddr,
+    attributes: &'a TxAttributes,
+    threshold: u16,
+}
 
-        raw_tx_builder
+impl<'a, S, F, O> FeeEstimator for TransferFeeEstimator<'a, S, F, O>
+where
+    S: Storage,
+    F: FeeAlgorithm + Clone,
+    O: TransactionObfuscation,
+{
+    fn estimate_fee(&self, inputs: usize, change: bool) -> Result<Coin> {
+        let mut raw_tx_builder = RawTransferTransactionBuilder::new(
+            self.attributes.clone(),
+            self.builder.fee_algorithm.clone(),
+        );
+        for _ in 0..inputs {
+            raw_tx_builder.add_input(self.sample_input.clone(), self.threshold);
+        }
+        for output in self.outputs.iter() {
+            raw_tx_builder.add_output(output.clone());
+        }
+        if change {
+            raw_tx_builder.add_output(TxOut::new(self.return_address.clone(), Coin::zero()));
+        }
+        raw_tx_builder.estimate_fee()
     }
 }
 
+/// Amount of the change output to the return address (zero if there's none)
+fn return_amount<F: FeeAlgorithm>(
+    raw_builder: &RawTransferTransactionBuilder<F>,
+    return_address: &ExtendedAddr,
+) -> Coin {
+    raw_builder
+        .iter_outputs()
+        .find(|&m| m.address == *return_address)
+        .map(|output| output.value)
+        .unwrap_or_default()
+}
+
 #[cfg(test)]
 mod default_wallet_transaction_builder_tests {
     use parity_scale_codec::{Decode, Encode};
@@ -500,4 +754,86 @@ mod default_wallet_transaction_builder_tests {
                 .kind()
         );
     }
+
+    #[test]
+    fn check_sub_threshold_change_consolidation() {
+        let name = "name";
+        let passphrase = SecUtf8::from("passphrase");
+
+        let storage = MemoryStorage::default();
+        let wallet_client = DefaultWalletClient::new_read_only(storage.clone());
+
+        let (enckey, _) = wallet_client
+            .new_wallet(
+                name,
+                &passphrase,
+                WalletKind::Basic,
+                HardwareKind::LocalOnly,
+                None,
+            )
+            .unwrap();
+
+        let unspent_transactions = UnspentTransactions::new(vec![(
+            TxoPointer::new([0; 32], 0),
+            TxOut::new(
+                wallet_client.new_transfer_address(name, &enckey).unwrap(),
+                Coin::new(10000).unwrap(),
+            ),
+        )]);
+        let return_address = wallet_client.new_transfer_address(name, &enckey).unwrap();
+        let consolidation_address = wallet_client.new_transfer_address(name, &enckey).unwrap();
+        let outputs = vec![TxOut::new(
+            wallet_client.new_transfer_address(name, &enckey).unwrap(),
+            Coin::new(9000).unwrap(),
+        )];
+
+        let signer_manager = WalletSignerManager::new(storage.clone(), HwKeyService::default());
+        let fee_algorithm =
+            LinearFee::new(Milli::try_new(1, 1).unwrap(), Milli::try_new(1, 1).unwrap());
+        let transaction_builder = DefaultWalletTransactionBuilder::new(
+            signer_manager,
+            fee_algorithm,
+            MockTransactionCipher,
+        );
+
+        let dry_run = transaction_builder
+            .dry_run(
+                &unspent_transactions,
+                outputs.clone(),
+                return_address.clone(),
+                TxAttributes::new(171),
+            )
+            .unwrap();
+        assert!(dry_run.size > 0);
+        let change = match dry_run.change {
+            ChangeOutcome::Returned(change) => change,
+            outcome => panic!("unexpected change outcome: {:?}", outcome),
+        };
+        assert_eq!(
+            Coin::new(10000).unwrap(),
+            ((change + dry_run.fee).unwrap() + Coin::new(9000).unwrap()).unwrap()
+        );
+
+        let transaction_builder = transaction_builder.with_change_policy(ChangePolicy {
+            min_change: Coin::new(5000).unwrap(),
+            sub_threshold: SubThresholdChange::Consolidate(consolidation_address.clone()),
+        });
+        let dry_run = transaction_builder
+            .dry_run(
+                &unspent_transactions,
+                outputs,
+                return_address,
+                TxAttributes::new(171),
+            )
+            .unwrap();
+        assert_eq!(
+            ChangeOutcome::Consolidated {
+                address: consolidation_address.clone(),
+                amount: change,
+            },
+            dry_run.change
+        );
+        assert_eq!(2, dry_run.outputs.len());
+        assert_eq!(consolidation_address, dry_run.outputs[1].address);
+    }
 }
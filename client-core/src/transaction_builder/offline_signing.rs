@@ -0,0 +1,315 @@
+//! Offline (air-gapped) signing of transfer transactions
+//!
+//! The `watch-only` wallet exports a `SigningPayload` (the unsigned transaction, the spent outputs
+//! with their addresses and amounts, and the merkle proofs of the signing keys in the input
+//! addresses) as a few QR code parts. The air-gapped signer checks and signs it with
+//! `sign_offline`, and returns the `OfflineSignature` the same way. The `watch-only` wallet
+//! then finishes the `TxAux` (the obfuscation needs the transaction query enclave).
+use std::collections::BTreeMap;
+
+use parity_scale_codec::{Decode, Encode};
+
+use chain_core::tx::data::TxId;
+use chain_core::tx::witness::TxWitness;
+use chain_tx_validation::witness::verify_tx_address;
+use client_common::{Error, ErrorKind, PrivateKeyAction, PublicKey, Result, ResultExt};
+
+use super::SigningPayload;
+
+/// Default maximum length of the data of a QR code part (fits a version 20 QR code
+/// in alphanumeric mode)
+pub const DEFAULT_QR_PART_LEN: usize = 800;
+
+/// Length of the checksum appended to the encoded data
+const CHECKSUM_LEN: usize = 4;
+
+/// RFC 4648 base32 alphabet (a subset of the QR code alphanumeric mode)
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// Signature of a `SigningPayload` produced by an offline signer
+#[derive(Debug, Clone, PartialEq, Decode, Encode)]
+pub struct OfflineSignature {
+    /// id of the signed transaction
+    pub tx_id: TxId,
+    /// input witnesses (in the order of the inputs)
+    pub witness: TxWitness,
+}
+
+impl OfflineSignature {
+    /// Returns the witness after checking it signs the transaction of the payload
+    pub fn into_witness(self, payload: &SigningPayload) -> Result<TxWitness> {
+        if self.tx_id != payload.tx_id() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Offline signature does not sign the transaction of the signing payload",
+            ));
+        }
+        Ok(self.witness)
+    }
+}
+
+/// Values exchanged with an offline signer as QR code parts:
+/// `<PREFIX>:<index>/<count>:<data>` where the data of all the parts (in the order of their
+/// indexes) is the base32 encoding of the SCALE encoded value followed by its checksum
+pub trait QrEncoding: Encode + Decode {
+    /// Prefix of the QR code parts
+    const PREFIX: &'static str;
+
+    /// Splits the encoded value into parts of at most `max_part_len` data characters
+    fn to_qr_parts(&self, max_part_len: usize) -> Vec<String> {
+        let mut raw_data = self.encode();
+        raw_data.extend_from_slice(&checksum(&raw_data));
+        let encoded = base32_encode(&raw_data);
+        let chunks = encoded
+            .as_bytes()
+            .chunks(std::cmp::max(max_part_len, 1))
+            .collect::<Vec<_>>();
+        let count = chunks.len();
+
+        chunks
+            .into_iter()
+            .enumerate()
+            .map(|(index, chunk)| {
+                format!(
+                    "{}:{}/{}:{}",
+                    Self::PREFIX,
+                    index + 1,
+                    count,
+                    String::from_utf8_lossy(chunk)
+                )
+            })
+            .collect()
+    }
+
+    /// Decodes a value from all of its QR code parts (in any order)
+    fn from_qr_parts<S: AsRef<str>>(parts: &[S]) -> Result<Self> {
+        let invalid_part = || {
+            Error::new(
+                ErrorKind::DeserializationError,
+                format!("Invalid {} QR code part", Self::PREFIX),
+            )
+        };
+
+        let mut chunks = BTreeMap::new();
+        let mut total = None;
+        for part in parts {
+            let mut fields = part.as_ref().trim().splitn(3, ':');
+            if fields.next() != Some(Self::PREFIX) {
+                return Err(invalid_part());
+            }
+            let (index, count) = fields
+                .next()
+                .and_then(|position| {
+                    let mut numbers = position.splitn(2, '/');
+                    let index = numbers.next()?.parse::<usize>().ok()?;
+                    let count = numbers.next()?.parse::<usize>().ok()?;
+                    Some((index, count))
+                })
+                .ok_or_else(invalid_part)?;
+            if index == 0 || index > count || total.map_or(false, |total| total != count) {
+                return Err(invalid_part());
+            }
+            total = Some(count);
+            chunks.insert(index, fields.next().ok_or_else(invalid_part)?);
+        }
+        match total {
+            Some(count) if count == chunks.len() => {}
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!(
+                        "Missing {} QR code parts ({} of {} received)",
+                        Self::PREFIX,
+                        chunks.len(),
+                        total.unwrap_or_default()
+                    ),
+                ))
+            }
+        }
+
+        let encoded = chunks.values().cloned().collect::<String>();
+        let raw_data = base32_decode(&encoded).ok_or_else(invalid_part)?;
+        if raw_data.len() < CHECKSUM_LEN {
+            return Err(invalid_part());
+        }
+        let (mut data, data_checksum) = raw_data.split_at(raw_data.len() - CHECKSUM_LEN);
+        if checksum(data) != data_checksum {
+            return Err(Error::new(
+                ErrorKind::InvalidChecksum,
+                format!("Invalid checksum of {} QR code parts", Self::PREFIX),
+            ));
+        }
+        Self::decode(&mut data).chain(|| {
+            (
+                ErrorKind::DeserializationError,
+                format!("Unable to deserialize {} QR code parts", Self::PREFIX),
+            )
+        })
+    }
+}
+
+impl QrEncoding for SigningPayload {
+    const PREFIX: &'static str = "CROTX";
+}
+
+impl QrEncoding for OfflineSignature {
+    const PREFIX: &'static str = "CROSIG";
+}
+
+/// Checks the signing payload and signs all of its inputs with the keys returned by `find_key`
+/// (on the air-gapped signer). Each input witness is verified against the address of the
+/// spent output, so a payload with wrong merkle proofs (or keys) is rejected.
+pub fn sign_offline<K>(payload: &SigningPayload, find_key: K) -> Result<OfflineSignature>
+where
+    K: Fn(&PublicKey) -> Result<Box<dyn PrivateKeyAction>>,
+{
+    let inputs = payload.inputs();
+    if inputs.is_empty() || payload.signing_keys.len() != inputs.len() {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "Number of signing keys does not match the number of inputs",
+        ));
+    }
+    // outputs must not spend more than the inputs
+    payload.fee()?;
+
+    let tx_id = payload.tx_id();
+    let witness = payload.sign(find_key)?;
+    for (index, (input, input_witness)) in inputs.iter().zip(witness.iter()).enumerate() {
+        verify_tx_address(input_witness, &tx_id, &input.prev_tx_out.address).map_err(|err| {
+            Error::new(
+                ErrorKind::VerifyError,
+                format!("Unable to verify witness of input {}: {}", index, err),
+            )
+        })?;
+    }
+
+    Ok(OfflineSignature { tx_id, witness })
+}
+
+fn checksum(data: &[u8]) -> [u8; CHECKSUM_LEN] {
+    let mut checksum = [0; CHECKSUM_LEN];
+    checksum.copy_from_slice(&blake3::hash(data).as_bytes()[..CHECKSUM_LEN]);
+    checksum
+}
+
+fn base32_encode(data: &[u8]) -> String {
+    let mut encoded = String::with_capacity((data.len() * 8 + 4) / 5);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for byte in data {
+        buffer = (buffer << 8) | u32::from(*byte);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            encoded.push(BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+        buffer &= (1 << bits) - 1;
+    }
+    if bits > 0 {
+        encoded.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+    encoded
+}
+
+fn base32_decode(encoded: &str) -> Option<Vec<u8>> {
+    let mut data = Vec::with_capacity(encoded.len() * 5 / 8);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for c in encoded.bytes() {
+        let value = BASE32_ALPHABET.iter().position(|a| *a == c)? as u32;
+        buffer = (buffer << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            data.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    Some(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chain_core::init::coin::Coin;
+    use chain_core::tx::data::attribute::TxAttributes;
+    use chain_core::tx::data::input::TxoPointer;
+    use chain_core::tx::data::output::TxOut;
+    use chain_core::tx::fee::{LinearFee, Milli};
+    use client_common::{MultiSigAddress, PrivateKey};
+
+    use crate::transaction_builder::{PayloadSigningKey, RawTransferTransactionBuilder};
+
+    #[test]
+    fn check_offline_signing_flow() {
+        let private_key = PrivateKey::new().unwrap();
+        let public_key = PublicKey::from(&private_key);
+        let address =
+            MultiSigAddress::new(vec![public_key.clone()], public_key.clone(), 1).unwrap();
+        let proof = address
+            .generate_proof(vec![public_key.clone()])
+            .unwrap()
+            .unwrap();
+
+        let fee_algorithm =
+            LinearFee::new(Milli::try_new(1, 1).unwrap(), Milli::try_new(1, 1).unwrap());
+        let mut builder =
+            RawTransferTransactionBuilder::new(TxAttributes::default(), fee_algorithm);
+        builder.add_input(
+            (
+                TxoPointer::new([1; 32], 0),
+                TxOut::new(address.to_extended_addr(), Coin::new(500).unwrap()),
+            ),
+            1,
+        );
+        builder.add_output(TxOut::new(
+            address.to_extended_addr(),
+            Coin::new(400).unwrap(),
+        ));
+        let signing_key = PayloadSigningKey { public_key, proof };
+        let payload = builder.to_signing_payload(vec![signing_key], Coin::new(400).unwrap());
+        assert_eq!(Coin::new(100).unwrap(), payload.fee().unwrap());
+
+        // payload travels to the air-gapped signer in (shuffled) QR code parts
+        let mut parts = payload.to_qr_parts(40);
+        assert!(parts.len() > 1);
+        parts.reverse();
+        assert!(SigningPayload::from_qr_parts(&parts[1..]).is_err());
+        let received = SigningPayload::from_qr_parts(&parts).unwrap();
+        assert_eq!(payload.tx_id(), received.tx_id());
+
+        let mut corrupted = parts.clone();
+        let data_start = corrupted[1].rfind(':').unwrap() + 1;
+        let replaced = if corrupted[1][data_start..].starts_with('A') {
+            "B"
+        } else {
+            "A"
+        };
+        corrupted[1].replace_range(data_start..=data_start, replaced);
+        assert!(SigningPayload::from_qr_parts(&corrupted).is_err());
+
+        let other_key = PrivateKey::new().unwrap();
+        assert_eq!(
+            ErrorKind::VerifyError,
+            sign_offline(&received, |_| Ok(Box::new(other_key.clone())))
+                .unwrap_err()
+                .kind()
+        );
+        let signature = sign_offline(&received, |_| Ok(Box::new(private_key.clone()))).unwrap();
+
+        // signature travels back to the watch-only wallet
+        let parts = signature.to_qr_parts(DEFAULT_QR_PART_LEN);
+        let returned = OfflineSignature::from_qr_parts(&parts).unwrap();
+        assert_eq!(signature, returned);
+        let witness = returned.into_witness(&payload).unwrap();
+        assert!(RawTransferTransactionBuilder::from_signing_payload(
+            payload,
+            witness,
+            LinearFee::new(Milli::try_new(1, 1).unwrap(), Milli::try_new(1, 1).unwrap()),
+        )
+        .unwrap()
+        .is_completed());
+    }
+}
@@ -6,19 +6,21 @@ use std::string::ToString;
 
 use parity_scale_codec::{Decode, Encode};
 
+use chain_core::common::Proof;
 use chain_core::init::coin::{sum_coins, Coin};
 use chain_core::tx::data::attribute::TxAttributes;
 use chain_core::tx::data::input::TxoPointer;
 use chain_core::tx::data::output::TxOut;
 use chain_core::tx::data::{Tx, TxId};
 use chain_core::tx::fee::FeeAlgorithm;
+use chain_core::tx::witness::tree::RawXOnlyPubkey;
 use chain_core::tx::witness::{TxInWitness, TxWitness};
 use chain_core::tx::{TransactionId, TxAux};
 use chain_tx_validation::witness::verify_tx_address;
 use chain_tx_validation::{check_inputs_basic, check_outputs_basic};
 use client_common::{
-    Error, ErrorKind, PublicKey, Result, ResultExt, SignedTransaction, Transaction,
-    TransactionObfuscation,
+    Error, ErrorKind, PrivateKeyAction, PublicKey, Result, ResultExt, SignedTransaction,
+    Transaction, TransactionObfuscation,
 };
 
 use crate::signer::{DummySigner, SignCondition, Signer};
@@ -124,6 +126,128 @@ impl FromStr for SignedTransferTransaction {
     }
 }
 
+/// Key signing an input of a `SigningPayload`
+#[derive(Debug, Clone, Decode, Encode)]
+pub struct PayloadSigningKey {
+    /// public key whose private key signs the transaction id
+    pub public_key: PublicKey,
+    /// merkle proof of the public key in the input address
+    pub proof: Proof<RawXOnlyPubkey>,
+}
+
+/// Transfer transaction built by a `watch-only` wallet (inputs selected, change and fee computed),
+/// to be signed by an offline signer (or a hardware wallet) holding the private keys.
+/// The offline signer only signs the transaction id, and the `watch-only` wallet
+/// then obfuscates and broadcasts the transaction with the returned witness
+#[derive(Debug, Clone, Decode, Encode)]
+pub struct SigningPayload {
+    /// unsigned transaction
+    pub raw_transaction: RawTransferTransaction,
+    /// signing keys of the inputs (in the order of the inputs)
+    pub signing_keys: Vec<PayloadSigningKey>,
+    /// the return amount of coin
+    pub return_amount: Coin,
+}
+
+impl SigningPayload {
+    /// Returns the transaction to be signed
+    pub fn transaction(&self) -> Transaction {
+        Transaction::TransferTransaction(self.raw_transaction.to_tx())
+    }
+
+    /// Returns the transaction id (the signed message)
+    pub fn tx_id(&self) -> TxId {
+        self.raw_transaction.to_tx().id()
+    }
+
+    /// Returns the spent outputs (with their addresses and amounts)
+    pub fn inputs(&self) -> &[WitnessedUTxO] {
+        &self.raw_transaction.inputs
+    }
+
+    /// Returns the outputs of the transaction (including the change)
+    pub fn outputs(&self) -> &[TxOut] {
+        &self.raw_transaction.outputs
+    }
+
+    /// Returns the fee paid by the transaction (the spent amount not in the outputs)
+    pub fn fee(&self) -> Result<Coin> {
+        let inputs =
+            sum_coins(self.inputs().iter().map(|input| input.prev_tx_out.value)).chain(|| {
+                (
+                    ErrorKind::IllegalInput,
+                    "Sum of inputs exceeds maximum allowed",
+                )
+            })?;
+        let outputs = sum_coins(self.outputs().iter().map(|output| output.value)).chain(|| {
+            (
+                ErrorKind::IllegalInput,
+                "Sum of outputs exceeds maximum allowed",
+            )
+        })?;
+        (inputs - outputs).chain(|| {
+            (
+                ErrorKind::IllegalInput,
+                "Sum of outputs exceeds the sum of inputs",
+            )
+        })
+    }
+
+    /// Returns the inputs spent by the transaction
+    pub fn used_inputs(&self) -> Vec<TxoPointer> {
+        self.raw_transaction
+            .inputs
+            .iter()
+            .map(|input| input.prev_txo_pointer.clone())
+            .collect()
+    }
+
+    /// Signs all the inputs with the signing keys returned by `find_key`
+    /// (on the offline signer, e.g. from its key storage or a hardware wallet)
+    pub fn sign<K>(&self, find_key: K) -> Result<TxWitness>
+    where
+        K: Fn(&PublicKey) -> Result<Box<dyn PrivateKeyAction>>,
+    {
+        let tx = self.transaction();
+        self.signing_keys
+            .iter()
+            .map(|signing_key| {
+                let sign_key = find_key(&signing_key.public_key)?;
+                Ok(TxInWitness::TreeSig(
+                    sign_key.schnorr_sign(&tx)?,
+                    signing_key.proof.clone(),
+                ))
+            })
+            .collect()
+    }
+}
+
+impl ToString for SigningPayload {
+    fn to_string(&self) -> String {
+        let raw_data = self.encode();
+        base64::encode(&raw_data)
+    }
+}
+
+impl FromStr for SigningPayload {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let raw_data = base64::decode(s).chain(|| {
+            (
+                ErrorKind::DeserializationError,
+                "Unable to decode signing payload",
+            )
+        })?;
+        Self::decode(&mut raw_data.as_slice()).chain(|| {
+            (
+                ErrorKind::DeserializationError,
+                "Unable to deserialize signing payload",
+            )
+        })
+    }
+}
+
 /// Raw transfer transaction data structure
 #[derive(Debug, Clone, Decode, Encode)]
 pub struct RawTransferTransaction {
@@ -132,6 +256,20 @@ pub struct RawTransferTransaction {
     attributes: TxAttributes,
 }
 
+impl RawTransferTransaction {
+    fn to_tx(&self) -> Tx {
+        Tx {
+            inputs: self
+                .inputs
+                .iter()
+                .map(|input| input.prev_txo_pointer.clone())
+                .collect(),
+            outputs: self.outputs.clone(),
+            attributes: self.attributes.clone(),
+        }
+    }
+}
+
 /// Raw transfer transaction builder
 #[derive(Debug)]
 pub struct RawTransferTransactionBuilder<F>
@@ -287,6 +425,38 @@ where
         Ok(())
     }
 
+    /// Adds witnesses collected in any order (e.g. from several parties): each witness is placed
+    /// at all the unsigned inputs whose address it signs for (so inputs spent from the same address
+    /// share one witness) and duplicate witnesses are ignored. Returns the number of inputs signed.
+    pub fn add_witnesses<I>(&mut self, witnesses: I) -> Result<usize>
+    where
+        I: IntoIterator<Item = TxInWitness>,
+    {
+        let tx_id = self.tx_id();
+        let mut signed = 0;
+        for witness in witnesses {
+            let mut matched = false;
+            for input in self.raw_transaction.inputs.iter_mut() {
+                if verify_tx_address(&witness, &tx_id, &input.prev_tx_out.address).is_err() {
+                    continue;
+                }
+                matched = true;
+                if input.witness.is_none() {
+                    input.witness = Some(witness.clone());
+                    signed += 1;
+                }
+            }
+            if !matched {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "Signature does not match any of the inputs",
+                ));
+            }
+        }
+
+        Ok(signed)
+    }
+
     /// Get mutable input at provided index
     fn mut_input_at_index(&mut self, index: usize) -> Result<&mut WitnessedUTxO> {
         if self.inputs_len() < index {
@@ -346,11 +516,21 @@ where
         Ok(fee)
     }
 
-    /// Estimate transaction fee with dummy signatures
-    pub fn estimate_fee(&self) -> Result<Coin> {
+    /// Mock transaction with dummy signatures (same size as the signed one)
+    fn mock_tx_aux(&self) -> Result<TxAux> {
         let dummy_signer = DummySigner();
         let witness = dummy_signer.schnorr_sign_inputs_len(&self.raw_transaction.inputs)?;
-        let tx_aux = dummy_signer.mock_txaux_for_tx(self.to_tx(), witness);
+        Ok(dummy_signer.mock_txaux_for_tx(self.to_tx(), witness))
+    }
+
+    /// Estimate serialized transaction size with dummy signatures
+    pub fn estimate_size(&self) -> Result<usize> {
+        Ok(self.mock_tx_aux()?.encode().len())
+    }
+
+    /// Estimate transaction fee with dummy signatures
+    pub fn estimate_fee(&self) -> Result<Coin> {
+        let tx_aux = self.mock_tx_aux()?;
         let estimated_fee = self
             .fee_algorithm
             .calculate_for_txaux(&tx_aux)
@@ -508,14 +688,7 @@ where
     }
 
     fn to_tx(&self) -> Tx {
-        Tx {
-            inputs: self
-                .iter_inputs()
-                .map(|input| input.prev_txo_pointer.clone())
-                .collect(),
-            outputs: self.raw_transaction.outputs.clone(),
-            attributes: self.raw_transaction.attributes.clone(),
-        }
+        self.raw_transaction.to_tx()
     }
 
     /// Returns  transaction
@@ -529,6 +702,43 @@ where
         self.raw_transaction.encode()
     }
 
+    /// Signing payload of the unsigned transaction, for an offline signer
+    pub fn to_signing_payload(
+        &self,
+        signing_keys: Vec<PayloadSigningKey>,
+        return_amount: Coin,
+    ) -> SigningPayload {
+        SigningPayload {
+            raw_transaction: self.raw_transaction.clone(),
+            signing_keys,
+            return_amount,
+        }
+    }
+
+    /// Create raw transaction builder from a signing payload and the witness of the offline signer
+    /// (one input witness per input, in the order of the inputs)
+    pub fn from_signing_payload(
+        payload: SigningPayload,
+        witness: TxWitness,
+        fee_algorithm: F,
+    ) -> Result<Self> {
+        if witness.len() != payload.raw_transaction.inputs.len() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Number of input witnesses does not match the number of inputs",
+            ));
+        }
+        let mut builder = RawTransferTransactionBuilder {
+            raw_transaction: payload.raw_transaction,
+            fee_algorithm,
+        };
+        for (index, input_witness) in witness.iter().enumerate() {
+            builder.add_witness(index, input_witness.clone())?;
+        }
+
+        Ok(builder)
+    }
+
     /// Create raw transaction builder from encoded incompleted raw transaction bytes
     pub fn from_incomplete(bytes: Vec<u8>, fee_algorithm: F) -> Result<Self> {
         let raw_transaction =
@@ -715,6 +925,40 @@ mod raw_transfer_transaction_builder_tests {
         }
     }
 
+    mod add_witnesses {
+        use super::*;
+
+        #[test]
+        fn should_fill_inputs_with_same_address() {
+            let (private_key, public_key, transfer_addr) = create_key_pair_and_transfer_addr();
+            let mut builder = create_2in2out_testing_raw_transaction_builder(transfer_addr);
+
+            let witness =
+                create_public_key_witness(private_key, public_key, &builder.to_transaction());
+            let signed = builder
+                .add_witnesses(vec![witness.clone(), witness])
+                .expect("should add witnesses to builder");
+
+            assert_eq!(2, signed);
+            assert!(builder.is_completed());
+            assert!(builder.verify().is_ok());
+        }
+
+        #[test]
+        fn should_return_error_when_witness_matches_no_input() {
+            let (_, _, transfer_addr) = create_key_pair_and_transfer_addr();
+            let (private_key, public_key, _) = create_key_pair_and_transfer_addr();
+            let mut builder = create_2in2out_testing_raw_transaction_builder(transfer_addr);
+
+            let witness =
+                create_public_key_witness(private_key, public_key, &builder.to_transaction());
+            let err = builder.add_witnesses(vec![witness]).unwrap_err();
+
+            assert_eq!(err.kind(), ErrorKind::InvalidInput);
+            assert!(!builder.is_completed());
+        }
+    }
+
     mod add_input {
         use super::*;
 
@@ -1197,6 +1441,52 @@ mod raw_transfer_transaction_builder_tests {
         assert_eq!(restored_raw_transaction_builder.is_completed(), false);
     }
 
+    #[test]
+    fn test_signing_payload_flow() {
+        let (private_key, public_key, transfer_addr) = create_key_pair_and_transfer_addr();
+        let builder = create_2in2out_testing_raw_transaction_builder(transfer_addr);
+
+        let proof = match create_public_key_witness(
+            private_key.clone(),
+            public_key.clone(),
+            &builder.to_transaction(),
+        ) {
+            TxInWitness::TreeSig(_, proof) => proof,
+            _ => unreachable!("public key witness is a tree signature"),
+        };
+        let signing_key = PayloadSigningKey { public_key, proof };
+        let payload = builder.to_signing_payload(
+            vec![signing_key.clone(), signing_key],
+            Coin::new(100).unwrap(),
+        );
+        assert_eq!(builder.to_transaction().id(), payload.tx_id());
+
+        let payload = SigningPayload::from_str(&payload.to_string()).unwrap();
+        let witness = payload
+            .sign(|_| Ok(Box::new(private_key.clone())))
+            .expect("should sign signing payload");
+
+        let signed = RawTransferTransactionBuilder::from_signing_payload(
+            payload.clone(),
+            witness.clone(),
+            create_testing_fee_algorithm(),
+        )
+        .expect("should add witnesses");
+        assert!(signed.is_completed());
+
+        let partial_witness: TxWitness = witness.iter().take(1).cloned().collect();
+        assert_eq!(
+            RawTransferTransactionBuilder::from_signing_payload(
+                payload,
+                partial_witness,
+                create_testing_fee_algorithm(),
+            )
+            .unwrap_err()
+            .kind(),
+            ErrorKind::InvalidInput
+        );
+    }
+
     fn create_2in2out_testing_raw_transaction_builder(
         transfer_addr: ExtendedAddr,
     ) -> RawTransferTransactionBuilder<LinearFee> {
@@ -3,11 +3,15 @@ use chain_core::tx::data::address::ExtendedAddr;
 use chain_core::tx::data::attribute::TxAttributes;
 use chain_core::tx::data::input::TxoPointer;
 use chain_core::tx::data::output::TxOut;
+use chain_core::tx::witness::TxWitness;
 use chain_core::tx::TxAux;
 use client_common::{ErrorKind, PrivateKey, Result, SecKey, SignedTransaction, Transaction};
 
+use crate::coin_selection::CoinSelectionStrategy;
+use crate::transaction_builder::{SigningPayload, TransferDryRun};
 use crate::{UnspentTransactions, WalletTransactionBuilder};
-use chain_core::tx::data::TxId;
+use chain_core::tx::data::{Tx, TxId};
+use std::sync::Arc;
 
 /// Implementation of `WalletTransactionBuilder` which always returns
 /// permission denied
@@ -15,6 +19,10 @@ use chain_core::tx::data::TxId;
 pub struct UnauthorizedWalletTransactionBuilder;
 
 impl WalletTransactionBuilder for UnauthorizedWalletTransactionBuilder {
+    fn with_coin_selection(&self, _: Arc<dyn CoinSelectionStrategy>) -> Self {
+        *self
+    }
+
     fn build_transfer_tx(
         &self,
         _: &str,
@@ -27,6 +35,36 @@ impl WalletTransactionBuilder for UnauthorizedWalletTransactionBuilder {
         Err(ErrorKind::PermissionDenied.into())
     }
 
+    fn build_signing_payload(
+        &self,
+        _: &str,
+        _: &SecKey,
+        _: UnspentTransactions,
+        _: Vec<TxOut>,
+        _: ExtendedAddr,
+        _: TxAttributes,
+    ) -> Result<SigningPayload> {
+        Err(ErrorKind::PermissionDenied.into())
+    }
+
+    fn finalize_signing_payload(&self, _: SigningPayload, _: TxWitness) -> Result<TxAux> {
+        Err(ErrorKind::PermissionDenied.into())
+    }
+
+    fn dry_run_transfer_tx(
+        &self,
+        _: &UnspentTransactions,
+        _: Vec<TxOut>,
+        _: ExtendedAddr,
+        _: TxAttributes,
+    ) -> Result<TransferDryRun> {
+        Err(ErrorKind::PermissionDenied.into())
+    }
+
+    fn transfer_fee(&self, _: &Tx, _: &TxWitness) -> Result<Coin> {
+        Err(ErrorKind::PermissionDenied.into())
+    }
+
     fn obfuscate(&self, _: SignedTransaction) -> Result<TxAux> {
         Err(ErrorKind::PermissionDenied.into())
     }
@@ -1,13 +1,54 @@
 //! Types used in `client-core`
+mod address_ownership;
 mod address_type;
+mod annotation;
+mod contact;
+mod data_anchor;
+mod fee_estimate;
+mod history_query;
+mod inheritance;
+mod invoice;
+mod job;
+mod recurring_payment;
+mod spendability;
+mod spending_policy;
+mod transfer_options;
+mod vault;
+mod wallet_metadata;
 mod wallet_type;
 
 pub mod transaction_change;
 
-pub use self::address_type::AddressType;
+pub use self::address_ownership::OwnedAddress;
+pub use self::address_type::{parse_staking_address, AddressType};
+pub use self::annotation::{Annotation, WalletAnnotations, MAX_ANNOTATION_FIELD_LENGTH};
+pub use self::contact::{Contact, ContactAddress, MAX_CONTACT_NAME_LENGTH};
+pub use self::data_anchor::{data_anchor_commitment, DataAnchorInfo};
+pub use self::fee_estimate::FeeEstimate;
+pub use self::history_query::{
+    TransactionDirection, TransactionFilter, TransactionHistoryPage, TransactionIndexEntry,
+    TransactionKind, WalletEvent,
+};
+pub use self::inheritance::{
+    inheritance_address_tree, inheritance_claim_proof, split_inheritance, Heir, InheritanceEvent,
+    InheritanceEventKind, InheritancePackage, InheritancePlan, InheritanceStatus,
+};
+pub use self::invoice::{Invoice, InvoiceEvent, InvoiceEventKind, InvoicePayment, InvoiceStatus};
+pub use self::job::{Job, JobKind, JobProgress, JobStatus};
+pub use self::recurring_payment::{
+    RecurringPayment, RecurringPaymentEvent, RecurringPaymentEventKind, RecurringPaymentStatus,
+    RetryPolicy,
+};
+pub use self::spendability::{
+    Spendability, SpendabilityReport, UnconfirmedAmount, UtxoSpendability,
+};
+pub use self::spending_policy::{LargeOutputRule, SpendingPolicy};
 #[doc(inline)]
 pub use self::transaction_change::{
-    BalanceChange, TransactionChange, TransactionInput, TransactionPending, TransactionType,
-    WalletBalance,
+    BalanceChange, MempoolTransaction, TransactionChange, TransactionInput, TransactionPending,
+    TransactionType, WalletBalance,
 };
+pub use self::transfer_options::TransferOptions;
+pub use self::vault::{pending_address_tree, Vault, VaultAlert, VaultSpend, VaultSpendStatus};
+pub use self::wallet_metadata::{wallet_id, WalletEntry, WalletMetadata};
 pub use self::wallet_type::WalletKind;
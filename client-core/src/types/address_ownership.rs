@@ -0,0 +1,39 @@
+//! Types for reporting the ownership of an address by a wallet
+use serde::{Deserialize, Serialize};
+
+use chain_core::common::H256;
+use client_common::PublicKey;
+
+use super::transaction_change::{deserialize_transaction_id, serialize_transaction_id};
+
+/// Details of an address owned by the wallet
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum OwnedAddress {
+    /// staking address of the wallet
+    Staking {
+        /// public key the address is derived from
+        public_key: PublicKey,
+        /// HD derivation path of the key (none for basic wallets and imported or watched keys)
+        derivation_path: Option<String>,
+        /// `true` if the wallet can't sign for the address
+        watch_only: bool,
+    },
+    /// (multi-sig) transfer address of the wallet
+    Transfer {
+        /// root hash of the merkle tree of the address
+        #[serde(serialize_with = "serialize_transaction_id")]
+        #[serde(deserialize_with = "deserialize_transaction_id")]
+        root_hash: H256,
+        /// public key of the wallet among the signers
+        self_public_key: PublicKey,
+        /// number of required signers
+        required_signers: u64,
+        /// total number of signers
+        total_signers: u64,
+        /// HD derivation path of the wallet public key (none for basic wallets and imported or watched keys)
+        derivation_path: Option<String>,
+        /// `true` if the wallet can't sign for the address
+        watch_only: bool,
+    },
+}
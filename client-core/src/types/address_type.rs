@@ -2,6 +2,8 @@ use std::str::FromStr;
 
 use unicase::eq_ascii;
 
+use chain_core::init::address::ErrorAddress;
+use chain_core::state::account::StakedStateAddress;
 use client_common::{Error, ErrorKind, Result};
 
 /// Enum for specifying different types of addresses
@@ -35,3 +37,43 @@ impl Default for AddressType {
         Self::Transfer
     }
 }
+
+/// Parses a staking address entered by the user (lowercase, uppercase or EIP-55 checksummed)
+/// and rejects mixed-case addresses with a wrong checksum as `ErrorKind::InvalidChecksum`
+pub fn parse_staking_address(address: &str) -> Result<StakedStateAddress> {
+    StakedStateAddress::from_str(address.trim()).map_err(|err| match err {
+        ErrorAddress::InvalidChecksum(_) => Error::new(
+            ErrorKind::InvalidChecksum,
+            format!(
+                "Staking address checksum mismatch (mistyped address?): {}",
+                address
+            ),
+        ),
+        err => Error::new_with_source(
+            ErrorKind::DeserializationError,
+            format!("Unable to deserialize staking address ({})", address),
+            Box::new(err),
+        ),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_parse_staking_address() {
+        assert!(parse_staking_address("0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed").is_ok());
+        assert!(parse_staking_address("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed").is_ok());
+        assert_eq!(
+            ErrorKind::InvalidChecksum,
+            parse_staking_address("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAeD")
+                .unwrap_err()
+                .kind()
+        );
+        assert_eq!(
+            ErrorKind::DeserializationError,
+            parse_staking_address("0x5aaeb6053f").unwrap_err().kind()
+        );
+    }
+}
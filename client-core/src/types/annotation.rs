@@ -0,0 +1,87 @@
+//! Types for the local annotations (memo, label and category) of transactions and addresses
+use std::collections::BTreeMap;
+
+use parity_scale_codec::{Decode, Encode};
+use serde::{Deserialize, Serialize};
+
+use client_common::{Error, ErrorKind, Result};
+
+/// Maximum length (in bytes) of each field of an annotation
+pub const MAX_ANNOTATION_FIELD_LENGTH: usize = 256;
+
+/// Annotation attached by the wallet user to a transaction or an address (only kept in the
+/// local wallet storage, never broadcast)
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize, Encode, Decode)]
+#[serde(default)]
+pub struct Annotation {
+    /// Free-form note
+    pub memo: Option<String>,
+    /// Short name (e.g. of the counterparty)
+    pub label: Option<String>,
+    /// Category (e.g. for bookkeeping)
+    pub category: Option<String>,
+}
+
+impl Annotation {
+    /// Returns `true` if none of the fields is set (or they are all empty)
+    pub fn is_empty(&self) -> bool {
+        self.fields().all(|(_, value)| value.is_empty())
+    }
+
+    /// Checks the length of the fields
+    pub fn validate(&self) -> Result<()> {
+        match self
+            .fields()
+            .find(|(_, value)| value.len() > MAX_ANNOTATION_FIELD_LENGTH)
+        {
+            Some((field, _)) => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Annotation {} should not be longer than {} bytes",
+                    field, MAX_ANNOTATION_FIELD_LENGTH
+                ),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    fn fields(&self) -> impl Iterator<Item = (&'static str, &str)> {
+        vec![
+            ("memo", &self.memo),
+            ("label", &self.label),
+            ("category", &self.category),
+        ]
+        .into_iter()
+        .filter_map(|(field, value)| value.as_deref().map(|value| (field, value)))
+    }
+}
+
+/// Annotations of a wallet (e.g. in the wallet export)
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WalletAnnotations {
+    /// Hex encoded transaction ID -> annotation
+    pub transactions: BTreeMap<String, Annotation>,
+    /// Address -> annotation
+    pub addresses: BTreeMap<String, Annotation>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_annotation_validation() {
+        let mut annotation = Annotation::default();
+        assert!(annotation.is_empty());
+        annotation.label = Some(String::new());
+        assert!(annotation.is_empty());
+
+        annotation.memo = Some("rent".to_owned());
+        assert!(!annotation.is_empty());
+        assert!(annotation.validate().is_ok());
+
+        annotation.category = Some("x".repeat(MAX_ANNOTATION_FIELD_LENGTH + 1));
+        assert!(annotation.validate().is_err());
+    }
+}
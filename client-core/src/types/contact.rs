@@ -0,0 +1,185 @@
+//! Types for the address book (named recipients) of a wallet
+use std::fmt;
+use std::str::FromStr;
+
+use parity_scale_codec::{Decode, Encode};
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use chain_core::init::address::CroAddress;
+use chain_core::init::network::{get_bech32_human_part_from_network, get_network, Network};
+use chain_core::state::account::StakedStateAddress;
+use chain_core::tx::data::address::ExtendedAddr;
+use client_common::{Error, ErrorKind, Result};
+
+use super::{parse_staking_address, MAX_ANNOTATION_FIELD_LENGTH};
+
+/// Maximum length (in bytes) of the name of a contact
+pub const MAX_CONTACT_NAME_LENGTH: usize = 64;
+
+/// Address of a contact
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+pub enum ContactAddress {
+    /// Transfer address (bech32)
+    Transfer(ExtendedAddr),
+    /// Staking address (hex)
+    Staking(StakedStateAddress),
+}
+
+impl ContactAddress {
+    /// Parses a bech32 transfer address or a hex staking address. Transfer addresses of another
+    /// network (e.g. a `tcro` address for a wallet on the mainnet) are rejected.
+    pub fn parse(address: &str, network: Network) -> Result<Self> {
+        let address = address.trim();
+        let expected_prefix = get_bech32_human_part_from_network(network);
+        let prefix = address
+            .rfind('1')
+            .map(|separator| address[..separator].to_lowercase());
+
+        match prefix {
+            Some(prefix) if prefix == expected_prefix => ExtendedAddr::from_cro(address, network)
+                .map(ContactAddress::Transfer)
+                .map_err(|err| {
+                    Error::new(
+                        ErrorKind::DeserializationError,
+                        format!("Invalid transfer address ({}): {}", address, err),
+                    )
+                }),
+            Some(prefix)
+                if [Network::Mainnet, Network::Testnet, Network::Devnet]
+                    .iter()
+                    .any(|other| get_bech32_human_part_from_network(*other) == prefix) =>
+            {
+                Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!(
+                        "Transfer address ({}) belongs to another network (expected a {} address)",
+                        address, expected_prefix
+                    ),
+                ))
+            }
+            _ => parse_staking_address(address).map(ContactAddress::Staking),
+        }
+    }
+}
+
+impl fmt::Display for ContactAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ContactAddress::Transfer(address) => write!(f, "{}", address),
+            ContactAddress::Staking(address) => write!(f, "{}", address),
+        }
+    }
+}
+
+impl FromStr for ContactAddress {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        ContactAddress::parse(s, get_network())
+    }
+}
+
+impl Serialize for ContactAddress {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for ContactAddress {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let address = String::deserialize(deserializer)?;
+        address.parse().map_err(D::Error::custom)
+    }
+}
+
+/// Named recipient in the address book of a wallet (only kept in the local wallet storage)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Encode, Decode)]
+pub struct Contact {
+    /// Name of the contact (unique in the address book)
+    pub name: String,
+    /// Address of the contact
+    pub address: ContactAddress,
+    /// Free-form note
+    #[serde(default)]
+    pub memo: Option<String>,
+}
+
+impl Contact {
+    /// Checks the name and the memo of the contact
+    pub fn validate(&self) -> Result<()> {
+        if self.name.trim().is_empty() || self.name.trim() != self.name {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Contact name should not be empty or start/end with whitespaces",
+            ));
+        }
+        if self.name.len() > MAX_CONTACT_NAME_LENGTH {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Contact name should not be longer than {} bytes",
+                    MAX_CONTACT_NAME_LENGTH
+                ),
+            ));
+        }
+        if self.memo.as_ref().map_or(0, String::len) > MAX_ANNOTATION_FIELD_LENGTH {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Contact memo should not be longer than {} bytes",
+                    MAX_ANNOTATION_FIELD_LENGTH
+                ),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_parse_contact_address() {
+        let address = ExtendedAddr::OrTree([1; 32]);
+        let devnet_address = address.to_cro(Network::Devnet).unwrap();
+        let testnet_address = address.to_cro(Network::Testnet).unwrap();
+
+        assert_eq!(
+            ContactAddress::Transfer(address),
+            ContactAddress::parse(&devnet_address, Network::Devnet).unwrap()
+        );
+        assert_eq!(
+            ErrorKind::InvalidInput,
+            ContactAddress::parse(&testnet_address, Network::Devnet)
+                .unwrap_err()
+                .kind()
+        );
+        assert_eq!(
+            ErrorKind::InvalidInput,
+            ContactAddress::parse(&devnet_address, Network::Mainnet)
+                .unwrap_err()
+                .kind()
+        );
+        assert_eq!(
+            ErrorKind::DeserializationError,
+            ContactAddress::parse(&devnet_address[..devnet_address.len() - 1], Network::Devnet)
+                .unwrap_err()
+                .kind()
+        );
+
+        let staking_address = "0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed";
+        assert_eq!(
+            ContactAddress::Staking(StakedStateAddress::from_str(staking_address).unwrap()),
+            ContactAddress::parse(staking_address, Network::Mainnet).unwrap()
+        );
+        assert!(ContactAddress::parse("cro", Network::Mainnet).is_err());
+    }
+}
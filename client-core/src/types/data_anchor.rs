@@ -0,0 +1,53 @@
+//! Types for anchoring commitments to external data in the chain
+use parity_scale_codec::Encode;
+use serde::{Deserialize, Serialize};
+
+use chain_core::common::H256;
+use chain_core::state::account::DataAnchorProof;
+use chain_core::tx::TransactionId;
+
+/// Commitment of the external data (e.g. a document) to be anchored (blake3 hash of the data)
+#[inline]
+pub fn data_anchor_commitment(data: &[u8]) -> H256 {
+    blake3::hash(data).into()
+}
+
+/// Anchoring of a commitment, as proven by the chain
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DataAnchorInfo {
+    /// hex encoded commitment
+    pub commitment: String,
+    /// staking address which paid the fee
+    pub staking_address: String,
+    /// hex encoded ID of the anchoring transaction
+    pub transaction_id: String,
+    /// the block the anchoring transaction was included in
+    pub block_height: u64,
+    /// hex encoded inclusion proof (SCALE encoded `DataAnchorProof`), which can be verified
+    /// against the app hash in the header of the next block
+    pub proof: String,
+}
+
+impl From<&DataAnchorProof> for DataAnchorInfo {
+    fn from(proof: &DataAnchorProof) -> Self {
+        DataAnchorInfo {
+            commitment: hex::encode(&proof.record.tx.commitment),
+            staking_address: proof.record.tx.from_staked_account.to_string(),
+            transaction_id: hex::encode(proof.record.tx.id()),
+            block_height: proof.record.height.value(),
+            proof: hex::encode(proof.encode()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_data_anchor_commitment() {
+        let commitment = data_anchor_commitment(b"document");
+        assert_eq!(commitment, data_anchor_commitment(b"document"));
+        assert_ne!(commitment, data_anchor_commitment(b"documents"));
+    }
+}
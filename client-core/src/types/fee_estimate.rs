@@ -0,0 +1,23 @@
+//! Types for transfer fee estimation
+use serde::{Deserialize, Serialize};
+
+use chain_core::init::coin::Coin;
+use chain_core::tx::data::input::TxoPointer;
+
+use crate::transaction_builder::ChangeOutcome;
+
+/// Estimated fee of a transfer transaction from the wallet (nothing is signed or broadcast)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FeeEstimate {
+    /// fee of the network linear fee policy for the estimated size
+    pub fee: Coin,
+    /// estimated serialized size of the signed transaction (in bytes)
+    pub size: u64,
+    /// inputs the transaction would spend
+    pub inputs: Vec<TxoPointer>,
+    /// suggested change split
+    pub change: ChangeOutcome,
+    /// wallet outputs not considered, as they are already spent by unconfirmed transactions
+    /// in the mempool
+    pub excluded_inputs: Vec<TxoPointer>,
+}
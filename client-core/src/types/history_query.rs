@@ -0,0 +1,195 @@
+//! Types for paged and filtered queries of the transaction history
+use parity_scale_codec::{Decode, Encode};
+use serde::{Deserialize, Serialize};
+
+use chain_core::init::coin::Coin;
+use chain_core::tx::data::TxId;
+
+use super::transaction_change::{BalanceChange, TransactionChange, TransactionType};
+
+/// Direction of the balance change caused by a transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TransactionDirection {
+    /// Balance addition
+    Incoming,
+    /// Balance reduction
+    Outgoing,
+}
+
+/// Kind of a transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TransactionKind {
+    /// Transfer transaction
+    Transfer,
+    /// Staking operation (deposit, unbond, withdraw, unjail, node join)
+    Staking,
+}
+
+impl From<TransactionType> for TransactionKind {
+    fn from(transaction_type: TransactionType) -> TransactionKind {
+        match transaction_type {
+            TransactionType::Transfer => TransactionKind::Transfer,
+            _ => TransactionKind::Staking,
+        }
+    }
+}
+
+/// Entry of the transaction history index of a wallet (one per transaction, in the order of
+/// the transaction log)
+#[derive(Debug, Clone, PartialEq, Encode, Decode)]
+pub struct TransactionIndexEntry {
+    /// Transaction ID
+    pub transaction_id: TxId,
+    /// Height of block which has this transaction
+    pub block_height: u64,
+    /// Balance change caused by transaction
+    pub balance_change: BalanceChange,
+    /// Transaction type
+    pub transaction_type: TransactionType,
+}
+
+impl From<&TransactionChange> for TransactionIndexEntry {
+    fn from(change: &TransactionChange) -> TransactionIndexEntry {
+        TransactionIndexEntry {
+            transaction_id: change.transaction_id,
+            block_height: change.block_height,
+            balance_change: change.balance_change,
+            transaction_type: change.transaction_type,
+        }
+    }
+}
+
+/// Filter of transaction history queries (all the conditions have to match, unset conditions
+/// match everything)
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TransactionFilter {
+    /// Direction of the balance change
+    pub direction: Option<TransactionDirection>,
+    /// Minimum amount of the balance change (inclusive)
+    pub min_amount: Option<Coin>,
+    /// Maximum amount of the balance change (inclusive)
+    pub max_amount: Option<Coin>,
+    /// Minimum block height (inclusive)
+    pub min_block_height: Option<u64>,
+    /// Maximum block height (inclusive)
+    pub max_block_height: Option<u64>,
+    /// Kind of the transaction
+    pub kind: Option<TransactionKind>,
+}
+
+impl TransactionFilter {
+    /// Returns `true` if the indexed transaction matches the filter (transactions which do not
+    /// change the balance never match)
+    pub fn matches(&self, entry: &TransactionIndexEntry) -> bool {
+        let (direction, amount) = match entry.balance_change {
+            BalanceChange::Incoming { value } => (TransactionDirection::Incoming, value),
+            BalanceChange::Outgoing { value } => (TransactionDirection::Outgoing, value),
+            BalanceChange::NoChange => return false,
+        };
+
+        self.direction.map_or(true, |d| d == direction)
+            && self.min_amount.map_or(true, |min| amount >= min)
+            && self.max_amount.map_or(true, |max| amount <= max)
+            && self
+                .min_block_height
+                .map_or(true, |min| entry.block_height >= min)
+            && self
+                .max_block_height
+                .map_or(true, |max| entry.block_height <= max)
+            && self
+                .kind
+                .map_or(true, |kind| kind == entry.transaction_type.into())
+    }
+}
+
+/// Page of a transaction history query
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TransactionHistoryPage {
+    /// Matched transactions
+    pub transactions: Vec<TransactionChange>,
+    /// Cursor to pass to the next query to continue after the last returned transaction
+    /// (`None` if there are no more matching transactions)
+    pub next_cursor: Option<u64>,
+}
+
+/// Event of the wallet event stream: a transaction added to the wallet, numbered by its
+/// position in the transaction log of the wallet
+///
+/// Sequence numbers are persistent, so downstream systems can resume the stream after the last
+/// processed event; they restart from 0 when the wallet state is replaced (e.g. synced again
+/// from genesis).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WalletEvent {
+    /// Sequence number
+    pub sequence: u64,
+    /// Added transaction
+    pub transaction: TransactionChange,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(
+        balance_change: BalanceChange,
+        transaction_type: TransactionType,
+    ) -> TransactionIndexEntry {
+        TransactionIndexEntry {
+            transaction_id: [0; 32],
+            block_height: 10,
+            balance_change,
+            transaction_type,
+        }
+    }
+
+    #[test]
+    fn check_transaction_filter() {
+        let incoming = entry(
+            BalanceChange::Incoming {
+                value: Coin::new(100).unwrap(),
+            },
+            TransactionType::Transfer,
+        );
+        let deposit = entry(
+            BalanceChange::Outgoing {
+                value: Coin::new(50).unwrap(),
+            },
+            TransactionType::Deposit,
+        );
+        let no_change = entry(BalanceChange::NoChange, TransactionType::Unjail);
+
+        let filter = TransactionFilter::default();
+        assert!(filter.matches(&incoming));
+        assert!(filter.matches(&deposit));
+        assert!(!filter.matches(&no_change));
+
+        let filter = TransactionFilter {
+            direction: Some(TransactionDirection::Outgoing),
+            ..Default::default()
+        };
+        assert!(!filter.matches(&incoming));
+        assert!(filter.matches(&deposit));
+
+        let filter = TransactionFilter {
+            min_amount: Some(Coin::new(60).unwrap()),
+            max_amount: Some(Coin::new(100).unwrap()),
+            ..Default::default()
+        };
+        assert!(filter.matches(&incoming));
+        assert!(!filter.matches(&deposit));
+
+        let filter = TransactionFilter {
+            min_block_height: Some(11),
+            ..Default::default()
+        };
+        assert!(!filter.matches(&incoming));
+        let filter = TransactionFilter {
+            max_block_height: Some(10),
+            kind: Some(TransactionKind::Staking),
+            ..Default::default()
+        };
+        assert!(!filter.matches(&incoming));
+        assert!(filter.matches(&deposit));
+    }
+}
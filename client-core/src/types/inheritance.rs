@@ -0,0 +1,297 @@
+//! Types for inheritance plans (dead-man switch)
+use parity_scale_codec::{Decode, Encode};
+use serde::{Deserialize, Serialize};
+
+use chain_core::common::{MerkleTree, Proof, Timespec};
+use chain_core::init::coin::Coin;
+use chain_core::tx::data::address::ExtendedAddr;
+use chain_core::tx::data::TxId;
+use chain_core::tx::witness::tree::{timelocked_leaf, RawXOnlyPubkey};
+use client_common::{Error, ErrorKind, PublicKey, Result, ResultExt};
+
+use super::transaction_change::{deserialize_transaction_id, serialize_transaction_id};
+
+/// Heir of an inheritance plan
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Encode, Decode)]
+pub struct Heir {
+    /// Address receiving the share of the heir
+    pub address: ExtendedAddr,
+    /// Share of the heir (relative to the shares of the other heirs)
+    pub share: u64,
+}
+
+/// Status of an inheritance plan
+///
+/// ```plain
+/// Unconfirmed -> Active -> RefreshDue -> Unlocked -> Claimed
+///                  |           |            |
+///                  +-----------+------------+--> Cancelled
+/// ```
+///
+/// Refreshing an active plan replaces its package and goes back to `Unconfirmed`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Encode, Decode)]
+#[serde(tag = "status")]
+pub enum InheritanceStatus {
+    /// Transaction funding the inheritance address of the package is broadcast, but not
+    /// synced yet
+    Unconfirmed,
+    /// Funds are on the inheritance address, waiting for the unlock time
+    Active,
+    /// The unlock time is close, the plan should be refreshed (reminder)
+    RefreshDue,
+    /// The unlock time passed without a refresh, the heirs can broadcast the package
+    Unlocked,
+    /// Funds are sent to the heirs
+    Claimed {
+        /// ID of the package transaction
+        #[serde(serialize_with = "serialize_transaction_id")]
+        #[serde(deserialize_with = "deserialize_transaction_id")]
+        transaction_id: TxId,
+    },
+    /// Funds of the inheritance address are spent by the owner
+    Cancelled {
+        /// ID of the cancelling transaction
+        #[serde(serialize_with = "serialize_transaction_id")]
+        #[serde(deserialize_with = "deserialize_transaction_id")]
+        transaction_id: TxId,
+    },
+}
+
+impl InheritanceStatus {
+    /// Returns `true` if the funds are still on the inheritance address
+    #[inline]
+    pub fn is_active(&self) -> bool {
+        match self {
+            InheritanceStatus::Claimed { .. } | InheritanceStatus::Cancelled { .. } => false,
+            _ => true,
+        }
+    }
+}
+
+/// Prepared package of an inheritance plan: the funds are held by an inheritance address of the
+/// owner key, and the pre-signed transaction sending them to the heirs is only valid from the
+/// unlock time (signed with the timelocked leaf of the owner key).
+///
+/// Until then, the owner can spend the funds with the plain leaf of the key, which cancels the
+/// package (refreshing moves them to a new inheritance address with a later unlock time).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Encode, Decode)]
+pub struct InheritancePackage {
+    /// Inheritance address holding the funds (the first output of the funding transaction)
+    pub address: ExtendedAddr,
+    /// ID of the transaction funding the inheritance address
+    #[serde(serialize_with = "serialize_transaction_id")]
+    #[serde(deserialize_with = "deserialize_transaction_id")]
+    pub funding_transaction_id: TxId,
+    /// Funds of the inheritance address
+    pub amount: Coin,
+    /// Time (seconds since the unix epoch, compared with the block times) from which
+    /// the package can be broadcast
+    pub unlock_time: Timespec,
+    /// ID of the pre-signed transaction
+    #[serde(serialize_with = "serialize_transaction_id")]
+    #[serde(deserialize_with = "deserialize_transaction_id")]
+    pub transaction_id: TxId,
+    /// Amounts sent to the heirs (in the order of the heirs of the plan)
+    pub heir_amounts: Vec<Coin>,
+    /// Pre-signed transaction (encoded `SignedTransaction`, obfuscated when it's broadcast);
+    /// it's only kept in the encrypted storage of the wallet and exported on request
+    #[serde(skip)]
+    pub transaction: Vec<u8>,
+}
+
+/// Inheritance plan of the wallet (dead-man switch)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Encode, Decode)]
+pub struct InheritancePlan {
+    /// Plan ID (sequential per wallet)
+    pub id: u64,
+    /// Key of the wallet holding the funds of the plan
+    pub owner_public_key: PublicKey,
+    /// Heirs receiving the funds
+    pub heirs: Vec<Heir>,
+    /// Time (in seconds) between a refresh and the unlock time of the package
+    pub refresh_period: Timespec,
+    /// Time (in seconds) before the unlock time from which the plan is due for a refresh
+    pub reminder_period: Timespec,
+    /// View keys of the transactions of the plan
+    pub view_keys: Vec<PublicKey>,
+    /// Current package
+    pub package: InheritancePackage,
+    /// Status
+    pub status: InheritanceStatus,
+}
+
+impl InheritancePlan {
+    /// Updates the status at the time of the latest block, returns the new status
+    /// if it changed
+    pub fn update_status(&mut self, block_time: Timespec) -> Option<InheritanceStatus> {
+        let status = match self.status {
+            InheritanceStatus::Active | InheritanceStatus::RefreshDue
+                if self.package.unlock_time <= block_time =>
+            {
+                InheritanceStatus::Unlocked
+            }
+            InheritanceStatus::Active
+                if self.package.unlock_time <= block_time.saturating_add(self.reminder_period) =>
+            {
+                InheritanceStatus::RefreshDue
+            }
+            _ => return None,
+        };
+        self.status = status.clone();
+        Some(status)
+    }
+
+    /// Proof of the plain leaf of the owner key in the inheritance address (refreshing or
+    /// cancelling the package)
+    pub fn owner_proof(&self) -> Option<Proof<RawXOnlyPubkey>> {
+        inheritance_address_tree(&self.owner_public_key, self.package.unlock_time)
+            .generate_proof(RawXOnlyPubkey::from(&self.owner_public_key))
+    }
+}
+
+/// Lifecycle event of an inheritance plan
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Encode, Decode)]
+pub enum InheritanceEventKind {
+    /// The plan was prepared
+    Prepared,
+    /// The package was replaced by one with a later unlock time
+    Refreshed {
+        /// Unlock time of the new package
+        unlock_time: Timespec,
+    },
+    /// The status of the plan changed
+    StatusChanged(InheritanceStatus),
+}
+
+/// Lifecycle event of an inheritance plan, with a sequence number (per wallet) for polling
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Encode, Decode)]
+pub struct InheritanceEvent {
+    /// Sequence number of the event
+    pub sequence: u64,
+    /// Plan ID
+    pub plan_id: u64,
+    /// Event
+    pub kind: InheritanceEventKind,
+}
+
+/// Merkle tree of an inheritance address: the owner key and its timelocked leaf
+pub fn inheritance_address_tree(
+    owner_public_key: &PublicKey,
+    unlock_time: Timespec,
+) -> MerkleTree<RawXOnlyPubkey> {
+    let owner_key = RawXOnlyPubkey::from(owner_public_key);
+    MerkleTree::new(vec![
+        owner_key.clone(),
+        timelocked_leaf(&owner_key, unlock_time),
+    ])
+}
+
+/// Proof of the timelocked leaf of the owner key in an inheritance address (signing
+/// the package)
+pub fn inheritance_claim_proof(
+    owner_public_key: &PublicKey,
+    unlock_time: Timespec,
+) -> Option<Proof<RawXOnlyPubkey>> {
+    let owner_key = RawXOnlyPubkey::from(owner_public_key);
+    inheritance_address_tree(owner_public_key, unlock_time)
+        .generate_proof(timelocked_leaf(&owner_key, unlock_time))
+}
+
+/// Splits the amount between the heirs in proportion to their shares (the remainder of
+/// the division goes to the first heir)
+pub fn split_inheritance(heirs: &[Heir], amount: Coin) -> Result<Vec<Coin>> {
+    let total_shares = heirs
+        .iter()
+        .try_fold(0u64, |total, heir| total.checked_add(heir.share))
+        .err_kind(ErrorKind::InvalidInput, || "Invalid shares of the heirs")?;
+    if total_shares == 0 {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "Shares of the heirs should be greater than zero",
+        ));
+    }
+
+    let amount = u128::from(u64::from(amount));
+    let mut amounts = heirs
+        .iter()
+        .map(|heir| (amount * u128::from(heir.share) / u128::from(total_shares)) as u64)
+        .collect::<Vec<_>>();
+    let remainder = amount as u64 - amounts.iter().sum::<u64>();
+    amounts[0] += remainder;
+
+    amounts
+        .into_iter()
+        .map(|value| {
+            if value == 0 {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "Amount of the inheritance plan is too small for the shares of the heirs",
+                ));
+            }
+            Coin::new(value).chain(|| (ErrorKind::InvalidInput, "Invalid amount of an heir"))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn heir(id: u8, share: u64) -> Heir {
+        Heir {
+            address: ExtendedAddr::OrTree([id; 32]),
+            share,
+        }
+    }
+
+    #[test]
+    fn check_split_inheritance() {
+        let amounts = split_inheritance(&[heir(1, 1), heir(2, 2)], Coin::new(100).unwrap());
+        assert_eq!(
+            vec![Coin::new(34).unwrap(), Coin::new(66).unwrap()],
+            amounts.unwrap()
+        );
+
+        assert!(split_inheritance(&[heir(1, 0)], Coin::new(100).unwrap()).is_err());
+        assert!(split_inheritance(&[heir(1, 1), heir(2, 1000)], Coin::new(100).unwrap()).is_err());
+    }
+
+    #[test]
+    fn check_status_update() {
+        let owner_public_key = PublicKey::from(&client_common::PrivateKey::new().unwrap());
+        let mut plan = InheritancePlan {
+            id: 0,
+            owner_public_key: owner_public_key.clone(),
+            heirs: vec![heir(1, 1)],
+            refresh_period: 100,
+            reminder_period: 10,
+            view_keys: vec![],
+            package: InheritancePackage {
+                address: ExtendedAddr::OrTree(
+                    inheritance_address_tree(&owner_public_key, 1100).root_hash(),
+                ),
+                funding_transaction_id: [0; 32],
+                amount: Coin::unit(),
+                unlock_time: 1100,
+                transaction_id: [1; 32],
+                heir_amounts: vec![Coin::unit()],
+                transaction: vec![],
+            },
+            status: InheritanceStatus::Unconfirmed,
+        };
+        assert!(plan.owner_proof().is_some());
+        assert!(inheritance_claim_proof(&owner_public_key, 1100).is_some());
+
+        // only confirmed plans are updated
+        assert_eq!(None, plan.update_status(2000));
+        plan.status = InheritanceStatus::Active;
+        assert_eq!(None, plan.update_status(1089));
+        assert_eq!(
+            Some(InheritanceStatus::RefreshDue),
+            plan.update_status(1090)
+        );
+        assert_eq!(None, plan.update_status(1099));
+        assert_eq!(Some(InheritanceStatus::Unlocked), plan.update_status(1100));
+        assert_eq!(None, plan.update_status(1200));
+    }
+}
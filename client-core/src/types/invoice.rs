@@ -0,0 +1,231 @@
+//! Types for tracking the payments of invoices
+use parity_scale_codec::{Decode, Encode};
+use serde::{Deserialize, Serialize};
+
+use chain_core::init::coin::Coin;
+use chain_core::tx::data::address::ExtendedAddr;
+use chain_core::tx::data::input::TxoSize;
+use chain_core::tx::data::TxId;
+use client_common::{ErrorKind, Result, ResultExt};
+
+use super::transaction_change::{deserialize_transaction_id, serialize_transaction_id};
+
+/// Status of an invoice
+///
+/// ```plain
+/// Open -> PartiallyPaid -> Paid -> Overpaid
+///   |          |
+///   +----------+--> Expired / Cancelled
+/// ```
+///
+/// Payments are recorded in any status (so late payments can be refunded),
+/// but expired and cancelled invoices keep their status.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize, Encode, Decode)]
+pub enum InvoiceStatus {
+    /// Waiting for payments
+    Open,
+    /// Received less than the invoice amount
+    PartiallyPaid,
+    /// Received exactly the invoice amount
+    Paid,
+    /// Received more than the invoice amount
+    Overpaid,
+    /// Not fully paid before the expiry time
+    Expired,
+    /// Cancelled by the merchant
+    Cancelled,
+}
+
+impl InvoiceStatus {
+    /// Returns `true` if the invoice still waits for payments
+    #[inline]
+    pub fn is_active(self) -> bool {
+        match self {
+            InvoiceStatus::Open | InvoiceStatus::PartiallyPaid => true,
+            _ => false,
+        }
+    }
+}
+
+/// Payment of an invoice (an output to the invoice address in a synced transaction)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Encode, Decode)]
+pub struct InvoicePayment {
+    /// Transaction ID
+    #[serde(serialize_with = "serialize_transaction_id")]
+    #[serde(deserialize_with = "deserialize_transaction_id")]
+    pub transaction_id: TxId,
+    /// Index of the output in the transaction
+    pub output_index: TxoSize,
+    /// Paid amount
+    pub amount: Coin,
+    /// Height of block which has this transaction
+    pub block_height: u64,
+    /// Time of block which has this transaction (seconds since the unix epoch)
+    pub block_time: u64,
+}
+
+/// Invoice with a dedicated transfer address of the wallet
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Encode, Decode)]
+pub struct Invoice {
+    /// Invoice ID (sequential per wallet)
+    pub id: u64,
+    /// Requested amount
+    pub amount: Coin,
+    /// Address to be paid (only used by this invoice)
+    pub address: ExtendedAddr,
+    /// Free text of the merchant (e.g. order reference)
+    pub memo: String,
+    /// Creation time (seconds since the unix epoch)
+    pub created_at: u64,
+    /// Expiry time (seconds since the unix epoch), compared with the block times
+    pub expires_at: u64,
+    /// Total received amount
+    pub received: Coin,
+    /// Received payments
+    pub payments: Vec<InvoicePayment>,
+    /// Current status
+    pub status: InvoiceStatus,
+}
+
+impl Invoice {
+    /// Records the payment and returns the new status if it changed
+    pub fn add_payment(&mut self, payment: InvoicePayment) -> Result<Option<InvoiceStatus>> {
+        self.received = (self.received + payment.amount).chain(|| {
+            (
+                ErrorKind::IllegalInput,
+                format!("Received amount of invoice {} overflows", self.id),
+            )
+        })?;
+        self.payments.push(payment);
+
+        let status = match self.status {
+            InvoiceStatus::Expired | InvoiceStatus::Cancelled => self.status,
+            _ if self.received > self.amount => InvoiceStatus::Overpaid,
+            _ if self.received == self.amount => InvoiceStatus::Paid,
+            _ => InvoiceStatus::PartiallyPaid,
+        };
+        Ok(self.set_status(status))
+    }
+
+    /// Expires the invoice if it is still active at the given time, returns the new status if
+    /// it changed
+    pub fn expire(&mut self, time: u64) -> Option<InvoiceStatus> {
+        if self.status.is_active() && time > self.expires_at {
+            self.set_status(InvoiceStatus::Expired)
+        } else {
+            None
+        }
+    }
+
+    /// Cancels the invoice (only if it is still active)
+    pub fn cancel(&mut self) -> Result<InvoiceStatus> {
+        if !self.status.is_active() {
+            return Err(client_common::Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Invoice {} can not be cancelled in status {:?}",
+                    self.id, self.status
+                ),
+            ));
+        }
+        self.status = InvoiceStatus::Cancelled;
+        Ok(self.status)
+    }
+
+    fn set_status(&mut self, status: InvoiceStatus) -> Option<InvoiceStatus> {
+        if self.status == status {
+            None
+        } else {
+            self.status = status;
+            Some(status)
+        }
+    }
+}
+
+/// Lifecycle event of an invoice
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Encode, Decode)]
+pub enum InvoiceEventKind {
+    /// The invoice was created
+    Created,
+    /// A payment was received
+    PaymentReceived(InvoicePayment),
+    /// The status of the invoice changed
+    StatusChanged(InvoiceStatus),
+}
+
+/// Lifecycle event of an invoice, with a sequence number (per wallet) for polling
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Encode, Decode)]
+pub struct InvoiceEvent {
+    /// Sequence number of the event
+    pub sequence: u64,
+    /// Invoice ID
+    pub invoice_id: u64,
+    /// Event
+    pub kind: InvoiceEventKind,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invoice(amount: u64) -> Invoice {
+        Invoice {
+            id: 0,
+            amount: Coin::new(amount).unwrap(),
+            address: ExtendedAddr::OrTree([0; 32]),
+            memo: "order".to_owned(),
+            created_at: 0,
+            expires_at: 100,
+            received: Coin::zero(),
+            payments: vec![],
+            status: InvoiceStatus::Open,
+        }
+    }
+
+    fn payment(amount: u64) -> InvoicePayment {
+        InvoicePayment {
+            transaction_id: [0; 32],
+            output_index: 0,
+            amount: Coin::new(amount).unwrap(),
+            block_height: 1,
+            block_time: 10,
+        }
+    }
+
+    #[test]
+    fn check_invoice_payment_states() {
+        let mut invoice = invoice(100);
+        assert_eq!(
+            Some(InvoiceStatus::PartiallyPaid),
+            invoice.add_payment(payment(40)).unwrap()
+        );
+        assert_eq!(None, invoice.add_payment(payment(20)).unwrap());
+        assert_eq!(
+            Some(InvoiceStatus::Paid),
+            invoice.add_payment(payment(40)).unwrap()
+        );
+        assert_eq!(
+            Some(InvoiceStatus::Overpaid),
+            invoice.add_payment(payment(1)).unwrap()
+        );
+        assert_eq!(Coin::new(101).unwrap(), invoice.received);
+        assert_eq!(None, invoice.expire(200));
+        assert!(invoice.cancel().is_err());
+    }
+
+    #[test]
+    fn check_invoice_expiry() {
+        let mut invoice = invoice(100);
+        assert_eq!(None, invoice.expire(100));
+        assert_eq!(Some(InvoiceStatus::Expired), invoice.expire(101));
+
+        // late payments are recorded, but the invoice stays expired
+        assert_eq!(None, invoice.add_payment(payment(100)).unwrap());
+        assert_eq!(InvoiceStatus::Expired, invoice.status);
+        assert_eq!(1, invoice.payments.len());
+
+        let mut invoice = self::invoice(100);
+        assert_eq!(InvoiceStatus::Cancelled, invoice.cancel().unwrap());
+        assert_eq!(None, invoice.expire(101));
+    }
+}
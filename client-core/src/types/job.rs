@@ -0,0 +1,227 @@
+//! Types for the long-running wallet operations run in the background
+use parity_scale_codec::{Decode, Encode};
+use serde::{Deserialize, Serialize};
+
+use chain_core::state::account::StakedStateAddress;
+use client_common::{Error, ErrorKind, Result};
+
+/// Long-running operation on a wallet
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Encode, Decode)]
+#[serde(tag = "type")]
+pub enum JobKind {
+    /// Synchronizes the wallet with the chain (from genesis if `reset` is set)
+    Sync {
+        /// Resets the wallet state before the sync
+        #[serde(default)]
+        reset: bool,
+    },
+    /// Rebuilds the unspent transactions and the transaction history index of the wallet
+    Reindex {
+        /// Number of worker threads
+        #[serde(default = "default_reindex_threads")]
+        threads: u32,
+    },
+    /// Sends all the spendable funds of the wallet (minus the fee) to a transfer address
+    Sweep {
+        /// Transfer address receiving the funds
+        to_address: String,
+        /// View keys (hex encoded) of the recipients of the transaction
+        #[serde(default)]
+        view_keys: Vec<String>,
+    },
+    /// Moves the stake of a staking address of the wallet to another staking address (unbond,
+    /// withdraw after the unbonding period, deposit)
+    StakeMigration {
+        /// Staking address the stake is moved from
+        from_address: StakedStateAddress,
+        /// Staking address the stake is moved to
+        to_address: StakedStateAddress,
+    },
+    /// Rebuilds the state of a wallet restored from its mnemonic by scanning the chain
+    Recovery,
+}
+
+fn default_reindex_threads() -> u32 {
+    1
+}
+
+/// Status of a job
+///
+/// ```plain
+/// Queued -> Running -> Completed
+///   |         |
+///   |         +------> Failed / Interrupted (by a restart)
+///   |         |
+///   +---------+------> Cancelled
+/// ```
+///
+/// Failed, interrupted and cancelled jobs can be queued again (resumed).
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize, Encode, Decode)]
+pub enum JobStatus {
+    /// Waiting for the previous jobs
+    Queued,
+    /// Being run
+    Running,
+    /// Finished successfully
+    Completed,
+    /// Finished with an error
+    Failed,
+    /// Cancelled by the wallet owner
+    Cancelled,
+    /// Stopped by a restart of the client
+    Interrupted,
+}
+
+impl JobStatus {
+    /// Returns `true` if the job is not queued or running
+    #[inline]
+    pub fn is_finished(self) -> bool {
+        !matches!(self, JobStatus::Queued | JobStatus::Running)
+    }
+
+    /// Returns `true` if the job can be queued again
+    #[inline]
+    pub fn is_resumable(self) -> bool {
+        matches!(
+            self,
+            JobStatus::Failed | JobStatus::Cancelled | JobStatus::Interrupted
+        )
+    }
+}
+
+/// Progress of a job (e.g. block heights of a sync or numbers of reindexed transactions)
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize, Deserialize, Encode, Decode)]
+pub struct JobProgress {
+    /// Start of the work
+    pub start: u64,
+    /// Work done so far
+    pub current: u64,
+    /// End of the work
+    pub end: u64,
+}
+
+impl JobProgress {
+    /// Percentage of the work done
+    pub fn percent(&self) -> f32 {
+        if self.end > self.start && self.current >= self.start {
+            (self.current - self.start) as f32 / (self.end - self.start) as f32 * 100.0
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Long-running operation on a wallet, persisted with its status and progress
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Encode, Decode)]
+pub struct Job {
+    /// Job ID (sequential)
+    pub id: u64,
+    /// Name of the wallet
+    pub wallet_name: String,
+    /// Operation
+    pub kind: JobKind,
+    /// Current status
+    pub status: JobStatus,
+    /// Latest reported progress
+    pub progress: JobProgress,
+    /// Error of the failed job
+    pub error: Option<String>,
+    /// Creation time (seconds since the unix epoch)
+    pub created_at: u64,
+    /// Time of the latest status or progress update (seconds since the unix epoch)
+    pub updated_at: u64,
+}
+
+impl Job {
+    /// Changes the status of the job if the transition is allowed
+    pub fn transition(&mut self, status: JobStatus) -> Result<()> {
+        let allowed = match (self.status, status) {
+            (JobStatus::Queued, JobStatus::Running) => true,
+            (JobStatus::Queued, JobStatus::Cancelled) => true,
+            (JobStatus::Running, next) => next.is_finished(),
+            (previous, JobStatus::Queued) => previous.is_resumable(),
+            _ => false,
+        };
+        if !allowed {
+            return Err(Error::new(
+                ErrorKind::IllegalInput,
+                format!(
+                    "Job {} can not change from {:?} to {:?}",
+                    self.id, self.status, status
+                ),
+            ));
+        }
+        self.status = status;
+        if status != JobStatus::Failed {
+            self.error = None;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_job_transitions() {
+        let mut job = Job {
+            id: 0,
+            wallet_name: "name".to_owned(),
+            kind: JobKind::Sync { reset: false },
+            status: JobStatus::Queued,
+            progress: JobProgress::default(),
+            error: None,
+            created_at: 0,
+            updated_at: 0,
+        };
+        assert!(job.transition(JobStatus::Completed).is_err());
+        job.transition(JobStatus::Running).unwrap();
+        job.transition(JobStatus::Interrupted).unwrap();
+        assert!(job.transition(JobStatus::Running).is_err());
+        job.transition(JobStatus::Queued).unwrap();
+        job.transition(JobStatus::Running).unwrap();
+        job.transition(JobStatus::Completed).unwrap();
+        assert!(job.transition(JobStatus::Queued).is_err());
+
+        let progress = JobProgress {
+            start: 10,
+            current: 15,
+            end: 20,
+        };
+        assert_eq!(50.0, progress.percent());
+    }
+
+    #[test]
+    fn check_job_kind_serialization() {
+        let kind: JobKind = serde_json::from_str(
+            r#"{"type":"StakeMigration","from_address":"0x0e7c045110b8dbf29765047380898919c5cb56f4","to_address":"0x1e7c045110b8dbf29765047380898919c5cb56f4"}"#,
+        )
+        .unwrap();
+        match &kind {
+            JobKind::StakeMigration {
+                from_address,
+                to_address,
+            } => assert_ne!(from_address, to_address),
+            _ => panic!("unexpected job kind"),
+        }
+        assert_eq!(
+            kind,
+            JobKind::decode(&mut kind.encode().as_slice()).unwrap()
+        );
+
+        let kind: JobKind =
+            serde_json::from_str(r#"{"type":"Sweep","to_address":"dcro1"}"#).unwrap();
+        assert_eq!(
+            JobKind::Sweep {
+                to_address: "dcro1".to_owned(),
+                view_keys: vec![],
+            },
+            kind
+        );
+        assert_eq!(
+            JobKind::Recovery,
+            serde_json::from_str(r#"{"type":"Recovery"}"#).unwrap()
+        );
+    }
+}
@@ -0,0 +1,267 @@
+//! Types for scheduling recurring payments of a wallet
+use parity_scale_codec::{Decode, Encode};
+use serde::{Deserialize, Serialize};
+
+use chain_core::init::coin::Coin;
+use chain_core::tx::data::address::ExtendedAddr;
+use chain_core::tx::data::TxId;
+use client_common::{Error, ErrorKind, PublicKey, Result};
+
+use super::transaction_change::{deserialize_transaction_id, serialize_transaction_id};
+
+/// Status of a recurring payment
+///
+/// ```plain
+/// Active -> Completed (after the end time)
+///   |
+///   +-----> Cancelled
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize, Encode, Decode)]
+pub enum RecurringPaymentStatus {
+    /// Paid on schedule
+    Active,
+    /// The next payment would be due after the end time
+    Completed,
+    /// Cancelled by the wallet owner
+    Cancelled,
+}
+
+/// Retry policy of the failed payments (e.g. insufficient balance or broadcast errors)
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize, Encode, Decode)]
+pub struct RetryPolicy {
+    /// Number of attempts of a payment before it is skipped (at least one)
+    pub max_attempts: u32,
+    /// Time (in seconds) between two attempts of a payment
+    pub retry_delay: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            retry_delay: 300,
+        }
+    }
+}
+
+/// Transfer paid on schedule by the wallet
+///
+/// Payments missed while the wallet wasn't unlocked are paid one by one, in order, on the
+/// next runs of the scheduler.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Encode, Decode)]
+pub struct RecurringPayment {
+    /// Recurring payment ID (sequential per wallet)
+    pub id: u64,
+    /// Destination address
+    pub to_address: ExtendedAddr,
+    /// Amount of each payment
+    pub amount: Coin,
+    /// Time (in seconds) between two payments
+    pub interval: u64,
+    /// No payment is due after this time (seconds since the unix epoch)
+    pub end_at: Option<u64>,
+    /// View keys of the payment transactions
+    pub view_keys: Vec<PublicKey>,
+    /// Retry policy of the failed payments
+    pub retry_policy: RetryPolicy,
+    /// Due time of the next payment (seconds since the unix epoch)
+    pub next_due_at: u64,
+    /// Number of failed attempts of the next payment
+    pub failed_attempts: u32,
+    /// Time of the next attempt (the due time, or later after failed attempts)
+    pub next_attempt_at: u64,
+    /// Creation time (seconds since the unix epoch)
+    pub created_at: u64,
+    /// Current status
+    pub status: RecurringPaymentStatus,
+}
+
+impl RecurringPayment {
+    /// Returns `true` if the payment should be attempted at the given time
+    #[inline]
+    pub fn is_due(&self, time: u64) -> bool {
+        self.status == RecurringPaymentStatus::Active && self.next_attempt_at <= time
+    }
+
+    /// Records the broadcast payment and schedules the next one, returns the events
+    pub fn paid(&mut self, transaction_id: TxId) -> Vec<RecurringPaymentEventKind> {
+        let mut events = vec![RecurringPaymentEventKind::Paid {
+            due_at: self.next_due_at,
+            transaction_id,
+        }];
+        events.extend(self.advance());
+        events
+    }
+
+    /// Records the failed attempt at the given time, the payment is retried after the retry
+    /// delay or skipped after the maximum number of attempts; returns the events
+    pub fn failed(&mut self, time: u64, error: String) -> Vec<RecurringPaymentEventKind> {
+        self.failed_attempts += 1;
+        let mut events = vec![RecurringPaymentEventKind::AttemptFailed {
+            due_at: self.next_due_at,
+            attempt: self.failed_attempts,
+            error,
+        }];
+        if self.failed_attempts >= self.retry_policy.max_attempts {
+            events.push(RecurringPaymentEventKind::Skipped {
+                due_at: self.next_due_at,
+            });
+            events.extend(self.advance());
+        } else {
+            self.next_attempt_at = time.saturating_add(self.retry_policy.retry_delay);
+        }
+        events
+    }
+
+    /// Cancels the recurring payment (only if it is still active)
+    pub fn cancel(&mut self) -> Result<RecurringPaymentStatus> {
+        if self.status != RecurringPaymentStatus::Active {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Recurring payment {} can not be cancelled in status {:?}",
+                    self.id, self.status
+                ),
+            ));
+        }
+        self.status = RecurringPaymentStatus::Cancelled;
+        Ok(self.status)
+    }
+
+    fn advance(&mut self) -> Option<RecurringPaymentEventKind> {
+        self.next_due_at = self.next_due_at.saturating_add(self.interval);
+        self.next_attempt_at = self.next_due_at;
+        self.failed_attempts = 0;
+        match self.end_at {
+            Some(end_at) if self.next_due_at > end_at => {
+                self.status = RecurringPaymentStatus::Completed;
+                Some(RecurringPaymentEventKind::StatusChanged(self.status))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Lifecycle event of a recurring payment
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Encode, Decode)]
+pub enum RecurringPaymentEventKind {
+    /// The recurring payment was created
+    Created,
+    /// The payment due at the given time was broadcast
+    Paid {
+        /// Due time of the payment
+        due_at: u64,
+        /// ID of the broadcast transaction
+        #[serde(serialize_with = "serialize_transaction_id")]
+        #[serde(deserialize_with = "deserialize_transaction_id")]
+        transaction_id: TxId,
+    },
+    /// An attempt of the payment due at the given time failed
+    AttemptFailed {
+        /// Due time of the payment
+        due_at: u64,
+        /// Number of the failed attempt (starting from 1)
+        attempt: u32,
+        /// Error of the attempt
+        error: String,
+    },
+    /// The payment due at the given time was skipped after the maximum number of attempts
+    Skipped {
+        /// Due time of the payment
+        due_at: u64,
+    },
+    /// The status of the recurring payment changed
+    StatusChanged(RecurringPaymentStatus),
+}
+
+/// Lifecycle event of a recurring payment, with a sequence number (per wallet) for polling
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Encode, Decode)]
+pub struct RecurringPaymentEvent {
+    /// Sequence number of the event
+    pub sequence: u64,
+    /// Recurring payment ID
+    pub payment_id: u64,
+    /// Event
+    pub kind: RecurringPaymentEventKind,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recurring_payment(end_at: Option<u64>) -> RecurringPayment {
+        RecurringPayment {
+            id: 0,
+            to_address: ExtendedAddr::OrTree([0; 32]),
+            amount: Coin::new(100).unwrap(),
+            interval: 100,
+            end_at,
+            view_keys: vec![],
+            retry_policy: RetryPolicy {
+                max_attempts: 2,
+                retry_delay: 10,
+            },
+            next_due_at: 100,
+            failed_attempts: 0,
+            next_attempt_at: 100,
+            created_at: 0,
+            status: RecurringPaymentStatus::Active,
+        }
+    }
+
+    #[test]
+    fn check_recurring_payment_schedule() {
+        let mut payment = recurring_payment(Some(250));
+        assert!(!payment.is_due(99));
+        assert!(payment.is_due(100));
+
+        assert_eq!(
+            vec![RecurringPaymentEventKind::Paid {
+                due_at: 100,
+                transaction_id: [1; 32],
+            }],
+            payment.paid([1; 32])
+        );
+        assert_eq!(200, payment.next_due_at);
+        assert!(!payment.is_due(150));
+
+        assert_eq!(
+            vec![
+                RecurringPaymentEventKind::Paid {
+                    due_at: 200,
+                    transaction_id: [2; 32],
+                },
+                RecurringPaymentEventKind::StatusChanged(RecurringPaymentStatus::Completed),
+            ],
+            payment.paid([2; 32])
+        );
+        assert!(!payment.is_due(300));
+        assert!(payment.cancel().is_err());
+    }
+
+    #[test]
+    fn check_recurring_payment_retry() {
+        let mut payment = recurring_payment(None);
+        assert_eq!(
+            vec![RecurringPaymentEventKind::AttemptFailed {
+                due_at: 100,
+                attempt: 1,
+                error: "error".to_owned(),
+            }],
+            payment.failed(101, "error".to_owned())
+        );
+        assert!(!payment.is_due(110));
+        assert!(payment.is_due(111));
+
+        let events = payment.failed(111, "error".to_owned());
+        assert_eq!(
+            RecurringPaymentEventKind::Skipped { due_at: 100 },
+            events[1]
+        );
+        assert_eq!(0, payment.failed_attempts);
+        assert_eq!(200, payment.next_attempt_at);
+
+        assert_eq!(RecurringPaymentStatus::Cancelled, payment.cancel().unwrap());
+        assert!(!payment.is_due(200));
+    }
+}
@@ -0,0 +1,180 @@
+//! Types for reporting why wallet outputs can or can't be spent
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use chain_core::common::Timespec;
+use chain_core::init::coin::{sum_coins, Coin};
+use chain_core::tx::data::{input::TxoPointer, output::TxOut, TxId};
+use client_common::{ErrorKind, Result, ResultExt};
+
+use super::transaction_change::{deserialize_transaction_id, serialize_transaction_id};
+
+/// Spendability of an unspent output of the wallet (the first matching reason, in this order)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status")]
+pub enum Spendability {
+    /// can be spent by a transfer transaction
+    Spendable,
+    /// already spent by a broadcast transaction of the wallet which isn't confirmed yet
+    Reserved {
+        /// ID of the pending transaction
+        #[serde(serialize_with = "serialize_transaction_id")]
+        #[serde(deserialize_with = "deserialize_transaction_id")]
+        transaction_id: TxId,
+    },
+    /// timelocked until the given time (compared to the time of the latest block)
+    Timelocked {
+        /// time the output can be spent from
+        until: Timespec,
+    },
+    /// held by a vault (or a pending vault spend), so it's only spent through the vault workflow
+    InVault,
+    /// held by an inheritance plan, so it's only spent through the inheritance workflow
+    InInheritancePlan,
+    /// frozen by the user, so it's not selected as an input of the transactions built by
+    /// the wallet
+    Frozen,
+    /// sent to a multi-sig address, so spending it needs signatures of other co-signers
+    NeedsCoSigners {
+        /// number of required signers
+        required_signers: usize,
+    },
+    /// value below the dust threshold (not worth the fee of spending it)
+    BelowDust {
+        /// dust threshold
+        threshold: Coin,
+    },
+}
+
+impl Spendability {
+    /// Returns `true` if the output can be spent
+    #[inline]
+    pub fn is_spendable(&self) -> bool {
+        *self == Spendability::Spendable
+    }
+}
+
+/// Unspent output of the wallet with its spendability
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UtxoSpendability {
+    /// Pointer to the output
+    #[serde(flatten)]
+    pub pointer: TxoPointer,
+    /// Details of the output
+    pub output: TxOut,
+    /// Whether or why not the output can be spent
+    pub spendability: Spendability,
+}
+
+/// Amount returned to the wallet by a broadcast transaction which isn't confirmed yet
+/// (it becomes an unspent output once the transaction is committed and the wallet is synced)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UnconfirmedAmount {
+    /// ID of the pending transaction
+    #[serde(serialize_with = "serialize_transaction_id")]
+    #[serde(deserialize_with = "deserialize_transaction_id")]
+    pub transaction_id: TxId,
+    /// Returned amount
+    pub amount: Coin,
+}
+
+/// Spendability of all the unspent outputs of the wallet
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpendabilityReport {
+    /// Unspent outputs
+    pub outputs: Vec<UtxoSpendability>,
+    /// Amounts of unconfirmed transactions
+    pub unconfirmed: Vec<UnconfirmedAmount>,
+}
+
+impl SpendabilityReport {
+    /// Returns the outputs which can be spent
+    pub fn spendable_outputs(&self) -> Vec<(TxoPointer, TxOut)> {
+        self.outputs
+            .iter()
+            .filter(|utxo| utxo.spendability.is_spendable())
+            .map(|utxo| (utxo.pointer.clone(), utxo.output.clone()))
+            .collect()
+    }
+
+    /// Returns the total amount of the outputs matching the predicate
+    pub fn amount<F>(&self, predicate: F) -> Result<Coin>
+    where
+        F: Fn(&Spendability) -> bool,
+    {
+        sum_coins(
+            self.outputs
+                .iter()
+                .filter(|utxo| predicate(&utxo.spendability))
+                .map(|utxo| utxo.output.value),
+        )
+        .chain(|| {
+            (
+                ErrorKind::IllegalInput,
+                "Sum of output values exceeds maximum allowed amount",
+            )
+        })
+    }
+
+    /// Returns the total unconfirmed amount
+    pub fn unconfirmed_amount(&self) -> Result<Coin> {
+        sum_coins(
+            self.unconfirmed
+                .iter()
+                .map(|unconfirmed| unconfirmed.amount),
+        )
+        .chain(|| {
+            (
+                ErrorKind::IllegalInput,
+                "Sum of unconfirmed amounts exceeds maximum allowed amount",
+            )
+        })
+    }
+}
+
+impl fmt::Display for SpendabilityReport {
+    /// Breakdown of the wallet balance by spendability (used in error messages)
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let amount =
+            |predicate: &dyn Fn(&Spendability) -> bool| self.amount(predicate).unwrap_or_default();
+        write!(f, "spendable: {}", amount(&Spendability::is_spendable))?;
+        let unavailable = [
+            (
+                "reserved by pending transactions",
+                amount(&|status| matches!(status, Spendability::Reserved { .. })),
+            ),
+            (
+                "timelocked",
+                amount(&|status| matches!(status, Spendability::Timelocked { .. })),
+            ),
+            (
+                "in vaults",
+                amount(&|status| matches!(status, Spendability::InVault)),
+            ),
+            (
+                "in inheritance plans",
+                amount(&|status| matches!(status, Spendability::InInheritancePlan)),
+            ),
+            (
+                "frozen",
+                amount(&|status| matches!(status, Spendability::Frozen)),
+            ),
+            (
+                "needing co-signers",
+                amount(&|status| matches!(status, Spendability::NeedsCoSigners { .. })),
+            ),
+            (
+                "below dust threshold",
+                amount(&|status| matches!(status, Spendability::BelowDust { .. })),
+            ),
+            ("unconfirmed", self.unconfirmed_amount().unwrap_or_default()),
+        ];
+        for (reason, value) in unavailable.iter() {
+            if *value != Coin::zero() {
+                write!(f, ", {}: {}", reason, value)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,151 @@
+//! Types for the spending policy (limits) of a wallet
+use parity_scale_codec::{Decode, Encode};
+use serde::{Deserialize, Serialize};
+
+use chain_core::common::Timespec;
+use chain_core::init::coin::{sum_coins, Coin};
+use chain_core::tx::data::address::ExtendedAddr;
+use chain_core::tx::data::output::TxOut;
+use client_common::{ErrorKind, Result, ResultExt};
+
+/// Outputs of at least `threshold` must be time-locked for at least `min_timelock` seconds
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Encode, Decode)]
+pub struct LargeOutputRule {
+    pub threshold: Coin,
+    pub min_timelock: Timespec,
+}
+
+/// Limits of the transfers to other wallets, enforced when transfer transactions are built or
+/// signed (transfers between the addresses of the wallet are not limited). A violating
+/// transaction needs an override approved with the override passphrase of the policy.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, Encode, Decode)]
+pub struct SpendingPolicy {
+    /// Maximum amount sent to other wallets in one transaction
+    #[serde(default)]
+    pub max_per_transaction: Option<Coin>,
+    /// Maximum amount sent to other wallets in any 24 hours
+    #[serde(default)]
+    pub max_per_day: Option<Coin>,
+    /// If not empty, only these addresses (of other wallets) can receive transfers
+    #[serde(default)]
+    pub whitelist: Vec<ExtendedAddr>,
+    /// Mandatory timelock of large outputs
+    #[serde(default)]
+    pub large_output: Option<LargeOutputRule>,
+}
+
+impl SpendingPolicy {
+    /// Returns the rules violated by the outputs sent to other wallets, given the amount sent to
+    /// other wallets in the last 24 hours (an empty list if the transaction is allowed)
+    pub fn violations(
+        &self,
+        outputs: &[TxOut],
+        spent_last_day: Coin,
+        now: Timespec,
+    ) -> Result<Vec<String>> {
+        let mut violations = Vec::new();
+        let amount = sum_coins(outputs.iter().map(|output| output.value))
+            .chain(|| (ErrorKind::IllegalInput, "Invalid amount of the outputs"))?;
+
+        if let Some(max_per_transaction) = self.max_per_transaction {
+            if amount > max_per_transaction {
+                violations.push(format!(
+                    "amount {} exceeds the limit per transaction ({})",
+                    amount, max_per_transaction
+                ));
+            }
+        }
+        if let Some(max_per_day) = self.max_per_day {
+            let spent = (spent_last_day + amount)
+                .chain(|| (ErrorKind::IllegalInput, "Invalid amount spent today"))?;
+            if spent > max_per_day {
+                violations.push(format!(
+                    "amount {} sent in the last 24 hours exceeds the daily limit ({})",
+                    spent, max_per_day
+                ));
+            }
+        }
+        if !self.whitelist.is_empty() {
+            for output in outputs {
+                if !self.whitelist.contains(&output.address) {
+                    violations.push(format!("address {} is not whitelisted", output.address));
+                }
+            }
+        }
+        if let Some(rule) = &self.large_output {
+            let min_valid_from = now.saturating_add(rule.min_timelock);
+            for output in outputs {
+                if output.value >= rule.threshold
+                    && output.valid_from.map_or(true, |time| time < min_valid_from)
+                {
+                    violations.push(format!(
+                        "output of {} to {} must be time-locked until at least {}",
+                        output.value, output.address, min_valid_from
+                    ));
+                }
+            }
+        }
+        Ok(violations)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_spending_policy_violations() {
+        let whitelisted = ExtendedAddr::OrTree([1; 32]);
+        let policy = SpendingPolicy {
+            max_per_transaction: Some(Coin::new(1000).unwrap()),
+            max_per_day: Some(Coin::new(1500).unwrap()),
+            whitelist: vec![whitelisted.clone()],
+            large_output: Some(LargeOutputRule {
+                threshold: Coin::new(800).unwrap(),
+                min_timelock: 3600,
+            }),
+        };
+        let now = 1_600_000_000;
+
+        let small = TxOut::new(whitelisted.clone(), Coin::new(500).unwrap());
+        assert!(policy
+            .violations(&[small.clone()], Coin::zero(), now)
+            .unwrap()
+            .is_empty());
+        // daily limit
+        assert_eq!(
+            1,
+            policy
+                .violations(&[small.clone()], Coin::new(1200).unwrap(), now)
+                .unwrap()
+                .len()
+        );
+        // limit per transaction + large output without timelock + not whitelisted
+        let other = TxOut::new(ExtendedAddr::OrTree([2; 32]), Coin::new(900).unwrap());
+        assert_eq!(
+            3,
+            policy
+                .violations(&[small.clone(), other], Coin::zero(), now)
+                .unwrap()
+                .len()
+        );
+        let locked =
+            TxOut::new_with_timelock(whitelisted.clone(), Coin::new(900).unwrap(), now + 3600);
+        assert!(policy
+            .violations(&[locked], Coin::zero(), now)
+            .unwrap()
+            .is_empty());
+        let short_lock = TxOut::new_with_timelock(whitelisted, Coin::new(900).unwrap(), now + 60);
+        assert_eq!(
+            1,
+            policy
+                .violations(&[short_lock], Coin::zero(), now)
+                .unwrap()
+                .len()
+        );
+        assert!(SpendingPolicy::default()
+            .violations(&[small], Coin::new(1_000_000).unwrap(), now)
+            .unwrap()
+            .is_empty());
+    }
+}
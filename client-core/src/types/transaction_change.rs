@@ -8,12 +8,15 @@ use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
 use chain_core::{
     init::coin::{Coin, CoinError},
+    state::account::StakedStateAddress,
     tx::data::{input::TxoPointer, output::TxOut, TxId},
     tx::fee::Fee,
 };
 use client_common::tendermint::types::Time;
 use client_common::{ErrorKind, Result, ResultExt, Transaction};
 
+use super::Annotation;
+
 /// Wallet balance info
 ///
 /// The semantic of `WalletBalance` is like this:
@@ -87,6 +90,28 @@ pub struct TransactionChange {
     pub block_height: u64,
     /// Time of block which has this transaction
     pub block_time: Time,
+    /// Local annotation of the transaction (not encoded with the wallet state: it's attached to
+    /// the history query results)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub annotation: Option<Annotation>,
+}
+
+/// Unconfirmed transaction (in the mempool of the node) which spends outputs of the wallet
+/// or operates on one of its staking addresses
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MempoolTransaction {
+    /// Transaction ID
+    #[serde(serialize_with = "serialize_transaction_id")]
+    #[serde(deserialize_with = "deserialize_transaction_id")]
+    pub transaction_id: TxId,
+    /// Transaction type
+    pub transaction_type: TransactionType,
+    /// Outputs of the wallet spent by the transaction
+    pub inputs: Vec<TransactionInput>,
+    /// Staking address of the wallet the transaction operates on
+    pub staking_address: Option<StakedStateAddress>,
+    /// Unbonded amount (only public in unbond transactions)
+    pub amount: Option<Coin>,
 }
 
 /// Transaction input
@@ -114,6 +139,10 @@ pub enum TransactionType {
     Unjail,
     /// Nodejoin transaction
     Nodejoin,
+    /// Transaction of a custom type (see `plugin`)
+    Custom,
+    /// Data anchoring transaction
+    DataAnchor,
 }
 
 impl fmt::Display for TransactionType {
@@ -125,6 +154,8 @@ impl fmt::Display for TransactionType {
             TransactionType::Deposit => write!(f, "Deposit"),
             TransactionType::Unjail => write!(f, "Unfail"),
             TransactionType::Nodejoin => write!(f, "Nodejoin"),
+            TransactionType::Custom => write!(f, "Custom"),
+            TransactionType::DataAnchor => write!(f, "DataAnchor"),
         }
     }
 }
@@ -147,7 +178,7 @@ pub enum BalanceChange {
     NoChange,
 }
 
-fn serialize_transaction_id<S>(
+pub(crate) fn serialize_transaction_id<S>(
     transaction_id: &TxId,
     serializer: S,
 ) -> std::result::Result<S::Ok, S::Error>
@@ -157,7 +188,9 @@ where
     serializer.serialize_str(&hex::encode(transaction_id))
 }
 
-fn deserialize_transaction_id<'de, D>(deserializer: D) -> std::result::Result<TxId, D::Error>
+pub(crate) fn deserialize_transaction_id<'de, D>(
+    deserializer: D,
+) -> std::result::Result<TxId, D::Error>
 where
     D: Deserializer<'de>,
 {
@@ -217,6 +250,7 @@ impl Decode for TransactionChange {
             transaction_type,
             block_height,
             block_time,
+            annotation: None,
         })
     }
 }
@@ -260,6 +294,7 @@ impl From<&Transaction> for TransactionType {
             Transaction::DepositStakeTransaction(_) => TransactionType::Deposit,
             Transaction::UnjailTransaction(_) => TransactionType::Unjail,
             Transaction::NodejoinTransaction(_) => TransactionType::Nodejoin,
+            Transaction::DataAnchorTransaction(_) => TransactionType::DataAnchor,
         }
     }
 }
@@ -282,6 +317,7 @@ mod tests {
             fee_paid: Fee::new(Coin::one()),
             block_height: 0,
             block_time: Time::now(),
+            annotation: None,
         };
 
         let encoded = transaction_change.encode();
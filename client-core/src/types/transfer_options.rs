@@ -0,0 +1,14 @@
+//! Options of the transfer transactions built by the wallet
+use chain_core::tx::data::address::ExtendedAddr;
+use chain_core::tx::data::input::TxoPointer;
+
+/// Options of a transfer transaction built by the wallet (per-output timelocks are set with
+/// `TxOut::valid_from` of the outputs)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TransferOptions {
+    /// Address receiving the change (a newly derived transfer address of the wallet if not set)
+    pub change_address: Option<ExtendedAddr>,
+    /// Unspent outputs excluded from the input selection of this transaction (in addition to
+    /// the frozen outputs of the wallet)
+    pub excluded_inputs: Vec<TxoPointer>,
+}
@@ -0,0 +1,154 @@
+//! Types for cold storage vaults with time-delayed spending
+use parity_scale_codec::{Decode, Encode};
+use serde::{Deserialize, Serialize};
+
+use chain_core::common::{MerkleTree, Proof, Timespec};
+use chain_core::init::coin::Coin;
+use chain_core::tx::data::address::ExtendedAddr;
+use chain_core::tx::data::TxId;
+use chain_core::tx::witness::tree::{timelocked_leaf, RawXOnlyPubkey};
+use client_common::PublicKey;
+
+use super::transaction_change::{deserialize_transaction_id, serialize_transaction_id};
+
+/// Vault of the wallet
+///
+/// Funds are held by the vault address (1-of-2 of the spend key of the wallet and the external
+/// cancel key). Spending from the vault goes through a pending address, where the spend key can
+/// only sign after the delay (timelocked leaf), while the cancel key can sweep the funds back
+/// to the vault at any time.
+///
+/// NOTE: there are no covenants, so the spend key can also sign for the vault address directly;
+/// the wallet only spends vault funds through the pending addresses and reports any other
+/// spend from the vault address (see `VaultAlert`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Encode, Decode)]
+pub struct Vault {
+    /// Vault address
+    pub address: ExtendedAddr,
+    /// Key of the wallet spending from the vault (after the delay)
+    pub spend_public_key: PublicKey,
+    /// Key cancelling pending spends (kept offline)
+    pub cancel_public_key: PublicKey,
+    /// Delay (in seconds) before a pending spend can be completed
+    pub delay: Timespec,
+    /// Spends from the vault address which weren't initiated by the wallet
+    pub alerts: Vec<VaultAlert>,
+}
+
+/// Spend from a vault address which wasn't initiated by the wallet (e.g. with a compromised
+/// spend key); the funds should be moved with the cancel key
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Encode, Decode)]
+pub struct VaultAlert {
+    /// Transaction ID
+    #[serde(serialize_with = "serialize_transaction_id")]
+    #[serde(deserialize_with = "deserialize_transaction_id")]
+    pub transaction_id: TxId,
+    /// Height of block which has this transaction
+    pub block_height: u64,
+}
+
+/// Status of a pending vault spend
+///
+/// ```plain
+/// Unconfirmed -> Pending -> Ready -> Completed
+///                   |         |
+///                   +---------+--> Cancelled
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Encode, Decode)]
+#[serde(tag = "status")]
+pub enum VaultSpendStatus {
+    /// Transaction moving the funds to the pending address is broadcast, but not synced yet
+    Unconfirmed,
+    /// Funds are on the pending address, waiting for the delay
+    Pending,
+    /// The delay passed, the spend can be completed
+    Ready,
+    /// Funds are sent to the destination
+    Completed {
+        /// ID of the completing transaction
+        #[serde(serialize_with = "serialize_transaction_id")]
+        #[serde(deserialize_with = "deserialize_transaction_id")]
+        transaction_id: TxId,
+    },
+    /// Funds are swept back to the vault with the cancel key
+    Cancelled {
+        /// ID of the cancelling transaction
+        #[serde(serialize_with = "serialize_transaction_id")]
+        #[serde(deserialize_with = "deserialize_transaction_id")]
+        transaction_id: TxId,
+    },
+}
+
+impl VaultSpendStatus {
+    /// Returns `true` if the funds are still on the pending address
+    #[inline]
+    pub fn is_active(&self) -> bool {
+        match self {
+            VaultSpendStatus::Unconfirmed | VaultSpendStatus::Pending | VaultSpendStatus::Ready => {
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Spend from a vault, waiting on its pending address for the delay
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Encode, Decode)]
+pub struct VaultSpend {
+    /// ID of the transaction moving the funds from the vault to the pending address
+    #[serde(serialize_with = "serialize_transaction_id")]
+    #[serde(deserialize_with = "deserialize_transaction_id")]
+    pub transaction_id: TxId,
+    /// Vault address
+    pub vault_address: ExtendedAddr,
+    /// Pending address
+    pub pending_address: ExtendedAddr,
+    /// Time (seconds since the unix epoch, compared with the block times) from which
+    /// the spend can be completed
+    pub unlock_time: Timespec,
+    /// Destination of the spend
+    pub to_address: ExtendedAddr,
+    /// Amount sent to the destination
+    pub amount: Coin,
+    /// View keys of the transactions of the spend
+    pub view_keys: Vec<PublicKey>,
+    /// Status
+    pub status: VaultSpendStatus,
+}
+
+/// Merkle tree of a pending address: the timelocked leaf of the spend key and the cancel key
+pub fn pending_address_tree(
+    spend_public_key: &PublicKey,
+    cancel_public_key: &PublicKey,
+    unlock_time: Timespec,
+) -> MerkleTree<RawXOnlyPubkey> {
+    MerkleTree::new(vec![
+        timelocked_leaf(&RawXOnlyPubkey::from(spend_public_key), unlock_time),
+        RawXOnlyPubkey::from(cancel_public_key),
+    ])
+}
+
+impl VaultSpend {
+    /// Proof of the leaf of the spend key (timelocked) in the pending address
+    pub fn spend_proof(&self, vault: &Vault) -> Option<Proof<RawXOnlyPubkey>> {
+        let tree = pending_address_tree(
+            &vault.spend_public_key,
+            &vault.cancel_public_key,
+            self.unlock_time,
+        );
+        tree.generate_proof(timelocked_leaf(
+            &RawXOnlyPubkey::from(&vault.spend_public_key),
+            self.unlock_time,
+        ))
+    }
+
+    /// Proof of the leaf of the cancel key in the pending address
+    pub fn cancel_proof(&self, vault: &Vault) -> Option<Proof<RawXOnlyPubkey>> {
+        let tree = pending_address_tree(
+            &vault.spend_public_key,
+            &vault.cancel_public_key,
+            self.unlock_time,
+        );
+        tree.generate_proof(RawXOnlyPubkey::from(&vault.cancel_public_key))
+    }
+}
@@ -0,0 +1,39 @@
+//! Types for the wallet identifiers and metadata
+use parity_scale_codec::{Decode, Encode};
+use serde::{Deserialize, Serialize};
+
+use super::WalletKind;
+use crate::hd_wallet::HardwareKind;
+use client_common::PublicKey;
+
+/// Metadata of a wallet, recorded when it's created or imported
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Encode, Decode)]
+pub struct WalletMetadata {
+    /// Wallet ID (derived from the view key, so it's the same for the wallets of a seed)
+    pub id: String,
+    /// Creation time (seconds since the unix epoch)
+    pub created_at: u64,
+    /// Kind of the wallet
+    pub wallet_kind: WalletKind,
+    /// Hardware wallet type
+    pub hardware_kind: HardwareKind,
+    /// Network ID of the client which created the wallet
+    pub network_id: u8,
+}
+
+/// Stored wallet with its metadata (`None` for the wallets created before the metadata
+/// was recorded)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WalletEntry {
+    /// Name of the wallet
+    pub name: String,
+    /// Metadata of the wallet
+    pub metadata: Option<WalletMetadata>,
+    /// Names of the other wallets with the same ID (restored from the same seed)
+    pub duplicates: Vec<String>,
+}
+
+/// Derives the wallet ID from its view key (hex encoded blake3 hash of the compressed key)
+pub fn wallet_id(view_key: &PublicKey) -> String {
+    hex::encode(blake3::hash(&view_key.serialize_compressed()).as_bytes())
+}
@@ -16,6 +16,9 @@ pub enum WalletKind {
     HD,
     /// HW Wallet
     HW,
+    /// Watch-only wallet: only holds public keys (and the view key for syncing);
+    /// transactions are signed by an offline signer from the exported signing payload
+    WatchOnly,
 }
 
 impl From<u64> for WalletKind {
@@ -23,6 +26,7 @@ impl From<u64> for WalletKind {
         match code {
             0 => WalletKind::Basic,
             1 => WalletKind::HD,
+            3 => WalletKind::WatchOnly,
             _ => WalletKind::HW,
         }
     }
@@ -38,10 +42,12 @@ impl FromStr for WalletKind {
             Ok(WalletKind::HW)
         } else if eq_ascii(s, "basic") {
             Ok(WalletKind::Basic)
+        } else if eq_ascii(s, "watch") {
+            Ok(WalletKind::WatchOnly)
         } else {
             Err(Error::new(
                 ErrorKind::InvalidInput,
-                "Wallet type can either be `hd` or `hw` or `basic`",
+                "Wallet type can either be `hd` or `hw` or `basic` or `watch`",
             ))
         }
     }
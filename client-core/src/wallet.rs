@@ -1,10 +1,19 @@
 //! Wallet management
 mod default_wallet_client;
+mod key_import;
+mod passphrase_policy;
+/// Wallet recovery from chain data
+pub mod recovery;
+/// Concurrent synchronization of multiple wallets
+pub mod sync_manager;
 /// Wallet synchronizer
 pub mod syncer;
+mod syncer_filters;
 mod syncer_logic;
 
 pub use default_wallet_client::DefaultWalletClient;
+pub use key_import::ExternalKey;
+pub use passphrase_policy::PassphrasePolicy;
 
 use indexmap::IndexSet;
 #[cfg(feature = "experimental")]
@@ -12,7 +21,7 @@ use secp256k1::schnorrsig::SchnorrSignature;
 use secstr::SecUtf8;
 use std::collections::BTreeSet;
 
-use chain_core::common::{Proof, H256};
+use chain_core::common::{Proof, Timespec, H256};
 use chain_core::init::address::RedeemAddress;
 use chain_core::init::coin::Coin;
 use chain_core::state::account::StakedStateAddress;
@@ -24,6 +33,7 @@ use chain_core::tx::data::output::TxOut;
 use chain_core::tx::data::Tx;
 use chain_core::tx::data::TxId;
 use chain_core::tx::witness::tree::RawXOnlyPubkey;
+use chain_core::tx::witness::TxWitness;
 use chain_core::tx::TxAux;
 use client_common::tendermint::types::BroadcastTxResponse;
 use client_common::{
@@ -33,9 +43,18 @@ use client_common::{
 use serde::{Deserialize, Serialize};
 
 use crate::hd_wallet::HardwareKind;
-use crate::service::{SyncState, WalletInfo};
-use crate::transaction_builder::{SignedTransferTransaction, UnsignedTransferTransaction};
-use crate::types::{AddressType, TransactionChange, TransactionPending, WalletBalance, WalletKind};
+use crate::multi_sig::MultiSigMessage;
+use crate::service::{ReindexProgress, ReindexReport, SyncState, WalletInfo};
+use crate::transaction_builder::{
+    SignedTransferTransaction, SigningPayload, UnsignedTransferTransaction,
+};
+use crate::types::{
+    AddressType, Annotation, Contact, FeeEstimate, Heir, InheritanceEvent, InheritancePlan,
+    Invoice, InvoiceEvent, MempoolTransaction, OwnedAddress, RecurringPayment,
+    RecurringPaymentEvent, RetryPolicy, SpendabilityReport, SpendingPolicy, TransactionChange,
+    TransactionFilter, TransactionHistoryPage, TransactionPending, TransferOptions, Vault,
+    VaultSpend, WalletAnnotations, WalletBalance, WalletEntry, WalletEvent, WalletKind,
+};
 use crate::{InputSelectionStrategy, Mnemonic, UnspentTransactions};
 
 /// information needed when create/delete a wallet
@@ -82,6 +101,30 @@ pub trait WalletClient: Send + Sync {
         network_id: u8,
     ) -> Result<TxId>;
 
+    /// Sends a transfer transaction with the given outputs (each of them can have its own
+    /// timelock), with the change address and the excluded inputs of `options`, returns the
+    /// transaction id directly
+    fn send_transfer(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        outputs: Vec<TxOut>,
+        options: &TransferOptions,
+        view_keys: &mut BTreeSet<PublicKey>,
+        network_id: u8,
+    ) -> Result<TxId>;
+
+    /// Sends all the spendable funds of the wallet (minus the fee) to a transfer address with a
+    /// single output, returns the transaction id and the sent amount
+    fn sweep_to_address(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        address: ExtendedAddr,
+        view_keys: &mut BTreeSet<PublicKey>,
+        network_id: u8,
+    ) -> Result<(TxId, Coin)>;
+
     /// send balance to a transfer address, waiting it transaction confirmed then return transaction id
     fn send_to_address_commit(
         &self,
@@ -96,6 +139,12 @@ pub trait WalletClient: Send + Sync {
     /// Retrieves names of all wallets stored
     fn wallets(&self) -> Result<Vec<String>>;
 
+    /// Retrieves all wallets stored with their ids and metadata
+    fn wallet_entries(&self) -> Result<Vec<WalletEntry>>;
+
+    /// Retrieves names of the wallets with given id (the same for the wallets of a seed)
+    fn wallets_by_id(&self, id: &str) -> Result<Vec<String>>;
+
     /// Creates a new wallet with given name, enckey and kind. Returns mnemonics if `wallet_kind` was `HD`.
     /// TODO: separate two apis
     /// new_wallet_basic(name, passphrase)
@@ -109,6 +158,16 @@ pub trait WalletClient: Send + Sync {
         mnemonics_word_count: Option<u32>,
     ) -> Result<(SecKey, Option<Mnemonic>)>;
 
+    /// Creates a new HD wallet whose seed is derived from the generated mnemonic and the BIP39
+    /// passphrase (the same mnemonic is restored with `Mnemonic::with_passphrase`)
+    fn new_hd_wallet_with_mnemonic_passphrase(
+        &self,
+        name: &str,
+        passphrase: &SecUtf8,
+        mnemonics_word_count: Option<u32>,
+        mnemonic_passphrase: &SecUtf8,
+    ) -> Result<(SecKey, Mnemonic)>;
+
     /// export wallet info including private key, transfer address, staking address and so on
     fn export_wallet(&self, name: &str, enckey: &SecKey) -> Result<WalletInfo>;
 
@@ -120,7 +179,24 @@ pub trait WalletClient: Send + Sync {
         wallet_info: &mut WalletInfo,
     ) -> Result<SecKey>;
 
-    /// Restores a HD wallet from given mnemonic
+    /// export wallet info and sync checkpoint as a backup encrypted with `backup_passphrase`
+    fn export_wallet_backup(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        backup_passphrase: &SecUtf8,
+    ) -> Result<Vec<u8>>;
+
+    /// import wallet from an encrypted backup, storing it as `name` protected by `passphrase`
+    fn import_wallet_backup(
+        &self,
+        name: &str,
+        passphrase: &SecUtf8,
+        backup: &[u8],
+        backup_passphrase: &SecUtf8,
+    ) -> Result<SecKey>;
+
+    /// Restores a HD wallet from given mnemonic (with its BIP39 passphrase, if any)
     fn restore_wallet(
         &self,
         name: &str,
@@ -193,6 +269,23 @@ pub trait WalletClient: Send + Sync {
         address: &ExtendedAddr,
     ) -> Result<Option<H256>>;
 
+    /// Returns the details of the staking address if it belongs to current wallet
+    fn staking_address_ownership(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        address: &StakedStateAddress,
+    ) -> Result<Option<OwnedAddress>>;
+
+    /// Returns the details of the transfer address if it belongs to current wallet
+    /// (the root hash is recomputed from the stored leaves of the address)
+    fn transfer_address_ownership(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        address: &ExtendedAddr,
+    ) -> Result<Option<OwnedAddress>>;
+
     /// Retrieves private key corresponding to given wallet name
     fn wallet_private_key(
         &self,
@@ -231,6 +324,17 @@ pub trait WalletClient: Send + Sync {
     /// Generates a new 1-of-1 transfer address
     fn new_transfer_address(&self, name: &str, enckey: &SecKey) -> Result<ExtendedAddr>;
 
+    /// Exports the account-level extended public key (BIP32 serialized) of the HD wallet for the
+    /// given address type and account, from which audit servers derive the (non-hardened)
+    /// addresses of the account without any private key
+    fn export_account_xpub(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        account: u32,
+        address_type: AddressType,
+    ) -> Result<String>;
+
     /// Add watch only staking address
     fn new_watch_staking_address(
         &self,
@@ -247,6 +351,19 @@ pub trait WalletClient: Send + Sync {
         public_key: &PublicKey,
     ) -> Result<ExtendedAddr>;
 
+    /// Imports an externally generated private key (as a non-HD key) and adds an address
+    /// of given type for it. Returns the public key of the imported key.
+    fn import_private_key(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        key: &ExternalKey,
+        address_type: AddressType,
+    ) -> Result<PublicKey>;
+
+    /// Retrieves public keys of the private keys imported into the wallet
+    fn imported_keys(&self, name: &str, enckey: &SecKey) -> Result<Vec<PublicKey>>;
+
     /// Generates a new multi-sig transfer address for creating m-of-n transactions
     ///
     /// # Arguments
@@ -293,6 +410,38 @@ pub trait WalletClient: Send + Sync {
         reversed: bool,
     ) -> Result<Vec<TransactionChange>>;
 
+    /// Retrieves a page of the transaction history of wallet matching the filter, starting after
+    /// the transaction at `cursor` (as returned in the previous page)
+    fn query_history(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        filter: &TransactionFilter,
+        cursor: Option<u64>,
+        limit: usize,
+        reversed: bool,
+    ) -> Result<TransactionHistoryPage>;
+
+    /// Returns at most `limit` events of the wallet event stream (the transactions in the order
+    /// they were added to the wallet), starting at the event with sequence number `from_sequence`
+    fn replay_events(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        from_sequence: u64,
+        limit: usize,
+    ) -> Result<Vec<WalletEvent>>;
+
+    /// Rebuilds the unspent transactions and the transaction history index of wallet from the
+    /// locally synced transactions, using `threads` worker threads
+    fn reindex_wallet(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        threads: usize,
+        progress: &mut dyn FnMut(ReindexProgress),
+    ) -> Result<ReindexReport>;
+
     /// Retrieves transaction change corresponding to given transaction ID
     fn get_transaction_change(
         &self,
@@ -304,6 +453,26 @@ pub trait WalletClient: Send + Sync {
     /// Retrieves all unspent transactions of wallet
     fn unspent_transactions(&self, name: &str, enckey: &SecKey) -> Result<UnspentTransactions>;
 
+    /// Returns whether or why not each unspent transaction of wallet can be spent (timelocks are
+    /// checked against the time of the latest block, and outputs with a value below
+    /// `dust_threshold` are reported as dust), with the amounts of the pending transactions
+    fn spendability(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        dust_threshold: Coin,
+    ) -> Result<SpendabilityReport>;
+
+    /// Freezes unspent transactions of wallet: they are not selected as inputs of the
+    /// transactions built by the wallet until they are unfrozen
+    fn freeze_outputs(&self, name: &str, enckey: &SecKey, outputs: &[TxoPointer]) -> Result<()>;
+
+    /// Unfreezes unspent transactions of wallet
+    fn unfreeze_outputs(&self, name: &str, enckey: &SecKey, outputs: &[TxoPointer]) -> Result<()>;
+
+    /// Retrieves the frozen unspent transactions of wallet
+    fn frozen_outputs(&self, name: &str, enckey: &SecKey) -> Result<Vec<TxoPointer>>;
+
     /// Checks if all the provided transaction inputs are present in unspent transaction for given wallet
     fn has_unspent_transactions(
         &self,
@@ -347,6 +516,30 @@ pub trait WalletClient: Send + Sync {
     /// Broadcasts a transaction to Thaler Experimental Network
     fn broadcast_transaction(&self, tx_aux: &TxAux) -> Result<BroadcastTxResponse>;
 
+    /// Returns the unconfirmed transactions (among at most `limit` ones in the mempool of the node)
+    /// which spend outputs of the wallet or operate on its staking addresses.
+    /// Outputs of transfer / withdraw transactions are encrypted and can only be decrypted by the
+    /// transaction query enclave once committed, so incoming transfers are not part of the preview.
+    fn mempool_transactions(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        limit: u64,
+    ) -> Result<Vec<MempoolTransaction>>;
+
+    /// Estimates the fee and the change split of a transfer transaction with the given outputs
+    /// (nothing is signed or broadcast). Wallet outputs spent by the unconfirmed transactions in
+    /// the mempool (among at most `limit` ones) are not selected as inputs.
+    fn estimate_fee(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        outputs: Vec<TxOut>,
+        view_keys: &mut BTreeSet<PublicKey>,
+        network_id: u8,
+        limit: u64,
+    ) -> Result<FeeEstimate>;
+
     /// When receiver's view key not included in the transaction, the receiver can't collect the outputs.
     /// The sender have to get the plain transaction and send it to the receiver by email or something
     /// so that the receiver can sync it into the wallet DB and get the outputs.
@@ -405,6 +598,308 @@ pub trait WalletClient: Send + Sync {
         signed_tx: SignedTransferTransaction,
     ) -> Result<TxId>;
 
+    /// Builds a transfer transaction to be signed by an offline signer (e.g. from a `watch-only`
+    /// wallet): the change of `watch-only` wallets goes to their first transfer address
+    fn build_signing_payload(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        to_address: ExtendedAddr,
+        amount: Coin,
+        view_keys: Vec<PublicKey>,
+        network_id: u8,
+    ) -> Result<SigningPayload>;
+
+    /// Broadcasts the transaction of the signing payload with the witness of the offline signer
+    fn broadcast_signing_payload(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        payload: SigningPayload,
+        witness: TxWitness,
+    ) -> Result<TxId>;
+
+    /// Creates an invoice of `amount` paid to a new transfer address of the wallet, which expires
+    /// `expiry` seconds after its creation (payments are detected when syncing the wallet)
+    fn create_invoice(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        amount: Coin,
+        expiry: u64,
+        memo: String,
+    ) -> Result<Invoice>;
+
+    /// Returns the invoice with the given id
+    fn invoice(&self, name: &str, enckey: &SecKey, id: u64) -> Result<Invoice>;
+
+    /// Returns all the invoices of the wallet
+    fn invoices(&self, name: &str, enckey: &SecKey) -> Result<Vec<Invoice>>;
+
+    /// Cancels the invoice (if it is still open or partially paid)
+    fn cancel_invoice(&self, name: &str, enckey: &SecKey, id: u64) -> Result<Invoice>;
+
+    /// Returns the invoice lifecycle events starting from the sequence number `from_sequence`
+    fn invoice_events(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        from_sequence: u64,
+    ) -> Result<Vec<InvoiceEvent>>;
+
+    /// Creates a vault of the wallet: its funds can be spent by a new key of the wallet, but
+    /// a spend only becomes valid `delay` seconds after it's initiated, while the (external)
+    /// cancel key can sweep the funds back to the vault in the meantime
+    fn new_vault(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        cancel_public_key: &PublicKey,
+        delay: Timespec,
+    ) -> Result<Vault>;
+
+    /// Returns all the vaults of the wallet
+    fn vaults(&self, name: &str, enckey: &SecKey) -> Result<Vec<Vault>>;
+
+    /// Returns all the spends from the vaults of the wallet (their status is updated when
+    /// syncing the wallet)
+    fn vault_spends(&self, name: &str, enckey: &SecKey) -> Result<Vec<VaultSpend>>;
+
+    /// Initiates a spend of `amount` from the vault to `to_address`: the amount (plus the fee
+    /// of completing the spend) is moved to a new pending address of the vault
+    #[allow(clippy::too_many_arguments)]
+    fn initiate_vault_spend(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        vault_address: &ExtendedAddr,
+        to_address: ExtendedAddr,
+        amount: Coin,
+        view_keys: &mut BTreeSet<PublicKey>,
+        network_id: u8,
+    ) -> Result<VaultSpend>;
+
+    /// Completes the vault spend initiated by the transaction with given ID (once the delay
+    /// passed): sends the amount from the pending address to the destination
+    fn complete_vault_spend(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        transaction_id: &TxId,
+        network_id: u8,
+    ) -> Result<TxId>;
+
+    /// Cancels the vault spend initiated by the transaction with given ID: sweeps the funds
+    /// of the pending address back to the vault with the cancel key
+    fn cancel_vault_spend(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        transaction_id: &TxId,
+        cancel_key: &PrivateKey,
+        network_id: u8,
+    ) -> Result<TxId>;
+
+    /// Prepares an inheritance plan (dead-man switch): `amount` is moved to an inheritance
+    /// address of a new key of the wallet, with a pre-signed package sending it to the heirs
+    /// which only becomes valid `refresh_period` seconds later, unless the plan is refreshed
+    /// (or cancelled) before; the plan is due for a refresh `reminder_period` seconds before
+    #[allow(clippy::too_many_arguments)]
+    fn new_inheritance_plan(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        heirs: Vec<Heir>,
+        amount: Coin,
+        refresh_period: Timespec,
+        reminder_period: Timespec,
+        view_keys: &mut BTreeSet<PublicKey>,
+        network_id: u8,
+    ) -> Result<InheritancePlan>;
+
+    /// Returns all the inheritance plans of the wallet (their status is updated when syncing
+    /// the wallet)
+    fn inheritance_plans(&self, name: &str, enckey: &SecKey) -> Result<Vec<InheritancePlan>>;
+
+    /// Returns the lifecycle events of the inheritance plans (including the refresh
+    /// reminders) with a sequence number greater than or equal to `from_sequence`
+    fn inheritance_events(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        from_sequence: u64,
+    ) -> Result<Vec<InheritanceEvent>>;
+
+    /// Refreshes the inheritance plan: moves its funds to a new inheritance address (which
+    /// cancels the current package) and pre-signs a package with a later unlock time
+    fn refresh_inheritance_plan(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        id: u64,
+        network_id: u8,
+    ) -> Result<InheritancePlan>;
+
+    /// Cancels the inheritance plan: moves its funds back to a transfer address of the wallet
+    fn cancel_inheritance_plan(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        id: u64,
+        network_id: u8,
+    ) -> Result<TxId>;
+
+    /// Exports the pre-signed package of the inheritance plan (to be handed to the heirs)
+    fn export_inheritance_package(&self, name: &str, enckey: &SecKey, id: u64) -> Result<Vec<u8>>;
+
+    /// Broadcasts an exported inheritance package (once it's unlocked), returns the ID of its
+    /// transaction
+    fn broadcast_inheritance_package(&self, package: &[u8]) -> Result<TxId>;
+
+    /// Schedules a transfer of `amount` to `to_address` every `interval` seconds, starting at
+    /// `start_at` (seconds since the unix epoch) until `end_at` (if any); the due payments are
+    /// sent by `pay_due_recurring_payments`
+    #[allow(clippy::too_many_arguments)]
+    fn create_recurring_payment(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        to_address: ExtendedAddr,
+        amount: Coin,
+        interval: u64,
+        start_at: u64,
+        end_at: Option<u64>,
+        view_keys: Vec<PublicKey>,
+        retry_policy: RetryPolicy,
+    ) -> Result<RecurringPayment>;
+
+    /// Returns all the recurring payments of the wallet
+    fn recurring_payments(&self, name: &str, enckey: &SecKey) -> Result<Vec<RecurringPayment>>;
+
+    /// Cancels the recurring payment (if it is still active)
+    fn cancel_recurring_payment(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        id: u64,
+    ) -> Result<RecurringPayment>;
+
+    /// Returns the recurring payment lifecycle events starting from the sequence number
+    /// `from_sequence`
+    fn recurring_payment_events(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        from_sequence: u64,
+    ) -> Result<Vec<RecurringPaymentEvent>>;
+
+    /// Builds, signs and broadcasts the recurring payments due at the current time (failed
+    /// payments are retried according to their retry policy), returns the new events
+    fn pay_due_recurring_payments(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        network_id: u8,
+    ) -> Result<Vec<RecurringPaymentEvent>>;
+
+    /// Sets the local annotation (memo, label and category) of a transaction, an empty
+    /// annotation removes the existing one
+    fn set_transaction_annotation(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        transaction_id: TxId,
+        annotation: Annotation,
+    ) -> Result<()>;
+
+    /// Returns the local annotation of a transaction
+    fn transaction_annotation(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        transaction_id: &TxId,
+    ) -> Result<Option<Annotation>>;
+
+    /// Removes the local annotation of a transaction, returns `false` if there wasn't any
+    fn delete_transaction_annotation(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        transaction_id: &TxId,
+    ) -> Result<bool>;
+
+    /// Sets the local annotation (memo, label and category) of an address, an empty annotation
+    /// removes the existing one
+    fn set_address_annotation(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        address: &ExtendedAddr,
+        annotation: Annotation,
+    ) -> Result<()>;
+
+    /// Returns the local annotation of an address
+    fn address_annotation(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        address: &ExtendedAddr,
+    ) -> Result<Option<Annotation>>;
+
+    /// Removes the local annotation of an address, returns `false` if there wasn't any
+    fn delete_address_annotation(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        address: &ExtendedAddr,
+    ) -> Result<bool>;
+
+    /// Returns all the local annotations of the wallet
+    fn annotations(&self, name: &str, enckey: &SecKey) -> Result<WalletAnnotations>;
+
+    /// Adds a named recipient to the address book of the wallet (fails if a contact with the
+    /// same name exists)
+    fn add_contact(&self, name: &str, enckey: &SecKey, contact: Contact) -> Result<()>;
+
+    /// Removes a contact from the address book, returns `false` if there wasn't any
+    fn remove_contact(&self, name: &str, enckey: &SecKey, contact_name: &str) -> Result<bool>;
+
+    /// Returns the contact with given name
+    fn contact(&self, name: &str, enckey: &SecKey, contact_name: &str) -> Result<Option<Contact>>;
+
+    /// Returns all the contacts in the address book of the wallet (sorted by name)
+    fn contacts(&self, name: &str, enckey: &SecKey) -> Result<Vec<Contact>>;
+
+    /// Sets the spending policy enforced when transfer transactions are built or signed; the
+    /// override passphrase is set with the first policy and must be provided to change it
+    fn set_spending_policy(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        policy: SpendingPolicy,
+        override_passphrase: &SecUtf8,
+    ) -> Result<()>;
+
+    /// Removes the spending policy of the wallet (requires the override passphrase)
+    fn remove_spending_policy(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        override_passphrase: &SecUtf8,
+    ) -> Result<()>;
+
+    /// Returns the spending policy of the wallet
+    fn spending_policy(&self, name: &str, enckey: &SecKey) -> Result<Option<SpendingPolicy>>;
+
+    /// Allows the next transaction violating the spending policy (for a few minutes), returns
+    /// the time until which the override is valid
+    fn approve_spending_override(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        override_passphrase: &SecUtf8,
+    ) -> Result<Timespec>;
+
     /// Get current sync state of wallet, return genesis one if not exists.
     fn get_sync_state(&self, name: &str) -> Result<SyncState>;
 
@@ -480,6 +975,22 @@ pub trait MultiSigWalletClient: WalletClient {
         public_key: &PublicKey,
     ) -> Result<()>;
 
+    /// Returns the next message of current signer to send to all co-signers (nonce commitment,
+    /// nonce or partial signature, depending on the messages already added to the session)
+    fn export_multi_sig_message(
+        &self,
+        session_id: &H256,
+        enckey: &SecKey,
+    ) -> Result<MultiSigMessage>;
+
+    /// Adds a message exported by a co-signer to session with given id
+    fn import_multi_sig_message(
+        &self,
+        session_id: &H256,
+        enckey: &SecKey,
+        message: &MultiSigMessage,
+    ) -> Result<()>;
+
     /// Returns final signature. This function will fail if partial signatures from all co-signers are not received.
     fn signature(&self, session_id: &H256, enckey: &SecKey) -> Result<SchnorrSignature>;
 
@@ -1,10 +1,24 @@
+use crate::coin_selection::SelectAll;
 use crate::hd_wallet::{ChainPath, HardwareKind};
+use crate::multi_sig::MultiSigMessage;
+use crate::plugin::is_custom_transaction;
 use crate::service::*;
 use crate::transaction_builder::UnauthorizedWalletTransactionBuilder;
-use crate::transaction_builder::{SignedTransferTransaction, UnsignedTransferTransaction};
+use crate::transaction_builder::{
+    SignedTransferTransaction, SigningPayload, UnsignedTransferTransaction,
+};
 use crate::types::{
-    AddressType, BalanceChange, TransactionChange, TransactionPending, WalletBalance, WalletKind,
+    inheritance_address_tree, inheritance_claim_proof, pending_address_tree, split_inheritance,
+    AddressType, Annotation, BalanceChange, Contact, FeeEstimate, Heir, InheritanceEvent,
+    InheritancePackage, InheritancePlan, InheritanceStatus, Invoice, InvoiceEvent,
+    MempoolTransaction, OwnedAddress, RecurringPayment, RecurringPaymentEvent, RetryPolicy,
+    Spendability, SpendabilityReport, SpendingPolicy, TransactionChange, TransactionFilter,
+    TransactionHistoryPage, TransactionInput, TransactionPending, TransactionType, TransferOptions,
+    Vault, VaultSpend, VaultSpendStatus, WalletAnnotations, WalletBalance, WalletEntry,
+    WalletEvent, WalletKind,
 };
+use crate::wallet::key_import::ExternalKey;
+use crate::wallet::passphrase_policy::PassphrasePolicy;
 use crate::wallet::syncer::{get_genesis_sync_state, AddressRecovery};
 use crate::wallet::syncer_logic::create_transaction_change;
 #[cfg(feature = "experimental")]
@@ -13,43 +27,40 @@ use crate::{
     InputSelectionStrategy, Mnemonic, UnspentTransactions, WalletClient, WalletTransactionBuilder,
 };
 use bit_vec::BitVec;
-use chain_core::common::{Proof, H256};
+use chain_core::common::{MerkleTree, Proof, Timespec, H256};
 use chain_core::init::address::RedeemAddress;
-use chain_core::init::coin::Coin;
+use chain_core::init::coin::{sum_coins, Coin};
 use chain_core::state::account::StakedStateAddress;
 use chain_core::tx::data::access::{TxAccess, TxAccessPolicy};
 use chain_core::tx::data::address::ExtendedAddr;
 use chain_core::tx::data::attribute::TxAttributes;
 use chain_core::tx::data::input::{str2txid, TxoPointer};
 use chain_core::tx::data::output::TxOut;
-#[cfg(feature = "experimental")]
 use chain_core::tx::data::Tx;
 use chain_core::tx::data::TxId;
 use chain_core::tx::fee::Fee;
 use chain_core::tx::witness::tree::RawXOnlyPubkey;
-#[cfg(feature = "experimental")]
-use chain_core::tx::witness::{TxInWitness, TxWitness};
-use chain_core::tx::{TxAux, TxEnclaveAux, TxObfuscated};
+use chain_core::tx::witness::TxInWitness;
+use chain_core::tx::witness::TxWitness;
+use chain_core::tx::{TxAux, TxEnclaveAux, TxObfuscated, TxPublicAux};
+use chain_tx_validation::witness::verify_tx_recover_address;
 use client_common::tendermint::types::Time;
 use client_common::tendermint::types::{AbciQueryExt, BlockResults, BroadcastTxResponse};
 use client_common::tendermint::{Client, UnauthorizedClient};
-#[cfg(feature = "experimental")]
 use client_common::SignedTransaction;
 use client_common::{
     seckey::derive_enckey, Error, ErrorKind, MultiSigAddress, PrivateKey, PrivateKeyAction,
     PublicKey, Result, ResultExt, SecKey, Storage, Transaction, TransactionInfo,
 };
 use indexmap::IndexSet;
-use parity_scale_codec::Encode;
-#[cfg(feature = "experimental")]
+use parity_scale_codec::{Decode, Encode};
 use secp256k1::schnorrsig::SchnorrSignature;
 use secstr::SecUtf8;
 use std::collections::BTreeMap;
 use std::collections::BTreeSet;
 use std::convert::TryInto;
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
-use zxcvbn::{feedback::Feedback, zxcvbn as estimate_password_strength};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 /// Default implementation of `WalletClient` based on `Storage` and `Index`
 #[derive(Debug, Default, Clone)]
 pub struct DefaultWalletClient<S, C, T>
@@ -63,6 +74,14 @@ where
     hw_key_service: HwKeyService,
     wallet_service: WalletService<S>,
     wallet_state_service: WalletStateService<S>,
+    invoice_service: InvoiceService<S>,
+    vault_service: VaultService<S>,
+    inheritance_service: InheritanceService<S>,
+    recurring_payment_service: RecurringPaymentService<S>,
+    annotation_service: AnnotationService<S>,
+    address_book_service: AddressBookService<S>,
+    spending_policy_service: SpendingPolicyService<S>,
+    job_service: JobService<S>,
     sync_state_service: SyncStateService<S>,
     root_hash_service: RootHashService<S>,
     #[cfg(feature = "experimental")]
@@ -70,6 +89,7 @@ where
 
     tendermint_client: C,
     transaction_builder: T,
+    input_selection_strategy: InputSelectionStrategy,
     block_height_ensure: Option<u64>,
     storage: S,
 }
@@ -94,17 +114,36 @@ where
             hw_key_service,
             wallet_service: WalletService::new(storage.clone()),
             wallet_state_service: WalletStateService::new(storage.clone()),
+            invoice_service: InvoiceService::new(storage.clone()),
+            vault_service: VaultService::new(storage.clone()),
+            inheritance_service: InheritanceService::new(storage.clone()),
+            recurring_payment_service: RecurringPaymentService::new(storage.clone()),
+            annotation_service: AnnotationService::new(storage.clone()),
+            address_book_service: AddressBookService::new(storage.clone()),
+            spending_policy_service: SpendingPolicyService::new(storage.clone()),
+            job_service: JobService::new(storage.clone()),
             sync_state_service: SyncStateService::new(storage.clone()),
             #[cfg(feature = "experimental")]
             multi_sig_session_service: MultiSigSessionService::new(storage.clone()),
             root_hash_service: RootHashService::new(storage.clone()),
             tendermint_client,
             transaction_builder,
+            input_selection_strategy: InputSelectionStrategy::default(),
             block_height_ensure,
             storage,
         }
     }
 
+    /// Sets the input selection strategy of transactions built without an explicit one
+    #[inline]
+    pub fn with_input_selection_strategy(
+        mut self,
+        input_selection_strategy: InputSelectionStrategy,
+    ) -> Self {
+        self.input_selection_strategy = input_selection_strategy;
+        self
+    }
+
     fn is_tx_exist(&self, name: &str, enckey: &SecKey, txid: TxId) -> Result<bool> {
         let tx_change = self
             .wallet_state_service
@@ -119,6 +158,426 @@ where
             });
         Ok(tx_change.is_ok())
     }
+
+    /// Explains an "Insufficient balance" error of the transaction builder with the breakdown
+    /// of the wallet balance by spendability (other errors are returned unchanged)
+    fn explain_insufficient_balance(&self, name: &str, enckey: &SecKey, error: Error) -> Error {
+        if error.kind() != ErrorKind::InvalidInput || error.message() != "Insufficient balance" {
+            return error;
+        }
+        match self.spendability(name, enckey, Coin::zero()) {
+            Ok(report) => Error::new(
+                ErrorKind::InvalidInput,
+                format!("Insufficient balance ({})", report),
+            ),
+            Err(_) => error,
+        }
+    }
+
+    /// Returns the derivation path of a public key of the wallet and whether the wallet
+    /// can't sign with it (watch-only)
+    fn key_ownership(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        public_key: &PublicKey,
+    ) -> Result<(Option<String>, bool)> {
+        let wallet = self.wallet_service.get_wallet_info(name, enckey)?;
+        let chain_path = self
+            .wallet_service
+            .find_chain_path(name, enckey, public_key)?;
+        let can_sign = match wallet.wallet_kind {
+            WalletKind::HW => chain_path.is_some(),
+            _ => self
+                .wallet_service
+                .find_private_key(name, enckey, public_key)?
+                .is_some(),
+        };
+        Ok((chain_path.map(ChainPath::into_string), !can_sign))
+    }
+
+    /// Returns the time of the latest block (seconds since the unix epoch)
+    fn latest_block_time(&self) -> Result<Timespec> {
+        Ok(self
+            .tendermint_client
+            .status()?
+            .sync_info
+            .latest_block_time
+            .duration_since(Time::unix_epoch())
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default())
+    }
+
+    /// Checks the outputs sent to other wallets against the spending policy of the wallet
+    /// (if any) and records them in its daily limit
+    fn enforce_spending_policy(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        outputs: &[TxOut],
+    ) -> Result<()> {
+        if self
+            .spending_policy_service
+            .get_policy(name, enckey)?
+            .is_none()
+        {
+            return Ok(());
+        }
+        let mut external_outputs = Vec::new();
+        for output in outputs {
+            if self
+                .wallet_service
+                .find_root_hash(name, enckey, &output.address)?
+                .is_none()
+            {
+                external_outputs.push(output.clone());
+            }
+        }
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .chain(|| (ErrorKind::InternalError, "System time is before unix epoch"))?
+            .as_secs();
+        self.spending_policy_service
+            .authorize(name, enckey, &external_outputs, now)
+    }
+
+    /// Builds the transaction sending all the funds of `from_address` (minus the fee) to
+    /// `to_address`, signing the inputs with given key and witness; returns the signed
+    /// transaction with its inputs and the sent amount
+    #[allow(clippy::too_many_arguments)]
+    fn build_sweep_transaction<W>(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        from_address: &ExtendedAddr,
+        to_address: ExtendedAddr,
+        view_keys: &[PublicKey],
+        sign_key: &dyn PrivateKeyAction,
+        witness: W,
+        network_id: u8,
+    ) -> Result<(SignedTransaction, Vec<TxoPointer>, Coin)>
+    where
+        W: Fn(SchnorrSignature) -> TxInWitness,
+    {
+        let inputs = self
+            .wallet_state_service
+            .get_unspent_transactions(name, enckey, false)?
+            .into_iter()
+            .filter(|(_, output)| output.address == *from_address)
+            .collect::<Vec<_>>();
+        if inputs.is_empty() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Address {} has no unspent funds", from_address),
+            ));
+        }
+        let total = sum_coins(inputs.iter().map(|(_, output)| output.value))
+            .chain(|| (ErrorKind::IllegalInput, "Invalid amount of the address"))?;
+        let used_inputs = inputs
+            .into_iter()
+            .map(|(input, _)| input)
+            .collect::<Vec<_>>();
+        let attributes = access_attributes(view_keys.iter(), network_id);
+
+        // the fee doesn't depend on the output value and the signatures
+        let mock_signature =
+            SchnorrSignature::from_default(&[0; 64]).expect("set mock signature failed");
+        let mock_tx = Tx::new_with(
+            used_inputs.clone(),
+            vec![TxOut::new(to_address.clone(), total)],
+            attributes.clone(),
+        );
+        let mock_witness = TxWitness::from(vec![witness(mock_signature); used_inputs.len()]);
+        let fee = self
+            .transaction_builder
+            .transfer_fee(&mock_tx, &mock_witness)?;
+        let value = (total - fee).chain(|| {
+            (
+                ErrorKind::InvalidInput,
+                "Funds of the address don't cover the fee",
+            )
+        })?;
+
+        let output = TxOut::new(to_address, value);
+        self.enforce_spending_policy(name, enckey, &[output.clone()])?;
+        let tx = Tx::new_with(used_inputs.clone(), vec![output], attributes);
+        let signature = sign_key.schnorr_sign(&Transaction::TransferTransaction(tx.clone()))?;
+        let tx_witness = TxWitness::from(vec![witness(signature); used_inputs.len()]);
+        Ok((
+            SignedTransaction::TransferTransaction(tx, tx_witness),
+            used_inputs,
+            value,
+        ))
+    }
+
+    /// Obfuscates and broadcasts a signed transaction of the wallet, then records its pending
+    /// state
+    fn broadcast_signed_transaction(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        signed_transaction: SignedTransaction,
+        used_inputs: Vec<TxoPointer>,
+        return_amount: Coin,
+    ) -> Result<TxId> {
+        let current_block_height = self.get_current_block_height()?;
+        let tx_aux = self.transaction_builder.obfuscate(signed_transaction)?;
+        self.broadcast_transaction(&tx_aux)?;
+        self.update_tx_pending_state(
+            name,
+            enckey,
+            tx_aux.tx_id(),
+            TransactionPending {
+                used_inputs,
+                block_height: current_block_height,
+                return_amount,
+            },
+        )?;
+        Ok(tx_aux.tx_id())
+    }
+
+    /// Sends all the funds of the pending address of a vault spend (minus the fee) to
+    /// `to_address`, signing the inputs with given key and witness
+    #[allow(clippy::too_many_arguments)]
+    fn sweep_pending_address<W>(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        spend: &VaultSpend,
+        to_address: ExtendedAddr,
+        sign_key: &dyn PrivateKeyAction,
+        witness: W,
+        network_id: u8,
+    ) -> Result<TxId>
+    where
+        W: Fn(SchnorrSignature) -> TxInWitness,
+    {
+        // funds swept back to the vault stay in the wallet
+        let to_vault = to_address == spend.vault_address;
+        let (signed_transaction, used_inputs, value) = self.build_sweep_transaction(
+            name,
+            enckey,
+            &spend.pending_address,
+            to_address,
+            &spend.view_keys,
+            sign_key,
+            witness,
+            network_id,
+        )?;
+        let return_amount = if to_vault { value } else { Coin::zero() };
+        self.broadcast_signed_transaction(
+            name,
+            enckey,
+            signed_transaction,
+            used_inputs,
+            return_amount,
+        )
+    }
+
+    /// Returns the addresses whose funds are only spent by the vault and inheritance workflows
+    fn reserved_addresses(&self, name: &str, enckey: &SecKey) -> Result<BTreeSet<ExtendedAddr>> {
+        let mut addresses = self.vault_service.get_vault_addresses(name, enckey)?;
+        addresses.extend(
+            self.inheritance_service
+                .get_inheritance_addresses(name, enckey)?,
+        );
+        Ok(addresses)
+    }
+
+    /// Returns the unspent transactions which can be selected as inputs of ordinary
+    /// transactions: vault and inheritance funds are only spent through their workflows,
+    /// frozen and explicitly excluded outputs are never selected
+    fn selectable_unspent_transactions(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        excluded_inputs: &[TxoPointer],
+    ) -> Result<UnspentTransactions> {
+        let reserved_addresses = self.reserved_addresses(name, enckey)?;
+        let mut excluded = self.wallet_state_service.get_frozen_outputs(name, enckey)?;
+        excluded.extend(excluded_inputs.iter().cloned());
+        Ok(UnspentTransactions::new(
+            self.unspent_transactions(name, enckey)?
+                .unwrap()
+                .into_iter()
+                .filter(|(pointer, output)| {
+                    !reserved_addresses.contains(&output.address) && !excluded.contains(pointer)
+                })
+                .collect(),
+        ))
+    }
+
+    /// Builds a transfer transaction with inputs selected among the selectable unspent
+    /// transactions (except `excluded_inputs`)
+    #[allow(clippy::too_many_arguments)]
+    fn build_transfer_transaction(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        outputs: Vec<TxOut>,
+        attributes: TxAttributes,
+        input_selection_strategy: Option<InputSelectionStrategy>,
+        return_address: ExtendedAddr,
+        excluded_inputs: &[TxoPointer],
+    ) -> Result<(TxAux, Vec<TxoPointer>, Coin)> {
+        let unspent_transactions =
+            self.selectable_unspent_transactions(name, enckey, excluded_inputs)?;
+        let coin_selection = input_selection_strategy
+            .unwrap_or(self.input_selection_strategy)
+            .coin_selection();
+
+        let built = self
+            .transaction_builder
+            .with_coin_selection(coin_selection)
+            .build_transfer_tx(
+                name,
+                enckey,
+                unspent_transactions,
+                outputs.clone(),
+                return_address,
+                attributes,
+            )
+            .map_err(|error| self.explain_insufficient_balance(name, enckey, error))?;
+        self.enforce_spending_policy(name, enckey, &outputs)?;
+        Ok(built)
+    }
+
+    /// Registers the inheritance address of the owner key with given unlock time
+    fn new_inheritance_address(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        owner_public_key: &PublicKey,
+        unlock_time: Timespec,
+    ) -> Result<ExtendedAddr> {
+        let merkle_tree = inheritance_address_tree(owner_public_key, unlock_time);
+        let root_hash = merkle_tree.root_hash();
+        self.root_hash_service
+            .set_multi_sig_address_from_root_hash(
+                name,
+                enckey,
+                &root_hash,
+                &MultiSigAddress {
+                    m: 1,
+                    n: 2,
+                    self_public_key: owner_public_key.clone(),
+                    merkle_tree,
+                },
+            )?;
+        self.wallet_service.add_root_hash(name, enckey, root_hash)?;
+        Ok(ExtendedAddr::OrTree(root_hash))
+    }
+
+    /// Pre-signs the package sending the funds of an inheritance address (the first output of
+    /// the funding transaction) to the heirs, valid from the unlock time
+    #[allow(clippy::too_many_arguments)]
+    fn sign_inheritance_package(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        owner_public_key: &PublicKey,
+        heirs: &[Heir],
+        view_keys: &[PublicKey],
+        address: ExtendedAddr,
+        unlock_time: Timespec,
+        funding_transaction_id: TxId,
+        amount: Coin,
+        network_id: u8,
+    ) -> Result<InheritancePackage> {
+        let proof = inheritance_claim_proof(owner_public_key, unlock_time)
+            .chain(|| (ErrorKind::InternalError, "Unable to generate proof"))?;
+        let owner_key = RawXOnlyPubkey::from(owner_public_key);
+        let witness = |signature| {
+            TxWitness::from(vec![TxInWitness::TimelockedTreeSig(
+                signature,
+                owner_key.clone(),
+                unlock_time,
+                proof.clone(),
+            )])
+        };
+        let inputs = vec![TxoPointer::new(funding_transaction_id, 0)];
+        let attributes = access_attributes(view_keys.iter(), network_id);
+
+        // the fee doesn't depend on the output values and the signature
+        let mock_tx = Tx::new_with(
+            inputs.clone(),
+            heirs
+                .iter()
+                .map(|heir| TxOut::new(heir.address.clone(), amount))
+                .collect(),
+            attributes.clone(),
+        );
+        let mock_signature =
+            SchnorrSignature::from_default(&[0; 64]).expect("set mock signature failed");
+        let fee = self
+            .transaction_builder
+            .transfer_fee(&mock_tx, &witness(mock_signature))?;
+        let value = (amount - fee).chain(|| {
+            (
+                ErrorKind::InvalidInput,
+                "Amount of the inheritance plan doesn't cover the fee",
+            )
+        })?;
+        let heir_amounts = split_inheritance(heirs, value)?;
+
+        let tx = Tx::new_with(
+            inputs,
+            heirs
+                .iter()
+                .zip(heir_amounts.iter())
+                .map(|(heir, value)| TxOut::new(heir.address.clone(), *value))
+                .collect(),
+            attributes,
+        );
+        let sign_key = self.sign_key(name, enckey, owner_public_key)?;
+        let signature = sign_key.schnorr_sign(&Transaction::TransferTransaction(tx.clone()))?;
+        let signed_transaction = SignedTransaction::TransferTransaction(tx, witness(signature));
+        Ok(InheritancePackage {
+            address,
+            funding_transaction_id,
+            amount,
+            unlock_time,
+            transaction_id: signed_transaction.tx_id(),
+            heir_amounts,
+            transaction: signed_transaction.encode(),
+        })
+    }
+
+    /// Stores the HD seed of the mnemonic (with its BIP39 passphrase) and the derived view key
+    fn create_hd_wallet(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        mnemonic: &Mnemonic,
+        hardware_kind: HardwareKind,
+    ) -> Result<()> {
+        self.hd_key_service
+            .add_mnemonic(name, Some(mnemonic), enckey)?;
+
+        let (public_key, private_key) =
+            self.hd_key_service
+                .generate_keypair(name, enckey, HDAccountType::Viewkey)?;
+
+        self.key_service
+            .add_wallet_private_key(name, &private_key, enckey)?;
+
+        self.wallet_service
+            .create(name, enckey, public_key, WalletKind::HD, hardware_kind)
+    }
+}
+
+/// Attributes of a transfer transaction readable by given view keys
+fn access_attributes<'a>(
+    view_keys: impl Iterator<Item = &'a PublicKey>,
+    network_id: u8,
+) -> TxAttributes {
+    let access_policies = view_keys
+        .map(|key| TxAccessPolicy {
+            view_key: key.into(),
+            access: TxAccess::AllData,
+        })
+        .collect::<BTreeSet<_>>();
+    TxAttributes::new_with_access(network_id, access_policies.into_iter().collect())
 }
 
 impl<S> DefaultWalletClient<S, UnauthorizedClient, UnauthorizedWalletTransactionBuilder>
@@ -167,7 +626,7 @@ where
             .hd_key_service
             .get_latest_transfer_index(name, enckey)?;
         let mut found = false;
-        let count = 20;
+        let count = GAP_LIMIT;
         for i in index..(index + count) {
             let publickey = self.hd_key_service.peek_pubkey(name, enckey, i)?;
             let (h256, _multisigaddr) = RootHashService::<S>::peek_new_root_hash(
@@ -249,9 +708,27 @@ where
         address: ExtendedAddr,
         view_keys: &mut BTreeSet<PublicKey>,
         network_id: u8,
+    ) -> Result<TxId> {
+        self.send_transfer(
+            name,
+            enckey,
+            vec![TxOut::new(address, amount)],
+            &TransferOptions::default(),
+            view_keys,
+            network_id,
+        )
+    }
+
+    fn send_transfer(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        outputs: Vec<TxOut>,
+        options: &TransferOptions,
+        view_keys: &mut BTreeSet<PublicKey>,
+        network_id: u8,
     ) -> Result<TxId> {
         let current_block_height = self.get_current_block_height()?;
-        let tx_out = TxOut::new(address, amount);
 
         let view_key = self.view_key(name, enckey)?;
 
@@ -268,16 +745,35 @@ where
         let attributes =
             TxAttributes::new_with_access(network_id, access_policies.into_iter().collect());
 
-        let return_address = self.new_transfer_address(name, enckey)?;
-        let (transaction, selected_inputs, return_amount) =
-            self.create_transaction(name, enckey, vec![tx_out], attributes, None, return_address)?;
+        let return_address = match &options.change_address {
+            Some(change_address) => change_address.clone(),
+            None => self.new_transfer_address(name, enckey)?,
+        };
+        // change sent to an address of another wallet doesn't come back to the balance
+        let own_return_address = self
+            .wallet_service
+            .find_root_hash(name, enckey, &return_address)?
+            .is_some();
+        let (transaction, selected_inputs, return_amount) = self.build_transfer_transaction(
+            name,
+            enckey,
+            outputs,
+            attributes,
+            None,
+            return_address,
+            &options.excluded_inputs,
+        )?;
 
         self.broadcast_transaction(&transaction)?;
         //update the wallet state
         let tx_pending = TransactionPending {
             used_inputs: selected_inputs,
             block_height: current_block_height,
-            return_amount,
+            return_amount: if own_return_address {
+                return_amount
+            } else {
+                Coin::zero()
+            },
         };
 
         self.update_tx_pending_state(name, enckey, transaction.tx_id(), tx_pending)?;
@@ -296,6 +792,71 @@ where
         }
     }
 
+    fn sweep_to_address(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        address: ExtendedAddr,
+        view_keys: &mut BTreeSet<PublicKey>,
+        network_id: u8,
+    ) -> Result<(TxId, Coin)> {
+        let current_block_height = self.get_current_block_height()?;
+
+        let view_key = self.view_key(name, enckey)?;
+        view_keys.insert(view_key);
+        let access_policies: BTreeSet<_> = view_keys
+            .iter()
+            .map(|key| TxAccessPolicy {
+                view_key: key.into(),
+                access: TxAccess::AllData,
+            })
+            .collect();
+        let attributes =
+            TxAttributes::new_with_access(network_id, access_policies.into_iter().collect());
+
+        // all the inputs are selected and, without outputs, the change output to the address
+        // gets everything but the fee
+        let unspent_transactions = self.selectable_unspent_transactions(name, enckey, &[])?;
+        let (transaction, used_inputs, amount) = self
+            .transaction_builder
+            .with_coin_selection(Arc::new(SelectAll))
+            .build_transfer_tx(
+                name,
+                enckey,
+                unspent_transactions,
+                vec![],
+                address.clone(),
+                attributes,
+            )
+            .map_err(|error| self.explain_insufficient_balance(name, enckey, error))?;
+        // a sub-threshold change is redirected to the consolidation address of the change policy
+        if amount == Coin::zero() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Swept amount is below the minimum change of the transaction builder",
+            ));
+        }
+        self.enforce_spending_policy(name, enckey, &[TxOut::new(address.clone(), amount)])?;
+
+        // funds swept to another address of the wallet stay in its balance
+        let own_address = self
+            .wallet_service
+            .find_root_hash(name, enckey, &address)?
+            .is_some();
+        self.broadcast_transaction(&transaction)?;
+        self.update_tx_pending_state(
+            name,
+            enckey,
+            transaction.tx_id(),
+            TransactionPending {
+                used_inputs,
+                block_height: current_block_height,
+                return_amount: if own_address { amount } else { Coin::zero() },
+            },
+        )?;
+        Ok((transaction.tx_id(), amount))
+    }
+
     /// broadcast transaction and waiting it confiremed
     fn send_to_address_commit(
         &self,
@@ -335,6 +896,14 @@ where
         self.wallet_service.names()
     }
 
+    fn wallet_entries(&self) -> Result<Vec<WalletEntry>> {
+        self.wallet_service.entries()
+    }
+
+    fn wallets_by_id(&self, id: &str) -> Result<Vec<String>> {
+        self.wallet_service.wallet_names_by_id(&id.to_lowercase())
+    }
+
     fn export_wallet(&self, name: &str, enckey: &SecKey) -> Result<WalletInfo> {
         let wallet = self.wallet_service.get_wallet(name, enckey)?;
         let private_key = self
@@ -386,6 +955,7 @@ where
 
         // get hdkey
         let hdkey = self.hd_key_service.get_hdkey(name, enckey)?;
+        let imported_keys = self.wallet_service.imported_keys(name, enckey)?;
 
         let wallet_info = WalletInfo {
             name: name.into(),
@@ -397,6 +967,8 @@ where
             hdkey,
             multisig_address_pair,
             staking_keys,
+            imported_keys,
+            annotations: self.annotation_service.get_annotations(name, enckey)?,
         };
         Ok(wallet_info)
     }
@@ -482,6 +1054,56 @@ where
             self.wallet_service
                 .add_staking_key(name, &enckey, staking_key)?;
         }
+
+        for public_key in wallet_info.imported_keys.iter() {
+            self.wallet_service
+                .add_imported_key(name, &enckey, public_key)?;
+        }
+
+        self.annotation_service
+            .import_annotations(name, &enckey, &wallet_info.annotations)?;
+        Ok(enckey)
+    }
+
+    fn export_wallet_backup(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        backup_passphrase: &SecUtf8,
+    ) -> Result<Vec<u8>> {
+        let wallet_info = self.export_wallet(name, enckey)?;
+        let sync_checkpoint = match (
+            self.sync_state_service.get_global_state(name)?,
+            load_wallet_state(&self.storage, name, enckey)?,
+        ) {
+            (Some(sync_state), Some(wallet_state)) => Some(SyncCheckpoint {
+                sync_state,
+                wallet_state,
+            }),
+            _ => None,
+        };
+        let backup = WalletBackup {
+            wallet_info,
+            sync_checkpoint,
+        };
+        self.wallet_service.export(&backup, backup_passphrase)
+    }
+
+    fn import_wallet_backup(
+        &self,
+        name: &str,
+        passphrase: &SecUtf8,
+        backup: &[u8],
+        backup_passphrase: &SecUtf8,
+    ) -> Result<SecKey> {
+        let mut backup = self.wallet_service.import(backup, backup_passphrase)?;
+        backup.wallet_info.name = name.to_owned();
+        let enckey = self.import_wallet(name, passphrase, &mut backup.wallet_info)?;
+        if let Some(checkpoint) = backup.sync_checkpoint {
+            save_wallet_state(&self.storage, name, &enckey, &checkpoint.wallet_state)?;
+            self.sync_state_service
+                .save_global_state(name, &checkpoint.sync_state)?;
+        }
         Ok(enckey)
     }
 
@@ -500,7 +1122,8 @@ where
         })?;
 
         match wallet_kind {
-            WalletKind::Basic => {
+            // a watch-only wallet only has the (local) view key pair, no spending keys are generated
+            WalletKind::Basic | WalletKind::WatchOnly => {
                 let private_key = PrivateKey::new()?;
                 let view_key = PublicKey::from(&private_key);
 
@@ -514,25 +1137,7 @@ where
             }
             WalletKind::HD => {
                 let mnemonic = Mnemonic::new(mnemonics_word_count.unwrap_or(24))?;
-
-                self.hd_key_service
-                    .add_mnemonic(name, Some(&mnemonic), &enckey)?;
-
-                let (public_key, private_key) =
-                    self.hd_key_service
-                        .generate_keypair(name, &enckey, HDAccountType::Viewkey)?;
-
-                self.key_service
-                    .add_wallet_private_key(name, &private_key, &enckey)?;
-
-                self.wallet_service.create(
-                    name,
-                    &enckey,
-                    public_key,
-                    wallet_kind,
-                    hardware_kind,
-                )?;
-
+                self.create_hd_wallet(name, &enckey, &mnemonic, hardware_kind)?;
                 Ok((enckey, Some(mnemonic)))
             }
             WalletKind::HW => {
@@ -551,11 +1156,24 @@ where
         }
     }
 
-    fn restore_wallet(
+    fn new_hd_wallet_with_mnemonic_passphrase(
         &self,
         name: &str,
         passphrase: &SecUtf8,
-        mnemonic: &Mnemonic,
+        mnemonics_word_count: Option<u32>,
+        mnemonic_passphrase: &SecUtf8,
+    ) -> Result<(SecKey, Mnemonic)> {
+        let mnemonic = Mnemonic::new(mnemonics_word_count.unwrap_or(24))?
+            .with_passphrase(mnemonic_passphrase.clone());
+        let enckey = self.restore_wallet(name, passphrase, &mnemonic)?;
+        Ok((enckey, mnemonic))
+    }
+
+    fn restore_wallet(
+        &self,
+        name: &str,
+        passphrase: &SecUtf8,
+        mnemonic: &Mnemonic,
     ) -> Result<SecKey> {
         check_passphrase_strength(name, passphrase)?;
 
@@ -563,23 +1181,7 @@ where
             "unable to derive encryption key from passphrase"
         })?;
 
-        self.hd_key_service
-            .add_mnemonic(name, Some(mnemonic), &enckey)?;
-
-        let (public_key, private_key) =
-            self.hd_key_service
-                .generate_keypair(name, &enckey, HDAccountType::Viewkey)?;
-
-        self.key_service
-            .add_wallet_private_key(name, &private_key, &enckey)?;
-
-        self.wallet_service.create(
-            name,
-            &enckey,
-            public_key,
-            WalletKind::HD,
-            HardwareKind::LocalOnly,
-        )?;
+        self.create_hd_wallet(name, &enckey, mnemonic, HardwareKind::LocalOnly)?;
         Ok(enckey)
     }
 
@@ -609,7 +1211,7 @@ where
     }
 
     fn delete_wallet(&self, name: &str, passphrase: &SecUtf8) -> Result<()> {
-        // remove from wallet/sync_state/wallet_state/key_service
+        // remove from wallet/sync_state/wallet_state/invoice/key_service
 
         let enckey = derive_enckey(passphrase, name).err_kind(ErrorKind::InvalidInput, || {
             "unable to derive encryption key from passphrase"
@@ -620,6 +1222,14 @@ where
         self.sync_state_service.delete_global_state(name)?;
         self.wallet_state_service
             .delete_wallet_state(name, &enckey)?;
+        self.invoice_service.delete_invoices(name)?;
+        self.vault_service.delete_vaults(name)?;
+        self.inheritance_service.delete_plans(name)?;
+        self.recurring_payment_service.delete_payments(name)?;
+        self.annotation_service.delete_annotations(name)?;
+        self.address_book_service.delete_address_book(name)?;
+        self.spending_policy_service.delete_policy(name)?;
+        self.job_service.delete_jobs(name)?;
         if self.hd_key_service.has_wallet(name)? {
             self.hd_key_service.delete_wallet(name, &enckey)?;
         }
@@ -712,6 +1322,72 @@ where
         self.wallet_service.find_root_hash(name, enckey, address)
     }
 
+    fn staking_address_ownership(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        address: &StakedStateAddress,
+    ) -> Result<Option<OwnedAddress>> {
+        let StakedStateAddress::BasicRedeem(redeem_address) = address;
+        let public_key = match self
+            .wallet_service
+            .find_staking_key(name, enckey, redeem_address)?
+        {
+            Some(public_key) => public_key,
+            None => return Ok(None),
+        };
+        let (derivation_path, watch_only) = self.key_ownership(name, enckey, &public_key)?;
+        Ok(Some(OwnedAddress::Staking {
+            public_key,
+            derivation_path,
+            watch_only,
+        }))
+    }
+
+    fn transfer_address_ownership(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        address: &ExtendedAddr,
+    ) -> Result<Option<OwnedAddress>> {
+        let root_hash = match self.wallet_service.find_root_hash(name, enckey, address)? {
+            Some(root_hash) => root_hash,
+            None => return Ok(None),
+        };
+        let multi_sig_address = self
+            .root_hash_service
+            .get_multi_sig_address_from_root_hash(name, &root_hash, enckey)?;
+
+        // the address is only reported as owned if the stored leaves still add up to it
+        let leaves = multi_sig_address
+            .merkle_tree
+            .values()
+            .into_iter()
+            .cloned()
+            .collect();
+        let ExtendedAddr::OrTree(address_root_hash) = address;
+        if MerkleTree::new(leaves).root_hash() != *address_root_hash {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Stored leaves of transfer address ({}) don't match its root hash",
+                    address
+                ),
+            ));
+        }
+
+        let self_public_key = multi_sig_address.self_public_key();
+        let (derivation_path, watch_only) = self.key_ownership(name, enckey, &self_public_key)?;
+        Ok(Some(OwnedAddress::Transfer {
+            root_hash,
+            self_public_key,
+            required_signers: multi_sig_address.m,
+            total_signers: multi_sig_address.n,
+            derivation_path,
+            watch_only,
+        }))
+    }
+
     #[inline]
     fn wallet_private_key(
         &self,
@@ -830,6 +1506,7 @@ where
                     .add_key_path(name, enckey, &public_key, &hd_path)?;
                 Ok(public_key)
             }
+            WalletKind::WatchOnly => Err(watch_only_keys_error()),
         }
     }
 
@@ -868,6 +1545,7 @@ where
                     .add_key_path(name, enckey, &public_key, &hd_path)?;
                 public_key
             }
+            WalletKind::WatchOnly => return Err(watch_only_keys_error()),
         };
 
         self.wallet_service
@@ -907,6 +1585,7 @@ where
                     .add_key_path(name, enckey, &public_key, &hd_path)?;
                 public_key
             }
+            WalletKind::WatchOnly => return Err(watch_only_keys_error()),
         };
         self.wallet_service
             .add_public_key(name, enckey, &public_key)?;
@@ -926,6 +1605,24 @@ where
         ret
     }
 
+    fn export_account_xpub(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        account: u32,
+        address_type: AddressType,
+    ) -> Result<String> {
+        let wallet = self.wallet_service.get_wallet_info(name, enckey)?;
+        if wallet.wallet_kind != WalletKind::HD {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Extended public keys can only be exported from HD wallets",
+            ));
+        }
+        self.hd_key_service
+            .export_account_xpub(name, enckey, account, address_type.into())
+    }
+
     fn new_watch_staking_address(
         &self,
         name: &str,
@@ -959,6 +1656,71 @@ where
         )
     }
 
+    fn import_private_key(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        key: &ExternalKey,
+        address_type: AddressType,
+    ) -> Result<PublicKey> {
+        let wallet = self.wallet_service.get_wallet_info(name, enckey)?;
+        if wallet.wallet_kind == WalletKind::HW {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Private keys can not be imported into a hardware wallet",
+            ));
+        }
+        if wallet.wallet_kind == WalletKind::WatchOnly {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Private keys can not be imported into a watch-only wallet",
+            ));
+        }
+        let private_key = key.decode()?;
+        let public_key = PublicKey::from(&private_key);
+        let exists = self
+            .wallet_service
+            .find_private_key(name, enckey, &public_key)?
+            .is_some();
+        if exists {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Imported key already exists in the wallet",
+            ));
+        }
+
+        self.wallet_service
+            .add_key_pairs(name, enckey, &public_key, &private_key)?;
+        self.wallet_service
+            .add_imported_key(name, enckey, &public_key)?;
+        match address_type {
+            AddressType::Staking => {
+                self.wallet_service
+                    .add_staking_key(name, enckey, &public_key)?;
+            }
+            AddressType::Transfer => {
+                self.wallet_service
+                    .add_public_key(name, enckey, &public_key)?;
+                self.new_multisig_transfer_address(
+                    name,
+                    enckey,
+                    vec![public_key.clone()],
+                    public_key.clone(),
+                    1,
+                )?;
+            }
+        }
+
+        self.storage
+            .flush()
+            .chain(|| (ErrorKind::IoError, "Unable to flush sled"))?;
+        Ok(public_key)
+    }
+
+    fn imported_keys(&self, name: &str, enckey: &SecKey) -> Result<Vec<PublicKey>> {
+        self.wallet_service.imported_keys(name, enckey)
+    }
+
     fn new_multisig_transfer_address(
         &self,
         name: &str,
@@ -1042,17 +1804,69 @@ where
         // Check if wallet exists
         self.wallet_service.view_key(name, enckey)?;
 
-        let history = self
+        let mut history = self
             .wallet_state_service
             .get_transaction_history(name, enckey, reversed)?
             .filter(|change| BalanceChange::NoChange != change.balance_change)
             .skip(offset)
             .take(limit)
             .collect::<Vec<_>>();
+        self.annotation_service
+            .annotate(name, enckey, &mut history)?;
 
         Ok(history)
     }
 
+    fn query_history(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        filter: &TransactionFilter,
+        cursor: Option<u64>,
+        limit: usize,
+        reversed: bool,
+    ) -> Result<TransactionHistoryPage> {
+        // Check if wallet exists
+        self.wallet_service.view_key(name, enckey)?;
+
+        let mut page = self
+            .wallet_state_service
+            .query_transaction_history(name, enckey, filter, cursor, limit, reversed)?;
+        self.annotation_service
+            .annotate(name, enckey, &mut page.transactions)?;
+        Ok(page)
+    }
+
+    fn replay_events(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        from_sequence: u64,
+        limit: usize,
+    ) -> Result<Vec<WalletEvent>> {
+        // Check if wallet exists
+        self.wallet_service.view_key(name, enckey)?;
+
+        self.wallet_state_service
+            .replay_events(name, enckey, from_sequence, limit)
+    }
+
+    fn reindex_wallet(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        threads: usize,
+        progress: &mut dyn FnMut(ReindexProgress),
+    ) -> Result<ReindexReport> {
+        let transfer_addresses = self
+            .wallet_service
+            .get_wallet(name, enckey)?
+            .get_transfer_addresses()?;
+
+        self.wallet_state_service
+            .reindex(name, enckey, &transfer_addresses, threads, progress)
+    }
+
     #[inline]
     fn get_transaction_change(
         &self,
@@ -1077,6 +1891,76 @@ where
         ))
     }
 
+    fn freeze_outputs(&self, name: &str, enckey: &SecKey, outputs: &[TxoPointer]) -> Result<()> {
+        self.wallet_state_service
+            .freeze_outputs(name, enckey, outputs)
+    }
+
+    fn unfreeze_outputs(&self, name: &str, enckey: &SecKey, outputs: &[TxoPointer]) -> Result<()> {
+        self.wallet_state_service
+            .unfreeze_outputs(name, enckey, outputs)
+    }
+
+    fn frozen_outputs(&self, name: &str, enckey: &SecKey) -> Result<Vec<TxoPointer>> {
+        Ok(self
+            .wallet_state_service
+            .get_frozen_outputs(name, enckey)?
+            .into_iter()
+            .collect())
+    }
+
+    fn spendability(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        dust_threshold: Coin,
+    ) -> Result<SpendabilityReport> {
+        // Check if wallet exists
+        self.wallet_service.view_key(name, enckey)?;
+
+        let block_time = self.latest_block_time()?;
+        // 1-of-1 transfer addresses are also stored as multi-sig addresses; watch-only ones
+        // have no details stored, so they're reported as spendable
+        let required_signers: BTreeMap<ExtendedAddr, usize> = self
+            .wallet_service
+            .root_hashes(name, enckey, 0, 0, false)?
+            .iter()
+            .filter_map(|root_hash| {
+                self.root_hash_service
+                    .get_multi_sig_address_from_root_hash(name, root_hash, enckey)
+                    .ok()
+            })
+            .map(|address| (address.to_extended_addr(), address.required_signers()))
+            .collect();
+
+        let vault_addresses = self.vault_service.get_vault_addresses(name, enckey)?;
+        let inheritance_addresses = self
+            .inheritance_service
+            .get_inheritance_addresses(name, enckey)?;
+        let frozen_outputs = self.wallet_state_service.get_frozen_outputs(name, enckey)?;
+
+        let mut report = self.wallet_state_service.get_spendability_report(
+            name,
+            enckey,
+            block_time,
+            dust_threshold,
+            |address| Ok(required_signers.get(address).copied().unwrap_or(1)),
+        )?;
+        for utxo in report.outputs.iter_mut() {
+            if matches!(utxo.spendability, Spendability::Reserved { .. }) {
+                continue;
+            }
+            if vault_addresses.contains(&utxo.output.address) {
+                utxo.spendability = Spendability::InVault;
+            } else if inheritance_addresses.contains(&utxo.output.address) {
+                utxo.spendability = Spendability::InInheritancePlan;
+            } else if frozen_outputs.contains(&utxo.pointer) {
+                utxo.spendability = Spendability::Frozen;
+            }
+        }
+        Ok(report)
+    }
+
     fn has_unspent_transactions(
         &self,
         name: &str,
@@ -1127,16 +2011,14 @@ where
         input_selection_strategy: Option<InputSelectionStrategy>,
         return_address: ExtendedAddr,
     ) -> Result<(TxAux, Vec<TxoPointer>, Coin)> {
-        let mut unspent_transactions = self.unspent_transactions(name, enckey)?;
-        unspent_transactions.apply_all(input_selection_strategy.unwrap_or_default().as_ref());
-
-        self.transaction_builder.build_transfer_tx(
+        self.build_transfer_transaction(
             name,
             enckey,
-            unspent_transactions,
             outputs,
-            return_address,
             attributes,
+            input_selection_strategy,
+            return_address,
+            &[],
         )
     }
 
@@ -1146,6 +2028,164 @@ where
             .broadcast_transaction(&tx_aux.encode())
     }
 
+    fn mempool_transactions(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        limit: u64,
+    ) -> Result<Vec<MempoolTransaction>> {
+        let unspent_transactions = self
+            .wallet_state_service
+            .get_unspent_transactions(name, enckey, true)?;
+
+        let mut transactions = Vec::new();
+        for raw_tx in self.tendermint_client.unconfirmed_txs(limit)? {
+            // transactions of custom types (see `plugin`) aren't tracked in the mempool
+            if is_custom_transaction(&raw_tx) {
+                continue;
+            }
+            let tx_aux = match TxAux::decode(&mut raw_tx.as_slice()) {
+                Ok(tx_aux) => tx_aux,
+                // invalid transactions are rejected by the node anyway
+                Err(_) => continue,
+            };
+            let (transaction_type, tx_inputs, staking_address, amount) = match &tx_aux {
+                TxAux::EnclaveTx(TxEnclaveAux::TransferTx { inputs, .. }) => {
+                    (TransactionType::Transfer, inputs.as_slice(), None, None)
+                }
+                TxAux::EnclaveTx(TxEnclaveAux::DepositStakeTx { tx, .. }) => (
+                    TransactionType::Deposit,
+                    tx.inputs.as_slice(),
+                    Some(tx.to_staked_account),
+                    None,
+                ),
+                TxAux::EnclaveTx(TxEnclaveAux::WithdrawUnbondedStakeTx {
+                    witness,
+                    payload,
+                    ..
+                }) => (
+                    TransactionType::Withdraw,
+                    &[][..],
+                    verify_tx_recover_address(witness, &payload.txid).ok(),
+                    None,
+                ),
+                TxAux::PublicTx(TxPublicAux::UnbondStakeTx(tx, _)) => (
+                    TransactionType::Unbond,
+                    &[][..],
+                    Some(tx.from_staked_account),
+                    Some(tx.value),
+                ),
+                TxAux::PublicTx(TxPublicAux::UnjailTx(tx, _)) => {
+                    (TransactionType::Unjail, &[][..], Some(tx.address), None)
+                }
+                TxAux::PublicTx(TxPublicAux::NodeJoinTx(tx, _)) => {
+                    (TransactionType::Nodejoin, &[][..], Some(tx.address), None)
+                }
+                // no balance change for the wallets
+                TxAux::PublicTx(TxPublicAux::NetworkParamsUpdateTx(..))
+                | TxAux::PublicTx(TxPublicAux::UpgradeSignalTx(..))
+                | TxAux::PublicTx(TxPublicAux::DataAnchorTx(..))
+                | TxAux::PublicTx(TxPublicAux::AccountFreezeTx(..)) => continue,
+            };
+
+            let inputs = tx_inputs
+                .iter()
+                .filter_map(|pointer| {
+                    unspent_transactions
+                        .get(pointer)
+                        .map(|output| TransactionInput {
+                            pointer: pointer.clone(),
+                            output: Some(output.clone()),
+                        })
+                })
+                .collect::<Vec<_>>();
+            let staking_address = match staking_address {
+                Some(StakedStateAddress::BasicRedeem(ref redeem_address))
+                    if self
+                        .find_staking_key(name, enckey, redeem_address)?
+                        .is_some() =>
+                {
+                    staking_address
+                }
+                _ => None,
+            };
+            if inputs.is_empty() && staking_address.is_none() {
+                continue;
+            }
+
+            transactions.push(MempoolTransaction {
+                transaction_id: tx_aux.tx_id(),
+                transaction_type,
+                inputs,
+                staking_address,
+                amount,
+            });
+        }
+        Ok(transactions)
+    }
+
+    fn estimate_fee(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        outputs: Vec<TxOut>,
+        view_keys: &mut BTreeSet<PublicKey>,
+        network_id: u8,
+        limit: u64,
+    ) -> Result<FeeEstimate> {
+        let view_key = self.view_key(name, enckey)?;
+        view_keys.insert(view_key);
+
+        let access_policies: BTreeSet<_> = view_keys
+            .iter()
+            .map(|key| TxAccessPolicy {
+                view_key: key.into(),
+                access: TxAccess::AllData,
+            })
+            .collect();
+        let attributes =
+            TxAttributes::new_with_access(network_id, access_policies.into_iter().collect());
+
+        // outputs spent by unconfirmed transactions (e.g. broadcast by another client of the
+        // same wallet) would make the transaction rejected, so they are not selected
+        let mempool_spent: BTreeSet<TxoPointer> = self
+            .mempool_transactions(name, enckey, limit)?
+            .into_iter()
+            .flat_map(|transaction| transaction.inputs)
+            .map(|input| input.pointer)
+            .collect();
+        let (unspent_transactions, excluded_inputs): (Vec<_>, Vec<_>) = self
+            .selectable_unspent_transactions(name, enckey, &[])?
+            .unwrap()
+            .into_iter()
+            .partition(|(pointer, _)| !mempool_spent.contains(pointer));
+
+        // the change output has the same size for any transfer address, so no new address
+        // is generated for the estimation
+        let return_address = ExtendedAddr::OrTree([0; 32]);
+        let dry_run = self
+            .transaction_builder
+            .with_coin_selection(self.input_selection_strategy.coin_selection())
+            .dry_run_transfer_tx(
+                &UnspentTransactions::new(unspent_transactions),
+                outputs,
+                return_address,
+                attributes,
+            )
+            .map_err(|error| self.explain_insufficient_balance(name, enckey, error))?;
+
+        Ok(FeeEstimate {
+            fee: dry_run.fee,
+            size: dry_run.size as u64,
+            inputs: dry_run.inputs,
+            change: dry_run.change,
+            excluded_inputs: excluded_inputs
+                .into_iter()
+                .map(|(pointer, _)| pointer)
+                .collect(),
+        })
+    }
+
     fn export_plain_tx(&self, name: &str, enckey: &SecKey, txid: &str) -> Result<TransactionInfo> {
         let txid = str2txid(txid).chain(|| (ErrorKind::InvalidInput, "invalid transaction id"))?;
         let tx = self.get_transaction(name, enckey, txid)?;
@@ -1263,7 +2303,7 @@ where
         view_keys: Vec<PublicKey>,
         network_id: u8,
     ) -> Result<UnsignedTransferTransaction> {
-        let unspent_transactions = self.unspent_transactions(name, enckey)?;
+        let unspent_transactions = self.selectable_unspent_transactions(name, enckey, &[])?;
         let return_address = self.new_transfer_address(name, enckey)?;
         let unsigned = UnsignedTransferTransaction {
             unspent_transactions,
@@ -1306,10 +2346,11 @@ where
                 name,
                 enckey,
                 unsigned_tx.unspent_transactions,
-                vec![tx_out],
+                vec![tx_out.clone()],
                 return_address,
                 attributes,
             )?;
+        self.enforce_spending_policy(name, enckey, &[tx_out])?;
         let signed_tx = SignedTransferTransaction {
             signed_transaction: transaction,
             used_inputs: selected_inputs,
@@ -1353,34 +2394,886 @@ where
         }
     }
 
-    fn get_sync_state(&self, name: &str) -> Result<SyncState> {
-        let mstate = self.sync_state_service.get_global_state(name)?;
-        let sync_state = if let Some(sync_state) = mstate {
-            sync_state
+    fn build_signing_payload(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        to_address: ExtendedAddr,
+        amount: Coin,
+        view_keys: Vec<PublicKey>,
+        network_id: u8,
+    ) -> Result<SigningPayload> {
+        let tx_out = TxOut::new(to_address, amount);
+        let view_key = self.view_key(name, enckey)?;
+        let access_policies: BTreeSet<_> = view_keys
+            .iter()
+            .chain(std::iter::once(&view_key))
+            .map(|key| TxAccessPolicy {
+                view_key: key.into(),
+                access: TxAccess::AllData,
+            })
+            .collect();
+        let attributes =
+            TxAttributes::new_with_access(network_id, access_policies.into_iter().collect());
+
+        let return_address = if self.get_wallet_kind(name, enckey)? == WalletKind::WatchOnly {
+            self.transfer_addresses(name, enckey, 0, 1, false)?
+                .into_iter()
+                .next()
+                .chain(|| {
+                    (
+                        ErrorKind::InvalidInput,
+                        "Watch-only wallet has no transfer address for the change",
+                    )
+                })?
         } else {
-            get_genesis_sync_state(&self.tendermint_client, true)?
+            self.new_transfer_address(name, enckey)?
         };
-        Ok(sync_state)
+
+        let unspent_transactions = self.selectable_unspent_transactions(name, enckey, &[])?;
+        let coin_selection = self.input_selection_strategy.coin_selection();
+
+        let payload = self
+            .transaction_builder
+            .with_coin_selection(coin_selection)
+            .build_signing_payload(
+                name,
+                enckey,
+                unspent_transactions,
+                vec![tx_out.clone()],
+                return_address,
+                attributes,
+            )?;
+        self.enforce_spending_policy(name, enckey, &[tx_out])?;
+        Ok(payload)
     }
-}
 
-#[cfg(feature = "experimental")]
-impl<S, C, T> MultiSigWalletClient for DefaultWalletClient<S, C, T>
-where
-    S: Storage + 'static,
-    C: Client,
-    T: WalletTransactionBuilder,
-{
-    fn schnorr_signature(
+    fn broadcast_signing_payload(
         &self,
         name: &str,
         enckey: &SecKey,
-        tx: &Transaction,
-        public_key: &PublicKey,
-    ) -> Result<SchnorrSignature> {
-        // To verify if the enckey is correct or not
-        self.transfer_addresses(name, enckey)?;
-        let wallet = self.wallet_service.get_wallet_info(name, enckey)?;
+        payload: SigningPayload,
+        witness: TxWitness,
+    ) -> Result<TxId> {
+        let tx_pending = TransactionPending {
+            used_inputs: payload.used_inputs(),
+            block_height: self.get_current_block_height()?,
+            return_amount: payload.return_amount,
+        };
+        let transaction = self
+            .transaction_builder
+            .finalize_signing_payload(payload, witness)?;
+
+        self.broadcast_transaction(&transaction)?;
+        self.update_tx_pending_state(name, enckey, transaction.tx_id(), tx_pending)?;
+
+        Ok(transaction.tx_id())
+    }
+
+    fn create_invoice(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        amount: Coin,
+        expiry: u64,
+        memo: String,
+    ) -> Result<Invoice> {
+        let created_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .chain(|| (ErrorKind::InternalError, "System time is before unix epoch"))?
+            .as_secs();
+        let expires_at = created_at
+            .checked_add(expiry)
+            .err_kind(ErrorKind::InvalidInput, || {
+                format!("Invalid invoice expiry: {}", expiry)
+            })?;
+        // a dedicated address per invoice, so its payments can be told apart
+        let address = self.new_transfer_address(name, enckey)?;
+        self.invoice_service
+            .create_invoice(name, enckey, amount, address, memo, created_at, expires_at)
+    }
+
+    #[inline]
+    fn invoice(&self, name: &str, enckey: &SecKey, id: u64) -> Result<Invoice> {
+        self.invoice_service.get_invoice(name, enckey, id)
+    }
+
+    #[inline]
+    fn invoices(&self, name: &str, enckey: &SecKey) -> Result<Vec<Invoice>> {
+        self.invoice_service.get_invoices(name, enckey)
+    }
+
+    #[inline]
+    fn cancel_invoice(&self, name: &str, enckey: &SecKey, id: u64) -> Result<Invoice> {
+        self.invoice_service.cancel_invoice(name, enckey, id)
+    }
+
+    #[inline]
+    fn invoice_events(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        from_sequence: u64,
+    ) -> Result<Vec<InvoiceEvent>> {
+        self.invoice_service.get_events(name, enckey, from_sequence)
+    }
+
+    fn new_vault(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        cancel_public_key: &PublicKey,
+        delay: Timespec,
+    ) -> Result<Vault> {
+        if delay == 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Delay of the vault should be greater than zero",
+            ));
+        }
+        let spend_public_key = self.new_public_key(name, enckey, Some(AddressType::Transfer))?;
+        if spend_public_key == *cancel_public_key {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Cancel key of the vault should be different from the keys of the wallet",
+            ));
+        }
+        let address = self.new_multisig_transfer_address(
+            name,
+            enckey,
+            vec![spend_public_key.clone(), cancel_public_key.clone()],
+            spend_public_key.clone(),
+            1,
+        )?;
+        let vault = Vault {
+            address,
+            spend_public_key,
+            cancel_public_key: cancel_public_key.clone(),
+            delay,
+            alerts: Vec::new(),
+        };
+        self.vault_service.add_vault(name, enckey, vault.clone())?;
+        Ok(vault)
+    }
+
+    #[inline]
+    fn vaults(&self, name: &str, enckey: &SecKey) -> Result<Vec<Vault>> {
+        self.vault_service.get_vaults(name, enckey)
+    }
+
+    #[inline]
+    fn vault_spends(&self, name: &str, enckey: &SecKey) -> Result<Vec<VaultSpend>> {
+        self.vault_service.get_spends(name, enckey)
+    }
+
+    fn initiate_vault_spend(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        vault_address: &ExtendedAddr,
+        to_address: ExtendedAddr,
+        amount: Coin,
+        view_keys: &mut BTreeSet<PublicKey>,
+        network_id: u8,
+    ) -> Result<VaultSpend> {
+        let vault = self.vault_service.get_vault(name, enckey, vault_address)?;
+        let current_block_height = self.get_current_block_height()?;
+        let unlock_time = self
+            .latest_block_time()?
+            .checked_add(vault.delay)
+            .chain(|| (ErrorKind::InvalidInput, "Invalid delay of the vault"))?;
+
+        // the pending address is stored with the spend key as the key of the wallet, so it's
+        // only spent with the timelocked leaf by the vault workflow
+        let merkle_tree = pending_address_tree(
+            &vault.spend_public_key,
+            &vault.cancel_public_key,
+            unlock_time,
+        );
+        let root_hash = merkle_tree.root_hash();
+        self.root_hash_service
+            .set_multi_sig_address_from_root_hash(
+                name,
+                enckey,
+                &root_hash,
+                &MultiSigAddress {
+                    m: 1,
+                    n: 2,
+                    self_public_key: vault.spend_public_key.clone(),
+                    merkle_tree,
+                },
+            )?;
+        self.wallet_service.add_root_hash(name, enckey, root_hash)?;
+
+        view_keys.insert(self.view_key(name, enckey)?);
+        let mut spend = VaultSpend {
+            transaction_id: TxId::default(),
+            vault_address: vault.address.clone(),
+            pending_address: ExtendedAddr::OrTree(root_hash),
+            unlock_time,
+            to_address: to_address.clone(),
+            amount,
+            view_keys: view_keys.iter().cloned().collect(),
+            status: VaultSpendStatus::Unconfirmed,
+        };
+        let attributes = access_attributes(view_keys.iter(), network_id);
+
+        // the pending address also holds the fee of the completing transaction
+        let proof = spend
+            .spend_proof(&vault)
+            .chain(|| (ErrorKind::InternalError, "Unable to generate proof"))?;
+        let completion_witness = TxWitness::from(vec![TxInWitness::TimelockedTreeSig(
+            SchnorrSignature::from_default(&[0; 64]).expect("set mock signature failed"),
+            RawXOnlyPubkey::from(&vault.spend_public_key),
+            unlock_time,
+            proof,
+        )]);
+        let completion_tx = Tx::new_with(
+            vec![TxoPointer::new(TxId::default(), 0)],
+            vec![TxOut::new(to_address, amount)],
+            attributes.clone(),
+        );
+        let completion_fee = self
+            .transaction_builder
+            .transfer_fee(&completion_tx, &completion_witness)?;
+        let pending_amount = (amount + completion_fee)
+            .chain(|| (ErrorKind::InvalidInput, "Invalid amount of the vault spend"))?;
+
+        let unspent_transactions = UnspentTransactions::new(
+            self.wallet_state_service
+                .get_unspent_transactions(name, enckey, false)?
+                .into_iter()
+                .filter(|(_, output)| output.address == vault.address)
+                .collect(),
+        );
+        let (tx_aux, used_inputs, return_amount) = self
+            .transaction_builder
+            .with_coin_selection(self.input_selection_strategy.coin_selection())
+            .build_transfer_tx(
+                name,
+                enckey,
+                unspent_transactions,
+                vec![TxOut::new(spend.pending_address.clone(), pending_amount)],
+                vault.address.clone(),
+                attributes,
+            )?;
+        spend.transaction_id = tx_aux.tx_id();
+
+        // the spend is recorded before broadcasting, so its pending address is never lost
+        self.vault_service.add_spend(name, enckey, spend.clone())?;
+        self.broadcast_transaction(&tx_aux)?;
+        self.update_tx_pending_state(
+            name,
+            enckey,
+            spend.transaction_id,
+            TransactionPending {
+                used_inputs,
+                block_height: current_block_height,
+                return_amount,
+            },
+        )?;
+        Ok(spend)
+    }
+
+    fn complete_vault_spend(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        transaction_id: &TxId,
+        network_id: u8,
+    ) -> Result<TxId> {
+        let spend = self.vault_service.get_spend(name, enckey, transaction_id)?;
+        if !spend.status.is_active() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Vault spend is already completed or cancelled",
+            ));
+        }
+        if self.latest_block_time()? < spend.unlock_time {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Delay of the vault spend hasn't passed yet",
+            ));
+        }
+        let vault = self
+            .vault_service
+            .get_vault(name, enckey, &spend.vault_address)?;
+        let proof = spend
+            .spend_proof(&vault)
+            .chain(|| (ErrorKind::InternalError, "Unable to generate proof"))?;
+        let sign_key = self.sign_key(name, enckey, &vault.spend_public_key)?;
+        let spend_key = RawXOnlyPubkey::from(&vault.spend_public_key);
+        self.sweep_pending_address(
+            name,
+            enckey,
+            &spend,
+            spend.to_address.clone(),
+            sign_key.as_ref(),
+            |signature| {
+                TxInWitness::TimelockedTreeSig(
+                    signature,
+                    spend_key.clone(),
+                    spend.unlock_time,
+                    proof.clone(),
+                )
+            },
+            network_id,
+        )
+    }
+
+    fn cancel_vault_spend(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        transaction_id: &TxId,
+        cancel_key: &PrivateKey,
+        network_id: u8,
+    ) -> Result<TxId> {
+        let spend = self.vault_service.get_spend(name, enckey, transaction_id)?;
+        if !spend.status.is_active() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Vault spend is already completed or cancelled",
+            ));
+        }
+        let vault = self
+            .vault_service
+            .get_vault(name, enckey, &spend.vault_address)?;
+        if PublicKey::from(cancel_key) != vault.cancel_public_key {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Given key is not the cancel key of the vault",
+            ));
+        }
+        let proof = spend
+            .cancel_proof(&vault)
+            .chain(|| (ErrorKind::InternalError, "Unable to generate proof"))?;
+        self.sweep_pending_address(
+            name,
+            enckey,
+            &spend,
+            spend.vault_address.clone(),
+            cancel_key,
+            |signature| TxInWitness::TreeSig(signature, proof.clone()),
+            network_id,
+        )
+    }
+
+    fn new_inheritance_plan(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        heirs: Vec<Heir>,
+        amount: Coin,
+        refresh_period: Timespec,
+        reminder_period: Timespec,
+        view_keys: &mut BTreeSet<PublicKey>,
+        network_id: u8,
+    ) -> Result<InheritancePlan> {
+        if heirs.is_empty() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Inheritance plan should have at least one heir",
+            ));
+        }
+        if reminder_period >= refresh_period {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Reminder period of the inheritance plan should be shorter than its refresh period",
+            ));
+        }
+        let current_block_height = self.get_current_block_height()?;
+        let unlock_time = self
+            .latest_block_time()?
+            .checked_add(refresh_period)
+            .chain(|| (ErrorKind::InvalidInput, "Invalid refresh period"))?;
+
+        let owner_public_key = self.new_public_key(name, enckey, Some(AddressType::Transfer))?;
+        let address = self.new_inheritance_address(name, enckey, &owner_public_key, unlock_time)?;
+        view_keys.insert(self.view_key(name, enckey)?);
+        let view_keys = view_keys.iter().cloned().collect::<Vec<_>>();
+
+        let unspent_transactions = self.selectable_unspent_transactions(name, enckey, &[])?;
+        let return_address = self.new_transfer_address(name, enckey)?;
+        let (tx_aux, used_inputs, return_amount) = self
+            .transaction_builder
+            .with_coin_selection(self.input_selection_strategy.coin_selection())
+            .build_transfer_tx(
+                name,
+                enckey,
+                unspent_transactions,
+                vec![TxOut::new(address.clone(), amount)],
+                return_address,
+                access_attributes(view_keys.iter(), network_id),
+            )?;
+        let package = self.sign_inheritance_package(
+            name,
+            enckey,
+            &owner_public_key,
+            &heirs,
+            &view_keys,
+            address,
+            unlock_time,
+            tx_aux.tx_id(),
+            amount,
+            network_id,
+        )?;
+
+        // the plan is recorded before broadcasting, so its inheritance address is never lost
+        let plan = self.inheritance_service.add_plan(
+            name,
+            enckey,
+            InheritancePlan {
+                id: 0,
+                owner_public_key,
+                heirs,
+                refresh_period,
+                reminder_period,
+                view_keys,
+                package,
+                status: InheritanceStatus::Unconfirmed,
+            },
+        )?;
+        self.broadcast_transaction(&tx_aux)?;
+        self.update_tx_pending_state(
+            name,
+            enckey,
+            tx_aux.tx_id(),
+            TransactionPending {
+                used_inputs,
+                block_height: current_block_height,
+                return_amount,
+            },
+        )?;
+        Ok(plan)
+    }
+
+    #[inline]
+    fn inheritance_plans(&self, name: &str, enckey: &SecKey) -> Result<Vec<InheritancePlan>> {
+        self.inheritance_service.get_plans(name, enckey)
+    }
+
+    #[inline]
+    fn inheritance_events(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        from_sequence: u64,
+    ) -> Result<Vec<InheritanceEvent>> {
+        self.inheritance_service
+            .get_events(name, enckey, from_sequence)
+    }
+
+    fn refresh_inheritance_plan(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        id: u64,
+        network_id: u8,
+    ) -> Result<InheritancePlan> {
+        let plan = self.inheritance_service.get_plan(name, enckey, id)?;
+        match plan.status {
+            InheritanceStatus::Unconfirmed => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "Package of the inheritance plan is not confirmed yet",
+                ))
+            }
+            InheritanceStatus::Claimed { .. } | InheritanceStatus::Cancelled { .. } => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "Inheritance plan is already claimed or cancelled",
+                ))
+            }
+            _ => {}
+        }
+        let unlock_time = self
+            .latest_block_time()?
+            .checked_add(plan.refresh_period)
+            .chain(|| (ErrorKind::InvalidInput, "Invalid refresh period"))?;
+        let address =
+            self.new_inheritance_address(name, enckey, &plan.owner_public_key, unlock_time)?;
+
+        // spending the funds with the plain leaf of the owner key cancels the current package
+        let proof = plan
+            .owner_proof()
+            .chain(|| (ErrorKind::InternalError, "Unable to generate proof"))?;
+        let sign_key = self.sign_key(name, enckey, &plan.owner_public_key)?;
+        let (signed_transaction, used_inputs, amount) = self.build_sweep_transaction(
+            name,
+            enckey,
+            &plan.package.address,
+            address.clone(),
+            &plan.view_keys,
+            sign_key.as_ref(),
+            |signature| TxInWitness::TreeSig(signature, proof.clone()),
+            network_id,
+        )?;
+        let package = self.sign_inheritance_package(
+            name,
+            enckey,
+            &plan.owner_public_key,
+            &plan.heirs,
+            &plan.view_keys,
+            address,
+            unlock_time,
+            signed_transaction.tx_id(),
+            amount,
+            network_id,
+        )?;
+
+        let plan = self
+            .inheritance_service
+            .refresh_plan(name, enckey, id, package)?;
+        // funds moved to the new inheritance address stay in the wallet
+        self.broadcast_signed_transaction(name, enckey, signed_transaction, used_inputs, amount)?;
+        Ok(plan)
+    }
+
+    fn cancel_inheritance_plan(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        id: u64,
+        network_id: u8,
+    ) -> Result<TxId> {
+        let plan = self.inheritance_service.get_plan(name, enckey, id)?;
+        if !plan.status.is_active() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Inheritance plan is already claimed or cancelled",
+            ));
+        }
+        let proof = plan
+            .owner_proof()
+            .chain(|| (ErrorKind::InternalError, "Unable to generate proof"))?;
+        let sign_key = self.sign_key(name, enckey, &plan.owner_public_key)?;
+        let to_address = self.new_transfer_address(name, enckey)?;
+        let (signed_transaction, used_inputs, amount) = self.build_sweep_transaction(
+            name,
+            enckey,
+            &plan.package.address,
+            to_address,
+            &plan.view_keys,
+            sign_key.as_ref(),
+            |signature| TxInWitness::TreeSig(signature, proof.clone()),
+            network_id,
+        )?;
+        self.broadcast_signed_transaction(name, enckey, signed_transaction, used_inputs, amount)
+    }
+
+    fn export_inheritance_package(&self, name: &str, enckey: &SecKey, id: u64) -> Result<Vec<u8>> {
+        let plan = self.inheritance_service.get_plan(name, enckey, id)?;
+        if !plan.status.is_active() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Inheritance plan is already claimed or cancelled",
+            ));
+        }
+        Ok(plan.package.transaction)
+    }
+
+    fn broadcast_inheritance_package(&self, package: &[u8]) -> Result<TxId> {
+        let signed_transaction = SignedTransaction::decode(&mut &package[..]).chain(|| {
+            (
+                ErrorKind::DeserializationError,
+                "Unable to deserialize inheritance package",
+            )
+        })?;
+        let unlock_time = match &signed_transaction {
+            SignedTransaction::TransferTransaction(_, witness) => witness
+                .iter()
+                .filter_map(TxInWitness::valid_from)
+                .max()
+                .unwrap_or_default(),
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "Inheritance package should be a transfer transaction",
+                ))
+            }
+        };
+        if self.latest_block_time()? < unlock_time {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Inheritance package is locked until {}", unlock_time),
+            ));
+        }
+        let tx_aux = self.transaction_builder.obfuscate(signed_transaction)?;
+        self.broadcast_transaction(&tx_aux)?;
+        Ok(tx_aux.tx_id())
+    }
+
+    fn create_recurring_payment(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        to_address: ExtendedAddr,
+        amount: Coin,
+        interval: u64,
+        start_at: u64,
+        end_at: Option<u64>,
+        view_keys: Vec<PublicKey>,
+        retry_policy: RetryPolicy,
+    ) -> Result<RecurringPayment> {
+        let created_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .chain(|| (ErrorKind::InternalError, "System time is before unix epoch"))?
+            .as_secs();
+        self.recurring_payment_service.add_payment(
+            name,
+            enckey,
+            to_address,
+            amount,
+            interval,
+            start_at,
+            end_at,
+            view_keys,
+            retry_policy,
+            created_at,
+        )
+    }
+
+    #[inline]
+    fn recurring_payments(&self, name: &str, enckey: &SecKey) -> Result<Vec<RecurringPayment>> {
+        self.recurring_payment_service.get_payments(name, enckey)
+    }
+
+    #[inline]
+    fn cancel_recurring_payment(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        id: u64,
+    ) -> Result<RecurringPayment> {
+        self.recurring_payment_service
+            .cancel_payment(name, enckey, id)
+    }
+
+    #[inline]
+    fn recurring_payment_events(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        from_sequence: u64,
+    ) -> Result<Vec<RecurringPaymentEvent>> {
+        self.recurring_payment_service
+            .get_events(name, enckey, from_sequence)
+    }
+
+    fn pay_due_recurring_payments(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        network_id: u8,
+    ) -> Result<Vec<RecurringPaymentEvent>> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .chain(|| (ErrorKind::InternalError, "System time is before unix epoch"))?
+            .as_secs();
+        let mut events = vec![];
+        for payment in self
+            .recurring_payment_service
+            .get_due_payments(name, enckey, now)?
+        {
+            let mut view_keys = payment.view_keys.iter().cloned().collect();
+            let result = self.send_to_address(
+                name,
+                enckey,
+                payment.amount,
+                payment.to_address.clone(),
+                &mut view_keys,
+                network_id,
+            );
+            // a failed payment doesn't prevent the other due payments
+            let new_events = match result {
+                Ok(transaction_id) => self.recurring_payment_service.record_paid(
+                    name,
+                    enckey,
+                    payment.id,
+                    transaction_id,
+                )?,
+                Err(err) => self.recurring_payment_service.record_failure(
+                    name,
+                    enckey,
+                    payment.id,
+                    now,
+                    &err.to_string(),
+                )?,
+            };
+            events.extend(new_events);
+        }
+        Ok(events)
+    }
+
+    fn set_transaction_annotation(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        transaction_id: TxId,
+        annotation: Annotation,
+    ) -> Result<()> {
+        // Check if wallet exists
+        self.wallet_service.view_key(name, enckey)?;
+        self.annotation_service
+            .set_transaction_annotation(name, enckey, transaction_id, annotation)
+    }
+
+    #[inline]
+    fn transaction_annotation(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        transaction_id: &TxId,
+    ) -> Result<Option<Annotation>> {
+        self.annotation_service
+            .get_transaction_annotation(name, enckey, transaction_id)
+    }
+
+    #[inline]
+    fn delete_transaction_annotation(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        transaction_id: &TxId,
+    ) -> Result<bool> {
+        self.annotation_service
+            .delete_transaction_annotation(name, enckey, transaction_id)
+    }
+
+    fn set_address_annotation(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        address: &ExtendedAddr,
+        annotation: Annotation,
+    ) -> Result<()> {
+        // Check if wallet exists
+        self.wallet_service.view_key(name, enckey)?;
+        self.annotation_service
+            .set_address_annotation(name, enckey, address, annotation)
+    }
+
+    #[inline]
+    fn address_annotation(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        address: &ExtendedAddr,
+    ) -> Result<Option<Annotation>> {
+        self.annotation_service
+            .get_address_annotation(name, enckey, address)
+    }
+
+    #[inline]
+    fn delete_address_annotation(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        address: &ExtendedAddr,
+    ) -> Result<bool> {
+        self.annotation_service
+            .delete_address_annotation(name, enckey, address)
+    }
+
+    #[inline]
+    fn annotations(&self, name: &str, enckey: &SecKey) -> Result<WalletAnnotations> {
+        self.annotation_service.get_annotations(name, enckey)
+    }
+
+    #[inline]
+    fn add_contact(&self, name: &str, enckey: &SecKey, contact: Contact) -> Result<()> {
+        self.address_book_service.add_contact(name, enckey, contact)
+    }
+
+    #[inline]
+    fn remove_contact(&self, name: &str, enckey: &SecKey, contact_name: &str) -> Result<bool> {
+        self.address_book_service
+            .remove_contact(name, enckey, contact_name)
+    }
+
+    #[inline]
+    fn contact(&self, name: &str, enckey: &SecKey, contact_name: &str) -> Result<Option<Contact>> {
+        self.address_book_service
+            .get_contact(name, enckey, contact_name)
+    }
+
+    #[inline]
+    fn contacts(&self, name: &str, enckey: &SecKey) -> Result<Vec<Contact>> {
+        self.address_book_service.get_contacts(name, enckey)
+    }
+
+    fn set_spending_policy(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        policy: SpendingPolicy,
+        override_passphrase: &SecUtf8,
+    ) -> Result<()> {
+        // Check if wallet exists
+        self.wallet_service.view_key(name, enckey)?;
+        self.spending_policy_service
+            .set_policy(name, enckey, policy, override_passphrase)
+    }
+
+    #[inline]
+    fn remove_spending_policy(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        override_passphrase: &SecUtf8,
+    ) -> Result<()> {
+        self.spending_policy_service
+            .remove_policy(name, enckey, override_passphrase)
+    }
+
+    #[inline]
+    fn spending_policy(&self, name: &str, enckey: &SecKey) -> Result<Option<SpendingPolicy>> {
+        self.spending_policy_service.get_policy(name, enckey)
+    }
+
+    fn approve_spending_override(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        override_passphrase: &SecUtf8,
+    ) -> Result<Timespec> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .chain(|| (ErrorKind::InternalError, "System time is before unix epoch"))?
+            .as_secs();
+        self.spending_policy_service
+            .approve_override(name, enckey, override_passphrase, now)
+    }
+
+    fn get_sync_state(&self, name: &str) -> Result<SyncState> {
+        let mstate = self.sync_state_service.get_global_state(name)?;
+        let sync_state = if let Some(sync_state) = mstate {
+            sync_state
+        } else {
+            get_genesis_sync_state(&self.tendermint_client, true)?
+        };
+        Ok(sync_state)
+    }
+}
+
+#[cfg(feature = "experimental")]
+impl<S, C, T> MultiSigWalletClient for DefaultWalletClient<S, C, T>
+where
+    S: Storage + 'static,
+    C: Client,
+    T: WalletTransactionBuilder,
+{
+    fn schnorr_signature(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        tx: &Transaction,
+        public_key: &PublicKey,
+    ) -> Result<SchnorrSignature> {
+        // To verify if the enckey is correct or not
+        self.transfer_addresses(name, enckey)?;
+        let wallet = self.wallet_service.get_wallet_info(name, enckey)?;
         let sign_key = match wallet.wallet_kind {
             WalletKind::HW => {
                 let chain_path = self
@@ -1488,6 +3381,25 @@ where
         )
     }
 
+    fn export_multi_sig_message(
+        &self,
+        session_id: &H256,
+        enckey: &SecKey,
+    ) -> Result<MultiSigMessage> {
+        self.multi_sig_session_service
+            .export_message(session_id, enckey)
+    }
+
+    fn import_multi_sig_message(
+        &self,
+        session_id: &H256,
+        enckey: &SecKey,
+        message: &MultiSigMessage,
+    ) -> Result<()> {
+        self.multi_sig_session_service
+            .import_message(session_id, message, enckey)
+    }
+
     fn signature(&self, session_id: &H256, enckey: &SecKey) -> Result<SchnorrSignature> {
         self.multi_sig_session_service.signature(session_id, enckey)
     }
@@ -1534,50 +3446,14 @@ where
 }
 
 fn check_passphrase_strength(name: &str, passphrase: &SecUtf8) -> Result<()> {
-    // `estimate_password_strength` returns a score between `0-4`. Any score less than 3 should be considered too
-    // weak.
-    let password_entropy = estimate_password_strength(passphrase.unsecure(), &[name])
-        .chain(|| (ErrorKind::IllegalInput, "Blank passphrase"))?;
-
-    #[cfg(debug_assertions)]
-    let entropy_score = 0;
-    #[cfg(not(debug_assertions))]
-    let entropy_score = 3;
-
-    if password_entropy.score() < entropy_score {
-        return Err(Error::new(
-            ErrorKind::IllegalInput,
-            format!(
-                "Weak passphrase: {}",
-                parse_feedback(password_entropy.feedback().as_ref())
-            ),
-        ));
-    }
-
-    Ok(())
+    PassphrasePolicy::global().check(name, passphrase)
 }
 
-fn parse_feedback(feedback: Option<&Feedback>) -> String {
-    match feedback {
-        None => "No feedback available!".to_string(),
-        Some(feedback) => {
-            let mut feedbacks = Vec::new();
-
-            if let Some(warning) = feedback.warning() {
-                feedbacks.push(format!("Warning: {}", warning));
-            }
-
-            for suggestion in feedback.suggestions() {
-                feedbacks.push(format!("Suggestion: {}", suggestion));
-            }
-
-            if feedbacks.is_empty() {
-                feedbacks.push("No feedback available!".to_string());
-            }
-
-            feedbacks.join(" | ")
-        }
-    }
+fn watch_only_keys_error() -> Error {
+    Error::new(
+        ErrorKind::InvalidInput,
+        "Watch-only wallet can not generate keys, add watch addresses with public keys instead",
+    )
 }
 
 fn import_transaction(
@@ -1627,11 +3503,126 @@ fn import_transaction(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::signer::WalletSignerManager;
+    use crate::transaction_builder::DefaultWalletTransactionBuilder;
     use crate::Mnemonic;
+    use chain_core::state::account::{StakedStateOpAttributes, StakedStateOpWitness, UnbondTx};
+    use chain_core::state::tendermint::BlockHeight;
+    use chain_core::state::ChainState;
+    use chain_core::tx::fee::{LinearFee, Milli};
     use client_common::storage::MemoryStorage;
-    use client_common::PublicKey;
+    use client_common::tendermint::types::*;
+    use client_common::{PublicKey, TransactionObfuscation};
+    use secp256k1::recovery::{RecoverableSignature, RecoveryId};
     use std::str::FromStr;
 
+    /// Node with the given (raw) unconfirmed transactions in its mempool
+    #[derive(Clone)]
+    struct MockMempoolClient {
+        unconfirmed: Vec<Vec<u8>>,
+    }
+
+    impl Client for MockMempoolClient {
+        fn genesis(&self) -> Result<Genesis> {
+            unreachable!()
+        }
+
+        fn status(&self) -> Result<StatusResponse> {
+            unreachable!()
+        }
+
+        fn block(&self, _height: u64) -> Result<Block> {
+            unreachable!()
+        }
+
+        fn block_batch<'a, T: Iterator<Item = &'a u64>>(&self, _heights: T) -> Result<Vec<Block>> {
+            unreachable!()
+        }
+
+        fn block_results(&self, _height: u64) -> Result<BlockResultsResponse> {
+            unreachable!()
+        }
+
+        fn block_results_batch<'a, T: Iterator<Item = &'a u64>>(
+            &self,
+            _heights: T,
+        ) -> Result<Vec<BlockResultsResponse>> {
+            unreachable!()
+        }
+
+        fn broadcast_transaction(&self, _transaction: &[u8]) -> Result<BroadcastTxResponse> {
+            unreachable!()
+        }
+
+        fn query(
+            &self,
+            _path: &str,
+            _data: &[u8],
+            _height: Option<Height>,
+            _prove: bool,
+        ) -> Result<AbciQuery> {
+            unreachable!()
+        }
+
+        fn unconfirmed_txs(&self, limit: u64) -> Result<Vec<Vec<u8>>> {
+            Ok(self
+                .unconfirmed
+                .iter()
+                .take(limit as usize)
+                .cloned()
+                .collect())
+        }
+
+        fn query_state_batch<T: Iterator<Item = u64>>(
+            &self,
+            _heights: T,
+        ) -> Result<Vec<ChainState>> {
+            unreachable!()
+        }
+    }
+
+    /// Transactions are never signed in the tests
+    #[derive(Debug, Clone)]
+    struct UnusedTransactionCipher;
+
+    impl TransactionObfuscation for UnusedTransactionCipher {
+        fn decrypt(
+            &self,
+            _transaction_ids: &[TxId],
+            _private_key: &PrivateKey,
+        ) -> Result<Vec<Transaction>> {
+            unreachable!()
+        }
+
+        fn encrypt(&self, _transaction: SignedTransaction) -> Result<TxAux> {
+            unreachable!()
+        }
+    }
+
+    fn transfer_tx_aux(txid: TxId, inputs: Vec<TxoPointer>) -> TxAux {
+        TxAux::EnclaveTx(TxEnclaveAux::TransferTx {
+            inputs,
+            no_of_outputs: 1,
+            payload: TxObfuscated {
+                txid,
+                key_from: BlockHeight::genesis(),
+                init_vector: [0; 12],
+                txpayload: Vec::new(),
+            },
+        })
+    }
+
+    fn unbond_tx_aux(address: StakedStateAddress, value: Coin) -> TxAux {
+        let witness = StakedStateOpWitness::new(
+            RecoverableSignature::from_compact(&[0u8; 64], RecoveryId::from_i32(0).unwrap())
+                .unwrap(),
+        );
+        TxAux::PublicTx(TxPublicAux::UnbondStakeTx(
+            UnbondTx::new(address, 0, value, StakedStateOpAttributes::new(0)),
+            witness,
+        ))
+    }
+
     #[test]
     fn check_delete_wallet() {
         let words = Mnemonic::from_secstr(&SecUtf8::from("pony thank pluck sweet bless tuna couple eight stove fluid essay debate cinnamon elite only")).unwrap();
@@ -1688,6 +3679,37 @@ mod tests {
         assert_eq!(transfer_addresses.len(), 2);
     }
 
+    #[test]
+    fn check_mnemonic_passphrase() {
+        let passphrase = SecUtf8::from("123456");
+        let client = DefaultWalletClient::new_read_only(MemoryStorage::default());
+        let (enckey, mnemonic) = client
+            .new_hd_wallet_with_mnemonic_passphrase(
+                "created",
+                &passphrase,
+                Some(12),
+                &SecUtf8::from("25th word"),
+            )
+            .expect("create wallet");
+        let created_address = client.new_staking_address("created", &enckey).unwrap();
+
+        // same mnemonic and passphrase give the same wallet, other passphrases independent ones
+        for (name, mnemonic_passphrase) in &[("restored", Some("25th word")), ("plain", None)] {
+            let words = Mnemonic::from_secstr(&mnemonic.phrase()).unwrap();
+            let words = match mnemonic_passphrase {
+                Some(mnemonic_passphrase) => {
+                    words.with_passphrase(SecUtf8::from(*mnemonic_passphrase))
+                }
+                None => words,
+            };
+            let enckey = client
+                .restore_wallet(name, &passphrase, &words)
+                .expect("restore wallet");
+            let address = client.new_staking_address(name, &enckey).unwrap();
+            assert_eq!(mnemonic_passphrase.is_some(), address == created_address);
+        }
+    }
+
     #[test]
     fn check_address_recover() {
         let words = Mnemonic::from_secstr(&SecUtf8::from("pony thank pluck sweet bless tuna couple eight stove fluid essay debate cinnamon elite only")).unwrap();
@@ -1755,4 +3777,279 @@ mod tests {
                 .unwrap()
         );
     }
+
+    #[test]
+    fn check_import_private_key() {
+        let words = Mnemonic::from_secstr(&SecUtf8::from("pony thank pluck sweet bless tuna couple eight stove fluid essay debate cinnamon elite only")).unwrap();
+        let name = "Default";
+        let passphrase = SecUtf8::from("123456");
+        let client = DefaultWalletClient::new_read_only(MemoryStorage::default());
+        let enckey = client.restore_wallet(name, &passphrase, &words).unwrap();
+
+        let key = ExternalKey::Hex(SecUtf8::from(
+            "0101010101010101010101010101010101010101010101010101010101010101",
+        ));
+        let public_key = client
+            .import_private_key(name, &enckey, &key, AddressType::Staking)
+            .unwrap();
+        let address = StakedStateAddress::BasicRedeem(RedeemAddress::from(&public_key));
+        assert!(client
+            .staking_addresses(name, &enckey, 0, 100, false)
+            .unwrap()
+            .contains(&address));
+        assert!(client
+            .import_private_key(name, &enckey, &key, AddressType::Transfer)
+            .is_err());
+
+        let wallet_info = client.export_wallet(name, &enckey).unwrap();
+        assert_eq!(vec![public_key], wallet_info.imported_keys);
+    }
+
+    #[test]
+    fn check_wallet_backup() {
+        let words = Mnemonic::from_secstr(&SecUtf8::from("pony thank pluck sweet bless tuna couple eight stove fluid essay debate cinnamon elite only")).unwrap();
+        let passphrase = SecUtf8::from("123456");
+        let backup_passphrase = SecUtf8::from("backup passphrase");
+        let client = DefaultWalletClient::new_read_only(MemoryStorage::default());
+        let enckey = client
+            .restore_wallet("Default", &passphrase, &words)
+            .unwrap();
+        let address = client.new_staking_address("Default", &enckey).unwrap();
+        let mut sync_state = SyncState::genesis([0; 32]);
+        sync_state.last_block_height = 10;
+        client
+            .sync_state_service
+            .save_global_state("Default", &sync_state)
+            .unwrap();
+        save_wallet_state(&client.storage, "Default", &enckey, &WalletState::default()).unwrap();
+
+        let backup = client
+            .export_wallet_backup("Default", &enckey, &backup_passphrase)
+            .unwrap();
+        assert!(client
+            .import_wallet_backup("Restored", &passphrase, &backup, &passphrase)
+            .is_err());
+        assert!(client
+            .import_wallet_backup("Restored", &passphrase, &backup[..20], &backup_passphrase)
+            .is_err());
+
+        let restored_enckey = client
+            .import_wallet_backup("Restored", &passphrase, &backup, &backup_passphrase)
+            .unwrap();
+        assert!(client
+            .staking_addresses("Restored", &restored_enckey, 0, 100, false)
+            .unwrap()
+            .contains(&address));
+        assert_eq!(
+            10,
+            client
+                .sync_state_service
+                .get_global_state("Restored")
+                .unwrap()
+                .unwrap()
+                .last_block_height
+        );
+    }
+
+    #[test]
+    fn check_address_ownership() {
+        let words = Mnemonic::from_secstr(&SecUtf8::from("pony thank pluck sweet bless tuna couple eight stove fluid essay debate cinnamon elite only")).unwrap();
+        let name = "Default";
+        let passphrase = SecUtf8::from("123456");
+        let client = DefaultWalletClient::new_read_only(MemoryStorage::default());
+        let enckey = client.restore_wallet(name, &passphrase, &words).unwrap();
+
+        let staking_address = client.new_staking_address(name, &enckey).unwrap();
+        match client
+            .staking_address_ownership(name, &enckey, &staking_address)
+            .unwrap()
+        {
+            Some(OwnedAddress::Staking {
+                derivation_path,
+                watch_only,
+                ..
+            }) => {
+                assert!(derivation_path.is_some());
+                assert!(!watch_only);
+            }
+            other => panic!("unexpected ownership: {:?}", other),
+        }
+
+        let transfer_address = client.new_transfer_address(name, &enckey).unwrap();
+        match client
+            .transfer_address_ownership(name, &enckey, &transfer_address)
+            .unwrap()
+        {
+            Some(OwnedAddress::Transfer {
+                root_hash,
+                required_signers,
+                total_signers,
+                watch_only,
+                ..
+            }) => {
+                assert_eq!(ExtendedAddr::OrTree(root_hash), transfer_address);
+                assert_eq!((1, 1), (required_signers, total_signers));
+                assert!(!watch_only);
+            }
+            other => panic!("unexpected ownership: {:?}", other),
+        }
+
+        let private_key = PrivateKey::deserialize_from(&[0x01; 32]).unwrap();
+        let watched_address = client
+            .new_watch_staking_address(name, &enckey, &PublicKey::from(&private_key))
+            .unwrap();
+        match client
+            .staking_address_ownership(name, &enckey, &watched_address)
+            .unwrap()
+        {
+            Some(OwnedAddress::Staking {
+                derivation_path,
+                watch_only,
+                ..
+            }) => {
+                assert_eq!(None, derivation_path);
+                assert!(watch_only);
+            }
+            other => panic!("unexpected ownership: {:?}", other),
+        }
+
+        let other_key = PublicKey::from(&PrivateKey::deserialize_from(&[0x02; 32]).unwrap());
+        let other_address = StakedStateAddress::BasicRedeem(RedeemAddress::from(&other_key));
+        assert_eq!(
+            None,
+            client
+                .staking_address_ownership(name, &enckey, &other_address)
+                .unwrap()
+        );
+        assert_eq!(
+            None,
+            client
+                .transfer_address_ownership(name, &enckey, &ExtendedAddr::OrTree([0; 32]))
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn check_mempool_transactions() {
+        let words = Mnemonic::from_secstr(&SecUtf8::from("pony thank pluck sweet bless tuna couple eight stove fluid essay debate cinnamon elite only")).unwrap();
+        let name = "Default";
+        let passphrase = SecUtf8::from("123456");
+        let read_only = DefaultWalletClient::new_read_only(MemoryStorage::default());
+        let enckey = read_only.restore_wallet(name, &passphrase, &words).unwrap();
+        let staking_address = read_only.new_staking_address(name, &enckey).unwrap();
+        let transfer_address = read_only.new_transfer_address(name, &enckey).unwrap();
+
+        let owned = TxoPointer::new([1; 32], 0);
+        let output = TxOut::new(transfer_address, Coin::new(100).unwrap());
+        let mut wallet_state = WalletState::default();
+        wallet_state
+            .unspent_transactions
+            .insert(owned.clone(), output.clone());
+        save_wallet_state(&read_only.storage, name, &enckey, &wallet_state).unwrap();
+
+        let other = TxoPointer::new([2; 32], 0);
+        let other_address = StakedStateAddress::BasicRedeem(RedeemAddress::from([3u8; 20]));
+        let unconfirmed = vec![
+            // spends an output of the wallet (and one of another wallet)
+            transfer_tx_aux([4; 32], vec![owned.clone(), other.clone()]).encode(),
+            // spends outputs of other wallets only
+            transfer_tx_aux([5; 32], vec![other]).encode(),
+            unbond_tx_aux(staking_address, Coin::new(10).unwrap()).encode(),
+            unbond_tx_aux(other_address, Coin::new(20).unwrap()).encode(),
+            // not a transaction
+            vec![0xff; 3],
+        ];
+        let client = DefaultWalletClient::new(
+            read_only.storage.clone(),
+            MockMempoolClient { unconfirmed },
+            UnauthorizedWalletTransactionBuilder,
+            None,
+            HwKeyService::default(),
+        );
+
+        let transactions = client.mempool_transactions(name, &enckey, 100).unwrap();
+        assert_eq!(
+            vec![
+                MempoolTransaction {
+                    transaction_id: [4; 32],
+                    transaction_type: TransactionType::Transfer,
+                    inputs: vec![TransactionInput {
+                        pointer: owned,
+                        output: Some(output),
+                    }],
+                    staking_address: None,
+                    amount: None,
+                },
+                MempoolTransaction {
+                    transaction_id: unbond_tx_aux(staking_address, Coin::new(10).unwrap()).tx_id(),
+                    transaction_type: TransactionType::Unbond,
+                    inputs: Vec::new(),
+                    staking_address: Some(staking_address),
+                    amount: Some(Coin::new(10).unwrap()),
+                },
+            ],
+            transactions
+        );
+
+        // only the first transaction of the mempool is looked at
+        assert_eq!(
+            1,
+            client.mempool_transactions(name, &enckey, 1).unwrap().len()
+        );
+    }
+
+    #[test]
+    fn check_estimate_fee_without_mempool_spent_outputs() {
+        let words = Mnemonic::from_secstr(&SecUtf8::from("pony thank pluck sweet bless tuna couple eight stove fluid essay debate cinnamon elite only")).unwrap();
+        let name = "Default";
+        let passphrase = SecUtf8::from("123456");
+        let storage = MemoryStorage::default();
+        let read_only = DefaultWalletClient::new_read_only(storage.clone());
+        let enckey = read_only.restore_wallet(name, &passphrase, &words).unwrap();
+        let transfer_address = read_only.new_transfer_address(name, &enckey).unwrap();
+
+        let spent = TxoPointer::new([1; 32], 0);
+        let available = TxoPointer::new([2; 32], 0);
+        let mut wallet_state = WalletState::default();
+        for pointer in &[&spent, &available] {
+            wallet_state.unspent_transactions.insert(
+                (*pointer).clone(),
+                TxOut::new(transfer_address.clone(), Coin::new(1000).unwrap()),
+            );
+        }
+        save_wallet_state(&storage, name, &enckey, &wallet_state).unwrap();
+
+        // another client of the wallet broadcasted a transaction spending the first output
+        let unconfirmed = vec![transfer_tx_aux([4; 32], vec![spent.clone()]).encode()];
+        let transaction_builder = DefaultWalletTransactionBuilder::new(
+            WalletSignerManager::new(storage.clone(), HwKeyService::default()),
+            LinearFee::new(Milli::try_new(1, 1).unwrap(), Milli::try_new(1, 1).unwrap()),
+            UnusedTransactionCipher,
+        );
+        let client = DefaultWalletClient::new(
+            storage,
+            MockMempoolClient { unconfirmed },
+            transaction_builder,
+            None,
+            HwKeyService::default(),
+        );
+
+        let outputs = vec![TxOut::new(
+            transfer_address.clone(),
+            Coin::new(500).unwrap(),
+        )];
+        let estimate = client
+            .estimate_fee(name, &enckey, outputs, &mut BTreeSet::new(), 0, 100)
+            .unwrap();
+        assert_eq!(vec![available], estimate.inputs);
+        assert_eq!(vec![spent], estimate.excluded_inputs);
+        assert!(estimate.size > 0);
+        assert!(estimate.fee > Coin::zero());
+
+        // the output spent in the mempool can't cover the missing amount
+        let outputs = vec![TxOut::new(transfer_address, Coin::new(1500).unwrap())];
+        assert!(client
+            .estimate_fee(name, &enckey, outputs, &mut BTreeSet::new(), 0, 100)
+            .is_err());
+    }
 }
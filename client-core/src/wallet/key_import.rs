@@ -0,0 +1,247 @@
+use std::num::NonZeroU32;
+
+use aes::Aes128;
+use base58::FromBase58;
+use ctr::cipher::{NewStreamCipher, SyncStreamCipher};
+use ring::{digest, pbkdf2};
+use secstr::SecUtf8;
+use serde::Deserialize;
+use zeroize::Zeroizing;
+
+use chain_core::init::address::keccak256;
+use client_common::{Error, ErrorKind, PrivateKey, Result, ResultExt};
+
+type Aes128Ctr = ctr::Ctr128<Aes128>;
+
+/// WIF version bytes (mainnet and testnet)
+const WIF_VERSIONS: [u8; 2] = [0x80, 0xef];
+/// WIF suffix of keys used with compressed public keys
+const WIF_COMPRESSED_FLAG: u8 = 0x01;
+
+/// Standalone private key generated by external tooling
+#[derive(Debug, Clone)]
+pub enum ExternalKey {
+    /// hex-encoded 32-byte secret key (optionally `0x`-prefixed)
+    Hex(SecUtf8),
+    /// Base58Check encoded secret key in the Wallet Import Format
+    Wif(SecUtf8),
+    /// encrypted keystore JSON (version 3, `pbkdf2` key derivation and `aes-128-ctr` cipher)
+    Keystore {
+        /// content of the keystore file
+        json: SecUtf8,
+        /// password of the keystore
+        password: SecUtf8,
+    },
+}
+
+impl ExternalKey {
+    /// Decodes (and decrypts) the private key
+    pub fn decode(&self) -> Result<PrivateKey> {
+        match self {
+            ExternalKey::Hex(encoded) => decode_hex(encoded),
+            ExternalKey::Wif(encoded) => decode_wif(encoded),
+            ExternalKey::Keystore { json, password } => decode_keystore(json.unsecure(), password),
+        }
+    }
+}
+
+fn invalid_key(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidInput, msg)
+}
+
+fn decode_hex(encoded: &SecUtf8) -> Result<PrivateKey> {
+    let encoded = encoded.unsecure().trim();
+    let encoded = encoded.strip_prefix("0x").unwrap_or(encoded);
+    let secret = Zeroizing::new(
+        hex::decode(encoded).chain(|| (ErrorKind::InvalidInput, "Invalid hex encoded key"))?,
+    );
+    if secret.len() != 32 {
+        return Err(invalid_key("Hex encoded key should be 32 bytes long"));
+    }
+    PrivateKey::deserialize_from(&secret)
+}
+
+fn decode_wif(encoded: &SecUtf8) -> Result<PrivateKey> {
+    let data = Zeroizing::new(
+        encoded
+            .unsecure()
+            .trim()
+            .from_base58()
+            .map_err(|_| invalid_key("Invalid base58 encoded key"))?,
+    );
+    if data.len() < 4 {
+        return Err(invalid_key("Invalid WIF encoded key"));
+    }
+    let (payload, checksum) = data.split_at(data.len() - 4);
+    let hash = digest::digest(
+        &digest::SHA256,
+        digest::digest(&digest::SHA256, payload).as_ref(),
+    );
+    if &hash.as_ref()[..4] != checksum {
+        return Err(invalid_key("Invalid checksum of WIF encoded key"));
+    }
+    let secret = match payload {
+        [version, secret @ ..] if secret.len() == 32 && WIF_VERSIONS.contains(version) => secret,
+        [version, secret @ .., WIF_COMPRESSED_FLAG]
+            if secret.len() == 32 && WIF_VERSIONS.contains(version) =>
+        {
+            secret
+        }
+        _ => return Err(invalid_key("Unsupported WIF encoded key")),
+    };
+    PrivateKey::deserialize_from(secret)
+}
+
+#[derive(Deserialize)]
+struct Keystore {
+    version: u32,
+    #[serde(alias = "Crypto")]
+    crypto: KeystoreCrypto,
+}
+
+#[derive(Deserialize)]
+struct KeystoreCrypto {
+    cipher: String,
+    cipherparams: CipherParams,
+    ciphertext: String,
+    kdf: String,
+    kdfparams: Pbkdf2Params,
+    mac: String,
+}
+
+#[derive(Deserialize)]
+struct CipherParams {
+    iv: String,
+}
+
+#[derive(Deserialize)]
+struct Pbkdf2Params {
+    c: u32,
+    dklen: usize,
+    prf: String,
+    salt: String,
+}
+
+fn decode_keystore(json: &str, password: &SecUtf8) -> Result<PrivateKey> {
+    let keystore: Keystore = serde_json::from_str(json)
+        .chain(|| (ErrorKind::DeserializationError, "Invalid keystore JSON"))?;
+    let crypto = keystore.crypto;
+    if keystore.version != 3 {
+        return Err(invalid_key("Unsupported keystore version"));
+    }
+    if crypto.kdf != "pbkdf2" || crypto.kdfparams.prf != "hmac-sha256" {
+        return Err(invalid_key(
+            "Unsupported keystore key derivation (only pbkdf2 with hmac-sha256 is supported)",
+        ));
+    }
+    if crypto.cipher != "aes-128-ctr" {
+        return Err(invalid_key("Unsupported keystore cipher"));
+    }
+    if crypto.kdfparams.dklen < 32 {
+        return Err(invalid_key("Invalid keystore derived key length"));
+    }
+    let iterations = NonZeroU32::new(crypto.kdfparams.c)
+        .chain(|| (ErrorKind::InvalidInput, "Invalid keystore iteration count"))?;
+    let salt = hex::decode(&crypto.kdfparams.salt)
+        .chain(|| (ErrorKind::InvalidInput, "Invalid keystore salt"))?;
+    let iv = hex::decode(&crypto.cipherparams.iv)
+        .chain(|| (ErrorKind::InvalidInput, "Invalid keystore iv"))?;
+    let ciphertext = hex::decode(&crypto.ciphertext)
+        .chain(|| (ErrorKind::InvalidInput, "Invalid keystore ciphertext"))?;
+    let mac =
+        hex::decode(&crypto.mac).chain(|| (ErrorKind::InvalidInput, "Invalid keystore mac"))?;
+
+    let mut derived_key = Zeroizing::new(vec![0u8; crypto.kdfparams.dklen]);
+    pbkdf2::derive(
+        pbkdf2::PBKDF2_HMAC_SHA256,
+        iterations,
+        &salt,
+        password.unsecure().as_bytes(),
+        &mut derived_key,
+    );
+
+    let mut mac_data = derived_key[16..32].to_vec();
+    mac_data.extend_from_slice(&ciphertext);
+    if keccak256(&mac_data)[..] != mac[..] {
+        return Err(Error::new(
+            ErrorKind::DecryptionError,
+            "Incorrect keystore password",
+        ));
+    }
+
+    let mut secret = Zeroizing::new(ciphertext);
+    Aes128Ctr::new_var(&derived_key[..16], &iv)
+        .map_err(|_| invalid_key("Invalid keystore iv length"))?
+        .apply_keystream(&mut secret);
+    PrivateKey::deserialize_from(&secret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // secret key 0x0101..01
+    const HEX_KEY: &str = "0x0101010101010101010101010101010101010101010101010101010101010101";
+    const WIF_KEY: &str = "KwFfNUhSDaASSAwtG7ssQM1uVX8RgX5GHWnnLfhfiQDigjioWXHH";
+
+    fn keystore(secret: &[u8], password: &str) -> String {
+        let salt = [2u8; 32];
+        let iv = [3u8; 16];
+        let mut derived_key = [0u8; 32];
+        pbkdf2::derive(
+            pbkdf2::PBKDF2_HMAC_SHA256,
+            NonZeroU32::new(2).unwrap(),
+            &salt,
+            password.as_bytes(),
+            &mut derived_key,
+        );
+        let mut ciphertext = secret.to_vec();
+        Aes128Ctr::new_var(&derived_key[..16], &iv)
+            .unwrap()
+            .apply_keystream(&mut ciphertext);
+        let mut mac_data = derived_key[16..32].to_vec();
+        mac_data.extend_from_slice(&ciphertext);
+        serde_json::json!({
+            "version": 3,
+            "crypto": {
+                "cipher": "aes-128-ctr",
+                "cipherparams": { "iv": hex::encode(iv) },
+                "ciphertext": hex::encode(ciphertext),
+                "kdf": "pbkdf2",
+                "kdfparams": { "c": 2, "dklen": 32, "prf": "hmac-sha256", "salt": hex::encode(salt) },
+                "mac": hex::encode(keccak256(&mac_data)),
+            }
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn check_decode_external_keys() {
+        let expected = PrivateKey::deserialize_from(&[1u8; 32]).unwrap();
+
+        let key = ExternalKey::Hex(HEX_KEY.into()).decode().unwrap();
+        assert_eq!(expected.serialize(), key.serialize());
+        let key = ExternalKey::Wif(WIF_KEY.into()).decode().unwrap();
+        assert_eq!(expected.serialize(), key.serialize());
+        let key = ExternalKey::Keystore {
+            json: keystore(&[1u8; 32], "password").into(),
+            password: "password".into(),
+        }
+        .decode()
+        .unwrap();
+        assert_eq!(expected.serialize(), key.serialize());
+
+        let error = ExternalKey::Keystore {
+            json: keystore(&[1u8; 32], "password").into(),
+            password: "wrong password".into(),
+        }
+        .decode()
+        .unwrap_err();
+        assert_eq!(ErrorKind::DecryptionError, error.kind());
+        assert!(
+            ExternalKey::Wif("KwFfNUhSDaASSAwtG7ssQM1uVX8RgX5GHWnnLfhfiQDigjioWXHJ".into())
+                .decode()
+                .is_err()
+        );
+    }
+}
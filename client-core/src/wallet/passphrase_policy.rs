@@ -0,0 +1,194 @@
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+
+use once_cell::sync::Lazy;
+use secstr::SecUtf8;
+use zxcvbn::{feedback::Feedback, zxcvbn as estimate_password_strength};
+
+use client_common::{Error, ErrorKind, Result, ResultExt};
+
+/// Minimal number of characters of a wallet passphrase
+pub const PASSPHRASE_MIN_LENGTH_ENV: &str = "CRYPTO_CLIENT_PASSPHRASE_MIN_LENGTH";
+/// Minimal zxcvbn score (0-4) of a wallet passphrase
+pub const PASSPHRASE_MIN_SCORE_ENV: &str = "CRYPTO_CLIENT_PASSPHRASE_MIN_SCORE";
+/// Path to a local list of breached passphrases (one per line)
+pub const PASSPHRASE_BREACH_LIST_ENV: &str = "CRYPTO_CLIENT_PASSPHRASE_BREACH_LIST";
+
+#[cfg(debug_assertions)]
+const DEFAULT_MIN_SCORE: u8 = 0;
+// `estimate_password_strength` returns a score between `0-4`. Any score less than 3 should be considered too
+// weak.
+#[cfg(not(debug_assertions))]
+const DEFAULT_MIN_SCORE: u8 = 3;
+
+static GLOBAL_POLICY: Lazy<PassphrasePolicy> = Lazy::new(|| {
+    PassphrasePolicy::from_env().unwrap_or_else(|e| {
+        log::error!("invalid passphrase policy configuration: {}", e);
+        PassphrasePolicy::default()
+    })
+});
+
+/// Requirements on passphrases of newly created / restored / imported wallets
+#[derive(Debug, Clone)]
+pub struct PassphrasePolicy {
+    /// minimal number of characters
+    pub min_length: usize,
+    /// minimal zxcvbn score (0-4)
+    pub min_score: u8,
+    /// breached passphrases (lowercase)
+    breached: HashSet<String>,
+}
+
+impl Default for PassphrasePolicy {
+    fn default() -> Self {
+        PassphrasePolicy {
+            min_length: 0,
+            min_score: DEFAULT_MIN_SCORE,
+            breached: HashSet::new(),
+        }
+    }
+}
+
+impl PassphrasePolicy {
+    /// Policy configured by the `CRYPTO_CLIENT_PASSPHRASE_*` environment variables
+    /// (read once per process)
+    pub fn global() -> &'static PassphrasePolicy {
+        &GLOBAL_POLICY
+    }
+
+    /// Reads the `CRYPTO_CLIENT_PASSPHRASE_*` environment variables
+    pub fn from_env() -> Result<Self> {
+        let mut policy = PassphrasePolicy::default();
+        if let Ok(value) = env::var(PASSPHRASE_MIN_LENGTH_ENV) {
+            policy.min_length = value.parse().chain(|| {
+                (
+                    ErrorKind::InvalidInput,
+                    format!("Invalid {}: {}", PASSPHRASE_MIN_LENGTH_ENV, value),
+                )
+            })?;
+        }
+        if let Ok(value) = env::var(PASSPHRASE_MIN_SCORE_ENV) {
+            policy.min_score = value.parse().chain(|| {
+                (
+                    ErrorKind::InvalidInput,
+                    format!("Invalid {}: {}", PASSPHRASE_MIN_SCORE_ENV, value),
+                )
+            })?;
+        }
+        if let Ok(path) = env::var(PASSPHRASE_BREACH_LIST_ENV) {
+            let content = fs::read_to_string(&path).chain(|| {
+                (
+                    ErrorKind::IoError,
+                    format!("Unable to read breached passphrase list: {}", path),
+                )
+            })?;
+            policy.add_breached(content.lines());
+        }
+        Ok(policy)
+    }
+
+    /// Adds passphrases to the breached list
+    pub fn add_breached<'a>(&mut self, passphrases: impl IntoIterator<Item = &'a str>) {
+        self.breached.extend(
+            passphrases
+                .into_iter()
+                .map(str::trim)
+                .filter(|passphrase| !passphrase.is_empty())
+                .map(str::to_lowercase),
+        );
+    }
+
+    /// Checks the passphrase of the wallet against the policy
+    pub fn check(&self, name: &str, passphrase: &SecUtf8) -> Result<()> {
+        let passphrase = passphrase.unsecure();
+        if passphrase.chars().count() < self.min_length {
+            return Err(Error::new(
+                ErrorKind::WeakPassphrase,
+                format!(
+                    "Passphrase too short: at least {} characters required",
+                    self.min_length
+                ),
+            ));
+        }
+        if self.breached.contains(&passphrase.to_lowercase()) {
+            return Err(Error::new(
+                ErrorKind::WeakPassphrase,
+                "Passphrase found in the list of breached passphrases",
+            ));
+        }
+
+        let password_entropy = estimate_password_strength(passphrase, &[name])
+            .chain(|| (ErrorKind::WeakPassphrase, "Blank passphrase"))?;
+        if password_entropy.score() < self.min_score {
+            return Err(Error::new(
+                ErrorKind::WeakPassphrase,
+                format!(
+                    "Weak passphrase (score {} of required {}): {}",
+                    password_entropy.score(),
+                    self.min_score,
+                    parse_feedback(password_entropy.feedback().as_ref())
+                ),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+fn parse_feedback(feedback: Option<&Feedback>) -> String {
+    match feedback {
+        None => "No feedback available!".to_string(),
+        Some(feedback) => {
+            let mut feedbacks = Vec::new();
+
+            if let Some(warning) = feedback.warning() {
+                feedbacks.push(format!("Warning: {}", warning));
+            }
+
+            for suggestion in feedback.suggestions() {
+                feedbacks.push(format!("Suggestion: {}", suggestion));
+            }
+
+            if feedbacks.is_empty() {
+                feedbacks.push("No feedback available!".to_string());
+            }
+
+            feedbacks.join(" | ")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_passphrase_policy() {
+        let mut policy = PassphrasePolicy {
+            min_length: 10,
+            min_score: 0,
+            ..Default::default()
+        };
+        policy.add_breached(vec!["Correct Horse Battery Staple", ""]);
+
+        let error = policy.check("name", &SecUtf8::from("short")).unwrap_err();
+        assert_eq!(ErrorKind::WeakPassphrase, error.kind());
+        let error = policy
+            .check("name", &SecUtf8::from("correct horse battery staple"))
+            .unwrap_err();
+        assert_eq!(ErrorKind::WeakPassphrase, error.kind());
+        assert!(policy
+            .check("name", &SecUtf8::from("some long passphrase"))
+            .is_ok());
+
+        policy.min_score = 4;
+        assert_eq!(
+            ErrorKind::WeakPassphrase,
+            policy
+                .check("name", &SecUtf8::from("passphrase"))
+                .unwrap_err()
+                .kind()
+        );
+    }
+}
@@ -0,0 +1,344 @@
+//! Recovery of a wallet purely from chain data
+use parity_scale_codec::{Decode, Encode};
+use secstr::SecUtf8;
+
+use chain_core::init::address::RedeemAddress;
+use chain_core::state::account::{StakedState, StakedStateAddress};
+use client_common::tendermint::types::AbciQueryExt;
+use client_common::tendermint::Client;
+use client_common::{
+    Error, ErrorKind, Result, ResultExt, SecKey, SecureStorage, Storage, TransactionObfuscation,
+};
+
+use super::syncer::{
+    AddressRecovery, LightClientHandle, ObfuscationSyncerConfig, ProgressReport, WalletSyncer,
+};
+use super::WalletClient;
+use crate::service::{
+    delete_sync_state, delete_wallet_state, load_sync_state, HDAccountType, HdKeyService,
+};
+use crate::Mnemonic;
+
+/// key space of in-progress wallet recoveries
+const KEYSPACE: &str = "core_wallet_recovery";
+
+/// Marker of an in-progress recovery, removed once the chain scan reaches its target
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+pub struct RecoveryState {
+    /// latest block height when the recovery was started: the recovery isn't finished
+    /// before the chain scan reaches it (e.g. when resumed with a lagging node)
+    pub target_block_height: u64,
+}
+
+/// Load the in-progress recovery marker of the wallet (if any)
+pub fn load_recovery_state<S: Storage>(storage: &S, name: &str) -> Result<Option<RecoveryState>> {
+    storage.load(KEYSPACE, name)
+}
+
+/// Rebuilds the wallet state from the mnemonic by scanning the chain:
+///
+/// 1. restores the HD keys (and the view key) from the mnemonic, generating the staking
+///    addresses up to the last one with a staked state (gap-limit discovery)
+/// 2. syncs from genesis through the block filters, decrypting matching transactions
+///    and generating further HD addresses whenever an owned one is seen in an output
+///    (address recovery is always enabled)
+///
+/// The sync state is persisted after every batch, so an interrupted recovery
+/// (e.g. the progress callback returned `false`) is resumed where it stopped
+/// when this is called again with the same wallet name and passphrase.
+pub fn recover_wallet_from_chain<S, C, O, L, W, F>(
+    config: ObfuscationSyncerConfig<S, C, O, L>,
+    wallet_client: W,
+    name: &str,
+    passphrase: &SecUtf8,
+    mnemonic: &Mnemonic,
+    progress_callback: F,
+) -> Result<SecKey>
+where
+    S: SecureStorage + 'static,
+    C: Client,
+    O: TransactionObfuscation,
+    L: LightClientHandle,
+    W: WalletClient + AddressRecovery,
+    F: FnMut(ProgressReport) -> bool,
+{
+    let enckey = start_wallet_recovery(
+        &config.storage,
+        &config.client,
+        &wallet_client,
+        name,
+        passphrase,
+        mnemonic,
+    )?;
+    resume_wallet_recovery(config, wallet_client, name, &enckey, progress_callback)?;
+    Ok(enckey)
+}
+
+/// Performs the first step of `recover_wallet_from_chain` (restoring the keys and discovering
+/// the staking addresses), returns the enckey of the wallet. If the recovery of the wallet is
+/// already in progress, only the passphrase is checked.
+pub fn start_wallet_recovery<S, C, W>(
+    storage: &S,
+    client: &C,
+    wallet_client: &W,
+    name: &str,
+    passphrase: &SecUtf8,
+    mnemonic: &Mnemonic,
+) -> Result<SecKey>
+where
+    S: Storage,
+    C: Client,
+    W: WalletClient,
+{
+    if load_recovery_state(storage, name)?.is_some() {
+        log::info!("resuming recovery of wallet {}", name);
+        return wallet_client.auth_token(name, passphrase);
+    }
+
+    let enckey = wallet_client.restore_wallet(name, passphrase, mnemonic)?;
+    let staking_addresses = discover_staking_addresses(storage, client, name, &enckey)?;
+    for _ in 0..staking_addresses {
+        wallet_client.new_staking_address(name, &enckey)?;
+    }
+    // the chain is scanned from genesis
+    delete_sync_state(storage, name)?;
+    delete_wallet_state(storage, name)?;
+    let target_block_height = client.status()?.sync_info.latest_block_height.value();
+    storage.save(
+        KEYSPACE,
+        name,
+        &RecoveryState {
+            target_block_height,
+        },
+    )?;
+    Ok(enckey)
+}
+
+/// Performs the chain scan of a recovery started by `start_wallet_recovery` (the second step of
+/// `recover_wallet_from_chain`), from where the previous scan of the wallet stopped; the recovery
+/// is finished once the scan reaches the target block height of the recovery state
+pub fn resume_wallet_recovery<S, C, O, L, W, F>(
+    config: ObfuscationSyncerConfig<S, C, O, L>,
+    wallet_client: W,
+    name: &str,
+    enckey: &SecKey,
+    progress_callback: F,
+) -> Result<()>
+where
+    S: SecureStorage + 'static,
+    C: Client,
+    O: TransactionObfuscation,
+    L: LightClientHandle,
+    W: WalletClient + AddressRecovery,
+    F: FnMut(ProgressReport) -> bool,
+{
+    let mut config = config;
+    config.options.enable_address_recovery = true;
+    let storage = config.storage.clone();
+    let state = load_recovery_state(&storage, name)?.err_kind(ErrorKind::InvalidInput, || {
+        format!("No recovery of wallet {} in progress", name)
+    })?;
+
+    let mut syncer = WalletSyncer::with_obfuscation_config(
+        config,
+        name.to_owned(),
+        enckey.clone(),
+        wallet_client,
+    )?;
+    // cancellation or failure leaves the marker in place, so the next call resumes
+    syncer.sync(progress_callback)?;
+    let synced_block_height = load_sync_state(&storage, name)?
+        .map(|sync_state| sync_state.last_block_height)
+        .unwrap_or_default();
+    if synced_block_height < state.target_block_height {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "Recovery of wallet {} stopped at block {} before reaching block {} (retry with a synced node)",
+                name, synced_block_height, state.target_block_height
+            ),
+        ));
+    }
+    storage.delete(KEYSPACE, name)?;
+    Ok(())
+}
+
+/// Returns the number of staking addresses to generate to reach the last one with a staked state
+/// (staking addresses aren't recovered from the transaction outputs during the sync)
+fn discover_staking_addresses<S: Storage, C: Client>(
+    storage: &S,
+    client: &C,
+    name: &str,
+    enckey: &SecKey,
+) -> Result<u32> {
+    HdKeyService::new(storage.clone()).discover_addresses(
+        name,
+        enckey,
+        HDAccountType::Staking,
+        |public_key| {
+            let address = StakedStateAddress::BasicRedeem(RedeemAddress::from(public_key));
+            let response = client.query("staking", address.as_ref(), None, false)?;
+            let staked_state = <Option<StakedState>>::decode(&mut response.bytes().as_slice())
+                .chain(|| {
+                    (
+                        ErrorKind::DeserializationError,
+                        format!("Cannot deserialize staked state for address: {}", address),
+                    )
+                })?;
+            Ok(staked_state.is_some())
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chain_core::tx::data::TxId;
+    use chain_core::tx::TxAux;
+    use client_common::storage::MemoryStorage;
+    use client_common::{PrivateKey, SignedTransaction, Transaction};
+    use test_common::block_generator::{BlockGenerator, GeneratorClient};
+
+    use crate::wallet::syncer::{compute_genesis_fingerprint, SyncerOptions};
+    use crate::wallet::DefaultWalletClient;
+
+    #[derive(Debug, Clone)]
+    struct MockTransactionCipher;
+
+    impl TransactionObfuscation for MockTransactionCipher {
+        fn decrypt(
+            &self,
+            transaction_ids: &[TxId],
+            _private_key: &PrivateKey,
+        ) -> Result<Vec<Transaction>> {
+            assert!(transaction_ids.is_empty());
+            Ok(vec![])
+        }
+
+        fn encrypt(&self, _transaction: SignedTransaction) -> Result<TxAux> {
+            unreachable!()
+        }
+    }
+
+    fn syncer_config(
+        storage: &MemoryStorage,
+        client: &GeneratorClient,
+    ) -> ObfuscationSyncerConfig<
+        MemoryStorage,
+        GeneratorClient,
+        MockTransactionCipher,
+        GeneratorClient,
+    > {
+        ObfuscationSyncerConfig {
+            storage: storage.clone(),
+            client: client.clone(),
+            obfuscation: MockTransactionCipher,
+            light_client: None,
+            options: SyncerOptions {
+                enable_fast_forward: false,
+                disable_light_client: true,
+                enable_address_recovery: false,
+                enable_block_filters: false,
+                batch_size: 2,
+                block_height_ensure: 50,
+                light_client_peers: "".into(),
+                light_client_trusting_period_seconds: 36000000,
+                light_client_trusting_height: 1,
+                light_client_trusting_blockhash: "".into(),
+            },
+        }
+    }
+
+    #[test]
+    fn check_resume_wallet_recovery() {
+        let storage = MemoryStorage::default();
+        let name = "name";
+        let passphrase = SecUtf8::from("passphrase");
+        let mnemonic = Mnemonic::new(24).unwrap();
+        let wallet = DefaultWalletClient::new_read_only(storage.clone());
+        let enckey = wallet.restore_wallet(name, &passphrase, &mnemonic).unwrap();
+
+        let client = GeneratorClient::new(BlockGenerator::one_node());
+        {
+            let mut gen = client.gen.write().unwrap();
+            for _ in 0..6 {
+                gen.gen_block(&[]);
+            }
+        }
+        let genesis = client.genesis().unwrap();
+        std::env::set_var(
+            "CRYPTO_GENESIS_FINGERPRINT",
+            compute_genesis_fingerprint(&genesis).unwrap(),
+        );
+
+        // no recovery in progress
+        assert!(resume_wallet_recovery(
+            syncer_config(&storage, &client),
+            wallet.clone(),
+            name,
+            &enckey,
+            |_| true
+        )
+        .is_err());
+
+        // the saved state of a started recovery
+        let state = RecoveryState {
+            target_block_height: 6,
+        };
+        storage.save(KEYSPACE, name, &state).unwrap();
+        // only the passphrase is checked while the recovery is in progress
+        assert!(start_wallet_recovery(
+            &storage,
+            &client,
+            &wallet,
+            name,
+            &SecUtf8::from("wrong"),
+            &mnemonic
+        )
+        .is_err());
+        start_wallet_recovery(&storage, &client, &wallet, name, &passphrase, &mnemonic).unwrap();
+
+        // cancelled after the first batch: the recovery stays in progress
+        assert!(resume_wallet_recovery(
+            syncer_config(&storage, &client),
+            wallet.clone(),
+            name,
+            &enckey,
+            |report| match report {
+                ProgressReport::Init { .. } => true,
+                ProgressReport::Update {
+                    current_block_height,
+                    ..
+                } => current_block_height < 2,
+            },
+        )
+        .is_err());
+        assert_eq!(Some(state), load_recovery_state(&storage, name).unwrap());
+        let sync_state = load_sync_state(&storage, name).unwrap().unwrap();
+        assert_eq!(2, sync_state.last_block_height);
+
+        // resumed from the saved sync state until the target block height
+        let mut start_height = None;
+        resume_wallet_recovery(
+            syncer_config(&storage, &client),
+            wallet,
+            name,
+            &enckey,
+            |report| {
+                if let ProgressReport::Init {
+                    start_block_height, ..
+                } = report
+                {
+                    start_height = Some(start_block_height);
+                }
+                true
+            },
+        )
+        .unwrap();
+        assert_eq!(Some(2), start_height);
+        assert_eq!(None, load_recovery_state(&storage, name).unwrap());
+        let sync_state = load_sync_state(&storage, name).unwrap().unwrap();
+        assert_eq!(6, sync_state.last_block_height);
+    }
+}
@@ -0,0 +1,413 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+
+use serde::{Deserialize, Serialize};
+
+use client_common::tendermint::Client;
+use client_common::{Error, ErrorKind, Result, SecureStorage, TransactionObfuscation};
+
+use super::syncer::{
+    AddressRecovery, LightClientHandle, ObfuscationSyncerConfig, ProgressReport,
+    TxObfuscationDecryptor, WalletSyncer,
+};
+use super::WalletRequest;
+
+/// Callback receiving the progress of the wallets (called from the synchronizing threads)
+pub type SyncCallback = Arc<dyn Fn(&SyncProgress) + Send + Sync>;
+
+/// Status of the synchronization of a wallet
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status")]
+pub enum SyncStatus {
+    /// Wallet is being synchronized
+    Running,
+    /// Wallet is synchronized up to the latest block
+    Completed,
+    /// Synchronization was cancelled
+    Cancelled,
+    /// Synchronization failed
+    Failed {
+        /// Error message
+        error: String,
+    },
+}
+
+/// Progress of the synchronization of a wallet
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SyncProgress {
+    /// Name of wallet
+    pub name: String,
+    /// Block height from which synchronization started
+    pub start_block_height: u64,
+    /// Current synchronized block height
+    pub current_block_height: u64,
+    /// Block height at which synchronization will finish
+    pub finish_block_height: u64,
+    /// Status of the synchronization
+    #[serde(flatten)]
+    pub status: SyncStatus,
+}
+
+impl SyncProgress {
+    fn new(name: &str) -> Self {
+        SyncProgress {
+            name: name.to_owned(),
+            start_block_height: 0,
+            current_block_height: 0,
+            finish_block_height: 0,
+            status: SyncStatus::Running,
+        }
+    }
+
+    /// Returns the synchronized percentage of the blocks
+    pub fn percent(&self) -> f32 {
+        if self.status == SyncStatus::Completed {
+            100.0
+        } else if self.finish_block_height > self.start_block_height
+            && self.current_block_height >= self.start_block_height
+        {
+            (self.current_block_height - self.start_block_height) as f32
+                / (self.finish_block_height - self.start_block_height) as f32
+                * 100.0
+        } else {
+            0.0
+        }
+    }
+}
+
+struct WalletSync {
+    progress: Arc<Mutex<SyncProgress>>,
+    cancelled: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+/// Synchronizes multiple wallets concurrently (one thread per wallet)
+///
+/// The wallets share the tendermint client of the config, so it should be a `CachedClient` for
+/// the blocks to be fetched only once. The sync state of a wallet is saved in the storage after
+/// each batch of blocks, so a cancelled or failed synchronization resumes from the last saved
+/// block when started again.
+pub struct SyncManager<S, C, O, T, L>
+where
+    S: SecureStorage + 'static,
+    C: Client + 'static,
+    O: TransactionObfuscation + 'static,
+    T: AddressRecovery + 'static,
+    L: LightClientHandle + 'static,
+{
+    config: ObfuscationSyncerConfig<S, C, O, L>,
+    recover_address: T,
+    callback: Option<SyncCallback>,
+    syncs: Arc<Mutex<BTreeMap<String, WalletSync>>>,
+}
+
+impl<S, C, O, T, L> SyncManager<S, C, O, T, L>
+where
+    S: SecureStorage + 'static,
+    C: Client + 'static,
+    O: TransactionObfuscation + 'static,
+    T: AddressRecovery + 'static,
+    L: LightClientHandle + 'static,
+{
+    /// Creates a new instance of `SyncManager`
+    pub fn new(config: ObfuscationSyncerConfig<S, C, O, L>, recover_address: T) -> Self {
+        SyncManager {
+            config,
+            recover_address,
+            callback: None,
+            syncs: Default::default(),
+        }
+    }
+
+    /// Calls `callback` on every progress of the wallets
+    pub fn with_callback(mut self, callback: SyncCallback) -> Self {
+        self.callback = Some(callback);
+        self
+    }
+
+    /// Starts synchronizing the wallet in a new thread (from the genesis if `reset` is `true`)
+    pub fn start(&self, request: WalletRequest, reset: bool) -> Result<()> {
+        let mut syncs = self.lock()?;
+
+        if let Some(sync) = syncs.get(&request.name) {
+            if lock_progress(&sync.progress).status == SyncStatus::Running {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Wallet is already synchronizing: {}", request.name),
+                ));
+            }
+        }
+
+        let syncer = WalletSyncer::with_obfuscation_config(
+            self.config.clone(),
+            request.name.clone(),
+            request.enckey,
+            self.recover_address.clone(),
+        )?;
+
+        let progress = Arc::new(Mutex::new(SyncProgress::new(&request.name)));
+        let cancelled = Arc::new(AtomicBool::new(false));
+
+        let handle = {
+            let progress = progress.clone();
+            let cancelled = cancelled.clone();
+            let callback = self.callback.clone();
+
+            thread::spawn(move || {
+                let result = run_sync(syncer, reset, &progress, &cancelled, &callback);
+                let status = match result {
+                    Ok(()) => SyncStatus::Completed,
+                    Err(_) if cancelled.load(Ordering::SeqCst) => SyncStatus::Cancelled,
+                    Err(err) => SyncStatus::Failed {
+                        error: err.to_string(),
+                    },
+                };
+                update_progress(&progress, &callback, |progress| progress.status = status);
+            })
+        };
+
+        syncs.insert(
+            request.name,
+            WalletSync {
+                progress,
+                cancelled,
+                handle: Some(handle),
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Returns the progress of the wallet (if it was started)
+    pub fn progress(&self, name: &str) -> Result<Option<SyncProgress>> {
+        Ok(self
+            .lock()?
+            .get(name)
+            .map(|sync| lock_progress(&sync.progress).clone()))
+    }
+
+    /// Cancels the synchronization of the wallet (after the current batch of blocks)
+    pub fn cancel(&self, name: &str) -> Result<()> {
+        let syncs = self.lock()?;
+        let sync = syncs.get(name).ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("Wallet is not synchronizing: {}", name),
+            )
+        })?;
+        sync.cancelled.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Waits for the end of the synchronization of the wallet and returns its final progress
+    pub fn wait(&self, name: &str) -> Result<SyncProgress> {
+        let (handle, progress) = {
+            let mut syncs = self.lock()?;
+            let sync = syncs.get_mut(name).ok_or_else(|| {
+                Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Wallet is not synchronizing: {}", name),
+                )
+            })?;
+            (sync.handle.take(), sync.progress.clone())
+        };
+
+        if let Some(handle) = handle {
+            handle.join().map_err(|_| {
+                Error::new(
+                    ErrorKind::InternalError,
+                    format!("Synchronization thread of wallet {} panicked", name),
+                )
+            })?;
+        }
+
+        let progress = lock_progress(&progress).clone();
+        Ok(progress)
+    }
+
+    fn lock(&self) -> Result<MutexGuard<BTreeMap<String, WalletSync>>> {
+        self.syncs.lock().map_err(|_| {
+            Error::new(
+                ErrorKind::InternalError,
+                "Unable to acquire lock on wallet synchronizations",
+            )
+        })
+    }
+}
+
+fn run_sync<S, C, O, T, L>(
+    mut syncer: WalletSyncer<S, C, TxObfuscationDecryptor<O>, T, L>,
+    reset: bool,
+    progress: &Mutex<SyncProgress>,
+    cancelled: &AtomicBool,
+    callback: &Option<SyncCallback>,
+) -> Result<()>
+where
+    S: SecureStorage + 'static,
+    C: Client,
+    O: TransactionObfuscation,
+    T: AddressRecovery,
+    L: LightClientHandle,
+{
+    if reset {
+        syncer.reset_state()?;
+    }
+
+    syncer.sync(|report| {
+        update_progress(progress, callback, |progress| match report {
+            ProgressReport::Init {
+                start_block_height,
+                finish_block_height,
+                ..
+            } => {
+                progress.start_block_height = start_block_height;
+                progress.current_block_height = start_block_height;
+                progress.finish_block_height = finish_block_height;
+            }
+            ProgressReport::Update {
+                current_block_height,
+                ..
+            } => progress.current_block_height = current_block_height,
+        });
+        !cancelled.load(Ordering::SeqCst)
+    })
+}
+
+fn lock_progress(progress: &Mutex<SyncProgress>) -> MutexGuard<SyncProgress> {
+    progress.lock().expect("get sync progress lock")
+}
+
+fn update_progress<F: FnOnce(&mut SyncProgress)>(
+    progress: &Mutex<SyncProgress>,
+    callback: &Option<SyncCallback>,
+    update: F,
+) {
+    let mut progress = lock_progress(progress);
+    update(&mut progress);
+    if let Some(callback) = callback {
+        callback(&progress);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use secstr::SecUtf8;
+
+    use client_common::storage::MemoryStorage;
+    use client_common::tendermint::{CachedClient, DEFAULT_BLOCK_CACHE_CAPACITY};
+    use client_common::MockAbciTransactionObfuscation;
+    use test_common::block_generator::{BlockGenerator, GeneratorClient};
+
+    use crate::hd_wallet::HardwareKind;
+    use crate::service::load_sync_state;
+    use crate::types::WalletKind;
+    use crate::wallet::syncer::{compute_genesis_fingerprint, SyncerOptions};
+    use crate::wallet::{DefaultWalletClient, WalletClient};
+
+    #[test]
+    fn check_concurrent_sync() {
+        let storage = MemoryStorage::default();
+        let wallet = DefaultWalletClient::new_read_only(storage.clone());
+
+        let requests = ["first", "second", "third"]
+            .iter()
+            .map(|name| {
+                let (enckey, _) = wallet
+                    .new_wallet(
+                        name,
+                        &SecUtf8::from("passphrase"),
+                        WalletKind::Basic,
+                        HardwareKind::LocalOnly,
+                        None,
+                    )
+                    .unwrap();
+                WalletRequest {
+                    name: (*name).to_owned(),
+                    enckey,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let client = GeneratorClient::new(BlockGenerator::one_node());
+        {
+            let mut gen = client.gen.write().unwrap();
+            for _ in 0..10 {
+                gen.gen_block(&[]);
+            }
+        }
+        let hash = compute_genesis_fingerprint(&client.genesis().unwrap()).unwrap();
+        std::env::set_var("CRYPTO_GENESIS_FINGERPRINT", hash);
+
+        let config = ObfuscationSyncerConfig::new(
+            storage.clone(),
+            CachedClient::new(client.clone(), DEFAULT_BLOCK_CACHE_CAPACITY),
+            MockAbciTransactionObfuscation::new(client.clone()),
+            SyncerOptions {
+                enable_fast_forward: false,
+                disable_light_client: false,
+                enable_address_recovery: false,
+                enable_block_filters: false,
+                batch_size: 5,
+                block_height_ensure: 50,
+                light_client_peers: "".into(),
+                light_client_trusting_period_seconds: 36000000,
+                light_client_trusting_height: 1,
+                light_client_trusting_blockhash: "".into(),
+            },
+            Some(client.clone()),
+        );
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let manager = SyncManager::new(config, wallet).with_callback({
+            let reports = reports.clone();
+            Arc::new(move |progress: &SyncProgress| {
+                reports.lock().unwrap().push(progress.clone());
+            })
+        });
+
+        for request in requests.iter() {
+            manager.start(request.clone(), false).unwrap();
+        }
+
+        let latest_height = client
+            .status()
+            .unwrap()
+            .sync_info
+            .latest_block_height
+            .value();
+        for request in requests.iter() {
+            let progress = manager.wait(&request.name).unwrap();
+            assert_eq!(SyncStatus::Completed, progress.status);
+            assert_eq!(100.0, progress.percent());
+            assert_eq!(
+                latest_height,
+                load_sync_state(&storage, &request.name)
+                    .unwrap()
+                    .unwrap()
+                    .last_block_height
+            );
+        }
+        assert!(reports
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|progress| progress.name == "second" && progress.status == SyncStatus::Running));
+
+        // resumes from the saved sync state
+        {
+            let mut gen = client.gen.write().unwrap();
+            for _ in 0..5 {
+                gen.gen_block(&[]);
+            }
+        }
+        manager.start(requests[0].clone(), false).unwrap();
+        let progress = manager.wait(&requests[0].name).unwrap();
+        assert_eq!(SyncStatus::Completed, progress.status);
+        assert!(progress.start_block_height >= latest_height);
+
+        assert!(manager.cancel("unknown").is_err());
+    }
+}
@@ -38,6 +38,7 @@ use chain_core::tx::fee::Fee;
 use chain_storage::jellyfish::compute_staking_root;
 use chain_tx_filter::BlockFilter;
 use chain_util::NonEmpty;
+use client_common::correlation::log_prefix;
 use client_common::tendermint::types::{
     Block, BlockExt, BlockResults, BlockResultsResponse, Genesis, Time,
 };
@@ -47,7 +48,11 @@ use client_common::{
     TransactionObfuscation,
 };
 
+use super::syncer_filters::{relevant_heights, WalletFilterItems};
 use super::syncer_logic::handle_blocks;
+use crate::plugin::{
+    decode_custom_transaction, has_plugins, is_custom_transaction, CustomTransaction,
+};
 use crate::service;
 use crate::service::{KeyService, SyncState, Wallet, WalletState, WalletStateMemento};
 use std::sync::Mutex;
@@ -112,6 +117,9 @@ pub struct SyncerOptions {
     pub enable_fast_forward: bool,
     pub disable_light_client: bool,
     pub enable_address_recovery: bool,
+    /// only download the blocks whose filters may match the wallet (see `relevant_heights`);
+    /// the hash chain is then only verified between the consecutive downloaded blocks
+    pub enable_block_filters: bool,
     pub batch_size: usize,
     pub block_height_ensure: u64,
     pub light_client_peers: String,
@@ -451,6 +459,27 @@ impl<
         self.sync_state.last_app_hash = block.app_hash.clone();
         self.sync_state.last_block_hash = block.block_hash.clone();
         self.sync_state.staking_root = block.staking_root;
+        service::update_invoices(
+            &self.env.storage,
+            &self.env.name,
+            &self.env.enckey,
+            &memento,
+            &block.block_time,
+        )?;
+        service::update_vaults(
+            &self.env.storage,
+            &self.env.name,
+            &self.env.enckey,
+            &memento,
+            &block.block_time,
+        )?;
+        service::update_inheritance_plans(
+            &self.env.storage,
+            &self.env.name,
+            &self.env.enckey,
+            &memento,
+            &block.block_time,
+        )?;
         self.save(&memento)?;
 
         if !self.update_progress(block.block_height) {
@@ -552,7 +581,7 @@ impl<
         target_block_hash: &str,
     ) -> Result<()> {
         self.sync_state.trusted = false;
-        log::debug!("sync_to block {} ", target_height);
+        log::debug!("{}sync_to block {} ", log_prefix(), target_height);
 
         // Send batch RPC requests to tendermint in chunks of `batch_size` requests per batch call
         for chunk in ((self.sync_state.last_block_height + 1)..=target_height)
@@ -580,6 +609,13 @@ impl<
                 }
             }
 
+            // the filters don't include the custom transactions of the plugins
+            let range = if self.env.options.enable_block_filters && !has_plugins() {
+                self.filter_range(range)?
+            } else {
+                range
+            };
+
             // Fetch batch details if it cannot be fast forwarded
             let mut blocks: Vec<Block> = vec![];
             let mut block_results: Vec<BlockResultsResponse> = vec![];
@@ -612,7 +648,7 @@ impl<
                         );
                     }
                 }
-                log::info!("retry fetching block-data");
+                log::info!("{}retry fetching block-data", log_prefix());
                 std::thread::sleep(std::time::Duration::from_secs(5));
             }
             // succeed?
@@ -620,6 +656,7 @@ impl<
                 return Err(Error::new(ErrorKind::IoError, "sync fetch-block failed"));
             }
 
+            let mut last_height = self.sync_state.last_block_height;
             for (block, block_result, state) in izip!(
                 blocks.into_iter(),
                 block_results.into_iter(),
@@ -633,8 +670,13 @@ impl<
                     &state,
                 )?;
 
+                // the blocks skipped by their filters are not downloaded
+                let consecutive = block.block_height == last_height + 1;
+                last_height = block.block_height;
+
                 // verify app hash chain
-                if !self.sync_state.last_app_hash.is_empty()
+                if consecutive
+                    && !self.sync_state.last_app_hash.is_empty()
                     && self.sync_state.last_app_hash != block.last_app_hash
                 {
                     return Err(Error::new(
@@ -645,7 +687,8 @@ impl<
                 self.sync_state.last_app_hash = block.app_hash.clone();
 
                 // verify block hash chain
-                if !self.sync_state.last_block_hash.is_empty()
+                if consecutive
+                    && !self.sync_state.last_block_hash.is_empty()
                     && self.sync_state.last_block_hash != block.last_block_hash
                 {
                     return Err(Error::new(
@@ -686,12 +729,33 @@ impl<
             }
             Ordering::Less => {
                 // not up-to-date, try again
-                log::warn!("not up to date, sync again");
+                log::warn!("{}not up to date, sync again", log_prefix());
                 self.sync_to(target_height, target_app_hash, target_block_hash)
             }
         }
     }
 
+    /// The heights of the range whose blocks may be relevant to the wallet
+    /// (the whole range if the filters are not available)
+    fn filter_range(&self, range: Vec<u64>) -> Result<Vec<u64>> {
+        let items = WalletFilterItems::new(&self.wallet, &self.wallet_state)?;
+        match relevant_heights(&self.env.client, &items, &range) {
+            Ok(heights) => {
+                log::debug!(
+                    "{}downloading {} of {} blocks",
+                    log_prefix(),
+                    heights.len(),
+                    range.len()
+                );
+                Ok(heights)
+            }
+            Err(err) => {
+                log::warn!("{}block filters not available: {}", log_prefix(), err);
+                Ok(range)
+            }
+        }
+    }
+
     fn rollback_pending_tx(&mut self, current_block_height: u64) -> Result<()> {
         let mut memento = WalletStateMemento::default();
         let state =
@@ -864,6 +928,8 @@ pub(crate) struct FilteredBlock {
     pub enclave_transaction_ids: Vec<TxId>,
     /// List of un-encrypted transactions (only contains transactions of type `DepositStake` and `UnbondStake`)
     pub staking_transactions: Vec<Transaction>,
+    /// Transactions of the custom types of the registered plugins
+    pub custom_transactions: Vec<CustomTransaction>,
     /// staking root after this block
     pub staking_root: H256,
 }
@@ -922,11 +988,17 @@ impl FilteredBlock {
 
         let enclave_transaction_ids =
             if block_filter.check_view_key(&wallet.view_key.clone().into()) {
-                block.enclave_transaction_ids()?
+                block.enclave_transaction_ids_except(&is_custom_transaction)?
             } else {
                 vec![]
             };
 
+        let custom_transactions = block
+            .data
+            .iter()
+            .filter_map(|raw| decode_custom_transaction(&raw.clone().into_vec()))
+            .collect();
+
         Ok(FilteredBlock {
             last_app_hash,
             app_hash,
@@ -938,6 +1010,7 @@ impl FilteredBlock {
             enclave_transaction_ids,
             block_filter,
             staking_transactions,
+            custom_transactions,
             staking_root: state.account_root,
         })
     }
@@ -965,7 +1038,7 @@ fn filter_staking_transactions(
     };
     if block_results.contains_staking() {
         let txs = block
-            .staking_transactions()?
+            .staking_transactions_except(&is_custom_transaction)?
             .iter()
             .filter(|&t| outgoing_tx(t))
             .cloned()
@@ -983,7 +1056,7 @@ fn filter_incomming_staking_transactions(
     block: &Block,
 ) -> Result<Vec<Transaction>> {
     if block_results.contains_account(wallet)? {
-        return block.staking_transactions();
+        return block.staking_transactions_except(&is_custom_transaction);
     }
 
     Ok(Default::default())
@@ -1144,6 +1217,7 @@ mod tests {
                     enable_fast_forward,
                     disable_light_client: enable_fast_forward,
                     enable_address_recovery: false,
+                    enable_block_filters: false,
                     batch_size: 20,
                     block_height_ensure: 50,
                     light_client_peers: "".into(),
@@ -1222,6 +1296,10 @@ mod tests {
             }
 
             /// Match batch state `abci_query` call to tendermint
+            fn unconfirmed_txs(&self, _limit: u64) -> Result<Vec<Vec<u8>>> {
+                unreachable!()
+            }
+
             fn query_state_batch<T: Iterator<Item = u64>>(
                 &self,
                 _heights: T,
@@ -1277,6 +1355,7 @@ mod tests {
                     enable_fast_forward,
                     disable_light_client: enable_fast_forward,
                     enable_address_recovery: false,
+                    enable_block_filters: false,
                     batch_size: 20,
                     block_height_ensure: 50,
                     light_client_peers: "".into(),
@@ -1334,6 +1413,7 @@ mod tests {
                     enable_fast_forward: false,
                     disable_light_client: false,
                     enable_address_recovery: true,
+                    enable_block_filters: false,
                     batch_size: 20,
                     block_height_ensure: 50,
                     light_client_peers: "".into(),
@@ -1398,6 +1478,7 @@ mod tests {
                     enable_fast_forward: false,
                     disable_light_client: false,
                     enable_address_recovery: true,
+                    enable_block_filters: false,
                     batch_size: 20,
                     block_height_ensure: 50,
                     light_client_peers: "".into(),
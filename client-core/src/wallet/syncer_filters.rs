@@ -0,0 +1,170 @@
+use std::convert::TryFrom;
+
+use parity_scale_codec::{Decode, Encode};
+use secp256k1::PublicKey;
+
+use chain_core::state::account::StakedStateAddress;
+use chain_core::state::tendermint::BlockHeight;
+use chain_core::tx::data::input::TxoPointer;
+use chain_tx_filter::{BlockFilter, CompactFilter};
+use client_common::tendermint::types::AbciQueryExt;
+use client_common::tendermint::Client;
+use client_common::{Error, ErrorKind, Result, ResultExt};
+
+use crate::service::{Wallet, WalletState};
+
+/// Filters of a block served by the "block-filters" ABCI query: the view key bloom filter
+/// (None if the block had no transactions with view keys) and the compact filter
+/// of its public transaction data
+pub(crate) type BlockFilters = (BlockHeight, Option<Vec<u8>>, Vec<u8>);
+
+/// What makes a block relevant to a wallet
+pub(crate) struct WalletFilterItems {
+    view_key: PublicKey,
+    staking_addresses: Vec<StakedStateAddress>,
+    /// unspent outputs and the inputs of the pending transactions
+    inputs: Vec<TxoPointer>,
+}
+
+impl WalletFilterItems {
+    pub(crate) fn new(wallet: &Wallet, wallet_state: &WalletState) -> Result<Self> {
+        let inputs = wallet_state
+            .unspent_transactions
+            .keys()
+            .cloned()
+            .chain(
+                wallet_state
+                    .pending_transactions
+                    .values()
+                    .flat_map(|pending| pending.used_inputs.iter().cloned()),
+            )
+            .collect();
+        Ok(WalletFilterItems {
+            view_key: wallet.view_key.clone().into(),
+            staking_addresses: wallet.get_staking_addresses()?.into_iter().collect(),
+            inputs,
+        })
+    }
+
+    /// true = the block may be relevant
+    /// false = the block is not relevant
+    pub(crate) fn matches(&self, filters: &BlockFilters) -> Result<bool> {
+        let (height, view_key_filter, compact_filter) = filters;
+        if let Some(view_key_filter) = view_key_filter {
+            let filter = BlockFilter::try_from(view_key_filter.as_slice())
+                .map_err(|e| Error::new(ErrorKind::DeserializationError, e))?;
+            if filter.check_view_key(&self.view_key) {
+                return Ok(true);
+            }
+        }
+        let filter = CompactFilter::try_from(compact_filter.as_slice())
+            .map_err(|e| Error::new(ErrorKind::DeserializationError, e))?;
+        Ok(
+            filter.match_any_staking_address(*height, &self.staking_addresses)
+                || filter.match_any_input(*height, &self.inputs),
+        )
+    }
+}
+
+/// The heights of the blocks in the (non-empty, consecutive) range which may be relevant
+/// to the wallet. The blocks without filters (e.g. committed before they were stored)
+/// and the last block of the range (the sync state advances to it) are always included.
+pub(crate) fn relevant_heights<C: Client>(
+    client: &C,
+    items: &WalletFilterItems,
+    range: &[u64],
+) -> Result<Vec<u64>> {
+    let (first, last) = (range[0], range[range.len() - 1]);
+    let data = (BlockHeight::new(first), BlockHeight::new(last)).encode();
+    let response = client.query("block-filters", &data, None, false)?;
+    let filters = Vec::<BlockFilters>::decode(&mut response.bytes().as_slice()).chain(|| {
+        (
+            ErrorKind::DeserializationError,
+            "Unable to decode block filters",
+        )
+    })?;
+    select_heights(items, range, &filters)
+}
+
+fn select_heights(
+    items: &WalletFilterItems,
+    range: &[u64],
+    filters: &[BlockFilters],
+) -> Result<Vec<u64>> {
+    let mut irrelevant = Vec::new();
+    for block_filters in filters.iter() {
+        if !items.matches(block_filters)? {
+            irrelevant.push(block_filters.0.value());
+        }
+    }
+    let last = range[range.len() - 1];
+    Ok(range
+        .iter()
+        .copied()
+        .filter(|height| *height == last || !irrelevant.contains(height))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chain_core::init::address::RedeemAddress;
+    use chain_tx_filter::CompactFilterBuilder;
+    use secp256k1::{SecretKey, SECP256K1};
+
+    #[test]
+    fn check_select_heights() {
+        let view_key =
+            PublicKey::from_secret_key(SECP256K1, &SecretKey::from_slice(&[0xcd; 32]).unwrap());
+        let address = StakedStateAddress::BasicRedeem(RedeemAddress::from([1; 20]));
+        let input = TxoPointer::new([2; 32], 0);
+        let items = WalletFilterItems {
+            view_key,
+            staking_addresses: vec![address],
+            inputs: vec![input.clone()],
+        };
+
+        let mut view_key_filter = BlockFilter::default();
+        view_key_filter.add_view_key(&view_key);
+        let (_, view_key_filter) = view_key_filter.get_tendermint_kv();
+        let compact = |height: u64, builder: CompactFilterBuilder| {
+            builder.build(BlockHeight::new(height)).to_bytes()
+        };
+        let mut staking = CompactFilterBuilder::default();
+        staking.add_staking_address(&address);
+        let mut spent = CompactFilterBuilder::default();
+        spent.add_input(&input);
+        let mut other = CompactFilterBuilder::default();
+        other.add_txid(&[3; 32]);
+
+        let filters = vec![
+            (
+                BlockHeight::new(1),
+                None,
+                compact(1, CompactFilterBuilder::default()),
+            ),
+            (
+                BlockHeight::new(2),
+                Some(view_key_filter),
+                compact(2, other),
+            ),
+            (BlockHeight::new(3), None, compact(3, staking)),
+            (BlockHeight::new(4), None, compact(4, spent)),
+            (
+                BlockHeight::new(6),
+                None,
+                compact(6, CompactFilterBuilder::default()),
+            ),
+            (
+                BlockHeight::new(7),
+                None,
+                compact(7, CompactFilterBuilder::default()),
+            ),
+        ];
+        // block 5 has no filters, block 7 is the last one
+        assert_eq!(
+            vec![2, 3, 4, 5, 7],
+            select_heights(&items, &[1, 2, 3, 4, 5, 6, 7], &filters).unwrap()
+        );
+    }
+}
@@ -15,6 +15,7 @@ use client_common::tendermint::types::Time;
 use client_common::Transaction;
 
 use super::syncer::FilteredBlock;
+use crate::plugin::{render_custom_transaction, CustomTransaction};
 use crate::service::{Wallet, WalletState};
 use crate::types::{BalanceChange, TransactionChange, TransactionInput, TransactionType};
 use crate::wallet::syncer::ProgressReport;
@@ -79,10 +80,54 @@ pub(crate) fn handle_blocks(
                 )?;
             }
         }
+
+        for tx in block.custom_transactions.iter() {
+            if let Some(fee) = block.valid_transaction_fees.get(&tx.transaction_id) {
+                handle_custom_transaction(
+                    wallet,
+                    wallet_state,
+                    &mut memento,
+                    tx,
+                    *fee,
+                    block.block_height,
+                    block.block_time,
+                );
+            }
+        }
     }
     Ok(memento)
 }
 
+/// Adds the custom transaction to the history if the plugin renders a balance change for the
+/// wallet (custom transactions don't change the unspent transactions)
+fn handle_custom_transaction(
+    wallet: &Wallet,
+    wallet_state: &mut WalletState,
+    memento: &mut WalletStateMemento,
+    transaction: &CustomTransaction,
+    fee_paid: Fee,
+    block_height: u64,
+    block_time: Time,
+) {
+    let balance_change = match render_custom_transaction(transaction, wallet) {
+        Some(balance_change) => balance_change,
+        None => return,
+    };
+    let transaction_change = TransactionChange {
+        transaction_id: transaction.transaction_id,
+        inputs: Vec::new(),
+        outputs: Vec::new(),
+        fee_paid,
+        balance_change,
+        transaction_type: TransactionType::Custom,
+        block_height,
+        block_time,
+        annotation: None,
+    };
+    memento.add_transaction_change(transaction_change.clone());
+    wallet_state.add_transaction_change(transaction_change.transaction_id, transaction_change);
+}
+
 pub fn create_transaction_change(
     wallet: &Wallet,
     wallet_state: &WalletState,
@@ -107,6 +152,7 @@ pub fn create_transaction_change(
         transaction_type,
         block_height,
         block_time,
+        annotation: None,
     };
     Ok(transaction_change)
 }
@@ -370,6 +416,7 @@ mod tests {
             enclave_transaction_ids: enclave_txs.iter().map(|tx| tx.id()).collect(),
             block_filter,
             staking_transactions: other_txs.to_vec(),
+            custom_transactions: Vec::new(),
             staking_root,
         }
     }
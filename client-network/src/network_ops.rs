@@ -1,10 +1,17 @@
 //! Network operations on Thaler Experimental Network
 mod default_network_ops_client;
+mod stake_migration;
+mod withdraw_template;
 
 pub use self::default_network_ops_client::DefaultNetworkOpsClient;
+pub use self::stake_migration::{StakeMigration, StakeMigrationStatus, StakeMigrator};
+pub use self::withdraw_template::{WithdrawTemplate, WithdrawTemplateStatus, WithdrawTemplates};
+use chain_core::common::H256;
 use chain_core::init::coin::Coin;
+use chain_core::init::params::NetworkParameters;
 use chain_core::state::account::{
-    CouncilNodeMeta, StakedState, StakedStateAddress, StakedStateOpAttributes,
+    CouncilNodeMeta, DataAnchorProof, StakedState, StakedStateAddress, StakedStateOpAttributes,
+    StakedStateOpWitness, WithdrawUnbondedTx,
 };
 use chain_core::tx::data::address::ExtendedAddr;
 use chain_core::tx::data::attribute::TxAttributes;
@@ -12,7 +19,7 @@ use chain_core::tx::data::input::TxoPointer;
 use chain_core::tx::data::output::TxOut;
 use chain_core::tx::TxAux;
 use client_common::tendermint::types::{Genesis, StatusResponse};
-use client_common::{ErrorKind, Result, ResultExt, SecKey};
+use client_common::{ErrorKind, Result, ResultExt, SecKey, SignedTransaction};
 use client_core::types::TransactionPending;
 
 /// Interface for performing network operations on Thaler Experimental Network
@@ -20,6 +27,9 @@ pub trait NetworkOpsClient: Send + Sync {
     /// calculate the deposit fee
     fn calculate_deposit_fee(&self) -> Result<Coin>;
 
+    /// calculate the fee of unbond transactions
+    fn calculate_unbond_fee(&self) -> Result<Coin>;
+
     /// creates a new transaction for bonding stake transaction with utxos
     fn create_deposit_bonded_stake_transaction(
         &self,
@@ -64,6 +74,23 @@ pub trait NetworkOpsClient: Send + Sync {
         verify_staking: bool,
     ) -> Result<(TxAux, TransactionPending)>;
 
+    /// Signs (without encrypting) a transaction withdrawing all the unbonded stake of an account
+    /// at the end of its unbonding period, which may not have passed yet. It's only valid while
+    /// the nonce of the account doesn't change.
+    fn create_withdraw_all_unbonded_stake_template(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        from_address: &StakedStateAddress,
+        to_address: ExtendedAddr,
+        attributes: TxAttributes,
+        verify_staking: bool,
+    ) -> Result<(WithdrawUnbondedTx, StakedStateOpWitness)>;
+
+    /// Encrypts a signed transaction for broadcasting (the transaction has to be valid
+    /// in the current state)
+    fn encrypt_transaction(&self, transaction: SignedTransaction) -> Result<TxAux>;
+
     /// Creates a new transaction for un-jailing a previously jailed account
     fn create_unjail_transaction(
         &self,
@@ -85,6 +112,22 @@ pub trait NetworkOpsClient: Send + Sync {
         verify_staking: bool,
     ) -> Result<TxAux>;
 
+    /// Creates a new transaction for anchoring the commitment (the fee is paid from the bonded
+    /// amount of the staking address)
+    fn create_data_anchor_transaction(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        address: StakedStateAddress,
+        commitment: H256,
+        attributes: StakedStateOpAttributes,
+        verify_staking: bool,
+    ) -> Result<TxAux>;
+
+    /// Returns the anchorings of the commitment, each one verified against the app hash
+    /// in the header of the block after the anchoring
+    fn get_data_anchor_proofs(&self, commitment: &H256) -> Result<Vec<DataAnchorProof>>;
+
     /// Returns staked stake corresponding to given address
     fn get_staked_state(
         &self,
@@ -106,6 +149,10 @@ pub trait NetworkOpsClient: Send + Sync {
         verify: bool,
     ) -> Result<Option<StakedState>>;
 
+    /// Returns the network parameters in force at the end of the block at given height,
+    /// verified against the app hash in the header of the next block
+    fn get_network_params(&self, height: u64) -> Result<NetworkParameters>;
+
     /// Return genesis of tendermint
     fn get_genesis(&self) -> Result<Genesis>;
 
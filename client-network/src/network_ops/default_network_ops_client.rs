@@ -1,12 +1,17 @@
+use std::convert::TryInto;
+
 use parity_scale_codec::Decode;
 
 use crate::NetworkOpsClient;
-use chain_core::common::Timespec;
+use chain_core::common::{Timespec, H256};
 use chain_core::init::coin::{sum_coins, Coin};
+use chain_core::init::params::NetworkParameters;
 use chain_core::state::account::{
-    CouncilNodeMeta, DepositBondTx, NodeMetadata, StakedState, StakedStateAddress,
-    StakedStateOpAttributes, StakedStateOpWitness, UnbondTx, UnjailTx, WithdrawUnbondedTx,
+    CouncilNodeMeta, DataAnchorProof, DataAnchorTx, DepositBondTx, NodeMetadata, Nonce,
+    StakedState, StakedStateAddress, StakedStateOpAttributes, StakedStateOpWitness, UnbondTx,
+    UnjailTx, WithdrawUnbondedTx,
 };
+use chain_core::state::governance::AccountFreezeState;
 use chain_core::state::validator::NodeJoinRequestTx;
 use chain_core::tx::data::address::ExtendedAddr;
 use chain_core::tx::data::attribute::TxAttributes;
@@ -14,6 +19,7 @@ use chain_core::tx::data::input::TxoPointer;
 use chain_core::tx::data::output::TxOut;
 use chain_core::tx::fee::FeeAlgorithm;
 use chain_core::tx::{TxAux, TxPublicAux};
+use chain_core::AppHashParts;
 use chain_storage::jellyfish::SparseMerkleProof;
 use chain_tx_validation::{check_inputs_basic, check_outputs_basic, verify_unjailed};
 use client_common::tendermint::types::{AbciQueryExt, Genesis, StatusResponse};
@@ -94,6 +100,75 @@ where
         Ok(fee)
     }
 
+    /// Outputs withdrawing all the unbonded stake (minus the fee) to `to_address`, locked to
+    /// the end of the unbonding period
+    fn withdraw_all_outputs(
+        &self,
+        staked_state: &StakedState,
+        to_address: ExtendedAddr,
+        attributes: &TxAttributes,
+    ) -> Result<Vec<TxOut>> {
+        verify_unjailed(staked_state).map_err(|e| {
+            Error::new(
+                ErrorKind::ValidationError,
+                format!("Failed to validate staking account: {}", e),
+            )
+        })?;
+
+        let temp_output =
+            TxOut::new_with_timelock(to_address.clone(), Coin::zero(), staked_state.unbonded_from);
+        let fee = self.calculate_fee(vec![temp_output], attributes.clone())?;
+        let amount = (staked_state.unbonded - fee).chain(|| {
+            (
+                ErrorKind::IllegalInput,
+                "Calculated fee is more than the unbonded amount",
+            )
+        })?;
+        let outputs = vec![TxOut::new_with_timelock(
+            to_address,
+            amount,
+            staked_state.unbonded_from,
+        )];
+
+        check_outputs_basic(&outputs).map_err(|e| {
+            Error::new(
+                ErrorKind::ValidationError,
+                format!("Failed to validate staking account: {}", e),
+            )
+        })?;
+        Ok(outputs)
+    }
+
+    /// Signs the withdraw transaction with the staking key of `from_address`
+    fn sign_withdraw_unbonded_stake_transaction(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        from_address: &StakedStateAddress,
+        nonce: Nonce,
+        outputs: Vec<TxOut>,
+        attributes: TxAttributes,
+    ) -> Result<(WithdrawUnbondedTx, StakedStateOpWitness)> {
+        let transaction = WithdrawUnbondedTx::new(nonce, outputs, attributes);
+        let tx = Transaction::WithdrawUnbondedStakeTransaction(transaction.clone());
+
+        let public_key = match from_address {
+            StakedStateAddress::BasicRedeem(ref redeem_address) => self
+                .wallet_client
+                .find_staking_key(name, enckey, redeem_address)?
+                .chain(|| {
+                    (
+                        ErrorKind::InvalidInput,
+                        "Address not found in current wallet",
+                    )
+                })?,
+        };
+        let sign_key = self.wallet_client.sign_key(name, enckey, &public_key)?;
+        let signature = sign_key.sign(&tx).map(StakedStateOpWitness::new)?;
+
+        Ok((transaction, signature))
+    }
+
     fn get_last_block_time(&self) -> Result<Timespec> {
         let status = self.client.status()?;
         Ok(to_timespec(
@@ -104,6 +179,25 @@ where
             },
         ))
     }
+
+    /// Unbonding and withdrawing out of a staking address frozen by the council are rejected
+    fn verify_unfrozen(&self, address: &StakedStateAddress) -> Result<()> {
+        let bytes = self
+            .client
+            .query("account-freeze", &[], None, false)?
+            .bytes();
+        let account_freeze = AccountFreezeState::decode(&mut bytes.as_slice())
+            .err_kind(ErrorKind::DeserializationError, || {
+                "Cannot deserialize the account freeze state"
+            })?;
+        if account_freeze.is_frozen(address) {
+            return Err(Error::new(
+                ErrorKind::IllegalInput,
+                "Staking address is frozen by a council decision",
+            ));
+        }
+        Ok(())
+    }
 }
 
 impl<W, S, C, F, E> NetworkOpsClient for DefaultNetworkOpsClient<W, S, C, F, E>
@@ -132,6 +226,27 @@ where
         Ok(fee)
     }
 
+    fn calculate_unbond_fee(&self) -> Result<Coin> {
+        let dummy_signer = DummySigner();
+        let tx = UnbondTx::new(
+            StakedStateAddress::BasicRedeem(Default::default()),
+            0,
+            Coin::zero(),
+            StakedStateOpAttributes::default(),
+        );
+        let fee = self
+            .fee_algorithm
+            .calculate_for_txaux(&dummy_signer.mock_txaux_for_unbond(tx))
+            .chain(|| {
+                (
+                    ErrorKind::IllegalInput,
+                    "Calculated fee is more than the maximum allowed value",
+                )
+            })?
+            .to_coin();
+        Ok(fee)
+    }
+
     fn create_deposit_bonded_stake_transaction<'a>(
         &'a self,
         name: &'a str,
@@ -202,6 +317,7 @@ where
                 format!("Failed to validate staking account: {}", e),
             )
         })?;
+        self.verify_unfrozen(&address)?;
 
         let nonce = staked_state.nonce;
 
@@ -274,6 +390,7 @@ where
                 format!("Failed to validate staking account: {}", e),
             )
         })?;
+        self.verify_unfrozen(from_address)?;
 
         let output_value = sum_coins(outputs.iter().map(|output| output.value))
             .chain(|| (ErrorKind::InvalidInput, "Error while adding output values"))?;
@@ -285,27 +402,16 @@ where
             ));
         }
 
-        let nonce = staked_state.nonce;
-
-        let transaction = WithdrawUnbondedTx::new(nonce, outputs, attributes);
-        let tx = Transaction::WithdrawUnbondedStakeTransaction(transaction.clone());
-
-        let public_key = match from_address {
-            StakedStateAddress::BasicRedeem(ref redeem_address) => self
-                .wallet_client
-                .find_staking_key(name, enckey, redeem_address)?
-                .chain(|| {
-                    (
-                        ErrorKind::InvalidInput,
-                        "Address not found in current wallet",
-                    )
-                })?,
-        };
-        let sign_key = self.wallet_client.sign_key(name, enckey, &public_key)?;
-        let signature = sign_key.sign(&tx).map(StakedStateOpWitness::new)?;
-
+        let (transaction, witness) = self.sign_withdraw_unbonded_stake_transaction(
+            name,
+            enckey,
+            from_address,
+            staked_state.nonce,
+            outputs,
+            attributes,
+        )?;
         let signed_transaction =
-            SignedTransaction::WithdrawUnbondedStakeTransaction(transaction, signature);
+            SignedTransaction::WithdrawUnbondedStakeTransaction(transaction, witness);
         let tx_aux = self.transaction_cipher.encrypt(signed_transaction)?;
         let block_height = match self.wallet_client.get_current_block_height() {
             Ok(h) => h,
@@ -376,35 +482,7 @@ where
         verify_staking: bool,
     ) -> Result<(TxAux, TransactionPending)> {
         let staked_state = self.get_staked_state(name, from_address, verify_staking)?;
-
-        verify_unjailed(&staked_state).map_err(|e| {
-            Error::new(
-                ErrorKind::ValidationError,
-                format!("Failed to validate staking account: {}", e),
-            )
-        })?;
-
-        let temp_output =
-            TxOut::new_with_timelock(to_address.clone(), Coin::zero(), staked_state.unbonded_from);
-        let fee = self.calculate_fee(vec![temp_output], attributes.clone())?;
-        let amount = (staked_state.unbonded - fee).chain(|| {
-            (
-                ErrorKind::IllegalInput,
-                "Calculated fee is more than the unbonded amount",
-            )
-        })?;
-        let outputs = vec![TxOut::new_with_timelock(
-            to_address,
-            amount,
-            staked_state.unbonded_from,
-        )];
-
-        check_outputs_basic(&outputs).map_err(|e| {
-            Error::new(
-                ErrorKind::ValidationError,
-                format!("Failed to validate staking account: {}", e),
-            )
-        })?;
+        let outputs = self.withdraw_all_outputs(&staked_state, to_address, &attributes)?;
 
         self.create_withdraw_unbonded_stake_transaction(
             name,
@@ -416,6 +494,40 @@ where
         )
     }
 
+    fn create_withdraw_all_unbonded_stake_template(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        from_address: &StakedStateAddress,
+        to_address: ExtendedAddr,
+        attributes: TxAttributes,
+        verify_staking: bool,
+    ) -> Result<(WithdrawUnbondedTx, StakedStateOpWitness)> {
+        let staked_state = self.get_staked_state(name, from_address, verify_staking)?;
+        if staked_state.unbonded == Coin::zero() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Staking account does not have any unbonded coins to withdraw (synchronizing your wallet may help)",
+            ));
+        }
+        self.verify_unfrozen(from_address)?;
+        let outputs = self.withdraw_all_outputs(&staked_state, to_address, &attributes)?;
+
+        self.sign_withdraw_unbonded_stake_transaction(
+            name,
+            enckey,
+            from_address,
+            staked_state.nonce,
+            outputs,
+            attributes,
+        )
+    }
+
+    #[inline]
+    fn encrypt_transaction(&self, transaction: SignedTransaction) -> Result<TxAux> {
+        self.transaction_cipher.encrypt(transaction)
+    }
+
     fn create_node_join_transaction(
         &self,
         name: &str,
@@ -462,6 +574,91 @@ where
         )))
     }
 
+    fn create_data_anchor_transaction(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        address: StakedStateAddress,
+        commitment: H256,
+        attributes: StakedStateOpAttributes,
+        verify_staking: bool,
+    ) -> Result<TxAux> {
+        let staked_state = self.get_staked_state(name, &address, verify_staking)?;
+
+        verify_unjailed(&staked_state).map_err(|e| {
+            Error::new(
+                ErrorKind::ValidationError,
+                format!("Failed to validate staking account: {}", e),
+            )
+        })?;
+
+        let transaction = DataAnchorTx::new(address, staked_state.nonce, commitment, attributes);
+        let tx = Transaction::DataAnchorTransaction(transaction.clone());
+
+        let public_key = match address {
+            StakedStateAddress::BasicRedeem(ref redeem_address) => self
+                .wallet_client
+                .find_staking_key(name, enckey, redeem_address)?
+                .chain(|| {
+                    (
+                        ErrorKind::InvalidInput,
+                        "Address not found in current wallet",
+                    )
+                })?,
+        };
+        let sign_key = self.wallet_client.sign_key(name, enckey, &public_key)?;
+        let signature = sign_key.sign(&tx).map(StakedStateOpWitness::new)?;
+
+        let txaux = TxAux::PublicTx(TxPublicAux::DataAnchorTx(transaction, signature));
+
+        let fee = self
+            .fee_algorithm
+            .calculate_for_txaux(&txaux)
+            .chain(|| {
+                (
+                    ErrorKind::IllegalInput,
+                    "Calculated fee is more than the maximum allowed value",
+                )
+            })?
+            .to_coin();
+        if staked_state.bonded < fee {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Staking account does not have enough bonded coins to pay the fee (synchronizing your wallet may help)",
+            ));
+        }
+
+        Ok(txaux)
+    }
+
+    fn get_data_anchor_proofs(&self, commitment: &H256) -> Result<Vec<DataAnchorProof>> {
+        let rsp = self.client.query("data-anchor", commitment, None, false)?;
+        let proofs = <Vec<DataAnchorProof>>::decode(&mut rsp.bytes().as_slice())
+            .err_kind(ErrorKind::DeserializationError, || {
+                "Cannot deserialize data anchor proofs"
+            })?;
+        for proof in proofs.iter() {
+            let height = proof.record.height.value();
+            // the app hash after a block is included in the header of the next one
+            let block = self.client.block(height + 1)?;
+            let app_hash: H256 = block
+                .header
+                .app_hash
+                .as_ref()
+                .try_into()
+                .err_kind(ErrorKind::VerifyError, || {
+                    format!("Invalid app hash in block {}", height + 1)
+                })?;
+            if !proof.verify(commitment, &app_hash) {
+                return Err(Error::new(
+                    ErrorKind::VerifyError,
+                    format!("Verify data anchor at height {} failed", height),
+                ));
+            }
+        }
+        Ok(proofs)
+    }
+
     fn get_staking(
         &self,
         name: &str,
@@ -481,18 +678,11 @@ where
                     format!("Cannot deserialize staked state for address: {}", address)
                 })?;
 
-            let mbytes = if let Some(proof) = rsp.proof.as_ref() {
-                if let Some(evidence) = proof.ops.first() {
-                    Some(evidence.data.as_slice())
-                } else {
-                    None
-                }
-            } else {
-                None
-            };
-            let mut proof_bytes = mbytes.err_kind(ErrorKind::TendermintRpcError, || {
-                format!("There is no proof for address: {}", address)
-            })?;
+            let mut proof_bytes = rsp
+                .proof_data("staking")
+                .err_kind(ErrorKind::TendermintRpcError, || {
+                    format!("There is no proof for address: {}", address)
+                })?;
             let proof = SparseMerkleProof::decode(&mut proof_bytes).err_kind(
                 ErrorKind::DeserializationError,
                 || {
@@ -503,8 +693,22 @@ where
                 },
             )?;
 
+            // the root is trusted if it's included in the app hash the wallet synced to,
+            // otherwise the staking root verified at the sync is used
+            let staking_root = if sync_state.last_app_hash.is_empty() {
+                sync_state.staking_root
+            } else {
+                let app_hash: H256 = hex::decode(&sync_state.last_app_hash)
+                    .ok()
+                    .and_then(|app_hash| app_hash.as_slice().try_into().ok())
+                    .err_kind(ErrorKind::DeserializationError, || {
+                        "Cannot deserialize the app hash of the sync state"
+                    })?;
+                rsp.verified_account_state_root(&app_hash)?
+                    .unwrap_or(sync_state.staking_root)
+            };
             proof
-                .verify(sync_state.staking_root, address, mstaking.as_ref())
+                .verify(staking_root, address, mstaking.as_ref())
                 .err_kind(ErrorKind::VerifyError, || "Verify staking state failed")?;
 
             mstaking
@@ -521,6 +725,49 @@ where
         Ok(mstaking)
     }
 
+    fn get_network_params(&self, height: u64) -> Result<NetworkParameters> {
+        let rsp = self
+            .client
+            .query("network-params", &[], Some(height.into()), true)?;
+        let params = NetworkParameters::decode(&mut rsp.bytes().as_slice())
+            .err_kind(ErrorKind::DeserializationError, || {
+                "Cannot deserialize network parameters"
+            })?;
+        let mut proof_bytes = rsp
+            .proof
+            .as_ref()
+            .and_then(|proof| proof.ops.first())
+            .map(|op| op.data.as_slice())
+            .err_kind(ErrorKind::TendermintRpcError, || {
+                format!(
+                    "There is no proof for network parameters at height {}",
+                    height
+                )
+            })?;
+        let proof = AppHashParts::decode(&mut proof_bytes)
+            .err_kind(ErrorKind::DeserializationError, || {
+                "Cannot deserialize network parameters proof"
+            })?;
+
+        // the app hash after a block is included in the header of the next one
+        let block = self.client.block(height + 1)?;
+        let app_hash: H256 = block
+            .header
+            .app_hash
+            .as_ref()
+            .try_into()
+            .err_kind(ErrorKind::VerifyError, || {
+                format!("Invalid app hash in block {}", height + 1)
+            })?;
+        if !proof.verify_network_params(&params, &app_hash) {
+            return Err(Error::new(
+                ErrorKind::VerifyError,
+                format!("Verify network parameters at height {} failed", height),
+            ));
+        }
+        Ok(params)
+    }
+
     fn get_genesis(&self) -> Result<Genesis> {
         self.client.genesis()
     }
@@ -530,7 +777,7 @@ where
     }
 }
 
-fn to_timespec(time: Time) -> Timespec {
+pub(crate) fn to_timespec(time: Time) -> Timespec {
     time.duration_since(Time::unix_epoch()).unwrap().as_secs()
 }
 
@@ -658,11 +905,17 @@ mod tests {
 
         fn query(
             &self,
-            _path: &str,
+            path: &str,
             _data: &[u8],
             _height: Option<Height>,
             _prove: bool,
         ) -> Result<AbciQuery> {
+            if path == "account-freeze" {
+                return Ok(AbciQuery {
+                    value: AccountFreezeState::default().encode(),
+                    ..Default::default()
+                });
+            }
             let staked_state = StakedState::new(
                 0,
                 Coin::new(1000000).unwrap(),
@@ -687,6 +940,10 @@ mod tests {
             })
         }
 
+        fn unconfirmed_txs(&self, _limit: u64) -> Result<Vec<Vec<u8>>> {
+            unreachable!()
+        }
+
         fn query_state_batch<T: Iterator<Item = u64>>(
             &self,
             _heights: T,
@@ -739,11 +996,17 @@ mod tests {
 
         fn query(
             &self,
-            _path: &str,
+            path: &str,
             _data: &[u8],
             _height: Option<Height>,
             _prove: bool,
         ) -> Result<AbciQuery> {
+            if path == "account-freeze" {
+                return Ok(AbciQuery {
+                    value: AccountFreezeState::default().encode(),
+                    ..Default::default()
+                });
+            }
             let staked_state = StakedState::new(
                 0,
                 Coin::new(1000000).unwrap(),
@@ -759,6 +1022,10 @@ mod tests {
             })
         }
 
+        fn unconfirmed_txs(&self, _limit: u64) -> Result<Vec<Vec<u8>>> {
+            unreachable!()
+        }
+
         fn query_state_batch<T: Iterator<Item = u64>>(
             &self,
             _heights: T,
@@ -0,0 +1,1014 @@
+//! Moving the stake of a staking address to another one:
+//!
+//! 1. unbond all the bonded stake of the old address
+//! 2. wait for the unbonding period
+//! 3. withdraw the unbonded stake to a (timelocked) output of a new transfer address of the wallet
+//! 4. deposit the withdrawn output to the new staking address
+//!
+//! The progress is persisted after every step, so the migration can be resumed at any time
+//! (e.g. after a restart) by calling `StakeMigrator::advance` again. The transaction of a step
+//! is saved with the step before it's broadcasted, and broadcasted again by `advance` until
+//! it's applied: a step interrupted around the broadcast never creates another transaction.
+use std::fmt;
+
+use parity_scale_codec::{Decode, Encode};
+
+use super::default_network_ops_client::to_timespec;
+use crate::NetworkOpsClient;
+use chain_core::common::Timespec;
+use chain_core::init::coin::Coin;
+use chain_core::state::account::{Nonce, StakedStateAddress, StakedStateOpAttributes};
+use chain_core::tx::data::access::{TxAccess, TxAccessPolicy};
+use chain_core::tx::data::attribute::TxAttributes;
+use chain_core::tx::data::input::TxoPointer;
+use chain_core::tx::data::TxId;
+use chain_core::tx::TxAux;
+use client_common::{Error, ErrorKind, Result, ResultExt, SecKey, Storage};
+use client_core::types::TransactionPending;
+use client_core::WalletClient;
+
+/// key space of stake migrations (one key space per wallet, keyed by the old staking address)
+const KEYSPACE: &str = "core_stake_migration";
+
+fn get_migration_keyspace(name: &str) -> String {
+    format!("{}_{}", KEYSPACE, name)
+}
+
+/// Step of a stake migration
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+pub enum StakeMigrationStatus {
+    /// nothing was broadcasted yet
+    Created,
+    /// waiting for the unbond transaction and then for the end of the unbonding period
+    Unbonding {
+        /// unbond transaction (none if the stake was already unbonded)
+        unbond_tx_id: Option<TxId>,
+        /// nonce of the old address once the unbond transaction is applied
+        applied_nonce: Nonce,
+        /// time the unbonded stake can be withdrawn (once known)
+        unbonded_from: Option<Timespec>,
+    },
+    /// waiting for the wallet to sync the output of the withdraw transaction
+    Withdrawing {
+        /// withdraw transaction
+        withdraw_tx_id: TxId,
+        /// nonce of the old address once the withdraw transaction is applied
+        applied_nonce: Nonce,
+    },
+    /// waiting for the deposit to be applied to the new address
+    Depositing {
+        /// deposit transaction
+        deposit_tx_id: TxId,
+        /// bonded amount of the new address before the deposit
+        bonded_before: Coin,
+    },
+    /// the stake was moved
+    Completed {
+        /// deposit transaction
+        deposit_tx_id: TxId,
+    },
+}
+
+impl fmt::Display for StakeMigrationStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StakeMigrationStatus::Created => write!(f, "not started"),
+            StakeMigrationStatus::Unbonding {
+                unbond_tx_id,
+                unbonded_from,
+                ..
+            } => {
+                write!(f, "unbonding")?;
+                if let Some(tx_id) = unbond_tx_id {
+                    write!(f, " (transaction {})", hex::encode(tx_id))?;
+                }
+                match unbonded_from {
+                    Some(time) => write!(f, ", withdrawable from {}", time),
+                    None => write!(f, ", waiting for the unbond transaction"),
+                }
+            }
+            StakeMigrationStatus::Withdrawing { withdraw_tx_id, .. } => write!(
+                f,
+                "withdrawn (transaction {}), waiting for the wallet to sync the output",
+                hex::encode(withdraw_tx_id)
+            ),
+            StakeMigrationStatus::Depositing { deposit_tx_id, .. } => {
+                write!(f, "depositing (transaction {})", hex::encode(deposit_tx_id))
+            }
+            StakeMigrationStatus::Completed { deposit_tx_id } => {
+                write!(f, "completed (transaction {})", hex::encode(deposit_tx_id))
+            }
+        }
+    }
+}
+
+impl StakeMigrationStatus {
+    /// Number of steps of a migration
+    pub const STEPS: u64 = 4;
+
+    /// Number of steps done (unbond, withdraw, deposit and its confirmation)
+    pub fn steps_done(&self) -> u64 {
+        match self {
+            StakeMigrationStatus::Created => 0,
+            StakeMigrationStatus::Unbonding { .. } => 1,
+            StakeMigrationStatus::Withdrawing { .. } => 2,
+            StakeMigrationStatus::Depositing { .. } => 3,
+            StakeMigrationStatus::Completed { .. } => 4,
+        }
+    }
+}
+
+/// Stake migration job
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+pub struct StakeMigration {
+    /// staking address the stake is moved from
+    pub from_address: StakedStateAddress,
+    /// staking address the stake is moved to
+    pub to_address: StakedStateAddress,
+    /// current step
+    pub status: StakeMigrationStatus,
+    /// transaction of the current step, until it's applied
+    pub pending_transaction: Option<TxAux>,
+}
+
+impl StakeMigration {
+    /// Returns `true` if the stake was moved
+    pub fn is_completed(&self) -> bool {
+        matches!(self.status, StakeMigrationStatus::Completed { .. })
+    }
+}
+
+/// Runs stake migrations of wallets
+#[derive(Clone)]
+pub struct StakeMigrator<S, W, N>
+where
+    S: Storage,
+    W: WalletClient,
+    N: NetworkOpsClient,
+{
+    storage: S,
+    wallet_client: W,
+    network_ops_client: N,
+    network_id: u8,
+}
+
+impl<S, W, N> StakeMigrator<S, W, N>
+where
+    S: Storage,
+    W: WalletClient,
+    N: NetworkOpsClient,
+{
+    /// Creates a new instance of `StakeMigrator`
+    pub fn new(storage: S, wallet_client: W, network_ops_client: N, network_id: u8) -> Self {
+        Self {
+            storage,
+            wallet_client,
+            network_ops_client,
+            network_id,
+        }
+    }
+
+    /// Starts the migration of the stake of `from_address` (which must belong to the wallet)
+    /// to `to_address` and performs its first step
+    pub fn start(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        from_address: StakedStateAddress,
+        to_address: StakedStateAddress,
+    ) -> Result<StakeMigration> {
+        if from_address == to_address {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Stake can only be migrated to a different staking address",
+            ));
+        }
+        if let Some(migration) = self.migration(name, &from_address)? {
+            if !migration.is_completed() {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!(
+                        "Migration of the stake of {} is already in progress",
+                        from_address
+                    ),
+                ));
+            }
+        }
+        match from_address {
+            StakedStateAddress::BasicRedeem(ref redeem_address) => self
+                .wallet_client
+                .find_staking_key(name, enckey, redeem_address)?
+                .chain(|| {
+                    (
+                        ErrorKind::InvalidInput,
+                        "Address not found in current wallet",
+                    )
+                })?,
+        };
+
+        let migration = StakeMigration {
+            from_address,
+            to_address,
+            status: StakeMigrationStatus::Created,
+            pending_transaction: None,
+        };
+        self.save(name, &migration)?;
+        self.advance(name, enckey, &from_address)
+    }
+
+    /// Returns the migration of the stake of `from_address`
+    pub fn migration(
+        &self,
+        name: &str,
+        from_address: &StakedStateAddress,
+    ) -> Result<Option<StakeMigration>> {
+        self.storage
+            .load(&get_migration_keyspace(name), &from_address.to_string())
+    }
+
+    /// Returns all the migrations of the wallet
+    pub fn migrations(&self, name: &str) -> Result<Vec<StakeMigration>> {
+        let keyspace = get_migration_keyspace(name);
+        let mut migrations = Vec::new();
+        for key in self.storage.keys(&keyspace)? {
+            let key = String::from_utf8(key).map_err(|_| {
+                Error::new(ErrorKind::DeserializationError, "Invalid migration key")
+            })?;
+            if let Some(migration) = self.storage.load(&keyspace, &key)? {
+                migrations.push(migration);
+            }
+        }
+        Ok(migrations)
+    }
+
+    /// Forgets the migration (transactions which were already broadcasted are not affected)
+    pub fn remove(&self, name: &str, from_address: &StakedStateAddress) -> Result<()> {
+        self.storage
+            .delete(get_migration_keyspace(name), from_address.to_string())?;
+        Ok(())
+    }
+
+    /// Performs the next step of the migration if it is possible now (the wallet is expected
+    /// to be synced), and returns the updated migration
+    pub fn advance(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        from_address: &StakedStateAddress,
+    ) -> Result<StakeMigration> {
+        let mut migration = self.migration(name, from_address)?.ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("No migration of the stake of {} found", from_address),
+            )
+        })?;
+
+        if let Some(transaction) = &migration.pending_transaction {
+            if !self.is_applied(name, &migration)? {
+                // the process may have stopped before the transaction was broadcasted (or the
+                // node may have dropped it)
+                if let Err(e) = self.wallet_client.broadcast_transaction(transaction) {
+                    log::warn!(
+                        "transaction {} of the stake migration of {} not broadcasted again: {}",
+                        hex::encode(transaction.tx_id()),
+                        from_address,
+                        e
+                    );
+                }
+                return Ok(migration);
+            }
+            migration.pending_transaction = None;
+            self.save(name, &migration)?;
+        }
+
+        let step = match migration.status {
+            StakeMigrationStatus::Created => self.unbond(name, enckey, &migration)?,
+            StakeMigrationStatus::Unbonding {
+                unbond_tx_id,
+                applied_nonce,
+                ..
+            } => self.withdraw(name, enckey, &migration, unbond_tx_id, applied_nonce)?,
+            StakeMigrationStatus::Withdrawing { withdraw_tx_id, .. } => {
+                self.deposit(name, enckey, &migration, withdraw_tx_id)?
+            }
+            StakeMigrationStatus::Depositing { deposit_tx_id, .. } => {
+                Some(Step::new(StakeMigrationStatus::Completed { deposit_tx_id }))
+            }
+            StakeMigrationStatus::Completed { .. } => None,
+        };
+
+        if let Some(step) = step {
+            let previous = migration.clone();
+            migration.status = step.status;
+            migration.pending_transaction = step.transaction.clone();
+            // saved before the broadcast, so the step is not performed again
+            self.save(name, &migration)?;
+            if let Some(transaction) = step.transaction {
+                if let Err(e) = self.wallet_client.broadcast_transaction(&transaction) {
+                    self.save(name, &previous)?;
+                    return Err(e);
+                }
+                if let Some(tx_pending) = step.tx_pending {
+                    self.wallet_client.update_tx_pending_state(
+                        name,
+                        enckey,
+                        transaction.tx_id(),
+                        tx_pending,
+                    )?;
+                }
+            }
+        }
+        Ok(migration)
+    }
+
+    /// Returns `true` if the transaction of the current step of the migration is applied
+    fn is_applied(&self, name: &str, migration: &StakeMigration) -> Result<bool> {
+        match migration.status {
+            StakeMigrationStatus::Unbonding { applied_nonce, .. }
+            | StakeMigrationStatus::Withdrawing { applied_nonce, .. } => {
+                let staked_state = self.network_ops_client.get_staked_state(
+                    name,
+                    &migration.from_address,
+                    true,
+                )?;
+                Ok(staked_state.nonce >= applied_nonce)
+            }
+            StakeMigrationStatus::Depositing { bonded_before, .. } => {
+                Ok(self.bonded(name, &migration.to_address)? > bonded_before)
+            }
+            StakeMigrationStatus::Created | StakeMigrationStatus::Completed { .. } => Ok(true),
+        }
+    }
+
+    fn save(&self, name: &str, migration: &StakeMigration) -> Result<()> {
+        self.storage.save(
+            &get_migration_keyspace(name),
+            &migration.from_address.to_string(),
+            migration,
+        )
+    }
+
+    fn bonded(&self, name: &str, address: &StakedStateAddress) -> Result<Coin> {
+        Ok(self
+            .network_ops_client
+            .get_staking(name, address, true)?
+            .map(|staking| staking.bonded)
+            .unwrap_or_else(Coin::zero))
+    }
+
+    fn unbond(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        migration: &StakeMigration,
+    ) -> Result<Option<Step>> {
+        let staked_state =
+            self.network_ops_client
+                .get_staked_state(name, &migration.from_address, true)?;
+        let fee = self.network_ops_client.calculate_unbond_fee()?;
+
+        if staked_state.bonded > fee {
+            let value = (staked_state.bonded - fee).chain(|| {
+                (
+                    ErrorKind::IllegalInput,
+                    "Unbond fee exceeds the bonded amount",
+                )
+            })?;
+            let transaction = self.network_ops_client.create_unbond_stake_transaction(
+                name,
+                enckey,
+                migration.from_address,
+                value,
+                StakedStateOpAttributes::new(self.network_id),
+                true,
+            )?;
+            Ok(Some(Step::with_transaction(
+                StakeMigrationStatus::Unbonding {
+                    unbond_tx_id: Some(transaction.tx_id()),
+                    applied_nonce: staked_state.nonce + 1,
+                    unbonded_from: None,
+                },
+                transaction,
+                None,
+            )))
+        } else if staked_state.unbonded > Coin::zero() {
+            // already unbonded, only the withdrawal is left
+            Ok(Some(Step::new(StakeMigrationStatus::Unbonding {
+                unbond_tx_id: None,
+                applied_nonce: staked_state.nonce,
+                unbonded_from: Some(staked_state.unbonded_from),
+            })))
+        } else {
+            Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Staking account does not have any stake to migrate (synchronizing your wallet may help)",
+            ))
+        }
+    }
+
+    fn withdraw(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        migration: &StakeMigration,
+        unbond_tx_id: Option<TxId>,
+        applied_nonce: Nonce,
+    ) -> Result<Option<Step>> {
+        let staked_state =
+            self.network_ops_client
+                .get_staked_state(name, &migration.from_address, true)?;
+        if staked_state.nonce < applied_nonce {
+            // unbond transaction not applied yet
+            return Ok(None);
+        }
+
+        let status = self.network_ops_client.get_status()?;
+        let last_block_time = to_timespec(status.sync_info.latest_block_time);
+        if staked_state.unbonded_from > last_block_time {
+            return Ok(Some(Step::new(StakeMigrationStatus::Unbonding {
+                unbond_tx_id,
+                applied_nonce,
+                unbonded_from: Some(staked_state.unbonded_from),
+            })));
+        }
+
+        let to_address = self.wallet_client.new_transfer_address(name, enckey)?;
+        let view_key = self.wallet_client.view_key(name, enckey)?;
+        let attributes = TxAttributes::new_with_access(
+            self.network_id,
+            vec![TxAccessPolicy {
+                view_key: (&view_key).into(),
+                access: TxAccess::AllData,
+            }],
+        );
+        let (transaction, tx_pending) = self
+            .network_ops_client
+            .create_withdraw_all_unbonded_stake_transaction(
+                name,
+                enckey,
+                &migration.from_address,
+                to_address,
+                attributes,
+                true,
+            )?;
+        Ok(Some(Step::with_transaction(
+            StakeMigrationStatus::Withdrawing {
+                withdraw_tx_id: transaction.tx_id(),
+                applied_nonce: staked_state.nonce + 1,
+            },
+            transaction,
+            Some(tx_pending),
+        )))
+    }
+
+    fn deposit(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        migration: &StakeMigration,
+        withdraw_tx_id: TxId,
+    ) -> Result<Option<Step>> {
+        // withdraw transactions of all unbonded stake have a single output
+        let input = TxoPointer::new(withdraw_tx_id, 0);
+        if !self
+            .wallet_client
+            .has_unspent_transactions(name, enckey, &[input.clone()])?
+        {
+            return Ok(None);
+        }
+        let output = self.wallet_client.output(name, enckey, &input)?;
+        let bonded_before = self.bonded(name, &migration.to_address)?;
+
+        let (transaction, tx_pending) = self
+            .network_ops_client
+            .create_deposit_bonded_stake_transaction(
+                name,
+                enckey,
+                vec![(input, output)],
+                migration.to_address,
+                StakedStateOpAttributes::new(self.network_id),
+                true,
+            )?;
+        Ok(Some(Step::with_transaction(
+            StakeMigrationStatus::Depositing {
+                deposit_tx_id: transaction.tx_id(),
+                bonded_before,
+            },
+            transaction,
+            Some(tx_pending),
+        )))
+    }
+}
+
+/// Next status of a migration with the transaction to broadcast for it
+struct Step {
+    status: StakeMigrationStatus,
+    transaction: Option<TxAux>,
+    tx_pending: Option<TransactionPending>,
+}
+
+impl Step {
+    fn new(status: StakeMigrationStatus) -> Self {
+        Step {
+            status,
+            transaction: None,
+            tx_pending: None,
+        }
+    }
+
+    fn with_transaction(
+        status: StakeMigrationStatus,
+        transaction: TxAux,
+        tx_pending: Option<TransactionPending>,
+    ) -> Self {
+        Step {
+            status,
+            transaction: Some(transaction),
+            tx_pending,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::{Arc, Mutex};
+
+    use secstr::SecUtf8;
+
+    use chain_core::init::address::RedeemAddress;
+    use chain_core::init::params::NetworkParameters;
+    use chain_core::state::account::{
+        CouncilNodeMeta, DataAnchorProof, StakedState, StakedStateOpWitness, WithdrawUnbondedTx,
+    };
+    use chain_core::state::tendermint::BlockHeight;
+    use chain_core::state::ChainState;
+    use chain_core::tx::data::address::ExtendedAddr;
+    use chain_core::tx::data::output::TxOut;
+    use chain_core::tx::{TxEnclaveAux, TxObfuscated};
+    use client_common::storage::MemoryStorage;
+    use client_common::tendermint::mock;
+    use client_common::tendermint::types::*;
+    use client_common::tendermint::Client;
+    use client_common::SignedTransaction;
+    use client_core::hd_wallet::HardwareKind;
+    use client_core::service::{HwKeyService, WalletStateMemento, WalletStateService};
+    use client_core::transaction_builder::UnauthorizedWalletTransactionBuilder;
+    use client_core::types::WalletKind;
+    use client_core::wallet::DefaultWalletClient;
+
+    /// Staking state of the chain and transactions received by the node
+    #[derive(Default)]
+    struct MockChain {
+        from_state: Option<StakedState>,
+        to_bonded: Coin,
+        /// number of transactions built
+        built: u8,
+        broadcasted: Vec<TxId>,
+        reject_broadcast: bool,
+    }
+
+    #[derive(Clone)]
+    struct MockClient {
+        chain: Arc<Mutex<MockChain>>,
+    }
+
+    impl Client for MockClient {
+        fn genesis(&self) -> Result<Genesis> {
+            unreachable!()
+        }
+
+        fn status(&self) -> Result<StatusResponse> {
+            unreachable!()
+        }
+
+        fn block(&self, _: u64) -> Result<Block> {
+            unreachable!()
+        }
+
+        fn block_batch<'a, T: Iterator<Item = &'a u64>>(&self, _heights: T) -> Result<Vec<Block>> {
+            unreachable!()
+        }
+
+        fn block_results(&self, _height: u64) -> Result<BlockResultsResponse> {
+            unreachable!()
+        }
+
+        fn block_results_batch<'a, T: Iterator<Item = &'a u64>>(
+            &self,
+            _heights: T,
+        ) -> Result<Vec<BlockResultsResponse>> {
+            unreachable!()
+        }
+
+        fn broadcast_transaction(&self, transaction: &[u8]) -> Result<BroadcastTxResponse> {
+            let mut chain = self.chain.lock().unwrap();
+            if chain.reject_broadcast {
+                return Err(Error::new(
+                    ErrorKind::TendermintRpcError,
+                    "Transaction rejected",
+                ));
+            }
+            let tx_aux = TxAux::decode(&mut &transaction[..]).unwrap();
+            chain.broadcasted.push(tx_aux.tx_id());
+            Ok(mock::broadcast_tx_response())
+        }
+
+        fn query(
+            &self,
+            _path: &str,
+            _data: &[u8],
+            _height: Option<Height>,
+            _prove: bool,
+        ) -> Result<AbciQuery> {
+            unreachable!()
+        }
+
+        fn unconfirmed_txs(&self, _limit: u64) -> Result<Vec<Vec<u8>>> {
+            unreachable!()
+        }
+
+        fn query_state_batch<T: Iterator<Item = u64>>(
+            &self,
+            _heights: T,
+        ) -> Result<Vec<ChainState>> {
+            unreachable!()
+        }
+    }
+
+    struct MockNetworkOpsClient {
+        chain: Arc<Mutex<MockChain>>,
+        to_address: StakedStateAddress,
+    }
+
+    impl MockNetworkOpsClient {
+        /// Transaction with the next transaction id (`[1; 32]`, `[2; 32]`...)
+        fn build_transaction(&self) -> TxAux {
+            let mut chain = self.chain.lock().unwrap();
+            chain.built += 1;
+            TxAux::EnclaveTx(TxEnclaveAux::TransferTx {
+                inputs: Vec::new(),
+                no_of_outputs: 1,
+                payload: TxObfuscated {
+                    txid: [chain.built; 32],
+                    key_from: BlockHeight::genesis(),
+                    init_vector: [0; 12],
+                    txpayload: Vec::new(),
+                },
+            })
+        }
+
+        fn tx_pending() -> TransactionPending {
+            TransactionPending {
+                used_inputs: Vec::new(),
+                block_height: 0,
+                return_amount: Coin::zero(),
+            }
+        }
+    }
+
+    impl NetworkOpsClient for MockNetworkOpsClient {
+        fn calculate_deposit_fee(&self) -> Result<Coin> {
+            Ok(Coin::unit())
+        }
+
+        fn calculate_unbond_fee(&self) -> Result<Coin> {
+            Ok(Coin::unit())
+        }
+
+        fn create_deposit_bonded_stake_transaction(
+            &self,
+            _name: &str,
+            _enckey: &SecKey,
+            _transaction: Vec<(TxoPointer, TxOut)>,
+            _to_address: StakedStateAddress,
+            _attributes: StakedStateOpAttributes,
+            _verify_staking: bool,
+        ) -> Result<(TxAux, TransactionPending)> {
+            Ok((self.build_transaction(), Self::tx_pending()))
+        }
+
+        fn create_unbond_stake_transaction(
+            &self,
+            _name: &str,
+            _enckey: &SecKey,
+            _address: StakedStateAddress,
+            _value: Coin,
+            _attributes: StakedStateOpAttributes,
+            _verify_staking: bool,
+        ) -> Result<TxAux> {
+            Ok(self.build_transaction())
+        }
+
+        fn create_withdraw_unbonded_stake_transaction(
+            &self,
+            _name: &str,
+            _enckey: &SecKey,
+            _from_address: &StakedStateAddress,
+            _outputs: Vec<TxOut>,
+            _attributes: TxAttributes,
+            _verify_staking: bool,
+        ) -> Result<(TxAux, TransactionPending)> {
+            unreachable!()
+        }
+
+        fn create_withdraw_all_unbonded_stake_transaction(
+            &self,
+            _name: &str,
+            _enckey: &SecKey,
+            _from_address: &StakedStateAddress,
+            _to_address: ExtendedAddr,
+            _attributes: TxAttributes,
+            _verify_staking: bool,
+        ) -> Result<(TxAux, TransactionPending)> {
+            Ok((self.build_transaction(), Self::tx_pending()))
+        }
+
+        fn create_withdraw_all_unbonded_stake_template(
+            &self,
+            _name: &str,
+            _enckey: &SecKey,
+            _from_address: &StakedStateAddress,
+            _to_address: ExtendedAddr,
+            _attributes: TxAttributes,
+            _verify_staking: bool,
+        ) -> Result<(WithdrawUnbondedTx, StakedStateOpWitness)> {
+            unreachable!()
+        }
+
+        fn encrypt_transaction(&self, _transaction: SignedTransaction) -> Result<TxAux> {
+            unreachable!()
+        }
+
+        fn create_unjail_transaction(
+            &self,
+            _name: &str,
+            _enckey: &SecKey,
+            _address: StakedStateAddress,
+            _attributes: StakedStateOpAttributes,
+            _verify_staking: bool,
+        ) -> Result<TxAux> {
+            unreachable!()
+        }
+
+        fn create_node_join_transaction(
+            &self,
+            _name: &str,
+            _enckey: &SecKey,
+            _staking_account_address: StakedStateAddress,
+            _attributes: StakedStateOpAttributes,
+            _node_metadata: CouncilNodeMeta,
+            _verify_staking: bool,
+        ) -> Result<TxAux> {
+            unreachable!()
+        }
+
+        fn create_data_anchor_transaction(
+            &self,
+            _name: &str,
+            _enckey: &SecKey,
+            _address: StakedStateAddress,
+            _commitment: chain_core::common::H256,
+            _attributes: StakedStateOpAttributes,
+            _verify_staking: bool,
+        ) -> Result<TxAux> {
+            unreachable!()
+        }
+
+        fn get_data_anchor_proofs(
+            &self,
+            _commitment: &chain_core::common::H256,
+        ) -> Result<Vec<DataAnchorProof>> {
+            unreachable!()
+        }
+
+        fn get_staking(
+            &self,
+            _name: &str,
+            address: &StakedStateAddress,
+            _verify: bool,
+        ) -> Result<Option<StakedState>> {
+            let chain = self.chain.lock().unwrap();
+            if *address == self.to_address {
+                Ok(Some(StakedState::new(
+                    0,
+                    chain.to_bonded,
+                    Coin::zero(),
+                    0,
+                    self.to_address,
+                    None,
+                )))
+            } else {
+                Ok(chain.from_state.clone())
+            }
+        }
+
+        fn get_network_params(&self, _height: u64) -> Result<NetworkParameters> {
+            unreachable!()
+        }
+
+        fn get_genesis(&self) -> Result<Genesis> {
+            unreachable!()
+        }
+
+        fn get_status(&self) -> Result<StatusResponse> {
+            Ok(mock::status_response())
+        }
+    }
+
+    type TestWalletClient =
+        DefaultWalletClient<MemoryStorage, MockClient, UnauthorizedWalletTransactionBuilder>;
+
+    fn make_migrator(
+        storage: &MemoryStorage,
+        chain: &Arc<Mutex<MockChain>>,
+        to_address: StakedStateAddress,
+    ) -> StakeMigrator<MemoryStorage, TestWalletClient, MockNetworkOpsClient> {
+        let wallet_client = DefaultWalletClient::new(
+            storage.clone(),
+            MockClient {
+                chain: chain.clone(),
+            },
+            UnauthorizedWalletTransactionBuilder,
+            None,
+            HwKeyService::default(),
+        );
+        let network_ops_client = MockNetworkOpsClient {
+            chain: chain.clone(),
+            to_address,
+        };
+        StakeMigrator::new(storage.clone(), wallet_client, network_ops_client, 0)
+    }
+
+    #[test]
+    fn check_migration_storage_roundtrip() {
+        let storage = MemoryStorage::default();
+        let migration = StakeMigration {
+            from_address: StakedStateAddress::BasicRedeem(RedeemAddress::from([1u8; 20])),
+            to_address: StakedStateAddress::BasicRedeem(RedeemAddress::from([2u8; 20])),
+            status: StakeMigrationStatus::Unbonding {
+                unbond_tx_id: Some([3u8; 32]),
+                applied_nonce: 1,
+                unbonded_from: None,
+            },
+            pending_transaction: None,
+        };
+        let keyspace = get_migration_keyspace("name");
+        storage
+            .save(&keyspace, &migration.from_address.to_string(), &migration)
+            .unwrap();
+
+        let loaded: StakeMigration = storage
+            .load(&keyspace, &migration.from_address.to_string())
+            .unwrap()
+            .unwrap();
+        assert_eq!(migration, loaded);
+        assert!(!loaded.is_completed());
+        assert!(storage
+            .keys(get_migration_keyspace("other"))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn check_migration_steps() {
+        let name = "name";
+        let storage = MemoryStorage::default();
+        let chain = Arc::new(Mutex::new(MockChain::default()));
+        let to_address = StakedStateAddress::BasicRedeem(RedeemAddress::from([2u8; 20]));
+        let migrator = make_migrator(&storage, &chain, to_address);
+
+        let (enckey, _) = migrator
+            .wallet_client
+            .new_wallet(
+                name,
+                &SecUtf8::from("passphrase"),
+                WalletKind::Basic,
+                HardwareKind::LocalOnly,
+                None,
+            )
+            .unwrap();
+        let from_address = migrator
+            .wallet_client
+            .new_staking_address(name, &enckey)
+            .unwrap();
+        chain.lock().unwrap().from_state = Some(StakedState::new(
+            0,
+            Coin::new(1000).unwrap(),
+            Coin::zero(),
+            0,
+            from_address,
+            None,
+        ));
+
+        // unbond
+        let migration = migrator
+            .start(name, &enckey, from_address, to_address)
+            .unwrap();
+        assert_eq!(
+            StakeMigrationStatus::Unbonding {
+                unbond_tx_id: Some([1; 32]),
+                applied_nonce: 1,
+                unbonded_from: None,
+            },
+            migration.status
+        );
+        assert_eq!(
+            Some([1; 32]),
+            migration.pending_transaction.map(|tx| tx.tx_id())
+        );
+        assert_eq!(vec![[1; 32]], chain.lock().unwrap().broadcasted);
+
+        // not applied yet: the same transaction is broadcasted again
+        let migration = migrator.advance(name, &enckey, &from_address).unwrap();
+        assert_eq!(1, migration.status.steps_done());
+        assert_eq!(1, chain.lock().unwrap().built);
+        assert_eq!(vec![[1; 32], [1; 32]], chain.lock().unwrap().broadcasted);
+
+        // unbond applied, the withdraw transaction is rejected: nothing changes
+        {
+            let mut chain = chain.lock().unwrap();
+            chain.from_state = Some(StakedState::new(
+                1,
+                Coin::zero(),
+                Coin::new(999).unwrap(),
+                0,
+                from_address,
+                None,
+            ));
+            chain.reject_broadcast = true;
+        }
+        assert!(migrator.advance(name, &enckey, &from_address).is_err());
+        let migration = migrator.migration(name, &from_address).unwrap().unwrap();
+        assert_eq!(1, migration.status.steps_done());
+        assert!(migration.pending_transaction.is_none());
+
+        // withdraw
+        chain.lock().unwrap().reject_broadcast = false;
+        let migration = migrator.advance(name, &enckey, &from_address).unwrap();
+        assert_eq!(
+            StakeMigrationStatus::Withdrawing {
+                withdraw_tx_id: [3; 32],
+                applied_nonce: 2,
+            },
+            migration.status
+        );
+        assert_eq!([3; 32], *chain.lock().unwrap().broadcasted.last().unwrap());
+
+        // the node dropped the withdraw transaction and the process restarted: the saved
+        // transaction is broadcasted again instead of building a new one
+        chain.lock().unwrap().broadcasted.clear();
+        let migrator = make_migrator(&storage, &chain, to_address);
+        let migration = migrator.advance(name, &enckey, &from_address).unwrap();
+        assert_eq!(2, migration.status.steps_done());
+        assert_eq!(3, chain.lock().unwrap().built);
+        assert_eq!(vec![[3; 32]], chain.lock().unwrap().broadcasted);
+
+        // withdraw applied, but the output isn't synced yet
+        chain.lock().unwrap().from_state = Some(StakedState::new(
+            2,
+            Coin::zero(),
+            Coin::zero(),
+            0,
+            from_address,
+            None,
+        ));
+        let migration = migrator.advance(name, &enckey, &from_address).unwrap();
+        assert_eq!(2, migration.status.steps_done());
+        assert!(migration.pending_transaction.is_none());
+
+        // deposit once the output is synced
+        let mut memento = WalletStateMemento::default();
+        memento.add_unspent_transaction(
+            TxoPointer::new([3; 32], 0),
+            TxOut::new(ExtendedAddr::OrTree([0; 32]), Coin::new(998).unwrap()),
+        );
+        WalletStateService::new(storage.clone())
+            .apply_memento(name, &enckey, &memento)
+            .unwrap();
+        let migration = migrator.advance(name, &enckey, &from_address).unwrap();
+        assert_eq!(
+            StakeMigrationStatus::Depositing {
+                deposit_tx_id: [4; 32],
+                bonded_before: Coin::zero(),
+            },
+            migration.status
+        );
+        let migration = migrator.advance(name, &enckey, &from_address).unwrap();
+        assert!(!migration.is_completed());
+        assert_eq!(4, chain.lock().unwrap().built);
+
+        // deposit applied
+        chain.lock().unwrap().to_bonded = Coin::new(997).unwrap();
+        let migration = migrator.advance(name, &enckey, &from_address).unwrap();
+        assert_eq!(
+            StakeMigrationStatus::Completed {
+                deposit_tx_id: [4; 32]
+            },
+            migration.status
+        );
+        assert!(migration.pending_transaction.is_none());
+        assert_eq!(4, chain.lock().unwrap().built);
+    }
+}
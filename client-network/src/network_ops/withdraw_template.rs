@@ -0,0 +1,318 @@
+//! Withdrawal of unbonded stake signed in advance:
+//!
+//! once the unbond transaction is applied, the transaction withdrawing all the unbonded stake
+//! (locked to the end of the unbonding period) can be signed right away and stored encrypted
+//! in the wallet storage. `WithdrawTemplates::broadcast_due` (e.g. called after every wallet
+//! sync) then broadcasts it as soon as the unbonding period is over, so the staking key
+//! only needs to be online once.
+//!
+//! The signed transaction only stays valid while the nonce of the staking account doesn't change:
+//! if any other transaction is applied to the account before, the template is invalidated.
+use parity_scale_codec::{Decode, Encode};
+
+use super::default_network_ops_client::to_timespec;
+use crate::NetworkOpsClient;
+use chain_core::common::Timespec;
+use chain_core::init::coin::Coin;
+use chain_core::state::account::{StakedStateAddress, StakedStateOpWitness, WithdrawUnbondedTx};
+use chain_core::tx::data::address::ExtendedAddr;
+use chain_core::tx::data::attribute::TxAttributes;
+use chain_core::tx::data::TxId;
+use client_common::{
+    Error, ErrorKind, Result, ResultExt, SecKey, SecureStorage, SignedTransaction, Storage,
+};
+use client_core::types::TransactionPending;
+use client_core::WalletClient;
+
+/// key space of withdraw templates (one key space per wallet, keyed by the staking address)
+const KEYSPACE: &str = "core_withdraw_template";
+
+fn get_template_keyspace(name: &str) -> String {
+    format!("{}_{}", KEYSPACE, name)
+}
+
+/// Status of a withdraw template
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+pub enum WithdrawTemplateStatus {
+    /// waiting for the end of the unbonding period
+    Pending,
+    /// the transaction was broadcasted
+    Broadcasted,
+    /// the nonce of the staking account changed, so the transaction can't be valid anymore
+    Invalidated,
+}
+
+/// Withdraw transaction of all the unbonded stake of an account, signed in advance
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+pub struct WithdrawTemplate {
+    /// staking address the unbonded stake is withdrawn from
+    pub from_address: StakedStateAddress,
+    /// signed transaction
+    pub transaction: WithdrawUnbondedTx,
+    /// signature of the staking key
+    pub witness: StakedStateOpWitness,
+    /// time the transaction becomes valid (the end of the unbonding period)
+    pub unbonded_from: Timespec,
+    /// current status
+    pub status: WithdrawTemplateStatus,
+}
+
+impl WithdrawTemplate {
+    /// Returns the transaction ID
+    pub fn tx_id(&self) -> TxId {
+        self.signed_transaction().tx_id()
+    }
+
+    /// Returns the withdrawn amount (the unbonded stake minus the fee)
+    pub fn amount(&self) -> Result<Coin> {
+        self.transaction
+            .get_output_total()
+            .chain(|| (ErrorKind::IllegalInput, "Invalid withdraw output values"))
+    }
+
+    fn signed_transaction(&self) -> SignedTransaction {
+        SignedTransaction::WithdrawUnbondedStakeTransaction(
+            self.transaction.clone(),
+            self.witness.clone(),
+        )
+    }
+}
+
+/// Manages the withdraw templates of wallets
+#[derive(Clone)]
+pub struct WithdrawTemplates<S, W, N>
+where
+    S: Storage,
+    W: WalletClient,
+    N: NetworkOpsClient,
+{
+    storage: S,
+    wallet_client: W,
+    network_ops_client: N,
+}
+
+impl<S, W, N> WithdrawTemplates<S, W, N>
+where
+    S: Storage,
+    W: WalletClient,
+    N: NetworkOpsClient,
+{
+    /// Creates a new instance of `WithdrawTemplates`
+    pub fn new(storage: S, wallet_client: W, network_ops_client: N) -> Self {
+        Self {
+            storage,
+            wallet_client,
+            network_ops_client,
+        }
+    }
+
+    /// Signs and stores the transaction withdrawing all the unbonded stake of `from_address`
+    /// (which must belong to the wallet) to `to_address` once the unbonding period is over.
+    /// The unbond transaction must already be applied (the wallet is expected to be synced).
+    pub fn create(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        from_address: StakedStateAddress,
+        to_address: ExtendedAddr,
+        attributes: TxAttributes,
+    ) -> Result<WithdrawTemplate> {
+        if let Some(template) = self.template(name, enckey, &from_address)? {
+            if template.status == WithdrawTemplateStatus::Pending {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!(
+                        "Withdraw template of {} is already pending (remove it first)",
+                        from_address
+                    ),
+                ));
+            }
+        }
+
+        let (transaction, witness) = self
+            .network_ops_client
+            .create_withdraw_all_unbonded_stake_template(
+                name,
+                enckey,
+                &from_address,
+                to_address,
+                attributes,
+                true,
+            )?;
+        let unbonded_from = transaction
+            .outputs
+            .iter()
+            .find_map(|output| output.valid_from)
+            .chain(|| {
+                (
+                    ErrorKind::IllegalInput,
+                    "Withdraw outputs are not locked to the end of the unbonding period",
+                )
+            })?;
+
+        let template = WithdrawTemplate {
+            from_address,
+            transaction,
+            witness,
+            unbonded_from,
+            status: WithdrawTemplateStatus::Pending,
+        };
+        self.save(name, enckey, &template)?;
+        Ok(template)
+    }
+
+    /// Returns the withdraw template of `from_address`
+    pub fn template(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        from_address: &StakedStateAddress,
+    ) -> Result<Option<WithdrawTemplate>> {
+        self.storage.load_secure(
+            &get_template_keyspace(name),
+            &from_address.to_string(),
+            enckey,
+        )
+    }
+
+    /// Returns all the withdraw templates of the wallet
+    pub fn templates(&self, name: &str, enckey: &SecKey) -> Result<Vec<WithdrawTemplate>> {
+        let keyspace = get_template_keyspace(name);
+        let mut templates = Vec::new();
+        for key in self.storage.keys(&keyspace)? {
+            let key = String::from_utf8(key).map_err(|_| {
+                Error::new(
+                    ErrorKind::DeserializationError,
+                    "Invalid withdraw template key",
+                )
+            })?;
+            if let Some(template) = self.storage.load_secure(&keyspace, &key, enckey)? {
+                templates.push(template);
+            }
+        }
+        Ok(templates)
+    }
+
+    /// Forgets the withdraw template (it can't be revoked if it was already broadcasted)
+    pub fn remove(&self, name: &str, from_address: &StakedStateAddress) -> Result<()> {
+        self.storage
+            .delete(get_template_keyspace(name), from_address.to_string())?;
+        Ok(())
+    }
+
+    /// Broadcasts the pending withdraw templates whose unbonding period is over (or invalidates
+    /// them if the staking account changed), and returns the updated templates
+    pub fn broadcast_due(&self, name: &str, enckey: &SecKey) -> Result<Vec<WithdrawTemplate>> {
+        let mut updated = Vec::new();
+        let mut last_block_time = None;
+        for mut template in self.templates(name, enckey)? {
+            if template.status != WithdrawTemplateStatus::Pending {
+                continue;
+            }
+            let block_time = match last_block_time {
+                Some(time) => time,
+                None => {
+                    let status = self.network_ops_client.get_status()?;
+                    let time = to_timespec(status.sync_info.latest_block_time);
+                    last_block_time = Some(time);
+                    time
+                }
+            };
+            if template.unbonded_from > block_time {
+                continue;
+            }
+
+            let staked_state =
+                self.network_ops_client
+                    .get_staked_state(name, &template.from_address, true)?;
+            if staked_state.nonce != template.transaction.nonce {
+                template.status = WithdrawTemplateStatus::Invalidated;
+            } else {
+                let transaction = self
+                    .network_ops_client
+                    .encrypt_transaction(template.signed_transaction())?;
+                self.wallet_client.broadcast_transaction(&transaction)?;
+                let tx_pending = TransactionPending {
+                    block_height: self.wallet_client.get_current_block_height()?,
+                    used_inputs: vec![],
+                    return_amount: template.amount()?,
+                };
+                self.wallet_client.update_tx_pending_state(
+                    name,
+                    enckey,
+                    transaction.tx_id(),
+                    tx_pending,
+                )?;
+                template.status = WithdrawTemplateStatus::Broadcasted;
+            }
+            self.save(name, enckey, &template)?;
+            updated.push(template);
+        }
+        Ok(updated)
+    }
+
+    fn save(&self, name: &str, enckey: &SecKey, template: &WithdrawTemplate) -> Result<()> {
+        self.storage.save_secure(
+            &get_template_keyspace(name),
+            &template.from_address.to_string(),
+            enckey,
+            template,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chain_core::init::address::RedeemAddress;
+    use chain_core::tx::data::output::TxOut;
+    use client_common::seckey::{enckey_from_bytes, generate_enckey};
+    use client_common::storage::MemoryStorage;
+    use secp256k1::recovery::{RecoverableSignature, RecoveryId};
+
+    #[test]
+    fn check_template_storage_encrypted() {
+        let storage = MemoryStorage::default();
+        let enckey = enckey_from_bytes(&[1u8; 32]).unwrap();
+        let from_address = StakedStateAddress::BasicRedeem(RedeemAddress::from([1u8; 20]));
+        let to_address = ExtendedAddr::OrTree([2u8; 32]);
+        let template = WithdrawTemplate {
+            from_address,
+            transaction: WithdrawUnbondedTx::new(
+                1,
+                vec![TxOut::new_with_timelock(
+                    to_address,
+                    Coin::new(100).unwrap(),
+                    1000,
+                )],
+                TxAttributes::new(0),
+            ),
+            witness: StakedStateOpWitness::new(
+                RecoverableSignature::from_compact(&[0u8; 64], RecoveryId::from_i32(0).unwrap())
+                    .unwrap(),
+            ),
+            unbonded_from: 1000,
+            status: WithdrawTemplateStatus::Pending,
+        };
+        let keyspace = get_template_keyspace("name");
+        let key = from_address.to_string();
+        storage
+            .save_secure(&keyspace, &key, &enckey, &template)
+            .unwrap();
+
+        assert_ne!(
+            Some(template.encode()),
+            storage.get(&keyspace, &key).unwrap()
+        );
+        let loaded: WithdrawTemplate = storage
+            .load_secure(&keyspace, &key, &enckey)
+            .unwrap()
+            .unwrap();
+        assert_eq!(template, loaded);
+        assert_eq!(Coin::new(100).unwrap(), loaded.amount().unwrap());
+        assert!(storage
+            .load_secure::<WithdrawTemplate>(&keyspace, &key, &generate_enckey())
+            .is_err());
+    }
+}
@@ -1,5 +1,6 @@
 mod program;
 mod server;
+mod websocket_server;
 
 fn main() {
     crate::program::run_cli();
@@ -31,6 +31,13 @@ pub struct Options {
     )]
     pub port: u16,
 
+    #[structopt(
+        name = "ws-port",
+        long,
+        help = "JSON-RPC WebSocket server port, which also serves the subscriptions (e.g. `wallet_subscribeBalance`). If not set, only the HTTP server is started"
+    )]
+    pub ws_port: Option<u16>,
+
     #[structopt(name = "chain-id", short, long, help = "Full chain ID")]
     pub chain_id: String,
 
@@ -102,6 +109,12 @@ pub struct Options {
         help = "Disable address recovery when syncing wallet, which is not necessary, when addresses already exist"
     )]
     pub disable_address_recovery: bool,
+    #[structopt(
+        name = "enable-block-filters",
+        long,
+        help = "Only download the blocks whose filters may match the wallet when syncing wallet (less bandwidth)"
+    )]
+    pub enable_block_filters: bool,
     #[structopt(
         name = "batch-size",
         short,
@@ -117,6 +130,26 @@ pub struct Options {
         help = "Number of block height to rollback the utxos in the pending transactions"
     )]
     pub block_height_ensure: u64,
+    #[structopt(
+        name = "rpc-credentials",
+        long,
+        help = "JSON file with the permission scopes (read, build, sign, admin) of each credential token, e.g. {\"<token>\": [\"read\"]}; the token is passed in the `Authorization: Bearer <token>` header. If not set, all methods are allowed without credentials"
+    )]
+    pub rpc_credentials: Option<String>,
+    #[structopt(
+        name = "tenants",
+        long,
+        conflicts_with = "rpc-credentials",
+        help = "JSON file enabling the multi-tenant hosted wallet mode, with the storage master key (hex) and the credential tokens of each tenant, e.g. {\"<tenant>\": {\"master_key\": \"<hex>\", \"credentials\": {\"<token>\": [\"admin\"]}}}; each tenant's wallets are only accessible with its own tokens"
+    )]
+    pub tenants: Option<String>,
+    #[structopt(
+        name = "proxy-config",
+        long,
+        conflicts_with_all = &["rpc-credentials", "tenants"],
+        help = "JSON file enabling the public read-only proxy mode (no wallets): only `status`, `block`, `block_results` and `abci_query` (with allowed query paths) are forwarded to tendermint, with a request size limit, per-client rate limit and response cache, e.g. {\"requests_per_minute\": 120, \"cache_ttl_seconds\": 2}"
+    )]
+    pub proxy_config: Option<String>,
 }
 
 #[allow(dead_code)]
@@ -1,19 +1,36 @@
 use crate::program::Options;
+use crate::websocket_server;
 
-use jsonrpc_http_server::{AccessControlAllowOrigin, DomainsValidation, ServerBuilder};
+use jsonrpc_core::{MetaIoHandler, Metadata, Middleware};
+use jsonrpc_http_server::hyper::header::{HeaderValue, AUTHORIZATION};
+use jsonrpc_http_server::hyper::{Body, Method, Request, StatusCode};
+use jsonrpc_http_server::{
+    AccessControlAllowOrigin, DomainsValidation, MetaExtractor, RequestMiddlewareAction, Response,
+    ServerBuilder,
+};
 use std::net::SocketAddr;
 
 use chain_core::init::network::{get_network, get_network_id, init_chain_id};
+use client_common::correlation::{parse_correlation_id, CORRELATION_ID_HEADER};
+use client_common::tendermint::WebsocketRpcClient;
 use client_common::Result;
 use client_common::{Error, ErrorKind};
 use client_core::wallet::syncer::SyncerOptions;
-use client_rpc_core::RpcHandler;
+use client_rpc_core::permission::{PermissionPolicy, RpcMeta};
+use client_rpc_core::proxy::{ProxyConfig, ProxyMeta};
+use client_rpc_core::rpc::health_rpc::{HealthChecker, HealthReport, HealthStatus};
+use client_rpc_core::tenant::TenantPolicy;
+use client_rpc_core::{ProxyHandler, RpcHandler, TenantRpcHandler};
 pub(crate) struct Server {
     host: String,
     port: u16,
+    ws_port: Option<u16>,
     network_id: u8,
     storage_dir: String,
     websocket_url: String,
+    rpc_credentials: Option<String>,
+    tenants: Option<String>,
+    proxy_config: Option<String>,
 
     sync_options: SyncerOptions,
 }
@@ -26,7 +43,7 @@ impl Server {
         println!("Network type {:?} id {:02X}", get_network(), network_id);
         let mut light_client_peers: String = "".to_string();
 
-        if !options.disable_light_client {
+        if !options.disable_light_client && options.proxy_config.is_none() {
             if let Some(value) = options.light_client_peers {
                 light_client_peers = value;
             } else {
@@ -40,13 +57,18 @@ impl Server {
         Ok(Server {
             host: options.host,
             port: options.port,
+            ws_port: options.ws_port,
             network_id,
             storage_dir: options.storage_dir,
             websocket_url: options.websocket_url,
+            rpc_credentials: options.rpc_credentials,
+            tenants: options.tenants,
+            proxy_config: options.proxy_config,
             sync_options: SyncerOptions {
                 enable_fast_forward: options.enable_fast_forward,
                 disable_light_client: options.disable_light_client,
                 enable_address_recovery: !options.disable_address_recovery,
+                enable_block_filters: options.enable_block_filters,
                 batch_size: options.batch_size,
                 block_height_ensure: options.block_height_ensure,
                 light_client_peers,
@@ -61,22 +83,89 @@ impl Server {
         if cfg!(feature = "mock-enclave") {
             log::warn!("{}", "WARNING: Using mock (non-enclave) infrastructure");
         }
-        RpcHandler::new(
-            &self.storage_dir,
-            &self.websocket_url,
-            self.network_id,
-            self.sync_options.clone(),
-            None,
-        )
+        match &self.rpc_credentials {
+            Some(path) => RpcHandler::new_with_permissions(
+                &self.storage_dir,
+                &self.websocket_url,
+                self.network_id,
+                self.sync_options.clone(),
+                PermissionPolicy::load(path)?,
+            ),
+            None => RpcHandler::new(
+                &self.storage_dir,
+                &self.websocket_url,
+                self.network_id,
+                self.sync_options.clone(),
+                None,
+            ),
+        }
     }
 
     pub(crate) fn start(&mut self) -> Result<()> {
-        let handler = self.create_rpc_handler()?;
-        let server = ServerBuilder::new(handler.io)
+        if let Some(path) = &self.proxy_config {
+            let config = ProxyConfig::load(path)?;
+            let max_request_size = config.max_request_size;
+            let handler = ProxyHandler::new(&self.websocket_url, config)?;
+            return self.serve(handler.io, extract_client, Some(max_request_size), None);
+        }
+        match &self.tenants {
+            Some(path) => {
+                let handler = TenantRpcHandler::new(
+                    &self.storage_dir,
+                    &self.websocket_url,
+                    self.network_id,
+                    self.sync_options.clone(),
+                    TenantPolicy::load(path)?,
+                )?;
+                self.serve_websocket(handler.io.clone())?;
+                self.serve(handler.io, extract_credentials, None, Some(handler.health))
+            }
+            None => {
+                let handler = self.create_rpc_handler()?;
+                self.serve_websocket(handler.io.clone())?;
+                self.serve(handler.io, extract_credentials, None, Some(handler.health))
+            }
+        }
+    }
+
+    /// Serves the requests (and subscriptions) over WebSocket too if `ws_port` is set
+    fn serve_websocket<M: Middleware<RpcMeta>>(&self, io: MetaIoHandler<RpcMeta, M>) -> Result<()> {
+        match self.ws_port {
+            Some(port) => {
+                websocket_server::spawn(io, SocketAddr::new(self.host.parse().unwrap(), port))
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Serves the JSON-RPC requests, and the `GET /health` and `GET /ready` requests
+    /// of the orchestration systems if the health checker is given
+    fn serve<T: Metadata, M: Middleware<T>, E: MetaExtractor<T>>(
+        &self,
+        io: MetaIoHandler<T, M>,
+        extractor: E,
+        max_request_size: Option<usize>,
+        health: Option<HealthChecker<WebsocketRpcClient>>,
+    ) -> Result<()> {
+        let mut builder = ServerBuilder::with_meta_extractor(io, extractor)
             // TODO: Either make CORS configurable or make it more strict
             .cors(DomainsValidation::AllowOnly(vec![
                 AccessControlAllowOrigin::Any,
-            ]))
+            ]));
+        if let Some(size) = max_request_size {
+            builder = builder.max_request_body_size(size);
+        }
+        if let Some(health) = health {
+            builder = builder.request_middleware(move |request: Request<Body>| {
+                let liveness = match (request.method(), request.uri().path()) {
+                    (&Method::GET, "/health") => true,
+                    (&Method::GET, "/ready") => false,
+                    _ => return RequestMiddlewareAction::from(request),
+                };
+                health_response(&health.report(liveness)).into()
+            });
+        }
+        let server = builder
             .start_http(&SocketAddr::new(self.host.parse().unwrap(), self.port))
             .expect("Unable to start JSON-RPC server");
 
@@ -86,3 +175,62 @@ impl Server {
         Ok(())
     }
 }
+
+/// The report with the `503 Service Unavailable` status code if a check failed
+fn health_response(report: &HealthReport) -> Response {
+    let code = match report.status {
+        HealthStatus::Fail => StatusCode::SERVICE_UNAVAILABLE,
+        HealthStatus::Pass | HealthStatus::Warn => StatusCode::OK,
+    };
+    Response {
+        code,
+        content_type: HeaderValue::from_static("application/json; charset=utf-8"),
+        content: serde_json::to_string(report).expect("serialize health report"),
+    }
+}
+
+/// Reads the credential token from the `Authorization: Bearer <token>` header
+/// and the correlation ID from the `X-Correlation-Id` header (invalid IDs are replaced)
+fn extract_credentials(request: &Request<Body>) -> RpcMeta {
+    meta_from_headers(|name| {
+        request
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+    })
+}
+
+/// Reads the request metadata from the headers (of HTTP requests or WebSocket handshakes)
+pub(crate) fn meta_from_headers<'a, F>(header: F) -> RpcMeta
+where
+    F: Fn(&str) -> Option<&'a str>,
+{
+    let correlation_id = header(CORRELATION_ID_HEADER).and_then(parse_correlation_id);
+    let token = header(AUTHORIZATION.as_str())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|token| token.trim().to_owned());
+    RpcMeta {
+        token,
+        correlation_id,
+        session: None,
+    }
+}
+
+/// Identifies the proxy client by the `X-Real-IP` or the first `X-Forwarded-For` address
+/// set by the reverse proxy / load balancer in front of the server
+/// (without them, all requests share one rate limit)
+fn extract_client(request: &Request<Body>) -> ProxyMeta {
+    let headers = request.headers();
+    let client = headers
+        .get("x-real-ip")
+        .and_then(|value| value.to_str().ok())
+        .or_else(|| {
+            headers
+                .get("x-forwarded-for")
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.split(',').next())
+        })
+        .map(|client| client.trim().to_owned())
+        .filter(|client| !client.is_empty());
+    ProxyMeta { client }
+}
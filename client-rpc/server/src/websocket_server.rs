@@ -0,0 +1,111 @@
+//! JSON-RPC server over WebSocket, whose connections can subscribe to notifications
+//! (e.g. `wallet_subscribeBalance`)
+use std::io::ErrorKind as IoErrorKind;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use jsonrpc_core::{MetaIoHandler, Middleware};
+use tungstenite::handshake::server::{Request, Response};
+use tungstenite::{Error as WebSocketError, Message, WebSocket};
+
+use client_common::{ErrorKind, Result, ResultExt};
+use client_rpc_core::permission::RpcMeta;
+use client_rpc_core::session::Session;
+
+use crate::server::meta_from_headers;
+
+/// Interval of pushing the pending notifications while waiting for requests
+const NOTIFICATION_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Starts serving the JSON-RPC requests over WebSocket at the address (each connection is
+/// served by its own thread)
+pub(crate) fn spawn<M: Middleware<RpcMeta>>(
+    io: MetaIoHandler<RpcMeta, M>,
+    address: SocketAddr,
+) -> Result<()> {
+    let listener = TcpListener::bind(address).chain(|| {
+        (
+            ErrorKind::InitializationError,
+            format!("Unable to start JSON-RPC WebSocket server at {}", address),
+        )
+    })?;
+    log::info!("JSON-RPC WebSocket server listening at {}", address);
+
+    let io = Arc::new(io);
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let io = io.clone();
+                    thread::spawn(move || handle_connection(&io, stream));
+                }
+                Err(e) => log::warn!("Unable to accept WebSocket connection: {}", e),
+            }
+        }
+    });
+    Ok(())
+}
+
+fn handle_connection<M: Middleware<RpcMeta>>(io: &MetaIoHandler<RpcMeta, M>, stream: TcpStream) {
+    let mut meta = RpcMeta::default();
+    let websocket = tungstenite::accept_hdr(stream, |request: &Request, response: Response| {
+        meta = meta_from_headers(|name| {
+            request
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+        });
+        Ok(response)
+    });
+    let mut websocket = match websocket {
+        Ok(websocket) => websocket,
+        Err(e) => {
+            log::warn!("WebSocket handshake failed: {}", e);
+            return;
+        }
+    };
+
+    // the notifications of the subscriptions made over the connection
+    let (sender, notifications) = channel();
+    meta.session = Some(Session::new(sender));
+
+    if let Err(e) = serve_connection(io, &mut websocket, meta, &notifications) {
+        log::warn!("WebSocket connection closed: {}", e);
+    }
+}
+
+fn serve_connection<M: Middleware<RpcMeta>>(
+    io: &MetaIoHandler<RpcMeta, M>,
+    websocket: &mut WebSocket<TcpStream>,
+    meta: RpcMeta,
+    notifications: &Receiver<String>,
+) -> std::result::Result<(), WebSocketError> {
+    websocket
+        .get_mut()
+        .set_read_timeout(Some(NOTIFICATION_INTERVAL))?;
+
+    loop {
+        match websocket.read_message() {
+            Ok(Message::Text(request)) => {
+                if let Some(response) = io.handle_request_sync(&request, meta.clone()) {
+                    websocket.write_message(Message::Text(response))?;
+                }
+            }
+            // pings are answered and close frames acknowledged by `tungstenite`
+            Ok(_) => {}
+            Err(WebSocketError::Io(ref e))
+                if e.kind() == IoErrorKind::WouldBlock || e.kind() == IoErrorKind::TimedOut => {}
+            Err(WebSocketError::ConnectionClosed) | Err(WebSocketError::AlreadyClosed) => {
+                return Ok(())
+            }
+            Err(e) => return Err(e),
+        }
+
+        for notification in notifications.try_iter() {
+            websocket.write_message(Message::Text(notification))?;
+        }
+    }
+}
@@ -1,42 +1,61 @@
-use jsonrpc_core::IoHandler;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use jsonrpc_core::{MetaIoHandler, Middleware};
+use secstr::SecUtf8;
 
 #[cfg(feature = "experimental")]
 use crate::rpc::multisig_rpc::{MultiSigRpc, MultiSigRpcImpl};
+use chain_core::state::account::StakedStateAddress;
 use chain_core::tx::fee::FeeAlgorithm;
 use client_common::cipher::TransactionObfuscation;
-use client_common::storage::SledStorage;
-use client_common::tendermint::{types::GenesisExt, Client, WebsocketRpcClient};
+use client_common::storage::{SledStorage, TenantStorage};
+use client_common::tendermint::{
+    types::GenesisExt, CachedClient, Client, WebsocketRpcClient, DEFAULT_BLOCK_CACHE_CAPACITY,
+};
 use client_common::Result;
 use client_common::Storage;
-use client_core::service::HwKeyService;
+use client_core::service::{HwKeyService, StorageMigrationService};
 use client_core::signer::WalletSignerManager;
 use client_core::transaction_builder::DefaultWalletTransactionBuilder;
+use client_core::wallet::recovery::{resume_wallet_recovery, start_wallet_recovery};
 use client_core::wallet::syncer::{
-    spawn_light_client_supervisor, ObfuscationSyncerConfig, SyncerOptions,
+    spawn_light_client_supervisor, LightClientHandle, ObfuscationSyncerConfig, ProgressReport,
+    SyncerOptions, WalletSyncer,
 };
-use client_core::wallet::DefaultWalletClient;
-use client_network::network_ops::DefaultNetworkOpsClient;
+use client_core::wallet::{DefaultWalletClient, WalletRequest};
+use client_core::Mnemonic;
+use client_network::network_ops::{DefaultNetworkOpsClient, StakeMigrator, WithdrawTemplates};
 
+use crate::permission::{PermissionMiddleware, PermissionPolicy, RpcMeta};
 use crate::rpc::{
+    audit_rpc::{AuditRpc, AuditRpcImpl},
+    health_rpc::{wallet_synced_heights, HealthChecker, HealthRpc, HealthRpcImpl, SyncedHeights},
     info_rpc::{InfoRpc, InfoRpcImpl},
+    inheritance_rpc::{InheritanceRpc, InheritanceRpcImpl},
+    invoice_rpc::{InvoiceRpc, InvoiceRpcImpl},
+    job_rpc::{JobHandlers, JobRpc, JobRpcImpl},
+    recurring_payment_rpc::{RecurringPaymentRpc, RecurringPaymentRpcImpl},
     staking_rpc::{StakingRpc, StakingRpcImpl},
+    subscription_rpc::{SubscriptionRpc, SubscriptionRpcImpl},
     sync_rpc::{CBindingCore, SyncRpc, SyncRpcImpl},
     transaction_rpc::{TransactionRpc, TransactionRpcImpl},
+    vault_rpc::{VaultRpc, VaultRpcImpl},
     wallet_rpc::{WalletRpc, WalletRpcImpl},
 };
+use crate::tenant::{TenantMiddleware, TenantPolicy};
 
-type AppWalletClient<O, F> = DefaultWalletClient<
-    SledStorage,
-    WebsocketRpcClient,
-    DefaultWalletTransactionBuilder<SledStorage, F, O>,
->;
-type AppOpsClient<O, F> =
-    DefaultNetworkOpsClient<AppWalletClient<O, F>, SledStorage, WebsocketRpcClient, F, O>;
-type AppSyncerConfig<O, L> = ObfuscationSyncerConfig<SledStorage, WebsocketRpcClient, O, L>;
+type AppWalletClient<S, O, F> =
+    DefaultWalletClient<S, WebsocketRpcClient, DefaultWalletTransactionBuilder<S, F, O>>;
+type AppOpsClient<S, O, F> =
+    DefaultNetworkOpsClient<AppWalletClient<S, O, F>, S, WebsocketRpcClient, F, O>;
+type AppSyncerConfig<S, O, L> = ObfuscationSyncerConfig<S, CachedClient<WebsocketRpcClient>, O, L>;
 
 #[derive(Clone)]
 pub struct RpcHandler {
-    pub io: IoHandler,
+    pub io: MetaIoHandler<RpcMeta, PermissionMiddleware>,
+    /// dependency checks of the `/health` and `/ready` endpoints
+    pub health: HealthChecker<WebsocketRpcClient>,
 }
 
 impl RpcHandler {
@@ -46,79 +65,23 @@ impl RpcHandler {
         network_id: u8,
         sync_options: SyncerOptions,
         progress_callback: Option<CBindingCore>,
+        permissions: Option<PermissionPolicy>,
     ) -> Result<Self> {
-        let mut io = IoHandler::new();
-        let storage = SledStorage::new(&storage_dir)?;
-
-        let polling_storage = storage.clone();
-        std::thread::spawn(move || {
-            loop {
-                polling_storage.flush().expect("sled storage flush");
-                // every 1 second
-                std::thread::sleep(std::time::Duration::from_secs(1));
-            }
-        });
-
+        let mut io = MetaIoHandler::with_middleware(PermissionMiddleware::new(permissions));
+        let storage = open_storage(storage_dir)?;
         let tendermint_client = WebsocketRpcClient::new(&websocket_url)?;
-        let obfuscation = tendermint_client.clone();
-        let fee_policy = tendermint_client.clone();
-
-        let wallet_client = make_wallet_client(
-            storage.clone(),
-            tendermint_client.clone(),
-            fee_policy.clone(),
-            obfuscation.clone(),
-        )?;
-        let ops_client = make_ops_client(
-            storage.clone(),
-            tendermint_client.clone(),
-            fee_policy.clone(),
-            tendermint_client.clone(),
-        )?;
-        let handle = if sync_options.disable_light_client {
-            None
-        } else {
-            Some(spawn_light_client_supervisor(
-                storage_dir.as_ref(),
-                tendermint_client.genesis()?.trusting_period() / 2,
-                sync_options.light_client_peers.clone(),
-                sync_options.light_client_trusting_period_seconds,
-                sync_options.light_client_trusting_height,
-                sync_options.light_client_trusting_blockhash.clone(),
-                None,
-            )?)
-        };
-        let syncer_config = AppSyncerConfig::new(
-            storage.clone(),
+        let handle = spawn_light_client(storage_dir, &tendermint_client, &sync_options)?;
+        let synced_heights = extend_with_services(
+            &mut io,
+            storage,
             tendermint_client.clone(),
-            obfuscation.clone(),
+            network_id,
             sync_options,
-            handle.clone(),
-        );
-
-        #[cfg(feature = "experimental")]
-        let multisig_rpc = MultiSigRpcImpl::new(wallet_client.clone());
-        let transaction_rpc = TransactionRpcImpl::new(network_id);
-        let staking_rpc =
-            StakingRpcImpl::new(wallet_client.clone(), ops_client.clone(), network_id);
-        let info_rpc = InfoRpcImpl::new(ops_client);
-
-        let sync_wallet_client =
-            make_wallet_client(storage, tendermint_client, fee_policy, obfuscation)?;
-
-        let sync_rpc =
-            SyncRpcImpl::new(syncer_config, progress_callback, sync_wallet_client, handle);
-        let wallet_rpc = WalletRpcImpl::new(wallet_client, network_id);
-
-        #[cfg(feature = "experimental")]
-        io.extend_with(multisig_rpc.to_delegate());
-        io.extend_with(transaction_rpc.to_delegate());
-        io.extend_with(staking_rpc.to_delegate());
-        io.extend_with(sync_rpc.to_delegate());
-        io.extend_with(wallet_rpc.to_delegate());
-        io.extend_with(info_rpc.to_delegate());
-
-        Ok(RpcHandler { io })
+            progress_callback,
+            handle,
+        )?;
+        let health = HealthChecker::new(tendermint_client, vec![synced_heights]);
+        Ok(RpcHandler { io, health })
     }
 
     pub fn new(
@@ -134,20 +97,358 @@ impl RpcHandler {
             network_id,
             sync_options,
             progress_callback,
+            None,
+        )
+    }
+
+    /// Creates the handler which enforces the permission scopes of the request credentials
+    pub fn new_with_permissions(
+        storage_dir: &str,
+        websocket_url: &str,
+        network_id: u8,
+        sync_options: SyncerOptions,
+        permissions: PermissionPolicy,
+    ) -> Result<Self> {
+        Self::new_impl(
+            storage_dir,
+            websocket_url,
+            network_id,
+            sync_options,
+            None,
+            Some(permissions),
         )
     }
 
     pub fn handle(&self, req: &str) -> Option<String> {
-        self.io.handle_request_sync(req)
+        self.io.handle_request_sync(req, RpcMeta::default())
     }
 }
 
-fn make_wallet_client<O: TransactionObfuscation, F: FeeAlgorithm>(
-    storage: SledStorage,
+/// Handler of the multi-tenant hosted wallet mode: the services of each tenant run over
+/// its own namespaced and encrypted storage (see `TenantStorage`), and calls are routed
+/// to the services of the tenant of the request credentials
+#[derive(Clone)]
+pub struct TenantRpcHandler {
+    pub io: MetaIoHandler<RpcMeta, TenantMiddleware>,
+    /// dependency checks of the `/health` and `/ready` endpoints (over the wallets of all tenants)
+    pub health: HealthChecker<WebsocketRpcClient>,
+}
+
+impl TenantRpcHandler {
+    pub fn new(
+        storage_dir: &str,
+        websocket_url: &str,
+        network_id: u8,
+        sync_options: SyncerOptions,
+        tenants: TenantPolicy,
+    ) -> Result<Self> {
+        let storage = open_storage(storage_dir)?;
+        let tendermint_client = WebsocketRpcClient::new(&websocket_url)?;
+        // the light client only verifies the (public) chain data, so it's shared by the tenants
+        let handle = spawn_light_client(storage_dir, &tendermint_client, &sync_options)?;
+        let mut handlers = HashMap::new();
+        let mut synced_heights = Vec::new();
+        for (tenant, config) in tenants.tenants() {
+            let tenant_storage = TenantStorage::open(storage.clone(), tenant, &config.master_key)?;
+            let mut io = MetaIoHandler::default();
+            let tenant_synced_heights = extend_with_services(
+                &mut io,
+                tenant_storage,
+                tendermint_client.clone(),
+                network_id,
+                sync_options.clone(),
+                None,
+                handle.clone(),
+            )?;
+            handlers.insert(tenant.to_owned(), io);
+            synced_heights.push(tenant_synced_heights);
+        }
+        log::info!("serving {} tenants", handlers.len());
+        let io = MetaIoHandler::with_middleware(TenantMiddleware::new(tenants, handlers));
+        let health = HealthChecker::new(tendermint_client, synced_heights);
+        Ok(TenantRpcHandler { io, health })
+    }
+}
+
+/// Opens the sled storage, flushed every second in the background
+fn open_storage(storage_dir: &str) -> Result<SledStorage> {
+    let storage = SledStorage::new(&storage_dir)?;
+
+    // wallets written by older clients are converted on startup (see `client-cli migrate`)
+    let report = StorageMigrationService::new(storage.clone()).migrate()?;
+    if report.applied {
+        log::info!(
+            "storage migrated from version {} to {}: {:?}",
+            report.from_version,
+            report.to_version,
+            report.legacy_records
+        );
+    }
+
+    let polling_storage = storage.clone();
+    std::thread::spawn(move || {
+        loop {
+            polling_storage.flush().expect("sled storage flush");
+            // every 1 second
+            std::thread::sleep(std::time::Duration::from_secs(1));
+        }
+    });
+    Ok(storage)
+}
+
+fn spawn_light_client(
+    storage_dir: &str,
+    tendermint_client: &WebsocketRpcClient,
+    sync_options: &SyncerOptions,
+) -> Result<Option<impl LightClientHandle + 'static>> {
+    if sync_options.disable_light_client {
+        return Ok(None);
+    }
+    Ok(Some(spawn_light_client_supervisor(
+        storage_dir.as_ref(),
+        tendermint_client.genesis()?.trusting_period() / 2,
+        sync_options.light_client_peers.clone(),
+        sync_options.light_client_trusting_period_seconds,
+        sync_options.light_client_trusting_height,
+        sync_options.light_client_trusting_blockhash.clone(),
+        None,
+    )?))
+}
+
+/// Adds the wallet, invoice, recurring payment, staking, sync, job, subscription, transaction,
+/// info, audit and health services over the storage, returns the synchronized block heights
+/// of its wallets (for the health checks)
+fn extend_with_services<S, M, L>(
+    io: &mut MetaIoHandler<RpcMeta, M>,
+    storage: S,
+    tendermint_client: WebsocketRpcClient,
+    network_id: u8,
+    sync_options: SyncerOptions,
+    progress_callback: Option<CBindingCore>,
+    handle: Option<L>,
+) -> Result<SyncedHeights>
+where
+    S: Storage + 'static,
+    M: Middleware<RpcMeta>,
+    L: LightClientHandle + 'static,
+{
+    let obfuscation = tendermint_client.clone();
+    let fee_policy = tendermint_client.clone();
+
+    let wallet_client = make_wallet_client(
+        storage.clone(),
+        tendermint_client.clone(),
+        fee_policy.clone(),
+        obfuscation.clone(),
+    )?;
+    let ops_client = make_ops_client(
+        storage.clone(),
+        tendermint_client.clone(),
+        fee_policy.clone(),
+        tendermint_client.clone(),
+    )?;
+    // the wallets synchronized concurrently fetch each block only once
+    let syncer_config = AppSyncerConfig::new(
+        storage.clone(),
+        CachedClient::new(tendermint_client.clone(), DEFAULT_BLOCK_CACHE_CAPACITY),
+        obfuscation.clone(),
+        sync_options,
+        handle.clone(),
+    );
+
+    #[cfg(feature = "experimental")]
+    let multisig_rpc = MultiSigRpcImpl::new(wallet_client.clone());
+    let transaction_rpc = TransactionRpcImpl::new(wallet_client.clone(), network_id);
+    let withdraw_templates =
+        WithdrawTemplates::new(storage.clone(), wallet_client.clone(), ops_client.clone());
+    let stake_migrator = StakeMigrator::new(
+        storage.clone(),
+        wallet_client.clone(),
+        ops_client.clone(),
+        network_id,
+    );
+    let staking_rpc = StakingRpcImpl::new(
+        wallet_client.clone(),
+        ops_client.clone(),
+        withdraw_templates.clone(),
+        stake_migrator.clone(),
+        network_id,
+    );
+    let info_rpc = InfoRpcImpl::new(ops_client);
+    let audit_rpc = AuditRpcImpl::new(storage.clone());
+    let synced_heights = wallet_synced_heights(wallet_client.clone());
+    let health_rpc = HealthRpcImpl::new(HealthChecker::new(
+        tendermint_client.clone(),
+        vec![synced_heights.clone()],
+    ));
+
+    let sync_wallet_client = make_wallet_client(
+        storage.clone(),
+        tendermint_client.clone(),
+        fee_policy,
+        obfuscation,
+    )?;
+
+    // the long-running operations are run in the background and polled by their job ids
+    let job_syncer_config = syncer_config.clone();
+    let job_recover_address = sync_wallet_client.clone();
+    let recovery_storage = storage.clone();
+    let recovery_client = tendermint_client.clone();
+    let recovery_wallet_client = wallet_client.clone();
+    let recovery_syncer_config = syncer_config.clone();
+    let recovery_recover_address = sync_wallet_client.clone();
+    let migration_syncer_config = syncer_config.clone();
+    let migration_recover_address = sync_wallet_client.clone();
+    let job_rpc = JobRpcImpl::new(
+        storage,
+        wallet_client.clone(),
+        network_id,
+        JobHandlers {
+            sync: Arc::new(
+                move |request: &WalletRequest,
+                      reset: bool,
+                      progress: &mut dyn FnMut(ProgressReport) -> bool| {
+                    let mut syncer = WalletSyncer::with_obfuscation_config(
+                        job_syncer_config.clone(),
+                        request.name.clone(),
+                        request.enckey.clone(),
+                        job_recover_address.clone(),
+                    )?;
+                    if reset {
+                        syncer.reset_state()?;
+                    }
+                    syncer.sync(progress)
+                },
+            ),
+            start_recovery: Arc::new(
+                move |name: &str, passphrase: &SecUtf8, mnemonic: &Mnemonic| {
+                    start_wallet_recovery(
+                        &recovery_storage,
+                        &recovery_client,
+                        &recovery_wallet_client,
+                        name,
+                        passphrase,
+                        mnemonic,
+                    )
+                },
+            ),
+            recovery: Arc::new(
+                move |request: &WalletRequest, progress: &mut dyn FnMut(ProgressReport) -> bool| {
+                    resume_wallet_recovery(
+                        recovery_syncer_config.clone(),
+                        recovery_recover_address.clone(),
+                        &request.name,
+                        &request.enckey,
+                        progress,
+                    )
+                },
+            ),
+            stake_migration: Arc::new(
+                move |request: &WalletRequest,
+                      from_address: &StakedStateAddress,
+                      to_address: &StakedStateAddress,
+                      resume: bool| {
+                    // the steps after the unbonding depend on the outputs synced by the wallet
+                    WalletSyncer::with_obfuscation_config(
+                        migration_syncer_config.clone(),
+                        request.name.clone(),
+                        request.enckey.clone(),
+                        migration_recover_address.clone(),
+                    )?
+                    .sync(|_| true)?;
+                    let migration = match stake_migrator.migration(&request.name, from_address)? {
+                        Some(migration) if resume || !migration.is_completed() => {
+                            stake_migrator.advance(&request.name, &request.enckey, from_address)?
+                        }
+                        _ => stake_migrator.start(
+                            &request.name,
+                            &request.enckey,
+                            *from_address,
+                            *to_address,
+                        )?,
+                    };
+                    Ok(migration.status.steps_done())
+                },
+            ),
+        },
+    )?;
+
+    // the unlocked wallets are synchronized before their due recurring payments are sent
+    let recurring_payment_syncer_config = syncer_config.clone();
+    let recurring_payment_recover_address = sync_wallet_client.clone();
+    let recurring_payment_rpc = RecurringPaymentRpcImpl::new(
+        wallet_client.clone(),
+        network_id,
+        Arc::new(move |request: &WalletRequest| {
+            WalletSyncer::with_obfuscation_config(
+                recurring_payment_syncer_config.clone(),
+                request.name.clone(),
+                request.enckey.clone(),
+                recurring_payment_recover_address.clone(),
+            )?
+            .sync(|_| true)
+        }),
+    );
+
+    // the subscribed wallets are synchronized on every new block before checking their balance
+    let subscription_syncer_config = syncer_config.clone();
+    let subscription_recover_address = sync_wallet_client.clone();
+    let subscription_rpc = SubscriptionRpcImpl::new(
+        wallet_client.clone(),
+        tendermint_client,
+        Arc::new(move |request: &WalletRequest| {
+            WalletSyncer::with_obfuscation_config(
+                subscription_syncer_config.clone(),
+                request.name.clone(),
+                request.enckey.clone(),
+                subscription_recover_address.clone(),
+            )?
+            .sync(|_| true)
+        }),
+    );
+
+    // the withdraw transactions signed in advance are broadcasted once they're valid
+    let sync_rpc = SyncRpcImpl::new(syncer_config, progress_callback, sync_wallet_client, handle)
+        .with_after_sync(Arc::new(move |request: &WalletRequest| {
+            for template in withdraw_templates.broadcast_due(&request.name, &request.enckey)? {
+                log::info!(
+                    "withdraw template of {} ({}): {:?}",
+                    template.from_address,
+                    hex::encode(template.tx_id()),
+                    template.status
+                );
+            }
+            Ok(())
+        }));
+    let invoice_rpc = InvoiceRpcImpl::new(wallet_client.clone());
+    let vault_rpc = VaultRpcImpl::new(wallet_client.clone(), network_id);
+    let inheritance_rpc = InheritanceRpcImpl::new(wallet_client.clone(), network_id);
+    let wallet_rpc = WalletRpcImpl::new(wallet_client, network_id);
+
+    #[cfg(feature = "experimental")]
+    io.extend_with(multisig_rpc.to_delegate());
+    io.extend_with(transaction_rpc.to_delegate());
+    io.extend_with(staking_rpc.to_delegate());
+    io.extend_with(sync_rpc.to_delegate());
+    io.extend_with(job_rpc.to_delegate());
+    io.extend_with(subscription_rpc.to_delegate());
+    io.extend_with(wallet_rpc.to_delegate());
+    io.extend_with(invoice_rpc.to_delegate());
+    io.extend_with(vault_rpc.to_delegate());
+    io.extend_with(inheritance_rpc.to_delegate());
+    io.extend_with(recurring_payment_rpc.to_delegate());
+    io.extend_with(info_rpc.to_delegate());
+    io.extend_with(audit_rpc.to_delegate());
+    io.extend_with(health_rpc.to_delegate());
+    Ok(synced_heights)
+}
+
+fn make_wallet_client<S: Storage, O: TransactionObfuscation, F: FeeAlgorithm>(
+    storage: S,
     tendermint_client: WebsocketRpcClient,
     fee_policy: F,
     obfuscator: O,
-) -> Result<AppWalletClient<O, F>> {
+) -> Result<AppWalletClient<S, O, F>> {
     let hw_key_service = HwKeyService::default();
     let signer_manager = WalletSignerManager::new(storage.clone(), hw_key_service.clone());
     Ok(DefaultWalletClient::new(
@@ -159,12 +460,12 @@ fn make_wallet_client<O: TransactionObfuscation, F: FeeAlgorithm>(
     ))
 }
 
-fn make_ops_client<O: TransactionObfuscation, F: FeeAlgorithm>(
-    storage: SledStorage,
+fn make_ops_client<S: Storage, O: TransactionObfuscation, F: FeeAlgorithm>(
+    storage: S,
     tendermint_client: WebsocketRpcClient,
     fee_policy: F,
     obfuscator: O,
-) -> Result<AppOpsClient<O, F>> {
+) -> Result<AppOpsClient<S, O, F>> {
     let hw_key_service = HwKeyService::default();
     let signer_manager = WalletSignerManager::new(storage.clone(), hw_key_service);
     let wallet_client = make_wallet_client(
@@ -1,24 +1,37 @@
 use std::fmt::Debug;
 
+use client_common::correlation::{current_correlation_id, log_prefix};
+use serde_json::json;
+
 pub mod handler;
+pub mod permission;
+pub mod proxy;
 pub mod rpc;
+pub mod session;
+pub mod tenant;
 
-pub use handler::RpcHandler;
+pub use handler::{RpcHandler, TenantRpcHandler};
+pub use proxy::ProxyHandler;
 
 pub fn to_rpc_error<E: ToString + Debug>(error: E) -> jsonrpc_core::Error {
-    log::error!("{:?}", error);
+    log::error!("{}{:?}", log_prefix(), error);
     jsonrpc_core::Error {
         code: jsonrpc_core::ErrorCode::InternalError,
         message: error.to_string(),
-        data: None,
+        data: correlation_data(),
     }
 }
 
 pub fn rpc_error_from_string(error: String) -> jsonrpc_core::Error {
-    log::error!("{}", error);
+    log::error!("{}{}", log_prefix(), error);
     jsonrpc_core::Error {
         code: jsonrpc_core::ErrorCode::InternalError,
         message: error,
-        data: None,
+        data: correlation_data(),
     }
 }
+
+/// Correlation ID of the failed call (to find the operation in the wallet and node logs)
+fn correlation_data() -> Option<jsonrpc_core::Value> {
+    current_correlation_id().map(|id| json!({ "correlationId": id }))
+}
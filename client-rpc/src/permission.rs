@@ -0,0 +1,349 @@
+//! Per-method permission scopes of RPC credentials
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use jsonrpc_core::futures::future::{self, Either};
+use jsonrpc_core::futures::Future;
+use jsonrpc_core::{
+    Call, Error, ErrorCode, FutureOutput, FutureResponse, Metadata, Middleware, Output,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use client_common::correlation::{
+    enter_correlation_id, log_prefix, new_correlation_id, CorrelationGuard,
+};
+use client_common::{ErrorKind, Result, ResultExt};
+
+use crate::session::Session;
+
+/// error code of requests without (known) credentials
+pub const MISSING_CREDENTIALS_CODE: i64 = -32010;
+/// error code of requests whose credentials don't have the method's scope
+pub const INSUFFICIENT_SCOPE_CODE: i64 = -32011;
+
+/// Permission scope of a RPC method
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    /// querying chain and wallet state (including syncing the wallet)
+    Read,
+    /// building unsigned transactions and generating addresses
+    Build,
+    /// signing and broadcasting transactions
+    Sign,
+    /// creating, restoring, deleting, importing and exporting wallets (implies all the other scopes)
+    Admin,
+}
+
+impl Scope {
+    /// Scope required for calling the method (unknown methods require `Admin`)
+    pub fn of_method(method: &str) -> Scope {
+        match method {
+            "genesis"
+            | "status"
+            | "health"
+            | "ready"
+            | "staking_state"
+            | "staking_withdrawTemplates"
+            | "staking_dataAnchors"
+            | "sync"
+            | "sync_progress"
+            | "sync_stop"
+            | "job_start"
+            | "job_list"
+            | "job_get"
+            | "job_cancel"
+            | "job_resume"
+            | "wallet_balance"
+            | "wallet_getViewKey"
+            | "wallet_isOwnAddress"
+            | "wallet_list"
+            | "wallet_listWithMetadata"
+            | "wallet_findById"
+            | "wallet_listImportedKeys"
+            | "wallet_listPublicKeys"
+            | "wallet_listStakingAddresses"
+            | "wallet_listTransferAddresses"
+            | "wallet_listUTxO"
+            | "wallet_spendability"
+            | "wallet_listFrozenOutputs"
+            | "wallet_listContacts"
+            | "wallet_spendingPolicy"
+            | "wallet_subscribeBalance"
+            | "wallet_unsubscribeBalance"
+            | "wallet_transactions"
+            | "wallet_queryTransactions"
+            | "wallet_mempoolTransactions"
+            | "wallet_replayEvents"
+            | "transaction_estimateFee"
+            | "invoice_get"
+            | "invoice_list"
+            | "invoice_events"
+            | "vault_list"
+            | "vault_spends"
+            | "inheritance_list"
+            | "inheritance_events"
+            | "recurringPayment_list"
+            | "recurringPayment_events"
+            | "audit_getAccount"
+            | "audit_listAddresses"
+            | "multiSig_listAddressPublicKeys" => Scope::Read,
+            "transaction_createRaw"
+            | "wallet_buildRawTransferTx"
+            | "wallet_buildSigningPayload"
+            | "wallet_createStakingAddress"
+            | "wallet_createStakingAddressBatch"
+            | "wallet_createWatchStakingAddress"
+            | "wallet_createTransferAddress"
+            | "wallet_createTransferAddressBatch"
+            | "wallet_createWatchTransferAddress"
+            | "wallet_addContact"
+            | "wallet_removeContact"
+            | "wallet_exportTransaction"
+            | "wallet_importTransaction"
+            | "wallet_freezeOutputs"
+            | "wallet_unfreezeOutputs"
+            | "invoice_create"
+            | "invoice_cancel"
+            | "vault_create"
+            | "audit_addAccount"
+            | "audit_nextAddress"
+            | "audit_markUsed"
+            | "multiSig_newAddressPublicKey"
+            | "multiSig_createAddress"
+            | "multiSig_newSession"
+            | "multiSig_nonceCommitment"
+            | "multiSig_addNonceCommitment"
+            | "multiSig_nonce"
+            | "multiSig_addNonce"
+            | "multiSig_addPartialSignature"
+            | "multiSig_importMessage" => Scope::Build,
+            "staking_depositStake"
+            | "staking_depositStakeWithInfo"
+            | "staking_depositAmountStake"
+            | "staking_unbondStake"
+            | "staking_unbondStakeWithInfo"
+            | "staking_withdrawAllUnbondedStake"
+            | "staking_withdrawAllUnbondedStakeWithInfo"
+            | "staking_createWithdrawTemplate"
+            | "staking_removeWithdrawTemplate"
+            | "staking_unjail"
+            | "staking_validatorNodeJoin"
+            | "staking_anchorData"
+            | "wallet_sendToAddress"
+            | "wallet_sendToContact"
+            | "wallet_sendTransfer"
+            | "wallet_broadcastSignedTransferTx"
+            | "wallet_broadcastSigningPayload"
+            | "vault_initiateSpend"
+            | "vault_completeSpend"
+            | "vault_cancelSpend"
+            | "inheritance_create"
+            | "inheritance_refresh"
+            | "inheritance_cancel"
+            | "inheritance_exportPackage"
+            | "inheritance_broadcastPackage"
+            | "recurringPayment_create"
+            | "recurringPayment_cancel"
+            | "recurringPayment_unlock"
+            | "recurringPayment_lock"
+            | "multiSig_partialSign"
+            | "multiSig_exportMessage"
+            | "multiSig_signature"
+            | "multiSig_broadcastWithSignature"
+            | "wallet_approveSpendingOverride" => Scope::Sign,
+            _ => Scope::Admin,
+        }
+    }
+}
+
+/// Request metadata: the credential token (from the `Authorization: Bearer <token>` header),
+/// the correlation ID (from the `X-Correlation-Id` header) and the session of WebSocket
+/// connections (for subscriptions)
+#[derive(Debug, Clone, Default)]
+pub struct RpcMeta {
+    pub token: Option<String>,
+    pub correlation_id: Option<String>,
+    pub session: Option<Session>,
+}
+
+impl Metadata for RpcMeta {}
+
+impl RpcMeta {
+    /// Enters the correlation ID of the request on the current thread (a new one if the caller
+    /// didn't provide it) and logs the call
+    pub fn trace_call(&self, call: &Call) -> CorrelationGuard {
+        let id = self
+            .correlation_id
+            .clone()
+            .unwrap_or_else(new_correlation_id);
+        let guard = enter_correlation_id(id);
+        match call {
+            Call::MethodCall(method_call) => {
+                log::info!("{}RPC call {}", log_prefix(), method_call.method)
+            }
+            Call::Notification(notification) => {
+                log::info!("{}RPC notification {}", log_prefix(), notification.method)
+            }
+            Call::Invalid { .. } => log::info!("{}invalid RPC call", log_prefix()),
+        }
+        guard
+    }
+}
+
+/// Scopes granted to each credential token
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(transparent)]
+pub struct PermissionPolicy {
+    credentials: HashMap<String, BTreeSet<Scope>>,
+}
+
+impl PermissionPolicy {
+    /// Loads the policy from a JSON file, e.g. `{"<token>": ["read"], "<admin token>": ["admin"]}`
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path).chain(|| {
+            (
+                ErrorKind::IoError,
+                format!("Unable to read RPC credentials file: {}", path.display()),
+            )
+        })?;
+        serde_json::from_str(&content).chain(|| {
+            (
+                ErrorKind::DeserializationError,
+                format!("Unable to parse RPC credentials file: {}", path.display()),
+            )
+        })
+    }
+
+    /// Grants the scopes to the credential token
+    pub fn grant(&mut self, token: &str, scopes: &[Scope]) {
+        self.credentials
+            .entry(token.to_owned())
+            .or_default()
+            .extend(scopes.iter().copied());
+    }
+
+    /// Whether the credential token is known
+    pub fn contains(&self, token: &str) -> bool {
+        self.credentials.contains_key(token)
+    }
+
+    /// All the known credential tokens
+    pub fn tokens(&self) -> impl Iterator<Item = &str> {
+        self.credentials.keys().map(String::as_str)
+    }
+
+    /// Checks whether the credential token can call the method
+    pub fn check(&self, token: Option<&str>, method: &str) -> std::result::Result<(), Error> {
+        let granted = token
+            .and_then(|token| self.credentials.get(token))
+            .ok_or_else(|| Error {
+                code: ErrorCode::ServerError(MISSING_CREDENTIALS_CODE),
+                message: "Missing or unknown RPC credentials".to_owned(),
+                data: None,
+            })?;
+        let required = Scope::of_method(method);
+        if granted.contains(&Scope::Admin) || granted.contains(&required) {
+            Ok(())
+        } else {
+            Err(Error {
+                code: ErrorCode::ServerError(INSUFFICIENT_SCOPE_CODE),
+                message: format!("Insufficient scope for {}", method),
+                data: Some(json!({ "required": required, "granted": granted })),
+            })
+        }
+    }
+}
+
+/// Middleware enforcing the permission policy (if configured) on every method call
+#[derive(Debug, Clone, Default)]
+pub struct PermissionMiddleware {
+    policy: Option<Arc<PermissionPolicy>>,
+}
+
+impl PermissionMiddleware {
+    pub fn new(policy: Option<PermissionPolicy>) -> Self {
+        PermissionMiddleware {
+            policy: policy.map(Arc::new),
+        }
+    }
+}
+
+impl Middleware<RpcMeta> for PermissionMiddleware {
+    type Future = FutureResponse;
+    type CallFuture = FutureOutput;
+
+    fn on_call<F, X>(&self, call: Call, meta: RpcMeta, next: F) -> Either<Self::CallFuture, X>
+    where
+        F: Fn(Call, RpcMeta) -> X + Send + Sync,
+        X: Future<Item = Option<Output>, Error = ()> + Send + 'static,
+    {
+        // the methods run synchronously within `next`, so they're traced by the guard
+        let _correlation = meta.trace_call(&call);
+        let policy = match &self.policy {
+            Some(policy) => policy,
+            None => return Either::B(next(call, meta)),
+        };
+        let denied = match &call {
+            Call::MethodCall(method_call) => policy
+                .check(meta.token.as_deref(), &method_call.method)
+                .err()
+                .map(|error| {
+                    Some(Output::from(
+                        Err(error),
+                        method_call.id.clone(),
+                        method_call.jsonrpc,
+                    ))
+                }),
+            Call::Notification(notification) => policy
+                .check(meta.token.as_deref(), &notification.method)
+                .err()
+                .map(|_| None),
+            Call::Invalid { .. } => None,
+        };
+        match denied {
+            Some(output) => {
+                log::warn!("{}RPC call denied: {:?}", log_prefix(), output);
+                Either::A(Box::new(future::ok(output)))
+            }
+            None => Either::B(next(call, meta)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_scopes() {
+        let mut policy = PermissionPolicy::default();
+        policy.grant("monitoring", &[Scope::Read]);
+        policy.grant("operator", &[Scope::Admin]);
+
+        assert!(policy.check(Some("monitoring"), "wallet_balance").is_ok());
+        let error = policy
+            .check(Some("monitoring"), "wallet_sendToAddress")
+            .unwrap_err();
+        assert_eq!(ErrorCode::ServerError(INSUFFICIENT_SCOPE_CODE), error.code);
+        let error = policy.check(None, "wallet_balance").unwrap_err();
+        assert_eq!(ErrorCode::ServerError(MISSING_CREDENTIALS_CODE), error.code);
+
+        assert!(policy.check(Some("operator"), "wallet_delete").is_ok());
+        assert_eq!(Scope::Admin, Scope::of_method("unknown_method"));
+    }
+
+    #[test]
+    fn check_policy_deserialization() {
+        let policy: PermissionPolicy =
+            serde_json::from_str(r#"{"token": ["read", "build"]}"#).unwrap();
+        assert!(policy
+            .check(Some("token"), "wallet_buildRawTransferTx")
+            .is_ok());
+        assert!(policy.check(Some("token"), "wallet_export").is_err());
+    }
+}
@@ -0,0 +1,427 @@
+//! Hardened read-only proxy of the Tendermint RPC and the ABCI queries for public endpoints
+//!
+//! Only the read paths needed by wallets are exposed (`status`, `block`, `block_results`
+//! and `abci_query` with an allow-list of query paths); requests are size-limited,
+//! rate-limited per client and the responses of hot queries are cached for a short time.
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use jsonrpc_core::futures::future::{self, Either};
+use jsonrpc_core::futures::Future;
+use jsonrpc_core::{
+    Call, Error, ErrorCode, FutureOutput, FutureResponse, MetaIoHandler, Metadata, Middleware,
+    Output, Result as RpcResult,
+};
+use jsonrpc_derive::rpc;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::to_rpc_error;
+use client_common::tendermint::types::Height;
+use client_common::tendermint::{Client, WebsocketRpcClient};
+use client_common::{ErrorKind, Result, ResultExt};
+
+/// error code of requests over the client's rate limit
+pub const RATE_LIMITED_CODE: i64 = -32012;
+
+/// ABCI query paths served by default (the typed query service is allowed with its path prefix)
+const DEFAULT_QUERY_PATHS: &[&str] = &[
+    "account",
+    "staking",
+    "state",
+    "meta",
+    "witness",
+    "merkle",
+    "network-params",
+    "council-nodes",
+    "account-freeze",
+    "compact-filter",
+    "block-filters",
+    "txquery",
+    "/chain.abci.query.v1.Query/",
+];
+
+/// Proxy settings (all fields are optional in the JSON file)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProxyConfig {
+    /// maximum size of a request body in bytes
+    pub max_request_size: usize,
+    /// requests allowed per client and minute
+    pub requests_per_minute: u32,
+    /// how long query responses are cached (0 disables the cache)
+    pub cache_ttl_seconds: u64,
+    /// maximum number of cached responses
+    pub cache_capacity: usize,
+    /// allowed ABCI query paths; a path ending with `/` allows every path with that prefix
+    pub allowed_query_paths: Vec<String>,
+}
+
+impl Default for ProxyConfig {
+    fn default() -> Self {
+        ProxyConfig {
+            max_request_size: 64 * 1024,
+            requests_per_minute: 600,
+            cache_ttl_seconds: 2,
+            cache_capacity: 10_000,
+            allowed_query_paths: DEFAULT_QUERY_PATHS.iter().map(|&p| p.to_owned()).collect(),
+        }
+    }
+}
+
+impl ProxyConfig {
+    /// Loads the settings from a JSON file, e.g. `{"requests_per_minute": 120}`
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path).chain(|| {
+            (
+                ErrorKind::IoError,
+                format!("Unable to read RPC proxy config file: {}", path.display()),
+            )
+        })?;
+        serde_json::from_str(&content).chain(|| {
+            (
+                ErrorKind::DeserializationError,
+                format!("Unable to parse RPC proxy config file: {}", path.display()),
+            )
+        })
+    }
+
+    /// Whether the ABCI query path is allowed
+    pub fn allows_query_path(&self, path: &str) -> bool {
+        self.allowed_query_paths.iter().any(|allowed| {
+            if allowed.ends_with('/') {
+                path.starts_with(allowed.as_str()) && path.len() > allowed.len()
+            } else {
+                path == allowed
+            }
+        })
+    }
+}
+
+/// Client identity of a proxy request (used as the rate limit key)
+#[derive(Debug, Clone, Default)]
+pub struct ProxyMeta {
+    pub client: Option<String>,
+}
+
+impl Metadata for ProxyMeta {}
+
+/// Per-client token bucket rate limiter
+#[derive(Debug)]
+pub struct RateLimiter {
+    capacity: f64,
+    refill_per_second: f64,
+    buckets: Mutex<HashMap<String, (f64, Instant)>>,
+}
+
+/// number of tracked clients after which the full buckets are dropped
+const MAX_TRACKED_CLIENTS: usize = 100_000;
+
+impl RateLimiter {
+    pub fn new(requests_per_minute: u32) -> Self {
+        RateLimiter {
+            capacity: f64::from(requests_per_minute.max(1)),
+            refill_per_second: f64::from(requests_per_minute.max(1)) / 60.0,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Takes a token of the client's bucket, returns false if it's empty
+    pub fn check(&self, client: &str) -> bool {
+        self.check_at(client, Instant::now())
+    }
+
+    fn check_at(&self, client: &str, now: Instant) -> bool {
+        let mut buckets = self.buckets.lock().expect("rate limiter lock poisoned");
+        if buckets.len() >= MAX_TRACKED_CLIENTS && !buckets.contains_key(client) {
+            let (capacity, refill) = (self.capacity, self.refill_per_second);
+            buckets.retain(|_, (tokens, last)| {
+                *tokens + now.saturating_duration_since(*last).as_secs_f64() * refill < capacity
+            });
+        }
+        let (tokens, last) = buckets
+            .entry(client.to_owned())
+            .or_insert((self.capacity, now));
+        let refilled = now.saturating_duration_since(*last).as_secs_f64() * self.refill_per_second;
+        *tokens = (*tokens + refilled).min(self.capacity);
+        *last = now;
+        if *tokens >= 1.0 {
+            *tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Middleware rejecting the calls over the client's rate limit
+/// (requests without a client identity share one bucket)
+#[derive(Debug, Clone)]
+pub struct RateLimitMiddleware {
+    limiter: Arc<RateLimiter>,
+}
+
+impl RateLimitMiddleware {
+    pub fn new(requests_per_minute: u32) -> Self {
+        RateLimitMiddleware {
+            limiter: Arc::new(RateLimiter::new(requests_per_minute)),
+        }
+    }
+}
+
+impl Middleware<ProxyMeta> for RateLimitMiddleware {
+    type Future = FutureResponse;
+    type CallFuture = FutureOutput;
+
+    fn on_call<F, X>(&self, call: Call, meta: ProxyMeta, next: F) -> Either<Self::CallFuture, X>
+    where
+        F: Fn(Call, ProxyMeta) -> X + Send + Sync,
+        X: Future<Item = Option<Output>, Error = ()> + Send + 'static,
+    {
+        let client = meta.client.as_deref().unwrap_or_default();
+        if self.limiter.check(client) {
+            return Either::B(next(call, meta));
+        }
+        log::warn!("RPC proxy call rate limited: {}", client);
+        let output = match &call {
+            Call::MethodCall(method_call) => Some(Output::from(
+                Err(Error {
+                    code: ErrorCode::ServerError(RATE_LIMITED_CODE),
+                    message: "Too many requests".to_owned(),
+                    data: None,
+                }),
+                method_call.id.clone(),
+                method_call.jsonrpc,
+            )),
+            _ => None,
+        };
+        Either::A(Box::new(future::ok(output)))
+    }
+}
+
+/// Time-limited cache of the proxied responses
+#[derive(Debug)]
+struct ResponseCache {
+    ttl: Duration,
+    capacity: usize,
+    entries: HashMap<String, (Instant, Value)>,
+}
+
+impl ResponseCache {
+    fn new(ttl: Duration, capacity: usize) -> Self {
+        ResponseCache {
+            ttl,
+            capacity,
+            entries: HashMap::new(),
+        }
+    }
+
+    fn get(&self, key: &str, now: Instant) -> Option<Value> {
+        self.entries
+            .get(key)
+            .filter(|(inserted, _)| now.saturating_duration_since(*inserted) < self.ttl)
+            .map(|(_, value)| value.clone())
+    }
+
+    fn insert(&mut self, key: String, value: Value, now: Instant) {
+        if self.ttl == Duration::from_secs(0) || self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() >= self.capacity {
+            let ttl = self.ttl;
+            self.entries
+                .retain(|_, (inserted, _)| now.saturating_duration_since(*inserted) < ttl);
+        }
+        if self.entries.len() < self.capacity {
+            self.entries.insert(key, (now, value));
+        }
+    }
+}
+
+#[rpc(server)]
+pub trait ProxyRpc: Send + Sync {
+    #[rpc(name = "status")]
+    fn status(&self) -> RpcResult<Value>;
+
+    #[rpc(name = "block")]
+    fn block(&self, height: u64) -> RpcResult<Value>;
+
+    #[rpc(name = "block_results")]
+    fn block_results(&self, height: u64) -> RpcResult<Value>;
+
+    /// `data` is hex-encoded, `height` 0 or missing means the latest
+    #[rpc(name = "abci_query")]
+    fn abci_query(
+        &self,
+        path: String,
+        data: String,
+        height: Option<u64>,
+        prove: Option<bool>,
+    ) -> RpcResult<Value>;
+}
+
+pub struct ProxyRpcImpl<C: Client> {
+    client: C,
+    config: ProxyConfig,
+    cache: Mutex<ResponseCache>,
+}
+
+impl<C: Client> ProxyRpcImpl<C> {
+    pub fn new(client: C, config: ProxyConfig) -> Self {
+        let cache = ResponseCache::new(
+            Duration::from_secs(config.cache_ttl_seconds),
+            config.cache_capacity,
+        );
+        ProxyRpcImpl {
+            client,
+            config,
+            cache: Mutex::new(cache),
+        }
+    }
+
+    fn cached<T: Serialize>(
+        &self,
+        key: String,
+        call: impl FnOnce(&C) -> Result<T>,
+    ) -> RpcResult<Value> {
+        let now = Instant::now();
+        if let Some(value) = self
+            .cache
+            .lock()
+            .expect("cache lock poisoned")
+            .get(&key, now)
+        {
+            return Ok(value);
+        }
+        let value = call(&self.client)
+            .map_err(to_rpc_error)
+            .and_then(|response| serde_json::to_value(response).map_err(to_rpc_error))?;
+        self.cache
+            .lock()
+            .expect("cache lock poisoned")
+            .insert(key, value.clone(), now);
+        Ok(value)
+    }
+}
+
+fn invalid_params(message: &str) -> Error {
+    Error::invalid_params(message.to_owned())
+}
+
+impl<C: Client + 'static> ProxyRpc for ProxyRpcImpl<C> {
+    fn status(&self) -> RpcResult<Value> {
+        self.cached("status".to_owned(), |client| client.status())
+    }
+
+    fn block(&self, height: u64) -> RpcResult<Value> {
+        if height == 0 {
+            return Err(invalid_params("height must be positive"));
+        }
+        self.cached(format!("block/{}", height), |client| client.block(height))
+    }
+
+    fn block_results(&self, height: u64) -> RpcResult<Value> {
+        if height == 0 {
+            return Err(invalid_params("height must be positive"));
+        }
+        self.cached(format!("block_results/{}", height), |client| {
+            client.block_results(height)
+        })
+    }
+
+    fn abci_query(
+        &self,
+        path: String,
+        data: String,
+        height: Option<u64>,
+        prove: Option<bool>,
+    ) -> RpcResult<Value> {
+        if !self.config.allows_query_path(&path) {
+            return Err(invalid_params("query path not allowed"));
+        }
+        let data = hex::decode(&data).map_err(|_| invalid_params("invalid hex data"))?;
+        let height = height.unwrap_or_default();
+        let prove = prove.unwrap_or_default();
+        let key = format!(
+            "abci_query/{}/{}/{}/{}",
+            path,
+            hex::encode(&data),
+            height,
+            prove
+        );
+        let height: Option<Height> = match height {
+            0 => None,
+            height => Some(height.into()),
+        };
+        self.cached(key, |client| client.query(&path, &data, height, prove))
+    }
+}
+
+/// JSON-RPC handler of the proxy
+#[derive(Clone)]
+pub struct ProxyHandler {
+    pub io: MetaIoHandler<ProxyMeta, RateLimitMiddleware>,
+}
+
+impl ProxyHandler {
+    pub fn new(websocket_url: &str, config: ProxyConfig) -> Result<Self> {
+        let mut io =
+            MetaIoHandler::with_middleware(RateLimitMiddleware::new(config.requests_per_minute));
+        let client = WebsocketRpcClient::new(websocket_url)?;
+        io.extend_with(ProxyRpcImpl::new(client, config).to_delegate());
+        Ok(ProxyHandler { io })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_query_path_allow_list() {
+        let config = ProxyConfig::default();
+        assert!(config.allows_query_path("account"));
+        assert!(config.allows_query_path("/chain.abci.query.v1.Query/StakedState"));
+        assert!(!config.allows_query_path("/chain.abci.query.v1.Query/"));
+        assert!(!config.allows_query_path("snapshot-chunk"));
+        assert!(!config.allows_query_path("storage-metrics"));
+        assert!(!config.allows_query_path("accounts"));
+
+        let config: ProxyConfig =
+            serde_json::from_str(r#"{"allowed_query_paths": ["txquery"]}"#).unwrap();
+        assert!(config.allows_query_path("txquery"));
+        assert!(!config.allows_query_path("account"));
+        assert_eq!(600, config.requests_per_minute);
+    }
+
+    #[test]
+    fn check_rate_limiter() {
+        let limiter = RateLimiter::new(2);
+        let now = Instant::now();
+        assert!(limiter.check_at("a", now));
+        assert!(limiter.check_at("a", now));
+        assert!(!limiter.check_at("a", now));
+        assert!(limiter.check_at("b", now));
+        assert!(limiter.check_at("a", now + Duration::from_secs(30)));
+        assert!(!limiter.check_at("a", now + Duration::from_secs(30)));
+    }
+
+    #[test]
+    fn check_response_cache() {
+        let mut cache = ResponseCache::new(Duration::from_secs(2), 1);
+        let now = Instant::now();
+        cache.insert("a".to_owned(), Value::from(1), now);
+        cache.insert("b".to_owned(), Value::from(2), now);
+        assert_eq!(Some(Value::from(1)), cache.get("a", now));
+        assert_eq!(None, cache.get("b", now));
+        assert_eq!(None, cache.get("a", now + Duration::from_secs(2)));
+
+        let later = now + Duration::from_secs(3);
+        cache.insert("b".to_owned(), Value::from(2), later);
+        assert_eq!(Some(Value::from(2)), cache.get("b", later));
+    }
+}
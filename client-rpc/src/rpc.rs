@@ -1,8 +1,16 @@
+pub mod audit_rpc;
+pub mod health_rpc;
 pub mod info_rpc;
+pub mod inheritance_rpc;
+pub mod invoice_rpc;
+pub mod job_rpc;
 #[cfg(feature = "experimental")]
 pub mod multisig_rpc;
+pub mod recurring_payment_rpc;
 pub mod staking_rpc;
+pub mod subscription_rpc;
 pub mod sync_rpc;
 pub mod sync_worker;
 pub mod transaction_rpc;
+pub mod vault_rpc;
 pub mod wallet_rpc;
@@ -0,0 +1,109 @@
+use jsonrpc_core::Result;
+use jsonrpc_derive::rpc;
+
+use chain_core::tx::data::address::ExtendedAddr;
+use client_common::Storage;
+use client_core::service::{AuditAccount, AuditAddress, AuditService};
+
+use crate::{rpc_error_from_string, to_rpc_error};
+
+/// Receive addresses derived from the exported extended public keys of HD wallets (see
+/// `wallet_exportAccountXpub`), without any wallet or private key on the server
+#[rpc(server)]
+pub trait AuditRpc: Send + Sync {
+    #[rpc(name = "audit_addAccount")]
+    fn add_account(
+        &self,
+        name: String,
+        xpub: String,
+        start_index: Option<u32>,
+    ) -> Result<AuditAccount>;
+
+    #[rpc(name = "audit_getAccount")]
+    fn get_account(&self, name: String) -> Result<AuditAccount>;
+
+    #[rpc(name = "audit_nextAddress")]
+    fn next_address(&self, name: String) -> Result<AuditAddress>;
+
+    #[rpc(name = "audit_markUsed")]
+    fn mark_used(&self, name: String, address: String) -> Result<AuditAccount>;
+
+    #[rpc(name = "audit_listAddresses")]
+    fn list_addresses(&self, name: String) -> Result<Vec<AuditAddress>>;
+
+    #[rpc(name = "audit_removeAccount")]
+    fn remove_account(&self, name: String) -> Result<()>;
+}
+
+pub struct AuditRpcImpl<S>
+where
+    S: Storage,
+{
+    storage: S,
+    service: AuditService<S>,
+}
+
+impl<S> AuditRpcImpl<S>
+where
+    S: Storage,
+{
+    pub fn new(storage: S) -> Self {
+        AuditRpcImpl {
+            service: AuditService::new(storage.clone()),
+            storage,
+        }
+    }
+}
+
+impl<S> AuditRpc for AuditRpcImpl<S>
+where
+    S: Storage + 'static,
+{
+    fn add_account(
+        &self,
+        name: String,
+        xpub: String,
+        start_index: Option<u32>,
+    ) -> Result<AuditAccount> {
+        let account = self
+            .service
+            .add_account(&name, &xpub, start_index.unwrap_or_default())
+            .map_err(to_rpc_error)?;
+        self.storage.flush().map_err(to_rpc_error)?;
+        Ok(account)
+    }
+
+    fn get_account(&self, name: String) -> Result<AuditAccount> {
+        self.service.get_account(&name).map_err(to_rpc_error)
+    }
+
+    fn next_address(&self, name: String) -> Result<AuditAddress> {
+        let address = self
+            .service
+            .next_receive_address(&name)
+            .map_err(to_rpc_error)?;
+        self.storage.flush().map_err(to_rpc_error)?;
+        Ok(address)
+    }
+
+    fn mark_used(&self, name: String, address: String) -> Result<AuditAccount> {
+        let address = address
+            .parse::<ExtendedAddr>()
+            .map_err(|err| rpc_error_from_string(format!("{}", err)))?;
+        let account = self
+            .service
+            .mark_used(&name, &address)
+            .map_err(to_rpc_error)?;
+        self.storage.flush().map_err(to_rpc_error)?;
+        Ok(account)
+    }
+
+    fn list_addresses(&self, name: String) -> Result<Vec<AuditAddress>> {
+        self.service.get_addresses(&name).map_err(to_rpc_error)
+    }
+
+    fn remove_account(&self, name: String) -> Result<()> {
+        self.service.delete_account(&name).map_err(to_rpc_error)?;
+        self.storage.flush().map_err(to_rpc_error)
+    }
+}
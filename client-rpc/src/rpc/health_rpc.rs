@@ -0,0 +1,320 @@
+use std::collections::BTreeMap;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use jsonrpc_core::Result;
+use jsonrpc_derive::rpc;
+use serde::{Deserialize, Serialize};
+
+use client_common::cipher::EndpointStats;
+use client_common::tendermint::types::Time;
+use client_common::tendermint::Client;
+use client_common::{Result as CommonResult, TransactionObfuscation};
+use client_core::WalletClient;
+
+/// Maximum number of blocks the least synchronized wallet may lag behind the node
+pub const MAX_SYNC_BACKLOG: u64 = 100;
+/// Maximum age (in seconds) of the latest block of the node
+pub const MAX_BLOCK_AGE: u64 = 60;
+/// Maximum age (in seconds) of the latest valid attestation of the tx-query enclaves
+pub const MAX_ATTESTATION_AGE: u64 = 3600;
+/// Timeout of the tx-query reachability check
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Synchronized block heights of the served wallets (fails if the storage is not accessible)
+pub type SyncedHeights = Arc<dyn Fn() -> CommonResult<Vec<u64>> + Send + Sync>;
+
+/// Reads the synchronized block heights of all the wallets of the client
+pub fn wallet_synced_heights<T: WalletClient + 'static>(client: T) -> SyncedHeights {
+    Arc::new(move || {
+        client
+            .wallets()?
+            .iter()
+            .map(|name| {
+                client
+                    .get_sync_state(name)
+                    .map(|state| state.last_block_height)
+            })
+            .collect()
+    })
+}
+
+/// Status of a check (and the overall status, which is the worst one of the checks)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+/// Result of a single dependency check
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CheckResult {
+    pub status: HealthStatus,
+    /// reason of the warning or failure
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// the measured value (block height, lag in blocks or age in seconds)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<u64>,
+}
+
+impl CheckResult {
+    fn pass(value: Option<u64>) -> Self {
+        CheckResult {
+            status: HealthStatus::Pass,
+            message: None,
+            value,
+        }
+    }
+
+    fn warn(message: String, value: Option<u64>) -> Self {
+        CheckResult {
+            status: HealthStatus::Warn,
+            message: Some(message),
+            value,
+        }
+    }
+
+    fn fail(message: String) -> Self {
+        CheckResult {
+            status: HealthStatus::Fail,
+            message: Some(message),
+            value: None,
+        }
+    }
+}
+
+/// Health (liveness) or readiness report: `storage`, `node`, `node_lag`, `tx_query`
+/// and `sync_backlog` checks
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HealthReport {
+    pub status: HealthStatus,
+    pub checks: BTreeMap<String, CheckResult>,
+}
+
+/// Checks the dependencies of the served wallets
+#[derive(Clone)]
+pub struct HealthChecker<C>
+where
+    C: Client + TransactionObfuscation,
+{
+    client: C,
+    synced_heights: Vec<SyncedHeights>,
+}
+
+impl<C> HealthChecker<C>
+where
+    C: Client + TransactionObfuscation,
+{
+    pub fn new(client: C, synced_heights: Vec<SyncedHeights>) -> Self {
+        HealthChecker {
+            client,
+            synced_heights,
+        }
+    }
+
+    /// The readiness report fails if any dependency fails; in the health (liveness) report,
+    /// the failed node and tx-query checks are only warnings (restarting the wallet server
+    /// doesn't fix them), so it only fails if the storage is not accessible
+    pub fn report(&self, liveness: bool) -> HealthReport {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+        let mut checks = BTreeMap::new();
+
+        let synced_heights = self
+            .synced_heights
+            .iter()
+            .map(|heights| heights())
+            .collect::<CommonResult<Vec<_>>>()
+            .map(|heights| heights.into_iter().flatten().min());
+        let storage = match &synced_heights {
+            Ok(_) => CheckResult::pass(None),
+            Err(e) => CheckResult::fail(format!("storage is not accessible: {}", e)),
+        };
+        checks.insert("storage".to_owned(), storage);
+
+        let node_height = match self.client.status() {
+            Ok(status) => {
+                let height = status.sync_info.latest_block_height.value();
+                checks.insert("node".to_owned(), CheckResult::pass(Some(height)));
+                let block_age = now.saturating_sub(to_secs(status.sync_info.latest_block_time));
+                let node_lag = if status.sync_info.catching_up {
+                    CheckResult::fail("node is catching up".to_owned())
+                } else if block_age > MAX_BLOCK_AGE {
+                    CheckResult::warn(
+                        format!("latest block is {} seconds old", block_age),
+                        Some(block_age),
+                    )
+                } else {
+                    CheckResult::pass(Some(block_age))
+                };
+                checks.insert("node_lag".to_owned(), node_lag);
+                Some(height)
+            }
+            Err(e) => {
+                let message = format!("node is not reachable: {}", e);
+                checks.insert("node".to_owned(), CheckResult::fail(message.clone()));
+                checks.insert("node_lag".to_owned(), CheckResult::fail(message));
+                None
+            }
+        };
+
+        let tx_query = match self.client.tx_query_stats() {
+            Ok(stats) => check_tx_query(&stats, now),
+            Err(e) => CheckResult::fail(format!("tx-query is not available: {}", e)),
+        };
+        checks.insert("tx_query".to_owned(), tx_query);
+
+        let sync_backlog = match (node_height, synced_heights) {
+            (Some(node_height), Ok(Some(synced_height))) => {
+                let backlog = node_height.saturating_sub(synced_height);
+                if backlog > MAX_SYNC_BACKLOG {
+                    CheckResult::warn(
+                        format!("a wallet is {} blocks behind the node", backlog),
+                        Some(backlog),
+                    )
+                } else {
+                    CheckResult::pass(Some(backlog))
+                }
+            }
+            // no wallets
+            (Some(_), Ok(None)) => CheckResult::pass(Some(0)),
+            _ => CheckResult::warn("sync backlog is unknown".to_owned(), None),
+        };
+        checks.insert("sync_backlog".to_owned(), sync_backlog);
+
+        if liveness {
+            for (name, check) in checks.iter_mut() {
+                if name != "storage" && check.status == HealthStatus::Fail {
+                    check.status = HealthStatus::Warn;
+                }
+            }
+        }
+        let status = checks
+            .values()
+            .map(|check| check.status)
+            .max()
+            .unwrap_or(HealthStatus::Pass);
+        HealthReport { status, checks }
+    }
+}
+
+/// At least one endpoint should be reachable, and the latest attestation should be recent
+fn check_tx_query(stats: &[EndpointStats], now: u64) -> CheckResult {
+    if stats.is_empty() {
+        // the transactions are not obfuscated by the tx-query enclaves
+        return CheckResult::pass(None);
+    }
+    let reachable = stats.iter().any(|endpoint| {
+        endpoint
+            .address
+            .to_socket_addrs()
+            .map(|mut addrs| {
+                addrs.any(|addr| TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT).is_ok())
+            })
+            .unwrap_or(false)
+    });
+    if !reachable {
+        return CheckResult::fail("no tx-query endpoint is reachable".to_owned());
+    }
+    match stats
+        .iter()
+        .filter_map(|endpoint| endpoint.last_success_at)
+        .max()
+    {
+        Some(attested_at) => {
+            let age = now.saturating_sub(attested_at);
+            if age > MAX_ATTESTATION_AGE {
+                CheckResult::warn(
+                    format!("latest tx-query attestation is {} seconds old", age),
+                    Some(age),
+                )
+            } else {
+                CheckResult::pass(Some(age))
+            }
+        }
+        None => CheckResult::warn("tx-query was not attested yet".to_owned(), None),
+    }
+}
+
+fn to_secs(time: Time) -> u64 {
+    time.duration_since(Time::unix_epoch())
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+#[rpc(server)]
+pub trait HealthRpc: Send + Sync {
+    #[rpc(name = "health")]
+    fn health(&self) -> Result<HealthReport>;
+
+    #[rpc(name = "ready")]
+    fn ready(&self) -> Result<HealthReport>;
+}
+
+pub struct HealthRpcImpl<C>
+where
+    C: Client + TransactionObfuscation,
+{
+    checker: HealthChecker<C>,
+}
+
+impl<C> HealthRpcImpl<C>
+where
+    C: Client + TransactionObfuscation,
+{
+    pub fn new(checker: HealthChecker<C>) -> Self {
+        HealthRpcImpl { checker }
+    }
+}
+
+impl<C> HealthRpc for HealthRpcImpl<C>
+where
+    C: Client + TransactionObfuscation + 'static,
+{
+    fn health(&self) -> Result<HealthReport> {
+        Ok(self.checker.report(true))
+    }
+
+    fn ready(&self) -> Result<HealthReport> {
+        Ok(self.checker.report(false))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_tx_query_freshness() {
+        assert_eq!(HealthStatus::Pass, check_tx_query(&[], 100).status);
+
+        // nothing listens on the port
+        let unreachable = EndpointStats {
+            address: "127.0.0.1:1".to_owned(),
+            last_success_at: Some(100),
+            ..Default::default()
+        };
+        assert_eq!(
+            HealthStatus::Fail,
+            check_tx_query(&[unreachable], 100).status
+        );
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let reachable = EndpointStats {
+            address: listener.local_addr().unwrap().to_string(),
+            last_success_at: Some(100),
+            ..Default::default()
+        };
+        let fresh = check_tx_query(&[reachable.clone()], 110);
+        assert_eq!(HealthStatus::Pass, fresh.status);
+        assert_eq!(Some(10), fresh.value);
+        let stale = check_tx_query(&[reachable], 100 + MAX_ATTESTATION_AGE + 1);
+        assert_eq!(HealthStatus::Warn, stale.status);
+    }
+}
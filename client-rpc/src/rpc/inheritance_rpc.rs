@@ -0,0 +1,179 @@
+use std::collections::BTreeSet;
+use std::str::FromStr;
+
+use jsonrpc_core::Result;
+use jsonrpc_derive::rpc;
+use serde::Deserialize;
+
+use chain_core::init::coin::Coin;
+use chain_core::tx::data::address::ExtendedAddr;
+use client_common::{PublicKey, Result as CommonResult};
+use client_core::types::{Heir, InheritanceEvent, InheritancePlan};
+use client_core::wallet::WalletRequest;
+use client_core::WalletClient;
+
+use crate::{rpc_error_from_string, to_rpc_error};
+
+/// Heir of an inheritance plan (with a bech32 address)
+#[derive(Debug, Clone, Deserialize)]
+pub struct HeirRequest {
+    pub address: String,
+    pub share: u64,
+}
+
+#[rpc(server)]
+pub trait InheritanceRpc: Send + Sync {
+    #[rpc(name = "inheritance_create")]
+    fn create(
+        &self,
+        request: WalletRequest,
+        heirs: Vec<HeirRequest>,
+        amount: Coin,
+        refresh_period: u64,
+        reminder_period: u64,
+        view_keys: Vec<String>,
+    ) -> Result<InheritancePlan>;
+
+    #[rpc(name = "inheritance_list")]
+    fn list(&self, request: WalletRequest) -> Result<Vec<InheritancePlan>>;
+
+    #[rpc(name = "inheritance_events")]
+    fn events(
+        &self,
+        request: WalletRequest,
+        from_sequence: Option<u64>,
+    ) -> Result<Vec<InheritanceEvent>>;
+
+    #[rpc(name = "inheritance_refresh")]
+    fn refresh(&self, request: WalletRequest, id: u64) -> Result<InheritancePlan>;
+
+    #[rpc(name = "inheritance_cancel")]
+    fn cancel(&self, request: WalletRequest, id: u64) -> Result<String>;
+
+    #[rpc(name = "inheritance_exportPackage")]
+    fn export_package(&self, request: WalletRequest, id: u64) -> Result<String>;
+
+    #[rpc(name = "inheritance_broadcastPackage")]
+    fn broadcast_package(&self, package: String) -> Result<String>;
+}
+
+pub struct InheritanceRpcImpl<T>
+where
+    T: WalletClient,
+{
+    client: T,
+    network_id: u8,
+}
+
+impl<T> InheritanceRpcImpl<T>
+where
+    T: WalletClient,
+{
+    pub fn new(client: T, network_id: u8) -> Self {
+        InheritanceRpcImpl { client, network_id }
+    }
+}
+
+impl<T> InheritanceRpc for InheritanceRpcImpl<T>
+where
+    T: WalletClient + 'static,
+{
+    fn create(
+        &self,
+        request: WalletRequest,
+        heirs: Vec<HeirRequest>,
+        amount: Coin,
+        refresh_period: u64,
+        reminder_period: u64,
+        view_keys: Vec<String>,
+    ) -> Result<InheritancePlan> {
+        let heirs = heirs
+            .into_iter()
+            .map(|heir| {
+                Ok(Heir {
+                    address: parse_address(&heir.address)?,
+                    share: heir.share,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let mut view_keys = view_keys
+            .iter()
+            .map(|view_key| PublicKey::from_str(view_key))
+            .collect::<CommonResult<BTreeSet<PublicKey>>>()
+            .map_err(to_rpc_error)?;
+        let plan = self
+            .client
+            .new_inheritance_plan(
+                &request.name,
+                &request.enckey,
+                heirs,
+                amount,
+                refresh_period,
+                reminder_period,
+                &mut view_keys,
+                self.network_id,
+            )
+            .map_err(to_rpc_error)?;
+        self.client.flush_database().map_err(to_rpc_error)?;
+        Ok(plan)
+    }
+
+    fn list(&self, request: WalletRequest) -> Result<Vec<InheritancePlan>> {
+        self.client
+            .inheritance_plans(&request.name, &request.enckey)
+            .map_err(to_rpc_error)
+    }
+
+    fn events(
+        &self,
+        request: WalletRequest,
+        from_sequence: Option<u64>,
+    ) -> Result<Vec<InheritanceEvent>> {
+        self.client
+            .inheritance_events(
+                &request.name,
+                &request.enckey,
+                from_sequence.unwrap_or_default(),
+            )
+            .map_err(to_rpc_error)
+    }
+
+    fn refresh(&self, request: WalletRequest, id: u64) -> Result<InheritancePlan> {
+        let plan = self
+            .client
+            .refresh_inheritance_plan(&request.name, &request.enckey, id, self.network_id)
+            .map_err(to_rpc_error)?;
+        self.client.flush_database().map_err(to_rpc_error)?;
+        Ok(plan)
+    }
+
+    fn cancel(&self, request: WalletRequest, id: u64) -> Result<String> {
+        let tx_id = self
+            .client
+            .cancel_inheritance_plan(&request.name, &request.enckey, id, self.network_id)
+            .map_err(to_rpc_error)?;
+        self.client.flush_database().map_err(to_rpc_error)?;
+        Ok(hex::encode(tx_id))
+    }
+
+    fn export_package(&self, request: WalletRequest, id: u64) -> Result<String> {
+        self.client
+            .export_inheritance_package(&request.name, &request.enckey, id)
+            .map(hex::encode)
+            .map_err(to_rpc_error)
+    }
+
+    fn broadcast_package(&self, package: String) -> Result<String> {
+        let package = hex::decode(&package).map_err(to_rpc_error)?;
+        self.client
+            .broadcast_inheritance_package(&package)
+            .map(hex::encode)
+            .map_err(to_rpc_error)
+    }
+}
+
+fn parse_address(address: &str) -> Result<ExtendedAddr> {
+    address
+        .parse::<ExtendedAddr>()
+        .map_err(|err| rpc_error_from_string(format!("{}", err)))
+}
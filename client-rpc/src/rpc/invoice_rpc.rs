@@ -0,0 +1,114 @@
+use jsonrpc_core::Result;
+use jsonrpc_derive::rpc;
+
+use chain_core::init::coin::Coin;
+use client_core::types::{Invoice, InvoiceEvent};
+use client_core::wallet::WalletRequest;
+use client_core::WalletClient;
+
+use crate::to_rpc_error;
+
+#[rpc(server)]
+pub trait InvoiceRpc: Send + Sync {
+    #[rpc(name = "invoice_create")]
+    fn create(
+        &self,
+        request: WalletRequest,
+        amount: Coin,
+        expiry: u64,
+        memo: Option<String>,
+    ) -> Result<Invoice>;
+
+    #[rpc(name = "invoice_get")]
+    fn get(&self, request: WalletRequest, id: u64) -> Result<Invoice>;
+
+    #[rpc(name = "invoice_list")]
+    fn list(&self, request: WalletRequest) -> Result<Vec<Invoice>>;
+
+    #[rpc(name = "invoice_cancel")]
+    fn cancel(&self, request: WalletRequest, id: u64) -> Result<Invoice>;
+
+    #[rpc(name = "invoice_events")]
+    fn events(
+        &self,
+        request: WalletRequest,
+        from_sequence: Option<u64>,
+    ) -> Result<Vec<InvoiceEvent>>;
+}
+
+pub struct InvoiceRpcImpl<T>
+where
+    T: WalletClient,
+{
+    client: T,
+}
+
+impl<T> InvoiceRpcImpl<T>
+where
+    T: WalletClient,
+{
+    pub fn new(client: T) -> Self {
+        InvoiceRpcImpl { client }
+    }
+}
+
+impl<T> InvoiceRpc for InvoiceRpcImpl<T>
+where
+    T: WalletClient + 'static,
+{
+    fn create(
+        &self,
+        request: WalletRequest,
+        amount: Coin,
+        expiry: u64,
+        memo: Option<String>,
+    ) -> Result<Invoice> {
+        let invoice = self
+            .client
+            .create_invoice(
+                &request.name,
+                &request.enckey,
+                amount,
+                expiry,
+                memo.unwrap_or_default(),
+            )
+            .map_err(to_rpc_error)?;
+        self.client.flush_database().map_err(to_rpc_error)?;
+        Ok(invoice)
+    }
+
+    fn get(&self, request: WalletRequest, id: u64) -> Result<Invoice> {
+        self.client
+            .invoice(&request.name, &request.enckey, id)
+            .map_err(to_rpc_error)
+    }
+
+    fn list(&self, request: WalletRequest) -> Result<Vec<Invoice>> {
+        self.client
+            .invoices(&request.name, &request.enckey)
+            .map_err(to_rpc_error)
+    }
+
+    fn cancel(&self, request: WalletRequest, id: u64) -> Result<Invoice> {
+        let invoice = self
+            .client
+            .cancel_invoice(&request.name, &request.enckey, id)
+            .map_err(to_rpc_error)?;
+        self.client.flush_database().map_err(to_rpc_error)?;
+        Ok(invoice)
+    }
+
+    fn events(
+        &self,
+        request: WalletRequest,
+        from_sequence: Option<u64>,
+    ) -> Result<Vec<InvoiceEvent>> {
+        self.client
+            .invoice_events(
+                &request.name,
+                &request.enckey,
+                from_sequence.unwrap_or_default(),
+            )
+            .map_err(to_rpc_error)
+    }
+}
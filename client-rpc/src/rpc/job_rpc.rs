@@ -0,0 +1,542 @@
+use std::collections::{BTreeSet, HashSet, VecDeque};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use jsonrpc_core::Result;
+use jsonrpc_derive::rpc;
+use secstr::SecUtf8;
+
+use chain_core::state::account::StakedStateAddress;
+use chain_core::tx::data::address::ExtendedAddr;
+use client_common::{Error, ErrorKind, PublicKey, Result as CommonResult, SecKey, Storage};
+use client_core::service::{JobService, ReindexProgress};
+use client_core::types::{Job, JobKind, JobProgress, JobStatus};
+use client_core::wallet::syncer::ProgressReport;
+use client_core::wallet::{CreateWalletRequest, WalletRequest};
+use client_core::{Mnemonic, WalletClient};
+use client_network::network_ops::StakeMigrationStatus;
+
+use crate::{rpc_error_from_string, to_rpc_error};
+
+/// Minimum time between two persisted progress updates of a running job
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Time between two steps of a stake migration job (waiting for its transactions to be applied
+/// and for the unbonding period)
+const STAKE_MIGRATION_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Synchronizes the wallet (after resetting its state if the flag is set), the progress callback
+/// stops the sync when it returns `false`
+pub type RunSync = Arc<
+    dyn Fn(&WalletRequest, bool, &mut dyn FnMut(ProgressReport) -> bool) -> CommonResult<()>
+        + Send
+        + Sync,
+>;
+
+/// Restores the wallet from its mnemonic for a recovery job (or only checks the passphrase if
+/// its recovery is already in progress), returns the enckey
+pub type StartRecovery =
+    Arc<dyn Fn(&str, &SecUtf8, &Mnemonic) -> CommonResult<SecKey> + Send + Sync>;
+
+/// Scans the chain for the recovery of the wallet, the progress callback stops the scan when it
+/// returns `false`
+pub type RunRecovery = Arc<
+    dyn Fn(&WalletRequest, &mut dyn FnMut(ProgressReport) -> bool) -> CommonResult<()>
+        + Send
+        + Sync,
+>;
+
+/// Synchronizes the wallet and performs the next step of the migration of the stake from the
+/// first address to the second one (starting it, unless the migration of the first address is
+/// in progress or the flag is set), returns the number of steps done
+pub type RunStakeMigration = Arc<
+    dyn Fn(&WalletRequest, &StakedStateAddress, &StakedStateAddress, bool) -> CommonResult<u64>
+        + Send
+        + Sync,
+>;
+
+/// Operations of the jobs which are not performed by the wallet client alone
+#[derive(Clone)]
+pub struct JobHandlers {
+    /// Runs sync jobs
+    pub sync: RunSync,
+    /// Starts recovery jobs
+    pub start_recovery: StartRecovery,
+    /// Runs recovery jobs
+    pub recovery: RunRecovery,
+    /// Runs the steps of stake migration jobs
+    pub stake_migration: RunStakeMigration,
+}
+
+/// Long-running wallet operations (sync, reindex, sweep, stake migration and recovery) run one
+/// after another in the background, their status and progress are polled with `job_get`
+/// instead of blocking the RPC call. Stake migrations wait for the unbonding period, so each
+/// one is run on its own thread instead of holding up the queue.
+#[rpc(server)]
+pub trait JobRpc: Send + Sync {
+    #[rpc(name = "job_start")]
+    fn start(&self, request: WalletRequest, kind: JobKind) -> Result<Job>;
+
+    #[rpc(name = "job_recover")]
+    fn recover(&self, request: CreateWalletRequest, mnemonic: Mnemonic) -> Result<Job>;
+
+    #[rpc(name = "job_list")]
+    fn list(&self, request: WalletRequest) -> Result<Vec<Job>>;
+
+    #[rpc(name = "job_get")]
+    fn get(&self, request: WalletRequest, id: u64) -> Result<Job>;
+
+    #[rpc(name = "job_cancel")]
+    fn cancel(&self, request: WalletRequest, id: u64) -> Result<Job>;
+
+    #[rpc(name = "job_resume")]
+    fn resume(&self, request: WalletRequest, id: u64) -> Result<Job>;
+}
+
+/// Runs the queued jobs (the queue with the enckeys is only kept in memory, so the jobs
+/// interrupted by a restart have to be resumed with `job_resume`)
+struct JobRunner<S: Storage, T: WalletClient> {
+    client: T,
+    service: JobService<S>,
+    network_id: u8,
+    handlers: JobHandlers,
+    queue: Mutex<VecDeque<(u64, WalletRequest)>>,
+    cancelled: Mutex<HashSet<u64>>,
+    running: AtomicBool,
+}
+
+pub struct JobRpcImpl<S: Storage, T: WalletClient> {
+    runner: Arc<JobRunner<S, T>>,
+}
+
+impl<S, T> JobRpcImpl<S, T>
+where
+    S: Storage + 'static,
+    T: WalletClient + 'static,
+{
+    pub fn new(storage: S, client: T, network_id: u8, handlers: JobHandlers) -> CommonResult<Self> {
+        let service = JobService::new(storage);
+        // the jobs of the previous run were stopped with the process
+        for job in service.interrupt_unfinished(now())? {
+            log::info!(
+                "job {} ({:?}) of wallet {} was interrupted",
+                job.id,
+                job.kind,
+                job.wallet_name
+            );
+        }
+        Ok(JobRpcImpl {
+            runner: Arc::new(JobRunner {
+                client,
+                service,
+                network_id,
+                handlers,
+                queue: Default::default(),
+                cancelled: Default::default(),
+                running: AtomicBool::new(false),
+            }),
+        })
+    }
+
+    /// Returns the job of the wallet
+    fn wallet_job(&self, request: &WalletRequest, id: u64) -> Result<Job> {
+        match self.runner.service.get_job(id).map_err(to_rpc_error)? {
+            Some(job) if job.wallet_name == request.name => Ok(job),
+            _ => Err(rpc_error_from_string(format!(
+                "Job with id {} not found",
+                id
+            ))),
+        }
+    }
+
+    /// Checks the enckey before it's kept in the queue
+    fn check_enckey(&self, request: &WalletRequest) -> Result<()> {
+        self.runner
+            .client
+            .balance(&request.name, &request.enckey)
+            .map(|_| ())
+            .map_err(to_rpc_error)
+    }
+
+    fn enqueue(&self, job: &Job, request: WalletRequest) -> Result<()> {
+        self.runner.client.flush_database().map_err(to_rpc_error)?;
+        if let JobKind::StakeMigration { .. } = job.kind {
+            let runner = self.runner.clone();
+            let id = job.id;
+            thread::spawn(move || runner.run_one(id, &request));
+            return Ok(());
+        }
+
+        let mut queue = self.runner.queue.lock().expect("job queue lock");
+        queue.push_back((job.id, request));
+
+        // the runner stops (under the lock) once the queue is empty
+        if !self.runner.running.swap(true, Ordering::SeqCst) {
+            let runner = self.runner.clone();
+            thread::spawn(move || runner.run());
+        }
+        Ok(())
+    }
+}
+
+impl<S, T> JobRpc for JobRpcImpl<S, T>
+where
+    S: Storage + 'static,
+    T: WalletClient + 'static,
+{
+    fn start(&self, request: WalletRequest, kind: JobKind) -> Result<Job> {
+        match &kind {
+            JobKind::Reindex { threads: 0 } => {
+                return Err(rpc_error_from_string(
+                    "Number of reindexing threads should be greater than zero".to_owned(),
+                ));
+            }
+            JobKind::Sweep {
+                to_address,
+                view_keys,
+            } => {
+                parse_sweep(to_address, view_keys).map_err(to_rpc_error)?;
+            }
+            JobKind::StakeMigration {
+                from_address,
+                to_address,
+            } if from_address == to_address => {
+                return Err(rpc_error_from_string(
+                    "Stake can only be migrated to a different staking address".to_owned(),
+                ));
+            }
+            JobKind::Recovery => {
+                return Err(rpc_error_from_string(
+                    "Recovery jobs are started with job_recover".to_owned(),
+                ));
+            }
+            _ => {}
+        }
+        self.check_enckey(&request)?;
+        let job = self
+            .runner
+            .service
+            .add_job(&request.name, kind, now())
+            .map_err(to_rpc_error)?;
+        self.enqueue(&job, request)?;
+        Ok(job)
+    }
+
+    fn recover(&self, request: CreateWalletRequest, mnemonic: Mnemonic) -> Result<Job> {
+        let enckey =
+            (self.runner.handlers.start_recovery)(&request.name, &request.passphrase, &mnemonic);
+        mnemonic.zeroize();
+        let enckey = enckey.map_err(to_rpc_error)?;
+        let job = self
+            .runner
+            .service
+            .add_job(&request.name, JobKind::Recovery, now())
+            .map_err(to_rpc_error)?;
+        self.enqueue(
+            &job,
+            WalletRequest {
+                name: request.name,
+                enckey,
+            },
+        )?;
+        Ok(job)
+    }
+
+    fn list(&self, request: WalletRequest) -> Result<Vec<Job>> {
+        self.check_enckey(&request)?;
+        self.runner
+            .service
+            .get_jobs(Some(&request.name))
+            .map_err(to_rpc_error)
+    }
+
+    fn get(&self, request: WalletRequest, id: u64) -> Result<Job> {
+        self.check_enckey(&request)?;
+        self.wallet_job(&request, id)
+    }
+
+    fn cancel(&self, request: WalletRequest, id: u64) -> Result<Job> {
+        self.check_enckey(&request)?;
+        let job = self.wallet_job(&request, id)?;
+        match (job.status, &job.kind) {
+            (JobStatus::Queued, _) => {
+                let mut queue = self.runner.queue.lock().expect("job queue lock");
+                queue.retain(|(queued_id, _)| *queued_id != id);
+                let job = self
+                    .runner
+                    .service
+                    .set_status(id, JobStatus::Cancelled, None, now())
+                    .map_err(to_rpc_error)?;
+                self.runner.client.flush_database().map_err(to_rpc_error)?;
+                Ok(job)
+            }
+            (JobStatus::Running, JobKind::Reindex { .. }) => Err(rpc_error_from_string(
+                "Reindexing can not be cancelled once it's started".to_owned(),
+            )),
+            (JobStatus::Running, JobKind::Sweep { .. }) => Err(rpc_error_from_string(
+                "Sweeping can not be cancelled once it's started".to_owned(),
+            )),
+            (JobStatus::Running, _) => {
+                // the sync (or recovery) stops at its next progress report, the stake migration
+                // before its next step
+                self.runner
+                    .cancelled
+                    .lock()
+                    .expect("cancelled jobs lock")
+                    .insert(id);
+                Ok(job)
+            }
+            (status, _) => Err(rpc_error_from_string(format!(
+                "Job {} is already finished ({:?})",
+                id, status
+            ))),
+        }
+    }
+
+    fn resume(&self, request: WalletRequest, id: u64) -> Result<Job> {
+        self.check_enckey(&request)?;
+        self.wallet_job(&request, id)?;
+        let job = self
+            .runner
+            .service
+            .requeue(id, now())
+            .map_err(to_rpc_error)?;
+        self.enqueue(&job, request)?;
+        Ok(job)
+    }
+}
+
+impl<S, T> JobRunner<S, T>
+where
+    S: Storage,
+    T: WalletClient,
+{
+    /// Runs the queued jobs one after another (until the queue is empty)
+    fn run(&self) {
+        loop {
+            let (id, request) = {
+                let mut queue = self.queue.lock().expect("job queue lock");
+                match queue.pop_front() {
+                    Some(queued) => queued,
+                    None => {
+                        self.running.store(false, Ordering::SeqCst);
+                        return;
+                    }
+                }
+            };
+            self.run_one(id, &request);
+        }
+    }
+
+    fn run_one(&self, id: u64, request: &WalletRequest) {
+        if let Err(e) = self.run_job(id, request) {
+            log::error!("unable to update job {}: {}", id, e);
+        }
+        if let Err(e) = self.client.flush_database() {
+            log::error!("unable to flush the database: {}", e);
+        }
+    }
+
+    fn run_job(&self, id: u64, request: &WalletRequest) -> CommonResult<()> {
+        let job = self
+            .service
+            .set_status(id, JobStatus::Running, None, now())?;
+        log::info!(
+            "job {} ({:?}) of wallet {} started",
+            id,
+            job.kind,
+            request.name
+        );
+
+        let mut progress = job.progress;
+        let mut reported_at = Instant::now();
+        let mut report = |progress: JobProgress, force: bool| {
+            if force || reported_at.elapsed() >= PROGRESS_INTERVAL {
+                reported_at = Instant::now();
+                if let Err(e) = self.service.update_progress(id, progress, now()) {
+                    log::warn!("unable to update the progress of job {}: {}", id, e);
+                }
+            }
+        };
+
+        let mut on_sync_report = |report_item: ProgressReport| {
+            match report_item {
+                ProgressReport::Init {
+                    start_block_height,
+                    finish_block_height,
+                    ..
+                } => {
+                    progress = JobProgress {
+                        start: start_block_height,
+                        current: start_block_height,
+                        end: finish_block_height,
+                    };
+                    report(progress, true);
+                }
+                ProgressReport::Update {
+                    current_block_height,
+                    ..
+                } => {
+                    progress.current = current_block_height;
+                    report(progress, progress.current == progress.end);
+                }
+            }
+            !self.is_cancelled(id)
+        };
+
+        let result = match job.kind {
+            JobKind::Sync { reset } => (self.handlers.sync)(request, reset, &mut on_sync_report),
+            JobKind::Recovery => (self.handlers.recovery)(request, &mut on_sync_report),
+            JobKind::Reindex { threads } => self
+                .client
+                .reindex_wallet(
+                    &request.name,
+                    &request.enckey,
+                    threads as usize,
+                    &mut |report_item| match report_item {
+                        ReindexProgress::Init { transactions, .. } => {
+                            progress = JobProgress {
+                                start: 0,
+                                current: 0,
+                                end: transactions,
+                            };
+                            report(progress, true);
+                        }
+                        ReindexProgress::Update {
+                            indexed_transactions,
+                            ..
+                        } => {
+                            progress.current = indexed_transactions;
+                            report(progress, progress.current == progress.end);
+                        }
+                    },
+                )
+                .map(|_| ()),
+            JobKind::Sweep {
+                to_address,
+                view_keys,
+            } => parse_sweep(&to_address, &view_keys).and_then(|(address, mut view_keys)| {
+                let (tx_id, amount) = self.client.sweep_to_address(
+                    &request.name,
+                    &request.enckey,
+                    address,
+                    &mut view_keys,
+                    self.network_id,
+                )?;
+                log::info!(
+                    "job {} swept {} to {} (transaction {})",
+                    id,
+                    amount,
+                    to_address,
+                    hex::encode(tx_id)
+                );
+                report(
+                    JobProgress {
+                        start: 0,
+                        current: 1,
+                        end: 1,
+                    },
+                    true,
+                );
+                Ok(())
+            }),
+            JobKind::StakeMigration {
+                from_address,
+                to_address,
+            } => {
+                // a resumed job only continues the migration (even if it was completed)
+                let mut resume = progress.end > 0;
+                loop {
+                    let steps = match (self.handlers.stake_migration)(
+                        request,
+                        &from_address,
+                        &to_address,
+                        resume,
+                    ) {
+                        Ok(steps) => steps,
+                        Err(e) => break Err(e),
+                    };
+                    resume = true;
+                    if progress.end == 0 || steps != progress.current {
+                        progress = JobProgress {
+                            start: 0,
+                            current: steps,
+                            end: StakeMigrationStatus::STEPS,
+                        };
+                        report(progress, true);
+                    }
+                    if steps == StakeMigrationStatus::STEPS {
+                        break Ok(());
+                    }
+                    if !self.wait_unless_cancelled(id, STAKE_MIGRATION_INTERVAL) {
+                        break Err(Error::new(ErrorKind::InvalidInput, "Cancelled by user"));
+                    }
+                }
+            }
+        };
+
+        let cancelled = self
+            .cancelled
+            .lock()
+            .expect("cancelled jobs lock")
+            .remove(&id);
+        let (status, error) = match result {
+            Ok(()) => (JobStatus::Completed, None),
+            Err(_) if cancelled => (JobStatus::Cancelled, None),
+            Err(e) => (JobStatus::Failed, Some(e.to_string())),
+        };
+        log::info!(
+            "job {} of wallet {} finished: {:?}",
+            id,
+            request.name,
+            status
+        );
+        self.service.set_status(id, status, error, now())?;
+        Ok(())
+    }
+
+    fn is_cancelled(&self, id: u64) -> bool {
+        self.cancelled
+            .lock()
+            .expect("cancelled jobs lock")
+            .contains(&id)
+    }
+
+    /// Waits for the given time, returns `false` as soon as the job is cancelled
+    fn wait_unless_cancelled(&self, id: u64, duration: Duration) -> bool {
+        let started_at = Instant::now();
+        while started_at.elapsed() < duration {
+            if self.is_cancelled(id) {
+                return false;
+            }
+            thread::sleep(Duration::from_secs(1));
+        }
+        !self.is_cancelled(id)
+    }
+}
+
+/// Parses the recipient and the view keys of a sweep job
+fn parse_sweep(
+    to_address: &str,
+    view_keys: &[String],
+) -> CommonResult<(ExtendedAddr, BTreeSet<PublicKey>)> {
+    let address = to_address
+        .parse::<ExtendedAddr>()
+        .map_err(|err| Error::new(ErrorKind::InvalidInput, err.to_string()))?;
+    let view_keys = view_keys
+        .iter()
+        .map(|view_key| PublicKey::from_str(view_key))
+        .collect::<CommonResult<BTreeSet<PublicKey>>>()?;
+    Ok((address, view_keys))
+}
+
+/// Seconds since the unix epoch
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
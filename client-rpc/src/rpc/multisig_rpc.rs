@@ -5,6 +5,7 @@ use jsonrpc_derive::rpc;
 use chain_core::common::{H256, HASH_SIZE_256};
 use chain_core::tx::data::Tx;
 use client_common::{Error, ErrorKind, PublicKey, Result as CommonResult, ResultExt, SecKey};
+use client_core::multi_sig::MultiSigMessage;
 use client_core::types::AddressType;
 use client_core::wallet::WalletRequest;
 use client_core::{MultiSigWalletClient, WalletClient};
@@ -74,6 +75,13 @@ pub trait MultiSigRpc: Send + Sync {
         public_key: String,
     ) -> Result<()>;
 
+    /// Next message of current signer for the co-signers (base64)
+    #[rpc(name = "multiSig_exportMessage")]
+    fn export_message(&self, session_id: String, enckey: SecKey) -> Result<String>;
+
+    #[rpc(name = "multiSig_importMessage")]
+    fn import_message(&self, session_id: String, enckey: SecKey, message: String) -> Result<()>;
+
     #[rpc(name = "multiSig_signature")]
     fn signature(&self, session_id: String, enckey: SecKey) -> Result<String>;
 
@@ -255,6 +263,24 @@ where
             .map_err(to_rpc_error)
     }
 
+    fn export_message(&self, session_id: String, enckey: SecKey) -> Result<String> {
+        let session_id = parse_hash_256(session_id).map_err(to_rpc_error)?;
+
+        self.client
+            .export_multi_sig_message(&session_id, &enckey)
+            .map(|message| message.to_string())
+            .map_err(to_rpc_error)
+    }
+
+    fn import_message(&self, session_id: String, enckey: SecKey, message: String) -> Result<()> {
+        let session_id = parse_hash_256(session_id).map_err(to_rpc_error)?;
+        let message = message.parse::<MultiSigMessage>().map_err(to_rpc_error)?;
+
+        self.client
+            .import_multi_sig_message(&session_id, &enckey, &message)
+            .map_err(to_rpc_error)
+    }
+
     fn signature(&self, session_id: String, enckey: SecKey) -> Result<String> {
         let session_id = parse_hash_256(session_id).map_err(to_rpc_error)?;
 
@@ -509,6 +535,10 @@ mod test {
             unreachable!("query")
         }
 
+        fn unconfirmed_txs(&self, _limit: u64) -> Result<Vec<Vec<u8>>> {
+            unreachable!()
+        }
+
         fn query_state_batch<T: Iterator<Item = u64>>(
             &self,
             _heights: T,
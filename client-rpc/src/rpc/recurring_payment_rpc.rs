@@ -0,0 +1,307 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use jsonrpc_core::Result;
+use jsonrpc_derive::rpc;
+use serde::Deserialize;
+
+use chain_core::init::coin::Coin;
+use chain_core::tx::data::address::ExtendedAddr;
+use client_common::{PublicKey, Result as CommonResult};
+use client_core::types::{RecurringPayment, RecurringPaymentEvent, RetryPolicy};
+use client_core::wallet::WalletRequest;
+use client_core::WalletClient;
+
+use crate::rpc::subscription_rpc::SyncWallet;
+use crate::{rpc_error_from_string, to_rpc_error};
+
+/// Time between two runs of the scheduler
+const SCHEDULER_TICK: Duration = Duration::from_secs(10);
+
+/// Schedule of a recurring payment (with a bech32 destination address and times in seconds
+/// since the unix epoch)
+#[derive(Debug, Clone, Deserialize)]
+pub struct RecurringPaymentRequest {
+    pub to_address: String,
+    pub amount: Coin,
+    pub interval: u64,
+    pub start_at: u64,
+    #[serde(default)]
+    pub end_at: Option<u64>,
+    #[serde(default)]
+    pub view_keys: Vec<String>,
+    #[serde(default)]
+    pub retry_policy: Option<RetryPolicy>,
+}
+
+#[rpc(server)]
+pub trait RecurringPaymentRpc: Send + Sync {
+    #[rpc(name = "recurringPayment_create")]
+    fn create(
+        &self,
+        request: WalletRequest,
+        payment: RecurringPaymentRequest,
+    ) -> Result<RecurringPayment>;
+
+    #[rpc(name = "recurringPayment_list")]
+    fn list(&self, request: WalletRequest) -> Result<Vec<RecurringPayment>>;
+
+    #[rpc(name = "recurringPayment_cancel")]
+    fn cancel(&self, request: WalletRequest, id: u64) -> Result<RecurringPayment>;
+
+    #[rpc(name = "recurringPayment_events")]
+    fn events(
+        &self,
+        request: WalletRequest,
+        from_sequence: Option<u64>,
+    ) -> Result<Vec<RecurringPaymentEvent>>;
+
+    #[rpc(name = "recurringPayment_unlock")]
+    fn unlock(&self, request: WalletRequest, duration: u64) -> Result<u64>;
+
+    #[rpc(name = "recurringPayment_lock")]
+    fn lock(&self, request: WalletRequest) -> Result<bool>;
+}
+
+/// Wallet unlocked for the scheduler: its due payments are sent until the expiry time
+struct UnlockSession {
+    request: WalletRequest,
+    expires_at: u64,
+}
+
+/// Sends the due recurring payments of the unlocked wallets (the unlock sessions are only
+/// kept in memory, so the wallets have to be unlocked again after a restart)
+struct Scheduler<T: WalletClient> {
+    client: T,
+    network_id: u8,
+    sync_wallet: SyncWallet,
+    sessions: Mutex<HashMap<String, UnlockSession>>,
+    running: AtomicBool,
+}
+
+pub struct RecurringPaymentRpcImpl<T: WalletClient> {
+    scheduler: Arc<Scheduler<T>>,
+}
+
+impl<T> RecurringPaymentRpcImpl<T>
+where
+    T: WalletClient + 'static,
+{
+    pub fn new(client: T, network_id: u8, sync_wallet: SyncWallet) -> Self {
+        RecurringPaymentRpcImpl {
+            scheduler: Arc::new(Scheduler {
+                client,
+                network_id,
+                sync_wallet,
+                sessions: Default::default(),
+                running: AtomicBool::new(false),
+            }),
+        }
+    }
+}
+
+impl<T> RecurringPaymentRpc for RecurringPaymentRpcImpl<T>
+where
+    T: WalletClient + 'static,
+{
+    fn create(
+        &self,
+        request: WalletRequest,
+        payment: RecurringPaymentRequest,
+    ) -> Result<RecurringPayment> {
+        let to_address = payment
+            .to_address
+            .parse::<ExtendedAddr>()
+            .map_err(|err| rpc_error_from_string(format!("{}", err)))?;
+        let view_keys = payment
+            .view_keys
+            .iter()
+            .map(|view_key| PublicKey::from_str(view_key))
+            .collect::<CommonResult<Vec<PublicKey>>>()
+            .map_err(to_rpc_error)?;
+        let client = &self.scheduler.client;
+        let payment = client
+            .create_recurring_payment(
+                &request.name,
+                &request.enckey,
+                to_address,
+                payment.amount,
+                payment.interval,
+                payment.start_at,
+                payment.end_at,
+                view_keys,
+                payment.retry_policy.unwrap_or_default(),
+            )
+            .map_err(to_rpc_error)?;
+        client.flush_database().map_err(to_rpc_error)?;
+        Ok(payment)
+    }
+
+    fn list(&self, request: WalletRequest) -> Result<Vec<RecurringPayment>> {
+        self.scheduler
+            .client
+            .recurring_payments(&request.name, &request.enckey)
+            .map_err(to_rpc_error)
+    }
+
+    fn cancel(&self, request: WalletRequest, id: u64) -> Result<RecurringPayment> {
+        let client = &self.scheduler.client;
+        let payment = client
+            .cancel_recurring_payment(&request.name, &request.enckey, id)
+            .map_err(to_rpc_error)?;
+        client.flush_database().map_err(to_rpc_error)?;
+        Ok(payment)
+    }
+
+    fn events(
+        &self,
+        request: WalletRequest,
+        from_sequence: Option<u64>,
+    ) -> Result<Vec<RecurringPaymentEvent>> {
+        self.scheduler
+            .client
+            .recurring_payment_events(
+                &request.name,
+                &request.enckey,
+                from_sequence.unwrap_or_default(),
+            )
+            .map_err(to_rpc_error)
+    }
+
+    fn unlock(&self, request: WalletRequest, duration: u64) -> Result<u64> {
+        if duration == 0 {
+            return Err(rpc_error_from_string(
+                "Unlock duration should be greater than zero".to_owned(),
+            ));
+        }
+        // the enckey is checked before it's kept by the scheduler
+        self.scheduler
+            .client
+            .balance(&request.name, &request.enckey)
+            .map_err(to_rpc_error)?;
+        let expires_at = now()
+            .checked_add(duration)
+            .ok_or_else(|| rpc_error_from_string(format!("Invalid duration: {}", duration)))?;
+
+        let mut sessions = self
+            .scheduler
+            .sessions
+            .lock()
+            .expect("recurring payment sessions lock");
+        sessions.insert(
+            request.name.clone(),
+            UnlockSession {
+                request,
+                expires_at,
+            },
+        );
+
+        // the scheduler stops (under the lock) once there are no sessions left
+        if !self.scheduler.running.swap(true, Ordering::SeqCst) {
+            let scheduler = self.scheduler.clone();
+            thread::spawn(move || scheduler.run());
+        }
+
+        Ok(expires_at)
+    }
+
+    fn lock(&self, request: WalletRequest) -> Result<bool> {
+        self.scheduler
+            .client
+            .balance(&request.name, &request.enckey)
+            .map_err(to_rpc_error)?;
+        let mut sessions = self
+            .scheduler
+            .sessions
+            .lock()
+            .expect("recurring payment sessions lock");
+        Ok(sessions.remove(&request.name).is_some())
+    }
+}
+
+impl<T> Scheduler<T>
+where
+    T: WalletClient,
+{
+    /// Pays the due payments of the unlocked wallets periodically (until there are no sessions)
+    fn run(&self) {
+        loop {
+            thread::sleep(SCHEDULER_TICK);
+            if !self.pay_due_payments() {
+                return;
+            }
+        }
+    }
+
+    /// Synchronizes the unlocked wallets and sends their due payments, returns `false` if
+    /// there are no more sessions
+    fn pay_due_payments(&self) -> bool {
+        let requests = {
+            let mut sessions = self
+                .sessions
+                .lock()
+                .expect("recurring payment sessions lock");
+            let now = now();
+            sessions.retain(|name, session| {
+                let unlocked = session.expires_at > now;
+                if !unlocked {
+                    log::info!("recurring payments of wallet {} are locked", name);
+                }
+                unlocked
+            });
+            if sessions.is_empty() {
+                self.running.store(false, Ordering::SeqCst);
+                return false;
+            }
+            sessions
+                .values()
+                .map(|session| session.request.clone())
+                .collect::<Vec<_>>()
+        };
+
+        for request in requests {
+            let result = (self.sync_wallet)(&request).and_then(|_| {
+                self.client.pay_due_recurring_payments(
+                    &request.name,
+                    &request.enckey,
+                    self.network_id,
+                )
+            });
+            match result {
+                Ok(events) => {
+                    for event in events.iter() {
+                        log::info!(
+                            "recurring payment {} of wallet {}: {:?}",
+                            event.payment_id,
+                            request.name,
+                            event.kind
+                        );
+                    }
+                    if !events.is_empty() {
+                        if let Err(e) = self.client.flush_database() {
+                            log::error!("unable to flush the database: {}", e);
+                        }
+                    }
+                }
+                Err(e) => log::warn!(
+                    "unable to pay the recurring payments of wallet {}: {}",
+                    request.name,
+                    e
+                ),
+            }
+        }
+        true
+    }
+}
+
+/// Seconds since the unix epoch
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
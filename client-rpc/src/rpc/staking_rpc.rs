@@ -1,10 +1,13 @@
 use std::collections::BTreeSet;
+use std::convert::TryInto;
 use std::str::FromStr;
 
 use jsonrpc_core::Result;
 use jsonrpc_derive::rpc;
+use serde::{Deserialize, Serialize};
 
 use crate::{rpc_error_from_string, to_rpc_error};
+use chain_core::common::{Timespec, H256};
 use chain_core::init::coin::Coin;
 use chain_core::state::account::{
     ConfidentialInit, CouncilNodeMeta, MLSInit, StakedState, StakedStateAddress,
@@ -16,12 +19,87 @@ use chain_core::tx::data::address::ExtendedAddr;
 use chain_core::tx::data::attribute::TxAttributes;
 use chain_core::tx::data::input::TxoPointer;
 use chain_core::tx::data::output::TxOut;
+use chain_core::tx::TxAux;
 use client_common::temporary_mls_init;
-use client_common::{Error, ErrorKind, PublicKey, Result as CommonResult, ResultExt, Transaction};
+use client_common::{
+    Error, ErrorKind, PublicKey, Result as CommonResult, ResultExt, SecKey, Storage, Transaction,
+};
+use client_core::types::{parse_staking_address, DataAnchorInfo};
 use client_core::wallet::WalletRequest;
 use client_core::WalletClient;
+use client_network::network_ops::{
+    StakeMigration, StakeMigrator, WithdrawTemplate, WithdrawTemplateStatus, WithdrawTemplates,
+};
 use client_network::NetworkOpsClient;
 
+/// Withdraw transaction signed in advance (see `staking_createWithdrawTemplate`)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WithdrawTemplateInfo {
+    pub from_address: String,
+    pub transaction_id: String,
+    pub amount: Coin,
+    /// the transaction is broadcasted after this time (the end of the unbonding period)
+    pub unbonded_from: Timespec,
+    /// "pending", "broadcasted" or "invalidated"
+    pub status: String,
+}
+
+impl From<&WithdrawTemplate> for WithdrawTemplateInfo {
+    fn from(template: &WithdrawTemplate) -> Self {
+        let status = match template.status {
+            WithdrawTemplateStatus::Pending => "pending",
+            WithdrawTemplateStatus::Broadcasted => "broadcasted",
+            WithdrawTemplateStatus::Invalidated => "invalidated",
+        };
+        WithdrawTemplateInfo {
+            from_address: template.from_address.to_string(),
+            transaction_id: hex::encode(template.tx_id()),
+            amount: template.amount().unwrap_or_else(|_| Coin::zero()),
+            unbonded_from: template.unbonded_from,
+            status: status.to_owned(),
+        }
+    }
+}
+
+/// Stake migration (see the `StakeMigration` jobs)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StakeMigrationInfo {
+    pub from_address: String,
+    pub to_address: String,
+    pub status: String,
+    /// number of steps done (out of 4: unbond, withdraw, deposit and its confirmation)
+    pub steps_done: u64,
+    /// transaction of the current step, until it's applied
+    pub pending_transaction_id: Option<String>,
+}
+
+impl From<&StakeMigration> for StakeMigrationInfo {
+    fn from(migration: &StakeMigration) -> Self {
+        StakeMigrationInfo {
+            from_address: migration.from_address.to_string(),
+            to_address: migration.to_address.to_string(),
+            status: migration.status.to_string(),
+            steps_done: migration.status.steps_done(),
+            pending_transaction_id: migration
+                .pending_transaction
+                .as_ref()
+                .map(|transaction| hex::encode(transaction.tx_id())),
+        }
+    }
+}
+
+/// Staking transaction built, signed and broadcasted in one call
+/// (see the `staking_*WithInfo` methods)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StakingTransactionInfo {
+    pub transaction_id: String,
+    /// height of the latest block when the transaction was broadcasted
+    pub broadcast_height: u64,
+    /// estimate only: the transaction passed the mempool check, so it's expected in the next
+    /// block, but it can be included later (e.g. if the mempool is congested)
+    pub estimated_inclusion_height: u64,
+}
+
 #[rpc(server)]
 pub trait StakingRpc: Send + Sync {
     #[rpc(name = "staking_depositStake")]
@@ -32,6 +110,14 @@ pub trait StakingRpc: Send + Sync {
         inputs: Vec<TxoPointer>,
     ) -> Result<String>;
 
+    #[rpc(name = "staking_depositStakeWithInfo")]
+    fn deposit_stake_with_info(
+        &self,
+        request: WalletRequest,
+        to_address: String,
+        inputs: Vec<TxoPointer>,
+    ) -> Result<StakingTransactionInfo>;
+
     #[rpc(name = "staking_depositAmountStake")]
     fn deposit_amount_stake(
         &self,
@@ -51,6 +137,14 @@ pub trait StakingRpc: Send + Sync {
         amount: Coin,
     ) -> Result<String>;
 
+    #[rpc(name = "staking_unbondStakeWithInfo")]
+    fn unbond_stake_with_info(
+        &self,
+        request: WalletRequest,
+        staking_address: String,
+        amount: Coin,
+    ) -> Result<StakingTransactionInfo>;
+
     #[rpc(name = "staking_withdrawAllUnbondedStake")]
     fn withdraw_all_unbonded_stake(
         &self,
@@ -60,6 +154,33 @@ pub trait StakingRpc: Send + Sync {
         view_keys: Vec<String>,
     ) -> Result<String>;
 
+    #[rpc(name = "staking_withdrawAllUnbondedStakeWithInfo")]
+    fn withdraw_all_unbonded_stake_with_info(
+        &self,
+        request: WalletRequest,
+        from_address: String,
+        to_address: String,
+        view_keys: Vec<String>,
+    ) -> Result<StakingTransactionInfo>;
+
+    #[rpc(name = "staking_createWithdrawTemplate")]
+    fn create_withdraw_template(
+        &self,
+        request: WalletRequest,
+        from_address: String,
+        to_address: String,
+        view_keys: Vec<String>,
+    ) -> Result<WithdrawTemplateInfo>;
+
+    #[rpc(name = "staking_withdrawTemplates")]
+    fn withdraw_templates(&self, request: WalletRequest) -> Result<Vec<WithdrawTemplateInfo>>;
+
+    #[rpc(name = "staking_removeWithdrawTemplate")]
+    fn remove_withdraw_template(&self, request: WalletRequest, from_address: String) -> Result<()>;
+
+    #[rpc(name = "staking_stakeMigrations")]
+    fn stake_migrations(&self, request: WalletRequest) -> Result<Vec<StakeMigrationInfo>>;
+
     #[rpc(name = "staking_unjail")]
     fn unjail(&self, request: WalletRequest, unjail_address: String) -> Result<String>;
 
@@ -72,34 +193,104 @@ pub trait StakingRpc: Send + Sync {
         staking_address: String,
         keypackage: String,
     ) -> Result<String>;
+
+    #[rpc(name = "staking_anchorData")]
+    fn anchor_data(
+        &self,
+        request: WalletRequest,
+        staking_address: String,
+        commitment: String,
+    ) -> Result<String>;
+
+    #[rpc(name = "staking_dataAnchors")]
+    fn data_anchors(&self, commitment: String) -> Result<Vec<DataAnchorInfo>>;
 }
 
-pub struct StakingRpcImpl<T, N>
+pub struct StakingRpcImpl<S, T, N>
 where
+    S: Storage,
     T: WalletClient,
     N: NetworkOpsClient,
 {
     client: T,
     ops_client: N,
+    withdraw_templates: WithdrawTemplates<S, T, N>,
+    stake_migrator: StakeMigrator<S, T, N>,
     network_id: u8,
 }
 
-impl<T, N> StakingRpcImpl<T, N>
+impl<S, T, N> StakingRpcImpl<S, T, N>
 where
+    S: Storage,
     T: WalletClient,
     N: NetworkOpsClient,
 {
-    pub fn new(client: T, ops_client: N, network_id: u8) -> Self {
+    pub fn new(
+        client: T,
+        ops_client: N,
+        withdraw_templates: WithdrawTemplates<S, T, N>,
+        stake_migrator: StakeMigrator<S, T, N>,
+        network_id: u8,
+    ) -> Self {
         StakingRpcImpl {
             client,
             ops_client,
+            withdraw_templates,
+            stake_migrator,
             network_id,
         }
     }
+
+    /// Withdraw transaction attributes giving access to the wallet's and the provided view keys
+    fn withdraw_attributes(
+        &self,
+        name: &str,
+        enckey: &SecKey,
+        view_keys: Vec<String>,
+    ) -> CommonResult<TxAttributes> {
+        let mut view_keys = view_keys
+            .iter()
+            .map(|key| PublicKey::from_str(key))
+            .collect::<CommonResult<BTreeSet<PublicKey>>>()?;
+        view_keys.insert(self.client.view_key(name, enckey)?);
+
+        let access_policies: BTreeSet<_> = view_keys
+            .iter()
+            .map(|key| TxAccessPolicy {
+                view_key: key.into(),
+                access: TxAccess::AllData,
+            })
+            .collect();
+        Ok(TxAttributes::new_with_access(
+            self.network_id,
+            access_policies.into_iter().collect(),
+        ))
+    }
+
+    /// Broadcasts the transaction (fails if it's rejected by the mempool check of the node)
+    fn broadcast(&self, transaction: &TxAux) -> CommonResult<StakingTransactionInfo> {
+        let broadcast_height = self.client.get_current_block_height()?;
+        self.client.broadcast_transaction(transaction)?;
+        Ok(StakingTransactionInfo {
+            transaction_id: hex::encode(transaction.tx_id()),
+            broadcast_height,
+            estimated_inclusion_height: broadcast_height + 1,
+        })
+    }
+}
+
+fn parse_to_address(to_address: &str) -> CommonResult<ExtendedAddr> {
+    ExtendedAddr::from_str(to_address).chain(|| {
+        (
+            ErrorKind::DeserializationError,
+            format!("Unable to deserialize to_address ({})", to_address),
+        )
+    })
 }
 
-impl<T, N> StakingRpc for StakingRpcImpl<T, N>
+impl<S, T, N> StakingRpc for StakingRpcImpl<S, T, N>
 where
+    S: Storage + 'static,
     T: WalletClient + 'static,
     N: NetworkOpsClient + 'static,
 {
@@ -109,14 +300,17 @@ where
         to_address: String,
         inputs: Vec<TxoPointer>,
     ) -> Result<String> {
-        let to_address = StakedStateAddress::from_str(&to_address)
-            .chain(|| {
-                (
-                    ErrorKind::DeserializationError,
-                    format!("Unable to deserialize to_address ({})", to_address),
-                )
-            })
-            .map_err(to_rpc_error)?;
+        self.deposit_stake_with_info(request, to_address, inputs)
+            .map(|info| info.transaction_id)
+    }
+
+    fn deposit_stake_with_info(
+        &self,
+        request: WalletRequest,
+        to_address: String,
+        inputs: Vec<TxoPointer>,
+    ) -> Result<StakingTransactionInfo> {
+        let to_address = parse_staking_address(&to_address).map_err(to_rpc_error)?;
         let attributes = StakedStateOpAttributes::new(self.network_id);
 
         if !self
@@ -151,9 +345,7 @@ where
             )
             .map_err(to_rpc_error)?;
 
-        self.client
-            .broadcast_transaction(&transaction)
-            .map_err(to_rpc_error)?;
+        let info = self.broadcast(&transaction).map_err(to_rpc_error)?;
 
         // update the wallet pending transaction state
         self.client
@@ -165,7 +357,7 @@ where
             )
             .map_err(to_rpc_error)?;
 
-        Ok(hex::encode(transaction.tx_id()))
+        Ok(info)
     }
 
     /// deposit amount coin to a deposit address
@@ -179,14 +371,7 @@ where
         to_address: String,
         amount: Coin,
     ) -> Result<String> {
-        let to_staking_address = StakedStateAddress::from_str(&to_address)
-            .chain(|| {
-                (
-                    ErrorKind::DeserializationError,
-                    format!("Unable to deserialize to_staking_address ({})", to_address),
-                )
-            })
-            .map_err(to_rpc_error)?;
+        let to_staking_address = parse_staking_address(&to_address).map_err(to_rpc_error)?;
         let attr = StakedStateOpAttributes::new(self.network_id);
         let fee = self
             .ops_client
@@ -266,18 +451,18 @@ where
         staking_address: String,
         amount: Coin,
     ) -> Result<String> {
+        self.unbond_stake_with_info(request, staking_address, amount)
+            .map(|info| info.transaction_id)
+    }
+
+    fn unbond_stake_with_info(
+        &self,
+        request: WalletRequest,
+        staking_address: String,
+        amount: Coin,
+    ) -> Result<StakingTransactionInfo> {
         let attr = StakedStateOpAttributes::new(self.network_id);
-        let addr = StakedStateAddress::from_str(&staking_address)
-            .chain(|| {
-                (
-                    ErrorKind::DeserializationError,
-                    format!(
-                        "Unable to deserialize staking address ({})",
-                        staking_address
-                    ),
-                )
-            })
-            .map_err(to_rpc_error)?;
+        let addr = parse_staking_address(&staking_address).map_err(to_rpc_error)?;
 
         let transaction = self
             .ops_client
@@ -291,11 +476,7 @@ where
             )
             .map_err(to_rpc_error)?;
 
-        self.client
-            .broadcast_transaction(&transaction)
-            .map_err(to_rpc_error)?;
-
-        Ok(hex::encode(transaction.tx_id()))
+        self.broadcast(&transaction).map_err(to_rpc_error)
     }
 
     fn withdraw_all_unbonded_stake(
@@ -305,46 +486,23 @@ where
         to_address: String,
         view_keys: Vec<String>,
     ) -> Result<String> {
-        let from_address = StakedStateAddress::from_str(&from_address)
-            .chain(|| {
-                (
-                    ErrorKind::DeserializationError,
-                    format!("Unable to deserialize from_address ({})", from_address),
-                )
-            })
-            .map_err(to_rpc_error)?;
-        let to_address = ExtendedAddr::from_str(&to_address)
-            .chain(|| {
-                (
-                    ErrorKind::DeserializationError,
-                    format!("Unable to deserialize to_address ({})", to_address),
-                )
-            })
-            .map_err(to_rpc_error)?;
-        let mut view_keys = view_keys
-            .iter()
-            .map(|key| PublicKey::from_str(key))
-            .collect::<CommonResult<BTreeSet<PublicKey>>>()
-            .map_err(to_rpc_error)?;
+        self.withdraw_all_unbonded_stake_with_info(request, from_address, to_address, view_keys)
+            .map(|info| info.transaction_id)
+    }
 
-        let view_key = self
-            .client
-            .view_key(&request.name, &request.enckey)
+    fn withdraw_all_unbonded_stake_with_info(
+        &self,
+        request: WalletRequest,
+        from_address: String,
+        to_address: String,
+        view_keys: Vec<String>,
+    ) -> Result<StakingTransactionInfo> {
+        let from_address = parse_staking_address(&from_address).map_err(to_rpc_error)?;
+        let to_address = parse_to_address(&to_address).map_err(to_rpc_error)?;
+        let attributes = self
+            .withdraw_attributes(&request.name, &request.enckey, view_keys)
             .map_err(to_rpc_error)?;
 
-        view_keys.insert(view_key);
-
-        let access_policies: BTreeSet<_> = view_keys
-            .iter()
-            .map(|key| TxAccessPolicy {
-                view_key: key.into(),
-                access: TxAccess::AllData,
-            })
-            .collect();
-
-        let attributes =
-            TxAttributes::new_with_access(self.network_id, access_policies.into_iter().collect());
-
         let (transaction, tx_pending) = self
             .ops_client
             .create_withdraw_all_unbonded_stake_transaction(
@@ -357,9 +515,7 @@ where
             )
             .map_err(to_rpc_error)?;
 
-        self.client
-            .broadcast_transaction(&transaction)
-            .map_err(to_rpc_error)?;
+        let info = self.broadcast(&transaction).map_err(to_rpc_error)?;
         // update the wallet pending transaction state
         self.client
             .update_tx_pending_state(
@@ -369,19 +525,69 @@ where
                 tx_pending,
             )
             .map_err(to_rpc_error)?;
-        Ok(hex::encode(transaction.tx_id()))
+        Ok(info)
     }
 
-    fn unjail(&self, request: WalletRequest, unjail_address: String) -> Result<String> {
-        let unjail_address = StakedStateAddress::from_str(&unjail_address)
-            .chain(|| {
-                (
-                    ErrorKind::DeserializationError,
-                    format!("Unable to deserialize unjail_address ({})", unjail_address),
-                )
-            })
+    fn create_withdraw_template(
+        &self,
+        request: WalletRequest,
+        from_address: String,
+        to_address: String,
+        view_keys: Vec<String>,
+    ) -> Result<WithdrawTemplateInfo> {
+        let from_address = parse_staking_address(&from_address).map_err(to_rpc_error)?;
+        let to_address = parse_to_address(&to_address).map_err(to_rpc_error)?;
+        let attributes = self
+            .withdraw_attributes(&request.name, &request.enckey, view_keys)
             .map_err(to_rpc_error)?;
 
+        let template = self
+            .withdraw_templates
+            .create(
+                &request.name,
+                &request.enckey,
+                from_address,
+                to_address,
+                attributes,
+            )
+            .map_err(to_rpc_error)?;
+        Ok(WithdrawTemplateInfo::from(&template))
+    }
+
+    fn withdraw_templates(&self, request: WalletRequest) -> Result<Vec<WithdrawTemplateInfo>> {
+        let templates = self
+            .withdraw_templates
+            .templates(&request.name, &request.enckey)
+            .map_err(to_rpc_error)?;
+        Ok(templates.iter().map(WithdrawTemplateInfo::from).collect())
+    }
+
+    fn remove_withdraw_template(&self, request: WalletRequest, from_address: String) -> Result<()> {
+        let from_address = parse_staking_address(&from_address).map_err(to_rpc_error)?;
+        // checks the enckey
+        self.withdraw_templates
+            .template(&request.name, &request.enckey, &from_address)
+            .map_err(to_rpc_error)?;
+        self.withdraw_templates
+            .remove(&request.name, &from_address)
+            .map_err(to_rpc_error)
+    }
+
+    fn stake_migrations(&self, request: WalletRequest) -> Result<Vec<StakeMigrationInfo>> {
+        // checks the enckey
+        self.client
+            .staking_addresses(&request.name, &request.enckey)
+            .map_err(to_rpc_error)?;
+        let migrations = self
+            .stake_migrator
+            .migrations(&request.name)
+            .map_err(to_rpc_error)?;
+        Ok(migrations.iter().map(StakeMigrationInfo::from).collect())
+    }
+
+    fn unjail(&self, request: WalletRequest, unjail_address: String) -> Result<String> {
+        let unjail_address = parse_staking_address(&unjail_address).map_err(to_rpc_error)?;
+
         let attributes = StakedStateOpAttributes::new(self.network_id);
 
         let transaction = self
@@ -411,15 +617,7 @@ where
         keypackage: String,
     ) -> Result<String> {
         let attributes = StakedStateOpAttributes::new(self.network_id);
-        let staking_account_address = staking_addr
-            .parse::<StakedStateAddress>()
-            .chain(|| {
-                (
-                    ErrorKind::DeserializationError,
-                    "Unable to deserialize staking address",
-                )
-            })
-            .map_err(to_rpc_error)?;
+        let staking_account_address = parse_staking_address(&staking_addr).map_err(to_rpc_error)?;
         let node_metadata =
             get_node_metadata(&validator_node_name, &validator_pubkey, &keypackage)?;
         let transaction = self
@@ -439,6 +637,59 @@ where
 
         Ok(hex::encode(transaction.tx_id()))
     }
+
+    fn anchor_data(
+        &self,
+        request: WalletRequest,
+        staking_address: String,
+        commitment: String,
+    ) -> Result<String> {
+        let attributes = StakedStateOpAttributes::new(self.network_id);
+        let address = parse_staking_address(&staking_address).map_err(to_rpc_error)?;
+        let commitment = parse_commitment(&commitment).map_err(to_rpc_error)?;
+
+        let transaction = self
+            .ops_client
+            .create_data_anchor_transaction(
+                &request.name,
+                &request.enckey,
+                address,
+                commitment,
+                attributes,
+                true,
+            )
+            .map_err(to_rpc_error)?;
+        self.client
+            .broadcast_transaction(&transaction)
+            .map_err(to_rpc_error)?;
+
+        Ok(hex::encode(transaction.tx_id()))
+    }
+
+    fn data_anchors(&self, commitment: String) -> Result<Vec<DataAnchorInfo>> {
+        let commitment = parse_commitment(&commitment).map_err(to_rpc_error)?;
+        let proofs = self
+            .ops_client
+            .get_data_anchor_proofs(&commitment)
+            .map_err(to_rpc_error)?;
+        Ok(proofs.iter().map(DataAnchorInfo::from).collect())
+    }
+}
+
+/// Parses the hex encoded commitment (see `data_anchor_commitment`)
+fn parse_commitment(commitment: &str) -> CommonResult<H256> {
+    let bytes = hex::decode(commitment).chain(|| {
+        (
+            ErrorKind::DeserializationError,
+            "Unable to decode hex encoded commitment",
+        )
+    })?;
+    bytes.as_slice().try_into().chain(|| {
+        (
+            ErrorKind::InvalidInput,
+            "Commitment should be 32 bytes long",
+        )
+    })
 }
 
 /// FIXME: take Add + Commit instead of keypackage
@@ -484,3 +735,334 @@ fn get_node_metadata(
         },
     ))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::{Arc, Mutex};
+
+    use parity_scale_codec::Decode;
+    use secstr::SecUtf8;
+
+    use chain_core::init::address::RedeemAddress;
+    use chain_core::init::params::NetworkParameters;
+    use chain_core::state::account::{DataAnchorProof, StakedStateOpWitness, WithdrawUnbondedTx};
+    use chain_core::state::tendermint::BlockHeight;
+    use chain_core::state::ChainState;
+    use chain_core::tx::data::TxId;
+    use chain_core::tx::{TxEnclaveAux, TxObfuscated};
+    use client_common::storage::MemoryStorage;
+    use client_common::tendermint::mock;
+    use client_common::tendermint::types::*;
+    use client_common::tendermint::Client;
+    use client_common::{seckey::derive_enckey, SignedTransaction};
+    use client_core::service::HwKeyService;
+    use client_core::transaction_builder::UnauthorizedWalletTransactionBuilder;
+    use client_core::types::TransactionPending;
+    use client_core::wallet::DefaultWalletClient;
+
+    /// Transactions received by the node
+    #[derive(Default)]
+    struct MockChain {
+        /// number of transactions built
+        built: u8,
+        broadcasted: Vec<TxId>,
+        reject_broadcast: bool,
+    }
+
+    #[derive(Clone)]
+    struct MockClient {
+        chain: Arc<Mutex<MockChain>>,
+    }
+
+    impl Client for MockClient {
+        fn genesis(&self) -> CommonResult<Genesis> {
+            unreachable!()
+        }
+
+        fn status(&self) -> CommonResult<StatusResponse> {
+            Ok(StatusResponse {
+                sync_info: status::SyncInfo {
+                    latest_block_height: Height::from(5),
+                    ..mock::sync_info()
+                },
+                ..mock::status_response()
+            })
+        }
+
+        fn block(&self, _: u64) -> CommonResult<Block> {
+            unreachable!()
+        }
+
+        fn block_batch<'a, T: Iterator<Item = &'a u64>>(
+            &self,
+            _heights: T,
+        ) -> CommonResult<Vec<Block>> {
+            unreachable!()
+        }
+
+        fn block_results(&self, _height: u64) -> CommonResult<BlockResultsResponse> {
+            unreachable!()
+        }
+
+        fn block_results_batch<'a, T: Iterator<Item = &'a u64>>(
+            &self,
+            _heights: T,
+        ) -> CommonResult<Vec<BlockResultsResponse>> {
+            unreachable!()
+        }
+
+        fn broadcast_transaction(&self, transaction: &[u8]) -> CommonResult<BroadcastTxResponse> {
+            let mut chain = self.chain.lock().unwrap();
+            if chain.reject_broadcast {
+                return Err(Error::new(
+                    ErrorKind::TendermintRpcError,
+                    "Transaction rejected",
+                ));
+            }
+            let tx_aux = TxAux::decode(&mut &transaction[..]).unwrap();
+            chain.broadcasted.push(tx_aux.tx_id());
+            Ok(mock::broadcast_tx_response())
+        }
+
+        fn query(
+            &self,
+            _path: &str,
+            _data: &[u8],
+            _height: Option<Height>,
+            _prove: bool,
+        ) -> CommonResult<AbciQuery> {
+            unreachable!()
+        }
+
+        fn unconfirmed_txs(&self, _limit: u64) -> CommonResult<Vec<Vec<u8>>> {
+            unreachable!()
+        }
+
+        fn query_state_batch<T: Iterator<Item = u64>>(
+            &self,
+            _heights: T,
+        ) -> CommonResult<Vec<ChainState>> {
+            unreachable!()
+        }
+    }
+
+    struct MockNetworkOpsClient {
+        chain: Arc<Mutex<MockChain>>,
+    }
+
+    impl NetworkOpsClient for MockNetworkOpsClient {
+        fn calculate_deposit_fee(&self) -> CommonResult<Coin> {
+            unreachable!()
+        }
+
+        fn calculate_unbond_fee(&self) -> CommonResult<Coin> {
+            unreachable!()
+        }
+
+        fn create_deposit_bonded_stake_transaction(
+            &self,
+            _name: &str,
+            _enckey: &SecKey,
+            _transaction: Vec<(TxoPointer, TxOut)>,
+            _to_address: StakedStateAddress,
+            _attributes: StakedStateOpAttributes,
+            _verify_staking: bool,
+        ) -> CommonResult<(TxAux, TransactionPending)> {
+            unreachable!()
+        }
+
+        /// Transaction with the next transaction id (`[1; 32]`, `[2; 32]`...)
+        fn create_unbond_stake_transaction(
+            &self,
+            _name: &str,
+            _enckey: &SecKey,
+            _address: StakedStateAddress,
+            _value: Coin,
+            _attributes: StakedStateOpAttributes,
+            _verify_staking: bool,
+        ) -> CommonResult<TxAux> {
+            let mut chain = self.chain.lock().unwrap();
+            chain.built += 1;
+            Ok(TxAux::EnclaveTx(TxEnclaveAux::TransferTx {
+                inputs: Vec::new(),
+                no_of_outputs: 1,
+                payload: TxObfuscated {
+                    txid: [chain.built; 32],
+                    key_from: BlockHeight::genesis(),
+                    init_vector: [0; 12],
+                    txpayload: Vec::new(),
+                },
+            }))
+        }
+
+        fn create_withdraw_unbonded_stake_transaction(
+            &self,
+            _name: &str,
+            _enckey: &SecKey,
+            _from_address: &StakedStateAddress,
+            _outputs: Vec<TxOut>,
+            _attributes: TxAttributes,
+            _verify_staking: bool,
+        ) -> CommonResult<(TxAux, TransactionPending)> {
+            unreachable!()
+        }
+
+        fn create_withdraw_all_unbonded_stake_transaction(
+            &self,
+            _name: &str,
+            _enckey: &SecKey,
+            _from_address: &StakedStateAddress,
+            _to_address: ExtendedAddr,
+            _attributes: TxAttributes,
+            _verify_staking: bool,
+        ) -> CommonResult<(TxAux, TransactionPending)> {
+            unreachable!()
+        }
+
+        fn create_withdraw_all_unbonded_stake_template(
+            &self,
+            _name: &str,
+            _enckey: &SecKey,
+            _from_address: &StakedStateAddress,
+            _to_address: ExtendedAddr,
+            _attributes: TxAttributes,
+            _verify_staking: bool,
+        ) -> CommonResult<(WithdrawUnbondedTx, StakedStateOpWitness)> {
+            unreachable!()
+        }
+
+        fn encrypt_transaction(&self, _transaction: SignedTransaction) -> CommonResult<TxAux> {
+            unreachable!()
+        }
+
+        fn create_unjail_transaction(
+            &self,
+            _name: &str,
+            _enckey: &SecKey,
+            _address: StakedStateAddress,
+            _attributes: StakedStateOpAttributes,
+            _verify_staking: bool,
+        ) -> CommonResult<TxAux> {
+            unreachable!()
+        }
+
+        fn create_node_join_transaction(
+            &self,
+            _name: &str,
+            _enckey: &SecKey,
+            _staking_account_address: StakedStateAddress,
+            _attributes: StakedStateOpAttributes,
+            _node_metadata: CouncilNodeMeta,
+            _verify_staking: bool,
+        ) -> CommonResult<TxAux> {
+            unreachable!()
+        }
+
+        fn create_data_anchor_transaction(
+            &self,
+            _name: &str,
+            _enckey: &SecKey,
+            _address: StakedStateAddress,
+            _commitment: H256,
+            _attributes: StakedStateOpAttributes,
+            _verify_staking: bool,
+        ) -> CommonResult<TxAux> {
+            unreachable!()
+        }
+
+        fn get_data_anchor_proofs(&self, _commitment: &H256) -> CommonResult<Vec<DataAnchorProof>> {
+            unreachable!()
+        }
+
+        fn get_staking(
+            &self,
+            _name: &str,
+            _address: &StakedStateAddress,
+            _verify: bool,
+        ) -> CommonResult<Option<StakedState>> {
+            unreachable!()
+        }
+
+        fn get_network_params(&self, _height: u64) -> CommonResult<NetworkParameters> {
+            unreachable!()
+        }
+
+        fn get_genesis(&self) -> CommonResult<Genesis> {
+            unreachable!()
+        }
+
+        fn get_status(&self) -> CommonResult<StatusResponse> {
+            unreachable!()
+        }
+    }
+
+    type TestWalletClient =
+        DefaultWalletClient<MemoryStorage, MockClient, UnauthorizedWalletTransactionBuilder>;
+
+    fn setup_staking_rpc(
+        chain: &Arc<Mutex<MockChain>>,
+    ) -> StakingRpcImpl<MemoryStorage, TestWalletClient, MockNetworkOpsClient> {
+        let storage = MemoryStorage::default();
+        let wallet_client = DefaultWalletClient::new(
+            storage.clone(),
+            MockClient {
+                chain: chain.clone(),
+            },
+            UnauthorizedWalletTransactionBuilder,
+            None,
+            HwKeyService::default(),
+        );
+        let ops_client = || MockNetworkOpsClient {
+            chain: chain.clone(),
+        };
+        StakingRpcImpl::new(
+            wallet_client.clone(),
+            ops_client(),
+            WithdrawTemplates::new(storage.clone(), wallet_client.clone(), ops_client()),
+            StakeMigrator::new(storage, wallet_client, ops_client(), 0),
+            0,
+        )
+    }
+
+    #[test]
+    fn check_staking_transaction_info() {
+        let chain = Arc::new(Mutex::new(MockChain::default()));
+        let staking_rpc = setup_staking_rpc(&chain);
+        let request = WalletRequest {
+            name: "name".to_owned(),
+            enckey: derive_enckey(&SecUtf8::from("passphrase"), "name").unwrap(),
+        };
+        let address = StakedStateAddress::BasicRedeem(RedeemAddress::from([1u8; 20])).to_string();
+
+        assert_eq!(
+            StakingTransactionInfo {
+                transaction_id: hex::encode([1u8; 32]),
+                broadcast_height: 5,
+                estimated_inclusion_height: 6,
+            },
+            staking_rpc
+                .unbond_stake_with_info(request.clone(), address.clone(), Coin::unit())
+                .unwrap()
+        );
+        // the existing method still returns the transaction id only
+        assert_eq!(
+            hex::encode([2u8; 32]),
+            staking_rpc
+                .unbond_stake(request.clone(), address.clone(), Coin::unit())
+                .unwrap()
+        );
+        assert_eq!(
+            vec![[1u8; 32], [2u8; 32]],
+            chain.lock().unwrap().broadcasted
+        );
+
+        // rejected by the mempool check of the node
+        chain.lock().unwrap().reject_broadcast = true;
+        assert!(staking_rpc
+            .unbond_stake_with_info(request, address, Coin::unit())
+            .is_err());
+        assert_eq!(2, chain.lock().unwrap().broadcasted.len());
+    }
+}
@@ -0,0 +1,336 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use jsonrpc_core::Result;
+use jsonrpc_derive::rpc;
+use serde::Serialize;
+
+use client_common::tendermint::{WebsocketRpcClient, NEW_BLOCK_QUERY};
+use client_common::Result as CommonResult;
+use client_core::types::{TransactionChange, WalletBalance};
+use client_core::wallet::WalletRequest;
+use client_core::WalletClient;
+
+use crate::permission::RpcMeta;
+use crate::session::Session;
+use crate::{rpc_error_from_string, to_rpc_error};
+
+/// Method of the notifications pushed to the subscribers of `wallet_subscribeBalance`
+const BALANCE_NOTIFICATION: &str = "wallet_balanceUpdate";
+/// Maximum number of new transactions reported in one notification
+const NEW_TRANSACTIONS_LIMIT: usize = 100;
+
+/// Synchronizes a wallet before its balance is checked
+pub type SyncWallet = Arc<dyn Fn(&WalletRequest) -> CommonResult<()> + Send + Sync>;
+
+#[rpc(server)]
+pub trait SubscriptionRpc: Send + Sync {
+    type Metadata;
+
+    #[rpc(meta, name = "wallet_subscribeBalance")]
+    fn subscribe_balance(&self, meta: Self::Metadata, request: WalletRequest) -> Result<String>;
+
+    #[rpc(meta, name = "wallet_unsubscribeBalance")]
+    fn unsubscribe_balance(&self, meta: Self::Metadata, subscription: String) -> Result<bool>;
+}
+
+/// Balance and new transactions of a wallet, pushed when a committed block changes them
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BalanceUpdate {
+    pub name: String,
+    pub balance: WalletBalance,
+    pub new_transactions: Vec<TransactionChange>,
+}
+
+struct BalanceSubscription {
+    request: WalletRequest,
+    session: Session,
+    balance: WalletBalance,
+    /// height of the latest transaction already reported
+    last_height: u64,
+}
+
+struct Watcher<T: WalletClient> {
+    client: T,
+    tendermint_client: WebsocketRpcClient,
+    sync_wallet: SyncWallet,
+    subscriptions: Mutex<HashMap<String, BalanceSubscription>>,
+    next_id: AtomicU64,
+    running: AtomicBool,
+}
+
+pub struct SubscriptionRpcImpl<T: WalletClient> {
+    watcher: Arc<Watcher<T>>,
+}
+
+impl<T> SubscriptionRpcImpl<T>
+where
+    T: WalletClient + 'static,
+{
+    pub fn new(client: T, tendermint_client: WebsocketRpcClient, sync_wallet: SyncWallet) -> Self {
+        SubscriptionRpcImpl {
+            watcher: Arc::new(Watcher {
+                client,
+                tendermint_client,
+                sync_wallet,
+                subscriptions: Default::default(),
+                next_id: AtomicU64::new(0),
+                running: AtomicBool::new(false),
+            }),
+        }
+    }
+}
+
+impl<T> SubscriptionRpc for SubscriptionRpcImpl<T>
+where
+    T: WalletClient + 'static,
+{
+    type Metadata = RpcMeta;
+
+    fn subscribe_balance(&self, meta: RpcMeta, request: WalletRequest) -> Result<String> {
+        let session = meta.session.ok_or_else(|| {
+            rpc_error_from_string("Subscriptions require a WebSocket connection".to_owned())
+        })?;
+
+        let balance = self
+            .watcher
+            .client
+            .balance(&request.name, &request.enckey)
+            .map_err(to_rpc_error)?;
+        let last_height = self
+            .watcher
+            .client
+            .history(&request.name, &request.enckey, 0, 1, true)
+            .map_err(to_rpc_error)?
+            .first()
+            .map(|change| change.block_height)
+            .unwrap_or_default();
+
+        let id = format!(
+            "0x{:x}",
+            self.watcher.next_id.fetch_add(1, Ordering::Relaxed)
+        );
+        let mut subscriptions = self
+            .watcher
+            .subscriptions
+            .lock()
+            .expect("balance subscriptions lock");
+        subscriptions.insert(
+            id.clone(),
+            BalanceSubscription {
+                request,
+                session,
+                balance,
+                last_height,
+            },
+        );
+
+        // the watcher stops (under the lock) once there are no subscriptions left
+        if !self.watcher.running.swap(true, Ordering::SeqCst) {
+            let watcher = self.watcher.clone();
+            thread::spawn(move || {
+                if let Err(e) = watcher.run() {
+                    log::error!("balance subscriptions stopped: {}", e);
+                    let _subscriptions = watcher.subscriptions.lock();
+                    watcher.running.store(false, Ordering::SeqCst);
+                }
+            });
+        }
+
+        Ok(id)
+    }
+
+    fn unsubscribe_balance(&self, meta: RpcMeta, subscription: String) -> Result<bool> {
+        let mut subscriptions = self
+            .watcher
+            .subscriptions
+            .lock()
+            .expect("balance subscriptions lock");
+        // only the session which subscribed can unsubscribe
+        let owned = match (subscriptions.get(&subscription), meta.session) {
+            (Some(existing), Some(session)) => existing.session.id() == session.id(),
+            _ => false,
+        };
+        if owned {
+            subscriptions.remove(&subscription);
+        }
+        Ok(owned)
+    }
+}
+
+impl<T> Watcher<T>
+where
+    T: WalletClient,
+{
+    /// Checks the subscribed wallets on every new block (until there are no subscriptions)
+    fn run(&self) -> CommonResult<()> {
+        let blocks = self.tendermint_client.subscribe(NEW_BLOCK_QUERY)?;
+        loop {
+            blocks.recv()?;
+            if !self.check_wallets() {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Synchronizes the subscribed wallets and notifies the changes, returns `false` if there
+    /// are no more subscriptions
+    fn check_wallets(&self) -> bool {
+        let requests = {
+            let subscriptions = self
+                .subscriptions
+                .lock()
+                .expect("balance subscriptions lock");
+            subscriptions
+                .values()
+                .map(|subscription| {
+                    (
+                        subscription.request.name.clone(),
+                        subscription.request.clone(),
+                    )
+                })
+                .collect::<BTreeMap<_, _>>()
+        };
+
+        // each wallet is synchronized once, however many subscriptions it has
+        let mut states = BTreeMap::new();
+        for (name, request) in requests {
+            let state = (self.sync_wallet)(&request)
+                .and_then(|_| self.client.balance(&request.name, &request.enckey))
+                .and_then(|balance| {
+                    let history = self.client.history(
+                        &request.name,
+                        &request.enckey,
+                        0,
+                        NEW_TRANSACTIONS_LIMIT,
+                        true,
+                    )?;
+                    Ok((balance, history))
+                });
+            match state {
+                Ok(state) => {
+                    states.insert(name, state);
+                }
+                Err(e) => log::warn!("unable to check the balance of wallet {}: {}", name, e),
+            }
+        }
+
+        let mut subscriptions = self
+            .subscriptions
+            .lock()
+            .expect("balance subscriptions lock");
+        subscriptions.retain(|id, subscription| {
+            let (balance, history) = match states.get(&subscription.request.name) {
+                Some(state) => state,
+                None => return true,
+            };
+            let update = match balance_update(subscription, balance, history) {
+                Some(update) => update,
+                None => return true,
+            };
+            subscription.balance = update.balance.clone();
+            if let Some(latest) = update.new_transactions.first() {
+                subscription.last_height = latest.block_height;
+            }
+
+            let result = serde_json::to_value(&update).expect("serialize balance update");
+            // subscriptions of closed connections are removed
+            subscription
+                .session
+                .notify(BALANCE_NOTIFICATION, id, result)
+        });
+        if subscriptions.is_empty() {
+            self.running.store(false, Ordering::SeqCst);
+            return false;
+        }
+        true
+    }
+}
+
+/// Returns the update to notify if the balance changed or transactions were added after the
+/// last reported one (`history` is sorted from the latest transaction)
+fn balance_update(
+    subscription: &BalanceSubscription,
+    balance: &WalletBalance,
+    history: &[TransactionChange],
+) -> Option<BalanceUpdate> {
+    let new_transactions = history
+        .iter()
+        .take_while(|change| change.block_height > subscription.last_height)
+        .cloned()
+        .collect::<Vec<_>>();
+    if new_transactions.is_empty() && subscription.balance == *balance {
+        return None;
+    }
+    Some(BalanceUpdate {
+        name: subscription.request.name.clone(),
+        balance: balance.clone(),
+        new_transactions,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::mpsc::channel;
+
+    use chain_core::init::coin::Coin;
+    use chain_core::tx::fee::Fee;
+    use client_common::seckey::derive_enckey;
+    use client_common::tendermint::types::Time;
+    use client_core::types::{BalanceChange, TransactionType};
+    use secstr::SecUtf8;
+
+    fn change(block_height: u64) -> TransactionChange {
+        TransactionChange {
+            transaction_id: [block_height as u8; 32],
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            fee_paid: Fee::new(Coin::zero()),
+            balance_change: BalanceChange::Incoming { value: Coin::one() },
+            transaction_type: TransactionType::Transfer,
+            block_height,
+            block_time: Time::now(),
+            annotation: None,
+        }
+    }
+
+    #[test]
+    fn check_balance_update() {
+        let (sender, _receiver) = channel();
+        let subscription = BalanceSubscription {
+            request: WalletRequest {
+                name: "name".to_owned(),
+                enckey: derive_enckey(&SecUtf8::from("passphrase"), "name").unwrap(),
+            },
+            session: Session::new(sender),
+            balance: WalletBalance::default(),
+            last_height: 2,
+        };
+
+        assert_eq!(
+            None,
+            balance_update(&subscription, &WalletBalance::default(), &[change(2)])
+        );
+
+        let balance = WalletBalance {
+            total: Coin::one(),
+            available: Coin::one(),
+            pending: Coin::zero(),
+        };
+        let update = balance_update(&subscription, &balance, &[change(4), change(3), change(2)])
+            .expect("balance update");
+        assert_eq!(balance, update.balance);
+        assert_eq!(
+            vec![4, 3],
+            update
+                .new_transactions
+                .iter()
+                .map(|change| change.block_height)
+                .collect::<Vec<_>>()
+        );
+    }
+}
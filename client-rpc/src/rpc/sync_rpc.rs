@@ -1,7 +1,9 @@
 use super::sync_worker::SyncWorker;
 use super::sync_worker::WorkerShared;
 use crate::to_rpc_error;
+use client_common::correlation::{current_correlation_id, enter_correlation_id, log_prefix};
 use client_common::tendermint::Client;
+use client_common::Result as CommonResult;
 use client_common::Storage;
 use client_common::TransactionObfuscation;
 use client_core::wallet::syncer::{
@@ -24,6 +26,10 @@ pub trait CBindingCallback: Send + Sync {
     fn get_user(&self) -> u64;
 }
 
+/// Action run after every successful wallet sync
+/// (e.g. broadcasting the withdraw transactions signed in advance)
+pub type AfterSync = Arc<dyn Fn(&WalletRequest) -> CommonResult<()> + Send + Sync>;
+
 #[derive(Clone)]
 pub struct CBindingCore {
     pub data: Arc<Mutex<dyn CBindingCallback>>,
@@ -89,6 +95,7 @@ where
     worker: WorkerShared,
     recover_address: T,
     light_client_handle: Option<L>,
+    after_sync: Option<AfterSync>,
 }
 
 impl<S, C, O, T, L> SyncRpcImpl<S, C, O, T, L>
@@ -114,8 +121,15 @@ where
 
             recover_address,
             light_client_handle,
+            after_sync: None,
         }
     }
+
+    /// Runs the action after every successful sync
+    pub fn with_after_sync(mut self, after_sync: AfterSync) -> Self {
+        self.after_sync = Some(after_sync);
+        self
+    }
 }
 
 fn process_sync<S, C, O, T, L>(
@@ -124,6 +138,42 @@ fn process_sync<S, C, O, T, L>(
     reset: bool,
     progress_callback: Option<CBindingCore>,
     recover_address: T,
+    after_sync: Option<AfterSync>,
+) -> Result<()>
+where
+    S: Storage + 'static,
+    C: Client,
+    O: TransactionObfuscation,
+    T: AddressRecovery,
+    L: Handle + Send + Sync + Clone,
+{
+    sync_wallet(
+        config,
+        request.clone(),
+        reset,
+        progress_callback,
+        recover_address,
+    )?;
+    if let Some(after_sync) = after_sync {
+        // the sync itself succeeded, so the failure is only logged
+        if let Err(e) = after_sync(&request) {
+            log::warn!(
+                "{}after sync action of wallet {} failed: {}",
+                log_prefix(),
+                request.name,
+                e
+            );
+        }
+    }
+    Ok(())
+}
+
+fn sync_wallet<S, C, O, T, L>(
+    config: ObfuscationSyncerConfig<S, C, O, L>,
+    request: WalletRequest,
+    reset: bool,
+    progress_callback: Option<CBindingCore>,
+    recover_address: T,
 ) -> Result<()>
 where
     S: Storage + 'static,
@@ -206,9 +256,10 @@ where
         reset: bool,
         do_loop: bool,
     ) -> Result<RunSyncResult> {
-        log::info!("run_sync");
+        log::info!("{}run_sync", log_prefix());
         let config = self.config.clone();
         let recover_address = self.recover_address.clone();
+        let after_sync = self.after_sync.clone();
 
         let name = request.name.clone();
         let worker = self.worker.clone();
@@ -226,7 +277,10 @@ where
             });
         }
 
+        // the sync thread keeps the correlation ID of the call which started it
+        let correlation_id = current_correlation_id();
         thread::spawn(move || {
+            let _correlation = correlation_id.map(enter_correlation_id);
             let localworker = worker;
             localworker.lock().expect("get sync worker lock").add(&name);
             let node = localworker.lock().expect("get sync worker lock").get(&name);
@@ -239,15 +293,21 @@ where
                     reset,
                     usercallback.clone(),
                     recover_address.clone(),
+                    after_sync.clone(),
+                );
+                log::info!(
+                    "{}process_sync finished {} {:?}",
+                    log_prefix(),
+                    name,
+                    result
                 );
-                log::info!("process_sync finished {} {:?}", name, result);
                 if let Err(error_message) = result {
                     localworker
                         .lock()
                         .expect("get sync worker lock")
                         .set_error_message(&name, &error_message.message.to_string());
 
-                    log::info!("wait for error notification {}", name);
+                    log::info!("{}wait for error notification {}", log_prefix(), name);
                     std::thread::sleep(std::time::Duration::from_secs(ERROR_NOTIFICATION_TIME));
                 }
 
@@ -276,7 +336,7 @@ where
                 .lock()
                 .expect("get sync worker lock")
                 .remove(&name);
-            log::info!("sync thread finished {}", name);
+            log::info!("{}sync thread finished {}", log_prefix(), name);
         });
 
         Ok(RunSyncResult {
@@ -305,6 +365,7 @@ where
                 sync_request.reset,
                 self.progress_callback.clone(),
                 self.recover_address.clone(),
+                self.after_sync.clone(),
             )?;
 
             Ok(RunSyncResult::default())
@@ -9,8 +9,16 @@ use chain_core::tx::data::output::TxOut;
 use chain_core::tx::data::{Tx, TxId};
 use chain_core::tx::TransactionId;
 use client_common::PublicKey;
+use client_core::types::FeeEstimate;
+use client_core::wallet::WalletRequest;
+use client_core::WalletClient;
 use std::collections::BTreeSet;
 
+use crate::to_rpc_error;
+
+/// Number of unconfirmed transactions checked for spent wallet outputs by `transaction_estimateFee`
+const DEFAULT_MEMPOOL_LIMIT: u64 = 100;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct RawTransaction {
     tx: Tx,
@@ -37,19 +45,34 @@ pub trait TransactionRpc: Send + Sync {
         outputs: Vec<TxOut>,
         view_keys: Vec<PublicKey>,
     ) -> Result<RawTransaction>;
+
+    #[rpc(name = "transaction_estimateFee")]
+    fn estimate_fee(
+        &self,
+        request: WalletRequest,
+        outputs: Vec<TxOut>,
+        view_keys: Vec<PublicKey>,
+    ) -> Result<FeeEstimate>;
 }
 
-pub struct TransactionRpcImpl {
+pub struct TransactionRpcImpl<T: WalletClient> {
+    client: T,
     network_id: u8,
 }
 
-impl TransactionRpcImpl {
-    pub fn new(network_id: u8) -> Self {
-        TransactionRpcImpl { network_id }
+impl<T> TransactionRpcImpl<T>
+where
+    T: WalletClient,
+{
+    pub fn new(client: T, network_id: u8) -> Self {
+        TransactionRpcImpl { client, network_id }
     }
 }
 
-impl TransactionRpc for TransactionRpcImpl {
+impl<T> TransactionRpc for TransactionRpcImpl<T>
+where
+    T: WalletClient + 'static,
+{
     fn create_raw(
         &self,
         inputs: Vec<TxoPointer>,
@@ -76,6 +99,25 @@ impl TransactionRpc for TransactionRpcImpl {
 
         Ok(RawTransaction { tx, tx_id })
     }
+
+    fn estimate_fee(
+        &self,
+        request: WalletRequest,
+        outputs: Vec<TxOut>,
+        view_keys: Vec<PublicKey>,
+    ) -> Result<FeeEstimate> {
+        let mut view_keys = view_keys.into_iter().collect::<BTreeSet<PublicKey>>();
+        self.client
+            .estimate_fee(
+                &request.name,
+                &request.enckey,
+                outputs,
+                &mut view_keys,
+                self.network_id,
+                DEFAULT_MEMPOOL_LIMIT,
+            )
+            .map_err(to_rpc_error)
+    }
 }
 
 #[cfg(test)]
@@ -85,12 +127,17 @@ mod test {
     use chain_core::init::coin::Coin;
     use chain_core::init::network::Network;
     use chain_core::tx::data::address::ExtendedAddr;
+    use client_common::storage::MemoryStorage;
     use client_common::PrivateKey;
+    use client_core::wallet::DefaultWalletClient;
 
     #[test]
     fn create_raw_flow() {
         let chain_id = hex::decode("AB").unwrap()[0];
-        let transaction_rpc = TransactionRpcImpl::new(chain_id);
+        let transaction_rpc = TransactionRpcImpl::new(
+            DefaultWalletClient::new_read_only(MemoryStorage::default()),
+            chain_id,
+        );
 
         let inputs = vec![TxoPointer::new([0; 32], 0), TxoPointer::new([1; 32], 0)];
 
@@ -0,0 +1,180 @@
+use std::collections::BTreeSet;
+use std::str::FromStr;
+
+use jsonrpc_core::Result;
+use jsonrpc_derive::rpc;
+use secstr::SecUtf8;
+
+use chain_core::init::coin::Coin;
+use chain_core::tx::data::address::ExtendedAddr;
+use chain_core::tx::data::input::str2txid;
+use chain_core::tx::data::TxId;
+use client_common::{PrivateKey, PublicKey, Result as CommonResult};
+use client_core::types::{Vault, VaultSpend};
+use client_core::wallet::WalletRequest;
+use client_core::WalletClient;
+
+use crate::{rpc_error_from_string, to_rpc_error};
+
+#[rpc(server)]
+pub trait VaultRpc: Send + Sync {
+    #[rpc(name = "vault_create")]
+    fn create(
+        &self,
+        request: WalletRequest,
+        cancel_public_key: String,
+        delay: u64,
+    ) -> Result<Vault>;
+
+    #[rpc(name = "vault_list")]
+    fn list(&self, request: WalletRequest) -> Result<Vec<Vault>>;
+
+    #[rpc(name = "vault_spends")]
+    fn spends(&self, request: WalletRequest) -> Result<Vec<VaultSpend>>;
+
+    #[rpc(name = "vault_initiateSpend")]
+    fn initiate_spend(
+        &self,
+        request: WalletRequest,
+        vault_address: String,
+        to_address: String,
+        amount: Coin,
+        view_keys: Vec<String>,
+    ) -> Result<VaultSpend>;
+
+    #[rpc(name = "vault_completeSpend")]
+    fn complete_spend(&self, request: WalletRequest, txid: String) -> Result<String>;
+
+    #[rpc(name = "vault_cancelSpend")]
+    fn cancel_spend(
+        &self,
+        request: WalletRequest,
+        txid: String,
+        cancel_key: SecUtf8,
+    ) -> Result<String>;
+}
+
+pub struct VaultRpcImpl<T>
+where
+    T: WalletClient,
+{
+    client: T,
+    network_id: u8,
+}
+
+impl<T> VaultRpcImpl<T>
+where
+    T: WalletClient,
+{
+    pub fn new(client: T, network_id: u8) -> Self {
+        VaultRpcImpl { client, network_id }
+    }
+}
+
+impl<T> VaultRpc for VaultRpcImpl<T>
+where
+    T: WalletClient + 'static,
+{
+    fn create(
+        &self,
+        request: WalletRequest,
+        cancel_public_key: String,
+        delay: u64,
+    ) -> Result<Vault> {
+        let cancel_public_key = PublicKey::from_str(&cancel_public_key).map_err(to_rpc_error)?;
+        let vault = self
+            .client
+            .new_vault(&request.name, &request.enckey, &cancel_public_key, delay)
+            .map_err(to_rpc_error)?;
+        self.client.flush_database().map_err(to_rpc_error)?;
+        Ok(vault)
+    }
+
+    fn list(&self, request: WalletRequest) -> Result<Vec<Vault>> {
+        self.client
+            .vaults(&request.name, &request.enckey)
+            .map_err(to_rpc_error)
+    }
+
+    fn spends(&self, request: WalletRequest) -> Result<Vec<VaultSpend>> {
+        self.client
+            .vault_spends(&request.name, &request.enckey)
+            .map_err(to_rpc_error)
+    }
+
+    fn initiate_spend(
+        &self,
+        request: WalletRequest,
+        vault_address: String,
+        to_address: String,
+        amount: Coin,
+        view_keys: Vec<String>,
+    ) -> Result<VaultSpend> {
+        let vault_address = parse_address(&vault_address)?;
+        let to_address = parse_address(&to_address)?;
+        let mut view_keys = view_keys
+            .iter()
+            .map(|view_key| PublicKey::from_str(view_key))
+            .collect::<CommonResult<BTreeSet<PublicKey>>>()
+            .map_err(to_rpc_error)?;
+        let spend = self
+            .client
+            .initiate_vault_spend(
+                &request.name,
+                &request.enckey,
+                &vault_address,
+                to_address,
+                amount,
+                &mut view_keys,
+                self.network_id,
+            )
+            .map_err(to_rpc_error)?;
+        self.client.flush_database().map_err(to_rpc_error)?;
+        Ok(spend)
+    }
+
+    fn complete_spend(&self, request: WalletRequest, txid: String) -> Result<String> {
+        let txid = parse_txid(&txid)?;
+        let tx_id = self
+            .client
+            .complete_vault_spend(&request.name, &request.enckey, &txid, self.network_id)
+            .map_err(to_rpc_error)?;
+        self.client.flush_database().map_err(to_rpc_error)?;
+        Ok(hex::encode(tx_id))
+    }
+
+    fn cancel_spend(
+        &self,
+        request: WalletRequest,
+        txid: String,
+        cancel_key: SecUtf8,
+    ) -> Result<String> {
+        let txid = parse_txid(&txid)?;
+        let cancel_key = PrivateKey::deserialize_from(
+            &hex::decode(cancel_key.unsecure()).map_err(to_rpc_error)?,
+        )
+        .map_err(to_rpc_error)?;
+        let tx_id = self
+            .client
+            .cancel_vault_spend(
+                &request.name,
+                &request.enckey,
+                &txid,
+                &cancel_key,
+                self.network_id,
+            )
+            .map_err(to_rpc_error)?;
+        self.client.flush_database().map_err(to_rpc_error)?;
+        Ok(hex::encode(tx_id))
+    }
+}
+
+fn parse_address(address: &str) -> Result<ExtendedAddr> {
+    address
+        .parse::<ExtendedAddr>()
+        .map_err(|err| rpc_error_from_string(format!("{}", err)))
+}
+
+fn parse_txid(txid: &str) -> Result<TxId> {
+    str2txid(txid).map_err(|err| rpc_error_from_string(format!("{}", err)))
+}
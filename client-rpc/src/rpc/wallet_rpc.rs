@@ -4,14 +4,25 @@ use std::str::FromStr;
 use jsonrpc_core::Result;
 use jsonrpc_derive::rpc;
 use secstr::SecUtf8;
+use serde::Deserialize;
 
+use chain_core::common::Timespec;
 use chain_core::init::coin::Coin;
+use chain_core::init::network::get_network;
 use chain_core::tx::data::address::ExtendedAddr;
+use chain_core::tx::data::input::TxoPointer;
+use chain_core::tx::data::output::TxOut;
+use chain_core::tx::witness::TxWitness;
 use client_common::{PrivateKey, PublicKey, Result as CommonResult, SecKey};
 use client_core::service::WalletInfo;
-use client_core::transaction_builder::SignedTransferTransaction;
-use client_core::types::{TransactionChange, WalletBalance, WalletKind};
-use client_core::wallet::{CreateWalletRequest, WalletRequest};
+use client_core::transaction_builder::{SignedTransferTransaction, SigningPayload};
+use client_core::types::{
+    parse_staking_address, AddressType, Contact, ContactAddress, LargeOutputRule,
+    MempoolTransaction, OwnedAddress, SpendabilityReport, SpendingPolicy, TransactionChange,
+    TransactionFilter, TransactionHistoryPage, TransferOptions, WalletBalance, WalletEntry,
+    WalletEvent, WalletKind,
+};
+use client_core::wallet::{CreateWalletRequest, ExternalKey, WalletRequest};
 #[cfg(feature = "experimental")]
 use client_core::MultiSigWalletClient;
 use client_core::{Mnemonic, UnspentTransactions, WalletClient};
@@ -20,6 +31,29 @@ use parity_scale_codec::{Decode, Encode};
 use crate::{rpc_error_from_string, to_rpc_error};
 use client_core::hd_wallet::HardwareKind;
 
+/// Default number of unconfirmed transactions fetched by `wallet_mempoolTransactions`
+const DEFAULT_MEMPOOL_LIMIT: u64 = 100;
+/// Default number of events returned by `wallet_replayEvents`
+const DEFAULT_REPLAY_LIMIT: usize = 1000;
+
+/// Output of a transfer (with a bech32 address and an optional timelock)
+#[derive(Debug, Clone, Deserialize)]
+pub struct TransferOutputRequest {
+    pub address: String,
+    pub amount: Coin,
+    pub valid_from: Option<Timespec>,
+}
+
+/// Spending policy of a wallet (with bech32 whitelisted addresses)
+#[derive(Debug, Clone, Deserialize)]
+pub struct SpendingPolicyRequest {
+    pub max_per_transaction: Option<Coin>,
+    pub max_per_day: Option<Coin>,
+    #[serde(default)]
+    pub whitelist: Vec<String>,
+    pub large_output: Option<LargeOutputRule>,
+}
+
 #[rpc(server)]
 pub trait WalletRpc: Send + Sync {
     #[rpc(name = "wallet_balance")]
@@ -68,12 +102,39 @@ pub trait WalletRpc: Send + Sync {
         public_key: PublicKey,
     ) -> Result<String>;
 
+    #[rpc(name = "wallet_exportAccountXpub")]
+    fn export_account_xpub(
+        &self,
+        request: WalletRequest,
+        address_type: String,
+        account: Option<u32>,
+    ) -> Result<String>;
+
+    #[rpc(name = "wallet_importPrivateKey")]
+    fn import_private_key(
+        &self,
+        request: WalletRequest,
+        address_type: String,
+        key_format: String,
+        key: SecUtf8,
+        keystore_password: Option<SecUtf8>,
+    ) -> Result<PublicKey>;
+
+    #[rpc(name = "wallet_listImportedKeys")]
+    fn list_imported_keys(&self, request: WalletRequest) -> Result<Vec<PublicKey>>;
+
     #[rpc(name = "wallet_getViewKey")]
     fn get_view_key(&self, request: WalletRequest, private: bool) -> Result<String>;
 
     #[rpc(name = "wallet_list")]
     fn list(&self) -> Result<Vec<String>>;
 
+    #[rpc(name = "wallet_listWithMetadata")]
+    fn list_with_metadata(&self) -> Result<Vec<WalletEntry>>;
+
+    #[rpc(name = "wallet_findById")]
+    fn find_by_id(&self, id: String) -> Result<Vec<String>>;
+
     #[rpc(name = "wallet_listPublicKeys")]
     fn list_public_keys(&self, request: WalletRequest) -> Result<Vec<PublicKey>>;
 
@@ -95,9 +156,23 @@ pub trait WalletRpc: Send + Sync {
         reversed: Option<bool>,
     ) -> Result<Vec<String>>;
 
+    #[rpc(name = "wallet_isOwnAddress")]
+    fn is_own_address(
+        &self,
+        request: WalletRequest,
+        address: String,
+    ) -> Result<Option<OwnedAddress>>;
+
     #[rpc(name = "wallet_listUTxO")]
     fn list_utxo(&self, request: WalletRequest) -> Result<UnspentTransactions>;
 
+    #[rpc(name = "wallet_spendability")]
+    fn spendability(
+        &self,
+        request: WalletRequest,
+        dust_threshold: Option<Coin>,
+    ) -> Result<SpendabilityReport>;
+
     #[rpc(name = "wallet_sendToAddress")]
     fn send_to_address(
         &self,
@@ -107,6 +182,74 @@ pub trait WalletRpc: Send + Sync {
         view_keys: Vec<String>,
     ) -> Result<String>;
 
+    #[rpc(name = "wallet_sendToContact")]
+    fn send_to_contact(
+        &self,
+        request: WalletRequest,
+        contact_name: String,
+        amount: Coin,
+        view_keys: Vec<String>,
+    ) -> Result<String>;
+
+    #[rpc(name = "wallet_addContact")]
+    fn add_contact(
+        &self,
+        request: WalletRequest,
+        contact_name: String,
+        address: String,
+        memo: Option<String>,
+    ) -> Result<()>;
+
+    #[rpc(name = "wallet_removeContact")]
+    fn remove_contact(&self, request: WalletRequest, contact_name: String) -> Result<bool>;
+
+    #[rpc(name = "wallet_listContacts")]
+    fn list_contacts(&self, request: WalletRequest) -> Result<Vec<Contact>>;
+
+    #[rpc(name = "wallet_setSpendingPolicy")]
+    fn set_spending_policy(
+        &self,
+        request: WalletRequest,
+        policy: SpendingPolicyRequest,
+        override_passphrase: SecUtf8,
+    ) -> Result<()>;
+
+    #[rpc(name = "wallet_removeSpendingPolicy")]
+    fn remove_spending_policy(
+        &self,
+        request: WalletRequest,
+        override_passphrase: SecUtf8,
+    ) -> Result<()>;
+
+    #[rpc(name = "wallet_spendingPolicy")]
+    fn spending_policy(&self, request: WalletRequest) -> Result<Option<SpendingPolicy>>;
+
+    #[rpc(name = "wallet_approveSpendingOverride")]
+    fn approve_spending_override(
+        &self,
+        request: WalletRequest,
+        override_passphrase: SecUtf8,
+    ) -> Result<Timespec>;
+
+    #[rpc(name = "wallet_sendTransfer")]
+    fn send_transfer(
+        &self,
+        request: WalletRequest,
+        outputs: Vec<TransferOutputRequest>,
+        change_address: Option<String>,
+        excluded_inputs: Option<Vec<TxoPointer>>,
+        view_keys: Vec<String>,
+    ) -> Result<String>;
+
+    #[rpc(name = "wallet_freezeOutputs")]
+    fn freeze_outputs(&self, request: WalletRequest, outputs: Vec<TxoPointer>) -> Result<()>;
+
+    #[rpc(name = "wallet_unfreezeOutputs")]
+    fn unfreeze_outputs(&self, request: WalletRequest, outputs: Vec<TxoPointer>) -> Result<()>;
+
+    #[rpc(name = "wallet_listFrozenOutputs")]
+    fn list_frozen_outputs(&self, request: WalletRequest) -> Result<Vec<TxoPointer>>;
+
     #[rpc(name = "wallet_buildRawTransferTx")]
     fn build_raw_transfer_tx(
         &self,
@@ -123,6 +266,23 @@ pub trait WalletRpc: Send + Sync {
         signed_tx: String,
     ) -> Result<String>;
 
+    #[rpc(name = "wallet_buildSigningPayload")]
+    fn build_signing_payload(
+        &self,
+        request: WalletRequest,
+        to_address: String,
+        amount: Coin,
+        view_keys: Vec<String>,
+    ) -> Result<String>;
+
+    #[rpc(name = "wallet_broadcastSigningPayload")]
+    fn broadcast_signing_payload(
+        &self,
+        request: WalletRequest,
+        payload: String,
+        witness: String,
+    ) -> Result<String>;
+
     #[rpc(name = "wallet_transactions")]
     fn transactions(
         &self,
@@ -132,6 +292,31 @@ pub trait WalletRpc: Send + Sync {
         reversed: bool,
     ) -> Result<Vec<TransactionChange>>;
 
+    #[rpc(name = "wallet_queryTransactions")]
+    fn query_transactions(
+        &self,
+        request: WalletRequest,
+        filter: Option<TransactionFilter>,
+        cursor: Option<u64>,
+        limit: usize,
+        reversed: bool,
+    ) -> Result<TransactionHistoryPage>;
+
+    #[rpc(name = "wallet_replayEvents")]
+    fn replay_events(
+        &self,
+        request: WalletRequest,
+        from_sequence: u64,
+        limit: Option<usize>,
+    ) -> Result<Vec<WalletEvent>>;
+
+    #[rpc(name = "wallet_mempoolTransactions")]
+    fn mempool_transactions(
+        &self,
+        request: WalletRequest,
+        limit: Option<u64>,
+    ) -> Result<Vec<MempoolTransaction>>;
+
     #[rpc(name = "wallet_exportTransaction")]
     fn export_plain_tx(&self, request: WalletRequest, txid: String) -> Result<String>;
 
@@ -193,16 +378,19 @@ where
             )
             .map_err(to_rpc_error)?;
 
-        self.client
-            .new_staking_address(&request.name, &enckey)
-            .map_err(to_rpc_error)?;
-        self.client
-            .new_transfer_address(&request.name, &enckey)
-            .map_err(to_rpc_error)?;
+        // watch-only wallets only get the watch addresses added with public keys
+        if kind != WalletKind::WatchOnly {
+            self.client
+                .new_staking_address(&request.name, &enckey)
+                .map_err(to_rpc_error)?;
+            self.client
+                .new_transfer_address(&request.name, &enckey)
+                .map_err(to_rpc_error)?;
+        }
 
         self.client.flush_database().map_err(to_rpc_error)?;
         match (kind, mnemonic) {
-            (WalletKind::Basic, None) => Ok((enckey, None)),
+            (WalletKind::Basic, None) | (WalletKind::WatchOnly, None) => Ok((enckey, None)),
             (WalletKind::HD, Some(mnemonic)) => {
                 Ok((enckey, Some(mnemonic.unsecure_phrase().to_string())))
             }
@@ -364,6 +552,68 @@ where
         self.client.wallets().map_err(to_rpc_error)
     }
 
+    fn list_with_metadata(&self) -> Result<Vec<WalletEntry>> {
+        self.client.wallet_entries().map_err(to_rpc_error)
+    }
+
+    fn find_by_id(&self, id: String) -> Result<Vec<String>> {
+        self.client.wallets_by_id(&id).map_err(to_rpc_error)
+    }
+
+    fn export_account_xpub(
+        &self,
+        request: WalletRequest,
+        address_type: String,
+        account: Option<u32>,
+    ) -> Result<String> {
+        let address_type = AddressType::from_str(&address_type).map_err(to_rpc_error)?;
+        self.client
+            .export_account_xpub(
+                &request.name,
+                &request.enckey,
+                account.unwrap_or_default(),
+                address_type,
+            )
+            .map_err(to_rpc_error)
+    }
+
+    fn import_private_key(
+        &self,
+        request: WalletRequest,
+        address_type: String,
+        key_format: String,
+        key: SecUtf8,
+        keystore_password: Option<SecUtf8>,
+    ) -> Result<PublicKey> {
+        let address_type = AddressType::from_str(&address_type).map_err(to_rpc_error)?;
+        let key = match (key_format.as_str(), keystore_password) {
+            ("hex", None) => ExternalKey::Hex(key),
+            ("wif", None) => ExternalKey::Wif(key),
+            ("keystore", Some(password)) => ExternalKey::Keystore {
+                json: key,
+                password,
+            },
+            _ => {
+                return Err(rpc_error_from_string(
+                    "Key format can either be `hex`, `wif` or `keystore` (with password)"
+                        .to_owned(),
+                ))
+            }
+        };
+        let public_key = self
+            .client
+            .import_private_key(&request.name, &request.enckey, &key, address_type)
+            .map_err(to_rpc_error)?;
+        self.client.flush_database().map_err(to_rpc_error)?;
+        Ok(public_key)
+    }
+
+    fn list_imported_keys(&self, request: WalletRequest) -> Result<Vec<PublicKey>> {
+        self.client
+            .imported_keys(&request.name, &request.enckey)
+            .map_err(to_rpc_error)
+    }
+
     fn list_public_keys(&self, request: WalletRequest) -> Result<Vec<PublicKey>> {
         self.client
             .public_keys(&request.name, &request.enckey)
@@ -409,12 +659,48 @@ where
             .map_err(to_rpc_error)
     }
 
+    fn is_own_address(
+        &self,
+        request: WalletRequest,
+        address: String,
+    ) -> Result<Option<OwnedAddress>> {
+        let address = address.trim();
+        // staking addresses are hex encoded (`0x...`), transfer addresses are bech32 encoded
+        if address.starts_with("0x") {
+            let address = parse_staking_address(address).map_err(to_rpc_error)?;
+            self.client
+                .staking_address_ownership(&request.name, &request.enckey, &address)
+                .map_err(to_rpc_error)
+        } else {
+            let address = address
+                .parse::<ExtendedAddr>()
+                .map_err(|err| rpc_error_from_string(format!("{}", err)))?;
+            self.client
+                .transfer_address_ownership(&request.name, &request.enckey, &address)
+                .map_err(to_rpc_error)
+        }
+    }
+
     fn list_utxo(&self, request: WalletRequest) -> Result<UnspentTransactions> {
         self.client
             .unspent_transactions(&request.name, &request.enckey)
             .map_err(to_rpc_error)
     }
 
+    fn spendability(
+        &self,
+        request: WalletRequest,
+        dust_threshold: Option<Coin>,
+    ) -> Result<SpendabilityReport> {
+        self.client
+            .spendability(
+                &request.name,
+                &request.enckey,
+                dust_threshold.unwrap_or_default(),
+            )
+            .map_err(to_rpc_error)
+    }
+
     fn send_to_address(
         &self,
         request: WalletRequest,
@@ -445,6 +731,206 @@ where
         Ok(hex::encode(tx_id))
     }
 
+    fn send_to_contact(
+        &self,
+        request: WalletRequest,
+        contact_name: String,
+        amount: Coin,
+        view_keys: Vec<String>,
+    ) -> Result<String> {
+        let contact = self
+            .client
+            .contact(&request.name, &request.enckey, &contact_name)
+            .map_err(to_rpc_error)?
+            .ok_or_else(|| {
+                rpc_error_from_string(format!("Contact with name {} not found", contact_name))
+            })?;
+        let address = match contact.address {
+            ContactAddress::Transfer(address) => address,
+            ContactAddress::Staking(_) => {
+                return Err(rpc_error_from_string(format!(
+                    "Contact {} has a staking address (use staking_depositStake instead)",
+                    contact_name
+                )))
+            }
+        };
+        let mut view_keys = view_keys
+            .iter()
+            .map(|view_key| PublicKey::from_str(view_key))
+            .collect::<CommonResult<BTreeSet<PublicKey>>>()
+            .map_err(to_rpc_error)?;
+        let tx_id = self
+            .client
+            .send_to_address(
+                &request.name,
+                &request.enckey,
+                amount,
+                address,
+                &mut view_keys,
+                self.network_id,
+            )
+            .map_err(to_rpc_error)?;
+        self.client.flush_database().map_err(to_rpc_error)?;
+        Ok(hex::encode(tx_id))
+    }
+
+    fn add_contact(
+        &self,
+        request: WalletRequest,
+        contact_name: String,
+        address: String,
+        memo: Option<String>,
+    ) -> Result<()> {
+        // transfer addresses of another network are rejected
+        let address = ContactAddress::parse(&address, get_network()).map_err(to_rpc_error)?;
+        let contact = Contact {
+            name: contact_name,
+            address,
+            memo,
+        };
+        self.client
+            .add_contact(&request.name, &request.enckey, contact)
+            .map_err(to_rpc_error)
+    }
+
+    fn remove_contact(&self, request: WalletRequest, contact_name: String) -> Result<bool> {
+        self.client
+            .remove_contact(&request.name, &request.enckey, &contact_name)
+            .map_err(to_rpc_error)
+    }
+
+    fn list_contacts(&self, request: WalletRequest) -> Result<Vec<Contact>> {
+        self.client
+            .contacts(&request.name, &request.enckey)
+            .map_err(to_rpc_error)
+    }
+
+    fn set_spending_policy(
+        &self,
+        request: WalletRequest,
+        policy: SpendingPolicyRequest,
+        override_passphrase: SecUtf8,
+    ) -> Result<()> {
+        let whitelist = policy
+            .whitelist
+            .iter()
+            .map(|address| {
+                address
+                    .parse::<ExtendedAddr>()
+                    .map_err(|err| rpc_error_from_string(format!("{}", err)))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let policy = SpendingPolicy {
+            max_per_transaction: policy.max_per_transaction,
+            max_per_day: policy.max_per_day,
+            whitelist,
+            large_output: policy.large_output,
+        };
+        self.client
+            .set_spending_policy(&request.name, &request.enckey, policy, &override_passphrase)
+            .map_err(to_rpc_error)
+    }
+
+    fn remove_spending_policy(
+        &self,
+        request: WalletRequest,
+        override_passphrase: SecUtf8,
+    ) -> Result<()> {
+        self.client
+            .remove_spending_policy(&request.name, &request.enckey, &override_passphrase)
+            .map_err(to_rpc_error)
+    }
+
+    fn spending_policy(&self, request: WalletRequest) -> Result<Option<SpendingPolicy>> {
+        self.client
+            .spending_policy(&request.name, &request.enckey)
+            .map_err(to_rpc_error)
+    }
+
+    fn approve_spending_override(
+        &self,
+        request: WalletRequest,
+        override_passphrase: SecUtf8,
+    ) -> Result<Timespec> {
+        self.client
+            .approve_spending_override(&request.name, &request.enckey, &override_passphrase)
+            .map_err(to_rpc_error)
+    }
+
+    fn send_transfer(
+        &self,
+        request: WalletRequest,
+        outputs: Vec<TransferOutputRequest>,
+        change_address: Option<String>,
+        excluded_inputs: Option<Vec<TxoPointer>>,
+        view_keys: Vec<String>,
+    ) -> Result<String> {
+        let outputs = outputs
+            .into_iter()
+            .map(|output| {
+                let address = output
+                    .address
+                    .parse::<ExtendedAddr>()
+                    .map_err(|err| rpc_error_from_string(format!("{}", err)))?;
+                Ok(match output.valid_from {
+                    Some(valid_from) => {
+                        TxOut::new_with_timelock(address, output.amount, valid_from)
+                    }
+                    None => TxOut::new(address, output.amount),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let change_address = change_address
+            .map(|address| {
+                address
+                    .parse::<ExtendedAddr>()
+                    .map_err(|err| rpc_error_from_string(format!("{}", err)))
+            })
+            .transpose()?;
+        let options = TransferOptions {
+            change_address,
+            excluded_inputs: excluded_inputs.unwrap_or_default(),
+        };
+        let mut view_keys = view_keys
+            .iter()
+            .map(|view_key| PublicKey::from_str(view_key))
+            .collect::<CommonResult<BTreeSet<PublicKey>>>()
+            .map_err(to_rpc_error)?;
+        let tx_id = self
+            .client
+            .send_transfer(
+                &request.name,
+                &request.enckey,
+                outputs,
+                &options,
+                &mut view_keys,
+                self.network_id,
+            )
+            .map_err(to_rpc_error)?;
+        self.client.flush_database().map_err(to_rpc_error)?;
+        Ok(hex::encode(tx_id))
+    }
+
+    fn freeze_outputs(&self, request: WalletRequest, outputs: Vec<TxoPointer>) -> Result<()> {
+        self.client
+            .freeze_outputs(&request.name, &request.enckey, &outputs)
+            .map_err(to_rpc_error)?;
+        self.client.flush_database().map_err(to_rpc_error)
+    }
+
+    fn unfreeze_outputs(&self, request: WalletRequest, outputs: Vec<TxoPointer>) -> Result<()> {
+        self.client
+            .unfreeze_outputs(&request.name, &request.enckey, &outputs)
+            .map_err(to_rpc_error)?;
+        self.client.flush_database().map_err(to_rpc_error)
+    }
+
+    fn list_frozen_outputs(&self, request: WalletRequest) -> Result<Vec<TxoPointer>> {
+        self.client
+            .frozen_outputs(&request.name, &request.enckey)
+            .map_err(to_rpc_error)
+    }
+
     fn build_raw_transfer_tx(
         &self,
         request: WalletRequest,
@@ -493,6 +979,53 @@ where
         Ok(hex::encode(tx_id))
     }
 
+    fn build_signing_payload(
+        &self,
+        request: WalletRequest,
+        to_address: String,
+        amount: Coin,
+        view_keys: Vec<String>,
+    ) -> Result<String> {
+        let to_address = to_address
+            .parse::<ExtendedAddr>()
+            .map_err(|err| rpc_error_from_string(format!("{}", err)))?;
+        let view_keys = view_keys
+            .iter()
+            .map(|view_key| PublicKey::from_str(view_key))
+            .collect::<CommonResult<Vec<PublicKey>>>()
+            .map_err(to_rpc_error)?;
+        let payload = self
+            .client
+            .build_signing_payload(
+                &request.name,
+                &request.enckey,
+                to_address,
+                amount,
+                view_keys,
+                self.network_id,
+            )
+            .map_err(to_rpc_error)?;
+        self.client.flush_database().map_err(to_rpc_error)?;
+        Ok(payload.to_string())
+    }
+
+    fn broadcast_signing_payload(
+        &self,
+        request: WalletRequest,
+        payload: String,
+        witness: String,
+    ) -> Result<String> {
+        let payload = SigningPayload::from_str(&payload).map_err(to_rpc_error)?;
+        let raw_witness = base64::decode(&witness).map_err(to_rpc_error)?;
+        let witness = TxWitness::decode(&mut raw_witness.as_slice()).map_err(to_rpc_error)?;
+        let tx_id = self
+            .client
+            .broadcast_signing_payload(&request.name, &request.enckey, payload, witness)
+            .map_err(to_rpc_error)?;
+        self.client.flush_database().map_err(to_rpc_error)?;
+        Ok(hex::encode(tx_id))
+    }
+
     fn export_plain_tx(&self, request: WalletRequest, txid: String) -> Result<String> {
         let tx_info = self
             .client
@@ -523,6 +1056,56 @@ where
             .map_err(to_rpc_error)
     }
 
+    fn query_transactions(
+        &self,
+        request: WalletRequest,
+        filter: Option<TransactionFilter>,
+        cursor: Option<u64>,
+        limit: usize,
+        reversed: bool,
+    ) -> Result<TransactionHistoryPage> {
+        self.client
+            .query_history(
+                &request.name,
+                &request.enckey,
+                &filter.unwrap_or_default(),
+                cursor,
+                limit,
+                reversed,
+            )
+            .map_err(to_rpc_error)
+    }
+
+    fn replay_events(
+        &self,
+        request: WalletRequest,
+        from_sequence: u64,
+        limit: Option<usize>,
+    ) -> Result<Vec<WalletEvent>> {
+        self.client
+            .replay_events(
+                &request.name,
+                &request.enckey,
+                from_sequence,
+                limit.unwrap_or(DEFAULT_REPLAY_LIMIT),
+            )
+            .map_err(to_rpc_error)
+    }
+
+    fn mempool_transactions(
+        &self,
+        request: WalletRequest,
+        limit: Option<u64>,
+    ) -> Result<Vec<MempoolTransaction>> {
+        self.client
+            .mempool_transactions(
+                &request.name,
+                &request.enckey,
+                limit.unwrap_or(DEFAULT_MEMPOOL_LIMIT),
+            )
+            .map_err(to_rpc_error)
+    }
+
     fn get_enc_key(&self, request: CreateWalletRequest) -> Result<SecKey> {
         self.client
             .auth_token(&request.name, &request.passphrase)
@@ -751,6 +1334,10 @@ pub mod tests {
             unreachable!("query")
         }
 
+        fn unconfirmed_txs(&self, _limit: u64) -> Result<Vec<Vec<u8>>> {
+            unreachable!()
+        }
+
         fn query_state_batch<T: Iterator<Item = u64>>(
             &self,
             _heights: T,
@@ -0,0 +1,48 @@
+//! Sessions of the clients connected over a persistent (WebSocket) connection
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+
+use serde_json::{json, Value};
+
+static NEXT_SESSION_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Persistent connection of a client, which the notifications of its subscriptions are pushed to
+#[derive(Debug, Clone)]
+pub struct Session {
+    id: u64,
+    sender: Arc<Mutex<Sender<String>>>,
+}
+
+impl Session {
+    /// Creates a session whose notifications (serialized JSON-RPC requests) are sent to `sender`
+    pub fn new(sender: Sender<String>) -> Self {
+        Session {
+            id: NEXT_SESSION_ID.fetch_add(1, Ordering::Relaxed),
+            sender: Arc::new(Mutex::new(sender)),
+        }
+    }
+
+    /// Unique ID of the session
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Pushes a JSON-RPC notification of the subscription, returns `false` if the connection
+    /// is closed
+    pub fn notify(&self, method: &str, subscription: &str, result: Value) -> bool {
+        let notification = json!({
+            "jsonrpc": "2.0",
+            "method": method,
+            "params": {
+                "subscription": subscription,
+                "result": result,
+            },
+        });
+        self.sender
+            .lock()
+            .expect("session sender lock")
+            .send(notification.to_string())
+            .is_ok()
+    }
+}
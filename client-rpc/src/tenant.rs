@@ -0,0 +1,229 @@
+//! Multi-tenant hosted wallet mode: each tenant has its own (namespaced and encrypted)
+//! wallet storage and services, and its credentials can only access the tenant's services
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use jsonrpc_core::futures::future::{self, Either};
+use jsonrpc_core::futures::Future;
+use jsonrpc_core::{
+    Call, Error, ErrorCode, FutureOutput, FutureResponse, MetaIoHandler, Middleware, Output,
+};
+use serde::Deserialize;
+
+use crate::permission::{PermissionPolicy, RpcMeta, MISSING_CREDENTIALS_CODE};
+use client_common::correlation::log_prefix;
+use client_common::storage::validate_tenant_id;
+use client_common::{ErrorKind, Result, ResultExt, SecKey};
+
+/// Configuration of a tenant
+#[derive(Debug, Clone, Deserialize)]
+pub struct TenantConfig {
+    /// key wrapping the tenant's storage encryption key (hex)
+    pub master_key: SecKey,
+    /// permission scopes of the tenant's credential tokens
+    pub credentials: PermissionPolicy,
+}
+
+/// Tenants by their identifiers
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(transparent)]
+pub struct TenantPolicy {
+    tenants: BTreeMap<String, TenantConfig>,
+}
+
+impl TenantPolicy {
+    /// Loads the tenants from a JSON file, e.g.
+    /// `{"<tenant>": {"master_key": "<hex>", "credentials": {"<token>": ["admin"]}}}`
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path).chain(|| {
+            (
+                ErrorKind::IoError,
+                format!("Unable to read tenants file: {}", path.display()),
+            )
+        })?;
+        let policy: TenantPolicy = serde_json::from_str(&content).chain(|| {
+            (
+                ErrorKind::DeserializationError,
+                format!("Unable to parse tenants file: {}", path.display()),
+            )
+        })?;
+        policy.validate()?;
+        Ok(policy)
+    }
+
+    /// Adds the tenant (unless its identifier is invalid or its tokens are already used)
+    pub fn add(&mut self, tenant: &str, config: TenantConfig) -> Result<()> {
+        let mut tenants = self.tenants.clone();
+        tenants.insert(tenant.to_owned(), config);
+        let policy = TenantPolicy { tenants };
+        policy.validate()?;
+        *self = policy;
+        Ok(())
+    }
+
+    /// Checks the tenant identifiers, and that no credential token is shared by tenants
+    fn validate(&self) -> Result<()> {
+        let mut owners = HashMap::new();
+        for (tenant, config) in self.tenants.iter() {
+            validate_tenant_id(tenant)?;
+            for token in config.credentials.tokens() {
+                if let Some(owner) = owners.insert(token, tenant) {
+                    return Err(client_common::Error::new(
+                        ErrorKind::InvalidInput,
+                        format!(
+                            "Credential token shared by tenants: {} and {}",
+                            owner, tenant
+                        ),
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Tenants with their configurations
+    pub fn tenants(&self) -> impl Iterator<Item = (&str, &TenantConfig)> {
+        self.tenants
+            .iter()
+            .map(|(tenant, config)| (tenant.as_str(), config))
+    }
+
+    /// Tenant of the credential token
+    pub fn authenticate(&self, token: Option<&str>) -> Option<&str> {
+        let token = token?;
+        self.tenants
+            .iter()
+            .find(|(_, config)| config.credentials.contains(token))
+            .map(|(tenant, _)| tenant.as_str())
+    }
+}
+
+/// Middleware routing every call to the services of the tenant of the request credentials
+/// (after checking the permission scopes of the credentials in the tenant)
+#[derive(Clone)]
+pub struct TenantMiddleware {
+    policy: Arc<TenantPolicy>,
+    handlers: Arc<HashMap<String, MetaIoHandler<RpcMeta>>>,
+}
+
+impl TenantMiddleware {
+    /// `handlers` are the services of each tenant of the policy (there must be one per tenant)
+    pub fn new(policy: TenantPolicy, handlers: HashMap<String, MetaIoHandler<RpcMeta>>) -> Self {
+        TenantMiddleware {
+            policy: Arc::new(policy),
+            handlers: Arc::new(handlers),
+        }
+    }
+
+    fn route(&self, method: &str, meta: &RpcMeta) -> std::result::Result<&str, Error> {
+        let token = meta.token.as_deref();
+        let tenant = self.policy.authenticate(token).ok_or_else(|| Error {
+            code: ErrorCode::ServerError(MISSING_CREDENTIALS_CODE),
+            message: "Missing or unknown RPC credentials".to_owned(),
+            data: None,
+        })?;
+        self.policy.tenants[tenant]
+            .credentials
+            .check(token, method)?;
+        Ok(tenant)
+    }
+}
+
+impl Middleware<RpcMeta> for TenantMiddleware {
+    type Future = FutureResponse;
+    type CallFuture = FutureOutput;
+
+    fn on_call<F, X>(&self, call: Call, meta: RpcMeta, next: F) -> Either<Self::CallFuture, X>
+    where
+        F: Fn(Call, RpcMeta) -> X + Send + Sync,
+        X: Future<Item = Option<Output>, Error = ()> + Send + 'static,
+    {
+        let _correlation = meta.trace_call(&call);
+        let routed = match &call {
+            Call::MethodCall(method_call) => {
+                self.route(&method_call.method, &meta).map_err(|error| {
+                    Some(Output::from(
+                        Err(error),
+                        method_call.id.clone(),
+                        method_call.jsonrpc,
+                    ))
+                })
+            }
+            Call::Notification(notification) => {
+                self.route(&notification.method, &meta).map_err(|_| None)
+            }
+            Call::Invalid { .. } => return Either::B(next(call, meta)),
+        };
+        match routed {
+            Ok(tenant) => {
+                let handler = &self.handlers[tenant];
+                Either::A(Box::new(handler.handle_call(call, meta)))
+            }
+            Err(output) => {
+                log::warn!("{}RPC call denied: {:?}", log_prefix(), output);
+                Either::A(Box::new(future::ok(output)))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::permission::{Scope, INSUFFICIENT_SCOPE_CODE};
+    use client_common::seckey::generate_enckey;
+    use jsonrpc_core::Value;
+
+    fn tenant(token: &str, scopes: &[Scope]) -> TenantConfig {
+        let mut credentials = PermissionPolicy::default();
+        credentials.grant(token, scopes);
+        TenantConfig {
+            master_key: generate_enckey(),
+            credentials,
+        }
+    }
+
+    fn handler(name: &'static str) -> MetaIoHandler<RpcMeta> {
+        let mut io = MetaIoHandler::default();
+        io.add_method("wallet_list", move |_| Ok(Value::String(name.to_owned())));
+        io
+    }
+
+    fn call(io: &MetaIoHandler<RpcMeta, TenantMiddleware>, token: &str, method: &str) -> String {
+        let request = format!(
+            r#"{{"jsonrpc": "2.0", "method": "{}", "params": [], "id": 1}}"#,
+            method
+        );
+        let meta = RpcMeta {
+            token: Some(token.to_owned()),
+            ..Default::default()
+        };
+        io.handle_request_sync(&request, meta).unwrap()
+    }
+
+    #[test]
+    fn check_tenant_routing() {
+        let mut policy = TenantPolicy::default();
+        policy.add("a", tenant("token-a", &[Scope::Read])).unwrap();
+        policy.add("b", tenant("token-b", &[Scope::Admin])).unwrap();
+        assert!(policy.add("c", tenant("token-a", &[Scope::Read])).is_err());
+        assert!(policy
+            .add("../c", tenant("token-c", &[Scope::Read]))
+            .is_err());
+
+        let mut handlers = HashMap::new();
+        handlers.insert("a".to_owned(), handler("a"));
+        handlers.insert("b".to_owned(), handler("b"));
+        let io = MetaIoHandler::with_middleware(TenantMiddleware::new(policy, handlers));
+
+        assert!(call(&io, "token-a", "wallet_list").contains(r#""result":"a""#));
+        assert!(call(&io, "token-b", "wallet_list").contains(r#""result":"b""#));
+        assert!(
+            call(&io, "token-a", "wallet_create").contains(&INSUFFICIENT_SCOPE_CODE.to_string())
+        );
+        assert!(call(&io, "unknown", "wallet_list").contains(&MISSING_CREDENTIALS_CODE.to_string()));
+    }
+}
@@ -218,6 +218,7 @@ unsafe fn create_rpc(
         enable_fast_forward: false,
         disable_light_client: true,
         enable_address_recovery: true,
+        enable_block_filters: false,
         batch_size: 50,
         block_height_ensure: 50,
         light_client_peers: "0000000000000000000000000000000000000000@127.0.0.1:26657,1000000000000000000000000000000000000000@127.0.0.1:26657"
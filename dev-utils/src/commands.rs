@@ -1,15 +1,21 @@
+mod ceremony_command;
 mod genesis_command;
 mod genesis_dev_config;
 mod init_command;
 mod keypackage_command;
+mod replay_check_command;
 mod run_command;
+mod scale_schema_command;
 mod stop_command;
 mod test_vector_command;
 
+pub use self::ceremony_command::CeremonyCommand;
 pub use self::genesis_command::GenesisCommand;
 pub use self::genesis_dev_config::{GenesisDevConfig, InitialFeePolicy};
 pub use self::init_command::InitCommand;
 pub use self::keypackage_command::KeypackageCommand;
+pub use self::replay_check_command::ReplayCheckCommand;
 pub use self::run_command::RunCommand;
+pub use self::scale_schema_command::ScaleSchemaCommand;
 pub use self::stop_command::StopCommand;
 pub use self::test_vector_command::TestVectorCommand;
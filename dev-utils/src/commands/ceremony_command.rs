@@ -0,0 +1,522 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use quest::password;
+use secp256k1::recovery::{RecoverableSignature, RecoveryId};
+use secp256k1::{Message, PublicKey, SecretKey};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use structopt::StructOpt;
+
+use chain_core::common::H256;
+use chain_core::init::address::{keccak256, RedeemAddress};
+use chain_core::init::config::InitConfig;
+use chain_core::state::account::{ConfidentialInit, MLSInit, NodeName, NodeSecurityContact};
+use chain_core::state::tendermint::{TendermintValidator, TendermintValidatorPubKey};
+use client_common::tendermint::types::Time;
+use client_common::{Error, ErrorKind, Result, ResultExt};
+
+use super::genesis_command::generate_genesis;
+use crate::commands::genesis_dev_config::GenesisDevConfig;
+use crate::verify_keypackage;
+
+/// Multi-party genesis ceremony:
+/// 1. each council node operator creates a signed registration (`register`)
+/// 2. the coordinator assembles the genesis from the base config and registrations (`assemble`)
+/// 3. each operator independently re-assembles the genesis and signs the result (`attest`)
+/// 4. anyone can check the result was attested by all the registered council nodes (`verify`)
+#[derive(Debug, StructOpt)]
+pub enum CeremonyCommand {
+    #[structopt(
+        name = "register",
+        about = "Create a council node registration signed by the staking address key"
+    )]
+    Register {
+        #[structopt(name = "name", short, long, help = "Council node name")]
+        name: NodeName,
+        #[structopt(
+            name = "security_contact",
+            short,
+            long,
+            help = "Optional security contact (e.g. security@example.com)"
+        )]
+        security_contact: Option<String>,
+        #[structopt(
+            name = "validator_pubkey",
+            short,
+            long,
+            help = "Base64 encoded Tendermint validator public key (from priv_validator_key.json)"
+        )]
+        validator_pubkey: String,
+        #[structopt(
+            name = "keypackage",
+            short,
+            long,
+            help = "Path to base64 encoded keypackage (see `dev-utils keypackage generate`)"
+        )]
+        keypackage: PathBuf,
+        #[structopt(
+            name = "output",
+            short,
+            long,
+            help = "Path to the registration file to create"
+        )]
+        output: PathBuf,
+    },
+    #[structopt(
+        name = "assemble",
+        about = "Validate registrations and deterministically assemble the genesis"
+    )]
+    Assemble {
+        #[structopt(flatten)]
+        inputs: CeremonyInputs,
+        #[structopt(
+            name = "genesis_time",
+            short = "t",
+            long,
+            help = "Genesis time agreed by the participants (e.g. 2020-10-01T00:00:00Z)"
+        )]
+        genesis_time: String,
+        #[structopt(
+            name = "output",
+            short,
+            long,
+            help = "Path to the ceremony result file to create"
+        )]
+        output: PathBuf,
+    },
+    #[structopt(
+        name = "attest",
+        about = "Re-assemble the genesis and sign the ceremony result if it matches"
+    )]
+    Attest {
+        #[structopt(flatten)]
+        inputs: CeremonyInputs,
+        #[structopt(name = "result", long, help = "Path to the ceremony result file")]
+        result: PathBuf,
+        #[structopt(
+            name = "output",
+            short,
+            long,
+            help = "Path to the attestation file to create"
+        )]
+        output: PathBuf,
+    },
+    #[structopt(
+        name = "verify",
+        about = "Check the ceremony result is attested by all registered council nodes"
+    )]
+    Verify {
+        #[structopt(flatten)]
+        inputs: CeremonyInputs,
+        #[structopt(name = "result", long, help = "Path to the ceremony result file")]
+        result: PathBuf,
+        #[structopt(
+            name = "attestations",
+            short,
+            long,
+            help = "Paths to the attestation files"
+        )]
+        attestations: Vec<PathBuf>,
+    },
+}
+
+/// Inputs of the genesis assembly (shared by `assemble`, `attest` and `verify`)
+#[derive(Debug, StructOpt)]
+pub struct CeremonyInputs {
+    #[structopt(
+        name = "genesis_dev_config_path",
+        short,
+        long,
+        help = "Path to the base genesis configuration with no council nodes -- see example-dev-conf.json"
+    )]
+    genesis_dev_config_path: PathBuf,
+    #[structopt(
+        name = "registrations",
+        short,
+        long,
+        help = "Paths to the council node registration files"
+    )]
+    registrations: Vec<PathBuf>,
+    #[structopt(
+        name = "skip_keypackage_verification",
+        long,
+        help = "Don't verify keypackages of the registrations (only for development networks)"
+    )]
+    skip_keypackage_verification: bool,
+}
+
+/// Council node data submitted by its operator
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CouncilNodeRegistration {
+    pub staking_address: RedeemAddress,
+    pub name: NodeName,
+    pub security_contact: NodeSecurityContact,
+    pub validator_pubkey: TendermintValidatorPubKey,
+    pub confidential_init: ConfidentialInit,
+}
+
+/// Statement of a council node operator that the ceremony result is correct
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CeremonyAttestation {
+    pub staking_address: RedeemAddress,
+    pub app_hash: String,
+    /// hex-encoded keccak256 digest of the ceremony result file
+    pub result_digest: String,
+}
+
+/// Payload with a recoverable signature (hex-encoded recovery id + compact signature)
+/// of the staking address key over the keccak256 digest of the JSON-serialized payload
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Signed<T> {
+    pub payload: T,
+    pub signature: String,
+}
+
+/// Assembled genesis
+#[derive(Debug, Serialize)]
+pub struct CeremonyResult {
+    pub genesis_time: String,
+    pub app_hash: String,
+    pub app_state: InitConfig,
+    pub validators: Vec<TendermintValidator>,
+}
+
+impl CeremonyCommand {
+    pub fn execute(&self) -> Result<()> {
+        match self {
+            CeremonyCommand::Register {
+                name,
+                security_contact,
+                validator_pubkey,
+                keypackage,
+                output,
+            } => {
+                let keypackage = fs::read_to_string(keypackage)
+                    .chain(|| (ErrorKind::IoError, "Unable to read keypackage"))?;
+                let keypackage = base64::decode(keypackage.trim())
+                    .chain(|| (ErrorKind::InvalidInput, "Unable to parse keypackage"))?;
+                let validator_pubkey =
+                    TendermintValidatorPubKey::from_base64(validator_pubkey.as_bytes())
+                        .chain(|| (ErrorKind::InvalidInput, "Invalid validator public key"))?;
+                let secret_key = ask_secret_key()?;
+                let staking_address = RedeemAddress::from(&PublicKey::from_secret_key(
+                    &secp256k1::SECP256K1,
+                    &secret_key,
+                ));
+                let registration = CouncilNodeRegistration {
+                    staking_address,
+                    name: name.clone(),
+                    security_contact: security_contact.clone(),
+                    validator_pubkey,
+                    confidential_init: ConfidentialInit {
+                        init_payload: MLSInit::Genesis(keypackage),
+                    },
+                };
+                write_json(output, &sign(registration, &secret_key)?)?;
+                println!("Registration of {} created", staking_address);
+                Ok(())
+            }
+            CeremonyCommand::Assemble {
+                inputs,
+                genesis_time,
+                output,
+            } => {
+                let result = inputs.assemble(genesis_time)?;
+                write_json(output, &result)?;
+                println!("App hash: {}", result.app_hash);
+                Ok(())
+            }
+            CeremonyCommand::Attest {
+                inputs,
+                result,
+                output,
+            } => {
+                let (result, result_digest) = inputs.check_result(result)?;
+                let secret_key = ask_secret_key()?;
+                let staking_address = RedeemAddress::from(&PublicKey::from_secret_key(
+                    &secp256k1::SECP256K1,
+                    &secret_key,
+                ));
+                let attestation = CeremonyAttestation {
+                    staking_address,
+                    app_hash: result.app_hash,
+                    result_digest: hex::encode(result_digest),
+                };
+                write_json(output, &sign(attestation, &secret_key)?)?;
+                println!("Attestation of {} created", staking_address);
+                Ok(())
+            }
+            CeremonyCommand::Verify {
+                inputs,
+                result,
+                attestations,
+            } => {
+                let (result, result_digest) = inputs.check_result(result)?;
+                let mut pending: BTreeSet<RedeemAddress> =
+                    result.app_state.council_nodes.keys().copied().collect();
+                for path in attestations.iter() {
+                    let attestation: CeremonyAttestation = verify(read_json(path)?)?;
+                    if attestation.result_digest != hex::encode(result_digest) {
+                        return Err(Error::new(
+                            ErrorKind::VerifyError,
+                            format!(
+                                "Attestation of {} is for a different result",
+                                attestation.staking_address
+                            ),
+                        ));
+                    }
+                    if !pending.remove(&attestation.staking_address) {
+                        return Err(Error::new(
+                            ErrorKind::VerifyError,
+                            format!(
+                                "Attestation of {} is not from a pending council node",
+                                attestation.staking_address
+                            ),
+                        ));
+                    }
+                }
+                if !pending.is_empty() {
+                    let pending: Vec<String> = pending.iter().map(ToString::to_string).collect();
+                    return Err(Error::new(
+                        ErrorKind::VerifyError,
+                        format!("Missing attestations of: {}", pending.join(", ")),
+                    ));
+                }
+                println!("Ceremony result attested by all council nodes");
+                println!("App hash: {}", result.app_hash);
+                Ok(())
+            }
+        }
+    }
+}
+
+impl CeremonyInputs {
+    /// Validates the registrations and assembles the genesis
+    /// (the result only depends on the inputs, not on the order of registrations)
+    fn assemble(&self, genesis_time: &str) -> Result<CeremonyResult> {
+        let genesis_time = Time::from_str(genesis_time)
+            .chain(|| (ErrorKind::InvalidInput, "Invalid genesis time"))?;
+        let mut config: GenesisDevConfig = read_json(&self.genesis_dev_config_path)?;
+        if !config.council_nodes.is_empty() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Base genesis configuration should not contain council nodes",
+            ));
+        }
+
+        let mut validator_pubkeys = BTreeSet::new();
+        for path in self.registrations.iter() {
+            let registration: CouncilNodeRegistration = verify(read_json(path)?)?;
+            let address = registration.staking_address;
+            let stake = config.distribution.get(&address).chain(|| {
+                (
+                    ErrorKind::InvalidInput,
+                    format!("Council node {} does not have fund distribution", address),
+                )
+            })?;
+            if *stake < config.required_council_node_stake {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Council node {} does not have the required stake", address),
+                ));
+            }
+            if !validator_pubkeys.insert(registration.validator_pubkey.clone()) {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Duplicate validator public key of {}", address),
+                ));
+            }
+            if !self.skip_keypackage_verification {
+                match &registration.confidential_init.init_payload {
+                    MLSInit::Genesis(keypackage) => verify_keypackage(keypackage)?,
+                    _ => {
+                        return Err(Error::new(
+                            ErrorKind::InvalidInput,
+                            format!("Council node {} does not have a keypackage", address),
+                        ))
+                    }
+                }
+            }
+            let node = (
+                registration.name,
+                registration.security_contact,
+                registration.validator_pubkey,
+                registration.confidential_init,
+            );
+            if config.council_nodes.insert(address, node).is_some() {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Duplicate registration of {}", address),
+                ));
+            }
+        }
+
+        let timespec = genesis_time
+            .duration_since(Time::unix_epoch())
+            .chain(|| (ErrorKind::InvalidInput, "Invalid genesis time"))?
+            .as_secs();
+        let (app_hash, app_state, validators) = generate_genesis(&config, timespec, &None)?;
+        Ok(CeremonyResult {
+            genesis_time: genesis_time.to_string(),
+            app_hash,
+            app_state,
+            validators,
+        })
+    }
+
+    /// Re-assembles the genesis and checks it matches the result file (returns the file digest)
+    fn check_result(&self, path: &Path) -> Result<(CeremonyResult, H256)> {
+        let content = fs::read_to_string(path)
+            .chain(|| (ErrorKind::IoError, "Unable to read ceremony result"))?;
+        let claimed: serde_json::Value = serde_json::from_str(&content).chain(|| {
+            (
+                ErrorKind::DeserializationError,
+                "Unable to parse ceremony result",
+            )
+        })?;
+        let genesis_time = claimed["genesis_time"]
+            .as_str()
+            .chain(|| (ErrorKind::InvalidInput, "Missing genesis time in result"))?;
+        let result = self.assemble(genesis_time)?;
+        if to_json(&result)? != content {
+            return Err(Error::new(
+                ErrorKind::VerifyError,
+                "Ceremony result does not match the assembled genesis",
+            ));
+        }
+        Ok((result, keccak256(content.as_bytes())))
+    }
+}
+
+fn ask_secret_key() -> Result<SecretKey> {
+    println!("Enter the hex encoded secret key of the staking address: ");
+    let secret = password().chain(|| (ErrorKind::IoError, "Unable to read secret key"))?;
+    let secret = hex::decode(secret.trim())
+        .chain(|| (ErrorKind::InvalidInput, "Invalid hex encoded secret key"))?;
+    SecretKey::from_slice(&secret).chain(|| (ErrorKind::InvalidInput, "Invalid secret key"))
+}
+
+fn payload_message<T: Serialize>(payload: &T) -> Result<Message> {
+    let encoded = serde_json::to_vec(payload)
+        .chain(|| (ErrorKind::SerializationError, "Unable to serialize payload"))?;
+    Message::from_slice(&keccak256(&encoded))
+        .chain(|| (ErrorKind::InvalidInput, "Unable to create message"))
+}
+
+fn sign<T: Serialize>(payload: T, secret_key: &SecretKey) -> Result<Signed<T>> {
+    let message = payload_message(&payload)?;
+    let (recovery_id, signature) = secp256k1::SECP256K1
+        .sign_recoverable(&message, secret_key)
+        .serialize_compact();
+    let mut serialized = vec![recovery_id.to_i32() as u8];
+    serialized.extend_from_slice(&signature);
+    Ok(Signed {
+        payload,
+        signature: hex::encode(serialized),
+    })
+}
+
+/// Checks the payload is signed by its staking address
+fn verify<T: Serialize + HasStakingAddress>(signed: Signed<T>) -> Result<T> {
+    let message = payload_message(&signed.payload)?;
+    let signature = hex::decode(&signed.signature)
+        .ok()
+        .filter(|signature| signature.len() == 65)
+        .chain(|| (ErrorKind::InvalidInput, "Invalid signature encoding"))?;
+    let recovery_id = RecoveryId::from_i32(i32::from(signature[0]))
+        .chain(|| (ErrorKind::InvalidInput, "Invalid signature recovery id"))?;
+    let signature = RecoverableSignature::from_compact(&signature[1..], recovery_id)
+        .chain(|| (ErrorKind::InvalidInput, "Invalid signature"))?;
+    let public_key = secp256k1::SECP256K1
+        .recover(&message, &signature)
+        .chain(|| (ErrorKind::VerifyError, "Unable to recover signer"))?;
+    let address = signed.payload.staking_address();
+    if RedeemAddress::from(&public_key) != address {
+        return Err(Error::new(
+            ErrorKind::VerifyError,
+            format!(
+                "Payload of {} is not signed by its staking address",
+                address
+            ),
+        ));
+    }
+    Ok(signed.payload)
+}
+
+trait HasStakingAddress {
+    fn staking_address(&self) -> RedeemAddress;
+}
+
+impl HasStakingAddress for CouncilNodeRegistration {
+    fn staking_address(&self) -> RedeemAddress {
+        self.staking_address
+    }
+}
+
+impl HasStakingAddress for CeremonyAttestation {
+    fn staking_address(&self) -> RedeemAddress {
+        self.staking_address
+    }
+}
+
+fn to_json<T: Serialize>(value: &T) -> Result<String> {
+    serde_json::to_string_pretty(value)
+        .chain(|| (ErrorKind::SerializationError, "Unable to serialize to JSON"))
+}
+
+fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<()> {
+    fs::write(path, to_json(value)?).chain(|| {
+        (
+            ErrorKind::IoError,
+            format!("Unable to write {}", path.display()),
+        )
+    })
+}
+
+fn read_json<T: DeserializeOwned>(path: &Path) -> Result<T> {
+    let content = fs::read_to_string(path).chain(|| {
+        (
+            ErrorKind::IoError,
+            format!("Unable to read {}", path.display()),
+        )
+    })?;
+    serde_json::from_str(&content).chain(|| {
+        (
+            ErrorKind::DeserializationError,
+            format!("Unable to parse {}", path.display()),
+        )
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn check_signed_registration() {
+        let secret_key = SecretKey::from_slice(&[0xcd; 32]).unwrap();
+        let staking_address = RedeemAddress::from(&PublicKey::from_secret_key(
+            &secp256k1::SECP256K1,
+            &secret_key,
+        ));
+        let registration = CouncilNodeRegistration {
+            staking_address,
+            name: "node".to_owned(),
+            security_contact: None,
+            validator_pubkey: TendermintValidatorPubKey::Ed25519([1; 32]),
+            confidential_init: ConfidentialInit {
+                init_payload: MLSInit::Genesis(vec![1, 2, 3]),
+            },
+        };
+        let signed = sign(registration.clone(), &secret_key).unwrap();
+        let encoded = to_json(&signed).unwrap();
+        let decoded: Signed<CouncilNodeRegistration> = serde_json::from_str(&encoded).unwrap();
+        assert_eq!(registration, verify(decoded).unwrap());
+
+        let mut tampered = signed;
+        tampered.payload.name = "other node".to_owned();
+        assert_eq!(ErrorKind::VerifyError, verify(tampered).unwrap_err().kind());
+    }
+}
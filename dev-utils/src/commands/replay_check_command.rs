@@ -0,0 +1,321 @@
+use std::path::PathBuf;
+
+use mls::ciphersuite::CipherSuite;
+use mls::DefaultCipherSuite;
+use parity_scale_codec::Encode;
+use secp256k1::{Message, SecretKey};
+use serde::Serialize;
+
+use chain_core::init::address::RedeemAddress;
+use chain_core::init::coin::Coin;
+use chain_core::state::account::{
+    StakedStateAddress, StakedStateOpAttributes, StakedStateOpWitness, UnbondTx,
+};
+use chain_core::tx::data::TxId;
+use chain_core::tx::{TransactionId, TxAux, TxPublicAux};
+use chain_tx_validation::witness::verify_tx_recover_address;
+use client_common::tendermint::{Client, WebsocketRpcClient};
+use client_common::{
+    Error, ErrorKind, PrivateKey, PrivateKeyAction, PublicKey, Result, ResultExt, Transaction,
+};
+
+/// Checks that a transaction signed for the origin network is rejected on the target network
+/// (e.g. a testnet or a fork started from a mainnet snapshot)
+///
+/// The test transaction is a public unbond transaction of a throw-away (or given) staking key,
+/// so that it can be checked without the enclaves.
+#[derive(Debug)]
+pub struct ReplayCheckCommand {
+    origin_chain_id: String,
+    target_chain_id: String,
+    tendermint_url: Option<String>,
+    secret_key: Option<String>,
+    output: Option<PathBuf>,
+}
+
+#[derive(Debug, Serialize)]
+struct ReplayCheck {
+    name: &'static str,
+    passed: bool,
+    detail: String,
+}
+
+#[derive(Debug, Serialize)]
+struct ReplayCheckReport {
+    origin_chain_id: String,
+    origin_chain_hex_id: String,
+    target_chain_id: String,
+    target_chain_hex_id: String,
+    staking_address: String,
+    transaction_id: String,
+    transaction: String,
+    checks: Vec<ReplayCheck>,
+    passed: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct SignedReplayCheckReport {
+    report: ReplayCheckReport,
+    /// hex encoded public key of the signer
+    public_key: String,
+    /// hex encoded recoverable signature (64 bytes + recovery id) of sha256(json(report))
+    signature: String,
+}
+
+impl ReplayCheckCommand {
+    pub fn new(
+        origin_chain_id: String,
+        target_chain_id: String,
+        tendermint_url: Option<String>,
+        secret_key: Option<String>,
+        output: Option<PathBuf>,
+    ) -> Self {
+        Self {
+            origin_chain_id,
+            target_chain_id,
+            tendermint_url,
+            secret_key,
+            output,
+        }
+    }
+
+    pub fn execute(&self) -> Result<()> {
+        let sign_key = match self.secret_key {
+            Some(ref secret_key) => {
+                let bytes = hex::decode(secret_key)
+                    .chain(|| (ErrorKind::InvalidInput, "Invalid hex secret key"))?;
+                PrivateKey::deserialize_from(&bytes)?
+            }
+            None => PrivateKey::new()?,
+        };
+
+        let report = self.check(&sign_key)?;
+        let passed = report.passed;
+        let signed_report = sign_report(report, &sign_key)?;
+        let json = serde_json::to_string_pretty(&signed_report)
+            .chain(|| (ErrorKind::SerializationError, "Unable to serialize report"))?;
+
+        match self.output {
+            Some(ref path) => std::fs::write(path, &json)
+                .chain(|| (ErrorKind::IoError, "Unable to write report"))?,
+            None => println!("{}", json),
+        }
+
+        if passed {
+            Ok(())
+        } else {
+            Err(Error::new(
+                ErrorKind::IllegalInput,
+                "Replay protection self-test failed: see the failed checks in the report",
+            ))
+        }
+    }
+
+    fn check(&self, sign_key: &PrivateKey) -> Result<ReplayCheckReport> {
+        let origin_hex_id = chain_hex_id(&self.origin_chain_id)?;
+        let target_hex_id = chain_hex_id(&self.target_chain_id)?;
+
+        let public_key = sign_key.public_key()?;
+        let staking_address = StakedStateAddress::BasicRedeem(RedeemAddress::from(&public_key));
+
+        let tx = unbond_tx(staking_address, origin_hex_id);
+        let txid = tx.id();
+        let witness = sign_key
+            .sign(&Transaction::UnbondStakeTransaction(tx.clone()))
+            .map(StakedStateOpWitness::new)?;
+
+        let mut checks = vec![
+            check_origin_witness(&witness, &txid, &staking_address),
+            check_attributes(origin_hex_id, target_hex_id),
+            check_witness_replay(&witness, staking_address, target_hex_id),
+        ];
+
+        let txaux = TxAux::PublicTx(TxPublicAux::UnbondStakeTx(tx, witness));
+        if let Some(ref tendermint_url) = self.tendermint_url {
+            checks.push(check_node(tendermint_url, target_hex_id, &txaux.encode())?);
+        }
+
+        let passed = checks.iter().all(|check| check.passed);
+        Ok(ReplayCheckReport {
+            origin_chain_id: self.origin_chain_id.clone(),
+            origin_chain_hex_id: hex::encode(&[origin_hex_id]),
+            target_chain_id: self.target_chain_id.clone(),
+            target_chain_hex_id: hex::encode(&[target_hex_id]),
+            staking_address: staking_address.to_string(),
+            transaction_id: hex::encode(&txid),
+            transaction: hex::encode(txaux.encode()),
+            checks,
+            passed,
+        })
+    }
+}
+
+/// the chain hex id is the last two hex digits of the chain id (see chain-abci)
+fn chain_hex_id(chain_id: &str) -> Result<u8> {
+    if chain_id.len() < 2 {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("Chain id is too short: {}", chain_id),
+        ));
+    }
+    let bytes = hex::decode(&chain_id[chain_id.len() - 2..]).chain(|| {
+        (
+            ErrorKind::InvalidInput,
+            format!("Chain id does not end with two hex digits: {}", chain_id),
+        )
+    })?;
+    Ok(bytes[0])
+}
+
+fn unbond_tx(staking_address: StakedStateAddress, chain_hex_id: u8) -> UnbondTx {
+    UnbondTx::new(
+        staking_address,
+        0,
+        Coin::unit(),
+        StakedStateOpAttributes::new(chain_hex_id),
+    )
+}
+
+fn check_origin_witness(
+    witness: &StakedStateOpWitness,
+    txid: &TxId,
+    staking_address: &StakedStateAddress,
+) -> ReplayCheck {
+    let passed = verify_tx_recover_address(witness, txid).ok().as_ref() == Some(staking_address);
+    ReplayCheck {
+        name: "origin_witness",
+        passed,
+        detail: if passed {
+            "the test transaction is valid on the origin network".to_owned()
+        } else {
+            "the witness of the test transaction does not match its signer".to_owned()
+        },
+    }
+}
+
+fn check_attributes(origin_hex_id: u8, target_hex_id: u8) -> ReplayCheck {
+    let passed = origin_hex_id != target_hex_id;
+    ReplayCheck {
+        name: "chain_hex_id",
+        passed,
+        detail: if passed {
+            format!(
+                "transaction attributes (chain hex id {:02x}) are rejected by the target network (chain hex id {:02x})",
+                origin_hex_id, target_hex_id
+            )
+        } else {
+            format!(
+                "both networks use chain hex id {:02x}: transactions can be replayed between them",
+                origin_hex_id
+            )
+        },
+    }
+}
+
+/// the chain hex id is part of the signed transaction data, so the witness can not be reused for
+/// the same transaction with the attributes of the target network
+fn check_witness_replay(
+    witness: &StakedStateOpWitness,
+    staking_address: StakedStateAddress,
+    target_hex_id: u8,
+) -> ReplayCheck {
+    let replayed_txid = unbond_tx(staking_address, target_hex_id).id();
+    let passed = verify_tx_recover_address(witness, &replayed_txid).ok() != Some(staking_address);
+    ReplayCheck {
+        name: "witness_domain_separation",
+        passed,
+        detail: if passed {
+            "the witness is not valid for the transaction with the target chain hex id".to_owned()
+        } else {
+            "the witness is also valid for the transaction with the target chain hex id".to_owned()
+        },
+    }
+}
+
+fn check_node(tendermint_url: &str, target_hex_id: u8, txaux: &[u8]) -> Result<ReplayCheck> {
+    let client = WebsocketRpcClient::new(tendermint_url)?;
+    let node_chain_id = client.genesis()?.chain_id;
+    let node_hex_id = chain_hex_id(node_chain_id.as_str())?;
+    if node_hex_id != target_hex_id {
+        return Ok(ReplayCheck {
+            name: "node_rejection",
+            passed: false,
+            detail: format!(
+                "node at {} is not on the target network (chain id {})",
+                tendermint_url, node_chain_id
+            ),
+        });
+    }
+
+    let check = match client.broadcast_transaction(txaux) {
+        Ok(_) => ReplayCheck {
+            name: "node_rejection",
+            passed: false,
+            detail: format!("node at {} accepted the test transaction", tendermint_url),
+        },
+        Err(e) => ReplayCheck {
+            name: "node_rejection",
+            passed: e.to_string().contains("chain_hex_id"),
+            detail: format!(
+                "node at {} rejected the test transaction: {}",
+                tendermint_url, e
+            ),
+        },
+    };
+    Ok(check)
+}
+
+fn sign_report(
+    report: ReplayCheckReport,
+    sign_key: &PrivateKey,
+) -> Result<SignedReplayCheckReport> {
+    let json = serde_json::to_vec(&report)
+        .chain(|| (ErrorKind::SerializationError, "Unable to serialize report"))?;
+    let digest = DefaultCipherSuite::hash(&json);
+    let message = Message::from_slice(digest.as_ref())
+        .chain(|| (ErrorKind::InternalError, "Invalid report digest"))?;
+    let signature = secp256k1::SECP256K1.sign_recoverable(&message, &SecretKey::from(sign_key));
+    let (recovery_id, signature) = signature.serialize_compact();
+
+    let mut signature = signature.to_vec();
+    signature.push(recovery_id.to_i32() as u8);
+    let public_key: PublicKey = sign_key.public_key()?;
+    Ok(SignedReplayCheckReport {
+        report,
+        public_key: hex::encode(public_key.serialize()),
+        signature: hex::encode(signature),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_replay_protection_checks() {
+        assert_eq!(0x2a, chain_hex_id("main-chain-2A").unwrap());
+        assert!(chain_hex_id("main-chain-zz").is_err());
+
+        let command = ReplayCheckCommand::new(
+            "main-chain-2A".to_owned(),
+            "test-chain-42".to_owned(),
+            None,
+            None,
+            None,
+        );
+        let report = command.check(&PrivateKey::new().unwrap()).unwrap();
+        assert!(report.passed);
+        assert_eq!(3, report.checks.len());
+
+        let command = ReplayCheckCommand::new(
+            "main-chain-2A".to_owned(),
+            "fork-chain-2A".to_owned(),
+            None,
+            None,
+            None,
+        );
+        let report = command.check(&PrivateKey::new().unwrap()).unwrap();
+        assert!(!report.passed);
+        assert!(!report.checks[1].passed);
+    }
+}
@@ -0,0 +1,90 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use structopt::StructOpt;
+
+use chain_core::schema::{current_schema, diff_schemas, TypeSchema};
+use client_common::{Error, ErrorKind, Result, ResultExt};
+
+#[derive(Debug, StructOpt)]
+pub enum ScaleSchemaCommand {
+    #[structopt(
+        name = "export",
+        about = "Export the SCALE schema (golden encodings) of the consensus-encoded types"
+    )]
+    Export {
+        #[structopt(
+            short,
+            long,
+            parse(from_os_str),
+            help = "Path of the schema file (printed by default)"
+        )]
+        output: Option<PathBuf>,
+    },
+    #[structopt(
+        name = "diff",
+        about = "Compare two exported SCALE schemas (e.g. of two releases)"
+    )]
+    Diff {
+        #[structopt(parse(from_os_str), help = "Path of the old schema file")]
+        old: PathBuf,
+        #[structopt(
+            parse(from_os_str),
+            help = "Path of the new schema file (the schema of this build by default)"
+        )]
+        new: Option<PathBuf>,
+    },
+}
+
+impl ScaleSchemaCommand {
+    pub fn execute(&self) -> Result<()> {
+        match self {
+            ScaleSchemaCommand::Export { output } => {
+                let schema = serde_json::to_string_pretty(&current_schema())
+                    .chain(|| (ErrorKind::SerializationError, "Unable to serialize schema"))?;
+                match output {
+                    Some(path) => fs::write(path, schema + "\n")
+                        .chain(|| (ErrorKind::IoError, "Unable to write schema file")),
+                    None => {
+                        println!("{}", schema);
+                        Ok(())
+                    }
+                }
+            }
+            ScaleSchemaCommand::Diff { old, new } => {
+                let old = read_schema(old)?;
+                let new = match new {
+                    Some(path) => read_schema(path)?,
+                    None => current_schema(),
+                };
+                let changes = diff_schemas(&old, &new);
+                if changes.is_empty() {
+                    println!("no changes");
+                    return Ok(());
+                }
+                for change in changes.iter() {
+                    println!("{}", change);
+                }
+                if changes.iter().all(|change| change.is_versioned()) {
+                    Ok(())
+                } else {
+                    Err(Error::new(
+                        ErrorKind::VerifyError,
+                        "Encodings changed without a schema version bump",
+                    ))
+                }
+            }
+        }
+    }
+}
+
+fn read_schema(path: &Path) -> Result<Vec<TypeSchema>> {
+    let content =
+        fs::read_to_string(path).chain(|| (ErrorKind::IoError, "Unable to read schema file"))?;
+    serde_json::from_str(&content).chain(|| {
+        (
+            ErrorKind::DeserializationError,
+            "Unable to parse schema file",
+        )
+    })
+}
@@ -1,9 +1,12 @@
+use std::path::PathBuf;
+
 use structopt::StructOpt;
 
 use client_common::Result;
 
 use crate::commands::{
-    GenesisCommand, InitCommand, KeypackageCommand, RunCommand, StopCommand, TestVectorCommand,
+    CeremonyCommand, GenesisCommand, InitCommand, KeypackageCommand, ReplayCheckCommand,
+    RunCommand, ScaleSchemaCommand, StopCommand, TestVectorCommand,
 };
 
 const NETWORKS: [&str; 3] = ["devnet", "testnet", "mainnet"];
@@ -24,6 +27,16 @@ pub enum DevUtils {
         genesis_command: GenesisCommand,
     },
 
+    /// Used for multi-party genesis creation
+    #[structopt(
+        name = "ceremony",
+        about = "Commands for the genesis ceremony of council node operators"
+    )]
+    Ceremony {
+        #[structopt(subcommand)]
+        ceremony_command: CeremonyCommand,
+    },
+
     /// Used for initializing
     #[structopt(
         name = "init",
@@ -72,18 +85,73 @@ pub enum DevUtils {
         aux_payload: String,
     },
 
+    /// Replay protection self-test
+    #[structopt(
+        name = "replay-check",
+        about = "Check that transactions of a network are rejected on another network (e.g. a fork or testnet)"
+    )]
+    ReplayCheck {
+        #[structopt(
+            name = "origin_chain_id",
+            short,
+            long,
+            help = "Full chain id of the network the test transaction is signed for"
+        )]
+        origin_chain_id: String,
+        #[structopt(
+            name = "target_chain_id",
+            short,
+            long,
+            help = "Full chain id of the network which has to reject the test transaction"
+        )]
+        target_chain_id: String,
+        #[structopt(
+            name = "tendermint_url",
+            short = "u",
+            long,
+            help = "Websocket url of a tendermint node of the target network (e.g. ws://localhost:26657/websocket)"
+        )]
+        tendermint_url: Option<String>,
+        #[structopt(
+            name = "secret_key",
+            short,
+            long,
+            help = "Hex encoded secret key signing the test transaction and the report (a new key is generated by default)"
+        )]
+        secret_key: Option<String>,
+        #[structopt(
+            name = "output",
+            short = "p",
+            long,
+            parse(from_os_str),
+            help = "Path of the signed report (printed by default)"
+        )]
+        output: Option<PathBuf>,
+    },
+
     /// Used for working with tendermint's genesis.json
     #[structopt(name = "keypackage", about = "Commands for keypackage")]
     Keypackage {
         #[structopt(subcommand)]
         keypackage_command: KeypackageCommand,
     },
+
+    /// Used for checking changes of the consensus encodings
+    #[structopt(
+        name = "scale-schema",
+        about = "Export and compare the SCALE schema of the consensus-encoded types"
+    )]
+    ScaleSchema {
+        #[structopt(subcommand)]
+        scale_schema_command: ScaleSchemaCommand,
+    },
 }
 
 impl DevUtils {
     pub fn execute(&self) -> Result<()> {
         match self {
             DevUtils::Genesis { genesis_command } => genesis_command.execute(),
+            DevUtils::Ceremony { ceremony_command } => ceremony_command.execute(),
             DevUtils::Init => {
                 let mut init_command = InitCommand::new();
                 init_command.execute()
@@ -105,7 +173,26 @@ impl DevUtils {
                     TestVectorCommand::new(network.clone(), seed.clone(), aux_payload);
                 test_vectors_command.execute()
             }
+            DevUtils::ReplayCheck {
+                origin_chain_id,
+                target_chain_id,
+                tendermint_url,
+                secret_key,
+                output,
+            } => {
+                let replay_check_command = ReplayCheckCommand::new(
+                    origin_chain_id.clone(),
+                    target_chain_id.clone(),
+                    tendermint_url.clone(),
+                    secret_key.clone(),
+                    output.clone(),
+                );
+                replay_check_command.execute()
+            }
             DevUtils::Keypackage { keypackage_command } => keypackage_command.execute(),
+            DevUtils::ScaleSchema {
+                scale_schema_command,
+            } => scale_schema_command.execute(),
         }
     }
 }
@@ -16,6 +16,7 @@ use chain_core::state::account::DepositBondTx;
 use chain_core::state::account::StakedState;
 use chain_core::state::account::StakedStateOpWitness;
 use chain_core::state::account::WithdrawUnbondedTx;
+use chain_core::state::tendermint::BlockHeight;
 use chain_core::tx::data::input::TxoPointer;
 use chain_core::tx::data::{Tx, TxId};
 use chain_core::tx::witness::TxWitness;
@@ -30,6 +31,12 @@ use secp256k1::{
 
 pub const ENCRYPTION_REQUEST_SIZE: usize = 1024 * 60; // 60 KB
 
+/// Maximum time (in seconds) TQE holds a subscription request before answering it
+pub const MAX_SUBSCRIPTION_TIMEOUT_SECS: u32 = 30;
+
+/// Maximum number of blocks TQE scans for one discovery request
+pub const MAX_DISCOVERY_HEIGHTS: u32 = 1000;
+
 /// raw sgx_sealed_data_t
 pub type SealedLog = Vec<u8>;
 
@@ -165,7 +172,7 @@ pub struct QueryEncryptRequest {
     pub txid: TxId,
     /// EncryptionRequest sealed by TQE to "mrsigner"
     pub sealed_enc_request: SealedLog,
-    /// plain tx size
+    /// size of the obfuscated tx (the minimal fee is computed for it)
     pub tx_size: u32,
     /// transaction inputs (if any; for deposits/transfers)
     pub tx_inputs: Option<Vec<TxoPointer>>,
@@ -180,6 +187,12 @@ pub enum EnclaveRequest {
     GetSealedTxData { txids: Vec<TxId> },
     /// request to encrypt tx by the current key (requested by TQE -- they should be on the same machine)
     EncryptTx(Box<QueryEncryptRequest>),
+    /// request to get the data sealed to "mrsigner" of the transactions (with outputs) committed
+    /// in the blocks after `after_height` (at most `max_heights` blocks; requested by TQE)
+    GetCommittedSealedTxs {
+        after_height: BlockHeight,
+        max_heights: u32,
+    },
 }
 
 pub type VerifyOk = (Fee, Option<StakedState>, Option<Box<SealedLog>>);
@@ -194,6 +207,12 @@ pub enum EnclaveResponse {
     EncryptTx(Result<TxObfuscated, chain_tx_validation::Error>),
     /// response if the enclave failed to parse the request
     UnknownRequest,
+    /// returns the last checked block height and the (block height, TXID, sealed data payload)
+    /// of the transactions committed up to it
+    GetCommittedSealedTxs {
+        last_height: BlockHeight,
+        txs: Vec<(BlockHeight, TxId, SealedLog)>,
+    },
 }
 
 /// initial request sent by client to TQE
@@ -201,6 +220,8 @@ pub enum EnclaveResponse {
 pub enum TxQueryInitRequest {
     Encrypt(Box<EncryptionRequest>),
     DecryptChallenge,
+    SubscribeChallenge,
+    DiscoverChallenge,
 }
 
 /// initial response by TQE
@@ -208,6 +229,8 @@ pub enum TxQueryInitRequest {
 pub enum TxQueryInitResponse {
     Encrypt(EncryptionResponse),
     DecryptChallenge(H256),
+    SubscribeChallenge(H256),
+    DiscoverChallenge(H256),
 }
 
 /// Sent initially in TxQueryInitRequest
@@ -359,6 +382,262 @@ pub struct DecryptionResponse {
     pub txs: Vec<TxWithOutputs>,
 }
 
+/// Subscription (long-poll) in direct communication (over one-side attested TLS) to TQE:
+/// TQE answers once transactions readable by the view key are committed after the given
+/// block height, or once the timeout is over
+pub struct SubscriptionRequestBody {
+    /// requester's public view key
+    pub view_key: PublicKey,
+    /// only the transactions committed in the blocks after this height are notified
+    pub after_height: BlockHeight,
+    /// maximum time (in seconds) to wait for transactions (capped by `MAX_SUBSCRIPTION_TIMEOUT_SECS`)
+    pub timeout_secs: u32,
+    /// 32-byte challenge obtained from TQE after establishing TLS connection
+    pub challenge: H256,
+}
+
+impl SubscriptionRequestBody {
+    pub fn new(
+        view_key: PublicKey,
+        after_height: BlockHeight,
+        timeout_secs: u32,
+        challenge: H256,
+    ) -> Self {
+        SubscriptionRequestBody {
+            view_key,
+            after_height,
+            timeout_secs,
+            challenge,
+        }
+    }
+
+    pub(crate) fn hash(&self) -> H256 {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(b"subscriptionrequest");
+        hasher.update(&self.encode());
+        hasher.finalize().into()
+    }
+}
+
+impl Encode for SubscriptionRequestBody {
+    fn encode_to<W: Output>(&self, dest: &mut W) {
+        self.view_key.serialize().encode_to(dest);
+        self.after_height.encode_to(dest);
+        self.timeout_secs.encode_to(dest);
+        self.challenge.encode_to(dest);
+    }
+}
+
+impl Decode for SubscriptionRequestBody {
+    fn decode<I: Input>(input: &mut I) -> Result<Self, parity_scale_codec::Error> {
+        let view_key_bytes = H264::decode(input)?;
+        let view_key = PublicKey::from_slice(&view_key_bytes)
+            .map_err(|_| parity_scale_codec::Error::from("Unable to parse public key"))?;
+        let after_height = BlockHeight::decode(input)?;
+        let timeout_secs = u32::decode(input)?;
+        let challenge = H256::decode(input)?;
+        Ok(SubscriptionRequestBody::new(
+            view_key,
+            after_height,
+            timeout_secs,
+            challenge,
+        ))
+    }
+}
+
+/// Signed subscription request in direct communication (over one-side attested TLS) to TQE
+pub struct SubscriptionRequest {
+    pub body: SubscriptionRequestBody,
+    pub view_key_sig: Signature,
+}
+
+impl SubscriptionRequest {
+    pub fn new(body: SubscriptionRequestBody, view_key_sig: Signature) -> Self {
+        SubscriptionRequest { body, view_key_sig }
+    }
+
+    pub fn create<C: Signing>(
+        secp: &Secp256k1<C>,
+        after_height: BlockHeight,
+        timeout_secs: u32,
+        challenge: H256,
+        view_secret_key: &SecretKey,
+    ) -> Self {
+        let public_key = PublicKey::from_secret_key(&secp, &view_secret_key);
+        let body = SubscriptionRequestBody::new(public_key, after_height, timeout_secs, challenge);
+        let message = Message::from_slice(&body.hash()[..]).expect("32 bytes");
+        let sig = secp.sign(&message, &view_secret_key);
+        SubscriptionRequest::new(body, sig)
+    }
+
+    pub fn verify<C: Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        challenge: H256,
+    ) -> Result<(), secp256k1::Error> {
+        if self.body.challenge != challenge {
+            return Err(secp256k1::Error::InvalidMessage);
+        }
+        let message = Message::from_slice(&self.body.hash()[..]).expect("32 bytes");
+        secp.verify(&message, &self.view_key_sig, &self.body.view_key)
+    }
+}
+
+impl Encode for SubscriptionRequest {
+    fn encode_to<W: Output>(&self, dest: &mut W) {
+        self.body.encode_to(dest);
+        self.view_key_sig.serialize_compact().encode_to(dest);
+    }
+}
+
+impl Decode for SubscriptionRequest {
+    fn decode<I: Input>(input: &mut I) -> Result<Self, parity_scale_codec::Error> {
+        let body = SubscriptionRequestBody::decode(input)?;
+        let view_sig_bytes = H512::decode(input)?;
+        let view_key_sig = Signature::from_compact(&view_sig_bytes)
+            .map_err(|_| parity_scale_codec::Error::from("Unable to parse signature"))?;
+        Ok(SubscriptionRequest::new(body, view_key_sig))
+    }
+}
+
+/// Notification in direct communication (over one-side attested TLS) from TQE
+#[derive(Encode, Decode)]
+pub struct SubscriptionNotification {
+    /// last checked block height (the next subscription should start after it)
+    pub last_height: BlockHeight,
+    /// transactions readable by the view key (with the height of their block)
+    /// committed after the requested height (empty if the timeout is over)
+    pub txs: Vec<(BlockHeight, TxWithOutputs)>,
+}
+
+/// Discovery in direct communication (over one-side attested TLS) to TQE:
+/// TQE answers with the transactions readable by the view key committed in the block range
+/// (i.e. the ones whose access policy lists the view key), so that the client doesn't need
+/// to download and try to decrypt all the transactions
+pub struct DiscoveryRequestBody {
+    /// requester's public view key
+    pub view_key: PublicKey,
+    /// first block height of the range
+    pub from_height: BlockHeight,
+    /// last block height of the range (at most `MAX_DISCOVERY_HEIGHTS` blocks are scanned)
+    pub to_height: BlockHeight,
+    /// 32-byte challenge obtained from TQE after establishing TLS connection
+    pub challenge: H256,
+}
+
+impl DiscoveryRequestBody {
+    pub fn new(
+        view_key: PublicKey,
+        from_height: BlockHeight,
+        to_height: BlockHeight,
+        challenge: H256,
+    ) -> Self {
+        DiscoveryRequestBody {
+            view_key,
+            from_height,
+            to_height,
+            challenge,
+        }
+    }
+
+    pub(crate) fn hash(&self) -> H256 {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(b"discoveryrequest");
+        hasher.update(&self.encode());
+        hasher.finalize().into()
+    }
+}
+
+impl Encode for DiscoveryRequestBody {
+    fn encode_to<W: Output>(&self, dest: &mut W) {
+        self.view_key.serialize().encode_to(dest);
+        self.from_height.encode_to(dest);
+        self.to_height.encode_to(dest);
+        self.challenge.encode_to(dest);
+    }
+}
+
+impl Decode for DiscoveryRequestBody {
+    fn decode<I: Input>(input: &mut I) -> Result<Self, parity_scale_codec::Error> {
+        let view_key_bytes = H264::decode(input)?;
+        let view_key = PublicKey::from_slice(&view_key_bytes)
+            .map_err(|_| parity_scale_codec::Error::from("Unable to parse public key"))?;
+        let from_height = BlockHeight::decode(input)?;
+        let to_height = BlockHeight::decode(input)?;
+        let challenge = H256::decode(input)?;
+        Ok(DiscoveryRequestBody::new(
+            view_key,
+            from_height,
+            to_height,
+            challenge,
+        ))
+    }
+}
+
+/// Signed discovery request in direct communication (over one-side attested TLS) to TQE
+pub struct DiscoveryRequest {
+    pub body: DiscoveryRequestBody,
+    pub view_key_sig: Signature,
+}
+
+impl DiscoveryRequest {
+    pub fn new(body: DiscoveryRequestBody, view_key_sig: Signature) -> Self {
+        DiscoveryRequest { body, view_key_sig }
+    }
+
+    pub fn create<C: Signing>(
+        secp: &Secp256k1<C>,
+        from_height: BlockHeight,
+        to_height: BlockHeight,
+        challenge: H256,
+        view_secret_key: &SecretKey,
+    ) -> Self {
+        let public_key = PublicKey::from_secret_key(&secp, &view_secret_key);
+        let body = DiscoveryRequestBody::new(public_key, from_height, to_height, challenge);
+        let message = Message::from_slice(&body.hash()[..]).expect("32 bytes");
+        let sig = secp.sign(&message, &view_secret_key);
+        DiscoveryRequest::new(body, sig)
+    }
+
+    pub fn verify<C: Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        challenge: H256,
+    ) -> Result<(), secp256k1::Error> {
+        if self.body.challenge != challenge {
+            return Err(secp256k1::Error::InvalidMessage);
+        }
+        let message = Message::from_slice(&self.body.hash()[..]).expect("32 bytes");
+        secp.verify(&message, &self.view_key_sig, &self.body.view_key)
+    }
+}
+
+impl Encode for DiscoveryRequest {
+    fn encode_to<W: Output>(&self, dest: &mut W) {
+        self.body.encode_to(dest);
+        self.view_key_sig.serialize_compact().encode_to(dest);
+    }
+}
+
+impl Decode for DiscoveryRequest {
+    fn decode<I: Input>(input: &mut I) -> Result<Self, parity_scale_codec::Error> {
+        let body = DiscoveryRequestBody::decode(input)?;
+        let view_sig_bytes = H512::decode(input)?;
+        let view_key_sig = Signature::from_compact(&view_sig_bytes)
+            .map_err(|_| parity_scale_codec::Error::from("Unable to parse signature"))?;
+        Ok(DiscoveryRequest::new(body, view_key_sig))
+    }
+}
+
+/// Response in direct communication (over one-side attested TLS) from TQE
+#[derive(Encode, Decode)]
+pub struct DiscoveryResponse {
+    /// last scanned block height (the range is continued after it if it's below `to_height`)
+    pub last_height: BlockHeight,
+    /// transactions readable by the view key (with the height of their block)
+    pub txs: Vec<(BlockHeight, TxWithOutputs)>,
+}
+
 #[cfg(test)]
 pub mod tests {
     use super::*;
@@ -383,4 +662,31 @@ pub mod tests {
             DecryptionRequest::create(&secp, vec![[0u8; 32], [1u8; 32]], [2u8; 32], &secret_key);
         assert!(req.verify(&secp, [0u8; 32]).is_err());
     }
+
+    #[test]
+    fn check_subscription_verify() {
+        let secp = secp256k1::SECP256K1;
+        let secret_key = SecretKey::from_slice(&[0xcd; 32]).expect("Unable to create secret key");
+        let req = SubscriptionRequest::create(&secp, 10.into(), 20, [2u8; 32], &secret_key);
+        let encoded = req.encode();
+        let decoded_req =
+            SubscriptionRequest::decode(&mut encoded.as_slice()).expect("encode-decode request");
+        assert_eq!(BlockHeight::new(10), decoded_req.body.after_height);
+        assert!(decoded_req.verify(&secp, [2u8; 32]).is_ok());
+        assert!(decoded_req.verify(&secp, [0u8; 32]).is_err());
+    }
+
+    #[test]
+    fn check_discovery_verify() {
+        let secp = secp256k1::SECP256K1;
+        let secret_key = SecretKey::from_slice(&[0xcd; 32]).expect("Unable to create secret key");
+        let req = DiscoveryRequest::create(&secp, 10.into(), 20.into(), [2u8; 32], &secret_key);
+        let encoded = req.encode();
+        let decoded_req =
+            DiscoveryRequest::decode(&mut encoded.as_slice()).expect("encode-decode request");
+        assert_eq!(BlockHeight::new(10), decoded_req.body.from_height);
+        assert_eq!(BlockHeight::new(20), decoded_req.body.to_height);
+        assert!(decoded_req.verify(&secp, [2u8; 32]).is_ok());
+        assert!(decoded_req.verify(&secp, [0u8; 32]).is_err());
+    }
 }
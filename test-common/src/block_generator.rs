@@ -23,8 +23,7 @@ use tendermint_rpc::endpoint::status;
 
 use chain_abci::app::ChainNodeState;
 use chain_abci::staking::StakingTable;
-use chain_core::common::{MerkleTree, Timespec};
-use chain_core::compute_app_hash;
+use chain_core::common::Timespec;
 use chain_core::init::config::NetworkParameters;
 use chain_core::init::{
     address::RedeemAddress, coin::Coin, config::InitConfig, network::Network, params,
@@ -289,13 +288,28 @@ impl TestnetSpec {
             .expect("distribution validation error");
         let account_root = put_stakings(&mut store, 0, genesis_state.accounts.iter()).unwrap();
         let network_params = NetworkParameters::Genesis(config.network_params.clone());
-        let app_hash = compute_app_hash(
-            &MerkleTree::empty(),
-            &account_root,
-            &genesis_state.rewards_pool,
-            &network_params,
+        let staking_table = StakingTable::from_genesis(
+            &StakingGetter::new(&store, 0),
+            network_params.get_required_council_node_stake(),
+            network_params.get_max_validators(),
+            &genesis_state
+                .validators
+                .iter()
+                .map(|(addr, _)| *addr)
+                .collect::<Vec<_>>(),
         );
 
+        let state = ChainNodeState::genesis(
+            genesis_seconds,
+            self.max_evidence_age,
+            account_root,
+            genesis_state.rewards_pool,
+            network_params,
+            staking_table,
+            genesis_state.isv_svn,
+        );
+        let app_hash = state.last_apphash;
+
         let share = self.share();
         let validators = self
             .nodes
@@ -329,28 +343,6 @@ impl TestnetSpec {
             app_state: Some(config),
         };
 
-        let staking_table = StakingTable::from_genesis(
-            &StakingGetter::new(&store, 0),
-            network_params.get_required_council_node_stake(),
-            network_params.get_max_validators(),
-            &genesis_state
-                .validators
-                .iter()
-                .map(|(addr, _)| *addr)
-                .collect::<Vec<_>>(),
-        );
-
-        let state = ChainNodeState::genesis(
-            app_hash,
-            genesis_seconds,
-            self.max_evidence_age,
-            account_root,
-            genesis_state.rewards_pool,
-            network_params,
-            staking_table,
-            genesis_state.isv_svn,
-        );
-
         (genesis, state)
     }
 
@@ -583,6 +575,10 @@ impl Client for GeneratorClient {
         unreachable!();
     }
 
+    fn unconfirmed_txs(&self, _limit: u64) -> Result<Vec<Vec<u8>>> {
+        Ok(vec![])
+    }
+
     fn query_state_batch<T: Iterator<Item = u64>>(&self, heights: T) -> Result<Vec<ChainState>> {
         Ok(heights
             .map(|height| {
@@ -11,10 +11,9 @@ use secp256k1::{
     Message, Secp256k1, Signing,
 };
 
-use chain_abci::app::{BufferType, ChainNodeApp};
+use chain_abci::app::{compute_genesis_app_hash, BufferType, ChainNodeApp};
 use chain_abci::enclave_bridge::mock::MockClient;
-use chain_core::common::{MerkleTree, Timespec, H256};
-use chain_core::compute_app_hash;
+use chain_core::common::{Timespec, H256};
 use chain_core::init::address::RedeemAddress;
 use chain_core::init::coin::Coin;
 use chain_core::init::config::{
@@ -308,10 +307,9 @@ impl ChainEnv {
             .expect("Error while validating distribution");
 
         let new_account_root = storage.put_stakings(0, &genesis_state.accounts);
-        let genesis_app_hash = compute_app_hash(
-            &MerkleTree::empty(),
+        let genesis_app_hash = compute_genesis_app_hash(
             &new_account_root,
-            &genesis_state.rewards_pool,
+            &genesis_state,
             &NetworkParameters::Genesis(init_network_params),
         );
         (